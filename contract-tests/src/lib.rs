@@ -0,0 +1,82 @@
+//! Consumer-side contracts of the Kafka events between the services.
+//!
+//! `fixtures/<topic>/<producer>-<case>.json` holds payloads recorded from the
+//! topics. Every consumer declares in `tests/<service>.rs` the DTO it parses a
+//! topic into and the fields it reads ([`Contract`]). A contract holds when
+//! every recorded payload of the topic still parses and every field the
+//! consumer reads keeps its recorded value through parsing. A DTO change on
+//! the producer side that drops, renames or retypes such a field therefore
+//! fails here before it breaks a consumer.
+//!
+//! New payloads are copied from the topic and stripped of customer data.
+//! Existing ones are never edited: consumers must keep reading events that
+//! are still in the topics.
+
+use std::{fs, path::PathBuf};
+
+use serde::{de::DeserializeOwned, Serialize};
+use serde_json::Value;
+
+/// Fields one consumer reads from one topic.
+#[derive(Debug, Clone, Copy)]
+pub struct Contract {
+    pub consumer: &'static str,
+    pub topic: &'static str,
+    /// JSON pointers of the fields the consumer reads (`/final_decisions`).
+    pub reads: &'static [&'static str],
+}
+
+/// Recorded payloads of `topic` as (file name, payload), sorted by name.
+pub fn recorded(topic: &str) -> Vec<(String, Value)> {
+    let dir = PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+        .join("fixtures")
+        .join(topic);
+    let mut payloads: Vec<(String, Value)> = fs::read_dir(&dir)
+        .unwrap_or_else(|e| panic!("{}: {e}", dir.display()))
+        .map(|entry| entry.expect("fixture entry").path())
+        .filter(|path| path.extension().is_some_and(|ext| ext == "json"))
+        .map(|path| {
+            let raw = fs::read_to_string(&path).expect("readable fixture");
+            let payload =
+                serde_json::from_str(&raw).unwrap_or_else(|e| panic!("{}: {e}", path.display()));
+            let name = path.file_name().unwrap().to_string_lossy().into_owned();
+            (name, payload)
+        })
+        .collect();
+    assert!(!payloads.is_empty(), "no recorded payloads for {topic}");
+    payloads.sort_by(|a, b| a.0.cmp(&b.0));
+    payloads
+}
+
+impl Contract {
+    /// Checks the contract against every recorded payload of the topic,
+    /// parsed into `T` as the consumer does.
+    pub fn verify<T: Serialize + DeserializeOwned>(&self) {
+        let Self {
+            consumer, topic, ..
+        } = self;
+        let payloads = recorded(topic);
+        for (name, payload) in &payloads {
+            let event: T = serde_json::from_value(payload.clone())
+                .unwrap_or_else(|e| panic!("{consumer} can no longer parse {topic}/{name}: {e}"));
+            let parsed = serde_json::to_value(&event).expect("serializable event");
+            for field in self.reads {
+                // Optionale Felder fehlen in älteren Payloads
+                let Some(recorded) = payload.pointer(field) else {
+                    continue;
+                };
+                assert_eq!(
+                    parsed.pointer(field),
+                    Some(recorded),
+                    "{consumer} reads {field} from {topic}/{name}, but the DTO does not keep it"
+                );
+            }
+        }
+        for field in self.reads {
+            assert!(
+                payloads.iter().any(|(_, p)| p.pointer(field).is_some()),
+                "{consumer} reads {field}, but no recorded {topic} payload contains it"
+            );
+        }
+    }
+}
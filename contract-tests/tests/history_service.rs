@@ -0,0 +1,36 @@
+//! `history-service` marks merged PDFs as running and stores pipeline results
+//! with their label (`start_kafka`).
+
+use contract_tests::Contract;
+use shared::dto::{PdfUploaded, PipelineRunResult};
+
+#[test]
+fn pdf_merged() {
+    Contract {
+        consumer: "history-service",
+        topic: "pdf-merged",
+        reads: &["/pdf_id", "/pipeline_id"],
+    }
+    .verify::<PdfUploaded>();
+}
+
+#[test]
+fn pipeline_result() {
+    Contract {
+        consumer: "history-service",
+        topic: "pipeline-result",
+        reads: &[
+            "/pdf_id",
+            "/pipeline_id",
+            "/run_id",
+            "/overall_score",
+            "/final_decisions",
+            "/contested",
+            "/started_at",
+            "/finished_at",
+            "/job_label",
+            "/external_ref",
+        ],
+    }
+    .verify::<PipelineRunResult>();
+}
@@ -0,0 +1,25 @@
+//! `pipeline-runner` runs the pipeline of every `pipeline-run` event, prioritized
+//! ones from `pipeline-run-priority` first.
+
+use contract_tests::Contract;
+use shared::dto::{PdfUploaded, PRIORITY_RUN_TOPIC};
+
+#[test]
+fn pipeline_run() {
+    Contract {
+        consumer: "pipeline-runner",
+        topic: "pipeline-run",
+        reads: &["/pdf_id", "/pipeline_id", "/priority", "/rerun_of", "/run_id"],
+    }
+    .verify::<PdfUploaded>();
+}
+
+#[test]
+fn pipeline_run_priority() {
+    Contract {
+        consumer: "pipeline-runner",
+        topic: PRIORITY_RUN_TOPIC,
+        reads: &["/pdf_id", "/pipeline_id", "/priority", "/rerun_of", "/run_id"],
+    }
+    .verify::<PdfUploaded>();
+}
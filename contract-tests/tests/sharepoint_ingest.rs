@@ -0,0 +1,14 @@
+//! `sharepoint-ingest` links its jobs to pipeline runs (`handle_pipeline_result`).
+
+use contract_tests::Contract;
+use shared::dto::PipelineRunResult;
+
+#[test]
+fn pipeline_result() {
+    Contract {
+        consumer: "sharepoint-ingest",
+        topic: "pipeline-result",
+        reads: &["/pdf_id", "/pipeline_id", "/run_id", "/status"],
+    }
+    .verify::<PipelineRunResult>();
+}
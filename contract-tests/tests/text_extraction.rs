@@ -0,0 +1,15 @@
+//! `text-extraction` extracts the text of every merged PDF, only the appended
+//! pages after `appended_from`.
+
+use contract_tests::Contract;
+use shared::dto::PdfUploaded;
+
+#[test]
+fn pdf_merged() {
+    Contract {
+        consumer: "text-extraction",
+        topic: "pdf-merged",
+        reads: &["/pdf_id", "/pipeline_id", "/priority", "/appended_from"],
+    }
+    .verify::<PdfUploaded>();
+}
@@ -3,18 +3,35 @@ const detail = document.getElementById('detail');
 const pdfFrame = document.getElementById('pdf');
 const meta = document.getElementById('meta');
 
-const socket = new WebSocket(`ws://${location.host}`);
+// höchste empfangene ID; beim Reconnect als last_seen_id mitgeschickt
+let lastSeenId = null;
 
-socket.addEventListener('message', ev => {
-  const msg = JSON.parse(ev.data);
-  if (msg.type === 'history') {
-    msg.data.forEach(addRow);
-  } else if (msg.type === 'update') {
-    addRow(msg.data, true);
-  }
-});
+function connect() {
+  const query = lastSeenId === null ? '' : `?last_seen_id=${lastSeenId}`;
+  const socket = new WebSocket(`ws://${location.host}/${query}`);
+
+  socket.addEventListener('message', ev => {
+    const msg = JSON.parse(ev.data);
+    // Quittung steuert den Nachschub des Servers (Flow Control)
+    if (typeof msg.seq === 'number') {
+      socket.send(JSON.stringify({ type: 'ack', seq: msg.seq }));
+    }
+    if (msg.type === 'snapshot') {
+      msg.data.forEach(row => addRow(row));
+    } else if (msg.type === 'replay') {
+      msg.data.forEach(row => addRow(row, true));
+    } else if (msg.type === 'update') {
+      addRow(msg.data, true);
+    }
+  });
+
+  socket.addEventListener('close', () => setTimeout(connect, 2000));
+}
+
+connect();
 
 function addRow(data, highlight) {
+  if (lastSeenId === null || data.id > lastSeenId) lastSeenId = data.id;
   const div = document.createElement('div');
   div.textContent = `${data.timestamp}: ${JSON.stringify(data.result)}`;
   if (highlight) div.classList.add('new');
@@ -11,6 +11,8 @@ export interface PipelineStep {
   active?: boolean;
   /** Freie JSON-Config pro Step (z. B. { min_signal: 0.5 }) */
   config?: Record<string, unknown>;
+  /** Verweis auf die Step-Library; ohne version folgt der Step der neuesten */
+  library?: { id: number; version?: number };
 }
 
 interface PipelineState {
@@ -44,6 +46,7 @@ function parseSteps(json: any): PipelineStep[] {
     route: s.route || undefined,
     active: s.active !== false,
     config: s.config ?? undefined,
+    library: s.library ?? undefined,
   }));
 }
 
@@ -115,6 +118,7 @@ export const usePipelineStore = create<PipelineState>((set, get) => ({
         noKey: s.noKey ?? null,
         active: s.active !== false,
         ...(s.config ? { config: s.config } : {}),
+        ...(s.library ? { library: s.library } : {}),
       })),
     };
 
@@ -320,7 +320,11 @@ export default function History() {
     socket.addEventListener('message', ev => {
       try {
         const msg = JSON.parse(ev.data);
-        if (msg.type === 'history' && Array.isArray(msg.data)) {
+        // Quittung steuert den Nachschub des Servers (Flow Control)
+        if (typeof msg.seq === 'number') {
+          socket.send(JSON.stringify({ type: 'ack', seq: msg.seq }));
+        }
+        if ((msg.type === 'snapshot' || msg.type === 'replay') && Array.isArray(msg.data)) {
           const bulk = msg.data.map((e: any) => normalizeEntry(e));
           setEntries(prev => {
             const map = new Map<number, HistoryEntry>();
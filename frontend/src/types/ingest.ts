@@ -11,6 +11,9 @@ export type FolderAutomationSummary = Readonly<{
   pipeline_id?: string | null;
   auto_ingest: boolean;
   auto_pipeline: boolean;
+  schedule?: string | null;
+  next_run_at?: string | null;
+  reingest?: 'new_files' | 'all_files' | null;
   last_seen?: string | null;
   updated_at?: string | null;
 }>;
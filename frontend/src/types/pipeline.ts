@@ -2,6 +2,7 @@ export interface TextPosition {
   page: number;
   bbox: [number, number, number, number];
   quote?: string;
+  confidence?: number;
 }
 
 export interface PromptResult {
@@ -0,0 +1,31 @@
+-- migrations/0013_history_source_context.sql
+SET search_path TO public;
+
+-- v_analysis_history_with_tenant um den SharePoint-Ordner erweitern.
+-- DROP statt REPLACE, weil ah.* inzwischen zusätzliche Spalten (started_at/finished_at) enthält.
+DROP VIEW IF EXISTS v_analysis_history_with_tenant;
+CREATE VIEW v_analysis_history_with_tenant AS
+SELECT
+    ah.*,
+    t.id   AS tenant_id,
+    t.name AS tenant_name,
+    ps.names AS pdf_names,
+    sj.folder_name AS sharepoint_folder_name
+FROM analysis_history ah
+         LEFT JOIN LATERAL (
+    SELECT u.*
+    FROM uploads u
+    WHERE u.pdf_id = ah.pdf_id
+      AND (ah.pipeline_id IS NULL OR u.pipeline_id = ah.pipeline_id)
+    ORDER BY u.id DESC
+        LIMIT 1
+) u ON TRUE
+    LEFT JOIN tenants t ON t.id = u.tenant_id
+    LEFT JOIN pdf_sources ps ON ps.pdf_id = ah.pdf_id
+    LEFT JOIN LATERAL (
+    SELECT j.folder_name
+    FROM sharepoint_jobs j
+    WHERE j.pdf_id = ah.pdf_id
+    ORDER BY j.created_at DESC
+        LIMIT 1
+) sj ON TRUE;
@@ -0,0 +1,20 @@
+-- migrations/0014_run_timeline.sql
+SET search_path TO public;
+
+-- Einheitliche Status-Chronologie über alle Services (Upload, OCR, Pipeline, SharePoint)
+CREATE TABLE IF NOT EXISTS run_timeline (
+    id BIGSERIAL PRIMARY KEY,
+    pdf_id INTEGER,
+    upload_id INTEGER,
+    run_id UUID,
+    pipeline_id UUID,
+    source TEXT NOT NULL,
+    status TEXT NOT NULL,
+    message TEXT,
+    details JSONB,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT now()
+);
+
+CREATE INDEX IF NOT EXISTS idx_run_timeline_pdf_id ON run_timeline (pdf_id, created_at);
+CREATE INDEX IF NOT EXISTS idx_run_timeline_run_id ON run_timeline (run_id);
+CREATE INDEX IF NOT EXISTS idx_run_timeline_upload_id ON run_timeline (upload_id);
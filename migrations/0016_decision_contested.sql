@@ -0,0 +1,7 @@
+SET search_path TO public;
+
+-- Finale Entscheidungen mit widersprüchlichen starken Stimmen markieren (Review-Workflow)
+ALTER TABLE pipeline_run_steps ADD COLUMN IF NOT EXISTS contested BOOLEAN NOT NULL DEFAULT FALSE;
+ALTER TABLE pipeline_runs ADD COLUMN IF NOT EXISTS contested BOOLEAN NOT NULL DEFAULT FALSE;
+
+CREATE INDEX IF NOT EXISTS idx_pipeline_runs_contested ON pipeline_runs (contested) WHERE contested;
@@ -0,0 +1,5 @@
+SET search_path TO public;
+
+-- Vollständige finale Extraktion nur verschlüsselt (Envelope, CREDENTIALS_MASTER_KEY);
+-- final_extraction und pipeline_run_steps.result enthalten maskierte Werte
+ALTER TABLE pipeline_runs ADD COLUMN IF NOT EXISTS final_extraction_sealed JSONB;
@@ -0,0 +1,35 @@
+SET search_path TO public;
+
+-- SFTP-Quellen je Mandant; Zugangsdaten als Envelope (CREDENTIALS_MASTER_KEY)
+CREATE TABLE IF NOT EXISTS sftp_sources (
+    id UUID PRIMARY KEY,
+    name TEXT NOT NULL,
+    tenant_id UUID,
+    pipeline_id UUID,
+    host TEXT NOT NULL,
+    port INTEGER NOT NULL DEFAULT 22,
+    username TEXT NOT NULL,
+    host_key_sha256 TEXT NOT NULL,
+    remote_dir TEXT NOT NULL DEFAULT '.',
+    patterns TEXT[] NOT NULL DEFAULT ARRAY['*.pdf', '*.pdf.pgp', '*.pdf.gpg'],
+    archive_dir TEXT,
+    credentials JSONB NOT NULL,
+    enabled BOOLEAN NOT NULL DEFAULT TRUE,
+    last_polled_at TIMESTAMPTZ,
+    last_error TEXT,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT now(),
+    updated_at TIMESTAMPTZ NOT NULL DEFAULT now()
+);
+
+-- bereits übernommene Dateien (Pfad + "size:mtime")
+CREATE TABLE IF NOT EXISTS sftp_seen_files (
+    source_id UUID NOT NULL REFERENCES sftp_sources(id) ON DELETE CASCADE,
+    path TEXT NOT NULL,
+    fingerprint TEXT NOT NULL,
+    job_id UUID,
+    seen_at TIMESTAMPTZ NOT NULL DEFAULT now(),
+    PRIMARY KEY (source_id, path, fingerprint)
+);
+
+-- Herkunft eines Jobs; NULL = SharePoint-Ordner
+ALTER TABLE sharepoint_jobs ADD COLUMN IF NOT EXISTS source JSONB;
@@ -0,0 +1,38 @@
+SET search_path TO public;
+
+-- IMAP-Postfächer je Mandant; Passwort als Envelope (CREDENTIALS_MASTER_KEY)
+CREATE TABLE IF NOT EXISTS imap_mailboxes (
+    id UUID PRIMARY KEY,
+    name TEXT NOT NULL,
+    tenant_id UUID,
+    pipeline_id UUID,
+    host TEXT NOT NULL,
+    port INTEGER NOT NULL DEFAULT 993,
+    username TEXT NOT NULL,
+    credentials JSONB NOT NULL,
+    folder TEXT NOT NULL DEFAULT 'INBOX',
+    processed_keyword TEXT NOT NULL DEFAULT '$RegressIngested',
+    include_body BOOLEAN NOT NULL DEFAULT TRUE,
+    enabled BOOLEAN NOT NULL DEFAULT TRUE,
+    last_polled_at TIMESTAMPTZ,
+    last_error TEXT,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT now(),
+    updated_at TIMESTAMPTZ NOT NULL DEFAULT now()
+);
+
+-- verarbeitete Mails (UIDVALIDITY + UID) mit erzeugtem Job und Run
+CREATE TABLE IF NOT EXISTS imap_messages (
+    mailbox_id UUID NOT NULL REFERENCES imap_mailboxes(id) ON DELETE CASCADE,
+    uid_validity BIGINT NOT NULL,
+    uid BIGINT NOT NULL,
+    message_id TEXT,
+    subject TEXT,
+    sender TEXT,
+    attachments INTEGER NOT NULL DEFAULT 0,
+    job_id UUID,
+    run_id UUID,
+    seen_at TIMESTAMPTZ NOT NULL DEFAULT now(),
+    PRIMARY KEY (mailbox_id, uid_validity, uid)
+);
+
+CREATE INDEX IF NOT EXISTS idx_imap_messages_job ON imap_messages (job_id);
@@ -0,0 +1,72 @@
+SET search_path TO public;
+
+-- Menschliches Label und externe Referenz (Aktenzeichen) je Job.
+-- Die Werte wandern vom Job über uploads und pipeline_runs bis in analysis_history.
+ALTER TABLE sharepoint_jobs
+    ADD COLUMN IF NOT EXISTS job_label TEXT,
+    ADD COLUMN IF NOT EXISTS external_ref TEXT;
+-- sharepoint_automation wird vom sharepoint-ingest beim Start angelegt
+ALTER TABLE IF EXISTS sharepoint_automation
+    ADD COLUMN IF NOT EXISTS job_label TEXT,
+    ADD COLUMN IF NOT EXISTS external_ref TEXT;
+ALTER TABLE uploads
+    ADD COLUMN IF NOT EXISTS job_label TEXT,
+    ADD COLUMN IF NOT EXISTS external_ref TEXT;
+ALTER TABLE pipeline_runs
+    ADD COLUMN IF NOT EXISTS job_label TEXT,
+    ADD COLUMN IF NOT EXISTS external_ref TEXT;
+ALTER TABLE analysis_history
+    ADD COLUMN IF NOT EXISTS job_label TEXT,
+    ADD COLUMN IF NOT EXISTS external_ref TEXT;
+
+CREATE INDEX IF NOT EXISTS idx_sharepoint_jobs_external_ref ON sharepoint_jobs (external_ref);
+CREATE INDEX IF NOT EXISTS idx_uploads_external_ref ON uploads (external_ref);
+CREATE INDEX IF NOT EXISTS idx_pipeline_runs_external_ref ON pipeline_runs (external_ref);
+CREATE INDEX IF NOT EXISTS idx_analysis_history_external_ref ON analysis_history (external_ref);
+
+-- Views neu anlegen, damit pr.* bzw. ah.* die neuen Spalten enthalten
+DROP VIEW IF EXISTS v_pipeline_runs_with_tenant;
+CREATE VIEW v_pipeline_runs_with_tenant AS
+SELECT
+    pr.*,
+    t.id   AS tenant_id,
+    t.name AS tenant_name,
+    ps.names AS pdf_names
+FROM pipeline_runs pr
+         LEFT JOIN LATERAL (
+    SELECT u.*
+    FROM uploads u
+    WHERE u.pdf_id = pr.pdf_id
+      AND (u.pipeline_id IS NULL OR u.pipeline_id = pr.pipeline_id)
+    ORDER BY u.id DESC
+        LIMIT 1
+) u ON TRUE
+    LEFT JOIN tenants t ON t.id = u.tenant_id
+    LEFT JOIN pdf_sources ps ON ps.pdf_id = pr.pdf_id;
+
+DROP VIEW IF EXISTS v_analysis_history_with_tenant;
+CREATE VIEW v_analysis_history_with_tenant AS
+SELECT
+    ah.*,
+    t.id   AS tenant_id,
+    t.name AS tenant_name,
+    ps.names AS pdf_names,
+    sj.folder_name AS sharepoint_folder_name
+FROM analysis_history ah
+         LEFT JOIN LATERAL (
+    SELECT u.*
+    FROM uploads u
+    WHERE u.pdf_id = ah.pdf_id
+      AND (ah.pipeline_id IS NULL OR u.pipeline_id = ah.pipeline_id)
+    ORDER BY u.id DESC
+        LIMIT 1
+) u ON TRUE
+    LEFT JOIN tenants t ON t.id = u.tenant_id
+    LEFT JOIN pdf_sources ps ON ps.pdf_id = ah.pdf_id
+    LEFT JOIN LATERAL (
+    SELECT j.folder_name
+    FROM sharepoint_jobs j
+    WHERE j.pdf_id = ah.pdf_id
+    ORDER BY j.created_at DESC
+        LIMIT 1
+) sj ON TRUE;
@@ -0,0 +1,23 @@
+SET search_path TO public;
+
+-- Quarantäne für Dateien, die beim Scan abgewiesen wurden (getarnte Executables,
+-- kein PDF, Größenlimits, ClamAV-Fund). Die Datei selbst liegt in QUARANTINE_DIR.
+CREATE TABLE IF NOT EXISTS scan_quarantine (
+    id UUID PRIMARY KEY,
+    job_id UUID,
+    folder_id TEXT,
+    tenant_id UUID,
+    file_name TEXT NOT NULL,
+    reason TEXT NOT NULL,
+    detail TEXT,
+    size_bytes BIGINT NOT NULL,
+    sha256 TEXT NOT NULL,
+    stored_path TEXT,
+    status TEXT NOT NULL DEFAULT 'quarantined' CHECK (status IN ('quarantined','released','deleted')),
+    created_at TIMESTAMPTZ NOT NULL DEFAULT now(),
+    resolved_at TIMESTAMPTZ
+);
+
+CREATE INDEX IF NOT EXISTS idx_scan_quarantine_status ON scan_quarantine (status, created_at DESC);
+CREATE INDEX IF NOT EXISTS idx_scan_quarantine_sha256 ON scan_quarantine (sha256);
+CREATE INDEX IF NOT EXISTS idx_scan_quarantine_job ON scan_quarantine (job_id);
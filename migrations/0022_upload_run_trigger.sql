@@ -0,0 +1,12 @@
+SET search_path TO public;
+
+-- Pipeline-Start direkt aus dem Upload:
+--   run_state NULL        -> keine Pipeline angegeben
+--   run_state 'pending'   -> startet, sobald text-extracted eintrifft
+--   run_state 'parked'    -> wartet auf POST /uploads/{id}/run
+--   run_state 'triggered' -> pipeline-run wurde publiziert
+ALTER TABLE uploads
+    ADD COLUMN IF NOT EXISTS run_state TEXT,
+    ADD COLUMN IF NOT EXISTS run_priority TEXT NOT NULL DEFAULT 'normal';
+
+CREATE INDEX IF NOT EXISTS idx_uploads_pending_run ON uploads (pdf_id) WHERE run_state = 'pending';
@@ -0,0 +1,31 @@
+SET search_path TO public;
+
+-- Einheitliches Run-Status-Vokabular (shared::dto::RunStatus):
+--   queued, running, completed, failed, canceled
+-- Alt-Schreibweisen werden umgeschrieben; POST /admin/run-status/backfill
+-- (pipeline-api) wiederholt das für Zeilen, die alte Dienste noch schreiben.
+UPDATE pipeline_runs SET status = CASE lower(status)
+        WHEN 'pending'   THEN 'queued'
+        WHEN 'finished'  THEN 'completed'
+        WHEN 'finalized' THEN 'completed'
+        WHEN 'succeeded' THEN 'completed'
+        WHEN 'error'     THEN 'failed'
+        WHEN 'timeout'   THEN 'failed'
+        WHEN 'cancelled' THEN 'canceled'
+        ELSE lower(status)
+    END
+ WHERE status <> lower(status)
+    OR lower(status) IN ('pending','finished','finalized','succeeded','error','timeout','cancelled');
+
+UPDATE analysis_history SET status = CASE lower(status)
+        WHEN 'pending'   THEN 'queued'
+        WHEN 'finished'  THEN 'completed'
+        WHEN 'finalized' THEN 'completed'
+        WHEN 'succeeded' THEN 'completed'
+        WHEN 'error'     THEN 'failed'
+        WHEN 'timeout'   THEN 'failed'
+        WHEN 'cancelled' THEN 'canceled'
+        ELSE lower(status)
+    END
+ WHERE status <> lower(status)
+    OR lower(status) IN ('pending','finished','finalized','succeeded','error','timeout','cancelled');
@@ -0,0 +1,7 @@
+SET search_path TO public;
+
+-- Reihenfolge der Prompts innerhalb einer Gruppe (Index in prompt_ids).
+-- Bestehende Gruppen behalten 0 und werden nach prompt_id sortiert.
+ALTER TABLE group_prompts ADD COLUMN IF NOT EXISTS position INTEGER NOT NULL DEFAULT 0;
+
+CREATE INDEX IF NOT EXISTS idx_group_prompts_position ON group_prompts (group_id, position);
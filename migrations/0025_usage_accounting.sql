@@ -0,0 +1,24 @@
+SET search_path TO public;
+
+-- Token-Verbrauch je Lauf (vom pipeline-runner gesetzt) für den Usage-Report.
+ALTER TABLE pipeline_runs
+    ADD COLUMN IF NOT EXISTS prompt_tokens BIGINT NOT NULL DEFAULT 0,
+    ADD COLUMN IF NOT EXISTS completion_tokens BIGINT NOT NULL DEFAULT 0,
+    ADD COLUMN IF NOT EXISTS openai_calls INTEGER NOT NULL DEFAULT 0;
+
+-- Zeitpunkt des Uploads, damit Dokumente einem Abrechnungsmonat zugeordnet werden können.
+ALTER TABLE uploads ADD COLUMN IF NOT EXISTS created_at TIMESTAMPTZ DEFAULT now();
+
+-- Bestehende Uploads: Zeitpunkt aus dem 'uploaded'-Event der Timeline übernehmen
+UPDATE uploads u
+SET created_at = t.first_seen
+FROM (
+    SELECT upload_id, MIN(created_at) AS first_seen
+    FROM run_timeline
+    WHERE status = 'uploaded' AND upload_id IS NOT NULL
+    GROUP BY upload_id
+) t
+WHERE t.upload_id = u.id;
+
+CREATE INDEX IF NOT EXISTS idx_uploads_created_at ON uploads (created_at);
+CREATE INDEX IF NOT EXISTS idx_pipeline_runs_started_at ON pipeline_runs (started_at);
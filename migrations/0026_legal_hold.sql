@@ -0,0 +1,11 @@
+SET search_path TO public;
+
+-- Legal Hold: Dokumente in Rechtsstreitigkeiten dürfen nicht gelöscht werden,
+-- bis der Hold wieder aufgehoben ist. Setzen/Aufheben wird in run_timeline protokolliert.
+ALTER TABLE merged_pdfs
+    ADD COLUMN IF NOT EXISTS legal_hold BOOLEAN NOT NULL DEFAULT FALSE,
+    ADD COLUMN IF NOT EXISTS legal_hold_reason TEXT,
+    ADD COLUMN IF NOT EXISTS legal_hold_by TEXT,
+    ADD COLUMN IF NOT EXISTS legal_hold_at TIMESTAMPTZ;
+
+CREATE INDEX IF NOT EXISTS idx_merged_pdfs_legal_hold ON merged_pdfs (id) WHERE legal_hold;
@@ -0,0 +1,8 @@
+SET search_path TO public;
+
+-- Reruns mit übernommener Extraktion (POST /runs/{id}/rerun?reuse=extraction)
+-- verweisen auf den Ursprungslauf.
+ALTER TABLE pipeline_runs
+    ADD COLUMN IF NOT EXISTS rerun_of UUID REFERENCES pipeline_runs(id) ON DELETE SET NULL;
+
+CREATE INDEX IF NOT EXISTS idx_pipeline_runs_rerun_of ON pipeline_runs (rerun_of) WHERE rerun_of IS NOT NULL;
@@ -0,0 +1,16 @@
+SET search_path TO public;
+
+-- Nachgereichte Seiten (POST /pdf/{id}/append) ersetzen merged_pdfs.data;
+-- der vorherige Stand bleibt als Version erhalten.
+ALTER TABLE merged_pdfs ADD COLUMN IF NOT EXISTS version INTEGER NOT NULL DEFAULT 1;
+
+CREATE TABLE IF NOT EXISTS pdf_versions (
+    pdf_id INTEGER NOT NULL REFERENCES merged_pdfs(id) ON DELETE CASCADE,
+    version INTEGER NOT NULL,
+    sha256 TEXT NOT NULL,
+    size_bytes INTEGER NOT NULL,
+    page_count INTEGER NOT NULL,
+    data BYTEA NOT NULL,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT now(),
+    PRIMARY KEY (pdf_id, version)
+);
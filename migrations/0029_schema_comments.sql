@@ -0,0 +1,42 @@
+SET search_path TO public;
+
+-- Spaltenbedeutungen für Integratoren; GET /admin/schema liefert diese Kommentare mit aus.
+
+COMMENT ON TABLE merged_pdfs IS 'Merged PDF per upload batch; the unit all text, runs and timeline entries refer to';
+COMMENT ON COLUMN merged_pdfs.sha256 IS 'SHA-256 of data (hex), changes when pages are appended';
+COMMENT ON COLUMN merged_pdfs.data IS 'Current PDF bytes; earlier states are kept in pdf_versions';
+COMMENT ON COLUMN merged_pdfs.version IS 'Starts at 1, incremented by POST /pdf/{id}/append';
+COMMENT ON COLUMN merged_pdfs.legal_hold IS 'Deletion is refused while true';
+
+COMMENT ON TABLE pdf_sources IS 'Original file names merged into a PDF';
+COMMENT ON COLUMN pdf_sources.names IS 'JSON array of the original file names in merge order';
+
+COMMENT ON TABLE pdf_texts IS 'Extracted text per page (text-extraction)';
+COMMENT ON COLUMN pdf_texts.page_no IS '0-based page number';
+
+COMMENT ON TABLE pdf_versions IS 'Previous states of merged_pdfs.data, one row per replaced version';
+
+COMMENT ON TABLE uploads IS 'Assignment of a PDF to a pipeline; one PDF can have several uploads';
+COMMENT ON COLUMN uploads.status IS 'Upload state: ocr while text is extracted, then ready or pending (run requested)';
+COMMENT ON COLUMN uploads.job_label IS 'Free text case label copied to pipeline_runs and analysis_history';
+COMMENT ON COLUMN uploads.external_ref IS 'Customer reference (file number) copied to pipeline_runs and analysis_history';
+
+COMMENT ON TABLE pipeline_runs IS 'One execution of a pipeline on a PDF (pipeline-runner)';
+COMMENT ON COLUMN pipeline_runs.status IS 'queued, running, completed, failed or canceled';
+COMMENT ON COLUMN pipeline_runs.overall_score IS 'Weighted score 0..1 over all final scoring results';
+COMMENT ON COLUMN pipeline_runs.final_extraction IS 'Final extraction values by json_key, sensitive values scrubbed';
+COMMENT ON COLUMN pipeline_runs.final_extraction_sealed IS 'Unscrubbed final extraction, envelope-encrypted with CREDENTIALS_MASTER_KEY';
+COMMENT ON COLUMN pipeline_runs.final_scores IS 'Final scoring results by key score_<prompt_id>';
+COMMENT ON COLUMN pipeline_runs.final_decisions IS 'Final decision answers by key decision_<prompt_id>';
+COMMENT ON COLUMN pipeline_runs.contested IS 'True when at least one final decision is contested';
+COMMENT ON COLUMN pipeline_runs.rerun_of IS 'Run whose final extraction was reused (POST /runs/{id}/rerun)';
+
+COMMENT ON TABLE pipeline_run_steps IS 'Logged batches and final results of each run step';
+COMMENT ON COLUMN pipeline_run_steps.contested IS 'Final decision with strong votes for different routes';
+
+COMMENT ON TABLE run_timeline IS 'Status chronology written by every service (GET /timeline)';
+COMMENT ON COLUMN run_timeline.source IS 'Service that recorded the event';
+COMMENT ON COLUMN run_timeline.status IS 'Event name, e.g. uploaded, text_extracted, running, completed';
+
+COMMENT ON TABLE event_outbox IS 'Kafka events pending or already published by the outbox relay';
+COMMENT ON COLUMN event_outbox.published_at IS 'NULL while the event still has to be published';
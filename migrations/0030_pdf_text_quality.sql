@@ -0,0 +1,9 @@
+SET search_path TO public;
+
+-- Qualitätsbewertung der Seitentexte: welche Strategie gewonnen hat (pdftotext
+-- oder eine OCR-Variante wie psm4@300dpi) und der Wörterbuch-Score des Texts.
+ALTER TABLE pdf_texts ADD COLUMN IF NOT EXISTS ocr_strategy TEXT;
+ALTER TABLE pdf_texts ADD COLUMN IF NOT EXISTS quality_score REAL;
+
+COMMENT ON COLUMN pdf_texts.ocr_strategy IS 'Winning extraction strategy: pdftotext or psm<mode>@<dpi>dpi for OCR';
+COMMENT ON COLUMN pdf_texts.quality_score IS 'Share of dictionary words in text (0..1), NULL for pages with too little text';
@@ -0,0 +1,24 @@
+SET search_path TO public;
+
+-- Entity-Index je Dokument (text-extraction mit ENTITY_INDEX=1): Datumsangaben,
+-- Beträge, IBANs, Namen und Adressen mit Seite und Bounding Box.
+CREATE TABLE IF NOT EXISTS pdf_entities (
+    id BIGSERIAL PRIMARY KEY,
+    merged_pdf_id INTEGER NOT NULL REFERENCES merged_pdfs(id) ON DELETE CASCADE,
+    page_no INTEGER NOT NULL,
+    kind TEXT NOT NULL,
+    value TEXT NOT NULL,
+    normalized TEXT NOT NULL,
+    bbox JSONB,
+    source TEXT NOT NULL,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT now()
+);
+
+CREATE INDEX IF NOT EXISTS idx_pdf_entities_pdf ON pdf_entities (merged_pdf_id, page_no);
+CREATE INDEX IF NOT EXISTS idx_pdf_entities_kind_value ON pdf_entities (kind, normalized);
+
+COMMENT ON TABLE pdf_entities IS 'Named entities per page (dates, amounts, IBANs, names, addresses), GET /pdf/{id}/entities';
+COMMENT ON COLUMN pdf_entities.page_no IS '0-based page number as in pdf_texts';
+COMMENT ON COLUMN pdf_entities.normalized IS 'ISO date, 1234.56 EUR, IBAN without spaces or lowercase text';
+COMMENT ON COLUMN pdf_entities.bbox IS '[x0, y0, x1, y1] in the layout coordinates of pdf_texts.layout_json';
+COMMENT ON COLUMN pdf_entities.source IS 'regex or model (ENTITY_MODEL_URL)';
@@ -0,0 +1,45 @@
+SET search_path TO public;
+
+-- Externe Ergebnis-Senken je Mandant (history-service): fertige Ergebnisse
+-- zusätzlich in eine Kunden-Postgres-Tabelle oder an einen HTTP-Endpunkt.
+CREATE TABLE IF NOT EXISTS result_sinks (
+    id SERIAL PRIMARY KEY,
+    tenant_id UUID NOT NULL REFERENCES tenants(id) ON DELETE CASCADE,
+    name TEXT NOT NULL,
+    kind TEXT NOT NULL,
+    target TEXT NOT NULL,
+    secret JSONB,
+    mapping JSONB NOT NULL DEFAULT '[]'::jsonb,
+    conflict_key TEXT[] NOT NULL DEFAULT '{}',
+    enabled BOOLEAN NOT NULL DEFAULT TRUE,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT now(),
+    UNIQUE (tenant_id, name)
+);
+
+-- Zustellungen je Senke und Ergebnis inkl. Wiederholungen
+CREATE TABLE IF NOT EXISTS result_sink_deliveries (
+    id BIGSERIAL PRIMARY KEY,
+    sink_id INTEGER NOT NULL REFERENCES result_sinks(id) ON DELETE CASCADE,
+    analysis_id INTEGER NOT NULL,
+    run_id UUID,
+    payload JSONB NOT NULL,
+    status TEXT NOT NULL DEFAULT 'pending',
+    attempts INTEGER NOT NULL DEFAULT 0,
+    last_error TEXT,
+    next_attempt_at TIMESTAMPTZ NOT NULL DEFAULT now(),
+    delivered_at TIMESTAMPTZ,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT now(),
+    UNIQUE (sink_id, analysis_id)
+);
+
+CREATE INDEX IF NOT EXISTS idx_result_sink_deliveries_due
+    ON result_sink_deliveries (next_attempt_at) WHERE status = 'pending';
+
+COMMENT ON TABLE result_sinks IS 'Customer destinations for finished run results, /tenants/{id}/sinks';
+COMMENT ON COLUMN result_sinks.kind IS 'postgres or http';
+COMMENT ON COLUMN result_sinks.target IS 'schema.table for postgres, URL for http';
+COMMENT ON COLUMN result_sinks.secret IS 'Envelope-encrypted DSN (postgres) or Authorization header (http)';
+COMMENT ON COLUMN result_sinks.mapping IS '[{"column", "path"}], dotted paths into the delivered record';
+COMMENT ON COLUMN result_sinks.conflict_key IS 'Unique columns of the target table; redeliveries update the row';
+COMMENT ON TABLE result_sink_deliveries IS 'Delivery queue and status per sink and analysis_history row';
+COMMENT ON COLUMN result_sink_deliveries.status IS 'pending, delivered or failed (after SINK_MAX_ATTEMPTS)';
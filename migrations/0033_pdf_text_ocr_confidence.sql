@@ -0,0 +1,7 @@
+SET search_path TO public;
+
+-- Wortkonfidenz aus dem hOCR (x_wconf): je Wort in layout_json.words[].confidence,
+-- je Seite als Mittelwert. Seiten aus der Textebene des PDFs bleiben NULL.
+ALTER TABLE pdf_texts ADD COLUMN IF NOT EXISTS ocr_confidence REAL;
+
+COMMENT ON COLUMN pdf_texts.ocr_confidence IS 'Mean OCR word confidence of the page (0..1), NULL for vector text';
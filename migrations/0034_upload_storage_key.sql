@@ -0,0 +1,8 @@
+SET search_path TO public;
+
+-- Direkt-Uploads nach S3 (BLOB_STORE=s3): Objekt-Schlüssel der vorsignierten
+-- PUT-URL; der Upload bleibt im Status 'awaiting_upload', bis
+-- POST /uploads/{id}/complete die Datei übernimmt.
+ALTER TABLE uploads ADD COLUMN IF NOT EXISTS storage_key TEXT;
+
+COMMENT ON COLUMN uploads.storage_key IS 'Object key of a direct upload to S3; the object is deleted once merged';
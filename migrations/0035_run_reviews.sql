@@ -0,0 +1,30 @@
+SET search_path TO public;
+
+-- Ergebnis der manuellen Prüfung eines Runs (PUT /runs/{id}/review); Grundlage
+-- der Kalibrierung von weight/min_signal der Scoring-Steps.
+CREATE TABLE IF NOT EXISTS run_reviews (
+    run_id          UUID PRIMARY KEY REFERENCES pipeline_runs(id) ON DELETE CASCADE,
+    verdict         TEXT        NOT NULL CHECK (verdict IN ('approved','rejected')),
+    prompt_verdicts JSONB       NOT NULL DEFAULT '{}'::jsonb,
+    reviewer        TEXT,
+    note            TEXT,
+    created_at      TIMESTAMPTZ NOT NULL DEFAULT now(),
+    updated_at      TIMESTAMPTZ NOT NULL DEFAULT now()
+);
+
+-- Kalibrierungsberichte (POST /pipelines/{id}/calibration); Vorschläge werden
+-- erst mit POST /calibration/{id}/apply in die Pipeline übernommen.
+CREATE TABLE IF NOT EXISTS calibration_reports (
+    id          UUID PRIMARY KEY,
+    pipeline_id UUID        NOT NULL REFERENCES pipelines(id) ON DELETE CASCADE,
+    report      JSONB       NOT NULL,
+    created_at  TIMESTAMPTZ NOT NULL DEFAULT now(),
+    applied_at  TIMESTAMPTZ
+);
+
+CREATE INDEX IF NOT EXISTS idx_calibration_reports_pipeline
+    ON calibration_reports (pipeline_id, created_at DESC);
+
+COMMENT ON TABLE run_reviews IS 'Reviewer outcome per run; prompt_verdicts maps scoring prompt ids to the correct answer';
+COMMENT ON TABLE calibration_reports IS 'Suggested weight/min_signal per scoring step derived from run_reviews';
+COMMENT ON COLUMN calibration_reports.applied_at IS 'Set once the suggestions were written into the pipeline config';
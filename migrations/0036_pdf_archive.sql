@@ -0,0 +1,23 @@
+SET search_path TO public;
+
+-- Cold Storage für alte zusammengeführte PDFs (pdf-ingest, archive.rs):
+-- Der Lifecycle-Job verschiebt data nach ARCHIVE_AFTER_DAYS in ein gzip-Archiv
+-- auf dem Dateisystem oder eine S3-Glacier-Klasse; Metadaten bleiben hier.
+-- Bestehende Zeilen erhalten als created_at den Zeitpunkt der Migration.
+ALTER TABLE merged_pdfs
+    ALTER COLUMN data DROP NOT NULL,
+    ADD COLUMN IF NOT EXISTS created_at TIMESTAMPTZ NOT NULL DEFAULT now(),
+    ADD COLUMN IF NOT EXISTS storage_tier TEXT NOT NULL DEFAULT 'hot',
+    ADD COLUMN IF NOT EXISTS archive_key TEXT,
+    ADD COLUMN IF NOT EXISTS archived_at TIMESTAMPTZ,
+    ADD COLUMN IF NOT EXISTS restored_at TIMESTAMPTZ;
+
+CREATE INDEX IF NOT EXISTS idx_merged_pdfs_storage_tier
+    ON merged_pdfs (storage_tier) WHERE storage_tier <> 'hot';
+
+COMMENT ON COLUMN merged_pdfs.data IS 'Current PDF bytes; NULL while archived, earlier states are kept in pdf_versions';
+COMMENT ON COLUMN merged_pdfs.created_at IS 'Time the merged PDF was first stored';
+COMMENT ON COLUMN merged_pdfs.storage_tier IS 'hot (bytes in data), archived (bytes only in cold storage) or restoring';
+COMMENT ON COLUMN merged_pdfs.archive_key IS 'File name or S3 key of the archived copy while not hot';
+COMMENT ON COLUMN merged_pdfs.archived_at IS 'Time the bytes were moved to cold storage';
+COMMENT ON COLUMN merged_pdfs.restored_at IS 'Last restore from cold storage; the archive age counts from here';
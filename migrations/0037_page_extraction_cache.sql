@@ -0,0 +1,24 @@
+SET search_path TO public;
+
+-- Seiten-Cache der Text-Extraktion (EXTRACTION_CACHE=postgres, cache.rs):
+-- Wiederholte Läufe auf demselben PDF übernehmen Text, Layout und OCR-Ergebnis
+-- einer Seite, solange Dokument-Hash und Extraktionsoptionen übereinstimmen.
+CREATE TABLE IF NOT EXISTS page_extraction_cache (
+    sha256 TEXT NOT NULL,
+    page_no INTEGER NOT NULL,
+    options_hash TEXT NOT NULL,
+    text TEXT NOT NULL,
+    ocr_used BOOLEAN NOT NULL,
+    layout_json JSONB,
+    ocr_strategy TEXT,
+    quality_score DOUBLE PRECISION,
+    lang TEXT,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT now(),
+    PRIMARY KEY (sha256, page_no, options_hash)
+);
+
+COMMENT ON TABLE page_extraction_cache IS 'Extracted pages reused by text-extraction across runs of the same PDF';
+COMMENT ON COLUMN page_extraction_cache.sha256 IS 'SHA-256 of the whole PDF (hex)';
+COMMENT ON COLUMN page_extraction_cache.page_no IS '0-based page number, as in pdf_texts';
+COMMENT ON COLUMN page_extraction_cache.options_hash IS 'Hash of the extraction options (OCR language, DPI, strategies, layout, ...) and the cache version';
+COMMENT ON COLUMN page_extraction_cache.created_at IS 'Rows can be deleted at any time; pages are extracted again on a miss';
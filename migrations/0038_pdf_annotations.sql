@@ -0,0 +1,27 @@
+SET search_path TO public;
+
+-- Markierungen aus der Review-Oberfläche: Highlights, Rahmen und Notizen je
+-- Seite, optional mit Bezug auf einen finalen Schlüssel eines Laufs.
+CREATE TABLE IF NOT EXISTS pdf_annotations (
+    id BIGSERIAL PRIMARY KEY,
+    merged_pdf_id INTEGER NOT NULL REFERENCES merged_pdfs(id) ON DELETE CASCADE,
+    page_no INTEGER NOT NULL,
+    kind TEXT NOT NULL,
+    bbox JSONB,
+    note TEXT,
+    final_key TEXT,
+    run_id UUID,
+    author TEXT,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT now()
+);
+
+CREATE INDEX IF NOT EXISTS idx_pdf_annotations_pdf ON pdf_annotations (merged_pdf_id, page_no);
+CREATE INDEX IF NOT EXISTS idx_pdf_annotations_final_key ON pdf_annotations (final_key)
+    WHERE final_key IS NOT NULL;
+
+COMMENT ON TABLE pdf_annotations IS 'Reviewer markups per page, POST/GET /pdf/{id}/annotations';
+COMMENT ON COLUMN pdf_annotations.page_no IS '0-based page number as in pdf_texts';
+COMMENT ON COLUMN pdf_annotations.kind IS 'highlight, rect or note';
+COMMENT ON COLUMN pdf_annotations.bbox IS '[x0, y0, x1, y1] in the layout coordinates of pdf_texts.layout_json';
+COMMENT ON COLUMN pdf_annotations.final_key IS 'Final key of a run the markup belongs to, usable as grounding hint';
+COMMENT ON COLUMN pdf_annotations.run_id IS 'Run the reviewer looked at, if any';
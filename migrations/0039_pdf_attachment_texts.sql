@@ -0,0 +1,21 @@
+SET search_path TO public;
+
+-- Text eingebetteter PDFs (Portfolios, Anhänge) je Seite, gefüllt von
+-- text-extraction mit EXTRACT_ATTACHMENTS=1.
+CREATE TABLE IF NOT EXISTS pdf_attachment_texts (
+    merged_pdf_id INTEGER NOT NULL REFERENCES merged_pdfs(id) ON DELETE CASCADE,
+    attachment_no INTEGER NOT NULL,
+    name TEXT NOT NULL,
+    size_bytes BIGINT NOT NULL,
+    page_no INTEGER NOT NULL,
+    text TEXT NOT NULL,
+    ocr_used BOOLEAN NOT NULL DEFAULT false,
+    lang TEXT,
+    layout_json JSONB,
+    PRIMARY KEY (merged_pdf_id, attachment_no, page_no)
+);
+
+COMMENT ON TABLE pdf_attachment_texts IS 'Per-page text of PDFs embedded in a merged PDF (pdfdetach)';
+COMMENT ON COLUMN pdf_attachment_texts.attachment_no IS '1-based index of the embedded file as listed by pdfdetach -list';
+COMMENT ON COLUMN pdf_attachment_texts.name IS 'File name stored in the embedding PDF';
+COMMENT ON COLUMN pdf_attachment_texts.page_no IS '0-based page number within the attachment';
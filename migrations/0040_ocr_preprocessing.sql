@@ -0,0 +1,8 @@
+SET search_path TO public;
+
+-- Bildvorverarbeitung vor der OCR (OCR_PREPROCESS) je Seite festhalten.
+ALTER TABLE pdf_texts ADD COLUMN IF NOT EXISTS ocr_preprocessing TEXT;
+ALTER TABLE page_extraction_cache ADD COLUMN IF NOT EXISTS ocr_preprocessing TEXT;
+
+COMMENT ON COLUMN pdf_texts.ocr_preprocessing IS 'Image steps applied before the winning OCR attempt, e.g. threshold,deskew:-1.4';
+COMMENT ON COLUMN page_extraction_cache.ocr_preprocessing IS 'Image steps applied before the winning OCR attempt';
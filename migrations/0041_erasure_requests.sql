@@ -0,0 +1,23 @@
+SET search_path TO public;
+
+-- Löschersuchen betroffener Personen (Art. 17 DSGVO): POST /erasure-requests auf
+-- pdf-ingest löscht bzw. anonymisiert alle Spuren eines Dokuments oder einer
+-- externen Referenz und legt hier den signierten Bericht ab (erasure.rs).
+CREATE TABLE IF NOT EXISTS erasure_requests (
+    id UUID PRIMARY KEY,
+    pdf_ids INTEGER[] NOT NULL,
+    external_ref_sha256 TEXT,
+    requested_by TEXT,
+    reason TEXT,
+    report TEXT NOT NULL,
+    signature TEXT NOT NULL,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT now()
+);
+
+CREATE INDEX IF NOT EXISTS idx_erasure_requests_pdf_ids ON erasure_requests USING GIN (pdf_ids);
+
+COMMENT ON TABLE erasure_requests IS 'Signed reports of data subject erasures, POST/GET /erasure-requests';
+COMMENT ON COLUMN erasure_requests.pdf_ids IS 'Erased merged PDFs';
+COMMENT ON COLUMN erasure_requests.external_ref_sha256 IS 'SHA-256 of the requested external reference; the reference itself is erased';
+COMMENT ON COLUMN erasure_requests.report IS 'Report JSON exactly as signed (TEXT, since JSONB reorders keys)';
+COMMENT ON COLUMN erasure_requests.signature IS 'Hex HMAC-SHA256 of report under ERASURE_SIGNING_KEY';
@@ -0,0 +1,29 @@
+SET search_path TO public;
+
+-- Dokument-Metadaten je zusammengeführtem PDF (text-extraction, pdfinfo):
+-- Informations-Dictionary, Verschlüsselung, Formular, Tagging und Seitengrößen.
+CREATE TABLE IF NOT EXISTS pdf_metadata (
+    merged_pdf_id INTEGER PRIMARY KEY REFERENCES merged_pdfs(id) ON DELETE CASCADE,
+    title TEXT,
+    subject TEXT,
+    keywords TEXT,
+    author TEXT,
+    creator TEXT,
+    producer TEXT,
+    creation_date TEXT,
+    mod_date TEXT,
+    pdf_version TEXT,
+    page_count INTEGER NOT NULL,
+    encrypted BOOLEAN NOT NULL DEFAULT false,
+    form TEXT,
+    tagged BOOLEAN NOT NULL DEFAULT false,
+    page_sizes JSONB NOT NULL DEFAULT '[]'::jsonb,
+    updated_at TIMESTAMPTZ NOT NULL DEFAULT now()
+);
+
+COMMENT ON TABLE pdf_metadata IS 'pdfinfo metadata per merged PDF, GET /pdf/{id}/metadata';
+COMMENT ON COLUMN pdf_metadata.creator IS 'Application that created the original document';
+COMMENT ON COLUMN pdf_metadata.producer IS 'Application that wrote the PDF, e.g. scanner software';
+COMMENT ON COLUMN pdf_metadata.creation_date IS 'ISO 8601 as printed by pdfinfo -isodates';
+COMMENT ON COLUMN pdf_metadata.form IS 'AcroForm or XFA; NULL without form';
+COMMENT ON COLUMN pdf_metadata.page_sizes IS '[{page_no, width, height, rotation}] in points, page_no 0-based';
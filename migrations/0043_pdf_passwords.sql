@@ -0,0 +1,8 @@
+SET search_path TO public;
+
+-- Passwort geschützter PDFs: POST /upload nimmt es als Multipart-Feld `password`
+-- entgegen, pdf-ingest versiegelt es mit CREDENTIALS_MASTER_KEY, text-extraction
+-- öffnet es für die poppler-Aufrufe (-opw/-upw).
+ALTER TABLE uploads ADD COLUMN IF NOT EXISTS pdf_password JSONB;
+
+COMMENT ON COLUMN uploads.pdf_password IS 'Password of an encrypted PDF, sealed with CREDENTIALS_MASTER_KEY (envelope JSON)';
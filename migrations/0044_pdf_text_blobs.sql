@@ -0,0 +1,35 @@
+SET search_path TO public;
+
+-- Seitentexte dedupliziert und komprimiert: text-extraction schreibt jeden Text
+-- einmal nach pdf_text_blobs (Schlüssel SHA-256), pdf_texts verweist nur noch per
+-- text_sha256 darauf. Gelesen wird über die View pdf_page_texts, die Zeilen von
+-- vor der Umstellung (pdf_texts.text) weiter liefert; umziehen lassen sie sich
+-- mit POST /admin/pdf-texts/compact (pdf-ingest).
+CREATE TABLE IF NOT EXISTS pdf_text_blobs (
+    sha256 TEXT PRIMARY KEY,
+    text TEXT NOT NULL,
+    char_len INTEGER NOT NULL,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT now(),
+    last_used_at TIMESTAMPTZ NOT NULL DEFAULT now()
+);
+
+-- komprimiert wird ab ~2 kB (TOAST); lz4 braucht Postgres 14+ mit lz4-Unterstützung,
+-- sonst diese Zeile weglassen (bleibt pglz)
+ALTER TABLE pdf_text_blobs ALTER COLUMN text SET COMPRESSION lz4;
+
+ALTER TABLE pdf_texts
+    ADD COLUMN IF NOT EXISTS text_sha256 TEXT REFERENCES pdf_text_blobs(sha256),
+    ALTER COLUMN text DROP NOT NULL;
+CREATE INDEX IF NOT EXISTS idx_pdf_texts_text_sha256 ON pdf_texts (text_sha256);
+
+CREATE OR REPLACE VIEW pdf_page_texts AS
+SELECT t.merged_pdf_id, t.page_no, COALESCE(b.text, t.text, '') AS text
+  FROM pdf_texts t
+  LEFT JOIN pdf_text_blobs b ON b.sha256 = t.text_sha256;
+
+COMMENT ON TABLE pdf_text_blobs IS 'Distinct page texts (lowercased), shared by all pages with the same text';
+COMMENT ON COLUMN pdf_text_blobs.sha256 IS 'Hex SHA-256 of the UTF-8 text';
+COMMENT ON COLUMN pdf_text_blobs.last_used_at IS 'Last write referencing the blob; unreferenced blobs older than an hour are removed';
+COMMENT ON COLUMN pdf_texts.text IS 'Inline page text of rows written before pdf_text_blobs, NULL once moved';
+COMMENT ON COLUMN pdf_texts.text_sha256 IS 'Page text in pdf_text_blobs';
+COMMENT ON VIEW pdf_page_texts IS 'Page texts resolved from pdf_text_blobs or the inline column';
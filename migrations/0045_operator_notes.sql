@@ -0,0 +1,62 @@
+SET search_path TO public;
+
+-- Bediener-Tags und Notizen an Uploads (PUT /uploads/{id}/notes, pdf-ingest) und
+-- Läufen (PUT /runs/{id}/notes, pipeline-api), z. B. "test", "priority customer",
+-- "duplicate". Tags sind normalisiert (klein, Leerraum zusammengefasst).
+CREATE TABLE IF NOT EXISTS upload_notes (
+    upload_id INTEGER PRIMARY KEY REFERENCES uploads(id) ON DELETE CASCADE,
+    tags TEXT[] NOT NULL DEFAULT '{}',
+    note TEXT,
+    updated_by TEXT,
+    updated_at TIMESTAMPTZ NOT NULL DEFAULT now()
+);
+CREATE INDEX IF NOT EXISTS idx_upload_notes_tags ON upload_notes USING GIN (tags);
+
+CREATE TABLE IF NOT EXISTS run_notes (
+    run_id UUID PRIMARY KEY REFERENCES pipeline_runs(id) ON DELETE CASCADE,
+    tags TEXT[] NOT NULL DEFAULT '{}',
+    note TEXT,
+    updated_by TEXT,
+    updated_at TIMESTAMPTZ NOT NULL DEFAULT now()
+);
+CREATE INDEX IF NOT EXISTS idx_run_notes_tags ON run_notes USING GIN (tags);
+
+COMMENT ON TABLE upload_notes IS 'Operator tags and note per upload';
+COMMENT ON TABLE run_notes IS 'Operator tags and note per pipeline run';
+COMMENT ON COLUMN upload_notes.tags IS 'Lowercased tags, inner whitespace collapsed';
+COMMENT ON COLUMN run_notes.tags IS 'Lowercased tags, inner whitespace collapsed';
+
+-- History-Einträge (und damit die WebSocket-Payloads) tragen die Tags von Lauf
+-- (über state->>'run_id') und Upload
+DROP VIEW IF EXISTS v_analysis_history_with_tenant;
+CREATE VIEW v_analysis_history_with_tenant AS
+SELECT
+    ah.*,
+    t.id   AS tenant_id,
+    t.name AS tenant_name,
+    ps.names AS pdf_names,
+    sj.folder_name AS sharepoint_folder_name,
+    COALESCE(rn.tags, '{}') AS run_tags,
+    rn.note AS run_note,
+    COALESCE(un.tags, '{}') AS upload_tags,
+    un.note AS upload_note
+FROM analysis_history ah
+         LEFT JOIN LATERAL (
+    SELECT u.*
+    FROM uploads u
+    WHERE u.pdf_id = ah.pdf_id
+      AND (ah.pipeline_id IS NULL OR u.pipeline_id = ah.pipeline_id)
+    ORDER BY u.id DESC
+        LIMIT 1
+) u ON TRUE
+    LEFT JOIN tenants t ON t.id = u.tenant_id
+    LEFT JOIN pdf_sources ps ON ps.pdf_id = ah.pdf_id
+    LEFT JOIN LATERAL (
+    SELECT j.folder_name
+    FROM sharepoint_jobs j
+    WHERE j.pdf_id = ah.pdf_id
+    ORDER BY j.created_at DESC
+        LIMIT 1
+) sj ON TRUE
+    LEFT JOIN run_notes rn ON rn.run_id::text = ah.state->>'run_id'
+    LEFT JOIN upload_notes un ON un.upload_id = u.id;
@@ -0,0 +1,20 @@
+SET search_path TO public;
+
+-- Änderungsprotokoll der app_settings (/admin/settings der pipeline-api, auch
+-- PUT /settings/openai-version und /admin/runner-settings); jede Änderung
+-- stellt zusätzlich ein settings-changed-Event in die event_outbox.
+CREATE TABLE IF NOT EXISTS app_settings_audit (
+    id BIGSERIAL PRIMARY KEY,
+    key TEXT NOT NULL,
+    old_value TEXT,
+    new_value TEXT,
+    changed_by TEXT,
+    changed_at TIMESTAMPTZ NOT NULL DEFAULT now()
+);
+CREATE INDEX IF NOT EXISTS idx_app_settings_audit_key
+    ON app_settings_audit (key, changed_at DESC);
+
+COMMENT ON TABLE app_settings_audit IS 'Change log of app_settings written by the pipeline-api';
+COMMENT ON COLUMN app_settings_audit.old_value IS 'NULL when the key was not set before';
+COMMENT ON COLUMN app_settings_audit.new_value IS 'NULL when the key was reset to its default';
+COMMENT ON COLUMN app_settings_audit.changed_by IS 'changed_by given by the caller, if any';
@@ -0,0 +1,13 @@
+SET search_path TO public;
+
+-- Mandanten, deren Seitentexte maskiert als Stichprobe für das Prompt-Engineering
+-- ausgegeben werden dürfen (GET /prompt-samples, pdf-ingest). Freigabe und
+-- Widerruf über PUT/DELETE /admin/tenants/{id}/prompt-samples.
+CREATE TABLE IF NOT EXISTS prompt_sample_tenants (
+    tenant_id UUID PRIMARY KEY REFERENCES tenants(id) ON DELETE CASCADE,
+    approved_by TEXT,
+    approved_at TIMESTAMPTZ NOT NULL DEFAULT now()
+);
+
+COMMENT ON TABLE prompt_sample_tenants IS 'Tenants approved for masked prompt samples of their page texts';
+COMMENT ON COLUMN prompt_sample_tenants.approved_by IS 'approved_by given by the admin, if any';
@@ -0,0 +1,13 @@
+SET search_path TO public;
+
+-- Durchsuchbare Kopie gescannter PDFs (text-extraction, SEARCHABLE_PDF=1):
+-- jede Seite als Bild mit unsichtbarer OCR-Textebene, ausgeliefert über
+-- GET /pdf/{id}/searchable. Gilt nur, solange searchable_sha256 = sha256.
+ALTER TABLE merged_pdfs
+    ADD COLUMN IF NOT EXISTS searchable_data BYTEA,
+    ADD COLUMN IF NOT EXISTS searchable_sha256 TEXT,
+    ADD COLUMN IF NOT EXISTS searchable_created_at TIMESTAMPTZ;
+
+COMMENT ON COLUMN merged_pdfs.searchable_data IS 'Searchable copy with an invisible OCR text layer; NULL if none';
+COMMENT ON COLUMN merged_pdfs.searchable_sha256 IS 'sha256 of the PDF the searchable copy was built from';
+COMMENT ON COLUMN merged_pdfs.searchable_created_at IS 'When the searchable copy was built';
@@ -0,0 +1,13 @@
+SET search_path TO public;
+
+-- Delta-Link je Laufwerk für den SharePoint-Automation-Poller: statt alle
+-- Unterordner des Eingangsordners zu listen, fragt er nur Änderungen seit dem
+-- letzten Poll ab (Microsoft Graph delta query).
+CREATE TABLE IF NOT EXISTS sharepoint_sync_state (
+    drive_id TEXT PRIMARY KEY,
+    delta_link TEXT NOT NULL,
+    updated_at TIMESTAMPTZ NOT NULL DEFAULT now()
+);
+
+COMMENT ON TABLE sharepoint_sync_state IS 'Graph delta link per drive, advanced after every successful automation poll';
+COMMENT ON COLUMN sharepoint_sync_state.delta_link IS 'Full @odata.deltaLink URL; deleting the row forces a full folder listing';
@@ -0,0 +1,15 @@
+SET search_path TO public;
+
+-- Graph-Subscriptions des SharePoint-Ingest: Change-Notifications auf das
+-- Laufwerk wecken den Automation-Poller, statt auf das nächste Intervall zu
+-- warten.
+CREATE TABLE IF NOT EXISTS sharepoint_subscriptions (
+    id TEXT PRIMARY KEY,
+    drive_id TEXT NOT NULL,
+    client_state TEXT NOT NULL,
+    expires_at TIMESTAMPTZ NOT NULL,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT now()
+);
+
+COMMENT ON TABLE sharepoint_subscriptions IS 'Microsoft Graph change notification subscriptions, renewed before expires_at';
+COMMENT ON COLUMN sharepoint_subscriptions.client_state IS 'Random secret Graph echoes in every notification; others are ignored';
@@ -0,0 +1,18 @@
+SET search_path TO public;
+
+-- Verkleinerte Seitenbilder aus dem OCR-Rendering (text-extraction,
+-- PAGE_IMAGE_CACHE=1), ausgeliefert über GET /pdf/{id}/pages/{page}/image.
+-- Seiten ohne gespeichertes Bild rendert pdf-ingest beim ersten Abruf nach.
+CREATE TABLE IF NOT EXISTS pdf_page_images (
+    merged_pdf_id INTEGER NOT NULL REFERENCES merged_pdfs(id) ON DELETE CASCADE,
+    page_no INTEGER NOT NULL,
+    width INTEGER NOT NULL,
+    height INTEGER NOT NULL,
+    png BYTEA NOT NULL,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT now(),
+    PRIMARY KEY (merged_pdf_id, page_no)
+);
+
+COMMENT ON TABLE pdf_page_images IS 'Downscaled PNG per page for previews and image-based steps';
+COMMENT ON COLUMN pdf_page_images.page_no IS '0-based like pdf_texts.page_no';
+COMMENT ON COLUMN pdf_page_images.png IS 'PNG, at most PAGE_IMAGE_MAX_PX on the longer side';
@@ -0,0 +1,26 @@
+SET search_path TO public;
+
+-- Automatische Wiederholung fehlgeschlagener Extraktionen (text-extraction):
+-- exponentielle Wartezeit, nach EXTRACTION_MAX_ATTEMPTS Versuchen 'poisoned'.
+-- GET /extractions/poisoned und POST /extractions/{pdf_id}/requeue (pdf-ingest).
+CREATE TABLE IF NOT EXISTS extraction_retries (
+    pdf_id INTEGER PRIMARY KEY REFERENCES merged_pdfs(id) ON DELETE CASCADE,
+    event JSONB NOT NULL,
+    first_page INTEGER NOT NULL DEFAULT 0,
+    status TEXT NOT NULL DEFAULT 'pending',
+    attempts INTEGER NOT NULL DEFAULT 0,
+    reason TEXT,
+    last_error TEXT,
+    next_attempt_at TIMESTAMPTZ NOT NULL DEFAULT now(),
+    created_at TIMESTAMPTZ NOT NULL DEFAULT now(),
+    updated_at TIMESTAMPTZ NOT NULL DEFAULT now()
+);
+
+CREATE INDEX IF NOT EXISTS idx_extraction_retries_due
+    ON extraction_retries (next_attempt_at) WHERE status = 'pending';
+
+COMMENT ON TABLE extraction_retries IS 'Failed text extractions waiting for a retry or poisoned';
+COMMENT ON COLUMN extraction_retries.event IS 'pdf-merged event published again for the retry';
+COMMENT ON COLUMN extraction_retries.first_page IS 'First page to extract, appended_from of the event or 0';
+COMMENT ON COLUMN extraction_retries.status IS 'pending or poisoned (after EXTRACTION_MAX_ATTEMPTS)';
+COMMENT ON COLUMN extraction_retries.reason IS 'Kind of the last failure: missing_binary, timeout, resource_limit, crashed or error';
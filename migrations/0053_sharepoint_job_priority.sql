@@ -0,0 +1,8 @@
+SET search_path TO public;
+
+-- Priorität der SharePoint-Jobs (low|normal|high): freie Slots gehen zuerst an
+-- Jobs hoher Priorität, bei gleicher Priorität reihum je Mandant.
+ALTER TABLE sharepoint_jobs
+    ADD COLUMN IF NOT EXISTS priority TEXT NOT NULL DEFAULT 'normal';
+
+COMMENT ON COLUMN sharepoint_jobs.priority IS 'Scheduling priority of the job (low, normal, high); forwarded to the upload';
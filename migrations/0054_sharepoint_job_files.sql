@@ -0,0 +1,19 @@
+SET search_path TO public;
+
+-- Status je Datei eines SharePoint-Jobs: list legt alle Dateien als pending an,
+-- download/filter setzen downloaded, failed oder skipped samt Fehlermeldung.
+-- Ohne Fremdschlüssel, da Jobs asynchron in sharepoint_jobs gespeichert werden.
+CREATE TABLE IF NOT EXISTS sharepoint_job_files (
+    job_id UUID NOT NULL,
+    position INTEGER NOT NULL,
+    file_id TEXT NOT NULL,
+    file_name TEXT NOT NULL,
+    status TEXT NOT NULL DEFAULT 'pending' CHECK (status IN ('pending','downloaded','failed','skipped')),
+    error TEXT,
+    updated_at TIMESTAMPTZ NOT NULL DEFAULT now(),
+    PRIMARY KEY (job_id, position)
+);
+
+COMMENT ON TABLE sharepoint_job_files IS 'Per-file state of SharePoint jobs in merge order';
+COMMENT ON COLUMN sharepoint_job_files.status IS 'pending, downloaded, failed (download error) or skipped (quarantined)';
+COMMENT ON COLUMN sharepoint_job_files.error IS 'Download error or quarantine reason';
@@ -0,0 +1,10 @@
+SET search_path TO public;
+
+-- Zeitplan je Automatisierungsregel (Cron-Ausdruck in UTC): Ordner mit
+-- schedule importiert der Schedule-Task von sharepoint-ingest zu jedem Termin
+-- erneut statt einmalig über den Automation-Poller; next_run_at ist der
+-- nächste Termin und wird von der Instanz weitergeschoben, die importiert.
+-- sharepoint_automation wird vom sharepoint-ingest beim Start angelegt
+ALTER TABLE IF EXISTS sharepoint_automation
+    ADD COLUMN IF NOT EXISTS schedule TEXT,
+    ADD COLUMN IF NOT EXISTS next_run_at TIMESTAMPTZ;
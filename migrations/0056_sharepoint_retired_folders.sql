@@ -0,0 +1,12 @@
+SET search_path TO public;
+
+-- Ordner, deren erfolgreiche Jobs über DELETE /jobs/{id} bzw. POST /jobs/purge
+-- gelöscht wurden: sie bleiben in /folders ausgeblendet und werden nicht erneut
+-- automatisch importiert, bis sie mit release_folders freigegeben werden.
+CREATE TABLE IF NOT EXISTS sharepoint_retired_folders (
+    folder_id TEXT PRIMARY KEY,
+    folder_name TEXT NOT NULL,
+    retired_at TIMESTAMPTZ NOT NULL DEFAULT now()
+);
+
+COMMENT ON TABLE sharepoint_retired_folders IS 'Folders hidden from /folders after their succeeded jobs were deleted';
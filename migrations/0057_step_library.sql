@@ -0,0 +1,25 @@
+SET search_path TO public;
+
+-- Step-Library des prompt-managers: benannte, vollständig konfigurierte
+-- Pipeline-Schritte (Prompt, Typ, Routing, Config mit Schwellen bzw. Schema).
+-- Jede Änderung legt eine neue Version an; Pipelines verweisen mit
+-- "library": {"id": …} auf die jeweils neueste oder mit "version" auf eine
+-- feste Version und halten eine Kopie des Schritts in config_json.
+CREATE TABLE IF NOT EXISTS step_library (
+    id SERIAL PRIMARY KEY,
+    name TEXT NOT NULL UNIQUE,
+    description TEXT,
+    version INTEGER NOT NULL DEFAULT 1
+);
+
+CREATE TABLE IF NOT EXISTS step_library_versions (
+    item_id INTEGER NOT NULL REFERENCES step_library(id) ON DELETE CASCADE,
+    version INTEGER NOT NULL,
+    step JSONB NOT NULL,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT now(),
+    PRIMARY KEY (item_id, version)
+);
+
+COMMENT ON TABLE step_library IS 'Reusable configured pipeline steps, managed by prompt-manager';
+COMMENT ON COLUMN step_library.version IS 'Latest version in step_library_versions';
+COMMENT ON TABLE step_library_versions IS 'Configured step (type, promptId, route, yesKey, noKey, config) per library version';
@@ -0,0 +1,21 @@
+SET search_path TO public;
+
+-- Bereits importierte Dateien je SharePoint-Ordner (Datei-ID und eTag), nach
+-- jedem erfolgreichen Upload fortgeschrieben. Regeln mit reingest
+-- ('new_files' oder 'all_files') legen für Ordner mit neuen oder geänderten
+-- PDFs einen weiteren Job an.
+CREATE TABLE IF NOT EXISTS sharepoint_folder_inventory (
+    folder_id TEXT NOT NULL,
+    file_id TEXT NOT NULL,
+    file_name TEXT NOT NULL,
+    etag TEXT,
+    job_id UUID NOT NULL,
+    ingested_at TIMESTAMPTZ NOT NULL DEFAULT now(),
+    PRIMARY KEY (folder_id, file_id)
+);
+
+COMMENT ON TABLE sharepoint_folder_inventory IS 'Files of a SharePoint folder merged by its succeeded jobs';
+
+-- sharepoint_automation wird vom sharepoint-ingest beim Start angelegt
+ALTER TABLE IF EXISTS sharepoint_automation
+    ADD COLUMN IF NOT EXISTS reingest TEXT;
@@ -0,0 +1,18 @@
+SET search_path TO public;
+
+-- Konfiguration je Pipeline-Lauf: Kopie von config_json, die Texte der
+-- referenzierten Prompts und die Modell-Einstellungen beim Start des Laufs.
+-- Der pipeline-runner schreibt sie, GET /runs/{id}/config der pipeline-api
+-- liest sie. pipeline_updated_at ist der Versionsstand der Pipeline.
+CREATE TABLE IF NOT EXISTS pipeline_run_configs (
+    run_id UUID PRIMARY KEY REFERENCES pipeline_runs(id) ON DELETE CASCADE,
+    pipeline_id UUID NOT NULL,
+    pipeline_name TEXT,
+    pipeline_updated_at TIMESTAMPTZ,
+    config JSONB NOT NULL,
+    prompts JSONB NOT NULL,
+    model JSONB NOT NULL,
+    captured_at TIMESTAMPTZ NOT NULL DEFAULT now()
+);
+
+COMMENT ON TABLE pipeline_run_configs IS 'Pipeline config, prompt texts and model settings a run executed with';
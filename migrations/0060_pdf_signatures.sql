@@ -0,0 +1,18 @@
+SET search_path TO public;
+
+-- MinHash-Signatur je Dokument (text-extraction) für die Dublettenerkennung
+-- über GET /pdf/{id}/similar (pdf-ingest).
+CREATE TABLE IF NOT EXISTS pdf_signatures (
+    merged_pdf_id INTEGER PRIMARY KEY REFERENCES merged_pdfs(id) ON DELETE CASCADE,
+    minhash BIGINT[] NOT NULL,
+    bands BIGINT[] NOT NULL,
+    shingle_count INTEGER NOT NULL,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT now()
+);
+
+CREATE INDEX IF NOT EXISTS idx_pdf_signatures_bands ON pdf_signatures USING GIN (bands);
+
+COMMENT ON TABLE pdf_signatures IS 'Near-duplicate signature of the full text per document, GET /pdf/{id}/similar';
+COMMENT ON COLUMN pdf_signatures.minhash IS '64 MinHash values over the word 5-shingles (shared::similarity)';
+COMMENT ON COLUMN pdf_signatures.bands IS '16 LSH band hashes of minhash; documents sharing one are compared';
+COMMENT ON COLUMN pdf_signatures.shingle_count IS 'Distinct shingles of the text';
@@ -0,0 +1,16 @@
+SET search_path TO public;
+
+-- Schlüsselwortregeln der Vorklassifizierung (pdf-ingest, AUTO_CLASSIFY):
+-- Uploads ohne Pipeline bekommen die Pipeline mit dem klarsten Treffer oder
+-- landen in der Warteschlange "Pipeline zuweisen" (run_state = 'unassigned').
+CREATE TABLE IF NOT EXISTS pipeline_routing_rules (
+    pipeline_id UUID PRIMARY KEY REFERENCES pipelines(id) ON DELETE CASCADE,
+    keywords TEXT[] NOT NULL,
+    updated_at TIMESTAMPTZ NOT NULL DEFAULT now()
+);
+
+ALTER TABLE uploads ADD COLUMN IF NOT EXISTS classification JSONB;
+
+COMMENT ON TABLE pipeline_routing_rules IS 'Keywords that route uploads without pipeline, /admin/classification-rules';
+COMMENT ON COLUMN pipeline_routing_rules.keywords IS 'Lowercase words or phrases, matched on whole words of the full text';
+COMMENT ON COLUMN uploads.classification IS 'Scores of the pre-router: chosen pipeline_id, confidence, candidates';
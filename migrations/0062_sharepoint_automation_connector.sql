@@ -0,0 +1,8 @@
+SET search_path TO public;
+
+-- Laufwerk eines automatisierten Ordners: 'sharepoint' (Site-Laufwerk),
+-- 'onedrive' (OneDrive von ONEDRIVE_USER) oder 'local' (Unterverzeichnis von
+-- LOCAL_SOURCE_DIR, z. B. eine eingebundene Netzwerkfreigabe).
+-- sharepoint_automation wird vom sharepoint-ingest beim Start angelegt
+ALTER TABLE IF EXISTS sharepoint_automation
+    ADD COLUMN IF NOT EXISTS connector TEXT NOT NULL DEFAULT 'sharepoint';
@@ -0,0 +1,26 @@
+SET search_path TO public;
+
+-- S3-/MinIO-Quellen je Mandant: Bucket + Präfix, Unterpräfixe sind Ordner
+-- des Connectors 's3' (sharepoint_automation.connector). Der Secret Access
+-- Key liegt als Envelope (CREDENTIALS_MASTER_KEY) in credentials; ohne
+-- access_key_id gelten S3_SOURCE_ACCESS_KEY_ID/S3_SOURCE_SECRET_ACCESS_KEY.
+-- Wird vom sharepoint-ingest beim Start ebenfalls angelegt
+CREATE TABLE IF NOT EXISTS s3_sources (
+    id UUID PRIMARY KEY,
+    name TEXT NOT NULL,
+    tenant_id UUID,
+    endpoint TEXT,
+    region TEXT,
+    bucket TEXT NOT NULL,
+    prefix TEXT NOT NULL DEFAULT '',
+    path_style BOOLEAN NOT NULL DEFAULT FALSE,
+    access_key_id TEXT,
+    credentials JSONB NOT NULL DEFAULT '{}'::jsonb,
+    enabled BOOLEAN NOT NULL DEFAULT TRUE,
+    last_polled_at TIMESTAMPTZ,
+    last_error TEXT,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT now(),
+    updated_at TIMESTAMPTZ NOT NULL DEFAULT now()
+);
+
+COMMENT ON TABLE s3_sources IS 'S3/MinIO buckets ingested through the s3 connector, /s3/sources';
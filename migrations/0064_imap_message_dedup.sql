@@ -0,0 +1,8 @@
+SET search_path TO public;
+
+-- Dubletten-Erkennung der IMAP-Postfächer über die Message-ID: eine Mail, die
+-- nach Verschieben, Kopieren oder neuer UIDVALIDITY erneut auftaucht, wird
+-- nicht ein zweites Mal importiert.
+-- Wird vom sharepoint-ingest beim Start ebenfalls angelegt
+CREATE INDEX IF NOT EXISTS idx_imap_messages_message_id
+    ON imap_messages (mailbox_id, message_id) WHERE message_id IS NOT NULL;
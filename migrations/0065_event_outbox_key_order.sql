@@ -0,0 +1,8 @@
+SET search_path TO public;
+
+-- Outbox-Events werden je Kafka-Key (pdf_id, siehe shared::kafka) in
+-- Reihenfolge veröffentlicht: ein Event wartet, solange ein älteres desselben
+-- Keys noch aussteht. Der Index stützt diese Prüfung.
+-- Wird vom pipeline-runner beim Start ebenfalls angelegt
+CREATE INDEX IF NOT EXISTS idx_event_outbox_pending_key
+    ON event_outbox (topic, key, id) WHERE published_at IS NULL;
@@ -0,0 +1,12 @@
+SET search_path TO public;
+
+-- Post-Aktionen der Automatisierungsregeln: Ordner nach abgeschlossenem Run
+-- verschieben, umbenennen oder das Ergebnis als JSON in den Ordner schreiben.
+-- post_action_state hält je Job fest, dass und mit welchem Ergebnis die
+-- Aktionen liefen, damit sie nur einmal ausgeführt werden.
+-- sharepoint_automation und sharepoint_jobs werden vom sharepoint-ingest beim
+-- Start angelegt
+ALTER TABLE IF EXISTS sharepoint_automation
+    ADD COLUMN IF NOT EXISTS post_actions JSONB NOT NULL DEFAULT '[]'::jsonb;
+ALTER TABLE IF EXISTS sharepoint_jobs
+    ADD COLUMN IF NOT EXISTS post_action_state JSONB;
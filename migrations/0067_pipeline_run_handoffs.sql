@@ -0,0 +1,21 @@
+SET search_path TO public;
+
+-- Übergabe laufender Runs beim Herunterfahren eines pipeline-runner-Replikats
+-- (Rolling Deploy): state hält Pipeline-Config, Batch-Konfiguration und den
+-- Checkpoint (nächster Step, fertige Batches). Das Replikat, das das
+-- erneut veröffentlichte Run-Event erhält, setzt claimed_at und macht dort
+-- weiter; nach Abschluss des Runs wird die Zeile gelöscht.
+-- Wird vom pipeline-runner beim Start ebenfalls angelegt
+CREATE TABLE IF NOT EXISTS pipeline_run_handoffs (
+    run_id UUID PRIMARY KEY REFERENCES pipeline_runs(id) ON DELETE CASCADE,
+    state JSONB NOT NULL,
+    handed_off_by TEXT,
+    handed_off_at TIMESTAMPTZ NOT NULL DEFAULT now(),
+    claimed_by TEXT,
+    claimed_at TIMESTAMPTZ
+);
+
+COMMENT ON TABLE pipeline_run_handoffs IS 'Runs interrupted by a runner shutdown, continued by another replica';
+COMMENT ON COLUMN pipeline_run_handoffs.state IS 'config_json, batch_cfg and checkpoint (next step, finished batches) of the run';
+COMMENT ON COLUMN pipeline_run_handoffs.handed_off_by IS 'HOSTNAME of the replica that stopped the run';
+COMMENT ON COLUMN pipeline_run_handoffs.claimed_by IS 'HOSTNAME of the replica that continues the run';
@@ -1,11 +1,13 @@
 //! Reverse proxy that routes frontend requests to the appropriate backend service.
 
-use actix_cors::Cors;
+use actix_web::http::header;
 use actix_web::web::Payload;
 use actix_web::{guard, web, App, HttpRequest, HttpResponse, HttpServer};
 use awc::Client;
-use futures_util::future;
-use tracing::info;
+use futures_util::{future, FutureExt};
+use serde_json::{json, Value};
+use shared::cors::CorsSettings;
+use tracing::{info, warn};
 
 /// Builds a fully-qualified URL by appending the request query string when
 /// present.
@@ -47,6 +49,61 @@ async fn health() -> HttpResponse {
     }
 }
 
+/// `/admin/schema` of every DB-owning service, combined by [`schema`].
+const SCHEMA_SOURCES: [(&str, &str); 4] = [
+    ("pdf-ingest", "http://pdf-ingest:8081/admin/schema"),
+    ("pipeline-api", "http://pipeline-api:8084/admin/schema"),
+    (
+        "history-service",
+        "http://history-service:8090/admin/schema",
+    ),
+    (
+        "sharepoint-ingest",
+        "http://sharepoint-ingest:8080/admin/schema",
+    ),
+];
+
+/// Collects the schema descriptions of all services into one document; services
+/// that cannot be reached are listed under `errors` instead of failing the call.
+async fn schema(req: HttpRequest) -> HttpResponse {
+    let client = Client::default();
+    let auth = req.headers().get(header::AUTHORIZATION).cloned();
+    let requests = SCHEMA_SOURCES.iter().map(|(service, url)| {
+        let mut request = client.get(*url);
+        if let Some(auth) = &auth {
+            request = request.insert_header((header::AUTHORIZATION, auth.clone()));
+        }
+        async move {
+            let mut res = request.send().await.map_err(|e| e.to_string())?;
+            if !res.status().is_success() {
+                return Err(format!("status {}", res.status()));
+            }
+            res.json::<Value>()
+                .limit(4 * 1024 * 1024)
+                .await
+                .map_err(|e| e.to_string())
+        }
+        .map(move |result| (*service, result))
+    });
+
+    let mut services = Vec::new();
+    let mut errors = Vec::new();
+    for (service, result) in future::join_all(requests).await {
+        match result {
+            Ok(schema) => services.push(schema),
+            Err(error) => {
+                warn!(service, %error, "schema introspection failed");
+                errors.push(json!({ "service": service, "error": error }));
+            }
+        }
+    }
+    // Ohne einen einzigen Erfolg ist es vermutlich ein Auth-/Netzproblem
+    if services.is_empty() {
+        return HttpResponse::BadGateway().json(json!({ "services": [], "errors": errors }));
+    }
+    HttpResponse::Ok().json(json!({ "services": services, "errors": errors }))
+}
+
 /// Proxies the incoming request and body to the provided URL while preserving
 /// headers from the caller.
 async fn proxy(req: HttpRequest, body: Payload, url: &str) -> HttpResponse {
@@ -94,6 +151,27 @@ async fn pdf_get_or_delete(req: HttpRequest, body: Payload) -> HttpResponse {
     proxy(req, body, url.as_str()).await
 }
 
+/// Forwards legal hold placement, lookup and removal to pdf-ingest.
+async fn pdf_legal_hold(req: HttpRequest, body: Payload) -> HttpResponse {
+    let id = req.match_info().query("id");
+    let url = with_qs(&format!("http://pdf-ingest:8081/pdf/{id}/legal-hold"), &req);
+    proxy(req, body, url.as_str()).await
+}
+
+/// Forwards page appends for an existing PDF to pdf-ingest.
+async fn pdf_append(req: HttpRequest, body: Payload) -> HttpResponse {
+    let id = req.match_info().query("id");
+    let url = with_qs(&format!("http://pdf-ingest:8081/pdf/{id}/append"), &req);
+    proxy(req, body, url.as_str()).await
+}
+
+/// Forwards re-OCR requests of low-confidence pages to pdf-ingest.
+async fn pdf_reocr(req: HttpRequest, body: Payload) -> HttpResponse {
+    let id = req.match_info().query("id");
+    let url = with_qs(&format!("http://pdf-ingest:8081/pdf/{id}/reocr"), &req);
+    proxy(req, body, url.as_str()).await
+}
+
 /// Routes list requests to the text-extraction service.
 async fn te_texts(req: HttpRequest, body: Payload) -> HttpResponse {
     let url = with_qs("http://text-extraction:8083/texts", &req);
@@ -159,11 +237,13 @@ async fn ingest(req: HttpRequest, body: Payload) -> HttpResponse {
 async fn main() -> std::io::Result<()> {
     tracing_subscriber::fmt::init();
     info!("starting api-gateway");
-    HttpServer::new(|| {
+    let cors = CorsSettings::from_env();
+    HttpServer::new(move || {
         App::new()
-            .wrap(Cors::permissive())
+            .wrap(cors.actix())
             // health
             .route("/health", web::get().to(health))
+            .route("/admin/schema", web::get().to(schema))
             // pdf-ingest
             .route("/upload", web::post().to(upload))
             .route("/uploads", web::get().to(uploads))
@@ -173,6 +253,14 @@ async fn main() -> std::io::Result<()> {
                     .route(web::get().to(pdf_get_or_delete))
                     .route(web::delete().to(pdf_get_or_delete)),
             )
+            .route("/pdf/{id}/append", web::post().to(pdf_append))
+            .route("/pdf/{id}/reocr", web::post().to(pdf_reocr))
+            .service(
+                web::resource("/pdf/{id}/legal-hold")
+                    .route(web::get().to(pdf_legal_hold))
+                    .route(web::put().to(pdf_legal_hold))
+                    .route(web::delete().to(pdf_legal_hold)),
+            )
             // text-extraction
             .route("/te/texts", web::get().to(te_texts))
             .route("/te/analyze", web::post().to(te_analyze))
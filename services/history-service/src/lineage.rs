@@ -0,0 +1,609 @@
+//! Provenance graph of a document (`GET /lineage?pdf_id=`).
+//!
+//! Joins the tables of all services into one graph: source folder, SFTP file or
+//! mail → SharePoint job → upload → merged PDF (with earlier versions) →
+//! extracted text → pipeline runs → downloaded exports. Sources whose tables do
+//! not exist in this deployment (e.g. no sharepoint-ingest) are skipped and
+//! reported under `warnings`.
+
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use serde_json::{json, Value};
+use std::collections::HashSet;
+use uuid::Uuid;
+
+use crate::Db;
+
+#[derive(Debug, Serialize)]
+pub struct LineageNode {
+    /// `<kind>:<key>`, e.g. `pdf:12` or `run:<uuid>`.
+    pub id: String,
+    pub kind: &'static str,
+    pub label: String,
+    pub data: Value,
+}
+
+#[derive(Debug, PartialEq, Serialize)]
+pub struct LineageEdge {
+    pub from: String,
+    pub to: String,
+    pub relation: &'static str,
+}
+
+#[derive(Debug, Serialize)]
+pub struct Lineage {
+    pub pdf_id: i32,
+    pub nodes: Vec<LineageNode>,
+    pub edges: Vec<LineageEdge>,
+    pub warnings: Vec<String>,
+}
+
+struct PdfRow {
+    sha256: String,
+    size_bytes: i32,
+    version: i32,
+}
+
+struct VersionRow {
+    version: i32,
+    sha256: String,
+    page_count: i32,
+    created_at: DateTime<Utc>,
+}
+
+struct ExtractionRow {
+    pages: i64,
+    ocr_pages: i64,
+    chars: i64,
+    extracted_at: Option<DateTime<Utc>>,
+}
+
+struct UploadRow {
+    id: i32,
+    pipeline_id: Option<Uuid>,
+    status: String,
+    job_label: Option<String>,
+    external_ref: Option<String>,
+}
+
+struct JobRow {
+    id: Uuid,
+    folder_id: String,
+    folder_name: String,
+    status: String,
+    upload_id: Option<i32>,
+    run_id: Option<Uuid>,
+}
+
+/// SFTP file or mail that produced a job.
+struct JobSourceRow {
+    job_id: Uuid,
+    kind: &'static str,
+    key: String,
+    label: String,
+}
+
+struct RunRow {
+    id: Uuid,
+    pipeline_id: Uuid,
+    status: String,
+    overall_score: Option<f64>,
+    rerun_of: Option<Uuid>,
+    started_at: Option<DateTime<Utc>>,
+    finished_at: Option<DateTime<Utc>>,
+}
+
+struct ExportRow {
+    run_id: Uuid,
+    format: Option<String>,
+    created_at: DateTime<Utc>,
+}
+
+#[derive(Default)]
+struct LineageInput {
+    source_files: Vec<String>,
+    versions: Vec<VersionRow>,
+    extraction: Option<ExtractionRow>,
+    uploads: Vec<UploadRow>,
+    jobs: Vec<JobRow>,
+    job_sources: Vec<JobSourceRow>,
+    runs: Vec<RunRow>,
+    exports: Vec<ExportRow>,
+}
+
+/// Loads the lineage of `pdf_id`; `None` if the PDF does not exist.
+pub async fn load(db: &Db, pdf_id: i32) -> Result<Option<Lineage>, tokio_postgres::Error> {
+    let Some(row) = db
+        .query_opt(
+            "SELECT sha256, size_bytes, COALESCE(version, 1) FROM merged_pdfs WHERE id = $1",
+            &[&pdf_id],
+        )
+        .await?
+    else {
+        return Ok(None);
+    };
+    let pdf = PdfRow {
+        sha256: row.get(0),
+        size_bytes: row.get(1),
+        version: row.get(2),
+    };
+
+    let mut warnings = Vec::new();
+    let mut input = LineageInput::default();
+
+    // Quelle, Uploads und Runs gehören zum Kern; fehlende Tabellen einzelner Dienste nur melden
+    if let Some(row) = optional(
+        db.query_opt(
+            "SELECT names FROM pdf_sources WHERE pdf_id = $1",
+            &[&pdf_id],
+        )
+        .await,
+        "pdf_sources",
+        &mut warnings,
+    )
+    .flatten()
+    {
+        input.source_files = row
+            .get::<_, Option<String>>(0)
+            .and_then(|raw| serde_json::from_str(&raw).ok())
+            .unwrap_or_default();
+    }
+
+    input.versions = optional(
+        db.query(
+            "SELECT version, sha256, page_count, created_at FROM pdf_versions \
+             WHERE pdf_id = $1 ORDER BY version",
+            &[&pdf_id],
+        )
+        .await,
+        "pdf_versions",
+        &mut warnings,
+    )
+    .unwrap_or_default()
+    .into_iter()
+    .map(|r| VersionRow {
+        version: r.get(0),
+        sha256: r.get(1),
+        page_count: r.get(2),
+        created_at: r.get(3),
+    })
+    .collect();
+
+    input.extraction = optional(
+        db.query_opt(
+            "SELECT COUNT(*), COUNT(*) FILTER (WHERE ocr_used), COALESCE(SUM(char_count), 0)::bigint, \
+                    (SELECT MAX(created_at) FROM run_timeline \
+                      WHERE pdf_id = $1 AND status = 'text_extracted') \
+               FROM pdf_texts WHERE merged_pdf_id = $1",
+            &[&pdf_id],
+        )
+        .await,
+        "pdf_texts",
+        &mut warnings,
+    )
+    .flatten()
+    .map(|r| ExtractionRow {
+        pages: r.get(0),
+        ocr_pages: r.get(1),
+        chars: r.get(2),
+        extracted_at: r.get(3),
+    })
+    .filter(|e| e.pages > 0);
+
+    input.uploads = db
+        .query(
+            "SELECT id, pipeline_id, status, job_label, external_ref FROM uploads \
+             WHERE pdf_id = $1 ORDER BY id",
+            &[&pdf_id],
+        )
+        .await?
+        .into_iter()
+        .map(|r| UploadRow {
+            id: r.get(0),
+            pipeline_id: r.get(1),
+            status: r.get(2),
+            job_label: r.get(3),
+            external_ref: r.get(4),
+        })
+        .collect();
+
+    let upload_ids: Vec<i32> = input.uploads.iter().map(|u| u.id).collect();
+    input.jobs = optional(
+        db.query(
+            "SELECT id, folder_id, folder_name, status, upload_id, pipeline_run_id \
+               FROM sharepoint_jobs \
+              WHERE pdf_id = $1 OR upload_id = ANY($2) ORDER BY created_at",
+            &[&pdf_id, &upload_ids],
+        )
+        .await,
+        "sharepoint_jobs",
+        &mut warnings,
+    )
+    .unwrap_or_default()
+    .into_iter()
+    .map(|r| JobRow {
+        id: r.get(0),
+        folder_id: r.get(1),
+        folder_name: r.get(2),
+        status: r.get(3),
+        upload_id: r.get(4),
+        run_id: r.get(5),
+    })
+    .collect();
+
+    if !input.jobs.is_empty() {
+        let job_ids: Vec<Uuid> = input.jobs.iter().map(|j| j.id).collect();
+        let sftp = optional(
+            db.query(
+                "SELECT f.job_id, s.id::text || ':' || f.path, s.name || ': ' || f.path \
+                   FROM sftp_seen_files f JOIN sftp_sources s ON s.id = f.source_id \
+                  WHERE f.job_id = ANY($1)",
+                &[&job_ids],
+            )
+            .await,
+            "sftp_seen_files",
+            &mut warnings,
+        )
+        .unwrap_or_default();
+        input
+            .job_sources
+            .extend(sftp.into_iter().map(|r| JobSourceRow {
+                job_id: r.get(0),
+                kind: "sftp_file",
+                key: r.get(1),
+                label: r.get(2),
+            }));
+        let mails = optional(
+            db.query(
+                "SELECT m.job_id, m.mailbox_id::text || ':' || m.uid_validity || ':' || m.uid, \
+                        b.name || ': ' || COALESCE(m.subject, '(no subject)') \
+                   FROM imap_messages m JOIN imap_mailboxes b ON b.id = m.mailbox_id \
+                  WHERE m.job_id = ANY($1)",
+                &[&job_ids],
+            )
+            .await,
+            "imap_messages",
+            &mut warnings,
+        )
+        .unwrap_or_default();
+        input
+            .job_sources
+            .extend(mails.into_iter().map(|r| JobSourceRow {
+                job_id: r.get(0),
+                kind: "mail",
+                key: r.get(1),
+                label: r.get(2),
+            }));
+    }
+
+    input.runs = db
+        .query(
+            "SELECT id, pipeline_id, status, overall_score::float8, rerun_of, started_at, finished_at \
+               FROM pipeline_runs WHERE pdf_id = $1 ORDER BY started_at NULLS LAST, id",
+            &[&pdf_id],
+        )
+        .await?
+        .into_iter()
+        .map(|r| RunRow {
+            id: r.get(0),
+            pipeline_id: r.get(1),
+            status: r.get(2),
+            overall_score: r.get(3),
+            rerun_of: r.get(4),
+            started_at: r.get(5),
+            finished_at: r.get(6),
+        })
+        .collect();
+
+    input.exports = db
+        .query(
+            "SELECT run_id, details->>'format', created_at FROM run_timeline \
+              WHERE pdf_id = $1 AND status = 'exported' AND run_id IS NOT NULL \
+              ORDER BY created_at, id",
+            &[&pdf_id],
+        )
+        .await?
+        .into_iter()
+        .map(|r| ExportRow {
+            run_id: r.get(0),
+            format: r.get(1),
+            created_at: r.get(2),
+        })
+        .collect();
+
+    let mut lineage = assemble(pdf_id, &pdf, input);
+    lineage.warnings = warnings;
+    Ok(Some(lineage))
+}
+
+/// Turns a failed query on an optional table into a warning.
+fn optional<T>(
+    result: Result<T, tokio_postgres::Error>,
+    table: &str,
+    warnings: &mut Vec<String>,
+) -> Option<T> {
+    match result {
+        Ok(value) => Some(value),
+        Err(e) => {
+            warnings.push(format!("{table} unavailable: {e}"));
+            None
+        }
+    }
+}
+
+#[derive(Default)]
+struct Graph {
+    nodes: Vec<LineageNode>,
+    edges: Vec<LineageEdge>,
+    seen: HashSet<String>,
+}
+
+impl Graph {
+    fn node(&mut self, id: String, kind: &'static str, label: String, data: Value) -> String {
+        if self.seen.insert(id.clone()) {
+            self.nodes.push(LineageNode {
+                id: id.clone(),
+                kind,
+                label,
+                data,
+            });
+        }
+        id
+    }
+
+    fn edge(&mut self, from: &str, to: &str, relation: &'static str) {
+        let edge = LineageEdge {
+            from: from.to_string(),
+            to: to.to_string(),
+            relation,
+        };
+        if !self.edges.contains(&edge) {
+            self.edges.push(edge);
+        }
+    }
+}
+
+fn assemble(pdf_id: i32, pdf: &PdfRow, input: LineageInput) -> Lineage {
+    let mut g = Graph::default();
+
+    let pdf_node = g.node(
+        format!("pdf:{pdf_id}"),
+        "pdf",
+        format!("PDF {pdf_id} (v{})", pdf.version),
+        json!({ "sha256": pdf.sha256, "size_bytes": pdf.size_bytes, "version": pdf.version }),
+    );
+
+    for name in &input.source_files {
+        let file = g.node(
+            format!("file:{name}"),
+            "source_file",
+            name.clone(),
+            Value::Null,
+        );
+        g.edge(&file, &pdf_node, "merged_into");
+    }
+
+    for v in &input.versions {
+        let version = g.node(
+            format!("pdf_version:{pdf_id}:{}", v.version),
+            "pdf_version",
+            format!("PDF {pdf_id} v{}", v.version),
+            json!({ "sha256": v.sha256, "page_count": v.page_count, "replaced_at": v.created_at }),
+        );
+        g.edge(&version, &pdf_node, "superseded_by");
+    }
+
+    let extraction = input.extraction.as_ref().map(|e| {
+        let node = g.node(
+            format!("extraction:{pdf_id}:{}", pdf.version),
+            "extraction",
+            format!("Text v{} ({} pages)", pdf.version, e.pages),
+            json!({
+                "version": pdf.version,
+                "pages": e.pages,
+                "ocr_pages": e.ocr_pages,
+                "chars": e.chars,
+                "extracted_at": e.extracted_at,
+            }),
+        );
+        g.edge(&pdf_node, &node, "extracted_to");
+        node
+    });
+
+    for u in &input.uploads {
+        let upload = g.node(
+            format!("upload:{}", u.id),
+            "upload",
+            format!("Upload {}", u.id),
+            json!({
+                "pipeline_id": u.pipeline_id,
+                "status": u.status,
+                "job_label": u.job_label,
+                "external_ref": u.external_ref,
+            }),
+        );
+        g.edge(&upload, &pdf_node, "merged_into");
+    }
+
+    for j in &input.jobs {
+        let job = g.node(
+            format!("job:{}", j.id),
+            "job",
+            format!("Job {}", j.folder_name),
+            json!({ "status": j.status, "upload_id": j.upload_id, "pipeline_run_id": j.run_id }),
+        );
+        let folder = g.node(
+            format!("folder:{}", j.folder_id),
+            "folder",
+            j.folder_name.clone(),
+            json!({ "folder_id": j.folder_id }),
+        );
+        g.edge(&folder, &job, "contains");
+        match j.upload_id {
+            Some(upload_id) if input.uploads.iter().any(|u| u.id == upload_id) => {
+                g.edge(&job, &format!("upload:{upload_id}"), "uploaded_as");
+            }
+            _ => g.edge(&job, &pdf_node, "merged_into"),
+        }
+        if let Some(run_id) = j.run_id {
+            g.edge(&job, &format!("run:{run_id}"), "triggered");
+        }
+    }
+
+    for s in &input.job_sources {
+        let source = g.node(
+            format!("{}:{}", s.kind, s.key),
+            s.kind,
+            s.label.clone(),
+            Value::Null,
+        );
+        g.edge(&source, &format!("job:{}", s.job_id), "collected_by");
+    }
+
+    for r in &input.runs {
+        let run = g.node(
+            format!("run:{}", r.id),
+            "run",
+            format!("Run {}", r.status),
+            json!({
+                "pipeline_id": r.pipeline_id,
+                "status": r.status,
+                "overall_score": r.overall_score,
+                "rerun_of": r.rerun_of,
+                "started_at": r.started_at,
+                "finished_at": r.finished_at,
+            }),
+        );
+        if let Some(extraction) = &extraction {
+            g.edge(extraction, &run, "input_of");
+        }
+        // Uploads derselben Pipeline haben den Run ausgelöst
+        for u in input
+            .uploads
+            .iter()
+            .filter(|u| u.pipeline_id == Some(r.pipeline_id))
+        {
+            g.edge(&format!("upload:{}", u.id), &run, "triggered");
+        }
+        if let Some(source_run) = r.rerun_of {
+            g.edge(&run, &format!("run:{source_run}"), "rerun_of");
+        }
+    }
+
+    for (n, e) in input.exports.iter().enumerate() {
+        let format = e.format.as_deref().unwrap_or("export");
+        let export = g.node(
+            format!("export:{}:{n}", e.run_id),
+            "export",
+            format!("{format} download"),
+            json!({ "format": format, "downloaded_at": e.created_at }),
+        );
+        g.edge(&format!("run:{}", e.run_id), &export, "exported_as");
+    }
+
+    // Kanten zu Knoten, die nicht geladen wurden (z. B. gelöschter Quell-Run), weglassen
+    let Graph {
+        nodes,
+        mut edges,
+        seen,
+    } = g;
+    edges.retain(|e| seen.contains(&e.from) && seen.contains(&e.to));
+    Lineage {
+        pdf_id,
+        nodes,
+        edges,
+        warnings: Vec::new(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn links_sources_through_runs_to_exports() {
+        let job_id = Uuid::new_v4();
+        let pipeline_id = Uuid::new_v4();
+        let first_run = Uuid::new_v4();
+        let rerun = Uuid::new_v4();
+        let run = |id, rerun_of| RunRow {
+            id,
+            pipeline_id,
+            status: "completed".into(),
+            overall_score: Some(0.8),
+            rerun_of,
+            started_at: None,
+            finished_at: None,
+        };
+        let input = LineageInput {
+            source_files: vec!["a.pdf".into()],
+            extraction: Some(ExtractionRow {
+                pages: 3,
+                ocr_pages: 1,
+                chars: 1200,
+                extracted_at: None,
+            }),
+            uploads: vec![UploadRow {
+                id: 5,
+                pipeline_id: Some(pipeline_id),
+                status: "ready".into(),
+                job_label: None,
+                external_ref: Some("AZ-42".into()),
+            }],
+            jobs: vec![JobRow {
+                id: job_id,
+                folder_id: "F1".into(),
+                folder_name: "Schaden 42".into(),
+                status: "succeeded".into(),
+                upload_id: Some(5),
+                run_id: Some(Uuid::new_v4()),
+            }],
+            runs: vec![run(first_run, None), run(rerun, Some(first_run))],
+            exports: vec![ExportRow {
+                run_id: rerun,
+                format: Some("bundle".into()),
+                created_at: Utc::now(),
+            }],
+            ..Default::default()
+        };
+        let pdf = PdfRow {
+            sha256: "abc".into(),
+            size_bytes: 10,
+            version: 2,
+        };
+        let lineage = assemble(7, &pdf, input);
+
+        let has = |from: &str, to: &str, relation: &str| {
+            lineage
+                .edges
+                .iter()
+                .any(|e| e.from == from && e.to == to && e.relation == relation)
+        };
+        let job = format!("job:{job_id}");
+        assert!(has("folder:F1", &job, "contains"));
+        assert!(has(&job, "upload:5", "uploaded_as"));
+        assert!(has("upload:5", "pdf:7", "merged_into"));
+        assert!(has("file:a.pdf", "pdf:7", "merged_into"));
+        assert!(has("pdf:7", "extraction:7:2", "extracted_to"));
+        assert!(has(
+            "extraction:7:2",
+            &format!("run:{first_run}"),
+            "input_of"
+        ));
+        assert!(has(
+            &format!("run:{rerun}"),
+            &format!("run:{first_run}"),
+            "rerun_of"
+        ));
+        assert!(has(
+            &format!("run:{rerun}"),
+            &format!("export:{rerun}:0"),
+            "exported_as"
+        ));
+        // Run aus dem Job gehört nicht zu diesem PDF → keine hängende Kante
+        assert!(!lineage
+            .edges
+            .iter()
+            .any(|e| e.from == job && e.relation == "triggered"));
+    }
+}
@@ -1,7 +1,6 @@
 //! WebSocket service that streams pipeline run updates to connected clients.
 
 use actix::prelude::*;
-use actix_cors::Cors;
 use actix_web::web::Payload;
 use actix_web::{web, App, Error, HttpRequest, HttpResponse, HttpServer, Responder};
 use actix_web_actors::ws;
@@ -13,15 +12,27 @@ use rdkafka::{
 use serde::{Deserialize, Serialize};
 use serde_json::json;
 use shared::config::Settings;
-use shared::dto::PipelineRunResult;
+use shared::cors::CorsSettings;
+use shared::dto::{PipelineRunResult, RunStatus};
+use shared::envelope::MasterKey;
+use shared::operator_notes;
+use shared::result_label::{LabelRules, ResultLabel};
+use shared::schema_doc;
+use shared::startup::{retry_with_backoff, Backoff, Readiness};
+use shared::telemetry::{EventKind, Telemetry};
 use std::collections::HashMap;
 use std::sync::Arc;
+use std::time::{Duration, Instant};
 use tokio::sync::RwLock;
 use tokio_postgres::{types::ToSql, Client, NoTls, Row};
 use tokio_stream::wrappers::{errors::BroadcastStreamRecvError, BroadcastStream};
 use tracing::{error, info, warn};
 use uuid::Uuid;
 
+mod lineage;
+mod sinks;
+mod stream;
+
 /* ============================================================================================
 DB-Manager: NoTLS, Auto-Reconnect bei "connection closed" + Heartbeat (SELECT 1)
 ============================================================================================ */
@@ -196,6 +207,50 @@ struct HistoryEntry {
     result_label: Option<String>,
     // NEU: optionaler Tenant-Name (nur gesetzt, wenn aus View selektiert)
     tenant_name: Option<String>,
+    // NEU: Original-Dateinamen (pdf_sources) und SharePoint-Ordner (sharepoint_jobs)
+    #[serde(default)]
+    source_files: Vec<String>,
+    folder_name: Option<String>,
+    // NEU: Label und Aktenzeichen des auslösenden Jobs (uploads → pipeline_runs)
+    #[serde(default)]
+    job_label: Option<String>,
+    #[serde(default)]
+    external_ref: Option<String>,
+    // Bediener-Tags von Lauf und Upload (run_notes/upload_notes), vereinigt
+    #[serde(default)]
+    tags: Vec<String>,
+    #[serde(default)]
+    upload_note: Option<String>,
+    #[serde(default)]
+    run_note: Option<String>,
+}
+
+/// One status event from `run_timeline`.
+#[derive(Debug, Serialize)]
+struct TimelineEntry {
+    id: i64,
+    pdf_id: Option<i32>,
+    upload_id: Option<i32>,
+    run_id: Option<Uuid>,
+    pipeline_id: Option<Uuid>,
+    source: String,
+    status: String,
+    message: Option<String>,
+    details: Option<serde_json::Value>,
+    created_at: DateTime<Utc>,
+}
+
+#[derive(Deserialize)]
+/// Query parameters accepted by `GET /timeline`.
+struct TimelineQuery {
+    pdf_id: Option<i32>,
+    run_id: Option<Uuid>,
+}
+
+#[derive(Deserialize)]
+/// Query parameters accepted by `GET /lineage`.
+struct LineageQuery {
+    pdf_id: i32,
 }
 
 #[derive(Clone)]
@@ -203,6 +258,10 @@ struct AppState {
     db: Arc<Db>,
     tx: tokio::sync::broadcast::Sender<HistoryEntry>,
     pdf_base: String,
+    readiness: Readiness,
+    ws: WsConfig,
+    admin_token: Option<String>,
+    master_key: Option<MasterKey>,
 }
 
 /* ============================================================================================
@@ -252,6 +311,16 @@ async fn ensure_schema_db(db: &Db) {
         )
         .await;
 
+    // NEU: Label/Aktenzeichen des Jobs (siehe migrations/0020_job_reference.sql)
+    let _ = db
+        .execute(
+            "ALTER TABLE analysis_history \
+         ADD COLUMN IF NOT EXISTS job_label TEXT, \
+         ADD COLUMN IF NOT EXISTS external_ref TEXT",
+            &[],
+        )
+        .await;
+
     // Backfill: started_at := timestamp; finished_at := timestamp wenn completed
     let _ = db
         .execute(
@@ -265,10 +334,27 @@ async fn ensure_schema_db(db: &Db) {
         &[],
     ).await;
 
+    // Einheitliche Status-Chronologie (siehe migrations/0014_run_timeline.sql)
+    let _ = db.execute(shared::timeline::CREATE_TABLE_SQL, &[]).await;
+
+    // Externe Ergebnis-Senken (siehe migrations/0032_result_sinks.sql)
+    for sql in sinks::CREATE_TABLES_SQL {
+        if let Err(e) = db.execute(sql, &[]).await {
+            warn!(%e, "result sink table not ensured");
+        }
+    }
+
     info!("database schema ensured");
 }
 
-// Mapping für Selektierungen aus der View (enthält zusätzlich tenant_name)
+/// Columns selected from `v_analysis_history_with_tenant`, in the order expected by
+/// [`row_to_entry_with_tenant`].
+const ENTRY_COLUMNS: &str =
+    "id, pdf_id, pipeline_id, state AS result, pdf_url, timestamp, status, score, \
+     label AS result_label, tenant_name, pdf_names, sharepoint_folder_name, job_label, external_ref, \
+     run_tags, upload_tags, run_note, upload_note";
+
+// Mapping für Selektierungen aus der View (enthält zusätzlich tenant_name, pdf_names, Ordner)
 /// Converts a database row into an in-memory history entry representation.
 fn row_to_entry_with_tenant(r: Row) -> HistoryEntry {
     HistoryEntry {
@@ -279,20 +365,48 @@ fn row_to_entry_with_tenant(r: Row) -> HistoryEntry {
         result: r.get(3),
         pdf_url: r.get(4),
         timestamp: r.get(5),
-        status: r.get(6),
+        status: RunStatus::canonical(r.get(6)),
         score: r.get(7),
         result_label: r.get(8),
         tenant_name: r.get(9), // tenant_name
+        source_files: parse_source_names(r.get(10)),
+        folder_name: r.get(11),
+        job_label: r.get(12),
+        external_ref: r.get(13),
+        tags: merge_tags(r.get(14), r.get(15)),
+        run_note: r.get(16),
+        upload_note: r.get(17),
+    }
+}
+
+/// Run tags followed by the upload tags not already among them.
+fn merge_tags(run_tags: Vec<String>, upload_tags: Vec<String>) -> Vec<String> {
+    let mut tags = run_tags;
+    for tag in upload_tags {
+        if !tags.contains(&tag) {
+            tags.push(tag);
+        }
     }
+    tags
+}
+
+/// Decodes `pdf_sources.names` (JSON array as text) into a list of file names.
+fn parse_source_names(raw: Option<String>) -> Vec<String> {
+    let Some(raw) = raw else { return vec![] };
+    serde_json::from_str::<Vec<String>>(&raw).unwrap_or_else(|_| {
+        raw.split(',')
+            .map(|s| s.trim().to_string())
+            .filter(|s| !s.is_empty())
+            .collect()
+    })
 }
 
 /// Loads the latest run results up to the provided limit.
 async fn latest_db(db: &Db, limit: i64) -> Vec<HistoryEntry> {
-    match db.query(
-        "SELECT id,pdf_id,pipeline_id,state AS result,pdf_url,timestamp,status,score,label AS result_label, tenant_name \
-         FROM v_analysis_history_with_tenant ORDER BY timestamp DESC LIMIT $1",
-        &[&limit],
-    ).await {
+    let sql = format!(
+        "SELECT {ENTRY_COLUMNS} FROM v_analysis_history_with_tenant ORDER BY timestamp DESC LIMIT $1"
+    );
+    match db.query(&sql, &[&limit]).await {
         Ok(rows) => rows.into_iter().map(row_to_entry_with_tenant).collect(),
         Err(e) => {
             error!(%e, "latest_db: query failed");
@@ -301,170 +415,202 @@ async fn latest_db(db: &Db, limit: i64) -> Vec<HistoryEntry> {
     }
 }
 
-/// Retrieves every run result stored for the tenant.
-async fn all_entries_db(db: &Db) -> Vec<HistoryEntry> {
-    match db.query(
-        "SELECT id,pdf_id,pipeline_id,state AS result,pdf_url,timestamp,status,score,label AS result_label, tenant_name \
-         FROM v_analysis_history_with_tenant ORDER BY timestamp DESC",
-        &[],
-    ).await {
+/// Loads up to `limit` entries older than `before_id` (all when `None`),
+/// newest first.
+async fn history_page_db(db: &Db, before_id: Option<i32>, limit: i64) -> Vec<HistoryEntry> {
+    let sql = format!(
+        "SELECT {ENTRY_COLUMNS} FROM v_analysis_history_with_tenant \
+         WHERE ($1::int IS NULL OR id < $1) \
+         ORDER BY id DESC LIMIT $2"
+    );
+    match db.query(&sql, &[&before_id, &limit]).await {
         Ok(rows) => rows.into_iter().map(row_to_entry_with_tenant).collect(),
         Err(e) => {
-            error!(%e, "all_entries_db: query failed");
+            error!(%e, ?before_id, "history_page_db: query failed");
             vec![]
         }
     }
 }
 
-/// Returns the newest runs filtered by the provided optional status.
-async fn latest_by_status_db(db: &Db, status: Option<String>) -> Vec<HistoryEntry> {
-    if let Some(s) = status {
-        match db
-            .query(
-                "SELECT * FROM ( \
-               SELECT DISTINCT ON (pdf_id) id, pdf_id, pipeline_id, state AS result, \
-                      pdf_url, timestamp, status, score, label AS result_label, tenant_name \
-               FROM v_analysis_history_with_tenant WHERE status = $1 \
-               ORDER BY pdf_id, timestamp DESC \
-             ) AS t ORDER BY timestamp DESC",
-                &[&s],
-            )
-            .await
-        {
-            Ok(rows) => rows.into_iter().map(row_to_entry_with_tenant).collect(),
-            Err(e) => {
-                error!(%e, "latest_by_status_db(status): query failed");
-                vec![]
-            }
-        }
-    } else {
-        match db
-            .query(
-                "SELECT * FROM ( \
-               SELECT DISTINCT ON (pdf_id) id, pdf_id, pipeline_id, state AS result, \
-                      pdf_url, timestamp, status, score, label AS result_label, tenant_name \
-               FROM v_analysis_history_with_tenant \
-               ORDER BY pdf_id, timestamp DESC \
-             ) AS t ORDER BY timestamp DESC",
-                &[],
-            )
-            .await
-        {
-            Ok(rows) => rows.into_iter().map(row_to_entry_with_tenant).collect(),
-            Err(e) => {
-                error!(%e, "latest_by_status_db(all): query failed");
-                vec![]
-            }
+/// Returns up to `limit` entries created after `last_seen_id` or changed since
+/// that entry was written (running rows are updated in place on completion).
+async fn entries_since_db(db: &Db, last_seen_id: i32, limit: i64) -> Vec<HistoryEntry> {
+    let sql = format!(
+        "SELECT {ENTRY_COLUMNS} FROM v_analysis_history_with_tenant \
+         WHERE id > $1 \
+            OR timestamp > (SELECT timestamp FROM analysis_history WHERE id = $1) \
+         ORDER BY id ASC LIMIT $2"
+    );
+    match db.query(&sql, &[&last_seen_id, &limit]).await {
+        Ok(rows) => rows.into_iter().map(row_to_entry_with_tenant).collect(),
+        Err(e) => {
+            error!(%e, last_seen_id, "entries_since_db: query failed");
+            vec![]
         }
     }
 }
 
-// NEU: gleiche Logik wie oben, aber über die View v_analysis_history_with_tenant + Tenant-Filter
-/// Returns the newest runs and their associated tenant when filtered by
-/// status.
-async fn latest_by_status_with_tenant_db(
-    db: &Db,
+/// Optional filters accepted by `/analyses`.
+#[derive(Debug, Default)]
+struct HistoryFilter {
     status: Option<String>,
     tenant_like: Option<String>,
-) -> Vec<HistoryEntry> {
-    match (tenant_like, status) {
-        (Some(t), Some(s)) => {
-            let sql = r#"
-                SELECT * FROM (
-                  SELECT DISTINCT ON (pdf_id)
-                         id, pdf_id, pipeline_id, state AS result,
-                         pdf_url, timestamp, status, score, label AS result_label,
-                         tenant_name
-                  FROM v_analysis_history_with_tenant
-                  WHERE tenant_name ILIKE '%' || $1 || '%'
-                    AND status = $2
-                  ORDER BY pdf_id, timestamp DESC
-                ) AS t
-                ORDER BY timestamp DESC
-            "#;
-            match db.query(sql, &[&t, &s]).await {
-                Ok(rows) => rows.into_iter().map(row_to_entry_with_tenant).collect(),
-                Err(e) => {
-                    error!(%e, "latest_by_status_with_tenant_db(t,s): query failed");
-                    vec![]
-                }
-            }
-        }
-        (Some(t), None) => {
-            let sql = r#"
-                SELECT * FROM (
-                  SELECT DISTINCT ON (pdf_id)
-                         id, pdf_id, pipeline_id, state AS result,
-                         pdf_url, timestamp, status, score, label AS result_label,
-                         tenant_name
-                  FROM v_analysis_history_with_tenant
-                  WHERE tenant_name ILIKE '%' || $1 || '%'
-                  ORDER BY pdf_id, timestamp DESC
-                ) AS t
-                ORDER BY timestamp DESC
-            "#;
-            match db.query(sql, &[&t]).await {
-                Ok(rows) => rows.into_iter().map(row_to_entry_with_tenant).collect(),
-                Err(e) => {
-                    error!(%e, "latest_by_status_with_tenant_db(t): query failed");
-                    vec![]
-                }
-            }
-        }
-        (None, Some(s)) => {
-            let sql = r#"
-                SELECT * FROM (
-                  SELECT DISTINCT ON (pdf_id)
-                         id, pdf_id, pipeline_id, state AS result,
-                         pdf_url, timestamp, status, score, label AS result_label,
-                         tenant_name
-                  FROM v_analysis_history_with_tenant
-                  WHERE status = $1
-                  ORDER BY pdf_id, timestamp DESC
-                ) AS t
-                ORDER BY timestamp DESC
-            "#;
-            match db.query(sql, &[&s]).await {
-                Ok(rows) => rows.into_iter().map(row_to_entry_with_tenant).collect(),
-                Err(e) => {
-                    error!(%e, "latest_by_status_with_tenant_db(s): query failed");
-                    vec![]
-                }
-            }
-        }
-        (None, None) => {
-            let sql = r#"
-                SELECT * FROM (
-                  SELECT DISTINCT ON (pdf_id)
-                         id, pdf_id, pipeline_id, state AS result,
-                         pdf_url, timestamp, status, score, label AS result_label,
-                         tenant_name
-                  FROM v_analysis_history_with_tenant
-                  ORDER BY pdf_id, timestamp DESC
-                ) AS t
-                ORDER BY timestamp DESC
-            "#;
-            match db.query(sql, &[]).await {
-                Ok(rows) => rows.into_iter().map(row_to_entry_with_tenant).collect(),
-                Err(e) => {
-                    error!(%e, "latest_by_status_with_tenant_db(all): query failed");
-                    vec![]
-                }
-            }
+    // Sucht in den Original-Dateinamen (pdf_sources.names)
+    file_like: Option<String>,
+    // Sucht im SharePoint-Ordnernamen
+    folder_like: Option<String>,
+    // Sucht im Job-Label; Aktenzeichen muss exakt passen
+    job_label_like: Option<String>,
+    external_ref: Option<String>,
+    // approved | review | rejected
+    label: Option<String>,
+    // Bediener-Tag an Lauf oder Upload, exakt (normalisiert)
+    tag: Option<String>,
+}
+
+impl HistoryFilter {
+    /// Reads the filters from the query string, ignoring empty values.
+    fn from_query(query: &HashMap<String, String>) -> Self {
+        let get = |key: &str| {
+            query
+                .get(key)
+                .map(|v| v.trim().to_string())
+                .filter(|v| !v.is_empty())
+        };
+        Self {
+            // Alt-Schreibweisen (finished, finalized) auf den gespeicherten Wert abbilden
+            status: get("status").as_deref().map(RunStatus::canonical),
+            tenant_like: get("tenant"),
+            file_like: get("filename"),
+            folder_like: get("folder"),
+            job_label_like: get("job_label"),
+            external_ref: get("external_ref"),
+            label: get("label").map(|l| {
+                l.parse::<ResultLabel>()
+                    .map_or(l, |label| label.to_string())
+            }),
+            tag: get("tag").and_then(|t| operator_notes::normalize_tag(&t)),
         }
     }
 }
 
-/// Fetches a single run result by its identifier.
-async fn fetch_entry_by_id(db: &Db, id: i32) -> Option<HistoryEntry> {
+/// Returns the newest run per PDF, optionally filtered by status, tenant,
+/// source file name, SharePoint folder, job label, external reference and tag.
+async fn latest_filtered_db(db: &Db, filter: &HistoryFilter) -> Vec<HistoryEntry> {
+    let sql = format!(
+        r#"
+        SELECT * FROM (
+          SELECT DISTINCT ON (pdf_id) {ENTRY_COLUMNS}
+          FROM v_analysis_history_with_tenant
+          WHERE ($1::text IS NULL OR status = $1)
+            AND ($2::text IS NULL OR tenant_name ILIKE '%' || $2 || '%')
+            AND ($3::text IS NULL OR pdf_names ILIKE '%' || $3 || '%')
+            AND ($4::text IS NULL OR sharepoint_folder_name ILIKE '%' || $4 || '%')
+            AND ($5::text IS NULL OR job_label ILIKE '%' || $5 || '%')
+            AND ($6::text IS NULL OR external_ref = $6)
+            AND ($7::text IS NULL OR label = $7)
+            AND ($8::text IS NULL OR $8 = ANY(run_tags) OR $8 = ANY(upload_tags))
+          ORDER BY pdf_id, timestamp DESC
+        ) AS t
+        ORDER BY timestamp DESC
+        "#
+    );
     match db
+        .query(
+            &sql,
+            &[
+                &filter.status,
+                &filter.tenant_like,
+                &filter.file_like,
+                &filter.folder_like,
+                &filter.job_label_like,
+                &filter.external_ref,
+                &filter.label,
+                &filter.tag,
+            ],
+        )
+        .await
+    {
+        Ok(rows) => rows.into_iter().map(row_to_entry_with_tenant).collect(),
+        Err(e) => {
+            error!(%e, ?filter, "latest_filtered_db: query failed");
+            vec![]
+        }
+    }
+}
+
+/// Labeling rules of a pipeline (`config_json.label_rules`), else `fallback`.
+async fn label_rules_db(
+    db: &Db,
+    pipeline_id: Uuid,
+    fallback: Option<&LabelRules>,
+) -> Option<LabelRules> {
+    let stored = match db
         .query_opt(
-            "SELECT id,pdf_id,pipeline_id,state AS result,pdf_url,timestamp,status,score,label AS result_label, tenant_name \
-             FROM v_analysis_history_with_tenant WHERE id = $1",
-            &[&id],
+            "SELECT config_json -> 'label_rules' FROM pipelines WHERE id = $1",
+            &[&pipeline_id],
         )
         .await
     {
+        Ok(row) => row
+            .and_then(|r| r.get::<_, Option<serde_json::Value>>(0))
+            .filter(|v| !v.is_null())
+            .and_then(|v| match serde_json::from_value::<LabelRules>(v) {
+                Ok(rules) => Some(rules),
+                Err(e) => {
+                    warn!(%e, %pipeline_id, "ignoring invalid label_rules");
+                    None
+                }
+            }),
+        Err(e) => {
+            warn!(%e, %pipeline_id, "failed to load label_rules");
+            None
+        }
+    };
+    stored.or_else(|| fallback.cloned())
+}
+
+/// Loads the timeline for a PDF and/or run, oldest event first.
+///
+/// Events recorded before the PDF existed (e.g. `uploaded`) only carry the
+/// upload id and are matched through `uploads.pdf_id`.
+async fn timeline_db(
+    db: &Db,
+    pdf_id: Option<i32>,
+    run_id: Option<Uuid>,
+) -> Result<Vec<TimelineEntry>, tokio_postgres::Error> {
+    let rows = db
+        .query(
+            "SELECT id, pdf_id, upload_id, run_id, pipeline_id, source, status, message, details, created_at \
+             FROM run_timeline t \
+             WHERE ($1::int IS NULL OR t.pdf_id = $1 \
+                    OR t.upload_id IN (SELECT u.id FROM uploads u WHERE u.pdf_id = $1)) \
+               AND ($2::uuid IS NULL OR t.run_id = $2) \
+             ORDER BY t.created_at ASC, t.id ASC",
+            &[&pdf_id, &run_id],
+        )
+        .await?;
+    Ok(rows
+        .into_iter()
+        .map(|r| TimelineEntry {
+            id: r.get(0),
+            pdf_id: r.get(1),
+            upload_id: r.get(2),
+            run_id: r.get(3),
+            pipeline_id: r.get(4),
+            source: r.get(5),
+            status: r.get(6),
+            message: r.get(7),
+            details: r.get(8),
+            created_at: r.get(9),
+        })
+        .collect())
+}
+
+/// Fetches a single run result by its identifier.
+async fn fetch_entry_by_id(db: &Db, id: i32) -> Option<HistoryEntry> {
+    let sql = format!("SELECT {ENTRY_COLUMNS} FROM v_analysis_history_with_tenant WHERE id = $1");
+    match db.query_opt(&sql, &[&id]).await {
         Ok(Some(row)) => Some(row_to_entry_with_tenant(row)),
         Ok(None) => None,
         Err(e) => {
@@ -484,8 +630,14 @@ async fn mark_pending_db(
 ) -> i32 {
     match db.query_opt(
         // started_at direkt auf timestamp setzen
-        "INSERT INTO analysis_history (pdf_id, pipeline_id, pdf_url, timestamp, status, started_at) \
-         VALUES ($1,$2,$3,$4,'running',$4) RETURNING id",
+        // Label/Aktenzeichen vom jüngsten Upload des PDFs übernehmen
+        "INSERT INTO analysis_history (pdf_id, pipeline_id, pdf_url, timestamp, status, started_at, job_label, external_ref) \
+         SELECT $1,$2,$3,$4,'running',$4, u.job_label, u.external_ref \
+         FROM (SELECT 1) AS one \
+         LEFT JOIN LATERAL ( \
+             SELECT job_label, external_ref FROM uploads WHERE pdf_id = $1 ORDER BY id DESC LIMIT 1 \
+         ) u ON TRUE \
+         RETURNING id",
         &[&pdf_id, &pipeline_id, &pdf_url, &timestamp],
     ).await {
         Ok(Some(row)) => row.get(0),
@@ -524,7 +676,8 @@ async fn insert_result_db(
                 // finished_at beim Abschluss setzen
                 "UPDATE analysis_history \
                  SET state=$2, pdf_url=$3, timestamp=$4, status='completed', score=$5, label=$6, finished_at=$7, \
-                     started_at = COALESCE($8, started_at) \
+                     started_at = COALESCE($8, started_at), \
+                     job_label = COALESCE($9, job_label), external_ref = COALESCE($10, external_ref) \
                  WHERE id=$1",
                 &[
                     &id,
@@ -535,6 +688,8 @@ async fn insert_result_db(
                     &entry.result_label,
                     &finished_ts,
                     &started_override,
+                    &entry.job_label,
+                    &entry.external_ref,
                 ],
             ).await {
                 error!(%e, id, "failed to update running row to completed");
@@ -545,8 +700,9 @@ async fn insert_result_db(
             match db.query_opt(
                 // Fallback: direkt completed eintragen – Start/Ende = timestamp
                 "INSERT INTO analysis_history \
-                 (pdf_id, pipeline_id, state, pdf_url, timestamp, status, score, label, started_at, finished_at) \
-                 VALUES ($1,$2,$3,$4,$5,'completed',$6,$7,$8,$9) RETURNING id",
+                 (pdf_id, pipeline_id, state, pdf_url, timestamp, status, score, label, started_at, finished_at, \
+                  job_label, external_ref) \
+                 VALUES ($1,$2,$3,$4,$5,'completed',$6,$7,$8,$9,$10,$11) RETURNING id",
                 &[
                     &entry.pdf_id,
                     &entry.pipeline_id,
@@ -557,6 +713,8 @@ async fn insert_result_db(
                     &entry.result_label,
                     &started_ts,
                     &finished_ts,
+                    &entry.job_label,
+                    &entry.external_ref,
                 ],
             ).await {
                 Ok(Some(row)) => row.get(0),
@@ -601,11 +759,11 @@ async fn analyses(
 ) -> impl Responder {
     // NEU: run_id-Suche (liefert Liste mit max. 1 Eintrag, kompatibel zum Frontend)
     if let Some(rid) = query.get("run_id") {
-        match state.db.query_opt(
-            "SELECT id,pdf_id,pipeline_id,state AS result,pdf_url,timestamp,status,score,label AS result_label, tenant_name \
-             FROM v_analysis_history_with_tenant WHERE state->>'run_id' = $1 ORDER BY timestamp DESC LIMIT 1",
-            &[rid],
-        ).await {
+        let sql = format!(
+            "SELECT {ENTRY_COLUMNS} FROM v_analysis_history_with_tenant \
+             WHERE state->>'run_id' = $1 ORDER BY timestamp DESC LIMIT 1"
+        );
+        match state.db.query_opt(&sql, &[rid]).await {
             Ok(Some(row)) => return HttpResponse::Ok().json(vec![row_to_entry_with_tenant(row)]),
             Ok(None) => return HttpResponse::Ok().json(Vec::<HistoryEntry>::new()),
             Err(e) => {
@@ -615,13 +773,33 @@ async fn analyses(
         }
     }
 
-    let status = query.get("status").cloned();
-    let tenant_like = query.get("tenant").cloned();
-    // View-basierte Ergebnisse (inkl. optionalem Tenant-Filter)
-    let items = latest_by_status_with_tenant_db(&state.db, status, tenant_like).await;
+    // View-basierte Ergebnisse (Status, Tenant, Dateiname, SharePoint-Ordner)
+    let filter = HistoryFilter::from_query(&query);
+    let items = latest_filtered_db(&state.db, &filter).await;
     HttpResponse::Ok().json(items)
 }
 
+#[derive(Deserialize)]
+/// Query parameters accepted by `GET /history`.
+struct HistoryQuery {
+    before_id: Option<i32>,
+    limit: Option<i64>,
+}
+
+/// Largest page of `GET /history`.
+const HISTORY_PAGE_MAX: i64 = 1000;
+
+/// Full history page by page, newest first; REST fallback for clients that
+/// need more than the WebSocket snapshot.
+async fn history(state: web::Data<AppState>, query: web::Query<HistoryQuery>) -> impl Responder {
+    let limit = query
+        .limit
+        .unwrap_or(state.ws.snapshot_limit as i64)
+        .clamp(1, HISTORY_PAGE_MAX);
+    let rows = history_page_db(&state.db, query.before_id, limit + 1).await;
+    HttpResponse::Ok().json(stream::HistoryPage::from_rows(rows, limit as usize))
+}
+
 /// Returns the stored result for the provided identifier.
 async fn result(state: web::Data<AppState>, path: web::Path<i32>) -> impl Responder {
     let pdf_id = path.into_inner();
@@ -641,7 +819,7 @@ async fn result(state: web::Data<AppState>, path: web::Path<i32>) -> impl Respon
             let mut value: serde_json::Value = r.get(0);
             let started_at: Option<DateTime<Utc>> = r.get(1);
             let finished_at: Option<DateTime<Utc>> = r.get(2);
-            let status: String = r.get(3);
+            let status = RunStatus::canonical(r.get(3));
             let pipeline_id: Uuid = r.get(4);
             let pdf_id_val: i32 = r.get(5);
 
@@ -680,6 +858,71 @@ async fn result(state: web::Data<AppState>, path: web::Path<i32>) -> impl Respon
     }
 }
 
+/// Returns the full status chronology for a PDF (`?pdf_id=`) or pipeline run (`?run_id=`).
+async fn timeline(state: web::Data<AppState>, query: web::Query<TimelineQuery>) -> impl Responder {
+    let TimelineQuery { pdf_id, run_id } = query.into_inner();
+    if pdf_id.is_none() && run_id.is_none() {
+        return HttpResponse::BadRequest().body("pdf_id or run_id required");
+    }
+    match timeline_db(&state.db, pdf_id, run_id).await {
+        Ok(items) => HttpResponse::Ok().json(items),
+        Err(e) => {
+            error!(%e, "timeline: db error");
+            HttpResponse::InternalServerError().finish()
+        }
+    }
+}
+
+/// Returns the provenance graph of a PDF from its sources to runs and exports.
+async fn lineage(state: web::Data<AppState>, query: web::Query<LineageQuery>) -> impl Responder {
+    match lineage::load(&state.db, query.pdf_id).await {
+        Ok(Some(graph)) => HttpResponse::Ok().json(graph),
+        Ok(None) => HttpResponse::NotFound().finish(),
+        Err(e) => {
+            error!(%e, pdf_id = query.pdf_id, "lineage: db error");
+            HttpResponse::InternalServerError().finish()
+        }
+    }
+}
+
+/// Tables created by the history service (`GET /admin/schema`).
+const OWNED_TABLES: [&str; 4] = [
+    "analysis_history",
+    "run_timeline",
+    "result_sinks",
+    "result_sink_deliveries",
+];
+
+/// `401` unless the request carries `Authorization: Bearer <ADMIN_TOKEN>` (if set).
+fn admin_denied(req: &HttpRequest, state: &AppState) -> Option<HttpResponse> {
+    let expected = state.admin_token.as_ref()?;
+    let token = req
+        .headers()
+        .get("Authorization")
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.strip_prefix("Bearer "))
+        .unwrap_or("");
+    (token != expected).then(|| HttpResponse::Unauthorized().body("invalid token"))
+}
+
+/// Describes the owned tables; needs `Authorization: Bearer <ADMIN_TOKEN>` if set.
+async fn schema(req: HttpRequest, state: web::Data<AppState>) -> impl Responder {
+    if let Some(denied) = admin_denied(&req, &state) {
+        return denied;
+    }
+    let described = match state.db.current().await {
+        Ok(client) => schema_doc::describe(&client, "history-service", &OWNED_TABLES).await,
+        Err(e) => Err(e.into()),
+    };
+    match described {
+        Ok(schema) => HttpResponse::Ok().json(schema),
+        Err(e) => {
+            error!("schema: {:#}", e);
+            HttpResponse::InternalServerError().finish()
+        }
+    }
+}
+
 /// Reports health status for both the service and the downstream database.
 async fn health(state: web::Data<AppState>) -> impl Responder {
     match state.db.ping().await {
@@ -688,6 +931,11 @@ async fn health(state: web::Data<AppState>) -> impl Responder {
     }
 }
 
+/// Readiness endpoint; returns 503 until Postgres and Kafka are connected.
+async fn readyz(state: web::Data<AppState>) -> HttpResponse {
+    state.readiness.respond()
+}
+
 // NEU: Tenants auflisten
 /// Lists the tenants known to the history service.
 async fn tenants_list(state: web::Data<AppState>) -> impl Responder {
@@ -748,34 +996,361 @@ async fn tenants_create(
     }
 }
 
+/// Lists the result sinks of a tenant (without secrets).
+async fn sinks_list(
+    req: HttpRequest,
+    state: web::Data<AppState>,
+    path: web::Path<Uuid>,
+) -> impl Responder {
+    if let Some(denied) = admin_denied(&req, &state) {
+        return denied;
+    }
+    match sinks::list(&state.db, path.into_inner()).await {
+        Ok(list) => HttpResponse::Ok().json(list),
+        Err(e) => {
+            error!(%e, "sinks_list: db error");
+            HttpResponse::InternalServerError().finish()
+        }
+    }
+}
+
+/// Registers a Postgres or HTTP sink receiving the tenant's finished results.
+async fn sinks_create(
+    req: HttpRequest,
+    state: web::Data<AppState>,
+    path: web::Path<Uuid>,
+    body: web::Json<sinks::SinkInput>,
+) -> impl Responder {
+    if let Some(denied) = admin_denied(&req, &state) {
+        return denied;
+    }
+    match sinks::create(
+        &state.db,
+        path.into_inner(),
+        &body,
+        state.master_key.as_ref(),
+    )
+    .await
+    {
+        Ok(Ok(sink)) => HttpResponse::Created().json(sink),
+        Ok(Err(msg)) => HttpResponse::BadRequest().body(msg),
+        Err(e) => {
+            error!(%e, "sinks_create: db error");
+            HttpResponse::InternalServerError().finish()
+        }
+    }
+}
+
+/// Removes a sink together with its delivery queue.
+async fn sinks_delete(
+    req: HttpRequest,
+    state: web::Data<AppState>,
+    path: web::Path<(Uuid, i32)>,
+) -> impl Responder {
+    if let Some(denied) = admin_denied(&req, &state) {
+        return denied;
+    }
+    let (tenant_id, sink_id) = path.into_inner();
+    match sinks::delete(&state.db, tenant_id, sink_id).await {
+        Ok(true) => HttpResponse::NoContent().finish(),
+        Ok(false) => HttpResponse::NotFound().finish(),
+        Err(e) => {
+            error!(%e, sink_id, "sinks_delete: db error");
+            HttpResponse::InternalServerError().finish()
+        }
+    }
+}
+
+#[derive(Deserialize)]
+/// Query parameters accepted by `GET /tenants/{id}/sinks/{sink_id}/deliveries`.
+struct DeliveryQuery {
+    status: Option<String>,
+    limit: Option<i64>,
+}
+
+/// Delivery status of a sink, newest first (`?status=failed`).
+async fn sink_deliveries(
+    req: HttpRequest,
+    state: web::Data<AppState>,
+    path: web::Path<(Uuid, i32)>,
+    query: web::Query<DeliveryQuery>,
+) -> impl Responder {
+    if let Some(denied) = admin_denied(&req, &state) {
+        return denied;
+    }
+    let (tenant_id, sink_id) = path.into_inner();
+    let limit = query.limit.unwrap_or(100).clamp(1, 1000);
+    match sinks::deliveries(
+        &state.db,
+        tenant_id,
+        sink_id,
+        query.status.as_deref(),
+        limit,
+    )
+    .await
+    {
+        Ok(items) => HttpResponse::Ok().json(items),
+        Err(e) => {
+            error!(%e, sink_id, "sink_deliveries: db error");
+            HttpResponse::InternalServerError().finish()
+        }
+    }
+}
+
+/// Queues a failed or delivered entry again.
+async fn sink_delivery_retry(
+    req: HttpRequest,
+    state: web::Data<AppState>,
+    path: web::Path<(Uuid, i32, i64)>,
+) -> impl Responder {
+    if let Some(denied) = admin_denied(&req, &state) {
+        return denied;
+    }
+    let (tenant_id, sink_id, delivery_id) = path.into_inner();
+    match sinks::retry(&state.db, tenant_id, sink_id, delivery_id).await {
+        Ok(true) => HttpResponse::Accepted().finish(),
+        Ok(false) => HttpResponse::NotFound().finish(),
+        Err(e) => {
+            error!(%e, delivery_id, "sink_delivery_retry: db error");
+            HttpResponse::InternalServerError().finish()
+        }
+    }
+}
+
 /* ============================================================================================
 WebSocket
 ============================================================================================ */
 
+/// Heartbeat/backpressure settings for WebSocket sessions.
+#[derive(Clone, Copy)]
+struct WsConfig {
+    heartbeat: Duration,
+    client_timeout: Duration,
+    // max. Live-Updates, die während eines Replays oder bei vollem Fenster gepuffert werden
+    max_pending: usize,
+    // Einträge im Snapshot beim Verbindungsaufbau
+    snapshot_limit: usize,
+    // max. unquittierte Nachrichten an Clients, die quittieren
+    ack_window: usize,
+}
+
+impl WsConfig {
+    /// Reads `WS_HEARTBEAT_SECS`, `WS_CLIENT_TIMEOUT_SECS`, `WS_MAX_PENDING`,
+    /// `WS_SNAPSHOT_LIMIT` and `WS_ACK_WINDOW`.
+    fn from_env() -> Self {
+        let positive = |key: &str, default: u64| {
+            std::env::var(key)
+                .ok()
+                .and_then(|v| v.parse::<u64>().ok())
+                .filter(|v| *v > 0)
+                .unwrap_or(default)
+        };
+        Self {
+            heartbeat: Duration::from_secs(positive("WS_HEARTBEAT_SECS", 5)),
+            client_timeout: Duration::from_secs(positive("WS_CLIENT_TIMEOUT_SECS", 30)),
+            max_pending: positive("WS_MAX_PENDING", 256) as usize,
+            snapshot_limit: positive("WS_SNAPSHOT_LIMIT", 200) as usize,
+            ack_window: positive("WS_ACK_WINDOW", 32) as usize,
+        }
+    }
+}
+
+#[derive(Deserialize)]
+/// Query parameters accepted when opening the WebSocket.
+struct WsQuery {
+    // Resume: nur Einträge nach dieser ID (bzw. seitdem geänderte) nachliefern
+    last_seen_id: Option<i32>,
+}
+
+#[derive(Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+/// Messages a client sends over the WebSocket (see `stream.rs`).
+enum ClientMessage {
+    Ack { seq: u64 },
+}
+
 struct WsConn {
     db: Arc<Db>,
-    rx: tokio::sync::broadcast::Receiver<HistoryEntry>,
+    rx: Option<tokio::sync::broadcast::Receiver<HistoryEntry>>,
+    cfg: WsConfig,
+    // letzter Lebenszeichen-Zeitpunkt des Clients (Pong/Nachricht)
+    hb: Instant,
+    last_seen_id: Option<i32>,
+    // höchste bereits an den Client gesendete ID
+    last_sent_id: i32,
+    // true, solange ein Replay aus der DB läuft; Live-Updates werden so lange gepuffert
+    replaying: bool,
+    // verhindert, dass ein überholtes Replay den Puffer leert
+    replay_gen: u64,
+    pending: stream::Pending,
+    window: stream::AckWindow,
+    // verlorene Updates; Resync aus der DB, sobald Replay und Fenster es zulassen
+    lagged: Option<u64>,
+}
+
+impl WsConn {
+    fn new(
+        db: Arc<Db>,
+        rx: tokio::sync::broadcast::Receiver<HistoryEntry>,
+        cfg: WsConfig,
+        last_seen_id: Option<i32>,
+    ) -> Self {
+        Self {
+            db,
+            rx: Some(rx),
+            cfg,
+            hb: Instant::now(),
+            last_seen_id,
+            last_sent_id: last_seen_id.unwrap_or(0),
+            replaying: false,
+            replay_gen: 0,
+            pending: stream::Pending::new(cfg.max_pending),
+            window: stream::AckWindow::new(cfg.ack_window),
+            lagged: None,
+        }
+    }
+
+    /// Pings the client periodically and drops the session once it stops answering.
+    fn start_heartbeat(&self, ctx: &mut ws::WebsocketContext<Self>) {
+        ctx.run_interval(self.cfg.heartbeat, |act, ctx| {
+            if Instant::now().duration_since(act.hb) > act.cfg.client_timeout {
+                warn!("websocket client heartbeat timed out; closing");
+                ctx.stop();
+                return;
+            }
+            ctx.ping(b"");
+        });
+    }
+
+    /// Sends a snapshot of the latest entries or, when resuming, only the
+    /// entries missed since `since_id` (a snapshot again if that is more than
+    /// the snapshot limit). Live updates are buffered until it has been sent.
+    fn replay(&mut self, since_id: Option<i32>, ctx: &mut ws::WebsocketContext<Self>) {
+        self.replaying = true;
+        self.replay_gen += 1;
+        let generation = self.replay_gen;
+        let db = self.db.clone();
+        let limit = self.cfg.snapshot_limit;
+        async move {
+            if let Some(id) = since_id {
+                let missed = entries_since_db(&db, id, limit as i64 + 1).await;
+                if missed.len() <= limit {
+                    let max_id = missed.iter().map(|e| e.id).max();
+                    return (json!({ "type": "replay", "data": missed }), max_id);
+                }
+            }
+            let rows = history_page_db(&db, None, limit as i64 + 1).await;
+            let page = stream::HistoryPage::from_rows(rows, limit);
+            let max_id = page.entries.first().map(|e| e.id);
+            let message = json!({
+                "type": "snapshot",
+                "data": page.entries,
+                "next_before_id": page.next_before_id,
+            });
+            (message, max_id)
+        }
+        .into_actor(self)
+        .map(move |(message, max_id), act, ctx| {
+            if act.replay_gen != generation {
+                return;
+            }
+            if let Some(max_id) = max_id {
+                act.last_sent_id = act.last_sent_id.max(max_id);
+            }
+            act.send(message, ctx);
+            act.replaying = false;
+            act.flush(ctx);
+        })
+        .spawn(ctx);
+    }
+
+    /// Sends `message` with the next sequence number.
+    fn send(&mut self, mut message: serde_json::Value, ctx: &mut ws::WebsocketContext<Self>) {
+        message["seq"] = json!(self.window.next_seq());
+        send_json(ctx, &message);
+    }
+
+    fn send_update(&mut self, entry: HistoryEntry, ctx: &mut ws::WebsocketContext<Self>) {
+        self.last_sent_id = self.last_sent_id.max(entry.id);
+        self.send(json!({ "type": "update", "data": entry }), ctx);
+    }
+
+    /// Sends a live update right away or buffers it during a replay and while
+    /// the client's window is full.
+    fn queue(&mut self, entry: HistoryEntry, ctx: &mut ws::WebsocketContext<Self>) {
+        if let Some(missed) = self.lagged.as_mut() {
+            // Der Resync liefert den Eintrag ohnehin aus der DB nach
+            *missed += 1;
+            return;
+        }
+        if !self.replaying && self.pending.is_empty() && self.window.is_open() {
+            self.send_update(entry, ctx);
+        } else if !self.pending.push(entry) {
+            let missed = self.pending.len() as u64 + 1;
+            self.lost(missed, ctx);
+        }
+    }
+
+    /// Drops the buffered updates of a client that fell behind; it is resynced
+    /// from the DB as soon as possible.
+    fn lost(&mut self, missed: u64, ctx: &mut ws::WebsocketContext<Self>) {
+        self.pending.clear();
+        self.lagged = Some(self.lagged.unwrap_or(0) + missed);
+        self.flush(ctx);
+    }
+
+    /// Sends buffered updates while the window allows it, or the pending resync.
+    fn flush(&mut self, ctx: &mut ws::WebsocketContext<Self>) {
+        if self.replaying {
+            return;
+        }
+        if let Some(missed) = self.lagged {
+            if self.window.is_open() {
+                self.lagged = None;
+                self.resync(missed, ctx);
+            }
+            return;
+        }
+        while self.window.is_open() {
+            let Some(entry) = self.pending.pop() else {
+                break;
+            };
+            self.send_update(entry, ctx);
+        }
+    }
+
+    /// Handles a client that fell behind: notify it and resync from the DB.
+    fn resync(&mut self, missed: u64, ctx: &mut ws::WebsocketContext<Self>) {
+        warn!(
+            missed,
+            last_sent_id = self.last_sent_id,
+            "websocket client lagged; resyncing"
+        );
+        self.send(json!({ "type": "lagged", "missed": missed }), ctx);
+        self.pending.clear();
+        let since = self.last_sent_id;
+        self.replay(Some(since), ctx);
+    }
+}
+
+fn send_json(ctx: &mut ws::WebsocketContext<WsConn>, value: &serde_json::Value) {
+    if let Ok(text) = serde_json::to_string(value) {
+        ctx.text(text);
+    }
 }
 
 impl actix::Actor for WsConn {
     type Context = ws::WebsocketContext<Self>;
 
-    /// Sends the most recent history entries to the client when the socket is
-    /// established.
+    /// Sends the history snapshot (or the missed entries when resuming) and
+    /// then switches to live updates.
     fn started(&mut self, ctx: &mut Self::Context) {
-        let dbwrap = self.db.clone();
-        async move { all_entries_db(&dbwrap).await }
-            .into_actor(self)
-            .map(|entries, _act, ctx| {
-                if let Ok(text) =
-                    serde_json::to_string(&serde_json::json!({"type":"history","data":entries}))
-                {
-                    ctx.text(text);
-                }
-            })
-            .spawn(ctx);
-
-        ctx.add_stream(BroadcastStream::new(self.rx.resubscribe()));
+        self.start_heartbeat(ctx);
+        if let Some(rx) = self.rx.take() {
+            ctx.add_stream(BroadcastStream::new(rx));
+        }
+        let since = self.last_seen_id;
+        self.replay(since, ctx);
     }
 }
 
@@ -786,23 +1361,41 @@ impl actix::StreamHandler<Result<HistoryEntry, BroadcastStreamRecvError>> for Ws
         item: Result<HistoryEntry, BroadcastStreamRecvError>,
         ctx: &mut Self::Context,
     ) {
-        if let Ok(entry) = item {
-            if let Ok(text) =
-                serde_json::to_string(&serde_json::json!({"type":"update","data":entry}))
-            {
-                ctx.text(text);
-            }
+        match item {
+            Ok(entry) => self.queue(entry, ctx),
+            Err(BroadcastStreamRecvError::Lagged(missed)) => self.lost(missed, ctx),
         }
     }
 }
 
 impl actix::StreamHandler<Result<ws::Message, ws::ProtocolError>> for WsConn {
-    /// Handles WebSocket control frames and closes the connection when
-    /// requested by the client.
+    /// Handles WebSocket control frames and acknowledgements and closes the
+    /// connection when requested by the client.
     fn handle(&mut self, item: Result<ws::Message, ws::ProtocolError>, ctx: &mut Self::Context) {
         match item {
-            Ok(ws::Message::Ping(msg)) => ctx.pong(&msg),
-            Ok(ws::Message::Close(_)) => ctx.stop(),
+            Ok(ws::Message::Ping(msg)) => {
+                self.hb = Instant::now();
+                ctx.pong(&msg);
+            }
+            Ok(ws::Message::Text(text)) => {
+                self.hb = Instant::now();
+                // Andere Nachrichten zählen nur als Lebenszeichen
+                if let Ok(ClientMessage::Ack { seq }) = serde_json::from_str(&text) {
+                    self.window.ack(seq);
+                    self.flush(ctx);
+                }
+            }
+            Ok(ws::Message::Pong(_)) | Ok(ws::Message::Binary(_)) => {
+                self.hb = Instant::now();
+            }
+            Ok(ws::Message::Close(reason)) => {
+                ctx.close(reason);
+                ctx.stop();
+            }
+            Err(e) => {
+                warn!(%e, "websocket protocol error");
+                ctx.stop();
+            }
             _ => {}
         }
     }
@@ -813,11 +1406,15 @@ async fn ws_index(
     req: HttpRequest,
     stream: Payload,
     state: web::Data<AppState>,
+    query: web::Query<WsQuery>,
 ) -> Result<HttpResponse, Error> {
-    let ws = WsConn {
-        db: state.db.clone(),
-        rx: state.tx.subscribe(),
-    };
+    // Vor dem Replay abonnieren, damit zwischenzeitliche Updates nicht verloren gehen
+    let ws = WsConn::new(
+        state.db.clone(),
+        state.tx.subscribe(),
+        state.ws,
+        query.last_seen_id,
+    );
     ws::start(ws, &req, stream)
 }
 
@@ -831,28 +1428,41 @@ async fn start_kafka(
     tx: tokio::sync::broadcast::Sender<HistoryEntry>,
     message_broker_url: String,
     pdf_base: String,
+    readiness: Readiness,
+    telemetry: Telemetry,
 ) {
     if message_broker_url.trim().is_empty() {
         warn!("MESSAGE_BROKER_URL empty; Kafka consumer disabled");
+        readiness.mark_ready("kafka");
         return;
     }
 
-    let consumer: StreamConsumer = match ClientConfig::new()
-        .set("group.id", "history-service")
-        .set("bootstrap.servers", &message_broker_url)
-        .create()
-    {
-        Ok(c) => c,
-        Err(e) => {
-            error!(%e, "failed to create kafka consumer");
-            return;
-        }
-    };
-
-    if let Err(e) = consumer.subscribe(&["pdf-merged", "pipeline-result"]) {
-        error!(%e, "failed to subscribe to topics");
-        return;
-    }
+    let topics = ["pdf-merged", "pipeline-result"];
+    // Labeling-Regeln für Pipelines ohne eigene label_rules
+    let default_label_rules: Option<LabelRules> = std::env::var("RESULT_LABEL_RULES")
+        .ok()
+        .filter(|v| !v.trim().is_empty())
+        .and_then(|v| {
+            serde_json::from_str(&v)
+                .map_err(|e| warn!(%e, "ignoring invalid RESULT_LABEL_RULES"))
+                .ok()
+        });
+    let backoff = Backoff::from_env();
+    // Broker-Erreichbarkeit prüfen (Topics anlegen), bevor wir "ready" melden
+    retry_with_backoff("kafka", backoff, || {
+        shared::kafka::ensure_topics(&message_broker_url, &topics)
+    })
+    .await;
+    let consumer: StreamConsumer = retry_with_backoff("kafka-consumer", backoff, || async {
+        let consumer: StreamConsumer = ClientConfig::new()
+            .set("group.id", "history-service")
+            .set("bootstrap.servers", &message_broker_url)
+            .create()?;
+        consumer.subscribe(&topics)?;
+        Ok::<_, rdkafka::error::KafkaError>(consumer)
+    })
+    .await;
+    readiness.mark_ready("kafka");
 
     info!("kafka consumer running");
     loop {
@@ -863,6 +1473,8 @@ async fn start_kafka(
                     match m.topic() {
                         "pdf-merged" => {
                             match serde_json::from_str::<shared::dto::PdfUploaded>(payload) {
+                                // Erneute OCR einzelner Seiten startet keinen Lauf
+                                Ok(data) if data.reocr.is_some() => {}
                                 Ok(data) => {
                                     let ts = Utc::now();
                                     let pdf_url = format!("{}/pdf/{}", pdf_base, data.pdf_id);
@@ -887,10 +1499,17 @@ async fn start_kafka(
                                                 result: None,
                                                 pdf_url,
                                                 timestamp: ts,
-                                                status: "running".into(),
+                                                status: RunStatus::Running.to_string(),
                                                 score: None,
                                                 result_label: None,
                                                 tenant_name: None,
+                                                source_files: vec![],
+                                                folder_name: None,
+                                                job_label: None,
+                                                external_ref: None,
+                                                tags: vec![],
+                                                upload_note: None,
+                                                run_note: None,
                                             };
                                             let _ = tx.send(fallback);
                                         }
@@ -915,6 +1534,30 @@ async fn start_kafka(
                                         .and_then(|s| DateTime::parse_from_rfc3339(s).ok())
                                         .map(|dt| dt.with_timezone(&Utc));
 
+                                    let result_label = label_rules_db(
+                                        &db,
+                                        data.pipeline_id,
+                                        default_label_rules.as_ref(),
+                                    )
+                                    .await
+                                    .and_then(|rules| {
+                                        rules.evaluate(
+                                            data.overall_score,
+                                            &data.final_decisions.clone().unwrap_or_default(),
+                                            data.contested,
+                                        )
+                                    })
+                                    .map(|label| label.to_string());
+                                    if result_label.as_deref() == Some("approved") {
+                                        telemetry.spawn_emit(
+                                            EventKind::ReviewApproved,
+                                            json!({
+                                                "pipeline": data.pipeline_id,
+                                                "label": "approved",
+                                            }),
+                                        );
+                                    }
+
                                     let mut entry = HistoryEntry {
                                         id: 0,
                                         pdf_id: data.pdf_id,
@@ -923,10 +1566,17 @@ async fn start_kafka(
                                         result: Some(value.clone()),
                                         pdf_url: format!("{}/pdf/{}", pdf_base, data.pdf_id),
                                         timestamp: finished_at_ts.unwrap_or_else(Utc::now),
-                                        status: "completed".into(),
+                                        status: RunStatus::Completed.to_string(),
                                         score: data.overall_score.map(|f| f as f64),
-                                        result_label: None,
+                                        result_label,
                                         tenant_name: None,
+                                        source_files: vec![],
+                                        folder_name: None,
+                                        job_label: data.job_label.clone(),
+                                        external_ref: data.external_ref.clone(),
+                                        tags: vec![],
+                                        upload_note: None,
+                                        run_note: None,
                                     };
 
                                     let id = insert_result_db(
@@ -938,11 +1588,27 @@ async fn start_kafka(
                                     .await;
                                     entry.id = id;
                                     if id > 0 {
-                                        if let Some(updated) = fetch_entry_by_id(&db, id).await {
-                                            let _ = tx.send(updated);
-                                        } else {
-                                            let _ = tx.send(entry);
+                                        // Tags kommen erst über die View in den Eintrag
+                                        let updated = fetch_entry_by_id(&db, id).await;
+                                        let tags = updated.as_ref().map_or(&[][..], |u| &u.tags);
+                                        let record = sinks::record(
+                                            id,
+                                            &data,
+                                            entry.result_label.as_deref(),
+                                            tags,
+                                        );
+                                        if let Err(e) = sinks::enqueue(
+                                            &db,
+                                            id,
+                                            data.pdf_id,
+                                            data.run_id,
+                                            &record,
+                                        )
+                                        .await
+                                        {
+                                            error!(%e, id, "failed to queue result sink deliveries");
                                         }
+                                        let _ = tx.send(updated.unwrap_or(entry));
                                     } else {
                                         let _ = tx.send(entry);
                                     }
@@ -978,30 +1644,69 @@ async fn main() -> std::io::Result<()> {
 
     // NoTLS + Auto-Reconnect
     let db = Db::new(settings.database_url.clone()).await;
+    let readiness = Readiness::new(&["postgres", "kafka"]);
 
-    // Schema sicherstellen (inkl. started_at/finished_at Backfill)
-    ensure_schema_db(&db).await;
+    // Schema sicherstellen (inkl. started_at/finished_at Backfill), sobald die DB erreichbar ist
+    {
+        let db = db.clone();
+        let readiness = readiness.clone();
+        actix_web::rt::spawn(async move {
+            retry_with_backoff("postgres", Backoff::from_env(), || db.ping()).await;
+            ensure_schema_db(&db).await;
+            readiness.mark_ready("postgres");
+        });
+    }
 
-    let (tx, _) = tokio::sync::broadcast::channel(100);
+    // Puffergröße pro Client; wer zurückfällt, bekommt "lagged" + Resync aus der DB
+    let ws_buffer: usize = std::env::var("WS_BUFFER")
+        .ok()
+        .and_then(|s| s.parse().ok())
+        .filter(|n| *n > 0)
+        .unwrap_or(100);
+    let (tx, _) = tokio::sync::broadcast::channel(ws_buffer);
     let pdf_base =
         std::env::var("PDF_INGEST_URL").unwrap_or_else(|_| "http://localhost:8081".into());
+    let master_key = match MasterKey::from_env() {
+        Ok(key) => Some(key),
+        Err(e) => {
+            warn!(%e, "result sink secrets unavailable");
+            None
+        }
+    };
     let state = web::Data::new(AppState {
         db: db.clone(),
         tx: tx.clone(),
         pdf_base: pdf_base.clone(),
+        readiness: readiness.clone(),
+        ws: WsConfig::from_env(),
+        admin_token: std::env::var("ADMIN_TOKEN").ok().filter(|t| !t.is_empty()),
+        master_key: master_key.clone(),
     });
 
+    // Zustellung an externe Ergebnis-Senken der Mandanten
+    actix_web::rt::spawn(sinks::run_worker(
+        db.clone(),
+        master_key,
+        sinks::WorkerConfig::from_env(),
+    ));
+
     // Kafka-Consumer
     {
         let db_for_kafka = db.clone();
         let tx_for_kafka = tx.clone();
         let pdf_base_for_kafka = pdf_base.clone();
         let broker_url = settings.message_broker_url.clone();
+        let telemetry = Telemetry::from_env("history-service", &broker_url).unwrap_or_else(|e| {
+            warn!(%e, "telemetry disabled");
+            Telemetry::disabled()
+        });
         actix_web::rt::spawn(start_kafka(
             db_for_kafka,
             tx_for_kafka,
             broker_url,
             pdf_base_for_kafka,
+            readiness.clone(),
+            telemetry,
         ));
     }
 
@@ -1009,19 +1714,39 @@ async fn main() -> std::io::Result<()> {
         .ok()
         .and_then(|s| s.parse().ok())
         .unwrap_or(8090);
+    let cors = CorsSettings::from_env();
     HttpServer::new(move || {
         App::new()
-            .wrap(Cors::permissive())
+            .wrap(cors.actix())
             .app_data(state.clone())
             .route("/classifications", web::get().to(classifications))
             .route("/analyses", web::get().to(analyses))
+            .route("/history", web::get().to(history))
             .route("/results/{id}", web::get().to(result))
+            .route("/timeline", web::get().to(timeline))
+            .route("/lineage", web::get().to(lineage))
+            .route("/admin/schema", web::get().to(schema))
             // WebSocket (Root)
             .route("/", web::get().to(ws_index))
             .route("/health", web::get().to(health))
+            .route("/readyz", web::get().to(readyz))
             // NEU: Tenants-API
             .route("/tenants", web::get().to(tenants_list))
             .route("/tenants", web::post().to(tenants_create))
+            .route("/tenants/{id}/sinks", web::get().to(sinks_list))
+            .route("/tenants/{id}/sinks", web::post().to(sinks_create))
+            .route(
+                "/tenants/{id}/sinks/{sink_id}",
+                web::delete().to(sinks_delete),
+            )
+            .route(
+                "/tenants/{id}/sinks/{sink_id}/deliveries",
+                web::get().to(sink_deliveries),
+            )
+            .route(
+                "/tenants/{id}/sinks/{sink_id}/deliveries/{delivery_id}/retry",
+                web::post().to(sink_delivery_retry),
+            )
     })
     .bind(("0.0.0.0", port))?
     .run()
@@ -0,0 +1,730 @@
+//! Delivery of finished run results into customer systems (external sinks).
+//!
+//! A tenant can register sinks in `result_sinks`: a table in a customer
+//! Postgres (`kind = postgres`, DSN sealed with `CREDENTIALS_MASTER_KEY`) or an
+//! HTTP endpoint (`kind = http`, optional sealed `Authorization` value). Every
+//! completed result of a PDF uploaded for the tenant is queued per sink in
+//! `result_sink_deliveries`; a background worker delivers the queue, retries
+//! with exponential backoff and keeps status, attempts and the last error.
+//!
+//! The delivered record has the fields `analysis_id`, `run_id`, `pdf_id`,
+//! `pipeline_id`, `status`, `overall_score`, `result_label`, `contested`,
+//! `job_label`, `external_ref`, `tags` (operator tags of run and upload),
+//! `finished_at`, `fields` (customer fields of the pipeline's output mapping)
+//! and `result` (full result). The `mapping` of a
+//! sink picks values by dotted path, e.g.
+//! `[{"column": "policy_no", "path": "fields.Policennummer"}]`.
+
+use std::time::Duration;
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Map, Value};
+use shared::dto::PipelineRunResult;
+use shared::envelope::{MasterKey, SealedSecret};
+use shared::startup::Backoff;
+use tokio_postgres::error::SqlState;
+use tracing::{error, info, warn};
+use uuid::Uuid;
+
+use crate::Db;
+
+/// Idempotent DDL (mirrors `migrations/0032_result_sinks.sql`).
+pub const CREATE_TABLES_SQL: [&str; 3] = [
+    "CREATE TABLE IF NOT EXISTS result_sinks (
+        id SERIAL PRIMARY KEY,
+        tenant_id UUID NOT NULL REFERENCES tenants(id) ON DELETE CASCADE,
+        name TEXT NOT NULL,
+        kind TEXT NOT NULL,
+        target TEXT NOT NULL,
+        secret JSONB,
+        mapping JSONB NOT NULL DEFAULT '[]'::jsonb,
+        conflict_key TEXT[] NOT NULL DEFAULT '{}',
+        enabled BOOLEAN NOT NULL DEFAULT TRUE,
+        created_at TIMESTAMPTZ NOT NULL DEFAULT now(),
+        UNIQUE (tenant_id, name)
+    )",
+    "CREATE TABLE IF NOT EXISTS result_sink_deliveries (
+        id BIGSERIAL PRIMARY KEY,
+        sink_id INTEGER NOT NULL REFERENCES result_sinks(id) ON DELETE CASCADE,
+        analysis_id INTEGER NOT NULL,
+        run_id UUID,
+        payload JSONB NOT NULL,
+        status TEXT NOT NULL DEFAULT 'pending',
+        attempts INTEGER NOT NULL DEFAULT 0,
+        last_error TEXT,
+        next_attempt_at TIMESTAMPTZ NOT NULL DEFAULT now(),
+        delivered_at TIMESTAMPTZ,
+        created_at TIMESTAMPTZ NOT NULL DEFAULT now(),
+        UNIQUE (sink_id, analysis_id)
+    )",
+    "CREATE INDEX IF NOT EXISTS idx_result_sink_deliveries_due
+        ON result_sink_deliveries (next_attempt_at) WHERE status = 'pending'",
+];
+
+/// Longest wait between two attempts.
+const MAX_RETRY_DELAY: Duration = Duration::from_secs(3600);
+/// Deliveries claimed per worker round.
+const BATCH_SIZE: i64 = 20;
+/// Timeout of one delivery (connect + write).
+const DELIVERY_TIMEOUT: Duration = Duration::from_secs(30);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SinkKind {
+    Postgres,
+    Http,
+}
+
+impl SinkKind {
+    fn as_str(self) -> &'static str {
+        match self {
+            SinkKind::Postgres => "postgres",
+            SinkKind::Http => "http",
+        }
+    }
+
+    fn parse(raw: &str) -> Option<Self> {
+        match raw {
+            "postgres" => Some(SinkKind::Postgres),
+            "http" => Some(SinkKind::Http),
+            _ => None,
+        }
+    }
+}
+
+/// Target column (or JSON field for HTTP) and dotted path into the record.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SinkColumn {
+    pub column: String,
+    pub path: String,
+}
+
+/// Body of `POST /tenants/{id}/sinks`.
+#[derive(Debug, Deserialize)]
+pub struct SinkInput {
+    pub name: String,
+    pub kind: SinkKind,
+    /// `schema.table` for Postgres, URL for HTTP.
+    pub target: String,
+    /// Postgres DSN or HTTP `Authorization` value; stored encrypted.
+    #[serde(default)]
+    pub secret: Option<String>,
+    #[serde(default)]
+    pub mapping: Vec<SinkColumn>,
+    /// Postgres columns of a unique constraint; redeliveries update the row.
+    #[serde(default)]
+    pub conflict_key: Vec<String>,
+    #[serde(default = "default_enabled")]
+    pub enabled: bool,
+}
+
+fn default_enabled() -> bool {
+    true
+}
+
+/// Sink as returned by the API; never contains the secret.
+#[derive(Debug, Serialize)]
+pub struct SinkSummary {
+    pub id: i32,
+    pub tenant_id: Uuid,
+    pub name: String,
+    pub kind: String,
+    pub target: String,
+    pub has_secret: bool,
+    pub mapping: Value,
+    pub conflict_key: Vec<String>,
+    pub enabled: bool,
+    pub created_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct Delivery {
+    pub id: i64,
+    pub analysis_id: i32,
+    pub run_id: Option<Uuid>,
+    pub status: String,
+    pub attempts: i32,
+    pub last_error: Option<String>,
+    pub next_attempt_at: DateTime<Utc>,
+    pub delivered_at: Option<DateTime<Utc>>,
+    pub created_at: DateTime<Utc>,
+}
+
+impl SinkInput {
+    /// Checks target, identifiers and mapping before the sink is stored.
+    pub fn validate(&self) -> Result<(), String> {
+        if self.name.trim().is_empty() {
+            return Err("name must not be empty".into());
+        }
+        for col in &self.mapping {
+            if col.path.trim().is_empty() {
+                return Err(format!("mapping of {} has no path", col.column));
+            }
+        }
+        match self.kind {
+            SinkKind::Postgres => {
+                if self.secret.as_deref().unwrap_or("").trim().is_empty() {
+                    return Err("postgres sinks need the DSN as secret".into());
+                }
+                if self.mapping.is_empty() {
+                    return Err("postgres sinks need a mapping".into());
+                }
+                let columns: Vec<&str> = self.mapping.iter().map(|c| c.column.as_str()).collect();
+                if let Some(key) = self
+                    .conflict_key
+                    .iter()
+                    .find(|k| !columns.contains(&k.as_str()))
+                {
+                    return Err(format!("conflict key {key} is not a mapped column"));
+                }
+                insert_sql(&self.target, &columns, &self.conflict_key).map(|_| ())
+            }
+            SinkKind::Http => {
+                if self.target.starts_with("http://") || self.target.starts_with("https://") {
+                    Ok(())
+                } else {
+                    Err("http sinks need an http(s) URL as target".into())
+                }
+            }
+        }
+    }
+}
+
+/// Stores a sink; the secret is sealed with `master`.
+pub async fn create(
+    db: &Db,
+    tenant_id: Uuid,
+    input: &SinkInput,
+    master: Option<&MasterKey>,
+) -> Result<Result<SinkSummary, String>, tokio_postgres::Error> {
+    if let Err(msg) = input.validate() {
+        return Ok(Err(msg));
+    }
+    let secret = match input.secret.as_deref().filter(|s| !s.trim().is_empty()) {
+        Some(plain) => {
+            let Some(master) = master else {
+                return Ok(Err("secrets need CREDENTIALS_MASTER_KEY".into()));
+            };
+            match master.seal(plain) {
+                Ok(sealed) => Some(serde_json::to_value(sealed).unwrap_or_default()),
+                Err(e) => return Ok(Err(e.to_string())),
+            }
+        }
+        None => None,
+    };
+    let mapping = serde_json::to_value(&input.mapping).unwrap_or_default();
+    let row = db
+        .query_opt(
+            "INSERT INTO result_sinks (tenant_id, name, kind, target, secret, mapping, conflict_key, enabled)
+             SELECT id, $2, $3, $4, $5, $6, $7, $8 FROM tenants WHERE id = $1
+             RETURNING id, created_at",
+            &[
+                &tenant_id,
+                &input.name.trim(),
+                &input.kind.as_str(),
+                &input.target.trim(),
+                &secret,
+                &mapping,
+                &input.conflict_key,
+                &input.enabled,
+            ],
+        )
+        .await;
+    let row = match row {
+        Ok(Some(row)) => row,
+        Ok(None) => return Ok(Err("unknown tenant".into())),
+        Err(e) if e.code() == Some(&SqlState::UNIQUE_VIOLATION) => {
+            return Ok(Err(format!("sink {} already exists", input.name.trim())));
+        }
+        Err(e) => return Err(e),
+    };
+    Ok(Ok(SinkSummary {
+        id: row.get(0),
+        tenant_id,
+        name: input.name.trim().to_string(),
+        kind: input.kind.as_str().to_string(),
+        target: input.target.trim().to_string(),
+        has_secret: secret.is_some(),
+        mapping,
+        conflict_key: input.conflict_key.clone(),
+        enabled: input.enabled,
+        created_at: row.get(1),
+    }))
+}
+
+pub async fn list(db: &Db, tenant_id: Uuid) -> Result<Vec<SinkSummary>, tokio_postgres::Error> {
+    let rows = db
+        .query(
+            "SELECT id, tenant_id, name, kind, target, secret IS NOT NULL, mapping, conflict_key,
+                    enabled, created_at
+               FROM result_sinks WHERE tenant_id = $1 ORDER BY name",
+            &[&tenant_id],
+        )
+        .await?;
+    Ok(rows
+        .into_iter()
+        .map(|r| SinkSummary {
+            id: r.get(0),
+            tenant_id: r.get(1),
+            name: r.get(2),
+            kind: r.get(3),
+            target: r.get(4),
+            has_secret: r.get(5),
+            mapping: r.get(6),
+            conflict_key: r.get(7),
+            enabled: r.get(8),
+            created_at: r.get(9),
+        })
+        .collect())
+}
+
+/// Deletes a sink with its queue; `false` if it does not belong to the tenant.
+pub async fn delete(db: &Db, tenant_id: Uuid, sink_id: i32) -> Result<bool, tokio_postgres::Error> {
+    let n = db
+        .execute(
+            "DELETE FROM result_sinks WHERE id = $1 AND tenant_id = $2",
+            &[&sink_id, &tenant_id],
+        )
+        .await?;
+    Ok(n > 0)
+}
+
+pub async fn deliveries(
+    db: &Db,
+    tenant_id: Uuid,
+    sink_id: i32,
+    status: Option<&str>,
+    limit: i64,
+) -> Result<Vec<Delivery>, tokio_postgres::Error> {
+    let rows = db
+        .query(
+            "SELECT d.id, d.analysis_id, d.run_id, d.status, d.attempts, d.last_error,
+                    d.next_attempt_at, d.delivered_at, d.created_at
+               FROM result_sink_deliveries d
+               JOIN result_sinks s ON s.id = d.sink_id
+              WHERE d.sink_id = $1 AND s.tenant_id = $2
+                AND ($3::text IS NULL OR d.status = $3)
+              ORDER BY d.created_at DESC
+              LIMIT $4",
+            &[&sink_id, &tenant_id, &status, &limit],
+        )
+        .await?;
+    Ok(rows
+        .into_iter()
+        .map(|r| Delivery {
+            id: r.get(0),
+            analysis_id: r.get(1),
+            run_id: r.get(2),
+            status: r.get(3),
+            attempts: r.get(4),
+            last_error: r.get(5),
+            next_attempt_at: r.get(6),
+            delivered_at: r.get(7),
+            created_at: r.get(8),
+        })
+        .collect())
+}
+
+/// Queues a delivery again (e.g. after fixing the target); `false` if unknown.
+pub async fn retry(
+    db: &Db,
+    tenant_id: Uuid,
+    sink_id: i32,
+    delivery_id: i64,
+) -> Result<bool, tokio_postgres::Error> {
+    let n = db
+        .execute(
+            "UPDATE result_sink_deliveries d
+                SET status = 'pending', attempts = 0, next_attempt_at = now()
+               FROM result_sinks s
+              WHERE d.id = $1 AND d.sink_id = $2 AND s.id = d.sink_id AND s.tenant_id = $3",
+            &[&delivery_id, &sink_id, &tenant_id],
+        )
+        .await?;
+    Ok(n > 0)
+}
+
+/// Record delivered to the sinks for one stored result.
+pub fn record(
+    analysis_id: i32,
+    result: &PipelineRunResult,
+    result_label: Option<&str>,
+    tags: &[String],
+) -> Value {
+    json!({
+        "analysis_id": analysis_id,
+        "run_id": result.run_id,
+        "pdf_id": result.pdf_id,
+        "pipeline_id": result.pipeline_id,
+        "status": result.status.map(|s| s.to_string()),
+        "overall_score": result.overall_score,
+        "result_label": result_label,
+        "contested": result.contested,
+        "job_label": result.job_label,
+        "external_ref": result.external_ref,
+        "tags": tags,
+        "finished_at": result.finished_at,
+        "fields": result.output.as_ref().map(|o| Value::Object(o.fields.clone())),
+        "result": result,
+    })
+}
+
+/// Queues `record` for every enabled sink of the tenant that uploaded the PDF.
+pub async fn enqueue(
+    db: &Db,
+    analysis_id: i32,
+    pdf_id: i32,
+    run_id: Option<Uuid>,
+    record: &Value,
+) -> Result<u64, tokio_postgres::Error> {
+    db.execute(
+        "INSERT INTO result_sink_deliveries (sink_id, analysis_id, run_id, payload)
+         SELECT s.id, $1, $2, $3 FROM result_sinks s
+          WHERE s.enabled
+            AND s.tenant_id = (SELECT tenant_id FROM uploads WHERE pdf_id = $4 ORDER BY id DESC LIMIT 1)
+         ON CONFLICT (sink_id, analysis_id) DO UPDATE
+            SET payload = EXCLUDED.payload, run_id = EXCLUDED.run_id, status = 'pending',
+                attempts = 0, last_error = NULL, next_attempt_at = now(), delivered_at = NULL",
+        &[&analysis_id, &run_id, record, &pdf_id],
+    )
+    .await
+}
+
+/// Worker settings (`SINK_POLL_SECS`, `SINK_MAX_ATTEMPTS`, `SINK_RETRY_BASE_SECS`).
+#[derive(Debug, Clone, Copy)]
+pub struct WorkerConfig {
+    pub poll: Duration,
+    pub max_attempts: i32,
+    /// Doubles per failed attempt, up to [`MAX_RETRY_DELAY`].
+    pub retry: Backoff,
+}
+
+impl WorkerConfig {
+    pub fn from_env() -> Self {
+        let secs = |key: &str, default: u64| {
+            std::env::var(key)
+                .ok()
+                .and_then(|v| v.parse::<u64>().ok())
+                .filter(|v| *v > 0)
+                .unwrap_or(default)
+        };
+        Self {
+            poll: Duration::from_secs(secs("SINK_POLL_SECS", 10)),
+            max_attempts: secs("SINK_MAX_ATTEMPTS", 8) as i32,
+            retry: Backoff {
+                initial: Duration::from_secs(secs("SINK_RETRY_BASE_SECS", 30)).min(MAX_RETRY_DELAY),
+                max: MAX_RETRY_DELAY,
+            },
+        }
+    }
+}
+
+struct PendingDelivery {
+    id: i64,
+    payload: Value,
+    attempts: i32,
+    kind: String,
+    target: String,
+    secret: Option<Value>,
+    mapping: Value,
+    conflict_key: Vec<String>,
+}
+
+/// Delivers due entries until the process ends.
+pub async fn run_worker(db: std::sync::Arc<Db>, master: Option<MasterKey>, config: WorkerConfig) {
+    let http = match reqwest::Client::builder().timeout(DELIVERY_TIMEOUT).build() {
+        Ok(client) => client,
+        Err(e) => {
+            error!(%e, "result sinks disabled: http client unavailable");
+            return;
+        }
+    };
+    info!(?config, "result sink worker running");
+    loop {
+        match claim(&db).await {
+            Ok(batch) => {
+                for delivery in batch {
+                    let outcome = deliver(&http, master.as_ref(), &delivery).await;
+                    if let Err(e) = finish(&db, &config, &delivery, outcome).await {
+                        error!(%e, id = delivery.id, "result sink status update failed");
+                    }
+                }
+            }
+            // Tabelle fehlt (Schema noch nicht angelegt) oder DB weg: nächste Runde
+            Err(e) => warn!(%e, "result sink queue not readable"),
+        }
+        tokio::time::sleep(config.poll).await;
+    }
+}
+
+/// Takes due deliveries; the lease keeps other replicas from sending them too.
+async fn claim(db: &Db) -> Result<Vec<PendingDelivery>, tokio_postgres::Error> {
+    let rows = db
+        .query(
+            "UPDATE result_sink_deliveries d
+                SET next_attempt_at = now() + interval '5 minutes'
+               FROM result_sinks s
+              WHERE s.id = d.sink_id
+                AND d.id IN (
+                    SELECT d2.id FROM result_sink_deliveries d2
+                      JOIN result_sinks s2 ON s2.id = d2.sink_id
+                     WHERE d2.status = 'pending' AND d2.next_attempt_at <= now() AND s2.enabled
+                     ORDER BY d2.next_attempt_at
+                     LIMIT $1
+                     FOR UPDATE OF d2 SKIP LOCKED)
+          RETURNING d.id, d.payload, d.attempts, s.kind, s.target, s.secret, s.mapping, s.conflict_key",
+            &[&BATCH_SIZE],
+        )
+        .await?;
+    Ok(rows
+        .into_iter()
+        .map(|r| PendingDelivery {
+            id: r.get(0),
+            payload: r.get(1),
+            attempts: r.get(2),
+            kind: r.get(3),
+            target: r.get(4),
+            secret: r.get(5),
+            mapping: r.get(6),
+            conflict_key: r.get(7),
+        })
+        .collect())
+}
+
+async fn finish(
+    db: &Db,
+    config: &WorkerConfig,
+    delivery: &PendingDelivery,
+    outcome: Result<(), String>,
+) -> Result<u64, tokio_postgres::Error> {
+    let attempts = delivery.attempts + 1;
+    match outcome {
+        Ok(()) => {
+            info!(id = delivery.id, attempts, "result delivered to sink");
+            db.execute(
+                "UPDATE result_sink_deliveries
+                    SET status = 'delivered', attempts = $2, last_error = NULL, delivered_at = now()
+                  WHERE id = $1",
+                &[&delivery.id, &attempts],
+            )
+            .await
+        }
+        Err(message) => {
+            let status = if attempts >= config.max_attempts {
+                "failed"
+            } else {
+                "pending"
+            };
+            let delay = config.retry.after_failures(attempts).as_secs_f64();
+            warn!(id = delivery.id, attempts, status, error = %message, "result sink delivery failed");
+            db.execute(
+                "UPDATE result_sink_deliveries
+                    SET status = $2, attempts = $3, last_error = $4,
+                        next_attempt_at = now() + make_interval(secs => $5)
+                  WHERE id = $1",
+                &[&delivery.id, &status, &attempts, &message, &delay],
+            )
+            .await
+        }
+    }
+}
+
+async fn deliver(
+    http: &reqwest::Client,
+    master: Option<&MasterKey>,
+    delivery: &PendingDelivery,
+) -> Result<(), String> {
+    let mapping: Vec<SinkColumn> =
+        serde_json::from_value(delivery.mapping.clone()).map_err(|e| format!("mapping: {e}"))?;
+    let secret = match &delivery.secret {
+        Some(raw) => {
+            let sealed: SealedSecret =
+                serde_json::from_value(raw.clone()).map_err(|e| format!("secret: {e}"))?;
+            let master = master.ok_or("CREDENTIALS_MASTER_KEY missing")?;
+            Some(master.open(&sealed).map_err(|e| e.to_string())?)
+        }
+        None => None,
+    };
+
+    match SinkKind::parse(&delivery.kind) {
+        Some(SinkKind::Postgres) => {
+            let dsn = secret.ok_or("postgres sink without DSN")?;
+            let row = map_record(&mapping, &delivery.payload);
+            let columns: Vec<&str> = mapping.iter().map(|c| c.column.as_str()).collect();
+            let sql = insert_sql(&delivery.target, &columns, &delivery.conflict_key)?;
+            tokio::time::timeout(DELIVERY_TIMEOUT, write_postgres(&dsn, &sql, row))
+                .await
+                .map_err(|_| "timeout writing to customer postgres".to_string())?
+        }
+        Some(SinkKind::Http) => {
+            let body = if mapping.is_empty() {
+                delivery.payload.clone()
+            } else {
+                Value::Object(map_record(&mapping, &delivery.payload))
+            };
+            let mut request = http
+                .post(&delivery.target)
+                .header("X-Regress-Delivery", delivery.id.to_string())
+                .json(&body);
+            if let Some(auth) = secret {
+                request = request.header(reqwest::header::AUTHORIZATION, auth);
+            }
+            let response = request.send().await.map_err(|e| e.to_string())?;
+            if response.status().is_success() {
+                Ok(())
+            } else {
+                Err(format!("http status {}", response.status()))
+            }
+        }
+        None => Err(format!("unknown sink kind {}", delivery.kind)),
+    }
+}
+
+async fn write_postgres(dsn: &str, sql: &str, row: Map<String, Value>) -> Result<(), String> {
+    let connector = native_tls::TlsConnector::new().map_err(|e| e.to_string())?;
+    let tls = postgres_native_tls::MakeTlsConnector::new(connector);
+    let (client, connection) = tokio_postgres::connect(dsn, tls)
+        .await
+        .map_err(|e| format!("connect: {e}"))?;
+    tokio::spawn(async move {
+        if let Err(e) = connection.await {
+            warn!(%e, "customer postgres connection ended");
+        }
+    });
+    client
+        .execute(sql, &[&Value::Object(row)])
+        .await
+        .map(|_| ())
+        .map_err(|e| format!("insert: {e}"))
+}
+
+/// Looks up a dotted path (`fields.policy_no`, `result.extraction.0.value`).
+fn resolve_path<'a>(record: &'a Value, path: &str) -> Option<&'a Value> {
+    path.split('.')
+        .filter(|p| !p.is_empty())
+        .try_fold(record, |value, segment| match value {
+            Value::Object(obj) => obj.get(segment),
+            Value::Array(items) => segment.parse::<usize>().ok().and_then(|i| items.get(i)),
+            _ => None,
+        })
+}
+
+/// Row of one record under the mapping; missing paths become `null`.
+fn map_record(mapping: &[SinkColumn], record: &Value) -> Map<String, Value> {
+    mapping
+        .iter()
+        .map(|c| {
+            let value = resolve_path(record, &c.path)
+                .cloned()
+                .unwrap_or(Value::Null);
+            (c.column.clone(), value)
+        })
+        .collect()
+}
+
+fn quote_ident(raw: &str) -> Result<String, String> {
+    let mut chars = raw.chars();
+    let valid = chars
+        .next()
+        .is_some_and(|c| c.is_ascii_alphabetic() || c == '_')
+        && chars.all(|c| c.is_ascii_alphanumeric() || c == '_');
+    if valid && raw.len() <= 63 {
+        Ok(format!("\"{raw}\""))
+    } else {
+        Err(format!("invalid identifier {raw:?}"))
+    }
+}
+
+/// Insert of one JSON row (`$1`); Postgres casts every value to its column type.
+fn insert_sql(table: &str, columns: &[&str], conflict_key: &[String]) -> Result<String, String> {
+    let table = table
+        .split('.')
+        .map(quote_ident)
+        .collect::<Result<Vec<_>, _>>()?;
+    if table.is_empty() || table.len() > 2 {
+        return Err("target must be table or schema.table".into());
+    }
+    let table = table.join(".");
+    let cols = columns
+        .iter()
+        .map(|c| quote_ident(c))
+        .collect::<Result<Vec<_>, _>>()?
+        .join(", ");
+    let mut sql = format!(
+        "INSERT INTO {table} ({cols}) SELECT {cols} FROM json_populate_record(NULL::{table}, $1::json)"
+    );
+    if !conflict_key.is_empty() {
+        let keys = conflict_key
+            .iter()
+            .map(|k| quote_ident(k))
+            .collect::<Result<Vec<_>, _>>()?;
+        let updates: Vec<String> = columns
+            .iter()
+            .filter(|c| !conflict_key.iter().any(|k| k == *c))
+            .map(|c| format!("\"{c}\" = EXCLUDED.\"{c}\""))
+            .collect();
+        if updates.is_empty() {
+            sql.push_str(&format!(" ON CONFLICT ({}) DO NOTHING", keys.join(", ")));
+        } else {
+            sql.push_str(&format!(
+                " ON CONFLICT ({}) DO UPDATE SET {}",
+                keys.join(", "),
+                updates.join(", ")
+            ));
+        }
+    }
+    Ok(sql)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn maps_record_and_builds_upsert() {
+        let record = json!({
+            "analysis_id": 7,
+            "overall_score": 0.8,
+            "fields": {"Policennummer": "P-1"},
+            "result": {"extraction": [{"value": "x"}]}
+        });
+        let mapping = vec![
+            SinkColumn {
+                column: "analysis_id".into(),
+                path: "analysis_id".into(),
+            },
+            SinkColumn {
+                column: "policy_no".into(),
+                path: "fields.Policennummer".into(),
+            },
+            SinkColumn {
+                column: "first".into(),
+                path: "result.extraction.0.value".into(),
+            },
+            SinkColumn {
+                column: "missing".into(),
+                path: "fields.nope".into(),
+            },
+        ];
+        let row = map_record(&mapping, &record);
+        assert_eq!(row["policy_no"], json!("P-1"));
+        assert_eq!(row["first"], json!("x"));
+        assert_eq!(row["missing"], Value::Null);
+
+        let sql = insert_sql(
+            "dwh.results",
+            &["analysis_id", "policy_no"],
+            &["analysis_id".into()],
+        )
+        .unwrap();
+        assert_eq!(
+            sql,
+            "INSERT INTO \"dwh\".\"results\" (\"analysis_id\", \"policy_no\") \
+             SELECT \"analysis_id\", \"policy_no\" FROM json_populate_record(NULL::\"dwh\".\"results\", $1::json) \
+             ON CONFLICT (\"analysis_id\") DO UPDATE SET \"policy_no\" = EXCLUDED.\"policy_no\""
+        );
+        assert!(insert_sql("results; DROP TABLE x", &["a"], &[]).is_err());
+        assert!(insert_sql("results", &["a\"b"], &[]).is_err());
+    }
+}
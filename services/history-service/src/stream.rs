@@ -0,0 +1,203 @@
+//! Snapshot, deltas and flow control of the history WebSocket.
+//!
+//! A new client gets a `snapshot` of the latest `WS_SNAPSHOT_LIMIT` entries
+//! (newest first) and then every changed entry as `update`; older entries are
+//! paged through `GET /history?before_id=<id>` ([`HistoryPage`]). Every
+//! message carries a `seq`. A client that acknowledges messages
+//! (`{"type": "ack", "seq": n}` with the latest `seq` it processed) gets at
+//! most `WS_ACK_WINDOW` unacknowledged messages ([`AckWindow`]); further
+//! updates wait in [`Pending`], where a newer version of an entry replaces the
+//! older one. Clients that never acknowledge are not throttled.
+
+use std::collections::VecDeque;
+
+use serde::Serialize;
+
+use crate::HistoryEntry;
+
+/// Unacknowledged messages of one client.
+#[derive(Debug, Clone, Copy)]
+pub struct AckWindow {
+    size: u64,
+    sent: u64,
+    // None, bis der Client das erste Mal quittiert
+    acked: Option<u64>,
+}
+
+impl AckWindow {
+    pub fn new(size: usize) -> Self {
+        Self {
+            size: size.max(1) as u64,
+            sent: 0,
+            acked: None,
+        }
+    }
+
+    /// Sequence number of the next message.
+    pub fn next_seq(&mut self) -> u64 {
+        self.sent += 1;
+        self.sent
+    }
+
+    /// Records an acknowledgement; stale or unknown numbers are clamped.
+    pub fn ack(&mut self, seq: u64) {
+        let seq = seq.min(self.sent);
+        self.acked = Some(self.acked.map_or(seq, |acked| acked.max(seq)));
+    }
+
+    /// Whether another message may be sent now.
+    pub fn is_open(&self) -> bool {
+        match self.acked {
+            Some(acked) => self.sent - acked < self.size,
+            None => true,
+        }
+    }
+}
+
+/// Updates waiting for a replay or a closed [`AckWindow`], at most one per
+/// entry.
+#[derive(Debug)]
+pub struct Pending {
+    entries: VecDeque<HistoryEntry>,
+    capacity: usize,
+}
+
+impl Pending {
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            entries: VecDeque::new(),
+            capacity: capacity.max(1),
+        }
+    }
+
+    /// Buffers `entry`, replacing an older version of it. `false` when the
+    /// buffer is full; the client then needs a resync from the database.
+    pub fn push(&mut self, entry: HistoryEntry) -> bool {
+        if let Some(slot) = self.entries.iter_mut().find(|e| e.id == entry.id) {
+            *slot = entry;
+            return true;
+        }
+        if self.entries.len() >= self.capacity {
+            return false;
+        }
+        self.entries.push_back(entry);
+        true
+    }
+
+    pub fn pop(&mut self) -> Option<HistoryEntry> {
+        self.entries.pop_front()
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    pub fn clear(&mut self) {
+        self.entries.clear();
+    }
+}
+
+/// One page of the history, newest first; `next_before_id` continues it.
+#[derive(Debug, Serialize)]
+pub struct HistoryPage {
+    pub entries: Vec<HistoryEntry>,
+    pub next_before_id: Option<i32>,
+}
+
+impl HistoryPage {
+    /// Builds the page from up to `limit + 1` rows; the extra row only tells
+    /// that more entries exist.
+    pub fn from_rows(mut entries: Vec<HistoryEntry>, limit: usize) -> Self {
+        let more = entries.len() > limit;
+        entries.truncate(limit);
+        let next_before_id = if more {
+            entries.last().map(|e| e.id)
+        } else {
+            None
+        };
+        Self {
+            entries,
+            next_before_id,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::Utc;
+    use uuid::Uuid;
+
+    fn entry(id: i32, status: &str) -> HistoryEntry {
+        HistoryEntry {
+            id,
+            pdf_id: id,
+            pipeline_id: Uuid::nil(),
+            prompt: None,
+            result: None,
+            pdf_url: String::new(),
+            timestamp: Utc::now(),
+            status: status.into(),
+            score: None,
+            result_label: None,
+            tenant_name: None,
+            source_files: vec![],
+            folder_name: None,
+            job_label: None,
+            external_ref: None,
+            tags: vec![],
+            upload_note: None,
+            run_note: None,
+        }
+    }
+
+    #[test]
+    fn window_throttles_only_acknowledging_clients() {
+        let mut window = AckWindow::new(2);
+        for _ in 0..5 {
+            window.next_seq();
+        }
+        assert!(window.is_open(), "no ack yet, no flow control");
+
+        window.ack(4);
+        assert!(window.is_open());
+        assert_eq!(window.next_seq(), 6);
+        assert!(!window.is_open());
+
+        window.ack(3);
+        assert!(!window.is_open(), "stale ack keeps the window");
+        window.ack(99);
+        assert!(window.is_open(), "ack is clamped to the last sent message");
+    }
+
+    #[test]
+    fn pending_keeps_the_newest_version_per_entry() {
+        let mut pending = Pending::new(2);
+        assert!(pending.push(entry(1, "running")));
+        assert!(pending.push(entry(2, "running")));
+        assert!(pending.push(entry(1, "completed")));
+        assert!(!pending.push(entry(3, "running")));
+        assert_eq!(pending.len(), 2);
+        let first = pending.pop().unwrap();
+        assert_eq!((first.id, first.status.as_str()), (1, "completed"));
+    }
+
+    #[test]
+    fn page_points_to_the_next_older_entries() {
+        let rows = vec![
+            entry(9, "completed"),
+            entry(7, "completed"),
+            entry(4, "completed"),
+        ];
+        let page = HistoryPage::from_rows(rows, 2);
+        assert_eq!(page.entries.len(), 2);
+        assert_eq!(page.next_before_id, Some(7));
+
+        let last = HistoryPage::from_rows(vec![entry(3, "completed")], 2);
+        assert_eq!(last.next_before_id, None);
+    }
+}
@@ -1,12 +1,14 @@
 //! Small service exposing aggregated metrics via HTTP.
 
-use actix_cors::Cors;
 use actix_web::{web, App, HttpResponse, HttpServer, Responder};
 use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
 use shared::config::Settings;
+use shared::cors::CorsSettings;
 use tracing::{error, info};
 
+mod usage;
+
 #[derive(Serialize)]
 /// Response item describing accuracy and cost for a single pipeline run.
 struct Metric {
@@ -248,12 +250,16 @@ async fn main() -> std::io::Result<()> {
     }
 
     let db = web::Data::new(db_client);
+    let prices = web::Data::new(usage::Prices::from_env());
 
+    let cors = CorsSettings::from_env();
     HttpServer::new(move || {
         App::new()
-            .wrap(Cors::permissive())
+            .wrap(cors.actix())
             .app_data(db.clone())
+            .app_data(prices.clone())
             .route("/metrics", web::get().to(metrics))
+            .route("/reports/usage", web::get().to(usage::usage_report))
             .route("/health", web::get().to(health))
     })
     .bind(("0.0.0.0", 8085))?
@@ -0,0 +1,242 @@
+//! Monthly usage report per tenant for billing (`GET /reports/usage`).
+//!
+//! Documents and pages are attributed to the month of the upload, runs and
+//! tokens to the month the run started. The estimated cost uses the prices
+//! from `USAGE_PRICE_PROMPT_PER_1K`, `USAGE_PRICE_COMPLETION_PER_1K` and
+//! `USAGE_PRICE_OCR_PAGE`.
+
+use actix_web::{web, HttpResponse};
+use chrono::{DateTime, Datelike, NaiveDate, Utc};
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use tracing::info;
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+/// Prices used for the cost estimate.
+pub struct Prices {
+    pub prompt_per_1k: f64,
+    pub completion_per_1k: f64,
+    pub ocr_page: f64,
+}
+
+impl Prices {
+    pub fn from_env() -> Self {
+        let price = |key: &str, default: f64| {
+            std::env::var(key)
+                .ok()
+                .and_then(|v| v.trim().parse::<f64>().ok())
+                .unwrap_or(default)
+        };
+        Self {
+            prompt_per_1k: price("USAGE_PRICE_PROMPT_PER_1K", 0.0025),
+            completion_per_1k: price("USAGE_PRICE_COMPLETION_PER_1K", 0.01),
+            ocr_page: price("USAGE_PRICE_OCR_PAGE", 0.0),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+/// Usage of one tenant within the reported month.
+pub struct TenantUsage {
+    pub tenant_id: String,
+    pub tenant_name: String,
+    pub documents: i64,
+    pub pages: i64,
+    pub ocr_pages: i64,
+    pub runs: i64,
+    pub prompt_tokens: i64,
+    pub completion_tokens: i64,
+    pub estimated_cost: f64,
+}
+
+impl TenantUsage {
+    fn apply_prices(&mut self, prices: &Prices) {
+        let cost = self.prompt_tokens as f64 / 1000.0 * prices.prompt_per_1k
+            + self.completion_tokens as f64 / 1000.0 * prices.completion_per_1k
+            + self.ocr_pages as f64 * prices.ocr_page;
+        // Auf Zehntel-Cent runden, damit CSV und JSON identisch sind
+        self.estimated_cost = (cost * 1000.0).round() / 1000.0;
+    }
+}
+
+#[derive(Default, Deserialize)]
+/// Query parameters accepted by `/reports/usage`.
+pub struct UsageQuery {
+    /// `YYYY-MM`; defaults to the current month.
+    month: Option<String>,
+    /// `json` (default) or `csv`.
+    format: Option<String>,
+}
+
+/// `[start, end)` of a `YYYY-MM` month in UTC.
+pub fn month_bounds(month: &str) -> Option<(DateTime<Utc>, DateTime<Utc>)> {
+    let start = NaiveDate::parse_from_str(&format!("{}-01", month.trim()), "%Y-%m-%d").ok()?;
+    let end = if start.month() == 12 {
+        NaiveDate::from_ymd_opt(start.year() + 1, 1, 1)?
+    } else {
+        NaiveDate::from_ymd_opt(start.year(), start.month() + 1, 1)?
+    };
+    Some((
+        start.and_hms_opt(0, 0, 0)?.and_utc(),
+        end.and_hms_opt(0, 0, 0)?.and_utc(),
+    ))
+}
+
+fn csv_field(value: &str) -> String {
+    if value.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value.to_string()
+    }
+}
+
+/// Renders the report as CSV with one line per tenant.
+pub fn to_csv(month: &str, rows: &[TenantUsage]) -> String {
+    let mut out = String::from(
+        "month,tenant_id,tenant_name,documents,pages,ocr_pages,runs,prompt_tokens,completion_tokens,estimated_cost\n",
+    );
+    for r in rows {
+        out.push_str(&format!(
+            "{},{},{},{},{},{},{},{},{},{:.3}\n",
+            month,
+            r.tenant_id,
+            csv_field(&r.tenant_name),
+            r.documents,
+            r.pages,
+            r.ocr_pages,
+            r.runs,
+            r.prompt_tokens,
+            r.completion_tokens,
+            r.estimated_cost
+        ));
+    }
+    out
+}
+
+const USAGE_SQL: &str = "
+    WITH docs AS (
+        SELECT u.tenant_id,
+               COUNT(DISTINCT u.id) AS documents,
+               COUNT(t.page_no) AS pages,
+               COUNT(t.page_no) FILTER (WHERE t.ocr_used) AS ocr_pages
+        FROM uploads u
+        LEFT JOIN pdf_texts t ON t.merged_pdf_id = u.pdf_id
+        WHERE u.created_at >= $1 AND u.created_at < $2 AND u.tenant_id IS NOT NULL
+        GROUP BY u.tenant_id
+    ),
+    runs AS (
+        SELECT u.tenant_id,
+               COUNT(*) AS runs,
+               COALESCE(SUM(r.prompt_tokens), 0)::BIGINT AS prompt_tokens,
+               COALESCE(SUM(r.completion_tokens), 0)::BIGINT AS completion_tokens
+        FROM pipeline_runs r
+        JOIN LATERAL (
+            SELECT tenant_id FROM uploads
+            WHERE pdf_id = r.pdf_id ORDER BY id DESC LIMIT 1
+        ) u ON TRUE
+        WHERE r.started_at >= $1 AND r.started_at < $2
+        GROUP BY u.tenant_id
+    )
+    SELECT t.id::text, t.name,
+           COALESCE(d.documents, 0), COALESCE(d.pages, 0), COALESCE(d.ocr_pages, 0),
+           COALESCE(r.runs, 0), COALESCE(r.prompt_tokens, 0), COALESCE(r.completion_tokens, 0)
+    FROM tenants t
+    LEFT JOIN docs d ON d.tenant_id = t.id
+    LEFT JOIN runs r ON r.tenant_id = t.id
+    WHERE d.tenant_id IS NOT NULL OR r.tenant_id IS NOT NULL
+    ORDER BY t.name";
+
+/// Aggregates the usage of every tenant active in the requested month.
+pub async fn usage_report(
+    db: web::Data<tokio_postgres::Client>,
+    prices: web::Data<Prices>,
+    query: web::Query<UsageQuery>,
+) -> actix_web::Result<HttpResponse> {
+    let month = query
+        .month
+        .clone()
+        .unwrap_or_else(|| Utc::now().format("%Y-%m").to_string());
+    let Some((start, end)) = month_bounds(&month) else {
+        return Ok(HttpResponse::BadRequest().body("month must be YYYY-MM"));
+    };
+    info!(%month, "loading usage report");
+
+    let rows = db
+        .query(USAGE_SQL, &[&start, &end])
+        .await
+        .map_err(actix_web::error::ErrorInternalServerError)?;
+    let tenants: Vec<TenantUsage> = rows
+        .into_iter()
+        .map(|r| {
+            let mut usage = TenantUsage {
+                tenant_id: r.get(0),
+                tenant_name: r.get(1),
+                documents: r.get(2),
+                pages: r.get(3),
+                ocr_pages: r.get(4),
+                runs: r.get(5),
+                prompt_tokens: r.get(6),
+                completion_tokens: r.get(7),
+                estimated_cost: 0.0,
+            };
+            usage.apply_prices(&prices);
+            usage
+        })
+        .collect();
+
+    if query
+        .format
+        .as_deref()
+        .is_some_and(|f| f.eq_ignore_ascii_case("csv"))
+    {
+        return Ok(HttpResponse::Ok()
+            .content_type("text/csv; charset=utf-8")
+            .insert_header((
+                "Content-Disposition",
+                format!("attachment; filename=\"usage-{month}.csv\""),
+            ))
+            .body(to_csv(&month, &tenants)));
+    }
+    Ok(HttpResponse::Ok().json(json!({
+        "month": month,
+        "prices": *prices.get_ref(),
+        "tenants": tenants,
+    })))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn month_bounds_and_csv() {
+        let (start, end) = month_bounds("2025-12").unwrap();
+        assert_eq!(start.to_rfc3339(), "2025-12-01T00:00:00+00:00");
+        assert_eq!(end.to_rfc3339(), "2026-01-01T00:00:00+00:00");
+        assert!(month_bounds("2025-13").is_none());
+        assert!(month_bounds("december").is_none());
+
+        let mut row = TenantUsage {
+            tenant_id: "t1".into(),
+            tenant_name: "Müller, Meier & Co".into(),
+            documents: 2,
+            pages: 10,
+            ocr_pages: 4,
+            runs: 3,
+            prompt_tokens: 12_000,
+            completion_tokens: 1_500,
+            estimated_cost: 0.0,
+        };
+        row.apply_prices(&Prices {
+            prompt_per_1k: 0.0025,
+            completion_per_1k: 0.01,
+            ocr_page: 0.01,
+        });
+        assert_eq!(row.estimated_cost, 0.085);
+        let csv = to_csv("2025-12", &[row]);
+        assert_eq!(
+            csv.lines().nth(1).unwrap(),
+            "2025-12,t1,\"Müller, Meier & Co\",2,10,4,3,12000,1500,0.085"
+        );
+    }
+}
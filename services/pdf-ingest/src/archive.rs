@@ -0,0 +1,404 @@
+//! Cold storage for old merged PDFs.
+//!
+//! With `ARCHIVE_STORE=fs` or `s3` a background job moves the bytes of merged
+//! PDFs that have been stored (or last restored) more than `ARCHIVE_AFTER_DAYS`
+//! ago out of `merged_pdfs.data`: into gzip files below `ARCHIVE_DIR`, or into
+//! objects of `ARCHIVE_STORAGE_CLASS` (e.g. `GLACIER`, `DEEP_ARCHIVE`) in the
+//! `S3_*` bucket. Hash, size, version, sources, texts and entities stay in
+//! Postgres, the searchable copy (`searchable_data`) is dropped. `GET /pdf/{id}` on an archived PDF answers `202` with
+//! `{"status": "restoring"}` and starts the restore; every pass of the job
+//! retries pending restores, since S3 needs hours to thaw an object. Restored
+//! bytes are checked against `merged_pdfs.sha256` before the PDF is `hot` again.
+
+use std::env;
+use std::io::{Read, Write};
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use anyhow::{anyhow, bail, Context, Result};
+use deadpool_postgres::Pool;
+use flate2::read::GzDecoder;
+use flate2::write::GzEncoder;
+use flate2::Compression;
+use sha2::{Digest, Sha256};
+use shared::timeline::{self, TimelineEvent};
+use tokio_postgres::Client;
+use tracing::{info, warn};
+
+use crate::direct_upload::{ArchivedObject, S3Store};
+
+/// `merged_pdfs.storage_tier` between restore request and return of the bytes
+/// (the others are `hot` and `archived`).
+pub const TIER_RESTORING: &str = "restoring";
+
+#[derive(Clone)]
+enum Backend {
+    Filesystem {
+        dir: PathBuf,
+    },
+    S3 {
+        store: Box<S3Store>,
+        prefix: String,
+        storage_class: String,
+        restore_days: u32,
+        restore_tier: String,
+    },
+}
+
+/// Moves PDFs between `merged_pdfs.data` and the cold tier.
+#[derive(Clone)]
+pub struct Archiver {
+    backend: Backend,
+    after_days: i32,
+    interval: Duration,
+    batch: i64,
+}
+
+impl Archiver {
+    /// Reads `ARCHIVE_*`; `None` unless `ARCHIVE_STORE` is `fs` or a fully
+    /// configured `s3`.
+    pub fn from_env() -> Option<Self> {
+        let var = |key: &str| env::var(key).ok().filter(|v| !v.trim().is_empty());
+        let number = |key: &str, default: u64| {
+            var(key)
+                .and_then(|v| v.trim().parse::<u64>().ok())
+                .unwrap_or(default)
+        };
+        let backend = match var("ARCHIVE_STORE")?.to_ascii_lowercase().as_str() {
+            "fs" => Backend::Filesystem {
+                dir: var("ARCHIVE_DIR")
+                    .unwrap_or_else(|| "/var/lib/regress/archive".to_string())
+                    .into(),
+            },
+            "s3" => Backend::S3 {
+                store: Box::new(S3Store::connect_from_env()?),
+                prefix: var("ARCHIVE_PREFIX").unwrap_or_else(|| "archive/".to_string()),
+                storage_class: var("ARCHIVE_STORAGE_CLASS")
+                    .unwrap_or_else(|| "GLACIER".to_string()),
+                restore_days: number("ARCHIVE_RESTORE_DAYS", 7).clamp(1, 365) as u32,
+                restore_tier: var("ARCHIVE_RESTORE_TIER").unwrap_or_else(|| "Standard".to_string()),
+            },
+            "off" | "none" => return None,
+            other => {
+                warn!(
+                    store = other,
+                    "unknown ARCHIVE_STORE; cold storage disabled"
+                );
+                return None;
+            }
+        };
+        Some(Self {
+            backend,
+            after_days: number("ARCHIVE_AFTER_DAYS", 180).clamp(1, i32::MAX as u64) as i32,
+            interval: Duration::from_secs(number("ARCHIVE_INTERVAL_SECS", 300).max(10)),
+            batch: number("ARCHIVE_BATCH", 50).clamp(1, 10_000) as i64,
+        })
+    }
+
+    /// Seconds a client should wait before asking for a restoring PDF again.
+    pub fn retry_after(&self) -> u64 {
+        match self.backend {
+            Backend::Filesystem { .. } => 5,
+            Backend::S3 { .. } => self.interval.as_secs(),
+        }
+    }
+
+    fn key(&self, pdf_id: i32, sha256: &str) -> String {
+        match &self.backend {
+            Backend::Filesystem { .. } => format!("{pdf_id}-{sha256}.pdf.gz"),
+            Backend::S3 { prefix, .. } => format!("{prefix}{pdf_id}-{sha256}.pdf"),
+        }
+    }
+
+    async fn put(&self, key: &str, data: Vec<u8>) -> Result<()> {
+        match &self.backend {
+            Backend::Filesystem { dir } => {
+                let path = dir.join(key);
+                tokio::task::spawn_blocking(move || write_gzip(&path, &data))
+                    .await
+                    .context("archive task")?
+            }
+            Backend::S3 {
+                store,
+                storage_class,
+                ..
+            } => store.put(key, data, storage_class).await,
+        }
+    }
+
+    /// Bytes of an archived PDF; `None` while S3 is still restoring the object.
+    async fn fetch(&self, key: &str) -> Result<Option<Vec<u8>>> {
+        match &self.backend {
+            Backend::Filesystem { dir } => {
+                let path = dir.join(key);
+                let data = tokio::task::spawn_blocking(move || read_gzip(&path))
+                    .await
+                    .context("archive task")??;
+                Ok(Some(data))
+            }
+            Backend::S3 {
+                store,
+                restore_days,
+                restore_tier,
+                ..
+            } => match store.fetch_archived(key).await? {
+                ArchivedObject::Ready(data) => Ok(Some(data)),
+                ArchivedObject::Archived => {
+                    store.restore(key, *restore_days, restore_tier).await?;
+                    Ok(None)
+                }
+                ArchivedObject::Missing => bail!("archive object {key} is missing"),
+            },
+        }
+    }
+
+    /// Deletes an archive object (best effort).
+    pub async fn remove(&self, key: &str) {
+        match &self.backend {
+            Backend::Filesystem { dir } => {
+                if let Err(e) = tokio::fs::remove_file(dir.join(key)).await {
+                    if e.kind() != std::io::ErrorKind::NotFound {
+                        warn!(%e, key, "remove archive file failed");
+                    }
+                }
+            }
+            Backend::S3 { store, .. } => store.delete(key).await,
+        }
+    }
+
+    /// Archives up to `ARCHIVE_BATCH` hot PDFs older than `ARCHIVE_AFTER_DAYS`.
+    pub async fn archive_due(&self, client: &Client) -> Result<usize> {
+        let due = client
+            .query(
+                "SELECT id, sha256 FROM merged_pdfs
+                  WHERE storage_tier = 'hot' AND data IS NOT NULL
+                    AND COALESCE(restored_at, created_at) < now() - make_interval(days => $1)
+                  ORDER BY id LIMIT $2",
+                &[&self.after_days, &self.batch],
+            )
+            .await
+            .context("select pdfs to archive")?;
+
+        let mut archived = 0;
+        for row in due {
+            let (id, sha256): (i32, String) = (row.get(0), row.get(1));
+            // Bytes einzeln laden, ein Batch kann mehrere GB umfassen
+            let Some(data) = client
+                .query_opt(
+                    "SELECT data FROM merged_pdfs WHERE id=$1 AND sha256=$2 AND storage_tier='hot'",
+                    &[&id, &sha256],
+                )
+                .await
+                .context("load pdf to archive")?
+                .and_then(|row| row.get::<_, Option<Vec<u8>>>(0))
+            else {
+                continue;
+            };
+            let size_bytes = data.len();
+            let key = self.key(id, &sha256);
+            if let Err(e) = self.put(&key, data).await {
+                warn!(pdf_id = id, "archive pdf failed: {e:#}");
+                continue;
+            }
+            // Nur freigeben, wenn sich das PDF seit dem Laden nicht geändert hat
+            let updated = client
+                .execute(
+                    "UPDATE merged_pdfs
+                        SET data = NULL, storage_tier = 'archived', archive_key = $3,
+                            archived_at = now(), searchable_data = NULL,
+                            searchable_sha256 = NULL
+                      WHERE id = $1 AND sha256 = $2 AND storage_tier = 'hot'",
+                    &[&id, &sha256, &key],
+                )
+                .await
+                .context("mark pdf archived")?;
+            if updated == 0 {
+                self.remove(&key).await;
+                continue;
+            }
+            timeline::record(
+                client,
+                &TimelineEvent::new("pdf-ingest", "archived")
+                    .pdf(Some(id))
+                    .details(serde_json::json!({ "archive_key": key, "size_bytes": size_bytes })),
+            )
+            .await;
+            archived += 1;
+        }
+        Ok(archived)
+    }
+
+    /// Tries to bring a `restoring` PDF back; `true` once it is hot again.
+    pub async fn restore(&self, client: &Client, pdf_id: i32) -> Result<bool> {
+        let Some(row) = client
+            .query_opt(
+                "SELECT archive_key, sha256 FROM merged_pdfs WHERE id=$1 AND storage_tier='restoring'",
+                &[&pdf_id],
+            )
+            .await
+            .context("load restoring pdf")?
+        else {
+            return Ok(false);
+        };
+        let (key, sha256): (Option<String>, String) = (row.get(0), row.get(1));
+        let key = key.ok_or_else(|| anyhow!("pdf {pdf_id} has no archive key"))?;
+        let Some(data) = self.fetch(&key).await? else {
+            return Ok(false);
+        };
+        if format!("{:x}", Sha256::digest(&data)) != sha256 {
+            bail!("archived copy of pdf {pdf_id} does not match its sha256");
+        }
+        let updated = client
+            .execute(
+                "UPDATE merged_pdfs
+                    SET data = $2, storage_tier = 'hot', archive_key = NULL,
+                        archived_at = NULL, restored_at = now()
+                  WHERE id = $1 AND storage_tier = 'restoring'",
+                &[&pdf_id, &data],
+            )
+            .await
+            .context("store restored pdf")?;
+        if updated == 0 {
+            return Ok(false);
+        }
+        timeline::record(
+            client,
+            &TimelineEvent::new("pdf-ingest", "restored")
+                .pdf(Some(pdf_id))
+                .details(serde_json::json!({ "archive_key": key })),
+        )
+        .await;
+        self.remove(&key).await;
+        Ok(true)
+    }
+
+    /// Restores one PDF in the background, right after it was requested.
+    pub fn spawn_restore(&self, pool: Pool, pdf_id: i32) {
+        let archiver = self.clone();
+        actix_web::rt::spawn(async move {
+            let result = match pool.get().await {
+                Ok(client) => archiver.restore(&client, pdf_id).await,
+                Err(e) => Err(e.into()),
+            };
+            match result {
+                Ok(true) => info!(pdf_id, "pdf restored from archive"),
+                Ok(false) => info!(pdf_id, "archive restore pending"),
+                Err(e) => warn!(pdf_id, "restore pdf failed: {e:#}"),
+            }
+        });
+    }
+
+    /// Lifecycle loop: finishes pending restores, then archives due PDFs.
+    pub async fn run(self, pool: Pool) {
+        let mut ticker = tokio::time::interval(self.interval);
+        loop {
+            ticker.tick().await;
+            let client = match pool.get().await {
+                Ok(client) => client,
+                Err(e) => {
+                    warn!(%e, "archive job: db pool get failed");
+                    continue;
+                }
+            };
+            let restoring = match client
+                .query(
+                    "SELECT id FROM merged_pdfs WHERE storage_tier = 'restoring' ORDER BY id",
+                    &[],
+                )
+                .await
+            {
+                Ok(rows) => rows.iter().map(|r| r.get::<_, i32>(0)).collect(),
+                Err(e) => {
+                    warn!(%e, "archive job: select restoring pdfs failed");
+                    Vec::new()
+                }
+            };
+            for pdf_id in restoring {
+                if let Err(e) = self.restore(&client, pdf_id).await {
+                    warn!(pdf_id, "restore pdf failed: {e:#}");
+                }
+            }
+            match self.archive_due(&client).await {
+                Ok(0) => {}
+                Ok(count) => info!(count, "archived merged pdfs"),
+                Err(e) => warn!("archive job failed: {e:#}"),
+            }
+        }
+    }
+}
+
+/// Marks an archived PDF as `restoring`; `true` if this call requested it.
+pub async fn request_restore(client: &Client, pdf_id: i32) -> Result<bool> {
+    let updated = client
+        .execute(
+            "UPDATE merged_pdfs SET storage_tier = 'restoring'
+              WHERE id = $1 AND storage_tier = 'archived'",
+            &[&pdf_id],
+        )
+        .await
+        .context("request restore")?;
+    if updated == 1 {
+        timeline::record(
+            client,
+            &TimelineEvent::new("pdf-ingest", "restore_requested").pdf(Some(pdf_id)),
+        )
+        .await;
+    }
+    Ok(updated == 1)
+}
+
+/// Writes `data` gzip-compressed, via a temporary file so a crash never
+/// leaves a truncated archive behind.
+fn write_gzip(path: &Path, data: &[u8]) -> Result<()> {
+    if let Some(dir) = path.parent() {
+        std::fs::create_dir_all(dir).with_context(|| format!("create {}", dir.display()))?;
+    }
+    let tmp = path.with_extension("tmp");
+    let file = std::fs::File::create(&tmp).with_context(|| format!("create {}", tmp.display()))?;
+    let mut encoder = GzEncoder::new(file, Compression::best());
+    encoder.write_all(data).context("compress pdf")?;
+    encoder
+        .finish()
+        .and_then(|file| file.sync_all())
+        .context("write archive file")?;
+    std::fs::rename(&tmp, path).with_context(|| format!("rename to {}", path.display()))?;
+    Ok(())
+}
+
+fn read_gzip(path: &Path) -> Result<Vec<u8>> {
+    let file = std::fs::File::open(path).with_context(|| format!("open {}", path.display()))?;
+    let mut data = Vec::new();
+    GzDecoder::new(file)
+        .read_to_end(&mut data)
+        .context("decompress pdf")?;
+    Ok(data)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[actix_web::test]
+    async fn filesystem_backend_round_trip() {
+        let dir = tempfile::tempdir().unwrap();
+        let archiver = Archiver {
+            backend: Backend::Filesystem {
+                dir: dir.path().join("cold"),
+            },
+            after_days: 180,
+            interval: Duration::from_secs(300),
+            batch: 50,
+        };
+        let data = b"%PDF-1.7 ".repeat(1000);
+        let key = archiver.key(7, "abc");
+        assert_eq!(key, "7-abc.pdf.gz");
+
+        archiver.put(&key, data.clone()).await.unwrap();
+        let stored = std::fs::metadata(dir.path().join("cold").join(&key)).unwrap();
+        assert!(stored.len() < data.len() as u64 / 10);
+        assert_eq!(archiver.fetch(&key).await.unwrap(), Some(data));
+
+        archiver.remove(&key).await;
+        assert!(archiver.fetch(&key).await.is_err());
+    }
+}
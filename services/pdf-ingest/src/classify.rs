@@ -0,0 +1,263 @@
+//! Pipeline pre-router for uploads without pipeline.
+//!
+//! With `AUTO_CLASSIFY=true` an upload that names no pipeline gets
+//! `run_state = 'classify'`. Once its text is extracted, the keyword rules in
+//! `pipeline_routing_rules` (one list per pipeline, managed via
+//! `/admin/classification-rules`) score every pipeline by the share of its
+//! keywords found in the text. The confidence is the lead of the best pipeline
+//! over the runner-up; at or above `CLASSIFY_MIN_CONFIDENCE` the upload gets
+//! that pipeline and its run starts, otherwise it lands in the "needs pipeline
+//! assignment" queue (`run_state = 'unassigned'`, `GET /uploads/unassigned`)
+//! until `POST /uploads/{id}/run` names the pipeline. The scores are kept in
+//! `uploads.classification` either way.
+//!
+//! Keywords match case-insensitively on whole words; a keyword with several
+//! words matches the phrase regardless of punctuation and line breaks.
+
+use std::env;
+
+use serde::{Deserialize, Serialize};
+use tokio_postgres::Client;
+use uuid::Uuid;
+
+/// Creates `pipeline_routing_rules` and `uploads.classification` (see
+/// migration 0061).
+pub const SCHEMA_SQL: &[&str] = &[
+    "CREATE TABLE IF NOT EXISTS pipeline_routing_rules (
+        pipeline_id UUID PRIMARY KEY REFERENCES pipelines(id) ON DELETE CASCADE,
+        keywords TEXT[] NOT NULL,
+        updated_at TIMESTAMPTZ NOT NULL DEFAULT now()
+    )",
+    "ALTER TABLE uploads ADD COLUMN IF NOT EXISTS classification JSONB",
+];
+
+/// `uploads.run_state` of uploads waiting for the classification.
+pub const STATE_CLASSIFY: &str = "classify";
+/// `uploads.run_state` of uploads the classification could not route.
+pub const STATE_UNASSIGNED: &str = "unassigned";
+
+/// Default of `CLASSIFY_MIN_CONFIDENCE`.
+pub const DEFAULT_MIN_CONFIDENCE: f64 = 0.5;
+
+/// Most keywords per pipeline.
+pub const MAX_KEYWORDS: usize = 200;
+
+#[derive(Debug, Clone, Copy)]
+pub struct ClassifyConfig {
+    /// Uploads without pipeline are classified (`AUTO_CLASSIFY`).
+    pub enabled: bool,
+    pub min_confidence: f64,
+}
+
+impl ClassifyConfig {
+    /// Reads `AUTO_CLASSIFY` (default off) and `CLASSIFY_MIN_CONFIDENCE`
+    /// (0..=1, default 0.5).
+    pub fn from_env() -> Self {
+        let enabled = env::var("AUTO_CLASSIFY")
+            .map(|v| {
+                matches!(
+                    v.trim().to_ascii_lowercase().as_str(),
+                    "1" | "true" | "yes" | "on"
+                )
+            })
+            .unwrap_or(false);
+        let min_confidence = env::var("CLASSIFY_MIN_CONFIDENCE")
+            .ok()
+            .and_then(|v| v.trim().parse::<f64>().ok())
+            .filter(|v| (0.0..=1.0).contains(v))
+            .unwrap_or(DEFAULT_MIN_CONFIDENCE);
+        Self {
+            enabled,
+            min_confidence,
+        }
+    }
+}
+
+/// Keyword rule of one pipeline.
+#[derive(Debug, Clone, Serialize)]
+pub struct RoutingRule {
+    pub pipeline_id: Uuid,
+    pub keywords: Vec<String>,
+}
+
+#[derive(Debug, Deserialize)]
+/// Body of `PUT /admin/classification-rules/{pipeline_id}`.
+pub struct RuleInput {
+    pub keywords: Vec<String>,
+}
+
+/// Score of one pipeline.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Candidate {
+    pub pipeline_id: Uuid,
+    /// Share of the pipeline's keywords found in the text.
+    pub score: f64,
+    pub matched: Vec<String>,
+}
+
+/// Outcome stored in `uploads.classification`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Classification {
+    /// Chosen pipeline, `None` below the threshold.
+    pub pipeline_id: Option<Uuid>,
+    pub confidence: f64,
+    pub min_confidence: f64,
+    /// Pipelines with at least one matched keyword, best first.
+    pub candidates: Vec<Candidate>,
+}
+
+/// Trims, lowercases and deduplicates keywords; empty ones are dropped.
+pub fn clean_keywords(keywords: &[String]) -> Vec<String> {
+    let mut cleaned: Vec<String> = Vec::new();
+    for keyword in keywords {
+        let keyword = normalize(keyword).trim().to_string();
+        if !keyword.is_empty() && !cleaned.contains(&keyword) {
+            cleaned.push(keyword);
+        }
+    }
+    cleaned
+}
+
+/// Scores `rules` against `text` (see module docs).
+pub fn classify(text: &str, rules: &[RoutingRule], min_confidence: f64) -> Classification {
+    let text = normalize(text);
+    let mut candidates: Vec<Candidate> = rules
+        .iter()
+        .filter_map(|rule| {
+            let keywords = clean_keywords(&rule.keywords);
+            let total = keywords.len();
+            let matched: Vec<String> = keywords
+                .into_iter()
+                .filter(|keyword| text.contains(&format!(" {keyword} ")))
+                .collect();
+            (!matched.is_empty()).then(|| Candidate {
+                pipeline_id: rule.pipeline_id,
+                score: matched.len() as f64 / total as f64,
+                matched,
+            })
+        })
+        .collect();
+    candidates.sort_by(|a, b| b.score.total_cmp(&a.score));
+    let best = candidates.first().map_or(0.0, |c| c.score);
+    let runner_up = candidates.get(1).map_or(0.0, |c| c.score);
+    let confidence = best - runner_up;
+    Classification {
+        pipeline_id: candidates
+            .first()
+            .filter(|_| confidence > 0.0 && confidence >= min_confidence)
+            .map(|c| c.pipeline_id),
+        confidence,
+        min_confidence,
+        candidates,
+    }
+}
+
+/// Lowercase words separated by single spaces, padded with a space on both
+/// sides so whole words match as `" word "`.
+fn normalize(text: &str) -> String {
+    let mut out = String::with_capacity(text.len() + 2);
+    out.push(' ');
+    for word in text
+        .split(|c: char| !c.is_alphanumeric())
+        .filter(|word| !word.is_empty())
+    {
+        out.push_str(&word.to_lowercase());
+        out.push(' ');
+    }
+    out
+}
+
+pub async fn load_rules(client: &Client) -> Result<Vec<RoutingRule>, tokio_postgres::Error> {
+    let rows = client
+        .query(
+            "SELECT pipeline_id, keywords FROM pipeline_routing_rules ORDER BY pipeline_id",
+            &[],
+        )
+        .await?;
+    Ok(rows
+        .iter()
+        .map(|row| RoutingRule {
+            pipeline_id: row.get(0),
+            keywords: row.get(1),
+        })
+        .collect())
+}
+
+/// Full text of a merged PDF, pages in order.
+pub async fn load_text(client: &Client, pdf_id: i32) -> Result<String, tokio_postgres::Error> {
+    let row = client
+        .query_one(
+            "SELECT COALESCE(string_agg(text, E'\\n' ORDER BY page_no), '')
+               FROM pdf_page_texts WHERE merged_pdf_id = $1",
+            &[&pdf_id],
+        )
+        .await?;
+    Ok(row.get(0))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rule(id: u128, keywords: &[&str]) -> RoutingRule {
+        RoutingRule {
+            pipeline_id: Uuid::from_u128(id),
+            keywords: keywords.iter().map(|k| k.to_string()).collect(),
+        }
+    }
+
+    #[test]
+    fn picks_the_pipeline_with_a_clear_lead() {
+        let rules = [
+            rule(1, &["Darlehensvertrag", "Sollzins", "Tilgung"]),
+            rule(2, &["Mietvertrag", "Kaution", "Kaltmiete", "Tilgung"]),
+        ];
+        let text = "DARLEHENSVERTRAG\nDer Sollzins beträgt 4,9 %. Tilgungsplan anbei.";
+        let result = classify(text, &rules, DEFAULT_MIN_CONFIDENCE);
+        assert_eq!(result.pipeline_id, Some(Uuid::from_u128(1)));
+        assert_eq!(result.candidates.len(), 1);
+        assert_eq!(
+            result.candidates[0].matched,
+            ["darlehensvertrag", "sollzins"]
+        );
+        assert!((result.confidence - 2.0 / 3.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn ambiguous_or_unknown_documents_stay_unassigned() {
+        let rules = [
+            rule(1, &["Vertrag", "Zins"]),
+            rule(2, &["Vertrag", "Miete"]),
+        ];
+        let tied = classify("Vertrag über Zins und Miete", &rules, 0.1);
+        assert_eq!(tied.pipeline_id, None);
+        assert_eq!(tied.confidence, 0.0);
+        assert_eq!(tied.candidates.len(), 2);
+
+        let unknown = classify("Lieferschein Nr. 4711", &rules, 0.0);
+        assert_eq!(unknown.pipeline_id, None);
+        assert!(unknown.candidates.is_empty());
+    }
+
+    #[test]
+    fn phrases_match_whole_words_only() {
+        let rules = [rule(1, &["  Private Krankenversicherung "])];
+        assert!(
+            classify("private\nKrankenversicherung, Tarif A", &rules, 0.5)
+                .pipeline_id
+                .is_some()
+        );
+        assert!(classify("privatekrankenversicherung", &rules, 0.5)
+            .pipeline_id
+            .is_none());
+        assert_eq!(
+            clean_keywords(&[
+                "Zins".into(),
+                " zins ".into(),
+                "".into(),
+                "Soll-Zins".into()
+            ]),
+            ["zins", "soll zins"]
+        );
+    }
+}
@@ -0,0 +1,628 @@
+//! Erasure of a data subject's documents (`POST /erasure-requests`).
+//!
+//! A document leaves traces far beyond `merged_pdfs`: page texts (with their
+//! deduplicated `pdf_text_blobs`) and layouts, page images, metadata, entities,
+//! annotations, attachment texts, the text-extraction page cache, uploads, run
+//! results with their quotes, operator notes, history entries, sink deliveries
+//! and timeline details. [`erase`] resolves a `pdf_id` or an external reference
+//! (`external_ref`) to all affected documents and runs and walks through
+//! [`STEPS`] in one transaction: rows that only exist because of the document
+//! are deleted, shared rows (runs, steps, timeline) keep their ids and metrics
+//! but lose their content. Documents under legal hold are never touched.
+//!
+//! The outcome is an [`ErasureReport`] with the affected rows per table, signed
+//! with HMAC-SHA256 under `ERASURE_SIGNING_KEY` and stored in
+//! `erasure_requests` together with the erasure itself.
+
+use std::collections::HashSet;
+use std::env;
+
+use anyhow::{Context, Result};
+use chrono::{SecondsFormat, Utc};
+use deadpool_postgres::Pool;
+use hmac::{Hmac, Mac};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use tracing::{info, warn};
+use uuid::Uuid;
+
+use crate::archive::Archiver;
+use crate::direct_upload::S3Store;
+
+/// Creates `erasure_requests` (see migration 0041).
+pub const CREATE_TABLE_SQL: &str = "CREATE TABLE IF NOT EXISTS erasure_requests (
+    id UUID PRIMARY KEY,
+    pdf_ids INTEGER[] NOT NULL,
+    external_ref_sha256 TEXT,
+    requested_by TEXT,
+    reason TEXT,
+    report TEXT NOT NULL,
+    signature TEXT NOT NULL,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT now()
+)";
+
+pub const ALGORITHM: &str = "HMAC-SHA256";
+
+/// Documents referenced by an external reference anywhere in the pipeline.
+const RESOLVE_REF_SQL: &str = "
+    SELECT pdf_id FROM uploads WHERE external_ref = $1 AND pdf_id IS NOT NULL
+    UNION SELECT pdf_id FROM pipeline_runs WHERE external_ref = $1 AND pdf_id IS NOT NULL
+    UNION SELECT pdf_id FROM analysis_history WHERE external_ref = $1 AND pdf_id IS NOT NULL
+    UNION SELECT pdf_id FROM sharepoint_jobs WHERE external_ref = $1 AND pdf_id IS NOT NULL";
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Action {
+    Deleted,
+    /// Row kept, personal content cleared.
+    Anonymized,
+}
+
+/// Parameter `$1` of a step: run ids (`UUID[]`) or document ids (`INTEGER[]`).
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Scope {
+    Runs,
+    Pdfs,
+}
+
+struct Step {
+    table: &'static str,
+    action: Action,
+    scope: Scope,
+    sql: &'static str,
+}
+
+/// Every table holding content of a document, in execution order: runs are
+/// detached before `merged_pdfs` goes (`pipeline_runs.pdf_id` has no cascade).
+/// Tables missing in the database are skipped.
+const STEPS: &[Step] = &[
+    Step {
+        table: "result_sink_deliveries",
+        action: Action::Deleted,
+        scope: Scope::Runs,
+        sql: "DELETE FROM result_sink_deliveries WHERE run_id = ANY($1)",
+    },
+    Step {
+        table: "analysis_history",
+        action: Action::Deleted,
+        scope: Scope::Runs,
+        sql: "DELETE FROM analysis_history WHERE run_id = ANY($1)",
+    },
+    Step {
+        table: "pipeline_step_attempts",
+        action: Action::Anonymized,
+        scope: Scope::Runs,
+        sql: "UPDATE pipeline_step_attempts SET candidate_value = NULL, openai_raw = NULL
+               WHERE run_step_id IN (SELECT id FROM pipeline_run_steps WHERE run_id = ANY($1))",
+    },
+    Step {
+        table: "pipeline_run_steps",
+        action: Action::Anonymized,
+        scope: Scope::Runs,
+        sql: "UPDATE pipeline_run_steps SET result = NULL, final_value = NULL, error = NULL
+               WHERE run_id = ANY($1)",
+    },
+    Step {
+        table: "run_reviews",
+        action: Action::Anonymized,
+        scope: Scope::Runs,
+        sql: "UPDATE run_reviews SET note = NULL, reviewer = NULL WHERE run_id = ANY($1)",
+    },
+    Step {
+        table: "run_notes",
+        action: Action::Deleted,
+        scope: Scope::Runs,
+        sql: "DELETE FROM run_notes WHERE run_id = ANY($1)",
+    },
+    Step {
+        table: "imap_messages",
+        action: Action::Anonymized,
+        scope: Scope::Runs,
+        sql: "UPDATE imap_messages SET subject = NULL, sender = NULL, message_id = NULL
+               WHERE run_id = ANY($1)",
+    },
+    Step {
+        table: "run_timeline",
+        action: Action::Anonymized,
+        scope: Scope::Runs,
+        sql: "UPDATE run_timeline SET message = NULL, details = NULL WHERE run_id = ANY($1)",
+    },
+    Step {
+        table: "pipeline_runs",
+        action: Action::Anonymized,
+        scope: Scope::Runs,
+        sql: "UPDATE pipeline_runs
+                 SET pdf_id = NULL, final_extraction = NULL, final_extraction_sealed = NULL,
+                     error = NULL, external_ref = NULL, job_label = NULL
+               WHERE id = ANY($1)",
+    },
+    Step {
+        table: "result_sink_deliveries",
+        action: Action::Deleted,
+        scope: Scope::Pdfs,
+        sql: "DELETE FROM result_sink_deliveries
+               WHERE analysis_id IN (SELECT id FROM analysis_history WHERE pdf_id = ANY($1))",
+    },
+    Step {
+        table: "analysis_history",
+        action: Action::Deleted,
+        scope: Scope::Pdfs,
+        sql: "DELETE FROM analysis_history WHERE pdf_id = ANY($1)",
+    },
+    Step {
+        table: "run_timeline",
+        action: Action::Anonymized,
+        scope: Scope::Pdfs,
+        sql: "UPDATE run_timeline SET message = NULL, details = NULL WHERE pdf_id = ANY($1)",
+    },
+    Step {
+        table: "sharepoint_jobs",
+        action: Action::Anonymized,
+        scope: Scope::Pdfs,
+        sql: "UPDATE sharepoint_jobs
+                 SET folder_name = 'erased', filenames_override = NULL, output = NULL,
+                     external_ref = NULL, job_label = NULL
+               WHERE pdf_id = ANY($1)",
+    },
+    Step {
+        table: "upload_notes",
+        action: Action::Deleted,
+        scope: Scope::Pdfs,
+        sql: "DELETE FROM upload_notes
+               WHERE upload_id IN (SELECT id FROM uploads WHERE pdf_id = ANY($1))",
+    },
+    Step {
+        table: "uploads",
+        action: Action::Deleted,
+        scope: Scope::Pdfs,
+        sql: "DELETE FROM uploads WHERE pdf_id = ANY($1)",
+    },
+    Step {
+        table: "page_extraction_cache",
+        action: Action::Deleted,
+        scope: Scope::Pdfs,
+        sql: "DELETE FROM page_extraction_cache WHERE sha256 IN (
+                  SELECT sha256 FROM merged_pdfs WHERE id = ANY($1)
+                  UNION SELECT sha256 FROM pdf_versions WHERE pdf_id = ANY($1))",
+    },
+    Step {
+        table: "pdf_attachment_texts",
+        action: Action::Deleted,
+        scope: Scope::Pdfs,
+        sql: "DELETE FROM pdf_attachment_texts WHERE merged_pdf_id = ANY($1)",
+    },
+    Step {
+        table: "pdf_annotations",
+        action: Action::Deleted,
+        scope: Scope::Pdfs,
+        sql: "DELETE FROM pdf_annotations WHERE merged_pdf_id = ANY($1)",
+    },
+    Step {
+        table: "pdf_metadata",
+        action: Action::Deleted,
+        scope: Scope::Pdfs,
+        sql: "DELETE FROM pdf_metadata WHERE merged_pdf_id = ANY($1)",
+    },
+    Step {
+        table: "pdf_entities",
+        action: Action::Deleted,
+        scope: Scope::Pdfs,
+        sql: "DELETE FROM pdf_entities WHERE merged_pdf_id = ANY($1)",
+    },
+    Step {
+        table: "pdf_signatures",
+        action: Action::Deleted,
+        scope: Scope::Pdfs,
+        sql: "DELETE FROM pdf_signatures WHERE merged_pdf_id = ANY($1)",
+    },
+    Step {
+        table: "extraction_retries",
+        action: Action::Deleted,
+        scope: Scope::Pdfs,
+        sql: "DELETE FROM extraction_retries WHERE pdf_id = ANY($1)",
+    },
+    Step {
+        table: "pdf_page_images",
+        action: Action::Deleted,
+        scope: Scope::Pdfs,
+        sql: "DELETE FROM pdf_page_images WHERE merged_pdf_id = ANY($1)",
+    },
+    Step {
+        table: "pdf_texts",
+        action: Action::Deleted,
+        scope: Scope::Pdfs,
+        // mitsamt den Text-Blobs, die kein anderes Dokument referenziert
+        sql: shared::page_texts::DELETE_PAGES_SQL,
+    },
+    Step {
+        table: "pdf_sources",
+        action: Action::Deleted,
+        scope: Scope::Pdfs,
+        sql: "DELETE FROM pdf_sources WHERE pdf_id = ANY($1)",
+    },
+    Step {
+        table: "pdf_versions",
+        action: Action::Deleted,
+        scope: Scope::Pdfs,
+        sql: "DELETE FROM pdf_versions WHERE pdf_id = ANY($1)",
+    },
+    Step {
+        table: "merged_pdfs",
+        action: Action::Deleted,
+        scope: Scope::Pdfs,
+        sql: "DELETE FROM merged_pdfs WHERE id = ANY($1)",
+    },
+];
+
+/// Body of `POST /erasure-requests`; exactly one of `pdf_id` and `external_ref`.
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct ErasureRequest {
+    #[serde(default)]
+    pub pdf_id: Option<i32>,
+    #[serde(default)]
+    pub external_ref: Option<String>,
+    #[serde(default)]
+    pub reason: Option<String>,
+    #[serde(default)]
+    pub requested_by: Option<String>,
+}
+
+impl ErasureRequest {
+    fn external_ref(&self) -> Option<&str> {
+        self.external_ref
+            .as_deref()
+            .map(str::trim)
+            .filter(|r| !r.is_empty())
+    }
+
+    /// The message is meant for the client.
+    pub fn validate(&self) -> Result<(), String> {
+        match (self.pdf_id, self.external_ref()) {
+            (Some(_), Some(_)) => Err("give either pdf_id or external_ref, not both".into()),
+            (None, None) => Err("pdf_id or external_ref is required".into()),
+            _ => Ok(()),
+        }
+    }
+}
+
+/// Rows of one table touched by an erasure.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TableErasure {
+    pub table: String,
+    pub action: Action,
+    pub rows: u64,
+}
+
+/// Signed record of an erasure. Contains ids and counts only, never content.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ErasureReport {
+    pub id: Uuid,
+    pub pdf_id: Option<i32>,
+    /// SHA-256 (hex) of the requested external reference; the reference itself
+    /// is erased as well.
+    pub external_ref_sha256: Option<String>,
+    pub pdf_ids: Vec<i32>,
+    pub run_ids: Vec<Uuid>,
+    pub reason: Option<String>,
+    pub requested_by: Option<String>,
+    /// Every table looked at, including those without matching rows.
+    pub tables: Vec<TableErasure>,
+    /// Archived copies and leftover direct-upload objects whose deletion was
+    /// requested after the commit (best effort, failures are logged).
+    pub objects_removed: usize,
+    /// Objects kept because `ARCHIVE_STORE` or `BLOB_STORE` is not configured.
+    pub objects_kept: usize,
+    /// RFC 3339.
+    pub completed_at: String,
+}
+
+/// Response of `POST /erasure-requests` and `GET /erasure-requests/{id}`.
+#[derive(Debug, Clone, Serialize)]
+pub struct SignedReport {
+    pub report: ErasureReport,
+    /// Hex HMAC over the compact JSON of `report` (fields in the order shown).
+    pub signature: String,
+    pub algorithm: &'static str,
+    /// Whether the stored signature matches under the current key (`GET` only).
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub verified: Option<bool>,
+}
+
+pub enum Outcome {
+    /// Nothing references the document or reference.
+    NotFound,
+    /// Documents under legal hold; nothing was changed.
+    Held(Vec<i32>),
+    Erased(Box<SignedReport>),
+}
+
+/// Key for the report signature (`ERASURE_SIGNING_KEY`).
+pub struct Signer {
+    key: Vec<u8>,
+}
+
+impl Signer {
+    pub fn new(key: &[u8]) -> Self {
+        Self { key: key.to_vec() }
+    }
+
+    pub fn from_env() -> Option<Self> {
+        env::var("ERASURE_SIGNING_KEY")
+            .ok()
+            .filter(|k| !k.is_empty())
+            .map(|k| Self::new(k.as_bytes()))
+    }
+
+    pub fn sign(&self, report: &ErasureReport) -> String {
+        let payload = serde_json::to_vec(report).expect("report serializes");
+        let mut mac =
+            Hmac::<Sha256>::new_from_slice(&self.key).expect("HMAC accepts keys of any length");
+        mac.update(&payload);
+        format!("{:x}", mac.finalize().into_bytes())
+    }
+
+    pub fn verify(&self, report: &ErasureReport, signature: &str) -> bool {
+        self.sign(report).eq_ignore_ascii_case(signature.trim())
+    }
+}
+
+/// Runs the erasure described by `request` (validated by the caller).
+pub async fn erase(
+    pool: &Pool,
+    signer: &Signer,
+    archive: Option<&Archiver>,
+    store: Option<&S3Store>,
+    request: &ErasureRequest,
+) -> Result<Outcome> {
+    let external_ref = request.external_ref();
+    let mut client = pool.get().await.context("db pool get")?;
+    let tx = client.transaction().await.context("begin erasure")?;
+
+    let mut pdf_ids: Vec<i32> = match (request.pdf_id, external_ref) {
+        (Some(id), _) => vec![id],
+        (None, Some(reference)) => tx
+            .query(RESOLVE_REF_SQL, &[&reference])
+            .await
+            .context("resolve external_ref")?
+            .iter()
+            .map(|row| row.get(0))
+            .collect(),
+        (None, None) => Vec::new(),
+    };
+    pdf_ids.sort_unstable();
+    pdf_ids.dedup();
+
+    // Zeilen sperren, damit bis zum Commit kein Legal Hold gesetzt wird
+    let documents = tx
+        .query(
+            "SELECT id, legal_hold, archive_key FROM merged_pdfs WHERE id = ANY($1) FOR UPDATE",
+            &[&pdf_ids],
+        )
+        .await
+        .context("lock documents")?;
+    let held: Vec<i32> = documents
+        .iter()
+        .filter(|row| row.get::<_, bool>(1))
+        .map(|row| row.get(0))
+        .collect();
+    if !held.is_empty() {
+        return Ok(Outcome::Held(held));
+    }
+    let archive_keys: Vec<String> = documents.iter().filter_map(|row| row.get(2)).collect();
+
+    let run_ids: Vec<Uuid> = tx
+        .query(
+            "SELECT id FROM pipeline_runs WHERE pdf_id = ANY($1) OR external_ref = $2 ORDER BY id",
+            &[&pdf_ids, &external_ref],
+        )
+        .await
+        .context("resolve runs")?
+        .iter()
+        .map(|row| row.get(0))
+        .collect();
+    let upload_keys: Vec<String> = tx
+        .query(
+            "SELECT storage_key FROM uploads WHERE pdf_id = ANY($1) AND storage_key IS NOT NULL",
+            &[&pdf_ids],
+        )
+        .await
+        .context("resolve upload objects")?
+        .iter()
+        .map(|row| row.get(0))
+        .collect();
+
+    let names: Vec<&str> = STEPS.iter().map(|step| step.table).collect();
+    let present: HashSet<String> = tx
+        .query(
+            "SELECT t FROM unnest($1::text[]) AS t WHERE to_regclass(t) IS NOT NULL",
+            &[&names],
+        )
+        .await
+        .context("check tables")?
+        .iter()
+        .map(|row| row.get(0))
+        .collect();
+
+    let mut tables: Vec<TableErasure> = Vec::new();
+    for step in STEPS.iter().filter(|step| present.contains(step.table)) {
+        let rows = match step.scope {
+            Scope::Runs => tx.execute(step.sql, &[&run_ids]).await,
+            Scope::Pdfs => tx.execute(step.sql, &[&pdf_ids]).await,
+        }
+        .with_context(|| format!("erase {}", step.table))?;
+        match tables
+            .iter_mut()
+            .find(|t| t.table == step.table && t.action == step.action)
+        {
+            Some(entry) => entry.rows += rows,
+            None => tables.push(TableErasure {
+                table: step.table.to_string(),
+                action: step.action,
+                rows,
+            }),
+        }
+    }
+    if tables.iter().all(|t| t.rows == 0) {
+        return Ok(Outcome::NotFound);
+    }
+
+    let (archive_removed, archive_kept) = match archive {
+        Some(_) => (archive_keys.len(), 0),
+        None => (0, archive_keys.len()),
+    };
+    let (uploads_removed, uploads_kept) = match store {
+        Some(_) => (upload_keys.len(), 0),
+        None => (0, upload_keys.len()),
+    };
+    let report = ErasureReport {
+        id: Uuid::new_v4(),
+        pdf_id: request.pdf_id,
+        external_ref_sha256: external_ref.map(|r| format!("{:x}", Sha256::digest(r.as_bytes()))),
+        pdf_ids,
+        run_ids,
+        reason: request.reason.clone(),
+        requested_by: request.requested_by.clone(),
+        tables,
+        objects_removed: archive_removed + uploads_removed,
+        objects_kept: archive_kept + uploads_kept,
+        completed_at: Utc::now().to_rfc3339_opts(SecondsFormat::Secs, true),
+    };
+    let signature = signer.sign(&report);
+    // Bericht in derselben Transaktion: keine Löschung ohne Nachweis
+    tx.execute(
+        "INSERT INTO erasure_requests
+            (id, pdf_ids, external_ref_sha256, requested_by, reason, report, signature)
+         VALUES ($1, $2, $3, $4, $5, $6, $7)",
+        &[
+            &report.id,
+            &report.pdf_ids,
+            &report.external_ref_sha256,
+            &report.requested_by,
+            &report.reason,
+            &serde_json::to_string(&report).context("serialize report")?,
+            &signature,
+        ],
+    )
+    .await
+    .context("store erasure report")?;
+    tx.commit().await.context("commit erasure")?;
+
+    for key in &archive_keys {
+        match archive {
+            Some(archive) => archive.remove(key).await,
+            None => warn!(key, "archived copy kept, ARCHIVE_STORE not configured"),
+        }
+    }
+    for key in &upload_keys {
+        match store {
+            Some(store) => store.delete(key).await,
+            None => warn!(key, "uploaded object kept, BLOB_STORE not configured"),
+        }
+    }
+    info!(id = %report.id, pdfs = report.pdf_ids.len(), runs = report.run_ids.len(), "erasure done");
+    Ok(Outcome::Erased(Box::new(SignedReport {
+        report,
+        signature,
+        algorithm: ALGORITHM,
+        verified: None,
+    })))
+}
+
+/// Loads a stored report and checks its signature with `signer`.
+pub async fn load(pool: &Pool, signer: Option<&Signer>, id: Uuid) -> Result<Option<SignedReport>> {
+    let client = pool.get().await.context("db pool get")?;
+    let Some(row) = client
+        .query_opt(
+            "SELECT report, signature FROM erasure_requests WHERE id = $1",
+            &[&id],
+        )
+        .await
+        .context("read erasure report")?
+    else {
+        return Ok(None);
+    };
+    let report: ErasureReport =
+        serde_json::from_str(row.get::<_, &str>(0)).context("parse erasure report")?;
+    let signature: String = row.get(1);
+    Ok(Some(SignedReport {
+        verified: signer.map(|s| s.verify(&report, &signature)),
+        report,
+        signature,
+        algorithm: ALGORITHM,
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn report() -> ErasureReport {
+        ErasureReport {
+            id: Uuid::nil(),
+            pdf_id: None,
+            external_ref_sha256: Some("ab".repeat(32)),
+            pdf_ids: vec![3, 7],
+            run_ids: vec![Uuid::nil()],
+            reason: Some("Art. 17 DSGVO".to_string()),
+            requested_by: Some("datenschutz".to_string()),
+            tables: vec![TableErasure {
+                table: "pdf_texts".to_string(),
+                action: Action::Deleted,
+                rows: 12,
+            }],
+            objects_removed: 1,
+            objects_kept: 0,
+            completed_at: "2026-10-16T09:00:00Z".to_string(),
+        }
+    }
+
+    #[test]
+    fn signature_survives_storage_and_detects_changes() {
+        let signer = Signer::new(b"geheim");
+        let original = report();
+        let signature = signer.sign(&original);
+        let stored: ErasureReport =
+            serde_json::from_str(&serde_json::to_string(&original).unwrap()).unwrap();
+        assert!(signer.verify(&stored, &signature));
+
+        let mut tampered = stored.clone();
+        tampered.tables[0].rows = 11;
+        assert!(!signer.verify(&tampered, &signature));
+        assert!(!Signer::new(b"anders").verify(&stored, &signature));
+    }
+
+    #[test]
+    fn request_needs_exactly_one_subject() {
+        let by_ref = ErasureRequest {
+            external_ref: Some(" AZ-2024-17 ".to_string()),
+            ..Default::default()
+        };
+        assert!(by_ref.validate().is_ok());
+        assert_eq!(by_ref.external_ref(), Some("AZ-2024-17"));
+        let blank = ErasureRequest {
+            external_ref: Some("  ".to_string()),
+            ..Default::default()
+        };
+        assert!(blank.validate().is_err());
+        let both = ErasureRequest {
+            pdf_id: Some(1),
+            ..by_ref
+        };
+        assert!(both.validate().is_err());
+    }
+
+    #[test]
+    fn runs_are_detached_before_documents_are_deleted() {
+        let position = |table: &str, scope: Scope| {
+            STEPS
+                .iter()
+                .position(|s| s.table == table && s.scope == scope)
+                .unwrap()
+        };
+        assert!(position("pipeline_runs", Scope::Runs) < position("merged_pdfs", Scope::Pdfs));
+        assert!(position("uploads", Scope::Pdfs) < position("merged_pdfs", Scope::Pdfs));
+        // die Notizen finden ihre Uploads nur, solange es sie noch gibt
+        assert!(position("upload_notes", Scope::Pdfs) < position("uploads", Scope::Pdfs));
+        assert_eq!(STEPS.last().unwrap().table, "merged_pdfs");
+    }
+}
@@ -1,19 +1,36 @@
 //! Handles PDF uploads, merges documents and publishes Kafka events.
 
-use actix_cors::Cors;
 use actix_multipart::Multipart;
 use actix_web::http::header;
 use actix_web::web::Bytes;
 use actix_web::{web, App, Error, HttpRequest, HttpResponse, HttpServer, Responder};
 use futures_util::StreamExt as _;
+use rdkafka::consumer::{Consumer, StreamConsumer};
+use rdkafka::message::Message;
 use rdkafka::producer::{FutureProducer, FutureRecord};
 use rdkafka::ClientConfig;
 use sha2::{Digest, Sha256};
+use shared::annotations::{self, AnnotationKind, NewAnnotation, PageAnnotation};
 use shared::config::Settings;
-use shared::dto::{PdfUploaded, UploadResponse};
+use shared::cors::CorsSettings;
+use shared::dto::{PdfUploaded, ReocrPages, RunPriority, TextExtracted, UploadResponse};
+use shared::entities::{self, EntityKind, PdfEntity};
+use shared::envelope::{MasterKey, SealedSecret};
+use shared::kafka;
+use shared::operator_notes::{self, NoteInput, OperatorNote};
+use shared::page_texts;
+use shared::pdf_metadata::{self, PdfMetadata};
+use shared::schema_doc;
+use shared::scrubber::Scrubber;
+use shared::similarity;
+use shared::startup::{retry_with_backoff, Backoff, Readiness};
+use shared::timeline::{self, TimelineEvent};
 use std::collections::{BTreeMap, HashSet};
 use std::time::Duration;
-use tracing::{error, info};
+use text_extraction::export::ExportFormat;
+use text_extraction::page_images;
+use text_extraction::{PageExtraction, PdfPassword, TempPdf};
+use tracing::{error, info, warn};
 use uuid::Uuid;
 
 use deadpool_postgres::{Manager, ManagerConfig, Pool, RecyclingMethod};
@@ -24,11 +41,28 @@ use lopdf::{Bookmark, Document, Object, ObjectId};
 use serde::{Deserialize, Serialize};
 use zip::ZipArchive;
 
+mod archive;
+mod classify;
+mod direct_upload;
+mod erasure;
+mod prompt_samples;
+
+use archive::Archiver;
+use classify::{ClassifyConfig, RuleInput};
+use direct_upload::S3Store;
+use erasure::{ErasureRequest, Outcome, Signer};
+use prompt_samples::SampleQuery;
+
 /// Liveness endpoint used for container health checks.
 async fn health() -> impl Responder {
     "OK"
 }
 
+/// Readiness endpoint; returns 503 until Postgres and Kafka are connected.
+async fn readyz(readiness: web::Data<Readiness>) -> HttpResponse {
+    readiness.respond()
+}
+
 #[derive(Serialize)]
 /// Minimal representation of an upload stored in the database.
 struct UploadEntry {
@@ -37,13 +71,314 @@ struct UploadEntry {
     status: String,
     #[serde(default)]
     names: Vec<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    job_label: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    external_ref: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    run_state: Option<String>,
+    run_priority: String,
+    legal_hold: bool,
+    /// Operator tags (`PUT /uploads/{id}/notes`).
+    tags: Vec<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    note: Option<String>,
 }
 
 #[derive(Deserialize)]
-/// Query parameters used for listing uploads.
+/// Query parameters accepted by the upload endpoint.
 struct UploadQuery {
     tenant_id: Option<Uuid>,
     pipeline_id: Option<Uuid>,
+    job_label: Option<String>,
+    external_ref: Option<String>,
+    priority: Option<String>,
+    /// `false` parks the upload until `POST /uploads/{id}/run` is called.
+    run_immediately: Option<bool>,
+}
+
+#[derive(Deserialize)]
+/// Body of `POST /uploads/direct`; the fields match [`UploadQuery`].
+struct DirectUploadRequest {
+    file_name: String,
+    tenant_id: Option<Uuid>,
+    pipeline_id: Option<Uuid>,
+    job_label: Option<String>,
+    external_ref: Option<String>,
+    priority: Option<String>,
+    run_immediately: Option<bool>,
+}
+
+#[derive(Deserialize, Default)]
+/// Optional body of `POST /uploads/{id}/run`.
+struct RunTriggerRequest {
+    pipeline_id: Option<Uuid>,
+    priority: Option<RunPriority>,
+}
+
+#[derive(Deserialize)]
+/// Query parameters of `POST /pdf/{id}/append`.
+struct AppendQuery {
+    /// `false` skips re-running pipelines configured with `run_on_update`.
+    run_pipelines: Option<bool>,
+    priority: Option<String>,
+}
+
+#[derive(Deserialize)]
+/// Filters for listing uploads (`job_label` as substring, `external_ref` and
+/// operator `tag` exact).
+struct UploadListQuery {
+    job_label: Option<String>,
+    external_ref: Option<String>,
+    tag: Option<String>,
+}
+
+#[derive(Deserialize, Default)]
+/// Optional body of `PUT /admin/tenants/{id}/prompt-samples`.
+struct SampleApproval {
+    approved_by: Option<String>,
+}
+
+#[derive(Deserialize)]
+/// Body of `PUT /pdf/{id}/legal-hold`.
+struct LegalHoldInput {
+    reason: String,
+    placed_by: Option<String>,
+}
+
+#[derive(Deserialize)]
+/// Query of `DELETE /pdf/{id}/legal-hold`.
+struct LegalHoldRelease {
+    removed_by: Option<String>,
+}
+
+#[derive(Deserialize)]
+/// Query of `GET /uploads/{id}/extract`.
+struct ExtractQuery {
+    /// `text` (default), `json`, `hocr` or `alto`.
+    format: Option<String>,
+}
+
+#[derive(Deserialize)]
+/// Filters of `GET /pdf/{id}/entities`.
+struct EntityQuery {
+    kind: Option<String>,
+    page: Option<i32>,
+    /// Case-insensitive substring of the normalized value.
+    q: Option<String>,
+}
+
+#[derive(Deserialize)]
+/// Parameters of `GET /pdf/{id}/similar`.
+struct SimilarQuery {
+    /// Minimum estimated similarity, `0.5..=1` (default 0.8).
+    threshold: Option<f64>,
+    limit: Option<i64>,
+}
+
+#[derive(Deserialize)]
+/// Parameters of `POST /pdf/{id}/reocr`.
+struct ReocrQuery {
+    /// Pages whose stored mean OCR confidence is below this value (`0..=1`).
+    min_confidence: f32,
+    /// Runs the pipelines that already ran on the PDF again afterwards.
+    #[serde(default)]
+    rerun: bool,
+}
+
+#[derive(Serialize)]
+struct SimilarDocument {
+    pdf_id: i32,
+    similarity: f64,
+    /// Source file names of the merged PDF.
+    names: Option<String>,
+}
+
+#[derive(Deserialize)]
+/// Filters of `GET /pdf/{id}/annotations`.
+struct AnnotationQuery {
+    page: Option<i32>,
+    final_key: Option<String>,
+}
+
+#[derive(Serialize)]
+/// Legal hold state of a merged PDF.
+struct LegalHold {
+    pdf_id: i32,
+    legal_hold: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    reason: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    placed_by: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    placed_at: Option<String>,
+}
+
+/// Trims a free-text reference value and drops it when blank.
+fn clean_reference(value: Option<&str>) -> Option<String> {
+    value
+        .map(str::trim)
+        .filter(|v| !v.is_empty())
+        .map(str::to_string)
+}
+
+/// Parses a run priority, rejecting unknown values with 400.
+fn parse_priority(value: &str) -> Result<RunPriority, Error> {
+    RunPriority::from_str(value.trim())
+        .map_err(|_| actix_web::error::ErrorBadRequest(format!("invalid priority '{value}'")))
+}
+
+/// Parses boolean form fields (`true`/`false`, `1`/`0`, `yes`/`no`).
+fn parse_flag(value: &str) -> Option<bool> {
+    match value.trim().to_ascii_lowercase().as_str() {
+        "true" | "1" | "yes" | "on" => Some(true),
+        "false" | "0" | "no" | "off" => Some(false),
+        _ => None,
+    }
+}
+
+/// Initial `uploads.run_state`: without a pipeline nothing is triggered unless
+/// the upload is classified (see `classify.rs`), otherwise the run either
+/// follows the extraction or waits for a manual trigger.
+fn initial_run_state(
+    pipeline_id: Uuid,
+    run_immediately: bool,
+    auto_classify: bool,
+) -> Option<&'static str> {
+    if pipeline_id.is_nil() {
+        (auto_classify && run_immediately).then_some(classify::STATE_CLASSIFY)
+    } else if run_immediately {
+        Some("pending")
+    } else {
+        Some("parked")
+    }
+}
+
+/// Reads a small multipart text field completely.
+async fn read_text_field(field: &mut actix_multipart::Field) -> Result<String, Error> {
+    let mut buf = Vec::new();
+    while let Some(chunk) = field.next().await {
+        let bytes: Bytes = chunk?;
+        buf.extend_from_slice(&bytes);
+    }
+    Ok(String::from_utf8_lossy(&buf).into_owned())
+}
+
+/// Publishes a `pipeline-run` event for the pipeline runner.
+async fn publish_run(
+    producer: &FutureProducer,
+    pdf_id: i32,
+    pipeline_id: Uuid,
+    priority: RunPriority,
+) {
+    let payload = serde_json::to_string(&PdfUploaded {
+        schema_version: PdfUploaded::SCHEMA_VERSION,
+        pdf_id,
+        pipeline_id,
+        priority,
+        rerun_of: None,
+        appended_from: None,
+        run_id: Some(Uuid::new_v4()),
+        reocr: None,
+    })
+    .unwrap();
+    match producer
+        .send(
+            FutureRecord::to("pipeline-run")
+                .payload(&payload)
+                .key(&kafka::document_key(pdf_id)),
+            Duration::from_secs(0),
+        )
+        .await
+    {
+        Ok(_) => info!(
+            step = "kafka.produce.ok",
+            topic = "pipeline-run",
+            pdf_id,
+            pipeline_id = %pipeline_id,
+            priority = %priority
+        ),
+        Err((e, _)) => error!(%e, pdf_id, "failed to publish pipeline-run event"),
+    }
+}
+
+/// Reads a multipart `file` field; ZIP archives contribute their PDF entries.
+async fn read_file_field(
+    field: &mut actix_multipart::Field,
+    files: &mut Vec<(Vec<u8>, String)>,
+) -> Result<(), Error> {
+    let filename = field
+        .content_disposition()
+        .get_filename()
+        .map(|f| f.to_string())
+        .unwrap_or_default();
+    let mut buf = Vec::new();
+    while let Some(chunk) = field.next().await {
+        let bytes: Bytes = chunk?;
+        buf.extend_from_slice(&bytes);
+    }
+
+    if filename.to_lowercase().ends_with(".zip") {
+        let reader = std::io::Cursor::new(buf);
+        let mut zip = ZipArchive::new(reader)
+            .map_err(|e| actix_web::error::ErrorBadRequest(e.to_string()))?;
+        for i in 0..zip.len() {
+            let mut f = zip
+                .by_index(i)
+                .map_err(|e| actix_web::error::ErrorBadRequest(e.to_string()))?;
+            if f.name().to_lowercase().ends_with(".pdf") {
+                let mut data = Vec::new();
+                std::io::copy(&mut f, &mut data)
+                    .map_err(|e| actix_web::error::ErrorBadRequest(e.to_string()))?;
+                files.push((data, f.name().to_string()));
+            }
+        }
+    } else {
+        files.push((buf, filename));
+    }
+    Ok(())
+}
+
+/// Normalises filenames extracted from ZIP archives or multipart payloads.
+fn normalize_source_name(raw: &str) -> Option<String> {
+    let trimmed = raw.trim();
+    if trimmed.is_empty() {
+        return None;
+    }
+
+    // ZIP-Einträge können Pfade enthalten – nur den eigentlichen Dateinamen behalten
+    let normalized = trimmed.replace('\\', "/");
+    let candidate = normalized
+        .rsplit('/')
+        .next()
+        .map(str::trim)
+        .unwrap_or(trimmed);
+
+    if candidate.is_empty() {
+        None
+    } else {
+        Some(candidate.to_string())
+    }
+}
+
+/// Appends the names of `files` to `existing` source names, without duplicates.
+fn source_names(existing: Vec<String>, files: &[(Vec<u8>, String)]) -> Vec<String> {
+    let mut seen: HashSet<String> = existing.iter().cloned().collect();
+    let mut names = existing;
+    let mut any_named = false;
+    for (_, raw_name) in files {
+        if let Some(clean) = normalize_source_name(raw_name) {
+            any_named = true;
+            if seen.insert(clean.clone()) {
+                names.push(clean);
+            }
+        }
+    }
+
+    if !any_named {
+        names.extend(files.iter().map(|f| f.1.clone()));
+    }
+    names
 }
 
 /// Ensures SSL is disabled in local connection strings.
@@ -199,6 +534,35 @@ fn merge_documents(documents: Vec<Document>) -> std::io::Result<Vec<u8>> {
     Ok(buf)
 }
 
+/// Tenant of an upload: explicit value, then `X-Tenant-ID`, then the `Default` tenant.
+async fn resolve_tenant(
+    req: &HttpRequest,
+    explicit: Option<Uuid>,
+    client: &deadpool_postgres::Client,
+) -> Result<Uuid, Error> {
+    if let Some(t) = explicit {
+        return Ok(t);
+    }
+    if let Some(h) = req.headers().get("X-Tenant-ID") {
+        let s = h
+            .to_str()
+            .map_err(|_| actix_web::error::ErrorBadRequest("invalid X-Tenant-ID header"))?;
+        return Uuid::parse_str(s)
+            .map_err(|_| actix_web::error::ErrorBadRequest("invalid X-Tenant-ID value"));
+    }
+    // Fallback auf 'Default' (nur wenn vorhanden)
+    match client
+        .query_opt("SELECT id FROM tenants WHERE name='Default' LIMIT 1", &[])
+        .await
+        .map_err(actix_web::error::ErrorInternalServerError)?
+    {
+        Some(row) => Ok(row.get::<_, Uuid>(0)),
+        None => Err(actix_web::error::ErrorBadRequest(
+            "tenant_id missing and no 'Default' tenant found",
+        )),
+    }
+}
+
 /// Handles multipart uploads, stores the merged PDF and publishes events.
 async fn upload(
     req: HttpRequest,
@@ -206,6 +570,7 @@ async fn upload(
     mut payload: Multipart,
     db: web::Data<Pool>,
     producer: web::Data<FutureProducer>,
+    classify: web::Data<ClassifyConfig>,
 ) -> Result<HttpResponse, Error> {
     info!("handling upload request");
     let client = db
@@ -213,35 +578,21 @@ async fn upload(
         .await
         .map_err(actix_web::error::ErrorInternalServerError)?;
 
-    // --- Tenant ermitteln ---
-    // 1) aus Query ?tenant_id=...  2) aus Header X-Tenant-ID  3) Fallback: tenants.name='Default'
-    let tenant_id = if let Some(t) = q.tenant_id {
-        t
-    } else if let Some(h) = req.headers().get("X-Tenant-ID") {
-        let s = h
-            .to_str()
-            .map_err(|_| actix_web::error::ErrorBadRequest("invalid X-Tenant-ID header"))?;
-        Uuid::parse_str(s)
-            .map_err(|_| actix_web::error::ErrorBadRequest("invalid X-Tenant-ID value"))?
-    } else {
-        // Fallback auf 'Default' (nur wenn vorhanden)
-        match client
-            .query_opt("SELECT id FROM tenants WHERE name='Default' LIMIT 1", &[])
-            .await
-            .map_err(actix_web::error::ErrorInternalServerError)?
-        {
-            Some(row) => row.get::<_, Uuid>(0),
-            None => {
-                return Err(actix_web::error::ErrorBadRequest(
-                    "tenant_id missing and no 'Default' tenant found",
-                ))
-            }
-        }
-    };
+    let tenant_id = resolve_tenant(&req, q.tenant_id, &client).await?;
 
     // optional: pipeline_id aus Query, falls nicht im Body übergeben wird
     let mut files: Vec<(Vec<u8>, String)> = Vec::new();
     let mut pipeline_id: Option<String> = q.pipeline_id.map(|u| u.to_string());
+    // Label und Aktenzeichen wandern bis in pipeline_runs und analysis_history
+    let mut job_label = clean_reference(q.job_label.as_deref());
+    let mut external_ref = clean_reference(q.external_ref.as_deref());
+    let mut priority = match q.priority.as_deref() {
+        Some(value) => parse_priority(value)?,
+        None => RunPriority::default(),
+    };
+    let mut run_immediately = q.run_immediately.unwrap_or(true);
+    // Nur als Multipart-Feld, damit es nicht in Access-Logs landet
+    let mut password: Option<String> = None;
 
     // Upload-Row mit tenant_id anlegen (status=merging)
     let upload_id: i32 = client
@@ -252,46 +603,56 @@ async fn upload(
         .await
         .map_err(actix_web::error::ErrorInternalServerError)?
         .get(0);
+    timeline::record(
+        &client,
+        &TimelineEvent::new("pdf-ingest", "uploaded").upload(Some(upload_id)),
+    )
+    .await;
 
     // Multipart parsen (Dateien + evtl. pipeline_id Feld)
     while let Some(item) = payload.next().await {
         let mut field = item?;
         match field.name() {
             "file" => {
-                let filename = field
-                    .content_disposition()
-                    .get_filename()
-                    .map(|f| f.to_string())
-                    .unwrap_or_default();
+                read_file_field(&mut field, &mut files).await?;
+            }
+            "pipeline_id" => {
+                while let Some(chunk) = field.next().await {
+                    let bytes: Bytes = chunk?;
+                    pipeline_id = Some(std::str::from_utf8(&bytes).unwrap_or_default().to_string());
+                }
+            }
+            name @ ("job_label" | "external_ref") => {
+                let is_label = name == "job_label";
                 let mut buf = Vec::new();
                 while let Some(chunk) = field.next().await {
                     let bytes: Bytes = chunk?;
                     buf.extend_from_slice(&bytes);
                 }
-
-                if filename.to_lowercase().ends_with(".zip") {
-                    let reader = std::io::Cursor::new(buf);
-                    let mut zip = ZipArchive::new(reader)
-                        .map_err(|e| actix_web::error::ErrorBadRequest(e.to_string()))?;
-                    for i in 0..zip.len() {
-                        let mut f = zip
-                            .by_index(i)
-                            .map_err(|e| actix_web::error::ErrorBadRequest(e.to_string()))?;
-                        if f.name().to_lowercase().ends_with(".pdf") {
-                            let mut data = Vec::new();
-                            std::io::copy(&mut f, &mut data)
-                                .map_err(|e| actix_web::error::ErrorBadRequest(e.to_string()))?;
-                            files.push((data, f.name().to_string()));
-                        }
-                    }
+                let value = clean_reference(std::str::from_utf8(&buf).ok());
+                if is_label {
+                    job_label = value.or(job_label);
                 } else {
-                    files.push((buf, filename));
+                    external_ref = value.or(external_ref);
                 }
             }
-            "pipeline_id" => {
-                while let Some(chunk) = field.next().await {
-                    let bytes: Bytes = chunk?;
-                    pipeline_id = Some(std::str::from_utf8(&bytes).unwrap_or_default().to_string());
+            "priority" => {
+                priority = parse_priority(&read_text_field(&mut field).await?)?;
+            }
+            "run_immediately" => {
+                let value = read_text_field(&mut field).await?;
+                run_immediately = parse_flag(&value).ok_or_else(|| {
+                    actix_web::error::ErrorBadRequest(format!("invalid run_immediately '{value}'"))
+                })?;
+            }
+            "password" => {
+                password = Some(read_text_field(&mut field).await?).filter(|p| !p.is_empty());
+            }
+            // Altes Feld des SharePoint-Ingest, Gegenteil von run_immediately
+            "defer_pipeline" => {
+                let value = read_text_field(&mut field).await?;
+                if let Some(defer) = parse_flag(&value) {
+                    run_immediately = !defer;
                 }
             }
             // Unbekannte Felder drainen
@@ -302,6 +663,12 @@ async fn upload(
     if files.is_empty() {
         return Ok(HttpResponse::BadRequest().finish());
     }
+    if password.is_some() && files.len() > 1 {
+        return Err(actix_web::error::ErrorBadRequest(
+            "password is only supported for single-file uploads",
+        ));
+    }
+    let password = password.as_deref().map(seal_password).transpose()?;
 
     // Merge oder einzelnes PDF
     let data = if files.len() == 1 {
@@ -322,6 +689,60 @@ async fn upload(
         merge_documents(docs).map_err(actix_web::error::ErrorInternalServerError)?
     };
 
+    let names = source_names(Vec::new(), &files);
+    let pid = pipeline_id
+        .as_deref()
+        .and_then(|s| Uuid::parse_str(s).ok())
+        .unwrap_or_else(Uuid::nil);
+    let stored = StoredUpload {
+        upload_id,
+        pipeline_id: pid,
+        job_label,
+        external_ref,
+        priority,
+        run_state: initial_run_state(pid, run_immediately, classify.enabled).map(str::to_string),
+        password,
+    };
+    let response = store_merged_pdf(&client, &producer, &stored, data, &names).await?;
+    Ok(HttpResponse::Ok().json(response))
+}
+
+/// Upload row a merged PDF is attached to, with the values for its run.
+struct StoredUpload {
+    upload_id: i32,
+    /// Nil when no pipeline was given.
+    pipeline_id: Uuid,
+    job_label: Option<String>,
+    external_ref: Option<String>,
+    priority: RunPriority,
+    run_state: Option<String>,
+    /// Sealed password of an encrypted PDF, see [`seal_password`].
+    password: Option<serde_json::Value>,
+}
+
+/// Seals the password of an encrypted PDF with `CREDENTIALS_MASTER_KEY`;
+/// text-extraction opens it from `uploads.pdf_password`.
+fn seal_password(password: &str) -> Result<serde_json::Value, Error> {
+    let key = MasterKey::from_env().map_err(|e| {
+        warn!(%e, "pdf password given without master key");
+        actix_web::error::ErrorServiceUnavailable(
+            "password-protected uploads need CREDENTIALS_MASTER_KEY",
+        )
+    })?;
+    let sealed = key
+        .seal(password)
+        .map_err(actix_web::error::ErrorInternalServerError)?;
+    serde_json::to_value(sealed).map_err(actix_web::error::ErrorInternalServerError)
+}
+
+/// Stores the PDF of an upload, links it and publishes `pdf-merged`.
+async fn store_merged_pdf(
+    client: &deadpool_postgres::Client,
+    producer: &FutureProducer,
+    upload: &StoredUpload,
+    data: Vec<u8>,
+    names: &[String],
+) -> Result<UploadResponse, Error> {
     info!(bytes = data.len(), "storing pdf");
     info!(step = "pdf.prepare", bytes = data.len(), "ready to insert");
     let sha256 = format!("{:x}", Sha256::digest(&data));
@@ -337,58 +758,44 @@ async fn upload(
     info!(id, "pdf stored in database");
     info!(step = "db.insert.ok", table = "merged_pdfs", id, sha256 = %sha256, size_bytes, "inserted merged pdf");
 
-    // Upload-Row updaten
-    let pid = pipeline_id
-        .as_deref()
-        .and_then(|s| Uuid::parse_str(s).ok())
-        .unwrap_or_else(Uuid::nil);
+    // Upload-Row updaten; muss vor dem pdf-merged-Event stehen, sonst verpasst
+    // der Trigger das text-extracted-Event
+    let pid = upload.pipeline_id;
+    let upload_id = upload.upload_id;
+    let run_state = upload.run_state.as_deref();
+    let priority = upload.priority;
+    let priority_text = priority.to_string();
 
     let _ = client
         .execute(
-            "UPDATE uploads SET pdf_id=$1, pipeline_id=$2, status='ocr' WHERE id=$3",
-            &[&id, &pid, &upload_id],
+            "UPDATE uploads SET pdf_id=$1, pipeline_id=$2, status='ocr', job_label=$4, external_ref=$5,
+                                run_state=$6, run_priority=$7, pdf_password=$8
+             WHERE id=$3",
+            &[
+                &id,
+                &pid,
+                &upload_id,
+                &upload.job_label,
+                &upload.external_ref,
+                &run_state,
+                &priority_text,
+                &upload.password,
+            ],
         )
         .await;
 
-    info!(step = "uploads.updated", upload_id, pdf_id = id, status = "ocr", pipeline_id = %pid, "upload updated");
+    info!(step = "uploads.updated", upload_id, pdf_id = id, status = "ocr", pipeline_id = %pid, run_state = ?run_state, "upload updated");
+    timeline::record(
+        client,
+        &TimelineEvent::new("pdf-ingest", "merged")
+            .pdf(Some(id))
+            .upload(Some(upload_id))
+            .pipeline(Some(pid).filter(|p| !p.is_nil()))
+            .details(serde_json::json!({ "sha256": sha256, "size_bytes": size_bytes })),
+    )
+    .await;
 
     // Quellen speichern (Dateinamen)
-    /// Normalises filenames extracted from ZIP archives or multipart payloads.
-    fn normalize_source_name(raw: &str) -> Option<String> {
-        let trimmed = raw.trim();
-        if trimmed.is_empty() {
-            return None;
-        }
-
-        // ZIP-Einträge können Pfade enthalten – nur den eigentlichen Dateinamen behalten
-        let normalized = trimmed.replace('\\', "/");
-        let candidate = normalized
-            .rsplit('/')
-            .next()
-            .map(str::trim)
-            .unwrap_or(trimmed);
-
-        if candidate.is_empty() {
-            None
-        } else {
-            Some(candidate.to_string())
-        }
-    }
-
-    let mut seen = HashSet::new();
-    let mut names: Vec<String> = Vec::new();
-    for (_, raw_name) in &files {
-        if let Some(clean) = normalize_source_name(raw_name) {
-            if seen.insert(clean.clone()) {
-                names.push(clean);
-            }
-        }
-    }
-
-    if names.is_empty() {
-        names = files.iter().map(|f| f.1.clone()).collect();
-    }
-
     let _ = client
         .execute(
             "INSERT INTO pdf_sources (pdf_id, names, count) VALUES ($1,$2,$3)
@@ -410,14 +817,22 @@ async fn upload(
 
     // Kafka-Event
     let payload = serde_json::to_string(&PdfUploaded {
+        schema_version: PdfUploaded::SCHEMA_VERSION,
         pdf_id: id,
         pipeline_id: pid,
+        priority,
+        rerun_of: None,
+        appended_from: None,
+        run_id: None,
+        reocr: None,
     })
     .unwrap();
 
     let _ = producer
         .send(
-            FutureRecord::to("pdf-merged").payload(&payload).key(&()),
+            FutureRecord::to("pdf-merged")
+                .payload(&payload)
+                .key(&kafka::document_key(id)),
             Duration::from_secs(0),
         )
         .await;
@@ -430,122 +845,2377 @@ async fn upload(
     );
     info!(id, "published pdf-merged event");
 
-    Ok(HttpResponse::Ok().json(UploadResponse {
+    Ok(UploadResponse {
         id: id.to_string(),
         upload_id: Some(upload_id),
         pdf_id: Some(id),
-    }))
+        run_state: upload.run_state.clone(),
+    })
 }
 
-/// Returns recent uploads for the administrative UI.
-async fn list_uploads(db: web::Data<Pool>) -> Result<HttpResponse, Error> {
+/// Issues a pre-signed `PUT` URL for uploading one large file directly to S3.
+///
+/// The upload stays `awaiting_upload` until `POST /uploads/{id}/complete`.
+async fn create_direct_upload(
+    req: HttpRequest,
+    body: web::Json<DirectUploadRequest>,
+    db: web::Data<Pool>,
+    store: web::Data<Option<S3Store>>,
+    classify: web::Data<ClassifyConfig>,
+) -> Result<HttpResponse, Error> {
+    let Some(store) = store.get_ref() else {
+        return Err(actix_web::error::ErrorNotImplemented(
+            "direct uploads require BLOB_STORE=s3",
+        ));
+    };
+    let body = body.into_inner();
+    if body.file_name.trim().is_empty() {
+        return Err(actix_web::error::ErrorBadRequest("file_name missing"));
+    }
+    let priority = match body.priority.as_deref() {
+        Some(value) => parse_priority(value)?,
+        None => RunPriority::default(),
+    };
     let client = db
         .get()
         .await
         .map_err(actix_web::error::ErrorInternalServerError)?;
-    let rows = client
-        .query(
-            "SELECT u.id, u.pdf_id, u.status, ps.names \
-             FROM uploads u \
-             LEFT JOIN pdf_sources ps ON ps.pdf_id = u.pdf_id \
-             ORDER BY u.id DESC",
-            &[],
-        )
-        .await
-        .map_err(actix_web::error::ErrorInternalServerError)?;
-
-    let items: Vec<UploadEntry> = rows
-        .into_iter()
-        .map(|r| UploadEntry {
-            id: r.get(0),
-            pdf_id: r.get(1),
-            status: r.get(2),
-            names: r
-                .get::<_, Option<String>>(3)
-                .and_then(|raw| serde_json::from_str::<Vec<String>>(&raw).ok())
-                .unwrap_or_default(),
-        })
-        .collect();
-
-    Ok(HttpResponse::Ok().json(items))
-}
+    let tenant_id = resolve_tenant(&req, body.tenant_id, &client).await?;
+    let pipeline_id = body.pipeline_id.unwrap_or_else(Uuid::nil);
+    let run_state = initial_run_state(
+        pipeline_id,
+        body.run_immediately.unwrap_or(true),
+        classify.enabled,
+    );
 
-/// Streams a previously stored merged PDF back to the caller.
-async fn get_pdf(id: web::Path<i32>, db: web::Data<Pool>) -> Result<HttpResponse, Error> {
-    let client = db
-        .get()
+    // pdf_id bleibt bis zum Abschluss leer, der Run-Trigger greift vorher nicht
+    let upload_id: i32 = client
+        .query_one(
+            "INSERT INTO uploads (tenant_id, status, pipeline_id, job_label, external_ref,
+                                  run_state, run_priority)
+             VALUES ($1, 'awaiting_upload', $2, $3, $4, $5, $6) RETURNING id",
+            &[
+                &tenant_id,
+                &pipeline_id,
+                &clean_reference(body.job_label.as_deref()),
+                &clean_reference(body.external_ref.as_deref()),
+                &run_state,
+                &priority.to_string(),
+            ],
+        )
         .await
-        .map_err(actix_web::error::ErrorInternalServerError)?;
-    let stmt = client
-        .prepare("SELECT data FROM merged_pdfs WHERE id=$1")
+        .map_err(actix_web::error::ErrorInternalServerError)?
+        .get(0);
+    let key = store.object_key(upload_id, &body.file_name);
+    client
+        .execute(
+            "UPDATE uploads SET storage_key=$2 WHERE id=$1",
+            &[&upload_id, &key],
+        )
         .await
         .map_err(actix_web::error::ErrorInternalServerError)?;
-    match client.query_opt(&stmt, &[&id.into_inner()]).await {
-        Ok(Some(row)) => {
-            let data: Vec<u8> = row.get(0);
-            Ok(HttpResponse::Ok()
-                .insert_header((header::CONTENT_TYPE, "application/pdf"))
-                .body(data))
-        }
-        Ok(None) => Ok(HttpResponse::NotFound().finish()),
-        Err(e) => Err(actix_web::error::ErrorInternalServerError(e)),
-    }
+    timeline::record(
+        &client,
+        &TimelineEvent::new("pdf-ingest", "upload_url_issued").upload(Some(upload_id)),
+    )
+    .await;
+
+    let now = chrono::Utc::now();
+    let expires_at = now + chrono::Duration::seconds(store.ttl().as_secs() as i64);
+    info!(upload_id, key = %key, "issued direct upload url");
+    Ok(HttpResponse::Created().json(serde_json::json!({
+        "upload_id": upload_id,
+        "method": "PUT",
+        "url": store.presign("PUT", &key, now),
+        "key": key,
+        "expires_at": expires_at.to_rfc3339(),
+    })))
 }
 
-/// Returns the OCR JSON stored for a merged PDF.
-async fn get_extract(id: web::Path<i32>, db: web::Data<Pool>) -> Result<HttpResponse, Error> {
+/// Takes over a file uploaded to S3 and feeds it into the regular merge path.
+async fn complete_direct_upload(
+    path: web::Path<i32>,
+    db: web::Data<Pool>,
+    producer: web::Data<FutureProducer>,
+    store: web::Data<Option<S3Store>>,
+) -> Result<HttpResponse, Error> {
+    let Some(store) = store.get_ref() else {
+        return Err(actix_web::error::ErrorNotImplemented(
+            "direct uploads require BLOB_STORE=s3",
+        ));
+    };
+    let upload_id = path.into_inner();
     let client = db
         .get()
         .await
         .map_err(actix_web::error::ErrorInternalServerError)?;
-    let stmt = client
-        .prepare(
-            // Alle Seiten in stabiler Reihenfolge zusammenführen
-            "SELECT COALESCE(
-                 string_agg(text, E'\n' ORDER BY page_no),
-                 ''
-             ) FROM pdf_texts WHERE merged_pdf_id = $1",
+
+    // Upload exklusiv übernehmen, damit doppelte Aufrufe nicht zweimal mergen
+    let Some(row) = client
+        .query_opt(
+            "UPDATE uploads SET status='merging'
+             WHERE id=$1 AND status='awaiting_upload' AND storage_key IS NOT NULL
+             RETURNING storage_key, pipeline_id, job_label, external_ref, run_state, run_priority",
+            &[&upload_id],
         )
         .await
-        .map_err(actix_web::error::ErrorInternalServerError)?;
-    match client.query_opt(&stmt, &[&id.into_inner()]).await {
-        Ok(Some(row)) => {
-            let text: String = row.get(0);
-            Ok(HttpResponse::Ok()
-                .insert_header((header::CONTENT_TYPE, "text/plain"))
-                .body(text))
+        .map_err(actix_web::error::ErrorInternalServerError)?
+    else {
+        return Err(actix_web::error::ErrorConflict(
+            "upload not found or not awaiting a direct upload",
+        ));
+    };
+    let key: String = row.get("storage_key");
+
+    let data = match store.fetch(&key).await {
+        Ok(Some(data)) if !data.is_empty() => data,
+        result => {
+            let _ = client
+                .execute(
+                    "UPDATE uploads SET status='awaiting_upload' WHERE id=$1",
+                    &[&upload_id],
+                )
+                .await;
+            return match result {
+                Err(e) => {
+                    error!(%e, upload_id, key = %key, "fetch direct upload failed");
+                    Err(actix_web::error::ErrorBadGateway(
+                        "object storage unavailable",
+                    ))
+                }
+                _ => Err(actix_web::error::ErrorConflict("file not uploaded yet")),
+            };
         }
-        Ok(None) => Ok(HttpResponse::NotFound().finish()),
-        Err(e) => Err(actix_web::error::ErrorInternalServerError(e)),
-    }
+    };
+    timeline::record(
+        &client,
+        &TimelineEvent::new("pdf-ingest", "uploaded")
+            .upload(Some(upload_id))
+            .details(serde_json::json!({ "direct": true })),
+    )
+    .await;
+
+    let stored = StoredUpload {
+        upload_id,
+        pipeline_id: row
+            .get::<_, Option<Uuid>>("pipeline_id")
+            .unwrap_or_else(Uuid::nil),
+        job_label: row.get("job_label"),
+        external_ref: row.get("external_ref"),
+        priority: row
+            .get::<_, Option<String>>("run_priority")
+            .and_then(|p| RunPriority::from_str(&p).ok())
+            .unwrap_or_default(),
+        run_state: row.get("run_state"),
+        password: None,
+    };
+    let names = source_names(Vec::new(), &[(Vec::new(), key.clone())]);
+    let response = store_merged_pdf(&client, &producer, &stored, data, &names).await?;
+    // Die Bytes liegen jetzt in merged_pdfs
+    store.delete(&key).await;
+    Ok(HttpResponse::Ok().json(response))
 }
 
-/// Deletes a merged PDF and its metadata from the database.
-async fn delete_pdf(id: web::Path<i32>, db: web::Data<Pool>) -> Result<HttpResponse, Error> {
+/// Appends the uploaded files to an existing merged PDF.
+///
+/// The previous document is kept in `pdf_versions`, only the appended pages are
+/// extracted again, and uploads of the PDF whose pipeline sets `run_on_update`
+/// run once the new text is available.
+async fn append_pdf(
+    id: web::Path<i32>,
+    q: web::Query<AppendQuery>,
+    mut payload: Multipart,
+    db: web::Data<Pool>,
+    producer: web::Data<FutureProducer>,
+    archive: web::Data<Option<Archiver>>,
+) -> Result<HttpResponse, Error> {
     let id = id.into_inner();
+    let priority = match q.priority.as_deref() {
+        Some(value) => parse_priority(value)?,
+        None => RunPriority::default(),
+    };
+    let run_pipelines = q.run_pipelines.unwrap_or(true);
+
+    let mut files: Vec<(Vec<u8>, String)> = Vec::new();
+    while let Some(item) = payload.next().await {
+        let mut field = item?;
+        if field.name() == "file" {
+            read_file_field(&mut field, &mut files).await?;
+        } else {
+            while let Some(_chunk) = field.next().await {}
+        }
+    }
+    if files.is_empty() {
+        return Ok(HttpResponse::BadRequest().finish());
+    }
+
+    let mut client = db
+        .get()
+        .await
+        .map_err(actix_web::error::ErrorInternalServerError)?;
+    let tx = client
+        .transaction()
+        .await
+        .map_err(actix_web::error::ErrorInternalServerError)?;
+
+    // Zeile sperren, damit parallele Anhänge nacheinander versioniert werden
+    let Some(row) = tx
+        .query_opt(
+            "SELECT data FROM merged_pdfs WHERE id=$1 FOR UPDATE",
+            &[&id],
+        )
+        .await
+        .map_err(actix_web::error::ErrorInternalServerError)?
+    else {
+        return Ok(HttpResponse::NotFound().finish());
+    };
+    let Some(current) = row.get::<_, Option<Vec<u8>>>(0) else {
+        // Archiviert: Wiederherstellung anstoßen, Anhängen später erneut versuchen
+        drop(tx);
+        let retry_after =
+            start_restore(&client, &db, archive.get_ref().as_ref(), id).await?;
+        return Ok(HttpResponse::Conflict()
+            .insert_header((header::RETRY_AFTER, retry_after.to_string()))
+            .json(serde_json::json!({
+                "error": "document is archived; retry once it is restored",
+                "status": archive::TIER_RESTORING,
+            })));
+    };
+    let existing =
+        Document::load_mem(&current).map_err(actix_web::error::ErrorInternalServerError)?;
+    let appended_from = existing.get_pages().len() as i32;
+
+    let mut docs = vec![existing];
+    for (bytes, name) in &files {
+        match Document::load_mem(bytes) {
+            Ok(doc) => docs.push(doc),
+            Err(e) => {
+                return Err(actix_web::error::ErrorBadRequest(format!(
+                    "invalid PDF '{}': {e}",
+                    name
+                )));
+            }
+        }
+    }
+    let pages_added: usize = docs[1..].iter().map(|d| d.get_pages().len()).sum();
+    let data = merge_documents(docs).map_err(actix_web::error::ErrorInternalServerError)?;
+    if data.is_empty() {
+        return Err(actix_web::error::ErrorInternalServerError(
+            "merged document is empty",
+        ));
+    }
+    let sha256 = format!("{:x}", Sha256::digest(&data));
+    let size_bytes = data.len() as i32;
+
+    // Bisherigen Stand als Version sichern, dann ersetzen
+    tx.execute(
+        "INSERT INTO pdf_versions (pdf_id, version, sha256, size_bytes, page_count, data)
+         SELECT id, version, sha256, size_bytes, $2, data FROM merged_pdfs WHERE id=$1",
+        &[&id, &appended_from],
+    )
+    .await
+    .map_err(actix_web::error::ErrorInternalServerError)?;
+    let version: i32 = tx
+        .query_one(
+            "UPDATE merged_pdfs SET data=$2, sha256=$3, size_bytes=$4, version=version+1
+             WHERE id=$1 RETURNING version",
+            &[&id, &data, &sha256, &size_bytes],
+        )
+        .await
+        .map_err(actix_web::error::ErrorInternalServerError)?
+        .get(0);
+
+    let existing_names: Vec<String> = tx
+        .query_opt("SELECT names FROM pdf_sources WHERE pdf_id=$1", &[&id])
+        .await
+        .map_err(actix_web::error::ErrorInternalServerError)?
+        .and_then(|row| row.get::<_, Option<String>>(0))
+        .and_then(|raw| serde_json::from_str(&raw).ok())
+        .unwrap_or_default();
+    let names = source_names(existing_names, &files);
+    tx.execute(
+        "INSERT INTO pdf_sources (pdf_id, names, count) VALUES ($1,$2,$3)
+         ON CONFLICT (pdf_id) DO UPDATE SET names=EXCLUDED.names, count=EXCLUDED.count",
+        &[
+            &id,
+            &serde_json::to_string(&names).unwrap(),
+            &(names.len() as i32),
+        ],
+    )
+    .await
+    .map_err(actix_web::error::ErrorInternalServerError)?;
+    tx.commit()
+        .await
+        .map_err(actix_web::error::ErrorInternalServerError)?;
+    info!(pdf_id = id, version, appended_from, "pages appended");
+
+    // Muss vor dem pdf-merged-Event stehen, sonst verpasst der Trigger das text-extracted-Event
+    let _ = client
+        .execute("UPDATE uploads SET status='ocr' WHERE pdf_id=$1", &[&id])
+        .await;
+    let mut triggered: Vec<i32> = Vec::new();
+    if run_pipelines {
+        let priority_text = priority.to_string();
+        match client
+            .query(
+                "UPDATE uploads u SET run_state='pending', run_priority=$2
+                 FROM pipelines p
+                 WHERE u.pdf_id=$1 AND p.id=u.pipeline_id
+                   AND COALESCE((p.config_json->>'run_on_update')::boolean, FALSE)
+                 RETURNING u.id",
+                &[&id, &priority_text],
+            )
+            .await
+        {
+            Ok(rows) => triggered = rows.iter().map(|r| r.get(0)).collect(),
+            Err(e) => warn!(%e, pdf_id = id, "failed to mark uploads for update runs"),
+        }
+    }
+
+    timeline::record(
+        &client,
+        &TimelineEvent::new("pdf-ingest", "appended")
+            .pdf(Some(id))
+            .details(serde_json::json!({
+                "version": version,
+                "appended_from": appended_from,
+                "pages_added": pages_added,
+                "files": files.iter().map(|f| f.1.as_str()).collect::<Vec<_>>(),
+                "triggered_uploads": triggered,
+            })),
+    )
+    .await;
+
+    let payload = serde_json::to_string(&PdfUploaded {
+        schema_version: PdfUploaded::SCHEMA_VERSION,
+        pdf_id: id,
+        pipeline_id: Uuid::nil(),
+        priority,
+        rerun_of: None,
+        appended_from: Some(appended_from),
+        run_id: None,
+        reocr: None,
+    })
+    .unwrap();
+    if let Err((e, _)) = producer
+        .send(
+            FutureRecord::to("pdf-merged")
+                .payload(&payload)
+                .key(&kafka::document_key(id)),
+            Duration::from_secs(0),
+        )
+        .await
+    {
+        error!(%e, pdf_id = id, "failed to publish pdf-merged event");
+    }
+
+    Ok(HttpResponse::Ok().json(serde_json::json!({
+        "pdf_id": id,
+        "version": version,
+        "appended_from": appended_from,
+        "pages_added": pages_added,
+        "sha256": sha256,
+        "size_bytes": size_bytes,
+        "triggered_uploads": triggered,
+    })))
+}
+
+/// Triggers the pipeline run of an upload manually (e.g. a parked upload).
+///
+/// Runs immediately when the text is already extracted, otherwise the upload
+/// is marked `pending` and the run follows the `text-extracted` event.
+async fn trigger_run(
+    path: web::Path<i32>,
+    body: Option<web::Json<RunTriggerRequest>>,
+    db: web::Data<Pool>,
+    producer: web::Data<FutureProducer>,
+) -> Result<HttpResponse, Error> {
+    let upload_id = path.into_inner();
+    let body = body.map(web::Json::into_inner).unwrap_or_default();
+    let client = db
+        .get()
+        .await
+        .map_err(actix_web::error::ErrorInternalServerError)?;
+    let Some(row) = client
+        .query_opt(
+            "SELECT pipeline_id, run_priority FROM uploads WHERE id=$1",
+            &[&upload_id],
+        )
+        .await
+        .map_err(actix_web::error::ErrorInternalServerError)?
+    else {
+        return Ok(HttpResponse::NotFound().finish());
+    };
+    let stored_pipeline: Option<Uuid> = row.get(0);
+    let Some(pipeline_id) = body
+        .pipeline_id
+        .or(stored_pipeline)
+        .filter(|id| !id.is_nil())
+    else {
+        return Err(actix_web::error::ErrorBadRequest("pipeline_id missing"));
+    };
+    let priority = body.priority.unwrap_or_else(|| {
+        row.get::<_, Option<String>>(1)
+            .and_then(|p| RunPriority::from_str(&p).ok())
+            .unwrap_or_default()
+    });
+    let priority_text = priority.to_string();
+
+    // Status und Trigger in einem Statement, damit kein text-extracted-Event verloren geht
+    let row = client
+        .query_one(
+            "UPDATE uploads
+             SET pipeline_id=$2, run_priority=$3,
+                 run_state = CASE WHEN lower(status)='ready' THEN 'triggered' ELSE 'pending' END
+             WHERE id=$1
+             RETURNING run_state, pdf_id",
+            &[&upload_id, &pipeline_id, &priority_text],
+        )
+        .await
+        .map_err(actix_web::error::ErrorInternalServerError)?;
+    let run_state: String = row.get(0);
+    let pdf_id: Option<i32> = row.get(1);
+
+    if let (Some(pdf_id), "triggered") = (pdf_id, run_state.as_str()) {
+        publish_run(&producer, pdf_id, pipeline_id, priority).await;
+        timeline::record(
+            &client,
+            &TimelineEvent::new("pdf-ingest", "run_triggered")
+                .pdf(Some(pdf_id))
+                .upload(Some(upload_id))
+                .pipeline(Some(pipeline_id))
+                .details(serde_json::json!({ "priority": priority, "manual": true })),
+        )
+        .await;
+    }
+
+    Ok(HttpResponse::Accepted().json(serde_json::json!({
+        "upload_id": upload_id,
+        "pdf_id": pdf_id,
+        "pipeline_id": pipeline_id,
+        "priority": priority,
+        "run_state": run_state,
+    })))
+}
+
+/// Starts pending pipeline runs once their text has been extracted and routes
+/// uploads waiting for the classification.
+async fn run_trigger_consumer(
+    pool: Pool,
+    producer: FutureProducer,
+    broker: String,
+    classify: ClassifyConfig,
+) {
+    let backoff = Backoff::from_env();
+    let consumer: StreamConsumer = retry_with_backoff("kafka-consumer", backoff, || async {
+        let consumer: StreamConsumer = ClientConfig::new()
+            .set("group.id", "pdf-ingest")
+            .set("bootstrap.servers", &broker)
+            .set("enable.auto.commit", "true")
+            .create()?;
+        consumer.subscribe(&["text-extracted"])?;
+        Ok::<_, rdkafka::error::KafkaError>(consumer)
+    })
+    .await;
+    info!("kafka consumer subscribed to text-extracted");
+
+    loop {
+        let message = match consumer.recv().await {
+            Ok(m) => m,
+            Err(e) => {
+                error!(%e, "kafka error");
+                continue;
+            }
+        };
+        let Some(Ok(payload)) = message.payload_view::<str>() else {
+            continue;
+        };
+        let evt: TextExtracted = match serde_json::from_str(payload) {
+            Ok(evt) => evt,
+            Err(e) => {
+                warn!(%e, "failed to parse text-extracted payload");
+                continue;
+            }
+        };
+        let client = match pool.get().await {
+            Ok(c) => c,
+            Err(e) => {
+                error!(%e, "db pool get failed");
+                continue;
+            }
+        };
+        let rows = match client
+            .query(
+                "UPDATE uploads SET run_state='triggered'
+                 WHERE pdf_id=$1 AND run_state='pending'
+                 RETURNING id, pipeline_id, run_priority",
+                &[&evt.pdf_id],
+            )
+            .await
+        {
+            Ok(rows) => rows,
+            Err(e) => {
+                error!(%e, pdf_id = evt.pdf_id, "failed to claim pending runs");
+                continue;
+            }
+        };
+        for row in rows {
+            let upload_id: i32 = row.get(0);
+            let Some(pipeline_id) = row.get::<_, Option<Uuid>>(1) else {
+                continue;
+            };
+            let priority = row
+                .get::<_, Option<String>>(2)
+                .and_then(|p| RunPriority::from_str(&p).ok())
+                .unwrap_or_default();
+            publish_run(&producer, evt.pdf_id, pipeline_id, priority).await;
+            timeline::record(
+                &client,
+                &TimelineEvent::new("pdf-ingest", "run_triggered")
+                    .pdf(Some(evt.pdf_id))
+                    .upload(Some(upload_id))
+                    .pipeline(Some(pipeline_id))
+                    .details(serde_json::json!({ "priority": priority, "manual": false })),
+            )
+            .await;
+        }
+        if let Err(e) = route_uploads(&client, &producer, evt.pdf_id, classify).await {
+            error!(%e, pdf_id = evt.pdf_id, "failed to classify uploads");
+        }
+    }
+}
+
+/// Picks the pipeline of the uploads of `pdf_id` that wait for the
+/// classification; uploads below the threshold become `unassigned`.
+async fn route_uploads(
+    client: &deadpool_postgres::Client,
+    producer: &FutureProducer,
+    pdf_id: i32,
+    cfg: ClassifyConfig,
+) -> Result<(), tokio_postgres::Error> {
+    // Zustand sofort weitersetzen, damit ein doppeltes Event nicht zweimal startet
+    let claimed = client
+        .query(
+            "UPDATE uploads SET run_state=$3
+             WHERE pdf_id=$1 AND run_state=$2
+             RETURNING id, run_priority",
+            &[
+                &pdf_id,
+                &classify::STATE_CLASSIFY,
+                &classify::STATE_UNASSIGNED,
+            ],
+        )
+        .await?;
+    if claimed.is_empty() {
+        return Ok(());
+    }
+    let rules = classify::load_rules(client).await?;
+    let text = classify::load_text(client, pdf_id).await?;
+    let result = classify::classify(&text, &rules, cfg.min_confidence);
+    let stored = serde_json::to_value(&result).unwrap_or_default();
+    for row in claimed {
+        let upload_id: i32 = row.get(0);
+        let priority = row
+            .get::<_, Option<String>>(1)
+            .and_then(|p| RunPriority::from_str(&p).ok())
+            .unwrap_or_default();
+        let Some(pipeline_id) = result.pipeline_id else {
+            client
+                .execute(
+                    "UPDATE uploads SET classification=$2 WHERE id=$1",
+                    &[&upload_id, &stored],
+                )
+                .await?;
+            info!(
+                upload_id,
+                pdf_id,
+                confidence = result.confidence,
+                "upload needs pipeline assignment"
+            );
+            timeline::record(
+                client,
+                &TimelineEvent::new("pdf-ingest", "classification_unassigned")
+                    .pdf(Some(pdf_id))
+                    .upload(Some(upload_id))
+                    .details(stored.clone()),
+            )
+            .await;
+            continue;
+        };
+        // Eine manuelle Zuweisung in der Zwischenzeit hat Vorrang
+        let routed = client
+            .execute(
+                "UPDATE uploads SET pipeline_id=$2, classification=$3, run_state='triggered'
+                 WHERE id=$1 AND run_state=$4",
+                &[
+                    &upload_id,
+                    &pipeline_id,
+                    &stored,
+                    &classify::STATE_UNASSIGNED,
+                ],
+            )
+            .await?;
+        if routed == 0 {
+            continue;
+        }
+        info!(upload_id, pdf_id, pipeline_id = %pipeline_id, confidence = result.confidence, "upload classified");
+        publish_run(producer, pdf_id, pipeline_id, priority).await;
+        timeline::record(
+            client,
+            &TimelineEvent::new("pdf-ingest", "run_triggered")
+                .pdf(Some(pdf_id))
+                .upload(Some(upload_id))
+                .pipeline(Some(pipeline_id))
+                .details(serde_json::json!({
+                    "priority": priority,
+                    "manual": false,
+                    "confidence": result.confidence,
+                })),
+        )
+        .await;
+    }
+    Ok(())
+}
+
+/// Returns recent uploads for the administrative UI.
+async fn list_uploads(
+    q: web::Query<UploadListQuery>,
+    db: web::Data<Pool>,
+) -> Result<HttpResponse, Error> {
+    let client = db
+        .get()
+        .await
+        .map_err(actix_web::error::ErrorInternalServerError)?;
+    let label = clean_reference(q.job_label.as_deref());
+    let external_ref = clean_reference(q.external_ref.as_deref());
+    let tag = q.tag.as_deref().and_then(operator_notes::normalize_tag);
+    let rows = client
+        .query(
+            "SELECT u.id, u.pdf_id, u.status, ps.names, u.job_label, u.external_ref, \
+                    u.run_state, u.run_priority, COALESCE(m.legal_hold, FALSE), \
+                    COALESCE(n.tags, '{}'), n.note \
+             FROM uploads u \
+             LEFT JOIN pdf_sources ps ON ps.pdf_id = u.pdf_id \
+             LEFT JOIN merged_pdfs m ON m.id = u.pdf_id \
+             LEFT JOIN upload_notes n ON n.upload_id = u.id \
+             WHERE ($1::text IS NULL OR u.job_label ILIKE '%' || $1 || '%') \
+               AND ($2::text IS NULL OR u.external_ref = $2) \
+               AND ($3::text IS NULL OR $3 = ANY(n.tags)) \
+             ORDER BY u.id DESC",
+            &[&label, &external_ref, &tag],
+        )
+        .await
+        .map_err(actix_web::error::ErrorInternalServerError)?;
+
+    let items: Vec<UploadEntry> = rows
+        .into_iter()
+        .map(|r| UploadEntry {
+            id: r.get(0),
+            pdf_id: r.get(1),
+            status: r.get(2),
+            names: r
+                .get::<_, Option<String>>(3)
+                .and_then(|raw| serde_json::from_str::<Vec<String>>(&raw).ok())
+                .unwrap_or_default(),
+            job_label: r.get(4),
+            external_ref: r.get(5),
+            run_state: r.get(6),
+            run_priority: r
+                .get::<_, Option<String>>(7)
+                .unwrap_or_else(|| RunPriority::default().to_string()),
+            legal_hold: r.get(8),
+            tags: r.get(9),
+            note: r.get(10),
+        })
+        .collect();
+
+    Ok(HttpResponse::Ok().json(items))
+}
+
+/// Uploads the classification could not route ("needs pipeline
+/// assignment"), oldest first, with the scores of the candidates.
+async fn list_unassigned_uploads(db: web::Data<Pool>) -> Result<HttpResponse, Error> {
+    let client = db
+        .get()
+        .await
+        .map_err(actix_web::error::ErrorInternalServerError)?;
+    let rows = client
+        .query(
+            "SELECT u.id, u.pdf_id, u.tenant_id, ps.names, u.job_label, u.external_ref, \
+                    u.run_priority, u.created_at::text, u.classification \
+             FROM uploads u \
+             LEFT JOIN pdf_sources ps ON ps.pdf_id = u.pdf_id \
+             WHERE u.run_state = $1 \
+             ORDER BY u.id",
+            &[&classify::STATE_UNASSIGNED],
+        )
+        .await
+        .map_err(actix_web::error::ErrorInternalServerError)?;
+    let items: Vec<serde_json::Value> = rows
+        .iter()
+        .map(|r| {
+            let names: Vec<String> = r
+                .get::<_, Option<String>>(3)
+                .and_then(|raw| serde_json::from_str(&raw).ok())
+                .unwrap_or_default();
+            serde_json::json!({
+                "id": r.get::<_, i32>(0),
+                "pdf_id": r.get::<_, Option<i32>>(1),
+                "tenant_id": r.get::<_, Option<Uuid>>(2),
+                "names": names,
+                "job_label": r.get::<_, Option<String>>(4),
+                "external_ref": r.get::<_, Option<String>>(5),
+                "run_priority": r.get::<_, Option<String>>(6),
+                "created_at": r.get::<_, Option<String>>(7),
+                "classification": r.get::<_, Option<serde_json::Value>>(8),
+            })
+        })
+        .collect();
+    Ok(HttpResponse::Ok().json(items))
+}
+
+/// Keyword rules of the classification. Needs `ADMIN_TOKEN` if set.
+async fn list_classification_rules(
+    req: HttpRequest,
+    db: web::Data<Pool>,
+) -> Result<HttpResponse, Error> {
+    if let Some(denied) = admin_denied(&req) {
+        return Ok(denied);
+    }
+    let client = db
+        .get()
+        .await
+        .map_err(actix_web::error::ErrorInternalServerError)?;
+    let rules = classify::load_rules(&client)
+        .await
+        .map_err(actix_web::error::ErrorInternalServerError)?;
+    Ok(HttpResponse::Ok().json(serde_json::json!({ "rules": rules })))
+}
+
+/// Sets the keywords that route documents to a pipeline. Needs `ADMIN_TOKEN`
+/// if set.
+async fn put_classification_rule(
+    req: HttpRequest,
+    pipeline_id: web::Path<Uuid>,
+    body: web::Json<RuleInput>,
+    db: web::Data<Pool>,
+) -> Result<HttpResponse, Error> {
+    if let Some(denied) = admin_denied(&req) {
+        return Ok(denied);
+    }
+    let pipeline_id = pipeline_id.into_inner();
+    let keywords = classify::clean_keywords(&body.keywords);
+    if keywords.is_empty() || keywords.len() > classify::MAX_KEYWORDS {
+        return Ok(HttpResponse::BadRequest().json(serde_json::json!({
+            "error": format!("expected 1 to {} keywords", classify::MAX_KEYWORDS),
+        })));
+    }
+    let client = db
+        .get()
+        .await
+        .map_err(actix_web::error::ErrorInternalServerError)?;
+    let row = client
+        .query_opt(
+            "INSERT INTO pipeline_routing_rules (pipeline_id, keywords)
+             SELECT id, $2 FROM pipelines WHERE id = $1
+             ON CONFLICT (pipeline_id) DO UPDATE SET keywords = EXCLUDED.keywords, updated_at = now()
+             RETURNING updated_at::text",
+            &[&pipeline_id, &keywords],
+        )
+        .await
+        .map_err(actix_web::error::ErrorInternalServerError)?;
+    let Some(row) = row else {
+        return Ok(HttpResponse::NotFound().finish());
+    };
+    info!(%pipeline_id, keywords = keywords.len(), "classification rule saved");
+    Ok(HttpResponse::Ok().json(serde_json::json!({
+        "pipeline_id": pipeline_id,
+        "keywords": keywords,
+        "updated_at": row.get::<_, String>(0),
+    })))
+}
+
+/// Removes the keywords of a pipeline. Needs `ADMIN_TOKEN` if set.
+async fn delete_classification_rule(
+    req: HttpRequest,
+    pipeline_id: web::Path<Uuid>,
+    db: web::Data<Pool>,
+) -> Result<HttpResponse, Error> {
+    if let Some(denied) = admin_denied(&req) {
+        return Ok(denied);
+    }
+    let pipeline_id = pipeline_id.into_inner();
+    let client = db
+        .get()
+        .await
+        .map_err(actix_web::error::ErrorInternalServerError)?;
+    let removed = client
+        .execute(
+            "DELETE FROM pipeline_routing_rules WHERE pipeline_id = $1",
+            &[&pipeline_id],
+        )
+        .await
+        .map_err(actix_web::error::ErrorInternalServerError)?;
+    if removed == 0 {
+        return Ok(HttpResponse::NotFound().finish());
+    }
+    info!(%pipeline_id, "classification rule removed");
+    Ok(HttpResponse::NoContent().finish())
+}
+
+/// Replaces the operator tags and note of an upload.
+async fn put_upload_notes(
+    id: web::Path<i32>,
+    body: web::Json<NoteInput>,
+    db: web::Data<Pool>,
+) -> Result<HttpResponse, Error> {
+    let id = id.into_inner();
+    let input = body.into_inner().normalized();
+    if let Err(e) = input.validate() {
+        return Ok(HttpResponse::BadRequest().json(serde_json::json!({ "error": e })));
+    }
+    let client = db
+        .get()
+        .await
+        .map_err(actix_web::error::ErrorInternalServerError)?;
+    let Some(row) = client
+        .query_opt(
+            operator_notes::UPSERT_UPLOAD_NOTE_SQL,
+            &[&id, &input.tags, &input.note, &input.author],
+        )
+        .await
+        .map_err(actix_web::error::ErrorInternalServerError)?
+    else {
+        return Ok(HttpResponse::NotFound().finish());
+    };
+    Ok(HttpResponse::Ok().json(OperatorNote {
+        tags: row.get(0),
+        note: row.get(1),
+        updated_by: row.get(2),
+        updated_at: row.get(3),
+    }))
+}
+
+/// Streams a previously stored merged PDF back to the caller.
+///
+/// Archived PDFs answer `202` with `{"status": "restoring"}` and a
+/// `Retry-After` header until they are back from cold storage.
+async fn get_pdf(
+    id: web::Path<i32>,
+    db: web::Data<Pool>,
+    archive: web::Data<Option<Archiver>>,
+) -> Result<HttpResponse, Error> {
+    let id = id.into_inner();
+    let client = db
+        .get()
+        .await
+        .map_err(actix_web::error::ErrorInternalServerError)?;
+    let stmt = client
+        .prepare("SELECT data FROM merged_pdfs WHERE id=$1")
+        .await
+        .map_err(actix_web::error::ErrorInternalServerError)?;
+    match client.query_opt(&stmt, &[&id]).await {
+        Ok(Some(row)) => match row.get::<_, Option<Vec<u8>>>(0) {
+            Some(data) => Ok(HttpResponse::Ok()
+                .insert_header((header::CONTENT_TYPE, "application/pdf"))
+                .body(data)),
+            None => {
+                let retry_after =
+                    start_restore(&client, &db, archive.get_ref().as_ref(), id).await?;
+                Ok(HttpResponse::Accepted()
+                    .insert_header((header::RETRY_AFTER, retry_after.to_string()))
+                    .json(serde_json::json!({
+                        "pdf_id": id,
+                        "status": archive::TIER_RESTORING,
+                    })))
+            }
+        },
+        Ok(None) => Ok(HttpResponse::NotFound().finish()),
+        Err(e) => Err(actix_web::error::ErrorInternalServerError(e)),
+    }
+}
+
+/// Requests the restore of an archived PDF; returns the `Retry-After` seconds.
+async fn start_restore(
+    client: &deadpool_postgres::Client,
+    pool: &Pool,
+    archive: Option<&Archiver>,
+    id: i32,
+) -> Result<u64, Error> {
+    let Some(archive) = archive else {
+        return Err(actix_web::error::ErrorServiceUnavailable(
+            "document is archived but ARCHIVE_STORE is not configured",
+        ));
+    };
+    if archive::request_restore(client, id)
+        .await
+        .map_err(actix_web::error::ErrorInternalServerError)?
+    {
+        archive.spawn_restore(pool.clone(), id);
+    }
+    Ok(archive.retry_after())
+}
+
+/// Returns the extracted text of a merged PDF, with `format=json|hocr|alto`
+/// including OCR details and word boxes (see `text_extraction::export`).
+async fn get_extract(
+    id: web::Path<i32>,
+    q: web::Query<ExtractQuery>,
+    db: web::Data<Pool>,
+) -> Result<HttpResponse, Error> {
+    let format: ExportFormat = match q.format.as_deref().unwrap_or_default().parse() {
+        Ok(format) => format,
+        Err(e) => return Ok(HttpResponse::BadRequest().json(serde_json::json!({ "error": e }))),
+    };
+    let client = db
+        .get()
+        .await
+        .map_err(actix_web::error::ErrorInternalServerError)?;
+    if format == ExportFormat::Text {
+        let stmt = client
+            .prepare(
+                // Alle Seiten in stabiler Reihenfolge zusammenführen
+                "SELECT COALESCE(
+                     string_agg(text, E'\n' ORDER BY page_no),
+                     ''
+                 ) FROM pdf_page_texts WHERE merged_pdf_id = $1",
+            )
+            .await
+            .map_err(actix_web::error::ErrorInternalServerError)?;
+        return match client.query_opt(&stmt, &[&id.into_inner()]).await {
+            Ok(Some(row)) => {
+                let text: String = row.get(0);
+                Ok(HttpResponse::Ok()
+                    .insert_header((header::CONTENT_TYPE, "text/plain"))
+                    .body(text))
+            }
+            Ok(None) => Ok(HttpResponse::NotFound().finish()),
+            Err(e) => Err(actix_web::error::ErrorInternalServerError(e)),
+        };
+    }
+    let rows = client
+        .query(
+            "SELECT t.page_no, p.text, t.ocr_used, t.layout_json, t.ocr_strategy,
+                    t.quality_score::float8 AS quality_score, t.lang, t.ocr_preprocessing
+               FROM pdf_texts t
+               JOIN pdf_page_texts p ON p.merged_pdf_id = t.merged_pdf_id AND p.page_no = t.page_no
+              WHERE t.merged_pdf_id = $1
+              ORDER BY t.page_no",
+            &[&id.into_inner()],
+        )
+        .await
+        .map_err(actix_web::error::ErrorInternalServerError)?;
+    let pages: Vec<PageExtraction> = rows
+        .iter()
+        .map(|row| PageExtraction {
+            page_no: row.get("page_no"),
+            text: row.get("text"),
+            ocr_used: row.get("ocr_used"),
+            // Layouts älterer Extraktionen, die nicht mehr passen, fallen weg
+            layout: row
+                .get::<_, Option<serde_json::Value>>("layout_json")
+                .and_then(|v| serde_json::from_value(v).ok()),
+            ocr_strategy: row.get("ocr_strategy"),
+            quality_score: row.get("quality_score"),
+            lang: row.get("lang"),
+            ocr_preprocessing: row.get("ocr_preprocessing"),
+            image: None,
+        })
+        .collect();
+    Ok(HttpResponse::Ok()
+        .insert_header((header::CONTENT_TYPE, format.content_type()))
+        .body(format.render(&pages)))
+}
+
+/// Deletes a merged PDF, its metadata and its archived copy; refused with
+/// `409` while the document is under legal hold.
+async fn delete_pdf(
+    id: web::Path<i32>,
+    db: web::Data<Pool>,
+    archive: web::Data<Option<Archiver>>,
+) -> Result<HttpResponse, Error> {
+    let id = id.into_inner();
+    let mut client = db
+        .get()
+        .await
+        .map_err(actix_web::error::ErrorInternalServerError)?;
+    let tx = client
+        .transaction()
+        .await
+        .map_err(actix_web::error::ErrorInternalServerError)?;
+
+    // Zeile sperren, damit zwischen Prüfung und Löschen kein Hold gesetzt wird
+    let row = tx
+        .query_opt(
+            "SELECT legal_hold, archive_key FROM merged_pdfs WHERE id=$1 FOR UPDATE",
+            &[&id],
+        )
+        .await
+        .map_err(actix_web::error::ErrorInternalServerError)?;
+    let held: Option<bool> = row.as_ref().map(|row| row.get(0));
+    let archive_key: Option<String> = row.and_then(|row| row.get(1));
+    match held {
+        None => return Ok(HttpResponse::NotFound().finish()),
+        Some(true) => {
+            drop(tx);
+            timeline::record(
+                &client,
+                &TimelineEvent::new("pdf-ingest", "delete_blocked")
+                    .pdf(Some(id))
+                    .message("document is under legal hold"),
+            )
+            .await;
+            return Ok(HttpResponse::Conflict()
+                .json(serde_json::json!({ "error": "document is under legal hold" })));
+        }
+        Some(false) => {}
+    }
+
+    // Abhängigkeiten aufräumen (dürfen fehlen)
+    let _ = tx
+        .execute("DELETE FROM pdf_sources WHERE pdf_id=$1", &[&id])
+        .await;
+    let _ = tx.execute(page_texts::DELETE_PAGES_SQL, &[&vec![id]]).await;
+
+    tx.execute("DELETE FROM merged_pdfs WHERE id=$1", &[&id])
+        .await
+        .map_err(actix_web::error::ErrorInternalServerError)?;
+    tx.commit()
+        .await
+        .map_err(actix_web::error::ErrorInternalServerError)?;
+    if let Some(key) = archive_key {
+        match archive.get_ref() {
+            Some(archive) => archive.remove(&key).await,
+            None => warn!(pdf_id = id, key, "archived copy kept, ARCHIVE_STORE not configured"),
+        }
+    }
+    timeline::record(
+        &client,
+        &TimelineEvent::new("pdf-ingest", "deleted").pdf(Some(id)),
+    )
+    .await;
+    Ok(HttpResponse::Ok().finish())
+}
+
+fn legal_hold_from_row(pdf_id: i32, row: &tokio_postgres::Row) -> LegalHold {
+    LegalHold {
+        pdf_id,
+        legal_hold: row.get(0),
+        reason: row.get(1),
+        placed_by: row.get(2),
+        placed_at: row.get(3),
+    }
+}
+
+/// OCRs the pages whose stored mean word confidence is below
+/// `min_confidence` again (text-extraction, `REOCR_STRATEGY`); a page is only
+/// replaced when the new result is more confident. With `rerun=true` the
+/// uploads whose run already started are marked `pending` and run again once
+/// the re-OCR is done; the runner only repeats the prompts. Needs
+/// `ADMIN_TOKEN` if set.
+async fn reocr_pdf(
+    req: HttpRequest,
+    id: web::Path<i32>,
+    q: web::Query<ReocrQuery>,
+    db: web::Data<Pool>,
+    producer: web::Data<FutureProducer>,
+    archive: web::Data<Option<Archiver>>,
+) -> Result<HttpResponse, Error> {
+    if let Some(denied) = admin_denied(&req) {
+        return Ok(denied);
+    }
+    let id = id.into_inner();
+    if !(q.min_confidence > 0.0 && q.min_confidence <= 1.0) {
+        return Ok(HttpResponse::BadRequest()
+            .json(serde_json::json!({ "error": "min_confidence must be between 0 and 1" })));
+    }
+    let client = db
+        .get()
+        .await
+        .map_err(actix_web::error::ErrorInternalServerError)?;
+    let Some(row) = client
+        .query_opt("SELECT data IS NULL FROM merged_pdfs WHERE id=$1", &[&id])
+        .await
+        .map_err(actix_web::error::ErrorInternalServerError)?
+    else {
+        return Ok(HttpResponse::NotFound().finish());
+    };
+    if row.get::<_, bool>(0) {
+        let retry_after = start_restore(&client, &db, archive.get_ref().as_ref(), id).await?;
+        return Ok(HttpResponse::Conflict()
+            .insert_header((header::RETRY_AFTER, retry_after.to_string()))
+            .json(serde_json::json!({
+                "error": "document is archived; retry once it is restored",
+                "status": archive::TIER_RESTORING,
+            })));
+    }
+    // Seiten aus der Textebene haben keine Konfidenz und bleiben unberührt
+    let pages: Vec<i32> = client
+        .query(
+            "SELECT page_no FROM pdf_texts
+              WHERE merged_pdf_id=$1 AND ocr_confidence < $2
+              ORDER BY page_no",
+            &[&id, &q.min_confidence],
+        )
+        .await
+        .map_err(actix_web::error::ErrorInternalServerError)?
+        .iter()
+        .map(|row| row.get(0))
+        .collect();
+    if pages.is_empty() {
+        return Ok(HttpResponse::Ok().json(serde_json::json!({
+            "pdf_id": id,
+            "min_confidence": q.min_confidence,
+            "pages": pages,
+            "rerun_uploads": [],
+        })));
+    }
+
+    // Vor dem Event markieren, sonst könnte text-extracted die Läufe verpassen
+    let rerun_uploads: Vec<i32> = if q.rerun {
+        client
+            .query(
+                "UPDATE uploads SET run_state='pending'
+                 WHERE pdf_id=$1 AND pipeline_id IS NOT NULL AND run_state='triggered'
+                 RETURNING id",
+                &[&id],
+            )
+            .await
+            .map_err(actix_web::error::ErrorInternalServerError)?
+            .iter()
+            .map(|row| row.get(0))
+            .collect()
+    } else {
+        Vec::new()
+    };
+    let payload = serde_json::to_string(&PdfUploaded {
+        schema_version: PdfUploaded::SCHEMA_VERSION,
+        pdf_id: id,
+        pipeline_id: Uuid::nil(),
+        priority: RunPriority::default(),
+        rerun_of: None,
+        appended_from: None,
+        run_id: None,
+        reocr: Some(ReocrPages {
+            pages: pages.clone(),
+            min_confidence: q.min_confidence,
+        }),
+    })
+    .unwrap();
+    if let Err((e, _)) = producer
+        .send(
+            FutureRecord::to("pdf-merged")
+                .payload(&payload)
+                .key(&kafka::document_key(id)),
+            Duration::from_secs(0),
+        )
+        .await
+    {
+        error!(%e, pdf_id = id, "failed to publish pdf-merged event");
+        let _ = client
+            .execute(
+                "UPDATE uploads SET run_state='triggered' WHERE id = ANY($1)",
+                &[&rerun_uploads],
+            )
+            .await;
+        return Err(actix_web::error::ErrorInternalServerError(
+            "failed to publish re-ocr request",
+        ));
+    }
+    info!(
+        pdf_id = id,
+        pages = pages.len(),
+        rerun = q.rerun,
+        "re-ocr requested"
+    );
+    timeline::record(
+        &client,
+        &TimelineEvent::new("pdf-ingest", "reocr_requested")
+            .pdf(Some(id))
+            .details(serde_json::json!({
+                "min_confidence": q.min_confidence,
+                "pages": pages,
+                "rerun_uploads": rerun_uploads,
+            })),
+    )
+    .await;
+    Ok(HttpResponse::Accepted().json(serde_json::json!({
+        "pdf_id": id,
+        "min_confidence": q.min_confidence,
+        "pages": pages,
+        "rerun_uploads": rerun_uploads,
+    })))
+}
+
+/// Earlier documents of the same tenant whose text is a near duplicate of this
+/// one (signatures stored by text-extraction, see `shared::similarity`).
+async fn get_similar(
+    id: web::Path<i32>,
+    q: web::Query<SimilarQuery>,
+    db: web::Data<Pool>,
+) -> Result<HttpResponse, Error> {
+    let id = id.into_inner();
+    let threshold = q.threshold.unwrap_or(similarity::DEFAULT_THRESHOLD);
+    if !(similarity::MIN_THRESHOLD..=1.0).contains(&threshold) {
+        return Ok(HttpResponse::BadRequest().json(serde_json::json!({
+            "error": format!("threshold must be between {} and 1", similarity::MIN_THRESHOLD)
+        })));
+    }
+    let limit = q.limit.unwrap_or(20).clamp(1, 100) as usize;
+    let client = db
+        .get()
+        .await
+        .map_err(actix_web::error::ErrorInternalServerError)?;
+    let exists = client
+        .query_opt("SELECT 1 FROM merged_pdfs WHERE id=$1", &[&id])
+        .await
+        .map_err(actix_web::error::ErrorInternalServerError)?;
+    if exists.is_none() {
+        return Ok(HttpResponse::NotFound().finish());
+    }
+    let Some(own) = client
+        .query_opt(
+            "SELECT minhash FROM pdf_signatures WHERE merged_pdf_id=$1",
+            &[&id],
+        )
+        .await
+        .map_err(actix_web::error::ErrorInternalServerError)?
+    else {
+        // Vor der Einführung extrahiert oder ohne Text
+        return Ok(HttpResponse::NotFound()
+            .json(serde_json::json!({ "error": "no signature for this document" })));
+    };
+    let minhash: Vec<i64> = own.get(0);
+    let rows = client
+        .query(
+            "SELECT s.merged_pdf_id, s.minhash, ps.names FROM pdf_signatures s \
+             LEFT JOIN pdf_sources ps ON ps.pdf_id = s.merged_pdf_id \
+             WHERE s.bands && $2 AND s.merged_pdf_id < $1 \
+               AND EXISTS (SELECT 1 FROM uploads a JOIN uploads b ON b.tenant_id = a.tenant_id \
+                           WHERE a.pdf_id = $1 AND b.pdf_id = s.merged_pdf_id)",
+            &[&id, &similarity::bands(&minhash)],
+        )
+        .await
+        .map_err(actix_web::error::ErrorInternalServerError)?;
+    let mut similar: Vec<SimilarDocument> = rows
+        .iter()
+        .filter_map(|row| {
+            let other: Vec<i64> = row.get(1);
+            let score = similarity::similarity(&minhash, &other);
+            (score >= threshold).then(|| SimilarDocument {
+                pdf_id: row.get(0),
+                similarity: score,
+                names: row.get(2),
+            })
+        })
+        .collect();
+    similar.sort_by(|a, b| {
+        b.similarity
+            .total_cmp(&a.similarity)
+            .then(b.pdf_id.cmp(&a.pdf_id))
+    });
+    similar.truncate(limit);
+    Ok(HttpResponse::Ok().json(serde_json::json!({
+        "pdf_id": id,
+        "threshold": threshold,
+        "similar": similar,
+    })))
+}
+
+/// Tables created and written by pdf-ingest (`GET /admin/schema`).
+const OWNED_TABLES: [&str; 14] = [
+    "merged_pdfs",
+    "pdf_sources",
+    "pdf_versions",
+    "pdf_entities",
+    "pdf_signatures",
+    "pdf_annotations",
+    "pdf_metadata",
+    "pdf_texts",
+    "pdf_text_blobs",
+    "uploads",
+    "upload_notes",
+    "erasure_requests",
+    "prompt_sample_tenants",
+    "pipeline_routing_rules",
+];
+
+/// `401` unless the request carries `ADMIN_TOKEN` (if set) as bearer token.
+fn admin_denied(req: &HttpRequest) -> Option<HttpResponse> {
+    let expected = std::env::var("ADMIN_TOKEN")
+        .ok()
+        .filter(|t| !t.is_empty())?;
+    let token = req
+        .headers()
+        .get(header::AUTHORIZATION)
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.strip_prefix("Bearer "))
+        .unwrap_or("");
+    (token != expected).then(|| HttpResponse::Unauthorized().body("invalid token"))
+}
+
+/// Like [`admin_denied`], but refuses with `503` while `ADMIN_TOKEN` is unset;
+/// for irreversible operations.
+fn admin_required(req: &HttpRequest) -> Option<HttpResponse> {
+    if !std::env::var("ADMIN_TOKEN").is_ok_and(|t| !t.is_empty()) {
+        return Some(
+            HttpResponse::ServiceUnavailable()
+                .json(serde_json::json!({ "error": "ADMIN_TOKEN is not set" })),
+        );
+    }
+    admin_denied(req)
+}
+
+/// Describes the tables owned by this service; needs `ADMIN_TOKEN` if set.
+async fn get_schema(req: HttpRequest, db: web::Data<Pool>) -> Result<HttpResponse, Error> {
+    if let Some(denied) = admin_denied(&req) {
+        return Ok(denied);
+    }
+    let client = db
+        .get()
+        .await
+        .map_err(actix_web::error::ErrorInternalServerError)?;
+    let schema = schema_doc::describe(&client, "pdf-ingest", &OWNED_TABLES)
+        .await
+        .map_err(actix_web::error::ErrorInternalServerError)?;
+    Ok(HttpResponse::Ok().json(schema))
+}
+
+/// Pages moved per statement by [`compact_pdf_texts`]; one transaction each.
+const COMPACT_BATCH: i64 = 500;
+
+#[derive(Deserialize)]
+struct CompactQuery {
+    limit: Option<i64>,
+    #[serde(default)]
+    dry_run: bool,
+}
+
+/// Moves up to `limit` pages with inline text into `pdf_text_blobs` and
+/// removes unreferenced blobs (see `shared::page_texts`); `dry_run` only
+/// reports the storage. Needs `ADMIN_TOKEN` if set.
+async fn compact_pdf_texts(
+    req: HttpRequest,
+    q: web::Query<CompactQuery>,
+    db: web::Data<Pool>,
+) -> Result<HttpResponse, Error> {
+    if let Some(denied) = admin_denied(&req) {
+        return Ok(denied);
+    }
+    let limit = q.limit.unwrap_or(10_000).max(0);
+    let client = db
+        .get()
+        .await
+        .map_err(actix_web::error::ErrorInternalServerError)?;
+    let mut migrated = 0i64;
+    let mut removed_blobs = 0u64;
+    if !q.dry_run {
+        while migrated < limit {
+            let batch = (limit - migrated).min(COMPACT_BATCH);
+            let moved = client
+                .execute(page_texts::COMPACT_BATCH_SQL, &[&batch])
+                .await
+                .map_err(actix_web::error::ErrorInternalServerError)?;
+            if moved == 0 {
+                break;
+            }
+            migrated += moved as i64;
+        }
+        removed_blobs = client
+            .execute(page_texts::REMOVE_ORPHANS_SQL, &[])
+            .await
+            .map_err(actix_web::error::ErrorInternalServerError)?;
+        info!(migrated, removed_blobs, "page texts compacted");
+    }
+    let stats = client
+        .query_one(page_texts::STATS_SQL, &[])
+        .await
+        .map_err(actix_web::error::ErrorInternalServerError)?;
+    Ok(HttpResponse::Ok().json(serde_json::json!({
+        "dry_run": q.dry_run,
+        "migrated": migrated,
+        "removed_blobs": removed_blobs,
+        "inline_pages": stats.get::<_, i64>(0),
+        "deduplicated_pages": stats.get::<_, i64>(1),
+        "blobs": stats.get::<_, i64>(2),
+        "blob_chars": stats.get::<_, i64>(3),
+        "blob_bytes": stats.get::<_, i64>(4),
+    })))
+}
+
+/// Lists the entity index of a merged PDF (filled by text-extraction with `ENTITY_INDEX=1`).
+async fn get_entities(
+    id: web::Path<i32>,
+    q: web::Query<EntityQuery>,
+    db: web::Data<Pool>,
+) -> Result<HttpResponse, Error> {
+    let id = id.into_inner();
+    let kind = match q.kind.as_deref().filter(|k| !k.is_empty()) {
+        Some(raw) => match EntityKind::parse(raw) {
+            Some(kind) => Some(kind.as_str()),
+            None => {
+                return Ok(HttpResponse::BadRequest()
+                    .json(serde_json::json!({ "error": format!("unknown kind '{raw}'") })))
+            }
+        },
+        None => None,
+    };
+    let needle =
+        q.q.as_deref()
+            .map(str::trim)
+            .filter(|v| !v.is_empty())
+            .map(|v| format!("%{}%", v.to_lowercase()));
+    let client = db
+        .get()
+        .await
+        .map_err(actix_web::error::ErrorInternalServerError)?;
+    let exists = client
+        .query_opt("SELECT 1 FROM merged_pdfs WHERE id=$1", &[&id])
+        .await
+        .map_err(actix_web::error::ErrorInternalServerError)?;
+    if exists.is_none() {
+        return Ok(HttpResponse::NotFound().finish());
+    }
+    let rows = client
+        .query(
+            "SELECT page_no, kind, value, normalized, bbox, source FROM pdf_entities \
+             WHERE merged_pdf_id=$1 \
+               AND ($2::text IS NULL OR kind = $2) \
+               AND ($3::int IS NULL OR page_no = $3) \
+               AND ($4::text IS NULL OR lower(normalized) LIKE $4) \
+             ORDER BY page_no, id",
+            &[&id, &kind, &q.page, &needle],
+        )
+        .await
+        .map_err(actix_web::error::ErrorInternalServerError)?;
+    let list: Vec<PdfEntity> = rows
+        .iter()
+        .filter_map(|row| {
+            let kind: String = row.get("kind");
+            let bbox: Option<serde_json::Value> = row.get("bbox");
+            Some(PdfEntity {
+                merged_pdf_id: id,
+                page_no: row.get("page_no"),
+                kind: EntityKind::parse(&kind)?,
+                value: row.get("value"),
+                normalized: row.get("normalized"),
+                bbox: bbox.and_then(|b| serde_json::from_value(b).ok()),
+                source: row.get("source"),
+            })
+        })
+        .collect();
+    Ok(HttpResponse::Ok().json(serde_json::json!({ "pdf_id": id, "entities": list })))
+}
+
+const ANNOTATION_COLUMNS: &str =
+    "id, merged_pdf_id, page_no, kind, bbox, note, final_key, run_id, author, created_at";
+
+fn annotation_from_row(row: &tokio_postgres::Row) -> Option<PageAnnotation> {
+    let kind: String = row.get("kind");
+    let bbox: Option<serde_json::Value> = row.get("bbox");
+    let created_at: chrono::DateTime<chrono::Utc> = row.get("created_at");
+    Some(PageAnnotation {
+        id: row.get("id"),
+        merged_pdf_id: row.get("merged_pdf_id"),
+        page_no: row.get("page_no"),
+        kind: AnnotationKind::parse(&kind)?,
+        bbox: bbox.and_then(|b| serde_json::from_value(b).ok()),
+        note: row.get("note"),
+        final_key: row.get("final_key"),
+        run_id: row.get("run_id"),
+        author: row.get("author"),
+        created_at: created_at.to_rfc3339(),
+    })
+}
+
+/// Annotations of a PDF, optionally of one page or final key, in creation order.
+async fn load_annotations(
+    client: &deadpool_postgres::Client,
+    id: i32,
+    page: Option<i32>,
+    final_key: Option<&str>,
+) -> Result<Vec<PageAnnotation>, tokio_postgres::Error> {
+    let rows = client
+        .query(
+            &format!(
+                "SELECT {ANNOTATION_COLUMNS} FROM pdf_annotations \
+                 WHERE merged_pdf_id=$1 \
+                   AND ($2::int IS NULL OR page_no = $2) \
+                   AND ($3::text IS NULL OR final_key = $3) \
+                 ORDER BY page_no, id"
+            ),
+            &[&id, &page, &final_key],
+        )
+        .await?;
+    Ok(rows.iter().filter_map(annotation_from_row).collect())
+}
+
+async fn pdf_exists(client: &deadpool_postgres::Client, id: i32) -> Result<bool, Error> {
+    let row = client
+        .query_opt("SELECT 1 FROM merged_pdfs WHERE id=$1", &[&id])
+        .await
+        .map_err(actix_web::error::ErrorInternalServerError)?;
+    Ok(row.is_some())
+}
+
+/// Returns the searchable copy of a scanned PDF (OCR text layer, see
+/// `text_extraction::searchable`); `404` while there is none for the current
+/// version of the document.
+async fn get_searchable_pdf(
+    id: web::Path<i32>,
+    db: web::Data<Pool>,
+) -> Result<HttpResponse, Error> {
+    let id = id.into_inner();
+    let client = db
+        .get()
+        .await
+        .map_err(actix_web::error::ErrorInternalServerError)?;
+    let row = client
+        .query_opt(
+            "SELECT searchable_data FROM merged_pdfs \
+             WHERE id=$1 AND searchable_sha256 = sha256",
+            &[&id],
+        )
+        .await
+        .map_err(actix_web::error::ErrorInternalServerError)?;
+    match row.and_then(|row| row.get::<_, Option<Vec<u8>>>(0)) {
+        Some(data) => Ok(HttpResponse::Ok()
+            .insert_header((header::CONTENT_TYPE, "application/pdf"))
+            .body(data)),
+        None if pdf_exists(&client, id).await? => {
+            Ok(HttpResponse::NotFound()
+                .json(serde_json::json!({ "error": "no searchable version" })))
+        }
+        None => Ok(HttpResponse::NotFound().finish()),
+    }
+}
+
+/// Returns the document metadata read by text-extraction; `404` until the
+/// first extraction has finished.
+async fn get_metadata(id: web::Path<i32>, db: web::Data<Pool>) -> Result<HttpResponse, Error> {
+    let id = id.into_inner();
+    let client = db
+        .get()
+        .await
+        .map_err(actix_web::error::ErrorInternalServerError)?;
+    let row = client
+        .query_opt(
+            "SELECT title, subject, keywords, author, creator, producer, creation_date, mod_date, \
+                    pdf_version, page_count, encrypted, form, tagged, page_sizes \
+             FROM pdf_metadata WHERE merged_pdf_id=$1",
+            &[&id],
+        )
+        .await
+        .map_err(actix_web::error::ErrorInternalServerError)?;
+    let Some(row) = row else {
+        if pdf_exists(&client, id).await? {
+            return Ok(HttpResponse::NotFound()
+                .json(serde_json::json!({ "error": "metadata not extracted yet" })));
+        }
+        return Ok(HttpResponse::NotFound().finish());
+    };
+    let page_sizes: serde_json::Value = row.get(13);
+    let meta = PdfMetadata {
+        title: row.get(0),
+        subject: row.get(1),
+        keywords: row.get(2),
+        author: row.get(3),
+        creator: row.get(4),
+        producer: row.get(5),
+        creation_date: row.get(6),
+        mod_date: row.get(7),
+        pdf_version: row.get(8),
+        page_count: row.get(9),
+        encrypted: row.get(10),
+        form: row.get(11),
+        tagged: row.get(12),
+        page_sizes: serde_json::from_value(page_sizes).unwrap_or_default(),
+        ..PdfMetadata::default()
+    };
+    Ok(HttpResponse::Ok().json(serde_json::json!({ "pdf_id": id, "metadata": meta })))
+}
+
+/// Lists the reviewer annotations of a merged PDF.
+async fn list_annotations(
+    id: web::Path<i32>,
+    q: web::Query<AnnotationQuery>,
+    db: web::Data<Pool>,
+) -> Result<HttpResponse, Error> {
+    let id = id.into_inner();
+    let final_key = q
+        .final_key
+        .as_deref()
+        .map(str::trim)
+        .filter(|k| !k.is_empty());
+    let client = db
+        .get()
+        .await
+        .map_err(actix_web::error::ErrorInternalServerError)?;
+    if !pdf_exists(&client, id).await? {
+        return Ok(HttpResponse::NotFound().finish());
+    }
+    let list = load_annotations(&client, id, q.page, final_key)
+        .await
+        .map_err(actix_web::error::ErrorInternalServerError)?;
+    Ok(HttpResponse::Ok().json(serde_json::json!({ "pdf_id": id, "annotations": list })))
+}
+
+/// Stores a highlight, box or note of the review UI on a page.
+async fn create_annotation(
+    id: web::Path<i32>,
+    body: web::Json<NewAnnotation>,
+    db: web::Data<Pool>,
+) -> Result<HttpResponse, Error> {
+    let id = id.into_inner();
+    let input = body.into_inner().normalized();
+    if let Err(msg) = input.validate() {
+        return Ok(HttpResponse::BadRequest().json(serde_json::json!({ "error": msg })));
+    }
+    let bbox = input
+        .bbox
+        .map(|b| serde_json::to_value(b).unwrap_or_default());
+    let client = db
+        .get()
+        .await
+        .map_err(actix_web::error::ErrorInternalServerError)?;
+    let Some(row) = client
+        .query_opt(
+            &format!(
+                "INSERT INTO pdf_annotations \
+                 (merged_pdf_id, page_no, kind, bbox, note, final_key, run_id, author) \
+                 SELECT id, $2, $3, $4, $5, $6, $7, $8 FROM merged_pdfs WHERE id=$1 \
+                 RETURNING {ANNOTATION_COLUMNS}"
+            ),
+            &[
+                &id,
+                &input.page_no,
+                &input.kind.as_str(),
+                &bbox,
+                &input.note,
+                &input.final_key,
+                &input.run_id,
+                &input.author,
+            ],
+        )
+        .await
+        .map_err(actix_web::error::ErrorInternalServerError)?
+    else {
+        return Ok(HttpResponse::NotFound().finish());
+    };
+    match annotation_from_row(&row) {
+        Some(annotation) => Ok(HttpResponse::Created().json(annotation)),
+        None => Err(actix_web::error::ErrorInternalServerError(
+            "stored annotation has an unknown kind",
+        )),
+    }
+}
+
+/// Removes one annotation of a merged PDF.
+async fn delete_annotation(
+    path: web::Path<(i32, i64)>,
+    db: web::Data<Pool>,
+) -> Result<HttpResponse, Error> {
+    let (id, annotation_id) = path.into_inner();
+    let client = db
+        .get()
+        .await
+        .map_err(actix_web::error::ErrorInternalServerError)?;
+    let deleted = client
+        .execute(
+            "DELETE FROM pdf_annotations WHERE id=$1 AND merged_pdf_id=$2",
+            &[&annotation_id, &id],
+        )
+        .await
+        .map_err(actix_web::error::ErrorInternalServerError)?;
+    if deleted == 0 {
+        return Ok(HttpResponse::NotFound().finish());
+    }
+    Ok(HttpResponse::NoContent().finish())
+}
+
+/// Returns text, layout and annotations of one page, the data the review UI
+/// needs to draw a page with its markups.
+async fn get_page(path: web::Path<(i32, i32)>, db: web::Data<Pool>) -> Result<HttpResponse, Error> {
+    let (id, page_no) = path.into_inner();
+    let client = db
+        .get()
+        .await
+        .map_err(actix_web::error::ErrorInternalServerError)?;
+    if !pdf_exists(&client, id).await? {
+        return Ok(HttpResponse::NotFound().finish());
+    }
+    // Seite darf noch fehlen (Extraktion läuft), Annotationen gibt es trotzdem
+    let page = client
+        .query_opt(
+            "SELECT COALESCE(b.text, t.text, '') AS text, t.ocr_used, t.layout_json \
+             FROM pdf_texts t LEFT JOIN pdf_text_blobs b ON b.sha256 = t.text_sha256 \
+             WHERE t.merged_pdf_id=$1 AND t.page_no=$2",
+            &[&id, &page_no],
+        )
+        .await
+        .map_err(actix_web::error::ErrorInternalServerError)?;
+    let annotations = load_annotations(&client, id, Some(page_no), None)
+        .await
+        .map_err(actix_web::error::ErrorInternalServerError)?;
+    let (text, ocr_used, layout) = match page {
+        Some(row) => (
+            Some(row.get::<_, String>("text")),
+            Some(row.get::<_, bool>("ocr_used")),
+            row.get::<_, Option<serde_json::Value>>("layout_json"),
+        ),
+        None => (None, None, None),
+    };
+    Ok(HttpResponse::Ok().json(serde_json::json!({
+        "pdf_id": id,
+        "page_no": page_no,
+        "text": text,
+        "ocr_used": ocr_used,
+        "layout": layout,
+        "annotations": annotations,
+    })))
+}
+
+/// Returns the downscaled PNG of a page (0-based like `pdf_texts.page_no`, see
+/// `text_extraction::page_images`). Pages the extraction did not keep are
+/// rendered on the first request and stored; `404` for pages that cannot be
+/// rendered, e.g. while the PDF is archived.
+async fn get_page_image(
+    path: web::Path<(i32, i32)>,
+    db: web::Data<Pool>,
+) -> Result<HttpResponse, Error> {
+    let (id, page_no) = path.into_inner();
+    let client = db
+        .get()
+        .await
+        .map_err(actix_web::error::ErrorInternalServerError)?;
+    let cached = client
+        .query_opt(
+            "SELECT png FROM pdf_page_images WHERE merged_pdf_id=$1 AND page_no=$2",
+            &[&id, &page_no],
+        )
+        .await
+        .map_err(actix_web::error::ErrorInternalServerError)?;
+    if let Some(row) = cached {
+        return Ok(HttpResponse::Ok()
+            .insert_header((header::CONTENT_TYPE, "image/png"))
+            .body(row.get::<_, Vec<u8>>(0)));
+    }
+    let row = client
+        .query_opt(
+            "SELECT m.data,
+                    (SELECT u.pdf_password FROM uploads u
+                      WHERE u.pdf_id = m.id AND u.pdf_password IS NOT NULL
+                      ORDER BY u.id DESC LIMIT 1)
+               FROM merged_pdfs m WHERE m.id = $1",
+            &[&id],
+        )
+        .await
+        .map_err(actix_web::error::ErrorInternalServerError)?;
+    let Some(row) = row else {
+        return Ok(HttpResponse::NotFound().finish());
+    };
+    let unavailable =
+        || HttpResponse::NotFound().json(serde_json::json!({ "error": "page image unavailable" }));
+    // NULL, solange das PDF im Cold Storage liegt
+    let Some(data) = row.get::<_, Option<Vec<u8>>>(0) else {
+        return Ok(unavailable());
+    };
+    if page_no < 0 {
+        return Ok(unavailable());
+    }
+    let password = row
+        .get::<_, Option<serde_json::Value>>(1)
+        .and_then(|v| serde_json::from_value::<SealedSecret>(v).ok())
+        .and_then(|sealed| {
+            MasterKey::from_env()
+                .and_then(|key| key.open(&sealed))
+                .map_err(|e| warn!(%e, id, "open stored pdf password failed"))
+                .ok()
+        })
+        .map(PdfPassword::new);
+    let temp = TempPdf::write(&data)
+        .await
+        .map_err(actix_web::error::ErrorInternalServerError)?;
+    let image = match page_images::render_preview(
+        temp.path(),
+        page_no + 1,
+        page_images::max_px_from_env(),
+        password.as_ref(),
+    )
+    .await
+    {
+        Ok(image) => image,
+        Err(e) => {
+            warn!(%e, id, page_no, "render page image failed");
+            return Ok(unavailable());
+        }
+    };
+    if let Err(e) = client
+        .execute(
+            page_images::UPSERT_SQL,
+            &[
+                &id,
+                &page_no,
+                &(image.width as i32),
+                &(image.height as i32),
+                &image.png,
+            ],
+        )
+        .await
+    {
+        warn!(%e, id, page_no, "store page image failed");
+    }
+    Ok(HttpResponse::Ok()
+        .insert_header((header::CONTENT_TYPE, "image/png"))
+        .body(image.png))
+}
+
+#[derive(Serialize)]
+/// Extraction that failed `EXTRACTION_MAX_ATTEMPTS` times in a row.
+struct PoisonedExtraction {
+    pdf_id: i32,
+    pipeline_id: Option<Uuid>,
+    first_page: i32,
+    attempts: i32,
+    reason: Option<String>,
+    last_error: Option<String>,
+    upload_ids: Vec<i32>,
+    created_at: String,
+    poisoned_at: String,
+}
+
+/// Lists poisoned extractions (see `text_extraction::retries`), latest first.
+/// Needs `ADMIN_TOKEN` if set.
+async fn list_poisoned_extractions(
+    req: HttpRequest,
+    db: web::Data<Pool>,
+) -> Result<HttpResponse, Error> {
+    if let Some(denied) = admin_denied(&req) {
+        return Ok(denied);
+    }
+    let client = db
+        .get()
+        .await
+        .map_err(actix_web::error::ErrorInternalServerError)?;
+    let rows = client
+        .query(
+            "SELECT r.pdf_id, r.event, r.first_page, r.attempts, r.reason, r.last_error, \
+                    r.created_at, r.updated_at, \
+                    ARRAY(SELECT u.id FROM uploads u WHERE u.pdf_id = r.pdf_id ORDER BY u.id) \
+             FROM extraction_retries r \
+             WHERE r.status = 'poisoned' \
+             ORDER BY r.updated_at DESC",
+            &[],
+        )
+        .await
+        .map_err(actix_web::error::ErrorInternalServerError)?;
+    let items: Vec<PoisonedExtraction> = rows
+        .iter()
+        .map(|r| {
+            let event: serde_json::Value = r.get(1);
+            let created_at: chrono::DateTime<chrono::Utc> = r.get(6);
+            let poisoned_at: chrono::DateTime<chrono::Utc> = r.get(7);
+            PoisonedExtraction {
+                pdf_id: r.get(0),
+                pipeline_id: serde_json::from_value::<PdfUploaded>(event)
+                    .ok()
+                    .map(|evt| evt.pipeline_id)
+                    .filter(|p| !p.is_nil()),
+                first_page: r.get(2),
+                attempts: r.get(3),
+                reason: r.get(4),
+                last_error: r.get(5),
+                upload_ids: r.get(8),
+                created_at: created_at.to_rfc3339(),
+                poisoned_at: poisoned_at.to_rfc3339(),
+            }
+        })
+        .collect();
+    Ok(HttpResponse::Ok().json(items))
+}
+
+/// Queues a poisoned extraction again with fresh attempts; text-extraction
+/// picks it up with its next retry poll. `404` unless the extraction is
+/// poisoned. Needs `ADMIN_TOKEN` if set.
+async fn requeue_extraction(
+    req: HttpRequest,
+    id: web::Path<i32>,
+    db: web::Data<Pool>,
+) -> Result<HttpResponse, Error> {
+    if let Some(denied) = admin_denied(&req) {
+        return Ok(denied);
+    }
+    let id = id.into_inner();
+    let client = db
+        .get()
+        .await
+        .map_err(actix_web::error::ErrorInternalServerError)?;
+    let requeued = client
+        .execute(text_extraction::retries::REQUEUE_SQL, &[&id])
+        .await
+        .map_err(actix_web::error::ErrorInternalServerError)?;
+    if requeued == 0 {
+        return Ok(
+            HttpResponse::NotFound().json(serde_json::json!({ "error": "no poisoned extraction" }))
+        );
+    }
+    let _ = client
+        .execute(
+            "UPDATE uploads SET status='ocr' WHERE pdf_id=$1 AND status='failed'",
+            &[&id],
+        )
+        .await;
+    timeline::record(
+        &client,
+        &TimelineEvent::new("pdf-ingest", "extraction_requeued").pdf(Some(id)),
+    )
+    .await;
+    info!(pdf_id = id, "poisoned extraction requeued");
+    Ok(HttpResponse::Accepted().json(serde_json::json!({ "pdf_id": id, "status": "pending" })))
+}
+
+/// Erases a document or all documents of an external reference across the
+/// pipeline tables and returns the signed report (see `erasure.rs`); `409`
+/// while a document is under legal hold. Needs `ADMIN_TOKEN`, `503` without.
+async fn create_erasure_request(
+    req: HttpRequest,
+    body: web::Json<ErasureRequest>,
+    db: web::Data<Pool>,
+    archive: web::Data<Option<Archiver>>,
+    store: web::Data<Option<S3Store>>,
+) -> Result<HttpResponse, Error> {
+    if let Some(denied) = admin_required(&req) {
+        return Ok(denied);
+    }
+    let body = body.into_inner();
+    if let Err(e) = body.validate() {
+        return Ok(HttpResponse::BadRequest().json(serde_json::json!({ "error": e })));
+    }
+    let Some(signer) = Signer::from_env() else {
+        return Ok(HttpResponse::ServiceUnavailable()
+            .json(serde_json::json!({ "error": "ERASURE_SIGNING_KEY is not set" })));
+    };
+    let outcome = erasure::erase(
+        db.get_ref(),
+        &signer,
+        archive.get_ref().as_ref(),
+        store.get_ref().as_ref(),
+        &body,
+    )
+    .await
+    .map_err(|e| {
+        error!(error = %e, "erasure failed");
+        actix_web::error::ErrorInternalServerError(e)
+    })?;
+    let client = db
+        .get()
+        .await
+        .map_err(actix_web::error::ErrorInternalServerError)?;
+    match outcome {
+        Outcome::NotFound => Ok(HttpResponse::NotFound().finish()),
+        Outcome::Held(pdf_ids) => {
+            for pdf_id in &pdf_ids {
+                timeline::record(
+                    &client,
+                    &TimelineEvent::new("pdf-ingest", "erasure_blocked")
+                        .pdf(Some(*pdf_id))
+                        .message("document is under legal hold"),
+                )
+                .await;
+            }
+            Ok(HttpResponse::Conflict().json(serde_json::json!({
+                "error": "document is under legal hold",
+                "pdf_ids": pdf_ids,
+            })))
+        }
+        Outcome::Erased(signed) => {
+            for pdf_id in &signed.report.pdf_ids {
+                timeline::record(
+                    &client,
+                    &TimelineEvent::new("pdf-ingest", "erased")
+                        .pdf(Some(*pdf_id))
+                        .details(serde_json::json!({ "erasure_id": signed.report.id })),
+                )
+                .await;
+            }
+            Ok(HttpResponse::Created().json(signed))
+        }
+    }
+}
+
+/// Returns a stored erasure report and whether its signature still verifies.
+async fn get_erasure_request(
+    req: HttpRequest,
+    id: web::Path<Uuid>,
+    db: web::Data<Pool>,
+) -> Result<HttpResponse, Error> {
+    if let Some(denied) = admin_denied(&req) {
+        return Ok(denied);
+    }
+    let signer = Signer::from_env();
+    match erasure::load(db.get_ref(), signer.as_ref(), id.into_inner())
+        .await
+        .map_err(actix_web::error::ErrorInternalServerError)?
+    {
+        Some(signed) => Ok(HttpResponse::Ok().json(signed)),
+        None => Ok(HttpResponse::NotFound().finish()),
+    }
+}
+
+/// Random masked page texts of an approved tenant for prompt engineering (see
+/// `prompt_samples.rs`); `403` unless approved, `422` for too narrow filters.
+async fn get_prompt_samples(
+    q: web::Query<SampleQuery>,
+    db: web::Data<Pool>,
+    scrubber: web::Data<Scrubber>,
+) -> Result<HttpResponse, Error> {
     let client = db
         .get()
         .await
         .map_err(actix_web::error::ErrorInternalServerError)?;
+    let min_documents = prompt_samples::min_documents();
+    let outcome = prompt_samples::draw(&client, scrubber.get_ref(), &q, min_documents)
+        .await
+        .map_err(actix_web::error::ErrorInternalServerError)?;
+    match outcome {
+        prompt_samples::Outcome::Samples(samples) => {
+            info!(tenant_id = %q.tenant_id, count = samples.len(), "prompt samples drawn");
+            Ok(HttpResponse::Ok().json(serde_json::json!({ "samples": samples })))
+        }
+        prompt_samples::Outcome::NotApproved => Ok(HttpResponse::Forbidden()
+            .json(serde_json::json!({ "error": "tenant is not approved for prompt samples" }))),
+        prompt_samples::Outcome::TooFewDocuments { min_documents } => {
+            Ok(HttpResponse::UnprocessableEntity().json(serde_json::json!({
+                "error": "filter matches too few documents",
+                "min_documents": min_documents,
+            })))
+        }
+    }
+}
 
-    // Abhängigkeiten aufräumen (dürfen fehlen)
-    let _ = client
-        .execute("DELETE FROM pdf_sources WHERE pdf_id=$1", &[&id])
-        .await;
-    let _ = client
-        .execute("DELETE FROM pdf_texts  WHERE merged_pdf_id=$1", &[&id])
-        .await;
+/// Allows prompt samples from a tenant's documents. Needs `ADMIN_TOKEN` if set.
+async fn approve_prompt_samples(
+    req: HttpRequest,
+    tenant_id: web::Path<Uuid>,
+    body: Option<web::Json<SampleApproval>>,
+    db: web::Data<Pool>,
+) -> Result<HttpResponse, Error> {
+    if let Some(denied) = admin_denied(&req) {
+        return Ok(denied);
+    }
+    let tenant_id = tenant_id.into_inner();
+    let input = body.map(web::Json::into_inner).unwrap_or_default();
+    let approved_by = clean_reference(input.approved_by.as_deref());
+    let client = db
+        .get()
+        .await
+        .map_err(actix_web::error::ErrorInternalServerError)?;
+    let row = client
+        .query_opt(
+            "INSERT INTO prompt_sample_tenants (tenant_id, approved_by)              SELECT id, $2 FROM tenants WHERE id = $1              ON CONFLICT (tenant_id) DO UPDATE SET approved_by = EXCLUDED.approved_by, approved_at = now()              RETURNING approved_at::text",
+            &[&tenant_id, &approved_by],
+        )
+        .await
+        .map_err(actix_web::error::ErrorInternalServerError)?;
+    let Some(row) = row else {
+        return Ok(HttpResponse::NotFound().finish());
+    };
+    info!(%tenant_id, ?approved_by, "tenant approved for prompt samples");
+    Ok(HttpResponse::Ok().json(serde_json::json!({
+        "tenant_id": tenant_id,
+        "approved_by": approved_by,
+        "approved_at": row.get::<_, String>(0),
+    })))
+}
 
-    let rows = client
-        .execute("DELETE FROM merged_pdfs WHERE id=$1", &[&id])
+/// Withdraws the approval of [`approve_prompt_samples`].
+async fn revoke_prompt_samples(
+    req: HttpRequest,
+    tenant_id: web::Path<Uuid>,
+    db: web::Data<Pool>,
+) -> Result<HttpResponse, Error> {
+    if let Some(denied) = admin_denied(&req) {
+        return Ok(denied);
+    }
+    let tenant_id = tenant_id.into_inner();
+    let client = db
+        .get()
+        .await
+        .map_err(actix_web::error::ErrorInternalServerError)?;
+    let removed = client
+        .execute(
+            "DELETE FROM prompt_sample_tenants WHERE tenant_id = $1",
+            &[&tenant_id],
+        )
         .await
         .map_err(actix_web::error::ErrorInternalServerError)?;
+    if removed == 0 {
+        return Ok(HttpResponse::NotFound().finish());
+    }
+    info!(%tenant_id, "prompt sample approval revoked");
+    Ok(HttpResponse::NoContent().finish())
+}
 
-    if rows == 0 {
-        Ok(HttpResponse::NotFound().finish())
-    } else {
-        Ok(HttpResponse::Ok().finish())
+/// Returns the legal hold state of a merged PDF.
+async fn get_legal_hold(id: web::Path<i32>, db: web::Data<Pool>) -> Result<HttpResponse, Error> {
+    let id = id.into_inner();
+    let client = db
+        .get()
+        .await
+        .map_err(actix_web::error::ErrorInternalServerError)?;
+    let row = client
+        .query_opt(
+            "SELECT legal_hold, legal_hold_reason, legal_hold_by, legal_hold_at::text \
+             FROM merged_pdfs WHERE id=$1",
+            &[&id],
+        )
+        .await
+        .map_err(actix_web::error::ErrorInternalServerError)?;
+    match row {
+        Some(row) => Ok(HttpResponse::Ok().json(legal_hold_from_row(id, &row))),
+        None => Ok(HttpResponse::NotFound().finish()),
+    }
+}
+
+/// Places a legal hold on a merged PDF; deletion is refused until it is removed.
+async fn place_legal_hold(
+    id: web::Path<i32>,
+    body: web::Json<LegalHoldInput>,
+    db: web::Data<Pool>,
+) -> Result<HttpResponse, Error> {
+    let id = id.into_inner();
+    let input = body.into_inner();
+    let reason = input.reason.trim().to_string();
+    if reason.is_empty() {
+        return Ok(
+            HttpResponse::BadRequest().json(serde_json::json!({ "error": "reason is required" }))
+        );
+    }
+    let placed_by = clean_reference(input.placed_by.as_deref());
+    let client = db
+        .get()
+        .await
+        .map_err(actix_web::error::ErrorInternalServerError)?;
+    let Some(row) = client
+        .query_opt(
+            "UPDATE merged_pdfs \
+             SET legal_hold = TRUE, legal_hold_reason = $2, legal_hold_by = $3, legal_hold_at = now() \
+             WHERE id=$1 \
+             RETURNING legal_hold, legal_hold_reason, legal_hold_by, legal_hold_at::text",
+            &[&id, &reason, &placed_by],
+        )
+        .await
+        .map_err(actix_web::error::ErrorInternalServerError)?
+    else {
+        return Ok(HttpResponse::NotFound().finish());
+    };
+    // Audit-Eintrag ist Pflicht: ohne Eintrag kein Erfolg melden
+    timeline::append(
+        &client,
+        &TimelineEvent::new("pdf-ingest", "legal_hold_placed")
+            .pdf(Some(id))
+            .message(reason.clone())
+            .details(serde_json::json!({ "by": placed_by })),
+    )
+    .await
+    .map_err(actix_web::error::ErrorInternalServerError)?;
+    info!(pdf_id = id, "legal hold placed");
+    Ok(HttpResponse::Ok().json(legal_hold_from_row(id, &row)))
+}
+
+/// Removes the legal hold of a merged PDF.
+async fn remove_legal_hold(
+    id: web::Path<i32>,
+    q: web::Query<LegalHoldRelease>,
+    db: web::Data<Pool>,
+) -> Result<HttpResponse, Error> {
+    let id = id.into_inner();
+    let removed_by = clean_reference(q.removed_by.as_deref());
+    let client = db
+        .get()
+        .await
+        .map_err(actix_web::error::ErrorInternalServerError)?;
+    let Some(previous) = client
+        .query_opt(
+            "UPDATE merged_pdfs m \
+             SET legal_hold = FALSE, legal_hold_reason = NULL, legal_hold_by = NULL, legal_hold_at = NULL \
+             FROM (SELECT id, legal_hold, legal_hold_reason FROM merged_pdfs WHERE id=$1 FOR UPDATE) old \
+             WHERE m.id = old.id \
+             RETURNING old.legal_hold, old.legal_hold_reason",
+            &[&id],
+        )
+        .await
+        .map_err(actix_web::error::ErrorInternalServerError)?
+    else {
+        return Ok(HttpResponse::NotFound().finish());
+    };
+    let was_held: bool = previous.get(0);
+    if was_held {
+        let reason: Option<String> = previous.get(1);
+        timeline::append(
+            &client,
+            &TimelineEvent::new("pdf-ingest", "legal_hold_removed")
+                .pdf(Some(id))
+                .details(serde_json::json!({ "by": removed_by, "reason": reason })),
+        )
+        .await
+        .map_err(actix_web::error::ErrorInternalServerError)?;
+        info!(pdf_id = id, "legal hold removed");
+    }
+    Ok(HttpResponse::Ok().json(LegalHold {
+        pdf_id: id,
+        legal_hold: false,
+        reason: None,
+        placed_by: None,
+        placed_at: None,
+    }))
+}
+
+/// Creates the tables used by the ingest service if they do not exist yet.
+async fn ensure_schema(client: &tokio_postgres::Client) {
+    let _ = client
+        .execute(
+            "CREATE TABLE IF NOT EXISTS merged_pdfs (
+               id SERIAL PRIMARY KEY, sha256 TEXT NOT NULL, size_bytes INTEGER NOT NULL, data BYTEA NOT NULL
+             )",
+            &[],
+        )
+        .await;
+    let _ = client
+        .execute(
+            "ALTER TABLE merged_pdfs
+               ADD COLUMN IF NOT EXISTS legal_hold BOOLEAN NOT NULL DEFAULT FALSE,
+               ADD COLUMN IF NOT EXISTS legal_hold_reason TEXT,
+               ADD COLUMN IF NOT EXISTS legal_hold_by TEXT,
+               ADD COLUMN IF NOT EXISTS legal_hold_at TIMESTAMPTZ",
+            &[],
+        )
+        .await;
+    let _ = client
+        .execute(
+            "ALTER TABLE merged_pdfs ADD COLUMN IF NOT EXISTS version INTEGER NOT NULL DEFAULT 1",
+            &[],
+        )
+        .await;
+    // Cold Storage (siehe archive.rs); data ist NULL, solange das PDF archiviert ist
+    let _ = client
+        .execute(
+            "ALTER TABLE merged_pdfs
+               ALTER COLUMN data DROP NOT NULL,
+               ADD COLUMN IF NOT EXISTS created_at TIMESTAMPTZ NOT NULL DEFAULT now(),
+               ADD COLUMN IF NOT EXISTS storage_tier TEXT NOT NULL DEFAULT 'hot',
+               ADD COLUMN IF NOT EXISTS archive_key TEXT,
+               ADD COLUMN IF NOT EXISTS archived_at TIMESTAMPTZ,
+               ADD COLUMN IF NOT EXISTS restored_at TIMESTAMPTZ",
+            &[],
+        )
+        .await;
+    // Durchsuchbare Kopie gescannter PDFs (text-extraction, SEARCHABLE_PDF=1)
+    let _ = client
+        .execute(text_extraction::searchable::SCHEMA_SQL, &[])
+        .await;
+    // Verkleinerte Seitenbilder für Vorschauen (text-extraction, PAGE_IMAGE_CACHE=1)
+    let _ = client
+        .execute(text_extraction::page_images::SCHEMA_SQL, &[])
+        .await;
+    // Wiederholungen fehlgeschlagener Extraktionen (text-extraction)
+    for sql in text_extraction::retries::SCHEMA_SQL {
+        let _ = client.execute(sql, &[]).await;
+    }
+    let _ = client
+        .execute(
+            "CREATE TABLE IF NOT EXISTS pdf_versions (
+               pdf_id INTEGER NOT NULL REFERENCES merged_pdfs(id) ON DELETE CASCADE,
+               version INTEGER NOT NULL,
+               sha256 TEXT NOT NULL,
+               size_bytes INTEGER NOT NULL,
+               page_count INTEGER NOT NULL,
+               data BYTEA NOT NULL,
+               created_at TIMESTAMPTZ NOT NULL DEFAULT now(),
+               PRIMARY KEY (pdf_id, version)
+             )",
+            &[],
+        )
+        .await;
+    let _ = client.execute(entities::CREATE_TABLE_SQL, &[]).await;
+    let _ = client.execute(annotations::CREATE_TABLE_SQL, &[]).await;
+    let _ = client.execute(pdf_metadata::CREATE_TABLE_SQL, &[]).await;
+    for sql in similarity::SCHEMA_SQL {
+        let _ = client.batch_execute(sql).await;
+    }
+    // Seitentexte (angelegt von text-extraction); fehlt pdf_texts noch, holt
+    // text-extraction das beim Start nach
+    for sql in page_texts::SCHEMA_SQL {
+        let _ = client.batch_execute(sql).await;
+    }
+    let _ = client.batch_execute(page_texts::COMPRESSION_SQL).await;
+    let _ = client
+        .execute(
+            "CREATE TABLE IF NOT EXISTS pdf_sources (
+               pdf_id INTEGER PRIMARY KEY REFERENCES merged_pdfs(id), names TEXT, count INTEGER
+             )",
+            &[],
+        )
+        .await;
+    let _ = client
+        .execute(
+            "CREATE TABLE IF NOT EXISTS uploads (
+               id SERIAL PRIMARY KEY, pdf_id INTEGER, pipeline_id UUID, status TEXT NOT NULL
+             )",
+            &[],
+        )
+        .await;
+    // NEU: tenant_id-Spalte sicherstellen (falls Migration in frischer DB noch nicht lief)
+    let _ = client
+        .execute(
+            "ALTER TABLE uploads ADD COLUMN IF NOT EXISTS tenant_id UUID",
+            &[],
+        )
+        .await;
+    let _ = client
+        .execute(
+            "ALTER TABLE uploads
+               ADD COLUMN IF NOT EXISTS job_label TEXT,
+               ADD COLUMN IF NOT EXISTS external_ref TEXT",
+            &[],
+        )
+        .await;
+    let _ = client
+        .execute(
+            "ALTER TABLE uploads ADD COLUMN IF NOT EXISTS created_at TIMESTAMPTZ DEFAULT now()",
+            &[],
+        )
+        .await;
+    let _ = client
+        .execute(
+            "ALTER TABLE uploads
+               ADD COLUMN IF NOT EXISTS run_state TEXT,
+               ADD COLUMN IF NOT EXISTS run_priority TEXT NOT NULL DEFAULT 'normal'",
+            &[],
+        )
+        .await;
+    let _ = client
+        .execute(
+            "ALTER TABLE uploads ADD COLUMN IF NOT EXISTS storage_key TEXT",
+            &[],
+        )
+        .await;
+    let _ = client
+        .execute(
+            "ALTER TABLE uploads ADD COLUMN IF NOT EXISTS pdf_password JSONB",
+            &[],
+        )
+        .await;
+    for sql in operator_notes::CREATE_UPLOAD_NOTES_SQL {
+        let _ = client.execute(*sql, &[]).await;
     }
+    // Vorklassifizierung (siehe classify.rs)
+    for sql in classify::SCHEMA_SQL {
+        let _ = client.execute(*sql, &[]).await;
+    }
+    let _ = client.execute(timeline::CREATE_TABLE_SQL, &[]).await;
+    let _ = client.execute(erasure::CREATE_TABLE_SQL, &[]).await;
+    let _ = client.execute(prompt_samples::CREATE_TABLE_SQL, &[]).await;
 }
 
 #[actix_web::main]
@@ -580,72 +3250,162 @@ async fn main() -> std::io::Result<()> {
     })?;
     info!("created postgres pool");
 
-    // Schema sicherstellen (mit Pool-Client)
+    let readiness = Readiness::new(&["postgres", "kafka"]);
+    let backoff = Backoff::from_env();
+
+    // Schema sicherstellen, sobald Postgres erreichbar ist (kein Crash-Loop beim Kaltstart)
     {
-        let client = pool.get().await.map_err(|e| {
-            error!(%e, "db get from pool failed");
-            std::io::Error::new(std::io::ErrorKind::Other, "db-pool-get")
-        })?;
-        let _ = client
-            .execute(
-                "CREATE TABLE IF NOT EXISTS merged_pdfs (
-               id SERIAL PRIMARY KEY, sha256 TEXT NOT NULL, size_bytes INTEGER NOT NULL, data BYTEA NOT NULL
-             )",
-                &[],
-            )
-            .await;
-        let _ = client
-            .execute(
-                "CREATE TABLE IF NOT EXISTS pdf_sources (
-               pdf_id INTEGER PRIMARY KEY REFERENCES merged_pdfs(id), names TEXT, count INTEGER
-             )",
-                &[],
-            )
-            .await;
-        let _ = client
-            .execute(
-                "CREATE TABLE IF NOT EXISTS uploads (
-               id SERIAL PRIMARY KEY, pdf_id INTEGER, pipeline_id UUID, status TEXT NOT NULL
-             )",
-                &[],
-            )
-            .await;
-        // NEU: tenant_id-Spalte sicherstellen (falls Migration in frischer DB noch nicht lief)
-        let _ = client
-            .execute(
-                "ALTER TABLE uploads ADD COLUMN IF NOT EXISTS tenant_id UUID",
-                &[],
-            )
-            .await;
+        let pool = pool.clone();
+        let readiness = readiness.clone();
+        actix_web::rt::spawn(async move {
+            let client = retry_with_backoff("postgres", backoff, || pool.get()).await;
+            ensure_schema(&client).await;
+            readiness.mark_ready("postgres");
+            info!("postgres ready");
+        });
     }
 
     // Kafka Producer
-    let producer: FutureProducer = match ClientConfig::new()
-        .set("bootstrap.servers", &settings.message_broker_url)
-        .create()
-    {
-        Ok(p) => p,
-        Err(e) => {
-            error!(%e, "failed to create kafka producer");
-            std::process::exit(1);
-        }
-    };
+    let broker = settings.message_broker_url.clone();
+    let producer: FutureProducer = retry_with_backoff("kafka-producer", backoff, || async {
+        ClientConfig::new()
+            .set("bootstrap.servers", &broker)
+            .create::<FutureProducer>()
+    })
+    .await;
     info!("kafka producer created");
+    {
+        let readiness = readiness.clone();
+        actix_web::rt::spawn(async move {
+            retry_with_backoff("kafka", backoff, || {
+                kafka::ensure_topics(&broker, &["pdf-merged", "pipeline-run", "text-extracted"])
+            })
+            .await;
+            readiness.mark_ready("kafka");
+            info!("kafka ready");
+        });
+    }
+
+    let classify = ClassifyConfig::from_env();
+    if classify.enabled {
+        info!(
+            min_confidence = classify.min_confidence,
+            "classification of uploads without pipeline enabled"
+        );
+    }
+    actix_web::rt::spawn(run_trigger_consumer(
+        pool.clone(),
+        producer.clone(),
+        settings.message_broker_url.clone(),
+        classify,
+    ));
 
     let db_pool = web::Data::new(pool);
     let producer_data = web::Data::new(producer);
+    let readiness_data = web::Data::new(readiness);
+    let store = S3Store::from_env();
+    if store.is_some() {
+        info!("direct uploads to S3 enabled");
+    }
+    let store_data = web::Data::new(store);
+    let archive = Archiver::from_env();
+    if let Some(archive) = archive.clone() {
+        info!("cold storage archive enabled");
+        actix_web::rt::spawn(archive.run(db_pool.get_ref().clone()));
+    }
+    let archive_data = web::Data::new(archive);
+    let scrubber = Scrubber::for_samples().map_err(|e| {
+        error!(%e, "invalid scrub rules");
+        std::io::Error::new(std::io::ErrorKind::Other, "scrub-rules")
+    })?;
+    let scrubber_data = web::Data::new(scrubber);
+    let classify_data = web::Data::new(classify);
 
+    let cors = CorsSettings::from_env();
     HttpServer::new(move || {
         App::new()
-            .wrap(Cors::permissive())
+            .wrap(cors.actix())
             .app_data(db_pool.clone())
             .app_data(producer_data.clone())
+            .app_data(readiness_data.clone())
+            .app_data(store_data.clone())
+            .app_data(archive_data.clone())
+            .app_data(scrubber_data.clone())
+            .app_data(classify_data.clone())
             .route("/upload", web::post().to(upload))
+            .route("/uploads/direct", web::post().to(create_direct_upload))
+            .route(
+                "/uploads/{id}/complete",
+                web::post().to(complete_direct_upload),
+            )
             .route("/uploads", web::get().to(list_uploads))
+            .route(
+                "/uploads/unassigned",
+                web::get().to(list_unassigned_uploads),
+            )
             .route("/uploads/{id}/extract", web::get().to(get_extract))
+            .route("/uploads/{id}/run", web::post().to(trigger_run))
+            .route("/uploads/{id}/notes", web::put().to(put_upload_notes))
             .route("/pdf/{id}", web::get().to(get_pdf))
             .route("/pdf/{id}", web::delete().to(delete_pdf))
+            .route("/pdf/{id}/append", web::post().to(append_pdf))
+            .route("/pdf/{id}/entities", web::get().to(get_entities))
+            .route("/pdf/{id}/similar", web::get().to(get_similar))
+            .route("/pdf/{id}/reocr", web::post().to(reocr_pdf))
+            .route("/pdf/{id}/metadata", web::get().to(get_metadata))
+            .route("/pdf/{id}/searchable", web::get().to(get_searchable_pdf))
+            .route("/pdf/{id}/annotations", web::get().to(list_annotations))
+            .route("/pdf/{id}/annotations", web::post().to(create_annotation))
+            .route(
+                "/pdf/{id}/annotations/{annotation_id}",
+                web::delete().to(delete_annotation),
+            )
+            .route("/pdf/{id}/pages/{page}", web::get().to(get_page))
+            .route(
+                "/pdf/{id}/pages/{page}/image",
+                web::get().to(get_page_image),
+            )
+            .route("/pdf/{id}/legal-hold", web::get().to(get_legal_hold))
+            .route("/pdf/{id}/legal-hold", web::put().to(place_legal_hold))
+            .route("/pdf/{id}/legal-hold", web::delete().to(remove_legal_hold))
+            .route(
+                "/extractions/poisoned",
+                web::get().to(list_poisoned_extractions),
+            )
+            .route(
+                "/extractions/{pdf_id}/requeue",
+                web::post().to(requeue_extraction),
+            )
+            .route("/erasure-requests", web::post().to(create_erasure_request))
+            .route("/erasure-requests/{id}", web::get().to(get_erasure_request))
+            .route("/prompt-samples", web::get().to(get_prompt_samples))
+            .route(
+                "/admin/tenants/{id}/prompt-samples",
+                web::put().to(approve_prompt_samples),
+            )
+            .route(
+                "/admin/tenants/{id}/prompt-samples",
+                web::delete().to(revoke_prompt_samples),
+            )
+            .route("/admin/schema", web::get().to(get_schema))
+            .route(
+                "/admin/classification-rules",
+                web::get().to(list_classification_rules),
+            )
+            .route(
+                "/admin/classification-rules/{pipeline_id}",
+                web::put().to(put_classification_rule),
+            )
+            .route(
+                "/admin/classification-rules/{pipeline_id}",
+                web::delete().to(delete_classification_rule),
+            )
+            .route(
+                "/admin/pdf-texts/compact",
+                web::post().to(compact_pdf_texts),
+            )
             .route("/health", web::get().to(health))
+            .route("/readyz", web::get().to(readyz))
     })
     .bind(("0.0.0.0", 8081))?
     .run()
@@ -669,6 +3429,50 @@ mod tests {
         assert!(resp.status().is_success());
     }
 
+    #[actix_web::test]
+    async fn run_trigger_options() {
+        use shared::dto::RunPriority;
+        use uuid::Uuid;
+
+        assert_eq!(super::parse_flag(" FALSE "), Some(false));
+        assert_eq!(super::parse_flag("1"), Some(true));
+        assert_eq!(super::parse_flag("maybe"), None);
+        assert_eq!(super::parse_priority("High").unwrap(), RunPriority::High);
+        assert!(super::parse_priority("urgent").is_err());
+
+        let pipeline = Uuid::from_u128(1);
+        assert_eq!(super::initial_run_state(Uuid::nil(), true, false), None);
+        assert_eq!(
+            super::initial_run_state(pipeline, true, false),
+            Some("pending")
+        );
+        assert_eq!(
+            super::initial_run_state(pipeline, false, true),
+            Some("parked")
+        );
+        assert_eq!(
+            super::initial_run_state(Uuid::nil(), true, true),
+            Some("classify")
+        );
+        assert_eq!(super::initial_run_state(Uuid::nil(), false, true), None);
+    }
+
+    #[actix_web::test]
+    async fn appended_source_names() {
+        let files = vec![
+            (Vec::new(), "scans/b.pdf".to_string()),
+            (Vec::new(), "a.pdf".to_string()),
+        ];
+        let names = super::source_names(vec!["a.pdf".into()], &files);
+        assert_eq!(names, vec!["a.pdf".to_string(), "b.pdf".to_string()]);
+
+        let unnamed = vec![(Vec::new(), " ".to_string())];
+        assert_eq!(
+            super::source_names(Vec::new(), &unnamed),
+            vec![" ".to_string()]
+        );
+    }
+
     #[actix_web::test]
     async fn get_pdf_ok() {
         let url = std::env::var("DATABASE_URL").unwrap_or_else(|_| {
@@ -691,6 +3495,18 @@ mod tests {
                             &[],
                         )
                         .await;
+                    let _ = client
+                        .execute(
+                            "ALTER TABLE merged_pdfs ADD COLUMN IF NOT EXISTS legal_hold BOOLEAN NOT NULL DEFAULT FALSE",
+                            &[],
+                        )
+                        .await;
+                    let _ = client
+                        .execute(
+                            "ALTER TABLE merged_pdfs ADD COLUMN IF NOT EXISTS archive_key TEXT",
+                            &[],
+                        )
+                        .await;
                     let _ = client
                         .execute(
                             "CREATE TABLE IF NOT EXISTS pdf_sources (pdf_id INTEGER PRIMARY KEY REFERENCES merged_pdfs(id), names TEXT, count INTEGER)",
@@ -714,6 +3530,7 @@ mod tests {
                     let app = test::init_service(
                         App::new()
                             .app_data(web::Data::new(pool.clone()))
+                            .app_data(web::Data::new(None::<super::Archiver>))
                             .route("/pdf/{id}", web::get().to(super::get_pdf))
                             .route("/pdf/{id}", web::delete().to(super::delete_pdf)),
                     )
@@ -762,6 +3579,9 @@ mod tests {
                             &[],
                         )
                         .await;
+                    for sql in shared::page_texts::SCHEMA_SQL {
+                        let _ = client.batch_execute(sql).await;
+                    }
                     let _ = client
                         .execute(
                             "INSERT INTO pdf_texts (merged_pdf_id, page_no, text) VALUES ($1,$2,$3)",
@@ -0,0 +1,129 @@
+//! Masked page-text samples for prompt engineering (`GET /prompt-samples`).
+//!
+//! Prompt authors need realistic text, not customer documents. [`draw`] only
+//! hands out text under these rules:
+//!
+//! * the tenant was approved by an admin (`PUT /admin/tenants/{id}/prompt-samples`,
+//!   table `prompt_sample_tenants`);
+//! * the filter must match at least `PROMPT_SAMPLE_MIN_DOCUMENTS` documents, so
+//!   a narrow filter cannot single out one document; the count itself is not
+//!   returned;
+//! * at most one random page per document, documents in random order, at most
+//!   [`MAX_SIZE`] pages per request;
+//! * no document, upload or page ids, and every text passes through
+//!   [`Scrubber::for_samples`] (IBANs, e-mail addresses, long numbers, dates).
+//!
+//! Names and free-form personal details are not recognized by the masking, so
+//! tenants should only be approved with that in mind.
+
+use std::env;
+
+use serde::{Deserialize, Serialize};
+use shared::scrubber::Scrubber;
+use uuid::Uuid;
+
+/// Creates `prompt_sample_tenants` (see migration 0047).
+pub const CREATE_TABLE_SQL: &str = "CREATE TABLE IF NOT EXISTS prompt_sample_tenants (
+    tenant_id UUID PRIMARY KEY REFERENCES tenants(id) ON DELETE CASCADE,
+    approved_by TEXT,
+    approved_at TIMESTAMPTZ NOT NULL DEFAULT now()
+)";
+
+pub const DEFAULT_SIZE: i64 = 5;
+pub const MAX_SIZE: i64 = 20;
+
+/// Matching documents per tenant, one random non-empty page each, in random
+/// order; every row carries the number of matching documents.
+const SAMPLE_SQL: &str = "
+    WITH pages AS (
+        SELECT DISTINCT ON (p.merged_pdf_id) p.merged_pdf_id, p.text
+          FROM pdf_page_texts p
+         WHERE p.merged_pdf_id IN (
+                   SELECT pdf_id FROM uploads
+                    WHERE tenant_id = $1
+                      AND pdf_id IS NOT NULL
+                      AND ($2::uuid IS NULL OR pipeline_id = $2))
+           AND btrim(p.text) <> ''
+           AND ($3::text IS NULL OR strpos(lower(p.text), lower($3)) > 0)
+         ORDER BY p.merged_pdf_id, random()
+    )
+    SELECT text, count(*) OVER () AS documents
+      FROM pages
+     ORDER BY random()
+     LIMIT $4";
+
+#[derive(Debug, Deserialize)]
+/// Filter of `GET /prompt-samples`.
+pub struct SampleQuery {
+    pub tenant_id: Uuid,
+    pub pipeline_id: Option<Uuid>,
+    /// Case-insensitive substring the page must contain.
+    pub contains: Option<String>,
+    pub size: Option<i64>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct Sample {
+    pub text: String,
+}
+
+#[derive(Debug)]
+pub enum Outcome {
+    Samples(Vec<Sample>),
+    NotApproved,
+    /// Fewer matching documents than `min_documents`.
+    TooFewDocuments {
+        min_documents: i64,
+    },
+}
+
+/// `PROMPT_SAMPLE_MIN_DOCUMENTS`, default 10.
+pub fn min_documents() -> i64 {
+    env::var("PROMPT_SAMPLE_MIN_DOCUMENTS")
+        .ok()
+        .and_then(|v| v.trim().parse::<i64>().ok())
+        .filter(|v| *v > 0)
+        .unwrap_or(10)
+}
+
+/// Draws a masked sample for `query` (see module docs).
+pub async fn draw(
+    client: &tokio_postgres::Client,
+    scrubber: &Scrubber,
+    query: &SampleQuery,
+    min_documents: i64,
+) -> Result<Outcome, tokio_postgres::Error> {
+    let approved = client
+        .query_opt(
+            "SELECT 1 FROM prompt_sample_tenants WHERE tenant_id = $1",
+            &[&query.tenant_id],
+        )
+        .await?
+        .is_some();
+    if !approved {
+        return Ok(Outcome::NotApproved);
+    }
+    let contains = query
+        .contains
+        .as_deref()
+        .map(str::trim)
+        .filter(|c| !c.is_empty());
+    let size = query.size.unwrap_or(DEFAULT_SIZE).clamp(1, MAX_SIZE);
+    let rows = client
+        .query(
+            SAMPLE_SQL,
+            &[&query.tenant_id, &query.pipeline_id, &contains, &size],
+        )
+        .await?;
+    let documents: i64 = rows.first().map(|r| r.get("documents")).unwrap_or(0);
+    if documents < min_documents {
+        return Ok(Outcome::TooFewDocuments { min_documents });
+    }
+    Ok(Outcome::Samples(
+        rows.iter()
+            .map(|r| Sample {
+                text: scrubber.scrub_text(r.get("text")),
+            })
+            .collect(),
+    ))
+}
@@ -0,0 +1,429 @@
+//! Run artifact bundle: one ZIP with everything needed to audit a run.
+//!
+//! Contains the run row, the pipeline configuration and the texts of all
+//! prompts referenced by it as the run saw them (see `shared::run_config`),
+//! every logged step, the final results, the run timeline, the operator tags
+//! and notes, the extracted page texts, the evidence crops and optionally the
+//! source PDF. Pipelines with an `output_mapping` also get
+//! `output.json` with the results under the customer field names.
+//!
+//! With `?role=` the bundle is built for a recipient role through the
+//! pipeline's redaction profile (`shared::redaction`): omitted parts are not
+//! written at all, redacted values never enter the ZIP, and `manifest.json`
+//! names the applied profile.
+
+use crate::evidence::{self, EvidenceOptions};
+use anyhow::{Context, Result};
+use serde::Deserialize;
+use serde_json::{json, Map, Value};
+use shared::output_mapping::{self, OutputField};
+use shared::redaction::{BundlePart, RedactionProfile};
+use shared::run_config;
+use shared::scrubber::Scrubber;
+use sqlx::{PgPool, Row};
+use std::io::{Cursor, Seek, Write};
+use tracing::warn;
+use uuid::Uuid;
+use zip::write::FileOptions;
+use zip::ZipWriter;
+
+/// Query parameters of `GET /runs/{id}/bundle.zip`.
+#[derive(Debug, Clone, Deserialize)]
+pub struct BundleOptions {
+    /// Adds the merged source PDF as `source.pdf`.
+    #[serde(default)]
+    pub include_pdf: bool,
+    /// Renders evidence crops into `evidence/` (default: on).
+    #[serde(default = "default_true")]
+    pub evidence: bool,
+    /// Recipient role; selects a redaction profile of the pipeline.
+    #[serde(default)]
+    pub role: Option<String>,
+}
+
+fn default_true() -> bool {
+    true
+}
+
+fn write_json<W: Write + Seek>(zip: &mut ZipWriter<W>, name: &str, value: &Value) -> Result<()> {
+    zip.start_file(name, FileOptions::default())?;
+    zip.write_all(&serde_json::to_vec_pretty(value)?)?;
+    Ok(())
+}
+
+/// Redaction profiles of the run's pipeline in its current config, so changed
+/// policies also apply to older runs; `None` when the run does not exist.
+pub async fn redaction_profiles(
+    pool: &PgPool,
+    run_id: Uuid,
+) -> Result<Option<Vec<RedactionProfile>>> {
+    let profiles = sqlx::query_scalar::<_, Option<Value>>(
+        "SELECT p.config_json -> 'redaction_profiles'
+           FROM pipeline_runs r LEFT JOIN pipelines p ON p.id = r.pipeline_id
+          WHERE r.id = $1",
+    )
+    .bind(run_id)
+    .fetch_optional(pool)
+    .await
+    .context("load redaction profiles")?;
+    profiles
+        .map(|value| {
+            value
+                .map(serde_json::from_value)
+                .transpose()
+                .map(Option::unwrap_or_default)
+                .context("invalid redaction_profiles")
+        })
+        .transpose()
+}
+
+/// Applies a redaction profile to the parts of the bundle.
+struct Redactor {
+    profile: Option<RedactionProfile>,
+    scrubber: Option<Scrubber>,
+}
+
+impl Redactor {
+    /// `mapping` lets the profile list output fields by customer name.
+    fn new(profile: Option<&RedactionProfile>, mapping: &[OutputField]) -> Result<Self> {
+        let profile = profile.map(|p| p.resolve(mapping));
+        let scrubber = match &profile {
+            Some(profile) if profile.mask_pii => {
+                Some(Scrubber::for_samples().context("pii scrubber")?)
+            }
+            _ => None,
+        };
+        Ok(Self { profile, scrubber })
+    }
+
+    fn omits(&self, part: BundlePart) -> bool {
+        self.profile.as_ref().is_some_and(|p| p.omits(part))
+    }
+
+    fn mask(&self, value: &mut Value) {
+        if let Some(scrubber) = &self.scrubber {
+            scrubber.scrub(value);
+        }
+    }
+
+    fn text(&self, text: String) -> String {
+        match &self.scrubber {
+            Some(scrubber) => scrubber.scrub_text(&text),
+            None => text,
+        }
+    }
+
+    /// Final results of the run row and the encrypted full extraction.
+    fn run(&self, run: &mut Value) {
+        let Some(profile) = &self.profile else {
+            return;
+        };
+        if let Some(row) = run.as_object_mut() {
+            row.remove("final_extraction_sealed");
+            for column in ["final_extraction", "final_scores", "final_decisions"] {
+                if let Some(finals) = row.get_mut(column).and_then(Value::as_object_mut) {
+                    profile.redact_finals(finals);
+                }
+            }
+        }
+        self.mask(run);
+    }
+}
+
+/// Builds the bundle; `None` when the run does not exist. `profile` is the
+/// redaction profile of [`BundleOptions::role`].
+pub async fn bundle_zip(
+    pool: &PgPool,
+    run_id: Uuid,
+    opts: &BundleOptions,
+    profile: Option<&RedactionProfile>,
+) -> Result<Option<Vec<u8>>> {
+    let Some(mut run) = sqlx::query_scalar::<_, Value>(
+        "SELECT row_to_json(r)::jsonb FROM pipeline_runs r WHERE r.id = $1",
+    )
+    .bind(run_id)
+    .fetch_optional(pool)
+    .await
+    .context("load run")?
+    else {
+        return Ok(None);
+    };
+    let pdf_id = run.get("pdf_id").and_then(Value::as_i64).unwrap_or(0) as i32;
+    let pipeline_id = run
+        .get("pipeline_id")
+        .and_then(Value::as_str)
+        .and_then(|s| Uuid::parse_str(s).ok());
+
+    let mut zip = ZipWriter::new(Cursor::new(Vec::new()));
+    let mut files: Vec<String> = Vec::new();
+    let mut warnings: Vec<String> = Vec::new();
+
+    // Konfiguration zur Laufzeit; ältere Runs ohne Snapshot zeigen den aktuellen Stand
+    let with_prompts = !profile.is_some_and(|p| p.omits(BundlePart::Prompts));
+    let mut config = Value::Null;
+    match run_config::load(pool, run_id)
+        .await
+        .context("load run config")?
+    {
+        Some(snapshot) if !with_prompts => config = snapshot.config,
+        Some(snapshot) => {
+            config = snapshot.config.clone();
+            write_json(
+                &mut zip,
+                "pipeline.json",
+                &json!({
+                    "id": pipeline_id,
+                    "name": snapshot.pipeline_name,
+                    "updated_at": snapshot.pipeline_updated_at,
+                    "config": config,
+                    "model": snapshot.model,
+                }),
+            )?;
+            files.push("pipeline.json".into());
+            write_json(&mut zip, "prompts.json", &json!(snapshot.prompts))?;
+            files.push("prompts.json".into());
+        }
+        None if !with_prompts => {
+            config = sqlx::query_scalar("SELECT config_json FROM pipelines WHERE id = $1")
+                .bind(pipeline_id)
+                .fetch_optional(pool)
+                .await
+                .context("load pipeline")?
+                .unwrap_or(Value::Null);
+        }
+        None => {
+            warnings.push(
+                "no config recorded for this run; pipeline.json and prompts.json show the current state"
+                    .into(),
+            );
+            let pipeline = sqlx::query(
+                "SELECT name, config_json, updated_at::text AS updated_at FROM pipelines WHERE id = $1",
+            )
+            .bind(pipeline_id)
+            .fetch_optional(pool)
+            .await
+            .context("load pipeline")?;
+            match pipeline {
+                Some(row) => {
+                    config = row.try_get("config_json").unwrap_or(Value::Null);
+                    let updated_at: Option<String> = row.try_get("updated_at").ok();
+                    write_json(
+                        &mut zip,
+                        "pipeline.json",
+                        &json!({
+                            "id": pipeline_id,
+                            "name": row.try_get::<String, _>("name").ok(),
+                            "updated_at": updated_at,
+                            "config": config,
+                        }),
+                    )?;
+                    files.push("pipeline.json".into());
+                }
+                None => warnings.push("pipeline no longer exists".into()),
+            }
+
+            let ids = run_config::prompt_ids(&config);
+            let prompts: Vec<Value> = sqlx::query(
+                "SELECT id, prompt_type, text, json_key FROM prompts WHERE id = ANY($1) ORDER BY id",
+            )
+            .bind(&ids)
+            .fetch_all(pool)
+            .await
+            .context("load prompts")?
+            .into_iter()
+            .map(|r| {
+                json!({
+                    "id": r.try_get::<i32, _>("id").ok(),
+                    "prompt_type": r.try_get::<String, _>("prompt_type").ok(),
+                    "json_key": r.try_get::<Option<String>, _>("json_key").ok().flatten(),
+                    "text": r.try_get::<String, _>("text").ok(),
+                })
+            })
+            .collect();
+            write_json(&mut zip, "prompts.json", &Value::Array(prompts))?;
+            files.push("prompts.json".into());
+        }
+    }
+
+    let mapping: Vec<OutputField> = config
+        .get("output_mapping")
+        .cloned()
+        .and_then(|v| serde_json::from_value(v).ok())
+        .unwrap_or_default();
+    let redactor = Redactor::new(profile, &mapping)?;
+    redactor.run(&mut run);
+    write_json(&mut zip, "run.json", &run)?;
+    files.push("run.json".into());
+
+    let mut steps: Vec<Value> = sqlx::query_scalar(
+        "SELECT row_to_json(s)::jsonb FROM pipeline_run_steps s WHERE s.run_id = $1 ORDER BY s.seq_no",
+    )
+    .bind(run_id)
+    .fetch_all(pool)
+    .await
+    .context("load run steps")?;
+
+    let mut results: Map<String, Value> = Map::new();
+    for step in steps
+        .iter()
+        .filter(|s| s.get("is_final").and_then(Value::as_bool) == Some(true))
+    {
+        let ptype = step
+            .get("prompt_type")
+            .and_then(Value::as_str)
+            .unwrap_or("unknown");
+        let key = step
+            .get("final_key")
+            .and_then(Value::as_str)
+            .unwrap_or_default();
+        let group = results
+            .entry(ptype.to_string())
+            .or_insert_with(|| json!({}));
+        group[key] = step.get("result").cloned().unwrap_or(Value::Null);
+    }
+    if !mapping.is_empty() {
+        let finals = output_mapping::collect_finals(results.values().filter_map(Value::as_object));
+        let mut output = output_mapping::render(&mapping, &finals);
+        if let Some(profile) = &redactor.profile {
+            profile.redact_output(&mut output, &mapping);
+        }
+        let mut output = json!(output);
+        redactor.mask(&mut output);
+        write_json(&mut zip, "output.json", &output)?;
+        files.push("output.json".into());
+    }
+    if let Some(profile) = &redactor.profile {
+        profile.redact_steps(&mut steps);
+        for group in results.values_mut().filter_map(Value::as_object_mut) {
+            profile.redact_finals(group);
+        }
+    }
+    let mut results = Value::Object(results);
+    redactor.mask(&mut results);
+    if !redactor.omits(BundlePart::Steps) {
+        let mut steps = Value::Array(steps);
+        redactor.mask(&mut steps);
+        write_json(&mut zip, "steps.json", &steps)?;
+        files.push("steps.json".into());
+    }
+    write_json(&mut zip, "results.json", &results)?;
+    files.push("results.json".into());
+
+    let timeline: Vec<Value> = sqlx::query_scalar(
+        "SELECT row_to_json(t)::jsonb FROM run_timeline t
+          WHERE t.run_id = $1 OR (t.run_id IS NULL AND t.pdf_id = $2)
+          ORDER BY t.created_at, t.id",
+    )
+    .bind(run_id)
+    .bind(pdf_id)
+    .fetch_all(pool)
+    .await
+    .unwrap_or_else(|e| {
+        warnings.push(format!("timeline unavailable: {e}"));
+        Vec::new()
+    });
+    if !redactor.omits(BundlePart::Timeline) {
+        let mut timeline = Value::Array(timeline);
+        redactor.mask(&mut timeline);
+        write_json(&mut zip, "timeline.json", &timeline)?;
+        files.push("timeline.json".into());
+    }
+
+    // Tags und Notizen der Bediener zum Lauf und zu den Uploads des PDFs
+    let notes = if redactor.omits(BundlePart::Notes) {
+        Ok(None)
+    } else {
+        sqlx::query_scalar::<_, Value>(
+            "SELECT jsonb_build_object(
+             'run', (SELECT to_jsonb(n) - 'run_id' FROM run_notes n WHERE n.run_id = $1),
+             'uploads', COALESCE((SELECT jsonb_agg(to_jsonb(n) ORDER BY n.upload_id)
+                                    FROM upload_notes n JOIN uploads u ON u.id = n.upload_id
+                                   WHERE u.pdf_id = $2), '[]'::jsonb))",
+        )
+        .bind(run_id)
+        .bind(pdf_id)
+        .fetch_one(pool)
+        .await
+        .map(Some)
+    };
+    match notes {
+        Ok(Some(mut notes)) => {
+            redactor.mask(&mut notes);
+            write_json(&mut zip, "notes.json", &notes)?;
+            files.push("notes.json".into());
+        }
+        Ok(None) => {}
+        Err(e) => warnings.push(format!("notes unavailable: {e}")),
+    }
+
+    let pages = if redactor.omits(BundlePart::PageText) {
+        Vec::new()
+    } else {
+        sqlx::query(
+            "SELECT page_no, text FROM pdf_page_texts WHERE merged_pdf_id = $1 ORDER BY page_no",
+        )
+        .bind(pdf_id)
+        .fetch_all(pool)
+        .await
+        .context("load page texts")?
+    };
+    for row in pages {
+        let page_no: i32 = row.try_get("page_no").unwrap_or_default();
+        let text = redactor.text(row.try_get("text").unwrap_or_default());
+        let name = format!("text/page-{:04}.txt", page_no);
+        zip.start_file(name.as_str(), FileOptions::default())?;
+        zip.write_all(text.as_bytes())?;
+        files.push(name);
+    }
+
+    if opts.evidence && redactor.omits(BundlePart::Evidence) {
+        warnings.push("evidence omitted by the redaction profile".into());
+    } else if opts.evidence {
+        let mut evidence_opts = EvidenceOptions::from_env();
+        if let Some(profile) = &redactor.profile {
+            evidence_opts.skip_keys = profile.fields.clone();
+        }
+        match evidence::write_evidence(pool, run_id, pdf_id, "evidence/", &mut zip, &evidence_opts)
+            .await
+        {
+            Ok(items) => files.extend(items.into_iter().filter_map(|i| i.file)),
+            Err(e) => {
+                warn!(%run_id, "bundle evidence failed: {:#}", e);
+                warnings.push(format!("evidence unavailable: {e}"));
+            }
+        }
+    }
+
+    if opts.include_pdf && redactor.omits(BundlePart::SourcePdf) {
+        warnings.push("source pdf omitted by the redaction profile".into());
+    } else if opts.include_pdf {
+        match sqlx::query_scalar::<_, Option<Vec<u8>>>("SELECT data FROM merged_pdfs WHERE id = $1")
+            .bind(pdf_id)
+            .fetch_optional(pool)
+            .await
+            .context("load merged pdf")?
+        {
+            Some(Some(pdf)) => {
+                zip.start_file("source.pdf", FileOptions::default())?;
+                zip.write_all(&pdf)?;
+                files.push("source.pdf".into());
+            }
+            Some(None) => warnings.push("source pdf is archived; GET /pdf/{id} restores it".into()),
+            None => warnings.push("source pdf not found".into()),
+        }
+    }
+
+    write_json(
+        &mut zip,
+        "manifest.json",
+        &json!({
+            "run_id": run_id,
+            "pdf_id": pdf_id,
+            "pipeline_id": pipeline_id,
+            "files": files,
+            "warnings": warnings,
+            "redaction": redactor.profile.as_ref().map(RedactionProfile::audit),
+        }),
+    )?;
+    Ok(Some(zip.finish()?.into_inner()))
+}
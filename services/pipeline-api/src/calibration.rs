@@ -0,0 +1,559 @@
+//! Recalibration of scoring steps from reviewed runs.
+//!
+//! Reviewers record the outcome of a run with `PUT /runs/{id}/review`
+//! (`approved`/`rejected`, optionally the correct answer per scoring prompt).
+//! `POST /pipelines/{id}/calibration` compares the final scoring results and the
+//! raw batch votes of all reviewed runs with these outcomes and stores a report
+//! with a suggested `weight` and `min_signal` per scoring step. Nothing changes
+//! until `POST /calibration/{id}/apply` writes the suggestions into the step
+//! configs of the pipeline.
+//!
+//! The expected answer of a scoring prompt is the reviewer's per-prompt answer
+//! or, without one, `true` for approved and `false` for rejected runs. The
+//! suggested weight is `2 * agreement - 1` (a prompt that agrees with reviewers
+//! no better than chance ends up at the floor of 0.1); the suggested
+//! `min_signal` is the threshold with the best vote accuracy that still keeps
+//! half of the votes.
+
+use std::collections::{BTreeMap, HashMap};
+
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use shared::dto::{PipelineConfig, PromptType};
+use shared::result_label::ResultLabel;
+use sqlx::{PgPool, Row};
+use uuid::Uuid;
+
+/// Reviewed runs a prompt needs before suggestions are made.
+pub const DEFAULT_MIN_REVIEWS: usize = 10;
+const WEIGHT_FLOOR: f64 = 0.1;
+/// Share of votes a suggested `min_signal` must keep.
+const MIN_VOTE_COVERAGE: f64 = 0.5;
+/// Accuracy gain required before a new `min_signal` is suggested.
+const MIN_ACCURACY_GAIN: f64 = 0.05;
+/// Smallest weight change worth suggesting.
+const MIN_WEIGHT_CHANGE: f64 = 0.05;
+
+/// Creates `run_reviews` and `calibration_reports` (see migration 0035).
+pub async fn ensure_schema(pool: &PgPool) -> Result<()> {
+    sqlx::query(
+        "CREATE TABLE IF NOT EXISTS run_reviews (
+            run_id UUID PRIMARY KEY REFERENCES pipeline_runs(id) ON DELETE CASCADE,
+            verdict TEXT NOT NULL,
+            prompt_verdicts JSONB NOT NULL DEFAULT '{}'::jsonb,
+            reviewer TEXT,
+            note TEXT,
+            created_at TIMESTAMPTZ NOT NULL DEFAULT now(),
+            updated_at TIMESTAMPTZ NOT NULL DEFAULT now()
+        )",
+    )
+    .execute(pool)
+    .await?;
+    sqlx::query(
+        "CREATE TABLE IF NOT EXISTS calibration_reports (
+            id UUID PRIMARY KEY,
+            pipeline_id UUID NOT NULL REFERENCES pipelines(id) ON DELETE CASCADE,
+            report JSONB NOT NULL,
+            created_at TIMESTAMPTZ NOT NULL DEFAULT now(),
+            applied_at TIMESTAMPTZ
+        )",
+    )
+    .execute(pool)
+    .await?;
+    Ok(())
+}
+
+/// Body of `PUT /runs/{id}/review`.
+#[derive(Debug, Deserialize)]
+pub struct ReviewInput {
+    /// `approved` or `rejected`.
+    pub verdict: ResultLabel,
+    /// Correct answer per scoring prompt id, where the reviewer checked it.
+    #[serde(default)]
+    pub prompts: BTreeMap<i32, bool>,
+    pub reviewer: Option<String>,
+    pub note: Option<String>,
+}
+
+/// Stores (or replaces) the review of a run; `false` if the run does not exist.
+pub async fn store_review(pool: &PgPool, run_id: Uuid, input: &ReviewInput) -> Result<bool> {
+    let res = sqlx::query(
+        "INSERT INTO run_reviews (run_id, verdict, prompt_verdicts, reviewer, note)
+         SELECT id, $2, $3, $4, $5 FROM pipeline_runs WHERE id = $1
+         ON CONFLICT (run_id) DO UPDATE
+            SET verdict = EXCLUDED.verdict, prompt_verdicts = EXCLUDED.prompt_verdicts,
+                reviewer = EXCLUDED.reviewer, note = EXCLUDED.note, updated_at = now()",
+    )
+    .bind(run_id)
+    .bind(input.verdict.to_string())
+    .bind(json!(input.prompts))
+    .bind(&input.reviewer)
+    .bind(&input.note)
+    .execute(pool)
+    .await?;
+    Ok(res.rows_affected() == 1)
+}
+
+/// Final or batch result of one scoring prompt in a reviewed run.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Sample {
+    pub run_id: Uuid,
+    pub prompt_id: i32,
+    /// Answer the reviewer expects.
+    pub expected: bool,
+    pub answer: bool,
+    /// Vote signal (`0.6 * strength + 0.4 * confidence`); `None` for final results.
+    pub signal: Option<f64>,
+}
+
+/// Expected answer of `prompt_id`: per-prompt review or the run verdict.
+fn expected_answer(verdict: ResultLabel, prompt_verdicts: &Value, prompt_id: i32) -> Option<bool> {
+    if let Some(answer) = prompt_verdicts
+        .get(prompt_id.to_string())
+        .and_then(Value::as_bool)
+    {
+        return Some(answer);
+    }
+    match verdict {
+        ResultLabel::Approved => Some(true),
+        ResultLabel::Rejected => Some(false),
+        ResultLabel::Review => None,
+    }
+}
+
+/// Scoring results of all reviewed runs of `pipeline_id`.
+pub async fn load_samples(pool: &PgPool, pipeline_id: Uuid) -> Result<Vec<Sample>> {
+    let rows = sqlx::query(
+        "SELECT s.run_id, s.prompt_id, s.is_final, s.result, r.verdict, r.prompt_verdicts
+           FROM run_reviews r
+           JOIN pipeline_runs pr ON pr.id = r.run_id
+           JOIN pipeline_run_steps s ON s.run_id = r.run_id
+          WHERE pr.pipeline_id = $1
+            AND s.prompt_type = 'ScoringPrompt'
+            AND s.prompt_id IS NOT NULL
+            AND s.result IS NOT NULL",
+    )
+    .bind(pipeline_id)
+    .fetch_all(pool)
+    .await?;
+
+    let mut samples = Vec::new();
+    for row in rows {
+        let run_id: Uuid = row.try_get("run_id")?;
+        let prompt_id: i32 = row.try_get("prompt_id")?;
+        let is_final: bool = row.try_get("is_final")?;
+        let result: Value = row.try_get("result")?;
+        let verdict: String = row.try_get("verdict")?;
+        let prompt_verdicts: Value = row.try_get("prompt_verdicts")?;
+        let Ok(verdict) = verdict.parse::<ResultLabel>() else {
+            continue;
+        };
+        let Some(expected) = expected_answer(verdict, &prompt_verdicts, prompt_id) else {
+            continue;
+        };
+        let sample = |answer, signal| Sample {
+            run_id,
+            prompt_id,
+            expected,
+            answer,
+            signal,
+        };
+        if is_final {
+            if let Some(answer) = result.get("result").and_then(Value::as_bool) {
+                samples.push(sample(answer, None));
+            }
+            continue;
+        }
+        let votes = result
+            .get("scores")
+            .and_then(Value::as_array)
+            .map(Vec::as_slice)
+            .unwrap_or_default();
+        for vote in votes {
+            if let Some((answer, signal)) = vote_signal(vote) {
+                samples.push(sample(answer, Some(signal)));
+            }
+        }
+    }
+    Ok(samples)
+}
+
+/// Answer and signal of one batch vote, computed like the runner; `unsure` is skipped.
+fn vote_signal(vote: &Value) -> Option<(bool, f64)> {
+    let label = vote
+        .get("vote")
+        .and_then(Value::as_str)
+        .unwrap_or_default()
+        .to_ascii_lowercase();
+    let answer = match label.as_str() {
+        "unsure" => return None,
+        "yes" => true,
+        "no" => false,
+        _ => vote.get("result").and_then(Value::as_bool)?,
+    };
+    let strength = vote.get("strength").and_then(Value::as_f64).unwrap_or(1.0);
+    let confidence = vote
+        .get("confidence")
+        .and_then(Value::as_f64)
+        .unwrap_or(0.5);
+    Some((answer, (0.6 * strength + 0.4 * confidence).clamp(0.0, 1.0)))
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+/// Analysis and suggestion for one scoring step.
+pub struct PromptCalibration {
+    pub step_id: Uuid,
+    pub prompt_id: i32,
+    /// Reviewed runs with a final result of this prompt.
+    pub reviewed_runs: usize,
+    /// Share of these runs whose final result matched the reviewer.
+    pub agreement: Option<f64>,
+    pub votes: usize,
+    /// Share of batch votes above the current `min_signal` matching the reviewer.
+    pub vote_accuracy: Option<f64>,
+    pub current_weight: f64,
+    pub suggested_weight: Option<f64>,
+    pub current_min_signal: f64,
+    pub suggested_min_signal: Option<f64>,
+    pub reason: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+/// Stored result of `POST /pipelines/{id}/calibration`.
+pub struct CalibrationReport {
+    pub pipeline_id: Uuid,
+    pub reviewed_runs: usize,
+    pub min_reviews: usize,
+    pub prompts: Vec<PromptCalibration>,
+}
+
+impl CalibrationReport {
+    pub fn has_suggestions(&self) -> bool {
+        self.prompts
+            .iter()
+            .any(|p| p.suggested_weight.is_some() || p.suggested_min_signal.is_some())
+    }
+}
+
+fn config_f64(config: Option<&Value>, key: &str, default: f64) -> f64 {
+    config
+        .and_then(|c| c.get(key))
+        .and_then(Value::as_f64)
+        .unwrap_or(default)
+}
+
+fn round2(v: f64) -> f64 {
+    (v * 100.0).round() / 100.0
+}
+
+/// Accuracy of the votes with a signal of at least `threshold` and their share.
+fn accuracy_at(votes: &[(f64, bool)], threshold: f64) -> Option<(f64, f64)> {
+    let kept: Vec<bool> = votes
+        .iter()
+        .filter(|(signal, _)| *signal >= threshold)
+        .map(|(_, correct)| *correct)
+        .collect();
+    if kept.is_empty() {
+        return None;
+    }
+    let correct = kept.iter().filter(|c| **c).count() as f64;
+    Some((
+        correct / kept.len() as f64,
+        kept.len() as f64 / votes.len() as f64,
+    ))
+}
+
+/// Threshold on the 0.05 grid with the best accuracy at sufficient coverage,
+/// if it beats `current` by [`MIN_ACCURACY_GAIN`].
+fn suggest_min_signal(votes: &[(f64, bool)], current: f64) -> Option<f64> {
+    let (current_accuracy, _) = accuracy_at(votes, current)?;
+    let mut best: Option<(f64, f64)> = None;
+    for step in 0..=18 {
+        let threshold = round2(step as f64 * 0.05);
+        let Some((accuracy, coverage)) = accuracy_at(votes, threshold) else {
+            continue;
+        };
+        if coverage < MIN_VOTE_COVERAGE {
+            continue;
+        }
+        if best.is_none_or(|(_, best_accuracy)| accuracy > best_accuracy + 1e-9) {
+            best = Some((threshold, accuracy));
+        }
+    }
+    let (threshold, accuracy) = best?;
+    (accuracy >= current_accuracy + MIN_ACCURACY_GAIN && (threshold - current).abs() > 1e-9)
+        .then_some(round2(threshold))
+}
+
+/// Compares the samples with the active scoring steps of `cfg`.
+pub fn analyze(
+    pipeline_id: Uuid,
+    cfg: &PipelineConfig,
+    samples: &[Sample],
+    min_reviews: usize,
+) -> CalibrationReport {
+    let mut by_prompt: HashMap<i32, Vec<&Sample>> = HashMap::new();
+    for sample in samples {
+        by_prompt.entry(sample.prompt_id).or_default().push(sample);
+    }
+    let mut runs: Vec<Uuid> = samples.iter().map(|s| s.run_id).collect();
+    runs.sort();
+    runs.dedup();
+
+    let mut prompts = Vec::new();
+    for step in cfg
+        .steps
+        .iter()
+        .filter(|s| s.active && s.step_type == PromptType::ScoringPrompt)
+    {
+        let current_weight = config_f64(step.config.as_ref(), "weight", 1.0);
+        let current_min_signal = config_f64(step.config.as_ref(), "min_signal", 0.0);
+        let own = by_prompt
+            .get(&step.prompt_id)
+            .map(Vec::as_slice)
+            .unwrap_or_default();
+        let finals: Vec<bool> = own
+            .iter()
+            .filter(|s| s.signal.is_none())
+            .map(|s| s.answer == s.expected)
+            .collect();
+        let votes: Vec<(f64, bool)> = own
+            .iter()
+            .filter_map(|s| Some((s.signal?, s.answer == s.expected)))
+            .collect();
+        let agreement = (!finals.is_empty())
+            .then(|| finals.iter().filter(|a| **a).count() as f64 / finals.len() as f64);
+        let vote_accuracy = accuracy_at(&votes, current_min_signal).map(|(a, _)| round2(a));
+
+        let mut calibration = PromptCalibration {
+            step_id: step.id,
+            prompt_id: step.prompt_id,
+            reviewed_runs: finals.len(),
+            agreement: agreement.map(round2),
+            votes: votes.len(),
+            vote_accuracy,
+            current_weight,
+            suggested_weight: None,
+            current_min_signal,
+            suggested_min_signal: None,
+            reason: String::new(),
+        };
+        let Some(agreement) = agreement.filter(|_| finals.len() >= min_reviews) else {
+            calibration.reason =
+                format!("only {} reviewed runs, {min_reviews} needed", finals.len());
+            prompts.push(calibration);
+            continue;
+        };
+
+        let weight = round2((2.0 * agreement - 1.0).clamp(WEIGHT_FLOOR, 1.0));
+        if (weight - current_weight).abs() >= MIN_WEIGHT_CHANGE {
+            calibration.suggested_weight = Some(weight);
+        }
+        if votes.len() >= min_reviews {
+            calibration.suggested_min_signal = suggest_min_signal(&votes, current_min_signal);
+        }
+        let mut reason = format!(
+            "agrees with reviewers in {:.0}% of {} runs",
+            agreement * 100.0,
+            finals.len()
+        );
+        if let Some(threshold) = calibration.suggested_min_signal {
+            let (accuracy, coverage) = accuracy_at(&votes, threshold).unwrap_or_default();
+            reason.push_str(&format!(
+                "; votes with signal >= {threshold:.2} are {:.0}% correct ({:.0}% of votes kept)",
+                accuracy * 100.0,
+                coverage * 100.0
+            ));
+        }
+        calibration.reason = reason;
+        prompts.push(calibration);
+    }
+
+    CalibrationReport {
+        pipeline_id,
+        reviewed_runs: runs.len(),
+        min_reviews,
+        prompts,
+    }
+}
+
+/// Writes the suggestions of `report` into the step configs; returns the changed steps.
+pub fn apply(cfg: &mut PipelineConfig, report: &CalibrationReport) -> usize {
+    let mut changed = 0;
+    for suggestion in &report.prompts {
+        if suggestion.suggested_weight.is_none() && suggestion.suggested_min_signal.is_none() {
+            continue;
+        }
+        let Some(step) = cfg.steps.iter_mut().find(|s| s.id == suggestion.step_id) else {
+            continue;
+        };
+        // Schritte aus der Step-Library ändern sich nur über die Library
+        if step.library.is_some() {
+            continue;
+        }
+        let mut config = match step.config.take() {
+            Some(Value::Object(map)) => map,
+            _ => serde_json::Map::new(),
+        };
+        if let Some(weight) = suggestion.suggested_weight {
+            config.insert("weight".into(), json!(weight));
+        }
+        if let Some(min_signal) = suggestion.suggested_min_signal {
+            config.insert("min_signal".into(), json!(min_signal));
+        }
+        step.config = Some(Value::Object(config));
+        changed += 1;
+    }
+    changed
+}
+
+/// Stores a report and returns its id.
+pub async fn store_report(pool: &PgPool, report: &CalibrationReport) -> Result<Uuid> {
+    let id = Uuid::new_v4();
+    sqlx::query("INSERT INTO calibration_reports (id, pipeline_id, report) VALUES ($1, $2, $3)")
+        .bind(id)
+        .bind(report.pipeline_id)
+        .bind(serde_json::to_value(report)?)
+        .execute(pool)
+        .await?;
+    Ok(id)
+}
+
+/// Stored report: `(report, created_at, applied_at)` with RFC 3339 timestamps.
+pub async fn load_report(
+    pool: &PgPool,
+    id: Uuid,
+) -> Result<Option<(CalibrationReport, String, Option<String>)>> {
+    let row = sqlx::query(
+        r#"SELECT report,
+                  to_char(created_at AT TIME ZONE 'UTC', 'YYYY-MM-DD"T"HH24:MI:SS"Z"') AS created_at,
+                  to_char(applied_at AT TIME ZONE 'UTC', 'YYYY-MM-DD"T"HH24:MI:SS"Z"') AS applied_at
+             FROM calibration_reports WHERE id = $1"#,
+    )
+    .bind(id)
+    .fetch_optional(pool)
+    .await?;
+    let Some(row) = row else {
+        return Ok(None);
+    };
+    let report: Value = row.try_get("report")?;
+    Ok(Some((
+        serde_json::from_value(report)?,
+        row.try_get("created_at")?,
+        row.try_get("applied_at")?,
+    )))
+}
+
+/// Marks a report as applied; `false` if it already was.
+pub async fn mark_applied(pool: &PgPool, id: Uuid) -> Result<bool> {
+    let res = sqlx::query(
+        "UPDATE calibration_reports SET applied_at = now() WHERE id = $1 AND applied_at IS NULL",
+    )
+    .bind(id)
+    .execute(pool)
+    .await?;
+    Ok(res.rows_affected() == 1)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use shared::dto::PipelineStep;
+
+    fn scoring_step(prompt_id: i32, config: Option<Value>) -> PipelineStep {
+        PipelineStep {
+            id: Uuid::new_v4(),
+            step_type: PromptType::ScoringPrompt,
+            prompt_id,
+            route: None,
+            yes_key: None,
+            no_key: None,
+            active: true,
+            config,
+            library: None,
+        }
+    }
+
+    #[test]
+    fn expected_answer_prefers_prompt_review() {
+        let prompts = json!({ "7": false });
+        assert_eq!(
+            expected_answer(ResultLabel::Approved, &prompts, 7),
+            Some(false)
+        );
+        assert_eq!(
+            expected_answer(ResultLabel::Approved, &prompts, 8),
+            Some(true)
+        );
+        assert_eq!(
+            expected_answer(ResultLabel::Rejected, &json!({}), 8),
+            Some(false)
+        );
+        assert_eq!(expected_answer(ResultLabel::Review, &json!({}), 8), None);
+    }
+
+    #[test]
+    fn disagreeing_prompt_is_down_weighted_and_thresholded() {
+        let mut cfg = PipelineConfig {
+            name: "p".into(),
+            steps: vec![
+                scoring_step(1, Some(json!({ "min_signal": 0.0 }))),
+                scoring_step(2, None),
+                scoring_step(3, None),
+            ],
+            output_mapping: Vec::new(),
+            label_rules: None,
+            run_on_update: false,
+            redaction_profiles: Vec::new(),
+        };
+        let mut samples = Vec::new();
+        for i in 0..12 {
+            let run_id = Uuid::new_v4();
+            let sample = |prompt_id, answer, signal| Sample {
+                run_id,
+                prompt_id,
+                expected: true,
+                answer,
+                signal,
+            };
+            // Prompt 1: 9 von 12 richtig, falsche Stimmen nur mit schwachem Signal
+            samples.push(sample(1, i < 9, None));
+            samples.push(sample(1, true, Some(0.9)));
+            samples.push(sample(1, false, Some(0.3)));
+            // Prompt 2: immer richtig
+            samples.push(sample(2, true, None));
+            samples.push(sample(2, true, Some(0.8)));
+        }
+        // Prompt 3: zu wenige Reviews
+        samples.push(Sample {
+            run_id: Uuid::new_v4(),
+            prompt_id: 3,
+            expected: false,
+            answer: true,
+            signal: None,
+        });
+
+        let report = analyze(Uuid::new_v4(), &cfg, &samples, 10);
+        assert_eq!(report.reviewed_runs, 13);
+        let [p1, p2, p3] = &report.prompts[..] else {
+            panic!("three scoring steps expected");
+        };
+        assert_eq!(p1.agreement, Some(0.75));
+        assert_eq!(p1.suggested_weight, Some(0.5));
+        assert_eq!(p1.vote_accuracy, Some(0.5));
+        assert_eq!(p1.suggested_min_signal, Some(0.35));
+        assert_eq!(p2.suggested_weight, None);
+        assert_eq!(p2.suggested_min_signal, None);
+        assert_eq!(p3.reviewed_runs, 1);
+        assert!(p3.reason.contains("10 needed"));
+        assert!(report.has_suggestions());
+
+        assert_eq!(apply(&mut cfg, &report), 1);
+        assert_eq!(
+            cfg.steps[0].config,
+            Some(json!({ "min_signal": 0.35, "weight": 0.5 }))
+        );
+        assert_eq!(cfg.steps[1].config, None);
+    }
+}
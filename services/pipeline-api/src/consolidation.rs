@@ -301,9 +301,9 @@ fn select_extraction_winner<'a>(
         let r = b.sample.0;
         let value = b.sample.1.clone();
         let (page, quote, bbox) = match r.source.as_ref() {
-            Some(TextPosition { page, quote, bbox }) => {
-                (Some(*page as u32), quote.clone(), Some(*bbox))
-            }
+            Some(TextPosition {
+                page, quote, bbox, ..
+            }) => (Some(*page as u32), quote.clone(), Some(*bbox)),
             _ => (None, None, None),
         };
         return Some(CanonicalField {
@@ -0,0 +1,355 @@
+//! Dry run of a pipeline on an upload (`POST /pipelines/{id}/estimate`).
+//!
+//! The stored pages are packed exactly like the runner would pack them
+//! ([`shared::packing::batches_for_step`]), without calling the model. Prompt
+//! tokens are estimated from the characters sent per request, completion
+//! tokens and wall-clock time per call from the last completed runs of the
+//! pipeline (or defaults when there are none). Costs use the prices of the
+//! usage report (`USAGE_PRICE_PROMPT_PER_1K`, `USAGE_PRICE_COMPLETION_PER_1K`).
+
+use std::collections::HashMap;
+
+use anyhow::Result;
+use serde::Serialize;
+use shared::dto::{PipelineConfig, PromptType};
+use shared::packing::{batches_for_step, BatchLayout};
+use shared::runner_settings;
+use sqlx::{PgPool, Row};
+use uuid::Uuid;
+
+/// Rough characters per token for German/English text.
+const CHARS_PER_TOKEN: f64 = 4.0;
+/// System prompt, JSON instructions and schema added to every request.
+const REQUEST_OVERHEAD_TOKENS: u64 = 150;
+/// Completed runs used to calibrate completion tokens and duration.
+const HISTORY_RUNS: i64 = 20;
+
+#[derive(Debug, Clone, Serialize)]
+/// Basis of an estimate, returned with it so operators can judge it.
+pub struct Assumptions {
+    pub layout: BatchLayout,
+    pub max_parallel: usize,
+    pub completion_tokens_per_call: f64,
+    /// Wall-clock seconds per model call, parallelism included.
+    pub seconds_per_call: f64,
+    pub prompt_price_per_1k: f64,
+    pub completion_price_per_1k: f64,
+    /// Number of completed runs the per-call values are taken from (0 = defaults).
+    pub calibrated_from_runs: i64,
+    pub warn_calls: u64,
+    pub warn_cost: f64,
+}
+
+impl Assumptions {
+    /// Runner env defaults with the stored runtime overrides and env prices.
+    pub async fn load(pool: &PgPool) -> Result<Self> {
+        let env = |key: &str, default: f64| {
+            std::env::var(key)
+                .ok()
+                .and_then(|v| v.trim().parse::<f64>().ok())
+                .unwrap_or(default)
+        };
+        let overrides = runner_settings::fetch(pool).await?;
+        let mut layout = BatchLayout::from_env();
+        if let Some(v) = overrides.page_batch_size {
+            layout.page_batch_size = v;
+        }
+        if let Some(v) = overrides.max_chars {
+            layout.max_chars = v;
+        }
+        let max_parallel = overrides
+            .max_parallel
+            .unwrap_or_else(|| env("PIPELINE_MAX_PARALLEL", 3.0) as usize)
+            .max(1);
+        Ok(Self {
+            layout,
+            max_parallel,
+            completion_tokens_per_call: env("ESTIMATE_COMPLETION_TOKENS", 250.0),
+            seconds_per_call: env("ESTIMATE_CALL_SECONDS", 6.0) / max_parallel as f64,
+            prompt_price_per_1k: env("USAGE_PRICE_PROMPT_PER_1K", 0.0025),
+            completion_price_per_1k: env("USAGE_PRICE_COMPLETION_PER_1K", 0.01),
+            calibrated_from_runs: 0,
+            warn_calls: env("ESTIMATE_WARN_CALLS", 500.0) as u64,
+            warn_cost: env("ESTIMATE_WARN_COST", 10.0),
+        })
+    }
+
+    /// Replaces the per-call defaults with the averages of recent completed runs.
+    pub async fn calibrate(&mut self, pool: &PgPool, pipeline_id: Uuid) -> Result<()> {
+        let row = sqlx::query(
+            "SELECT COUNT(*) AS runs,
+                    COALESCE(SUM(completion_tokens), 0)::BIGINT AS completion_tokens,
+                    COALESCE(SUM(openai_calls), 0)::BIGINT AS calls,
+                    COALESCE(SUM(EXTRACT(EPOCH FROM finished_at - started_at)), 0)::FLOAT8 AS seconds
+               FROM (SELECT completion_tokens, openai_calls, started_at, finished_at
+                       FROM pipeline_runs
+                      WHERE pipeline_id = $1 AND status = 'completed' AND openai_calls > 0
+                        AND started_at IS NOT NULL AND finished_at IS NOT NULL
+                      ORDER BY finished_at DESC
+                      LIMIT $2) recent",
+        )
+        .bind(pipeline_id)
+        .bind(HISTORY_RUNS)
+        .fetch_one(pool)
+        .await?;
+        let runs: i64 = row.try_get("runs")?;
+        let calls: i64 = row.try_get("calls")?;
+        if runs == 0 || calls == 0 {
+            return Ok(());
+        }
+        let completion: i64 = row.try_get("completion_tokens")?;
+        let seconds: f64 = row.try_get("seconds")?;
+        // Ältere Runs ohne Token-Erfassung behalten den Default
+        if completion > 0 {
+            self.completion_tokens_per_call = completion as f64 / calls as f64;
+        }
+        self.seconds_per_call = seconds / calls as f64;
+        self.calibrated_from_runs = runs;
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+/// Expected model usage of one active step.
+pub struct StepEstimate {
+    pub step_id: Uuid,
+    pub prompt_id: i32,
+    pub step_type: PromptType,
+    /// Step only runs on a route chosen by an earlier decision.
+    pub conditional: bool,
+    pub batches: usize,
+    pub prompt_tokens: u64,
+    pub completion_tokens: u64,
+    pub cost: f64,
+}
+
+#[derive(Debug, Clone, Serialize)]
+/// Projected usage of a whole run; conditional steps are counted as if they ran.
+pub struct RunEstimate {
+    pub pages: usize,
+    pub chars: usize,
+    /// Pages larger than `max_chars` that are split across batches.
+    pub split_pages: usize,
+    pub steps: Vec<StepEstimate>,
+    pub openai_calls: usize,
+    pub prompt_tokens: u64,
+    pub completion_tokens: u64,
+    pub estimated_cost: f64,
+    pub estimated_duration_secs: u64,
+    pub warnings: Vec<String>,
+    pub assumptions: Assumptions,
+}
+
+fn tokens(chars: usize) -> u64 {
+    (chars as f64 / CHARS_PER_TOKEN).ceil() as u64
+}
+
+fn round_cost(cost: f64) -> f64 {
+    (cost * 1000.0).round() / 1000.0
+}
+
+/// Estimates a run of `cfg` on `pages`; `prompt_chars` maps prompt ids to their text length.
+pub fn estimate(
+    cfg: &PipelineConfig,
+    pages: &[(i32, String)],
+    prompt_chars: &HashMap<i32, usize>,
+    assumptions: Assumptions,
+) -> RunEstimate {
+    let a = &assumptions;
+    let mut steps = Vec::new();
+    let mut split_pages = 0;
+    for step in cfg.steps.iter().filter(|s| s.active) {
+        let batches = batches_for_step(&step.step_type, pages, &a.layout);
+        if step.step_type == PromptType::ExtractionPrompt {
+            split_pages = batches
+                .iter()
+                .filter(|b| b.slices[0].part == Some(1))
+                .count();
+        }
+        let prompt_len = prompt_chars.get(&step.prompt_id).copied().unwrap_or(0);
+        let prompt_tokens: u64 = batches
+            .iter()
+            .map(|b| tokens(b.char_count() + prompt_len) + REQUEST_OVERHEAD_TOKENS)
+            .sum();
+        let completion_tokens = (batches.len() as f64 * a.completion_tokens_per_call) as u64;
+        let cost = prompt_tokens as f64 / 1000.0 * a.prompt_price_per_1k
+            + completion_tokens as f64 / 1000.0 * a.completion_price_per_1k;
+        steps.push(StepEstimate {
+            step_id: step.id,
+            prompt_id: step.prompt_id,
+            step_type: step.step_type.clone(),
+            conditional: step.route.as_deref().is_some_and(|r| r != "ROOT"),
+            batches: batches.len(),
+            prompt_tokens,
+            completion_tokens,
+            cost: round_cost(cost),
+        });
+    }
+
+    let openai_calls: usize = steps.iter().map(|s| s.batches).sum();
+    let prompt_tokens = steps.iter().map(|s| s.prompt_tokens).sum();
+    let completion_tokens = steps.iter().map(|s| s.completion_tokens).sum();
+    let estimated_cost = round_cost(steps.iter().map(|s| s.cost).sum());
+
+    let mut warnings = Vec::new();
+    if steps.is_empty() {
+        warnings.push("pipeline has no active steps".to_string());
+    }
+    if openai_calls as u64 > a.warn_calls {
+        warnings.push(format!(
+            "{openai_calls} model calls exceed ESTIMATE_WARN_CALLS ({})",
+            a.warn_calls
+        ));
+    }
+    if estimated_cost > a.warn_cost {
+        warnings.push(format!(
+            "estimated cost {estimated_cost:.2} exceeds ESTIMATE_WARN_COST ({:.2})",
+            a.warn_cost
+        ));
+    }
+    if split_pages > 0 {
+        warnings.push(format!(
+            "{split_pages} pages exceed max_chars ({}) and are split",
+            a.layout.max_chars
+        ));
+    }
+    let conditional = steps.iter().filter(|s| s.conditional).count();
+    if conditional > 0 {
+        warnings.push(format!(
+            "{conditional} route-dependent steps are included; the actual run may skip them"
+        ));
+    }
+
+    RunEstimate {
+        pages: pages.len(),
+        chars: pages.iter().map(|(_, t)| t.len()).sum(),
+        split_pages,
+        openai_calls,
+        prompt_tokens,
+        completion_tokens,
+        estimated_cost,
+        estimated_duration_secs: (openai_calls as f64 * a.seconds_per_call).ceil() as u64,
+        steps,
+        warnings,
+        assumptions,
+    }
+}
+
+/// Stored pages of `pdf_id` in page order, as the runner loads them.
+pub async fn load_pages(pool: &PgPool, pdf_id: i32) -> Result<Vec<(i32, String)>> {
+    Ok(sqlx::query_as::<_, (i32, String)>(
+        "SELECT page_no, text FROM pdf_page_texts WHERE merged_pdf_id = $1 ORDER BY page_no",
+    )
+    .bind(pdf_id)
+    .fetch_all(pool)
+    .await?)
+}
+
+/// Text length of the prompts used by `cfg`.
+pub async fn load_prompt_chars(pool: &PgPool, cfg: &PipelineConfig) -> Result<HashMap<i32, usize>> {
+    let ids: Vec<i32> = cfg.steps.iter().map(|s| s.prompt_id).collect();
+    let rows =
+        sqlx::query_as::<_, (i32, String)>("SELECT id, text FROM prompts WHERE id = ANY($1)")
+            .bind(&ids)
+            .fetch_all(pool)
+            .await?;
+    Ok(rows
+        .into_iter()
+        .map(|(id, text)| (id, text.len()))
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use shared::dto::PipelineStep;
+
+    fn step(step_type: PromptType, prompt_id: i32, route: Option<&str>) -> PipelineStep {
+        PipelineStep {
+            id: Uuid::new_v4(),
+            step_type,
+            prompt_id,
+            route: route.map(str::to_string),
+            yes_key: None,
+            no_key: None,
+            active: true,
+            config: None,
+            library: None,
+        }
+    }
+
+    fn assumptions() -> Assumptions {
+        Assumptions {
+            layout: BatchLayout {
+                page_batch_size: 5,
+                max_chars: 1_000,
+                min_pages_for_batching: 4,
+                overlap_pages: 0,
+            },
+            max_parallel: 2,
+            completion_tokens_per_call: 100.0,
+            seconds_per_call: 2.0,
+            prompt_price_per_1k: 1.0,
+            completion_price_per_1k: 2.0,
+            calibrated_from_runs: 0,
+            warn_calls: 20,
+            warn_cost: 100.0,
+        }
+    }
+
+    #[test]
+    fn counts_batches_like_the_runner() {
+        let cfg = PipelineConfig {
+            name: "p".into(),
+            steps: vec![
+                step(PromptType::ExtractionPrompt, 1, None),
+                step(PromptType::ScoringPrompt, 2, None),
+                step(PromptType::DecisionPrompt, 3, Some("ROOT")),
+            ],
+            output_mapping: Vec::new(),
+            label_rules: None,
+            run_on_update: false,
+            redaction_profiles: Vec::new(),
+        };
+        // 12 Seiten à 380 Zeichen: 12 Extraktionen, 6 Scoring-Batches (2 Seiten
+        // passen in 1.000 Zeichen), Entscheidung passt nicht in einen Batch
+        let pages: Vec<(i32, String)> = (1..=12).map(|p| (p, "wort ".repeat(76))).collect();
+        let prompts = HashMap::from([(1, 400), (2, 400), (3, 400)]);
+
+        let est = estimate(&cfg, &pages, &prompts, assumptions());
+        let batches: Vec<usize> = est.steps.iter().map(|s| s.batches).collect();
+        assert_eq!(batches, vec![12, 6, 6]);
+        assert_eq!(est.openai_calls, 24);
+        assert_eq!(est.completion_tokens, 2_400);
+        assert_eq!(est.estimated_duration_secs, 48);
+        assert!(est.steps.iter().all(|s| !s.conditional));
+        assert!(est.warnings[0].contains("ESTIMATE_WARN_CALLS"));
+        let step_sum: f64 = est.steps.iter().map(|s| s.cost).sum();
+        assert!((est.estimated_cost - step_sum).abs() < 0.01);
+    }
+
+    #[test]
+    fn flags_split_pages_and_conditional_steps() {
+        let mut inactive = step(PromptType::ScoringPrompt, 2, None);
+        inactive.active = false;
+        let cfg = PipelineConfig {
+            name: "p".into(),
+            steps: vec![
+                step(PromptType::ExtractionPrompt, 1, None),
+                inactive,
+                step(PromptType::DecisionPrompt, 3, Some("yes")),
+            ],
+            output_mapping: Vec::new(),
+            label_rules: None,
+            run_on_update: false,
+            redaction_profiles: Vec::new(),
+        };
+        let pages = vec![(1, "Ein Satz. ".repeat(250)), (2, "kurz".to_string())];
+
+        let est = estimate(&cfg, &pages, &HashMap::new(), assumptions());
+        assert_eq!(est.steps.len(), 2);
+        assert_eq!(est.split_pages, 1);
+        assert!(est.steps[1].conditional);
+        assert_eq!(est.warnings.len(), 2);
+    }
+}
@@ -0,0 +1,440 @@
+//! Evidence export for final scoring results: cropped page images per cited quote.
+//!
+//! The quote of every `support` entry is located in the stored page layout
+//! (`pdf_texts.layout_json`) and the matching region is rendered with
+//! `pdftoppm`, so reviewers can verify a decision without access to the app.
+
+use anyhow::{anyhow, Context, Result};
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use sqlx::{PgPool, Row};
+use std::io::{Cursor, Seek, Write};
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+use tokio::process::Command;
+use tracing::warn;
+use uuid::Uuid;
+use zip::write::FileOptions;
+use zip::{CompressionMethod, ZipWriter};
+
+/// Settings for the crop renderer, read from `EVIDENCE_*` environment variables.
+#[derive(Debug, Clone)]
+pub struct EvidenceOptions {
+    /// Extra pixels around the located quote.
+    pub padding: i32,
+    /// Maximum number of crops per final score.
+    pub max_per_score: usize,
+    pub render_timeout: Duration,
+    /// Final keys whose crops are left out (redacted bundle fields).
+    pub skip_keys: Vec<String>,
+}
+
+impl EvidenceOptions {
+    pub fn from_env() -> Self {
+        let read = |key: &str, default: u64| {
+            std::env::var(key)
+                .ok()
+                .and_then(|v| v.trim().parse::<u64>().ok())
+                .unwrap_or(default)
+        };
+        Self {
+            padding: read("EVIDENCE_CROP_PADDING", 12) as i32,
+            max_per_score: read("EVIDENCE_MAX_PER_SCORE", 3) as usize,
+            render_timeout: Duration::from_secs(read("EVIDENCE_RENDER_TIMEOUT_SECS", 30)),
+            skip_keys: Vec::new(),
+        }
+    }
+}
+
+/// Manifest entry describing one exported crop.
+#[derive(Debug, Serialize)]
+pub struct EvidenceItem {
+    pub final_key: String,
+    pub prompt_id: Option<i32>,
+    pub label: Option<String>,
+    pub explanation: Option<String>,
+    /// Zero-based page number as stored in `pdf_texts`.
+    pub page: u32,
+    pub quote: Option<String>,
+    /// Crop region in layout coordinates; `None` when the whole page was rendered.
+    pub bbox: Option<[i32; 4]>,
+    pub file: Option<String>,
+    pub error: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct LayoutWord {
+    bbox: [i32; 4],
+    text: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct PageLayout {
+    page_width: i32,
+    page_height: i32,
+    #[serde(default)]
+    words: Vec<LayoutWord>,
+}
+
+/// A cited source (`page`, `quote`, `bbox`) taken from a final scoring result.
+#[derive(Debug, Clone, PartialEq)]
+struct SupportSource {
+    page: u32,
+    quote: Option<String>,
+    bbox: Option<[f32; 4]>,
+}
+
+/// Reads the `support` field of a final scoring step (array or single object).
+fn support_sources(result: &Value) -> Vec<SupportSource> {
+    let items: Vec<&Value> = match result.get("support") {
+        Some(Value::Array(items)) => items.iter().collect(),
+        Some(obj @ Value::Object(_)) => vec![obj],
+        _ => Vec::new(),
+    };
+    items
+        .into_iter()
+        .filter_map(|src| {
+            let page = src.get("page")?.as_u64()? as u32;
+            let quote = src
+                .get("quote")
+                .and_then(Value::as_str)
+                .map(str::trim)
+                .filter(|q| !q.is_empty())
+                .map(str::to_string);
+            let bbox = src
+                .get("bbox")
+                .and_then(|b| serde_json::from_value::<[f32; 4]>(b.clone()).ok())
+                .filter(|b| b.iter().any(|v| *v != 0.0));
+            Some(SupportSource { page, quote, bbox })
+        })
+        .collect()
+}
+
+/// Final key as file name part: only `[A-Za-z0-9_-]`, so a key like
+/// `../x` cannot leave the archive folder.
+fn file_stem(key: &str) -> String {
+    key.chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() || c == '_' || c == '-' {
+                c
+            } else {
+                '_'
+            }
+        })
+        .collect()
+}
+
+fn normalize_token(raw: &str) -> String {
+    raw.chars()
+        .filter(|c| c.is_alphanumeric())
+        .flat_map(char::to_lowercase)
+        .collect()
+}
+
+/// Finds the longest run of layout words matching the beginning of `quote` and
+/// returns the union of their bounding boxes.
+fn locate_quote(words: &[LayoutWord], quote: &str) -> Option<[i32; 4]> {
+    let needle: Vec<String> = quote
+        .split_whitespace()
+        .map(normalize_token)
+        .filter(|t| !t.is_empty())
+        .collect();
+    // Satzzeichen-only Wörter ignorieren, Index für die BBox merken
+    let tokens: Vec<(usize, String)> = words
+        .iter()
+        .enumerate()
+        .map(|(i, w)| (i, normalize_token(&w.text)))
+        .filter(|(_, t)| !t.is_empty())
+        .collect();
+
+    let mut best: Option<(usize, usize)> = None;
+    for start in 0..tokens.len() {
+        let len = tokens[start..]
+            .iter()
+            .zip(&needle)
+            .take_while(|((_, a), b)| a == *b)
+            .count();
+        if len > best.map(|(_, l)| l).unwrap_or(0) {
+            best = Some((start, len));
+        }
+    }
+
+    let (start, len) = best?;
+    if len < needle.len().min(2) {
+        return None;
+    }
+    tokens[start..start + len]
+        .iter()
+        .map(|(i, _)| words[*i].bbox)
+        .reduce(|a, b| {
+            [
+                a[0].min(b[0]),
+                a[1].min(b[1]),
+                a[2].max(b[2]),
+                a[3].max(b[3]),
+            ]
+        })
+}
+
+/// Renders one page (optionally cropped to `bbox`) as PNG, scaled to the layout size.
+async fn render_png(
+    pdf_path: &Path,
+    page: u32,
+    layout: Option<&PageLayout>,
+    bbox: Option<[i32; 4]>,
+    opts: &EvidenceOptions,
+) -> Result<Vec<u8>> {
+    let prefix = std::env::temp_dir().join(format!("evidence-{}", Uuid::new_v4()));
+    let mut cmd = Command::new("pdftoppm");
+    cmd.arg("-f")
+        .arg((page + 1).to_string())
+        .arg("-l")
+        .arg((page + 1).to_string())
+        .arg("-png")
+        .arg("-singlefile");
+    if let Some(layout) = layout {
+        // Auf Layout-Koordinaten skalieren, damit die BBoxes direkt als Crop passen
+        cmd.arg("-scale-to-x")
+            .arg(layout.page_width.to_string())
+            .arg("-scale-to-y")
+            .arg(layout.page_height.to_string());
+        if let Some([x0, y0, x1, y1]) = bbox {
+            let x = (x0 - opts.padding).max(0);
+            let y = (y0 - opts.padding).max(0);
+            let w = (x1 + opts.padding).min(layout.page_width) - x;
+            let h = (y1 + opts.padding).min(layout.page_height) - y;
+            cmd.arg("-x")
+                .arg(x.to_string())
+                .arg("-y")
+                .arg(y.to_string())
+                .arg("-W")
+                .arg(w.max(1).to_string())
+                .arg("-H")
+                .arg(h.max(1).to_string());
+        }
+    } else {
+        cmd.arg("-r").arg("100");
+    }
+    cmd.arg(pdf_path).arg(&prefix);
+
+    let output = tokio::time::timeout(opts.render_timeout, cmd.output())
+        .await
+        .context("timeout running pdftoppm")?
+        .context("spawn pdftoppm")?;
+    let png_path = prefix.with_extension("png");
+    if !output.status.success() {
+        let _ = tokio::fs::remove_file(&png_path).await;
+        return Err(anyhow!(
+            "pdftoppm exit status on page {}: {}",
+            page + 1,
+            String::from_utf8_lossy(&output.stderr).trim()
+        ));
+    }
+    let bytes = tokio::fs::read(&png_path)
+        .await
+        .context("read rendered png")?;
+    let _ = tokio::fs::remove_file(&png_path).await;
+    Ok(bytes)
+}
+
+struct TempPdf(PathBuf);
+
+impl Drop for TempPdf {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_file(&self.0);
+    }
+}
+
+/// Writes crops and a `manifest.json` for all final scoring results of a run
+/// into `zip` below `prefix` (e.g. `evidence/`). Returns the manifest entries.
+pub async fn write_evidence<W: Write + Seek>(
+    pool: &PgPool,
+    run_id: Uuid,
+    pdf_id: i32,
+    prefix: &str,
+    zip: &mut ZipWriter<W>,
+    opts: &EvidenceOptions,
+) -> Result<Vec<EvidenceItem>> {
+    let finals = sqlx::query(
+        r#"SELECT prompt_id, final_key, result
+           FROM pipeline_run_steps
+           WHERE run_id=$1 AND is_final = TRUE AND prompt_type = 'ScoringPrompt'
+           ORDER BY final_key"#,
+    )
+    .bind(run_id)
+    .fetch_all(pool)
+    .await
+    .context("load final scoring steps")?;
+
+    let pdf: Vec<u8> =
+        sqlx::query_scalar::<_, Option<Vec<u8>>>("SELECT data FROM merged_pdfs WHERE id=$1")
+            .bind(pdf_id)
+            .fetch_one(pool)
+            .await
+            .context("load merged pdf")?
+            .context("merged pdf is archived; GET /pdf/{id} restores it")?;
+    let pdf_path = TempPdf(std::env::temp_dir().join(format!("evidence-{}.pdf", Uuid::new_v4())));
+    tokio::fs::write(&pdf_path.0, &pdf)
+        .await
+        .context("write temp pdf")?;
+
+    let mut items = Vec::new();
+    for row in finals {
+        let final_key: String = row
+            .try_get::<Option<String>, _>("final_key")
+            .ok()
+            .flatten()
+            .unwrap_or_default();
+        if opts.skip_keys.contains(&final_key) {
+            continue;
+        }
+        let prompt_id: Option<i32> = row.try_get("prompt_id").ok();
+        let result: Value = row.try_get("result").unwrap_or(Value::Null);
+        let label = result
+            .get("label")
+            .and_then(Value::as_str)
+            .map(str::to_string);
+        let explanation = result
+            .get("explanation")
+            .and_then(Value::as_str)
+            .map(str::to_string);
+
+        for (n, src) in support_sources(&result)
+            .into_iter()
+            .take(opts.max_per_score)
+            .enumerate()
+        {
+            let layout: Option<PageLayout> = sqlx::query_scalar::<_, Option<Value>>(
+                "SELECT layout_json FROM pdf_texts WHERE merged_pdf_id=$1 AND page_no=$2",
+            )
+            .bind(pdf_id)
+            .bind(src.page as i32)
+            .fetch_optional(pool)
+            .await
+            .ok()
+            .flatten()
+            .flatten()
+            .and_then(|v| serde_json::from_value(v).ok());
+
+            let bbox = match (&layout, &src.quote) {
+                (Some(layout), Some(quote)) => locate_quote(&layout.words, quote),
+                _ => None,
+            }
+            .or_else(|| {
+                src.bbox.map(|b| {
+                    [
+                        b[0].floor() as i32,
+                        b[1].floor() as i32,
+                        b[2].ceil() as i32,
+                        b[3].ceil() as i32,
+                    ]
+                })
+            })
+            .filter(|_| layout.is_some());
+
+            let mut item = EvidenceItem {
+                final_key: final_key.clone(),
+                prompt_id,
+                label: label.clone(),
+                explanation: explanation.clone(),
+                page: src.page,
+                quote: src.quote.clone(),
+                bbox,
+                file: None,
+                error: None,
+            };
+            match render_png(&pdf_path.0, src.page, layout.as_ref(), bbox, opts).await {
+                Ok(png) => {
+                    let name = format!(
+                        "{prefix}{}_{}_p{}.png",
+                        file_stem(&final_key),
+                        n + 1,
+                        src.page + 1
+                    );
+                    zip.start_file(
+                        name.as_str(),
+                        FileOptions::default().compression_method(CompressionMethod::Stored),
+                    )?;
+                    zip.write_all(&png)?;
+                    item.file = Some(name);
+                }
+                Err(e) => {
+                    warn!(%e, %run_id, final_key = %final_key, page = src.page, "evidence crop failed");
+                    item.error = Some(e.to_string());
+                }
+            }
+            items.push(item);
+        }
+    }
+
+    zip.start_file(format!("{prefix}manifest.json"), FileOptions::default())?;
+    zip.write_all(&serde_json::to_vec_pretty(&json!({
+        "run_id": run_id,
+        "pdf_id": pdf_id,
+        "items": items,
+    }))?)?;
+    Ok(items)
+}
+
+/// Builds a standalone archive containing only the evidence crops of a run.
+pub async fn evidence_zip(
+    pool: &PgPool,
+    run_id: Uuid,
+    pdf_id: i32,
+    opts: &EvidenceOptions,
+) -> Result<Vec<u8>> {
+    let mut zip = ZipWriter::new(Cursor::new(Vec::new()));
+    write_evidence(pool, run_id, pdf_id, "evidence/", &mut zip, opts).await?;
+    Ok(zip.finish()?.into_inner())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn word(text: &str, x: i32) -> LayoutWord {
+        LayoutWord {
+            bbox: [x, 10, x + 40, 30],
+            text: text.to_string(),
+        }
+    }
+
+    #[test]
+    fn locates_quote_across_words() {
+        let words = vec![
+            word("Die", 0),
+            word("Police", 50),
+            word("ist", 100),
+            word("gekündigt.", 150),
+            word("Ende", 200),
+        ];
+        assert_eq!(
+            locate_quote(&words, "police ist Gekündigt"),
+            Some([50, 10, 190, 30])
+        );
+        assert_eq!(locate_quote(&words, "nicht vorhanden"), None);
+    }
+
+    #[test]
+    fn file_stem_keeps_names_inside_the_archive() {
+        assert_eq!(file_stem("risk_score-2"), "risk_score-2");
+        assert_eq!(file_stem("../../etc/passwd"), "______etc_passwd");
+        assert_eq!(file_stem("Kündigung"), "K_ndigung");
+    }
+
+    #[test]
+    fn reads_support_array_and_object() {
+        let arr = json!({ "support": [
+            { "page": 2, "quote": " Zitat ", "bbox": [0.0, 0.0, 0.0, 0.0] },
+            { "page": 3, "bbox": [1.0, 2.0, 3.0, 4.0] }
+        ]});
+        let sources = support_sources(&arr);
+        assert_eq!(sources.len(), 2);
+        assert_eq!(sources[0].quote.as_deref(), Some("Zitat"));
+        assert_eq!(sources[0].bbox, None);
+        assert_eq!(sources[1].bbox, Some([1.0, 2.0, 3.0, 4.0]));
+
+        let obj = json!({ "support": { "page": 0, "quote": "x" } });
+        assert_eq!(support_sources(&obj).len(), 1);
+    }
+}
@@ -0,0 +1,137 @@
+//! Expands a prompt group into pipeline steps.
+//!
+//! `POST /pipelines/{id}/steps/from-group/{group_id}` inserts one step per
+//! prompt of the group, in group order, with a default `config` per prompt type
+//! so that large scoring pipelines need not be built step by step.
+
+use std::collections::HashMap;
+use std::str::FromStr;
+
+use anyhow::Result;
+use serde::Deserialize;
+use serde_json::{json, Value};
+use shared::dto::{PipelineStep, PromptType};
+use sqlx::PgPool;
+use tracing::warn;
+use uuid::Uuid;
+
+/// Body of `POST /pipelines/{id}/steps/from-group/{group_id}`; all fields optional.
+#[derive(Debug, Default, Deserialize)]
+pub struct GroupStepsInput {
+    /// Insert position; appends when missing.
+    pub index: Option<usize>,
+    /// Defaults to `true`.
+    pub active: Option<bool>,
+    /// Skip prompts that already have a step in the pipeline.
+    #[serde(default)]
+    pub skip_existing: bool,
+    /// Per prompt type config overriding the built-in defaults,
+    /// e.g. `{"ScoringPrompt": {"min_signal": 0.6}}`.
+    #[serde(default)]
+    pub defaults: HashMap<String, Value>,
+}
+
+/// Config the runner would assume for a step without one.
+fn default_config(step_type: &PromptType) -> Option<Value> {
+    match step_type {
+        PromptType::ExtractionPrompt => None,
+        PromptType::ScoringPrompt => Some(json!({ "min_signal": 0.0 })),
+        PromptType::DecisionPrompt => Some(json!({ "min_confidence": 0.0 })),
+    }
+}
+
+/// Prompts `(id, prompt_type)` of a group in group order; `None` if the group does not exist.
+pub async fn load_group_prompts(
+    pool: &PgPool,
+    group_id: i32,
+) -> Result<Option<Vec<(i32, String)>>> {
+    let exists = sqlx::query_scalar::<_, i32>("SELECT id FROM prompt_groups WHERE id = $1")
+        .bind(group_id)
+        .fetch_optional(pool)
+        .await?;
+    if exists.is_none() {
+        return Ok(None);
+    }
+    let prompts = sqlx::query_as::<_, (i32, String)>(
+        r#"SELECT p.id, p.prompt_type
+             FROM group_prompts gp
+             JOIN prompts p ON p.id = gp.prompt_id
+            WHERE gp.group_id = $1
+            ORDER BY gp.position, gp.prompt_id"#,
+    )
+    .bind(group_id)
+    .fetch_all(pool)
+    .await?;
+    Ok(Some(prompts))
+}
+
+/// Builds the steps for `prompts`; prompts whose type cannot be a pipeline step
+/// (e.g. `FinalPrompt`) and, with `skip_existing`, prompts in `existing` are left out.
+pub fn build_steps(
+    prompts: &[(i32, String)],
+    existing: &[PipelineStep],
+    input: &GroupStepsInput,
+) -> Vec<PipelineStep> {
+    prompts
+        .iter()
+        .filter(|(id, _)| !input.skip_existing || !existing.iter().any(|s| s.prompt_id == *id))
+        .filter_map(|(id, prompt_type)| {
+            let Ok(step_type) = PromptType::from_str(prompt_type) else {
+                warn!(
+                    prompt_id = id,
+                    prompt_type, "prompt type cannot be a pipeline step"
+                );
+                return None;
+            };
+            let config = input
+                .defaults
+                .get(prompt_type.as_str())
+                .cloned()
+                .or_else(|| default_config(&step_type));
+            Some(PipelineStep {
+                id: Uuid::new_v4(),
+                step_type,
+                prompt_id: *id,
+                route: None,
+                yes_key: None,
+                no_key: None,
+                active: input.active.unwrap_or(true),
+                config,
+                library: None,
+            })
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn keeps_group_order_and_applies_defaults() {
+        let prompts = vec![
+            (7, "ScoringPrompt".to_string()),
+            (3, "ExtractionPrompt".to_string()),
+            (5, "FinalPrompt".to_string()),
+            (4, "DecisionPrompt".to_string()),
+        ];
+        let input: GroupStepsInput =
+            serde_json::from_str(r#"{"defaults": {"ScoringPrompt": {"min_signal": 0.6}}}"#)
+                .unwrap();
+
+        let steps = build_steps(&prompts, &[], &input);
+        let ids: Vec<i32> = steps.iter().map(|s| s.prompt_id).collect();
+        assert_eq!(ids, vec![7, 3, 4]);
+        assert_eq!(steps[0].config, Some(json!({ "min_signal": 0.6 })));
+        assert_eq!(steps[1].config, None);
+        assert_eq!(steps[2].config, Some(json!({ "min_confidence": 0.0 })));
+        assert!(steps.iter().all(|s| s.active));
+
+        let skip = GroupStepsInput {
+            skip_existing: true,
+            ..Default::default()
+        };
+        let again = build_steps(&prompts, &steps, &skip);
+        assert!(again.is_empty());
+    }
+}
@@ -1,28 +1,67 @@
 //! HTTP API exposing pipeline management endpoints and forwarding runs to Kafka.
 
-use actix_cors::Cors;
 use actix_web::web::Json;
-use actix_web::{web, App, HttpResponse, HttpServer, Responder};
+use actix_web::{web, App, HttpRequest, HttpResponse, HttpServer, Responder};
 use rdkafka::producer::{FutureProducer, FutureRecord};
 use rdkafka::ClientConfig;
 use serde::{Deserialize, Serialize};
 use serde_json::{json, Map, Value};
-use shared::dto::{PdfUploaded, PipelineConfig, PipelineStep, PromptType, RunStep};
+use shared::app_settings::{self, KnownSetting, StoredSetting};
+use shared::cors::CorsSettings;
+use shared::dto::{
+    PdfUploaded, PipelineConfig, PipelineStep, PromptType, RunPriority, RunStatus, RunStep,
+    PRIORITY_RUN_TOPIC,
+};
+use shared::envelope::{MasterKey, SealedSecret};
+use shared::flags::{self, FeatureFlag};
 use shared::kafka;
 use shared::openai_settings;
+use shared::operator_notes::{self, NoteInput, OperatorNote};
+use shared::outbox;
+use shared::output_mapping;
+use shared::redaction::{self, RedactionProfile};
+use shared::result_label::ResultLabel;
+use shared::run_config;
+use shared::runner_settings::{self, RunnerSettings};
+use shared::schema_doc;
+use shared::startup::{retry_with_backoff, Backoff, Readiness};
+use shared::step_library::{self, ExpandError};
+use shared::telemetry::{EventKind, Telemetry};
+use shared::tenant_credentials::{self, CredentialError, CredentialInput};
 use sqlx::{postgres::PgPoolOptions, PgPool, Row};
 use std::collections::HashMap;
 use std::time::Duration;
 use tracing::{error, info, warn};
 use uuid::Uuid;
 
+mod bundle;
+mod calibration;
 mod consolidation; // belassen, falls später genutzt
+mod estimate;
+mod evidence;
+mod group_steps;
+mod onboarding;
+mod queue;
+mod run_steps;
+mod status;
 
 #[derive(Clone)]
 struct AppState {
     pool: PgPool,
     producer: FutureProducer,
     broker: String,
+    readiness: Readiness,
+    admin_token: Option<String>,
+    /// Key-encryption key for tenant credentials (`CREDENTIALS_MASTER_KEY`).
+    master_key: Option<MasterKey>,
+    /// Opt-in product analytics (`TELEMETRY_SINK`).
+    telemetry: Telemetry,
+    /// Probes behind `GET /status`.
+    status: status::StatusChecker,
+    /// Settings of `GET /runs/queue`.
+    queue: queue::QueueSettings,
+    /// Settings of `POST /tenants/onboard`.
+    onboarding: onboarding::OnboardingSettings,
 }
 
 #[derive(Serialize)]
@@ -125,6 +164,26 @@ async fn init_db(pool: &PgPool) {
         error!(%e, "failed to create table app_settings");
     }
 
+    if let Err(e) = outbox::ensure_schema(pool).await {
+        error!(%e, "failed to create table event_outbox");
+    }
+
+    for sql in app_settings::CREATE_AUDIT_SQL {
+        if let Err(e) = sqlx::query(sql).execute(pool).await {
+            error!(%e, "failed to create table app_settings_audit");
+        }
+    }
+
+    if let Err(e) = calibration::ensure_schema(pool).await {
+        error!(%e, "failed to create tables run_reviews/calibration_reports");
+    }
+
+    for sql in operator_notes::CREATE_RUN_NOTES_SQL {
+        if let Err(e) = sqlx::query(sql).execute(pool).await {
+            error!(%e, "failed to create table run_notes");
+        }
+    }
+
     info!("ensured pipelines and settings tables exist");
 }
 
@@ -143,9 +202,31 @@ async fn fetch_config(pool: &PgPool, id: Uuid) -> Result<PipelineConfig, HttpRes
     serde_json::from_value(value).map_err(|_| HttpResponse::InternalServerError().finish())
 }
 
-async fn store_config(pool: &PgPool, id: Uuid, cfg: &PipelineConfig) -> Result<(), HttpResponse> {
+/// Fills library steps from [`step_library`]; `400` for an unknown item or version.
+async fn expand_library_steps(pool: &PgPool, cfg: &mut PipelineConfig) -> Result<(), HttpResponse> {
+    match step_library::expand(pool, &mut cfg.steps).await {
+        Ok(()) => Ok(()),
+        Err(err @ ExpandError::Unknown(_)) => {
+            Err(HttpResponse::BadRequest().json(json!({ "error": err.to_string() })))
+        }
+        Err(ExpandError::Db(e)) => {
+            error!(%e, "failed to load library steps");
+            Err(HttpResponse::InternalServerError().finish())
+        }
+    }
+}
+
+async fn store_config(
+    pool: &PgPool,
+    id: Uuid,
+    cfg: &mut PipelineConfig,
+) -> Result<(), HttpResponse> {
+    if let Err(e) = redaction::validate(&cfg.redaction_profiles) {
+        return Err(HttpResponse::BadRequest().json(json!({ "error": e.to_string() })));
+    }
+    expand_library_steps(pool, cfg).await?;
     let json =
-        serde_json::to_value(cfg).map_err(|_| HttpResponse::InternalServerError().finish())?;
+        serde_json::to_value(&*cfg).map_err(|_| HttpResponse::InternalServerError().finish())?;
     let res =
         sqlx::query("UPDATE pipelines SET name=$2, config_json=$3, updated_at=now() WHERE id=$1")
             .bind(id)
@@ -191,20 +272,6 @@ async fn fetch_setting(pool: &PgPool, key: &str) -> Result<Option<String>, sqlx:
         .await
 }
 
-async fn store_setting(pool: &PgPool, key: &str, value: &str) -> Result<(), sqlx::Error> {
-    sqlx::query(
-        "INSERT INTO app_settings (key, value, updated_at)
-         VALUES ($1, $2, now())
-         ON CONFLICT (key)
-         DO UPDATE SET value = EXCLUDED.value, updated_at = now()",
-    )
-    .bind(key)
-    .bind(value)
-    .execute(pool)
-    .await
-    .map(|_| ())
-}
-
 #[derive(Serialize)]
 struct OpenAiVersionResponse {
     key: String,
@@ -252,14 +319,15 @@ async fn put_openai_version(
         }));
     }
 
-    match store_setting(
+    match app_settings::store(
         &data.pool,
         openai_settings::OPENAI_VERSION_KEY,
-        &payload.version,
+        Some(&payload.version),
+        None,
     )
     .await
     {
-        Ok(()) => HttpResponse::Ok().json(OpenAiVersionResponse {
+        Ok(_) => HttpResponse::Ok().json(OpenAiVersionResponse {
             key: payload.version.clone(),
             endpoint: openai_settings::endpoint_for(&payload.version),
         }),
@@ -270,6 +338,505 @@ async fn put_openai_version(
     }
 }
 
+/* --------------------------- Runner-Settings --------------------------- */
+
+/// Returns the runtime overrides for the pipeline-runner (admin only).
+async fn get_runner_settings(req: HttpRequest, data: web::Data<AppState>) -> HttpResponse {
+    if let Err(resp) = authorize_admin(&req, &data) {
+        return resp;
+    }
+    match runner_settings::fetch(&data.pool).await {
+        Ok(settings) => HttpResponse::Ok().json(settings),
+        Err(e) => {
+            error!(%e, "failed to read runner settings");
+            HttpResponse::InternalServerError().finish()
+        }
+    }
+}
+
+/// Replaces the runtime overrides; `null` fields fall back to the runner's env defaults.
+async fn put_runner_settings(
+    req: HttpRequest,
+    data: web::Data<AppState>,
+    payload: Json<RunnerSettings>,
+) -> HttpResponse {
+    if let Err(resp) = authorize_admin(&req, &data) {
+        return resp;
+    }
+    let settings = payload.into_inner();
+    if let Err(msg) = settings.validate() {
+        return HttpResponse::BadRequest().json(json!({ "error": msg }));
+    }
+    match runner_settings::store(&data.pool, &settings).await {
+        Ok(()) => {
+            info!(?settings, "runner settings updated");
+            HttpResponse::Ok().json(settings)
+        }
+        Err(e) => {
+            error!(%e, "failed to store runner settings");
+            HttpResponse::InternalServerError().finish()
+        }
+    }
+}
+
+/* ----------------------------- App-Settings ----------------------------- */
+
+#[derive(Deserialize)]
+struct SettingUpdate {
+    value: Value,
+    changed_by: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct SettingReset {
+    changed_by: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct SettingAuditQuery {
+    limit: Option<i64>,
+}
+
+/// Known setting with its current value (default while no row is stored).
+fn setting_json(setting: &KnownSetting, stored: Option<&StoredSetting>) -> Value {
+    json!({
+        "key": setting.key,
+        "description": setting.description,
+        "kind": setting.kind,
+        "editable": setting.editable(),
+        "options": setting.options(),
+        "value": stored.map_or_else(|| setting.default_value(), |s| setting.decode(&s.value)),
+        "is_default": stored.is_none(),
+        "updated_at": stored.and_then(|s| s.updated_at.clone()),
+    })
+}
+
+fn unknown_setting(key: &str) -> HttpResponse {
+    HttpResponse::NotFound().json(json!({ "error": format!("unknown setting '{key}'") }))
+}
+
+/// Lists the known settings with their values (admin only).
+async fn list_app_settings(req: HttpRequest, data: web::Data<AppState>) -> HttpResponse {
+    if let Err(resp) = authorize_admin(&req, &data) {
+        return resp;
+    }
+    let keys: Vec<&str> = app_settings::KNOWN_SETTINGS.iter().map(|s| s.key).collect();
+    match app_settings::fetch(&data.pool, &keys).await {
+        Ok(stored) => {
+            let stored: HashMap<String, StoredSetting> = stored.into_iter().collect();
+            HttpResponse::Ok().json(
+                app_settings::KNOWN_SETTINGS
+                    .iter()
+                    .map(|s| setting_json(s, stored.get(s.key)))
+                    .collect::<Vec<_>>(),
+            )
+        }
+        Err(e) => {
+            error!(%e, "failed to read app settings");
+            HttpResponse::InternalServerError().finish()
+        }
+    }
+}
+
+/// Returns one known setting (admin only).
+async fn get_app_setting(
+    req: HttpRequest,
+    data: web::Data<AppState>,
+    path: web::Path<String>,
+) -> HttpResponse {
+    if let Err(resp) = authorize_admin(&req, &data) {
+        return resp;
+    }
+    match app_settings::known(&path) {
+        Some(setting) => setting_response(&data.pool, setting).await,
+        None => unknown_setting(&path),
+    }
+}
+
+async fn setting_response(pool: &PgPool, setting: &KnownSetting) -> HttpResponse {
+    match app_settings::fetch(pool, &[setting.key]).await {
+        Ok(stored) => {
+            HttpResponse::Ok().json(setting_json(setting, stored.first().map(|(_, s)| s)))
+        }
+        Err(e) => {
+            error!(%e, key = setting.key, "failed to read app setting");
+            HttpResponse::InternalServerError().finish()
+        }
+    }
+}
+
+/// Validates and stores a setting; audited and announced on `settings-changed`.
+async fn put_app_setting(
+    req: HttpRequest,
+    data: web::Data<AppState>,
+    path: web::Path<String>,
+    payload: Json<SettingUpdate>,
+) -> HttpResponse {
+    if let Err(resp) = authorize_admin(&req, &data) {
+        return resp;
+    }
+    let Some(setting) = app_settings::known(&path) else {
+        return unknown_setting(&path);
+    };
+    let value = match setting.validate(&payload.value) {
+        Ok(value) => value,
+        Err(msg) => return HttpResponse::BadRequest().json(json!({ "error": msg })),
+    };
+    let changed_by = payload
+        .changed_by
+        .as_deref()
+        .map(str::trim)
+        .filter(|c| !c.is_empty());
+    match app_settings::store(&data.pool, setting.key, Some(&value), changed_by).await {
+        Ok(changed) => {
+            if changed {
+                info!(key = setting.key, ?changed_by, "app setting changed");
+            }
+            setting_response(&data.pool, setting).await
+        }
+        Err(e) => {
+            error!(%e, key = setting.key, "failed to store app setting");
+            HttpResponse::InternalServerError().finish()
+        }
+    }
+}
+
+/// Deletes the stored value so the default applies again.
+async fn delete_app_setting(
+    req: HttpRequest,
+    data: web::Data<AppState>,
+    path: web::Path<String>,
+    query: web::Query<SettingReset>,
+) -> HttpResponse {
+    if let Err(resp) = authorize_admin(&req, &data) {
+        return resp;
+    }
+    let Some(setting) = app_settings::known(&path) else {
+        return unknown_setting(&path);
+    };
+    if !setting.editable() {
+        return HttpResponse::BadRequest()
+            .json(json!({ "error": format!("{} is read-only", setting.key) }));
+    }
+    let changed_by = query
+        .changed_by
+        .as_deref()
+        .map(str::trim)
+        .filter(|c| !c.is_empty());
+    match app_settings::store(&data.pool, setting.key, None, changed_by).await {
+        Ok(_) => {
+            info!(key = setting.key, ?changed_by, "app setting reset");
+            HttpResponse::NoContent().finish()
+        }
+        Err(e) => {
+            error!(%e, key = setting.key, "failed to reset app setting");
+            HttpResponse::InternalServerError().finish()
+        }
+    }
+}
+
+/// Latest changes of a setting, newest first (admin only).
+async fn get_app_setting_audit(
+    req: HttpRequest,
+    data: web::Data<AppState>,
+    path: web::Path<String>,
+    query: web::Query<SettingAuditQuery>,
+) -> HttpResponse {
+    if let Err(resp) = authorize_admin(&req, &data) {
+        return resp;
+    }
+    let Some(setting) = app_settings::known(&path) else {
+        return unknown_setting(&path);
+    };
+    let limit = query.limit.unwrap_or(50).clamp(1, 500);
+    match app_settings::audit(&data.pool, setting.key, limit).await {
+        Ok(entries) => HttpResponse::Ok().json(entries),
+        Err(e) => {
+            error!(%e, key = setting.key, "failed to read app settings audit");
+            HttpResponse::InternalServerError().finish()
+        }
+    }
+}
+
+/* ----------------------------- Feature-Flags ----------------------------- */
+
+#[derive(Deserialize)]
+struct FlagUpdate {
+    enabled: bool,
+    /// Without tenant the global value is set.
+    tenant_id: Option<Uuid>,
+    changed_by: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct FlagReset {
+    tenant_id: Option<Uuid>,
+    changed_by: Option<String>,
+}
+
+fn unknown_flag(key: &str) -> HttpResponse {
+    HttpResponse::NotFound().json(json!({ "error": format!("unknown flag '{key}'") }))
+}
+
+/// Lists all feature flags with global value and tenant overrides (admin only).
+async fn list_flags(req: HttpRequest, data: web::Data<AppState>) -> HttpResponse {
+    if let Err(resp) = authorize_admin(&req, &data) {
+        return resp;
+    }
+    match flags::list(&data.pool).await {
+        Ok(states) => HttpResponse::Ok().json(states),
+        Err(e) => {
+            error!(%e, "failed to read feature flags");
+            HttpResponse::InternalServerError().finish()
+        }
+    }
+}
+
+async fn flag_response(pool: &PgPool, flag: &FeatureFlag) -> HttpResponse {
+    match flags::list(pool).await {
+        Ok(states) => match states.into_iter().find(|s| s.flag.key == flag.key) {
+            Some(state) => HttpResponse::Ok().json(state),
+            None => unknown_flag(flag.key),
+        },
+        Err(e) => {
+            error!(%e, flag = flag.key, "failed to read feature flag");
+            HttpResponse::InternalServerError().finish()
+        }
+    }
+}
+
+/// Returns one feature flag (admin only).
+async fn get_flag(
+    req: HttpRequest,
+    data: web::Data<AppState>,
+    path: web::Path<String>,
+) -> HttpResponse {
+    if let Err(resp) = authorize_admin(&req, &data) {
+        return resp;
+    }
+    match flags::known(&path) {
+        Some(flag) => flag_response(&data.pool, flag).await,
+        None => unknown_flag(&path),
+    }
+}
+
+/// Sets a flag globally or for one tenant; audited like every app setting.
+async fn put_flag(
+    req: HttpRequest,
+    data: web::Data<AppState>,
+    path: web::Path<String>,
+    payload: Json<FlagUpdate>,
+) -> HttpResponse {
+    if let Err(resp) = authorize_admin(&req, &data) {
+        return resp;
+    }
+    let Some(flag) = flags::known(&path) else {
+        return unknown_flag(&path);
+    };
+    let changed_by = payload
+        .changed_by
+        .as_deref()
+        .map(str::trim)
+        .filter(|c| !c.is_empty());
+    match flags::store(
+        &data.pool,
+        flag,
+        payload.tenant_id,
+        Some(payload.enabled),
+        changed_by,
+    )
+    .await
+    {
+        Ok(changed) => {
+            if changed {
+                info!(flag = flag.key, tenant_id = ?payload.tenant_id, enabled = payload.enabled, ?changed_by, "feature flag changed");
+            }
+            flag_response(&data.pool, flag).await
+        }
+        Err(e) => {
+            error!(%e, flag = flag.key, "failed to store feature flag");
+            HttpResponse::InternalServerError().finish()
+        }
+    }
+}
+
+/// Removes the global value or a tenant override.
+async fn delete_flag(
+    req: HttpRequest,
+    data: web::Data<AppState>,
+    path: web::Path<String>,
+    query: web::Query<FlagReset>,
+) -> HttpResponse {
+    if let Err(resp) = authorize_admin(&req, &data) {
+        return resp;
+    }
+    let Some(flag) = flags::known(&path) else {
+        return unknown_flag(&path);
+    };
+    let changed_by = query
+        .changed_by
+        .as_deref()
+        .map(str::trim)
+        .filter(|c| !c.is_empty());
+    match flags::store(&data.pool, flag, query.tenant_id, None, changed_by).await {
+        Ok(_) => {
+            info!(flag = flag.key, tenant_id = ?query.tenant_id, ?changed_by, "feature flag reset");
+            HttpResponse::NoContent().finish()
+        }
+        Err(e) => {
+            error!(%e, flag = flag.key, "failed to reset feature flag");
+            HttpResponse::InternalServerError().finish()
+        }
+    }
+}
+
+/* ------------------------- Tenant-Credentials ------------------------- */
+
+fn credential_error(e: CredentialError) -> HttpResponse {
+    match e {
+        CredentialError::Invalid(msg) => HttpResponse::BadRequest().json(json!({ "error": msg })),
+        other => {
+            error!(%other, "tenant credential operation failed");
+            HttpResponse::InternalServerError().finish()
+        }
+    }
+}
+
+/// Lists all tenants with OpenAI credentials (masked, admin only).
+async fn list_tenant_credentials(req: HttpRequest, data: web::Data<AppState>) -> HttpResponse {
+    if let Err(resp) = authorize_admin(&req, &data) {
+        return resp;
+    }
+    match tenant_credentials::list(&data.pool).await {
+        Ok(items) => HttpResponse::Ok().json(items),
+        Err(e) => credential_error(e),
+    }
+}
+
+/// Returns the tenant's OpenAI credentials (masked, admin only).
+async fn get_tenant_credentials(
+    req: HttpRequest,
+    data: web::Data<AppState>,
+    path: web::Path<Uuid>,
+) -> HttpResponse {
+    if let Err(resp) = authorize_admin(&req, &data) {
+        return resp;
+    }
+    match tenant_credentials::summary(&data.pool, path.into_inner()).await {
+        Ok(Some(summary)) => HttpResponse::Ok().json(summary),
+        Ok(None) => HttpResponse::NotFound().finish(),
+        Err(e) => credential_error(e),
+    }
+}
+
+/// Stores (encrypts) the tenant's OpenAI credentials (admin only).
+async fn put_tenant_credentials(
+    req: HttpRequest,
+    data: web::Data<AppState>,
+    path: web::Path<Uuid>,
+    payload: Json<CredentialInput>,
+) -> HttpResponse {
+    if let Err(resp) = authorize_admin(&req, &data) {
+        return resp;
+    }
+    let Some(master) = data.master_key.as_ref() else {
+        return HttpResponse::ServiceUnavailable().json(json!({
+            "error": "CREDENTIALS_MASTER_KEY is not configured",
+        }));
+    };
+    let tenant_id = path.into_inner();
+    match sqlx::query_scalar::<_, Uuid>("SELECT id FROM tenants WHERE id = $1")
+        .bind(tenant_id)
+        .fetch_optional(&data.pool)
+        .await
+    {
+        Ok(Some(_)) => {}
+        Ok(None) => return HttpResponse::NotFound().body("tenant not found"),
+        Err(e) => {
+            error!("db error: {}", e);
+            return HttpResponse::InternalServerError().finish();
+        }
+    }
+    match tenant_credentials::store(&data.pool, master, tenant_id, payload.into_inner()).await {
+        Ok(summary) => {
+            info!(%tenant_id, "tenant OpenAI credentials updated");
+            HttpResponse::Ok().json(summary)
+        }
+        Err(e) => credential_error(e),
+    }
+}
+
+/// Creates a tenant with pipeline, flags, credentials, automation rules and
+/// result sinks in one call (admin only, see `onboarding.rs`).
+async fn onboard_tenant(
+    req: HttpRequest,
+    data: web::Data<AppState>,
+    payload: Json<onboarding::OnboardRequest>,
+) -> HttpResponse {
+    if let Err(resp) = authorize_admin(&req, &data) {
+        return resp;
+    }
+    let authorization = req
+        .headers()
+        .get("Authorization")
+        .and_then(|v| v.to_str().ok());
+    match onboarding::onboard(
+        &data.pool,
+        data.master_key.as_ref(),
+        &data.onboarding,
+        authorization,
+        payload.into_inner(),
+    )
+    .await
+    {
+        Ok(done) => {
+            info!(tenant_id = %done.tenant.id, tenant = %done.tenant.name, pipeline = ?done.pipeline.as_ref().map(|p| p.id), sinks = done.sinks.len(), "tenant onboarded");
+            HttpResponse::Created().json(done)
+        }
+        Err(onboarding::OnboardError::Invalid(msg)) => {
+            HttpResponse::BadRequest().json(json!({ "error": msg }))
+        }
+        Err(onboarding::OnboardError::Conflict(msg)) => {
+            HttpResponse::Conflict().json(json!({ "error": msg }))
+        }
+        Err(onboarding::OnboardError::Unavailable(msg)) => {
+            HttpResponse::ServiceUnavailable().json(json!({ "error": msg }))
+        }
+        Err(onboarding::OnboardError::Sink { status, error }) => {
+            // 4xx von history-service: Eingabefehler des Aufrufers
+            let status = match status {
+                400..=499 => actix_web::http::StatusCode::BAD_REQUEST,
+                _ => actix_web::http::StatusCode::BAD_GATEWAY,
+            };
+            HttpResponse::build(status).json(json!({ "error": error }))
+        }
+        Err(onboarding::OnboardError::Db(e)) => {
+            error!(%e, "tenant onboarding failed");
+            HttpResponse::InternalServerError().finish()
+        }
+    }
+}
+
+/// Removes the tenant's OpenAI credentials; runs fall back to the global key.
+async fn delete_tenant_credentials(
+    req: HttpRequest,
+    data: web::Data<AppState>,
+    path: web::Path<Uuid>,
+) -> HttpResponse {
+    if let Err(resp) = authorize_admin(&req, &data) {
+        return resp;
+    }
+    let tenant_id = path.into_inner();
+    match tenant_credentials::delete(&data.pool, tenant_id).await {
+        Ok(true) => {
+            info!(%tenant_id, "tenant OpenAI credentials removed");
+            HttpResponse::NoContent().finish()
+        }
+        Ok(false) => HttpResponse::NotFound().finish(),
+        Err(e) => credential_error(e),
+    }
+}
+
 /* ------------------------------ Handlers ------------------------------ */
 
 async fn list_pipelines(data: web::Data<AppState>) -> impl Responder {
@@ -306,14 +873,21 @@ async fn list_pipelines(data: web::Data<AppState>) -> impl Responder {
 struct RunMetaRow {
     pipeline_id: uuid::Uuid,
     pdf_id: i32,
+    status: Option<String>,
     overall_score: Option<f32>,
+    contested: bool,
+    job_label: Option<String>,
+    external_ref: Option<String>,
+    rerun_of: Option<Uuid>,
 }
 
 async fn get_run(data: web::Data<AppState>, path: web::Path<uuid::Uuid>) -> impl Responder {
     let run_id = path.into_inner();
 
     let meta = match sqlx::query_as::<_, RunMetaRow>(
-        "SELECT pipeline_id, pdf_id, overall_score FROM pipeline_runs WHERE id=$1",
+        "SELECT pipeline_id, pdf_id, status, overall_score, contested, job_label, external_ref,
+                rerun_of
+         FROM pipeline_runs WHERE id=$1",
     )
     .bind(run_id)
     .fetch_one(&data.pool)
@@ -400,10 +974,32 @@ async fn get_run(data: web::Data<AppState>, path: web::Path<uuid::Uuid>) -> impl
         })
         .collect();
 
-    let res_json = json!({
+    let (tags, note): (Vec<String>, Option<String>) =
+        sqlx::query_as("SELECT tags, note FROM run_notes WHERE run_id=$1")
+            .bind(run_id)
+            .fetch_optional(&data.pool)
+            .await
+            .unwrap_or_else(|e| {
+                warn!(%e, %run_id, "run notes unavailable");
+                None
+            })
+            .unwrap_or_default();
+
+    // Alt-Werte (finished/finalized/...) kanonisch ausliefern
+    let status = meta.status.as_deref().map(|raw| {
+        RunStatus::parse_lenient(raw).map_or_else(|| raw.to_string(), |s| s.to_string())
+    });
+    let mut res_json = json!({
         "pdf_id": meta.pdf_id,
         "pipeline_id": meta.pipeline_id,
+        "status": status,
         "overall_score": meta.overall_score,
+        "contested": meta.contested,
+        "job_label": meta.job_label,
+        "external_ref": meta.external_ref,
+        "rerun_of": meta.rerun_of,
+        "tags": tags,
+        "note": note,
         "extracted": extracted,
         "scores": scores,
         "decisions": decisions,
@@ -413,7 +1009,372 @@ async fn get_run(data: web::Data<AppState>, path: web::Path<uuid::Uuid>) -> impl
         "log": steps
     });
 
-    HttpResponse::Ok().json(res_json)
+    // Kundenfeldnamen, falls die Pipeline ein output_mapping hat
+    if let Ok(cfg) = fetch_config(&data.pool, meta.pipeline_id).await {
+        if !cfg.output_mapping.is_empty() {
+            let finals = output_mapping::collect_finals([&extracted, &scores, &decisions]);
+            res_json["output"] = json!(output_mapping::render(&cfg.output_mapping, &finals));
+        }
+    }
+
+    HttpResponse::Ok().json(res_json)
+}
+
+/// Records a downloaded export in `run_timeline` (best effort, used by `GET /lineage`),
+/// including the applied redaction profile.
+async fn record_export(
+    pool: &PgPool,
+    run_id: Uuid,
+    format: &str,
+    profile: Option<&RedactionProfile>,
+) {
+    let mut details = json!({ "format": format });
+    if let Some(profile) = profile {
+        details["role"] = json!(profile.role);
+        details["redaction"] = profile.audit();
+    }
+    if let Err(e) = sqlx::query(
+        "INSERT INTO run_timeline (pdf_id, run_id, pipeline_id, source, status, details)
+         SELECT pdf_id, id, pipeline_id, 'pipeline-api', 'exported', $2
+           FROM pipeline_runs WHERE id = $1",
+    )
+    .bind(run_id)
+    .bind(details)
+    .execute(pool)
+    .await
+    {
+        warn!(%e, %run_id, "failed to append exported timeline event");
+    }
+}
+
+/// Packages config, prompts, steps, results, timeline, texts and evidence of a run as ZIP.
+async fn get_run_bundle(
+    data: web::Data<AppState>,
+    path: web::Path<Uuid>,
+    query: web::Query<bundle::BundleOptions>,
+) -> HttpResponse {
+    let run_id = path.into_inner();
+    let profiles = match &query.role {
+        Some(_) => match bundle::redaction_profiles(&data.pool, run_id).await {
+            Ok(Some(profiles)) => profiles,
+            Ok(None) => return HttpResponse::NotFound().finish(),
+            Err(e) => {
+                error!(%run_id, "loading redaction profiles failed: {:#}", e);
+                return HttpResponse::InternalServerError().finish();
+            }
+        },
+        None => Vec::new(),
+    };
+    let profile = match &query.role {
+        Some(role) => match redaction::find(&profiles, role) {
+            Some(profile) => Some(profile),
+            None => {
+                return HttpResponse::BadRequest().json(json!({
+                    "error": format!("no redaction profile for role '{}'", role.trim())
+                }))
+            }
+        },
+        None => None,
+    };
+    match bundle::bundle_zip(&data.pool, run_id, &query, profile).await {
+        Ok(Some(bytes)) => {
+            record_export(&data.pool, run_id, "bundle", profile).await;
+            data.telemetry
+                .spawn_emit(EventKind::ExportDownloaded, json!({ "format": "bundle" }));
+            HttpResponse::Ok()
+                .content_type("application/zip")
+                .insert_header((
+                    "Content-Disposition",
+                    format!("attachment; filename=\"run-{run_id}-bundle.zip\""),
+                ))
+                .body(bytes)
+        }
+        Ok(None) => HttpResponse::NotFound().finish(),
+        Err(e) => {
+            error!(%run_id, "bundle export failed: {:#}", e);
+            HttpResponse::InternalServerError().finish()
+        }
+    }
+}
+
+/// Pipeline config, prompt texts and model settings the run executed with.
+async fn get_run_config(data: web::Data<AppState>, path: web::Path<Uuid>) -> HttpResponse {
+    let run_id = path.into_inner();
+    match run_config::load(&data.pool, run_id).await {
+        Ok(Some(config)) => return HttpResponse::Ok().json(config),
+        Ok(None) => {}
+        Err(e) => {
+            error!(%run_id, "run config query failed: {}", e);
+            return HttpResponse::InternalServerError().finish();
+        }
+    }
+    // Runs vor Einführung der Snapshots haben keine gespeicherte Konfiguration
+    match sqlx::query_scalar::<_, i32>("SELECT 1 FROM pipeline_runs WHERE id=$1")
+        .bind(run_id)
+        .fetch_optional(&data.pool)
+        .await
+    {
+        Ok(Some(_)) => {
+            HttpResponse::NotFound().json(json!({ "error": "no config recorded for this run" }))
+        }
+        Ok(None) => HttpResponse::NotFound().finish(),
+        Err(e) => {
+            error!(%run_id, "db error run lookup: {}", e);
+            HttpResponse::InternalServerError().finish()
+        }
+    }
+}
+
+/// Pages through the logged steps of a run (filters, keyset cursor, summary mode).
+async fn get_run_steps(
+    data: web::Data<AppState>,
+    path: web::Path<Uuid>,
+    query: web::Query<run_steps::StepQuery>,
+) -> HttpResponse {
+    let run_id = path.into_inner();
+    match sqlx::query_scalar::<_, i32>("SELECT 1 FROM pipeline_runs WHERE id=$1")
+        .bind(run_id)
+        .fetch_optional(&data.pool)
+        .await
+    {
+        Ok(Some(_)) => {}
+        Ok(None) => return HttpResponse::NotFound().finish(),
+        Err(e) => {
+            error!(%run_id, "db error run lookup: {}", e);
+            return HttpResponse::InternalServerError().finish();
+        }
+    }
+    let res = if query.summary {
+        run_steps::summary(&data.pool, run_id, &query)
+            .await
+            .map(|s| HttpResponse::Ok().json(s))
+    } else {
+        run_steps::list(&data.pool, run_id, &query)
+            .await
+            .map(|p| HttpResponse::Ok().json(p))
+    };
+    res.unwrap_or_else(|e| {
+        error!(%run_id, "run steps query failed: {:#}", e);
+        HttpResponse::InternalServerError().finish()
+    })
+}
+
+/// Exports cropped page images for all final scoring results of a run as ZIP.
+async fn get_run_evidence(data: web::Data<AppState>, path: web::Path<Uuid>) -> HttpResponse {
+    let run_id = path.into_inner();
+    let pdf_id = match sqlx::query_scalar::<_, i32>("SELECT pdf_id FROM pipeline_runs WHERE id=$1")
+        .bind(run_id)
+        .fetch_optional(&data.pool)
+        .await
+    {
+        Ok(Some(id)) => id,
+        Ok(None) => return HttpResponse::NotFound().finish(),
+        Err(e) => {
+            error!("db error: {}", e);
+            return HttpResponse::InternalServerError().finish();
+        }
+    };
+
+    let opts = evidence::EvidenceOptions::from_env();
+    match evidence::evidence_zip(&data.pool, run_id, pdf_id, &opts).await {
+        Ok(bytes) => {
+            record_export(&data.pool, run_id, "evidence", None).await;
+            data.telemetry
+                .spawn_emit(EventKind::ExportDownloaded, json!({ "format": "evidence" }));
+            HttpResponse::Ok()
+                .content_type("application/zip")
+                .insert_header((
+                    "Content-Disposition",
+                    format!("attachment; filename=\"run-{run_id}-evidence.zip\""),
+                ))
+                .body(bytes)
+        }
+        Err(e) => {
+            error!(%run_id, "evidence export failed: {:#}", e);
+            HttpResponse::InternalServerError().finish()
+        }
+    }
+}
+
+/// Checks the `Authorization: Bearer <ADMIN_TOKEN>` header when a token is configured.
+fn authorize_admin(req: &HttpRequest, data: &AppState) -> Result<(), HttpResponse> {
+    if let Some(expected) = &data.admin_token {
+        let token = req
+            .headers()
+            .get("Authorization")
+            .and_then(|v| v.to_str().ok())
+            .and_then(|v| v.strip_prefix("Bearer "))
+            .unwrap_or("");
+        if token != expected {
+            return Err(HttpResponse::Unauthorized().body("invalid token"));
+        }
+    }
+    Ok(())
+}
+
+/// Re-queues the stored `pipeline-result` event of a run for publication (admin only).
+async fn republish_run_result(
+    req: HttpRequest,
+    data: web::Data<AppState>,
+    path: web::Path<Uuid>,
+) -> HttpResponse {
+    if let Err(resp) = authorize_admin(&req, &data) {
+        return resp;
+    }
+
+    let run_id = path.into_inner();
+    match outbox::requeue_run(&data.pool, "pipeline-result", &run_id.to_string()).await {
+        Ok(true) => {
+            info!(%run_id, "pipeline-result re-queued for publication");
+            HttpResponse::Accepted().json(json!({ "run_id": run_id, "status": "requeued" }))
+        }
+        Ok(false) => HttpResponse::NotFound().body("no pipeline-result stored for run"),
+        Err(e) => {
+            error!("db error: {}", e);
+            HttpResponse::InternalServerError().finish()
+        }
+    }
+}
+
+/// Pipeline, run and settings tables served by `GET /admin/schema`; the runner
+/// writes the run tables but has no HTTP API of its own.
+const OWNED_TABLES: [&str; 8] = [
+    "pipelines",
+    "pipeline_runs",
+    "pipeline_run_steps",
+    "pipeline_run_configs",
+    "run_notes",
+    "event_outbox",
+    "app_settings",
+    "app_settings_audit",
+];
+
+/// Describes columns, indexes and foreign keys of the owned tables (admin only).
+async fn get_schema(req: HttpRequest, data: web::Data<AppState>) -> HttpResponse {
+    if let Err(resp) = authorize_admin(&req, &data) {
+        return resp;
+    }
+    match schema_doc::describe_pool(&data.pool, "pipeline-api", &OWNED_TABLES).await {
+        Ok(schema) => HttpResponse::Ok().json(schema),
+        Err(e) => {
+            error!("db error: {:#}", e);
+            HttpResponse::InternalServerError().finish()
+        }
+    }
+}
+
+/// Decrypts the unscrubbed final extraction of a run (admin only; unlike the
+/// other admin endpoints never open without `ADMIN_TOKEN`).
+async fn get_sealed_final_extraction(
+    req: HttpRequest,
+    data: web::Data<AppState>,
+    path: web::Path<Uuid>,
+) -> HttpResponse {
+    if data.admin_token.is_none() {
+        return HttpResponse::ServiceUnavailable().json(json!({
+            "error": "ADMIN_TOKEN is not configured",
+        }));
+    }
+    if let Err(resp) = authorize_admin(&req, &data) {
+        return resp;
+    }
+    let Some(master) = data.master_key.as_ref() else {
+        return HttpResponse::ServiceUnavailable().json(json!({
+            "error": "CREDENTIALS_MASTER_KEY is not configured",
+        }));
+    };
+
+    let run_id = path.into_inner();
+    let sealed = match sqlx::query_scalar::<_, Option<Value>>(
+        "SELECT final_extraction_sealed FROM pipeline_runs WHERE id = $1",
+    )
+    .bind(run_id)
+    .fetch_optional(&data.pool)
+    .await
+    {
+        Ok(Some(Some(v))) => v,
+        Ok(_) => return HttpResponse::NotFound().body("no sealed extraction stored for run"),
+        Err(e) => {
+            error!("db error: {}", e);
+            return HttpResponse::InternalServerError().finish();
+        }
+    };
+    let opened = serde_json::from_value::<SealedSecret>(sealed)
+        .map_err(|e| e.to_string())
+        .and_then(|s| master.open(&s).map_err(|e| e.to_string()))
+        .and_then(|plain| serde_json::from_str::<Value>(&plain).map_err(|e| e.to_string()));
+    match opened {
+        Ok(extraction) => {
+            info!(%run_id, "sealed final extraction disclosed");
+            HttpResponse::Ok().json(json!({ "run_id": run_id, "final_extraction": extraction }))
+        }
+        Err(e) => {
+            error!(%run_id, "failed to open sealed extraction: {}", e);
+            HttpResponse::InternalServerError().finish()
+        }
+    }
+}
+
+/// Tables whose `status` column holds a [`RunStatus`].
+const RUN_STATUS_TABLES: [&str; 2] = ["pipeline_runs", "analysis_history"];
+
+#[derive(Deserialize)]
+struct BackfillQuery {
+    #[serde(default)]
+    dry_run: bool,
+}
+
+/// Rewrites legacy run status spellings to the canonical [`RunStatus`] values
+/// (admin only). `?dry_run=true` only counts the affected rows.
+async fn backfill_run_status(
+    req: HttpRequest,
+    data: web::Data<AppState>,
+    query: web::Query<BackfillQuery>,
+) -> HttpResponse {
+    if let Err(resp) = authorize_admin(&req, &data) {
+        return resp;
+    }
+
+    let mut report = Map::new();
+    for table in RUN_STATUS_TABLES {
+        let mut counts = Map::new();
+        for (legacy, status) in RunStatus::LEGACY_ALIASES {
+            // Tabellenname stammt aus der Konstante oben, nicht aus der Anfrage
+            let result = if query.dry_run {
+                sqlx::query_scalar::<_, i64>(&format!(
+                    "SELECT COUNT(*) FROM {table} WHERE lower(status) = $1"
+                ))
+                .bind(legacy)
+                .fetch_one(&data.pool)
+                .await
+            } else {
+                sqlx::query(&format!(
+                    "UPDATE {table} SET status = $2 WHERE lower(status) = $1"
+                ))
+                .bind(legacy)
+                .bind(status.as_str())
+                .execute(&data.pool)
+                .await
+                .map(|done| done.rows_affected() as i64)
+            };
+            match result {
+                Ok(0) => {}
+                Ok(n) => {
+                    counts.insert(legacy.to_string(), json!(n));
+                }
+                Err(e) => {
+                    error!(table, legacy, "run status backfill failed: {}", e);
+                    return HttpResponse::InternalServerError().finish();
+                }
+            }
+        }
+        report.insert(table.to_string(), Value::Object(counts));
+    }
+    let report = Value::Object(report);
+    if !query.dry_run {
+        info!(%report, "run status backfill applied");
+    }
+    HttpResponse::Ok().json(json!({ "dry_run": query.dry_run, "tables": report }))
 }
 
 #[derive(Deserialize)]
@@ -423,8 +1384,11 @@ struct NameInput {
 
 async fn create_pipeline(
     data: web::Data<AppState>,
-    Json(cfg): web::Json<PipelineConfig>,
+    Json(mut cfg): web::Json<PipelineConfig>,
 ) -> impl Responder {
+    if let Err(resp) = expand_library_steps(&data.pool, &mut cfg).await {
+        return resp;
+    }
     let id = Uuid::new_v4();
     let name = cfg.name.clone();
     let steps = cfg.steps.clone();
@@ -441,7 +1405,13 @@ async fn create_pipeline(
         .execute(&data.pool)
         .await
     {
-        Ok(_) => HttpResponse::Created().json(PipelineInfo { id, name, steps }),
+        Ok(_) => {
+            data.telemetry.spawn_emit(
+                EventKind::PipelineCreated,
+                json!({ "pipeline": id.to_string(), "steps": steps.len(), "duplicated": false }),
+            );
+            HttpResponse::Created().json(PipelineInfo { id, name, steps })
+        }
         Err(e) => {
             error!("insert error: {}", e);
             HttpResponse::InternalServerError().finish()
@@ -481,11 +1451,17 @@ async fn duplicate_pipeline(data: web::Data<AppState>, path: web::Path<Uuid>) ->
         .execute(&data.pool)
         .await
     {
-        Ok(_) => HttpResponse::Created().json(PipelineInfo {
-            id: new_id,
-            name: new_name,
-            steps,
-        }),
+        Ok(_) => {
+            data.telemetry.spawn_emit(
+                EventKind::PipelineCreated,
+                json!({ "pipeline": new_id.to_string(), "steps": steps.len(), "duplicated": true }),
+            );
+            HttpResponse::Created().json(PipelineInfo {
+                id: new_id,
+                name: new_name,
+                steps,
+            })
+        }
         Err(e) => {
             error!("insert error: {}", e);
             HttpResponse::InternalServerError().finish()
@@ -520,11 +1496,36 @@ async fn update_pipeline(
 ) -> impl Responder {
     // Versuch: als volle Pipeline interpretieren
     if body.get("steps").is_some() {
-        let cfg: PipelineConfig = match serde_json::from_value(body.clone()) {
+        let mut cfg: PipelineConfig = match serde_json::from_value(body.clone()) {
             Ok(c) => c,
             Err(_) => return HttpResponse::BadRequest().finish(),
         };
-        return match store_config(&data.pool, *path, &cfg).await {
+        // Der Editor schickt nur name + steps: vorhandenes output_mapping/label_rules behalten
+        if [
+            "output_mapping",
+            "label_rules",
+            "run_on_update",
+            "redaction_profiles",
+        ]
+        .iter()
+        .any(|key| body.get(key).is_none())
+        {
+            if let Ok(existing) = fetch_config(&data.pool, *path).await {
+                if body.get("output_mapping").is_none() {
+                    cfg.output_mapping = existing.output_mapping;
+                }
+                if body.get("label_rules").is_none() {
+                    cfg.label_rules = existing.label_rules;
+                }
+                if body.get("run_on_update").is_none() {
+                    cfg.run_on_update = existing.run_on_update;
+                }
+                if body.get("redaction_profiles").is_none() {
+                    cfg.redaction_profiles = existing.redaction_profiles;
+                }
+            }
+        }
+        return match store_config(&data.pool, *path, &mut cfg).await {
             Ok(()) => HttpResponse::NoContent().finish(),
             Err(e) => e,
         };
@@ -555,7 +1556,7 @@ async fn update_pipeline(
 
     cfg.name = input.name.clone();
 
-    match store_config(&data.pool, *path, &cfg).await {
+    match store_config(&data.pool, *path, &mut cfg).await {
         Ok(()) => HttpResponse::NoContent().finish(),
         Err(e) => e,
     }
@@ -590,7 +1591,7 @@ async fn add_step(
                 return HttpResponse::BadRequest().finish();
             }
             cfg.steps.insert(input.index, input.step);
-            match store_config(&data.pool, *path, &cfg).await {
+            match store_config(&data.pool, *path, &mut cfg).await {
                 Ok(()) => HttpResponse::NoContent().finish(),
                 Err(e) => e,
             }
@@ -599,6 +1600,40 @@ async fn add_step(
     }
 }
 
+/// Inserts one step per prompt of a prompt group (in group order).
+async fn add_steps_from_group(
+    data: web::Data<AppState>,
+    path: web::Path<(Uuid, i32)>,
+    body: Option<web::Json<group_steps::GroupStepsInput>>,
+) -> impl Responder {
+    let (id, group_id) = path.into_inner();
+    let input = body.map(|b| b.into_inner()).unwrap_or_default();
+
+    let mut cfg = match fetch_config(&data.pool, id).await {
+        Ok(c) => c,
+        Err(e) => return e,
+    };
+    let index = input.index.unwrap_or(cfg.steps.len());
+    if index > cfg.steps.len() {
+        return HttpResponse::BadRequest().finish();
+    }
+    let prompts = match group_steps::load_group_prompts(&data.pool, group_id).await {
+        Ok(Some(p)) => p,
+        Ok(None) => return HttpResponse::NotFound().finish(),
+        Err(e) => {
+            error!(%id, group_id, "failed to load prompt group: {:#}", e);
+            return HttpResponse::InternalServerError().finish();
+        }
+    };
+
+    let steps = group_steps::build_steps(&prompts, &cfg.steps, &input);
+    cfg.steps.splice(index..index, steps.iter().cloned());
+    match store_config(&data.pool, id, &mut cfg).await {
+        Ok(()) => HttpResponse::Ok().json(json!({ "index": index, "steps": steps })),
+        Err(e) => e,
+    }
+}
+
 #[derive(Deserialize)]
 #[serde(rename_all = "camelCase")]
 struct StepPatch {
@@ -614,6 +1649,9 @@ struct StepPatch {
     active: Option<bool>,
     // z. B. { "min_signal": 0.5 }
     config: Option<Value>,
+    // null löst den Schritt von der Step-Library
+    #[serde(default, deserialize_with = "step_library::deserialize_patch")]
+    library: Option<Option<step_library::LibraryRef>>,
 }
 
 async fn update_step(
@@ -658,8 +1696,11 @@ async fn update_step(
     if let Some(v) = patch.config {
         step.config = Some(v);
     }
+    if let Some(v) = patch.library {
+        step.library = v;
+    }
 
-    match store_config(&data.pool, id, &cfg).await {
+    match store_config(&data.pool, id, &mut cfg).await {
         Ok(()) => HttpResponse::NoContent().finish(),
         Err(e) => e,
     }
@@ -683,7 +1724,7 @@ async fn delete_step(data: web::Data<AppState>, path: web::Path<(Uuid, String)>)
         return HttpResponse::NotFound().finish();
     }
 
-    match store_config(&data.pool, id, &cfg).await {
+    match store_config(&data.pool, id, &mut cfg).await {
         Ok(()) => HttpResponse::NoContent().finish(),
         Err(e) => e,
     }
@@ -724,15 +1765,223 @@ async fn reorder_steps(
 
     cfg.steps = new_steps;
 
-    match store_config(&data.pool, *path, &cfg).await {
+    match store_config(&data.pool, *path, &mut cfg).await {
         Ok(()) => HttpResponse::NoContent().finish(),
         Err(e) => e,
     }
 }
 
+#[derive(Deserialize)]
+struct EstimateInput {
+    #[serde(alias = "file_id")]
+    upload_id: i32,
+}
+
+/// Dry run: pages, batches, tokens, cost and duration of a run without model calls.
+async fn estimate_run(
+    data: web::Data<AppState>,
+    path: web::Path<Uuid>,
+    Json(input): web::Json<EstimateInput>,
+) -> HttpResponse {
+    let pdf_id: Option<i32> = match sqlx::query_scalar("SELECT pdf_id FROM uploads WHERE id=$1")
+        .bind(input.upload_id)
+        .fetch_optional(&data.pool)
+        .await
+    {
+        Ok(Some(pdf_id)) => pdf_id,
+        Ok(None) => return HttpResponse::NotFound().finish(),
+        Err(e) => {
+            error!(%e, "estimate: load upload failed");
+            return HttpResponse::InternalServerError().finish();
+        }
+    };
+    let Some(pdf_id) = pdf_id else {
+        return HttpResponse::Conflict().json(json!({ "error": "upload is not merged yet" }));
+    };
+    let cfg = match fetch_config(&data.pool, *path).await {
+        Ok(cfg) => cfg,
+        Err(resp) => return resp,
+    };
+
+    let loaded = async {
+        let pages = estimate::load_pages(&data.pool, pdf_id).await?;
+        let prompt_chars = estimate::load_prompt_chars(&data.pool, &cfg).await?;
+        let mut assumptions = estimate::Assumptions::load(&data.pool).await?;
+        assumptions.calibrate(&data.pool, *path).await?;
+        anyhow::Ok((pages, prompt_chars, assumptions))
+    }
+    .await;
+    let (pages, prompt_chars, assumptions) = match loaded {
+        Ok(loaded) => loaded,
+        Err(e) => {
+            error!(%e, pdf_id, "estimate: load inputs failed");
+            return HttpResponse::InternalServerError().finish();
+        }
+    };
+    if pages.is_empty() {
+        return HttpResponse::Conflict().json(json!({ "error": "text extraction not finished" }));
+    }
+
+    HttpResponse::Ok().json(estimate::estimate(&cfg, &pages, &prompt_chars, assumptions))
+}
+
+async fn put_run_review(
+    data: web::Data<AppState>,
+    path: web::Path<Uuid>,
+    Json(input): web::Json<calibration::ReviewInput>,
+) -> HttpResponse {
+    if input.verdict == ResultLabel::Review {
+        return HttpResponse::BadRequest()
+            .json(json!({ "error": "verdict must be approved or rejected" }));
+    }
+    let run_id = path.into_inner();
+    match calibration::store_review(&data.pool, run_id, &input).await {
+        Ok(true) => HttpResponse::NoContent().finish(),
+        Ok(false) => HttpResponse::NotFound().finish(),
+        Err(e) => {
+            error!(%e, %run_id, "store review failed");
+            HttpResponse::InternalServerError().finish()
+        }
+    }
+}
+
+/// Replaces the operator tags and note of a run.
+async fn put_run_notes(
+    data: web::Data<AppState>,
+    path: web::Path<Uuid>,
+    Json(input): web::Json<NoteInput>,
+) -> HttpResponse {
+    let input = input.normalized();
+    if let Err(e) = input.validate() {
+        return HttpResponse::BadRequest().json(json!({ "error": e }));
+    }
+    let run_id = path.into_inner();
+    match sqlx::query_as::<_, (Vec<String>, Option<String>, Option<String>, Option<String>)>(
+        operator_notes::UPSERT_RUN_NOTE_SQL,
+    )
+    .bind(run_id)
+    .bind(&input.tags)
+    .bind(&input.note)
+    .bind(&input.author)
+    .fetch_optional(&data.pool)
+    .await
+    {
+        Ok(Some((tags, note, updated_by, updated_at))) => HttpResponse::Ok().json(OperatorNote {
+            tags,
+            note,
+            updated_by,
+            updated_at,
+        }),
+        Ok(None) => HttpResponse::NotFound().finish(),
+        Err(e) => {
+            error!(%e, %run_id, "store run notes failed");
+            HttpResponse::InternalServerError().finish()
+        }
+    }
+}
+
+#[derive(Deserialize)]
+struct CalibrationQuery {
+    min_reviews: Option<usize>,
+}
+
+async fn create_calibration_report(
+    data: web::Data<AppState>,
+    path: web::Path<Uuid>,
+    query: web::Query<CalibrationQuery>,
+) -> HttpResponse {
+    let pipeline_id = path.into_inner();
+    let cfg = match fetch_config(&data.pool, pipeline_id).await {
+        Ok(cfg) => cfg,
+        Err(resp) => return resp,
+    };
+    let samples = match calibration::load_samples(&data.pool, pipeline_id).await {
+        Ok(samples) => samples,
+        Err(e) => {
+            error!(%e, %pipeline_id, "calibration: load reviews failed");
+            return HttpResponse::InternalServerError().finish();
+        }
+    };
+    let min_reviews = query
+        .min_reviews
+        .unwrap_or(calibration::DEFAULT_MIN_REVIEWS)
+        .max(1);
+    let report = calibration::analyze(pipeline_id, &cfg, &samples, min_reviews);
+    match calibration::store_report(&data.pool, &report).await {
+        Ok(id) => HttpResponse::Created().json(json!({
+            "id": id,
+            "has_suggestions": report.has_suggestions(),
+            "report": report,
+        })),
+        Err(e) => {
+            error!(%e, %pipeline_id, "calibration: store report failed");
+            HttpResponse::InternalServerError().finish()
+        }
+    }
+}
+
+async fn get_calibration_report(data: web::Data<AppState>, path: web::Path<Uuid>) -> HttpResponse {
+    let id = path.into_inner();
+    match calibration::load_report(&data.pool, id).await {
+        Ok(Some((report, created_at, applied_at))) => HttpResponse::Ok().json(json!({
+            "id": id,
+            "created_at": created_at,
+            "applied_at": applied_at,
+            "has_suggestions": report.has_suggestions(),
+            "report": report,
+        })),
+        Ok(None) => HttpResponse::NotFound().finish(),
+        Err(e) => {
+            error!(%e, %id, "calibration: load report failed");
+            HttpResponse::InternalServerError().finish()
+        }
+    }
+}
+
+async fn apply_calibration_report(
+    req: HttpRequest,
+    data: web::Data<AppState>,
+    path: web::Path<Uuid>,
+) -> HttpResponse {
+    if let Err(resp) = authorize_admin(&req, &data) {
+        return resp;
+    }
+    let id = path.into_inner();
+    let (report, applied_at) = match calibration::load_report(&data.pool, id).await {
+        Ok(Some((report, _, applied_at))) => (report, applied_at),
+        Ok(None) => return HttpResponse::NotFound().finish(),
+        Err(e) => {
+            error!(%e, %id, "calibration: load report failed");
+            return HttpResponse::InternalServerError().finish();
+        }
+    };
+    if applied_at.is_some() {
+        return HttpResponse::Conflict().json(json!({ "error": "report already applied" }));
+    }
+    let mut cfg = match fetch_config(&data.pool, report.pipeline_id).await {
+        Ok(cfg) => cfg,
+        Err(resp) => return resp,
+    };
+    // Erneutes Anwenden schreibt dieselben Werte, daher erst speichern, dann markieren
+    let changed = calibration::apply(&mut cfg, &report);
+    if changed > 0 {
+        if let Err(resp) = store_config(&data.pool, report.pipeline_id, &mut cfg).await {
+            return resp;
+        }
+    }
+    if let Err(e) = calibration::mark_applied(&data.pool, id).await {
+        error!(%e, %id, "calibration: mark applied failed");
+        return HttpResponse::InternalServerError().finish();
+    }
+    info!(%id, pipeline_id = %report.pipeline_id, changed, "calibration report applied");
+    HttpResponse::Ok().json(json!({ "id": id, "changed_steps": changed, "steps": cfg.steps }))
+}
+
 #[derive(Deserialize)]
 struct RunInput {
     file_id: i32,
+    #[serde(default)]
+    priority: RunPriority,
 }
 
 async fn run_pipeline(
@@ -758,10 +2007,23 @@ async fn run_pipeline(
         .bind(input.file_id)
         .execute(&data.pool)
         .await;
+    // Geparkte oder wartende Uploads gelten damit als ausgelöst (Spalten legt pdf-ingest an)
+    let _ = sqlx::query("UPDATE uploads SET run_state='triggered', run_priority=$1 WHERE id=$2")
+        .bind(input.priority.to_string())
+        .bind(input.file_id)
+        .execute(&data.pool)
+        .await;
 
+    let run_id = Uuid::new_v4();
     let payload = match serde_json::to_string(&PdfUploaded {
+        schema_version: PdfUploaded::SCHEMA_VERSION,
         pdf_id,
         pipeline_id: *path,
+        priority: input.priority,
+        rerun_of: None,
+        appended_from: None,
+        run_id: Some(run_id),
+        reocr: None,
     }) {
         Ok(p) => p,
         Err(_) => return HttpResponse::InternalServerError().finish(),
@@ -770,18 +2032,204 @@ async fn run_pipeline(
     let _ = data
         .producer
         .send(
-            FutureRecord::to("pipeline-run").payload(&payload).key(&()),
+            FutureRecord::to("pipeline-run")
+                .payload(&payload)
+                .key(&kafka::document_key(pdf_id)),
             Duration::from_secs(0),
         )
         .await;
 
     HttpResponse::Accepted().json(json!({
-        "status": "queued",
+        "status": RunStatus::Queued,
+        "run_id": run_id,
+        "pdf_id": pdf_id,
+        "pipeline_id": *path,
+        "priority": input.priority
+    }))
+}
+
+#[derive(Deserialize)]
+struct RerunQuery {
+    reuse: Option<String>,
+    #[serde(default)]
+    priority: RunPriority,
+}
+
+/// Queues a new run of the same PDF and pipeline that reuses the final
+/// extraction of run `id` and only executes scoring and decision steps again.
+async fn rerun_run(
+    data: web::Data<AppState>,
+    path: web::Path<Uuid>,
+    query: web::Query<RerunQuery>,
+) -> HttpResponse {
+    if query.reuse.as_deref() != Some("extraction") {
+        return HttpResponse::BadRequest().json(json!({
+            "error": "unsupported rerun mode, expected reuse=extraction",
+        }));
+    }
+
+    let source_run = path.into_inner();
+    let (pipeline_id, pdf_id, status) = match sqlx::query_as::<_, (Uuid, i32, Option<String>)>(
+        "SELECT pipeline_id, pdf_id, status FROM pipeline_runs WHERE id = $1",
+    )
+    .bind(source_run)
+    .fetch_optional(&data.pool)
+    .await
+    {
+        Ok(Some(row)) => row,
+        Ok(None) => return HttpResponse::NotFound().finish(),
+        Err(e) => {
+            error!("db error: {}", e);
+            return HttpResponse::InternalServerError().finish();
+        }
+    };
+    // Nur abgeschlossene Läufe haben eine vollständige Final-Extraction
+    if status.as_deref().and_then(RunStatus::parse_lenient) != Some(RunStatus::Completed) {
+        return HttpResponse::Conflict().json(json!({
+            "error": "only completed runs can be rerun with reused extraction",
+            "status": status,
+        }));
+    }
+
+    let run_id = Uuid::new_v4();
+    let payload = match serde_json::to_string(&PdfUploaded {
+        schema_version: PdfUploaded::SCHEMA_VERSION,
+        pdf_id,
+        pipeline_id,
+        priority: query.priority,
+        rerun_of: Some(source_run),
+        appended_from: None,
+        run_id: Some(run_id),
+        reocr: None,
+    }) {
+        Ok(p) => p,
+        Err(_) => return HttpResponse::InternalServerError().finish(),
+    };
+    if let Err((e, _)) = data
+        .producer
+        .send(
+            FutureRecord::to("pipeline-run")
+                .payload(&payload)
+                .key(&kafka::document_key(pdf_id)),
+            Duration::from_secs(0),
+        )
+        .await
+    {
+        error!(%source_run, "failed to queue rerun: {}", e);
+        return HttpResponse::ServiceUnavailable().finish();
+    }
+
+    info!(%source_run, "rerun with reused extraction queued");
+    HttpResponse::Accepted().json(json!({
+        "status": RunStatus::Queued,
+        "run_id": run_id,
+        "rerun_of": source_run,
+        "reuse": "extraction",
         "pdf_id": pdf_id,
-        "pipeline_id": *path
+        "pipeline_id": pipeline_id,
+        "priority": query.priority
+    }))
+}
+
+/// Moves a queued run ahead: its event is published again with priority
+/// `high` on `pipeline-run-priority`, which the runner reads before
+/// `pipeline-run`. The original event is skipped once the run has started.
+async fn prioritize_run(data: web::Data<AppState>, path: web::Path<Uuid>) -> HttpResponse {
+    let run_id = path.into_inner();
+    match sqlx::query_scalar::<_, Option<String>>("SELECT status FROM pipeline_runs WHERE id = $1")
+        .bind(run_id)
+        .fetch_optional(&data.pool)
+        .await
+    {
+        Ok(None) => {}
+        Ok(Some(status)) => {
+            return HttpResponse::Conflict().json(json!({
+                "error": "run already started",
+                "status": status,
+            }))
+        }
+        Err(e) => {
+            error!("db error: {}", e);
+            return HttpResponse::InternalServerError().finish();
+        }
+    }
+
+    let mut evt = match queue::find(&data.broker, &data.queue, run_id).await {
+        Ok(Some(queue::QueuedRun::Waiting(evt))) => evt,
+        Ok(Some(queue::QueuedRun::Prioritized)) => {
+            return HttpResponse::Ok().json(json!({
+                "status": RunStatus::Queued,
+                "run_id": run_id,
+                "priority": RunPriority::High,
+            }))
+        }
+        Ok(None) => {
+            return HttpResponse::NotFound().json(json!({ "error": "run not in queue" }));
+        }
+        Err(e) => {
+            error!(%e, %run_id, "run queue failed");
+            return HttpResponse::ServiceUnavailable().finish();
+        }
+    };
+    evt.priority = RunPriority::High;
+    let payload = match serde_json::to_string(&evt) {
+        Ok(p) => p,
+        Err(_) => return HttpResponse::InternalServerError().finish(),
+    };
+    if let Err((e, _)) = data
+        .producer
+        .send(
+            FutureRecord::to(PRIORITY_RUN_TOPIC)
+                .payload(&payload)
+                .key(&kafka::document_key(evt.pdf_id)),
+            Duration::from_secs(0),
+        )
+        .await
+    {
+        error!(%run_id, "failed to prioritize run: {}", e);
+        return HttpResponse::ServiceUnavailable().finish();
+    }
+
+    info!(%run_id, "queued run prioritized");
+    HttpResponse::Accepted().json(json!({
+        "status": RunStatus::Queued,
+        "run_id": run_id,
+        "pdf_id": evt.pdf_id,
+        "pipeline_id": evt.pipeline_id,
+        "priority": RunPriority::High,
     }))
 }
 
+async fn readyz(state: web::Data<AppState>) -> HttpResponse {
+    state.readiness.respond()
+}
+
+async fn get_status(state: web::Data<AppState>) -> HttpResponse {
+    let report = state.status.check(&state.pool, &state.producer).await;
+    if report.status == status::Light::Red {
+        HttpResponse::ServiceUnavailable().json(report)
+    } else {
+        HttpResponse::Ok().json(report)
+    }
+}
+
+#[derive(Deserialize)]
+struct QueueQuery {
+    pdf_id: Option<i32>,
+}
+
+/// Pending `pipeline-run` events, runs in flight and estimated start times;
+/// `?pdf_id=` keeps the entries of one document.
+async fn get_run_queue(state: web::Data<AppState>, q: web::Query<QueueQuery>) -> HttpResponse {
+    match queue::report(&state.pool, &state.broker, &state.queue, q.pdf_id).await {
+        Ok(report) => HttpResponse::Ok().json(report),
+        Err(e) => {
+            error!(%e, "run queue failed");
+            HttpResponse::InternalServerError().finish()
+        }
+    }
+}
+
 /* ------------------------------ main ------------------------------ */
 
 #[actix_web::main]
@@ -796,9 +2244,22 @@ async fn main() -> std::io::Result<()> {
         }
     };
 
-    let topics = ["pipeline-run", "pipeline-result"];
-    if let Err(e) = kafka::ensure_topics(&settings.message_broker_url, &topics).await {
-        warn!(%e, "failed to ensure kafka topics (continuing)");
+    let readiness = Readiness::new(&["postgres", "kafka"]);
+    let backoff = Backoff::from_env();
+
+    {
+        let broker = settings.message_broker_url.clone();
+        let readiness = readiness.clone();
+        actix_web::rt::spawn(async move {
+            let topics = [
+                "pipeline-run",
+                PRIORITY_RUN_TOPIC,
+                "pipeline-result",
+                app_settings::SETTINGS_CHANGED_TOPIC,
+            ];
+            retry_with_backoff("kafka", backoff, || kafka::ensure_topics(&broker, &topics)).await;
+            readiness.mark_ready("kafka");
+        });
     }
 
     let db_url = ensure_sslmode_disable(&settings.database_url);
@@ -806,44 +2267,72 @@ async fn main() -> std::io::Result<()> {
         warn!("DATABASE_URL had no sslmode – using '{}'", db_url);
     }
 
+    // Lazy pool: Verbindung wird im Hintergrund mit Backoff aufgebaut
     let pool = match PgPoolOptions::new()
         .max_connections(10)
         .acquire_timeout(Duration::from_secs(10))
-        .connect(&db_url)
-        .await
+        .connect_lazy(&db_url)
     {
         Ok(p) => p,
         Err(e) => {
-            error!(%e, "failed to connect to Postgres");
+            error!(%e, "invalid DATABASE_URL");
             std::process::exit(1);
         }
     };
 
-    init_db(&pool).await;
-
-    let producer: FutureProducer = match ClientConfig::new()
-        .set("bootstrap.servers", &settings.message_broker_url)
-        .create()
     {
-        Ok(p) => p,
-        Err(e) => {
-            error!(%e, "failed to create kafka producer");
-            std::process::exit(1);
-        }
-    };
+        let pool = pool.clone();
+        let readiness = readiness.clone();
+        actix_web::rt::spawn(async move {
+            retry_with_backoff("postgres", backoff, || {
+                sqlx::query("SELECT 1").execute(&pool)
+            })
+            .await;
+            init_db(&pool).await;
+            readiness.mark_ready("postgres");
+        });
+    }
+
+    let broker = settings.message_broker_url.clone();
+    let producer: FutureProducer = retry_with_backoff("kafka-producer", backoff, || async {
+        ClientConfig::new()
+            .set("bootstrap.servers", &broker)
+            .create::<FutureProducer>()
+    })
+    .await;
 
     let state = AppState {
         pool,
         producer,
         broker: settings.message_broker_url.clone(),
+        readiness,
+        admin_token: std::env::var("ADMIN_TOKEN").ok().filter(|t| !t.is_empty()),
+        master_key: match MasterKey::from_env() {
+            Ok(key) => Some(key),
+            Err(e) => {
+                warn!(%e, "tenant credential management disabled");
+                None
+            }
+        },
+        telemetry: match Telemetry::from_env("pipeline-api", &settings.message_broker_url) {
+            Ok(t) => t,
+            Err(e) => {
+                warn!(%e, "telemetry disabled");
+                Telemetry::disabled()
+            }
+        },
+        status: status::StatusChecker::from_env(),
+        queue: queue::QueueSettings::from_env(),
+        onboarding: onboarding::OnboardingSettings::from_env(),
     };
 
     info!("starting pipeline-api on 0.0.0.0:8084");
 
+    let cors = CorsSettings::from_env();
     HttpServer::new(move || {
         App::new()
             .app_data(web::Data::new(state.clone()))
-            .wrap(Cors::permissive())
+            .wrap(cors.actix())
             .route("/pipelines", web::get().to(list_pipelines))
             .route("/pipelines", web::post().to(create_pipeline))
             .service(
@@ -858,7 +2347,21 @@ async fn main() -> std::io::Result<()> {
             )
             .route("/pipelines/{id}/steps", web::put().to(add_step))
             .route("/pipelines/{id}/steps/order", web::put().to(reorder_steps))
+            .route(
+                "/pipelines/{id}/steps/from-group/{group_id}",
+                web::post().to(add_steps_from_group),
+            )
             .route("/pipelines/{id}/run", web::post().to(run_pipeline))
+            .route("/pipelines/{id}/estimate", web::post().to(estimate_run))
+            .route(
+                "/pipelines/{id}/calibration",
+                web::post().to(create_calibration_report),
+            )
+            .route("/calibration/{id}", web::get().to(get_calibration_report))
+            .route(
+                "/calibration/{id}/apply",
+                web::post().to(apply_calibration_report),
+            )
             .service(
                 web::resource("/pipelines/{id}/steps/{step_id}")
                     .route(web::patch().to(update_step))
@@ -869,7 +2372,66 @@ async fn main() -> std::io::Result<()> {
                     .route(web::get().to(get_openai_version))
                     .route(web::put().to(put_openai_version)),
             )
+            .route("/admin/settings", web::get().to(list_app_settings))
+            .service(
+                web::resource("/admin/settings/{key}")
+                    .route(web::get().to(get_app_setting))
+                    .route(web::put().to(put_app_setting))
+                    .route(web::delete().to(delete_app_setting)),
+            )
+            .route(
+                "/admin/settings/{key}/audit",
+                web::get().to(get_app_setting_audit),
+            )
+            .route("/admin/flags", web::get().to(list_flags))
+            .service(
+                web::resource("/admin/flags/{key}")
+                    .route(web::get().to(get_flag))
+                    .route(web::put().to(put_flag))
+                    .route(web::delete().to(delete_flag)),
+            )
+            .service(
+                web::resource("/admin/runner-settings")
+                    .route(web::get().to(get_runner_settings))
+                    .route(web::put().to(put_runner_settings)),
+            )
+            .route(
+                "/settings/tenant-credentials",
+                web::get().to(list_tenant_credentials),
+            )
+            .service(
+                web::resource("/settings/tenants/{tenant_id}/openai-credentials")
+                    .route(web::get().to(get_tenant_credentials))
+                    .route(web::put().to(put_tenant_credentials))
+                    .route(web::delete().to(delete_tenant_credentials)),
+            )
+            // vor /runs/{id}, sonst greift die UUID-Route
+            .route("/tenants/onboard", web::post().to(onboard_tenant))
+            .route("/runs/queue", web::get().to(get_run_queue))
             .route("/runs/{id}", web::get().to(get_run))
+            .route("/runs/{id}/steps", web::get().to(get_run_steps))
+            .route("/runs/{id}/evidence.zip", web::get().to(get_run_evidence))
+            .route("/runs/{id}/bundle.zip", web::get().to(get_run_bundle))
+            .route("/runs/{id}/config", web::get().to(get_run_config))
+            .route("/runs/{id}/rerun", web::post().to(rerun_run))
+            .route("/runs/{id}/prioritize", web::post().to(prioritize_run))
+            .route("/runs/{id}/review", web::put().to(put_run_review))
+            .route("/runs/{id}/notes", web::put().to(put_run_notes))
+            .route(
+                "/admin/runs/{id}/republish",
+                web::post().to(republish_run_result),
+            )
+            .route(
+                "/admin/runs/{id}/final-extraction",
+                web::get().to(get_sealed_final_extraction),
+            )
+            .route(
+                "/admin/run-status/backfill",
+                web::post().to(backfill_run_status),
+            )
+            .route("/admin/schema", web::get().to(get_schema))
+            .route("/readyz", web::get().to(readyz))
+            .route("/status", web::get().to(get_status))
     })
     .bind(("0.0.0.0", 8084))?
     .run()
@@ -0,0 +1,469 @@
+//! Onboarding of a tenant in one call (`POST /tenants/onboard`).
+//!
+//! A new tenant used to need the tenant itself and its result sinks
+//! (history-service), a pipeline, OpenAI credentials and flag overrides
+//! (pipeline-api) and automation rules for its SharePoint folders
+//! (sharepoint-ingest). [`onboard`] writes tenant, the copy of a template
+//! pipeline, flag overrides, credentials and automation rules in one
+//! transaction; all services share the database. Result sinks are created
+//! afterwards through history-service, which checks them and seals their
+//! secrets; if it rejects one, everything created before is removed again
+//! ([`offboard`]) and the call fails as a whole.
+
+use std::collections::{BTreeMap, HashSet};
+use std::env;
+use std::time::Duration;
+
+use reqwest::header::{AUTHORIZATION, CONTENT_TYPE};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use shared::dto::PipelineConfig;
+use shared::envelope::MasterKey;
+use shared::flags;
+use shared::tenant_credentials::{self, CredentialError, CredentialInput, CredentialSummary};
+use sqlx::{PgPool, Row};
+use tracing::{error, warn};
+use uuid::Uuid;
+
+#[derive(Debug, Clone)]
+pub struct OnboardingSettings {
+    /// Base URL of history-service (`HISTORY_SERVICE_URL`).
+    pub history_url: String,
+    client: reqwest::Client,
+}
+
+impl OnboardingSettings {
+    pub fn from_env() -> Self {
+        let history_url = env::var("HISTORY_SERVICE_URL")
+            .ok()
+            .map(|v| v.trim().trim_end_matches('/').to_string())
+            .filter(|v| !v.is_empty())
+            .unwrap_or_else(|| "http://history-service:8090".to_string());
+        let client = reqwest::Client::builder()
+            .timeout(Duration::from_secs(10))
+            .build()
+            .unwrap_or_default();
+        Self {
+            history_url,
+            client,
+        }
+    }
+}
+
+/// Body of `POST /tenants/onboard`; everything except `name` is optional.
+#[derive(Debug, Deserialize)]
+pub struct OnboardRequest {
+    pub name: String,
+    #[serde(default)]
+    pub pipeline: Option<PipelineTemplate>,
+    /// Tenant overrides of feature flags.
+    #[serde(default)]
+    pub flags: BTreeMap<String, bool>,
+    #[serde(default)]
+    pub openai: Option<CredentialInput>,
+    #[serde(default)]
+    pub automation: Option<AutomationDefaults>,
+    /// Bodies of `POST /tenants/{id}/sinks` (history-service).
+    #[serde(default)]
+    pub sinks: Vec<Value>,
+    #[serde(default)]
+    pub changed_by: Option<String>,
+}
+
+/// Existing pipeline copied as the tenant's default pipeline.
+#[derive(Debug, Deserialize)]
+pub struct PipelineTemplate {
+    pub template_id: Uuid,
+    /// Name of the copy, default `<template> – <tenant>`.
+    #[serde(default)]
+    pub name: Option<String>,
+}
+
+/// SharePoint folders handled automatically for the tenant.
+#[derive(Debug, Deserialize)]
+pub struct AutomationDefaults {
+    pub folders: Vec<AutomationFolder>,
+    #[serde(default = "default_true")]
+    pub auto_ingest: bool,
+    /// Starts the default pipeline after the ingest; needs `pipeline`.
+    #[serde(default)]
+    pub auto_pipeline: bool,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct AutomationFolder {
+    pub folder_id: String,
+    pub folder_name: String,
+}
+
+fn default_true() -> bool {
+    true
+}
+
+#[derive(Debug, Serialize)]
+pub struct TenantRef {
+    pub id: Uuid,
+    pub name: String,
+}
+
+#[derive(Debug, Serialize)]
+pub struct PipelineRef {
+    pub id: Uuid,
+    pub name: String,
+}
+
+/// Response of `POST /tenants/onboard`.
+#[derive(Debug, Serialize)]
+pub struct Onboarded {
+    pub tenant: TenantRef,
+    pub pipeline: Option<PipelineRef>,
+    pub flags: BTreeMap<String, bool>,
+    pub openai: Option<CredentialSummary>,
+    /// Folder ids with an automation rule.
+    pub automation: Vec<String>,
+    /// Sinks as returned by history-service.
+    pub sinks: Vec<Value>,
+}
+
+#[derive(Debug)]
+pub enum OnboardError {
+    /// Rejected request (400).
+    Invalid(String),
+    /// Tenant, pipeline name or folder already taken (409).
+    Conflict(String),
+    /// `CREDENTIALS_MASTER_KEY` missing for `openai` (503).
+    Unavailable(String),
+    /// history-service rejected a sink or was not reachable; the tenant was
+    /// removed again. Carries its status (502 if unreachable).
+    Sink {
+        status: u16,
+        error: String,
+    },
+    Db(sqlx::Error),
+}
+
+impl From<sqlx::Error> for OnboardError {
+    fn from(e: sqlx::Error) -> Self {
+        OnboardError::Db(e)
+    }
+}
+
+impl From<CredentialError> for OnboardError {
+    fn from(e: CredentialError) -> Self {
+        match e {
+            CredentialError::Invalid(msg) => OnboardError::Invalid(format!("openai: {msg}")),
+            CredentialError::Db(e) => OnboardError::Db(e),
+            other => OnboardError::Invalid(format!("openai: {other}")),
+        }
+    }
+}
+
+fn unique_violation(e: &sqlx::Error) -> bool {
+    e.as_database_error()
+        .and_then(|d| d.code())
+        .is_some_and(|code| code == "23505")
+}
+
+/// Checks the request before anything is written.
+pub fn validate(req: &OnboardRequest) -> Result<(), String> {
+    if req.name.trim().is_empty() {
+        return Err("name must not be empty".into());
+    }
+    if let Some(key) = req.flags.keys().find(|k| flags::known(k).is_none()) {
+        return Err(format!("unknown flag {key}"));
+    }
+    if let Some(automation) = &req.automation {
+        if automation.auto_pipeline && req.pipeline.is_none() {
+            return Err("automation.auto_pipeline needs a pipeline".into());
+        }
+        let mut seen = HashSet::new();
+        for folder in &automation.folders {
+            let id = folder.folder_id.trim();
+            if id.is_empty() || folder.folder_name.trim().is_empty() {
+                return Err("automation folders need folder_id and folder_name".into());
+            }
+            if !seen.insert(id) {
+                return Err(format!("folder {id} listed twice"));
+            }
+        }
+    }
+    if req.sinks.iter().any(|s| !s.is_object()) {
+        return Err("sinks must be objects".into());
+    }
+    Ok(())
+}
+
+/// What [`offboard`] removes when a sink is rejected.
+struct Created {
+    tenant_id: Uuid,
+    pipeline_id: Option<Uuid>,
+    folder_ids: Vec<String>,
+    flags: Vec<String>,
+    openai: bool,
+}
+
+/// Creates the tenant with all requested defaults (see module docs).
+/// `authorization` is forwarded to history-service.
+pub async fn onboard(
+    pool: &PgPool,
+    master: Option<&MasterKey>,
+    settings: &OnboardingSettings,
+    authorization: Option<&str>,
+    req: OnboardRequest,
+) -> Result<Onboarded, OnboardError> {
+    validate(&req).map_err(OnboardError::Invalid)?;
+    let master = match (&req.openai, master) {
+        (Some(_), None) => {
+            return Err(OnboardError::Unavailable(
+                "CREDENTIALS_MASTER_KEY is not configured".into(),
+            ))
+        }
+        (_, master) => master,
+    };
+    let name = req.name.trim().to_string();
+    let changed_by = req
+        .changed_by
+        .as_deref()
+        .map(str::trim)
+        .filter(|c| !c.is_empty());
+
+    let mut tx = pool.begin().await?;
+    let tenant_id: Uuid = sqlx::query_scalar(
+        "INSERT INTO tenants (name) VALUES ($1) ON CONFLICT (name) DO NOTHING RETURNING id",
+    )
+    .bind(&name)
+    .fetch_optional(&mut *tx)
+    .await?
+    .ok_or_else(|| OnboardError::Conflict(format!("tenant {name} already exists")))?;
+
+    let pipeline = match &req.pipeline {
+        Some(template) => {
+            let row = sqlx::query("SELECT config_json FROM pipelines WHERE id = $1")
+                .bind(template.template_id)
+                .fetch_optional(&mut *tx)
+                .await?
+                .ok_or_else(|| {
+                    OnboardError::Invalid(format!(
+                        "unknown template pipeline {}",
+                        template.template_id
+                    ))
+                })?;
+            let mut cfg: PipelineConfig = serde_json::from_value(row.get("config_json"))
+                .map_err(|e| OnboardError::Invalid(format!("template pipeline unreadable: {e}")))?;
+            cfg.name = template
+                .name
+                .as_deref()
+                .map(str::trim)
+                .filter(|n| !n.is_empty())
+                .map(str::to_string)
+                .unwrap_or_else(|| format!("{} – {name}", cfg.name));
+            cfg.steps
+                .iter_mut()
+                .for_each(|step| step.id = Uuid::new_v4());
+            let id = Uuid::new_v4();
+            let config = serde_json::to_value(&cfg)
+                .map_err(|e| OnboardError::Invalid(format!("template pipeline: {e}")))?;
+            sqlx::query("INSERT INTO pipelines (id, name, config_json) VALUES ($1, $2, $3)")
+                .bind(id)
+                .bind(&cfg.name)
+                .bind(config)
+                .execute(&mut *tx)
+                .await
+                .map_err(|e| {
+                    if unique_violation(&e) {
+                        OnboardError::Conflict(format!("pipeline {} already exists", cfg.name))
+                    } else {
+                        OnboardError::Db(e)
+                    }
+                })?;
+            Some(PipelineRef { id, name: cfg.name })
+        }
+        None => None,
+    };
+
+    for (key, enabled) in &req.flags {
+        if let Some(flag) = flags::known(key) {
+            flags::store_in(&mut tx, flag, Some(tenant_id), Some(*enabled), changed_by).await?;
+        }
+    }
+
+    let openai = match (req.openai, master) {
+        (Some(input), Some(master)) => {
+            Some(tenant_credentials::store(&mut *tx, master, tenant_id, input).await?)
+        }
+        _ => None,
+    };
+
+    let mut folder_ids = Vec::new();
+    if let Some(automation) = &req.automation {
+        let pipeline_id = pipeline.as_ref().map(|p| p.id);
+        for folder in &automation.folders {
+            let folder_id = folder.folder_id.trim();
+            let inserted = sqlx::query(
+                "INSERT INTO sharepoint_automation
+                    (folder_id, folder_name, tenant_id, pipeline_id, auto_ingest, auto_pipeline)
+                 VALUES ($1, $2, $3, $4, $5, $6)
+                 ON CONFLICT (folder_id) DO NOTHING",
+            )
+            .bind(folder_id)
+            .bind(folder.folder_name.trim())
+            .bind(tenant_id)
+            .bind(pipeline_id)
+            .bind(automation.auto_ingest)
+            .bind(automation.auto_pipeline)
+            .execute(&mut *tx)
+            .await?;
+            if inserted.rows_affected() == 0 {
+                return Err(OnboardError::Conflict(format!(
+                    "folder {folder_id} already has an automation rule"
+                )));
+            }
+            folder_ids.push(folder_id.to_string());
+        }
+    }
+    tx.commit().await?;
+
+    let created = Created {
+        tenant_id,
+        pipeline_id: pipeline.as_ref().map(|p| p.id),
+        folder_ids: folder_ids.clone(),
+        flags: req.flags.keys().cloned().collect(),
+        openai: openai.is_some(),
+    };
+    let mut sinks = Vec::new();
+    for sink in &req.sinks {
+        match create_sink(settings, authorization, tenant_id, sink).await {
+            Ok(created) => sinks.push(created),
+            Err((status, error)) => {
+                if let Err(e) = offboard(pool, &created, changed_by).await {
+                    error!(%e, %tenant_id, "removing partially onboarded tenant failed");
+                }
+                return Err(OnboardError::Sink { status, error });
+            }
+        }
+    }
+
+    Ok(Onboarded {
+        tenant: TenantRef {
+            id: tenant_id,
+            name,
+        },
+        pipeline,
+        flags: req.flags,
+        openai,
+        automation: folder_ids,
+        sinks,
+    })
+}
+
+/// `POST /tenants/{id}/sinks` on history-service; on failure its status (502
+/// if unreachable) and error text.
+async fn create_sink(
+    settings: &OnboardingSettings,
+    authorization: Option<&str>,
+    tenant_id: Uuid,
+    sink: &Value,
+) -> Result<Value, (u16, String)> {
+    let url = format!("{}/tenants/{tenant_id}/sinks", settings.history_url);
+    let mut request = settings
+        .client
+        .post(&url)
+        .header(CONTENT_TYPE, "application/json")
+        .body(sink.to_string());
+    if let Some(auth) = authorization {
+        request = request.header(AUTHORIZATION, auth);
+    }
+    let res = request.send().await.map_err(|e| {
+        warn!(%e, %url, "history-service not reachable");
+        (502, format!("history-service not reachable: {e}"))
+    })?;
+    let status = res.status();
+    let body = res.bytes().await.unwrap_or_default();
+    if !status.is_success() {
+        let text = String::from_utf8_lossy(&body).trim().to_string();
+        let name = sink.get("name").and_then(Value::as_str).unwrap_or("?");
+        return Err((status.as_u16(), format!("sink {name}: {text}")));
+    }
+    serde_json::from_slice(&body).map_err(|e| (502, format!("invalid sink response: {e}")))
+}
+
+/// Removes what [`onboard`] committed; the tenant goes last and takes its
+/// sinks with it (`ON DELETE CASCADE`).
+async fn offboard(
+    pool: &PgPool,
+    created: &Created,
+    changed_by: Option<&str>,
+) -> Result<(), sqlx::Error> {
+    let mut tx = pool.begin().await?;
+    sqlx::query("DELETE FROM sharepoint_automation WHERE folder_id = ANY($1) AND tenant_id = $2")
+        .bind(&created.folder_ids)
+        .bind(created.tenant_id)
+        .execute(&mut *tx)
+        .await?;
+    for key in &created.flags {
+        if let Some(flag) = flags::known(key) {
+            flags::store_in(&mut tx, flag, Some(created.tenant_id), None, changed_by).await?;
+        }
+    }
+    if created.openai {
+        sqlx::query("DELETE FROM app_settings WHERE key = $1")
+            .bind(tenant_credentials::settings_key(created.tenant_id))
+            .execute(&mut *tx)
+            .await?;
+    }
+    if let Some(pipeline_id) = created.pipeline_id {
+        sqlx::query("DELETE FROM pipelines WHERE id = $1")
+            .bind(pipeline_id)
+            .execute(&mut *tx)
+            .await?;
+    }
+    sqlx::query("DELETE FROM tenants WHERE id = $1")
+        .bind(created.tenant_id)
+        .execute(&mut *tx)
+        .await?;
+    tx.commit().await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn request(body: Value) -> OnboardRequest {
+        serde_json::from_value(body).unwrap()
+    }
+
+    #[test]
+    fn validates_before_writing() {
+        let full = request(json!({
+            "name": "Kanzlei Müller",
+            "pipeline": {"template_id": Uuid::nil()},
+            "flags": {"runner.structured_outputs": false},
+            "automation": {
+                "folders": [{"folder_id": "01AB", "folder_name": "Eingang Müller"}],
+                "auto_pipeline": true
+            },
+            "sinks": [{"name": "crm", "kind": "http", "target": "https://crm.local/hook"}]
+        }));
+        assert_eq!(validate(&full), Ok(()));
+        assert!(full.automation.as_ref().unwrap().auto_ingest);
+
+        let blank = request(json!({"name": "  "}));
+        assert_eq!(validate(&blank), Err("name must not be empty".into()));
+        let flag = request(json!({"name": "A", "flags": {"runner.nope": true}}));
+        assert_eq!(validate(&flag), Err("unknown flag runner.nope".into()));
+        let no_pipeline = request(json!({
+            "name": "A",
+            "automation": {"folders": [], "auto_pipeline": true}
+        }));
+        assert!(validate(&no_pipeline).is_err());
+        let twice = request(json!({
+            "name": "A",
+            "automation": {"folders": [
+                {"folder_id": "x", "folder_name": "X"},
+                {"folder_id": "x ", "folder_name": "X2"}
+            ]}
+        }));
+        assert_eq!(validate(&twice), Err("folder x listed twice".into()));
+    }
+}
@@ -0,0 +1,546 @@
+//! Visibility into the run queue (`GET /runs/queue`).
+//!
+//! Runs are requested through `pipeline-run` events that the pipeline runner
+//! consumes one after another. Until the runner gets to an event nothing in
+//! Postgres mentions the run, so a document can look stuck while it is only
+//! waiting. The queue view reads the committed offsets of the runner's consumer
+//! group, decodes the events between them and the end of the topic into
+//! pdf/pipeline pairs (at most `QUEUE_MAX_EVENTS`), lists the runs in flight and
+//! estimates start times from the runs finished during the last
+//! `QUEUE_THROUGHPUT_WINDOW_MINS`. Reading never joins the group and never
+//! commits, so the runner is not disturbed.
+//!
+//! Runs moved ahead by `POST /runs/{id}/prioritize` wait on
+//! `pipeline-run-priority`, which the runner reads first; they lead the queue.
+//! Their original event stays in `pipeline-run` and is skipped by the runner
+//! (and here) because the run id is taken.
+
+use std::collections::{HashMap, HashSet};
+use std::env;
+use std::time::{Duration, Instant};
+
+use anyhow::{Context, Result};
+use chrono::{DateTime, Utc};
+use rdkafka::consumer::{BaseConsumer, Consumer};
+use rdkafka::{ClientConfig, Message, Offset, TopicPartitionList};
+use serde::Serialize;
+use shared::dto::{PdfUploaded, RunPriority, PRIORITY_RUN_TOPIC};
+use sqlx::{PgPool, Row};
+use uuid::Uuid;
+
+const TOPIC: &str = "pipeline-run";
+
+#[derive(Debug, Clone)]
+pub struct QueueSettings {
+    /// Consumer group of the pipeline runner.
+    pub group: String,
+    pub max_events: usize,
+    pub window_mins: i32,
+    /// Budget for all Kafka requests of one call.
+    pub timeout: Duration,
+}
+
+impl QueueSettings {
+    pub fn from_env() -> Self {
+        let parse = |name: &str, default: u64| {
+            env::var(name)
+                .ok()
+                .and_then(|v| v.trim().parse::<u64>().ok())
+                .unwrap_or(default)
+        };
+        Self {
+            group: env::var("QUEUE_CONSUMER_GROUP")
+                .ok()
+                .filter(|g| !g.trim().is_empty())
+                .unwrap_or_else(|| "pipeline-runner".to_string()),
+            max_events: parse("QUEUE_MAX_EVENTS", 500) as usize,
+            window_mins: parse("QUEUE_THROUGHPUT_WINDOW_MINS", 60).clamp(1, 24 * 60) as i32,
+            timeout: Duration::from_millis(parse("QUEUE_KAFKA_TIMEOUT_MS", 3000)),
+        }
+    }
+}
+
+/// Event waiting for the runner.
+#[derive(Debug, Clone, Serialize)]
+pub struct PendingRun {
+    /// 1-based; the runner takes events in this order.
+    pub position: usize,
+    /// Id the run will get, `None` for events of older producers.
+    pub run_id: Option<Uuid>,
+    /// Waits on `pipeline-run-priority`.
+    pub prioritized: bool,
+    pub pdf_id: Option<i32>,
+    pub pipeline_id: Option<Uuid>,
+    pub priority: Option<RunPriority>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub rerun_of: Option<Uuid>,
+    pub enqueued_at: Option<DateTime<Utc>>,
+    pub partition: i32,
+    pub offset: i64,
+    /// `None` while there is no throughput to extrapolate from.
+    pub estimated_start: Option<DateTime<Utc>>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct InFlightRun {
+    pub run_id: Uuid,
+    pub pdf_id: Option<i32>,
+    pub pipeline_id: Uuid,
+    pub started_at: Option<DateTime<Utc>>,
+    pub running_secs: Option<u64>,
+}
+
+/// Finished runs (completed, failed or timed out) of the window.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+pub struct Throughput {
+    pub window_mins: i32,
+    pub finished: i64,
+    pub runs_per_hour: f64,
+    pub avg_duration_secs: Option<f64>,
+}
+
+impl Throughput {
+    fn seconds_per_run(&self) -> Option<f64> {
+        (self.finished > 0).then(|| self.window_mins as f64 * 60.0 / self.finished as f64)
+    }
+}
+
+/// Body of `GET /runs/queue`.
+#[derive(Debug, Clone, Serialize)]
+pub struct QueueReport {
+    pub checked_at: DateTime<Utc>,
+    pub consumer_group: String,
+    /// Events not yet consumed by the runner; `None` if Kafka was not readable.
+    pub lag: Option<i64>,
+    pub pending: Vec<PendingRun>,
+    /// More events are waiting than `QUEUE_MAX_EVENTS` allows to decode.
+    pub truncated: bool,
+    pub in_flight: Vec<InFlightRun>,
+    pub throughput: Throughput,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+/// Raw event read from the topic.
+#[derive(Debug)]
+struct QueuedEvent {
+    prioritized: bool,
+    partition: i32,
+    offset: i64,
+    timestamp_ms: Option<i64>,
+    event: Option<PdfUploaded>,
+}
+
+struct Backlog {
+    lag: i64,
+    events: Vec<QueuedEvent>,
+}
+
+/// Collects the queue; `pdf_id` keeps only the entries of one document (the
+/// positions stay those of the whole queue).
+pub async fn report(
+    pool: &PgPool,
+    broker: &str,
+    settings: &QueueSettings,
+    pdf_id: Option<i32>,
+) -> Result<QueueReport> {
+    let now = Utc::now();
+    let backlog = {
+        let broker = broker.to_string();
+        let settings = settings.clone();
+        tokio::task::spawn_blocking(move || read_backlogs(&broker, &settings))
+            .await
+            .context("queue reader panicked")?
+    };
+    let in_flight = load_in_flight(pool).await?;
+    let throughput = load_throughput(pool, settings.window_mins).await?;
+
+    let (lag, events, error) = match backlog {
+        Ok(backlog) => (Some(backlog.lag), backlog.events, None),
+        Err(e) => (None, Vec::new(), Some(format!("{e:#}"))),
+    };
+    let truncated = lag.is_some_and(|lag| lag > events.len() as i64);
+    let started = load_started(pool, &events).await?;
+    let pending = pending_runs(events, &started, &throughput, now)
+        .into_iter()
+        .filter(|run| pdf_id.is_none() || run.pdf_id == pdf_id)
+        .collect();
+    let in_flight = in_flight
+        .into_iter()
+        .filter(|run| pdf_id.is_none() || run.pdf_id == pdf_id)
+        .collect();
+    Ok(QueueReport {
+        checked_at: now,
+        consumer_group: settings.group.clone(),
+        lag,
+        pending,
+        truncated,
+        in_flight,
+        throughput,
+        error,
+    })
+}
+
+/// Orders the events as the runner will see them and estimates their start:
+/// with `n` runs finished per window, position `p` starts after `p` further
+/// runs have finished. Events of `started` runs and the second event of a
+/// prioritized run are dropped.
+fn pending_runs(
+    mut events: Vec<QueuedEvent>,
+    started: &HashSet<Uuid>,
+    throughput: &Throughput,
+    now: DateTime<Utc>,
+) -> Vec<PendingRun> {
+    events.sort_by_key(|e| {
+        (
+            !e.prioritized,
+            e.timestamp_ms.unwrap_or(i64::MAX),
+            e.partition,
+            e.offset,
+        )
+    });
+    let mut seen = HashSet::new();
+    events.retain(|e| match e.event.as_ref().and_then(|ev| ev.run_id) {
+        Some(run_id) => !started.contains(&run_id) && seen.insert(run_id),
+        None => true,
+    });
+    let per_run = throughput.seconds_per_run();
+    events
+        .into_iter()
+        .enumerate()
+        .map(|(i, e)| {
+            let position = i + 1;
+            let estimated_start = per_run.map(|secs| {
+                now + chrono::Duration::seconds((secs * position as f64).round() as i64)
+            });
+            PendingRun {
+                position,
+                run_id: e.event.as_ref().and_then(|ev| ev.run_id),
+                prioritized: e.prioritized,
+                pdf_id: e.event.as_ref().map(|ev| ev.pdf_id),
+                pipeline_id: e.event.as_ref().map(|ev| ev.pipeline_id),
+                priority: e.event.as_ref().map(|ev| ev.priority),
+                rerun_of: e.event.as_ref().and_then(|ev| ev.rerun_of),
+                enqueued_at: e.timestamp_ms.and_then(DateTime::from_timestamp_millis),
+                partition: e.partition,
+                offset: e.offset,
+                estimated_start,
+            }
+        })
+        .collect()
+}
+
+/// Where [`find`] found a queued run.
+pub enum QueuedRun {
+    /// Event on `pipeline-run`.
+    Waiting(PdfUploaded),
+    /// Already on `pipeline-run-priority`.
+    Prioritized,
+}
+
+/// Looks up the waiting event of `run_id` among the decoded events of both
+/// topics.
+pub async fn find(
+    broker: &str,
+    settings: &QueueSettings,
+    run_id: Uuid,
+) -> Result<Option<QueuedRun>> {
+    let broker = broker.to_string();
+    let settings = settings.clone();
+    let backlog = tokio::task::spawn_blocking(move || read_backlogs(&broker, &settings))
+        .await
+        .context("queue reader panicked")??;
+    let mut found = None;
+    for queued in backlog.events {
+        match queued.event {
+            Some(event) if event.run_id == Some(run_id) => {
+                if queued.prioritized {
+                    return Ok(Some(QueuedRun::Prioritized));
+                }
+                found = Some(QueuedRun::Waiting(event));
+            }
+            _ => {}
+        }
+    }
+    Ok(found)
+}
+
+/// Backlog of `pipeline-run-priority` and `pipeline-run` (blocking).
+fn read_backlogs(broker: &str, settings: &QueueSettings) -> Result<Backlog> {
+    let mut backlog = read_backlog(broker, settings, PRIORITY_RUN_TOPIC)?;
+    let regular = read_backlog(broker, settings, TOPIC)?;
+    backlog.lag += regular.lag;
+    backlog.events.extend(regular.events);
+    Ok(backlog)
+}
+
+/// Reads the events between the committed offsets of the runner group and the
+/// end of `topic` (blocking).
+fn read_backlog(broker: &str, settings: &QueueSettings, topic: &str) -> Result<Backlog> {
+    let consumer: BaseConsumer = ClientConfig::new()
+        .set("bootstrap.servers", broker)
+        .set("group.id", &settings.group)
+        // Nur lesen: ohne subscribe() kein Gruppenbeitritt, ohne Commit keine Offsets
+        .set("enable.auto.commit", "false")
+        .set("enable.auto.offset.store", "false")
+        .create()
+        .context("create queue consumer")?;
+    let timeout = settings.timeout;
+    let metadata = consumer
+        .fetch_metadata(Some(topic), timeout)
+        .context("topic metadata")?;
+    let partitions: Vec<i32> = metadata
+        .topics()
+        .iter()
+        .flat_map(|t| t.partitions().iter().map(|p| p.id()))
+        .collect();
+    let mut list = TopicPartitionList::new();
+    for partition in &partitions {
+        list.add_partition(topic, *partition);
+    }
+    let committed = consumer
+        .committed_offsets(list, timeout)
+        .context("committed offsets")?;
+
+    let mut lag = 0;
+    let mut ends = HashMap::new();
+    let mut assignment = TopicPartitionList::new();
+    for partition in partitions {
+        let (low, high) = consumer
+            .fetch_watermarks(topic, partition, timeout)
+            .context("watermarks")?;
+        // Ohne Commit beginnt der Runner am Ende (auto.offset.reset=latest)
+        let start = match committed
+            .find_partition(topic, partition)
+            .map(|e| e.offset())
+        {
+            Some(Offset::Offset(offset)) => offset.max(low),
+            _ => high,
+        };
+        if high > start {
+            lag += high - start;
+            ends.insert(partition, high);
+            assignment
+                .add_partition_offset(topic, partition, Offset::Offset(start))
+                .context("assign partition")?;
+        }
+    }
+    if ends.is_empty() {
+        return Ok(Backlog {
+            lag,
+            events: Vec::new(),
+        });
+    }
+    consumer.assign(&assignment).context("assign")?;
+
+    let deadline = Instant::now() + timeout;
+    let mut events = Vec::new();
+    while !ends.is_empty() && events.len() < settings.max_events && Instant::now() < deadline {
+        let Some(message) = consumer.poll(Duration::from_millis(100)) else {
+            continue;
+        };
+        let message = message.context("read queue")?;
+        let (partition, offset) = (message.partition(), message.offset());
+        let Some(&end) = ends.get(&partition) else {
+            continue;
+        };
+        if offset + 1 >= end {
+            ends.remove(&partition);
+        }
+        if offset >= end {
+            continue;
+        }
+        events.push(QueuedEvent {
+            prioritized: topic == PRIORITY_RUN_TOPIC,
+            partition,
+            offset,
+            timestamp_ms: message.timestamp().to_millis(),
+            event: message
+                .payload()
+                .and_then(|payload| serde_json::from_slice(payload).ok()),
+        });
+    }
+    Ok(Backlog { lag, events })
+}
+
+/// Ids of queued runs that the runner already started.
+async fn load_started(pool: &PgPool, events: &[QueuedEvent]) -> Result<HashSet<Uuid>> {
+    let ids: Vec<Uuid> = events
+        .iter()
+        .filter_map(|e| e.event.as_ref().and_then(|ev| ev.run_id))
+        .collect();
+    if ids.is_empty() {
+        return Ok(HashSet::new());
+    }
+    let started: Vec<Uuid> = sqlx::query_scalar("SELECT id FROM pipeline_runs WHERE id = ANY($1)")
+        .bind(&ids)
+        .fetch_all(pool)
+        .await
+        .context("load started runs")?;
+    Ok(started.into_iter().collect())
+}
+
+async fn load_in_flight(pool: &PgPool) -> Result<Vec<InFlightRun>> {
+    let rows = sqlx::query(
+        "SELECT id, pdf_id, pipeline_id,
+                EXTRACT(EPOCH FROM started_at)::FLOAT8 AS started,
+                EXTRACT(EPOCH FROM now() - started_at)::FLOAT8 AS running
+           FROM pipeline_runs
+          WHERE status = 'running'
+          ORDER BY started_at NULLS LAST",
+    )
+    .fetch_all(pool)
+    .await
+    .context("load running runs")?;
+    Ok(rows
+        .iter()
+        .map(|row| {
+            let started: Option<f64> = row.get("started");
+            let running: Option<f64> = row.get("running");
+            InFlightRun {
+                run_id: row.get("id"),
+                pdf_id: row.get("pdf_id"),
+                pipeline_id: row.get("pipeline_id"),
+                started_at: started
+                    .and_then(|secs| DateTime::from_timestamp_millis((secs * 1000.0) as i64)),
+                running_secs: running.map(|secs| secs.max(0.0) as u64),
+            }
+        })
+        .collect())
+}
+
+async fn load_throughput(pool: &PgPool, window_mins: i32) -> Result<Throughput> {
+    let row = sqlx::query(
+        "SELECT COUNT(*) AS finished,
+                AVG(EXTRACT(EPOCH FROM finished_at - started_at))::FLOAT8 AS avg_secs
+           FROM pipeline_runs
+          WHERE status IN ('completed', 'failed', 'timeout')
+            AND finished_at >= now() - make_interval(mins => $1)",
+    )
+    .bind(window_mins)
+    .fetch_one(pool)
+    .await
+    .context("load throughput")?;
+    let finished: i64 = row.get("finished");
+    Ok(Throughput {
+        window_mins,
+        finished,
+        runs_per_hour: finished as f64 * 60.0 / window_mins as f64,
+        avg_duration_secs: row.get("avg_secs"),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn event(partition: i32, offset: i64, timestamp_ms: i64, pdf_id: i32) -> QueuedEvent {
+        QueuedEvent {
+            prioritized: false,
+            partition,
+            offset,
+            timestamp_ms: Some(timestamp_ms),
+            event: Some(PdfUploaded {
+                schema_version: PdfUploaded::SCHEMA_VERSION,
+                pdf_id,
+                pipeline_id: Uuid::nil(),
+                priority: RunPriority::default(),
+                rerun_of: None,
+                appended_from: None,
+                run_id: None,
+                reocr: None,
+            }),
+        }
+    }
+
+    #[test]
+    fn orders_events_and_extrapolates_start() {
+        let now = DateTime::from_timestamp(1_700_000_000, 0).unwrap();
+        // 12 Läufe pro Stunde → alle 300 s einer
+        let throughput = Throughput {
+            window_mins: 60,
+            finished: 12,
+            runs_per_hour: 12.0,
+            avg_duration_secs: Some(280.0),
+        };
+        let undecodable = QueuedEvent {
+            event: None,
+            ..event(0, 9, 3_000, 0)
+        };
+        let runs = pending_runs(
+            vec![event(1, 4, 2_000, 7), event(0, 8, 1_000, 3), undecodable],
+            &HashSet::new(),
+            &throughput,
+            now,
+        );
+        assert_eq!(
+            runs.iter().map(|r| r.pdf_id).collect::<Vec<_>>(),
+            vec![Some(3), Some(7), None]
+        );
+        assert_eq!(runs[0].position, 1);
+        assert_eq!(
+            runs[1].estimated_start,
+            Some(now + chrono::Duration::seconds(600))
+        );
+        assert_eq!(runs[0].enqueued_at, DateTime::from_timestamp_millis(1_000));
+    }
+
+    #[test]
+    fn no_estimate_without_throughput() {
+        let idle = Throughput {
+            window_mins: 60,
+            finished: 0,
+            runs_per_hour: 0.0,
+            avg_duration_secs: None,
+        };
+        let runs = pending_runs(
+            vec![event(0, 0, 1_000, 1)],
+            &HashSet::new(),
+            &idle,
+            Utc::now(),
+        );
+        assert!(runs[0].estimated_start.is_none());
+    }
+
+    #[test]
+    fn prioritized_runs_lead_and_count_once() {
+        let idle = Throughput {
+            window_mins: 60,
+            finished: 0,
+            runs_per_hour: 0.0,
+            avg_duration_secs: None,
+        };
+        let with_run = |partition, offset, timestamp_ms, pdf_id, run_id: u128| {
+            let mut queued = event(partition, offset, timestamp_ms, pdf_id);
+            if let Some(evt) = queued.event.as_mut() {
+                evt.run_id = Some(Uuid::from_u128(run_id));
+            }
+            queued
+        };
+        // Lauf 2 wurde priorisiert: das Original in pipeline-run bleibt liegen
+        let boosted = QueuedEvent {
+            prioritized: true,
+            ..with_run(0, 0, 5_000, 2, 2)
+        };
+        let started = HashSet::from([Uuid::from_u128(3)]);
+        let runs = pending_runs(
+            vec![
+                with_run(0, 1, 1_000, 1, 1),
+                with_run(0, 2, 2_000, 2, 2),
+                with_run(0, 3, 3_000, 3, 3),
+                event(0, 4, 4_000, 4),
+                boosted,
+            ],
+            &started,
+            &idle,
+            Utc::now(),
+        );
+        assert_eq!(
+            runs.iter().map(|r| r.pdf_id).collect::<Vec<_>>(),
+            vec![Some(2), Some(1), Some(4)]
+        );
+        assert!(runs[0].prioritized);
+        assert_eq!(runs[0].run_id, Some(Uuid::from_u128(2)));
+        assert_eq!(runs[2].position, 3);
+    }
+}
@@ -0,0 +1,202 @@
+//! Paged and filtered access to `pipeline_run_steps`.
+//!
+//! Large runs log thousands of steps; `GET /runs/{id}/steps` pages through them
+//! by `seq_no` (keyset, `after` + `limit`) and offers a summary mode with
+//! counts per prompt type plus the failed steps only.
+
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use sqlx::{FromRow, PgPool};
+use uuid::Uuid;
+
+const DEFAULT_LIMIT: i64 = 100;
+const MAX_LIMIT: i64 = 1000;
+
+/// A step counts as failed if any nested result carries a non-null `error`.
+const FAILED_SQL: &str = "jsonb_path_exists(s.result, '$.**.error ? (@ != null)')";
+
+/// Query parameters of `GET /runs/{id}/steps`.
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct StepQuery {
+    pub prompt_type: Option<String>,
+    pub step_id: Option<String>,
+    /// Matches the `page` column and the pages of the logged batches.
+    pub page: Option<i32>,
+    pub is_final: Option<bool>,
+    /// Only steps with errors.
+    #[serde(default)]
+    pub failed: bool,
+    /// Return steps with `seq_no` greater than this cursor.
+    pub after: Option<i32>,
+    pub limit: Option<i64>,
+    /// Counts per prompt type and failed steps instead of the step rows.
+    #[serde(default)]
+    pub summary: bool,
+}
+
+impl StepQuery {
+    fn limit(&self) -> i64 {
+        self.limit.unwrap_or(DEFAULT_LIMIT).clamp(1, MAX_LIMIT)
+    }
+}
+
+/// Shared WHERE clause; `$1` is the run id, `$2..$7` the filters.
+fn where_sql() -> String {
+    format!(
+        "s.run_id = $1
+           AND ($2::text IS NULL OR s.prompt_type = $2)
+           AND ($3::text IS NULL OR s.step_id = $3)
+           AND ($4::int IS NULL OR s.page = $4
+                OR jsonb_path_exists(s.result, '$.batches[*].pages[*] ? (@ == $p)',
+                                     jsonb_build_object('p', $4::int)))
+           AND ($5::bool IS NULL OR s.is_final = $5)
+           AND (NOT $6 OR {FAILED_SQL})"
+    )
+}
+
+#[derive(Debug, Serialize, FromRow)]
+pub struct StepRow {
+    pub seq_no: i32,
+    pub step_id: Option<String>,
+    pub prompt_id: Option<i32>,
+    pub prompt_type: Option<String>,
+    pub decision_key: Option<String>,
+    pub route: Option<String>,
+    pub is_final: bool,
+    pub final_key: Option<String>,
+    pub confidence: Option<f32>,
+    pub answer: Option<bool>,
+    pub page: Option<i32>,
+    pub failed: bool,
+    pub result: Option<Value>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct StepPage {
+    pub items: Vec<StepRow>,
+    /// Cursor for the next page; `None` on the last page.
+    pub next_after: Option<i32>,
+}
+
+#[derive(Debug, Serialize, FromRow)]
+pub struct TypeCount {
+    pub prompt_type: Option<String>,
+    pub total: i64,
+    pub is_final: i64,
+    pub failed: i64,
+}
+
+#[derive(Debug, Serialize, FromRow)]
+pub struct FailedStep {
+    pub seq_no: i32,
+    pub step_id: Option<String>,
+    pub prompt_id: Option<i32>,
+    pub prompt_type: Option<String>,
+    pub errors: Value,
+}
+
+#[derive(Debug, Serialize)]
+pub struct StepSummary {
+    pub total: i64,
+    pub by_type: Vec<TypeCount>,
+    pub failures: Vec<FailedStep>,
+}
+
+macro_rules! bind_filters {
+    ($query:expr, $run_id:expr, $q:expr) => {
+        $query
+            .bind($run_id)
+            .bind($q.prompt_type.as_deref())
+            .bind($q.step_id.as_deref())
+            .bind($q.page)
+            .bind($q.is_final)
+            .bind($q.failed)
+    };
+}
+
+/// One page of steps ordered by `seq_no`.
+pub async fn list(pool: &PgPool, run_id: Uuid, q: &StepQuery) -> Result<StepPage> {
+    let limit = q.limit();
+    let sql = format!(
+        "SELECT s.seq_no, s.step_id, s.prompt_id, s.prompt_type, s.decision_key, s.route,
+                COALESCE(s.is_final, FALSE) AS is_final, s.final_key, s.confidence, s.answer,
+                s.page, {FAILED_SQL} AS failed, s.result
+           FROM pipeline_run_steps s
+          WHERE {}
+            AND ($7::int IS NULL OR s.seq_no > $7)
+          ORDER BY s.seq_no
+          LIMIT $8",
+        where_sql()
+    );
+    // eine Zeile mehr laden, um das Ende zu erkennen
+    let mut items: Vec<StepRow> = bind_filters!(sqlx::query_as::<_, StepRow>(&sql), run_id, q)
+        .bind(q.after)
+        .bind(limit + 1)
+        .fetch_all(pool)
+        .await
+        .context("load run steps")?;
+    let next_after = if items.len() as i64 > limit {
+        items.truncate(limit as usize);
+        items.last().map(|s| s.seq_no)
+    } else {
+        None
+    };
+    Ok(StepPage { items, next_after })
+}
+
+/// Counts per prompt type and the failed steps matching the filters.
+pub async fn summary(pool: &PgPool, run_id: Uuid, q: &StepQuery) -> Result<StepSummary> {
+    let counts_sql = format!(
+        "SELECT s.prompt_type,
+                COUNT(*) AS total,
+                COUNT(*) FILTER (WHERE s.is_final) AS is_final,
+                COUNT(*) FILTER (WHERE {FAILED_SQL}) AS failed
+           FROM pipeline_run_steps s
+          WHERE {}
+          GROUP BY s.prompt_type
+          ORDER BY s.prompt_type",
+        where_sql()
+    );
+    let by_type: Vec<TypeCount> =
+        bind_filters!(sqlx::query_as::<_, TypeCount>(&counts_sql), run_id, q)
+            .fetch_all(pool)
+            .await
+            .context("count run steps")?;
+
+    let failures_sql = format!(
+        "SELECT s.seq_no, s.step_id, s.prompt_id, s.prompt_type,
+                jsonb_path_query_array(s.result, '$.**.error ? (@ != null)') AS errors
+           FROM pipeline_run_steps s
+          WHERE {} AND {FAILED_SQL}
+          ORDER BY s.seq_no
+          LIMIT $7",
+        where_sql()
+    );
+    let failures: Vec<FailedStep> =
+        bind_filters!(sqlx::query_as::<_, FailedStep>(&failures_sql), run_id, q)
+            .bind(q.limit())
+            .fetch_all(pool)
+            .await
+            .context("load failed run steps")?;
+
+    Ok(StepSummary {
+        total: by_type.iter().map(|t| t.total).sum(),
+        by_type,
+        failures,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn limit_is_clamped() {
+        let q: StepQuery = serde_json::from_str(r#"{"limit": 50000, "failed": true}"#).unwrap();
+        assert_eq!(q.limit(), MAX_LIMIT);
+        assert!(q.failed && !q.summary);
+        assert_eq!(StepQuery::default().limit(), DEFAULT_LIMIT);
+        assert!(where_sql().contains("$6"));
+    }
+}
@@ -0,0 +1,283 @@
+//! Aggregated health of the deployment for the ops dashboard (`GET /status`).
+//!
+//! Postgres, Kafka and the health endpoint of every service in `STATUS_TARGETS`
+//! are probed concurrently, each with `STATUS_TIMEOUT_MS`. Every dependency gets
+//! a traffic light — `green` when it answered, `yellow` when it took longer than
+//! `STATUS_SLOW_MS`, `red` when it failed — plus its latency and the time of the
+//! last successful probe of this process. The overall light is the worst one,
+//! except that a failing service only turns it `yellow`: without Postgres or
+//! Kafka nothing works, without e.g. the metrics service everything else still
+//! does.
+
+use std::collections::HashMap;
+use std::env;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use chrono::{DateTime, Utc};
+use futures_util::future::join_all;
+use rdkafka::producer::{FutureProducer, Producer};
+use serde::Serialize;
+use sqlx::PgPool;
+
+/// Health endpoints of the services in docker-compose.
+const DEFAULT_TARGETS: &str = "api-gateway=http://api-gateway:8080/health,\
+    pdf-ingest=http://pdf-ingest:8081/readyz,\
+    prompt-manager=http://prompt-manager:8082/health,\
+    text-extraction=http://text-extraction:8083/health,\
+    metrics=http://metrics:8085/health,\
+    history-service=http://history-service:8090/readyz";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize)]
+#[serde(rename_all = "lowercase")]
+/// Traffic light, ordered from best to worst.
+pub enum Light {
+    Green,
+    Yellow,
+    Red,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum DependencyKind {
+    Database,
+    Broker,
+    Service,
+}
+
+#[derive(Debug, Clone, Serialize)]
+/// Result of one probe.
+pub struct DependencyStatus {
+    pub name: String,
+    pub kind: DependencyKind,
+    pub status: Light,
+    pub latency_ms: u64,
+    pub last_success: Option<DateTime<Utc>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+/// Body of `GET /status`.
+pub struct StatusReport {
+    pub status: Light,
+    pub checked_at: DateTime<Utc>,
+    pub dependencies: Vec<DependencyStatus>,
+}
+
+/// Name, kind, latency and outcome of one probe.
+type Probe = (String, DependencyKind, Duration, Result<(), String>);
+
+#[derive(Debug, Clone, PartialEq)]
+struct Target {
+    name: String,
+    url: String,
+}
+
+/// Parses `STATUS_TARGETS`: `name=url` pairs separated by commas.
+fn parse_targets(raw: &str) -> Vec<Target> {
+    raw.split(',')
+        .filter_map(|entry| {
+            let (name, url) = entry.trim().split_once('=')?;
+            let (name, url) = (name.trim(), url.trim());
+            (!name.is_empty() && !url.is_empty()).then(|| Target {
+                name: name.to_string(),
+                url: url.to_string(),
+            })
+        })
+        .collect()
+}
+
+/// Worst light, with failing services capped at `yellow`.
+pub fn overall(dependencies: &[DependencyStatus]) -> Light {
+    dependencies
+        .iter()
+        .map(|d| match d.kind {
+            DependencyKind::Service => d.status.min(Light::Yellow),
+            _ => d.status,
+        })
+        .max()
+        .unwrap_or(Light::Green)
+}
+
+/// Probes the dependencies and remembers their last success.
+#[derive(Clone)]
+pub struct StatusChecker {
+    targets: Arc<Vec<Target>>,
+    http: reqwest::Client,
+    timeout: Duration,
+    slow: Duration,
+    last_success: Arc<Mutex<HashMap<String, DateTime<Utc>>>>,
+}
+
+impl StatusChecker {
+    /// Reads `STATUS_TARGETS`, `STATUS_TIMEOUT_MS` (2000) and `STATUS_SLOW_MS` (500).
+    pub fn from_env() -> Self {
+        let read = |key: &str, default: u64| {
+            env::var(key)
+                .ok()
+                .and_then(|v| v.trim().parse::<u64>().ok())
+                .map(Duration::from_millis)
+                .unwrap_or(Duration::from_millis(default))
+        };
+        let targets = env::var("STATUS_TARGETS").unwrap_or_else(|_| DEFAULT_TARGETS.to_string());
+        let timeout = read("STATUS_TIMEOUT_MS", 2000);
+        Self {
+            targets: Arc::new(parse_targets(&targets)),
+            http: reqwest::Client::builder()
+                .timeout(timeout)
+                .build()
+                .unwrap_or_default(),
+            timeout,
+            slow: read("STATUS_SLOW_MS", 500),
+            last_success: Arc::default(),
+        }
+    }
+
+    /// Runs all probes concurrently.
+    pub async fn check(&self, pool: &PgPool, producer: &FutureProducer) -> StatusReport {
+        let services = join_all(self.targets.iter().map(|target| async move {
+            let started = Instant::now();
+            let result = match self.http.get(&target.url).send().await {
+                Ok(resp) if resp.status().is_success() => Ok(()),
+                Ok(resp) => Err(format!("HTTP {}", resp.status().as_u16())),
+                Err(e) => Err(e.to_string()),
+            };
+            (
+                target.name.clone(),
+                DependencyKind::Service,
+                started.elapsed(),
+                result,
+            )
+        }));
+        let (database, broker, services) = tokio::join!(
+            self.check_postgres(pool),
+            self.check_kafka(producer),
+            services
+        );
+
+        let mut dependencies = Vec::new();
+        for (name, kind, latency, result) in [database, broker].into_iter().chain(services) {
+            dependencies.push(self.record(name, kind, latency, result));
+        }
+        StatusReport {
+            status: overall(&dependencies),
+            checked_at: Utc::now(),
+            dependencies,
+        }
+    }
+
+    async fn check_postgres(&self, pool: &PgPool) -> Probe {
+        let started = Instant::now();
+        let result =
+            match tokio::time::timeout(self.timeout, sqlx::query("SELECT 1").execute(pool)).await {
+                Ok(Ok(_)) => Ok(()),
+                Ok(Err(e)) => Err(e.to_string()),
+                Err(_) => Err("timeout".to_string()),
+            };
+        (
+            "postgres".to_string(),
+            DependencyKind::Database,
+            started.elapsed(),
+            result,
+        )
+    }
+
+    async fn check_kafka(&self, producer: &FutureProducer) -> Probe {
+        let started = Instant::now();
+        let producer = producer.clone();
+        let timeout = self.timeout;
+        // fetch_metadata blockiert bis zur Antwort des Brokers
+        let result = tokio::task::spawn_blocking(move || {
+            producer
+                .client()
+                .fetch_metadata(None, timeout)
+                .map(|_| ())
+                .map_err(|e| e.to_string())
+        })
+        .await
+        .unwrap_or_else(|e| Err(e.to_string()));
+        (
+            "kafka".to_string(),
+            DependencyKind::Broker,
+            started.elapsed(),
+            result,
+        )
+    }
+
+    fn record(
+        &self,
+        name: String,
+        kind: DependencyKind,
+        latency: Duration,
+        result: Result<(), String>,
+    ) -> DependencyStatus {
+        let mut last_success = self.last_success.lock().unwrap_or_else(|e| e.into_inner());
+        let (status, error) = match result {
+            Ok(()) => {
+                last_success.insert(name.clone(), Utc::now());
+                let light = if latency > self.slow {
+                    Light::Yellow
+                } else {
+                    Light::Green
+                };
+                (light, None)
+            }
+            Err(e) => (Light::Red, Some(e)),
+        };
+        DependencyStatus {
+            last_success: last_success.get(&name).copied(),
+            name,
+            kind,
+            status,
+            latency_ms: latency.as_millis() as u64,
+            error,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn dep(kind: DependencyKind, status: Light) -> DependencyStatus {
+        DependencyStatus {
+            name: "x".to_string(),
+            kind,
+            status,
+            latency_ms: 1,
+            last_success: None,
+            error: None,
+        }
+    }
+
+    #[test]
+    fn parses_targets() {
+        let targets = parse_targets(" a=http://a:1/health ,broken,b= ,c=http://c/readyz");
+        assert_eq!(
+            targets,
+            vec![
+                Target {
+                    name: "a".to_string(),
+                    url: "http://a:1/health".to_string(),
+                },
+                Target {
+                    name: "c".to_string(),
+                    url: "http://c/readyz".to_string(),
+                },
+            ]
+        );
+        assert_eq!(parse_targets(DEFAULT_TARGETS).len(), 6);
+    }
+
+    #[test]
+    fn failing_services_only_turn_yellow() {
+        let db_ok = dep(DependencyKind::Database, Light::Green);
+        let service_down = dep(DependencyKind::Service, Light::Red);
+        assert_eq!(overall(std::slice::from_ref(&db_ok)), Light::Green);
+        assert_eq!(overall(&[db_ok, service_down.clone()]), Light::Yellow);
+        let kafka_down = dep(DependencyKind::Broker, Light::Red);
+        assert_eq!(overall(&[kafka_down, service_down]), Light::Red);
+        assert_eq!(overall(&[]), Light::Green);
+    }
+}
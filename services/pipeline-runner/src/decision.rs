@@ -0,0 +1,303 @@
+//! Final decision aggregation with contradiction surfacing.
+//!
+//! Every batch vote of a decision step is kept (route, answer, explanation,
+//! cited quote). Besides the majority route the aggregate reports a
+//! `contradiction_score` — the weight of strong votes for other routes relative
+//! to all strong votes — and flags the decision as `contested` when strongly
+//! supported votes disagree, so the review workflow can pick it up.
+
+use serde::Serialize;
+use serde_json::{json, Value};
+use std::collections::BTreeMap;
+
+/// Votes at or above this strength count as "strong".
+const STRONG_VOTE: f32 = 0.75;
+/// Contradicting votes reported with the final decision.
+const TOP_CONTRADICTIONS: usize = 3;
+
+/// Contradiction score from which a decision counts as contested
+/// (`DECISION_CONTESTED_THRESHOLD`, default 0.5).
+pub fn contested_threshold() -> f32 {
+    std::env::var("DECISION_CONTESTED_THRESHOLD")
+        .ok()
+        .and_then(|v| v.parse::<f32>().ok())
+        .map(|v| v.clamp(0.0, 1.0))
+        .unwrap_or(0.5)
+}
+
+/// One per-batch vote of a decision prompt.
+#[derive(Debug, Clone, Serialize)]
+pub struct DecisionVote {
+    pub batch: Option<usize>,
+    pub pages: Vec<i64>,
+    pub route: String,
+    pub answer: Option<bool>,
+    /// 0..1; model confidence if given, otherwise derived from answer and quote.
+    pub strength: f32,
+    pub explanation: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub source: Option<Value>,
+}
+
+impl DecisionVote {
+    fn quote(&self) -> Option<&str> {
+        self.source
+            .as_ref()
+            .and_then(|s| s.get("quote"))
+            .and_then(Value::as_str)
+            .filter(|q| !q.trim().is_empty())
+    }
+
+    /// Builds a vote from a serialized `PromptResult` (`votes[]` / `consolidated`).
+    pub fn from_result(value: &Value, batch: Option<usize>, pages: Vec<i64>) -> Self {
+        let raw: Value = value
+            .get("openai_raw")
+            .and_then(Value::as_str)
+            .and_then(|s| serde_json::from_str(s).ok())
+            .unwrap_or(Value::Null);
+        let explanation = value
+            .get("value")
+            .and_then(|v| v.get("explanation"))
+            .or_else(|| raw.get("explanation"))
+            .and_then(Value::as_str)
+            .map(str::trim)
+            .filter(|s| !s.is_empty())
+            .map(str::to_string);
+        let answer = value.get("boolean").and_then(Value::as_bool);
+        let route = normalize_route(
+            value
+                .get("route")
+                .and_then(Value::as_str)
+                .unwrap_or("UNKNOWN"),
+        );
+        let source = value.get("source").filter(|s| !s.is_null()).cloned();
+        let mut vote = Self {
+            batch,
+            pages,
+            route,
+            answer,
+            strength: 0.0,
+            explanation,
+            source,
+        };
+        vote.strength = match raw.get("confidence").and_then(Value::as_f64) {
+            Some(c) => (c as f32).clamp(0.0, 1.0),
+            None => match (
+                vote.answer.is_some() || route_to_bool(&vote.route).is_some(),
+                vote.quote(),
+            ) {
+                (true, Some(_)) => 1.0,
+                (true, None) => 0.5,
+                (false, _) => 0.0,
+            },
+        };
+        vote
+    }
+
+    fn is_yes(&self) -> Option<bool> {
+        self.answer.or_else(|| route_to_bool(&self.route))
+    }
+}
+
+pub fn normalize_route(route: &str) -> String {
+    route.trim().to_ascii_uppercase()
+}
+
+pub fn route_to_bool(route: &str) -> Option<bool> {
+    match route {
+        "YES" | "TRUE" | "JA" | "Y" | "1" => Some(true),
+        "NO" | "FALSE" | "NEIN" | "N" | "0" => Some(false),
+        _ => None,
+    }
+}
+
+/// Collects all votes of a logged decision step (`votes` + `batches`), falling
+/// back to the consolidated result for older log entries.
+pub fn votes_from_step(result: &Value) -> Vec<DecisionVote> {
+    let batches = result.get("batches").and_then(Value::as_array);
+    let votes = result
+        .get("votes")
+        .and_then(Value::as_array)
+        .filter(|v| !v.is_empty());
+    match votes {
+        Some(votes) => votes
+            .iter()
+            .enumerate()
+            .map(|(i, v)| {
+                let pages = batches
+                    .and_then(|b| b.get(i))
+                    .and_then(|b| b.get("pages"))
+                    .and_then(Value::as_array)
+                    .map(|p| p.iter().filter_map(Value::as_i64).collect())
+                    .unwrap_or_default();
+                DecisionVote::from_result(v, Some(i), pages)
+            })
+            .collect(),
+        None => result
+            .get("consolidated")
+            .map(|c| vec![DecisionVote::from_result(c, None, Vec::new())])
+            .unwrap_or_default(),
+    }
+}
+
+/// Aggregated final decision of one prompt.
+#[derive(Debug, Clone)]
+pub struct DecisionSummary {
+    pub route: String,
+    pub answer: Option<bool>,
+    pub confidence: f32,
+    pub votes_yes: i64,
+    pub votes_no: i64,
+    pub contradiction_score: f32,
+    pub contested: bool,
+    pub explanation: Option<String>,
+    pub support: Vec<Value>,
+    pub contradictions: Vec<DecisionVote>,
+    pub votes: Vec<DecisionVote>,
+}
+
+impl DecisionSummary {
+    /// JSON stored as `result` of the final decision step.
+    pub fn to_json(&self) -> Value {
+        json!({
+            "route": self.route,
+            "answer": self.answer,
+            "confidence": self.confidence,
+            "votes_yes": self.votes_yes,
+            "votes_no": self.votes_no,
+            "explanation": self.explanation,
+            "support": self.support,
+            "contradiction_score": self.contradiction_score,
+            "contested": self.contested,
+            "contradicting_evidence": self.contradictions,
+            "votes": self.votes,
+        })
+    }
+}
+
+/// Majority vote over all routes; `None` without votes.
+///
+/// `contested_threshold` is the minimum contradiction score at which strong
+/// votes for different routes mark the decision as contested.
+pub fn aggregate(votes: Vec<DecisionVote>, contested_threshold: f32) -> Option<DecisionSummary> {
+    if votes.is_empty() {
+        return None;
+    }
+    let mut route_votes: BTreeMap<&str, i64> = BTreeMap::new();
+    for v in &votes {
+        *route_votes.entry(v.route.as_str()).or_default() += 1;
+    }
+    let total: i64 = route_votes.values().sum();
+    let (best_route, best_cnt) = route_votes
+        .iter()
+        .max_by(|a, b| a.1.cmp(b.1))
+        .map(|(r, c)| (r.to_string(), *c))?;
+    let confidence = (best_cnt as f32 / total as f32).clamp(0.0, 1.0);
+
+    let votes_yes = votes.iter().filter(|v| v.is_yes() == Some(true)).count() as i64;
+    let votes_no = votes.iter().filter(|v| v.is_yes() == Some(false)).count() as i64;
+
+    // Widerspruch: Gewicht starker Gegenstimmen relativ zu allen starken Stimmen
+    let strong = |v: &&DecisionVote| v.strength >= STRONG_VOTE;
+    let strong_for: f32 = votes
+        .iter()
+        .filter(strong)
+        .filter(|v| v.route == best_route)
+        .map(|v| v.strength)
+        .sum();
+    let strong_against: f32 = votes
+        .iter()
+        .filter(strong)
+        .filter(|v| v.route != best_route && v.route != "UNKNOWN")
+        .map(|v| v.strength)
+        .sum();
+    let contradiction_score = if strong_for + strong_against > 0.0 {
+        (2.0 * strong_against / (strong_for + strong_against)).clamp(0.0, 1.0)
+    } else {
+        0.0
+    };
+    let contested =
+        strong_for > 0.0 && strong_against > 0.0 && contradiction_score >= contested_threshold;
+
+    let mut contradictions: Vec<DecisionVote> = votes
+        .iter()
+        .filter(|v| v.route != best_route && v.route != "UNKNOWN" && v.strength > 0.0)
+        .cloned()
+        .collect();
+    contradictions.sort_by(|a, b| b.strength.total_cmp(&a.strength));
+    contradictions.truncate(TOP_CONTRADICTIONS);
+
+    let majority = || votes.iter().filter(|v| v.route == best_route);
+    let explanation = majority().find_map(|v| v.explanation.clone());
+    let support: Vec<Value> = majority()
+        .filter_map(|v| v.source.clone())
+        .take(3)
+        .collect();
+
+    Some(DecisionSummary {
+        answer: route_to_bool(&best_route),
+        route: best_route,
+        confidence,
+        votes_yes,
+        votes_no,
+        contradiction_score,
+        contested,
+        explanation,
+        support,
+        contradictions,
+        votes,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn vote(route: &str, answer: Option<bool>, quote: Option<&str>, explanation: &str) -> Value {
+        json!({
+            "route": route,
+            "boolean": answer,
+            "source": quote.map(|q| json!({ "page": 1, "bbox": [0, 0, 0, 0], "quote": q })),
+            "openai_raw": json!({ "explanation": explanation }).to_string(),
+        })
+    }
+
+    #[test]
+    fn strong_disagreement_is_contested() {
+        let step = json!({
+            "batches": [{ "pages": [1, 2] }, { "pages": [3] }, { "pages": [4] }],
+            "votes": [
+                vote("YES", Some(true), Some("Vertrag gekündigt"), "Kündigung liegt vor"),
+                vote("YES", Some(true), Some("Kündigung zum 31.12."), "Frist genannt"),
+                vote("NO", Some(false), Some("Vertrag läuft weiter"), "Verlängerung"),
+            ]
+        });
+        let votes = votes_from_step(&step);
+        assert_eq!(votes[2].pages, vec![4]);
+        assert_eq!(votes[2].explanation.as_deref(), Some("Verlängerung"));
+
+        let summary = aggregate(votes, 0.5).unwrap();
+        assert_eq!(summary.route, "YES");
+        assert_eq!(summary.answer, Some(true));
+        assert!((summary.contradiction_score - 2.0 / 3.0).abs() < 1e-6);
+        assert!(summary.contested);
+        assert_eq!(summary.contradictions.len(), 1);
+        assert_eq!(summary.contradictions[0].route, "NO");
+        assert_eq!(summary.votes.len(), 3);
+        assert_eq!(summary.explanation.as_deref(), Some("Kündigung liegt vor"));
+    }
+
+    #[test]
+    fn weak_minority_is_not_contested() {
+        let step = json!({ "votes": [
+            vote("NO", Some(false), Some("keine Kündigung"), "eindeutig"),
+            vote("NO", Some(false), Some("läuft weiter"), "eindeutig"),
+            vote("YES", Some(true), None, "vielleicht"),
+        ]});
+        let summary = aggregate(votes_from_step(&step), 0.5).unwrap();
+        assert_eq!(summary.route, "NO");
+        assert_eq!(summary.contradiction_score, 0.0);
+        assert!(!summary.contested);
+        assert_eq!(summary.contradictions.len(), 1);
+    }
+}
@@ -0,0 +1,173 @@
+//! Drain and hand-off of in-flight runs on shutdown.
+//!
+//! On SIGTERM/SIGINT the runner stops taking events. A run in progress lets
+//! its running batches finish (at most `RUNNER_DRAIN_TIMEOUT_SECS`), starts no
+//! further batch or step and is handed off: the [`Resume`] state lands in
+//! `pipeline_run_handoffs` and the run event, now carrying the `run_id`, is
+//! re-published through the outbox on `pipeline-run-priority` (same document
+//! key). The replica that receives it claims the row and continues the run
+//! where it stopped instead of starting over; the timeline shows
+//! `handed_off` and `resumed`. Rolling deploys thus neither lose nor repeat
+//! OpenAI calls of finished batches.
+
+use std::time::Duration;
+
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use shared::dto::{PdfUploaded, PRIORITY_RUN_TOPIC};
+use shared::outbox;
+use sqlx::PgPool;
+use tokio::signal::unix::{signal, SignalKind};
+use tokio::sync::watch;
+use tracing::{info, warn};
+use uuid::Uuid;
+
+use crate::runner::{BatchCfg, Checkpoint};
+
+/// Idempotent DDL (mirrors `migrations/0067_pipeline_run_handoffs.sql`).
+pub const CREATE_TABLE_SQL: &str = "CREATE TABLE IF NOT EXISTS pipeline_run_handoffs (
+    run_id UUID PRIMARY KEY REFERENCES pipeline_runs(id) ON DELETE CASCADE,
+    state JSONB NOT NULL,
+    handed_off_by TEXT,
+    handed_off_at TIMESTAMPTZ NOT NULL DEFAULT now(),
+    claimed_by TEXT,
+    claimed_at TIMESTAMPTZ
+)";
+
+/// Default of `RUNNER_DRAIN_TIMEOUT_SECS`; below the 30 s Kubernetes/Compose
+/// grace period, so the hand-off is stored before the process gets killed.
+pub const DEFAULT_DRAIN_TIMEOUT_SECS: u64 = 20;
+
+/// Shutdown state shared by the event loop and the running pipeline.
+#[derive(Clone)]
+pub struct Drain {
+    rx: watch::Receiver<bool>,
+    /// How long running batches may take once the drain started.
+    timeout: Duration,
+}
+
+impl Drain {
+    /// Starts draining on the first SIGTERM or SIGINT.
+    pub fn listen(timeout: Duration) -> Self {
+        let (tx, rx) = watch::channel(false);
+        tokio::spawn(async move {
+            let mut term = match signal(SignalKind::terminate()) {
+                Ok(term) => term,
+                Err(e) => {
+                    warn!(%e, "failed to install SIGTERM handler; runs are not handed off");
+                    // Sender behalten, sonst gilt der Kanal als geschlossen
+                    std::future::pending::<()>().await;
+                    return;
+                }
+            };
+            tokio::select! {
+                _ = term.recv() => {}
+                _ = tokio::signal::ctrl_c() => {}
+            }
+            info!(
+                timeout_secs = timeout.as_secs(),
+                "shutdown requested; draining"
+            );
+            let _ = tx.send(true);
+        });
+        Self { rx, timeout }
+    }
+
+    pub fn is_draining(&self) -> bool {
+        *self.rx.borrow()
+    }
+
+    /// Resolves once the drain started.
+    pub async fn wait(&self) {
+        let mut rx = self.rx.clone();
+        if rx.wait_for(|draining| *draining).await.is_err() {
+            std::future::pending::<()>().await;
+        }
+    }
+
+    /// Resolves when running batches have to be given up.
+    pub async fn deadline(&self) {
+        self.wait().await;
+        tokio::time::sleep(self.timeout).await;
+    }
+
+    #[cfg(test)]
+    pub fn manual(timeout: Duration) -> (watch::Sender<bool>, Self) {
+        let (tx, rx) = watch::channel(false);
+        (tx, Self { rx, timeout })
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+/// Everything another replica needs to continue a handed-off run.
+pub struct Resume {
+    /// Pipeline config the run started with; later edits do not apply.
+    pub config_json: Value,
+    pub batch_cfg: BatchCfg,
+    pub checkpoint: Checkpoint,
+}
+
+/// Name of this replica in the hand-off rows and timeline details.
+pub fn replica() -> Option<String> {
+    std::env::var("HOSTNAME").ok().filter(|h| !h.is_empty())
+}
+
+/// Stores the state of `run_id` and re-publishes its run event, in one
+/// transaction.
+pub async fn hand_off(
+    pool: &PgPool,
+    evt: &PdfUploaded,
+    run_id: Uuid,
+    resume: &Resume,
+) -> anyhow::Result<()> {
+    let mut payload = serde_json::to_value(evt)?;
+    payload["run_id"] = json!(run_id.to_string());
+    let mut tx = pool.begin().await?;
+    sqlx::query(
+        "INSERT INTO pipeline_run_handoffs (run_id, state, handed_off_by)
+         VALUES ($1, $2, $3)
+         ON CONFLICT (run_id) DO UPDATE
+           SET state = EXCLUDED.state, handed_off_by = EXCLUDED.handed_off_by,
+               handed_off_at = now(), claimed_by = NULL, claimed_at = NULL",
+    )
+    .bind(run_id)
+    .bind(serde_json::to_value(resume)?)
+    .bind(replica())
+    .execute(&mut *tx)
+    .await?;
+    outbox::enqueue(
+        &mut *tx,
+        PRIORITY_RUN_TOPIC,
+        Some(&shared::kafka::document_key(evt.pdf_id)),
+        &payload.to_string(),
+    )
+    .await?;
+    tx.commit().await?;
+    Ok(())
+}
+
+/// Takes over a handed-off run; `None` if there is none or another replica
+/// was faster.
+pub async fn claim(pool: &PgPool, run_id: Uuid) -> anyhow::Result<Option<Resume>> {
+    let state: Option<Value> = sqlx::query_scalar(
+        "UPDATE pipeline_run_handoffs SET claimed_by = $2, claimed_at = now()
+          WHERE run_id = $1 AND claimed_at IS NULL
+          RETURNING state",
+    )
+    .bind(run_id)
+    .bind(replica())
+    .fetch_optional(pool)
+    .await?;
+    Ok(state.map(serde_json::from_value).transpose()?)
+}
+
+/// Removes the hand-off row once the resumed run finished.
+pub async fn finish(pool: &PgPool, run_id: Uuid) {
+    if let Err(e) = sqlx::query("DELETE FROM pipeline_run_handoffs WHERE run_id = $1")
+        .bind(run_id)
+        .execute(pool)
+        .await
+    {
+        warn!(%e, %run_id, "failed to remove run hand-off");
+    }
+}
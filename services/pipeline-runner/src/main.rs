@@ -1,27 +1,45 @@
 //! Entry point for the pipeline runner service handling pipeline execution and persistence.
 
 use rdkafka::{
-    consumer::{Consumer, StreamConsumer},
-    producer::{FutureProducer, FutureRecord},
+    consumer::{CommitMode, Consumer, StreamConsumer},
+    producer::FutureProducer,
     ClientConfig, Message,
 };
 use serde_json::{json, Value};
+use shared::app_settings;
 use shared::dto::{
-    PdfUploaded, PipelineConfig, PipelineRunResult, PromptResult, TernaryLabel, TextPosition,
+    PdfUploaded, PipelineConfig, PipelineRunResult, PromptResult, RunStatus, TernaryLabel,
+    TextPosition, PRIORITY_RUN_TOPIC,
 };
-use shared::openai_client;
+use shared::envelope::{self, EnvelopeError, MasterKey, SealedSecret};
+use shared::flags::{self, FeatureFlag};
+use shared::openai_client::{self, OpenAiCredentials, TokenUsage};
 use shared::openai_settings;
+use shared::outbox::{self, OutboxRelay};
+use shared::output_mapping;
+use shared::run_config::{self, ModelSettings};
+use shared::runner_settings::{self, RunnerSettings};
+use shared::scrubber::{self, Scrubber};
+use shared::telemetry::{EventKind, Telemetry};
+use shared::tenant_credentials;
+use shared::timeline::{self, TimelineEvent};
 use sqlx::{postgres::PgPoolOptions, PgPool, Row};
 use time::{format_description::well_known::Rfc3339, OffsetDateTime};
 
 use std::collections::HashMap;
+use std::sync::Arc;
 use std::time::Duration;
+use tokio::sync::{watch, Notify};
 use tokio::task::LocalSet;
 use tracing::{error, info, warn};
 use tracing_subscriber::{fmt, EnvFilter};
 use uuid::Uuid;
 
+mod decision;
+mod handoff;
+mod ocr_confidence;
 mod runner;
+mod workspace;
 
 /// Ensures the connection string explicitly disables SSL for local usage.
 fn ensure_sslmode_disable(url: &str) -> String {
@@ -61,6 +79,29 @@ async fn main() -> anyhow::Result<()> {
     local.run_until(async { app_main().await }).await
 }
 
+/// Consumer of the runner group on `topic`, with generous timeouts to support
+/// long running prompts.
+fn run_consumer(broker: &str, topic: &str) -> anyhow::Result<StreamConsumer> {
+    let consumer: StreamConsumer = ClientConfig::new()
+        .set("group.id", "pipeline-runner")
+        .set("bootstrap.servers", broker)
+        .set("enable.auto.commit", "true")
+        .set("session.timeout.ms", "45000")
+        .set("max.poll.interval.ms", "1800000")
+        .set("heartbeat.interval.ms", "5000")
+        .set("socket.keepalive.enable", "true")
+        .create()
+        .map_err(|e| {
+            error!(%e, "failed to create kafka consumer");
+            e
+        })?;
+    consumer.subscribe(&[topic]).map_err(|e| {
+        error!(%e, topic, "failed to subscribe to topic");
+        e
+    })?;
+    Ok(consumer)
+}
+
 /// Sets up dependencies and runs the pipeline runner event loop.
 async fn app_main() -> anyhow::Result<()> {
     fmt().with_env_filter(EnvFilter::from_default_env()).init();
@@ -69,8 +110,16 @@ async fn app_main() -> anyhow::Result<()> {
         .or_else(|_| std::env::var("BROKER"))
         .unwrap_or_else(|_| "kafka:9092".into());
 
-    if let Err(e) =
-        shared::kafka::ensure_topics(&broker, &["pipeline-run", "pipeline-result"]).await
+    if let Err(e) = shared::kafka::ensure_topics(
+        &broker,
+        &[
+            "pipeline-run",
+            PRIORITY_RUN_TOPIC,
+            "pipeline-result",
+            app_settings::SETTINGS_CHANGED_TOPIC,
+        ],
+    )
+    .await
     {
         warn!(%e, "failed to ensure kafka topics (continuing)");
     }
@@ -81,17 +130,18 @@ async fn app_main() -> anyhow::Result<()> {
         warn!("DATABASE_URL had no sslmode – using '{}'", db_url);
     }
 
-    let batch_cfg = runner::BatchCfg {
+    let base_batch_cfg = runner::BatchCfg {
         page_batch_size: env_parse("PIPELINE_PAGE_BATCH_SIZE", 5usize),
         max_parallel: env_parse("PIPELINE_MAX_PARALLEL", 3usize),
         max_chars: env_parse("PIPELINE_MAX_CHARS", 20_000usize),
         openai_timeout_ms: env_parse("PIPELINE_OPENAI_TIMEOUT_MS", 25_000u64),
         openai_retries: env_parse("PIPELINE_OPENAI_RETRIES", 2usize),
+        structured_outputs: flags::STRUCTURED_OUTPUTS.default,
     };
     info!(
         "batch_cfg={{page_batch_size:{}, max_parallel:{}, max_chars:{}, timeout_ms:{}, retries:{}}}",
-        batch_cfg.page_batch_size, batch_cfg.max_parallel, batch_cfg.max_chars,
-        batch_cfg.openai_timeout_ms, batch_cfg.openai_retries
+        base_batch_cfg.page_batch_size, base_batch_cfg.max_parallel, base_batch_cfg.max_chars,
+        base_batch_cfg.openai_timeout_ms, base_batch_cfg.openai_retries
     );
 
     // Configure a SQLx pool with conservative timeouts so long-running batches stay healthy.
@@ -166,10 +216,49 @@ async fn app_main() -> anyhow::Result<()> {
     let _ = sqlx::query("ALTER TABLE pipeline_run_steps ADD COLUMN IF NOT EXISTS page INT")
         .execute(&pool)
         .await;
+    let _ = sqlx::query(
+        "ALTER TABLE pipeline_run_steps ADD COLUMN IF NOT EXISTS contested BOOLEAN NOT NULL DEFAULT FALSE",
+    )
+    .execute(&pool)
+    .await;
+    let _ = sqlx::query(
+        "ALTER TABLE pipeline_runs ADD COLUMN IF NOT EXISTS contested BOOLEAN NOT NULL DEFAULT FALSE",
+    )
+    .execute(&pool)
+    .await;
+    let _ = sqlx::query(
+        "ALTER TABLE pipeline_runs ADD COLUMN IF NOT EXISTS final_extraction_sealed JSONB",
+    )
+    .execute(&pool)
+    .await;
+    let _ = sqlx::query(
+        "ALTER TABLE pipeline_runs ADD COLUMN IF NOT EXISTS job_label TEXT, ADD COLUMN IF NOT EXISTS external_ref TEXT",
+    )
+    .execute(&pool)
+    .await;
+    let _ = sqlx::query(
+        "ALTER TABLE pipeline_runs
+           ADD COLUMN IF NOT EXISTS prompt_tokens BIGINT NOT NULL DEFAULT 0,
+           ADD COLUMN IF NOT EXISTS completion_tokens BIGINT NOT NULL DEFAULT 0,
+           ADD COLUMN IF NOT EXISTS openai_calls INTEGER NOT NULL DEFAULT 0",
+    )
+    .execute(&pool)
+    .await;
+    let _ = sqlx::query(
+        "ALTER TABLE pipeline_runs ADD COLUMN IF NOT EXISTS rerun_of UUID REFERENCES pipeline_runs(id) ON DELETE SET NULL",
+    )
+    .execute(&pool)
+    .await;
 
     let _ = sqlx::query("CREATE INDEX IF NOT EXISTS idx_prs_run_final_type ON pipeline_run_steps (run_id, is_final, prompt_type)").execute(&pool).await;
     let _ = sqlx::query("CREATE INDEX IF NOT EXISTS idx_prs_run_final_key  ON pipeline_run_steps (run_id, final_key) WHERE is_final = TRUE").execute(&pool).await;
 
+    let _ = sqlx::query(timeline::CREATE_TABLE_SQL).execute(&pool).await;
+    let _ = sqlx::query(run_config::CREATE_TABLE_SQL)
+        .execute(&pool)
+        .await;
+    let _ = sqlx::query(handoff::CREATE_TABLE_SQL).execute(&pool).await;
+
     let _ = sqlx::query(
         "CREATE TABLE IF NOT EXISTS app_settings (
             key TEXT PRIMARY KEY,
@@ -184,24 +273,56 @@ async fn app_main() -> anyhow::Result<()> {
         warn!(%e, "failed to load OpenAI configuration from settings, using defaults");
     }
 
-    // Configure Kafka clients with generous timeouts to support long running prompts.
-    let consumer: StreamConsumer = ClientConfig::new()
-        .set("group.id", "pipeline-runner")
-        .set("bootstrap.servers", &broker)
-        .set("enable.auto.commit", "true")
-        .set("session.timeout.ms", "45000")
-        .set("max.poll.interval.ms", "1800000")
-        .set("heartbeat.interval.ms", "5000")
-        .set("socket.keepalive.enable", "true")
-        .create()
-        .map_err(|e| {
-            error!(%e, "failed to create kafka consumer");
-            e
-        })?;
-    consumer.subscribe(&["pipeline-run"]).map_err(|e| {
-        error!(%e, "failed to subscribe to topic pipeline-run");
-        e
-    })?;
+    // NEU: Laufzeit-Overrides für BatchCfg (app_settings.runner_settings)
+    let settings_refresh = Arc::new(Notify::new());
+    let batch_cfg_rx =
+        spawn_settings_refresh(pool.clone(), base_batch_cfg, settings_refresh.clone());
+    // Änderungen über /admin/settings ohne Neustart übernehmen
+    if let Err(e) = spawn_settings_listener(&broker, pool.clone(), settings_refresh) {
+        warn!(%e, "settings-changed listener disabled; OpenAI version changes need a restart");
+    }
+
+    // NEU: Master-Key für mandantenspezifische OpenAI-Keys (optional)
+    let master_key = match MasterKey::from_env() {
+        Ok(key) => Some(key),
+        Err(EnvelopeError::MissingMasterKey) => {
+            info!(
+                "{} not set; tenant OpenAI credentials disabled",
+                envelope::MASTER_KEY_ENV
+            );
+            None
+        }
+        Err(e) => {
+            warn!(%e, "invalid master key; tenant OpenAI credentials disabled");
+            None
+        }
+    };
+
+    // NEU: sensible Werte (IBAN, Gesundheitsdaten, …) vor dem Speichern maskieren
+    let scrubber = Scrubber::from_env()?;
+    if scrubber.is_enabled() && master_key.is_none() {
+        warn!("result scrubber active without master key; full extraction values are not kept");
+    }
+    let scrub_events = scrubber::events_from_env();
+
+    // NEU: anonymisierte Nutzungs-Events (nur mit TELEMETRY_SINK)
+    let telemetry = Telemetry::from_env("pipeline-runner", &broker).unwrap_or_else(|e| {
+        warn!(%e, "telemetry disabled");
+        Telemetry::disabled()
+    });
+
+    // NEU: Scratch-Verzeichnis pro Run, Reste abgestürzter Läufe wegräumen
+    let workspace_cfg = workspace::WorkspaceConfig::from_env();
+    match workspace_cfg.sweep() {
+        Ok(0) => {}
+        Ok(removed) => info!(removed, "removed stale run workspaces"),
+        Err(e) => warn!(%e, root = %workspace_cfg.root.display(), "failed to prepare run work dir"),
+    }
+    spawn_disk_gauge(pool.clone(), workspace_cfg.clone());
+
+    let consumer = run_consumer(&broker, "pipeline-run")?;
+    // Vorgezogene Läufe (POST /runs/{id}/prioritize) kommen vor allen anderen dran
+    let priority_consumer = run_consumer(&broker, PRIORITY_RUN_TOPIC)?;
 
     let producer: FutureProducer = ClientConfig::new()
         .set("bootstrap.servers", &broker)
@@ -211,10 +332,36 @@ async fn app_main() -> anyhow::Result<()> {
             e
         })?;
 
+    if let Err(e) = outbox::ensure_schema(&pool).await {
+        warn!(%e, "failed to ensure event_outbox table");
+    }
+    OutboxRelay::from_env(pool.clone(), producer).spawn();
+
+    // NEU: bei SIGTERM laufende Runs an ein anderes Replikat übergeben
+    let drain = handoff::Drain::listen(Duration::from_secs(env_parse(
+        "RUNNER_DRAIN_TIMEOUT_SECS",
+        handoff::DEFAULT_DRAIN_TIMEOUT_SECS,
+    )));
+
     info!("pipeline-runner started (broker={})", broker);
 
     loop {
-        match consumer.recv().await {
+        let received = tokio::select! {
+            biased;
+            _ = drain.wait() => {
+                // Offsets sichern, damit übergebene Events nicht erneut zugestellt werden
+                for c in [&priority_consumer, &consumer] {
+                    if let Err(e) = c.commit_consumer_state(CommitMode::Sync) {
+                        warn!(%e, "failed to commit consumer offsets");
+                    }
+                }
+                info!("drained; pipeline-runner stopped");
+                return Ok(());
+            }
+            m = priority_consumer.recv() => m,
+            m = consumer.recv() => m,
+        };
+        match received {
             Err(e) => {
                 error!(%e, "kafka error");
                 continue;
@@ -233,26 +380,60 @@ async fn app_main() -> anyhow::Result<()> {
                     }
                 };
 
-                info!(id = evt.pdf_id, pipeline = %evt.pipeline_id, "processing event");
+                info!(id = evt.pdf_id, pipeline = %evt.pipeline_id, priority = %evt.priority, "processing event");
 
-                // Pipeline-Config laden
-                let row = match sqlx::query("SELECT config_json FROM pipelines WHERE id = $1")
-                    .bind(evt.pipeline_id)
-                    .fetch_one(&pool)
-                    .await
-                {
-                    Ok(r) => r,
-                    Err(e) => {
-                        warn!(%e, pipeline = %evt.pipeline_id, "pipeline config not found");
-                        continue;
-                    }
+                // Von einem anderen Replikat übergebener Run: dort weitermachen
+                let resume = match evt.run_id {
+                    Some(run_id) => match handoff::claim(&pool, run_id).await {
+                        Ok(resume) => resume,
+                        Err(e) => {
+                            warn!(%e, %run_id, "failed to claim run hand-off");
+                            None
+                        }
+                    },
+                    None => None,
                 };
 
-                let config_json: Value = match row.try_get("config_json") {
-                    Ok(v) => v,
-                    Err(e) => {
-                        warn!(%e, "config_json column missing/invalid");
-                        continue;
+                // Vorgezogene Läufe stehen auch noch in pipeline-run
+                if let Some(run_id) = evt.run_id.filter(|_| resume.is_none()) {
+                    match sqlx::query("SELECT 1 FROM pipeline_runs WHERE id = $1")
+                        .bind(run_id)
+                        .fetch_optional(&pool)
+                        .await
+                    {
+                        Ok(Some(_)) => {
+                            info!(%run_id, "run already started, skipping event");
+                            continue;
+                        }
+                        Ok(None) => {}
+                        Err(e) => warn!(%e, %run_id, "failed to check run id"),
+                    }
+                }
+
+                // Pipeline-Config laden (übergebene Runs behalten ihre)
+                let config_json: Value = match &resume {
+                    Some(resume) => resume.config_json.clone(),
+                    None => {
+                        let row = match sqlx::query(
+                            "SELECT config_json FROM pipelines WHERE id = $1",
+                        )
+                        .bind(evt.pipeline_id)
+                        .fetch_one(&pool)
+                        .await
+                        {
+                            Ok(r) => r,
+                            Err(e) => {
+                                warn!(%e, pipeline = %evt.pipeline_id, "pipeline config not found");
+                                continue;
+                            }
+                        };
+                        match row.try_get("config_json") {
+                            Ok(v) => v,
+                            Err(e) => {
+                                warn!(%e, "config_json column missing/invalid");
+                                continue;
+                            }
+                        }
                     }
                 };
 
@@ -268,6 +449,8 @@ async fn app_main() -> anyhow::Result<()> {
 
                 // Per-Scoring-Step Konfiguration (promptId → min_signal)
                 let mut scoring_cfg: HashMap<i32, f64> = HashMap::new();
+                // Gewicht je Scoring-Step im Overall Score (promptId → weight 0..1)
+                let mut scoring_weight: HashMap<i32, f32> = HashMap::new();
                 // Per-Decision-Step Konfiguration (promptId → min_confidence)
                 let mut decision_cfg: HashMap<i32, f64> = HashMap::new();
                 if let Some(steps) = config_json.get("steps").and_then(|v| v.as_array()) {
@@ -297,6 +480,11 @@ async fn app_main() -> anyhow::Result<()> {
                                     })
                                     .unwrap_or(0.0);
                                 scoring_cfg.insert(pid64 as i32, min_signal);
+                                let weight = cfgv
+                                    .and_then(|c| c.get("weight"))
+                                    .and_then(|v| v.as_f64())
+                                    .unwrap_or(1.0);
+                                scoring_weight.insert(pid64 as i32, weight.clamp(0.0, 1.0) as f32);
                             }
                         } else if t == "DecisionPrompt" {
                             let pid = s
@@ -326,7 +514,7 @@ async fn app_main() -> anyhow::Result<()> {
                 let pages: Vec<(i32, String)> = match sqlx::query(
                     r#"
                     SELECT page_no, text
-                    FROM pdf_texts
+                    FROM pdf_page_texts
                     WHERE merged_pdf_id = $1
                     ORDER BY page_no
                     "#,
@@ -349,6 +537,8 @@ async fn app_main() -> anyhow::Result<()> {
                     }
                 };
 
+                let pages = append_attachment_pages(&pool, evt.pdf_id, pages).await;
+
                 let total_chars: usize = pages.iter().map(|(_, t): &(i32, String)| t.len()).sum();
                 info!(
                     id = evt.pdf_id,
@@ -357,24 +547,222 @@ async fn app_main() -> anyhow::Result<()> {
                     "loaded pages from db"
                 );
 
-                // Run anlegen
-                let run_id = Uuid::new_v4();
-                if let Err(e) = sqlx::query(
-                    "INSERT INTO pipeline_runs (id, pipeline_id, pdf_id, status) VALUES ($1,$2,$3,'running')",
-                )
+                // Rerun: Final-Extraction des Ursprungslaufs statt neuer Extraktion
+                let reused = match evt.rerun_of {
+                    Some(source_run) => {
+                        match load_reused_extraction(&pool, master_key.as_ref(), source_run).await {
+                            Ok(reused) => {
+                                info!(%source_run, fields = reused.len(), "reusing extraction of earlier run");
+                                Some(reused)
+                            }
+                            Err(e) => {
+                                warn!(%e, %source_run, "failed to load extraction for rerun");
+                                continue;
+                            }
+                        }
+                    }
+                    None => None,
+                };
+
+                // Snapshot: laufende Runs behalten ihre Konfiguration, auch über eine Übergabe
+                let batch_cfg = match &resume {
+                    Some(resume) => resume.batch_cfg.clone(),
+                    None => {
+                        let mut batch_cfg = batch_cfg_rx.borrow().clone();
+                        batch_cfg.structured_outputs =
+                            run_flag(&pool, &flags::STRUCTURED_OUTPUTS, &evt).await;
+                        batch_cfg
+                    }
+                };
+
+                // Bei zu wenig Plattenplatz keinen neuen Run starten
+                wait_for_disk_space(&workspace_cfg).await;
+
+                // Run anlegen; Label/Aktenzeichen vom jüngsten Upload des PDFs übernehmen
+                let run_id = evt.run_id.unwrap_or_else(Uuid::new_v4);
+                let run_started = std::time::Instant::now();
+                // Wird am Ende der Iteration entfernt, auch bei `continue`
+                let run_workspace = match workspace_cfg.create(run_id) {
+                    Ok(ws) => {
+                        info!(%run_id, work_dir = %ws.path().display(), "run workspace created");
+                        ws
+                    }
+                    Err(e) => {
+                        error!(%e, %run_id, "failed to create run workspace");
+                        continue;
+                    }
+                };
+                let checkpoint = resume.map(|resume| resume.checkpoint);
+                let resumed = checkpoint.is_some();
+                let reference = if let Some(checkpoint) = &checkpoint {
+                    record_timeline(
+                        &pool,
+                        &TimelineEvent::new("pipeline-runner", "resumed")
+                            .pdf(Some(evt.pdf_id))
+                            .run(Some(run_id))
+                            .pipeline(Some(evt.pipeline_id))
+                            .details(
+                                json!({ "step": checkpoint.step, "replica": handoff::replica() }),
+                            ),
+                    )
+                    .await;
+                    sqlx::query_as::<_, (Option<String>, Option<String>)>(
+                        "SELECT job_label, external_ref FROM pipeline_runs WHERE id = $1",
+                    )
+                    .bind(run_id)
+                    .fetch_one(&pool)
+                    .await
+                } else {
+                    sqlx::query_as::<_, (Option<String>, Option<String>)>(
+                        "INSERT INTO pipeline_runs (id, pipeline_id, pdf_id, status, job_label, external_ref, rerun_of)
+                         SELECT $1, $2, $3, $4, u.job_label, u.external_ref, $5
+                         FROM (SELECT 1) AS one
+                         LEFT JOIN LATERAL (
+                             SELECT job_label, external_ref FROM uploads
+                             WHERE pdf_id = $3 ORDER BY id DESC LIMIT 1
+                         ) u ON TRUE
+                         RETURNING job_label, external_ref",
+                    )
                     .bind(run_id)
                     .bind(evt.pipeline_id)
                     .bind(evt.pdf_id)
-                    .execute(&pool)
+                    .bind(RunStatus::Running.as_str())
+                    .bind(evt.rerun_of)
+                    .fetch_one(&pool)
                     .await
-                {
-                    error!(%e, %run_id, "failed to insert pipeline_runs row");
-                    continue;
+                };
+                let (job_label, external_ref) = match reference {
+                    Ok(reference) => reference,
+                    Err(e) => {
+                        error!(%e, %run_id, resumed, "failed to insert pipeline_runs row");
+                        continue;
+                    }
+                };
+                if !resumed {
+                    record_timeline(
+                        &pool,
+                        &TimelineEvent::new("pipeline-runner", RunStatus::Running.as_str())
+                            .pdf(Some(evt.pdf_id))
+                            .run(Some(run_id))
+                            .pipeline(Some(evt.pipeline_id))
+                            .details(json!({ "rerun_of": evt.rerun_of })),
+                    )
+                    .await;
+                }
+
+                let credentials = tenant_openai_credentials(&pool, master_key.as_ref(), &evt).await;
+                // Konfiguration des Laufs für GET /runs/{id}/config festhalten
+                let tenant_endpoint = match &credentials {
+                    Ok(Some(credentials)) => Some(credentials.endpoint.clone()),
+                    _ => None,
+                };
+                let model = model_settings(&batch_cfg, tenant_endpoint);
+                // Übergebene Runs haben ihre Konfiguration schon beim Start festgehalten
+                if !resumed {
+                    if let Err(e) =
+                        run_config::capture(&pool, run_id, evt.pipeline_id, &config_json, &model)
+                            .await
+                    {
+                        warn!(%e, %run_id, "failed to store run config");
+                    }
                 }
 
-                // Ausführen
-                match runner::execute_with_pages(&cfg, &pages, &batch_cfg).await {
-                    Ok(outcome) => {
+                // Ausführen (mit den OpenAI-Credentials des Mandanten, falls hinterlegt)
+                let usage = Arc::new(TokenUsage::default());
+                let execution = openai_client::with_usage(usage.clone(), async {
+                    match credentials {
+                        Ok(Some(credentials)) => {
+                            info!(%run_id, "using tenant OpenAI credentials");
+                            openai_client::with_credentials(
+                                credentials,
+                                runner::execute_with_pages(
+                                    &cfg,
+                                    &pages,
+                                    &batch_cfg,
+                                    reused.as_ref(),
+                                    checkpoint,
+                                    &drain,
+                                ),
+                            )
+                            .await
+                        }
+                        Ok(None) => {
+                            runner::execute_with_pages(
+                                &cfg,
+                                &pages,
+                                &batch_cfg,
+                                reused.as_ref(),
+                                checkpoint,
+                                &drain,
+                            )
+                            .await
+                        }
+                        Err(e) => Err(e.context("tenant OpenAI credentials unavailable")),
+                    }
+                })
+                .await;
+                // Token-Verbrauch für Abrechnung/Usage-Report, auch bei fehlgeschlagenen Läufen;
+                // addiert, weil ein übergebener Run auf mehreren Replikaten läuft
+                if let Err(e) = sqlx::query(
+                    "UPDATE pipeline_runs
+                       SET prompt_tokens = prompt_tokens + $2,
+                           completion_tokens = completion_tokens + $3,
+                           openai_calls = openai_calls + $4
+                     WHERE id = $1",
+                )
+                .bind(run_id)
+                .bind(usage.prompt_tokens() as i64)
+                .bind(usage.completion_tokens() as i64)
+                .bind(usage.calls() as i32)
+                .execute(&pool)
+                .await
+                {
+                    warn!(%e, %run_id, "failed to store token usage");
+                }
+                let execution = match execution {
+                    Ok(runner::Execution::Finished(outcome)) => Ok(outcome),
+                    Ok(runner::Execution::Interrupted(checkpoint)) => {
+                        let step = checkpoint.step;
+                        let resume = handoff::Resume {
+                            config_json: config_json.clone(),
+                            batch_cfg: batch_cfg.clone(),
+                            checkpoint,
+                        };
+                        match handoff::hand_off(&pool, &evt, run_id, &resume).await {
+                            Ok(()) => {
+                                info!(%run_id, step, "run handed off");
+                                record_timeline(
+                                    &pool,
+                                    &TimelineEvent::new("pipeline-runner", "handed_off")
+                                        .pdf(Some(evt.pdf_id))
+                                        .run(Some(run_id))
+                                        .pipeline(Some(evt.pipeline_id))
+                                        .details(
+                                            json!({ "step": step, "replica": handoff::replica() }),
+                                        ),
+                                )
+                                .await;
+                                continue;
+                            }
+                            Err(e) => Err(e.context("hand-off on shutdown failed")),
+                        }
+                    }
+                    Err(e) => Err(e),
+                };
+                let run_status = if execution.is_ok() {
+                    RunStatus::Completed
+                } else {
+                    RunStatus::Failed
+                };
+                let mut contested = false;
+                match execution {
+                    Ok(mut outcome) => {
+                        // OCR-Konfidenz der zitierten Wörter an die Quellen hängen
+                        match ocr_confidence::OcrWords::load(&pool, evt.pdf_id).await {
+                            Ok(words) if !words.is_empty() => words.annotate_outcome(&mut outcome),
+                            Ok(_) => {}
+                            Err(e) => warn!(%e, %run_id, "failed to load ocr word confidence"),
+                        }
                         // 1) Batches als Steps loggen
                         let mut seq: i32 = 1;
                         for rs in &outcome.log {
@@ -390,7 +778,7 @@ async fn app_main() -> anyhow::Result<()> {
                                 .bind(rs.prompt_type.to_string())
                                 .bind(&rs.decision_key)
                                 .bind(&rs.route)
-                                .bind(&rs.result)
+                                .bind(scrubber.scrubbed(&rs.result))
                                 .execute(&pool)
                                 .await
                             {
@@ -429,11 +817,15 @@ async fn app_main() -> anyhow::Result<()> {
                                 .unwrap_or_else(|| format!("field_{}", pid));
 
                             // Quelle sicher extrahieren
-                            let (page_opt, quote_opt, bbox_opt) = match &chosen.source {
-                                Some(TextPosition { page, bbox, quote }) => {
-                                    (Some(*page as i32), quote.clone(), Some(*bbox))
-                                }
-                                None => (None, None, None),
+                            let (page_opt, quote_opt, bbox_opt, ocr_conf_opt) = match &chosen.source
+                            {
+                                Some(TextPosition {
+                                    page,
+                                    bbox,
+                                    quote,
+                                    confidence,
+                                }) => (Some(*page as i32), quote.clone(), Some(*bbox), *confidence),
+                                None => (None, None, None, None),
                             };
                             let conf = chosen.weight.unwrap_or(0.0);
 
@@ -442,7 +834,8 @@ async fn app_main() -> anyhow::Result<()> {
                                 "confidence": conf,
                                 "page": page_opt,
                                 "quote": quote_opt,
-                                "bbox": bbox_opt
+                                "bbox": bbox_opt,
+                                "ocr_confidence": ocr_conf_opt
                             });
 
                             if let Err(e) = sqlx::query(
@@ -455,7 +848,7 @@ async fn app_main() -> anyhow::Result<()> {
                                 .bind("final-extraction")
                                 .bind(pid)
                                 .bind(&key)
-                                .bind(&result)
+                                .bind(scrubber.scrubbed(&result))
                                 .bind(conf as f32)
                                 .bind(page_opt)
                                 .execute(&pool)
@@ -726,7 +1119,7 @@ async fn app_main() -> anyhow::Result<()> {
                                     .bind("final-scoring")
                                     .bind(pid)
                                     .bind(&key)
-                                    .bind(&result_json)
+                                    .bind(scrubber.scrubbed(&result_json))
                                     .bind(confidence)
                                     .execute(&pool)
                                     .await
@@ -740,8 +1133,9 @@ async fn app_main() -> anyhow::Result<()> {
                                 final_scores_hm.insert(key.clone(), score_tri as f32);
                                 final_score_labels_hm.insert(key.clone(), lbl_enum);
 
-                                overall_inputs_tri.push((score_tri as f32, confidence));
-                                overall_inputs_bool.push((result_bool, confidence));
+                                let weight = scoring_weight.get(&pid).copied().unwrap_or(1.0);
+                                overall_inputs_tri.push((score_tri as f32, confidence * weight));
+                                overall_inputs_bool.push((result_bool, confidence * weight));
                             }
 
                             // Fallback von outcome.scoring (falls log keine Inhalte hatte)
@@ -789,7 +1183,7 @@ async fn app_main() -> anyhow::Result<()> {
                                     .bind("final-scoring")
                                     .bind(pid)
                                     .bind(&key)
-                                    .bind(&result_json)
+                                    .bind(scrubber.scrubbed(&result_json))
                                     .bind(confidence)
                                     .execute(&pool)
                                     .await
@@ -802,37 +1196,21 @@ async fn app_main() -> anyhow::Result<()> {
                                 final_scores_hm.insert(key.clone(), score_tri as f32);
                                 final_score_labels_hm.insert(key.clone(), lbl_enum);
 
-                                overall_inputs_tri.push((score_tri as f32, confidence));
-                                overall_inputs_bool.push((r.result, confidence));
+                                let weight = scoring_weight.get(&pid).copied().unwrap_or(1.0);
+                                overall_inputs_tri.push((score_tri as f32, confidence * weight));
+                                overall_inputs_bool.push((r.result, confidence * weight));
                             }
                         }
 
-                        // 2c) Final-Decision je prompt_id (unverändert)
+                        // 2c) Final-Decision je prompt_id – alle Stimmen inkl. Begründung behalten,
+                        //     Widersprüche starker Stimmen als `contested` markieren
+                        let mut run_contested = false;
                         {
                             use std::collections::BTreeMap;
 
-                            #[derive(Default)]
-                            struct DecisionAgg {
-                                route_votes: BTreeMap<String, i64>,
-                                yes_votes: i64,
-                                no_votes: i64,
-                                support_by_route: BTreeMap<String, Vec<serde_json::Value>>,
-                                explanations_by_route: BTreeMap<String, Vec<String>>,
-                            }
-
-                            fn normalize_route(route: &str) -> String {
-                                route.trim().to_ascii_uppercase()
-                            }
-
-                            fn route_to_bool(route: &str) -> Option<bool> {
-                                match route {
-                                    "YES" | "TRUE" | "JA" | "Y" | "1" => Some(true),
-                                    "NO" | "FALSE" | "NEIN" | "N" | "0" => Some(false),
-                                    _ => None,
-                                }
-                            }
-
-                            let mut dc_by_pid: BTreeMap<i32, DecisionAgg> = BTreeMap::new();
+                            let contested_threshold = decision::contested_threshold();
+                            let mut votes_by_pid: BTreeMap<i32, Vec<decision::DecisionVote>> =
+                                BTreeMap::new();
 
                             for step in &outcome.log {
                                 if step.prompt_type != shared::dto::PromptType::DecisionPrompt {
@@ -841,212 +1219,65 @@ async fn app_main() -> anyhow::Result<()> {
                                 let Ok(pid) = i32::try_from(step.prompt_id) else {
                                     continue;
                                 };
-                                let agg = dc_by_pid.entry(pid).or_default();
-
-                                let votes = step
-                                    .result
-                                    .get("votes")
-                                    .and_then(|v| v.as_array())
-                                    .cloned()
-                                    .unwrap_or_default();
-
-                                if votes.is_empty() {
-                                    if let Some(cons) = step.result.get("consolidated") {
-                                        let route = cons
-                                            .get("route")
-                                            .and_then(|v| v.as_str())
-                                            .unwrap_or("UNKNOWN");
-                                        let norm = normalize_route(route);
-                                        *agg.route_votes.entry(norm.clone()).or_default() += 1;
-                                        if let Some(src) = cons.get("source") {
-                                            agg.support_by_route
-                                                .entry(norm.clone())
-                                                .or_default()
-                                                .push(src.clone());
-                                        }
-                                        if let Some(b) =
-                                            cons.get("boolean").and_then(|v| v.as_bool())
-                                        {
-                                            if b {
-                                                agg.yes_votes += 1;
-                                            } else {
-                                                agg.no_votes += 1;
-                                            }
-                                        } else if let Some(ans) = route_to_bool(&norm) {
-                                            if ans {
-                                                agg.yes_votes += 1;
-                                            } else {
-                                                agg.no_votes += 1;
-                                            }
-                                        }
-                                    }
-                                    continue;
-                                }
-
-                                for vote in votes {
-                                    let route = vote
-                                        .get("route")
-                                        .and_then(|v| v.as_str())
-                                        .unwrap_or("UNKNOWN");
-                                    let norm = normalize_route(route);
-                                    *agg.route_votes.entry(norm.clone()).or_default() += 1;
-
-                                    if let Some(src) = vote.get("source") {
-                                        agg.support_by_route
-                                            .entry(norm.clone())
-                                            .or_default()
-                                            .push(src.clone());
-                                    }
-
-                                    if let Some(val) = vote
-                                        .get("value")
-                                        .and_then(|v| v.get("explanation"))
-                                        .and_then(|x| x.as_str())
-                                    {
-                                        let trimmed = val.trim();
-                                        if !trimmed.is_empty() {
-                                            agg.explanations_by_route
-                                                .entry(norm.clone())
-                                                .or_default()
-                                                .push(trimmed.to_string());
-                                        }
-                                    }
-
-                                    if let Some(b) = vote.get("boolean").and_then(|v| v.as_bool()) {
-                                        if b {
-                                            agg.yes_votes += 1;
-                                        } else {
-                                            agg.no_votes += 1;
-                                        }
-                                    } else if let Some(ans) = route_to_bool(&norm) {
-                                        if ans {
-                                            agg.yes_votes += 1;
-                                        } else {
-                                            agg.no_votes += 1;
-                                        }
-                                    }
-                                }
+                                votes_by_pid
+                                    .entry(pid)
+                                    .or_default()
+                                    .extend(decision::votes_from_step(&step.result));
                             }
                             for r in &outcome.decision {
                                 let pid = r.prompt_id as i32;
-                                let mut agg = dc_by_pid.entry(pid).or_default();
-                                let current_votes: i64 = agg.route_votes.values().sum();
-                                if current_votes > 0 {
+                                let votes = votes_by_pid.entry(pid).or_default();
+                                if !votes.is_empty() {
                                     continue;
                                 }
-
-                                let route = r.route.clone().unwrap_or_else(|| "UNKNOWN".into());
-                                let norm = normalize_route(&route);
-                                *agg.route_votes.entry(norm.clone()).or_default() += 1;
-
-                                if let Some(src) =
-                                    r.source.as_ref().and_then(|s| serde_json::to_value(s).ok())
-                                {
-                                    agg.support_by_route
-                                        .entry(norm.clone())
-                                        .or_default()
-                                        .push(src);
-                                }
-
-                                if let Some(val) = r
-                                    .value
-                                    .as_ref()
-                                    .and_then(|v| v.get("explanation"))
-                                    .and_then(|x| x.as_str())
-                                {
-                                    let trimmed = val.trim();
-                                    if !trimmed.is_empty() {
-                                        agg.explanations_by_route
-                                            .entry(norm.clone())
-                                            .or_default()
-                                            .push(trimmed.to_string());
-                                    }
-                                }
-
-                                if let Some(b) = r.boolean {
-                                    if b {
-                                        agg.yes_votes += 1;
-                                    } else {
-                                        agg.no_votes += 1;
-                                    }
-                                } else if let Some(ans) = route_to_bool(&norm) {
-                                    if ans {
-                                        agg.yes_votes += 1;
-                                    } else {
-                                        agg.no_votes += 1;
-                                    }
+                                if let Ok(v) = serde_json::to_value(r) {
+                                    votes.push(decision::DecisionVote::from_result(
+                                        &v,
+                                        None,
+                                        Vec::new(),
+                                    ));
                                 }
                             }
 
-                            for (pid, agg) in dc_by_pid {
-                                let DecisionAgg {
-                                    route_votes,
-                                    yes_votes,
-                                    no_votes,
-                                    support_by_route,
-                                    explanations_by_route,
-                                } = agg;
-
-                                let total_votes: i64 = route_votes.values().sum();
-                                if total_votes <= 0 {
+                            for (pid, votes) in votes_by_pid {
+                                let Some(summary) = decision::aggregate(votes, contested_threshold)
+                                else {
                                     continue;
-                                }
-
-                                let (best_route, best_cnt) = route_votes
-                                    .iter()
-                                    .max_by(|a, b| a.1.cmp(b.1))
-                                    .map(|(route, cnt)| (route.clone(), *cnt))
-                                    .unwrap_or_else(|| (String::from("UNKNOWN"), 0));
-
-                                let mut confidence = (best_cnt as f32) / (total_votes as f32);
-                                if !confidence.is_finite() {
-                                    confidence = 0.0;
-                                }
-                                let confidence = confidence.clamp(0.0, 1.0);
+                                };
 
                                 let min_conf =
                                     decision_cfg.get(&pid).copied().unwrap_or(0.0) as f32;
-                                if confidence < min_conf {
+                                if summary.confidence < min_conf {
                                     continue;
                                 }
 
-                                let answer = route_to_bool(&best_route);
-
-                                let explanation =
-                                    explanations_by_route.get(&best_route).and_then(|vals| {
-                                        vals.iter().find(|s| !s.trim().is_empty()).cloned()
-                                    });
-
-                                let support: Vec<serde_json::Value> = support_by_route
-                                    .get(&best_route)
-                                    .map(|vec| vec.iter().take(3).cloned().collect())
-                                    .unwrap_or_default();
-
                                 let key = format!("decision_{}", pid);
-                                let result_json = serde_json::json!({
-                                    "route": best_route,
-                                    "answer": answer,
-                                    "confidence": confidence,
-                                    "votes_yes": agg.yes_votes,
-                                    "votes_no": agg.no_votes,
-                                    "explanation": explanation,
-                                    "support": support
-                                });
+                                let result_json = summary.to_json();
+                                if summary.contested {
+                                    run_contested = true;
+                                    info!(
+                                        %run_id,
+                                        final_key = %key,
+                                        contradiction_score = summary.contradiction_score,
+                                        "final decision contested"
+                                    );
+                                }
 
                                 if let Err(e) = sqlx::query(
                                     "INSERT INTO pipeline_run_steps
-                                       (run_id, seq_no, step_id, prompt_id, prompt_type, is_final, final_key, result, confidence, answer, route)
-                                     VALUES ($1,$2,$3,$4,'DecisionPrompt',true,$5,$6,$7,$8,$9)"
+                                       (run_id, seq_no, step_id, prompt_id, prompt_type, is_final, final_key, result, confidence, answer, route, contested)
+                                     VALUES ($1,$2,$3,$4,'DecisionPrompt',true,$5,$6,$7,$8,$9,$10)"
                                 )
                                     .bind(run_id)
                                     .bind(seq)
                                     .bind("final-decision")
                                     .bind(pid)
                                     .bind(&key)
-                                    .bind(&result_json)
-                                    .bind(confidence)
-                                    .bind(answer)
-                                    .bind(result_json.get("route").and_then(|x| x.as_str()).unwrap_or("UNKNOWN"))
+                                    .bind(scrubber.scrubbed(&result_json))
+                                    .bind(summary.confidence)
+                                    .bind(summary.answer)
+                                    .bind(&summary.route)
+                                    .bind(summary.contested)
                                     .execute(&pool)
                                     .await
                                 {
@@ -1056,12 +1287,12 @@ async fn app_main() -> anyhow::Result<()> {
 
                                 // Für pipeline_runs sammeln
                                 final_decisions_map
-                                    .insert(key.clone(), json!(answer.unwrap_or(false)));
+                                    .insert(key.clone(), json!(summary.answer.unwrap_or(false)));
                             }
                         }
 
                         // 3) Overall Score (Zahl auf Run-Ebene)
-                        //    Tri-State bevorzugen (Normierung (score+1)/2), Gewicht = Konsolidierungs-Confidence.
+                        //    Tri-State bevorzugen (Normierung (score+1)/2), Gewicht = Konsolidierungs-Confidence × Step-`weight`.
                         let overall: f32 = if !overall_inputs_tri.is_empty() {
                             let mut sum_w = 0.0f32;
                             let mut sum_v = 0.0f32;
@@ -1087,6 +1318,19 @@ async fn app_main() -> anyhow::Result<()> {
                         } else {
                             Value::Object(final_extraction_map.clone())
                         };
+                        // Volle Werte nur verschlüsselt; Klartext-Spalte wird gescrubbt
+                        let final_extraction_sealed = match master_key.as_ref() {
+                            Some(key) if scrubber.is_enabled() && !final_extraction_v.is_null() => {
+                                key.seal(&final_extraction_v.to_string())
+                                    .map_err(
+                                        |e| warn!(%e, %run_id, "failed to seal final extraction"),
+                                    )
+                                    .ok()
+                                    .and_then(|sealed| serde_json::to_value(sealed).ok())
+                            }
+                            _ => None,
+                        };
+                        let final_extraction_v = scrubber.scrubbed(&final_extraction_v);
                         let final_scores_v = if final_scores_map.is_empty() {
                             Value::Null
                         } else {
@@ -1101,11 +1345,13 @@ async fn app_main() -> anyhow::Result<()> {
                         if let Err(e) = sqlx::query(
                             "UPDATE pipeline_runs
                                SET finished_at = now(),
-                                   status = 'finished',
+                                   status = $8,
                                    overall_score = $2,
                                    final_extraction = COALESCE($3, final_extraction),
                                    final_scores     = COALESCE($4, final_scores),
-                                   final_decisions  = COALESCE($5, final_decisions)
+                                   final_decisions  = COALESCE($5, final_decisions),
+                                   contested = $6,
+                                   final_extraction_sealed = $7
                              WHERE id = $1",
                         )
                         .bind(run_id)
@@ -1113,11 +1359,23 @@ async fn app_main() -> anyhow::Result<()> {
                         .bind(final_extraction_v)
                         .bind(final_scores_v)
                         .bind(final_decisions_v)
+                        .bind(run_contested)
+                        .bind(final_extraction_sealed)
+                        .bind(RunStatus::Completed.as_str())
                         .execute(&pool)
                         .await
                         {
                             warn!(%e, %run_id, "failed to finalize pipeline_run row");
                         }
+                        record_timeline(
+                            &pool,
+                            &TimelineEvent::new("pipeline-runner", RunStatus::Completed.as_str())
+                                .pdf(Some(evt.pdf_id))
+                                .run(Some(run_id))
+                                .pipeline(Some(evt.pipeline_id))
+                                .details(json!({ "overall_score": overall })),
+                        )
+                        .await;
 
                         // 4) Event für UI/Monitoring – mit run_id
                         let (started_at, finished_at) = match sqlx::query_as::<
@@ -1141,7 +1399,20 @@ async fn app_main() -> anyhow::Result<()> {
                             }
                         };
 
+                        // Kundenfeldnamen laut output_mapping der Pipeline
+                        let output = (!cfg.output_mapping.is_empty()).then(|| {
+                            output_mapping::render(
+                                &cfg.output_mapping,
+                                &output_mapping::collect_finals([
+                                    &final_extraction_map,
+                                    &final_scores_map,
+                                    &final_decisions_map,
+                                ]),
+                            )
+                        });
+
                         let result = PipelineRunResult {
+                            schema_version: PipelineRunResult::SCHEMA_VERSION,
                             run_id: Some(run_id),
                             pdf_id: evt.pdf_id,
                             pipeline_id: evt.pipeline_id,
@@ -1153,32 +1424,386 @@ async fn app_main() -> anyhow::Result<()> {
                             log: outcome.log,
                             final_scores: Some(final_scores_hm),
                             final_score_labels: Some(final_score_labels_hm),
-                            status: Some("finished".to_string()),
+                            final_decisions: Some(
+                                final_decisions_map
+                                    .iter()
+                                    .filter_map(|(k, v)| v.as_bool().map(|b| (k.clone(), b)))
+                                    .collect(),
+                            ),
+                            status: Some(RunStatus::Completed),
                             started_at,
                             finished_at,
+                            contested: run_contested,
+                            job_label,
+                            external_ref,
+                            output,
+                            rerun_of: evt.rerun_of,
                         };
 
                         if let Ok(mut result_json) = serde_json::to_value(&result) {
                             result_json["run_id"] = json!(run_id.to_string());
+                            // Event nur auf Wunsch maskieren, Sinks erwarten Klartext
+                            if scrub_events {
+                                scrubber.scrub(&mut result_json);
+                            }
                             if let Ok(payload) = serde_json::to_string(&result_json) {
-                                let _ = producer
-                                    .send(
-                                        FutureRecord::to("pipeline-result")
-                                            .payload(&payload)
-                                            .key(&run_id.to_string()),
-                                        Duration::from_secs(0),
-                                    )
-                                    .await;
+                                // Zustellung über die Outbox (Retry bis Kafka bestätigt)
+                                if let Err(e) = outbox::enqueue(
+                                    &pool,
+                                    "pipeline-result",
+                                    Some(&shared::kafka::document_key(evt.pdf_id)),
+                                    &payload,
+                                )
+                                .await
+                                {
+                                    error!(%e, %run_id, "failed to enqueue pipeline-result");
+                                }
                             }
                         }
+                        contested = run_contested;
                     }
                     Err(e) => {
                         error!(%e, %run_id, "pipeline execution failed");
-                        let _ = sqlx::query("UPDATE pipeline_runs SET status='failed', finished_at=now() WHERE id=$1")
-                            .bind(run_id).execute(&pool).await;
+                        let _ = sqlx::query(
+                            "UPDATE pipeline_runs SET status=$2, finished_at=now() WHERE id=$1",
+                        )
+                        .bind(run_id)
+                        .bind(RunStatus::Failed.as_str())
+                        .execute(&pool)
+                        .await;
+                        record_timeline(
+                            &pool,
+                            &TimelineEvent::new("pipeline-runner", RunStatus::Failed.as_str())
+                                .pdf(Some(evt.pdf_id))
+                                .run(Some(run_id))
+                                .pipeline(Some(evt.pipeline_id))
+                                .message(e.to_string()),
+                        )
+                        .await;
+                    }
+                }
+                if resumed {
+                    handoff::finish(&pool, run_id).await;
+                }
+                telemetry.spawn_emit(
+                    EventKind::RunFinished,
+                    json!({
+                        "pipeline": evt.pipeline_id,
+                        "status": run_status.as_str(),
+                        "duration_ms": run_started.elapsed().as_millis() as u64,
+                        "pages": pages.len(),
+                        "steps": cfg.steps.len(),
+                        "contested": contested,
+                        "reused_extraction": evt.rerun_of.is_some(),
+                    }),
+                );
+                info!(
+                    %run_id,
+                    scratch_bytes = run_workspace.size(),
+                    "run finished; removing workspace"
+                );
+            }
+        }
+    }
+}
+
+/// Appends a status event to `run_timeline` (best effort).
+async fn record_timeline(pool: &PgPool, event: &TimelineEvent) {
+    if let Err(e) = sqlx::query(timeline::INSERT_SQL)
+        .bind(event.pdf_id)
+        .bind(event.upload_id)
+        .bind(event.run_id)
+        .bind(event.pipeline_id)
+        .bind(&event.source)
+        .bind(&event.status)
+        .bind(&event.message)
+        .bind(&event.details)
+        .execute(pool)
+        .await
+    {
+        warn!(%e, status = %event.status, "failed to append run_timeline event");
+    }
+}
+
+/// Appends the pages of embedded PDFs (`pdf_attachment_texts`, text-extraction
+/// with `EXTRACT_ATTACHMENTS=1`) after the document pages, numbered on from the
+/// last page; the first page of every attachment starts with its file name.
+async fn append_attachment_pages(
+    pool: &PgPool,
+    pdf_id: i32,
+    mut pages: Vec<(i32, String)>,
+) -> Vec<(i32, String)> {
+    let rows = match sqlx::query_as::<_, (String, i32, String)>(
+        "SELECT name, page_no, text FROM pdf_attachment_texts
+          WHERE merged_pdf_id = $1
+          ORDER BY attachment_no, page_no",
+    )
+    .bind(pdf_id)
+    .fetch_all(pool)
+    .await
+    {
+        Ok(rows) => rows,
+        // Tabelle fehlt, solange text-extraction keine Anhänge extrahiert hat
+        Err(e) => {
+            warn!(%e, pdf_id, "attachment pages not loaded");
+            return pages;
+        }
+    };
+    let mut next = pages.iter().map(|(no, _)| no + 1).max().unwrap_or(0);
+    for (name, page_no, text) in rows {
+        let text = if page_no == 0 {
+            format!("[anhang: {name}]\n{text}")
+        } else {
+            text
+        };
+        pages.push((next, text));
+        next += 1;
+    }
+    pages
+}
+
+/// Loads the final extraction of `source_run` for a rerun, keyed by prompt id.
+///
+/// Stored step results are scrubbed; when the run also sealed its full
+/// extraction and the master key is available, those values are used instead.
+async fn load_reused_extraction(
+    pool: &PgPool,
+    master_key: Option<&MasterKey>,
+    source_run: Uuid,
+) -> anyhow::Result<runner::ReusedExtraction> {
+    let rows = sqlx::query_as::<_, (i32, Option<String>, Option<Value>)>(
+        "SELECT prompt_id, final_key, result
+           FROM pipeline_run_steps
+          WHERE run_id = $1 AND is_final = TRUE AND prompt_type = 'ExtractionPrompt'",
+    )
+    .bind(source_run)
+    .fetch_all(pool)
+    .await?;
+
+    let sealed = sqlx::query_scalar::<_, Option<Value>>(
+        "SELECT final_extraction_sealed FROM pipeline_runs WHERE id = $1",
+    )
+    .bind(source_run)
+    .fetch_optional(pool)
+    .await?
+    .flatten();
+    let unsealed: Option<Value> = match (master_key, sealed) {
+        (Some(master), Some(sealed)) => {
+            let secret: SealedSecret = serde_json::from_value(sealed)?;
+            Some(serde_json::from_str(&master.open(&secret)?)?)
+        }
+        _ => None,
+    };
+
+    Ok(rows
+        .into_iter()
+        .map(|(pid, key, result)| {
+            let key = key.unwrap_or_else(|| format!("field_{pid}"));
+            let result = unsealed
+                .as_ref()
+                .and_then(|full| full.get(&key))
+                .or(result.as_ref())
+                .cloned()
+                .unwrap_or(Value::Null);
+            (pid, runner::reused_extraction_result(pid, &key, &result))
+        })
+        .collect())
+}
+
+/// Polls `app_settings.runner_settings` and publishes the effective [`runner::BatchCfg`].
+///
+/// The interval is `RUNNER_SETTINGS_REFRESH_SECS` (default 15); invalid or
+/// unreadable settings keep the last applied configuration.
+/// Publishes the runner's disk usage to `app_settings.runner_disk_usage`
+/// every `RUNNER_DISK_CHECK_SECS`.
+fn spawn_disk_gauge(pool: PgPool, cfg: workspace::WorkspaceConfig) {
+    tokio::spawn(async move {
+        loop {
+            let usage = cfg.usage();
+            if let Ok(value) = serde_json::to_string(&usage) {
+                if let Err(e) = sqlx::query(
+                    "INSERT INTO app_settings (key, value, updated_at)
+                     VALUES ('runner_disk_usage', $1, now())
+                     ON CONFLICT (key)
+                     DO UPDATE SET value = EXCLUDED.value, updated_at = now()",
+                )
+                .bind(value)
+                .execute(&pool)
+                .await
+                {
+                    warn!(%e, "failed to store runner disk usage");
+                }
+            }
+            tokio::time::sleep(cfg.check_interval).await;
+        }
+    });
+}
+
+/// Blocks until the work dir has at least `RUNNER_MIN_FREE_MB` free; the
+/// current message is kept and processed afterwards.
+async fn wait_for_disk_space(cfg: &workspace::WorkspaceConfig) {
+    loop {
+        let usage = cfg.usage();
+        if !usage.is_low() {
+            return;
+        }
+        warn!(
+            free_bytes = usage.free_bytes,
+            min_free_bytes = usage.min_free_bytes,
+            work_dir_bytes = usage.work_dir_bytes,
+            "free disk space below threshold; not starting new runs"
+        );
+        tokio::time::sleep(cfg.check_interval).await;
+    }
+}
+
+fn spawn_settings_refresh(
+    pool: PgPool,
+    base: runner::BatchCfg,
+    refresh: Arc<Notify>,
+) -> watch::Receiver<runner::BatchCfg> {
+    let (tx, rx) = watch::channel(base.clone());
+    let interval = Duration::from_secs(env_parse("RUNNER_SETTINGS_REFRESH_SECS", 15u64).max(1));
+    tokio::spawn(async move {
+        let mut applied = RunnerSettings::default();
+        loop {
+            match runner_settings::fetch(&pool).await {
+                Ok(settings) if settings != applied => match settings.validate() {
+                    Ok(()) => {
+                        let cfg = base.with_overrides(&settings);
+                        info!(?cfg, "runner settings updated");
+                        tx.send_replace(cfg);
+                        applied = settings;
                     }
+                    Err(e) => warn!(%e, "ignoring invalid runner settings"),
+                },
+                Ok(_) => {}
+                Err(e) => warn!(%e, "failed to refresh runner settings"),
+            }
+            tokio::select! {
+                _ = tokio::time::sleep(interval) => {}
+                _ = refresh.notified() => {}
+            }
+        }
+    });
+    rx
+}
+
+/// Follows `settings-changed` (see [`app_settings`]): reloads the OpenAI
+/// version and wakes the runner settings refresh. Each instance reads with its
+/// own consumer group from the end of the topic, so every runner reloads.
+fn spawn_settings_listener(broker: &str, pool: PgPool, refresh: Arc<Notify>) -> anyhow::Result<()> {
+    let consumer: StreamConsumer = ClientConfig::new()
+        .set(
+            "group.id",
+            format!("pipeline-runner-settings-{}", Uuid::new_v4()),
+        )
+        .set("bootstrap.servers", broker)
+        .set("enable.auto.commit", "false")
+        .set("auto.offset.reset", "latest")
+        .create()?;
+    consumer.subscribe(&[app_settings::SETTINGS_CHANGED_TOPIC])?;
+    tokio::spawn(async move {
+        loop {
+            let message = match consumer.recv().await {
+                Ok(m) => m,
+                Err(e) => {
+                    warn!(%e, "settings-changed consumer error");
+                    tokio::time::sleep(Duration::from_secs(1)).await;
+                    continue;
                 }
+            };
+            let Some(Ok(payload)) = message.payload_view::<str>() else {
+                continue;
+            };
+            let event: app_settings::SettingsChanged = match serde_json::from_str(payload) {
+                Ok(event) => event,
+                Err(e) => {
+                    warn!(%e, "failed to parse settings-changed payload");
+                    continue;
+                }
+            };
+            info!(key = %event.key, changed_by = ?event.changed_by, "settings changed");
+            match event.key.as_str() {
+                openai_settings::OPENAI_VERSION_KEY => {
+                    if let Err(e) = configure_openai_from_settings(&pool).await {
+                        warn!(%e, "failed to reload OpenAI configuration");
+                    }
+                }
+                runner_settings::RUNNER_SETTINGS_KEY => refresh.notify_one(),
+                _ => {}
+            }
+        }
+    });
+    Ok(())
+}
+
+/// Resolves a feature flag for the run's tenant; lookup errors keep the default.
+async fn run_flag(pool: &PgPool, flag: &FeatureFlag, evt: &PdfUploaded) -> bool {
+    let tenant_id =
+        match tenant_credentials::tenant_for_pdf(pool, evt.pdf_id, evt.pipeline_id).await {
+            Ok(tenant_id) => tenant_id,
+            Err(e) => {
+                warn!(%e, flag = flag.key, "failed to resolve tenant for feature flag");
+                None
+            }
+        };
+    match flags::is_enabled(pool, flag, tenant_id).await {
+        Ok(enabled) => enabled,
+        Err(e) => {
+            warn!(%e, flag = flag.key, "failed to read feature flag");
+            flag.default
+        }
+    }
+}
+
+/// Model settings of a run; `tenant_endpoint` is set when the tenant's
+/// credentials are used (inner `None` keeps the global endpoint).
+fn model_settings(
+    batch_cfg: &runner::BatchCfg,
+    tenant_endpoint: Option<Option<String>>,
+) -> ModelSettings {
+    let openai = openai_client::current_openai_config();
+    ModelSettings {
+        model: openai.default_model,
+        endpoint: tenant_endpoint.clone().flatten().unwrap_or(openai.endpoint),
+        endpoint_kind: openai.endpoint_kind.as_str().to_string(),
+        tenant_credentials: tenant_endpoint.is_some(),
+        page_batch_size: batch_cfg.page_batch_size,
+        max_parallel: batch_cfg.max_parallel,
+        max_chars: batch_cfg.max_chars,
+        openai_timeout_ms: batch_cfg.openai_timeout_ms,
+        openai_retries: batch_cfg.openai_retries,
+        structured_outputs: batch_cfg.structured_outputs,
+    }
+}
+
+/// Resolves the OpenAI credentials of the run's tenant, if any are stored.
+///
+/// A tenant with stored credentials never silently falls back to the global key.
+async fn tenant_openai_credentials(
+    pool: &PgPool,
+    master_key: Option<&MasterKey>,
+    evt: &PdfUploaded,
+) -> anyhow::Result<Option<OpenAiCredentials>> {
+    let Some(tenant_id) =
+        tenant_credentials::tenant_for_pdf(pool, evt.pdf_id, evt.pipeline_id).await?
+    else {
+        return Ok(None);
+    };
+    match master_key {
+        Some(master) => Ok(tenant_credentials::load(pool, master, tenant_id).await?),
+        None => {
+            if tenant_credentials::summary(pool, tenant_id)
+                .await?
+                .is_some()
+            {
+                anyhow::bail!(
+                    "tenant {tenant_id} has OpenAI credentials but {} is not set",
+                    envelope::MASTER_KEY_ENV
+                );
             }
+            Ok(None)
         }
     }
 }
@@ -0,0 +1,179 @@
+//! OCR word confidence of cited sources.
+//!
+//! text-extraction stores the Tesseract confidence of every OCR word in
+//! `pdf_texts.layout_json`. After a run the quotes of all sources are located
+//! on their page and [`TextPosition::confidence`] is set to the mean confidence
+//! of the matched words, so consumers can weight OCR-derived evidence lower
+//! than quotes from the PDF text layer (which stay `None`).
+
+use std::collections::HashMap;
+
+use serde::Deserialize;
+use shared::dto::TextPosition;
+use sqlx::{PgPool, Row};
+
+use crate::runner::RunOutcome;
+
+#[derive(Debug, Deserialize)]
+struct LayoutWord {
+    text: String,
+    #[serde(default)]
+    confidence: Option<f32>,
+}
+
+#[derive(Debug, Deserialize)]
+struct PageLayout {
+    #[serde(default)]
+    words: Vec<LayoutWord>,
+}
+
+/// OCR words per zero-based page of one PDF.
+#[derive(Debug, Default)]
+pub struct OcrWords {
+    pages: HashMap<u32, Vec<LayoutWord>>,
+}
+
+impl OcrWords {
+    /// Loads the layouts of all OCR pages (`ocr_confidence` set) of `pdf_id`.
+    pub async fn load(pool: &PgPool, pdf_id: i32) -> sqlx::Result<Self> {
+        let rows = sqlx::query(
+            "SELECT page_no, layout_json::text AS layout FROM pdf_texts
+              WHERE merged_pdf_id = $1 AND ocr_confidence IS NOT NULL AND layout_json IS NOT NULL",
+        )
+        .bind(pdf_id)
+        .fetch_all(pool)
+        .await?;
+        let mut pages = HashMap::new();
+        for row in rows {
+            let page_no: i32 = row.get("page_no");
+            let layout: String = row.get("layout");
+            if let (Ok(page), Ok(layout)) = (
+                u32::try_from(page_no),
+                serde_json::from_str::<PageLayout>(&layout),
+            ) {
+                pages.insert(page, layout.words);
+            }
+        }
+        Ok(Self { pages })
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.pages.is_empty()
+    }
+
+    /// Mean confidence of the longest run of words matching the start of `quote`.
+    fn quote_confidence(&self, page: u32, quote: &str) -> Option<f32> {
+        let words = self.pages.get(&page)?;
+        let needle: Vec<String> = quote
+            .split_whitespace()
+            .map(normalize_token)
+            .filter(|t| !t.is_empty())
+            .collect();
+        let tokens: Vec<(usize, String)> = words
+            .iter()
+            .enumerate()
+            .map(|(i, w)| (i, normalize_token(&w.text)))
+            .filter(|(_, t)| !t.is_empty())
+            .collect();
+
+        let mut best: Option<(usize, usize)> = None;
+        for start in 0..tokens.len() {
+            let len = tokens[start..]
+                .iter()
+                .zip(&needle)
+                .take_while(|((_, a), b)| a == *b)
+                .count();
+            if len > best.map(|(_, l)| l).unwrap_or(0) {
+                best = Some((start, len));
+            }
+        }
+        let (start, len) = best?;
+        if len < needle.len().min(2) {
+            return None;
+        }
+        let scores: Vec<f32> = tokens[start..start + len]
+            .iter()
+            .filter_map(|(i, _)| words[*i].confidence)
+            .collect();
+        if scores.is_empty() {
+            return None;
+        }
+        Some(scores.iter().sum::<f32>() / scores.len() as f32)
+    }
+
+    /// Sets the confidence of a source that has a quote and none yet.
+    pub fn annotate(&self, source: &mut TextPosition) {
+        if source.confidence.is_some() {
+            return;
+        }
+        if let Some(quote) = source.quote.as_deref() {
+            source.confidence = self.quote_confidence(source.page, quote);
+        }
+    }
+
+    /// Annotates the sources of all extraction, scoring and decision results.
+    pub fn annotate_outcome(&self, outcome: &mut RunOutcome) {
+        let prompt_sources = outcome
+            .extraction
+            .iter_mut()
+            .chain(outcome.decision.iter_mut())
+            .filter_map(|r| r.source.as_mut());
+        for source in prompt_sources {
+            self.annotate(source);
+        }
+        for result in &mut outcome.scoring {
+            self.annotate(&mut result.source);
+        }
+    }
+}
+
+fn normalize_token(raw: &str) -> String {
+    raw.chars()
+        .filter(|c| c.is_alphanumeric())
+        .flat_map(char::to_lowercase)
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn word(text: &str, confidence: Option<f32>) -> LayoutWord {
+        LayoutWord {
+            text: text.to_string(),
+            confidence,
+        }
+    }
+
+    #[test]
+    fn annotates_located_quotes_only() {
+        let words = OcrWords {
+            pages: HashMap::from([(
+                1,
+                vec![
+                    word("Kaufpreis:", Some(0.5)),
+                    word("250.000", Some(0.75)),
+                    word("EUR", Some(1.0)),
+                ],
+            )]),
+        };
+        let position = |page, quote: &str| TextPosition {
+            page,
+            bbox: [0.0; 4],
+            quote: Some(quote.to_string()),
+            confidence: None,
+        };
+
+        let mut ocr = position(1, "Kaufpreis: 250.000");
+        words.annotate(&mut ocr);
+        assert_eq!(ocr.confidence, Some(0.625));
+
+        let mut vector_page = position(0, "Kaufpreis: 250.000");
+        words.annotate(&mut vector_page);
+        assert_eq!(vector_page.confidence, None);
+
+        let mut unmatched = position(1, "Verkäufer Müller");
+        words.annotate(&mut unmatched);
+        assert_eq!(unmatched.confidence, None);
+    }
+}
@@ -1,9 +1,12 @@
 //! Orchestrates the execution of pipeline steps and integrates OpenAI calls.
 
 use std::collections::HashMap;
+use std::future::Future;
 use std::time::Duration;
 
-use futures::{stream, StreamExt};
+use futures::{future, stream, StreamExt};
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
 use serde_json::{json, Value as JsonValue};
 use tracing::{info, warn};
 
@@ -11,23 +14,44 @@ use shared::dto::{
     PipelineConfig, PromptResult, PromptType, RunStep, ScoringResult, TernaryLabel, TextPosition,
 };
 use shared::openai_client as ai;
+use shared::runner_settings::RunnerSettings;
 
-#[derive(Clone, Debug)]
+use shared::packing::{batches_for_step, BatchLayout, PackedBatch};
+
+use crate::handoff::Drain;
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
 /// Runtime configuration for batched OpenAI requests.
 pub struct BatchCfg {
     /// Number of pages to include per extraction batch.
     pub page_batch_size: usize,
     /// Maximum number of concurrent OpenAI requests.
     pub max_parallel: usize,
-    /// Maximum number of characters per request payload.
+    /// Maximum number of characters per request payload (page markers included).
     pub max_chars: usize,
     /// Timeout for OpenAI requests in milliseconds.
     pub openai_timeout_ms: u64,
     /// Number of retries to attempt when OpenAI calls fail.
     pub openai_retries: usize,
+    /// Feature flag `runner.structured_outputs`, resolved per run for its tenant.
+    pub structured_outputs: bool,
+}
+
+impl BatchCfg {
+    /// Applies runtime overrides from `app_settings` on top of the env defaults.
+    pub fn with_overrides(&self, settings: &RunnerSettings) -> Self {
+        Self {
+            page_batch_size: settings.page_batch_size.unwrap_or(self.page_batch_size),
+            max_parallel: settings.max_parallel.unwrap_or(self.max_parallel),
+            max_chars: settings.max_chars.unwrap_or(self.max_chars),
+            openai_timeout_ms: settings.openai_timeout_ms.unwrap_or(self.openai_timeout_ms),
+            openai_retries: settings.openai_retries.unwrap_or(self.openai_retries),
+            structured_outputs: self.structured_outputs,
+        }
+    }
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
 /// Aggregated outcome produced by executing a pipeline.
 pub struct RunOutcome {
     pub extraction: Vec<PromptResult>,
@@ -36,16 +60,97 @@ pub struct RunOutcome {
     pub log: Vec<RunStep>,
 }
 
+#[derive(Debug, Clone, Serialize, Deserialize)]
+/// Position of a run interrupted by a drain (see `handoff`).
+pub struct Checkpoint {
+    /// Index in `PipelineConfig::steps` of the step to run next.
+    pub step: usize,
+    pub route: String,
+    pub seq_no: u32,
+    /// Results of the steps before `step`.
+    pub outcome: RunOutcome,
+    /// Finished batch results of `step`, in batch order.
+    #[serde(default)]
+    pub batches_done: JsonValue,
+}
+
+#[derive(Debug)]
+/// Result of [`execute_with_pages`].
+pub enum Execution {
+    Finished(RunOutcome),
+    /// Stopped for a drain; the remaining steps still have to run.
+    Interrupted(Checkpoint),
+}
+
+/// Final extraction results of an earlier run, keyed by prompt id.
+pub type ReusedExtraction = HashMap<i32, PromptResult>;
+
+/// Rebuilds an extraction result from a persisted `final-extraction` step
+/// (`{ value, confidence, page, quote, bbox, ocr_confidence }`).
+pub fn reused_extraction_result(
+    prompt_id: i32,
+    final_key: &str,
+    result: &JsonValue,
+) -> PromptResult {
+    let page = result
+        .get("page")
+        .and_then(|v| v.as_u64())
+        .and_then(|p| u32::try_from(p).ok());
+    let bbox = result
+        .get("bbox")
+        .and_then(|v| serde_json::from_value::<[f32; 4]>(v.clone()).ok())
+        .unwrap_or([0.0, 0.0, 0.0, 0.0]);
+    let quote = result
+        .get("quote")
+        .and_then(|v| v.as_str())
+        .map(str::to_string);
+    PromptResult {
+        prompt_id,
+        prompt_type: PromptType::ExtractionPrompt,
+        prompt_text: String::new(),
+        value: result.get("value").filter(|v| !v.is_null()).cloned(),
+        boolean: None,
+        route: None,
+        weight: result
+            .get("confidence")
+            .and_then(|v| v.as_f64())
+            .map(|c| c as f32),
+        source: page.map(|page| TextPosition {
+            page,
+            bbox,
+            quote,
+            confidence: result
+                .get("ocr_confidence")
+                .and_then(|v| v.as_f64())
+                .map(|c| c as f32),
+        }),
+        openai_raw: String::new(),
+        json_key: Some(final_key.to_string()),
+        error: None,
+    }
+}
+
 /// Executes a pipeline against the provided pages using the supplied batching
 /// configuration.
+///
+/// Extraction steps whose prompt has an entry in `reused` take that result
+/// instead of calling OpenAI; scoring and decision steps always run.
+///
+/// With `resume` the run continues at the checkpoint. Once `drain` is set no
+/// further batch is started; the run returns [`Execution::Interrupted`] with
+/// the results so far.
 pub async fn execute_with_pages(
     cfg: &PipelineConfig,
     pages: &[(i32, String)],
     batch_cfg: &BatchCfg,
-) -> anyhow::Result<RunOutcome> {
+    reused: Option<&ReusedExtraction>,
+    resume: Option<Checkpoint>,
+    drain: &Drain,
+) -> anyhow::Result<Execution> {
     info!(
-        "run: pages={} (batch_size={}, max_parallel={}, max_chars={}, timeout={}ms, retries={})",
+        "run: pages={} resume_at={:?} (batch_size={}, max_parallel={}, max_chars={}, timeout={}ms, retries={})",
         pages.len(),
+        resume.as_ref().map(|cp| cp.step),
         batch_cfg.page_batch_size,
         batch_cfg.max_parallel,
         batch_cfg.max_chars,
@@ -53,19 +158,29 @@ pub async fn execute_with_pages(
         batch_cfg.openai_retries
     );
 
+    let layout = BatchLayout {
+        page_batch_size: batch_cfg.page_batch_size,
+        max_chars: batch_cfg.max_chars,
+        min_pages_for_batching: env_usize("PIPELINE_MIN_PAGES_FOR_BATCHING", 4),
+        overlap_pages: env_usize("PIPELINE_OVERLAP_PAGES", 1),
+    };
+
     // Map für Evidence-Resolver: echte Seiten (1-basiert) → Text
     let page_map: HashMap<u32, String> =
         pages.iter().map(|(p, t)| (*p as u32, t.clone())).collect();
 
-    let mut extraction_all: Vec<PromptResult> = Vec::new();
-    let mut scoring_all: Vec<ScoringResult> = Vec::new();
-    let mut decision_all: Vec<PromptResult> = Vec::new();
-    let mut run_log: Vec<RunStep> = Vec::new();
-
-    let mut current_route = "ROOT".to_string();
-    let mut seq_no: u32 = 1;
+    let (start, mut current_route, mut seq_no, mut outcome, mut batches_done) = match resume {
+        Some(cp) => (cp.step, cp.route, cp.seq_no, cp.outcome, cp.batches_done),
+        None => (
+            0,
+            "ROOT".to_string(),
+            1,
+            RunOutcome::default(),
+            JsonValue::Null,
+        ),
+    };
 
-    for step in &cfg.steps {
+    for (idx, step) in cfg.steps.iter().enumerate().skip(start) {
         if !step.active {
             continue;
         }
@@ -74,31 +189,67 @@ pub async fn execute_with_pages(
                 continue;
             }
         }
+        if drain.is_draining() {
+            return Ok(interrupt(
+                idx,
+                current_route,
+                seq_no,
+                outcome,
+                &[] as &[JsonValue],
+            ));
+        }
+        // Nur der Step am Checkpoint hat schon fertige Batches
+        let done = std::mem::take(&mut batches_done);
 
         match step.step_type {
             PromptType::ExtractionPrompt => {
                 let prompt_text = fetch_prompt_text_for_log(step.prompt_id as i32).await;
 
+                // Rerun: Ergebnis des Ursprungslaufs übernehmen, kein OpenAI-Call
+                if let Some(prev) = reused.and_then(|r| r.get(&step.prompt_id)) {
+                    let mut result = prev.clone();
+                    result.prompt_text = prompt_text.clone();
+                    outcome.log.push(RunStep {
+                        seq_no,
+                        step_id: step.id.to_string(),
+                        prompt_id: step.prompt_id as i64,
+                        prompt_type: PromptType::ExtractionPrompt,
+                        decision_key: None,
+                        route: Some(current_route.clone()),
+                        result: json!({
+                            "prompt_text": prompt_text,
+                            "reused": true,
+                            "results": [{
+                                "value": result.value,
+                                "source": result.source,
+                                "error": result.error,
+                            }]
+                        }),
+                    });
+                    outcome.extraction.push(result);
+                    seq_no += 1;
+                    continue;
+                }
+
+                let schema =
+                    ai::ResponseSchema::for_step(step).filter(|_| batch_cfg.structured_outputs);
+
                 // Extraction: strikt pro Seite
-                let batches = make_batches_step(
-                    pages,
-                    1, // page_batch_size
-                    batch_cfg.max_chars,
-                    1, // min_pages_for_batching
-                    0, // overlap_pages
-                );
+                let batches = batches_for_step(&step.step_type, pages, &layout);
 
-                let futs = batches.iter().map(|(_pnos, text, _cc)| {
-                    let text = text.clone();
+                let futs = batches.iter().map(|batch| {
+                    let text = batch.text.clone();
                     let prompt_id = step.prompt_id as i32;
                     let cfg_clone = batch_cfg.clone();
                     let prompt_text_for_log = prompt_text.clone();
+                    let schema = schema.clone();
                     async move {
                         call_extract_with_retries(
                             prompt_id,
                             &text,
                             &cfg_clone,
                             &prompt_text_for_log,
+                            schema.as_ref(),
                         )
                         .await
                         .unwrap_or_else(|e| PromptResult {
@@ -117,40 +268,46 @@ pub async fn execute_with_pages(
                     }
                 });
 
-                let mut results: Vec<PromptResult> = stream::iter(futs)
-                    .buffer_unordered(batch_cfg.max_parallel)
-                    .collect()
-                    .await;
+                // buffered (statt unordered): Ergebnis i gehört zu Batch i
+                let mut results: Vec<PromptResult> =
+                    run_batches(futs, finished_batches(done), batch_cfg.max_parallel, drain).await;
+                if results.len() < batches.len() {
+                    return Ok(interrupt(idx, current_route, seq_no, outcome, &results));
+                }
 
                 // Evidence-Fix: korrekte Seitenzuordnung
-                for r in results.iter_mut() {
+                let mut citations = Vec::new();
+                for (i, r) in results.iter_mut().enumerate() {
                     if let Some(src) = r.source.as_mut() {
+                        let cited = src.page;
                         let quote = src.quote.clone().unwrap_or_default();
                         let val = r
                             .value
                             .as_ref()
                             .and_then(|v| v.as_str())
                             .unwrap_or_default();
-                        if let Some((page, _)) =
-                            ai::resolve_page_for_quote_value(&quote, val, &page_map)
-                        {
+                        let resolved = ai::resolve_page_for_quote_value(&quote, val, &page_map)
+                            .map(|(page, _)| page);
+                        if let Some(page) = resolved {
                             src.page = page;
                         }
+                        citations.push(citation_entry(i, &batches[i], cited, resolved));
                     }
                 }
 
-                extraction_all.extend(results.clone());
+                outcome.extraction.extend(results.clone());
 
-                run_log.push(RunStep {
+                outcome.log.push(RunStep {
                     seq_no,
-                    step_id: step.id.to_string(),          // Uuid -> String
-                    prompt_id: step.prompt_id as i64,      // i32 -> i64
+                    step_id: step.id.to_string(),     // Uuid -> String
+                    prompt_id: step.prompt_id as i64, // i32 -> i64
                     prompt_type: PromptType::ExtractionPrompt,
                     decision_key: None,
                     route: Some(current_route.clone()),
                     result: json!({
                         "prompt_text": prompt_text,
-                        "batches": batches.iter().map(|(pnos, _t, cc)| json!({ "pages": pnos, "char_count": cc })).collect::<Vec<_>>(),
+                        "batches": batches.iter().map(PackedBatch::log_entry).collect::<Vec<_>>(),
+                        "citations": citations,
                         "results": results.iter().map(|r| json!({
                             "value": r.value,
                             "source": r.source,
@@ -165,18 +322,10 @@ pub async fn execute_with_pages(
                 let prompt_text = fetch_prompt_text_for_log(step.prompt_id as i32).await;
 
                 // Scoring: Batches mit optionaler Überlappung
-                let min_pages = env_usize("PIPELINE_MIN_PAGES_FOR_BATCHING", 4);
-                let overlap = env_usize("PIPELINE_OVERLAP_PAGES", 1);
-                let batches = make_batches_step(
-                    pages,
-                    batch_cfg.page_batch_size,
-                    batch_cfg.max_chars,
-                    min_pages,
-                    overlap,
-                );
+                let batches = batches_for_step(&step.step_type, pages, &layout);
 
-                let futs = batches.iter().map(|(_pnos, text, _cc)| {
-                    let text = text.clone();
+                let futs = batches.iter().map(|batch| {
+                    let text = batch.text.clone();
                     let prompt_id_i32 = step.prompt_id as i32;
                     let cfg_clone = batch_cfg.clone();
                     async move {
@@ -189,6 +338,7 @@ pub async fn execute_with_pages(
                                     page: 0,
                                     bbox: [0.0, 0.0, 0.0, 0.0],
                                     quote: None,
+                                    confidence: None,
                                 },
                                 explanation: format!("score failed: {e}"),
                                 vote: Some(TernaryLabel::Unsure),
@@ -200,17 +350,29 @@ pub async fn execute_with_pages(
                     }
                 });
 
-                let mut batch_scores: Vec<ScoringResult> = stream::iter(futs)
-                    .buffer_unordered(batch_cfg.max_parallel)
-                    .collect()
-                    .await;
+                let mut batch_scores: Vec<ScoringResult> =
+                    run_batches(futs, finished_batches(done), batch_cfg.max_parallel, drain).await;
+                if batch_scores.len() < batches.len() {
+                    return Ok(interrupt(
+                        idx,
+                        current_route,
+                        seq_no,
+                        outcome,
+                        &batch_scores,
+                    ));
+                }
 
                 // Evidence-Fix für jede Batch-Score
-                for s in batch_scores.iter_mut() {
+                let mut citations = Vec::new();
+                for (i, s) in batch_scores.iter_mut().enumerate() {
+                    let cited = s.source.page;
                     let q = s.source.quote.clone().unwrap_or_default();
-                    if let Some((page, _)) = ai::resolve_page_for_quote_value(&q, "", &page_map) {
+                    let resolved =
+                        ai::resolve_page_for_quote_value(&q, "", &page_map).map(|(page, _)| page);
+                    if let Some(page) = resolved {
                         s.source.page = page;
                     }
+                    citations.push(citation_entry(i, &batches[i], cited, resolved));
                 }
 
                 // Tri-State Konsolidierung
@@ -223,18 +385,19 @@ pub async fn execute_with_pages(
                     }
                 }
 
-                scoring_all.push(consolidated.clone());
+                outcome.scoring.push(consolidated.clone());
 
-                run_log.push(RunStep {
+                outcome.log.push(RunStep {
                     seq_no,
-                    step_id: step.id.to_string(),          // Uuid -> String
-                    prompt_id: step.prompt_id as i64,      // i32 -> i64
+                    step_id: step.id.to_string(),     // Uuid -> String
+                    prompt_id: step.prompt_id as i64, // i32 -> i64
                     prompt_type: PromptType::ScoringPrompt,
                     decision_key: None,
                     route: Some(current_route.clone()),
                     result: json!({
                         "prompt_text": prompt_text,
-                        "batches": batches.iter().map(|(pnos, _t, cc)| json!({ "pages": pnos, "char_count": cc })).collect::<Vec<_>>(),
+                        "batches": batches.iter().map(PackedBatch::log_entry).collect::<Vec<_>>(),
+                        "citations": citations,
                         "scores": batch_scores,
                         "consolidated": consolidated
                     }),
@@ -246,30 +409,20 @@ pub async fn execute_with_pages(
                 let yes_key = step.yes_key.clone().unwrap_or_else(|| "YES".into());
                 let no_key = step.no_key.clone().unwrap_or_else(|| "NO".into());
                 let prompt_text = fetch_prompt_text_for_log(step.prompt_id as i32).await;
+                let schema =
+                    ai::ResponseSchema::for_step(step).filter(|_| batch_cfg.structured_outputs);
 
                 // Decision: versuche EINEN Batch; Fallback → mehrere
-                let single =
-                    make_batches_step(pages, usize::MAX, batch_cfg.max_chars, usize::MAX, 0);
-                let min_pages = env_usize("PIPELINE_MIN_PAGES_FOR_BATCHING", 4);
-                let batches = if single.len() == 1 {
-                    single
-                } else {
-                    make_batches_step(
-                        pages,
-                        batch_cfg.page_batch_size,
-                        batch_cfg.max_chars,
-                        min_pages,
-                        0,
-                    )
-                };
-
-                let futs = batches.iter().map(|(_pnos, text, _cc)| {
-                    let text = text.clone();
+                let batches = batches_for_step(&step.step_type, pages, &layout);
+
+                let futs = batches.iter().map(|batch| {
+                    let text = batch.text.clone();
                     let prompt_id = step.prompt_id as i32;
                     let cfg_clone = batch_cfg.clone();
                     let yes_key = yes_key.clone();
                     let no_key = no_key.clone();
                     let prompt_text_for_log = prompt_text.clone();
+                    let schema = schema.clone();
                     async move {
                         call_decide_with_retries(
                             prompt_id,
@@ -278,6 +431,7 @@ pub async fn execute_with_pages(
                             &yes_key,
                             &no_key,
                             &prompt_text_for_log,
+                            schema.as_ref(),
                         )
                         .await
                         .unwrap_or_else(|e| PromptResult {
@@ -296,19 +450,24 @@ pub async fn execute_with_pages(
                     }
                 });
 
-                let mut decisions: Vec<PromptResult> = stream::iter(futs)
-                    .buffer_unordered(batch_cfg.max_parallel)
-                    .collect()
-                    .await;
+                let mut decisions: Vec<PromptResult> =
+                    run_batches(futs, finished_batches(done), batch_cfg.max_parallel, drain).await;
+                if decisions.len() < batches.len() {
+                    return Ok(interrupt(idx, current_route, seq_no, outcome, &decisions));
+                }
 
                 // Evidence-Fix für jede Entscheidung
-                for r in decisions.iter_mut() {
+                let mut citations = Vec::new();
+                for (i, r) in decisions.iter_mut().enumerate() {
                     if let Some(src) = r.source.as_mut() {
+                        let cited = src.page;
                         let q = src.quote.clone().unwrap_or_default();
-                        if let Some((page, _)) = ai::resolve_page_for_quote_value(&q, "", &page_map)
-                        {
+                        let resolved = ai::resolve_page_for_quote_value(&q, "", &page_map)
+                            .map(|(page, _)| page);
+                        if let Some(page) = resolved {
                             src.page = page;
                         }
+                        citations.push(citation_entry(i, &batches[i], cited, resolved));
                     }
                 }
 
@@ -329,18 +488,19 @@ pub async fn execute_with_pages(
                     }
                 }
 
-                decision_all.push(consolidated.clone());
+                outcome.decision.push(consolidated.clone());
 
-                run_log.push(RunStep {
+                outcome.log.push(RunStep {
                     seq_no,
-                    step_id: step.id.to_string(),          // Uuid -> String
-                    prompt_id: step.prompt_id as i64,      // i32 -> i64
+                    step_id: step.id.to_string(),     // Uuid -> String
+                    prompt_id: step.prompt_id as i64, // i32 -> i64
                     prompt_type: PromptType::DecisionPrompt,
                     decision_key: None,
                     route: Some(current_route.clone()),
                     result: json!({
                         "prompt_text": prompt_text,
-                        "batches": batches.iter().map(|(pnos, _t, cc)| json!({ "pages": pnos, "char_count": cc })).collect::<Vec<_>>(),
+                        "batches": batches.iter().map(PackedBatch::log_entry).collect::<Vec<_>>(),
+                        "citations": citations,
                         "votes": decisions,
                         "consolidated": consolidated
                     }),
@@ -350,43 +510,68 @@ pub async fn execute_with_pages(
         }
     }
 
-    Ok(RunOutcome {
-        extraction: extraction_all,
-        scoring: scoring_all,
-        decision: decision_all,
-        log: run_log,
-    })
+    Ok(Execution::Finished(outcome))
 }
 
-#[allow(dead_code)]
-fn make_batches(
-    pages: &[(i32, String)],
-    page_batch_size: usize,
-    max_chars: usize,
-) -> Vec<(Vec<i32>, String)> {
-    let mut out: Vec<(Vec<i32>, String)> = Vec::new();
-    let mut cur_pages: Vec<i32> = Vec::new();
-    let mut cur = String::new();
-
-    for (pno, txt) in pages {
-        let normalized = normalize_spaces(txt);
-
-        let would_len = cur.len() + normalized.len() + 1;
-        if !cur_pages.is_empty() && (cur_pages.len() >= page_batch_size || would_len > max_chars) {
-            out.push((std::mem::take(&mut cur_pages), std::mem::take(&mut cur)));
-        }
-
-        cur_pages.push(*pno);
-        if !cur.is_empty() {
-            cur.push('\n');
+/// Runs the batch calls of one step, skipping the `done` ones. After a drain
+/// started no further call is started and running calls are given up at the
+/// drain deadline; the result then holds fewer entries than calls.
+async fn run_batches<T, F>(
+    calls: impl Iterator<Item = F>,
+    done: Vec<T>,
+    max_parallel: usize,
+    drain: &Drain,
+) -> Vec<T>
+where
+    F: Future<Output = T>,
+{
+    let mut results = done;
+    let pending = stream::iter(calls.skip(results.len()))
+        .take_while(|_| future::ready(!drain.is_draining()))
+        .buffered(max_parallel.max(1));
+    let deadline = drain.deadline();
+    tokio::pin!(pending, deadline);
+    loop {
+        tokio::select! {
+            next = pending.next() => match next {
+                Some(result) => results.push(result),
+                None => break,
+            },
+            _ = &mut deadline => {
+                warn!("drain deadline reached; giving up running batches");
+                break;
+            }
         }
-        cur.push_str(&normalized);
     }
+    results
+}
 
-    if !cur_pages.is_empty() {
-        out.push((cur_pages, cur));
+/// Batch results stored in a checkpoint; unreadable ones are run again.
+fn finished_batches<T: DeserializeOwned>(done: JsonValue) -> Vec<T> {
+    if done.is_null() {
+        return Vec::new();
     }
-    out
+    serde_json::from_value(done).unwrap_or_else(|e| {
+        warn!(%e, "discarding batch results of checkpoint");
+        Vec::new()
+    })
+}
+
+fn interrupt<T: Serialize>(
+    step: usize,
+    route: String,
+    seq_no: u32,
+    outcome: RunOutcome,
+    done: &[T],
+) -> Execution {
+    info!(step, batches_done = done.len(), "run interrupted for drain");
+    Execution::Interrupted(Checkpoint {
+        step,
+        route,
+        seq_no,
+        outcome,
+        batches_done: serde_json::to_value(done).unwrap_or_default(),
+    })
 }
 
 fn env_usize(key: &str, default: usize) -> usize {
@@ -402,104 +587,21 @@ fn env_u64(key: &str, default: u64) -> u64 {
         .unwrap_or(default)
 }
 
-fn make_batches_step(
-    pages: &[(i32, String)],
-    page_batch_size: usize,
-    max_chars: usize,
-    min_pages_for_batching: usize,
-    overlap_pages: usize,
-) -> Vec<(Vec<i32>, String, usize)> {
-    if pages.is_empty() {
-        return Vec::new();
-    }
-    let total_pages = pages.len();
-    if total_pages <= min_pages_for_batching || page_batch_size == usize::MAX {
-        let mut text = String::new();
-        for (_pno, t) in pages {
-            let normalized = normalize_spaces(t);
-            if !text.is_empty() {
-                text.push('\n');
-            }
-            text.push_str(&normalized);
-        }
-        let char_count = text.len();
-        if char_count > max_chars && max_chars > 0 && page_batch_size == usize::MAX {
-            return make_batches_step(pages, 6, max_chars, 4, 0);
-        }
-        let pnos: Vec<i32> = pages.iter().map(|(p, _)| *p).collect();
-        return vec![(pnos, text, char_count)];
-    }
-
-    let mut out: Vec<(Vec<i32>, String, usize)> = Vec::new();
-    let mut cur_pages: Vec<i32> = Vec::new();
-    let mut cur = String::new();
-    let mut cur_chars = 0usize;
-    let mut last_overlap: Vec<String> = Vec::new();
-
-    for (idx, (pno, txt)) in pages.iter().enumerate() {
-        let normalized = normalize_spaces(txt);
-        let needed = (if cur.is_empty() { 0 } else { 1 }) + normalized.len();
-        let would_exceed_chars = max_chars > 0 && (cur_chars + needed) > max_chars;
-        let would_exceed_pages = cur_pages.len() >= page_batch_size;
-
-        if would_exceed_chars || would_exceed_pages {
-            out.push((cur_pages.clone(), cur.clone(), cur_chars));
-            if overlap_pages > 0 {
-                last_overlap.clear();
-                let mut k = overlap_pages;
-                let mut i = idx;
-                while k > 0 && i > 0 {
-                    let (_pp, prev_txt) = &pages[i - 1];
-                    last_overlap.push(normalize_spaces(prev_txt));
-                    i -= 1;
-                    k -= 1;
-                }
-                last_overlap.reverse();
-            }
-            cur_pages.clear();
-            cur.clear();
-            cur_chars = 0;
-        }
-
-        if !last_overlap.is_empty() && cur.is_empty() {
-            for seg in &last_overlap {
-                if !cur.is_empty() {
-                    cur.push('\n');
-                }
-                cur.push_str(seg);
-            }
-            cur_chars = cur.len();
-            last_overlap.clear();
-        }
-
-        if !cur.is_empty() {
-            cur.push('\n');
-        }
-        cur.push_str(&normalized);
-        cur_pages.push(*pno);
-        cur_chars += needed;
-    }
-    if !cur_pages.is_empty() {
-        out.push((cur_pages, cur, cur_chars));
-    }
-    out
-}
-
-fn normalize_spaces(s: &str) -> String {
-    let mut out = String::with_capacity(s.len());
-    let mut last_was_space = false;
-    for ch in s.chars() {
-        if ch.is_whitespace() {
-            if !last_was_space {
-                out.push(' ');
-                last_was_space = true;
-            }
-        } else {
-            last_was_space = false;
-            out.push(ch);
-        }
-    }
-    out.trim().to_string()
+/// Run-log entry comparing the page cited by the model (`[[PAGE n]]` marker)
+/// with the page resolved from the quote and the pages sent in the batch.
+fn citation_entry(
+    batch_idx: usize,
+    batch: &PackedBatch,
+    cited: u32,
+    resolved: Option<u32>,
+) -> JsonValue {
+    json!({
+        "batch": batch_idx,
+        "batch_pages": batch.pages(),
+        "cited_page": cited,
+        "cited_in_batch": batch.contains_page(cited as i32),
+        "resolved_page": resolved,
+    })
 }
 
 async fn call_extract_with_retries(
@@ -507,13 +609,14 @@ async fn call_extract_with_retries(
     text: &str,
     cfg: &BatchCfg,
     prompt_text_for_log: &str,
+    schema: Option<&ai::ResponseSchema>,
 ) -> anyhow::Result<PromptResult> {
     let mut last_err: Option<anyhow::Error> = None;
 
     for attempt in 0..=cfg.openai_retries {
         let res = tokio::time::timeout(
             Duration::from_millis(cfg.openai_timeout_ms),
-            ai::extract(prompt_id, text),
+            ai::extract_with_schema(prompt_id, text, schema),
         )
         .await;
 
@@ -606,6 +709,7 @@ async fn call_decide_with_retries(
     yes_key: &str,
     no_key: &str,
     prompt_text_for_log: &str,
+    schema: Option<&ai::ResponseSchema>,
 ) -> anyhow::Result<PromptResult> {
     let mut last_err: Option<anyhow::Error> = None;
     let state: HashMap<String, JsonValue> = HashMap::new();
@@ -613,7 +717,7 @@ async fn call_decide_with_retries(
     for attempt in 0..=cfg.openai_retries {
         let res = tokio::time::timeout(
             Duration::from_millis(cfg.openai_timeout_ms),
-            ai::decide(prompt_id, text, &state),
+            ai::decide_with_schema(prompt_id, text, &state, schema),
         )
         .await;
 
@@ -705,6 +809,7 @@ fn consolidate_scoring(v: &Vec<ScoringResult>) -> ScoringResult {
                 page: 0,
                 bbox: [0.0, 0.0, 0.0, 0.0],
                 quote: None,
+                confidence: None,
             },
             explanation: "no scores".into(),
             vote: Some(TernaryLabel::Unsure),
@@ -930,3 +1035,63 @@ pub fn compute_overall_score(items: &[(bool, f32)]) -> Option<f32> {
 
     Some((weighted_true / total_weight).clamp(0.0, 1.0))
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn batches_resume_after_done_and_stop_on_drain() {
+        let (tx, drain) = Drain::manual(Duration::from_millis(50));
+        let calls = (0..4).map(|i| async move { i * 10 });
+        let results = run_batches(calls, vec![0, 10], 2, &drain).await;
+        assert_eq!(results, [0, 10, 20, 30]);
+
+        // Batch 0 löst den Drain aus: Batch 2 startet nicht mehr, Batch 1 hängt
+        // und wird zur Deadline aufgegeben
+        let tx = &tx;
+        let calls = (0..4).map(|i| async move {
+            match i {
+                0 => {
+                    tx.send(true).unwrap();
+                }
+                1 => std::future::pending::<()>().await,
+                _ => panic!("batch {i} started after drain"),
+            }
+            i
+        });
+        let results = run_batches(calls, Vec::new(), 2, &drain).await;
+        assert_eq!(results, [0]);
+    }
+
+    #[test]
+    fn checkpoint_keeps_finished_batches() {
+        let done = vec![ScoringResult {
+            prompt_id: 7,
+            result: true,
+            source: TextPosition {
+                page: 2,
+                bbox: [0.0, 0.0, 0.0, 0.0],
+                quote: Some("versichert".into()),
+                confidence: None,
+            },
+            explanation: String::new(),
+            vote: Some(TernaryLabel::Yes),
+            strength: Some(0.9),
+            confidence: Some(0.8),
+            score: None,
+            label: None,
+        }];
+        let Execution::Interrupted(cp) =
+            interrupt(3, "YES".into(), 4, RunOutcome::default(), &done)
+        else {
+            panic!("expected interruption");
+        };
+        let cp: Checkpoint = serde_json::from_value(serde_json::to_value(&cp).unwrap()).unwrap();
+        assert_eq!((cp.step, cp.route.as_str(), cp.seq_no), (3, "YES", 4));
+        let restored: Vec<ScoringResult> = finished_batches(cp.batches_done);
+        assert_eq!(restored.len(), 1);
+        assert_eq!(restored[0].source.page, 2);
+        assert!(finished_batches::<ScoringResult>(JsonValue::Null).is_empty());
+    }
+}
@@ -0,0 +1,201 @@
+//! Per-run scratch directories and disk usage accounting.
+//!
+//! Every run gets `RUNNER_WORK_DIR/<run_id>`, which is removed when the
+//! [`RunWorkspace`] guard is dropped, on success, error and early `continue`
+//! alike. Leftovers of a crashed process are swept on startup. New runs only
+//! start while the file system holding the work dir has at least
+//! `RUNNER_MIN_FREE_MB` free.
+
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use serde::Serialize;
+use tracing::warn;
+use uuid::Uuid;
+
+#[derive(Debug, Clone)]
+/// Work dir settings read from the environment.
+pub struct WorkspaceConfig {
+    pub root: PathBuf,
+    pub min_free_bytes: u64,
+    /// Interval of the disk gauge and of the free-space re-check.
+    pub check_interval: Duration,
+}
+
+impl WorkspaceConfig {
+    pub fn from_env() -> Self {
+        let root = std::env::var("RUNNER_WORK_DIR")
+            .map(PathBuf::from)
+            .unwrap_or_else(|_| std::env::temp_dir().join("pipeline-runner"));
+        Self {
+            root,
+            min_free_bytes: crate::env_parse("RUNNER_MIN_FREE_MB", 512u64) * 1024 * 1024,
+            check_interval: Duration::from_secs(
+                crate::env_parse("RUNNER_DISK_CHECK_SECS", 30u64).max(1),
+            ),
+        }
+    }
+
+    /// Creates the work dir and removes run directories left behind by a
+    /// previous process; returns the number of removed entries.
+    pub fn sweep(&self) -> io::Result<usize> {
+        fs::create_dir_all(&self.root)?;
+        let mut removed = 0;
+        for entry in fs::read_dir(&self.root)? {
+            let path = entry?.path();
+            let res = if path.is_dir() {
+                fs::remove_dir_all(&path)
+            } else {
+                fs::remove_file(&path)
+            };
+            match res {
+                Ok(()) => removed += 1,
+                Err(e) => warn!(%e, path = %path.display(), "failed to remove stale run artifact"),
+            }
+        }
+        Ok(removed)
+    }
+
+    /// Creates the scratch directory of a run.
+    pub fn create(&self, run_id: Uuid) -> io::Result<RunWorkspace> {
+        let path = self.root.join(run_id.to_string());
+        fs::create_dir_all(&path)?;
+        Ok(RunWorkspace { path })
+    }
+
+    pub fn usage(&self) -> DiskUsage {
+        let (free_bytes, total_bytes) = match fs_space(&self.root) {
+            Some((free, total)) => (Some(free), Some(total)),
+            None => (None, None),
+        };
+        DiskUsage {
+            free_bytes,
+            total_bytes,
+            work_dir_bytes: dir_size(&self.root),
+            min_free_bytes: self.min_free_bytes,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+/// Disk gauge of the runner; `free_bytes`/`total_bytes` are `None` where the
+/// platform offers no file system statistics.
+pub struct DiskUsage {
+    pub free_bytes: Option<u64>,
+    pub total_bytes: Option<u64>,
+    pub work_dir_bytes: u64,
+    pub min_free_bytes: u64,
+}
+
+impl DiskUsage {
+    /// True when new runs must not start.
+    pub fn is_low(&self) -> bool {
+        self.free_bytes
+            .map(|free| free < self.min_free_bytes)
+            .unwrap_or(false)
+    }
+}
+
+/// Scratch directory of one run; removed on drop.
+pub struct RunWorkspace {
+    path: PathBuf,
+}
+
+impl RunWorkspace {
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Bytes currently stored below the scratch directory.
+    pub fn size(&self) -> u64 {
+        dir_size(&self.path)
+    }
+}
+
+impl Drop for RunWorkspace {
+    fn drop(&mut self) {
+        match fs::remove_dir_all(&self.path) {
+            Ok(()) => {}
+            Err(e) if e.kind() == io::ErrorKind::NotFound => {}
+            Err(e) => warn!(%e, path = %self.path.display(), "failed to remove run workspace"),
+        }
+    }
+}
+
+/// Recursive size of all files below `path`; unreadable entries count as 0.
+fn dir_size(path: &Path) -> u64 {
+    let Ok(entries) = fs::read_dir(path) else {
+        return 0;
+    };
+    entries
+        .filter_map(Result::ok)
+        .map(|entry| match entry.metadata() {
+            Ok(meta) if meta.is_dir() => dir_size(&entry.path()),
+            Ok(meta) => meta.len(),
+            Err(_) => 0,
+        })
+        .sum()
+}
+
+/// `(free, total)` bytes of the file system holding `path`.
+#[cfg(unix)]
+// Feldbreiten von statvfs unterscheiden sich je Plattform
+#[allow(clippy::unnecessary_cast)]
+fn fs_space(path: &Path) -> Option<(u64, u64)> {
+    use std::ffi::CString;
+    use std::os::unix::ffi::OsStrExt;
+
+    let c_path = CString::new(path.as_os_str().as_bytes()).ok()?;
+    let mut stat: libc::statvfs = unsafe { std::mem::zeroed() };
+    // SAFETY: c_path ist nullterminiert, stat ein gültiger Ausgabepuffer
+    if unsafe { libc::statvfs(c_path.as_ptr(), &mut stat) } != 0 {
+        return None;
+    }
+    let block = stat.f_frsize as u64;
+    Some((stat.f_bavail as u64 * block, stat.f_blocks as u64 * block))
+}
+
+#[cfg(not(unix))]
+fn fs_space(_path: &Path) -> Option<(u64, u64)> {
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn workspace_is_removed_on_drop_and_swept() {
+        let cfg = WorkspaceConfig {
+            root: std::env::temp_dir().join(format!("runner-ws-test-{}", Uuid::new_v4())),
+            min_free_bytes: 0,
+            check_interval: Duration::from_secs(1),
+        };
+        cfg.sweep().unwrap();
+
+        let ws = cfg.create(Uuid::new_v4()).unwrap();
+        fs::write(ws.path().join("page.txt"), b"0123456789").unwrap();
+        assert_eq!(ws.size(), 10);
+        assert_eq!(cfg.usage().work_dir_bytes, 10);
+        let path = ws.path().to_path_buf();
+        drop(ws);
+        assert!(!path.exists());
+
+        // Reste eines abgestürzten Prozesses
+        let leftover = cfg.create(Uuid::new_v4()).unwrap();
+        std::mem::forget(leftover);
+        assert_eq!(cfg.sweep().unwrap(), 1);
+
+        let usage = cfg.usage();
+        assert!(!usage.is_low());
+        assert!(DiskUsage {
+            free_bytes: Some(1),
+            min_free_bytes: 2,
+            ..usage
+        }
+        .is_low());
+        fs::remove_dir_all(&cfg.root).unwrap();
+    }
+}
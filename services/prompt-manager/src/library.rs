@@ -0,0 +1,370 @@
+//! REST API of the step library (see `shared::step_library`).
+//!
+//! Saving an item with a changed step stores a new version and refreshes the
+//! copy in every pipeline whose step follows the item without pinned version;
+//! pinned steps keep their version until the pipeline moves the pin. Items
+//! still used by a pipeline cannot be deleted.
+
+use std::sync::Arc;
+
+use axum::{extract::Path, extract::State, http::StatusCode, Json};
+use sea_orm::{
+    ActiveModelTrait, ColumnTrait, ConnectionTrait, DatabaseConnection, EntityTrait, QueryFilter,
+    QueryOrder, Set, TransactionTrait,
+};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use shared::step_library::{step_ref, LibraryStep};
+use uuid::Uuid;
+
+use crate::model::{
+    library_item::{self, ActiveModel as ItemActiveModel, Entity as ItemEntity},
+    library_version::{self, ActiveModel as VersionActiveModel, Entity as VersionEntity},
+    pipeline::{ActiveModel as PipelineActiveModel, Entity as PipelineEntity},
+    prompt::Entity as Prompt,
+};
+use crate::{bad_request, conflict, int_err, not_found, ErrorResponse};
+
+type ApiError = (StatusCode, Json<ErrorResponse>);
+
+#[derive(Serialize)]
+/// API representation of a library item with its latest step.
+pub struct LibraryItemData {
+    id: i32,
+    name: String,
+    description: Option<String>,
+    version: i32,
+    step: LibraryStep,
+    used_by: Vec<LibraryUsage>,
+}
+
+#[derive(Serialize)]
+/// A pipeline step referencing the item; `version` is set when pinned.
+pub struct LibraryUsage {
+    pipeline_id: Uuid,
+    pipeline_name: String,
+    step_id: Option<String>,
+    version: Option<i32>,
+}
+
+#[derive(Serialize)]
+pub struct LibraryVersionData {
+    version: i32,
+    step: LibraryStep,
+}
+
+#[derive(Deserialize)]
+pub struct LibraryItemInput {
+    name: String,
+    #[serde(default)]
+    description: Option<String>,
+    step: LibraryStep,
+}
+
+/// Steps of `config` (a pipeline's `config_json`) referencing `item_id`.
+fn usages_in(config: &Value, item_id: i32) -> Vec<(Option<String>, Option<i32>)> {
+    config
+        .get("steps")
+        .and_then(Value::as_array)
+        .into_iter()
+        .flatten()
+        .filter_map(|step| {
+            let library = step_ref(step).filter(|r| r.id == item_id)?;
+            let step_id = step.get("id").and_then(Value::as_str).map(str::to_string);
+            Some((step_id, library.version))
+        })
+        .collect()
+}
+
+/// Refreshes the steps of `config` following `item_id`; `true` if any changed.
+fn refresh_followers(config: &mut Value, item_id: i32, content: &LibraryStep) -> bool {
+    let Some(steps) = config.get_mut("steps").and_then(Value::as_array_mut) else {
+        return false;
+    };
+    let mut changed = false;
+    for step in steps {
+        if step_ref(step).is_some_and(|r| r.id == item_id && r.version.is_none()) {
+            let before = step.clone();
+            content.apply_json(step);
+            changed |= *step != before;
+        }
+    }
+    changed
+}
+
+async fn usages<C: ConnectionTrait>(db: &C, item_id: i32) -> Result<Vec<LibraryUsage>, ApiError> {
+    let pipelines = PipelineEntity::find().all(db).await.map_err(int_err)?;
+    Ok(pipelines
+        .into_iter()
+        .flat_map(|p| {
+            usages_in(&p.config_json, item_id)
+                .into_iter()
+                .map(move |(step_id, version)| LibraryUsage {
+                    pipeline_id: p.id,
+                    pipeline_name: p.name.clone(),
+                    step_id,
+                    version,
+                })
+        })
+        .collect())
+}
+
+async fn latest_step<C: ConnectionTrait>(
+    db: &C,
+    item: &library_item::Model,
+) -> Result<LibraryStep, ApiError> {
+    let Some(latest) = VersionEntity::find_by_id((item.id, item.version))
+        .one(db)
+        .await
+        .map_err(int_err)?
+    else {
+        return Err(not_found());
+    };
+    serde_json::from_value(latest.step).map_err(int_err)
+}
+
+async fn item_data<C: ConnectionTrait>(
+    db: &C,
+    item: library_item::Model,
+) -> Result<LibraryItemData, ApiError> {
+    let step = latest_step(db, &item).await?;
+    Ok(LibraryItemData {
+        used_by: usages(db, item.id).await?,
+        id: item.id,
+        name: item.name,
+        description: item.description,
+        version: item.version,
+        step,
+    })
+}
+
+/// Name must be unique and the prompt must exist with the step's type.
+async fn validate<C: ConnectionTrait>(
+    db: &C,
+    input: &LibraryItemInput,
+    id: Option<i32>,
+) -> Result<String, ApiError> {
+    let name = input.name.trim();
+    if name.is_empty() {
+        return Err(bad_request("name required"));
+    }
+    let mut same_name = ItemEntity::find().filter(library_item::Column::Name.eq(name));
+    if let Some(id) = id {
+        same_name = same_name.filter(library_item::Column::Id.ne(id));
+    }
+    if same_name.one(db).await.map_err(int_err)?.is_some() {
+        return Err(conflict("name already exists"));
+    }
+    let Some(prompt) = Prompt::find_by_id(input.step.prompt_id)
+        .one(db)
+        .await
+        .map_err(int_err)?
+    else {
+        return Err(bad_request("unknown prompt"));
+    };
+    if prompt.prompt_type != input.step.step_type.to_string() {
+        return Err(bad_request("step type does not match the prompt type"));
+    }
+    Ok(name.to_string())
+}
+
+async fn insert_version<C: ConnectionTrait>(
+    db: &C,
+    item_id: i32,
+    version: i32,
+    step: &LibraryStep,
+) -> Result<(), ApiError> {
+    let model = VersionActiveModel {
+        item_id: Set(item_id),
+        version: Set(version),
+        step: Set(serde_json::to_value(step).map_err(int_err)?),
+    };
+    model.insert(db).await.map_err(int_err)?;
+    Ok(())
+}
+
+pub async fn list_items(
+    State(db): State<Arc<DatabaseConnection>>,
+) -> Result<Json<Vec<LibraryItemData>>, ApiError> {
+    let items = ItemEntity::find()
+        .order_by_asc(library_item::Column::Name)
+        .all(&*db)
+        .await
+        .map_err(int_err)?;
+    let mut result = Vec::with_capacity(items.len());
+    for item in items {
+        result.push(item_data(&*db, item).await?);
+    }
+    Ok(Json(result))
+}
+
+pub async fn get_item(
+    Path(id): Path<i32>,
+    State(db): State<Arc<DatabaseConnection>>,
+) -> Result<Json<LibraryItemData>, ApiError> {
+    let Some(item) = ItemEntity::find_by_id(id)
+        .one(&*db)
+        .await
+        .map_err(int_err)?
+    else {
+        return Err(not_found());
+    };
+    Ok(Json(item_data(&*db, item).await?))
+}
+
+pub async fn create_item(
+    State(db): State<Arc<DatabaseConnection>>,
+    Json(input): Json<LibraryItemInput>,
+) -> Result<Json<LibraryItemData>, ApiError> {
+    let txn = db.begin().await.map_err(int_err)?;
+    let name = validate(&txn, &input, None).await?;
+    let model = ItemActiveModel {
+        name: Set(name),
+        description: Set(input.description.clone()),
+        version: Set(1),
+        ..Default::default()
+    };
+    let item = model.insert(&txn).await.map_err(int_err)?;
+    insert_version(&txn, item.id, 1, &input.step).await?;
+    let data = item_data(&txn, item).await?;
+    txn.commit().await.map_err(int_err)?;
+    Ok(Json(data))
+}
+
+/// Renames the item; a changed step becomes the next version.
+pub async fn update_item(
+    Path(id): Path<i32>,
+    State(db): State<Arc<DatabaseConnection>>,
+    Json(input): Json<LibraryItemInput>,
+) -> Result<Json<LibraryItemData>, ApiError> {
+    let txn = db.begin().await.map_err(int_err)?;
+    let Some(item) = ItemEntity::find_by_id(id)
+        .one(&txn)
+        .await
+        .map_err(int_err)?
+    else {
+        return Err(not_found());
+    };
+    let name = validate(&txn, &input, Some(id)).await?;
+    let latest = latest_step(&txn, &item).await?;
+    let mut version = item.version;
+    if latest != input.step {
+        version += 1;
+        insert_version(&txn, id, version, &input.step).await?;
+        for pipeline in PipelineEntity::find().all(&txn).await.map_err(int_err)? {
+            let mut config = pipeline.config_json.clone();
+            if refresh_followers(&mut config, id, &input.step) {
+                let mut active: PipelineActiveModel = pipeline.into();
+                active.config_json = Set(config);
+                active.update(&txn).await.map_err(int_err)?;
+            }
+        }
+    }
+    let mut active: ItemActiveModel = item.into();
+    active.name = Set(name);
+    active.description = Set(input.description.clone());
+    active.version = Set(version);
+    let item = active.update(&txn).await.map_err(int_err)?;
+    let data = item_data(&txn, item).await?;
+    txn.commit().await.map_err(int_err)?;
+    Ok(Json(data))
+}
+
+pub async fn delete_item(
+    Path(id): Path<i32>,
+    State(db): State<Arc<DatabaseConnection>>,
+) -> Result<(), ApiError> {
+    let Some(item) = ItemEntity::find_by_id(id)
+        .one(&*db)
+        .await
+        .map_err(int_err)?
+    else {
+        return Err(not_found());
+    };
+    if !usages(&*db, id).await?.is_empty() {
+        return Err(conflict("library step is used by a pipeline"));
+    }
+    let active: ItemActiveModel = item.into();
+    active.delete(&*db).await.map_err(int_err)?;
+    Ok(())
+}
+
+pub async fn list_versions(
+    Path(id): Path<i32>,
+    State(db): State<Arc<DatabaseConnection>>,
+) -> Result<Json<Vec<LibraryVersionData>>, ApiError> {
+    if ItemEntity::find_by_id(id)
+        .one(&*db)
+        .await
+        .map_err(int_err)?
+        .is_none()
+    {
+        return Err(not_found());
+    }
+    let versions = VersionEntity::find()
+        .filter(library_version::Column::ItemId.eq(id))
+        .order_by_desc(library_version::Column::Version)
+        .all(&*db)
+        .await
+        .map_err(int_err)?;
+    let mut result = Vec::with_capacity(versions.len());
+    for v in versions {
+        result.push(LibraryVersionData {
+            version: v.version,
+            step: serde_json::from_value(v.step).map_err(int_err)?,
+        });
+    }
+    Ok(Json(result))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn config() -> Value {
+        json!({
+            "name": "Kredit",
+            "steps": [
+                {"id": "a", "type": "ScoringPrompt", "promptId": 7, "active": true,
+                 "config": {"min_signal": 0.2}, "library": {"id": 3}},
+                {"id": "b", "type": "ScoringPrompt", "promptId": 7, "active": true,
+                 "config": {"min_signal": 0.2}, "library": {"id": 3, "version": 1}},
+                {"id": "c", "type": "ExtractionPrompt", "promptId": 1, "active": true}
+            ]
+        })
+    }
+
+    #[test]
+    fn finds_following_and_pinned_steps() {
+        let found = usages_in(&config(), 3);
+        assert_eq!(
+            found,
+            vec![
+                (Some("a".to_string()), None),
+                (Some("b".to_string()), Some(1))
+            ]
+        );
+        assert!(usages_in(&config(), 4).is_empty());
+    }
+
+    #[test]
+    fn refresh_only_touches_steps_following_the_item() {
+        let content: LibraryStep = serde_json::from_value(json!({
+            "type": "ScoringPrompt", "promptId": 7, "config": {"min_signal": 0.6}
+        }))
+        .unwrap();
+        let mut config = config();
+        assert!(refresh_followers(&mut config, 3, &content));
+        assert_eq!(config["steps"][0]["config"]["min_signal"], json!(0.6));
+        assert_eq!(
+            config["steps"][1]["config"]["min_signal"],
+            json!(0.2),
+            "pinned"
+        );
+        assert!(
+            !refresh_followers(&mut config, 3, &content),
+            "already current"
+        );
+    }
+}
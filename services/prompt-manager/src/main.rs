@@ -10,19 +10,20 @@ use reqwest::Client;
 use sea_orm::prelude::Decimal;
 use sea_orm::{
     ActiveModelTrait, ColumnTrait, ConnectionTrait, Database, DatabaseConnection, EntityTrait,
-    QueryFilter, Set, Statement,
+    QueryFilter, QueryOrder, Set, Statement,
 };
 use serde::{Deserialize, Serialize};
 use shared::config::Settings;
+use shared::cors::CorsSettings;
 use shared::dto::PromptType;
 use shared::openai_client::PromptError;
 use std::str::FromStr;
 use std::sync::Arc;
-use tower_http::cors::CorsLayer;
 use tracing::{error, info};
 use tracing_subscriber::{fmt, EnvFilter};
 use uuid::Uuid;
 
+mod library;
 mod model;
 mod review;
 use model::{
@@ -369,22 +370,49 @@ async fn set_favorite(
 
 /* ---------------- Groups ---------------- */
 
+/// Prompt ids of a group in group order.
+async fn group_prompt_ids(
+    db: &DatabaseConnection,
+    group_id: i32,
+) -> Result<Vec<i32>, (StatusCode, Json<ErrorResponse>)> {
+    let members = GroupPromptEntity::find()
+        .filter(model::group_prompt::Column::GroupId.eq(group_id))
+        .order_by_asc(model::group_prompt::Column::Position)
+        .order_by_asc(model::group_prompt::Column::PromptId)
+        .all(db)
+        .await
+        .map_err(int_err)?;
+    Ok(members.into_iter().map(|m| m.prompt_id).collect())
+}
+
+/// Replaces the members of a group, keeping the order of `prompt_ids`.
+async fn insert_group_prompts(
+    db: &DatabaseConnection,
+    group_id: i32,
+    prompt_ids: &[i32],
+) -> Result<(), (StatusCode, Json<ErrorResponse>)> {
+    for (position, pid) in prompt_ids.iter().enumerate() {
+        let mut gp: GroupPromptActiveModel = Default::default();
+        gp.group_id = Set(group_id);
+        gp.prompt_id = Set(*pid);
+        gp.position = Set(position as i32);
+        gp.insert(db).await.map_err(int_err)?;
+    }
+    Ok(())
+}
+
 async fn list_groups(
     State(db): State<Arc<DatabaseConnection>>,
 ) -> Result<Json<Vec<GroupData>>, (StatusCode, Json<ErrorResponse>)> {
     let groups = GroupEntity::find().all(&*db).await.map_err(int_err)?;
     let mut result = Vec::new();
     for g in groups {
-        let members = GroupPromptEntity::find()
-            .filter(model::group_prompt::Column::GroupId.eq(g.id))
-            .all(&*db)
-            .await
-            .map_err(int_err)?;
+        let prompt_ids = group_prompt_ids(&db, g.id).await?;
         result.push(GroupData {
             id: g.id,
             name: g.name,
             favorite: g.favorite,
-            prompt_ids: members.into_iter().map(|m| m.prompt_id).collect(),
+            prompt_ids,
         });
     }
     Ok(Json(result))
@@ -398,12 +426,7 @@ async fn create_group(
     group.name = Set(input.name);
     group.favorite = Set(input.favorite);
     let g = group.insert(&*db).await.map_err(int_err)?;
-    for pid in &input.prompt_ids {
-        let mut gp: GroupPromptActiveModel = Default::default();
-        gp.group_id = Set(g.id);
-        gp.prompt_id = Set(*pid);
-        gp.insert(&*db).await.map_err(int_err)?;
-    }
+    insert_group_prompts(&db, g.id, &input.prompt_ids).await?;
     Ok(Json(GroupData {
         id: g.id,
         name: g.name,
@@ -435,19 +458,13 @@ async fn update_group(
         .await
         .map_err(int_err)?;
 
-    let ids = input.prompt_ids.clone();
-    for pid in &ids {
-        let mut gp: GroupPromptActiveModel = Default::default();
-        gp.group_id = Set(id);
-        gp.prompt_id = Set(*pid);
-        gp.insert(&*db).await.map_err(int_err)?;
-    }
+    insert_group_prompts(&db, id, &input.prompt_ids).await?;
 
     Ok(Json(GroupData {
         id: g.id,
         name: g.name,
         favorite: g.favorite,
-        prompt_ids: ids,
+        prompt_ids: input.prompt_ids,
     }))
 }
 
@@ -466,16 +483,12 @@ async fn set_group_favorite(
     group.favorite = input.favorite;
     let active: GroupActiveModel = group.into();
     let g = active.update(&*db).await.map_err(int_err)?;
-    let members = GroupPromptEntity::find()
-        .filter(model::group_prompt::Column::GroupId.eq(g.id))
-        .all(&*db)
-        .await
-        .map_err(int_err)?;
+    let prompt_ids = group_prompt_ids(&db, g.id).await?;
     Ok(Json(GroupData {
         id: g.id,
         name: g.name,
         favorite: g.favorite,
-        prompt_ids: members.into_iter().map(|m| m.prompt_id).collect(),
+        prompt_ids,
     }))
 }
 
@@ -568,6 +581,12 @@ fn bad_request(msg: &str) -> (StatusCode, Json<ErrorResponse>) {
         Json(ErrorResponse { error: msg.into() }),
     )
 }
+fn conflict(msg: &str) -> (StatusCode, Json<ErrorResponse>) {
+    (
+        StatusCode::CONFLICT,
+        Json(ErrorResponse { error: msg.into() }),
+    )
+}
 fn not_found() -> (StatusCode, Json<ErrorResponse>) {
     (
         StatusCode::NOT_FOUND,
@@ -714,12 +733,25 @@ async fn ensure_prompt_schema(db: &DatabaseConnection) -> Result<(), sea_orm::Db
         CREATE TABLE IF NOT EXISTS group_prompts (
           group_id   INTEGER NOT NULL REFERENCES prompt_groups(id) ON DELETE CASCADE,
           prompt_id  INTEGER NOT NULL REFERENCES prompts(id) ON DELETE CASCADE,
+          position   INTEGER NOT NULL DEFAULT 0,
           PRIMARY KEY (group_id, prompt_id)
         )
     "#
         .to_string(),
     ))
     .await?;
+    db.execute(Statement::from_string(
+        be,
+        "ALTER TABLE group_prompts ADD COLUMN IF NOT EXISTS position INTEGER NOT NULL DEFAULT 0"
+            .to_string(),
+    ))
+    .await?;
+
+    // step_library + step_library_versions
+    for sql in shared::step_library::CREATE_STEP_LIBRARY_SQL {
+        db.execute(Statement::from_string(be, sql.to_string()))
+            .await?;
+    }
 
     Ok(())
 }
@@ -757,13 +789,24 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
         .route("/prompt-groups", get(list_groups).post(create_group))
         .route("/prompt-groups/:id", put(update_group))
         .route("/prompt-groups/:id/favorite", put(set_group_favorite))
+        .route(
+            "/step-library",
+            get(library::list_items).post(library::create_item),
+        )
+        .route(
+            "/step-library/:id",
+            get(library::get_item)
+                .put(library::update_item)
+                .delete(library::delete_item),
+        )
+        .route("/step-library/:id/versions", get(library::list_versions))
         .route("/pipelines", get(list_pipelines).post(create_pipeline))
         .route(
             "/pipelines/:id",
             put(update_pipeline).delete(delete_pipeline),
         )
         .with_state(db.clone())
-        .layer(CorsLayer::permissive());
+        .layer(CorsSettings::from_env().tower());
 
     info!("starting prompt-manager on 0.0.0.0:8082");
     axum::Server::bind(&"0.0.0.0:8082".parse::<std::net::SocketAddr>()?)
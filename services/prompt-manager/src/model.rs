@@ -1,4 +1,4 @@
-//! SeaORM entity definitions for prompts, groups and pipelines.
+//! SeaORM entity definitions for prompts, groups, pipelines and the step library.
 
 use sea_orm::entity::prelude::*;
 
@@ -56,6 +56,8 @@ pub mod group_prompt {
         pub group_id: i32,
         #[sea_orm(primary_key, auto_increment = false)]
         pub prompt_id: i32,
+        /// Order of the prompt within the group (index in `prompt_ids`).
+        pub position: i32,
     }
 
     #[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
@@ -86,6 +88,48 @@ pub mod pipeline {
     impl ActiveModelBehavior for ActiveModel {}
 }
 
+/* ---------- STEP LIBRARY ---------- */
+
+pub mod library_item {
+    use super::*;
+
+    #[derive(Clone, Debug, PartialEq, DeriveEntityModel)]
+    #[sea_orm(table_name = "step_library")]
+    pub struct Model {
+        #[sea_orm(primary_key)]
+        pub id: i32,
+        pub name: String,
+        pub description: Option<String>,
+        /// Latest version in `step_library_versions`.
+        pub version: i32,
+    }
+
+    #[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
+    pub enum Relation {}
+
+    impl ActiveModelBehavior for ActiveModel {}
+}
+
+pub mod library_version {
+    use super::*;
+
+    #[derive(Clone, Debug, PartialEq, DeriveEntityModel)]
+    #[sea_orm(table_name = "step_library_versions")]
+    pub struct Model {
+        #[sea_orm(primary_key, auto_increment = false)]
+        pub item_id: i32,
+        #[sea_orm(primary_key, auto_increment = false)]
+        pub version: i32,
+        /// Configured step (`shared::step_library::LibraryStep`).
+        pub step: Json,
+    }
+
+    #[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
+    pub enum Relation {}
+
+    impl ActiveModelBehavior for ActiveModel {}
+}
+
 /* ---------- Re-exports ---------- */
 
 pub use group::{ActiveModel as GroupActiveModel, Entity as GroupEntity, Model as GroupModel};
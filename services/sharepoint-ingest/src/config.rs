@@ -1,8 +1,11 @@
 //! Configuration loading for the SharePoint ingest service.
 
-use std::{env, time::Duration};
+use std::{env, path::PathBuf, time::Duration};
 
 use anyhow::{Context, Result};
+use shared::cors::CorsSettings;
+
+use crate::job_files::FailureTolerance;
 
 #[derive(Debug, Clone)]
 pub struct Config {
@@ -19,7 +22,7 @@ pub struct Config {
     pub pipeline_api_url: String,
     pub pipeline_api_token: Option<String>,
     pub admin_token: Option<String>,
-    pub cors_origins: Option<Vec<String>>,
+    pub cors: CorsSettings,
     pub max_concurrency: usize,
     pub http_bind: String,
     pub http_port: u16,
@@ -27,11 +30,42 @@ pub struct Config {
     pub upload_timeout: Duration,
     pub database_url: String,
     pub automation_poll_interval: Duration,
+    /// Public URL of `POST /graph/notifications`; enables the Graph
+    /// subscription (see `webhook.rs`).
+    pub graph_webhook_url: Option<String>,
     pub message_broker_url: Option<String>,
     pub pipeline_result_topic: String,
     pub pipeline_result_group: String,
     pub upload_ready_poll_interval: Duration,
     pub upload_ready_poll_attempts: u32,
+    /// Interval of the SFTP source poller; zero disables it.
+    pub sftp_poll_interval: Duration,
+    pub sftp_timeout: Duration,
+    /// Interval of the IMAP mailbox poller; zero disables it.
+    pub imap_poll_interval: Duration,
+    pub imap_timeout: Duration,
+    /// Failed downloads a SharePoint job tolerates (`JOB_FILE_FAILURE_TOLERANCE`).
+    pub file_failure_tolerance: FailureTolerance,
+    /// Directory of the per-job work directories (`JOB_WORK_DIR`); kept across
+    /// restarts so interrupted jobs resume.
+    pub job_work_dir: PathBuf,
+    /// User whose OneDrive the `onedrive` connector reads (`ONEDRIVE_USER`);
+    /// `None` disables the connector.
+    pub onedrive_user: Option<String>,
+    /// OneDrive folder whose subfolders are ingested (`ONEDRIVE_INPUT_FOLDER`).
+    pub onedrive_input_folder: String,
+    /// Directory whose subdirectories the `local` connector ingests
+    /// (`LOCAL_SOURCE_DIR`), e.g. a mounted network share.
+    pub local_source_dir: Option<PathBuf>,
+    /// Endpoint of `s3` sources without their own (`S3_SOURCE_ENDPOINT`);
+    /// `None` uses AWS.
+    pub s3_source_endpoint: Option<String>,
+    /// Region of `s3` sources without their own (`S3_SOURCE_REGION`).
+    pub s3_source_region: String,
+    /// Key id and secret of `s3` sources without stored keys
+    /// (`S3_SOURCE_ACCESS_KEY_ID`, `S3_SOURCE_SECRET_ACCESS_KEY`).
+    pub s3_source_access_key: Option<(String, String)>,
+    pub s3_timeout: Duration,
 }
 
 impl Config {
@@ -55,13 +89,7 @@ impl Config {
             env::var("PIPELINE_API_URL").unwrap_or_else(|_| "http://pipeline-api:8084".to_string());
         let pipeline_api_token = env::var("PIPELINE_API_TOKEN").ok();
         let admin_token = env::var("ADMIN_TOKEN").ok();
-        let cors_origins = env::var("CORS_ORIGINS").ok().map(|origins| {
-            origins
-                .split(',')
-                .map(|s| s.trim().to_string())
-                .filter(|s| !s.is_empty())
-                .collect::<Vec<_>>()
-        });
+        let cors = CorsSettings::from_env();
         let max_concurrency = env::var("MAX_CONCURRENCY")
             .ok()
             .and_then(|v| v.parse::<usize>().ok())
@@ -93,6 +121,10 @@ impl Config {
                 .filter(|v: &u64| *v > 0)
                 .unwrap_or(120),
         );
+        let graph_webhook_url = env::var("GRAPH_WEBHOOK_URL")
+            .ok()
+            .map(|v| v.trim().to_string())
+            .filter(|v| !v.is_empty());
         let message_broker_url = env::var("MESSAGE_BROKER_URL").ok();
         let pipeline_result_topic =
             env::var("PIPELINE_RESULT_TOPIC").unwrap_or_else(|_| "pipeline-result".to_string());
@@ -110,6 +142,78 @@ impl Config {
             .and_then(|v| v.parse().ok())
             .filter(|v: &u32| *v > 0)
             .unwrap_or(12);
+        let sftp_poll_interval = Duration::from_secs(
+            env::var("SFTP_POLL_INTERVAL_SECS")
+                .ok()
+                .and_then(|v| v.parse().ok())
+                .unwrap_or(300),
+        );
+        let sftp_timeout = Duration::from_secs(
+            env::var("SFTP_TIMEOUT_SECS")
+                .ok()
+                .and_then(|v| v.parse().ok())
+                .filter(|v: &u64| *v > 0)
+                .unwrap_or(60),
+        );
+        let imap_poll_interval = Duration::from_secs(
+            env::var("IMAP_POLL_INTERVAL_SECS")
+                .ok()
+                .and_then(|v| v.parse().ok())
+                .unwrap_or(120),
+        );
+        let imap_timeout = Duration::from_secs(
+            env::var("IMAP_TIMEOUT_SECS")
+                .ok()
+                .and_then(|v| v.parse().ok())
+                .filter(|v: &u64| *v > 0)
+                .unwrap_or(60),
+        );
+        let file_failure_tolerance = match env::var("JOB_FILE_FAILURE_TOLERANCE") {
+            Ok(raw) if !raw.trim().is_empty() => {
+                FailureTolerance::parse(&raw).context("invalid JOB_FILE_FAILURE_TOLERANCE")?
+            }
+            _ => FailureTolerance::default(),
+        };
+        let job_work_dir = env::var("JOB_WORK_DIR")
+            .ok()
+            .filter(|v| !v.trim().is_empty())
+            .map(PathBuf::from)
+            .unwrap_or_else(|| PathBuf::from("/var/lib/sharepoint-ingest/jobs"));
+        let onedrive_user = env::var("ONEDRIVE_USER")
+            .ok()
+            .map(|v| v.trim().to_string())
+            .filter(|v| !v.is_empty());
+        let onedrive_input_folder =
+            env::var("ONEDRIVE_INPUT_FOLDER").unwrap_or_else(|_| input_folder.clone());
+        let local_source_dir = env::var("LOCAL_SOURCE_DIR")
+            .ok()
+            .filter(|v| !v.trim().is_empty())
+            .map(PathBuf::from);
+        let s3_source_endpoint = env::var("S3_SOURCE_ENDPOINT")
+            .ok()
+            .map(|v| v.trim().trim_end_matches('/').to_string())
+            .filter(|v| !v.is_empty());
+        let s3_source_region = env::var("S3_SOURCE_REGION")
+            .ok()
+            .map(|v| v.trim().to_string())
+            .filter(|v| !v.is_empty())
+            .unwrap_or_else(|| "us-east-1".to_string());
+        let s3_source_access_key = match (
+            env::var("S3_SOURCE_ACCESS_KEY_ID"),
+            env::var("S3_SOURCE_SECRET_ACCESS_KEY"),
+        ) {
+            (Ok(id), Ok(secret)) if !id.trim().is_empty() && !secret.is_empty() => {
+                Some((id.trim().to_string(), secret))
+            }
+            _ => None,
+        };
+        let s3_timeout = Duration::from_secs(
+            env::var("S3_SOURCE_TIMEOUT_SECS")
+                .ok()
+                .and_then(|v| v.parse().ok())
+                .filter(|v: &u64| *v > 0)
+                .unwrap_or(60),
+        );
 
         Ok(Self {
             tenant_id,
@@ -125,7 +229,7 @@ impl Config {
             pipeline_api_url,
             pipeline_api_token,
             admin_token,
-            cors_origins,
+            cors,
             max_concurrency,
             http_bind,
             http_port,
@@ -133,11 +237,25 @@ impl Config {
             upload_timeout,
             database_url,
             automation_poll_interval,
+            graph_webhook_url,
             message_broker_url,
             pipeline_result_topic,
             pipeline_result_group,
             upload_ready_poll_interval,
             upload_ready_poll_attempts,
+            sftp_poll_interval,
+            sftp_timeout,
+            imap_poll_interval,
+            imap_timeout,
+            file_failure_tolerance,
+            job_work_dir,
+            onedrive_user,
+            onedrive_input_folder,
+            local_source_dir,
+            s3_source_endpoint,
+            s3_source_region,
+            s3_source_access_key,
+            s3_timeout,
         })
     }
 
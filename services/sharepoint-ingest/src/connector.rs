@@ -0,0 +1,372 @@
+//! Drive connectors a folder job can ingest from.
+//!
+//! A [`SourceConnector`] lists the ingest folders, the PDFs of one folder and
+//! downloads files; the `list` and `download` steps, the preview and the
+//! automation poller only talk to this trait. Built in are
+//!
+//! - `sharepoint`: the site drive (`SITE_HOST`/`SITE_PATH`), subfolders of
+//!   `INPUT_FOLDER`; the only connector with Graph delta and webhooks
+//! - `onedrive`: the OneDrive of `ONEDRIVE_USER` via the same app
+//!   registration, subfolders of `ONEDRIVE_INPUT_FOLDER`
+//! - `local`: subdirectories of `LOCAL_SOURCE_DIR`, e.g. a mounted network
+//!   share; folder and file ids are paths relative to that directory
+//! - `s3`: key prefixes of the buckets in `s3_sources` (see `s3.rs`)
+//!
+//! Automation rules pick their connector (`sharepoint_automation.connector`);
+//! folders of `onedrive`, `local` and `s3` rules are listed completely on
+//! every poll.
+
+use std::future::Future;
+use std::path::{Component, Path, PathBuf};
+use std::pin::Pin;
+use std::sync::Arc;
+use std::time::UNIX_EPOCH;
+
+use anyhow::{anyhow, bail, Context, Result};
+use serde::{Deserialize, Serialize};
+use tokio::io::AsyncReadExt;
+use tracing::warn;
+
+use crate::config::Config;
+use crate::msgraph::{GraphFile, GraphFolder, MsGraphClient};
+use crate::s3::S3Connector;
+
+pub type ConnectorFuture<'a, T> = Pin<Box<dyn Future<Output = Result<T>> + Send + 'a>>;
+
+/// Creates `sharepoint_automation.connector` (see migration 0062).
+pub const CONNECTOR_SCHEMA_SQL: &str = r#"
+ALTER TABLE sharepoint_automation
+    ADD COLUMN IF NOT EXISTS connector TEXT NOT NULL DEFAULT 'sharepoint';
+"#;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ConnectorKind {
+    #[default]
+    SharePoint,
+    OneDrive,
+    Local,
+    S3,
+}
+
+impl ConnectorKind {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ConnectorKind::SharePoint => "sharepoint",
+            ConnectorKind::OneDrive => "onedrive",
+            ConnectorKind::Local => "local",
+            ConnectorKind::S3 => "s3",
+        }
+    }
+
+    /// Reads the stored column; unknown values fall back to SharePoint.
+    pub fn from_column(value: &str) -> Self {
+        match value {
+            "onedrive" => ConnectorKind::OneDrive,
+            "local" => ConnectorKind::Local,
+            "s3" => ConnectorKind::S3,
+            _ => ConnectorKind::SharePoint,
+        }
+    }
+}
+
+/// Folder source of a job.
+pub trait SourceConnector: Send + Sync {
+    /// Path of the folder whose subfolders are listed.
+    fn base(&self) -> String;
+    /// Immediate subfolders of [`SourceConnector::base`].
+    fn list_folders(&self) -> ConnectorFuture<'_, Vec<GraphFolder>>;
+    /// PDFs of one folder; empty if the folder does not exist.
+    fn list_pdfs<'a>(&'a self, folder_id: &'a str) -> ConnectorFuture<'a, Vec<GraphFile>>;
+    fn download<'a>(&'a self, file_id: &'a str, dest: &'a Path) -> ConnectorFuture<'a, ()>;
+    /// At most `len` bytes from the start of a file.
+    fn download_head<'a>(&'a self, file_id: &'a str, len: usize) -> ConnectorFuture<'a, Vec<u8>>;
+    /// Graph client of the drive; only Graph folders support post-actions
+    /// (see `post_action.rs`).
+    fn graph(&self) -> Option<Arc<MsGraphClient>> {
+        None
+    }
+}
+
+/// SharePoint site drive or OneDrive via Microsoft Graph.
+pub struct GraphDriveConnector {
+    graph: Arc<MsGraphClient>,
+    input_path: String,
+}
+
+impl GraphDriveConnector {
+    pub fn new(graph: Arc<MsGraphClient>, input_path: String) -> Self {
+        Self { graph, input_path }
+    }
+}
+
+impl SourceConnector for GraphDriveConnector {
+    fn base(&self) -> String {
+        self.input_path.clone()
+    }
+
+    fn list_folders(&self) -> ConnectorFuture<'_, Vec<GraphFolder>> {
+        Box::pin(self.graph.list_subfolders(&self.input_path))
+    }
+
+    fn list_pdfs<'a>(&'a self, folder_id: &'a str) -> ConnectorFuture<'a, Vec<GraphFile>> {
+        Box::pin(self.graph.list_pdfs_in_folder(folder_id))
+    }
+
+    fn download<'a>(&'a self, file_id: &'a str, dest: &'a Path) -> ConnectorFuture<'a, ()> {
+        Box::pin(self.graph.download_file(file_id, dest))
+    }
+
+    fn download_head<'a>(&'a self, file_id: &'a str, len: usize) -> ConnectorFuture<'a, Vec<u8>> {
+        Box::pin(self.graph.download_head(file_id, len))
+    }
+
+    fn graph(&self) -> Option<Arc<MsGraphClient>> {
+        Some(self.graph.clone())
+    }
+}
+
+/// Subdirectories of a local directory.
+pub struct LocalDirConnector {
+    root: PathBuf,
+}
+
+impl LocalDirConnector {
+    pub fn new(root: PathBuf) -> Self {
+        Self { root }
+    }
+
+    /// Path of a folder or file id; ids leaving the root are refused.
+    fn resolve(&self, id: &str) -> Result<PathBuf> {
+        let relative = Path::new(id);
+        let plain = relative
+            .components()
+            .all(|component| matches!(component, Component::Normal(_)));
+        if id.is_empty() || !plain {
+            bail!("invalid local path {id:?}");
+        }
+        Ok(self.root.join(relative))
+    }
+
+    async fn folders(&self) -> Result<Vec<GraphFolder>> {
+        let mut entries = tokio::fs::read_dir(&self.root)
+            .await
+            .with_context(|| format!("reading {}", self.root.display()))?;
+        let mut folders = Vec::new();
+        while let Some(entry) = entries.next_entry().await? {
+            if !entry.file_type().await?.is_dir() {
+                continue;
+            }
+            let Some(name) = entry.file_name().to_str().map(str::to_string) else {
+                continue;
+            };
+            let mut children = tokio::fs::read_dir(entry.path()).await?;
+            let mut file_count = 0;
+            while children.next_entry().await?.is_some() {
+                file_count += 1;
+            }
+            folders.push(GraphFolder {
+                id: name.clone(),
+                name,
+                file_count,
+            });
+        }
+        folders.sort_by(|a, b| a.name.cmp(&b.name));
+        Ok(folders)
+    }
+
+    async fn pdfs(&self, folder_id: &str) -> Result<Vec<GraphFile>> {
+        let dir = self.resolve(folder_id)?;
+        let mut entries = match tokio::fs::read_dir(&dir).await {
+            Ok(entries) => entries,
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+            Err(err) => return Err(err).with_context(|| format!("reading {}", dir.display())),
+        };
+        let mut files = Vec::new();
+        while let Some(entry) = entries.next_entry().await? {
+            let Some(name) = entry.file_name().to_str().map(str::to_string) else {
+                continue;
+            };
+            let metadata = entry.metadata().await?;
+            if !metadata.is_file() || !name.to_ascii_lowercase().ends_with(".pdf") {
+                continue;
+            }
+            // Größe und Änderungszeit ersetzen den eTag für das Inventar
+            let modified = metadata
+                .modified()
+                .ok()
+                .and_then(|time| time.duration_since(UNIX_EPOCH).ok())
+                .unwrap_or_default();
+            files.push(GraphFile {
+                id: format!("{folder_id}/{name}"),
+                name,
+                etag: Some(format!("{}-{}", metadata.len(), modified.as_nanos())),
+                size: Some(metadata.len() as i64),
+            });
+        }
+        files.sort_by(|a, b| a.name.cmp(&b.name));
+        Ok(files)
+    }
+
+    async fn copy(&self, file_id: &str, dest: &Path) -> Result<()> {
+        let source = self.resolve(file_id)?;
+        tokio::fs::copy(&source, dest)
+            .await
+            .with_context(|| format!("copying {}", source.display()))?;
+        Ok(())
+    }
+
+    async fn head(&self, file_id: &str, len: usize) -> Result<Vec<u8>> {
+        let source = self.resolve(file_id)?;
+        let file = tokio::fs::File::open(&source)
+            .await
+            .with_context(|| format!("opening {}", source.display()))?;
+        let mut head = Vec::with_capacity(len);
+        file.take(len as u64).read_to_end(&mut head).await?;
+        Ok(head)
+    }
+}
+
+impl SourceConnector for LocalDirConnector {
+    fn base(&self) -> String {
+        self.root.display().to_string()
+    }
+
+    fn list_folders(&self) -> ConnectorFuture<'_, Vec<GraphFolder>> {
+        Box::pin(self.folders())
+    }
+
+    fn list_pdfs<'a>(&'a self, folder_id: &'a str) -> ConnectorFuture<'a, Vec<GraphFile>> {
+        Box::pin(self.pdfs(folder_id))
+    }
+
+    fn download<'a>(&'a self, file_id: &'a str, dest: &'a Path) -> ConnectorFuture<'a, ()> {
+        Box::pin(self.copy(file_id, dest))
+    }
+
+    fn download_head<'a>(&'a self, file_id: &'a str, len: usize) -> ConnectorFuture<'a, Vec<u8>> {
+        Box::pin(self.head(file_id, len))
+    }
+}
+
+/// The configured connectors; SharePoint and S3 are always there.
+#[derive(Clone)]
+pub struct Connectors {
+    sharepoint: Arc<dyn SourceConnector>,
+    onedrive: Option<Arc<dyn SourceConnector>>,
+    local: Option<Arc<dyn SourceConnector>>,
+    s3: Arc<dyn SourceConnector>,
+}
+
+impl Connectors {
+    /// SharePoint via `graph`, the `s3` sources, OneDrive and the local
+    /// directory if configured.
+    pub async fn from_config(
+        config: &Config,
+        graph: Arc<MsGraphClient>,
+        s3: Arc<S3Connector>,
+    ) -> Result<Self> {
+        let sharepoint = Arc::new(GraphDriveConnector::new(graph, config.drive_input_path()));
+        let onedrive = match &config.onedrive_user {
+            Some(user) => {
+                let client = MsGraphClient::for_user_drive(config, user)?;
+                // Fehlender Zugriff auf das OneDrive soll SharePoint nicht blockieren
+                if let Err(err) = client.ensure_folder(&config.onedrive_input_folder).await {
+                    warn!(%user, error = %err, "onedrive input folder not available");
+                }
+                Some(Arc::new(GraphDriveConnector::new(
+                    Arc::new(client),
+                    config.onedrive_input_folder.clone(),
+                )) as Arc<dyn SourceConnector>)
+            }
+            None => None,
+        };
+        let local = config.local_source_dir.as_ref().map(|root| {
+            if !root.is_dir() {
+                warn!(dir = %root.display(), "LOCAL_SOURCE_DIR is not a directory");
+            }
+            Arc::new(LocalDirConnector::new(root.clone())) as Arc<dyn SourceConnector>
+        });
+        Ok(Self {
+            sharepoint,
+            onedrive,
+            local,
+            s3,
+        })
+    }
+
+    pub fn get(&self, kind: ConnectorKind) -> Result<Arc<dyn SourceConnector>> {
+        let connector = match kind {
+            ConnectorKind::SharePoint => Some(&self.sharepoint),
+            ConnectorKind::OneDrive => self.onedrive.as_ref(),
+            ConnectorKind::Local => self.local.as_ref(),
+            ConnectorKind::S3 => Some(&self.s3),
+        };
+        connector
+            .cloned()
+            .ok_or_else(|| anyhow!("connector {} is not configured", kind.as_str()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn local_connector_lists_and_copies_pdfs() {
+        let root = tempfile::tempdir().unwrap();
+        let folder = root.path().join("Akte 1");
+        std::fs::create_dir(&folder).unwrap();
+        std::fs::write(folder.join("b.PDF"), b"%PDF-1.4 b").unwrap();
+        std::fs::write(folder.join("a.pdf"), b"%PDF-1.4 a").unwrap();
+        std::fs::write(folder.join("notes.txt"), b"x").unwrap();
+        std::fs::write(root.path().join("loose.pdf"), b"%PDF").unwrap();
+        let connector = LocalDirConnector::new(root.path().to_path_buf());
+
+        let folders = connector.list_folders().await.unwrap();
+        assert_eq!(folders.len(), 1);
+        assert_eq!(
+            (folders[0].id.as_str(), folders[0].file_count),
+            ("Akte 1", 3)
+        );
+
+        let files = connector.list_pdfs("Akte 1").await.unwrap();
+        let ids: Vec<_> = files.iter().map(|f| f.id.as_str()).collect();
+        assert_eq!(ids, ["Akte 1/a.pdf", "Akte 1/b.PDF"]);
+        assert_eq!(files[0].size, Some(10));
+        assert!(files[0].etag.is_some());
+        assert!(connector.list_pdfs("Akte 2").await.unwrap().is_empty());
+
+        let dest = root.path().join("copy.pdf");
+        connector.download(&files[0].id, &dest).await.unwrap();
+        assert_eq!(std::fs::read(&dest).unwrap(), b"%PDF-1.4 a");
+        let head = connector.download_head(&files[1].id, 4).await.unwrap();
+        assert_eq!(head, b"%PDF");
+    }
+
+    #[tokio::test]
+    async fn local_connector_stays_inside_its_root() {
+        let root = tempfile::tempdir().unwrap();
+        let connector = LocalDirConnector::new(root.path().join("share"));
+        for id in ["../share", "/etc/passwd", "Akte/../../x.pdf", "./a.pdf", ""] {
+            assert!(connector.resolve(id).is_err(), "{id}");
+        }
+        let dest = root.path().join("x.pdf");
+        assert!(connector.download("../x.pdf", &dest).await.is_err());
+    }
+
+    #[test]
+    fn kind_round_trips_through_the_column() {
+        for kind in [
+            ConnectorKind::SharePoint,
+            ConnectorKind::OneDrive,
+            ConnectorKind::Local,
+            ConnectorKind::S3,
+        ] {
+            assert_eq!(ConnectorKind::from_column(kind.as_str()), kind);
+            let json = serde_json::to_value(kind).unwrap();
+            assert_eq!(json, kind.as_str());
+        }
+        assert_eq!(ConnectorKind::from_column("ftp"), ConnectorKind::SharePoint);
+    }
+}
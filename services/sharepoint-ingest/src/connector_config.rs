@@ -0,0 +1,47 @@
+//! Configuration helpers shared by the IMAP, SFTP and S3 connectors.
+//!
+//! All three keep their sources in the database with the credentials
+//! envelope-encrypted by `CREDENTIALS_MASTER_KEY`, and take optional text
+//! fields from the admin API where a blank value means "not set".
+
+use anyhow::{anyhow, Result};
+use shared::envelope::MasterKey;
+
+/// Error when credentials have to be sealed or opened without master key.
+pub const MASTER_KEY_MISSING: &str = "CREDENTIALS_MASTER_KEY is not configured";
+
+/// Optional input field; blank counts as absent.
+pub fn non_empty(value: Option<String>) -> Option<String> {
+    value.filter(|v| !v.trim().is_empty())
+}
+
+/// The master key, or [`MASTER_KEY_MISSING`].
+pub fn require_master_key(master: Option<&MasterKey>) -> Result<&MasterKey> {
+    master.ok_or_else(|| anyhow!(MASTER_KEY_MISSING))
+}
+
+/// Connector whose sources carry sealed credentials.
+pub trait MasterKeyed {
+    /// `CREDENTIALS_MASTER_KEY`, if configured.
+    fn master_key(&self) -> Option<&MasterKey>;
+
+    fn require_master_key(&self) -> Result<&MasterKey> {
+        require_master_key(self.master_key())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn blank_values_are_absent() {
+        assert_eq!(non_empty(None), None);
+        assert_eq!(non_empty(Some("  ".to_string())), None);
+        assert_eq!(
+            non_empty(Some("INBOX".to_string())).as_deref(),
+            Some("INBOX")
+        );
+        assert!(require_master_key(None).is_err());
+    }
+}
@@ -0,0 +1,162 @@
+//! Incremental polling of the SharePoint input folder via Graph delta queries.
+//!
+//! Instead of listing every subfolder of `INPUT_FOLDER` on each poll, the
+//! automation poller keeps one delta link per drive in `sharepoint_sync_state`
+//! and only looks at folders that were created, renamed or received files since
+//! then ([`changed_folders`]). Without a stored link (first start, expired link,
+//! or after [`reset`]) the poller lists all folders once and stores the link of
+//! the current drive state. The link is only advanced after a successful poll,
+//! so failed polls see the same changes again.
+
+use std::collections::{HashMap, HashSet};
+
+use anyhow::Result;
+use tokio_postgres::Client;
+
+use crate::msgraph::{GraphChange, GraphFolder};
+
+pub const DELTA_SCHEMA_SQL: &str = r#"
+CREATE TABLE IF NOT EXISTS sharepoint_sync_state (
+    drive_id TEXT PRIMARY KEY,
+    delta_link TEXT NOT NULL,
+    updated_at TIMESTAMPTZ NOT NULL DEFAULT now()
+);
+"#;
+
+/// Stored delta link of a drive.
+pub async fn load_link(client: &Client, drive_id: &str) -> Result<Option<String>> {
+    let row = client
+        .query_opt(
+            "SELECT delta_link FROM sharepoint_sync_state WHERE drive_id = $1",
+            &[&drive_id],
+        )
+        .await?;
+    Ok(row.map(|row| row.get(0)))
+}
+
+/// Replaces the link a poll started from with the link after it. Nothing is
+/// stored if the link changed meanwhile, e.g. by [`reset`] during the poll.
+pub async fn advance_link(
+    client: &Client,
+    drive_id: &str,
+    previous: Option<&str>,
+    delta_link: &str,
+) -> Result<bool> {
+    let updated = match previous {
+        Some(previous) => {
+            client
+                .execute(
+                    "UPDATE sharepoint_sync_state SET delta_link = $3, updated_at = now()
+                     WHERE drive_id = $1 AND delta_link = $2",
+                    &[&drive_id, &previous, &delta_link],
+                )
+                .await?
+        }
+        None => {
+            client
+                .execute(
+                    "INSERT INTO sharepoint_sync_state (drive_id, delta_link)
+                     VALUES ($1, $2)
+                     ON CONFLICT (drive_id) DO NOTHING",
+                    &[&drive_id, &delta_link],
+                )
+                .await?
+        }
+    };
+    Ok(updated > 0)
+}
+
+/// Drops all delta links, so the next poll lists every folder again; needed
+/// whenever automation rules change for folders that did not change themselves.
+pub async fn reset(client: &Client) -> Result<()> {
+    client
+        .execute("DELETE FROM sharepoint_sync_state", &[])
+        .await?;
+    Ok(())
+}
+
+/// Subfolders of the input folder touched by `changes`.
+#[derive(Debug, Default)]
+pub struct ChangedFolders {
+    /// Folders contained in the delta itself.
+    pub folders: Vec<GraphFolder>,
+    /// Folders that only appear as parent of a changed file; the caller looks
+    /// them up and keeps those directly below the input folder.
+    pub unresolved: Vec<String>,
+}
+
+/// Picks the direct subfolders of `input_id` from a delta: changed folders
+/// themselves and the folders of changed files. Deleted items and everything
+/// outside the input folder are ignored.
+pub fn changed_folders(changes: &[GraphChange], input_id: &str) -> ChangedFolders {
+    let folders: HashMap<&str, &GraphChange> = changes
+        .iter()
+        .filter(|c| c.is_folder && !c.deleted)
+        .map(|c| (c.id.as_str(), c))
+        .collect();
+    let mut result = ChangedFolders::default();
+    let mut seen = HashSet::new();
+    for change in changes.iter().filter(|c| !c.deleted) {
+        let folder_id = if change.is_folder {
+            change.id.as_str()
+        } else {
+            match change.parent_id.as_deref() {
+                Some(parent) => parent,
+                None => continue,
+            }
+        };
+        if folder_id == input_id || !seen.insert(folder_id) {
+            continue;
+        }
+        match folders.get(folder_id) {
+            Some(folder) if folder.parent_id.as_deref() == Some(input_id) => {
+                result.folders.push(GraphFolder {
+                    id: folder.id.clone(),
+                    name: folder.name.clone(),
+                    file_count: folder.child_count,
+                });
+            }
+            Some(_) => {}
+            None => result.unresolved.push(folder_id.to_string()),
+        }
+    }
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn change(id: &str, parent: &str, is_folder: bool) -> GraphChange {
+        GraphChange {
+            id: id.to_string(),
+            name: format!("name-{id}"),
+            parent_id: Some(parent.to_string()),
+            is_folder,
+            child_count: 0,
+            deleted: false,
+        }
+    }
+
+    #[test]
+    fn keeps_direct_subfolders_and_parents_of_changed_files() {
+        let mut deleted = change("gone", "input", true);
+        deleted.deleted = true;
+        let changes = vec![
+            change("input", "root", true),
+            change("a", "input", true),
+            change("a.pdf", "a", false),
+            change("b.pdf", "b", false),
+            change("c.pdf", "b", false),
+            change("nested", "a", true),
+            change("elsewhere", "other", true),
+            deleted,
+        ];
+        let changed = changed_folders(&changes, "input");
+        let ids: Vec<&str> = changed.folders.iter().map(|f| f.id.as_str()).collect();
+        assert_eq!(ids, ["a"]);
+        assert_eq!(changed.folders[0].name, "name-a");
+        // "b" fehlt im Delta und wird nachgeschlagen, "nested" liegt eine Ebene tiefer
+        assert_eq!(changed.unresolved, ["b"]);
+    }
+}
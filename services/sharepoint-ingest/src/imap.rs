@@ -0,0 +1,785 @@
+//! IMAP mailbox connector: PDF attachments of incoming mails become ingest jobs.
+//!
+//! Each mailbox belongs to a tenant (and optionally a pipeline). The poller
+//! searches the configured folder for mails without the processed keyword,
+//! creates one job per mail and tags it (`$RegressIngested`, `Regress-Job-<id>`);
+//! once the pipeline run is known the mail additionally gets `Regress-Run-<id>`.
+//! The job worker fetches the mail again by UID, renders the mail body as a
+//! cover page and merges it with the attachments.
+//!
+//! Only IMAPS (implicit TLS) with LOGIN is supported; the client below speaks
+//! just the handful of commands needed for that.
+
+use std::{path::Path, path::PathBuf, sync::Arc, time::Duration};
+
+use anyhow::{anyhow, bail, Context, Result};
+use chrono::{DateTime, Utc};
+use deadpool_postgres::Pool;
+use mail_parser::{MessageParser, MimeHeaders};
+use serde::{Deserialize, Serialize};
+use shared::envelope::{MasterKey, SealedSecret};
+use tokio::{
+    io::{AsyncBufReadExt, AsyncReadExt, AsyncWriteExt, BufReader},
+    net::TcpStream,
+    time::timeout,
+};
+use tokio_postgres::Row;
+use tokio_rustls::{
+    client::TlsStream,
+    rustls::{self, pki_types::ServerName, ClientConfig, RootCertStore},
+    TlsConnector,
+};
+use uuid::Uuid;
+
+use crate::connector_config::{non_empty, MasterKeyed};
+use crate::{pdfops::write_text_pdf, sanitize_filename};
+
+pub const IMAP_SCHEMA_SQL: &str = r#"
+CREATE TABLE IF NOT EXISTS imap_mailboxes (
+    id UUID PRIMARY KEY,
+    name TEXT NOT NULL,
+    tenant_id UUID,
+    pipeline_id UUID,
+    host TEXT NOT NULL,
+    port INTEGER NOT NULL DEFAULT 993,
+    username TEXT NOT NULL,
+    credentials JSONB NOT NULL,
+    folder TEXT NOT NULL DEFAULT 'INBOX',
+    processed_keyword TEXT NOT NULL DEFAULT '$RegressIngested',
+    include_body BOOLEAN NOT NULL DEFAULT TRUE,
+    enabled BOOLEAN NOT NULL DEFAULT TRUE,
+    last_polled_at TIMESTAMPTZ,
+    last_error TEXT,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT now(),
+    updated_at TIMESTAMPTZ NOT NULL DEFAULT now()
+);
+
+CREATE TABLE IF NOT EXISTS imap_messages (
+    mailbox_id UUID NOT NULL REFERENCES imap_mailboxes(id) ON DELETE CASCADE,
+    uid_validity BIGINT NOT NULL,
+    uid BIGINT NOT NULL,
+    message_id TEXT,
+    subject TEXT,
+    sender TEXT,
+    attachments INTEGER NOT NULL DEFAULT 0,
+    job_id UUID,
+    run_id UUID,
+    seen_at TIMESTAMPTZ NOT NULL DEFAULT now(),
+    PRIMARY KEY (mailbox_id, uid_validity, uid)
+);
+
+CREATE INDEX IF NOT EXISTS idx_imap_messages_job ON imap_messages (job_id);
+CREATE INDEX IF NOT EXISTS idx_imap_messages_message_id
+    ON imap_messages (mailbox_id, message_id) WHERE message_id IS NOT NULL;
+"#;
+
+const MAILBOX_COLUMNS: &str = "id, name, tenant_id, pipeline_id, host, port, username, credentials,
+     folder, processed_keyword, include_body, enabled, last_polled_at, last_error, updated_at";
+
+/// Mails handled per mailbox and poll; the rest follows in the next round.
+pub const MAX_MESSAGES_PER_POLL: usize = 25;
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+struct SealedCredentials {
+    #[serde(default)]
+    password: Option<SealedSecret>,
+}
+
+#[derive(Debug, Clone)]
+pub struct ImapMailbox {
+    pub id: Uuid,
+    pub name: String,
+    pub tenant_id: Option<Uuid>,
+    pub pipeline_id: Option<Uuid>,
+    pub host: String,
+    pub port: i32,
+    pub username: String,
+    credentials: SealedCredentials,
+    pub folder: String,
+    pub processed_keyword: String,
+    pub include_body: bool,
+    pub enabled: bool,
+    pub last_polled_at: Option<DateTime<Utc>>,
+    pub last_error: Option<String>,
+    pub updated_at: DateTime<Utc>,
+}
+
+/// Mailbox as returned by the API; the password is reduced to a flag.
+#[derive(Debug, Serialize)]
+pub struct ImapMailboxSummary {
+    pub id: Uuid,
+    pub name: String,
+    pub tenant_id: Option<Uuid>,
+    pub pipeline_id: Option<Uuid>,
+    pub host: String,
+    pub port: i32,
+    pub username: String,
+    pub folder: String,
+    pub processed_keyword: String,
+    pub include_body: bool,
+    pub enabled: bool,
+    pub has_password: bool,
+    pub last_polled_at: Option<DateTime<Utc>>,
+    pub last_error: Option<String>,
+    pub updated_at: DateTime<Utc>,
+}
+
+impl ImapMailbox {
+    fn from_row(row: &Row) -> Result<Self> {
+        let credentials: serde_json::Value = row.get("credentials");
+        Ok(Self {
+            id: row.get("id"),
+            name: row.get("name"),
+            tenant_id: row.get("tenant_id"),
+            pipeline_id: row.get("pipeline_id"),
+            host: row.get("host"),
+            port: row.get("port"),
+            username: row.get("username"),
+            credentials: serde_json::from_value(credentials)
+                .context("invalid imap credentials record")?,
+            folder: row.get("folder"),
+            processed_keyword: row.get("processed_keyword"),
+            include_body: row.get("include_body"),
+            enabled: row.get("enabled"),
+            last_polled_at: row.get("last_polled_at"),
+            last_error: row.get("last_error"),
+            updated_at: row.get("updated_at"),
+        })
+    }
+
+    pub fn summary(&self) -> ImapMailboxSummary {
+        ImapMailboxSummary {
+            id: self.id,
+            name: self.name.clone(),
+            tenant_id: self.tenant_id,
+            pipeline_id: self.pipeline_id,
+            host: self.host.clone(),
+            port: self.port,
+            username: self.username.clone(),
+            folder: self.folder.clone(),
+            processed_keyword: self.processed_keyword.clone(),
+            include_body: self.include_body,
+            enabled: self.enabled,
+            has_password: self.credentials.password.is_some(),
+            last_polled_at: self.last_polled_at,
+            last_error: self.last_error.clone(),
+            updated_at: self.updated_at,
+        }
+    }
+
+    /// `folder_id` of jobs created for this mailbox.
+    pub fn job_folder_id(&self) -> String {
+        format!("imap:{}", self.id)
+    }
+}
+
+/// Payload of `PUT /imap/mailboxes/{id}`; an omitted password keeps the stored one.
+#[derive(Debug, Deserialize)]
+pub struct ImapMailboxInput {
+    pub name: String,
+    #[serde(default)]
+    pub tenant_id: Option<Uuid>,
+    #[serde(default)]
+    pub pipeline_id: Option<Uuid>,
+    pub host: String,
+    #[serde(default)]
+    pub port: Option<i32>,
+    pub username: String,
+    #[serde(default)]
+    pub password: Option<String>,
+    #[serde(default)]
+    pub folder: Option<String>,
+    #[serde(default)]
+    pub processed_keyword: Option<String>,
+    #[serde(default)]
+    pub include_body: Option<bool>,
+    #[serde(default)]
+    pub enabled: Option<bool>,
+}
+
+/// IMAP keywords are atoms; restrict them to a conservative character set.
+fn is_valid_keyword(value: &str) -> bool {
+    !value.is_empty()
+        && value
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || matches!(c, '$' | '_' | '-' | '.'))
+}
+
+impl ImapMailboxInput {
+    /// Checks required fields; `is_new` additionally requires a password.
+    pub fn validate(&self, is_new: bool) -> Result<()> {
+        if self.name.trim().is_empty() || self.host.trim().is_empty() {
+            bail!("name and host are required");
+        }
+        if self.username.trim().is_empty() {
+            bail!("username is required");
+        }
+        if let Some(keyword) = self.processed_keyword.as_deref() {
+            if !is_valid_keyword(keyword) {
+                bail!("processed_keyword must only contain letters, digits, '$', '_', '-' or '.'");
+            }
+        }
+        if is_new && non_empty(self.password.clone()).is_none() {
+            bail!("password is required");
+        }
+        Ok(())
+    }
+}
+
+pub async fn list_mailboxes(pool: &Pool) -> Result<Vec<ImapMailbox>> {
+    let client = pool.get().await?;
+    let rows = client
+        .query(
+            &format!("SELECT {MAILBOX_COLUMNS} FROM imap_mailboxes ORDER BY name"),
+            &[],
+        )
+        .await?;
+    rows.iter().map(ImapMailbox::from_row).collect()
+}
+
+pub async fn load_mailbox(pool: &Pool, id: Uuid) -> Result<Option<ImapMailbox>> {
+    let client = pool.get().await?;
+    let row = client
+        .query_opt(
+            &format!("SELECT {MAILBOX_COLUMNS} FROM imap_mailboxes WHERE id = $1"),
+            &[&id],
+        )
+        .await?;
+    row.as_ref().map(ImapMailbox::from_row).transpose()
+}
+
+/// Seals the password and upserts a mailbox validated with [`ImapMailboxInput::validate`].
+pub async fn upsert_mailbox(
+    pool: &Pool,
+    master: &MasterKey,
+    id: Uuid,
+    input: ImapMailboxInput,
+) -> Result<ImapMailbox> {
+    let existing = load_mailbox(pool, id)
+        .await?
+        .map(|m| m.credentials)
+        .unwrap_or_default();
+    let password = non_empty(input.password)
+        .map(|p| master.seal(&p))
+        .transpose()?;
+    let credentials = SealedCredentials {
+        password: password.or(existing.password),
+    };
+
+    let client = pool.get().await?;
+    client
+        .execute(
+            "INSERT INTO imap_mailboxes (
+                id, name, tenant_id, pipeline_id, host, port, username, credentials,
+                folder, processed_keyword, include_body, enabled
+             ) VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12)
+             ON CONFLICT (id) DO UPDATE SET
+                name = EXCLUDED.name,
+                tenant_id = EXCLUDED.tenant_id,
+                pipeline_id = EXCLUDED.pipeline_id,
+                host = EXCLUDED.host,
+                port = EXCLUDED.port,
+                username = EXCLUDED.username,
+                credentials = EXCLUDED.credentials,
+                folder = EXCLUDED.folder,
+                processed_keyword = EXCLUDED.processed_keyword,
+                include_body = EXCLUDED.include_body,
+                enabled = EXCLUDED.enabled,
+                updated_at = now()",
+            &[
+                &id,
+                &input.name.trim(),
+                &input.tenant_id,
+                &input.pipeline_id,
+                &input.host.trim(),
+                &input.port.unwrap_or(993),
+                &input.username,
+                &serde_json::to_value(&credentials)?,
+                &non_empty(input.folder).unwrap_or_else(|| "INBOX".to_string()),
+                &non_empty(input.processed_keyword)
+                    .unwrap_or_else(|| "$RegressIngested".to_string()),
+                &input.include_body.unwrap_or(true),
+                &input.enabled.unwrap_or(true),
+            ],
+        )
+        .await?;
+    load_mailbox(pool, id)
+        .await?
+        .ok_or_else(|| anyhow!("imap mailbox vanished after upsert"))
+}
+
+pub async fn delete_mailbox(pool: &Pool, id: Uuid) -> Result<bool> {
+    let client = pool.get().await?;
+    let deleted = client
+        .execute("DELETE FROM imap_mailboxes WHERE id = $1", &[&id])
+        .await?;
+    Ok(deleted > 0)
+}
+
+pub async fn record_poll(pool: &Pool, id: Uuid, error: Option<&str>) -> Result<()> {
+    let client = pool.get().await?;
+    client
+        .execute(
+            "UPDATE imap_mailboxes SET last_polled_at = now(), last_error = $2 WHERE id = $1",
+            &[&id, &error],
+        )
+        .await?;
+    Ok(())
+}
+
+/// True if the mail was handled before: the same UID, or another copy with the
+/// same Message-ID (moved back, copied, or listed again after a new
+/// UIDVALIDITY, which resets the processed keyword).
+pub async fn is_known_message(
+    pool: &Pool,
+    mailbox_id: Uuid,
+    uid_validity: u32,
+    uid: u32,
+    message_id: Option<&str>,
+) -> Result<bool> {
+    let client = pool.get().await?;
+    let row = client
+        .query_one(
+            "SELECT EXISTS (
+                SELECT 1 FROM imap_messages
+                 WHERE mailbox_id = $1
+                   AND ((uid_validity = $2 AND uid = $3) OR message_id = $4)
+             )",
+            &[
+                &mailbox_id,
+                &(uid_validity as i64),
+                &(uid as i64),
+                &message_id,
+            ],
+        )
+        .await?;
+    Ok(row.get(0))
+}
+
+/// Remembers a handled mail and the job created for it.
+pub async fn record_message(
+    pool: &Pool,
+    mailbox_id: Uuid,
+    uid_validity: u32,
+    uid: u32,
+    mail: &MailInfo,
+    job_id: Option<Uuid>,
+) -> Result<()> {
+    let client = pool.get().await?;
+    client
+        .execute(
+            "INSERT INTO imap_messages (
+                mailbox_id, uid_validity, uid, message_id, subject, sender, attachments, job_id
+             ) VALUES ($1, $2, $3, $4, $5, $6, $7, $8)
+             ON CONFLICT (mailbox_id, uid_validity, uid) DO UPDATE SET job_id = EXCLUDED.job_id",
+            &[
+                &mailbox_id,
+                &(uid_validity as i64),
+                &(uid as i64),
+                &mail.message_id,
+                &mail.subject,
+                &mail.sender,
+                &(mail.attachments.len() as i32),
+                &job_id,
+            ],
+        )
+        .await?;
+    Ok(())
+}
+
+/// Mail of a job whose run has not been tagged yet: `(mailbox_id, uid_validity, uid)`.
+pub async fn untagged_message_for_job(
+    pool: &Pool,
+    job_id: Uuid,
+    run_id: Uuid,
+) -> Result<Option<(Uuid, u32, u32)>> {
+    let client = pool.get().await?;
+    let row = client
+        .query_opt(
+            "SELECT mailbox_id, uid_validity, uid FROM imap_messages
+             WHERE job_id = $1 AND run_id IS DISTINCT FROM $2",
+            &[&job_id, &run_id],
+        )
+        .await?;
+    Ok(row.map(|r| {
+        (
+            r.get("mailbox_id"),
+            r.get::<_, i64>("uid_validity") as u32,
+            r.get::<_, i64>("uid") as u32,
+        )
+    }))
+}
+
+pub async fn set_message_run(pool: &Pool, job_id: Uuid, run_id: Uuid) -> Result<()> {
+    let client = pool.get().await?;
+    client
+        .execute(
+            "UPDATE imap_messages SET run_id = $2 WHERE job_id = $1",
+            &[&job_id, &run_id],
+        )
+        .await?;
+    Ok(())
+}
+
+/// Header data and PDF attachment names of a mail.
+#[derive(Debug, Clone)]
+pub struct MailInfo {
+    pub message_id: Option<String>,
+    pub subject: Option<String>,
+    pub sender: Option<String>,
+    pub date: Option<String>,
+    pub attachments: Vec<String>,
+}
+
+impl MailInfo {
+    pub fn parse(raw: &[u8]) -> Result<Self> {
+        let message = MessageParser::default()
+            .parse(raw)
+            .ok_or_else(|| anyhow!("unparseable mail"))?;
+        Ok(Self {
+            message_id: message
+                .message_id()
+                .map(str::trim)
+                .filter(|id| !id.is_empty())
+                .map(str::to_string),
+            subject: message.subject().map(str::to_string),
+            sender: message
+                .from()
+                .and_then(|from| from.first())
+                .and_then(|addr| addr.address())
+                .map(str::to_string),
+            date: message.date().map(|d| d.to_rfc3339()),
+            attachments: message
+                .attachments()
+                .filter(|part| is_pdf_part(part))
+                .enumerate()
+                .map(|(idx, part)| {
+                    part.attachment_name()
+                        .map(str::to_string)
+                        .unwrap_or_else(|| format!("anhang-{}.pdf", idx + 1))
+                })
+                .collect(),
+        })
+    }
+
+    /// Job name: the subject, falling back to the sender.
+    pub fn job_name(&self, mailbox: &str) -> String {
+        match (self.subject.as_deref(), self.sender.as_deref()) {
+            (Some(subject), _) if !subject.trim().is_empty() => subject.trim().to_string(),
+            (_, Some(sender)) => format!("E-Mail von {sender}"),
+            _ => format!("E-Mail {mailbox}"),
+        }
+    }
+}
+
+fn is_pdf_part(part: &mail_parser::MessagePart<'_>) -> bool {
+    let by_type = part
+        .content_type()
+        .map(|ct| {
+            ct.ctype().eq_ignore_ascii_case("application")
+                && ct
+                    .subtype()
+                    .is_some_and(|sub| sub.eq_ignore_ascii_case("pdf"))
+        })
+        .unwrap_or(false);
+    let by_name = part
+        .attachment_name()
+        .is_some_and(|name| name.to_ascii_lowercase().ends_with(".pdf"));
+    by_type || by_name
+}
+
+/// Writes the cover page (optional) and the PDF attachments of `raw` into `dest`.
+pub fn extract_pdfs(raw: &[u8], include_body: bool, dest: &Path) -> Result<Vec<PathBuf>> {
+    let message = MessageParser::default()
+        .parse(raw)
+        .ok_or_else(|| anyhow!("unparseable mail"))?;
+    let info = MailInfo::parse(raw)?;
+    let mut files = Vec::new();
+
+    if include_body {
+        let mut lines = vec![
+            "E-Mail-Eingang".to_string(),
+            String::new(),
+            format!("Von: {}", info.sender.as_deref().unwrap_or("-")),
+            format!("Datum: {}", info.date.as_deref().unwrap_or("-")),
+            format!("Betreff: {}", info.subject.as_deref().unwrap_or("-")),
+            format!("Anhänge: {}", info.attachments.join(", ")),
+            String::new(),
+        ];
+        if let Some(body) = message.body_text(0) {
+            lines.extend(body.lines().map(str::to_string));
+        }
+        let cover = dest.join("000-email.pdf");
+        write_text_pdf(&lines, &cover)?;
+        files.push(cover);
+    }
+
+    for (idx, (part, name)) in message
+        .attachments()
+        .filter(|part| is_pdf_part(part))
+        .zip(info.attachments.iter())
+        .enumerate()
+    {
+        let path = dest.join(format!("{:03}-{}", idx + 1, sanitize_filename(name)));
+        std::fs::write(&path, part.contents())
+            .with_context(|| format!("write attachment {name}"))?;
+        files.push(path);
+    }
+    Ok(files)
+}
+
+/// Opens IMAP sessions for stored mailboxes.
+#[derive(Clone)]
+pub struct ImapConnector {
+    master_key: Option<MasterKey>,
+    timeout: Duration,
+    tls: TlsConnector,
+}
+
+impl MasterKeyed for ImapConnector {
+    fn master_key(&self) -> Option<&MasterKey> {
+        self.master_key.as_ref()
+    }
+}
+
+impl ImapConnector {
+    pub fn new(master_key: Option<MasterKey>, timeout: Duration) -> Result<Self> {
+        let mut roots = RootCertStore::empty();
+        roots.extend(webpki_roots::TLS_SERVER_ROOTS.iter().cloned());
+        let config =
+            ClientConfig::builder_with_provider(Arc::new(rustls::crypto::ring::default_provider()))
+                .with_safe_default_protocol_versions()?
+                .with_root_certificates(roots)
+                .with_no_client_auth();
+        Ok(Self {
+            master_key,
+            timeout,
+            tls: TlsConnector::from(Arc::new(config)),
+        })
+    }
+
+    /// Connects, logs in and selects the mailbox folder.
+    pub async fn open(&self, mailbox: &ImapMailbox) -> Result<ImapSession> {
+        let master = self.require_master_key()?;
+        let password = mailbox
+            .credentials
+            .password
+            .as_ref()
+            .map(|p| master.open(p))
+            .transpose()?
+            .ok_or_else(|| anyhow!("mailbox has no password"))?;
+
+        let tcp = timeout(
+            self.timeout,
+            TcpStream::connect((mailbox.host.as_str(), mailbox.port as u16)),
+        )
+        .await
+        .context("imap connect timed out")?
+        .with_context(|| format!("connect {}:{}", mailbox.host, mailbox.port))?;
+        let server_name = ServerName::try_from(mailbox.host.clone())
+            .with_context(|| format!("invalid host {}", mailbox.host))?;
+        let stream = timeout(self.timeout, self.tls.connect(server_name, tcp))
+            .await
+            .context("imap tls handshake timed out")?
+            .context("imap tls handshake")?;
+
+        let mut session = ImapSession {
+            stream: BufReader::new(stream),
+            timeout: self.timeout,
+            next_tag: 0,
+            uid_validity: 0,
+        };
+        let greeting = session.read_line().await?;
+        if !greeting.text.starts_with("* OK") {
+            bail!("unexpected imap greeting: {}", greeting.text);
+        }
+        session
+            .command(&format!(
+                "LOGIN {} {}",
+                quote(&mailbox.username),
+                quote(&password)
+            ))
+            .await
+            .context("imap login")?;
+        let lines = session
+            .command(&format!("SELECT {}", quote(&mailbox.folder)))
+            .await
+            .with_context(|| format!("select {}", mailbox.folder))?;
+        session.uid_validity = lines
+            .iter()
+            .find_map(|l| parse_uid_validity(&l.text))
+            .ok_or_else(|| anyhow!("server did not report UIDVALIDITY"))?;
+        Ok(session)
+    }
+}
+
+#[derive(Debug, Default)]
+struct ImapLine {
+    text: String,
+    literals: Vec<Vec<u8>>,
+}
+
+pub struct ImapSession {
+    stream: BufReader<TlsStream<TcpStream>>,
+    timeout: Duration,
+    next_tag: u32,
+    pub uid_validity: u32,
+}
+
+impl ImapSession {
+    /// UIDs of mails in the selected folder without `keyword`, oldest first.
+    pub async fn search_without(&mut self, keyword: &str) -> Result<Vec<u32>> {
+        let lines = self
+            .command(&format!("UID SEARCH UNKEYWORD {keyword} NOT DELETED"))
+            .await?;
+        let mut uids: Vec<u32> = lines
+            .iter()
+            .filter_map(|l| l.text.strip_prefix("* SEARCH"))
+            .flat_map(|rest| rest.split_whitespace().filter_map(|v| v.parse().ok()))
+            .collect();
+        uids.sort_unstable();
+        Ok(uids)
+    }
+
+    /// Raw RFC 822 message without setting `\Seen`.
+    pub async fn fetch(&mut self, uid: u32) -> Result<Vec<u8>> {
+        let lines = self
+            .command(&format!("UID FETCH {uid} (UID BODY.PEEK[])"))
+            .await?;
+        lines
+            .into_iter()
+            .find_map(|l| l.literals.into_iter().next())
+            .ok_or_else(|| anyhow!("mail uid {uid} not found"))
+    }
+
+    pub async fn add_keywords(&mut self, uid: u32, keywords: &[&str]) -> Result<()> {
+        self.command(&format!(
+            "UID STORE {uid} +FLAGS.SILENT ({})",
+            keywords.join(" ")
+        ))
+        .await?;
+        Ok(())
+    }
+
+    pub async fn logout(mut self) {
+        let _ = self.command("LOGOUT").await;
+    }
+
+    async fn command(&mut self, command: &str) -> Result<Vec<ImapLine>> {
+        self.next_tag += 1;
+        let tag = format!("R{}", self.next_tag);
+        let stream = self.stream.get_mut();
+        timeout(self.timeout, async {
+            stream
+                .write_all(format!("{tag} {command}\r\n").as_bytes())
+                .await?;
+            stream.flush().await
+        })
+        .await
+        .context("imap write timed out")??;
+
+        let mut lines = Vec::new();
+        loop {
+            let line = self.read_line().await?;
+            if let Some(status) = line.text.strip_prefix(&format!("{tag} ")) {
+                if status.starts_with("OK") {
+                    return Ok(lines);
+                }
+                bail!("imap command failed: {status}");
+            }
+            lines.push(line);
+        }
+    }
+
+    /// Reads one response line including any `{n}` literals it announces.
+    async fn read_line(&mut self) -> Result<ImapLine> {
+        let mut line = ImapLine::default();
+        loop {
+            let mut buf = Vec::new();
+            let read = timeout(self.timeout, self.stream.read_until(b'\n', &mut buf))
+                .await
+                .context("imap read timed out")??;
+            if read == 0 {
+                bail!("imap connection closed");
+            }
+            let text = String::from_utf8_lossy(&buf);
+            let text = text.trim_end_matches(['\r', '\n']);
+            line.text.push_str(text);
+            let Some(len) = literal_len(text) else {
+                return Ok(line);
+            };
+            let mut literal = vec![0u8; len];
+            timeout(self.timeout, self.stream.read_exact(&mut literal))
+                .await
+                .context("imap read timed out")??;
+            line.literals.push(literal);
+        }
+    }
+}
+
+fn quote(value: &str) -> String {
+    format!("\"{}\"", value.replace('\\', "\\\\").replace('"', "\\\""))
+}
+
+fn literal_len(text: &str) -> Option<usize> {
+    let inner = text.strip_suffix('}')?;
+    let start = inner.rfind('{')?;
+    inner[start + 1..].trim_end_matches('+').parse().ok()
+}
+
+fn parse_uid_validity(text: &str) -> Option<u32> {
+    let rest = &text[text.find("[UIDVALIDITY ")? + "[UIDVALIDITY ".len()..];
+    rest.split(']').next()?.trim().parse().ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const MAIL: &[u8] = b"From: Kunde <kunde@example.org>\r\n\
+Subject: Schaden 4711\r\n\
+Message-ID: <abc@example.org>\r\n\
+MIME-Version: 1.0\r\n\
+Content-Type: multipart/mixed; boundary=\"b\"\r\n\
+\r\n\
+--b\r\n\
+Content-Type: text/plain; charset=utf-8\r\n\
+\r\n\
+Anbei die Unterlagen.\r\n\
+--b\r\n\
+Content-Type: application/pdf; name=\"rechnung.pdf\"\r\n\
+Content-Disposition: attachment; filename=\"rechnung.pdf\"\r\n\
+Content-Transfer-Encoding: base64\r\n\
+\r\n\
+JVBERi0xLjQK\r\n\
+--b\r\n\
+Content-Type: image/png; name=\"logo.png\"\r\n\
+Content-Disposition: attachment; filename=\"logo.png\"\r\n\
+\r\n\
+png\r\n\
+--b--\r\n";
+
+    #[test]
+    fn parses_mail_and_protocol_fragments() {
+        let info = MailInfo::parse(MAIL).unwrap();
+        assert_eq!(info.subject.as_deref(), Some("Schaden 4711"));
+        assert_eq!(info.sender.as_deref(), Some("kunde@example.org"));
+        assert_eq!(info.message_id.as_deref(), Some("abc@example.org"));
+        assert_eq!(info.attachments, vec!["rechnung.pdf".to_string()]);
+        assert_eq!(info.job_name("Postfach"), "Schaden 4711");
+
+        let dir = tempfile::tempdir().unwrap();
+        let files = extract_pdfs(MAIL, true, dir.path()).unwrap();
+        assert_eq!(files.len(), 2);
+        assert_eq!(std::fs::read(&files[1]).unwrap(), b"%PDF-1.4\n");
+
+        assert_eq!(literal_len("* 1 FETCH (UID 7 BODY[] {5120}"), Some(5120));
+        assert_eq!(literal_len("* OK done"), None);
+        assert_eq!(
+            parse_uid_validity("* OK [UIDVALIDITY 3857529045] UIDs valid"),
+            Some(3857529045)
+        );
+        assert_eq!(quote(r#"pa"ss\"#), r#""pa\"ss\\""#);
+        assert!(is_valid_keyword("$RegressIngested") && !is_valid_keyword("a b"));
+    }
+}
@@ -0,0 +1,251 @@
+//! Operator inbox: everything that waits for a human, in one list.
+//!
+//! `GET /inbox` combines quarantined files, failed ingest jobs, finished runs
+//! labelled `review` and ingested jobs without a pipeline. Items are ordered
+//! by category (in the order of [`InboxCategory`]) and then oldest first; the
+//! counts cover all open items, independent of the returned page.
+
+use std::collections::BTreeMap;
+
+use anyhow::Result;
+use chrono::{DateTime, Utc};
+use deadpool_postgres::Pool;
+use serde::Serialize;
+use tokio_postgres::Row;
+use uuid::Uuid;
+
+/// Maximum number of items returned by `GET /inbox`.
+pub const MAX_ITEMS: i64 = 500;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize)]
+#[serde(rename_all = "snake_case")]
+/// Kind of open item; variants are declared in inbox priority order.
+pub enum InboxCategory {
+    /// File rejected by the scan; release or delete it.
+    Quarantined,
+    /// Ingest job failed; retry or cancel it.
+    FailedUpload,
+    /// Finished run whose result label is `review`.
+    Review,
+    /// Ingested job without pipeline; assign one and start the run.
+    NeedsPipeline,
+}
+
+impl InboxCategory {
+    pub const ALL: [InboxCategory; 4] = [
+        InboxCategory::Quarantined,
+        InboxCategory::FailedUpload,
+        InboxCategory::Review,
+        InboxCategory::NeedsPipeline,
+    ];
+
+    /// Query returning `id, title, detail, tenant_id, job_id, pdf_id, job_label,
+    /// external_ref, since, total` for the open items of this category.
+    fn query(self) -> &'static str {
+        match self {
+            InboxCategory::Quarantined => {
+                "SELECT id::text AS id, file_name AS title,
+                        reason || COALESCE(': ' || detail, '') AS detail,
+                        tenant_id, job_id, NULL::int AS pdf_id,
+                        NULL::text AS job_label, NULL::text AS external_ref,
+                        created_at AS since, count(*) OVER () AS total
+                 FROM scan_quarantine
+                 WHERE status = 'quarantined'
+                   AND ($1::uuid IS NULL OR tenant_id = $1)
+                 ORDER BY created_at
+                 LIMIT $2"
+            }
+            InboxCategory::FailedUpload => {
+                "SELECT id::text AS id, folder_name AS title, message AS detail,
+                        tenant_id, id AS job_id, pdf_id, job_label, external_ref,
+                        updated_at AS since, count(*) OVER () AS total
+                 FROM sharepoint_jobs
+                 WHERE status = 'failed'
+                   AND ($1::uuid IS NULL OR tenant_id = $1)
+                 ORDER BY updated_at
+                 LIMIT $2"
+            }
+            InboxCategory::Review => {
+                "WITH latest AS (
+                     SELECT DISTINCT ON (h.pdf_id, h.pipeline_id)
+                            h.id, h.pdf_id, h.label, h.job_label, h.external_ref,
+                            COALESCE(h.finished_at, h.timestamp) AS since,
+                            p.name AS pipeline_name
+                     FROM analysis_history h
+                     LEFT JOIN pipelines p ON p.id = h.pipeline_id
+                     WHERE h.status = 'completed'
+                     ORDER BY h.pdf_id, h.pipeline_id, h.id DESC
+                 )
+                 SELECT l.id::text AS id,
+                        COALESCE(l.job_label, 'PDF ' || l.pdf_id) AS title,
+                        l.pipeline_name AS detail,
+                        u.tenant_id, NULL::uuid AS job_id, l.pdf_id,
+                        l.job_label, l.external_ref,
+                        l.since, count(*) OVER () AS total
+                 FROM latest l
+                 LEFT JOIN LATERAL (
+                     SELECT tenant_id FROM uploads
+                     WHERE pdf_id = l.pdf_id ORDER BY id DESC LIMIT 1
+                 ) u ON TRUE
+                 WHERE l.label = 'review'
+                   AND ($1::uuid IS NULL OR u.tenant_id = $1)
+                 ORDER BY l.since NULLS LAST
+                 LIMIT $2"
+            }
+            InboxCategory::NeedsPipeline => {
+                "SELECT id::text AS id, folder_name AS title, NULL::text AS detail,
+                        tenant_id, id AS job_id, pdf_id, job_label, external_ref,
+                        updated_at AS since, count(*) OVER () AS total
+                 FROM sharepoint_jobs sp
+                 WHERE status = 'succeeded'
+                   AND upload_id IS NOT NULL
+                   AND pipeline_id IS NULL
+                   AND pipeline_run_id IS NULL
+                   AND NOT EXISTS (SELECT 1 FROM pipeline_runs pr WHERE pr.pdf_id = sp.pdf_id)
+                   AND ($1::uuid IS NULL OR tenant_id = $1)
+                 ORDER BY updated_at
+                 LIMIT $2"
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct InboxItem {
+    pub category: InboxCategory,
+    /// Id within the category: quarantine entry, job or history entry.
+    pub id: String,
+    pub title: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub detail: Option<String>,
+    pub tenant_id: Option<Uuid>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub job_id: Option<Uuid>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub pdf_id: Option<i32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub job_label: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub external_ref: Option<String>,
+    /// Time the item started waiting.
+    pub since: Option<DateTime<Utc>>,
+}
+
+impl InboxItem {
+    fn from_row(category: InboxCategory, row: &Row) -> Self {
+        Self {
+            category,
+            id: row.get("id"),
+            title: row.get::<_, Option<String>>("title").unwrap_or_default(),
+            detail: row.get("detail"),
+            tenant_id: row.get("tenant_id"),
+            job_id: row.get("job_id"),
+            pdf_id: row.get("pdf_id"),
+            job_label: row.get("job_label"),
+            external_ref: row.get("external_ref"),
+            since: row.get("since"),
+        }
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub struct Inbox {
+    /// Open items per category, including those beyond `limit`.
+    pub counts: BTreeMap<InboxCategory, i64>,
+    pub total: i64,
+    pub items: Vec<InboxItem>,
+}
+
+/// Loads the open items of all categories, optionally for one tenant.
+pub async fn load(pool: &Pool, tenant_id: Option<Uuid>, limit: i64) -> Result<Inbox> {
+    let limit = limit.clamp(1, MAX_ITEMS);
+    let client = pool.get().await?;
+    let mut counts = BTreeMap::new();
+    let mut items = Vec::new();
+    for category in InboxCategory::ALL {
+        let rows = client
+            .query(category.query(), &[&tenant_id, &limit])
+            .await?;
+        let total = rows.first().map(|r| r.get::<_, i64>("total")).unwrap_or(0);
+        counts.insert(category, total);
+        items.extend(rows.iter().map(|row| InboxItem::from_row(category, row)));
+    }
+    prioritize(&mut items);
+    items.truncate(limit as usize);
+    Ok(Inbox {
+        total: counts.values().sum(),
+        counts,
+        items,
+    })
+}
+
+/// Orders items by category rank, then by waiting time (oldest first).
+fn prioritize(items: &mut [InboxItem]) {
+    items.sort_by(|a, b| {
+        a.category
+            .cmp(&b.category)
+            .then_with(|| match (a.since, b.since) {
+                (Some(a), Some(b)) => a.cmp(&b),
+                (Some(_), None) => std::cmp::Ordering::Less,
+                (None, Some(_)) => std::cmp::Ordering::Greater,
+                (None, None) => std::cmp::Ordering::Equal,
+            })
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::Duration;
+
+    fn item(category: InboxCategory, id: &str, age_hours: Option<i64>) -> InboxItem {
+        InboxItem {
+            category,
+            id: id.to_string(),
+            title: id.to_string(),
+            detail: None,
+            tenant_id: None,
+            job_id: None,
+            pdf_id: None,
+            job_label: None,
+            external_ref: None,
+            since: age_hours.map(|h| Utc::now() - Duration::hours(h)),
+        }
+    }
+
+    #[test]
+    fn prioritize_orders_by_category_then_age() {
+        let mut items = vec![
+            item(InboxCategory::NeedsPipeline, "job-old", Some(48)),
+            item(InboxCategory::Review, "review-undated", None),
+            item(InboxCategory::Review, "review-new", Some(1)),
+            item(InboxCategory::Quarantined, "file", Some(2)),
+            item(InboxCategory::Review, "review-old", Some(10)),
+            item(InboxCategory::FailedUpload, "failed", Some(5)),
+        ];
+        prioritize(&mut items);
+        let ids: Vec<&str> = items.iter().map(|i| i.id.as_str()).collect();
+        assert_eq!(
+            ids,
+            vec![
+                "file",
+                "failed",
+                "review-old",
+                "review-new",
+                "review-undated",
+                "job-old"
+            ]
+        );
+    }
+
+    #[test]
+    fn counts_serialize_with_category_names() {
+        let counts = BTreeMap::from([
+            (InboxCategory::NeedsPipeline, 2),
+            (InboxCategory::Quarantined, 1),
+        ]);
+        let json = serde_json::to_value(&counts).unwrap();
+        assert_eq!(json["needs_pipeline"], 2);
+        assert_eq!(json["quarantined"], 1);
+    }
+}
@@ -0,0 +1,181 @@
+//! Files of a SharePoint folder that were already ingested.
+//!
+//! Every successful SharePoint upload stores the merged files with their
+//! eTag in `sharepoint_folder_inventory`. A folder rule with `reingest` lets
+//! the automation poller (and the schedule task) compare the current listing
+//! with it: a PDF that is not in the inventory or whose eTag changed starts a
+//! new job of the folder. `new_files` merges only those files
+//! ([`JobSource::SharePointFiles`](crate::job::JobSource)), `all_files` the
+//! whole folder again. Folders ingested before the inventory existed fall back
+//! to the files of their latest succeeded job, without eTags.
+//!
+//! The poller only looks at folders the Graph delta reports as changed, so the
+//! extra listing costs one request per changed folder with an ingested job.
+
+use std::collections::HashMap;
+
+use anyhow::Result;
+use deadpool_postgres::Pool;
+use serde::{Deserialize, Serialize};
+use tokio_postgres::Client;
+use uuid::Uuid;
+
+use crate::msgraph::GraphFile;
+
+/// Creates `sharepoint_folder_inventory` (see migration 0058).
+pub const INVENTORY_SCHEMA_SQL: &str = r#"
+CREATE TABLE IF NOT EXISTS sharepoint_folder_inventory (
+    folder_id TEXT NOT NULL,
+    file_id TEXT NOT NULL,
+    file_name TEXT NOT NULL,
+    etag TEXT,
+    job_id UUID NOT NULL,
+    ingested_at TIMESTAMPTZ NOT NULL DEFAULT now(),
+    PRIMARY KEY (folder_id, file_id)
+);
+"#;
+
+/// What a re-ingest of a folder merges.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ReingestMode {
+    /// Only the new or changed files.
+    NewFiles,
+    /// All PDFs of the folder.
+    AllFiles,
+}
+
+impl ReingestMode {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ReingestMode::NewFiles => "new_files",
+            ReingestMode::AllFiles => "all_files",
+        }
+    }
+
+    /// Reads the stored column; unknown values disable the re-ingest.
+    pub fn from_column(value: Option<&str>) -> Option<Self> {
+        match value? {
+            "new_files" => Some(ReingestMode::NewFiles),
+            "all_files" => Some(ReingestMode::AllFiles),
+            _ => None,
+        }
+    }
+}
+
+/// Ingested files of a folder: file id to eTag.
+pub type Inventory = HashMap<String, Option<String>>;
+
+/// Listed files that are missing in `inventory` or whose eTag changed. A
+/// missing eTag on either side never counts as change.
+pub fn changed_files(listed: &[GraphFile], inventory: &Inventory) -> Vec<GraphFile> {
+    listed
+        .iter()
+        .filter(|file| match inventory.get(&file.id) {
+            None => true,
+            Some(Some(known)) => file.etag.as_ref().is_some_and(|etag| etag != known),
+            Some(None) => false,
+        })
+        .cloned()
+        .collect()
+}
+
+/// Inventory of `folder_id`; without one the files of the latest succeeded
+/// job.
+pub async fn load(client: &Client, folder_id: &str) -> Result<Inventory> {
+    let rows = client
+        .query(
+            "SELECT file_id, etag FROM sharepoint_folder_inventory WHERE folder_id = $1",
+            &[&folder_id],
+        )
+        .await?;
+    if !rows.is_empty() {
+        return Ok(rows
+            .iter()
+            .map(|row| (row.get("file_id"), row.get("etag")))
+            .collect());
+    }
+    let rows = client
+        .query(
+            "SELECT f.file_id FROM sharepoint_job_files f
+             WHERE f.status IN ('downloaded', 'skipped')
+               AND f.job_id = (SELECT id FROM sharepoint_jobs
+                               WHERE folder_id = $1 AND status = 'succeeded'
+                               ORDER BY created_at DESC LIMIT 1)",
+            &[&folder_id],
+        )
+        .await?;
+    Ok(rows.iter().map(|row| (row.get("file_id"), None)).collect())
+}
+
+/// Adds the ingested `files` of job `job_id` to the folder's inventory.
+pub async fn record(pool: &Pool, folder_id: &str, job_id: Uuid, files: &[GraphFile]) -> Result<()> {
+    let ids: Vec<&str> = files.iter().map(|f| f.id.as_str()).collect();
+    let names: Vec<&str> = files.iter().map(|f| f.name.as_str()).collect();
+    let etags: Vec<Option<&str>> = files.iter().map(|f| f.etag.as_deref()).collect();
+    let client = pool.get().await?;
+    client
+        .execute(
+            "INSERT INTO sharepoint_folder_inventory (folder_id, file_id, file_name, etag, job_id)
+             SELECT $1, f.id, f.name, f.etag, $2
+             FROM UNNEST($3::text[], $4::text[], $5::text[]) AS f(id, name, etag)
+             ON CONFLICT (folder_id, file_id) DO UPDATE
+             SET file_name = EXCLUDED.file_name, etag = EXCLUDED.etag,
+                 job_id = EXCLUDED.job_id, ingested_at = now()",
+            &[&folder_id, &job_id, &ids, &names, &etags],
+        )
+        .await?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn file(id: &str, etag: Option<&str>) -> GraphFile {
+        GraphFile {
+            id: id.into(),
+            name: format!("{id}.pdf"),
+            etag: etag.map(str::to_string),
+            size: None,
+        }
+    }
+
+    #[test]
+    fn new_and_changed_files_count() {
+        let inventory = Inventory::from([
+            ("a".to_string(), Some("\"{A},1\"".to_string())),
+            ("b".to_string(), Some("\"{B},1\"".to_string())),
+        ]);
+        let listed = [
+            file("a", Some("\"{A},1\"")),
+            file("b", Some("\"{B},2\"")),
+            file("c", Some("\"{C},1\"")),
+        ];
+        let ids: Vec<String> = changed_files(&listed, &inventory)
+            .into_iter()
+            .map(|f| f.id)
+            .collect();
+        assert_eq!(ids, ["b", "c"]);
+        assert!(changed_files(&listed[..1], &inventory).is_empty());
+    }
+
+    #[test]
+    fn missing_etags_only_compare_ids() {
+        // Inventar aus einem Job vor der Einführung der eTags
+        let inventory = Inventory::from([("a".to_string(), None)]);
+        assert!(changed_files(&[file("a", Some("\"{A},3\""))], &inventory).is_empty());
+        let known = Inventory::from([("a".to_string(), Some("\"{A},1\"".to_string()))]);
+        assert!(changed_files(&[file("a", None)], &known).is_empty());
+        assert_eq!(changed_files(&[file("d", None)], &known).len(), 1);
+    }
+
+    #[test]
+    fn mode_round_trips_through_the_column() {
+        for mode in [ReingestMode::NewFiles, ReingestMode::AllFiles] {
+            assert_eq!(ReingestMode::from_column(Some(mode.as_str())), Some(mode));
+        }
+        assert_eq!(ReingestMode::from_column(None), None);
+        assert_eq!(ReingestMode::from_column(Some("sometimes")), None);
+    }
+}
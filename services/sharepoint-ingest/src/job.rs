@@ -1,6 +1,6 @@
 //! Background job definitions that coordinate SharePoint downloads and uploads.
 
-use std::{collections::HashMap, str::FromStr, sync::Arc};
+use std::{collections::HashMap, path::PathBuf, str::FromStr, sync::Arc};
 
 use anyhow::anyhow;
 use chrono::{DateTime, Utc};
@@ -8,13 +8,19 @@ use deadpool_postgres::Pool;
 use parking_lot::{Mutex, RwLock};
 use serde::{Deserialize, Serialize};
 use serde_json::Value;
+use shared::dto::RunPriority;
+use shared::timeline::{self, TimelineEvent};
 use tokio::{
-    sync::{mpsc, watch},
+    sync::{broadcast, mpsc, watch},
     task::JoinHandle,
 };
 use tracing::warn;
 use uuid::Uuid;
 
+use crate::connector::ConnectorKind;
+use crate::job_files::FileStatus;
+use crate::msgraph::GraphFile;
+use crate::scheduler::{JobScheduler, JobSlot};
 use crate::upload_adapter::UploadResult;
 
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
@@ -39,6 +45,13 @@ impl JobStatus {
             JobStatus::Canceled => "canceled",
         }
     }
+
+    pub fn is_finished(&self) -> bool {
+        matches!(
+            self,
+            JobStatus::Succeeded | JobStatus::Failed | JobStatus::Canceled
+        )
+    }
 }
 
 impl FromStr for JobStatus {
@@ -94,6 +107,105 @@ impl Default for JobOrder {
     }
 }
 
+/// Where a job fetches its PDFs from.
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum JobSource {
+    /// SharePoint folder `folder_id` via Microsoft Graph.
+    #[default]
+    SharePoint,
+    /// Only these files of the SharePoint folder, e.g. those added since the
+    /// last ingest (see `inventory.rs`).
+    #[serde(rename = "sharepoint_files")]
+    SharePointFiles { file_ids: Vec<String> },
+    /// Remote paths of an SFTP source, in merge order.
+    Sftp { source_id: Uuid, files: Vec<String> },
+    /// One mail of an IMAP mailbox, identified by UID.
+    Imap {
+        mailbox_id: Uuid,
+        uid_validity: u32,
+        uid: u32,
+    },
+    /// Folder `folder_id` of the OneDrive or local-directory connector (see
+    /// `connector.rs`); `file_ids` limits the job like `SharePointFiles`.
+    Drive {
+        connector: ConnectorKind,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        file_ids: Option<Vec<String>>,
+    },
+}
+
+impl JobSource {
+    /// Job of a drive folder; SharePoint keeps the variants of older jobs.
+    pub fn folder(connector: ConnectorKind, file_ids: Option<Vec<String>>) -> Self {
+        match (connector, file_ids) {
+            (ConnectorKind::SharePoint, None) => JobSource::SharePoint,
+            (ConnectorKind::SharePoint, Some(file_ids)) => JobSource::SharePointFiles { file_ids },
+            (connector, file_ids) => JobSource::Drive {
+                connector,
+                file_ids,
+            },
+        }
+    }
+
+    /// Connector and file selection of a drive folder job.
+    pub fn folder_connector(&self) -> Option<(ConnectorKind, Option<&[String]>)> {
+        match self {
+            JobSource::SharePoint => Some((ConnectorKind::SharePoint, None)),
+            JobSource::SharePointFiles { file_ids } => {
+                Some((ConnectorKind::SharePoint, Some(file_ids)))
+            }
+            JobSource::Drive {
+                connector,
+                file_ids,
+            } => Some((*connector, file_ids.as_deref())),
+            JobSource::Sftp { .. } | JobSource::Imap { .. } => None,
+        }
+    }
+}
+
+/// Human label and external reference (e.g. case number) of a job.
+///
+/// Both values travel with the upload into `pipeline_runs` and `analysis_history`.
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq, Eq)]
+pub struct JobReference {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub job_label: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub external_ref: Option<String>,
+}
+
+impl JobReference {
+    /// Builds a reference, dropping blank values.
+    pub fn new(job_label: Option<String>, external_ref: Option<String>) -> Self {
+        let clean = |value: Option<String>| {
+            value
+                .map(|v| v.trim().to_string())
+                .filter(|v| !v.is_empty())
+        };
+        Self {
+            job_label: clean(job_label),
+            external_ref: clean(external_ref),
+        }
+    }
+
+    /// Listing filter: label as case-insensitive substring, external ref exact.
+    pub fn matches(&self, label: Option<&str>, external_ref: Option<&str>) -> bool {
+        let label_ok = match label {
+            None => true,
+            Some(needle) => self
+                .job_label
+                .as_deref()
+                .is_some_and(|l| l.to_lowercase().contains(&needle.to_lowercase())),
+        };
+        let ref_ok = match external_ref {
+            None => true,
+            Some(wanted) => self.external_ref.as_deref() == Some(wanted),
+        };
+        label_ok && ref_ok
+    }
+}
+
 #[derive(Debug, Clone)]
 pub struct ManagedJob {
     pub state: Arc<Mutex<JobState>>,
@@ -119,16 +231,107 @@ pub struct JobState {
     pub pdf_id: Option<i32>,
     pub auto_managed: bool,
     pub auto_last_seen_at: Option<DateTime<Utc>>,
+    pub source: JobSource,
+    pub reference: JobReference,
+    /// Scheduling priority, forwarded to the upload.
+    pub priority: RunPriority,
+    /// Progress of an unfinished run, see [`JobCheckpoint`].
+    pub checkpoint: Option<JobCheckpoint>,
     pub created_at: DateTime<Utc>,
     pub updated_at: DateTime<Utc>,
 }
 
+/// Progress of a running job, kept in `sharepoint_jobs.output` next to the
+/// upload result so a restarted service resumes the job after the last
+/// completed step instead of starting over (see `steps.rs`).
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct JobCheckpoint {
+    /// Step names of the plan the progress belongs to.
+    pub plan: Vec<String>,
+    /// Number of completed steps.
+    pub completed: usize,
+    /// Job directory holding the files below.
+    pub work_dir: PathBuf,
+    #[serde(default)]
+    pub remote_files: Vec<GraphFile>,
+    /// Local PDFs in merge order; during `download` the ones done so far.
+    #[serde(default)]
+    pub files: Vec<CheckpointFile>,
+    /// Raw mail of an IMAP job, stored in the job directory.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub raw_mail: Option<PathBuf>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub merged: Option<PathBuf>,
+    /// How often the job was resumed after a restart.
+    #[serde(default)]
+    pub resumes: u32,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CheckpointFile {
+    pub path: PathBuf,
+    /// Position in `remote_files` (SharePoint jobs).
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub position: Option<usize>,
+}
+
+/// Content of `sharepoint_jobs.output`; rows without checkpoint hold the
+/// plain upload result.
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct StoredOutput {
+    #[serde(flatten)]
+    upload: Option<UploadResult>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    checkpoint: Option<JobCheckpoint>,
+}
+
+/// Restarts after which a job that was running each time fails instead of
+/// being resumed again.
+pub const MAX_RESUMES: u32 = 3;
+
+/// What a restarted service does with a persisted job.
+#[derive(Debug, PartialEq, Eq)]
+pub enum Restart {
+    Finished,
+    Resume { paused: bool },
+    GiveUp,
+}
+
 impl JobState {
+    /// Ends the checkpoint together with the job.
     pub fn set_status(&mut self, status: JobStatus) {
+        if status.is_finished() {
+            self.checkpoint = None;
+        }
         self.status = status;
         self.updated_at = Utc::now();
     }
 
+    /// Prepares an unfinished job after a restart: queued and running jobs
+    /// are queued again, paused ones stay paused. A job interrupted while
+    /// running [`MAX_RESUMES`] times fails.
+    pub fn prepare_restart(&mut self) -> Restart {
+        match self.status {
+            JobStatus::Running => {
+                let resumes = self.checkpoint.as_ref().map_or(0, |c| c.resumes);
+                if resumes >= MAX_RESUMES {
+                    self.set_status(JobStatus::Failed);
+                    self.set_message("Verarbeitung beim Neustart wiederholt unterbrochen");
+                    return Restart::GiveUp;
+                }
+                self.checkpoint
+                    .get_or_insert_with(JobCheckpoint::default)
+                    .resumes = resumes + 1;
+                self.set_status(JobStatus::Queued);
+                self.set_message("nach Neustart wieder eingereiht");
+                Restart::Resume { paused: false }
+            }
+            JobStatus::Queued => Restart::Resume { paused: false },
+            JobStatus::Paused => Restart::Resume { paused: true },
+            _ => Restart::Finished,
+        }
+    }
+
     pub fn set_progress(&mut self, progress: f32) {
         self.progress = progress.clamp(0.0, 1.0);
         self.updated_at = Utc::now();
@@ -157,6 +360,52 @@ pub struct JobRegistry {
 struct JobRegistryInner {
     jobs: RwLock<HashMap<Uuid, ManagedJob>>,
     handles: Mutex<HashMap<Uuid, JoinHandle<()>>>,
+    scheduler: Arc<JobScheduler>,
+    events: broadcast::Sender<JobEvent>,
+}
+
+/// Updates a subscriber that falls behind by more than this many events
+/// misses; `GET /jobs/events` then sends the full list again.
+const EVENT_BUFFER: usize = 256;
+
+/// Change streamed by `GET /jobs/events`.
+#[derive(Debug, Clone, Serialize)]
+#[serde(untagged)]
+pub enum JobEvent {
+    /// New state of a job (created or updated).
+    Job(Box<JobSummary>),
+    /// A listed file of a SharePoint job was downloaded, failed or skipped.
+    File(JobFileEvent),
+    /// The job was deleted or purged.
+    Removed { id: Uuid },
+}
+
+impl JobEvent {
+    /// SSE event name.
+    pub fn name(&self) -> &'static str {
+        match self {
+            JobEvent::Job(_) => "job",
+            JobEvent::File(_) => "file",
+            JobEvent::Removed { .. } => "removed",
+        }
+    }
+
+    /// Server-Sent-Events frame, the event as JSON in `data`.
+    pub fn sse_frame(&self) -> serde_json::Result<String> {
+        let data = serde_json::to_string(self)?;
+        Ok(format!("event: {}\ndata: {data}\n\n", self.name()))
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct JobFileEvent {
+    pub job_id: Uuid,
+    /// Position in merge order, starting at 0 (as in `GET /jobs/{id}/files`).
+    pub position: usize,
+    pub file_id: String,
+    pub file_name: String,
+    pub status: &'static str,
+    pub error: Option<String>,
 }
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
@@ -185,16 +434,24 @@ pub struct JobSummary {
     pub pdf_id: Option<i32>,
     pub auto_managed: bool,
     pub auto_last_seen_at: Option<DateTime<Utc>>,
+    pub source: JobSource,
+    #[serde(flatten)]
+    pub reference: JobReference,
+    pub priority: RunPriority,
     pub created_at: DateTime<Utc>,
     pub updated_at: DateTime<Utc>,
 }
 
 impl JobRegistry {
-    pub fn new(persistence: Option<JobPersistence>) -> Self {
+    /// Registry whose jobs run at most `max_concurrency` at a time (see
+    /// [`JobRegistry::acquire_slot`]).
+    pub fn new(persistence: Option<JobPersistence>, max_concurrency: usize) -> Self {
         Self {
             inner: Arc::new(JobRegistryInner {
                 jobs: RwLock::new(HashMap::new()),
                 handles: Mutex::new(HashMap::new()),
+                scheduler: JobScheduler::new(max_concurrency),
+                events: broadcast::channel(EVENT_BUFFER).0,
             }),
             persistence,
         }
@@ -210,6 +467,9 @@ impl JobRegistry {
         tenant_id: Option<Uuid>,
         pipeline_id: Option<Uuid>,
         auto_managed: bool,
+        source: JobSource,
+        reference: JobReference,
+        priority: RunPriority,
     ) -> ManagedJob {
         let id = Uuid::new_v4();
         let (tx, _rx) = watch::channel(JobCommand::Run);
@@ -232,6 +492,10 @@ impl JobRegistry {
             pdf_id: None,
             auto_managed,
             auto_last_seen_at: auto_managed.then_some(now),
+            source,
+            reference,
+            priority,
+            checkpoint: None,
             created_at: now,
             updated_at: now,
         };
@@ -260,6 +524,19 @@ impl JobRegistry {
         self.inner.handles.lock().insert(job_id, handle);
     }
 
+    /// Forgets a finished job; `None` for unknown or still active jobs.
+    pub fn remove(&self, id: &Uuid) -> Option<JobState> {
+        let mut jobs = self.inner.jobs.write();
+        if !jobs.get(id)?.state.lock().status.is_finished() {
+            return None;
+        }
+        let job = jobs.remove(id)?;
+        self.inner.handles.lock().remove(id);
+        let state = job.state.lock().clone();
+        let _ = self.inner.events.send(JobEvent::Removed { id: *id });
+        Some(state)
+    }
+
     pub fn list(&self) -> Vec<JobSummary> {
         self.inner
             .jobs
@@ -284,6 +561,42 @@ impl JobRegistry {
         }
     }
 
+    /// Live job and file updates, see [`JobEvent`].
+    pub fn subscribe(&self) -> broadcast::Receiver<JobEvent> {
+        self.inner.events.subscribe()
+    }
+
+    /// Announces the new state of a listed file of job `id`.
+    pub fn file_changed(
+        &self,
+        id: Uuid,
+        position: usize,
+        file: &GraphFile,
+        status: FileStatus,
+        error: Option<&str>,
+    ) {
+        let _ = self.inner.events.send(JobEvent::File(JobFileEvent {
+            job_id: id,
+            position,
+            file_id: file.id.clone(),
+            file_name: file.name.clone(),
+            status: status.as_str(),
+            error: error.map(str::to_string),
+        }));
+    }
+
+    /// Waits until the job may run, by priority and per-tenant fair share
+    /// (see `scheduler.rs`); the job runs while the slot is held. `None` for
+    /// unknown jobs.
+    pub async fn acquire_slot(&self, id: &Uuid) -> Option<JobSlot> {
+        let (priority, tenant_id) = {
+            let job = self.get(id)?;
+            let state = job.state.lock();
+            (state.priority, state.tenant_id)
+        };
+        self.inner.scheduler.acquire(priority, tenant_id).await
+    }
+
     pub fn cancel(&self, id: &Uuid) -> bool {
         if let Some(job) = self.get(id) {
             let _ = job.control_tx.send(JobCommand::Cancel);
@@ -312,6 +625,12 @@ impl JobRegistry {
     }
 
     fn notify(&self, state: JobState) {
+        if self.inner.events.receiver_count() > 0 {
+            let _ = self
+                .inner
+                .events
+                .send(JobEvent::Job(Box::new(summary_of(&state))));
+        }
         if let Some(persistence) = &self.persistence {
             persistence.send(state);
         }
@@ -319,7 +638,10 @@ impl JobRegistry {
 }
 
 pub fn job_summary(job: &ManagedJob) -> JobSummary {
-    let state = job.state.lock();
+    summary_of(&job.state.lock())
+}
+
+fn summary_of(state: &JobState) -> JobSummary {
     JobSummary {
         id: state.id,
         folder_id: state.folder_id.clone(),
@@ -338,6 +660,9 @@ pub fn job_summary(job: &ManagedJob) -> JobSummary {
         pdf_id: state.pdf_id,
         auto_managed: state.auto_managed,
         auto_last_seen_at: state.auto_last_seen_at,
+        source: state.source.clone(),
+        reference: state.reference.clone(),
+        priority: state.priority,
         created_at: state.created_at,
         updated_at: state.updated_at,
     }
@@ -352,10 +677,16 @@ impl JobPersistence {
     pub fn new(store: Arc<JobStore>) -> Self {
         let (tx, mut rx) = mpsc::unbounded_channel();
         tokio::spawn(async move {
+            // Letzter bekannter Status je Job, damit nur Statuswechsel in run_timeline landen
+            let mut last_status: HashMap<Uuid, JobStatus> = HashMap::new();
             while let Some(state) = rx.recv().await {
                 if let Err(err) = store.persist_state(&state).await {
                     warn!(job_id = %state.id, error = %err, "failed to persist job state");
                 }
+                if last_status.get(&state.id) != Some(&state.status) {
+                    store.append_timeline(&state).await;
+                    last_status.insert(state.id, state.status.clone());
+                }
             }
         });
         Self { tx }
@@ -378,9 +709,18 @@ impl JobStore {
 
     pub async fn persist_state(&self, state: &JobState) -> anyhow::Result<()> {
         let client = self.pool.get().await?;
-        let output_json: Option<Value> = match state.output.as_ref() {
-            Some(result) => Some(serde_json::to_value(result)?),
-            None => None,
+        let output_json: Option<Value> = if state.output.is_none() && state.checkpoint.is_none() {
+            None
+        } else {
+            Some(serde_json::to_value(StoredOutput {
+                upload: state.output.clone(),
+                checkpoint: state.checkpoint.clone(),
+            })?)
+        };
+        // SharePoint-Jobs bleiben NULL, damit alte Zeilen und neue gleich aussehen
+        let source_json: Option<Value> = match &state.source {
+            JobSource::SharePoint => None,
+            other => Some(serde_json::to_value(other)?),
         };
         let filenames = state.filenames_override.as_ref();
         let message = state.message.as_deref();
@@ -389,11 +729,13 @@ impl JobStore {
                 "INSERT INTO sharepoint_jobs (
                     id, folder_id, folder_name, status, progress, message, order_key,
                     filenames_override, upload_url, tenant_id, pipeline_id, pipeline_run_id,
-                    upload_id, pdf_id, output, auto_managed, auto_last_seen_at, created_at, updated_at
+                    upload_id, pdf_id, output, auto_managed, auto_last_seen_at, created_at, updated_at,
+                    source, job_label, external_ref, priority
                  ) VALUES (
                     $1, $2, $3, $4, $5, $6, $7,
                     $8, $9, $10, $11, $12,
-                    $13, $14, $15, $16, $17, $18, $19
+                    $13, $14, $15, $16, $17, $18, $19,
+                    $20, $21, $22, $23
                  )
                  ON CONFLICT (id) DO UPDATE SET
                     folder_id = EXCLUDED.folder_id,
@@ -412,6 +754,10 @@ impl JobStore {
                     output = EXCLUDED.output,
                     auto_managed = EXCLUDED.auto_managed,
                     auto_last_seen_at = EXCLUDED.auto_last_seen_at,
+                    source = EXCLUDED.source,
+                    job_label = EXCLUDED.job_label,
+                    external_ref = EXCLUDED.external_ref,
+                    priority = EXCLUDED.priority,
                     updated_at = EXCLUDED.updated_at",
                 &[
                     &state.id,
@@ -433,19 +779,51 @@ impl JobStore {
                     &state.auto_last_seen_at,
                     &state.created_at,
                     &state.updated_at,
+                    &source_json,
+                    &state.reference.job_label,
+                    &state.reference.external_ref,
+                    &state.priority.to_string(),
                 ],
             )
             .await?;
         Ok(())
     }
 
+    /// Records a job status change in the shared `run_timeline` (best effort).
+    pub async fn append_timeline(&self, state: &JobState) {
+        let client = match self.pool.get().await {
+            Ok(client) => client,
+            Err(err) => {
+                warn!(job_id = %state.id, error = %err, "failed to get connection for run_timeline");
+                return;
+            }
+        };
+        let mut event = TimelineEvent::new("sharepoint-ingest", state.status.as_str())
+            .pdf(state.pdf_id)
+            .upload(state.upload_id)
+            .run(state.pipeline_run_id)
+            .pipeline(state.pipeline_id)
+            .details(serde_json::json!({
+                "job_id": state.id,
+                "folder_id": state.folder_id,
+                "folder_name": state.folder_name,
+                "job_label": state.reference.job_label,
+                "external_ref": state.reference.external_ref,
+            }));
+        if let Some(message) = state.message.as_deref() {
+            event = event.message(message);
+        }
+        timeline::record(&client, &event).await;
+    }
+
     pub async fn load_all(&self) -> anyhow::Result<Vec<JobState>> {
         let client = self.pool.get().await?;
         let rows = client
             .query(
                 "SELECT id, folder_id, folder_name, status, progress, message, order_key,
                         filenames_override, upload_url, tenant_id, pipeline_id, pipeline_run_id,
-                        upload_id, pdf_id, output, auto_managed, auto_last_seen_at, created_at, updated_at
+                        upload_id, pdf_id, output, auto_managed, auto_last_seen_at, created_at, updated_at,
+                        source, job_label, external_ref, priority
                  FROM sharepoint_jobs
                  ORDER BY created_at ASC",
                 &[],
@@ -475,16 +853,31 @@ impl JobStore {
             let auto_managed: bool = row.get("auto_managed");
             let auto_last_seen_at: Option<DateTime<Utc>> = row.get("auto_last_seen_at");
             let output_value: Option<Value> = row.get("output");
-            let output = match output_value {
-                Some(value) => match serde_json::from_value::<UploadResult>(value) {
-                    Ok(parsed) => Some(parsed),
-                    Err(err) => {
-                        warn!(%err, "failed to parse upload_result from sharepoint_jobs");
-                        None
-                    }
-                },
-                None => None,
+            let stored = match output_value {
+                Some(value) => {
+                    serde_json::from_value::<StoredOutput>(value).unwrap_or_else(|err| {
+                        warn!(%err, "failed to parse output from sharepoint_jobs");
+                        StoredOutput::default()
+                    })
+                }
+                None => StoredOutput::default(),
+            };
+            let source = match row.get::<_, Option<Value>>("source") {
+                Some(value) => serde_json::from_value::<JobSource>(value).unwrap_or_else(|err| {
+                    warn!(%err, "failed to parse job source from sharepoint_jobs");
+                    JobSource::SharePoint
+                }),
+                None => JobSource::SharePoint,
             };
+            let reference = JobReference {
+                job_label: row.get("job_label"),
+                external_ref: row.get("external_ref"),
+            };
+            // Unbekannte Werte gelten als normal
+            let priority = row
+                .get::<_, Option<String>>("priority")
+                .and_then(|p| p.parse().ok())
+                .unwrap_or_default();
             let progress: f64 = row.get("progress");
             let created_at: DateTime<Utc> = row.get("created_at");
             let updated_at: DateTime<Utc> = row.get("updated_at");
@@ -498,7 +891,7 @@ impl JobStore {
                 message,
                 order,
                 filenames_override: filenames,
-                output,
+                output: stored.upload,
                 upload_url,
                 tenant_id,
                 pipeline_id,
@@ -507,6 +900,10 @@ impl JobStore {
                 pdf_id,
                 auto_managed,
                 auto_last_seen_at,
+                source,
+                reference,
+                priority,
+                checkpoint: stored.checkpoint,
                 created_at,
                 updated_at,
             });
@@ -515,3 +912,141 @@ impl JobStore {
         Ok(jobs)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn folder_sources_keep_sharepoint_jobs_unchanged() {
+        assert_eq!(
+            JobSource::folder(ConnectorKind::SharePoint, None),
+            JobSource::SharePoint
+        );
+        let local = JobSource::folder(ConnectorKind::Local, Some(vec!["Akte/a.pdf".into()]));
+        let value = serde_json::to_value(&local).unwrap();
+        assert_eq!(
+            value,
+            serde_json::json!({"kind": "drive", "connector": "local", "file_ids": ["Akte/a.pdf"]})
+        );
+        let parsed: JobSource = serde_json::from_value(value).unwrap();
+        assert_eq!(
+            parsed.folder_connector(),
+            Some((ConnectorKind::Local, Some(&["Akte/a.pdf".to_string()][..])))
+        );
+        let sftp = JobSource::Sftp {
+            source_id: Uuid::nil(),
+            files: Vec::new(),
+        };
+        assert_eq!(sftp.folder_connector(), None);
+    }
+
+    #[test]
+    fn stored_output_keeps_upload_result_and_checkpoint_apart() {
+        let old = serde_json::json!({
+            "status": "ok",
+            "response": {"upload_id": 7},
+            "uploaded_at": "2026-01-05T10:00:00Z",
+            "upload_id": 7
+        });
+        let stored: StoredOutput = serde_json::from_value(old.clone()).unwrap();
+        assert_eq!(stored.upload.unwrap().upload_id, Some(7));
+        assert!(stored.checkpoint.is_none());
+
+        let running = StoredOutput {
+            upload: None,
+            checkpoint: Some(JobCheckpoint {
+                plan: vec!["list".into(), "download".into()],
+                completed: 1,
+                ..JobCheckpoint::default()
+            }),
+        };
+        let value = serde_json::to_value(&running).unwrap();
+        assert!(value.get("status").is_none());
+        let parsed: StoredOutput = serde_json::from_value(value).unwrap();
+        assert!(parsed.upload.is_none());
+        assert_eq!(parsed.checkpoint.unwrap().completed, 1);
+    }
+
+    #[test]
+    fn restart_resumes_until_the_limit() {
+        let registry = JobRegistry::new(None, 1);
+        let job = registry.create_job(
+            "folder".into(),
+            "Folder".into(),
+            JobOrder::default(),
+            None,
+            None,
+            None,
+            None,
+            false,
+            JobSource::SharePoint,
+            JobReference::default(),
+            RunPriority::Normal,
+        );
+        let mut state = job.state.lock().clone();
+        state.set_status(JobStatus::Running);
+        for attempt in 1..=MAX_RESUMES {
+            assert_eq!(state.prepare_restart(), Restart::Resume { paused: false });
+            assert_eq!(state.status, JobStatus::Queued);
+            assert_eq!(state.checkpoint.as_ref().unwrap().resumes, attempt);
+            state.set_status(JobStatus::Running);
+        }
+        assert_eq!(state.prepare_restart(), Restart::GiveUp);
+        assert_eq!(state.status, JobStatus::Failed);
+        assert!(state.checkpoint.is_none());
+
+        state.set_status(JobStatus::Paused);
+        assert_eq!(state.prepare_restart(), Restart::Resume { paused: true });
+        state.set_status(JobStatus::Succeeded);
+        assert_eq!(state.prepare_restart(), Restart::Finished);
+    }
+
+    #[test]
+    fn subscribers_see_job_and_file_updates() {
+        let registry = JobRegistry::new(None, 1);
+        let mut events = registry.subscribe();
+        let job = registry.create_job(
+            "folder".into(),
+            "Folder".into(),
+            JobOrder::default(),
+            None,
+            None,
+            None,
+            None,
+            false,
+            JobSource::SharePoint,
+            JobReference::default(),
+            RunPriority::Normal,
+        );
+        let id = job.state.lock().id;
+        registry.update(&id, |s| s.set_progress(0.5));
+        let file = GraphFile {
+            id: "f1".into(),
+            name: "Antrag.pdf".into(),
+            etag: None,
+            size: None,
+        };
+        registry.file_changed(id, 0, &file, FileStatus::Failed, Some("timeout"));
+        registry.update(&id, |s| s.set_status(JobStatus::Succeeded));
+        registry.remove(&id).unwrap();
+
+        let frames: Vec<String> = std::iter::from_fn(|| events.try_recv().ok())
+            .map(|event| event.sse_frame().unwrap())
+            .collect();
+        assert_eq!(frames.len(), 5);
+        assert!(frames[0].starts_with("event: job\ndata: {"));
+        assert!(frames[1].contains("\"progress\":0.5"));
+        assert_eq!(
+            frames[2],
+            format!(
+                "event: file\ndata: {{\"job_id\":\"{id}\",\"position\":0,\"file_id\":\"f1\",\
+                 \"file_name\":\"Antrag.pdf\",\"status\":\"failed\",\"error\":\"timeout\"}}\n\n"
+            )
+        );
+        assert_eq!(
+            frames[4],
+            format!("event: removed\ndata: {{\"id\":\"{id}\"}}\n\n")
+        );
+    }
+}
@@ -0,0 +1,265 @@
+//! Per-file state of SharePoint jobs (`sharepoint_job_files`).
+//!
+//! `list` records every file of the folder as `pending`; `download` marks it
+//! `downloaded` or `failed` with the error, `filter` marks quarantined files
+//! as `skipped` with the quarantine reason. Files of a canceled job stay
+//! `pending`. A failed download fails the job only once more files failed
+//! than `JOB_FILE_FAILURE_TOLERANCE` allows ([`FailureTolerance`]); the
+//! remaining files are merged as usual.
+//!
+//! Tracking is best effort: a failing write is logged and never fails the job.
+
+use anyhow::{bail, Result};
+use chrono::{DateTime, Utc};
+use deadpool_postgres::Pool;
+use serde::Serialize;
+use tokio_postgres::Row;
+use uuid::Uuid;
+
+use crate::msgraph::GraphFile;
+
+/// Creates `sharepoint_job_files` (see migration 0054). Like `scan_quarantine`
+/// without foreign key, jobs are persisted asynchronously.
+pub const JOB_FILES_SCHEMA_SQL: &str = r#"
+CREATE TABLE IF NOT EXISTS sharepoint_job_files (
+    job_id UUID NOT NULL,
+    position INTEGER NOT NULL,
+    file_id TEXT NOT NULL,
+    file_name TEXT NOT NULL,
+    status TEXT NOT NULL DEFAULT 'pending' CHECK (status IN ('pending','downloaded','failed','skipped')),
+    error TEXT,
+    updated_at TIMESTAMPTZ NOT NULL DEFAULT now(),
+    PRIMARY KEY (job_id, position)
+);
+"#;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FileStatus {
+    Downloaded,
+    Failed,
+    Skipped,
+}
+
+impl FileStatus {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            FileStatus::Downloaded => "downloaded",
+            FileStatus::Failed => "failed",
+            FileStatus::Skipped => "skipped",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct JobFile {
+    /// Position in merge order, starting at 0.
+    pub position: i32,
+    pub file_id: String,
+    pub file_name: String,
+    pub status: String,
+    pub error: Option<String>,
+    pub updated_at: DateTime<Utc>,
+}
+
+impl JobFile {
+    fn from_row(row: &Row) -> Self {
+        Self {
+            position: row.get("position"),
+            file_id: row.get("file_id"),
+            file_name: row.get("file_name"),
+            status: row.get("status"),
+            error: row.get("error"),
+            updated_at: row.get("updated_at"),
+        }
+    }
+}
+
+/// Number of files per status.
+#[derive(Debug, Default, Clone, PartialEq, Eq, Serialize)]
+pub struct FileCounts {
+    pub total: usize,
+    pub pending: usize,
+    pub downloaded: usize,
+    pub failed: usize,
+    pub skipped: usize,
+}
+
+impl FileCounts {
+    pub fn of(files: &[JobFile]) -> Self {
+        let mut counts = Self {
+            total: files.len(),
+            ..Self::default()
+        };
+        for file in files {
+            match file.status.as_str() {
+                "downloaded" => counts.downloaded += 1,
+                "failed" => counts.failed += 1,
+                "skipped" => counts.skipped += 1,
+                _ => counts.pending += 1,
+            }
+        }
+        counts
+    }
+}
+
+/// Failed downloads a job tolerates: a number of files (`3`) or a share of
+/// the folder (`10%`). Default `0`, every failed download fails the job.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FailureTolerance {
+    Files(usize),
+    Percent(u8),
+}
+
+impl Default for FailureTolerance {
+    fn default() -> Self {
+        Self::Files(0)
+    }
+}
+
+impl FailureTolerance {
+    pub fn parse(raw: &str) -> Result<Self> {
+        let raw = raw.trim();
+        if let Some(percent) = raw.strip_suffix('%') {
+            let percent: u8 = percent.trim().parse()?;
+            if percent > 100 {
+                bail!("share above 100%");
+            }
+            return Ok(Self::Percent(percent));
+        }
+        Ok(Self::Files(raw.parse()?))
+    }
+
+    /// Whether `failed` of `total` files may fail; a job without a single
+    /// downloaded file always fails.
+    pub fn allows(&self, failed: usize, total: usize) -> bool {
+        if failed == 0 {
+            return true;
+        }
+        if failed >= total {
+            return false;
+        }
+        match *self {
+            Self::Files(limit) => failed <= limit,
+            Self::Percent(percent) => failed * 100 <= usize::from(percent) * total,
+        }
+    }
+}
+
+/// Records the listed files as `pending`, replacing an earlier listing.
+pub async fn record_listing(pool: &Pool, job_id: Uuid, files: &[GraphFile]) -> Result<()> {
+    let positions: Vec<i32> = (0..files.len() as i32).collect();
+    let ids: Vec<&str> = files.iter().map(|f| f.id.as_str()).collect();
+    let names: Vec<&str> = files.iter().map(|f| f.name.as_str()).collect();
+    let mut client = pool.get().await?;
+    let tx = client.transaction().await?;
+    tx.execute(
+        "DELETE FROM sharepoint_job_files WHERE job_id = $1",
+        &[&job_id],
+    )
+    .await?;
+    tx.execute(
+        "INSERT INTO sharepoint_job_files (job_id, position, file_id, file_name)
+         SELECT $1, * FROM UNNEST($2::int[], $3::text[], $4::text[])",
+        &[&job_id, &positions, &ids, &names],
+    )
+    .await?;
+    tx.commit().await?;
+    Ok(())
+}
+
+pub async fn set_status(
+    pool: &Pool,
+    job_id: Uuid,
+    position: usize,
+    status: FileStatus,
+    error: Option<&str>,
+) -> Result<()> {
+    let client = pool.get().await?;
+    client
+        .execute(
+            "UPDATE sharepoint_job_files SET status = $3, error = $4, updated_at = now()
+             WHERE job_id = $1 AND position = $2",
+            &[&job_id, &(position as i32), &status.as_str(), &error],
+        )
+        .await?;
+    Ok(())
+}
+
+/// Files of a job in merge order.
+pub async fn list(pool: &Pool, job_id: Uuid) -> Result<Vec<JobFile>> {
+    let client = pool.get().await?;
+    let rows = client
+        .query(
+            "SELECT position, file_id, file_name, status, error, updated_at
+             FROM sharepoint_job_files WHERE job_id = $1 ORDER BY position",
+            &[&job_id],
+        )
+        .await?;
+    Ok(rows.iter().map(JobFile::from_row).collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_counts_and_shares() {
+        assert_eq!(
+            FailureTolerance::parse("3").unwrap(),
+            FailureTolerance::Files(3)
+        );
+        assert_eq!(
+            FailureTolerance::parse(" 10% ").unwrap(),
+            FailureTolerance::Percent(10)
+        );
+        assert!(FailureTolerance::parse("150%").is_err());
+        assert!(FailureTolerance::parse("some").is_err());
+    }
+
+    #[test]
+    fn tolerance_never_accepts_a_job_without_files() {
+        let strict = FailureTolerance::default();
+        assert!(strict.allows(0, 5));
+        assert!(!strict.allows(1, 5));
+
+        let two = FailureTolerance::Files(2);
+        assert!(two.allows(2, 10));
+        assert!(!two.allows(3, 10));
+        assert!(!two.allows(2, 2));
+
+        let share = FailureTolerance::Percent(10);
+        assert!(share.allows(2, 20));
+        assert!(!share.allows(3, 20));
+        assert!(!FailureTolerance::Percent(100).allows(4, 4));
+    }
+
+    #[test]
+    fn counts_files_per_status() {
+        let file = |status: &str| JobFile {
+            position: 0,
+            file_id: "id".into(),
+            file_name: "a.pdf".into(),
+            status: status.into(),
+            error: None,
+            updated_at: Utc::now(),
+        };
+        let files = [
+            file("downloaded"),
+            file("failed"),
+            file("downloaded"),
+            file("pending"),
+            file("skipped"),
+        ];
+        let counts = FileCounts::of(&files);
+        assert_eq!(
+            counts,
+            FileCounts {
+                total: 5,
+                pending: 1,
+                downloaded: 2,
+                failed: 1,
+                skipped: 1,
+            }
+        );
+    }
+}
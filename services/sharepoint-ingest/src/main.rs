@@ -1,37 +1,66 @@
 //! Service responsible for scanning SharePoint folders and enqueueing PDF jobs.
 
 mod config;
+mod connector;
+mod connector_config;
+mod delta;
+mod imap;
+mod inbox;
+mod inventory;
 mod job;
+mod job_files;
 mod msgraph;
 mod pdfops;
+mod pgp;
 mod pipeline_adapter;
+mod post_action;
+mod preview;
+mod purge;
+mod s3;
 mod scan;
+mod schedule;
+mod scheduler;
+mod sftp;
+mod stats;
+mod steps;
 mod upload_adapter;
+mod webhook;
 
 use std::collections::{HashMap, HashSet};
 use std::str::FromStr;
 use std::sync::Arc;
 
-use actix_cors::Cors;
 use actix_web::{
     error::ErrorBadRequest, http::header, middleware::Logger, web, App, HttpRequest, HttpResponse,
     HttpServer, Responder,
 };
-use anyhow::{anyhow, Context};
+use anyhow::Context;
 use chrono::{DateTime, Utc};
+use connector::{ConnectorKind, Connectors};
+use connector_config::{MasterKeyed, MASTER_KEY_MISSING};
 use deadpool_postgres::{Manager, ManagerConfig, Pool, RecyclingMethod};
-use job::{job_summary, JobOrder, JobPersistence, JobRegistry, JobStatus, JobStore, ManagedJob};
+use imap::{ImapConnector, ImapMailboxInput};
+use inventory::ReingestMode;
+use job::{
+    job_summary, JobEvent, JobOrder, JobPersistence, JobReference, JobRegistry, JobSource,
+    JobStatus, JobStore, ManagedJob, Restart,
+};
 use msgraph::{GraphFile, GraphFolder, MsGraphClient};
-use pdfops::merge_pdfs;
 use pipeline_adapter::PipelineAdapter;
+use post_action::PostAction;
 use rdkafka::{
     consumer::{Consumer, StreamConsumer},
     ClientConfig, Message,
 };
-use scan::{assert_pdf, scan_with_clamd, ScanConfig};
+use s3::{S3Connector, S3Defaults, S3SourceInput};
+use scan::ScanConfig;
 use serde_json::json;
-use shared::dto::PipelineRunResult;
-use tokio::sync::{watch, Semaphore};
+use sftp::{SftpConnector, SftpSourceInput};
+use shared::dto::{PipelineRunResult, RunPriority, RunStatus};
+use shared::envelope::MasterKey;
+use shared::schema_doc;
+use steps::{JobContext, JobPlan, JobServices};
+use tokio::sync::{broadcast::error::RecvError, watch, Notify};
 use tokio::time::sleep;
 use tokio_postgres::{NoTls, Row};
 use tracing::{error, info, warn};
@@ -103,6 +132,25 @@ ALTER TABLE sharepoint_jobs
     ADD COLUMN IF NOT EXISTS auto_managed BOOLEAN NOT NULL DEFAULT FALSE;
 ALTER TABLE sharepoint_jobs
     ADD COLUMN IF NOT EXISTS auto_last_seen_at TIMESTAMPTZ;
+ALTER TABLE sharepoint_jobs
+    ADD COLUMN IF NOT EXISTS source JSONB;
+ALTER TABLE sharepoint_jobs
+    ADD COLUMN IF NOT EXISTS job_label TEXT;
+ALTER TABLE sharepoint_jobs
+    ADD COLUMN IF NOT EXISTS external_ref TEXT;
+ALTER TABLE sharepoint_jobs
+    ADD COLUMN IF NOT EXISTS priority TEXT NOT NULL DEFAULT 'normal';
+CREATE INDEX IF NOT EXISTS idx_sharepoint_jobs_external_ref ON sharepoint_jobs (external_ref);
+ALTER TABLE sharepoint_automation
+    ADD COLUMN IF NOT EXISTS job_label TEXT;
+ALTER TABLE sharepoint_automation
+    ADD COLUMN IF NOT EXISTS external_ref TEXT;
+ALTER TABLE sharepoint_automation
+    ADD COLUMN IF NOT EXISTS schedule TEXT;
+ALTER TABLE sharepoint_automation
+    ADD COLUMN IF NOT EXISTS next_run_at TIMESTAMPTZ;
+ALTER TABLE sharepoint_automation
+    ADD COLUMN IF NOT EXISTS reingest TEXT;
 "#;
 
 use crate::config::Config;
@@ -122,12 +170,20 @@ fn ensure_sslmode_disable(url: &str) -> String {
 struct AppState {
     config: Arc<Config>,
     graph: Arc<MsGraphClient>,
+    /// Folder sources of jobs (see `connector.rs`).
+    connectors: Connectors,
     uploader: Arc<UploadAdapter>,
     jobs: JobRegistry,
-    semaphore: Arc<Semaphore>,
     db_pool: Pool,
     job_store: Arc<JobStore>,
     pipeline: Arc<PipelineAdapter>,
+    sftp: Arc<SftpConnector>,
+    /// Also part of `connectors`; kept for the source API.
+    s3: Arc<S3Connector>,
+    imap: Arc<ImapConnector>,
+    job_plan: Arc<JobPlan>,
+    /// Wakes the automation poller before its next interval.
+    poll_trigger: Arc<Notify>,
 }
 
 #[derive(serde::Serialize)]
@@ -162,6 +218,18 @@ struct FolderAutomation {
     auto_ingest: bool,
     auto_pipeline: bool,
     #[serde(skip_serializing_if = "Option::is_none")]
+    job_label: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    external_ref: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    schedule: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    next_run_at: Option<DateTime<Utc>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    reingest: Option<ReingestMode>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    post_actions: Vec<PostAction>,
+    #[serde(skip_serializing_if = "Option::is_none")]
     last_seen: Option<DateTime<Utc>>,
     #[serde(skip_serializing_if = "Option::is_none")]
     updated_at: Option<DateTime<Utc>>,
@@ -176,6 +244,17 @@ struct AutomationRecord {
     auto_ingest: bool,
     auto_pipeline: bool,
     managed_by_default: bool,
+    job_label: Option<String>,
+    external_ref: Option<String>,
+    /// Cron expression (see `schedule.rs`); `None` ingests via the poller.
+    schedule: Option<String>,
+    next_run_at: Option<DateTime<Utc>>,
+    /// Jobs for files added after the last ingest (see `inventory.rs`).
+    reingest: Option<ReingestMode>,
+    /// Drive the folder lives in.
+    connector: ConnectorKind,
+    /// Run after the pipeline run of a job finished (see `post_action.rs`).
+    post_actions: Vec<PostAction>,
     last_seen: Option<DateTime<Utc>>,
     updated_at: DateTime<Utc>,
 }
@@ -192,6 +271,19 @@ struct AutomationRuleResponse {
     auto_pipeline: bool,
     managed_by_default: bool,
     #[serde(skip_serializing_if = "Option::is_none")]
+    job_label: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    external_ref: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    schedule: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    next_run_at: Option<DateTime<Utc>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    reingest: Option<ReingestMode>,
+    connector: ConnectorKind,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    post_actions: Vec<PostAction>,
+    #[serde(skip_serializing_if = "Option::is_none")]
     last_seen: Option<DateTime<Utc>>,
     updated_at: DateTime<Utc>,
 }
@@ -238,6 +330,20 @@ struct AutomationUpsertRequest {
     auto_ingest: Option<bool>,
     #[serde(default)]
     auto_pipeline: Option<bool>,
+    #[serde(default)]
+    job_label: Option<String>,
+    #[serde(default)]
+    external_ref: Option<String>,
+    #[serde(default)]
+    schedule: Option<String>,
+    #[serde(default)]
+    reingest: Option<ReingestMode>,
+    /// Keeps the connector of an existing rule when absent.
+    #[serde(default)]
+    connector: Option<ConnectorKind>,
+    /// Keeps the post-actions of an existing rule when absent.
+    #[serde(default)]
+    post_actions: Option<Vec<PostAction>>,
 }
 
 #[derive(serde::Deserialize)]
@@ -256,6 +362,12 @@ impl AutomationRecord {
             pipeline_id: self.pipeline_id,
             auto_ingest: self.auto_ingest,
             auto_pipeline: self.auto_pipeline,
+            job_label: self.job_label.clone(),
+            external_ref: self.external_ref.clone(),
+            schedule: self.schedule.clone(),
+            next_run_at: self.next_run_at,
+            reingest: self.reingest,
+            post_actions: self.post_actions.clone(),
             last_seen: self.last_seen,
             updated_at: Some(self.updated_at),
         }
@@ -270,6 +382,13 @@ impl AutomationRecord {
             auto_ingest: self.auto_ingest,
             auto_pipeline: self.auto_pipeline,
             managed_by_default: self.managed_by_default,
+            job_label: self.job_label,
+            external_ref: self.external_ref,
+            schedule: self.schedule,
+            next_run_at: self.next_run_at,
+            reingest: self.reingest,
+            connector: self.connector,
+            post_actions: self.post_actions,
             last_seen: self.last_seen,
             updated_at: self.updated_at,
         }
@@ -301,6 +420,54 @@ struct JobCreateRequest {
     tenant_id: Option<Uuid>,
     #[serde(default)]
     pipeline_id: Option<Uuid>,
+    /// Label/Aktenzeichen für alle Jobs dieses Requests.
+    #[serde(default)]
+    job_label: Option<String>,
+    #[serde(default)]
+    external_ref: Option<String>,
+    /// Überschreibt `job_label` bzw. `external_ref` je Ordner.
+    #[serde(default)]
+    labels: Option<HashMap<String, String>>,
+    #[serde(default)]
+    external_refs: Option<HashMap<String, String>>,
+    /// `high` zieht die Jobs vor wartende Jobs (siehe `scheduler.rs`).
+    #[serde(default)]
+    priority: Option<RunPriority>,
+    /// Laufwerk der Ordner (siehe `connector.rs`).
+    #[serde(default)]
+    connector: ConnectorKind,
+}
+
+/// `?connector=` of `GET /folders`.
+#[derive(serde::Deserialize, Default)]
+struct ConnectorQuery {
+    #[serde(default)]
+    connector: ConnectorKind,
+}
+
+/// Listing filter: `job_label` matches case-insensitive substrings, `external_ref` exactly.
+#[derive(serde::Deserialize, Default)]
+struct JobReferenceQuery {
+    #[serde(default)]
+    job_label: Option<String>,
+    #[serde(default)]
+    external_ref: Option<String>,
+}
+
+impl JobReferenceQuery {
+    fn label(&self) -> Option<&str> {
+        self.job_label
+            .as_deref()
+            .map(str::trim)
+            .filter(|v| !v.is_empty())
+    }
+
+    fn external_ref(&self) -> Option<&str> {
+        self.external_ref
+            .as_deref()
+            .map(str::trim)
+            .filter(|v| !v.is_empty())
+    }
 }
 
 #[derive(serde::Serialize)]
@@ -317,6 +484,8 @@ struct ProcessedFoldersResponse {
 struct ProcessedFoldersQuery {
     #[serde(default)]
     stage: Option<String>,
+    #[serde(flatten)]
+    reference: JobReferenceQuery,
 }
 
 #[derive(serde::Serialize)]
@@ -333,6 +502,10 @@ struct ProcessedFolderItem {
     upload_id: Option<i32>,
     pdf_id: Option<i32>,
     #[serde(skip_serializing_if = "Option::is_none")]
+    job_label: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    external_ref: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
     upload_status: Option<String>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pipeline_status: Option<String>,
@@ -397,6 +570,10 @@ struct AggregatedJobEntry {
     pipeline_id: Option<Uuid>,
     pdf_id: Option<i32>,
     upload_id: Option<i32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    job_label: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    external_ref: Option<String>,
     created_at: DateTime<Utc>,
     updated_at: Option<DateTime<Utc>>,
 }
@@ -438,19 +615,31 @@ async fn main() -> std::io::Result<()> {
     })?;
 
     let job_store = Arc::new(JobStore::new(pool.clone()));
-    let jobs = JobRegistry::new(Some(JobPersistence::new(job_store.clone())));
+    let jobs = JobRegistry::new(
+        Some(JobPersistence::new(job_store.clone())),
+        raw_config.max_concurrency,
+    );
 
+    // Unterbrochene Jobs laufen nach dem Aufbau des AppState ab ihrem Checkpoint weiter
+    let mut interrupted = Vec::new();
     match job_store.load_all().await {
         Ok(records) => {
             let mut restored = 0usize;
             for mut state in records {
-                if matches!(state.status, JobStatus::Running | JobStatus::Queued) {
-                    state.set_status(JobStatus::Failed);
-                    state.set_message("Verarbeitung beim Neustart unterbrochen");
+                let restart = state.prepare_restart();
+                if restart != Restart::Finished {
                     if let Err(err) = job_store.persist_state(&state).await {
                         warn!(job_id = %state.id, error = %err, "failed to persist interrupted state");
                     }
                 }
+                match restart {
+                    Restart::Resume { paused } => interrupted.push((state.id, paused)),
+                    Restart::GiveUp => {
+                        warn!(job_id = %state.id, "job interrupted too often; giving up");
+                        steps::remove_work_dir(&raw_config, state.id).await;
+                    }
+                    Restart::Finished => {}
+                }
                 jobs.restore_job(state);
                 restored += 1;
             }
@@ -463,13 +652,32 @@ async fn main() -> std::io::Result<()> {
         }
     }
 
+    let master_key = match MasterKey::from_env() {
+        Ok(key) => Some(key),
+        Err(err) => {
+            warn!(error = %err, "no credentials master key; sftp sources, imap mailboxes and stored s3 keys cannot be used");
+            None
+        }
+    };
+
     let config = Arc::new(raw_config);
-    let max_concurrency = config.max_concurrency;
     let graph = Arc::new(MsGraphClient::new(&config).expect("graph client"));
     graph
         .bootstrap(&config)
         .await
         .expect("graph bootstrap failed");
+    let s3 = Arc::new(
+        S3Connector::new(
+            pool.clone(),
+            master_key.clone(),
+            S3Defaults::from_config(&config),
+            config.s3_timeout,
+        )
+        .expect("s3 connector"),
+    );
+    let connectors = Connectors::from_config(&config, graph.clone(), s3.clone())
+        .await
+        .expect("source connectors");
     let uploader = Arc::new(
         UploadAdapter::new(
             config.upload_url.clone(),
@@ -487,18 +695,47 @@ async fn main() -> std::io::Result<()> {
         .expect("pipeline adapter"),
     );
 
+    let job_plan = JobPlan::from_env().expect("job step configuration");
+    info!(steps = ?job_plan.names(), "job steps configured");
+
     let state = AppState {
         config: config.clone(),
         graph,
+        connectors,
         uploader,
         jobs,
-        semaphore: Arc::new(Semaphore::new(max_concurrency)),
         db_pool: pool.clone(),
         job_store,
         pipeline,
+        sftp: Arc::new(SftpConnector::new(master_key.clone(), config.sftp_timeout)),
+        s3,
+        imap: Arc::new(
+            ImapConnector::new(master_key, config.imap_timeout).expect("imap connector"),
+        ),
+        job_plan: Arc::new(job_plan),
+        poll_trigger: Arc::new(Notify::new()),
     };
 
+    if !interrupted.is_empty() {
+        info!(
+            count = interrupted.len(),
+            "resuming interrupted SharePoint jobs"
+        );
+    }
+    for (job_id, paused) in interrupted {
+        if let Some(job) = state.jobs.get(&job_id) {
+            if paused {
+                state.jobs.pause(&job_id);
+            }
+            spawn_job_worker(state.clone(), job);
+        }
+    }
+
     spawn_folder_poller(state.clone());
+    spawn_automation_scheduler(state.clone());
+    spawn_subscription_manager(state.clone());
+    spawn_sftp_poller(state.clone());
+    spawn_imap_poller(state.clone());
     spawn_pipeline_result_consumer(state.clone());
 
     let bind_addr = format!("{}:{}", state.config.http_bind, state.config.http_port);
@@ -508,32 +745,24 @@ async fn main() -> std::io::Result<()> {
 
     HttpServer::new(move || {
         let app_state = server_state.clone();
-        let mut cors = Cors::default()
-            .allowed_methods(vec!["GET", "POST"])
-            .allowed_headers(vec![header::CONTENT_TYPE, header::AUTHORIZATION])
-            .max_age(3600);
-
-        if let Some(origins) = &app_state.config.cors_origins {
-            for origin in origins {
-                cors = cors.allowed_origin(origin);
-            }
-        } else {
-            warn!("CORS_ORIGINS not set; allowing any origin");
-            cors = cors.allow_any_origin();
-        }
+        let cors = app_state.config.cors.actix();
 
         App::new()
             .app_data(web::Data::new(app_state))
             .wrap(Logger::default())
             .wrap(cors)
             .route("/healthz", web::get().to(healthz))
+            .route("/admin/schema", web::get().to(admin_schema))
             .route("/folders", web::get().to(list_folders))
+            .route("/folders/{id}/stats", web::get().to(folder_stats))
             .route("/processed-folders", web::get().to(list_processed_folders))
             .route(
                 "/processed-folders/run",
                 web::post().to(run_processed_folders),
             )
             .route("/jobs/all", web::get().to(list_all_jobs))
+            .route("/inbox", web::get().to(get_inbox))
+            .route("/graph/notifications", web::post().to(graph_notifications))
             .service(
                 web::scope("/automation")
                     .route("/settings", web::get().to(list_automation_settings))
@@ -548,10 +777,46 @@ async fn main() -> std::io::Result<()> {
                 web::scope("/jobs")
                     .route("", web::get().to(list_jobs))
                     .route("", web::post().to(create_jobs))
+                    .route("/preview", web::post().to(preview_jobs))
+                    .route("/events", web::get().to(job_events))
                     .route("/{id}/pause", web::post().to(pause_job))
                     .route("/{id}/resume", web::post().to(resume_job))
                     .route("/{id}/cancel", web::post().to(cancel_job))
-                    .route("/{id}/retry", web::post().to(retry_job)),
+                    .route("/purge", web::post().to(purge_jobs))
+                    .route("/{id}", web::delete().to(delete_job))
+                    .route("/{id}/retry", web::post().to(retry_job))
+                    .route("/{id}/files", web::get().to(list_job_files)),
+            )
+            .service(
+                web::scope("/quarantine")
+                    .route("", web::get().to(list_quarantine))
+                    .route("/{id}", web::get().to(get_quarantine))
+                    .route("/{id}", web::delete().to(delete_quarantine))
+                    .route("/{id}/file", web::get().to(download_quarantine_file))
+                    .route("/{id}/release", web::post().to(release_quarantine)),
+            )
+            .service(
+                web::scope("/sftp")
+                    .route("/sources", web::get().to(list_sftp_sources))
+                    .route("/sources/{id}", web::put().to(upsert_sftp_source))
+                    .route("/sources/{id}", web::delete().to(delete_sftp_source))
+                    .route("/sources/{id}/poll", web::post().to(poll_sftp_source_now)),
+            )
+            .service(
+                web::scope("/s3")
+                    .route("/sources", web::get().to(list_s3_sources))
+                    .route("/sources/{id}", web::put().to(upsert_s3_source))
+                    .route("/sources/{id}", web::delete().to(delete_s3_source)),
+            )
+            .service(
+                web::scope("/imap")
+                    .route("/mailboxes", web::get().to(list_imap_mailboxes))
+                    .route("/mailboxes/{id}", web::put().to(upsert_imap_mailbox))
+                    .route("/mailboxes/{id}", web::delete().to(delete_imap_mailbox))
+                    .route(
+                        "/mailboxes/{id}/poll",
+                        web::post().to(poll_imap_mailbox_now),
+                    ),
             )
     })
     .bind(bind_addr)?
@@ -568,6 +833,54 @@ async fn ensure_sharepoint_schema(pool: &Pool) -> anyhow::Result<()> {
         .batch_execute(SHAREPOINT_SCHEMA_SQL)
         .await
         .context("create sharepoint_jobs schema")?;
+    client
+        .batch_execute(delta::DELTA_SCHEMA_SQL)
+        .await
+        .context("create sharepoint sync state")?;
+    client
+        .batch_execute(webhook::WEBHOOK_SCHEMA_SQL)
+        .await
+        .context("create sharepoint subscriptions")?;
+    client
+        .batch_execute(sftp::SFTP_SCHEMA_SQL)
+        .await
+        .context("create sftp schema")?;
+    client
+        .batch_execute(s3::S3_SCHEMA_SQL)
+        .await
+        .context("create s3 schema")?;
+    client
+        .batch_execute(imap::IMAP_SCHEMA_SQL)
+        .await
+        .context("create imap schema")?;
+    client
+        .batch_execute(scan::QUARANTINE_SCHEMA_SQL)
+        .await
+        .context("create quarantine schema")?;
+    client
+        .batch_execute(job_files::JOB_FILES_SCHEMA_SQL)
+        .await
+        .context("create sharepoint_job_files schema")?;
+    client
+        .batch_execute(purge::PURGE_SCHEMA_SQL)
+        .await
+        .context("create sharepoint_retired_folders schema")?;
+    client
+        .batch_execute(inventory::INVENTORY_SCHEMA_SQL)
+        .await
+        .context("create sharepoint_folder_inventory schema")?;
+    client
+        .batch_execute(connector::CONNECTOR_SCHEMA_SQL)
+        .await
+        .context("create sharepoint_automation connector column")?;
+    client
+        .batch_execute(post_action::POST_ACTION_SCHEMA_SQL)
+        .await
+        .context("create post action columns")?;
+    client
+        .batch_execute(shared::timeline::CREATE_TABLE_SQL)
+        .await
+        .context("create run_timeline table")?;
     Ok(())
 }
 
@@ -580,6 +893,13 @@ fn automation_from_row(row: &Row) -> AutomationRecord {
         auto_ingest: row.get("auto_ingest"),
         auto_pipeline: row.get("auto_pipeline"),
         managed_by_default: row.get("managed_by_default"),
+        job_label: row.get("job_label"),
+        external_ref: row.get("external_ref"),
+        schedule: row.get("schedule"),
+        next_run_at: row.get("next_run_at"),
+        reingest: ReingestMode::from_column(row.get("reingest")),
+        connector: ConnectorKind::from_column(row.get("connector")),
+        post_actions: post_action::from_column(row.get("post_actions")),
         last_seen: row.get("last_seen"),
         updated_at: row.get("updated_at"),
     }
@@ -606,7 +926,8 @@ async fn load_automation_rules(
 ) -> anyhow::Result<Vec<AutomationRecord>> {
     let rows = client
         .query(
-            "SELECT folder_id, folder_name, tenant_id, pipeline_id, auto_ingest, auto_pipeline, managed_by_default, last_seen, updated_at
+            "SELECT folder_id, folder_name, tenant_id, pipeline_id, auto_ingest, auto_pipeline, managed_by_default,
+                    job_label, external_ref, schedule, next_run_at, reingest, connector, post_actions, last_seen, updated_at
              FROM sharepoint_automation",
             &[],
         )
@@ -623,7 +944,8 @@ async fn load_automation_rule(
 ) -> anyhow::Result<Option<AutomationRecord>> {
     let row = client
         .query_opt(
-            "SELECT folder_id, folder_name, tenant_id, pipeline_id, auto_ingest, auto_pipeline, managed_by_default, last_seen, updated_at
+            "SELECT folder_id, folder_name, tenant_id, pipeline_id, auto_ingest, auto_pipeline, managed_by_default,
+                    job_label, external_ref, schedule, next_run_at, reingest, connector, post_actions, last_seen, updated_at
              FROM sharepoint_automation WHERE folder_id = $1",
             &[&folder_id],
         )
@@ -690,15 +1012,53 @@ async fn healthz(
     Ok(web::Json(HealthResponse { status: "ok" }))
 }
 
+/// Tables created by sharepoint-ingest and its SFTP/S3/IMAP connectors.
+const OWNED_TABLES: [&str; 14] = [
+    "sharepoint_jobs",
+    "sharepoint_job_files",
+    "sharepoint_retired_folders",
+    "sharepoint_folder_inventory",
+    "sharepoint_automation",
+    "sharepoint_automation_defaults",
+    "sharepoint_sync_state",
+    "sharepoint_subscriptions",
+    "sftp_sources",
+    "sftp_seen_files",
+    "s3_sources",
+    "imap_mailboxes",
+    "imap_messages",
+    "scan_quarantine",
+];
+
+async fn admin_schema(
+    req: HttpRequest,
+    state: web::Data<AppState>,
+) -> actix_web::Result<impl Responder> {
+    ensure_authorized(&req, &state.config)?;
+    let client = state
+        .db_pool
+        .get()
+        .await
+        .map_err(actix_web::error::ErrorInternalServerError)?;
+    let schema = schema_doc::describe(&client, "sharepoint-ingest", &OWNED_TABLES)
+        .await
+        .map_err(actix_web::error::ErrorInternalServerError)?;
+    Ok(web::Json(schema))
+}
+
 async fn list_folders(
     req: HttpRequest,
     state: web::Data<AppState>,
+    query: web::Query<ConnectorQuery>,
 ) -> actix_web::Result<impl Responder> {
     ensure_authorized(&req, &state.config)?;
-    let base = state.config.drive_input_path();
-    let folders = state
-        .graph
-        .list_subfolders(&base)
+    let connector = state
+        .connectors
+        .get(query.connector)
+        .map_err(|err| ErrorBadRequest(err.to_string()))?;
+    let base = connector.base();
+    let folders = connector
+        .list_folders()
         .await
         .map_err(actix_web::error::ErrorInternalServerError)?;
     let client = state
@@ -724,11 +1084,17 @@ async fn list_folders(
             }
         }
     }
+    hidden_folders.extend(
+        purge::retired_folders(&client)
+            .await
+            .map_err(actix_web::error::ErrorInternalServerError)?,
+    );
     let automation_rules = load_automation_rules(&client)
         .await
         .map_err(actix_web::error::ErrorInternalServerError)?;
     let mut automation_map: HashMap<String, AutomationRecord> = automation_rules
         .into_iter()
+        .filter(|record| record.connector == query.connector)
         .map(|record| (record.folder_id.clone(), record))
         .collect();
     let items = folders
@@ -761,6 +1127,30 @@ async fn list_folders(
     }))
 }
 
+/// File types, sizes and ages of a folder so oversized or misplaced files show up before ingest.
+async fn folder_stats(
+    req: HttpRequest,
+    state: web::Data<AppState>,
+    path: web::Path<String>,
+) -> actix_web::Result<impl Responder> {
+    ensure_authorized(&req, &state.config)?;
+    let folder_id = path.into_inner();
+    let items = state
+        .graph
+        .list_folder_items(&folder_id)
+        .await
+        .map_err(actix_web::error::ErrorBadGateway)?;
+    match items {
+        Some(items) => Ok(HttpResponse::Ok().json(stats::FolderStats::compute(
+            &folder_id,
+            &items,
+            &ScanConfig::from_env(),
+            Utc::now(),
+        ))),
+        None => Ok(HttpResponse::NotFound().finish()),
+    }
+}
+
 async fn list_automation_rules(
     req: HttpRequest,
     state: web::Data<AppState>,
@@ -835,11 +1225,40 @@ async fn upsert_automation_rule(
     let folder_name = requested_name.unwrap_or(fallback_name);
     let auto_ingest = payload.auto_ingest.unwrap_or(false);
     let auto_pipeline = payload.auto_pipeline.unwrap_or(false);
+    let reference = JobReference::new(payload.job_label.clone(), payload.external_ref.clone());
+    let schedule = schedule::validate(payload.schedule.as_deref())
+        .map_err(|err| ErrorBadRequest(err.to_string()))?;
+    let next_run_at = schedule
+        .as_deref()
+        .and_then(|raw| schedule::Schedule::parse(raw).ok())
+        .and_then(|schedule| schedule.next_after(Utc::now()));
+    let connector = payload
+        .connector
+        .or_else(|| existing.as_ref().map(|record| record.connector))
+        .unwrap_or_default();
+    let source = state
+        .connectors
+        .get(connector)
+        .map_err(|err| ErrorBadRequest(err.to_string()))?;
+    let post_actions = payload
+        .post_actions
+        .clone()
+        .or_else(|| existing.as_ref().map(|record| record.post_actions.clone()))
+        .unwrap_or_default();
+    post_action::validate(&post_actions).map_err(|err| ErrorBadRequest(err.to_string()))?;
+    if !post_actions.is_empty() && source.graph().is_none() {
+        return Err(ErrorBadRequest(format!(
+            "connector {} does not support post actions",
+            connector.as_str()
+        )));
+    }
+    let post_actions_json =
+        serde_json::to_value(&post_actions).map_err(actix_web::error::ErrorInternalServerError)?;
 
     client
         .execute(
-            "INSERT INTO sharepoint_automation (folder_id, folder_name, tenant_id, pipeline_id, auto_ingest, auto_pipeline, managed_by_default)
-             VALUES ($1, $2, $3, $4, $5, $6, FALSE)
+            "INSERT INTO sharepoint_automation (folder_id, folder_name, tenant_id, pipeline_id, auto_ingest, auto_pipeline, managed_by_default, job_label, external_ref, schedule, next_run_at, reingest, connector, post_actions)
+             VALUES ($1, $2, $3, $4, $5, $6, FALSE, $7, $8, $9, $10, $11, $12, $13)
              ON CONFLICT (folder_id) DO UPDATE SET
                  folder_name = EXCLUDED.folder_name,
                  tenant_id = EXCLUDED.tenant_id,
@@ -847,12 +1266,38 @@ async fn upsert_automation_rule(
                  auto_ingest = EXCLUDED.auto_ingest,
                  auto_pipeline = EXCLUDED.auto_pipeline,
                  managed_by_default = FALSE,
+                 job_label = EXCLUDED.job_label,
+                 external_ref = EXCLUDED.external_ref,
+                 schedule = EXCLUDED.schedule,
+                 next_run_at = EXCLUDED.next_run_at,
+                 reingest = EXCLUDED.reingest,
+                 connector = EXCLUDED.connector,
+                 post_actions = EXCLUDED.post_actions,
                  updated_at = now()",
-            &[&folder_id, &folder_name, &payload.tenant_id, &payload.pipeline_id, &auto_ingest, &auto_pipeline],
+            &[
+                &folder_id,
+                &folder_name,
+                &payload.tenant_id,
+                &payload.pipeline_id,
+                &auto_ingest,
+                &auto_pipeline,
+                &reference.job_label,
+                &reference.external_ref,
+                &schedule,
+                &next_run_at,
+                &payload.reingest.map(|mode| mode.as_str()),
+                &connector.as_str(),
+                &post_actions_json,
+            ],
         )
         .await
         .map_err(actix_web::error::ErrorInternalServerError)?;
 
+    // Unveränderte Ordner liefert das Delta nicht, der nächste Poll listet alle
+    if let Err(err) = delta::reset(&client).await {
+        warn!(error = %err, "failed to reset sharepoint delta state");
+    }
+
     let updated = load_automation_rule(&client, &folder_id)
         .await
         .map_err(actix_web::error::ErrorInternalServerError)?;
@@ -907,6 +1352,11 @@ async fn upsert_automation_setting(
     let updated = upsert_automation_default(&client, &normalized, &payload)
         .await
         .map_err(actix_web::error::ErrorInternalServerError)?;
+    if normalized == "ingest" {
+        if let Err(err) = delta::reset(&client).await {
+            warn!(error = %err, "failed to reset sharepoint delta state");
+        }
+    }
 
     Ok(HttpResponse::Ok().json(updated.into_response()))
 }
@@ -921,9 +1371,12 @@ async fn create_jobs(
         return Err(ErrorBadRequest("folder_ids required"));
     }
 
-    let base_folders = state
-        .graph
-        .list_subfolders(&state.config.drive_input_path())
+    let connector = state
+        .connectors
+        .get(payload.connector)
+        .map_err(|err| ErrorBadRequest(err.to_string()))?;
+    let base_folders = connector
+        .list_folders()
         .await
         .map_err(actix_web::error::ErrorInternalServerError)?;
     let folder_map: HashMap<_, _> = base_folders
@@ -972,6 +1425,18 @@ async fn create_jobs(
             .as_ref()
             .and_then(|map| map.get(folder_id).cloned());
         let job_order = payload.order.clone().unwrap_or_default();
+        let reference = JobReference::new(
+            payload
+                .labels
+                .as_ref()
+                .and_then(|map| map.get(folder_id).cloned())
+                .or_else(|| payload.job_label.clone()),
+            payload
+                .external_refs
+                .as_ref()
+                .and_then(|map| map.get(folder_id).cloned())
+                .or_else(|| payload.external_ref.clone()),
+        );
         let job = state.jobs.create_job(
             folder.id.clone(),
             folder.name.clone(),
@@ -981,6 +1446,9 @@ async fn create_jobs(
             tenant_override,
             pipeline_override,
             false,
+            JobSource::folder(payload.connector, None),
+            reference,
+            payload.priority.unwrap_or_default(),
         );
         let summary = job_summary(&job);
         spawn_job_worker(app_state.clone(), job);
@@ -990,15 +1458,114 @@ async fn create_jobs(
     Ok(web::Json(JobsResponse { jobs: created }))
 }
 
+/// Merge manifest `POST /jobs` would produce for the same body, without downloading or creating jobs.
+async fn preview_jobs(
+    req: HttpRequest,
+    state: web::Data<AppState>,
+    payload: web::Json<JobCreateRequest>,
+) -> actix_web::Result<impl Responder> {
+    ensure_authorized(&req, &state.config)?;
+    if payload.folder_ids.is_empty() {
+        return Err(ErrorBadRequest("folder_ids required"));
+    }
+
+    let connector = state
+        .connectors
+        .get(payload.connector)
+        .map_err(|err| ErrorBadRequest(err.to_string()))?;
+    let base_folders = connector
+        .list_folders()
+        .await
+        .map_err(actix_web::error::ErrorInternalServerError)?;
+    let folder_names: HashMap<_, _> = base_folders.into_iter().map(|f| (f.id, f.name)).collect();
+
+    let scan_config = ScanConfig::from_env();
+    let mut folders = Vec::new();
+    for folder_id in &payload.folder_ids {
+        let folder_preview = preview::preview_folder(
+            connector.as_ref(),
+            folder_id.clone(),
+            folder_names
+                .get(folder_id)
+                .cloned()
+                .unwrap_or_else(|| folder_id.clone()),
+            payload.order.clone().unwrap_or_default(),
+            payload
+                .filenames
+                .as_ref()
+                .and_then(|map| map.get(folder_id).cloned()),
+            &scan_config,
+        )
+        .await
+        .map_err(actix_web::error::ErrorBadGateway)?;
+        folders.push(folder_preview);
+    }
+
+    Ok(web::Json(preview::JobPreviewResponse { folders }))
+}
+
 async fn list_jobs(
     req: HttpRequest,
     state: web::Data<AppState>,
+    query: web::Query<JobReferenceQuery>,
 ) -> actix_web::Result<impl Responder> {
     ensure_authorized(&req, &state.config)?;
-    let jobs = state.jobs.list();
+    let jobs = state
+        .jobs
+        .list()
+        .into_iter()
+        .filter(|job| job.reference.matches(query.label(), query.external_ref()))
+        .collect();
     Ok(web::Json(JobsResponse { jobs }))
 }
 
+/// Interval of the keep-alive comment on `GET /jobs/events`, so proxies do
+/// not close an idle stream.
+const JOB_EVENTS_KEEPALIVE: std::time::Duration = std::time::Duration::from_secs(15);
+
+/// Streams job updates as Server-Sent Events: first every job as `job`, then
+/// `job`, `file` and `removed` events as they happen. A subscriber that fell
+/// behind gets all jobs again.
+async fn job_events(
+    req: HttpRequest,
+    state: web::Data<AppState>,
+) -> actix_web::Result<HttpResponse> {
+    ensure_authorized(&req, &state.config)?;
+    let mut rx = state.jobs.subscribe();
+    let jobs = state.jobs.clone();
+    let stream = async_stream::stream! {
+        let mut keepalive = tokio::time::interval(JOB_EVENTS_KEEPALIVE);
+        let mut pending: Vec<JobEvent> = jobs.list().into_iter().map(|job| JobEvent::Job(Box::new(job))).collect();
+        loop {
+            for event in pending.drain(..) {
+                match event.sse_frame() {
+                    Ok(frame) => yield Ok::<_, actix_web::Error>(web::Bytes::from(frame)),
+                    Err(err) => error!(error = %err, "failed to serialize job event"),
+                }
+            }
+            let received = tokio::select! {
+                received = rx.recv() => Some(received),
+                _ = keepalive.tick() => None,
+            };
+            match received {
+                None => yield Ok(web::Bytes::from_static(b": keep-alive\n\n")),
+                Some(Ok(event)) => pending.push(event),
+                Some(Err(RecvError::Lagged(missed))) => {
+                    warn!(missed, "job event subscriber fell behind, resending jobs");
+                    pending.extend(jobs.list().into_iter().map(|job| JobEvent::Job(Box::new(job))));
+                }
+                Some(Err(RecvError::Closed)) => break,
+            }
+        }
+    };
+    Ok(HttpResponse::Ok()
+        .insert_header((header::CONTENT_TYPE, "text/event-stream"))
+        .insert_header((header::CACHE_CONTROL, "no-cache"))
+        // nginx puffert sonst die Antwort
+        .insert_header(("X-Accel-Buffering", "no"))
+        .streaming(stream))
+}
+
 async fn list_processed_folders(
     req: HttpRequest,
     state: web::Data<AppState>,
@@ -1016,11 +1583,14 @@ async fn list_processed_folders(
         .unwrap_or("pending")
         .to_ascii_lowercase();
     let include_pipeline = matches!(stage.as_str(), "completed" | "finished");
+    let label_filter = query.reference.label();
+    let ref_filter = query.reference.external_ref();
     let rows = if include_pipeline {
         client
             .query(
                 "SELECT sp.id, sp.folder_id, sp.folder_name, sp.status, sp.progress, sp.message, sp.tenant_id,
                         sp.pipeline_id, sp.pipeline_run_id, sp.upload_id, sp.pdf_id, sp.created_at, sp.updated_at,
+                        sp.job_label, sp.external_ref,
                         u.status AS upload_status, pr.status AS pipeline_status, pr.error AS pipeline_error,
                         pr.started_at AS pipeline_started_at, pr.finished_at AS pipeline_finished_at
                  FROM sharepoint_jobs sp
@@ -1028,8 +1598,10 @@ async fn list_processed_folders(
                  LEFT JOIN pipeline_runs pr ON pr.id = sp.pipeline_run_id
                  WHERE sp.status = 'succeeded' AND sp.upload_id IS NOT NULL AND sp.pipeline_run_id IS NOT NULL
                        AND lower(u.status) = 'ready'
+                       AND ($1::text IS NULL OR sp.job_label ILIKE '%' || $1 || '%')
+                       AND ($2::text IS NULL OR sp.external_ref = $2)
                  ORDER BY sp.updated_at DESC",
-                &[],
+                &[&label_filter, &ref_filter],
             )
             .await
             .map_err(actix_web::error::ErrorInternalServerError)?
@@ -1038,13 +1610,15 @@ async fn list_processed_folders(
             .query(
                 "SELECT sp.id, sp.folder_id, sp.folder_name, sp.status, sp.progress, sp.message, sp.tenant_id,
                         sp.pipeline_id, sp.pipeline_run_id, sp.upload_id, sp.pdf_id, sp.created_at, sp.updated_at,
-                        u.status AS upload_status
+                        sp.job_label, sp.external_ref, u.status AS upload_status
                  FROM sharepoint_jobs sp
                  JOIN uploads u ON u.id = sp.upload_id
                  WHERE sp.status = 'succeeded' AND sp.upload_id IS NOT NULL AND lower(u.status) = 'ready'
                        AND sp.pipeline_run_id IS NULL
+                       AND ($1::text IS NULL OR sp.job_label ILIKE '%' || $1 || '%')
+                       AND ($2::text IS NULL OR sp.external_ref = $2)
                  ORDER BY sp.updated_at DESC",
-                &[],
+                &[&label_filter, &ref_filter],
             )
             .await
             .map_err(actix_web::error::ErrorInternalServerError)?
@@ -1066,6 +1640,7 @@ async fn list_processed_folders(
             let status: Option<String> = row.get("pipeline_status");
             let category = status.as_deref().map(|value| map_pipeline_status(value));
             let progress = status.as_deref().map(|value| map_pipeline_progress(value));
+            let status = status.as_deref().map(RunStatus::canonical);
             let error = row.get::<_, Option<String>>("pipeline_error");
             let started_at = row.get::<_, Option<DateTime<Utc>>>("pipeline_started_at");
             let finished_at = row.get::<_, Option<DateTime<Utc>>>("pipeline_finished_at");
@@ -1085,6 +1660,8 @@ async fn list_processed_folders(
             pipeline_run_id: row.get("pipeline_run_id"),
             upload_id: row.get("upload_id"),
             pdf_id: row.get("pdf_id"),
+            job_label: row.get("job_label"),
+            external_ref: row.get("external_ref"),
             upload_status: Some(row.get("upload_status")),
             pipeline_status,
             pipeline_status_category,
@@ -1271,6 +1848,65 @@ async fn cancel_job(
     }
 }
 
+#[derive(serde::Deserialize, Default)]
+struct DeleteJobQuery {
+    #[serde(default)]
+    release_folder: bool,
+}
+
+async fn delete_job(
+    req: HttpRequest,
+    state: web::Data<AppState>,
+    path: web::Path<Uuid>,
+    query: web::Query<DeleteJobQuery>,
+) -> actix_web::Result<HttpResponse> {
+    ensure_authorized(&req, &state.config)?;
+    let job_id = path.into_inner();
+    if state.jobs.get(&job_id).is_none() {
+        return Ok(HttpResponse::NotFound().finish());
+    }
+    let Some(job) = state.jobs.remove(&job_id) else {
+        return Ok(HttpResponse::Conflict().body("job is still active"));
+    };
+    let result = purge::delete_jobs(&state.db_pool, &[job], query.release_folder)
+        .await
+        .map_err(actix_web::error::ErrorInternalServerError)?;
+    Ok(HttpResponse::Ok().json(result))
+}
+
+async fn purge_jobs(
+    req: HttpRequest,
+    state: web::Data<AppState>,
+    payload: web::Json<purge::PurgeFilter>,
+) -> actix_web::Result<HttpResponse> {
+    ensure_authorized(&req, &state.config)?;
+    let filter = payload.into_inner();
+    filter
+        .validate()
+        .map_err(|err| ErrorBadRequest(err.to_string()))?;
+    let jobs: Vec<job::JobState> = state
+        .jobs
+        .list()
+        .into_iter()
+        .filter_map(|summary| {
+            let snapshot = state.jobs.get(&summary.id)?.state.lock().clone();
+            if !filter.matches(&snapshot) {
+                return None;
+            }
+            state.jobs.remove(&summary.id)
+        })
+        .collect();
+    let result = purge::delete_jobs(&state.db_pool, &jobs, filter.release_folders)
+        .await
+        .map_err(actix_web::error::ErrorInternalServerError)?;
+    info!(
+        deleted = result.deleted.len(),
+        retired = result.retired_folders.len(),
+        "purged sharepoint jobs"
+    );
+    Ok(HttpResponse::Ok().json(result))
+}
+
 async fn retry_job(
     req: HttpRequest,
     state: web::Data<AppState>,
@@ -1284,6 +1920,33 @@ async fn retry_job(
     };
     let snapshot = original.state.lock().clone();
     drop(original);
+    let summary = respawn_job(state.get_ref(), snapshot);
+    Ok(HttpResponse::Ok().json(json!({ "job": summary })))
+}
+
+/// Per-file state of a job (see `job_files.rs`); empty for SFTP and IMAP jobs.
+async fn list_job_files(
+    req: HttpRequest,
+    state: web::Data<AppState>,
+    path: web::Path<Uuid>,
+) -> actix_web::Result<HttpResponse> {
+    ensure_authorized(&req, &state.config)?;
+    let job_id = path.into_inner();
+    if state.jobs.get(&job_id).is_none() {
+        return Ok(HttpResponse::NotFound().finish());
+    }
+    let files = job_files::list(&state.db_pool, job_id)
+        .await
+        .map_err(actix_web::error::ErrorInternalServerError)?;
+    Ok(HttpResponse::Ok().json(json!({
+        "job_id": job_id,
+        "counts": job_files::FileCounts::of(&files),
+        "files": files,
+    })))
+}
+
+/// Starts a fresh job with the settings of `snapshot`.
+fn respawn_job(state: &AppState, snapshot: job::JobState) -> job::JobSummary {
     let job = state.jobs.create_job(
         snapshot.folder_id,
         snapshot.folder_name,
@@ -1293,16 +1956,19 @@ async fn retry_job(
         snapshot.tenant_id,
         snapshot.pipeline_id,
         snapshot.auto_managed,
+        snapshot.source,
+        snapshot.reference,
+        snapshot.priority,
     );
     let summary = job_summary(&job);
-    let app_state = state.get_ref().clone();
-    spawn_job_worker(app_state, job);
-    Ok(HttpResponse::Ok().json(json!({ "job": summary })))
+    spawn_job_worker(state.clone(), job);
+    summary
 }
 
 async fn list_all_jobs(
     req: HttpRequest,
     state: web::Data<AppState>,
+    query: web::Query<JobReferenceQuery>,
 ) -> actix_web::Result<impl Responder> {
     ensure_authorized(&req, &state.config)?;
     let client = state
@@ -1314,11 +1980,13 @@ async fn list_all_jobs(
     let sharepoint_rows = client
         .query(
             "SELECT id, folder_name, status, progress, message, pipeline_id, pipeline_run_id,
-                    upload_id, pdf_id, created_at, updated_at
+                    upload_id, pdf_id, job_label, external_ref, created_at, updated_at
              FROM sharepoint_jobs
+             WHERE ($1::text IS NULL OR job_label ILIKE '%' || $1 || '%')
+               AND ($2::text IS NULL OR external_ref = $2)
              ORDER BY created_at DESC
              LIMIT 200",
-            &[],
+            &[&query.label(), &query.external_ref()],
         )
         .await
         .map_err(actix_web::error::ErrorInternalServerError)?;
@@ -1341,6 +2009,8 @@ async fn list_all_jobs(
             pipeline_id: row.get("pipeline_id"),
             pdf_id: row.get("pdf_id"),
             upload_id: row.get("upload_id"),
+            job_label: row.get("job_label"),
+            external_ref: row.get("external_ref"),
             created_at: row.get("created_at"),
             updated_at: Some(row.get("updated_at")),
         });
@@ -1350,13 +2020,17 @@ async fn list_all_jobs(
         .query(
             "SELECT pr.id, pr.status, pr.error, pr.created_at, pr.started_at, pr.finished_at,
                     pr.pipeline_id, sp.id AS sharepoint_job_id, sp.folder_name, sp.upload_id,
-                    sp.pdf_id, p.name
+                    sp.pdf_id, p.name,
+                    COALESCE(pr.job_label, sp.job_label) AS job_label,
+                    COALESCE(pr.external_ref, sp.external_ref) AS external_ref
              FROM pipeline_runs pr
              JOIN sharepoint_jobs sp ON sp.pdf_id = pr.pdf_id
              LEFT JOIN pipelines p ON p.id = pr.pipeline_id
+             WHERE ($1::text IS NULL OR COALESCE(pr.job_label, sp.job_label) ILIKE '%' || $1 || '%')
+               AND ($2::text IS NULL OR COALESCE(pr.external_ref, sp.external_ref) = $2)
              ORDER BY pr.created_at DESC
              LIMIT 200",
-            &[],
+            &[&query.label(), &query.external_ref()],
         )
         .await
         .map_err(actix_web::error::ErrorInternalServerError)?;
@@ -1372,7 +2046,7 @@ async fn list_all_jobs(
         jobs.push(AggregatedJobEntry {
             id: row.get::<_, Uuid>("id").to_string(),
             source: AggregatedJobSource::Pipeline,
-            status: status_text,
+            status: RunStatus::canonical(&status_text),
             status_category,
             progress,
             message: row.get::<_, Option<String>>("error"),
@@ -1382,6 +2056,8 @@ async fn list_all_jobs(
             pipeline_id: row.get("pipeline_id"),
             pdf_id: row.get("pdf_id"),
             upload_id: row.get("upload_id"),
+            job_label: row.get("job_label"),
+            external_ref: row.get("external_ref"),
             created_at,
             updated_at,
         });
@@ -1392,53 +2068,410 @@ async fn list_all_jobs(
     Ok(web::Json(AggregatedJobsResponse { jobs }))
 }
 
-fn spawn_job_worker(state: AppState, job: ManagedJob) {
-    let job_id = job.state.lock().id;
-    let jobs = state.jobs.clone();
-    let graph = state.graph.clone();
-    let uploader = state.uploader.clone();
-    let config = state.config.clone();
-    let semaphore = state.semaphore.clone();
-    let mut control_rx = job.control_tx.subscribe();
-    let pipeline = state.pipeline.clone();
-    let db_pool = state.db_pool.clone();
+#[derive(serde::Deserialize)]
+struct InboxQuery {
+    #[serde(default)]
+    tenant_id: Option<Uuid>,
+    #[serde(default)]
+    limit: Option<i64>,
+}
 
-    let handle = tokio::spawn(async move {
-        jobs.update(&job_id, |s| {
-            s.set_status(JobStatus::Running);
-            s.set_message("job started");
-        });
+/// Work list of the operator landing page, see [`inbox`].
+async fn get_inbox(
+    req: HttpRequest,
+    state: web::Data<AppState>,
+    query: web::Query<InboxQuery>,
+) -> actix_web::Result<impl Responder> {
+    ensure_authorized(&req, &state.config)?;
+    let inbox = inbox::load(&state.db_pool, query.tenant_id, query.limit.unwrap_or(100))
+        .await
+        .map_err(actix_web::error::ErrorInternalServerError)?;
+    Ok(HttpResponse::Ok().json(inbox))
+}
 
-        if let Err(err) = wait_until_running(&jobs, job_id, &mut control_rx).await {
-            handle_control_error(err, &jobs, job_id).await;
-            return;
-        }
+#[derive(serde::Deserialize)]
+struct QuarantineQuery {
+    #[serde(default)]
+    status: Option<String>,
+    #[serde(default)]
+    job_id: Option<Uuid>,
+}
 
-        let permit = match semaphore.acquire_owned().await {
-            Ok(permit) => permit,
-            Err(err) => {
-                jobs.update(&job_id, |s| {
-                    s.set_status(JobStatus::Failed);
-                    s.set_message(format!("failed to schedule job: {err}"));
-                });
-                return;
+#[derive(serde::Deserialize, Default)]
+struct QuarantineReleaseRequest {
+    /// Startet den ursprünglichen Job neu (Default), damit die Datei verarbeitet wird.
+    #[serde(default)]
+    retry: Option<bool>,
+}
+
+async fn list_quarantine(
+    req: HttpRequest,
+    state: web::Data<AppState>,
+    query: web::Query<QuarantineQuery>,
+) -> actix_web::Result<impl Responder> {
+    ensure_authorized(&req, &state.config)?;
+    let status = query
+        .status
+        .as_deref()
+        .map(str::trim)
+        .filter(|v| !v.is_empty());
+    let items = scan::list_quarantine(&state.db_pool, status, query.job_id)
+        .await
+        .map_err(actix_web::error::ErrorInternalServerError)?;
+    Ok(HttpResponse::Ok().json(json!({ "items": items })))
+}
+
+async fn get_quarantine(
+    req: HttpRequest,
+    state: web::Data<AppState>,
+    path: web::Path<Uuid>,
+) -> actix_web::Result<impl Responder> {
+    ensure_authorized(&req, &state.config)?;
+    let entry = scan::load_quarantine(&state.db_pool, path.into_inner())
+        .await
+        .map_err(actix_web::error::ErrorInternalServerError)?;
+    match entry {
+        Some(entry) => Ok(HttpResponse::Ok().json(entry)),
+        None => Ok(HttpResponse::NotFound().finish()),
+    }
+}
+
+/// Returns the quarantined bytes as an opaque download for offline inspection.
+async fn download_quarantine_file(
+    req: HttpRequest,
+    state: web::Data<AppState>,
+    path: web::Path<Uuid>,
+) -> actix_web::Result<HttpResponse> {
+    ensure_authorized(&req, &state.config)?;
+    let entry = scan::load_quarantine(&state.db_pool, path.into_inner())
+        .await
+        .map_err(actix_web::error::ErrorInternalServerError)?;
+    let Some((entry, stored_path)) = entry.and_then(|e| e.stored_path.clone().map(|p| (e, p)))
+    else {
+        return Ok(HttpResponse::NotFound().finish());
+    };
+    let data = match tokio::fs::read(&stored_path).await {
+        Ok(data) => data,
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound => {
+            return Ok(HttpResponse::Gone().finish())
+        }
+        Err(err) => return Err(actix_web::error::ErrorInternalServerError(err)),
+    };
+    Ok(HttpResponse::Ok()
+        .insert_header((header::CONTENT_TYPE, "application/octet-stream"))
+        .insert_header((
+            header::CONTENT_DISPOSITION,
+            format!(
+                "attachment; filename=\"{}.quarantined\"",
+                sanitize_filename(&entry.file_name)
+            ),
+        ))
+        .body(data))
+}
+
+async fn release_quarantine(
+    req: HttpRequest,
+    state: web::Data<AppState>,
+    path: web::Path<Uuid>,
+    payload: Option<web::Json<QuarantineReleaseRequest>>,
+) -> actix_web::Result<impl Responder> {
+    ensure_authorized(&req, &state.config)?;
+    let retry = payload
+        .map(|p| p.into_inner())
+        .unwrap_or_default()
+        .retry
+        .unwrap_or(true);
+    let entry = scan::release_quarantine(&state.db_pool, path.into_inner())
+        .await
+        .map_err(actix_web::error::ErrorInternalServerError)?;
+    let Some(entry) = entry else {
+        return Ok(HttpResponse::NotFound().finish());
+    };
+    info!(quarantine_id = %entry.id, file = %entry.file_name, "quarantined file released");
+    // Freigegebene Dateien passieren den nächsten Scan über ihren SHA-256
+    let snapshot = entry
+        .job_id
+        .filter(|_| retry)
+        .and_then(|job_id| state.jobs.get(&job_id))
+        .map(|job| job.state.lock().clone())
+        .filter(|s| !matches!(s.status, JobStatus::Queued | JobStatus::Running));
+    let job = snapshot.map(|snapshot| respawn_job(state.get_ref(), snapshot));
+    Ok(HttpResponse::Ok().json(json!({ "entry": entry, "job": job })))
+}
+
+async fn delete_quarantine(
+    req: HttpRequest,
+    state: web::Data<AppState>,
+    path: web::Path<Uuid>,
+) -> actix_web::Result<impl Responder> {
+    ensure_authorized(&req, &state.config)?;
+    let entry = scan::delete_quarantine(&state.db_pool, path.into_inner())
+        .await
+        .map_err(actix_web::error::ErrorInternalServerError)?;
+    match entry {
+        Some(_) => Ok(HttpResponse::NoContent().finish()),
+        None => Ok(HttpResponse::NotFound().finish()),
+    }
+}
+
+async fn list_sftp_sources(
+    req: HttpRequest,
+    state: web::Data<AppState>,
+) -> actix_web::Result<impl Responder> {
+    ensure_authorized(&req, &state.config)?;
+    let sources = sftp::list_sources(&state.db_pool)
+        .await
+        .map_err(actix_web::error::ErrorInternalServerError)?;
+    let items: Vec<_> = sources.iter().map(|s| s.summary()).collect();
+    Ok(HttpResponse::Ok().json(json!({ "items": items })))
+}
+
+async fn upsert_sftp_source(
+    req: HttpRequest,
+    state: web::Data<AppState>,
+    path: web::Path<Uuid>,
+    payload: web::Json<SftpSourceInput>,
+) -> actix_web::Result<impl Responder> {
+    ensure_authorized(&req, &state.config)?;
+    let Some(master) = state.sftp.master_key() else {
+        return Ok(HttpResponse::ServiceUnavailable().json(json!({ "error": MASTER_KEY_MISSING })));
+    };
+    let id = path.into_inner();
+    let input = payload.into_inner();
+    let existing = sftp::load_source(&state.db_pool, id)
+        .await
+        .map_err(actix_web::error::ErrorInternalServerError)?;
+    input
+        .validate(existing.is_none())
+        .map_err(|err| ErrorBadRequest(err.to_string()))?;
+    let source = sftp::upsert_source(&state.db_pool, master, id, input)
+        .await
+        .map_err(actix_web::error::ErrorInternalServerError)?;
+    Ok(HttpResponse::Ok().json(source.summary()))
+}
+
+async fn delete_sftp_source(
+    req: HttpRequest,
+    state: web::Data<AppState>,
+    path: web::Path<Uuid>,
+) -> actix_web::Result<impl Responder> {
+    ensure_authorized(&req, &state.config)?;
+    let deleted = sftp::delete_source(&state.db_pool, path.into_inner())
+        .await
+        .map_err(actix_web::error::ErrorInternalServerError)?;
+    if deleted {
+        Ok(HttpResponse::NoContent().finish())
+    } else {
+        Ok(HttpResponse::NotFound().finish())
+    }
+}
+
+async fn poll_sftp_source_now(
+    req: HttpRequest,
+    state: web::Data<AppState>,
+    path: web::Path<Uuid>,
+) -> actix_web::Result<impl Responder> {
+    ensure_authorized(&req, &state.config)?;
+    let source = sftp::load_source(&state.db_pool, path.into_inner())
+        .await
+        .map_err(actix_web::error::ErrorInternalServerError)?;
+    let Some(source) = source else {
+        return Ok(HttpResponse::NotFound().finish());
+    };
+    let job = poll_sftp_source(state.get_ref(), &source)
+        .await
+        .map_err(actix_web::error::ErrorBadGateway)?;
+    Ok(HttpResponse::Ok().json(json!({ "job": job })))
+}
+
+async fn list_s3_sources(
+    req: HttpRequest,
+    state: web::Data<AppState>,
+) -> actix_web::Result<impl Responder> {
+    ensure_authorized(&req, &state.config)?;
+    let sources = s3::list_sources(&state.db_pool)
+        .await
+        .map_err(actix_web::error::ErrorInternalServerError)?;
+    let items: Vec<_> = sources.iter().map(|s| s.summary()).collect();
+    Ok(HttpResponse::Ok().json(json!({ "items": items })))
+}
+
+async fn upsert_s3_source(
+    req: HttpRequest,
+    state: web::Data<AppState>,
+    path: web::Path<Uuid>,
+    payload: web::Json<S3SourceInput>,
+) -> actix_web::Result<impl Responder> {
+    ensure_authorized(&req, &state.config)?;
+    let input = payload.into_inner();
+    // Ohne Master-Key nur Quellen mit den Zugangsdaten aus der Konfiguration
+    if input.has_secret() && state.s3.master_key().is_none() {
+        return Ok(HttpResponse::ServiceUnavailable().json(json!({ "error": MASTER_KEY_MISSING })));
+    }
+    let id = path.into_inner();
+    let existing = s3::load_source(&state.db_pool, id)
+        .await
+        .map_err(actix_web::error::ErrorInternalServerError)?;
+    input
+        .validate(existing.is_none())
+        .map_err(|err| ErrorBadRequest(err.to_string()))?;
+    let source = s3::upsert_source(&state.db_pool, state.s3.master_key(), id, input)
+        .await
+        .map_err(actix_web::error::ErrorInternalServerError)?;
+    Ok(HttpResponse::Ok().json(source.summary()))
+}
+
+async fn delete_s3_source(
+    req: HttpRequest,
+    state: web::Data<AppState>,
+    path: web::Path<Uuid>,
+) -> actix_web::Result<impl Responder> {
+    ensure_authorized(&req, &state.config)?;
+    let deleted = s3::delete_source(&state.db_pool, path.into_inner())
+        .await
+        .map_err(actix_web::error::ErrorInternalServerError)?;
+    if deleted {
+        Ok(HttpResponse::NoContent().finish())
+    } else {
+        Ok(HttpResponse::NotFound().finish())
+    }
+}
+
+async fn list_imap_mailboxes(
+    req: HttpRequest,
+    state: web::Data<AppState>,
+) -> actix_web::Result<impl Responder> {
+    ensure_authorized(&req, &state.config)?;
+    let mailboxes = imap::list_mailboxes(&state.db_pool)
+        .await
+        .map_err(actix_web::error::ErrorInternalServerError)?;
+    let items: Vec<_> = mailboxes.iter().map(|m| m.summary()).collect();
+    Ok(HttpResponse::Ok().json(json!({ "items": items })))
+}
+
+async fn upsert_imap_mailbox(
+    req: HttpRequest,
+    state: web::Data<AppState>,
+    path: web::Path<Uuid>,
+    payload: web::Json<ImapMailboxInput>,
+) -> actix_web::Result<impl Responder> {
+    ensure_authorized(&req, &state.config)?;
+    let Some(master) = state.imap.master_key() else {
+        return Ok(HttpResponse::ServiceUnavailable().json(json!({ "error": MASTER_KEY_MISSING })));
+    };
+    let id = path.into_inner();
+    let input = payload.into_inner();
+    let existing = imap::load_mailbox(&state.db_pool, id)
+        .await
+        .map_err(actix_web::error::ErrorInternalServerError)?;
+    input
+        .validate(existing.is_none())
+        .map_err(|err| ErrorBadRequest(err.to_string()))?;
+    let mailbox = imap::upsert_mailbox(&state.db_pool, master, id, input)
+        .await
+        .map_err(actix_web::error::ErrorInternalServerError)?;
+    Ok(HttpResponse::Ok().json(mailbox.summary()))
+}
+
+async fn delete_imap_mailbox(
+    req: HttpRequest,
+    state: web::Data<AppState>,
+    path: web::Path<Uuid>,
+) -> actix_web::Result<impl Responder> {
+    ensure_authorized(&req, &state.config)?;
+    let deleted = imap::delete_mailbox(&state.db_pool, path.into_inner())
+        .await
+        .map_err(actix_web::error::ErrorInternalServerError)?;
+    if deleted {
+        Ok(HttpResponse::NoContent().finish())
+    } else {
+        Ok(HttpResponse::NotFound().finish())
+    }
+}
+
+async fn poll_imap_mailbox_now(
+    req: HttpRequest,
+    state: web::Data<AppState>,
+    path: web::Path<Uuid>,
+) -> actix_web::Result<impl Responder> {
+    ensure_authorized(&req, &state.config)?;
+    let mailbox = imap::load_mailbox(&state.db_pool, path.into_inner())
+        .await
+        .map_err(actix_web::error::ErrorInternalServerError)?;
+    let Some(mailbox) = mailbox else {
+        return Ok(HttpResponse::NotFound().finish());
+    };
+    let jobs = poll_imap_mailbox(state.get_ref(), &mailbox)
+        .await
+        .map_err(actix_web::error::ErrorBadGateway)?;
+    Ok(HttpResponse::Ok().json(json!({ "jobs": jobs })))
+}
+
+fn spawn_job_worker(state: AppState, job: ManagedJob) {
+    let job_id = job.state.lock().id;
+    let jobs = state.jobs.clone();
+    let mut control_rx = job.control_tx.subscribe();
+    let plan = state.job_plan.clone();
+    let config = state.config.clone();
+    let services = JobServices {
+        config: state.config.clone(),
+        connectors: state.connectors.clone(),
+        uploader: state.uploader.clone(),
+        pipeline: state.pipeline.clone(),
+        sftp: state.sftp.clone(),
+        imap: state.imap.clone(),
+        db_pool: state.db_pool.clone(),
+        jobs: state.jobs.clone(),
+    };
+
+    let handle = tokio::spawn(async move {
+        if let Err(err) = wait_until_running(&jobs, job_id, &mut control_rx).await {
+            handle_control_error(err, &jobs, job_id).await;
+            steps::remove_work_dir(&config, job_id).await;
+            return;
+        }
+
+        jobs.update(&job_id, |s| {
+            s.set_status(JobStatus::Queued);
+            s.set_message("waiting for a free slot");
+        });
+        let acquire = jobs.acquire_slot(&job_id);
+        tokio::pin!(acquire);
+        let slot = loop {
+            tokio::select! {
+                slot = &mut acquire => break slot,
+                changed = control_rx.changed() => {
+                    // Abbruch auch in der Warteschlange; Pause wirkt erst nach dem Start
+                    if changed.is_err() || matches!(*control_rx.borrow(), job::JobCommand::Cancel) {
+                        handle_control_error(JobRunError::Canceled, &jobs, job_id).await;
+                        steps::remove_work_dir(&config, job_id).await;
+                        return;
+                    }
+                }
             }
         };
+        let Some(slot) = slot else {
+            jobs.update(&job_id, |s| {
+                s.set_status(JobStatus::Failed);
+                s.set_message("failed to schedule job");
+            });
+            steps::remove_work_dir(&config, job_id).await;
+            return;
+        };
+        jobs.update(&job_id, |s| {
+            s.set_status(JobStatus::Running);
+            s.set_message("job started");
+        });
 
-        let run_result = run_job_inner(
-            config.clone(),
-            graph.clone(),
-            uploader.clone(),
-            pipeline.clone(),
-            db_pool.clone(),
-            jobs.clone(),
-            job_id,
-            job,
-            control_rx,
-        )
-        .await;
+        let snapshot = job.state.lock().clone();
+        drop(job);
+        let run_result = match JobContext::new(job_id, snapshot, services, control_rx) {
+            Ok(mut ctx) => plan.run(&mut ctx).await,
+            Err(err) => Err(JobRunError::Failure(err)),
+        };
 
-        drop(permit);
+        drop(slot);
+        steps::remove_work_dir(&config, job_id).await;
 
         match run_result {
             Ok(()) => {
@@ -1479,11 +2512,464 @@ fn spawn_folder_poller(state: AppState) {
             if let Err(err) = poll_automation_once(&poll_state).await {
                 warn!(error = %err, "automation poller iteration failed");
             }
+            tokio::select! {
+                _ = sleep(interval) => {}
+                _ = poll_state.poll_trigger.notified() => {
+                    sleep(webhook::DEBOUNCE).await;
+                }
+            }
+        }
+    });
+}
+
+fn spawn_automation_scheduler(state: AppState) {
+    tokio::spawn(async move {
+        loop {
+            if let Err(err) = run_due_schedules(&state).await {
+                warn!(error = %err, "automation schedule iteration failed");
+            }
+            sleep(schedule::TICK).await;
+        }
+    });
+}
+
+/// Imports the folders of all due scheduled rules (see `schedule.rs`).
+async fn run_due_schedules(state: &AppState) -> anyhow::Result<()> {
+    let client = state.db_pool.get().await?;
+    let now = Utc::now();
+    for rule in load_automation_rules(&client).await? {
+        let Some(raw) = rule.schedule.as_deref().filter(|_| rule.auto_ingest) else {
+            continue;
+        };
+        let next = match schedule::Schedule::parse(raw) {
+            Ok(schedule) => schedule.next_after(now),
+            Err(err) => {
+                warn!(folder_id = %rule.folder_id, error = %err, "skipping automation schedule");
+                continue;
+            }
+        };
+        // Ohne Termin (Regel direkt in der Datenbank angelegt) wird nur geplant
+        let due = match rule.next_run_at {
+            Some(next_run_at) if next_run_at > now => continue,
+            Some(_) => true,
+            None => false,
+        };
+        // Nur wer den Termin weiterschiebt, importiert (mehrere Instanzen)
+        let claimed = client
+            .execute(
+                "UPDATE sharepoint_automation SET next_run_at = $3
+                 WHERE folder_id = $1 AND next_run_at IS NOT DISTINCT FROM $2",
+                &[&rule.folder_id, &rule.next_run_at, &next],
+            )
+            .await?;
+        if claimed == 0 || !due {
+            continue;
+        }
+        if let Err(err) = ingest_scheduled_folder(state, &client, &rule).await {
+            warn!(folder_id = %rule.folder_id, error = %err, "scheduled ingest failed");
+        }
+    }
+    Ok(())
+}
+
+async fn ingest_scheduled_folder(
+    state: &AppState,
+    client: &tokio_postgres::Client,
+    rule: &AutomationRecord,
+) -> anyhow::Result<()> {
+    let active = client
+        .query_opt(
+            "SELECT id FROM sharepoint_jobs
+             WHERE folder_id = $1 AND status IN ('queued', 'running', 'paused')
+             LIMIT 1",
+            &[&rule.folder_id],
+        )
+        .await?;
+    if active.is_some() {
+        info!(folder_id = %rule.folder_id, "folder job still active; skipping scheduled ingest");
+        return Ok(());
+    }
+    let mode = rule.reingest.unwrap_or(ReingestMode::AllFiles);
+    let Some(source) =
+        reingest_source(state, client, rule.connector, &rule.folder_id, mode).await?
+    else {
+        info!(folder_id = %rule.folder_id, "no new files; skipping scheduled ingest");
+        return Ok(());
+    };
+
+    let job = state.jobs.create_job(
+        rule.folder_id.clone(),
+        rule.folder_name.clone(),
+        JobOrder::Alpha,
+        None,
+        None,
+        rule.tenant_id,
+        None,
+        true,
+        source,
+        JobReference::new(rule.job_label.clone(), rule.external_ref.clone()),
+        RunPriority::default(),
+    );
+    let job_id = job.state.lock().id;
+    state.jobs.update(&job_id, |s| {
+        s.set_message("Geplanter Import gestartet");
+    });
+    info!(
+        %job_id,
+        folder = %rule.folder_name,
+        schedule = rule.schedule.as_deref().unwrap_or_default(),
+        "scheduled automation job created"
+    );
+    spawn_job_worker(state.clone(), job);
+    Ok(())
+}
+
+/// Source of a new job of `folder_id` for the PDFs missing in its inventory
+/// (see `inventory.rs`); `None` if there are none. Folders without inventory
+/// are imported as a whole.
+async fn reingest_source(
+    state: &AppState,
+    client: &tokio_postgres::Client,
+    connector: ConnectorKind,
+    folder_id: &str,
+    mode: ReingestMode,
+) -> anyhow::Result<Option<JobSource>> {
+    let listed = state
+        .connectors
+        .get(connector)?
+        .list_pdfs(folder_id)
+        .await?;
+    let known = inventory::load(client, folder_id).await?;
+    let changed = inventory::changed_files(&listed, &known);
+    if changed.is_empty() {
+        return Ok(None);
+    }
+    let file_ids = match mode {
+        ReingestMode::NewFiles if !known.is_empty() => {
+            Some(changed.into_iter().map(|file| file.id).collect())
+        }
+        _ => None,
+    };
+    Ok(Some(JobSource::folder(connector, file_ids)))
+}
+
+fn spawn_subscription_manager(state: AppState) {
+    let Some(notification_url) = state.config.graph_webhook_url.clone() else {
+        info!("GRAPH_WEBHOOK_URL not set; graph change notifications disabled");
+        return;
+    };
+    tokio::spawn(async move {
+        // Graph prüft die URL beim Anlegen, der HTTP-Server muss schon laufen
+        sleep(std::time::Duration::from_secs(5)).await;
+        loop {
+            let result = match state.db_pool.get().await {
+                Ok(client) => {
+                    webhook::ensure_subscription(&state.graph, &client, &notification_url).await
+                }
+                Err(err) => Err(err.into()),
+            };
+            let next = match result {
+                Ok(()) => webhook::CHECK_INTERVAL,
+                Err(err) => {
+                    warn!(error = %err, "graph subscription check failed");
+                    std::time::Duration::from_secs(60)
+                }
+            };
+            sleep(next).await;
+        }
+    });
+}
+
+#[derive(serde::Deserialize)]
+struct NotificationQuery {
+    #[serde(rename = "validationToken")]
+    validation_token: Option<String>,
+}
+
+/// Receives Graph change notifications; Graph checks the endpoint with a
+/// `validationToken` that has to be echoed as plain text.
+async fn graph_notifications(
+    state: web::Data<AppState>,
+    query: web::Query<NotificationQuery>,
+    body: web::Bytes,
+) -> HttpResponse {
+    if let Some(token) = &query.validation_token {
+        return HttpResponse::Ok()
+            .content_type("text/plain")
+            .body(token.clone());
+    }
+    let batch: webhook::NotificationBatch = match serde_json::from_slice(&body) {
+        Ok(batch) => batch,
+        Err(err) => {
+            warn!(error = %err, "invalid graph notification payload");
+            return HttpResponse::BadRequest().finish();
+        }
+    };
+    let client_states = match state.db_pool.get().await {
+        Ok(client) => webhook::client_states(&client).await,
+        Err(err) => Err(err.into()),
+    };
+    match client_states {
+        Ok(client_states) => {
+            let accepted = webhook::accepted(&batch.value, &client_states);
+            if accepted > 0 {
+                info!(
+                    notifications = accepted,
+                    "graph change notification received"
+                );
+                state.poll_trigger.notify_one();
+            } else {
+                warn!(
+                    notifications = batch.value.len(),
+                    "graph notifications without known subscription ignored"
+                );
+            }
+        }
+        Err(err) => {
+            // Im Zweifel pollen, das Delta zeigt ohnehin nur echte Änderungen
+            warn!(error = %err, "failed to load graph subscriptions");
+            state.poll_trigger.notify_one();
+        }
+    }
+    HttpResponse::Accepted().finish()
+}
+
+fn spawn_sftp_poller(state: AppState) {
+    let interval = state.config.sftp_poll_interval;
+    if interval.is_zero() {
+        info!("sftp poll interval is zero; sftp poller disabled");
+        return;
+    }
+    let poll_state = state.clone();
+    tokio::spawn(async move {
+        loop {
+            match sftp::list_sources(&poll_state.db_pool).await {
+                Ok(sources) => {
+                    for source in sources.iter().filter(|s| s.enabled) {
+                        if let Err(err) = poll_sftp_source(&poll_state, source).await {
+                            warn!(source_id = %source.id, error = %err, "sftp poll failed");
+                        }
+                    }
+                }
+                Err(err) => warn!(error = %err, "failed to load sftp sources"),
+            }
+            sleep(interval).await;
+        }
+    });
+}
+
+/// Creates one job for all new files of an SFTP source.
+async fn poll_sftp_source(
+    state: &AppState,
+    source: &sftp::SftpSource,
+) -> anyhow::Result<Option<job::JobSummary>> {
+    let listed = match state.sftp.list(source).await {
+        Ok(files) => files,
+        Err(err) => {
+            let message = format!("{err:#}");
+            if let Err(record_err) =
+                sftp::record_poll(&state.db_pool, source.id, Some(&message)).await
+            {
+                warn!(source_id = %source.id, error = %record_err, "failed to record sftp poll");
+            }
+            return Err(err);
+        }
+    };
+    let files = sftp::unseen_files(&state.db_pool, source.id, listed).await?;
+    sftp::record_poll(&state.db_pool, source.id, None).await?;
+    if files.is_empty() {
+        return Ok(None);
+    }
+
+    let folder_name = format!("{} {}", source.name, Utc::now().format("%Y-%m-%d %H:%M"));
+    let job = state.jobs.create_job(
+        source.job_folder_id(),
+        folder_name,
+        JobOrder::Alpha,
+        None,
+        None,
+        source.tenant_id,
+        source.pipeline_id,
+        true,
+        JobSource::Sftp {
+            source_id: source.id,
+            files: files.iter().map(|f| f.path.clone()).collect(),
+        },
+        JobReference::default(),
+        RunPriority::default(),
+    );
+    let job_id = job.state.lock().id;
+    sftp::mark_seen(&state.db_pool, source.id, &files, job_id).await?;
+    state.jobs.update(&job_id, |s| {
+        s.set_message(format!("SFTP-Import gestartet ({} Dateien)", files.len()));
+    });
+    info!(source_id = %source.id, %job_id, files = files.len(), "sftp job created");
+    let summary = job_summary(&job);
+    spawn_job_worker(state.clone(), job);
+    Ok(Some(summary))
+}
+
+fn spawn_imap_poller(state: AppState) {
+    let interval = state.config.imap_poll_interval;
+    if interval.is_zero() {
+        info!("imap poll interval is zero; imap poller disabled");
+        return;
+    }
+    let poll_state = state.clone();
+    tokio::spawn(async move {
+        loop {
+            match imap::list_mailboxes(&poll_state.db_pool).await {
+                Ok(mailboxes) => {
+                    for mailbox in mailboxes.iter().filter(|m| m.enabled) {
+                        if let Err(err) = poll_imap_mailbox(&poll_state, mailbox).await {
+                            warn!(mailbox_id = %mailbox.id, error = %err, "imap poll failed");
+                        }
+                    }
+                }
+                Err(err) => warn!(error = %err, "failed to load imap mailboxes"),
+            }
             sleep(interval).await;
         }
     });
 }
 
+/// Creates one job per new mail with PDF attachments and tags the mails.
+async fn poll_imap_mailbox(
+    state: &AppState,
+    mailbox: &imap::ImapMailbox,
+) -> anyhow::Result<Vec<job::JobSummary>> {
+    let result = poll_imap_mailbox_inner(state, mailbox).await;
+    let error = result.as_ref().err().map(|err| format!("{err:#}"));
+    if let Err(err) = imap::record_poll(&state.db_pool, mailbox.id, error.as_deref()).await {
+        warn!(mailbox_id = %mailbox.id, error = %err, "failed to record imap poll");
+    }
+    result
+}
+
+async fn poll_imap_mailbox_inner(
+    state: &AppState,
+    mailbox: &imap::ImapMailbox,
+) -> anyhow::Result<Vec<job::JobSummary>> {
+    let mut session = state.imap.open(mailbox).await?;
+    let uids = session.search_without(&mailbox.processed_keyword).await?;
+    let mut created = Vec::new();
+    for uid in uids.into_iter().take(imap::MAX_MESSAGES_PER_POLL) {
+        let raw = session.fetch(uid).await?;
+        let mail = match imap::MailInfo::parse(&raw) {
+            Ok(mail) => mail,
+            Err(err) => {
+                warn!(mailbox_id = %mailbox.id, uid, error = %err, "skipping unparseable mail");
+                session
+                    .add_keywords(uid, &[&mailbox.processed_keyword])
+                    .await?;
+                continue;
+            }
+        };
+        if imap::is_known_message(
+            &state.db_pool,
+            mailbox.id,
+            session.uid_validity,
+            uid,
+            mail.message_id.as_deref(),
+        )
+        .await?
+        {
+            // schon importiert (Keyword verloren, Kopie oder neue UIDVALIDITY): nur markieren
+            info!(
+                mailbox_id = %mailbox.id,
+                uid,
+                message_id = ?mail.message_id,
+                "skipping known mail"
+            );
+            session
+                .add_keywords(uid, &[&mailbox.processed_keyword])
+                .await?;
+            continue;
+        }
+        if mail.attachments.is_empty() {
+            // ohne PDF-Anhang kein Job, die Mail wird trotzdem als verarbeitet markiert
+            imap::record_message(
+                &state.db_pool,
+                mailbox.id,
+                session.uid_validity,
+                uid,
+                &mail,
+                None,
+            )
+            .await?;
+            session
+                .add_keywords(uid, &[&mailbox.processed_keyword])
+                .await?;
+            continue;
+        }
+
+        let job = state.jobs.create_job(
+            mailbox.job_folder_id(),
+            mail.job_name(&mailbox.name),
+            JobOrder::Alpha,
+            None,
+            None,
+            mailbox.tenant_id,
+            mailbox.pipeline_id,
+            true,
+            JobSource::Imap {
+                mailbox_id: mailbox.id,
+                uid_validity: session.uid_validity,
+                uid,
+            },
+            JobReference::default(),
+            RunPriority::default(),
+        );
+        let job_id = job.state.lock().id;
+        imap::record_message(
+            &state.db_pool,
+            mailbox.id,
+            session.uid_validity,
+            uid,
+            &mail,
+            Some(job_id),
+        )
+        .await?;
+        session
+            .add_keywords(
+                uid,
+                &[&mailbox.processed_keyword, &format!("Regress-Job-{job_id}")],
+            )
+            .await?;
+        state.jobs.update(&job_id, |s| {
+            s.set_message(format!(
+                "E-Mail-Import gestartet ({} Anhänge)",
+                mail.attachments.len()
+            ));
+        });
+        info!(mailbox_id = %mailbox.id, uid, %job_id, "imap job created");
+        created.push(job_summary(&job));
+        spawn_job_worker(state.clone(), job);
+    }
+    session.logout().await;
+    Ok(created)
+}
+
+/// Tags the source mail of an IMAP job with its pipeline run (best effort).
+async fn tag_imap_run(state: &AppState, job_id: Uuid, run_id: Uuid) -> anyhow::Result<()> {
+    let Some((mailbox_id, uid_validity, uid)) =
+        imap::untagged_message_for_job(&state.db_pool, job_id, run_id).await?
+    else {
+        return Ok(());
+    };
+    let Some(mailbox) = imap::load_mailbox(&state.db_pool, mailbox_id).await? else {
+        return Ok(());
+    };
+    let mut session = state.imap.open(&mailbox).await?;
+    if session.uid_validity == uid_validity {
+        session
+            .add_keywords(uid, &[&format!("Regress-Run-{run_id}")])
+            .await?;
+    }
+    session.logout().await;
+    imap::set_message_run(&state.db_pool, job_id, run_id).await
+}
+
 fn spawn_pipeline_result_consumer(state: AppState) {
     let Some(broker) = state
         .config
@@ -1522,21 +3008,116 @@ fn ensure_ingest_pipeline_disabled(rule: &mut AutomationRecord) -> bool {
     changed
 }
 
-async fn poll_automation_once(state: &AppState) -> anyhow::Result<()> {
-    let folders = state
-        .graph
+/// Folders one automation poll looks at and the delta links around it.
+struct PollFolders {
+    folders: Vec<GraphFolder>,
+    drive_id: String,
+    /// Link the poll started from; `None` on the first poll of the drive.
+    previous_link: Option<String>,
+    /// Link to store once the poll is done.
+    delta_link: String,
+}
+
+/// Changed subfolders of the input folder since the stored delta link, or all
+/// of them without a (valid) link (see `delta.rs`).
+async fn folders_to_poll(
+    state: &AppState,
+    client: &tokio_postgres::Client,
+) -> anyhow::Result<PollFolders> {
+    let graph = &state.graph;
+    let drive_id = graph.drive_id().await?;
+    let previous_link = delta::load_link(client, &drive_id).await?;
+    if let Some(link) = &previous_link {
+        match graph.delta_since(link).await? {
+            Some(delta) => {
+                let mut folders = Vec::new();
+                if let Some(input) = graph.item_by_path(&state.config.drive_input_path()).await? {
+                    let changed = delta::changed_folders(&delta.changes, &input.id);
+                    folders = changed.folders;
+                    for folder_id in changed.unresolved {
+                        match graph.item_by_id(&folder_id).await? {
+                            Some(item)
+                                if item.is_folder
+                                    && item.parent_id.as_deref() == Some(input.id.as_str()) =>
+                            {
+                                folders.push(GraphFolder {
+                                    id: item.id,
+                                    name: item.name,
+                                    file_count: item.child_count,
+                                });
+                            }
+                            _ => {}
+                        }
+                    }
+                }
+                info!(
+                    changes = delta.changes.len(),
+                    folders = folders.len(),
+                    "sharepoint delta loaded"
+                );
+                return Ok(PollFolders {
+                    folders,
+                    drive_id,
+                    previous_link,
+                    delta_link: delta.delta_link,
+                });
+            }
+            None => warn!("sharepoint delta link expired; listing all folders"),
+        }
+    }
+    // Link vor der Auflistung holen, damit Änderungen währenddessen im nächsten Delta stehen
+    let delta_link = graph.latest_delta_link().await?;
+    let folders = graph
         .list_subfolders(&state.config.drive_input_path())
         .await?;
-    if folders.is_empty() {
-        return Ok(());
+    info!(folders = folders.len(), "sharepoint folders listed");
+    Ok(PollFolders {
+        folders,
+        drive_id,
+        previous_link,
+        delta_link,
+    })
+}
+
+/// Folders of the rules on other connectors than SharePoint.
+async fn connector_folders(
+    state: &AppState,
+    rules: &HashMap<String, AutomationRecord>,
+) -> Vec<GraphFolder> {
+    let kinds: HashSet<ConnectorKind> = rules
+        .values()
+        .filter(|rule| rule.auto_ingest && rule.connector != ConnectorKind::SharePoint)
+        .map(|rule| rule.connector)
+        .collect();
+    let mut folders = Vec::new();
+    for kind in kinds {
+        let listed = match state.connectors.get(kind) {
+            Ok(connector) => connector.list_folders().await,
+            Err(err) => Err(err),
+        };
+        match listed {
+            Ok(listed) => folders.extend(listed.into_iter().filter(|folder| {
+                rules
+                    .get(&folder.id)
+                    .is_some_and(|rule| rule.connector == kind)
+            })),
+            Err(err) => {
+                warn!(connector = kind.as_str(), error = %err, "connector folder listing failed")
+            }
+        }
     }
+    folders
+}
+
+async fn poll_automation_once(state: &AppState) -> anyhow::Result<()> {
+    let client = state.db_pool.get().await?;
+    let poll = folders_to_poll(state, &client).await?;
 
     let mut folder_map: HashMap<String, GraphFolder> = HashMap::new();
-    for folder in folders {
+    for folder in poll.folders {
         folder_map.insert(folder.id.clone(), folder);
     }
 
-    let client = state.db_pool.get().await?;
     let defaults = load_automation_defaults(&client).await?;
     let mut defaults_map: HashMap<String, DefaultAutomationSettings> = HashMap::new();
     for default in defaults {
@@ -1546,6 +3127,7 @@ async fn poll_automation_once(state: &AppState) -> anyhow::Result<()> {
     let processing_default = defaults_map.get("processing").cloned();
 
     let rules = load_automation_rules(&client).await?;
+    let retired = purge::retired_folders(&client).await?;
     let mut rule_map: HashMap<String, AutomationRecord> = rules
         .into_iter()
         .map(|record| (record.folder_id.clone(), record))
@@ -1595,7 +3177,8 @@ async fn poll_automation_once(state: &AppState) -> anyhow::Result<()> {
                              folder_id, folder_name, tenant_id, pipeline_id, auto_ingest, auto_pipeline, managed_by_default, last_seen
                          ) VALUES ($1, $2, $3, NULL, TRUE, FALSE, TRUE, $4)
                          ON CONFLICT (folder_id) DO NOTHING
-                         RETURNING folder_id, folder_name, tenant_id, pipeline_id, auto_ingest, auto_pipeline, managed_by_default, last_seen, updated_at",
+                         RETURNING folder_id, folder_name, tenant_id, pipeline_id, auto_ingest, auto_pipeline, managed_by_default,
+                                   job_label, external_ref, schedule, next_run_at, reingest, connector, post_actions, last_seen, updated_at",
                         &[&folder.id, &folder.name, &default.tenant_id, &now],
                     )
                     .await?;
@@ -1615,6 +3198,11 @@ async fn poll_automation_once(state: &AppState) -> anyhow::Result<()> {
         }
     }
 
+    // OneDrive und lokale Ordner haben kein Delta und werden vollständig gelistet
+    for folder in connector_folders(state, &rule_map).await {
+        folder_map.entry(folder.id.clone()).or_insert(folder);
+    }
+
     for folder in folder_map.values() {
         let _ = client
             .execute(
@@ -1651,18 +3239,51 @@ async fn poll_automation_once(state: &AppState) -> anyhow::Result<()> {
                 .await?;
         }
 
+        // Geplante Ordner importiert der Schedule-Task
+        if rule.schedule.is_some() {
+            continue;
+        }
+
         let Some(folder) = folder_map.get(&rule.folder_id) else {
             continue;
         };
 
-        let existing = client
-            .query_opt(
-                "SELECT id FROM sharepoint_jobs WHERE folder_id = $1",
+        if retired.contains(&rule.folder_id) {
+            continue;
+        }
+        let statuses: Vec<String> = client
+            .query(
+                "SELECT DISTINCT status FROM sharepoint_jobs WHERE folder_id = $1",
                 &[&rule.folder_id],
             )
-            .await?;
-        if existing.is_some() {
-            continue;
+            .await?
+            .iter()
+            .map(|row| row.get("status"))
+            .collect();
+        let mut source = JobSource::folder(rule.connector, None);
+        let mut reingest = false;
+        if !statuses.is_empty() {
+            // Bereits importierte Ordner nur mit Re-Ingest, ohne aktiven Job und nach Erfolg
+            let Some(mode) = rule.reingest else {
+                continue;
+            };
+            let idle = statuses.iter().all(|status| {
+                JobStatus::from_str(status)
+                    .map(|status| status.is_finished())
+                    .unwrap_or(true)
+            });
+            if !idle || !statuses.iter().any(|status| status == "succeeded") {
+                continue;
+            }
+            match reingest_source(state, &client, rule.connector, &folder.id, mode).await {
+                Ok(Some(changed)) => source = changed,
+                Ok(None) => continue,
+                Err(err) => {
+                    warn!(folder_id = %folder.id, error = %err, "re-ingest check failed");
+                    continue;
+                }
+            }
+            reingest = true;
         }
 
         let job = state.jobs.create_job(
@@ -1674,6 +3295,9 @@ async fn poll_automation_once(state: &AppState) -> anyhow::Result<()> {
             rule.tenant_id,
             None,
             true,
+            source,
+            JobReference::new(rule.job_label.clone(), rule.external_ref.clone()),
+            RunPriority::default(),
         );
         let job_id = job.state.lock().id;
         let source = if rule.managed_by_default {
@@ -1682,7 +3306,9 @@ async fn poll_automation_once(state: &AppState) -> anyhow::Result<()> {
             "folder"
         };
         state.jobs.update(&job_id, |s| {
-            if rule.managed_by_default {
+            if reingest {
+                s.set_message("Neue Dateien seit letztem Import");
+            } else if rule.managed_by_default {
                 s.set_message("Automatischer Import (global) gestartet");
             } else {
                 s.set_message("Automatischer Import gestartet");
@@ -1694,10 +3320,21 @@ async fn poll_automation_once(state: &AppState) -> anyhow::Result<()> {
             auto_pipeline = rule.auto_pipeline,
             pipeline_reset = pipeline_reset,
             source = source,
+            reingest,
             "automation job created"
         );
         spawn_job_worker(state.clone(), job);
     }
+    let advanced = delta::advance_link(
+        &client,
+        &poll.drive_id,
+        poll.previous_link.as_deref(),
+        &poll.delta_link,
+    )
+    .await?;
+    if !advanced {
+        info!("sharepoint delta state changed during poll; keeping it");
+    }
 
     if let Some(default) = processing_default {
         if default.enabled {
@@ -1802,7 +3439,7 @@ async fn handle_pipeline_result(state: &AppState, result: PipelineRunResult) ->
     let client = state.db_pool.get().await?;
     let row = client
         .query_opt(
-            "SELECT id FROM sharepoint_jobs WHERE pdf_id = $1 ORDER BY created_at DESC LIMIT 1",
+            "SELECT id, folder_id, source FROM sharepoint_jobs WHERE pdf_id = $1 ORDER BY created_at DESC LIMIT 1",
             &[&result.pdf_id],
         )
         .await?;
@@ -1827,11 +3464,16 @@ async fn handle_pipeline_result(state: &AppState, result: PipelineRunResult) ->
         )
         .await?;
 
-    let status_text = result
-        .status
-        .clone()
-        .unwrap_or_else(|| "finished".to_string());
+    let run_status = result.status.unwrap_or(RunStatus::Completed);
     let run_id = result.run_id;
+    if let Some(run_id) = run_id {
+        let tag_state = state.clone();
+        tokio::spawn(async move {
+            if let Err(err) = tag_imap_run(&tag_state, job_id, run_id).await {
+                warn!(%job_id, %run_id, error = %err, "failed to tag imap mail with run");
+            }
+        });
+    }
     state.jobs.update(&job_id, |state| {
         if state.pipeline_id.is_none() {
             state.pipeline_id = Some(result.pipeline_id);
@@ -1839,10 +3481,9 @@ async fn handle_pipeline_result(state: &AppState, result: PipelineRunResult) ->
         if let Some(run_id) = run_id {
             state.pipeline_run_id = Some(run_id);
         }
-        let category = map_pipeline_status(&status_text);
-        let mut message = match category {
+        let mut message = match job_status_for_run(run_status) {
             JobStatus::Succeeded => "Pipeline abgeschlossen".to_string(),
-            JobStatus::Failed => format!("Pipeline fehlgeschlagen ({status_text})"),
+            JobStatus::Failed => format!("Pipeline fehlgeschlagen ({run_status})"),
             JobStatus::Running => "Pipeline gestartet".to_string(),
             JobStatus::Queued => "Pipeline eingereiht".to_string(),
             JobStatus::Canceled => "Pipeline abgebrochen".to_string(),
@@ -1854,166 +3495,81 @@ async fn handle_pipeline_result(state: &AppState, result: PipelineRunResult) ->
         state.set_message(message);
     });
 
+    if matches!(run_status, RunStatus::Completed | RunStatus::Failed) {
+        let folder_id: String = row.get("folder_id");
+        let source = row
+            .get::<_, Option<serde_json::Value>>("source")
+            .and_then(|value| serde_json::from_value::<JobSource>(value).ok())
+            .unwrap_or_default();
+        let action_state = state.clone();
+        tokio::spawn(async move {
+            if let Err(err) = run_post_actions(
+                &action_state,
+                job_id,
+                &folder_id,
+                &source,
+                &result,
+                run_status,
+            )
+            .await
+            {
+                warn!(%job_id, error = %err, "failed to run post actions");
+            }
+        });
+    }
+
     Ok(())
 }
 
-async fn run_job_inner(
-    config: Arc<Config>,
-    graph: Arc<MsGraphClient>,
-    uploader: Arc<UploadAdapter>,
-    pipeline: Arc<PipelineAdapter>,
-    db_pool: Pool,
-    jobs: JobRegistry,
+/// Runs the post-actions of the folder's automation rule once per job (see
+/// `post_action.rs`).
+async fn run_post_actions(
+    state: &AppState,
     job_id: Uuid,
-    job: ManagedJob,
-    mut control_rx: watch::Receiver<job::JobCommand>,
-) -> Result<(), JobRunError> {
-    let snapshot = job.state.lock().clone();
-    drop(job);
-
-    let files = graph
-        .list_pdfs_in_folder(&snapshot.folder_id)
-        .await
-        .map_err(JobRunError::Failure)?;
-    if files.is_empty() {
-        return Err(JobRunError::Failure(anyhow!("no pdf files found")));
+    folder_id: &str,
+    source: &JobSource,
+    result: &PipelineRunResult,
+    status: RunStatus,
+) -> anyhow::Result<()> {
+    let Some((connector, _)) = source.folder_connector() else {
+        return Ok(());
+    };
+    let client = state.db_pool.get().await?;
+    let Some(rule) = load_automation_rule(&client, folder_id).await? else {
+        return Ok(());
+    };
+    // Gleiche Ordner-ID in einem anderen Laufwerk
+    if rule.connector != connector || !rule.post_actions.iter().any(|a| a.on.matches(status)) {
+        return Ok(());
     }
-
-    let ordered = order_files(
-        files,
-        snapshot.order.clone(),
-        snapshot.filenames_override.clone(),
-    );
-    let temp_dir = tempfile::tempdir().map_err(|err| JobRunError::Failure(err.into()))?;
-    let total = ordered.len();
-    let download_weight = 0.5f32;
-    let merge_weight = 0.3f32;
-    let upload_weight = 0.2f32;
-
-    let mut downloaded = Vec::new();
-    for (idx, file) in ordered.iter().enumerate() {
-        wait_until_running(&jobs, job_id, &mut control_rx).await?;
-        let filename = format!("{idx:03}-{}", sanitize_filename(&file.name));
-        let dest = temp_dir.path().join(&filename);
-        graph
-            .download_file(&file.id, &dest)
-            .await
-            .map_err(JobRunError::Failure)?;
-        downloaded.push(dest);
-        let progress = download_weight * ((idx + 1) as f32 / total as f32);
-        jobs.update(&job_id, |s| {
-            s.set_progress(progress);
-            s.set_message(format!("downloaded {}/{}", idx + 1, total));
-        });
+    let Some(graph) = state.connectors.get(connector)?.graph() else {
+        warn!(%job_id, connector = connector.as_str(), "post actions need a graph folder");
+        return Ok(());
+    };
+    if !post_action::claim(&client, job_id, result.run_id).await? {
+        return Ok(());
     }
-
-    wait_until_running(&jobs, job_id, &mut control_rx).await?;
-    let merged_path = temp_dir.path().join("merged.pdf");
-    merge_pdfs(&downloaded, &merged_path).map_err(JobRunError::Failure)?;
-    jobs.update(&job_id, |s| {
-        s.set_progress(download_weight + merge_weight);
-        s.set_message("pdf merged");
-    });
-
-    wait_until_running(&jobs, job_id, &mut control_rx).await?;
-    // Validate merged PDF before uploading
-    assert_pdf(&merged_path).map_err(JobRunError::Failure)?;
-    let scan_cfg = ScanConfig::from_env();
-    scan_with_clamd(&merged_path, &scan_cfg)
-        .await
-        .map_err(JobRunError::Failure)?;
-    jobs.update(&job_id, |s| {
-        s.set_message("security scan passed");
-    });
-    let upload_name = format!("{}-merged.pdf", sanitize_filename(&snapshot.folder_name));
-    let upload_override = snapshot.upload_url.clone();
-    let tenant_override = snapshot.tenant_id;
-    let upload_result = uploader
-        .upload(
-            &merged_path,
-            &upload_name,
-            upload_override.as_deref(),
-            tenant_override,
-            snapshot.pipeline_id,
-        )
-        .await
-        .map_err(JobRunError::Failure)?;
-    jobs.update(&job_id, |s| {
-        s.set_progress(download_weight + merge_weight + upload_weight * 0.5);
-        s.set_message("upload completed");
-        s.set_output(upload_result.clone());
-    });
-
-    let state_pipeline_id = jobs
-        .get(&job_id)
-        .and_then(|managed| managed.state.lock().pipeline_id);
-    let pipeline_assigned_in_state = state_pipeline_id.is_some();
-    let snapshot_pipeline_id = snapshot.pipeline_id;
-    let pipeline_candidate = state_pipeline_id.or(snapshot_pipeline_id);
-    let pipeline_id = if pipeline_candidate.is_some()
-        && (snapshot_pipeline_id.is_some() || !snapshot.auto_managed || pipeline_assigned_in_state)
-    {
-        pipeline_candidate
-    } else {
-        None
+    drop(client);
+
+    let ctx = post_action::PostActionContext {
+        job_id,
+        folder_id,
+        status,
+        result,
+        processed_folder: &state.config.drive_processed_path(),
+        failed_folder: &state.config.drive_failed_path(),
     };
-
-    let upload_ready = upload_result.upload_id;
-
-    if let Some(pipeline_id) = pipeline_id {
-        if let Some(upload_id) = upload_ready {
-            jobs.update(&job_id, |s| {
-                s.pipeline_id = Some(pipeline_id);
-                s.set_message("prüfe Upload-Status für Pipeline");
-            });
-            let ready = wait_for_upload_ready(
-                &db_pool,
-                upload_id,
-                config.upload_ready_poll_attempts,
-                config.upload_ready_poll_interval,
-            )
-            .await
-            .map_err(JobRunError::Failure)?;
-
-            if ready {
-                match pipeline.start_run(pipeline_id, upload_id).await {
-                    Ok(_) => {
-                        info!(%job_id, %upload_id, %pipeline_id, "pipeline run started automatically");
-                        jobs.update(&job_id, |s| {
-                            s.pipeline_id = Some(pipeline_id);
-                            s.set_message("Pipeline automatisch gestartet");
-                        });
-                    }
-                    Err(err) => {
-                        warn!(%job_id, %upload_id, error = %err, "automatic pipeline start failed");
-                        jobs.update(&job_id, |s| {
-                            s.set_message(format!("Pipeline-Start fehlgeschlagen: {err}"));
-                        });
-                    }
-                }
-            } else {
-                warn!(%job_id, %upload_id, "upload not ready for pipeline start");
-                jobs.update(&job_id, |s| {
-                    s.set_message("Upload noch nicht bereit für Pipeline");
-                });
-            }
-        } else {
-            warn!(%job_id, "upload id missing; cannot start pipeline automatically");
-            jobs.update(&job_id, |s| {
-                s.set_message("Upload-ID fehlt für Pipeline-Start");
-            });
-        }
-    } else {
-        jobs.update(&job_id, |s| {
-            s.set_message("bereit für Pipeline-Verarbeitung");
-        });
+    let outcomes = post_action::execute(&graph, &ctx, &rule.post_actions).await;
+    for outcome in outcomes.iter().filter(|outcome| !outcome.ok) {
+        warn!(
+            %job_id,
+            action = outcome.kind,
+            error = outcome.detail.as_deref().unwrap_or_default(),
+            "post action failed"
+        );
     }
-
-    jobs.update(&job_id, |s| {
-        s.set_progress(download_weight + merge_weight + upload_weight);
-    });
-
-    Ok(())
+    let client = state.db_pool.get().await?;
+    post_action::record(&client, job_id, result.run_id, status, &outcomes).await
 }
 
 #[derive(Debug)]
@@ -2149,29 +3705,31 @@ fn sanitize_filename(name: &str) -> String {
     sanitized.trim_matches('_').to_string()
 }
 
+fn job_status_for_run(status: RunStatus) -> JobStatus {
+    match status {
+        RunStatus::Queued => JobStatus::Queued,
+        RunStatus::Running => JobStatus::Running,
+        RunStatus::Completed => JobStatus::Succeeded,
+        RunStatus::Failed => JobStatus::Failed,
+        RunStatus::Canceled => JobStatus::Canceled,
+    }
+}
+
+/// Maps a stored run status (canonical or legacy spelling) onto the job vocabulary.
 fn map_pipeline_status(status: &str) -> JobStatus {
-    match status.to_ascii_lowercase().as_str() {
-        "queued" => JobStatus::Queued,
-        "running" => JobStatus::Running,
-        "completed" | "finished" | "finalized" => JobStatus::Succeeded,
-        "failed" | "timeout" | "error" => JobStatus::Failed,
-        "canceled" => JobStatus::Canceled,
-        other => {
-            warn!(
-                status = other,
-                "unknown pipeline status, defaulting to failed"
-            );
+    match RunStatus::parse_lenient(status) {
+        Some(status) => job_status_for_run(status),
+        None => {
+            warn!(status, "unknown pipeline status, defaulting to failed");
             JobStatus::Failed
         }
     }
 }
 
 fn map_pipeline_progress(status: &str) -> f32 {
-    match status.to_ascii_lowercase().as_str() {
-        "queued" => 0.0,
-        "running" => 0.5,
-        "completed" | "finished" | "finalized" => 1.0,
-        "failed" | "timeout" | "error" | "canceled" => 1.0,
+    match RunStatus::parse_lenient(status) {
+        Some(RunStatus::Queued) => 0.0,
+        Some(RunStatus::Running) => 0.5,
         _ => 1.0,
     }
 }
@@ -2195,6 +3753,13 @@ mod tests {
             auto_ingest,
             auto_pipeline,
             managed_by_default: false,
+            job_label: None,
+            external_ref: None,
+            schedule: None,
+            reingest: None,
+            next_run_at: None,
+            connector: ConnectorKind::SharePoint,
+            post_actions: Vec::new(),
             last_seen: None,
             updated_at: Utc::now(),
         }
@@ -2212,6 +3777,31 @@ mod tests {
         assert!(!rule.auto_pipeline);
     }
 
+    #[test]
+    fn pipeline_status_accepts_legacy_spellings() {
+        assert_eq!(map_pipeline_status("finished"), JobStatus::Succeeded);
+        assert_eq!(map_pipeline_status("Finalized"), JobStatus::Succeeded);
+        assert_eq!(map_pipeline_status("timeout"), JobStatus::Failed);
+        assert_eq!(map_pipeline_status("queued"), JobStatus::Queued);
+        assert_eq!(map_pipeline_status("bogus"), JobStatus::Failed);
+        assert_eq!(RunStatus::canonical("finished"), "completed");
+        assert_eq!(RunStatus::canonical("bogus"), "bogus");
+
+        let legacy: PipelineRunResult = serde_json::from_value(serde_json::json!({
+            "pdf_id": 1,
+            "pipeline_id": Uuid::nil(),
+            "overall_score": null,
+            "extracted": {},
+            "scoring": [],
+            "extraction": [],
+            "decision": [],
+            "log": [],
+            "status": "finished"
+        }))
+        .unwrap();
+        assert_eq!(legacy.status, Some(RunStatus::Completed));
+    }
+
     #[test]
     fn ensure_ingest_pipeline_disabled_noops_when_already_disabled() {
         let mut rule = make_rule(true, None, false);
@@ -2234,6 +3824,20 @@ mod tests {
         assert!(rule.pipeline_id.is_some());
         assert!(rule.auto_pipeline);
     }
+
+    #[test]
+    fn job_reference_drops_blank_values_and_filters() {
+        let reference =
+            JobReference::new(Some("  Schaden Müller ".to_string()), Some(" ".to_string()));
+        assert_eq!(reference.job_label.as_deref(), Some("Schaden Müller"));
+        assert!(reference.external_ref.is_none());
+
+        let reference = JobReference::new(Some("Schaden Müller".into()), Some("AZ-42".into()));
+        assert!(reference.matches(None, None));
+        assert!(reference.matches(Some("müller"), Some("AZ-42")));
+        assert!(!reference.matches(Some("meier"), None));
+        assert!(!reference.matches(None, Some("az-42")));
+    }
 }
 
 fn ensure_authorized(req: &HttpRequest, config: &Config) -> actix_web::Result<()> {
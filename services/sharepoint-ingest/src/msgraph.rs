@@ -7,14 +7,20 @@ use std::{
 
 use crate::config::Config;
 use anyhow::{anyhow, Context, Result};
+use chrono::{DateTime, Utc};
 use parking_lot::RwLock;
 use rand::Rng;
-use reqwest::{Client, Method, RequestBuilder, Response, StatusCode};
+use reqwest::{
+    header::{CONTENT_TYPE, RANGE},
+    Client, Method, RequestBuilder, Response, StatusCode,
+};
 use serde::{Deserialize, Serialize};
 use tokio::{fs::File, io::AsyncWriteExt, time::sleep};
 
 const GRAPH_BASE: &str = "https://graph.microsoft.com/v1.0";
 const MAX_RETRIES: u32 = 3;
+/// Fields of [`GraphChange`]; the delta links keep the `$select` of the request.
+const DELTA_SELECT: &str = "id,name,parentReference,folder,deleted";
 
 pub struct MsGraphClient {
     http: Client,
@@ -23,6 +29,8 @@ pub struct MsGraphClient {
     client_secret: String,
     site_host: String,
     site_path: String,
+    /// User whose OneDrive is used instead of the site drive.
+    drive_user: Option<String>,
     site_id: RwLock<Option<String>>,
     drive_id: RwLock<Option<String>>,
     token: RwLock<Option<CachedToken>>,
@@ -35,10 +43,55 @@ pub struct GraphFolder {
     pub file_count: i64,
 }
 
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct GraphFile {
     pub id: String,
     pub name: String,
+    /// Changes whenever the file content or metadata changes.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub etag: Option<String>,
+    /// Size in bytes as listed by Graph.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub size: Option<i64>,
+}
+
+/// File or folder with the metadata needed for folder statistics.
+#[derive(Clone, Debug)]
+pub struct GraphItemInfo {
+    pub id: String,
+    pub name: String,
+    pub size: i64,
+    pub last_modified: Option<DateTime<Utc>>,
+    pub mime_type: Option<String>,
+    pub is_folder: bool,
+}
+
+/// Item of a delta query: created, changed, moved or deleted since the last
+/// delta link.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct GraphChange {
+    pub id: String,
+    pub name: String,
+    /// Folder the item is (now) in; `None` for the drive root.
+    pub parent_id: Option<String>,
+    pub is_folder: bool,
+    pub child_count: i64,
+    pub deleted: bool,
+}
+
+/// Changes of a drive and the link that continues after them.
+#[derive(Clone, Debug)]
+pub struct DriveDelta {
+    pub changes: Vec<GraphChange>,
+    pub delta_link: String,
+}
+
+/// Change notification subscription on the drive root.
+#[derive(Clone, Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct GraphSubscription {
+    pub id: String,
+    pub expiration_date_time: DateTime<Utc>,
 }
 
 #[derive(Clone, Debug, Serialize, Deserialize)]
@@ -63,6 +116,10 @@ struct DriveItemResponse {
 struct DriveItem {
     id: String,
     name: String,
+    #[serde(default, rename = "eTag")]
+    e_tag: Option<String>,
+    #[serde(default)]
+    size: Option<i64>,
     #[serde(default)]
     folder: Option<FolderFacet>,
     #[serde(default)]
@@ -71,7 +128,7 @@ struct DriveItem {
 
 #[derive(Debug, Deserialize)]
 struct FolderFacet {
-    #[serde(default)]
+    #[serde(default, alias = "childCount")]
     child_count: Option<i64>,
 }
 
@@ -81,6 +138,78 @@ struct FileFacet {
     mime_type: Option<String>,
 }
 
+#[derive(Debug, Deserialize)]
+struct DriveItemDetailsPage {
+    value: Vec<DriveItemDetails>,
+    #[serde(rename = "@odata.nextLink", default)]
+    next_link: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct DriveItemDetails {
+    id: String,
+    name: String,
+    #[serde(default)]
+    size: Option<i64>,
+    #[serde(default)]
+    last_modified_date_time: Option<DateTime<Utc>>,
+    #[serde(default)]
+    folder: Option<serde_json::Value>,
+    #[serde(default)]
+    file: Option<DriveItemFile>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct DriveItemFile {
+    #[serde(default)]
+    mime_type: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct DeltaPage {
+    #[serde(default)]
+    value: Vec<DeltaItem>,
+    #[serde(rename = "@odata.nextLink", default)]
+    next_link: Option<String>,
+    #[serde(rename = "@odata.deltaLink", default)]
+    delta_link: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct DeltaItem {
+    id: String,
+    #[serde(default)]
+    name: Option<String>,
+    #[serde(default)]
+    parent_reference: Option<ParentReference>,
+    #[serde(default)]
+    folder: Option<FolderFacet>,
+    #[serde(default)]
+    deleted: Option<serde_json::Value>,
+}
+
+#[derive(Debug, Deserialize)]
+struct ParentReference {
+    #[serde(default)]
+    id: Option<String>,
+}
+
+impl From<DeltaItem> for GraphChange {
+    fn from(item: DeltaItem) -> Self {
+        Self {
+            id: item.id,
+            name: item.name.unwrap_or_default(),
+            parent_id: item.parent_reference.and_then(|p| p.id),
+            is_folder: item.folder.is_some(),
+            child_count: item.folder.and_then(|f| f.child_count).unwrap_or_default(),
+            deleted: item.deleted.is_some(),
+        }
+    }
+}
+
 #[derive(Debug, Deserialize)]
 struct SiteResponse {
     id: String,
@@ -107,12 +236,21 @@ impl MsGraphClient {
             client_secret: config.client_secret.clone(),
             site_host: config.site_host.clone(),
             site_path: config.site_path.clone(),
+            drive_user: None,
             site_id: RwLock::new(None),
             drive_id: RwLock::new(None),
             token: RwLock::new(None),
         })
     }
 
+    /// Client for the OneDrive of `user` (UPN or object id) instead of the
+    /// site drive; uses the same app registration.
+    pub fn for_user_drive(config: &Config, user: &str) -> Result<Self> {
+        let mut client = Self::new(config)?;
+        client.drive_user = Some(user.to_string());
+        Ok(client)
+    }
+
     /// Ensures the required base folder exists inside the SharePoint drive.
     pub async fn bootstrap(&self, config: &Config) -> Result<()> {
         self.ensure_site_and_drive().await?;
@@ -153,7 +291,7 @@ impl MsGraphClient {
     pub async fn list_pdfs_in_folder(&self, folder_id: &str) -> Result<Vec<GraphFile>> {
         let drive_id = self.ensure_site_and_drive().await?;
         let url = format!(
-            "{GRAPH_BASE}/drives/{drive_id}/items/{folder_id}/children?$select=id,name,size,file"
+            "{GRAPH_BASE}/drives/{drive_id}/items/{folder_id}/children?$select=id,name,size,file,eTag"
         );
         let resp = self
             .send_with_retry(self.authorized_request(Method::GET, url).await?)
@@ -177,11 +315,167 @@ impl MsGraphClient {
             .map(|item| GraphFile {
                 id: item.id,
                 name: item.name,
+                etag: item.e_tag,
+                size: item.size,
             })
             .collect();
         Ok(files)
     }
 
+    /// Lists all direct children of a folder with size and modification time,
+    /// following Graph paging. `None` if the folder does not exist.
+    pub async fn list_folder_items(&self, folder_id: &str) -> Result<Option<Vec<GraphItemInfo>>> {
+        let drive_id = self.ensure_site_and_drive().await?;
+        let mut url = Some(format!(
+            "{GRAPH_BASE}/drives/{drive_id}/items/{folder_id}/children\
+             ?$select=id,name,size,lastModifiedDateTime,file,folder&$top=999"
+        ));
+        let mut items = Vec::new();
+        while let Some(next) = url.take() {
+            let resp = self
+                .send_with_retry(self.authorized_request(Method::GET, next).await?)
+                .await?;
+            if resp.status() == StatusCode::NOT_FOUND {
+                return Ok(None);
+            }
+            let page: DriveItemDetailsPage = resp.error_for_status()?.json().await?;
+            items.extend(page.value.into_iter().map(|item| GraphItemInfo {
+                id: item.id,
+                name: item.name,
+                size: item.size.unwrap_or_default(),
+                last_modified: item.last_modified_date_time,
+                mime_type: item.file.and_then(|f| f.mime_type),
+                is_folder: item.folder.is_some(),
+            }));
+            url = page.next_link;
+        }
+        Ok(Some(items))
+    }
+
+    /// Identifier of the drive all paths refer to.
+    pub async fn drive_id(&self) -> Result<String> {
+        self.ensure_site_and_drive().await
+    }
+
+    /// Looks up a drive item by path; `None` if it does not exist.
+    pub async fn item_by_path(&self, drive_path: &str) -> Result<Option<GraphChange>> {
+        let drive_id = self.ensure_site_and_drive().await?;
+        let path = encode_path(drive_path);
+        let url = format!("{GRAPH_BASE}/drives/{drive_id}/root:/{path}?$select={DELTA_SELECT}");
+        self.fetch_item(url).await
+    }
+
+    /// Looks up a drive item by id; `None` if it does not exist (any more).
+    pub async fn item_by_id(&self, item_id: &str) -> Result<Option<GraphChange>> {
+        let drive_id = self.ensure_site_and_drive().await?;
+        let url = format!("{GRAPH_BASE}/drives/{drive_id}/items/{item_id}?$select={DELTA_SELECT}");
+        self.fetch_item(url).await
+    }
+
+    async fn fetch_item(&self, url: String) -> Result<Option<GraphChange>> {
+        let resp = self
+            .send_with_retry(self.authorized_request(Method::GET, url).await?)
+            .await?;
+        if resp.status() == StatusCode::NOT_FOUND {
+            return Ok(None);
+        }
+        let item: DeltaItem = resp.error_for_status()?.json().await?;
+        Ok(Some(item.into()))
+    }
+
+    /// Delta link of the current state of the drive, without enumerating it
+    /// (`token=latest`).
+    pub async fn latest_delta_link(&self) -> Result<String> {
+        let drive_id = self.ensure_site_and_drive().await?;
+        let url = format!(
+            "{GRAPH_BASE}/drives/{drive_id}/root/delta?token=latest&$select={DELTA_SELECT}"
+        );
+        let resp = self
+            .send_with_retry(self.authorized_request(Method::GET, url).await?)
+            .await?;
+        let page: DeltaPage = resp.error_for_status()?.json().await?;
+        page.delta_link
+            .ok_or_else(|| anyhow!("delta response without @odata.deltaLink"))
+    }
+
+    /// All changes of the drive since `delta_link`, following Graph paging.
+    /// `None` if the link expired (`410 Gone`) and the drive has to be listed
+    /// again.
+    pub async fn delta_since(&self, delta_link: &str) -> Result<Option<DriveDelta>> {
+        let mut url = delta_link.to_string();
+        let mut changes = Vec::new();
+        loop {
+            let resp = self
+                .send_with_retry(self.authorized_request(Method::GET, url).await?)
+                .await?;
+            if resp.status() == StatusCode::GONE {
+                return Ok(None);
+            }
+            let page: DeltaPage = resp.error_for_status()?.json().await?;
+            changes.extend(page.value.into_iter().map(GraphChange::from));
+            match (page.next_link, page.delta_link) {
+                (Some(next), _) => url = next,
+                (None, Some(delta_link)) => {
+                    return Ok(Some(DriveDelta {
+                        changes,
+                        delta_link,
+                    }))
+                }
+                (None, None) => return Err(anyhow!("delta page without next or delta link")),
+            }
+        }
+    }
+
+    /// Subscribes `notification_url` to changes of the whole drive. Graph
+    /// validates the URL synchronously, so the endpoint must already be
+    /// reachable.
+    pub async fn create_subscription(
+        &self,
+        notification_url: &str,
+        client_state: &str,
+        expires_at: DateTime<Utc>,
+    ) -> Result<GraphSubscription> {
+        let drive_id = self.ensure_site_and_drive().await?;
+        let url = format!("{GRAPH_BASE}/subscriptions");
+        let resp = self
+            .send_with_retry(self.authorized_request(Method::POST, url).await?.json(
+                &serde_json::json!({
+                    "changeType": "updated",
+                    "notificationUrl": notification_url,
+                    "resource": format!("drives/{drive_id}/root"),
+                    "expirationDateTime": expires_at,
+                    "clientState": client_state,
+                }),
+            ))
+            .await?;
+        let status = resp.status();
+        if !status.is_success() {
+            let body = resp.text().await.unwrap_or_default();
+            return Err(anyhow!("create subscription failed with {status}: {body}"));
+        }
+        Ok(resp.json().await?)
+    }
+
+    /// Extends a subscription; `None` if Graph no longer knows it.
+    pub async fn renew_subscription(
+        &self,
+        subscription_id: &str,
+        expires_at: DateTime<Utc>,
+    ) -> Result<Option<GraphSubscription>> {
+        let url = format!("{GRAPH_BASE}/subscriptions/{subscription_id}");
+        let resp = self
+            .send_with_retry(
+                self.authorized_request(Method::PATCH, url)
+                    .await?
+                    .json(&serde_json::json!({ "expirationDateTime": expires_at })),
+            )
+            .await?;
+        if resp.status() == StatusCode::NOT_FOUND {
+            return Ok(None);
+        }
+        Ok(Some(resp.error_for_status()?.json().await?))
+    }
+
     /// Downloads the file with the given identifier into the destination path.
     pub async fn download_file(&self, file_id: &str, dest: &Path) -> Result<()> {
         let drive_id = self.ensure_site_and_drive().await?;
@@ -198,6 +492,34 @@ impl MsGraphClient {
         Ok(())
     }
 
+    /// Reads at most `len` bytes from the start of a file. Asks for a byte
+    /// range and stops reading after `len` bytes if the server sends more.
+    pub async fn download_head(&self, file_id: &str, len: usize) -> Result<Vec<u8>> {
+        let drive_id = self.ensure_site_and_drive().await?;
+        let url = format!("{GRAPH_BASE}/drives/{drive_id}/items/{file_id}/content");
+        let resp = self
+            .send_with_retry(
+                self.authorized_request(Method::GET, url)
+                    .await?
+                    .header(RANGE, format!("bytes=0-{}", len.saturating_sub(1))),
+            )
+            .await?;
+        if resp.status() == StatusCode::RANGE_NOT_SATISFIABLE {
+            // leere Datei
+            return Ok(Vec::new());
+        }
+        let mut resp = resp.error_for_status()?;
+        let mut head = Vec::with_capacity(len);
+        while head.len() < len {
+            let Some(chunk) = resp.chunk().await? else {
+                break;
+            };
+            head.extend_from_slice(&chunk);
+        }
+        head.truncate(len);
+        Ok(head)
+    }
+
     /// Ensures the provided drive path exists by creating the folder hierarchy
     /// if it is missing.
     pub async fn ensure_folder(&self, drive_path: &str) -> Result<()> {
@@ -261,10 +583,90 @@ impl MsGraphClient {
         Ok(())
     }
 
+    /// Moves an item into the folder `parent_id` and/or renames it. Graph
+    /// appends a number to the name if the target already holds one.
+    pub async fn update_item(
+        &self,
+        item_id: &str,
+        parent_id: Option<&str>,
+        name: Option<&str>,
+    ) -> Result<GraphChange> {
+        let drive_id = self.ensure_site_and_drive().await?;
+        let url = format!(
+            "{GRAPH_BASE}/drives/{drive_id}/items/{item_id}?@microsoft.graph.conflictBehavior=rename&$select={DELTA_SELECT}"
+        );
+        let mut body = serde_json::Map::new();
+        if let Some(parent_id) = parent_id {
+            body.insert(
+                "parentReference".into(),
+                serde_json::json!({ "id": parent_id }),
+            );
+        }
+        if let Some(name) = name {
+            body.insert("name".into(), serde_json::json!(name));
+        }
+        let resp = self
+            .send_with_retry(
+                self.authorized_request(Method::PATCH, url)
+                    .await?
+                    .json(&body),
+            )
+            .await?;
+        let status = resp.status();
+        if !status.is_success() {
+            let body = resp.text().await.unwrap_or_default();
+            return Err(anyhow!(
+                "update of item {item_id} failed with {status}: {body}"
+            ));
+        }
+        let item: DeltaItem = resp.json().await?;
+        Ok(item.into())
+    }
+
+    /// Writes a small file (up to 4 MB) into the folder `folder_id`,
+    /// replacing a file of the same name.
+    pub async fn upload_small_file(
+        &self,
+        folder_id: &str,
+        name: &str,
+        content_type: &str,
+        content: Vec<u8>,
+    ) -> Result<()> {
+        let drive_id = self.ensure_site_and_drive().await?;
+        let url = format!(
+            "{GRAPH_BASE}/drives/{drive_id}/items/{folder_id}:/{}:/content",
+            encode_path(name)
+        );
+        let resp = self
+            .send_with_retry(
+                self.authorized_request(Method::PUT, url)
+                    .await?
+                    .header(CONTENT_TYPE, content_type)
+                    .body(content),
+            )
+            .await?;
+        let status = resp.status();
+        if !status.is_success() {
+            let body = resp.text().await.unwrap_or_default();
+            return Err(anyhow!("upload of {name} failed with {status}: {body}"));
+        }
+        Ok(())
+    }
+
     async fn ensure_site_and_drive(&self) -> Result<String> {
         if let Some(drive_id) = self.drive_id.read().clone() {
             return Ok(drive_id);
         }
+        if let Some(user) = &self.drive_user {
+            let drive_id = self
+                .fetch_drive(format!(
+                    "{GRAPH_BASE}/users/{}/drive",
+                    urlencoding::encode(user)
+                ))
+                .await?;
+            *self.drive_id.write() = Some(drive_id.clone());
+            return Ok(drive_id);
+        }
 
         let cached_site = { self.site_id.read().clone() };
         let site_id = if let Some(site_id) = cached_site {
@@ -297,7 +699,11 @@ impl MsGraphClient {
     }
 
     async fn fetch_drive_id(&self, site_id: &str) -> Result<String> {
-        let url = format!("{GRAPH_BASE}/sites/{site_id}/drive");
+        self.fetch_drive(format!("{GRAPH_BASE}/sites/{site_id}/drive"))
+            .await
+    }
+
+    async fn fetch_drive(&self, url: String) -> Result<String> {
         let resp = self
             .send_with_retry(self.authorized_request(Method::GET, url).await?)
             .await?
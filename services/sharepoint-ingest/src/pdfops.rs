@@ -5,7 +5,12 @@ use std::path::{Path, PathBuf};
 use std::collections::BTreeMap;
 
 use anyhow::{anyhow, Context, Result};
-use lopdf::{Document, Object, ObjectId};
+use lopdf::content::{Content, Operation};
+use lopdf::{dictionary, Document, Object, ObjectId, Stream};
+
+/// Lines per page and characters per line of [`write_text_pdf`] (A4, Helvetica 10pt).
+const TEXT_LINES_PER_PAGE: usize = 58;
+const TEXT_LINE_WIDTH: usize = 95;
 
 /// Merges the provided PDF files into a single output document.
 pub fn merge_pdfs(inputs: &[PathBuf], output: &Path) -> Result<()> {
@@ -115,14 +120,94 @@ pub fn merge_pdfs(inputs: &[PathBuf], output: &Path) -> Result<()> {
     Ok(())
 }
 
+/// Writes plain text as a simple A4 PDF, e.g. an e-mail cover page.
+///
+/// Uses the standard Helvetica font with WinAnsi encoding; characters outside
+/// Latin-1 are replaced with `?`.
+pub fn write_text_pdf(lines: &[String], output: &Path) -> Result<()> {
+    let wrapped: Vec<String> = lines.iter().flat_map(|l| wrap_line(l)).collect();
+    let mut doc = Document::with_version("1.5");
+    let pages_id = doc.new_object_id();
+    let font_id = doc.add_object(dictionary! {
+        "Type" => "Font",
+        "Subtype" => "Type1",
+        "BaseFont" => "Helvetica",
+        "Encoding" => "WinAnsiEncoding",
+    });
+    let resources_id = doc.add_object(dictionary! { "Font" => dictionary! { "F1" => font_id } });
+
+    let mut kids = Vec::new();
+    let chunks: Vec<&[String]> = if wrapped.is_empty() {
+        vec![&[]]
+    } else {
+        wrapped.chunks(TEXT_LINES_PER_PAGE).collect()
+    };
+    for chunk in chunks {
+        let mut operations = vec![
+            Operation::new("BT", vec![]),
+            Operation::new("Tf", vec!["F1".into(), 10.into()]),
+            Operation::new("TL", vec![13.into()]),
+            Operation::new("Td", vec![50.into(), 790.into()]),
+        ];
+        for line in chunk {
+            operations.push(Operation::new(
+                "Tj",
+                vec![Object::string_literal(to_latin1(line))],
+            ));
+            operations.push(Operation::new("T*", vec![]));
+        }
+        operations.push(Operation::new("ET", vec![]));
+        let content = Content { operations };
+        let content_id = doc.add_object(Stream::new(dictionary! {}, content.encode()?));
+        let page_id = doc.add_object(dictionary! {
+            "Type" => "Page",
+            "Parent" => pages_id,
+            "Contents" => content_id,
+            "Resources" => resources_id,
+            "MediaBox" => vec![0.into(), 0.into(), 595.into(), 842.into()],
+        });
+        kids.push(Object::Reference(page_id));
+    }
+
+    let count = kids.len() as u32;
+    doc.objects.insert(
+        pages_id,
+        Object::Dictionary(dictionary! { "Type" => "Pages", "Kids" => kids, "Count" => count }),
+    );
+    let catalog_id = doc.add_object(dictionary! { "Type" => "Catalog", "Pages" => pages_id });
+    doc.trailer.set("Root", catalog_id);
+    doc.save(output)
+        .with_context(|| format!("saving text pdf to {:?}", output))?;
+    Ok(())
+}
+
+fn wrap_line(line: &str) -> Vec<String> {
+    let chars: Vec<char> = line.chars().filter(|c| *c != '\r').collect();
+    if chars.is_empty() {
+        return vec![String::new()];
+    }
+    chars
+        .chunks(TEXT_LINE_WIDTH)
+        .map(|c| c.iter().collect())
+        .collect()
+}
+
+fn to_latin1(line: &str) -> Vec<u8> {
+    line.chars()
+        .map(|c| match c {
+            '\t' => b' ',
+            c if (c as u32) < 0x20 => b' ',
+            c if (c as u32) <= 0xFF => c as u32 as u8,
+            _ => b'?',
+        })
+        .collect()
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
     use tempfile::tempdir;
 
-    use lopdf::content::{Content, Operation};
-    use lopdf::{dictionary, Object, Stream};
-
     fn write_test_pdf(path: &Path, text: &str) {
         let mut doc = Document::with_version("1.5");
         let pages_id = doc.new_object_id();
@@ -173,4 +258,19 @@ mod tests {
         let metadata = std::fs::metadata(&merged).unwrap();
         assert!(metadata.len() > 0);
     }
+
+    #[test]
+    fn text_pdf_paginates_and_merges() {
+        let dir = tempdir().unwrap();
+        let cover = dir.path().join("cover.pdf");
+        let lines: Vec<String> = (0..70).map(|i| format!("Zeile {i} – Größe")).collect();
+        write_text_pdf(&lines, &cover).expect("cover written");
+        assert_eq!(Document::load(&cover).unwrap().get_pages().len(), 2);
+
+        let attachment = dir.path().join("a.pdf");
+        write_test_pdf(&attachment, "Anlage");
+        let merged = dir.path().join("merged.pdf");
+        merge_pdfs(&[cover, attachment], &merged).expect("merge works");
+        assert_eq!(Document::load(&merged).unwrap().get_pages().len(), 3);
+    }
 }
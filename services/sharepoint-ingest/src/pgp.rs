@@ -0,0 +1,86 @@
+//! PGP decryption of delivered files via the `gpg` binary.
+//!
+//! Each call imports the private key into a throw-away `GNUPGHOME`, so keys of
+//! different tenants never share a keyring and nothing is left on disk.
+
+use std::{path::Path, process::Stdio, time::Duration};
+
+use anyhow::{bail, Context, Result};
+use tokio::{io::AsyncWriteExt, process::Command, time::timeout};
+
+const GPG_TIMEOUT: Duration = Duration::from_secs(120);
+
+/// File extensions treated as PGP encrypted.
+const PGP_EXTENSIONS: [&str; 3] = ["pgp", "gpg", "asc"];
+
+fn gpg_binary() -> String {
+    std::env::var("GPG_BINARY").unwrap_or_else(|_| "gpg".to_string())
+}
+
+/// Name without the PGP extension, `None` if the file is not encrypted.
+pub fn decrypted_name(name: &str) -> Option<&str> {
+    let (stem, ext) = name.rsplit_once('.')?;
+    PGP_EXTENSIONS
+        .iter()
+        .any(|e| ext.eq_ignore_ascii_case(e))
+        .then_some(stem)
+}
+
+async fn run_gpg(home: &Path, args: &[&str], stdin: &[u8]) -> Result<()> {
+    let mut child = Command::new(gpg_binary())
+        .env("GNUPGHOME", home)
+        .args(["--batch", "--no-tty", "--quiet"])
+        .args(args)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::null())
+        .stderr(Stdio::piped())
+        .kill_on_drop(true)
+        .spawn()
+        .context("spawn gpg")?;
+    if let Some(mut input) = child.stdin.take() {
+        input.write_all(stdin).await.context("write gpg stdin")?;
+    }
+    let output = timeout(GPG_TIMEOUT, child.wait_with_output())
+        .await
+        .context("gpg timed out")??;
+    if !output.status.success() {
+        bail!(
+            "gpg {} failed: {}",
+            args.last().copied().unwrap_or_default(),
+            String::from_utf8_lossy(&output.stderr).trim()
+        );
+    }
+    Ok(())
+}
+
+/// Decrypts `input` into `output` with the given armored private key.
+pub async fn decrypt_file(
+    input: &Path,
+    output: &Path,
+    private_key: &str,
+    passphrase: Option<&str>,
+) -> Result<()> {
+    let home = tempfile::tempdir().context("create gpg home")?;
+    run_gpg(home.path(), &["--import"], private_key.as_bytes())
+        .await
+        .context("import pgp private key")?;
+    let output_arg = output.to_string_lossy();
+    let input_arg = input.to_string_lossy();
+    run_gpg(
+        home.path(),
+        &[
+            "--yes",
+            "--pinentry-mode",
+            "loopback",
+            "--passphrase-fd",
+            "0",
+            "--output",
+            &output_arg,
+            "--decrypt",
+            &input_arg,
+        ],
+        passphrase.unwrap_or_default().as_bytes(),
+    )
+    .await
+    .with_context(|| format!("decrypt {}", input.display()))
+}
@@ -0,0 +1,388 @@
+//! Post-actions of automation rules: what happens to a drive folder once the
+//! pipeline run of its job is finished.
+//!
+//! A rule lists its actions in `sharepoint_automation.post_actions`. When
+//! `handle_pipeline_result` sees a `completed` or `failed` run of a job whose
+//! folder has such a rule, the actions run in the listed order:
+//!
+//! - `move`: moves the folder below `target` (completed runs, default
+//!   `PROCESSED_FOLDER`) or `failed_target` (failed runs, default
+//!   `FAILED_FOLDER`); the target folder is created if missing
+//! - `rename`: appends `suffix` to the folder name, `{status}` is replaced
+//!   with the run status
+//! - `write_result`: writes the run result as JSON file `file_name` into the
+//!   folder
+//!
+//! `on` limits an action to `completed` or `failed` runs (default: both).
+//! Only Graph folders (`sharepoint`, `onedrive`) support post-actions; all
+//! actions address the folder by its item id, which survives moves and
+//! renames. Each job runs its actions once: the outcome, including errors, is
+//! stored in `sharepoint_jobs.post_action_state`, and later results of the
+//! same job (re-runs) leave the folder alone.
+
+use anyhow::{anyhow, bail, Result};
+use chrono::Utc;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use shared::dto::{PipelineRunResult, RunStatus};
+use tokio_postgres::Client;
+use uuid::Uuid;
+
+use crate::msgraph::MsGraphClient;
+
+/// Creates the post-action columns (see migration 0066).
+pub const POST_ACTION_SCHEMA_SQL: &str = r#"
+ALTER TABLE sharepoint_automation
+    ADD COLUMN IF NOT EXISTS post_actions JSONB NOT NULL DEFAULT '[]'::jsonb;
+ALTER TABLE sharepoint_jobs
+    ADD COLUMN IF NOT EXISTS post_action_state JSONB;
+"#;
+
+/// Default of `write_result.file_name`.
+pub const DEFAULT_RESULT_FILE: &str = "regress-result.json";
+/// Default of `rename.suffix`.
+pub const DEFAULT_RENAME_SUFFIX: &str = " [{status}]";
+
+/// Run states an action applies to.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Trigger {
+    /// Completed and failed runs.
+    #[default]
+    Finished,
+    Completed,
+    Failed,
+}
+
+impl Trigger {
+    pub fn matches(self, status: RunStatus) -> bool {
+        match self {
+            Trigger::Finished => matches!(status, RunStatus::Completed | RunStatus::Failed),
+            Trigger::Completed => status == RunStatus::Completed,
+            Trigger::Failed => status == RunStatus::Failed,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum Action {
+    /// Moves the folder into another drive folder.
+    Move {
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        target: Option<String>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        failed_target: Option<String>,
+    },
+    /// Appends a status suffix to the folder name.
+    Rename {
+        #[serde(default = "default_suffix")]
+        suffix: String,
+    },
+    /// Writes the run result as JSON file into the folder.
+    WriteResult {
+        #[serde(default = "default_file_name")]
+        file_name: String,
+    },
+}
+
+/// One post-action of an automation rule.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PostAction {
+    #[serde(flatten)]
+    pub action: Action,
+    #[serde(default)]
+    pub on: Trigger,
+}
+
+fn default_suffix() -> String {
+    DEFAULT_RENAME_SUFFIX.to_string()
+}
+
+fn default_file_name() -> String {
+    DEFAULT_RESULT_FILE.to_string()
+}
+
+/// Checks the actions of a rule before they are stored.
+pub fn validate(actions: &[PostAction]) -> Result<()> {
+    for action in actions {
+        match &action.action {
+            Action::Move {
+                target,
+                failed_target,
+            } => {
+                for path in [target, failed_target].into_iter().flatten() {
+                    if path.trim().trim_matches('/').is_empty() {
+                        bail!("move target must not be empty");
+                    }
+                }
+            }
+            Action::Rename { suffix } => check_name("rename suffix", suffix)?,
+            Action::WriteResult { file_name } => check_name("result file name", file_name)?,
+        }
+    }
+    Ok(())
+}
+
+fn check_name(what: &str, value: &str) -> Result<()> {
+    if value.trim().is_empty() {
+        bail!("{what} must not be empty");
+    }
+    if value.contains(['/', '\\']) {
+        bail!("{what} {value:?} must not contain a path separator");
+    }
+    Ok(())
+}
+
+/// Reads the stored column; an unreadable value disables the actions.
+pub fn from_column(value: Option<Value>) -> Vec<PostAction> {
+    value
+        .and_then(|value| serde_json::from_value(value).ok())
+        .unwrap_or_default()
+}
+
+impl Action {
+    pub fn kind(&self) -> &'static str {
+        match self {
+            Action::Move { .. } => "move",
+            Action::Rename { .. } => "rename",
+            Action::WriteResult { .. } => "write_result",
+        }
+    }
+}
+
+/// Folder name with the expanded suffix; `None` if it already ends with it.
+pub fn renamed(name: &str, suffix: &str, status: RunStatus) -> Option<String> {
+    let suffix = suffix.replace("{status}", status.as_str());
+    if name.ends_with(&suffix) {
+        return None;
+    }
+    Some(format!("{name}{suffix}"))
+}
+
+/// Content of the `write_result` file.
+pub fn result_document(job_id: Uuid, result: &PipelineRunResult, status: RunStatus) -> Value {
+    json!({
+        "job_id": job_id,
+        "pdf_id": result.pdf_id,
+        "pipeline_id": result.pipeline_id,
+        "run_id": result.run_id,
+        "status": status.as_str(),
+        "job_label": result.job_label,
+        "external_ref": result.external_ref,
+        "overall_score": result.overall_score,
+        "contested": result.contested,
+        "final_decisions": result.final_decisions,
+        "final_scores": result.final_scores,
+        "extracted": result.extracted,
+        "output": result.output,
+        "finished_at": result.finished_at,
+    })
+}
+
+/// Folder and run a job's actions apply to.
+pub struct PostActionContext<'a> {
+    pub job_id: Uuid,
+    pub folder_id: &'a str,
+    pub status: RunStatus,
+    pub result: &'a PipelineRunResult,
+    /// `PROCESSED_FOLDER` and `FAILED_FOLDER`.
+    pub processed_folder: &'a str,
+    pub failed_folder: &'a str,
+}
+
+/// Outcome of one action, as stored in `post_action_state`.
+#[derive(Debug, Clone, Serialize)]
+pub struct ActionOutcome {
+    pub kind: &'static str,
+    pub ok: bool,
+    /// Target path, new name or file name; the error if `ok` is false.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub detail: Option<String>,
+}
+
+/// Runs the actions matching the run status in order. A failed action is
+/// recorded and does not stop the following ones.
+pub async fn execute(
+    graph: &MsGraphClient,
+    ctx: &PostActionContext<'_>,
+    actions: &[PostAction],
+) -> Vec<ActionOutcome> {
+    let mut outcomes = Vec::new();
+    for action in actions
+        .iter()
+        .filter(|action| action.on.matches(ctx.status))
+    {
+        let kind = action.action.kind();
+        let outcome = match run(graph, ctx, &action.action).await {
+            Ok(detail) => ActionOutcome {
+                kind,
+                ok: true,
+                detail,
+            },
+            Err(err) => ActionOutcome {
+                kind,
+                ok: false,
+                detail: Some(format!("{err:#}")),
+            },
+        };
+        outcomes.push(outcome);
+    }
+    outcomes
+}
+
+async fn run(
+    graph: &MsGraphClient,
+    ctx: &PostActionContext<'_>,
+    action: &Action,
+) -> Result<Option<String>> {
+    match action {
+        Action::Move {
+            target,
+            failed_target,
+        } => {
+            let target = match ctx.status {
+                RunStatus::Failed => failed_target.as_deref().unwrap_or(ctx.failed_folder),
+                _ => target.as_deref().unwrap_or(ctx.processed_folder),
+            };
+            let target = target.trim().trim_matches('/');
+            graph.ensure_folder(target).await?;
+            let parent = graph
+                .item_by_path(target)
+                .await?
+                .ok_or_else(|| anyhow!("target folder {target} not found"))?;
+            graph
+                .update_item(ctx.folder_id, Some(&parent.id), None)
+                .await?;
+            Ok(Some(target.to_string()))
+        }
+        Action::Rename { suffix } => {
+            let folder = graph
+                .item_by_id(ctx.folder_id)
+                .await?
+                .ok_or_else(|| anyhow!("folder {} not found", ctx.folder_id))?;
+            let Some(name) = renamed(&folder.name, suffix, ctx.status) else {
+                return Ok(Some(folder.name));
+            };
+            let item = graph.update_item(ctx.folder_id, None, Some(&name)).await?;
+            Ok(Some(item.name))
+        }
+        Action::WriteResult { file_name } => {
+            let document = result_document(ctx.job_id, ctx.result, ctx.status);
+            let content = serde_json::to_vec_pretty(&document)?;
+            graph
+                .upload_small_file(ctx.folder_id, file_name, "application/json", content)
+                .await?;
+            Ok(Some(file_name.clone()))
+        }
+    }
+}
+
+/// Reserves the job for its post-actions; false if they already ran (or
+/// are running) for an earlier result.
+pub async fn claim(client: &Client, job_id: Uuid, run_id: Option<Uuid>) -> Result<bool> {
+    let state = json!({ "run_id": run_id, "started_at": Utc::now() });
+    let updated = client
+        .execute(
+            "UPDATE sharepoint_jobs SET post_action_state = $2
+             WHERE id = $1 AND post_action_state IS NULL",
+            &[&job_id, &state],
+        )
+        .await?;
+    Ok(updated > 0)
+}
+
+/// Stores the outcome of the claimed job's actions.
+pub async fn record(
+    client: &Client,
+    job_id: Uuid,
+    run_id: Option<Uuid>,
+    status: RunStatus,
+    outcomes: &[ActionOutcome],
+) -> Result<()> {
+    let state = json!({
+        "run_id": run_id,
+        "status": status.as_str(),
+        "finished_at": Utc::now(),
+        "actions": outcomes,
+    });
+    client
+        .execute(
+            "UPDATE sharepoint_jobs SET post_action_state = $2, updated_at = now() WHERE id = $1",
+            &[&job_id, &state],
+        )
+        .await?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_actions_with_defaults() {
+        let actions: Vec<PostAction> = serde_json::from_value(json!([
+            {"kind": "write_result"},
+            {"kind": "rename", "on": "failed"},
+            {"kind": "move", "target": "Erledigt/2025"}
+        ]))
+        .unwrap();
+        assert_eq!(
+            actions[0].action,
+            Action::WriteResult {
+                file_name: DEFAULT_RESULT_FILE.into()
+            }
+        );
+        assert_eq!(actions[0].on, Trigger::Finished);
+        assert_eq!(
+            actions[1].action,
+            Action::Rename {
+                suffix: DEFAULT_RENAME_SUFFIX.into()
+            }
+        );
+        assert!(!actions[1].on.matches(RunStatus::Completed));
+        assert!(actions[1].on.matches(RunStatus::Failed));
+        assert!(!Trigger::Finished.matches(RunStatus::Canceled));
+        assert!(validate(&actions).is_ok());
+
+        let stored = serde_json::to_value(&actions).unwrap();
+        assert_eq!(
+            stored[2],
+            json!({"kind": "move", "target": "Erledigt/2025", "on": "finished"})
+        );
+        assert_eq!(from_column(Some(stored)), actions);
+        assert!(from_column(Some(json!({"kind": "delete"}))).is_empty());
+    }
+
+    #[test]
+    fn rejects_empty_values_and_paths() {
+        let check = |value: Value| {
+            let actions: Vec<PostAction> = serde_json::from_value(value).unwrap();
+            validate(&actions).unwrap_err().to_string()
+        };
+        assert_eq!(
+            check(json!([{"kind": "move", "failed_target": " / "}])),
+            "move target must not be empty"
+        );
+        assert_eq!(
+            check(json!([{"kind": "rename", "suffix": " "}])),
+            "rename suffix must not be empty"
+        );
+        assert!(
+            check(json!([{"kind": "write_result", "file_name": "../x.json"}]))
+                .contains("path separator")
+        );
+    }
+
+    #[test]
+    fn appends_the_status_suffix_once() {
+        assert_eq!(
+            renamed("Akte 42", DEFAULT_RENAME_SUFFIX, RunStatus::Completed).as_deref(),
+            Some("Akte 42 [completed]")
+        );
+        assert_eq!(
+            renamed("Akte 42_failed", "_{status}", RunStatus::Failed),
+            None
+        );
+    }
+}
@@ -0,0 +1,235 @@
+//! Dry run of `POST /jobs` (`POST /jobs/preview`): lists the folders, applies
+//! order and filename overrides and runs the size and type checks of `scan.rs`
+//! on the file heads. Nothing is downloaded completely and no job is created.
+//!
+//! Page counts are exact for linearized PDFs (`/N` of the linearization
+//! dictionary in the first bytes); for all other files they are estimated from
+//! the size. ClamAV and files released from quarantine are not considered, the
+//! job may therefore still reject or accept a file differently.
+
+use anyhow::Result;
+use serde::Serialize;
+
+use crate::{
+    connector::SourceConnector,
+    job::JobOrder,
+    msgraph::GraphFile,
+    order_files,
+    scan::{self, Rejection, ScanConfig, SNIFF_BYTES},
+};
+
+/// Average size of a PDF page for the estimate; scans are larger, text pages
+/// smaller.
+const AVG_PAGE_BYTES: u64 = 100 * 1024;
+
+#[derive(Debug, Serialize)]
+pub struct JobPreviewResponse {
+    pub folders: Vec<FolderPreview>,
+}
+
+/// Planned merge manifest of one folder.
+#[derive(Debug, Serialize)]
+pub struct FolderPreview {
+    pub folder_id: String,
+    pub folder_name: String,
+    /// Files in merge order, rejected ones included.
+    pub files: Vec<PreviewFile>,
+    pub merged_count: usize,
+    pub rejected_count: usize,
+    /// Size and pages of the merged files.
+    pub total_bytes: u64,
+    pub estimated_pages: u64,
+    /// Names from `filenames` that are not in the folder.
+    pub missing_filenames: Vec<String>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct PreviewFile {
+    /// 1-based position in the merged PDF, `None` for rejected files.
+    pub position: Option<usize>,
+    pub id: String,
+    pub name: String,
+    pub size: u64,
+    pub estimated_pages: Option<u64>,
+    /// Page count read from the PDF instead of estimated.
+    pub pages_exact: bool,
+    pub rejection: Option<Rejection>,
+}
+
+impl FolderPreview {
+    fn new(folder_id: String, folder_name: String, missing_filenames: Vec<String>) -> Self {
+        Self {
+            folder_id,
+            folder_name,
+            files: Vec::new(),
+            merged_count: 0,
+            rejected_count: 0,
+            total_bytes: 0,
+            estimated_pages: 0,
+            missing_filenames,
+        }
+    }
+
+    /// Appends the next file; `pages` is `None` when the file is rejected.
+    fn push(&mut self, file: GraphFile, pages: Option<(u64, bool)>, rejection: Option<Rejection>) {
+        let size = file_size(&file);
+        let position = if rejection.is_none() {
+            self.merged_count += 1;
+            self.total_bytes += size;
+            self.estimated_pages += pages.map_or(0, |(count, _)| count);
+            Some(self.merged_count)
+        } else {
+            self.rejected_count += 1;
+            None
+        };
+        self.files.push(PreviewFile {
+            position,
+            id: file.id,
+            name: file.name,
+            size,
+            estimated_pages: pages.map(|(count, _)| count),
+            pages_exact: pages.is_some_and(|(_, exact)| exact),
+            rejection,
+        });
+    }
+}
+
+/// Builds the manifest of one folder; reads only the first bytes of each file
+/// that passes the size limits.
+pub async fn preview_folder(
+    connector: &dyn SourceConnector,
+    folder_id: String,
+    folder_name: String,
+    order: JobOrder,
+    filenames_override: Option<Vec<String>>,
+    cfg: &ScanConfig,
+) -> Result<FolderPreview> {
+    let listed = connector.list_pdfs(&folder_id).await?;
+    let missing = missing_filenames(&listed, filenames_override.as_deref());
+    let mut preview = FolderPreview::new(folder_id, folder_name, missing);
+    for file in order_files(listed, order, filenames_override) {
+        let size = file_size(&file);
+        if let Some(rejection) = scan::check_size(size, preview.total_bytes, cfg) {
+            preview.push(file, None, Some(rejection));
+            continue;
+        }
+        let head = connector.download_head(&file.id, SNIFF_BYTES).await?;
+        match scan::sniff_bytes(&head) {
+            Some(rejection) => preview.push(file, None, Some(rejection)),
+            None => preview.push(file, Some(estimate_pages(&head, size)), None),
+        }
+    }
+    Ok(preview)
+}
+
+fn file_size(file: &GraphFile) -> u64 {
+    file.size.unwrap_or(0).max(0) as u64
+}
+
+fn missing_filenames(listed: &[GraphFile], filenames: Option<&[String]>) -> Vec<String> {
+    filenames
+        .unwrap_or_default()
+        .iter()
+        .filter(|name| !listed.iter().any(|file| &file.name == *name))
+        .cloned()
+        .collect()
+}
+
+/// Page count of the PDF and whether it is exact.
+pub fn estimate_pages(head: &[u8], size: u64) -> (u64, bool) {
+    match linearized_page_count(head) {
+        Some(count) => (count, true),
+        None => (size.div_ceil(AVG_PAGE_BYTES).max(1), false),
+    }
+}
+
+/// `/N` of the linearization dictionary, e.g.
+/// `<</Linearized 1/L 48213/H [ 648 160 ]/O 11/E 40113/N 3/T 47907>>`.
+fn linearized_page_count(head: &[u8]) -> Option<u64> {
+    let start = find(head, b"/Linearized")?;
+    let dict = &head[start..];
+    let dict = &dict[..find(dict, b">>").unwrap_or(dict.len())];
+    let mut rest = dict;
+    while let Some(pos) = find(rest, b"/N") {
+        rest = &rest[pos + 2..];
+        if rest.first().is_some_and(|b| b.is_ascii_alphanumeric()) {
+            continue;
+        }
+        let digits: String = rest
+            .iter()
+            .skip_while(|b| b.is_ascii_whitespace())
+            .take_while(|b| b.is_ascii_digit())
+            .map(|&b| b as char)
+            .collect();
+        return digits.parse().ok().filter(|count| *count > 0);
+    }
+    None
+}
+
+fn find(haystack: &[u8], needle: &[u8]) -> Option<usize> {
+    haystack
+        .windows(needle.len())
+        .position(|window| window == needle)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::scan::RejectReason;
+    use std::path::PathBuf;
+
+    fn file(name: &str, size: i64) -> GraphFile {
+        GraphFile {
+            id: name.to_string(),
+            name: name.to_string(),
+            etag: None,
+            size: Some(size),
+        }
+    }
+
+    #[test]
+    fn reads_page_count_of_linearized_pdfs() {
+        let head = b"%PDF-1.7\n%\xe2\xe3\xcf\xd3\n11 0 obj\n<</Linearized 1/L 48213/H [ 648 160 ]/O 11/E 40113/N 3/T 47907>>\nendobj\n";
+        assert_eq!(estimate_pages(head, 48_213), (3, true));
+        // /N außerhalb des Linearisierungs-Dictionaries zählt nicht
+        let plain = b"%PDF-1.4\n1 0 obj\n<</Type/Catalog/Names 2 0 R>>\n<</N 9>>";
+        assert_eq!(estimate_pages(plain, 250 * 1024), (3, false));
+        assert_eq!(estimate_pages(b"%PDF-1.4\n", 0), (1, false));
+    }
+
+    #[test]
+    fn plans_merge_positions_totals_and_missing_names() {
+        let cfg = ScanConfig {
+            enabled: false,
+            clamd_addr: None,
+            max_upload_bytes: 1_000,
+            max_file_bytes: 500,
+            max_job_bytes: 800,
+            quarantine_dir: PathBuf::from("/tmp"),
+        };
+        let listed = [file("a.pdf", 400), file("b.pdf", 600), file("c.pdf", 300)];
+        let wanted = vec!["c.pdf".to_string(), "x.pdf".to_string()];
+        let mut preview = FolderPreview::new(
+            "f1".into(),
+            "Akte".into(),
+            missing_filenames(&listed, Some(&wanted)),
+        );
+        for file in listed {
+            let rejection = scan::check_size(file_size(&file), preview.total_bytes, &cfg);
+            let pages = rejection.is_none().then_some((2, false));
+            preview.push(file, pages, rejection);
+        }
+
+        assert_eq!(preview.missing_filenames, ["x.pdf"]);
+        let positions: Vec<_> = preview.files.iter().map(|f| f.position).collect();
+        assert_eq!(positions, [Some(1), None, Some(2)]);
+        assert_eq!(
+            preview.files[1].rejection.as_ref().map(|r| r.reason),
+            Some(RejectReason::FileTooLarge)
+        );
+        assert_eq!(preview.merged_count, 2);
+        assert_eq!(preview.rejected_count, 1);
+        assert_eq!(preview.total_bytes, 700);
+        assert_eq!(preview.estimated_pages, 4);
+    }
+}
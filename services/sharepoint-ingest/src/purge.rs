@@ -0,0 +1,219 @@
+//! Deleting finished jobs (`DELETE /jobs/{id}`, `POST /jobs/purge`).
+//!
+//! Only succeeded, failed and canceled jobs are deleted. The job row and its
+//! per-file state go away; quarantine entries, seen SFTP files and IMAP
+//! messages keep their rows (they prevent a second import) and only lose the
+//! reference to the job. A folder with a succeeded job stays hidden from
+//! `/folders` and the automation poller after the job is gone: it is recorded
+//! in `sharepoint_retired_folders`, unless the request releases the folders
+//! for a new ingest.
+
+use std::collections::HashSet;
+
+use anyhow::{bail, Result};
+use chrono::{DateTime, Utc};
+use deadpool_postgres::Pool;
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+use crate::job::{JobState, JobStatus};
+
+/// Creates `sharepoint_retired_folders` (see migration 0056).
+pub const PURGE_SCHEMA_SQL: &str = r#"
+CREATE TABLE IF NOT EXISTS sharepoint_retired_folders (
+    folder_id TEXT PRIMARY KEY,
+    folder_name TEXT NOT NULL,
+    retired_at TIMESTAMPTZ NOT NULL DEFAULT now()
+);
+"#;
+
+/// Body of `POST /jobs/purge`; at least one of `status`, `before` and
+/// `folder_id` is required.
+#[derive(Debug, Default, Deserialize)]
+pub struct PurgeFilter {
+    /// Finished statuses to delete; all three when empty.
+    #[serde(default)]
+    pub status: Vec<JobStatus>,
+    /// Only jobs created before this time.
+    #[serde(default)]
+    pub before: Option<DateTime<Utc>>,
+    #[serde(default)]
+    pub folder_id: Option<String>,
+    /// Show the folders in `/folders` again and let the automation ingest them.
+    #[serde(default)]
+    pub release_folders: bool,
+}
+
+impl PurgeFilter {
+    pub fn validate(&self) -> Result<()> {
+        if self.status.is_empty() && self.before.is_none() && self.folder_id.is_none() {
+            bail!("purge needs status, before or folder_id");
+        }
+        if let Some(status) = self.status.iter().find(|s| !s.is_finished()) {
+            bail!("{} jobs cannot be deleted", status.as_str());
+        }
+        Ok(())
+    }
+
+    pub fn matches(&self, job: &JobState) -> bool {
+        let before = match self.before {
+            Some(before) => job.created_at < before,
+            None => true,
+        };
+        let folder = match &self.folder_id {
+            Some(folder_id) => &job.folder_id == folder_id,
+            None => true,
+        };
+        job.status.is_finished()
+            && (self.status.is_empty() || self.status.contains(&job.status))
+            && before
+            && folder
+    }
+}
+
+#[derive(Debug, Default, Serialize)]
+pub struct PurgeResult {
+    pub deleted: Vec<Uuid>,
+    /// Folders that stay hidden although their jobs were deleted.
+    pub retired_folders: Vec<String>,
+    /// Folders available for a new ingest again.
+    pub released_folders: Vec<String>,
+}
+
+/// Deletes the records of `jobs` (already removed from the registry) in one
+/// transaction.
+pub async fn delete_jobs(
+    pool: &Pool,
+    jobs: &[JobState],
+    release_folders: bool,
+) -> Result<PurgeResult> {
+    let ids: Vec<Uuid> = jobs.iter().map(|job| job.id).collect();
+    let mut client = pool.get().await?;
+    let tx = client.transaction().await?;
+    tx.execute(
+        "DELETE FROM sharepoint_job_files WHERE job_id = ANY($1)",
+        &[&ids],
+    )
+    .await?;
+    for table in ["scan_quarantine", "sftp_seen_files", "imap_messages"] {
+        tx.execute(
+            &format!("UPDATE {table} SET job_id = NULL WHERE job_id = ANY($1)"),
+            &[&ids],
+        )
+        .await?;
+    }
+    tx.execute("DELETE FROM sharepoint_jobs WHERE id = ANY($1)", &[&ids])
+        .await?;
+
+    let mut result = PurgeResult {
+        deleted: ids,
+        ..PurgeResult::default()
+    };
+    let mut seen = HashSet::new();
+    for job in jobs {
+        if !seen.insert(job.folder_id.as_str()) {
+            continue;
+        }
+        if release_folders {
+            tx.execute(
+                "DELETE FROM sharepoint_retired_folders WHERE folder_id = $1",
+                &[&job.folder_id],
+            )
+            .await?;
+            result.released_folders.push(job.folder_id.clone());
+        } else if jobs
+            .iter()
+            .any(|j| j.folder_id == job.folder_id && j.status == JobStatus::Succeeded)
+        {
+            tx.execute(
+                "INSERT INTO sharepoint_retired_folders (folder_id, folder_name) VALUES ($1, $2)
+                 ON CONFLICT (folder_id) DO NOTHING",
+                &[&job.folder_id, &job.folder_name],
+            )
+            .await?;
+            result.retired_folders.push(job.folder_id.clone());
+        }
+    }
+    tx.commit().await?;
+    Ok(result)
+}
+
+/// Folders whose succeeded jobs were deleted without releasing them.
+pub async fn retired_folders(client: &tokio_postgres::Client) -> Result<HashSet<String>> {
+    let rows = client
+        .query("SELECT folder_id FROM sharepoint_retired_folders", &[])
+        .await?;
+    Ok(rows.iter().map(|row| row.get("folder_id")).collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::job::{JobOrder, JobReference, JobRegistry, JobSource};
+    use shared::dto::RunPriority;
+
+    #[test]
+    fn rejects_unfiltered_and_active_purges() {
+        assert!(PurgeFilter::default().validate().is_err());
+        let running = PurgeFilter {
+            status: vec![JobStatus::Failed, JobStatus::Running],
+            ..PurgeFilter::default()
+        };
+        assert!(running.validate().is_err());
+        let folder = PurgeFilter {
+            folder_id: Some("folder".into()),
+            ..PurgeFilter::default()
+        };
+        assert!(folder.validate().is_ok());
+    }
+
+    #[test]
+    fn matches_only_finished_jobs_within_the_filter() {
+        let cutoff = Utc::now();
+        let registry = JobRegistry::new(None, 1);
+        let job = |status, folder_id: &str, age_days| {
+            let job = registry.create_job(
+                folder_id.into(),
+                folder_id.into(),
+                JobOrder::default(),
+                None,
+                None,
+                None,
+                None,
+                false,
+                JobSource::SharePoint,
+                JobReference::default(),
+                RunPriority::Normal,
+            );
+            let mut state = job.state.lock().clone();
+            state.set_status(status);
+            state.created_at = cutoff - chrono::Duration::days(age_days);
+            state
+        };
+        let filter = PurgeFilter {
+            status: vec![JobStatus::Failed, JobStatus::Succeeded],
+            before: Some(cutoff - chrono::Duration::days(30)),
+            ..PurgeFilter::default()
+        };
+        assert!(filter.matches(&job(JobStatus::Failed, "a", 40)));
+        assert!(
+            !filter.matches(&job(JobStatus::Failed, "a", 10)),
+            "too recent"
+        );
+        assert!(
+            !filter.matches(&job(JobStatus::Canceled, "a", 40)),
+            "other status"
+        );
+
+        let folder = PurgeFilter {
+            folder_id: Some("a".into()),
+            ..PurgeFilter::default()
+        };
+        assert!(folder.matches(&job(JobStatus::Canceled, "a", 0)));
+        assert!(!folder.matches(&job(JobStatus::Canceled, "b", 0)));
+        assert!(
+            !folder.matches(&job(JobStatus::Running, "a", 0)),
+            "active job"
+        );
+    }
+}
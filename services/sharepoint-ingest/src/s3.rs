@@ -0,0 +1,746 @@
+//! S3 source connector (AWS S3, MinIO and other S3-compatible stores):
+//! customers drop scanned batches into a bucket instead of SharePoint.
+//!
+//! Sources are stored in `s3_sources`, one bucket and key prefix each, scoped
+//! to a tenant. Every "directory" directly below the prefix is an ingest
+//! folder; folder ids are `<source id>/<prefix><folder>/`, file ids
+//! `<source id>/<object key>`. Jobs, automation rules and the poller use the
+//! source like any other drive (`connector: "s3"`), so merge, scan, upload and
+//! pipeline start are unchanged.
+//!
+//! The secret access key is envelope-encrypted with `CREDENTIALS_MASTER_KEY`.
+//! Sources without stored keys use `S3_SOURCE_ACCESS_KEY_ID` and
+//! `S3_SOURCE_SECRET_ACCESS_KEY`, e.g. for an instance role proxy or a MinIO
+//! shared by all tenants. Requests are signed with AWS Signature V4 (query
+//! string, `UNSIGNED-PAYLOAD`, see [`shared::sigv4`]).
+
+use std::collections::BTreeMap;
+use std::path::Path;
+use std::time::Duration;
+
+use anyhow::{anyhow, bail, Context, Result};
+use chrono::{DateTime, Utc};
+use deadpool_postgres::Pool;
+use quick_xml::events::Event;
+use quick_xml::Reader;
+use reqwest::{header::RANGE, StatusCode, Url};
+use serde::{Deserialize, Serialize};
+use shared::envelope::{MasterKey, SealedSecret};
+use shared::sigv4::Presigner;
+use tokio::fs::File;
+use tokio::io::AsyncWriteExt;
+use tokio_postgres::Row;
+use tracing::warn;
+use uuid::Uuid;
+
+use crate::config::Config;
+use crate::connector::{ConnectorFuture, SourceConnector};
+use crate::connector_config::{non_empty, require_master_key, MasterKeyed};
+use crate::msgraph::{GraphFile, GraphFolder};
+
+/// Creates `s3_sources` (see migration 0063).
+pub const S3_SCHEMA_SQL: &str = r#"
+CREATE TABLE IF NOT EXISTS s3_sources (
+    id UUID PRIMARY KEY,
+    name TEXT NOT NULL,
+    tenant_id UUID,
+    endpoint TEXT,
+    region TEXT,
+    bucket TEXT NOT NULL,
+    prefix TEXT NOT NULL DEFAULT '',
+    path_style BOOLEAN NOT NULL DEFAULT FALSE,
+    access_key_id TEXT,
+    credentials JSONB NOT NULL DEFAULT '{}'::jsonb,
+    enabled BOOLEAN NOT NULL DEFAULT TRUE,
+    last_polled_at TIMESTAMPTZ,
+    last_error TEXT,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT now(),
+    updated_at TIMESTAMPTZ NOT NULL DEFAULT now()
+);
+"#;
+
+const SOURCE_COLUMNS: &str = "id, name, tenant_id, endpoint, region, bucket, prefix, path_style,
+     access_key_id, credentials, enabled, last_polled_at, last_error, updated_at";
+
+/// Validity of the signed request URLs; they are used right away.
+const PRESIGN_TTL: Duration = Duration::from_secs(300);
+
+/// Secrets of a source.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+struct SealedCredentials {
+    #[serde(default)]
+    secret_access_key: Option<SealedSecret>,
+}
+
+#[derive(Debug, Clone)]
+pub struct S3Source {
+    pub id: Uuid,
+    pub name: String,
+    pub tenant_id: Option<Uuid>,
+    /// `None` uses `S3_SOURCE_ENDPOINT` or AWS.
+    pub endpoint: Option<String>,
+    /// `None` uses `S3_SOURCE_REGION`.
+    pub region: Option<String>,
+    pub bucket: String,
+    /// Key prefix ending with `/`, empty for the whole bucket.
+    pub prefix: String,
+    pub path_style: bool,
+    pub access_key_id: Option<String>,
+    credentials: SealedCredentials,
+    pub enabled: bool,
+    pub last_polled_at: Option<DateTime<Utc>>,
+    pub last_error: Option<String>,
+    pub updated_at: DateTime<Utc>,
+}
+
+/// Source as returned by the API; the secret is reduced to a flag.
+#[derive(Debug, Serialize)]
+pub struct S3SourceSummary {
+    pub id: Uuid,
+    pub name: String,
+    pub tenant_id: Option<Uuid>,
+    pub endpoint: Option<String>,
+    pub region: Option<String>,
+    pub bucket: String,
+    pub prefix: String,
+    pub path_style: bool,
+    pub access_key_id: Option<String>,
+    pub has_secret_access_key: bool,
+    pub enabled: bool,
+    pub last_polled_at: Option<DateTime<Utc>>,
+    pub last_error: Option<String>,
+    pub updated_at: DateTime<Utc>,
+}
+
+impl S3Source {
+    fn from_row(row: &Row) -> Result<Self> {
+        let credentials: serde_json::Value = row.get("credentials");
+        Ok(Self {
+            id: row.get("id"),
+            name: row.get("name"),
+            tenant_id: row.get("tenant_id"),
+            endpoint: row.get("endpoint"),
+            region: row.get("region"),
+            bucket: row.get("bucket"),
+            prefix: row.get("prefix"),
+            path_style: row.get("path_style"),
+            access_key_id: row.get("access_key_id"),
+            credentials: serde_json::from_value(credentials)
+                .context("invalid s3 credentials record")?,
+            enabled: row.get("enabled"),
+            last_polled_at: row.get("last_polled_at"),
+            last_error: row.get("last_error"),
+            updated_at: row.get("updated_at"),
+        })
+    }
+
+    pub fn summary(&self) -> S3SourceSummary {
+        S3SourceSummary {
+            id: self.id,
+            name: self.name.clone(),
+            tenant_id: self.tenant_id,
+            endpoint: self.endpoint.clone(),
+            region: self.region.clone(),
+            bucket: self.bucket.clone(),
+            prefix: self.prefix.clone(),
+            path_style: self.path_style,
+            access_key_id: self.access_key_id.clone(),
+            has_secret_access_key: self.credentials.secret_access_key.is_some(),
+            enabled: self.enabled,
+            last_polled_at: self.last_polled_at,
+            last_error: self.last_error.clone(),
+            updated_at: self.updated_at,
+        }
+    }
+}
+
+/// Payload of `PUT /s3/sources/{id}`; an omitted secret keeps its stored value.
+#[derive(Debug, Deserialize)]
+pub struct S3SourceInput {
+    pub name: String,
+    #[serde(default)]
+    pub tenant_id: Option<Uuid>,
+    #[serde(default)]
+    pub endpoint: Option<String>,
+    #[serde(default)]
+    pub region: Option<String>,
+    pub bucket: String,
+    #[serde(default)]
+    pub prefix: Option<String>,
+    #[serde(default)]
+    pub path_style: Option<bool>,
+    #[serde(default)]
+    pub access_key_id: Option<String>,
+    #[serde(default)]
+    pub secret_access_key: Option<String>,
+    #[serde(default)]
+    pub enabled: Option<bool>,
+}
+
+impl S3SourceInput {
+    /// Checks required fields; `is_new` requires the secret with a key id.
+    pub fn validate(&self, is_new: bool) -> Result<()> {
+        if self.name.trim().is_empty() || self.bucket.trim().is_empty() {
+            bail!("name and bucket are required");
+        }
+        if let Some(endpoint) = non_empty(self.endpoint.clone()) {
+            let url = Url::parse(endpoint.trim()).context("invalid endpoint")?;
+            if url.host_str().is_none() {
+                bail!("endpoint without host");
+            }
+        }
+        if is_new
+            && non_empty(self.access_key_id.clone()).is_some()
+            && non_empty(self.secret_access_key.clone()).is_none()
+        {
+            bail!("secret_access_key is required with access_key_id");
+        }
+        Ok(())
+    }
+
+    pub fn has_secret(&self) -> bool {
+        non_empty(self.secret_access_key.clone()).is_some()
+    }
+}
+
+/// `scans//eingang` → `scans/eingang/`; empty stays empty.
+fn normalize_prefix(prefix: &str) -> String {
+    let mut normalized: String = prefix
+        .split('/')
+        .filter(|segment| !segment.is_empty())
+        .collect::<Vec<_>>()
+        .join("/");
+    if !normalized.is_empty() {
+        normalized.push('/');
+    }
+    normalized
+}
+
+pub async fn list_sources(pool: &Pool) -> Result<Vec<S3Source>> {
+    let client = pool.get().await?;
+    let rows = client
+        .query(
+            &format!("SELECT {SOURCE_COLUMNS} FROM s3_sources ORDER BY name"),
+            &[],
+        )
+        .await?;
+    rows.iter().map(S3Source::from_row).collect()
+}
+
+pub async fn load_source(pool: &Pool, id: Uuid) -> Result<Option<S3Source>> {
+    let client = pool.get().await?;
+    let row = client
+        .query_opt(
+            &format!("SELECT {SOURCE_COLUMNS} FROM s3_sources WHERE id = $1"),
+            &[&id],
+        )
+        .await?;
+    row.as_ref().map(S3Source::from_row).transpose()
+}
+
+/// Seals the secret and upserts a source validated with [`S3SourceInput::validate`];
+/// `master` is only needed when the input carries a secret.
+pub async fn upsert_source(
+    pool: &Pool,
+    master: Option<&MasterKey>,
+    id: Uuid,
+    input: S3SourceInput,
+) -> Result<S3Source> {
+    let existing = load_source(pool, id)
+        .await?
+        .map(|s| s.credentials)
+        .unwrap_or_default();
+    let secret = match non_empty(input.secret_access_key) {
+        Some(secret) => Some(require_master_key(master)?.seal(&secret)?),
+        None => existing.secret_access_key,
+    };
+    let credentials = SealedCredentials {
+        secret_access_key: secret,
+    };
+    let client = pool.get().await?;
+    client
+        .execute(
+            "INSERT INTO s3_sources (
+                id, name, tenant_id, endpoint, region, bucket, prefix, path_style,
+                access_key_id, credentials, enabled
+             ) VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11)
+             ON CONFLICT (id) DO UPDATE SET
+                name = EXCLUDED.name,
+                tenant_id = EXCLUDED.tenant_id,
+                endpoint = EXCLUDED.endpoint,
+                region = EXCLUDED.region,
+                bucket = EXCLUDED.bucket,
+                prefix = EXCLUDED.prefix,
+                path_style = EXCLUDED.path_style,
+                access_key_id = EXCLUDED.access_key_id,
+                credentials = EXCLUDED.credentials,
+                enabled = EXCLUDED.enabled,
+                updated_at = now()",
+            &[
+                &id,
+                &input.name.trim(),
+                &input.tenant_id,
+                &non_empty(input.endpoint).map(|v| v.trim().trim_end_matches('/').to_string()),
+                &non_empty(input.region).map(|v| v.trim().to_string()),
+                &input.bucket.trim(),
+                &normalize_prefix(input.prefix.as_deref().unwrap_or_default()),
+                &input.path_style.unwrap_or(false),
+                &non_empty(input.access_key_id).map(|v| v.trim().to_string()),
+                &serde_json::to_value(&credentials)?,
+                &input.enabled.unwrap_or(true),
+            ],
+        )
+        .await?;
+    load_source(pool, id)
+        .await?
+        .ok_or_else(|| anyhow!("s3 source vanished after upsert"))
+}
+
+pub async fn delete_source(pool: &Pool, id: Uuid) -> Result<bool> {
+    let client = pool.get().await?;
+    let deleted = client
+        .execute("DELETE FROM s3_sources WHERE id = $1", &[&id])
+        .await?;
+    Ok(deleted > 0)
+}
+
+async fn record_poll(pool: &Pool, id: Uuid, error: Option<&str>) -> Result<()> {
+    let client = pool.get().await?;
+    client
+        .execute(
+            "UPDATE s3_sources SET last_polled_at = now(), last_error = $2 WHERE id = $1",
+            &[&id, &error],
+        )
+        .await?;
+    Ok(())
+}
+
+/// Connection settings of sources that store none of their own.
+#[derive(Clone)]
+pub struct S3Defaults {
+    pub endpoint: Option<String>,
+    pub region: String,
+    pub access_key: Option<(String, String)>,
+}
+
+impl S3Defaults {
+    pub fn from_config(config: &Config) -> Self {
+        Self {
+            endpoint: config.s3_source_endpoint.clone(),
+            region: config.s3_source_region.clone(),
+            access_key: config.s3_source_access_key.clone(),
+        }
+    }
+}
+
+/// An object of a `ListObjectsV2` response.
+#[derive(Debug, Clone, Default, PartialEq)]
+struct S3Object {
+    key: String,
+    etag: Option<String>,
+    size: i64,
+}
+
+/// One page of a `ListObjectsV2` response.
+#[derive(Debug, Default)]
+struct ListPage {
+    objects: Vec<S3Object>,
+    next_token: Option<String>,
+}
+
+fn parse_list(xml: &str) -> Result<ListPage> {
+    let mut reader = Reader::from_str(xml);
+    reader.trim_text(true);
+    let mut page = ListPage::default();
+    let mut path: Vec<String> = Vec::new();
+    let mut object = S3Object::default();
+    let mut truncated = false;
+    loop {
+        match reader
+            .read_event()
+            .context("parse ListObjectsV2 response")?
+        {
+            Event::Start(tag) => {
+                let name = String::from_utf8_lossy(tag.local_name().as_ref()).into_owned();
+                if name == "Contents" {
+                    object = S3Object::default();
+                }
+                path.push(name);
+            }
+            Event::End(_) => {
+                let closed = path.pop();
+                if closed.as_deref() == Some("Contents") {
+                    page.objects.push(std::mem::take(&mut object));
+                }
+            }
+            Event::Text(text) => {
+                let text = text.unescape().context("parse ListObjectsV2 response")?;
+                let parent = path.len().checked_sub(2).map(|i| path[i].as_str());
+                match (parent, path.last().map(String::as_str)) {
+                    (Some("Contents"), Some("Key")) => object.key = text.into_owned(),
+                    (Some("Contents"), Some("ETag")) => {
+                        object.etag = Some(text.trim_matches('"').to_string())
+                    }
+                    (Some("Contents"), Some("Size")) => object.size = text.parse().unwrap_or(0),
+                    (_, Some("IsTruncated")) => truncated = text == "true",
+                    (_, Some("NextContinuationToken")) => page.next_token = Some(text.into_owned()),
+                    _ => {}
+                }
+            }
+            Event::Eof => break,
+            _ => {}
+        }
+    }
+    if !truncated {
+        page.next_token = None;
+    }
+    Ok(page)
+}
+
+/// Groups the keys below `prefix` by their first segment; objects directly
+/// under the prefix are no folder.
+fn group_folders(source_id: Uuid, prefix: &str, objects: &[S3Object]) -> Vec<GraphFolder> {
+    let mut counts: BTreeMap<&str, i64> = BTreeMap::new();
+    for object in objects {
+        let Some(rest) = object.key.strip_prefix(prefix) else {
+            continue;
+        };
+        let Some((folder, name)) = rest.split_once('/') else {
+            continue;
+        };
+        if folder.is_empty() {
+            continue;
+        }
+        // "Ordner"-Marker ohne Namen zählen nicht als Datei
+        *counts.entry(folder).or_default() += i64::from(!name.is_empty());
+    }
+    counts
+        .into_iter()
+        .map(|(folder, file_count)| GraphFolder {
+            id: format!("{source_id}/{prefix}{folder}/"),
+            name: folder.to_string(),
+            file_count,
+        })
+        .collect()
+}
+
+/// Splits a folder or file id into source id and object key.
+fn split_id(id: &str) -> Result<(Uuid, &str)> {
+    let (source, key) = id
+        .split_once('/')
+        .ok_or_else(|| anyhow!("invalid s3 id {id:?}"))?;
+    let source = Uuid::parse_str(source).with_context(|| format!("invalid s3 id {id:?}"))?;
+    Ok((source, key))
+}
+
+/// Buckets of the `s3_sources`; one connector serves all enabled sources.
+pub struct S3Connector {
+    pool: Pool,
+    master_key: Option<MasterKey>,
+    defaults: S3Defaults,
+    http: reqwest::Client,
+    timeout: Duration,
+}
+
+impl MasterKeyed for S3Connector {
+    fn master_key(&self) -> Option<&MasterKey> {
+        self.master_key.as_ref()
+    }
+}
+
+impl S3Connector {
+    pub fn new(
+        pool: Pool,
+        master_key: Option<MasterKey>,
+        defaults: S3Defaults,
+        timeout: Duration,
+    ) -> Result<Self> {
+        let http = reqwest::Client::builder()
+            .connect_timeout(timeout)
+            .build()
+            .context("build s3 http client")?;
+        Ok(Self {
+            pool,
+            master_key,
+            defaults,
+            http,
+            timeout,
+        })
+    }
+
+    fn bucket(&self, source: &S3Source) -> Result<Presigner> {
+        let region = source
+            .region
+            .clone()
+            .unwrap_or_else(|| self.defaults.region.clone());
+        let endpoint = source
+            .endpoint
+            .clone()
+            .or_else(|| self.defaults.endpoint.clone())
+            .unwrap_or_else(|| format!("https://s3.{region}.amazonaws.com"));
+        let (access_key, secret_key) =
+            match (&source.access_key_id, &source.credentials.secret_access_key) {
+                (Some(key_id), Some(secret)) => {
+                    let master = self.require_master_key()?;
+                    (key_id.clone(), master.open(secret)?)
+                }
+                (Some(_), None) => bail!("s3 source {} has no secret_access_key", source.id),
+                (None, _) => self.defaults.access_key.clone().ok_or_else(|| {
+                    anyhow!(
+                        "s3 source {} has no credentials and S3_SOURCE_ACCESS_KEY_ID is not set",
+                        source.id
+                    )
+                })?,
+            };
+        let mut bucket = Presigner::new(
+            &endpoint,
+            source.bucket.clone(),
+            region,
+            access_key,
+            secret_key,
+        )?;
+        bucket.path_style = source.path_style;
+        bucket.ttl = PRESIGN_TTL;
+        Ok(bucket)
+    }
+
+    /// Enabled source of a folder or file id and its object key; keys outside
+    /// the source prefix are refused.
+    async fn resolve<'a>(&self, id: &'a str) -> Result<(S3Source, Presigner, &'a str)> {
+        let (source_id, key) = split_id(id)?;
+        let source = load_source(&self.pool, source_id)
+            .await?
+            .filter(|source| source.enabled)
+            .ok_or_else(|| anyhow!("s3 source {source_id} not found or disabled"))?;
+        if !key.starts_with(&source.prefix) || key.split('/').any(|s| s == "..") {
+            bail!("key {key:?} is outside of s3 source {source_id}");
+        }
+        let bucket = self.bucket(&source)?;
+        Ok((source, bucket, key))
+    }
+
+    /// All objects below `prefix`, non-recursive with `delimited`.
+    async fn list_objects(
+        &self,
+        bucket: &Presigner,
+        prefix: &str,
+        delimited: bool,
+    ) -> Result<Vec<S3Object>> {
+        let mut objects = Vec::new();
+        let mut token: Option<String> = None;
+        loop {
+            let mut params = vec![("list-type", "2"), ("prefix", prefix)];
+            if delimited {
+                params.push(("delimiter", "/"));
+            }
+            if let Some(token) = &token {
+                params.push(("continuation-token", token));
+            }
+            let url = bucket.presign("GET", "", Utc::now(), &params, &[]);
+            let body = self
+                .http
+                .get(url)
+                .timeout(self.timeout)
+                .send()
+                .await
+                .context("request bucket listing")?
+                .error_for_status()
+                .context("list bucket")?
+                .text()
+                .await
+                .context("read bucket listing")?;
+            let page = parse_list(&body)?;
+            objects.extend(page.objects);
+            match page.next_token {
+                Some(next) => token = Some(next),
+                None => break,
+            }
+        }
+        Ok(objects)
+    }
+
+    async fn folders(&self) -> Result<Vec<GraphFolder>> {
+        let mut folders = Vec::new();
+        for source in list_sources(&self.pool).await? {
+            if !source.enabled {
+                continue;
+            }
+            // Ein nicht erreichbarer Bucket soll die übrigen Quellen nicht blockieren
+            let listed = match self.bucket(&source) {
+                Ok(bucket) => self.list_objects(&bucket, &source.prefix, false).await,
+                Err(err) => Err(err),
+            };
+            let error = listed.as_ref().err().map(|err| format!("{err:#}"));
+            if let Err(err) = record_poll(&self.pool, source.id, error.as_deref()).await {
+                warn!(source = %source.id, error = %err, "recording s3 poll failed");
+            }
+            match listed {
+                Ok(objects) => folders.extend(group_folders(source.id, &source.prefix, &objects)),
+                Err(err) => warn!(source = %source.id, error = %err, "s3 listing failed"),
+            }
+        }
+        folders.sort_by(|a, b| a.name.cmp(&b.name));
+        Ok(folders)
+    }
+
+    async fn pdfs(&self, folder_id: &str) -> Result<Vec<GraphFile>> {
+        let (source, bucket, prefix) = self.resolve(folder_id).await?;
+        let prefix = normalize_prefix(prefix);
+        let mut files: Vec<GraphFile> = self
+            .list_objects(&bucket, &prefix, true)
+            .await?
+            .into_iter()
+            .filter_map(|object| {
+                let name = object.key.strip_prefix(&prefix)?.to_string();
+                if name.contains('/') || !name.to_ascii_lowercase().ends_with(".pdf") {
+                    return None;
+                }
+                Some(GraphFile {
+                    id: format!("{}/{}", source.id, object.key),
+                    name,
+                    etag: object.etag,
+                    size: Some(object.size),
+                })
+            })
+            .collect();
+        files.sort_by(|a, b| a.name.cmp(&b.name));
+        Ok(files)
+    }
+
+    async fn get(&self, file_id: &str, range: Option<usize>) -> Result<reqwest::Response> {
+        let (_, bucket, key) = self.resolve(file_id).await?;
+        let mut request = self
+            .http
+            .get(bucket.presign("GET", key, Utc::now(), &[], &[]));
+        if let Some(len) = range {
+            request = request.header(RANGE, format!("bytes=0-{}", len.saturating_sub(1)));
+        }
+        request.send().await.context("request object")
+    }
+
+    async fn copy(&self, file_id: &str, dest: &Path) -> Result<()> {
+        let mut response = self
+            .get(file_id, None)
+            .await?
+            .error_for_status()
+            .context("fetch object")?;
+        let mut file = File::create(dest).await?;
+        while let Some(chunk) = response.chunk().await? {
+            file.write_all(&chunk).await?;
+        }
+        file.flush().await?;
+        Ok(())
+    }
+
+    async fn head(&self, file_id: &str, len: usize) -> Result<Vec<u8>> {
+        let response = self.get(file_id, Some(len)).await?;
+        if response.status() == StatusCode::RANGE_NOT_SATISFIABLE {
+            // leeres Objekt
+            return Ok(Vec::new());
+        }
+        let mut response = response.error_for_status().context("fetch object")?;
+        let mut head = Vec::with_capacity(len);
+        while head.len() < len {
+            let Some(chunk) = response.chunk().await? else {
+                break;
+            };
+            head.extend_from_slice(&chunk);
+        }
+        head.truncate(len);
+        Ok(head)
+    }
+}
+
+impl SourceConnector for S3Connector {
+    fn base(&self) -> String {
+        "s3".to_string()
+    }
+
+    fn list_folders(&self) -> ConnectorFuture<'_, Vec<GraphFolder>> {
+        Box::pin(self.folders())
+    }
+
+    fn list_pdfs<'a>(&'a self, folder_id: &'a str) -> ConnectorFuture<'a, Vec<GraphFile>> {
+        Box::pin(self.pdfs(folder_id))
+    }
+
+    fn download<'a>(&'a self, file_id: &'a str, dest: &'a Path) -> ConnectorFuture<'a, ()> {
+        Box::pin(self.copy(file_id, dest))
+    }
+
+    fn download_head<'a>(&'a self, file_id: &'a str, len: usize) -> ConnectorFuture<'a, Vec<u8>> {
+        Box::pin(self.head(file_id, len))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_listing_pages_and_groups_folders() {
+        let xml = r#"<?xml version="1.0" encoding="UTF-8"?>
+<ListBucketResult xmlns="http://s3.amazonaws.com/doc/2006-03-01/">
+  <Name>examplebucket</Name><Prefix>scans/</Prefix><KeyCount>5</KeyCount>
+  <IsTruncated>true</IsTruncated>
+  <NextContinuationToken>1ueGcxLPRx1Tr/XYExHnhbYLgveDs2J/wm36Hy4vbOwM=</NextContinuationToken>
+  <Contents><Key>scans/Akte 1/</Key><ETag>&quot;d41d8cd98f00b204e9800998ecf8427e&quot;</ETag><Size>0</Size></Contents>
+  <Contents><Key>scans/Akte 1/a.pdf</Key><ETag>"9b2cf535f27731c974343645a3985328"</ETag><Size>1024</Size></Contents>
+  <Contents><Key>scans/Akte 1/sub/b.pdf</Key><Size>10</Size></Contents>
+  <Contents><Key>scans/B &amp; C/c.PDF</Key><Size>20</Size></Contents>
+  <Contents><Key>scans/loose.pdf</Key><Size>5</Size></Contents>
+</ListBucketResult>"#;
+        let page = parse_list(xml).unwrap();
+        assert_eq!(
+            page.next_token.as_deref(),
+            Some("1ueGcxLPRx1Tr/XYExHnhbYLgveDs2J/wm36Hy4vbOwM=")
+        );
+        assert_eq!(page.objects.len(), 5);
+        assert_eq!(
+            page.objects[1],
+            S3Object {
+                key: "scans/Akte 1/a.pdf".into(),
+                etag: Some("9b2cf535f27731c974343645a3985328".into()),
+                size: 1024,
+            }
+        );
+        assert_eq!(page.objects[3].key, "scans/B & C/c.PDF");
+
+        let source = Uuid::from_u128(7);
+        let folders = group_folders(source, "scans/", &page.objects);
+        let folders: Vec<_> = folders
+            .iter()
+            .map(|f| (f.id.as_str(), f.name.as_str(), f.file_count))
+            .collect();
+        assert_eq!(
+            folders,
+            [
+                (
+                    "00000000-0000-0000-0000-000000000007/scans/Akte 1/",
+                    "Akte 1",
+                    2
+                ),
+                (
+                    "00000000-0000-0000-0000-000000000007/scans/B & C/",
+                    "B & C",
+                    1
+                ),
+            ]
+        );
+
+        let last =
+            parse_list("<ListBucketResult><IsTruncated>false</IsTruncated></ListBucketResult>")
+                .unwrap();
+        assert!(last.next_token.is_none() && last.objects.is_empty());
+    }
+
+    #[test]
+    fn ids_and_prefixes() {
+        let (source, key) = split_id("00000000-0000-0000-0000-000000000007/scans/a.pdf").unwrap();
+        assert_eq!((source, key), (Uuid::from_u128(7), "scans/a.pdf"));
+        assert!(split_id("scans/a.pdf").is_err());
+        assert!(split_id("no-slash").is_err());
+        assert_eq!(normalize_prefix("/scans//eingang"), "scans/eingang/");
+        assert_eq!(normalize_prefix(""), "");
+    }
+}
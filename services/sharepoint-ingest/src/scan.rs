@@ -1,17 +1,66 @@
-//! Lightweight TCP scanner that discovers the SharePoint conversion worker.
+//! Security checks for downloaded files: type sniffing, size ceilings, ClamAV
+//! and a quarantine store for rejected files.
+//!
+//! Rejected files are moved into `QUARANTINE_DIR` and recorded in
+//! `scan_quarantine`; the job continues with the remaining files. Operators can
+//! inspect, release (file is accepted on the next run) or delete them.
 
 use anyhow::{bail, Context, Result};
-use std::{env, fs, io::Read, net::SocketAddr, path::Path};
+use chrono::{DateTime, Utc};
+use deadpool_postgres::Pool;
+use serde::Serialize;
+use sha2::{Digest, Sha256};
+use std::{
+    env, fs,
+    io::Read,
+    net::SocketAddr,
+    path::{Path, PathBuf},
+};
 use tokio::{
     io::{AsyncReadExt, AsyncWriteExt},
     net::TcpStream,
 };
+use tokio_postgres::Row;
+use tracing::warn;
+use uuid::Uuid;
+
+pub const QUARANTINE_SCHEMA_SQL: &str = r#"
+CREATE TABLE IF NOT EXISTS scan_quarantine (
+    id UUID PRIMARY KEY,
+    job_id UUID,
+    folder_id TEXT,
+    tenant_id UUID,
+    file_name TEXT NOT NULL,
+    reason TEXT NOT NULL,
+    detail TEXT,
+    size_bytes BIGINT NOT NULL,
+    sha256 TEXT NOT NULL,
+    stored_path TEXT,
+    status TEXT NOT NULL DEFAULT 'quarantined' CHECK (status IN ('quarantined','released','deleted')),
+    created_at TIMESTAMPTZ NOT NULL DEFAULT now(),
+    resolved_at TIMESTAMPTZ
+);
+
+CREATE INDEX IF NOT EXISTS idx_scan_quarantine_status ON scan_quarantine (status, created_at DESC);
+CREATE INDEX IF NOT EXISTS idx_scan_quarantine_sha256 ON scan_quarantine (sha256);
+CREATE INDEX IF NOT EXISTS idx_scan_quarantine_job ON scan_quarantine (job_id);
+"#;
+
+const QUARANTINE_COLUMNS: &str =
+    "id, job_id, folder_id, tenant_id, file_name, reason, detail, size_bytes,
+     sha256, stored_path, status, created_at, resolved_at";
+
+/// Bytes read from the file head for type sniffing.
+pub const SNIFF_BYTES: usize = 8 * 1024;
 
 #[derive(Clone, Debug)]
 pub struct ScanConfig {
     pub enabled: bool,
     pub clamd_addr: Option<SocketAddr>,
     pub max_upload_bytes: u64,
+    pub max_file_bytes: u64,
+    pub max_job_bytes: u64,
+    pub quarantine_dir: PathBuf,
 }
 
 impl ScanConfig {
@@ -27,14 +76,68 @@ impl ScanConfig {
             .and_then(|v| v.parse().ok())
             .unwrap_or(3310);
         let clamd_addr = host.and_then(|h| format!("{}:{}", h, port).parse().ok());
+        let mb = |key: &str, default: u64| {
+            env::var(key)
+                .ok()
+                .and_then(|v| v.parse().ok())
+                .unwrap_or(default)
+                * 1024
+                * 1024
+        };
         let max_upload_mb = env::var("MAX_UPLOAD_MB")
             .ok()
             .and_then(|v| v.parse().ok())
             .unwrap_or(200u64);
+        let quarantine_dir = env::var("QUARANTINE_DIR")
+            .ok()
+            .filter(|v| !v.trim().is_empty())
+            .unwrap_or_else(|| "/var/lib/sharepoint-ingest/quarantine".to_string());
         Self {
             enabled,
             clamd_addr,
             max_upload_bytes: max_upload_mb * 1024 * 1024,
+            max_file_bytes: mb("SCAN_MAX_FILE_MB", 100),
+            // Ohne eigenes Limit gilt für den ganzen Job dieselbe Grenze wie für den Upload
+            max_job_bytes: mb("SCAN_MAX_JOB_MB", max_upload_mb),
+            quarantine_dir: PathBuf::from(quarantine_dir),
+        }
+    }
+}
+
+/// Why a file was moved into quarantine.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum RejectReason {
+    DisguisedExecutable,
+    NotPdf,
+    FileTooLarge,
+    JobTooLarge,
+    Malware,
+}
+
+impl RejectReason {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            RejectReason::DisguisedExecutable => "disguised_executable",
+            RejectReason::NotPdf => "not_pdf",
+            RejectReason::FileTooLarge => "file_too_large",
+            RejectReason::JobTooLarge => "job_too_large",
+            RejectReason::Malware => "malware",
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Rejection {
+    pub reason: RejectReason,
+    pub detail: String,
+}
+
+impl Rejection {
+    fn new(reason: RejectReason, detail: impl Into<String>) -> Self {
+        Self {
+            reason,
+            detail: detail.into(),
         }
     }
 }
@@ -67,13 +170,40 @@ pub fn assert_pdf(path: &Path) -> Result<()> {
     Ok(())
 }
 
-/// Wirf Fehler bei Fund. Gibt Ok(()) wenn sauber oder Scan deaktiviert.
-pub async fn scan_with_clamd(path: &Path, cfg: &ScanConfig) -> Result<()> {
+/// Classifies a file head: executables and scripts are rejected regardless of
+/// their extension, everything else must carry the PDF magic.
+pub fn sniff_bytes(head: &[u8]) -> Option<Rejection> {
+    if let Some(kind) = infer::get(head) {
+        if kind.matcher_type() == infer::MatcherType::App {
+            return Some(Rejection::new(
+                RejectReason::DisguisedExecutable,
+                format!("content is {}", kind.mime_type()),
+            ));
+        }
+    }
+    if head.starts_with(b"#!") {
+        return Some(Rejection::new(
+            RejectReason::DisguisedExecutable,
+            "content is a script (shebang)",
+        ));
+    }
+    if !head.starts_with(b"%PDF-") {
+        let detail = infer::get(head)
+            .map(|kind| format!("content is {}", kind.mime_type()))
+            .unwrap_or_else(|| "pdf magic header missing".to_string());
+        return Some(Rejection::new(RejectReason::NotPdf, detail));
+    }
+    None
+}
+
+/// Returns the ClamAV signature if clamd reports a finding, `None` if the file
+/// is clean or scanning is disabled.
+pub async fn clamd_verdict(path: &Path, cfg: &ScanConfig) -> Result<Option<String>> {
     if !cfg.enabled {
-        return Ok(());
+        return Ok(None);
     }
     let Some(addr) = cfg.clamd_addr else {
-        return Ok(());
+        return Ok(None);
     }; // Scanner optional
     let meta = fs::metadata(path)?;
     if meta.len() > cfg.max_upload_bytes {
@@ -102,17 +232,306 @@ pub async fn scan_with_clamd(path: &Path, cfg: &ScanConfig) -> Result<()> {
     let text = String::from_utf8_lossy(&resp);
     // Beispiele: "stream: OK", "stream: Eicar-Test-Signature FOUND"
     if text.contains("FOUND") {
-        bail!("clamav detected malware: {}", text.trim());
+        let signature = text
+            .trim()
+            .trim_start_matches("stream:")
+            .trim_end_matches("FOUND")
+            .trim();
+        return Ok(Some(signature.to_string()));
     }
     if !text.contains("OK") {
         bail!("clamav scan uncertain: {}", text.trim());
     }
-    Ok(())
+    Ok(None)
+}
+
+/// Per-file and per-job size limits; `job_bytes` as in [`inspect_file`].
+pub fn check_size(size: u64, job_bytes: u64, cfg: &ScanConfig) -> Option<Rejection> {
+    if size > cfg.max_file_bytes {
+        return Some(Rejection::new(
+            RejectReason::FileTooLarge,
+            format!(
+                "{size} bytes exceed the per-file limit of {}",
+                cfg.max_file_bytes
+            ),
+        ));
+    }
+    if job_bytes + size > cfg.max_job_bytes {
+        return Some(Rejection::new(
+            RejectReason::JobTooLarge,
+            format!(
+                "{} bytes exceed the per-job limit of {}",
+                job_bytes + size,
+                cfg.max_job_bytes
+            ),
+        ));
+    }
+    None
+}
+
+/// Runs all checks on one file. `job_bytes` is the size of the files already
+/// accepted for the same job.
+pub async fn inspect_file(
+    path: &Path,
+    job_bytes: u64,
+    cfg: &ScanConfig,
+) -> Result<Option<Rejection>> {
+    let size = fs::metadata(path)
+        .with_context(|| format!("stat {}", path.display()))?
+        .len();
+    if let Some(rejection) = check_size(size, job_bytes, cfg) {
+        return Ok(Some(rejection));
+    }
+    let mut head = Vec::with_capacity(SNIFF_BYTES);
+    fs::File::open(path)
+        .with_context(|| format!("open {}", path.display()))?
+        .take(SNIFF_BYTES as u64)
+        .read_to_end(&mut head)?;
+    if let Some(rejection) = sniff_bytes(&head) {
+        return Ok(Some(rejection));
+    }
+    if let Some(signature) = clamd_verdict(path, cfg).await? {
+        return Ok(Some(Rejection::new(RejectReason::Malware, signature)));
+    }
+    Ok(None)
+}
+
+/// Hex encoded SHA-256 of a file.
+pub fn file_sha256(path: &Path) -> Result<String> {
+    let mut file = fs::File::open(path).with_context(|| format!("open {}", path.display()))?;
+    let mut hasher = Sha256::new();
+    let mut buf = vec![0u8; 64 * 1024];
+    loop {
+        let n = file.read(&mut buf)?;
+        if n == 0 {
+            break;
+        }
+        hasher.update(&buf[..n]);
+    }
+    Ok(format!("{:x}", hasher.finalize()))
+}
+
+/// Job context stored with every quarantined file.
+#[derive(Debug, Clone)]
+pub struct QuarantineOrigin {
+    pub job_id: Uuid,
+    pub folder_id: String,
+    pub tenant_id: Option<Uuid>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct QuarantineEntry {
+    pub id: Uuid,
+    pub job_id: Option<Uuid>,
+    pub folder_id: Option<String>,
+    pub tenant_id: Option<Uuid>,
+    pub file_name: String,
+    pub reason: String,
+    pub detail: Option<String>,
+    pub size_bytes: i64,
+    pub sha256: String,
+    #[serde(skip)]
+    pub stored_path: Option<String>,
+    pub status: String,
+    pub created_at: DateTime<Utc>,
+    pub resolved_at: Option<DateTime<Utc>>,
+}
+
+impl QuarantineEntry {
+    fn from_row(row: &Row) -> Self {
+        Self {
+            id: row.get("id"),
+            job_id: row.get("job_id"),
+            folder_id: row.get("folder_id"),
+            tenant_id: row.get("tenant_id"),
+            file_name: row.get("file_name"),
+            reason: row.get("reason"),
+            detail: row.get("detail"),
+            size_bytes: row.get("size_bytes"),
+            sha256: row.get("sha256"),
+            stored_path: row.get("stored_path"),
+            status: row.get("status"),
+            created_at: row.get("created_at"),
+            resolved_at: row.get("resolved_at"),
+        }
+    }
+}
+
+/// Result of screening the downloaded files of a job.
+#[derive(Debug, Default)]
+pub struct Screening {
+    pub accepted: Vec<PathBuf>,
+    pub quarantined: Vec<QuarantineEntry>,
+}
+
+/// Checks every file, moves rejected ones into quarantine and keeps the order
+/// of the accepted ones. Files released by an operator skip the checks.
+pub async fn screen_files(
+    pool: &Pool,
+    cfg: &ScanConfig,
+    origin: &QuarantineOrigin,
+    files: Vec<PathBuf>,
+) -> Result<Screening> {
+    let mut screening = Screening::default();
+    let mut job_bytes = 0u64;
+    for path in files {
+        let sha256 = file_sha256(&path)?;
+        let size = fs::metadata(&path)?.len();
+        if is_released(pool, &sha256).await? {
+            job_bytes += size;
+            screening.accepted.push(path);
+            continue;
+        }
+        match inspect_file(&path, job_bytes, cfg).await? {
+            None => {
+                job_bytes += size;
+                screening.accepted.push(path);
+            }
+            Some(rejection) => {
+                let entry = quarantine_file(pool, cfg, origin, &path, &sha256, &rejection).await?;
+                warn!(
+                    job_id = %origin.job_id,
+                    file = %entry.file_name,
+                    reason = rejection.reason.as_str(),
+                    detail = %rejection.detail,
+                    "file quarantined"
+                );
+                screening.quarantined.push(entry);
+            }
+        }
+    }
+    Ok(screening)
+}
+
+async fn is_released(pool: &Pool, sha256: &str) -> Result<bool> {
+    let client = pool.get().await?;
+    let row = client
+        .query_opt(
+            "SELECT 1 FROM scan_quarantine WHERE sha256 = $1 AND status = 'released' LIMIT 1",
+            &[&sha256],
+        )
+        .await?;
+    Ok(row.is_some())
+}
+
+async fn quarantine_file(
+    pool: &Pool,
+    cfg: &ScanConfig,
+    origin: &QuarantineOrigin,
+    path: &Path,
+    sha256: &str,
+    rejection: &Rejection,
+) -> Result<QuarantineEntry> {
+    let id = Uuid::new_v4();
+    let size = fs::metadata(path)?.len() as i64;
+    let file_name = path
+        .file_name()
+        .map(|n| n.to_string_lossy().to_string())
+        .unwrap_or_default();
+    fs::create_dir_all(&cfg.quarantine_dir)
+        .with_context(|| format!("create {}", cfg.quarantine_dir.display()))?;
+    // Ohne Endung ablegen, damit nichts versehentlich geöffnet/ausgeführt wird
+    let stored = cfg.quarantine_dir.join(format!("{id}.bin"));
+    if fs::rename(path, &stored).is_err() {
+        // Temp-Verzeichnis kann auf einem anderen Dateisystem liegen
+        fs::copy(path, &stored).with_context(|| format!("copy into {}", stored.display()))?;
+        let _ = fs::remove_file(path);
+    }
+    let stored_path = stored.to_string_lossy().to_string();
+    let client = pool.get().await?;
+    let row = client
+        .query_one(
+            &format!(
+                "INSERT INTO scan_quarantine (id, job_id, folder_id, tenant_id, file_name, reason, detail,
+                                              size_bytes, sha256, stored_path)
+                 VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10)
+                 RETURNING {QUARANTINE_COLUMNS}"
+            ),
+            &[
+                &id,
+                &origin.job_id,
+                &origin.folder_id,
+                &origin.tenant_id,
+                &file_name,
+                &rejection.reason.as_str(),
+                &rejection.detail,
+                &size,
+                &sha256,
+                &stored_path,
+            ],
+        )
+        .await?;
+    Ok(QuarantineEntry::from_row(&row))
+}
+
+pub async fn list_quarantine(
+    pool: &Pool,
+    status: Option<&str>,
+    job_id: Option<Uuid>,
+) -> Result<Vec<QuarantineEntry>> {
+    let client = pool.get().await?;
+    let rows = client
+        .query(
+            &format!(
+                "SELECT {QUARANTINE_COLUMNS} FROM scan_quarantine
+                 WHERE ($1::text IS NULL OR status = $1)
+                   AND ($2::uuid IS NULL OR job_id = $2)
+                 ORDER BY created_at DESC
+                 LIMIT 500"
+            ),
+            &[&status, &job_id],
+        )
+        .await?;
+    Ok(rows.iter().map(QuarantineEntry::from_row).collect())
+}
+
+pub async fn load_quarantine(pool: &Pool, id: Uuid) -> Result<Option<QuarantineEntry>> {
+    let client = pool.get().await?;
+    let row = client
+        .query_opt(
+            &format!("SELECT {QUARANTINE_COLUMNS} FROM scan_quarantine WHERE id = $1"),
+            &[&id],
+        )
+        .await?;
+    Ok(row.as_ref().map(QuarantineEntry::from_row))
+}
+
+/// Marks a quarantined file as released so its hash passes the next scan.
+pub async fn release_quarantine(pool: &Pool, id: Uuid) -> Result<Option<QuarantineEntry>> {
+    resolve(pool, id, "released").await
+}
+
+/// Deletes the stored copy and marks the entry as deleted.
+pub async fn delete_quarantine(pool: &Pool, id: Uuid) -> Result<Option<QuarantineEntry>> {
+    let entry = resolve(pool, id, "deleted").await?;
+    if let Some(path) = entry.as_ref().and_then(|e| e.stored_path.as_deref()) {
+        if let Err(err) = fs::remove_file(path) {
+            if err.kind() != std::io::ErrorKind::NotFound {
+                return Err(err).with_context(|| format!("remove {path}"));
+            }
+        }
+    }
+    Ok(entry)
+}
+
+async fn resolve(pool: &Pool, id: Uuid, status: &str) -> Result<Option<QuarantineEntry>> {
+    let client = pool.get().await?;
+    let row = client
+        .query_opt(
+            &format!(
+                "UPDATE scan_quarantine SET status = $2, resolved_at = now()
+                 WHERE id = $1 AND status <> 'deleted'
+                 RETURNING {QUARANTINE_COLUMNS}"
+            ),
+            &[&id, &status],
+        )
+        .await?;
+    Ok(row.as_ref().map(QuarantineEntry::from_row))
 }
 
 #[cfg(test)]
 mod tests {
-    use super::assert_pdf;
+    use super::{assert_pdf, sniff_bytes, RejectReason};
     use std::io::Write;
 
     #[test]
@@ -124,4 +543,29 @@ mod tests {
         tmp.write_all(b"%PDF-1.7\n").expect("write pdf header");
         assert_pdf(tmp.path()).expect("valid pdf should pass");
     }
+
+    #[test]
+    fn sniff_rejects_disguised_executables() {
+        assert!(sniff_bytes(b"%PDF-1.7\n").is_none());
+        let mut exe = b"MZ".to_vec();
+        exe.resize(128, 0);
+        assert_eq!(
+            sniff_bytes(&exe).map(|r| r.reason),
+            Some(RejectReason::DisguisedExecutable)
+        );
+        let mut elf = b"\x7fELF\x02\x01\x01".to_vec();
+        elf.resize(64, 0);
+        assert_eq!(
+            sniff_bytes(&elf).map(|r| r.reason),
+            Some(RejectReason::DisguisedExecutable)
+        );
+        assert_eq!(
+            sniff_bytes(b"#!/bin/sh\nrm -rf /").map(|r| r.reason),
+            Some(RejectReason::DisguisedExecutable)
+        );
+        assert_eq!(
+            sniff_bytes(b"PK\x03\x04").map(|r| r.reason),
+            Some(RejectReason::NotPdf)
+        );
+    }
 }
@@ -0,0 +1,91 @@
+//! Cron schedules of automation rules.
+//!
+//! A folder rule with `schedule` (five-field cron expression in UTC such as
+//! `*/15 * * * *`, or `@daily`) is no longer ingested by the automation
+//! poller but by the schedule task: once `next_run_at` has passed, the task
+//! moves it to the next occurrence and imports the folder again, unless a job
+//! of the folder is still active or the folder holds no PDF missing in its
+//! inventory (see `inventory.rs`). Rules without schedule keep the poller's
+//! behaviour of one job per new folder.
+
+use std::time::Duration;
+
+use anyhow::{anyhow, Result};
+use chrono::{DateTime, Utc};
+use croner::Cron;
+
+/// Interval in which the schedule task looks for due rules.
+pub const TICK: Duration = Duration::from_secs(30);
+
+#[derive(Debug, Clone)]
+pub struct Schedule {
+    cron: Cron,
+}
+
+impl Schedule {
+    pub fn parse(expression: &str) -> Result<Self> {
+        let cron = Cron::new(expression.trim())
+            .parse()
+            .map_err(|err| anyhow!("invalid schedule {expression:?}: {err}"))?;
+        Ok(Self { cron })
+    }
+
+    /// First occurrence after `time`; `None` for patterns that never match
+    /// (`0 0 30 2 *`).
+    pub fn next_after(&self, time: DateTime<Utc>) -> Option<DateTime<Utc>> {
+        self.cron.find_next_occurrence(&time, false).ok()
+    }
+}
+
+/// Normalizes a schedule of the automation API: blank removes it, anything
+/// else must parse and occur at least once.
+pub fn validate(raw: Option<&str>) -> Result<Option<String>> {
+    let Some(raw) = raw.map(str::trim).filter(|raw| !raw.is_empty()) else {
+        return Ok(None);
+    };
+    let schedule = Schedule::parse(raw)?;
+    if schedule.next_after(Utc::now()).is_none() {
+        return Err(anyhow!("schedule {raw:?} never occurs"));
+    }
+    Ok(Some(raw.to_string()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    #[test]
+    fn finds_the_next_occurrence_in_utc() {
+        let at = Utc.with_ymd_and_hms(2025, 3, 4, 10, 7, 30).unwrap();
+        let inbox = Schedule::parse("*/15 * * * *").unwrap();
+        assert_eq!(
+            inbox.next_after(at),
+            Some(Utc.with_ymd_and_hms(2025, 3, 4, 10, 15, 0).unwrap())
+        );
+        let archive = Schedule::parse("@daily").unwrap();
+        assert_eq!(
+            archive.next_after(at),
+            Some(Utc.with_ymd_and_hms(2025, 3, 5, 0, 0, 0).unwrap())
+        );
+        // Genau zum Termin zählt der nächste
+        let due = Utc.with_ymd_and_hms(2025, 3, 4, 10, 15, 0).unwrap();
+        assert_eq!(
+            inbox.next_after(due),
+            Some(Utc.with_ymd_and_hms(2025, 3, 4, 10, 30, 0).unwrap())
+        );
+    }
+
+    #[test]
+    fn validates_api_input() {
+        assert_eq!(validate(None).unwrap(), None);
+        assert_eq!(validate(Some("  ")).unwrap(), None);
+        assert_eq!(
+            validate(Some(" 0 2 * * 1-5 ")).unwrap().as_deref(),
+            Some("0 2 * * 1-5")
+        );
+        assert!(validate(Some("every night")).is_err());
+        assert!(validate(Some("0 0 2 * * *")).is_err(), "no seconds field");
+        assert!(validate(Some("0 0 30 2 *")).is_err());
+    }
+}
@@ -0,0 +1,235 @@
+//! Slot scheduling of jobs.
+//!
+//! At most `MAX_CONCURRENCY` jobs run at a time. A free slot goes to the
+//! waiting job with the highest priority; among jobs of the same priority the
+//! tenant with the fewest running jobs comes first, then the tenant whose last
+//! job started longest ago (fair share), then the job that waits longest. An
+//! urgent folder created with `"priority": "high"` therefore starts with the
+//! next free slot, ahead of a queued bulk ingest, and one tenant's bulk ingest
+//! does not block the jobs of other tenants. Running jobs are never
+//! interrupted.
+
+use std::{cmp::Reverse, collections::HashMap, sync::Arc};
+
+use parking_lot::Mutex;
+use shared::dto::RunPriority;
+use tokio::sync::oneshot;
+use uuid::Uuid;
+
+#[derive(Debug)]
+pub struct JobScheduler {
+    capacity: usize,
+    state: Mutex<SchedulerState>,
+}
+
+#[derive(Debug, Default)]
+struct SchedulerState {
+    /// Running jobs per tenant (`None`: jobs without tenant).
+    running: HashMap<Option<Uuid>, usize>,
+    total: usize,
+    /// Start number of the latest job per tenant, for round robin.
+    last_started: HashMap<Option<Uuid>, u64>,
+    started: u64,
+    waiting: Vec<Waiter>,
+    next_seq: u64,
+}
+
+#[derive(Debug)]
+struct Waiter {
+    priority: RunPriority,
+    tenant_id: Option<Uuid>,
+    seq: u64,
+    tx: oneshot::Sender<JobSlot>,
+}
+
+/// Slot of a running job; dropping it hands the slot to the next job.
+#[derive(Debug)]
+pub struct JobSlot {
+    scheduler: Arc<JobScheduler>,
+    tenant_id: Option<Uuid>,
+}
+
+impl Drop for JobSlot {
+    fn drop(&mut self) {
+        self.scheduler.release(self.tenant_id);
+    }
+}
+
+impl JobScheduler {
+    pub fn new(capacity: usize) -> Arc<Self> {
+        Arc::new(Self {
+            capacity: capacity.max(1),
+            state: Mutex::new(SchedulerState::default()),
+        })
+    }
+
+    /// Waits for a slot; `None` only if the scheduler dropped the request.
+    pub async fn acquire(
+        self: &Arc<Self>,
+        priority: RunPriority,
+        tenant_id: Option<Uuid>,
+    ) -> Option<JobSlot> {
+        let (tx, rx) = oneshot::channel();
+        {
+            let mut state = self.state.lock();
+            let seq = state.next_seq;
+            state.next_seq += 1;
+            state.waiting.push(Waiter {
+                priority,
+                tenant_id,
+                seq,
+                tx,
+            });
+        }
+        self.dispatch();
+        rx.await.ok()
+    }
+
+    /// Number of running and waiting jobs.
+    #[cfg(test)]
+    fn load(&self) -> (usize, usize) {
+        let state = self.state.lock();
+        (state.total, state.waiting.len())
+    }
+
+    fn release(self: &Arc<Self>, tenant_id: Option<Uuid>) {
+        {
+            let mut state = self.state.lock();
+            state.total = state.total.saturating_sub(1);
+            if let Some(count) = state.running.get_mut(&tenant_id) {
+                *count -= 1;
+                if *count == 0 {
+                    state.running.remove(&tenant_id);
+                }
+            }
+        }
+        self.dispatch();
+    }
+
+    /// Hands free slots to waiting jobs.
+    fn dispatch(self: &Arc<Self>) {
+        loop {
+            let (tx, slot) = {
+                let mut state = self.state.lock();
+                if state.total >= self.capacity {
+                    return;
+                }
+                let Some(index) = next_waiter(&state) else {
+                    return;
+                };
+                let waiter = state.waiting.swap_remove(index);
+                // Job-Task wurde beendet, während er wartete
+                if waiter.tx.is_closed() {
+                    continue;
+                }
+                state.total += 1;
+                state.started += 1;
+                let started = state.started;
+                *state.running.entry(waiter.tenant_id).or_default() += 1;
+                state.last_started.insert(waiter.tenant_id, started);
+                let slot = JobSlot {
+                    scheduler: self.clone(),
+                    tenant_id: waiter.tenant_id,
+                };
+                (waiter.tx, slot)
+            };
+            // Außerhalb des Locks: ein abgelehnter Slot gibt sich beim Drop selbst frei
+            let _ = tx.send(slot);
+        }
+    }
+}
+
+/// Index of the waiter that gets the next slot (see module docs).
+fn next_waiter(state: &SchedulerState) -> Option<usize> {
+    state
+        .waiting
+        .iter()
+        .enumerate()
+        .min_by_key(|(_, w)| {
+            (
+                Reverse(w.priority),
+                state.running.get(&w.tenant_id).copied().unwrap_or(0),
+                state.last_started.get(&w.tenant_id).copied().unwrap_or(0),
+                w.seq,
+            )
+        })
+        .map(|(index, _)| index)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    async fn started(rx: &mut tokio::sync::mpsc::UnboundedReceiver<&'static str>) -> &'static str {
+        tokio::time::timeout(Duration::from_secs(1), rx.recv())
+            .await
+            .unwrap()
+            .unwrap()
+    }
+
+    #[tokio::test]
+    async fn prefers_priority_then_rotates_tenants() {
+        let scheduler = JobScheduler::new(1);
+        let bulk = Some(Uuid::from_u128(1));
+        let other = Some(Uuid::from_u128(2));
+        let first = scheduler.acquire(RunPriority::Normal, bulk).await.unwrap();
+
+        let (tx, mut rx) = tokio::sync::mpsc::unbounded_channel();
+        let jobs = [
+            ("bulk-2", RunPriority::Low, bulk),
+            ("bulk-3", RunPriority::Normal, bulk),
+            ("other", RunPriority::Normal, other),
+            ("urgent", RunPriority::High, bulk),
+        ];
+        let mut handles = Vec::new();
+        for (name, priority, tenant) in jobs {
+            let scheduler = scheduler.clone();
+            let tx = tx.clone();
+            handles.push(tokio::spawn(async move {
+                let slot = scheduler.acquire(priority, tenant).await.unwrap();
+                tx.send(name).unwrap();
+                // Slot kurz halten, damit die übrigen Jobs warten
+                tokio::time::sleep(Duration::from_millis(20)).await;
+                drop(slot);
+            }));
+            tokio::time::sleep(Duration::from_millis(5)).await;
+        }
+        assert_eq!(scheduler.load(), (1, 4));
+
+        drop(first);
+        let order = [
+            started(&mut rx).await,
+            started(&mut rx).await,
+            started(&mut rx).await,
+            started(&mut rx).await,
+        ];
+        // "other" vor "bulk-3": der Mandant von "bulk" kam mit "urgent" zuletzt dran
+        assert_eq!(order, ["urgent", "other", "bulk-3", "bulk-2"]);
+        for handle in handles {
+            handle.await.unwrap();
+        }
+        assert_eq!(scheduler.load(), (0, 0));
+    }
+
+    #[test]
+    fn fair_share_picks_the_tenant_with_fewest_running_jobs() {
+        let (a, b) = (Some(Uuid::from_u128(1)), Some(Uuid::from_u128(2)));
+        let waiter = |tenant_id, seq| Waiter {
+            priority: RunPriority::Normal,
+            tenant_id,
+            seq,
+            tx: oneshot::channel().0,
+        };
+        let mut state = SchedulerState {
+            waiting: vec![waiter(a, 0), waiter(a, 1), waiter(b, 2)],
+            ..SchedulerState::default()
+        };
+        assert_eq!(next_waiter(&state), Some(0));
+        state.running = HashMap::from([(a, 2), (b, 1)]);
+        assert_eq!(next_waiter(&state), Some(2));
+        state.running.clear();
+        state.last_started = HashMap::from([(a, 7), (b, 3)]);
+        assert_eq!(next_waiter(&state), Some(2));
+    }
+}
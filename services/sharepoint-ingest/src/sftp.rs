@@ -0,0 +1,673 @@
+//! SFTP source connector: partners drop (optionally PGP encrypted) PDF batches
+//! into a remote directory that is polled per tenant.
+//!
+//! Sources are stored in `sftp_sources`; passwords, SSH keys and PGP keys are
+//! envelope-encrypted with `CREDENTIALS_MASTER_KEY`. New files are recorded in
+//! `sftp_seen_files` and handed to the regular job worker, which downloads,
+//! decrypts, merges, scans and uploads them like a SharePoint folder.
+
+use std::{
+    io::{Read, Write},
+    net::{TcpStream, ToSocketAddrs},
+    path::{Path, PathBuf},
+    time::Duration,
+};
+
+use anyhow::{anyhow, bail, Context, Result};
+use base64::{engine::general_purpose::STANDARD_NO_PAD, Engine as _};
+use chrono::{DateTime, Utc};
+use deadpool_postgres::Pool;
+use serde::{Deserialize, Serialize};
+use shared::envelope::{MasterKey, SealedSecret};
+use ssh2::{HashType, Session};
+use tokio_postgres::Row;
+use uuid::Uuid;
+
+use crate::connector_config::{non_empty, MasterKeyed};
+use crate::{pgp, sanitize_filename};
+
+pub const SFTP_SCHEMA_SQL: &str = r#"
+CREATE TABLE IF NOT EXISTS sftp_sources (
+    id UUID PRIMARY KEY,
+    name TEXT NOT NULL,
+    tenant_id UUID,
+    pipeline_id UUID,
+    host TEXT NOT NULL,
+    port INTEGER NOT NULL DEFAULT 22,
+    username TEXT NOT NULL,
+    host_key_sha256 TEXT NOT NULL,
+    remote_dir TEXT NOT NULL DEFAULT '.',
+    patterns TEXT[] NOT NULL DEFAULT ARRAY['*.pdf', '*.pdf.pgp', '*.pdf.gpg'],
+    archive_dir TEXT,
+    credentials JSONB NOT NULL,
+    enabled BOOLEAN NOT NULL DEFAULT TRUE,
+    last_polled_at TIMESTAMPTZ,
+    last_error TEXT,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT now(),
+    updated_at TIMESTAMPTZ NOT NULL DEFAULT now()
+);
+
+CREATE TABLE IF NOT EXISTS sftp_seen_files (
+    source_id UUID NOT NULL REFERENCES sftp_sources(id) ON DELETE CASCADE,
+    path TEXT NOT NULL,
+    fingerprint TEXT NOT NULL,
+    job_id UUID,
+    seen_at TIMESTAMPTZ NOT NULL DEFAULT now(),
+    PRIMARY KEY (source_id, path, fingerprint)
+);
+"#;
+
+const SOURCE_COLUMNS: &str = "id, name, tenant_id, pipeline_id, host, port, username, host_key_sha256,
+     remote_dir, patterns, archive_dir, credentials, enabled, last_polled_at, last_error, updated_at";
+
+/// Secrets of a source, each sealed individually.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+struct SealedCredentials {
+    #[serde(default)]
+    password: Option<SealedSecret>,
+    #[serde(default)]
+    private_key: Option<SealedSecret>,
+    #[serde(default)]
+    key_passphrase: Option<SealedSecret>,
+    #[serde(default)]
+    pgp_private_key: Option<SealedSecret>,
+    #[serde(default)]
+    pgp_passphrase: Option<SealedSecret>,
+}
+
+#[derive(Debug, Clone)]
+pub struct SftpSource {
+    pub id: Uuid,
+    pub name: String,
+    pub tenant_id: Option<Uuid>,
+    pub pipeline_id: Option<Uuid>,
+    pub host: String,
+    pub port: i32,
+    pub username: String,
+    pub host_key_sha256: String,
+    pub remote_dir: String,
+    pub patterns: Vec<String>,
+    pub archive_dir: Option<String>,
+    credentials: SealedCredentials,
+    pub enabled: bool,
+    pub last_polled_at: Option<DateTime<Utc>>,
+    pub last_error: Option<String>,
+    pub updated_at: DateTime<Utc>,
+}
+
+/// Source as returned by the API; secrets are reduced to flags.
+#[derive(Debug, Serialize)]
+pub struct SftpSourceSummary {
+    pub id: Uuid,
+    pub name: String,
+    pub tenant_id: Option<Uuid>,
+    pub pipeline_id: Option<Uuid>,
+    pub host: String,
+    pub port: i32,
+    pub username: String,
+    pub host_key_sha256: String,
+    pub remote_dir: String,
+    pub patterns: Vec<String>,
+    pub archive_dir: Option<String>,
+    pub enabled: bool,
+    pub has_password: bool,
+    pub has_private_key: bool,
+    pub has_pgp_key: bool,
+    pub last_polled_at: Option<DateTime<Utc>>,
+    pub last_error: Option<String>,
+    pub updated_at: DateTime<Utc>,
+}
+
+impl SftpSource {
+    fn from_row(row: &Row) -> Result<Self> {
+        let credentials: serde_json::Value = row.get("credentials");
+        Ok(Self {
+            id: row.get("id"),
+            name: row.get("name"),
+            tenant_id: row.get("tenant_id"),
+            pipeline_id: row.get("pipeline_id"),
+            host: row.get("host"),
+            port: row.get("port"),
+            username: row.get("username"),
+            host_key_sha256: row.get("host_key_sha256"),
+            remote_dir: row.get("remote_dir"),
+            patterns: row.get("patterns"),
+            archive_dir: row.get("archive_dir"),
+            credentials: serde_json::from_value(credentials)
+                .context("invalid sftp credentials record")?,
+            enabled: row.get("enabled"),
+            last_polled_at: row.get("last_polled_at"),
+            last_error: row.get("last_error"),
+            updated_at: row.get("updated_at"),
+        })
+    }
+
+    pub fn summary(&self) -> SftpSourceSummary {
+        SftpSourceSummary {
+            id: self.id,
+            name: self.name.clone(),
+            tenant_id: self.tenant_id,
+            pipeline_id: self.pipeline_id,
+            host: self.host.clone(),
+            port: self.port,
+            username: self.username.clone(),
+            host_key_sha256: self.host_key_sha256.clone(),
+            remote_dir: self.remote_dir.clone(),
+            patterns: self.patterns.clone(),
+            archive_dir: self.archive_dir.clone(),
+            enabled: self.enabled,
+            has_password: self.credentials.password.is_some(),
+            has_private_key: self.credentials.private_key.is_some(),
+            has_pgp_key: self.credentials.pgp_private_key.is_some(),
+            last_polled_at: self.last_polled_at,
+            last_error: self.last_error.clone(),
+            updated_at: self.updated_at,
+        }
+    }
+
+    /// `folder_id` of jobs created for this source.
+    pub fn job_folder_id(&self) -> String {
+        format!("sftp:{}", self.id)
+    }
+
+    /// True if the file name matches one of the configured glob patterns.
+    pub fn matches(&self, name: &str) -> bool {
+        self.patterns.iter().any(|p| {
+            glob::Pattern::new(p)
+                .map(|pattern| pattern.matches_with(name, glob_options()))
+                .unwrap_or(false)
+        })
+    }
+}
+
+fn glob_options() -> glob::MatchOptions {
+    glob::MatchOptions {
+        case_sensitive: false,
+        require_literal_separator: true,
+        require_literal_leading_dot: true,
+    }
+}
+
+/// Payload of `PUT /sftp/sources/{id}`; omitted secrets keep their stored value.
+#[derive(Debug, Deserialize)]
+pub struct SftpSourceInput {
+    pub name: String,
+    #[serde(default)]
+    pub tenant_id: Option<Uuid>,
+    #[serde(default)]
+    pub pipeline_id: Option<Uuid>,
+    pub host: String,
+    #[serde(default)]
+    pub port: Option<i32>,
+    pub username: String,
+    pub host_key_sha256: String,
+    #[serde(default)]
+    pub remote_dir: Option<String>,
+    #[serde(default)]
+    pub patterns: Option<Vec<String>>,
+    #[serde(default)]
+    pub archive_dir: Option<String>,
+    #[serde(default)]
+    pub enabled: Option<bool>,
+    #[serde(default)]
+    pub password: Option<String>,
+    #[serde(default)]
+    pub private_key: Option<String>,
+    #[serde(default)]
+    pub key_passphrase: Option<String>,
+    #[serde(default)]
+    pub pgp_private_key: Option<String>,
+    #[serde(default)]
+    pub pgp_passphrase: Option<String>,
+}
+
+/// Normalizes `SHA256:abc=` / `abc` to the unpadded base64 digest.
+fn normalize_fingerprint(value: &str) -> String {
+    let trimmed = value.trim();
+    let digest = trimmed
+        .strip_prefix("SHA256:")
+        .or_else(|| trimmed.strip_prefix("sha256:"))
+        .unwrap_or(trimmed);
+    digest.trim_end_matches('=').to_string()
+}
+
+fn seal_opt(master: &MasterKey, value: Option<String>) -> Result<Option<SealedSecret>> {
+    Ok(non_empty(value).map(|v| master.seal(&v)).transpose()?)
+}
+
+fn open_opt(master: &MasterKey, value: &Option<SealedSecret>) -> Result<Option<String>> {
+    Ok(value.as_ref().map(|v| master.open(v)).transpose()?)
+}
+
+pub async fn list_sources(pool: &Pool) -> Result<Vec<SftpSource>> {
+    let client = pool.get().await?;
+    let rows = client
+        .query(
+            &format!("SELECT {SOURCE_COLUMNS} FROM sftp_sources ORDER BY name"),
+            &[],
+        )
+        .await?;
+    rows.iter().map(SftpSource::from_row).collect()
+}
+
+pub async fn load_source(pool: &Pool, id: Uuid) -> Result<Option<SftpSource>> {
+    let client = pool.get().await?;
+    let row = client
+        .query_opt(
+            &format!("SELECT {SOURCE_COLUMNS} FROM sftp_sources WHERE id = $1"),
+            &[&id],
+        )
+        .await?;
+    row.as_ref().map(SftpSource::from_row).transpose()
+}
+
+impl SftpSourceInput {
+    /// Checks required fields; `is_new` additionally requires credentials.
+    pub fn validate(&self, is_new: bool) -> Result<()> {
+        if self.name.trim().is_empty() || self.host.trim().is_empty() {
+            bail!("name and host are required");
+        }
+        if normalize_fingerprint(&self.host_key_sha256).is_empty() {
+            bail!("host_key_sha256 is required");
+        }
+        for pattern in self.patterns.iter().flatten() {
+            glob::Pattern::new(pattern).with_context(|| format!("invalid pattern '{pattern}'"))?;
+        }
+        if is_new
+            && non_empty(self.password.clone()).is_none()
+            && non_empty(self.private_key.clone()).is_none()
+        {
+            bail!("password or private_key is required");
+        }
+        Ok(())
+    }
+}
+
+/// Seals the secrets and upserts a source validated with [`SftpSourceInput::validate`].
+pub async fn upsert_source(
+    pool: &Pool,
+    master: &MasterKey,
+    id: Uuid,
+    input: SftpSourceInput,
+) -> Result<SftpSource> {
+    let fingerprint = normalize_fingerprint(&input.host_key_sha256);
+    let patterns = input
+        .patterns
+        .unwrap_or_else(|| vec!["*.pdf".into(), "*.pdf.pgp".into(), "*.pdf.gpg".into()]);
+
+    let existing = load_source(pool, id)
+        .await?
+        .map(|s| s.credentials)
+        .unwrap_or_default();
+    let credentials = SealedCredentials {
+        password: seal_opt(master, input.password)?.or(existing.password),
+        private_key: seal_opt(master, input.private_key)?.or(existing.private_key),
+        key_passphrase: seal_opt(master, input.key_passphrase)?.or(existing.key_passphrase),
+        pgp_private_key: seal_opt(master, input.pgp_private_key)?.or(existing.pgp_private_key),
+        pgp_passphrase: seal_opt(master, input.pgp_passphrase)?.or(existing.pgp_passphrase),
+    };
+    let client = pool.get().await?;
+    client
+        .execute(
+            "INSERT INTO sftp_sources (
+                id, name, tenant_id, pipeline_id, host, port, username, host_key_sha256,
+                remote_dir, patterns, archive_dir, credentials, enabled
+             ) VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12, $13)
+             ON CONFLICT (id) DO UPDATE SET
+                name = EXCLUDED.name,
+                tenant_id = EXCLUDED.tenant_id,
+                pipeline_id = EXCLUDED.pipeline_id,
+                host = EXCLUDED.host,
+                port = EXCLUDED.port,
+                username = EXCLUDED.username,
+                host_key_sha256 = EXCLUDED.host_key_sha256,
+                remote_dir = EXCLUDED.remote_dir,
+                patterns = EXCLUDED.patterns,
+                archive_dir = EXCLUDED.archive_dir,
+                credentials = EXCLUDED.credentials,
+                enabled = EXCLUDED.enabled,
+                updated_at = now()",
+            &[
+                &id,
+                &input.name.trim(),
+                &input.tenant_id,
+                &input.pipeline_id,
+                &input.host.trim(),
+                &input.port.unwrap_or(22),
+                &input.username,
+                &fingerprint,
+                &non_empty(input.remote_dir).unwrap_or_else(|| ".".to_string()),
+                &patterns,
+                &non_empty(input.archive_dir),
+                &serde_json::to_value(&credentials)?,
+                &input.enabled.unwrap_or(true),
+            ],
+        )
+        .await?;
+    load_source(pool, id)
+        .await?
+        .ok_or_else(|| anyhow!("sftp source vanished after upsert"))
+}
+
+pub async fn delete_source(pool: &Pool, id: Uuid) -> Result<bool> {
+    let client = pool.get().await?;
+    let deleted = client
+        .execute("DELETE FROM sftp_sources WHERE id = $1", &[&id])
+        .await?;
+    Ok(deleted > 0)
+}
+
+pub async fn record_poll(pool: &Pool, id: Uuid, error: Option<&str>) -> Result<()> {
+    let client = pool.get().await?;
+    client
+        .execute(
+            "UPDATE sftp_sources SET last_polled_at = now(), last_error = $2 WHERE id = $1",
+            &[&id, &error],
+        )
+        .await?;
+    Ok(())
+}
+
+/// A file in the source's remote directory.
+#[derive(Debug, Clone)]
+pub struct RemoteFile {
+    pub path: String,
+    pub name: String,
+    /// `size:mtime`; a re-delivered file with new content gets a new job.
+    pub fingerprint: String,
+}
+
+/// Filters out files that already produced a job.
+pub async fn unseen_files(
+    pool: &Pool,
+    source_id: Uuid,
+    files: Vec<RemoteFile>,
+) -> Result<Vec<RemoteFile>> {
+    let client = pool.get().await?;
+    let rows = client
+        .query(
+            "SELECT path, fingerprint FROM sftp_seen_files WHERE source_id = $1",
+            &[&source_id],
+        )
+        .await?;
+    let seen: std::collections::HashSet<(String, String)> = rows
+        .iter()
+        .map(|r| (r.get("path"), r.get("fingerprint")))
+        .collect();
+    Ok(files
+        .into_iter()
+        .filter(|f| !seen.contains(&(f.path.clone(), f.fingerprint.clone())))
+        .collect())
+}
+
+pub async fn mark_seen(
+    pool: &Pool,
+    source_id: Uuid,
+    files: &[RemoteFile],
+    job_id: Uuid,
+) -> Result<()> {
+    let client = pool.get().await?;
+    for file in files {
+        client
+            .execute(
+                "INSERT INTO sftp_seen_files (source_id, path, fingerprint, job_id)
+                 VALUES ($1, $2, $3, $4)
+                 ON CONFLICT DO NOTHING",
+                &[&source_id, &file.path, &file.fingerprint, &job_id],
+            )
+            .await?;
+    }
+    Ok(())
+}
+
+/// Decrypted connection secrets.
+struct Secrets {
+    password: Option<String>,
+    private_key: Option<String>,
+    key_passphrase: Option<String>,
+    pgp_private_key: Option<String>,
+    pgp_passphrase: Option<String>,
+}
+
+/// Opens SFTP sessions for stored sources.
+#[derive(Clone)]
+pub struct SftpConnector {
+    master_key: Option<MasterKey>,
+    timeout: Duration,
+}
+
+impl MasterKeyed for SftpConnector {
+    fn master_key(&self) -> Option<&MasterKey> {
+        self.master_key.as_ref()
+    }
+}
+
+impl SftpConnector {
+    pub fn new(master_key: Option<MasterKey>, timeout: Duration) -> Self {
+        Self {
+            master_key,
+            timeout,
+        }
+    }
+
+    fn secrets(&self, source: &SftpSource) -> Result<Secrets> {
+        let master = self.require_master_key()?;
+        let c = &source.credentials;
+        Ok(Secrets {
+            password: open_opt(master, &c.password)?,
+            private_key: open_opt(master, &c.private_key)?,
+            key_passphrase: open_opt(master, &c.key_passphrase)?,
+            pgp_private_key: open_opt(master, &c.pgp_private_key)?,
+            pgp_passphrase: open_opt(master, &c.pgp_passphrase)?,
+        })
+    }
+
+    /// Runs `f` with an authenticated SFTP channel on a blocking thread.
+    async fn with_sftp<T, F>(&self, source: &SftpSource, f: F) -> Result<T>
+    where
+        T: Send + 'static,
+        F: FnOnce(&ssh2::Sftp) -> Result<T> + Send + 'static,
+    {
+        let secrets = self.secrets(source)?;
+        let source = source.clone();
+        let timeout = self.timeout;
+        tokio::task::spawn_blocking(move || {
+            let session = connect(&source, &secrets, timeout)?;
+            let sftp = session.sftp().context("open sftp channel")?;
+            f(&sftp)
+        })
+        .await
+        .context("sftp task panicked")?
+    }
+
+    /// Lists files in the remote directory matching the source's patterns.
+    pub async fn list(&self, source: &SftpSource) -> Result<Vec<RemoteFile>> {
+        let matcher = source.clone();
+        let dir = PathBuf::from(&source.remote_dir);
+        let mut files = self
+            .with_sftp(source, move |sftp| {
+                let entries = sftp
+                    .readdir(&dir)
+                    .with_context(|| format!("list {}", dir.display()))?;
+                Ok(entries
+                    .into_iter()
+                    .filter(|(_, stat)| stat.is_file())
+                    .filter_map(|(path, stat)| {
+                        let name = path.file_name()?.to_string_lossy().to_string();
+                        matcher.matches(&name).then(|| RemoteFile {
+                            path: path.to_string_lossy().to_string(),
+                            name,
+                            fingerprint: format!(
+                                "{}:{}",
+                                stat.size.unwrap_or_default(),
+                                stat.mtime.unwrap_or_default()
+                            ),
+                        })
+                    })
+                    .collect::<Vec<_>>())
+            })
+            .await?;
+        files.sort_by_key(|f| f.name.to_lowercase());
+        Ok(files)
+    }
+
+    /// Downloads `paths` into `dest` and decrypts PGP files; returns the PDFs in order.
+    pub async fn fetch(
+        &self,
+        source: &SftpSource,
+        paths: &[String],
+        dest: &Path,
+    ) -> Result<Vec<PathBuf>> {
+        let secrets = self.secrets(source)?;
+        let remote = paths.to_vec();
+        let target = dest.to_path_buf();
+        let downloaded = self
+            .with_sftp(source, move |sftp| {
+                let mut out = Vec::with_capacity(remote.len());
+                for (idx, path) in remote.iter().enumerate() {
+                    let name = Path::new(path)
+                        .file_name()
+                        .map(|n| n.to_string_lossy().to_string())
+                        .unwrap_or_else(|| path.clone());
+                    let local = target.join(format!("{idx:03}-{}", sanitize_filename(&name)));
+                    let mut file = sftp
+                        .open(Path::new(path))
+                        .with_context(|| format!("open remote {path}"))?;
+                    let mut buf = Vec::new();
+                    file.read_to_end(&mut buf)
+                        .with_context(|| format!("download {path}"))?;
+                    std::fs::File::create(&local)?.write_all(&buf)?;
+                    out.push(local);
+                }
+                Ok(out)
+            })
+            .await?;
+
+        let mut pdfs = Vec::with_capacity(downloaded.len());
+        for local in downloaded {
+            let file_name = local
+                .file_name()
+                .map(|n| n.to_string_lossy().to_string())
+                .unwrap_or_default();
+            match pgp::decrypted_name(&file_name) {
+                Some(plain) => {
+                    let key = secrets.pgp_private_key.as_deref().ok_or_else(|| {
+                        anyhow!("{file_name} is PGP encrypted but no pgp_private_key is configured")
+                    })?;
+                    let output = local.with_file_name(plain);
+                    pgp::decrypt_file(&local, &output, key, secrets.pgp_passphrase.as_deref())
+                        .await?;
+                    let _ = tokio::fs::remove_file(&local).await;
+                    pdfs.push(output);
+                }
+                None => pdfs.push(local),
+            }
+        }
+        Ok(pdfs)
+    }
+
+    /// Moves processed files into `archive_dir` (no-op without one).
+    pub async fn archive(&self, source: &SftpSource, paths: &[String]) -> Result<()> {
+        let Some(archive_dir) = source.archive_dir.clone() else {
+            return Ok(());
+        };
+        let remote = paths.to_vec();
+        self.with_sftp(source, move |sftp| {
+            for path in &remote {
+                let src = Path::new(path);
+                let Some(name) = src.file_name() else {
+                    continue;
+                };
+                let dst = Path::new(&archive_dir).join(name);
+                sftp.rename(src, &dst, None)
+                    .with_context(|| format!("archive {path}"))?;
+            }
+            Ok(())
+        })
+        .await
+    }
+}
+
+fn connect(source: &SftpSource, secrets: &Secrets, timeout: Duration) -> Result<Session> {
+    let addr = (source.host.as_str(), source.port as u16)
+        .to_socket_addrs()
+        .with_context(|| format!("resolve {}", source.host))?
+        .next()
+        .ok_or_else(|| anyhow!("no address for {}", source.host))?;
+    let tcp = TcpStream::connect_timeout(&addr, timeout)
+        .with_context(|| format!("connect {}:{}", source.host, source.port))?;
+    let mut session = Session::new().context("create ssh session")?;
+    session.set_timeout(timeout.as_millis().min(u32::MAX as u128) as u32);
+    session.set_tcp_stream(tcp);
+    session.handshake().context("ssh handshake")?;
+
+    let presented = session
+        .host_key_hash(HashType::Sha256)
+        .map(|h| STANDARD_NO_PAD.encode(h))
+        .ok_or_else(|| anyhow!("server did not present a host key"))?;
+    if presented != normalize_fingerprint(&source.host_key_sha256) {
+        bail!(
+            "host key mismatch for {} (got SHA256:{presented})",
+            source.host
+        );
+    }
+
+    if let Some(key) = secrets.private_key.as_deref() {
+        session
+            .userauth_pubkey_memory(
+                &source.username,
+                None,
+                key,
+                secrets.key_passphrase.as_deref(),
+            )
+            .context("ssh key authentication")?;
+    } else if let Some(password) = secrets.password.as_deref() {
+        session
+            .userauth_password(&source.username, password)
+            .context("ssh password authentication")?;
+    }
+    if !session.authenticated() {
+        bail!("ssh authentication failed for {}", source.username);
+    }
+    Ok(session)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn source(patterns: &[&str]) -> SftpSource {
+        SftpSource {
+            id: Uuid::nil(),
+            name: "partner".into(),
+            tenant_id: None,
+            pipeline_id: None,
+            host: "sftp.example".into(),
+            port: 22,
+            username: "regress".into(),
+            host_key_sha256: "abc".into(),
+            remote_dir: "/out".into(),
+            patterns: patterns.iter().map(|p| p.to_string()).collect(),
+            archive_dir: None,
+            credentials: SealedCredentials::default(),
+            enabled: true,
+            last_polled_at: None,
+            last_error: None,
+            updated_at: Utc::now(),
+        }
+    }
+
+    #[test]
+    fn patterns_fingerprints_and_pgp_names() {
+        let src = source(&["*.pdf", "claim_*.pdf.pgp"]);
+        assert!(src.matches("Scan.PDF"));
+        assert!(src.matches("claim_0815.pdf.pgp"));
+        assert!(!src.matches("other.pdf.pgp"));
+        assert!(!src.matches(".hidden.pdf"));
+        assert_eq!(src.job_folder_id(), format!("sftp:{}", Uuid::nil()));
+
+        assert_eq!(normalize_fingerprint(" SHA256:q3Lr+x9w= "), "q3Lr+x9w");
+        assert_eq!(pgp::decrypted_name("claim.pdf.GPG"), Some("claim.pdf"));
+        assert_eq!(pgp::decrypted_name("claim.pdf"), None);
+    }
+}
@@ -0,0 +1,239 @@
+//! Folder statistics shown before a folder is queued: file types, sizes and
+//! age, plus the files that would trip the scan limits.
+
+use std::collections::HashMap;
+
+use chrono::{DateTime, Duration, Utc};
+use serde::Serialize;
+
+use crate::{msgraph::GraphItemInfo, scan::ScanConfig};
+
+/// Number of entries in [`FolderStats::largest`].
+const LARGEST_FILES: usize = 10;
+
+#[derive(Debug, Serialize)]
+pub struct FolderStats {
+    pub folder_id: String,
+    pub file_count: usize,
+    pub subfolder_count: usize,
+    pub total_bytes: i64,
+    pub pdf_count: usize,
+    pub pdf_bytes: i64,
+    pub by_extension: Vec<ExtensionStats>,
+    pub largest: Vec<FileStat>,
+    pub last_modified: ModifiedDistribution,
+    pub limits: LimitCheck,
+}
+
+#[derive(Debug, Serialize, PartialEq, Eq)]
+pub struct ExtensionStats {
+    /// Lower-case extension without dot, `""` for files without one.
+    pub extension: String,
+    pub count: usize,
+    pub bytes: i64,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct FileStat {
+    pub id: String,
+    pub name: String,
+    pub size: i64,
+    pub last_modified: Option<DateTime<Utc>>,
+    pub mime_type: Option<String>,
+}
+
+#[derive(Debug, Default, Serialize, PartialEq, Eq)]
+pub struct ModifiedDistribution {
+    pub oldest: Option<DateTime<Utc>>,
+    pub newest: Option<DateTime<Utc>>,
+    pub last_24h: usize,
+    pub last_7d: usize,
+    pub last_30d: usize,
+    pub older: usize,
+    pub unknown: usize,
+}
+
+/// Files that the job would reject (see `scan.rs`) or silently skip.
+#[derive(Debug, Serialize)]
+pub struct LimitCheck {
+    pub max_file_bytes: u64,
+    pub max_job_bytes: u64,
+    pub oversized: Vec<FileStat>,
+    pub job_too_large: bool,
+    /// Non-PDF files are ignored by SharePoint jobs.
+    pub non_pdf_count: usize,
+}
+
+fn extension(name: &str) -> String {
+    name.rsplit_once('.')
+        .map(|(_, ext)| ext.to_ascii_lowercase())
+        .unwrap_or_default()
+}
+
+fn is_pdf(item: &GraphItemInfo) -> bool {
+    item.mime_type
+        .as_deref()
+        .map(|mt| mt.eq_ignore_ascii_case("application/pdf"))
+        .unwrap_or_else(|| extension(&item.name) == "pdf")
+}
+
+fn file_stat(item: &GraphItemInfo) -> FileStat {
+    FileStat {
+        id: item.id.clone(),
+        name: item.name.clone(),
+        size: item.size,
+        last_modified: item.last_modified,
+        mime_type: item.mime_type.clone(),
+    }
+}
+
+impl FolderStats {
+    /// Aggregates the direct children of a folder relative to `now`.
+    pub fn compute(
+        folder_id: &str,
+        items: &[GraphItemInfo],
+        limits: &ScanConfig,
+        now: DateTime<Utc>,
+    ) -> Self {
+        let (folders, files): (Vec<_>, Vec<_>) = items.iter().partition(|item| item.is_folder);
+
+        let mut by_extension: HashMap<String, ExtensionStats> = HashMap::new();
+        let mut last_modified = ModifiedDistribution::default();
+        let mut pdf_count = 0;
+        let mut pdf_bytes = 0;
+        for item in &files {
+            let ext = extension(&item.name);
+            let entry = by_extension
+                .entry(ext.clone())
+                .or_insert_with(|| ExtensionStats {
+                    extension: ext,
+                    count: 0,
+                    bytes: 0,
+                });
+            entry.count += 1;
+            entry.bytes += item.size;
+            if is_pdf(item) {
+                pdf_count += 1;
+                pdf_bytes += item.size;
+            }
+
+            match item.last_modified {
+                Some(modified) => {
+                    last_modified.oldest =
+                        Some(last_modified.oldest.map_or(modified, |o| o.min(modified)));
+                    last_modified.newest =
+                        Some(last_modified.newest.map_or(modified, |n| n.max(modified)));
+                    let age = now - modified;
+                    if age <= Duration::hours(24) {
+                        last_modified.last_24h += 1;
+                    } else if age <= Duration::days(7) {
+                        last_modified.last_7d += 1;
+                    } else if age <= Duration::days(30) {
+                        last_modified.last_30d += 1;
+                    } else {
+                        last_modified.older += 1;
+                    }
+                }
+                None => last_modified.unknown += 1,
+            }
+        }
+
+        let mut by_extension: Vec<_> = by_extension.into_values().collect();
+        by_extension.sort_by(|a, b| {
+            b.count
+                .cmp(&a.count)
+                .then_with(|| a.extension.cmp(&b.extension))
+        });
+
+        let mut largest: Vec<_> = files.iter().map(|item| file_stat(item)).collect();
+        largest.sort_by(|a, b| b.size.cmp(&a.size).then_with(|| a.name.cmp(&b.name)));
+        largest.truncate(LARGEST_FILES);
+
+        let oversized = files
+            .iter()
+            .filter(|item| is_pdf(item) && item.size.max(0) as u64 > limits.max_file_bytes)
+            .map(|item| file_stat(item))
+            .collect();
+
+        Self {
+            folder_id: folder_id.to_string(),
+            file_count: files.len(),
+            subfolder_count: folders.len(),
+            total_bytes: files.iter().map(|item| item.size).sum(),
+            pdf_count,
+            pdf_bytes,
+            by_extension,
+            largest,
+            last_modified,
+            limits: LimitCheck {
+                max_file_bytes: limits.max_file_bytes,
+                max_job_bytes: limits.max_job_bytes,
+                oversized,
+                job_too_large: pdf_bytes.max(0) as u64 > limits.max_job_bytes,
+                non_pdf_count: files.len() - pdf_count,
+            },
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::PathBuf;
+
+    fn item(name: &str, size: i64, age_days: i64, now: DateTime<Utc>) -> GraphItemInfo {
+        GraphItemInfo {
+            id: name.to_string(),
+            name: name.to_string(),
+            size,
+            last_modified: Some(now - Duration::days(age_days)),
+            mime_type: None,
+            is_folder: false,
+        }
+    }
+
+    #[test]
+    fn aggregates_types_sizes_and_limits() {
+        let now = Utc::now();
+        let limits = ScanConfig {
+            enabled: false,
+            clamd_addr: None,
+            max_upload_bytes: 1_000,
+            max_file_bytes: 500,
+            max_job_bytes: 800,
+            quarantine_dir: PathBuf::from("/tmp"),
+        };
+        let mut sub = item("Archiv", 0, 0, now);
+        sub.is_folder = true;
+        let items = vec![
+            item("a.pdf", 100, 0, now),
+            item("b.PDF", 600, 3, now),
+            item("c.pdf", 200, 20, now),
+            item("movie.mp4", 5_000, 90, now),
+            sub,
+        ];
+
+        let stats = FolderStats::compute("f1", &items, &limits, now);
+        assert_eq!(stats.file_count, 4);
+        assert_eq!(stats.subfolder_count, 1);
+        assert_eq!(stats.total_bytes, 5_900);
+        assert_eq!(stats.pdf_count, 3);
+        assert_eq!(
+            stats.by_extension[0],
+            ExtensionStats {
+                extension: "pdf".into(),
+                count: 3,
+                bytes: 900
+            }
+        );
+        assert_eq!(stats.largest[0].name, "movie.mp4");
+        assert_eq!(stats.last_modified.last_24h, 1);
+        assert_eq!(stats.last_modified.last_7d, 1);
+        assert_eq!(stats.last_modified.last_30d, 1);
+        assert_eq!(stats.last_modified.older, 1);
+        assert_eq!(stats.limits.oversized.len(), 1);
+        assert_eq!(stats.limits.oversized[0].name, "b.PDF");
+        assert!(stats.limits.job_too_large);
+        assert_eq!(stats.limits.non_pdf_count, 1);
+    }
+}
@@ -0,0 +1,1105 @@
+//! Job worker as a sequence of pluggable steps.
+//!
+//! Every job runs the steps of a [`JobPlan`] in order on a shared
+//! [`JobContext`]. The default plan is
+//! `list, download, convert, filter, merge, scan, upload, trigger`:
+//!
+//! - `list`: folder listing of the job's connector (SharePoint, OneDrive, local
+//!   directory) in job order, SFTP source or IMAP mailbox
+//! - `download`: files into the job's temp directory (IMAP: the raw mail)
+//! - `convert`: PDFs and, with `include_body`, the body of a mail
+//! - `filter`: quarantine screening of the single files
+//! - `merge`, `scan`: merged PDF and its structural check
+//! - `upload`: to the upload API; afterwards SFTP files are archived and
+//!   files of drive folders added to the folder inventory
+//! - `trigger`: pipeline start once the upload is ready
+//!
+//! `JOB_STEPS` (JSON array) replaces the plan. Built-in steps are given by
+//! name, custom steps as objects, so deployments can add e.g. watermarking or
+//! an external validation without forking the service:
+//!
+//! ```json
+//! ["list", "download", "convert", "filter", "merge",
+//!  {"type": "command", "name": "watermark", "program": "/opt/bin/watermark", "args": ["{pdf}"]},
+//!  {"type": "webhook", "name": "validate", "url": "https://validator.local/check"},
+//!  "scan", "upload", "trigger"]
+//! ```
+//!
+//! `command` runs a program on the merged PDF (placeholders `{pdf}`, `{job_id}`,
+//! `{folder}`) and fails the job on a non-zero exit. `webhook` posts the job
+//! metadata and the SHA-256 of the merged PDF; a non-2xx answer or
+//! `{"accept": false, "reason": "..."}` fails the job.
+
+use std::collections::HashMap;
+use std::future::Future;
+use std::path::PathBuf;
+use std::pin::Pin;
+use std::sync::Arc;
+use std::time::Duration;
+
+use anyhow::{anyhow, bail, Context, Result};
+use deadpool_postgres::Pool;
+use serde::Deserialize;
+use serde_json::json;
+use tokio::process::Command;
+use tokio::sync::watch;
+use tokio::time::timeout;
+use tracing::{error, info, warn};
+use uuid::Uuid;
+
+use crate::config::Config;
+use crate::connector::Connectors;
+use crate::imap::{self, ImapConnector, ImapMailbox};
+use crate::inventory;
+use crate::job::{CheckpointFile, JobCheckpoint, JobCommand, JobRegistry, JobSource, JobState};
+use crate::job_files::{self, FileStatus};
+use crate::msgraph::GraphFile;
+use crate::pdfops::merge_pdfs;
+use crate::pipeline_adapter::PipelineAdapter;
+use crate::scan::{self, assert_pdf, QuarantineOrigin, ScanConfig};
+use crate::sftp::{self, SftpConnector, SftpSource};
+use crate::upload_adapter::{UploadAdapter, UploadResult};
+use crate::{
+    order_files, sanitize_filename, wait_for_upload_ready, wait_until_running, JobRunError,
+};
+
+pub const JOB_STEPS_ENV: &str = "JOB_STEPS";
+
+/// Built-in steps in their default order.
+const DEFAULT_STEPS: [&str; 8] = [
+    "list", "download", "convert", "filter", "merge", "scan", "upload", "trigger",
+];
+
+/// File of the raw mail of an IMAP job in the job directory.
+const RAW_MAIL_FILE: &str = "mail.eml";
+
+/// Default timeout of custom steps.
+const CUSTOM_STEP_TIMEOUT: Duration = Duration::from_secs(120);
+
+const DOWNLOAD_WEIGHT: f32 = 0.5;
+const MERGE_WEIGHT: f32 = 0.3;
+const UPLOAD_WEIGHT: f32 = 0.2;
+
+pub type StepFuture<'a> = Pin<Box<dyn Future<Output = Result<(), JobRunError>> + Send + 'a>>;
+
+/// One stage of the job worker.
+pub trait JobStep: Send + Sync {
+    fn name(&self) -> &str;
+    fn run<'a>(&'a self, ctx: &'a mut JobContext) -> StepFuture<'a>;
+}
+
+/// Services a job needs; cloned from the application state.
+#[derive(Clone)]
+pub struct JobServices {
+    pub config: Arc<Config>,
+    pub connectors: Connectors,
+    pub uploader: Arc<UploadAdapter>,
+    pub pipeline: Arc<PipelineAdapter>,
+    pub sftp: Arc<SftpConnector>,
+    pub imap: Arc<ImapConnector>,
+    pub db_pool: Pool,
+    pub jobs: JobRegistry,
+}
+
+/// State passed from step to step.
+pub struct JobContext {
+    pub job_id: Uuid,
+    /// Job state at start; live state is updated via [`JobContext::update`].
+    pub snapshot: JobState,
+    pub services: JobServices,
+    /// Job directory below `JOB_WORK_DIR`; kept across restarts and removed
+    /// once the job ends ([`remove_work_dir`]).
+    pub work_dir: PathBuf,
+    /// Drive folder files in merge order (`list`).
+    pub remote_files: Vec<GraphFile>,
+    pub sftp_source: Option<SftpSource>,
+    pub mailbox: Option<ImapMailbox>,
+    /// Raw mail of an IMAP job (`download`), turned into PDFs by `convert`.
+    pub raw_mail: Option<Vec<u8>>,
+    /// Local PDFs in merge order.
+    pub files: Vec<PathBuf>,
+    /// Position in `remote_files` of every downloaded drive folder file.
+    pub file_positions: HashMap<PathBuf, usize>,
+    pub merged: Option<PathBuf>,
+    pub upload: Option<UploadResult>,
+    control_rx: watch::Receiver<JobCommand>,
+    /// Step names of the running plan and number of completed steps.
+    plan: Vec<String>,
+    completed: usize,
+    resumes: u32,
+}
+
+impl JobContext {
+    pub fn new(
+        job_id: Uuid,
+        snapshot: JobState,
+        services: JobServices,
+        control_rx: watch::Receiver<JobCommand>,
+    ) -> Result<Self> {
+        let work_dir = work_dir_for(&services.config, job_id);
+        std::fs::create_dir_all(&work_dir)
+            .with_context(|| format!("creating job directory {}", work_dir.display()))?;
+        let resumes = snapshot.checkpoint.as_ref().map_or(0, |c| c.resumes);
+        Ok(Self {
+            job_id,
+            snapshot,
+            services,
+            work_dir,
+            remote_files: Vec::new(),
+            sftp_source: None,
+            mailbox: None,
+            raw_mail: None,
+            files: Vec::new(),
+            file_positions: HashMap::new(),
+            merged: None,
+            upload: None,
+            control_rx,
+            plan: Vec::new(),
+            completed: 0,
+            resumes,
+        })
+    }
+
+    /// Takes over the progress of an interrupted run of the same plan and
+    /// returns the number of steps to skip; otherwise the job starts over.
+    async fn resume(&mut self, plan: Vec<String>) -> Result<usize, JobRunError> {
+        self.plan = plan;
+        let Some(checkpoint) = self.snapshot.checkpoint.clone() else {
+            return Ok(0);
+        };
+        if checkpoint.completed == 0 {
+            return Ok(0);
+        }
+        if checkpoint.plan != self.plan || checkpoint.work_dir != self.work_dir {
+            warn!(job_id = %self.job_id, "job plan changed since the checkpoint; starting over");
+            return Ok(0);
+        }
+        let missing = checkpoint
+            .files
+            .iter()
+            .map(|f| &f.path)
+            .chain(&checkpoint.raw_mail)
+            .chain(&checkpoint.merged)
+            .find(|path| !path.exists());
+        if let Some(path) = missing {
+            warn!(job_id = %self.job_id, path = %path.display(), "checkpoint file missing; starting over");
+            return Ok(0);
+        }
+        if let Some(path) = &checkpoint.raw_mail {
+            self.raw_mail = Some(std::fs::read(path).context("reading stored mail")?);
+        }
+        self.remote_files = checkpoint.remote_files;
+        for file in checkpoint.files {
+            if let Some(position) = file.position {
+                self.file_positions.insert(file.path.clone(), position);
+            }
+            self.files.push(file.path);
+        }
+        self.merged = checkpoint.merged;
+        self.upload = self.snapshot.output.clone();
+        self.completed = checkpoint.completed;
+        // Quelle lädt sonst `list`, das übersprungen wird
+        self.load_source().await?;
+        info!(
+            job_id = %self.job_id,
+            completed = self.completed,
+            files = self.files.len(),
+            "resuming job from checkpoint"
+        );
+        Ok(self.completed)
+    }
+
+    /// Persists the progress with the job state (see [`JobCheckpoint`]).
+    fn save_progress(&self) {
+        let checkpoint = JobCheckpoint {
+            plan: self.plan.clone(),
+            completed: self.completed,
+            work_dir: self.work_dir.clone(),
+            remote_files: self.remote_files.clone(),
+            files: self
+                .files
+                .iter()
+                .map(|path| CheckpointFile {
+                    path: path.clone(),
+                    position: self.file_positions.get(path).copied(),
+                })
+                .collect(),
+            raw_mail: self
+                .raw_mail
+                .as_ref()
+                .map(|_| self.work_dir.join(RAW_MAIL_FILE)),
+            merged: self.merged.clone(),
+            resumes: self.resumes,
+        };
+        self.update(|s| s.checkpoint = Some(checkpoint));
+    }
+
+    /// Loads the SFTP source or IMAP mailbox of the job.
+    async fn load_source(&mut self) -> Result<(), JobRunError> {
+        match self.snapshot.source.clone() {
+            JobSource::SharePoint | JobSource::SharePointFiles { .. } => {}
+            JobSource::Drive { .. } => {}
+            JobSource::Sftp { source_id, .. } => {
+                let source = sftp::load_source(&self.services.db_pool, source_id)
+                    .await?
+                    .ok_or_else(|| anyhow!("sftp source {source_id} no longer exists"))?;
+                self.sftp_source = Some(source);
+            }
+            JobSource::Imap { mailbox_id, .. } => {
+                let mailbox = imap::load_mailbox(&self.services.db_pool, mailbox_id)
+                    .await?
+                    .ok_or_else(|| anyhow!("imap mailbox {mailbox_id} no longer exists"))?;
+                self.mailbox = Some(mailbox);
+            }
+        }
+        Ok(())
+    }
+
+    /// Waits while the job is paused; fails with `Canceled` once it is canceled.
+    pub async fn checkpoint(&mut self) -> Result<(), JobRunError> {
+        wait_until_running(&self.services.jobs, self.job_id, &mut self.control_rx).await
+    }
+
+    pub fn update<F: FnOnce(&mut JobState)>(&self, f: F) {
+        self.services.jobs.update(&self.job_id, f);
+    }
+
+    /// Updates the state of a listed SharePoint file and announces it to
+    /// `GET /jobs/events`; failures are only logged.
+    async fn track_file(&self, position: usize, status: FileStatus, error: Option<&str>) {
+        if let Some(file) = self.remote_files.get(position) {
+            self.services
+                .jobs
+                .file_changed(self.job_id, position, file, status, error);
+        }
+        if let Err(err) =
+            job_files::set_status(&self.services.db_pool, self.job_id, position, status, error)
+                .await
+        {
+            warn!(job_id = %self.job_id, position, error = %err, "failed to track job file");
+        }
+    }
+
+    fn merged_pdf(&self) -> Result<PathBuf> {
+        self.merged
+            .clone()
+            .ok_or_else(|| anyhow!("no merged pdf yet; add the merge step earlier"))
+    }
+}
+
+/// Job directory of `job_id`.
+pub fn work_dir_for(config: &Config, job_id: Uuid) -> PathBuf {
+    config.job_work_dir.join(job_id.to_string())
+}
+
+/// Removes the job directory of a finished job; failures are only logged.
+pub async fn remove_work_dir(config: &Config, job_id: Uuid) {
+    let dir = work_dir_for(config, job_id);
+    if let Err(err) = tokio::fs::remove_dir_all(&dir).await {
+        if err.kind() != std::io::ErrorKind::NotFound {
+            warn!(%job_id, dir = %dir.display(), error = %err, "failed to remove job directory");
+        }
+    }
+}
+
+/// Ordered steps every job runs through.
+#[derive(Clone)]
+pub struct JobPlan {
+    steps: Vec<Arc<dyn JobStep>>,
+}
+
+impl Default for JobPlan {
+    fn default() -> Self {
+        Self {
+            steps: DEFAULT_STEPS
+                .iter()
+                .map(|name| builtin(name).expect("built-in step"))
+                .collect(),
+        }
+    }
+}
+
+impl JobPlan {
+    /// Reads `JOB_STEPS`; the default plan when unset.
+    pub fn from_env() -> Result<Self> {
+        match std::env::var(JOB_STEPS_ENV) {
+            Ok(raw) if !raw.trim().is_empty() => {
+                Self::from_json(&raw).with_context(|| format!("invalid {JOB_STEPS_ENV}"))
+            }
+            _ => Ok(Self::default()),
+        }
+    }
+
+    pub fn from_json(raw: &str) -> Result<Self> {
+        let specs: Vec<StepSpec> = serde_json::from_str(raw)?;
+        let mut steps: Vec<Arc<dyn JobStep>> = Vec::with_capacity(specs.len());
+        for spec in specs {
+            let step: Arc<dyn JobStep> = match spec {
+                StepSpec::Builtin(name) => {
+                    builtin(&name).ok_or_else(|| anyhow!("unknown step '{name}'"))?
+                }
+                StepSpec::Custom(CustomStep::Command {
+                    name,
+                    program,
+                    args,
+                    timeout_secs,
+                }) => Arc::new(CommandStep {
+                    name: custom_name(name, "command")?,
+                    program,
+                    args,
+                    timeout: timeout_secs
+                        .map(Duration::from_secs)
+                        .unwrap_or(CUSTOM_STEP_TIMEOUT),
+                }),
+                StepSpec::Custom(CustomStep::Webhook {
+                    name,
+                    url,
+                    timeout_secs,
+                }) => Arc::new(WebhookStep {
+                    name: custom_name(name, "webhook")?,
+                    client: reqwest::Client::builder()
+                        .timeout(
+                            timeout_secs
+                                .map(Duration::from_secs)
+                                .unwrap_or(CUSTOM_STEP_TIMEOUT),
+                        )
+                        .build()
+                        .context("building webhook client")?,
+                    url,
+                }),
+            };
+            steps.push(step);
+        }
+        let plan = Self { steps };
+        plan.validate()?;
+        Ok(plan)
+    }
+
+    pub fn names(&self) -> Vec<String> {
+        self.steps.iter().map(|s| s.name().to_string()).collect()
+    }
+
+    /// Built-in steps depend on the output of earlier ones.
+    fn validate(&self) -> Result<()> {
+        let names = self.names();
+        let position = |name: &str| names.iter().position(|n| n == name);
+        for builtin in DEFAULT_STEPS {
+            if names.iter().filter(|n| *n == builtin).count() > 1 {
+                bail!("step '{builtin}' listed twice");
+            }
+        }
+        let requires = [
+            ("download", "list"),
+            ("convert", "download"),
+            ("filter", "download"),
+            ("merge", "download"),
+            ("scan", "merge"),
+            ("upload", "merge"),
+            ("trigger", "upload"),
+        ];
+        for (step, needs) in requires {
+            if let Some(at) = position(step) {
+                match position(needs) {
+                    Some(before) if before < at => {}
+                    _ => bail!("step '{step}' needs '{needs}' before it"),
+                }
+            }
+        }
+        if position("upload").is_none() {
+            bail!("step 'upload' is required");
+        }
+        Ok(())
+    }
+
+    /// Runs the steps, after the completed ones of an interrupted run, and
+    /// records the progress after every step.
+    pub async fn run(&self, ctx: &mut JobContext) -> Result<(), JobRunError> {
+        let skip = ctx.resume(self.names()).await?;
+        for step in self.steps.iter().skip(skip) {
+            ctx.checkpoint().await?;
+            info!(job_id = %ctx.job_id, step = step.name(), "running job step");
+            if let Err(err) = step.run(ctx).await {
+                if let JobRunError::Failure(ref cause) = err {
+                    error!(job_id = %ctx.job_id, step = step.name(), error = ?cause, "job step failed");
+                }
+                return Err(err);
+            }
+            ctx.completed += 1;
+            ctx.save_progress();
+        }
+        Ok(())
+    }
+}
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum StepSpec {
+    Builtin(String),
+    Custom(CustomStep),
+}
+
+#[derive(Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+enum CustomStep {
+    Command {
+        name: Option<String>,
+        program: String,
+        #[serde(default)]
+        args: Vec<String>,
+        timeout_secs: Option<u64>,
+    },
+    Webhook {
+        name: Option<String>,
+        url: String,
+        timeout_secs: Option<u64>,
+    },
+}
+
+/// Name of a custom step; built-in names are reserved, otherwise a custom
+/// step could stand in for a required built-in one.
+fn custom_name(name: Option<String>, default: &str) -> Result<String> {
+    let name = name.unwrap_or_else(|| default.to_string());
+    if builtin(&name).is_some() {
+        bail!("custom step must not be named like the built-in step '{name}'");
+    }
+    Ok(name)
+}
+
+fn builtin(name: &str) -> Option<Arc<dyn JobStep>> {
+    let step: Arc<dyn JobStep> = match name {
+        "list" => Arc::new(ListStep),
+        "download" => Arc::new(DownloadStep),
+        "convert" => Arc::new(ConvertStep),
+        "filter" => Arc::new(FilterStep),
+        "merge" => Arc::new(MergeStep),
+        "scan" => Arc::new(ScanStep),
+        "upload" => Arc::new(UploadStep),
+        "trigger" => Arc::new(TriggerStep),
+        _ => return None,
+    };
+    Some(step)
+}
+
+struct ListStep;
+
+impl JobStep for ListStep {
+    fn name(&self) -> &str {
+        "list"
+    }
+
+    fn run<'a>(&'a self, ctx: &'a mut JobContext) -> StepFuture<'a> {
+        Box::pin(async move {
+            match ctx.snapshot.source.clone() {
+                source @ (JobSource::SharePoint
+                | JobSource::SharePointFiles { .. }
+                | JobSource::Drive { .. }) => {
+                    let Some((kind, file_ids)) = source.folder_connector() else {
+                        unreachable!("folder source without connector");
+                    };
+                    let connector = ctx.services.connectors.get(kind)?;
+                    let mut files = connector
+                        .list_pdfs(&ctx.snapshot.folder_id)
+                        .await
+                        .map_err(JobRunError::Failure)?;
+                    if let Some(file_ids) = file_ids {
+                        files.retain(|file| file_ids.contains(&file.id));
+                    }
+                    if files.is_empty() {
+                        return Err(JobRunError::Failure(anyhow!("no pdf files found")));
+                    }
+                    ctx.remote_files = order_files(
+                        files,
+                        ctx.snapshot.order.clone(),
+                        ctx.snapshot.filenames_override.clone(),
+                    );
+                    if let Err(err) = job_files::record_listing(
+                        &ctx.services.db_pool,
+                        ctx.job_id,
+                        &ctx.remote_files,
+                    )
+                    .await
+                    {
+                        warn!(job_id = %ctx.job_id, error = %err, "failed to record job files");
+                    }
+                }
+                JobSource::Sftp { files, .. } => {
+                    if files.is_empty() {
+                        return Err(JobRunError::Failure(anyhow!("no pdf files found")));
+                    }
+                    ctx.load_source().await?;
+                }
+                JobSource::Imap { .. } => ctx.load_source().await?,
+            }
+            Ok(())
+        })
+    }
+}
+
+struct DownloadStep;
+
+impl JobStep for DownloadStep {
+    fn name(&self) -> &str {
+        "download"
+    }
+
+    fn run<'a>(&'a self, ctx: &'a mut JobContext) -> StepFuture<'a> {
+        Box::pin(async move {
+            match ctx.snapshot.source.clone() {
+                source @ (JobSource::SharePoint
+                | JobSource::SharePointFiles { .. }
+                | JobSource::Drive { .. }) => {
+                    let Some((kind, _)) = source.folder_connector() else {
+                        unreachable!("folder source without connector");
+                    };
+                    let connector = ctx.services.connectors.get(kind)?;
+                    let remote = ctx.remote_files.clone();
+                    let total = remote.len();
+                    let tolerance = ctx.services.config.file_failure_tolerance;
+                    let mut failed = 0usize;
+                    for (idx, file) in remote.iter().enumerate() {
+                        ctx.checkpoint().await?;
+                        let filename = format!("{idx:03}-{}", sanitize_filename(&file.name));
+                        let dest = ctx.work_dir.join(&filename);
+                        // Bereits vor einem Neustart geladen
+                        if ctx.file_positions.contains_key(&dest) {
+                            continue;
+                        }
+                        match connector.download(&file.id, &dest).await {
+                            Ok(()) => {
+                                ctx.track_file(idx, FileStatus::Downloaded, None).await;
+                                ctx.file_positions.insert(dest.clone(), idx);
+                                ctx.files.push(dest);
+                                ctx.save_progress();
+                            }
+                            Err(err) => {
+                                failed += 1;
+                                let reason = format!("{err:#}");
+                                warn!(job_id = %ctx.job_id, file = %file.name, error = %reason, "download failed");
+                                ctx.track_file(idx, FileStatus::Failed, Some(&reason)).await;
+                                // Einzelne Fehler nur bis zur Toleranz; ohne eine Datei kein Job
+                                if !tolerance.allows(failed, total) {
+                                    return Err(JobRunError::Failure(err.context(format!(
+                                        "download of {} failed ({failed} of {total} files failed)",
+                                        file.name
+                                    ))));
+                                }
+                            }
+                        }
+                        let progress = DOWNLOAD_WEIGHT * ((idx + 1) as f32 / total as f32);
+                        ctx.update(|s| {
+                            s.set_progress(progress);
+                            if failed > 0 {
+                                s.set_message(format!(
+                                    "downloaded {}/{total}, {failed} failed",
+                                    idx + 1 - failed
+                                ));
+                            } else {
+                                s.set_message(format!("downloaded {}/{}", idx + 1, total));
+                            }
+                        });
+                    }
+                    // Nach einem Neustart erneut versuchte Dateien zurück in Merge-Reihenfolge
+                    let positions = &ctx.file_positions;
+                    ctx.files.sort_by_key(|path| positions.get(path).copied());
+                }
+                JobSource::Sftp { files, .. } => {
+                    let source = ctx
+                        .sftp_source
+                        .as_ref()
+                        .ok_or_else(|| anyhow!("sftp source not loaded"))?;
+                    let downloaded = ctx
+                        .services
+                        .sftp
+                        .fetch(source, &files, &ctx.work_dir)
+                        .await?;
+                    let total = downloaded.len();
+                    ctx.files.extend(downloaded);
+                    ctx.update(|s| {
+                        s.set_progress(DOWNLOAD_WEIGHT);
+                        s.set_message(format!("downloaded {total}/{total} via sftp"));
+                    });
+                }
+                JobSource::Imap {
+                    uid_validity, uid, ..
+                } => {
+                    let mailbox = ctx
+                        .mailbox
+                        .as_ref()
+                        .ok_or_else(|| anyhow!("imap mailbox not loaded"))?;
+                    let mut session = ctx.services.imap.open(mailbox).await?;
+                    if session.uid_validity != uid_validity {
+                        return Err(JobRunError::Failure(anyhow!(
+                            "mailbox {} was rebuilt (UIDVALIDITY changed); mail {uid} is gone",
+                            mailbox.folder
+                        )));
+                    }
+                    let raw = session.fetch(uid).await?;
+                    session.logout().await;
+                    // Für die Wiederaufnahme nach einem Neustart
+                    tokio::fs::write(ctx.work_dir.join(RAW_MAIL_FILE), &raw)
+                        .await
+                        .context("storing mail")?;
+                    ctx.raw_mail = Some(raw);
+                }
+            }
+            Ok(())
+        })
+    }
+}
+
+struct ConvertStep;
+
+impl JobStep for ConvertStep {
+    fn name(&self) -> &str {
+        "convert"
+    }
+
+    fn run<'a>(&'a self, ctx: &'a mut JobContext) -> StepFuture<'a> {
+        Box::pin(async move {
+            let Some(raw) = ctx.raw_mail.take() else {
+                return Ok(());
+            };
+            let include_body = ctx.mailbox.as_ref().is_some_and(|m| m.include_body);
+            let converted = imap::extract_pdfs(&raw, include_body, &ctx.work_dir)?;
+            if converted.is_empty() {
+                return Err(JobRunError::Failure(anyhow!("no pdf files found")));
+            }
+            let total = converted.len();
+            ctx.files.extend(converted);
+            ctx.update(|s| {
+                s.set_progress(DOWNLOAD_WEIGHT);
+                s.set_message(format!("{total} PDFs aus E-Mail übernommen"));
+            });
+            Ok(())
+        })
+    }
+}
+
+struct FilterStep;
+
+impl JobStep for FilterStep {
+    fn name(&self) -> &str {
+        "filter"
+    }
+
+    fn run<'a>(&'a self, ctx: &'a mut JobContext) -> StepFuture<'a> {
+        Box::pin(async move {
+            // Dateien einzeln prüfen; Auffälliges landet in Quarantäne statt den Job abzubrechen
+            let scan_cfg = ScanConfig::from_env();
+            let origin = QuarantineOrigin {
+                job_id: ctx.job_id,
+                folder_id: ctx.snapshot.folder_id.clone(),
+                tenant_id: ctx.snapshot.tenant_id,
+            };
+            let files = std::mem::take(&mut ctx.files);
+            let screening =
+                scan::screen_files(&ctx.services.db_pool, &scan_cfg, &origin, files).await?;
+            for entry in &screening.quarantined {
+                let position = ctx
+                    .file_positions
+                    .iter()
+                    .find(|(path, _)| path.file_name().is_some_and(|n| *n == *entry.file_name))
+                    .map(|(_, position)| *position);
+                if let Some(position) = position {
+                    let reason = format!("quarantined: {}", entry.reason);
+                    ctx.track_file(position, FileStatus::Skipped, Some(&reason))
+                        .await;
+                }
+            }
+            if !screening.quarantined.is_empty() {
+                let names = screening
+                    .quarantined
+                    .iter()
+                    .map(|e| format!("{} ({})", e.file_name, e.reason))
+                    .collect::<Vec<_>>()
+                    .join(", ");
+                if screening.accepted.is_empty() {
+                    return Err(JobRunError::Failure(anyhow!(
+                        "alle Dateien in Quarantäne: {names}"
+                    )));
+                }
+                let count = screening.quarantined.len();
+                ctx.update(|s| {
+                    s.set_message(format!("{count} Datei(en) in Quarantäne: {names}"));
+                });
+            }
+            ctx.files = screening.accepted;
+            Ok(())
+        })
+    }
+}
+
+struct MergeStep;
+
+impl JobStep for MergeStep {
+    fn name(&self) -> &str {
+        "merge"
+    }
+
+    fn run<'a>(&'a self, ctx: &'a mut JobContext) -> StepFuture<'a> {
+        Box::pin(async move {
+            let merged_path = ctx.work_dir.join("merged.pdf");
+            merge_pdfs(&ctx.files, &merged_path).map_err(JobRunError::Failure)?;
+            ctx.merged = Some(merged_path);
+            ctx.update(|s| {
+                s.set_progress(DOWNLOAD_WEIGHT + MERGE_WEIGHT);
+                s.set_message("pdf merged");
+            });
+            Ok(())
+        })
+    }
+}
+
+struct ScanStep;
+
+impl JobStep for ScanStep {
+    fn name(&self) -> &str {
+        "scan"
+    }
+
+    fn run<'a>(&'a self, ctx: &'a mut JobContext) -> StepFuture<'a> {
+        Box::pin(async move {
+            // Validate merged PDF before uploading
+            assert_pdf(&ctx.merged_pdf()?).map_err(JobRunError::Failure)?;
+            ctx.update(|s| {
+                s.set_message("security scan passed");
+            });
+            Ok(())
+        })
+    }
+}
+
+struct UploadStep;
+
+impl JobStep for UploadStep {
+    fn name(&self) -> &str {
+        "upload"
+    }
+
+    fn run<'a>(&'a self, ctx: &'a mut JobContext) -> StepFuture<'a> {
+        Box::pin(async move {
+            let merged = ctx.merged_pdf()?;
+            let snapshot = &ctx.snapshot;
+            let upload_name = format!("{}-merged.pdf", sanitize_filename(&snapshot.folder_name));
+            let upload_result = ctx
+                .services
+                .uploader
+                .upload(
+                    &merged,
+                    &upload_name,
+                    snapshot.upload_url.as_deref(),
+                    snapshot.tenant_id,
+                    snapshot.pipeline_id,
+                    &snapshot.reference,
+                    snapshot.priority,
+                )
+                .await
+                .map_err(JobRunError::Failure)?;
+            ctx.update(|s| {
+                s.set_progress(DOWNLOAD_WEIGHT + MERGE_WEIGHT + UPLOAD_WEIGHT * 0.5);
+                s.set_message("upload completed");
+                s.set_output(upload_result.clone());
+            });
+            ctx.upload = Some(upload_result);
+
+            if let (Some(source), JobSource::Sftp { files, .. }) =
+                (&ctx.sftp_source, &ctx.snapshot.source)
+            {
+                // Archivieren ist best effort; die Dateien sind bereits als gesehen markiert
+                if let Err(err) = ctx.services.sftp.archive(source, files).await {
+                    warn!(job_id = %ctx.job_id, error = %err, "failed to archive sftp files");
+                }
+            }
+            if !ctx.remote_files.is_empty() {
+                let ingested: Vec<GraphFile> = ctx
+                    .file_positions
+                    .values()
+                    .filter_map(|&idx| ctx.remote_files.get(idx).cloned())
+                    .collect();
+                // Best effort; ohne Inventar erkennt die Automation nur neue Ordner
+                if let Err(err) = inventory::record(
+                    &ctx.services.db_pool,
+                    &ctx.snapshot.folder_id,
+                    ctx.job_id,
+                    &ingested,
+                )
+                .await
+                {
+                    warn!(job_id = %ctx.job_id, error = %err, "failed to record folder inventory");
+                }
+            }
+            Ok(())
+        })
+    }
+}
+
+struct TriggerStep;
+
+impl JobStep for TriggerStep {
+    fn name(&self) -> &str {
+        "trigger"
+    }
+
+    fn run<'a>(&'a self, ctx: &'a mut JobContext) -> StepFuture<'a> {
+        Box::pin(async move {
+            let job_id = ctx.job_id;
+            let jobs = ctx.services.jobs.clone();
+            let snapshot = &ctx.snapshot;
+            let state_pipeline_id = jobs
+                .get(&job_id)
+                .and_then(|managed| managed.state.lock().pipeline_id);
+            let pipeline_assigned_in_state = state_pipeline_id.is_some();
+            let snapshot_pipeline_id = snapshot.pipeline_id;
+            let pipeline_candidate = state_pipeline_id.or(snapshot_pipeline_id);
+            let pipeline_id = if pipeline_candidate.is_some()
+                && (snapshot_pipeline_id.is_some()
+                    || !snapshot.auto_managed
+                    || pipeline_assigned_in_state)
+            {
+                pipeline_candidate
+            } else {
+                None
+            };
+
+            let upload_ready = ctx.upload.as_ref().and_then(|u| u.upload_id);
+
+            if let Some(pipeline_id) = pipeline_id {
+                if let Some(upload_id) = upload_ready {
+                    jobs.update(&job_id, |s| {
+                        s.pipeline_id = Some(pipeline_id);
+                        s.set_message("prüfe Upload-Status für Pipeline");
+                    });
+                    let config = &ctx.services.config;
+                    let ready = wait_for_upload_ready(
+                        &ctx.services.db_pool,
+                        upload_id,
+                        config.upload_ready_poll_attempts,
+                        config.upload_ready_poll_interval,
+                    )
+                    .await
+                    .map_err(JobRunError::Failure)?;
+
+                    if ready {
+                        match ctx
+                            .services
+                            .pipeline
+                            .start_run(pipeline_id, upload_id)
+                            .await
+                        {
+                            Ok(_) => {
+                                info!(%job_id, %upload_id, %pipeline_id, "pipeline run started automatically");
+                                jobs.update(&job_id, |s| {
+                                    s.pipeline_id = Some(pipeline_id);
+                                    s.set_message("Pipeline automatisch gestartet");
+                                });
+                            }
+                            Err(err) => {
+                                warn!(%job_id, %upload_id, error = %err, "automatic pipeline start failed");
+                                jobs.update(&job_id, |s| {
+                                    s.set_message(format!("Pipeline-Start fehlgeschlagen: {err}"));
+                                });
+                            }
+                        }
+                    } else {
+                        warn!(%job_id, %upload_id, "upload not ready for pipeline start");
+                        jobs.update(&job_id, |s| {
+                            s.set_message("Upload noch nicht bereit für Pipeline");
+                        });
+                    }
+                } else {
+                    warn!(%job_id, "upload id missing; cannot start pipeline automatically");
+                    jobs.update(&job_id, |s| {
+                        s.set_message("Upload-ID fehlt für Pipeline-Start");
+                    });
+                }
+            } else {
+                jobs.update(&job_id, |s| {
+                    s.set_message("bereit für Pipeline-Verarbeitung");
+                });
+            }
+
+            jobs.update(&job_id, |s| {
+                s.set_progress(DOWNLOAD_WEIGHT + MERGE_WEIGHT + UPLOAD_WEIGHT);
+            });
+            Ok(())
+        })
+    }
+}
+
+/// Runs an external program on the merged PDF, e.g. for watermarking in place.
+struct CommandStep {
+    name: String,
+    program: String,
+    args: Vec<String>,
+    timeout: Duration,
+}
+
+impl JobStep for CommandStep {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn run<'a>(&'a self, ctx: &'a mut JobContext) -> StepFuture<'a> {
+        Box::pin(async move {
+            let merged = ctx.merged_pdf()?;
+            let pdf = merged.to_string_lossy();
+            let job_id = ctx.job_id.to_string();
+            let args: Vec<String> = self
+                .args
+                .iter()
+                .map(|arg| {
+                    arg.replace("{pdf}", &pdf)
+                        .replace("{job_id}", &job_id)
+                        .replace("{folder}", &ctx.snapshot.folder_name)
+                })
+                .collect();
+            let output = timeout(
+                self.timeout,
+                Command::new(&self.program)
+                    .args(&args)
+                    .kill_on_drop(true)
+                    .output(),
+            )
+            .await
+            .map_err(|_| anyhow!("step '{}' timed out", self.name))?
+            .with_context(|| format!("starting {}", self.program))?;
+            if !output.status.success() {
+                let stderr = String::from_utf8_lossy(&output.stderr);
+                return Err(JobRunError::Failure(anyhow!(
+                    "step '{}' failed ({}): {}",
+                    self.name,
+                    output.status,
+                    stderr.trim()
+                )));
+            }
+            ctx.update(|s| s.set_message(format!("{} abgeschlossen", self.name)));
+            Ok(())
+        })
+    }
+}
+
+#[derive(Deserialize)]
+struct WebhookVerdict {
+    #[serde(default = "default_accept")]
+    accept: bool,
+    reason: Option<String>,
+}
+
+fn default_accept() -> bool {
+    true
+}
+
+/// Asks an external service to validate the job before it continues.
+struct WebhookStep {
+    name: String,
+    url: String,
+    client: reqwest::Client,
+}
+
+impl JobStep for WebhookStep {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn run<'a>(&'a self, ctx: &'a mut JobContext) -> StepFuture<'a> {
+        Box::pin(async move {
+            let merged = match &ctx.merged {
+                Some(path) => Some(json!({
+                    "sha256": scan::file_sha256(path)?,
+                    "size_bytes": std::fs::metadata(path).map(|m| m.len()).ok(),
+                })),
+                None => None,
+            };
+            let files: Vec<String> = ctx
+                .files
+                .iter()
+                .filter_map(|p| p.file_name().map(|n| n.to_string_lossy().into_owned()))
+                .collect();
+            let body = json!({
+                "step": self.name,
+                "job_id": ctx.job_id,
+                "folder_id": ctx.snapshot.folder_id,
+                "folder_name": ctx.snapshot.folder_name,
+                "tenant_id": ctx.snapshot.tenant_id,
+                "pipeline_id": ctx.snapshot.pipeline_id,
+                "reference": ctx.snapshot.reference,
+                "files": files,
+                "merged": merged,
+            });
+            let response = self
+                .client
+                .post(&self.url)
+                .json(&body)
+                .send()
+                .await
+                .with_context(|| format!("step '{}' request failed", self.name))?;
+            let status = response.status();
+            if !status.is_success() {
+                return Err(JobRunError::Failure(anyhow!(
+                    "step '{}' rejected the job (HTTP {status})",
+                    self.name
+                )));
+            }
+            let verdict = response
+                .json::<WebhookVerdict>()
+                .await
+                .unwrap_or(WebhookVerdict {
+                    accept: true,
+                    reason: None,
+                });
+            if !verdict.accept {
+                return Err(JobRunError::Failure(anyhow!(
+                    "step '{}' rejected the job: {}",
+                    self.name,
+                    verdict.reason.unwrap_or_else(|| "no reason given".into())
+                )));
+            }
+            ctx.update(|s| s.set_message(format!("{} bestätigt", self.name)));
+            Ok(())
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn plan_parses_custom_steps_and_checks_order() {
+        assert_eq!(JobPlan::default().names(), DEFAULT_STEPS);
+
+        let plan = JobPlan::from_json(
+            r#"["list", "download", "merge",
+                {"type": "command", "name": "watermark", "program": "/bin/true", "args": ["{pdf}"]},
+                {"type": "webhook", "url": "http://validator.local/check"},
+                "upload"]"#,
+        )
+        .unwrap();
+        assert_eq!(
+            plan.names(),
+            [
+                "list",
+                "download",
+                "merge",
+                "watermark",
+                "webhook",
+                "upload"
+            ]
+        );
+
+        let err = JobPlan::from_json(r#"["list", "merge", "download", "upload"]"#)
+            .err()
+            .unwrap();
+        assert!(err.to_string().contains("'merge' needs 'download'"));
+        assert!(JobPlan::from_json(r#"["list", "download", "merge"]"#).is_err());
+        assert!(JobPlan::from_json(r#"["list", "download", "zip", "upload"]"#).is_err());
+
+        let err = JobPlan::from_json(
+            r#"["list", "download", "merge",
+                {"type": "webhook", "name": "upload", "url": "http://validator.local/check"}]"#,
+        )
+        .err()
+        .unwrap();
+        assert!(err.to_string().contains("built-in step 'upload'"));
+    }
+}
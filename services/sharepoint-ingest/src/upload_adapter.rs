@@ -8,9 +8,12 @@ use reqwest::multipart::{Form, Part};
 use reqwest::Client;
 use serde::{Deserialize, Serialize};
 use serde_json::Value;
+use shared::dto::RunPriority;
 use tracing::warn;
 use uuid::Uuid;
 
+use crate::job::JobReference;
+
 #[derive(Clone)]
 pub struct UploadAdapter {
     client: Client,
@@ -57,6 +60,7 @@ impl UploadAdapter {
 
     /// Uploads the file to the configured endpoint and returns the API response
     /// metadata.
+    #[allow(clippy::too_many_arguments)]
     pub async fn upload(
         &self,
         file_path: &Path,
@@ -64,16 +68,28 @@ impl UploadAdapter {
         override_url: Option<&str>,
         tenant_id: Option<Uuid>,
         pipeline_id: Option<Uuid>,
+        reference: &JobReference,
+        priority: RunPriority,
     ) -> Result<UploadResult> {
         let tenant_value = tenant_id.map(|id| id.to_string());
         let pipeline_value = pipeline_id.map(|id| id.to_string());
-        let mut form = Form::new().text("defer_pipeline", "true".to_string());
+        // Den Pipeline-Start übernimmt der Job selbst, sobald der Upload bereit ist
+        let mut form = Form::new().text("run_immediately", "false".to_string());
         if let Some(tenant) = &tenant_value {
             form = form.text("tenant_id", tenant.clone());
         }
         if let Some(pipeline) = &pipeline_value {
             form = form.text("pipeline_id", pipeline.clone());
         }
+        if let Some(label) = &reference.job_label {
+            form = form.text("job_label", label.clone());
+        }
+        if let Some(external_ref) = &reference.external_ref {
+            form = form.text("external_ref", external_ref.clone());
+        }
+        if priority != RunPriority::Normal {
+            form = form.text("priority", priority.to_string());
+        }
         let part = Part::file(file_path)
             .await?
             .file_name(file_name.to_string())
@@ -0,0 +1,173 @@
+//! Microsoft Graph change notifications for the SharePoint drive.
+//!
+//! With `GRAPH_WEBHOOK_URL` set, the service keeps one Graph subscription on
+//! the drive root ([`ensure_subscription`], checked every
+//! [`CHECK_INTERVAL`]) and receives its notifications under
+//! `POST /graph/notifications`. A notification carries no details about the
+//! change; it only wakes the automation poller, which picks up the changed
+//! folders through the delta query (see `delta.rs`). The regular poll keeps
+//! running as fallback for lost notifications.
+//!
+//! Each subscription gets a random `clientState`, stored with it in
+//! `sharepoint_subscriptions`; notifications with an unknown subscription or a
+//! different `clientState` are ignored ([`accepted`]).
+
+use std::collections::HashMap;
+
+use anyhow::Result;
+use chrono::{Duration as ChronoDuration, Utc};
+use serde::Deserialize;
+use tokio_postgres::Client;
+use tracing::info;
+use uuid::Uuid;
+
+use crate::msgraph::MsGraphClient;
+
+pub const WEBHOOK_SCHEMA_SQL: &str = r#"
+CREATE TABLE IF NOT EXISTS sharepoint_subscriptions (
+    id TEXT PRIMARY KEY,
+    drive_id TEXT NOT NULL,
+    client_state TEXT NOT NULL,
+    expires_at TIMESTAMPTZ NOT NULL,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT now()
+);
+"#;
+
+/// How often the subscription is checked and, if due, renewed.
+pub const CHECK_INTERVAL: std::time::Duration = std::time::Duration::from_secs(3600);
+/// Notifications often come in bursts; the poller waits this long after the
+/// first one, so one poll covers the whole burst.
+pub const DEBOUNCE: std::time::Duration = std::time::Duration::from_secs(2);
+
+/// Requested lifetime; Graph allows just under 30 days for drive items.
+fn lifetime() -> ChronoDuration {
+    ChronoDuration::days(3)
+}
+
+/// Subscriptions expiring within this window are renewed.
+fn renew_before() -> ChronoDuration {
+    ChronoDuration::days(1)
+}
+
+/// Body of a Graph notification request.
+#[derive(Debug, Deserialize)]
+pub struct NotificationBatch {
+    #[serde(default)]
+    pub value: Vec<Notification>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Notification {
+    pub subscription_id: String,
+    #[serde(default)]
+    pub client_state: Option<String>,
+}
+
+/// `clientState` per stored subscription id.
+pub async fn client_states(client: &Client) -> Result<HashMap<String, String>> {
+    let rows = client
+        .query("SELECT id, client_state FROM sharepoint_subscriptions", &[])
+        .await?;
+    Ok(rows.iter().map(|row| (row.get(0), row.get(1))).collect())
+}
+
+/// Number of notifications that belong to one of our subscriptions.
+pub fn accepted(notifications: &[Notification], client_states: &HashMap<String, String>) -> usize {
+    notifications
+        .iter()
+        .filter(|n| {
+            client_states
+                .get(&n.subscription_id)
+                .is_some_and(|state| n.client_state.as_deref() == Some(state.as_str()))
+        })
+        .count()
+}
+
+/// Creates the subscription of the drive or renews it once it expires within
+/// a day; a subscription Graph no longer knows is replaced.
+pub async fn ensure_subscription(
+    graph: &MsGraphClient,
+    client: &Client,
+    notification_url: &str,
+) -> Result<()> {
+    let drive_id = graph.drive_id().await?;
+    client
+        .execute(
+            "DELETE FROM sharepoint_subscriptions WHERE expires_at < now()",
+            &[],
+        )
+        .await?;
+    let current = client
+        .query_opt(
+            "SELECT id, expires_at FROM sharepoint_subscriptions
+             WHERE drive_id = $1 ORDER BY expires_at DESC LIMIT 1",
+            &[&drive_id],
+        )
+        .await?;
+    let now = Utc::now();
+    if let Some(row) = current {
+        let id: String = row.get(0);
+        let expires_at: chrono::DateTime<Utc> = row.get(1);
+        if expires_at - now > renew_before() {
+            return Ok(());
+        }
+        match graph.renew_subscription(&id, now + lifetime()).await? {
+            Some(renewed) => {
+                client
+                    .execute(
+                        "UPDATE sharepoint_subscriptions SET expires_at = $2 WHERE id = $1",
+                        &[&id, &renewed.expiration_date_time],
+                    )
+                    .await?;
+                info!(subscription_id = %id, expires_at = %renewed.expiration_date_time, "graph subscription renewed");
+                return Ok(());
+            }
+            None => {
+                client
+                    .execute("DELETE FROM sharepoint_subscriptions WHERE id = $1", &[&id])
+                    .await?;
+            }
+        }
+    }
+    let client_state = Uuid::new_v4().simple().to_string();
+    // Benachrichtigungen vor dem INSERT werden verworfen; der reguläre Poll holt sie nach
+    let created = graph
+        .create_subscription(notification_url, &client_state, now + lifetime())
+        .await?;
+    client
+        .execute(
+            "INSERT INTO sharepoint_subscriptions (id, drive_id, client_state, expires_at)
+             VALUES ($1, $2, $3, $4)
+             ON CONFLICT (id) DO UPDATE SET expires_at = EXCLUDED.expires_at",
+            &[
+                &created.id,
+                &drive_id,
+                &client_state,
+                &created.expiration_date_time,
+            ],
+        )
+        .await?;
+    info!(subscription_id = %created.id, expires_at = %created.expiration_date_time, "graph subscription created");
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn accepts_only_known_subscriptions_with_matching_client_state() {
+        let batch: NotificationBatch = serde_json::from_value(serde_json::json!({
+            "value": [
+                {"subscriptionId": "sub-1", "clientState": "secret", "changeType": "updated"},
+                {"subscriptionId": "sub-1", "clientState": "wrong"},
+                {"subscriptionId": "sub-1"},
+                {"subscriptionId": "sub-2", "clientState": "secret"}
+            ]
+        }))
+        .unwrap();
+        let states = HashMap::from([("sub-1".to_string(), "secret".to_string())]);
+        assert_eq!(accepted(&batch.value, &states), 1);
+    }
+}
@@ -0,0 +1,237 @@
+//! Embedded files of PDF portfolios and attachments.
+//!
+//! A PDF can carry further files in its `EmbeddedFiles` name tree; portfolios
+//! consist of little more than a cover page and such attachments. The page
+//! extraction only sees the pages of the outer document, so with
+//! `EXTRACT_ATTACHMENTS=1` the service lists the embedded files with
+//! `pdfdetach -list`, saves every embedded PDF through the sandbox and runs it
+//! through the regular per-page extraction ([`extract_attachment_pages`]).
+//! Other file types are skipped, nested attachments are not unpacked.
+
+use std::env;
+
+use anyhow::{Context, Result};
+use tokio::io::AsyncReadExt;
+use tracing::{info, warn};
+
+use crate::encryption::{self, PdfPassword};
+use crate::sandbox::{self, Tool};
+use crate::{extract_text_pages, ExtractionConfig, PageExtraction, TempPdf};
+
+/// Creates `pdf_attachment_texts` (see migration 0039).
+pub const CREATE_TABLE_SQL: &str = "CREATE TABLE IF NOT EXISTS pdf_attachment_texts (
+    merged_pdf_id INTEGER NOT NULL REFERENCES merged_pdfs(id) ON DELETE CASCADE,
+    attachment_no INTEGER NOT NULL,
+    name TEXT NOT NULL,
+    size_bytes BIGINT NOT NULL,
+    page_no INTEGER NOT NULL,
+    text TEXT NOT NULL,
+    ocr_used BOOLEAN NOT NULL DEFAULT false,
+    lang TEXT,
+    layout_json JSONB,
+    PRIMARY KEY (merged_pdf_id, attachment_no, page_no)
+)";
+
+/// `%PDF-` must appear within the first KiB of a PDF.
+const HEADER_WINDOW: usize = 1024;
+
+#[derive(Clone, Debug)]
+/// Configuration of the attachment pass (`EXTRACT_ATTACHMENTS`, `ATTACHMENT_MAX_FILES`).
+pub struct AttachmentOptions {
+    pub enabled: bool,
+    /// Embedded files looked at per document; the rest is logged and skipped.
+    pub max_files: usize,
+}
+
+impl Default for AttachmentOptions {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            max_files: 20,
+        }
+    }
+}
+
+impl AttachmentOptions {
+    pub fn from_env() -> Self {
+        let defaults = Self::default();
+        Self {
+            enabled: env::var("EXTRACT_ATTACHMENTS")
+                .map(|v| v == "1" || v.eq_ignore_ascii_case("true"))
+                .unwrap_or(defaults.enabled),
+            max_files: env::var("ATTACHMENT_MAX_FILES")
+                .ok()
+                .and_then(|v| v.trim().parse().ok())
+                .unwrap_or(defaults.max_files),
+        }
+    }
+}
+
+/// Entry of `pdfdetach -list`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct EmbeddedFile {
+    /// 1-based, as expected by `pdfdetach -save`.
+    pub index: u32,
+    pub name: String,
+}
+
+/// Embedded PDF saved to a temporary file.
+#[derive(Debug)]
+pub struct Attachment {
+    pub index: u32,
+    pub name: String,
+    pub size_bytes: u64,
+    pub file: TempPdf,
+}
+
+/// Pages of one embedded PDF, numbered from 0 within the attachment.
+#[derive(Clone, Debug)]
+pub struct AttachmentExtraction {
+    pub index: u32,
+    pub name: String,
+    pub size_bytes: u64,
+    pub pages: Vec<PageExtraction>,
+}
+
+/// Parses the `N: name` lines of `pdfdetach -list`.
+pub fn parse_list(output: &str) -> Vec<EmbeddedFile> {
+    output
+        .lines()
+        .filter_map(|line| {
+            let (index, name) = line.split_once(':')?;
+            Some(EmbeddedFile {
+                index: index.trim().parse().ok()?,
+                name: name.trim().to_string(),
+            })
+        })
+        .collect()
+}
+
+/// Lists the embedded files of the PDF at `path`.
+pub async fn list_attachments(
+    path: &str,
+    password: Option<&PdfPassword>,
+) -> Result<Vec<EmbeddedFile>> {
+    let mut args = encryption::password_args(password);
+    args.extend(["-list", "-enc", "UTF-8", path]);
+    let output = sandbox::run(Tool::Pdfdetach, args)
+        .await
+        .context("pdfdetach -list")?;
+    Ok(parse_list(&String::from_utf8_lossy(&output.stdout)))
+}
+
+/// Saves the embedded PDFs of `path` (at most `options.max_files` entries are
+/// looked at). Files that fail to save are logged and skipped.
+pub async fn extract_attachments(
+    path: &str,
+    password: Option<&PdfPassword>,
+    options: &AttachmentOptions,
+) -> Result<Vec<Attachment>> {
+    let files = list_attachments(path, password).await?;
+    if files.len() > options.max_files {
+        warn!(
+            path,
+            files = files.len(),
+            max = options.max_files,
+            "too many embedded files, skipping the rest"
+        );
+    }
+    let mut out = Vec::new();
+    for file in files.into_iter().take(options.max_files) {
+        // Ziel vorab reservieren: der eingebettete Name kann Pfadanteile enthalten
+        let temp = TempPdf::reserve();
+        let index = file.index.to_string();
+        let mut args = encryption::password_args(password);
+        args.extend(["-save", &index, "-o", temp.path(), path]);
+        if let Err(e) = sandbox::run(Tool::Pdfdetach, args).await {
+            warn!(%e, path, name = %file.name, "save embedded file failed");
+            continue;
+        }
+        match is_pdf(temp.path()).await {
+            Ok(true) => {}
+            Ok(false) => {
+                info!(path, name = %file.name, "embedded file is not a pdf, skipped");
+                continue;
+            }
+            Err(e) => {
+                warn!(%e, path, name = %file.name, "read embedded file failed");
+                continue;
+            }
+        }
+        let size_bytes = tokio::fs::metadata(temp.path())
+            .await
+            .map(|m| m.len())
+            .unwrap_or(0);
+        out.push(Attachment {
+            index: file.index,
+            name: file.name,
+            size_bytes,
+            file: temp,
+        });
+    }
+    Ok(out)
+}
+
+/// Extracts the pages of every embedded PDF of `path` like the document itself.
+/// An attachment whose extraction fails is logged and left out.
+pub async fn extract_attachment_pages(
+    path: &str,
+    config: &ExtractionConfig,
+    options: &AttachmentOptions,
+) -> Result<Vec<AttachmentExtraction>> {
+    let mut out = Vec::new();
+    // Das Passwort gilt für das Trägerdokument; ungeschützte Anhänge öffnet
+    // poppler auch mit Passwort
+    for attachment in extract_attachments(path, config.password.as_ref(), options).await? {
+        match extract_text_pages(attachment.file.path(), config).await {
+            Ok(pages) => out.push(AttachmentExtraction {
+                index: attachment.index,
+                name: attachment.name,
+                size_bytes: attachment.size_bytes,
+                pages,
+            }),
+            Err(e) => warn!(%e, path, name = %attachment.name, "attachment extraction failed"),
+        }
+    }
+    Ok(out)
+}
+
+async fn is_pdf(path: &str) -> Result<bool> {
+    let mut file = tokio::fs::File::open(path).await?;
+    let mut head = Vec::with_capacity(HEADER_WINDOW);
+    (&mut file)
+        .take(HEADER_WINDOW as u64)
+        .read_to_end(&mut head)
+        .await?;
+    Ok(head.windows(5).any(|w| w == b"%PDF-"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_pdfdetach_list() {
+        let output =
+            "3 embedded files\n1: Rechnung 2024.pdf\n2: daten.xml\n3: ../anhang: scan.pdf\n";
+        let files = parse_list(output);
+        assert_eq!(files.len(), 3);
+        assert_eq!(
+            files[0],
+            EmbeddedFile {
+                index: 1,
+                name: "Rechnung 2024.pdf".to_string()
+            }
+        );
+        assert_eq!(files[2].name, "../anhang: scan.pdf");
+        assert!(parse_list("0 embedded files\n").is_empty());
+    }
+
+    #[tokio::test]
+    async fn recognizes_pdf_header() {
+        let pdf = TempPdf::write(b"\n%PDF-1.7\n...").await.unwrap();
+        assert!(is_pdf(pdf.path()).await.unwrap());
+        let xml = TempPdf::write(b"<?xml version=\"1.0\"?>").await.unwrap();
+        assert!(!is_pdf(xml.path()).await.unwrap());
+    }
+}
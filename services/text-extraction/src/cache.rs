@@ -0,0 +1,351 @@
+//! Reuse of page extractions across runs.
+//!
+//! Re-running a pipeline on the same document would OCR every page again. With
+//! a cache set on [`crate::ExtractionConfig::cache`], every page is looked up by
+//! the SHA-256 of the PDF, its page number and a hash of all options that
+//! influence the result ([`options_hash`]); only misses are extracted and then
+//! stored. [`PostgresCache`] keeps the pages in `page_extraction_cache` and
+//! survives restarts, [`MemoryCache`] holds a bounded number of pages in the
+//! process. The service selects one with `EXTRACTION_CACHE` (see
+//! [`cache_from_env`]). Cache errors are logged and never fail an extraction.
+
+use std::{
+    collections::{HashMap, VecDeque},
+    env, fmt,
+    future::Future,
+    pin::Pin,
+    sync::{Arc, Mutex},
+};
+
+use anyhow::{Context, Result};
+use deadpool_postgres::Pool;
+use sha2::{Digest, Sha256};
+use tracing::warn;
+
+use crate::{ExtractionConfig, PageExtraction, PageLayout};
+
+/// Bump when the extraction logic changes, so stored pages are not reused.
+const CACHE_VERSION: u32 = 1;
+
+/// Creates `page_extraction_cache` (see migrations 0037 and 0040).
+pub const CREATE_TABLE_SQL: &str = "CREATE TABLE IF NOT EXISTS page_extraction_cache (
+    sha256 TEXT NOT NULL,
+    page_no INTEGER NOT NULL,
+    options_hash TEXT NOT NULL,
+    text TEXT NOT NULL,
+    ocr_used BOOLEAN NOT NULL,
+    layout_json JSONB,
+    ocr_strategy TEXT,
+    quality_score DOUBLE PRECISION,
+    lang TEXT,
+    ocr_preprocessing TEXT,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT now(),
+    PRIMARY KEY (sha256, page_no, options_hash)
+)";
+
+/// Identifies one page extraction.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct CacheKey {
+    /// SHA-256 of the PDF (hex).
+    pub sha256: String,
+    /// 0-based, like [`PageExtraction::page_no`].
+    pub page_no: i32,
+    /// See [`options_hash`].
+    pub options: String,
+}
+
+pub type CacheFuture<'a, T> = Pin<Box<dyn Future<Output = Result<T>> + Send + 'a>>;
+
+pub trait ExtractionCache: Send + Sync + fmt::Debug {
+    fn get<'a>(&'a self, key: &'a CacheKey) -> CacheFuture<'a, Option<PageExtraction>>;
+
+    fn put<'a>(&'a self, key: &'a CacheKey, page: &'a PageExtraction) -> CacheFuture<'a, ()>;
+}
+
+/// Hash (hex) of every option that changes the extraction of a page.
+pub fn options_hash(config: &ExtractionConfig) -> String {
+    let strategies =
+        |list: &[crate::OcrStrategy]| list.iter().map(|s| s.label()).collect::<Vec<_>>().join(",");
+    let fingerprint = format!(
+        "v{CACHE_VERSION}|layout_text={}|ocr={}|engine={}|lang={}|detect={}|psm={}|dpi={}\
+         |min_nonws={}|quality_min={}|retry={}|min_conf={}|fallback={}|osd={}|force={}\
+         |layout={}|backend={:?}|preprocess={}",
+        config.pdftext_layout,
+        config.ocr_enabled,
+        config.ocr_engine.name(),
+        config.ocr_lang,
+        config.ocr_lang_detect.join(","),
+        config.ocr_psm,
+        config.ocr_dpi,
+        config.ocr_min_nonws,
+        config.ocr_quality_min,
+        strategies(&config.ocr_retry_strategies),
+        config.ocr_min_confidence,
+        strategies(&config.ocr_fallback_strategies),
+        config.ocr_detect_orientation,
+        config.force_ocr,
+        config.layout_enabled,
+        config.layout_backend,
+        config
+            .ocr_preprocess
+            .iter()
+            .map(|s| s.name())
+            .collect::<Vec<_>>()
+            .join(","),
+    );
+    format!("{:x}", Sha256::digest(fingerprint.as_bytes()))
+}
+
+/// SHA-256 (hex) of the file at `path`.
+pub(crate) async fn file_sha256(path: &str) -> Result<String> {
+    let data = tokio::fs::read(path)
+        .await
+        .with_context(|| format!("read {path}"))?;
+    Ok(format!("{:x}", Sha256::digest(&data)))
+}
+
+/// Cache for `EXTRACTION_CACHE`: `postgres`, `memory` (at most
+/// `EXTRACTION_CACHE_MAX_PAGES` pages) or none.
+pub fn cache_from_env(pool: &Pool) -> Option<Arc<dyn ExtractionCache>> {
+    let kind = env::var("EXTRACTION_CACHE").ok()?;
+    match kind.trim().to_ascii_lowercase().as_str() {
+        "postgres" | "pg" => Some(Arc::new(PostgresCache::new(pool.clone()))),
+        "memory" => {
+            let max_pages = env::var("EXTRACTION_CACHE_MAX_PAGES")
+                .ok()
+                .and_then(|v| v.trim().parse::<usize>().ok())
+                .unwrap_or(10_000);
+            Some(Arc::new(MemoryCache::new(max_pages)))
+        }
+        "" | "off" | "none" => None,
+        other => {
+            warn!(cache = other, "unknown EXTRACTION_CACHE, cache disabled");
+            None
+        }
+    }
+}
+
+#[derive(Default)]
+struct MemoryState {
+    pages: HashMap<CacheKey, PageExtraction>,
+    /// Insertion order; the oldest page is evicted first.
+    order: VecDeque<CacheKey>,
+}
+
+/// Pages held in the process, at most `max_pages`.
+pub struct MemoryCache {
+    max_pages: usize,
+    state: Mutex<MemoryState>,
+}
+
+impl MemoryCache {
+    pub fn new(max_pages: usize) -> Self {
+        Self {
+            max_pages: max_pages.max(1),
+            state: Mutex::default(),
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.state.lock().unwrap().pages.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+impl fmt::Debug for MemoryCache {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("MemoryCache")
+            .field("max_pages", &self.max_pages)
+            .field("pages", &self.len())
+            .finish()
+    }
+}
+
+impl ExtractionCache for MemoryCache {
+    fn get<'a>(&'a self, key: &'a CacheKey) -> CacheFuture<'a, Option<PageExtraction>> {
+        let page = self.state.lock().unwrap().pages.get(key).cloned();
+        Box::pin(async move { Ok(page) })
+    }
+
+    fn put<'a>(&'a self, key: &'a CacheKey, page: &'a PageExtraction) -> CacheFuture<'a, ()> {
+        let mut state = self.state.lock().unwrap();
+        if state.pages.insert(key.clone(), page.clone()).is_none() {
+            state.order.push_back(key.clone());
+        }
+        while state.pages.len() > self.max_pages {
+            let Some(oldest) = state.order.pop_front() else {
+                break;
+            };
+            state.pages.remove(&oldest);
+        }
+        Box::pin(async { Ok(()) })
+    }
+}
+
+/// Pages stored in `page_extraction_cache`.
+#[derive(Clone)]
+pub struct PostgresCache {
+    pool: Pool,
+}
+
+impl PostgresCache {
+    pub fn new(pool: Pool) -> Self {
+        Self { pool }
+    }
+}
+
+impl fmt::Debug for PostgresCache {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("PostgresCache")
+    }
+}
+
+impl ExtractionCache for PostgresCache {
+    fn get<'a>(&'a self, key: &'a CacheKey) -> CacheFuture<'a, Option<PageExtraction>> {
+        Box::pin(async move {
+            let client = self.pool.get().await.context("db pool get")?;
+            let Some(row) = client
+                .query_opt(
+                    "SELECT text, ocr_used, layout_json, ocr_strategy, quality_score, lang,
+                            ocr_preprocessing
+                       FROM page_extraction_cache
+                      WHERE sha256 = $1 AND page_no = $2 AND options_hash = $3",
+                    &[&key.sha256, &key.page_no, &key.options],
+                )
+                .await
+                .context("read page cache")?
+            else {
+                return Ok(None);
+            };
+            let layout: Option<serde_json::Value> = row.get(2);
+            let layout = match layout {
+                Some(value) => {
+                    Some(serde_json::from_value::<PageLayout>(value).context("cached layout")?)
+                }
+                None => None,
+            };
+            Ok(Some(PageExtraction {
+                page_no: key.page_no,
+                text: row.get(0),
+                ocr_used: row.get(1),
+                layout,
+                ocr_strategy: row.get(3),
+                quality_score: row.get(4),
+                lang: row.get(5),
+                ocr_preprocessing: row.get(6),
+                image: None,
+            }))
+        })
+    }
+
+    fn put<'a>(&'a self, key: &'a CacheKey, page: &'a PageExtraction) -> CacheFuture<'a, ()> {
+        Box::pin(async move {
+            let layout = page
+                .layout
+                .as_ref()
+                .map(serde_json::to_value)
+                .transpose()
+                .context("serialize layout")?;
+            let client = self.pool.get().await.context("db pool get")?;
+            client
+                .execute(
+                    "INSERT INTO page_extraction_cache
+                        (sha256, page_no, options_hash, text, ocr_used, layout_json,
+                         ocr_strategy, quality_score, lang, ocr_preprocessing)
+                     VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10)
+                     ON CONFLICT (sha256, page_no, options_hash) DO NOTHING",
+                    &[
+                        &key.sha256,
+                        &key.page_no,
+                        &key.options,
+                        &page.text,
+                        &page.ocr_used,
+                        &layout,
+                        &page.ocr_strategy,
+                        &page.quality_score,
+                        &page.lang,
+                        &page.ocr_preprocessing,
+                    ],
+                )
+                .await
+                .context("write page cache")?;
+            Ok(())
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn key(page_no: i32) -> CacheKey {
+        CacheKey {
+            sha256: "abc".to_string(),
+            page_no,
+            options: options_hash(&ExtractionConfig::default()),
+        }
+    }
+
+    fn page(page_no: i32) -> PageExtraction {
+        PageExtraction {
+            page_no,
+            text: format!("Seite {page_no}"),
+            ocr_used: true,
+            layout: None,
+            ocr_strategy: Some("psm6@300dpi".to_string()),
+            quality_score: Some(0.9),
+            lang: Some("deu".to_string()),
+            ocr_preprocessing: None,
+            image: None,
+        }
+    }
+
+    #[test]
+    fn options_hash_tracks_relevant_options() {
+        let base = ExtractionConfig::default();
+        assert_eq!(options_hash(&base), options_hash(&base.clone()));
+        assert_ne!(
+            options_hash(&base),
+            options_hash(&base.clone().ocr_dpi(400))
+        );
+        assert_ne!(
+            options_hash(&base),
+            options_hash(&base.clone().ocr_lang("eng"))
+        );
+        assert_ne!(
+            options_hash(&base),
+            options_hash(
+                &base
+                    .clone()
+                    .ocr_preprocess(vec![crate::preprocess::PreprocessStep::Threshold])
+            )
+        );
+        // Parallelität ändert das Ergebnis nicht
+        assert_eq!(
+            options_hash(&base),
+            options_hash(&base.clone().max_parallel_text(8).max_parallel_ocr(8))
+        );
+    }
+
+    #[tokio::test]
+    async fn memory_cache_evicts_oldest_pages() {
+        let cache = MemoryCache::new(2);
+        for page_no in 0..3 {
+            cache.put(&key(page_no), &page(page_no)).await.unwrap();
+        }
+        assert_eq!(cache.len(), 2);
+        assert!(cache.get(&key(0)).await.unwrap().is_none());
+        let hit = cache.get(&key(2)).await.unwrap().unwrap();
+        assert_eq!(hit.text, "Seite 2");
+        assert_eq!(hit.lang.as_deref(), Some("deu"));
+
+        let other_options = CacheKey {
+            options: options_hash(&ExtractionConfig::default().ocr_dpi(400)),
+            ..key(2)
+        };
+        assert!(cache.get(&other_options).await.unwrap().is_none());
+    }
+}
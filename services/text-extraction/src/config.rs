@@ -0,0 +1,445 @@
+//! Settings of one extraction run.
+//!
+//! [`ExtractionConfig`] is passed to [`crate::extract_text_pages`] and
+//! [`crate::ocr_page`], so a process can run extractions with different OCR
+//! languages or resolutions side by side. The service builds it once with
+//! [`ExtractionConfig::from_env`]; embedding callers start from
+//! [`ExtractionConfig::default`] and override single values:
+//!
+//! ```
+//! use text_extraction::ExtractionConfig;
+//!
+//! let english = ExtractionConfig::default().ocr_lang("eng").ocr_dpi(400);
+//! let german = ExtractionConfig::default().ocr_lang("deu").layout_enabled(false);
+//! ```
+
+use std::{env, sync::Arc};
+
+use crate::cache::ExtractionCache;
+use crate::encryption::PdfPassword;
+use crate::language;
+use crate::ocr::{self, OcrEngine, TesseractCli};
+use crate::page_images;
+use crate::preprocess::{self, PreprocessStep};
+
+#[derive(Clone, Debug)]
+/// Configuration controlling text extraction, OCR and layout capture.
+pub struct ExtractionConfig {
+    pub(crate) pdftext_layout: bool,
+    pub(crate) ocr_enabled: bool,
+    pub(crate) ocr_engine: Arc<dyn OcrEngine>,
+    pub(crate) ocr_lang: String,
+    pub(crate) ocr_lang_detect: Vec<String>,
+    pub(crate) ocr_psm: String,
+    pub(crate) ocr_dpi: u32,
+    pub(crate) ocr_min_nonws: usize,
+    pub(crate) ocr_quality_min: f64,
+    pub(crate) ocr_retry_strategies: Vec<OcrStrategy>,
+    pub(crate) ocr_min_confidence: f32,
+    pub(crate) ocr_fallback_strategies: Vec<OcrStrategy>,
+    pub(crate) ocr_detect_orientation: bool,
+    pub(crate) ocr_preprocess: Vec<PreprocessStep>,
+    pub(crate) text_layer_policy: TextLayerPolicy,
+    pub(crate) text_layer_samples: usize,
+    pub(crate) text_layer_min_similarity: f64,
+    /// Set per document when the text layer failed verification.
+    pub(crate) force_ocr: bool,
+    /// Strategy of [`ExtractionConfig::for_reocr`].
+    pub(crate) reocr_strategy: OcrStrategy,
+    pub(crate) layout_enabled: bool,
+    pub(crate) layout_backend: LayoutBackend,
+    pub(crate) max_parallel_text: usize,
+    pub(crate) max_parallel_ocr: usize,
+    pub(crate) cache: Option<Arc<dyn ExtractionCache>>,
+    /// Set per document for password-protected PDFs.
+    pub(crate) password: Option<PdfPassword>,
+    /// Longer side of page images kept from OCR renders; `None` keeps none.
+    pub(crate) page_image_max_px: Option<u32>,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+/// Tesseract page segmentation mode and render resolution of one OCR attempt.
+pub struct OcrStrategy {
+    pub(crate) psm: String,
+    pub(crate) dpi: u32,
+}
+
+impl OcrStrategy {
+    pub fn new(psm: impl Into<String>, dpi: u32) -> Self {
+        Self {
+            psm: psm.into(),
+            dpi,
+        }
+    }
+
+    /// Label stored in `pdf_texts.ocr_strategy`, e.g. `psm6@300dpi`.
+    pub fn label(&self) -> String {
+        format!("psm{}@{}dpi", self.psm, self.dpi)
+    }
+}
+
+/// Parses `OCR_RETRY_STRATEGIES`, e.g. `6@300,4@300,3@400`; invalid entries are skipped.
+pub(crate) fn parse_strategies(raw: &str) -> Vec<OcrStrategy> {
+    raw.split(',')
+        .filter_map(|entry| {
+            let (psm, dpi) = entry.trim().split_once('@')?;
+            let psm = psm.trim();
+            if psm.is_empty() || !psm.chars().all(|c| c.is_ascii_digit()) {
+                return None;
+            }
+            let dpi = dpi.trim().parse::<u32>().ok().filter(|d| *d > 0)?;
+            Some(OcrStrategy::new(psm, dpi))
+        })
+        .collect()
+}
+
+#[derive(Clone, Debug, Copy, PartialEq, Eq)]
+/// How far the embedded text layer of a PDF is trusted (`TEXT_LAYER_POLICY`).
+pub enum TextLayerPolicy {
+    /// Use `pdftotext` whenever it yields enough text.
+    Trust,
+    /// Compare sampled pages with OCR and switch the document to OCR on divergence.
+    Verify,
+}
+
+#[derive(Clone, Debug, Copy, PartialEq, Eq)]
+/// Available layout extraction strategies.
+pub enum LayoutBackend {
+    BBox,
+    PdfToHtml,
+}
+
+impl Default for ExtractionConfig {
+    fn default() -> Self {
+        Self {
+            pdftext_layout: true,
+            ocr_enabled: true,
+            ocr_engine: Arc::new(TesseractCli),
+            ocr_lang: "deu+eng".to_string(),
+            ocr_lang_detect: Vec::new(),
+            ocr_psm: "6".to_string(),
+            ocr_dpi: 300,
+            ocr_min_nonws: 24,
+            ocr_quality_min: 0.15,
+            ocr_retry_strategies: parse_strategies("6@300,4@300,3@400"),
+            ocr_min_confidence: 0.6,
+            ocr_fallback_strategies: parse_strategies("4@300,11@300,6@400"),
+            ocr_detect_orientation: true,
+            ocr_preprocess: Vec::new(),
+            text_layer_policy: TextLayerPolicy::Trust,
+            text_layer_samples: 2,
+            text_layer_min_similarity: 0.5,
+            force_ocr: false,
+            reocr_strategy: OcrStrategy::new("4", 400),
+            layout_enabled: true,
+            layout_backend: LayoutBackend::BBox,
+            max_parallel_text: 4,
+            max_parallel_ocr: 2,
+            cache: None,
+            password: None,
+            page_image_max_px: None,
+        }
+    }
+}
+
+impl ExtractionConfig {
+    /// Defaults overridden by the service environment (`OCR_LANG`, `OCR_DPI`, ...).
+    pub fn from_env() -> Self {
+        let mut config = Self::default();
+        if let Ok(v) = env::var("PDFTEXT_LAYOUT") {
+            config.pdftext_layout = v != "0";
+        }
+        if let Ok(v) = env::var("OCR_ENABLED") {
+            config.ocr_enabled = v != "0";
+        }
+        if let Ok(v) = env::var("OCR_ENGINE") {
+            config.ocr_engine = ocr::engine_from_name(&v);
+        }
+        if let Ok(v) = env::var("OCR_LANG") {
+            config.ocr_lang = v;
+        }
+        if let Ok(v) = env::var("OCR_LANG_DETECT") {
+            config.ocr_lang_detect = language::parse_candidates(&v);
+        }
+        if let Ok(v) = env::var("OCR_PSM") {
+            config.ocr_psm = v;
+        }
+        if let Some(v) = parse_env("OCR_DPI") {
+            config.ocr_dpi = v;
+        }
+        if let Some(v) = parse_env("OCR_MIN_NONWS") {
+            config.ocr_min_nonws = v;
+        }
+        if let Some(v) = parse_env("OCR_QUALITY_MIN") {
+            config.ocr_quality_min = v;
+        }
+        if let Ok(v) = env::var("OCR_RETRY_STRATEGIES") {
+            config.ocr_retry_strategies = parse_strategies(&v);
+        }
+        if let Some(v) = parse_env("OCR_MIN_CONFIDENCE") {
+            config.ocr_min_confidence = v;
+        }
+        if let Ok(v) = env::var("OCR_FALLBACK_STRATEGIES") {
+            config.ocr_fallback_strategies = parse_strategies(&v);
+        }
+        if let Ok(v) = env::var("OCR_DETECT_ORIENTATION") {
+            config.ocr_detect_orientation = v != "0";
+        }
+        if let Ok(v) = env::var("OCR_PREPROCESS") {
+            config.ocr_preprocess = preprocess::parse_steps(&v);
+        }
+        if let Ok(v) = env::var("TEXT_LAYER_POLICY") {
+            config.text_layer_policy = match v.to_ascii_lowercase().as_str() {
+                "verify" => TextLayerPolicy::Verify,
+                _ => TextLayerPolicy::Trust,
+            };
+        }
+        if let Some(v) = parse_env("TEXT_LAYER_SAMPLE_PAGES").filter(|v| *v > 0) {
+            config.text_layer_samples = v;
+        }
+        if let Some(v) = parse_env("TEXT_LAYER_MIN_SIMILARITY") {
+            config.text_layer_min_similarity = v;
+        }
+        if let Some(strategy) = env::var("REOCR_STRATEGY")
+            .ok()
+            .and_then(|v| parse_strategies(&v).into_iter().next())
+        {
+            config.reocr_strategy = strategy;
+        }
+        if let Ok(v) = env::var("LAYOUT_ENABLED") {
+            config.layout_enabled = v != "0";
+        }
+        if let Ok(v) = env::var("LAYOUT_BACKEND") {
+            config.layout_backend = match v.to_ascii_lowercase().as_str() {
+                "pdftohtml" => LayoutBackend::PdfToHtml,
+                _ => LayoutBackend::BBox,
+            };
+        }
+        if let Some(v) = parse_env("MAX_PARALLEL_TEXT").filter(|v| *v > 0) {
+            config.max_parallel_text = v;
+        }
+        if let Some(v) = parse_env("MAX_PARALLEL_OCR").filter(|v| *v > 0) {
+            config.max_parallel_ocr = v;
+        }
+        if env::var("PAGE_IMAGE_CACHE").is_ok_and(|v| v == "1" || v.eq_ignore_ascii_case("true")) {
+            config.page_image_max_px = Some(page_images::max_px_from_env());
+        }
+        config
+    }
+
+    /// Runs `pdftotext` with `-layout`.
+    pub fn pdftext_layout(mut self, enabled: bool) -> Self {
+        self.pdftext_layout = enabled;
+        self
+    }
+
+    pub fn ocr_enabled(mut self, enabled: bool) -> Self {
+        self.ocr_enabled = enabled;
+        self
+    }
+
+    /// Engine recognizing the rendered pages (see [`crate::ocr`]).
+    pub fn ocr_engine(mut self, engine: Arc<dyn OcrEngine>) -> Self {
+        self.ocr_engine = engine;
+        self
+    }
+
+    /// Tesseract language(s), e.g. `deu+eng`.
+    pub fn ocr_lang(mut self, lang: impl Into<String>) -> Self {
+        self.ocr_lang = lang.into();
+        self
+    }
+
+    /// Languages a page may be in (ISO 639-3, see [`crate::language`]); pages
+    /// detected outside `ocr_lang` are OCRed in their language. Empty disables
+    /// the detection.
+    pub fn ocr_lang_detect<S: Into<String>>(
+        mut self,
+        candidates: impl IntoIterator<Item = S>,
+    ) -> Self {
+        self.ocr_lang_detect = candidates.into_iter().map(Into::into).collect();
+        self
+    }
+
+    /// Tesseract page segmentation mode of the first OCR attempt.
+    pub fn ocr_psm(mut self, psm: impl Into<String>) -> Self {
+        self.ocr_psm = psm.into();
+        self
+    }
+
+    /// Render resolution of the first OCR attempt.
+    pub fn ocr_dpi(mut self, dpi: u32) -> Self {
+        self.ocr_dpi = dpi;
+        self
+    }
+
+    /// Pages with fewer non-whitespace characters in the text layer are OCRed.
+    pub fn ocr_min_nonws(mut self, min: usize) -> Self {
+        self.ocr_min_nonws = min;
+        self
+    }
+
+    /// Dictionary score below which a text layer counts as garbled.
+    pub fn ocr_quality_min(mut self, min: f64) -> Self {
+        self.ocr_quality_min = min;
+        self
+    }
+
+    /// Strategies tried on garbled pages; empty disables the re-OCR.
+    pub fn ocr_retry_strategies(mut self, strategies: Vec<OcrStrategy>) -> Self {
+        self.ocr_retry_strategies = strategies;
+        self
+    }
+
+    /// Mean word confidence (0..1) below which an OCR result counts as failed.
+    pub fn ocr_min_confidence(mut self, min: f32) -> Self {
+        self.ocr_min_confidence = min;
+        self
+    }
+
+    /// Strategies tried when the first OCR attempt fails; empty disables the ladder.
+    pub fn ocr_fallback_strategies(mut self, strategies: Vec<OcrStrategy>) -> Self {
+        self.ocr_fallback_strategies = strategies;
+        self
+    }
+
+    /// Detects the page orientation (Tesseract OSD) before the fallback attempts.
+    pub fn ocr_detect_orientation(mut self, enabled: bool) -> Self {
+        self.ocr_detect_orientation = enabled;
+        self
+    }
+
+    /// Image cleanup before every recognition (see [`crate::preprocess`]).
+    pub fn ocr_preprocess(mut self, steps: Vec<PreprocessStep>) -> Self {
+        self.ocr_preprocess = steps;
+        self
+    }
+
+    pub fn text_layer_policy(mut self, policy: TextLayerPolicy) -> Self {
+        self.text_layer_policy = policy;
+        self
+    }
+
+    /// Number of pages compared with OCR under [`TextLayerPolicy::Verify`].
+    pub fn text_layer_samples(mut self, samples: usize) -> Self {
+        self.text_layer_samples = samples.max(1);
+        self
+    }
+
+    pub fn text_layer_min_similarity(mut self, min: f64) -> Self {
+        self.text_layer_min_similarity = min;
+        self
+    }
+
+    /// OCRs every page, even when the text layer looks fine.
+    pub fn force_ocr(mut self, enabled: bool) -> Self {
+        self.force_ocr = enabled;
+        self
+    }
+
+    /// Strategy of [`ExtractionConfig::for_reocr`] (`REOCR_STRATEGY`, default `4@400`).
+    pub fn reocr_strategy(mut self, strategy: OcrStrategy) -> Self {
+        self.reocr_strategy = strategy;
+        self
+    }
+
+    /// Settings for pages OCRed again because their confidence was too low:
+    /// forced OCR with the re-OCR strategy and hOCR layout, so the new
+    /// confidence can be compared with the stored one.
+    pub fn for_reocr(&self) -> Self {
+        let mut config = self.clone().force_ocr(true).layout_enabled(true);
+        config.ocr_psm = self.reocr_strategy.psm.clone();
+        config.ocr_dpi = self.reocr_strategy.dpi;
+        config
+    }
+
+    pub fn layout_enabled(mut self, enabled: bool) -> Self {
+        self.layout_enabled = enabled;
+        self
+    }
+
+    pub fn layout_backend(mut self, backend: LayoutBackend) -> Self {
+        self.layout_backend = backend;
+        self
+    }
+
+    /// Text slots (`pdftotext`, layout) of the scheduler created by
+    /// [`crate::extract_text_pages`].
+    pub fn max_parallel_text(mut self, max: usize) -> Self {
+        self.max_parallel_text = max.max(1);
+        self
+    }
+
+    /// OCR slots of the scheduler created by [`crate::extract_text_pages`].
+    pub fn max_parallel_ocr(mut self, max: usize) -> Self {
+        self.max_parallel_ocr = max.max(1);
+        self
+    }
+
+    /// Reuses page extractions of earlier runs (see [`crate::cache`]).
+    pub fn cache(mut self, cache: Arc<dyn ExtractionCache>) -> Self {
+        self.cache = Some(cache);
+        self
+    }
+
+    /// Opens a password-protected PDF (see [`crate::encryption`]).
+    pub fn password(mut self, password: PdfPassword) -> Self {
+        self.password = Some(password);
+        self
+    }
+
+    /// Keeps OCR renders scaled down to `max_px` in
+    /// [`crate::PageExtraction::image`] (see [`crate::page_images`]).
+    pub fn page_images(mut self, max_px: Option<u32>) -> Self {
+        self.page_image_max_px = max_px;
+        self
+    }
+
+    pub(crate) fn default_strategy(&self) -> OcrStrategy {
+        OcrStrategy::new(self.ocr_psm.clone(), self.ocr_dpi)
+    }
+}
+
+fn parse_env<T: std::str::FromStr>(key: &str) -> Option<T> {
+    env::var(key).ok().and_then(|v| v.parse::<T>().ok())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_strategies_skips_invalid_entries() {
+        let strategies = parse_strategies("6@300, 4@300,x@200,3@0,11@400,7");
+        let labels: Vec<String> = strategies.iter().map(OcrStrategy::label).collect();
+        assert_eq!(labels, vec!["psm6@300dpi", "psm4@300dpi", "psm11@400dpi"]);
+    }
+
+    #[test]
+    fn configs_are_independent() {
+        let base = ExtractionConfig::default();
+        let english = base.clone().ocr_lang("eng").ocr_dpi(400).ocr_psm("4");
+        let german = base.ocr_lang("deu").layout_enabled(false);
+
+        assert_eq!(english.ocr_lang, "eng");
+        assert_eq!(english.default_strategy().label(), "psm4@400dpi");
+        assert!(english.layout_enabled);
+        assert_eq!(german.ocr_lang, "deu");
+        assert_eq!(german.default_strategy().label(), "psm6@300dpi");
+        assert!(!german.layout_enabled);
+    }
+
+    #[test]
+    fn reocr_forces_ocr_with_its_own_strategy() {
+        let base = ExtractionConfig::default()
+            .layout_enabled(false)
+            .reocr_strategy(OcrStrategy::new("3", 450));
+        let reocr = base.for_reocr();
+
+        assert!(reocr.force_ocr);
+        assert!(reocr.layout_enabled);
+        assert_eq!(reocr.default_strategy().label(), "psm3@450dpi");
+        assert!(!base.force_ocr);
+        assert_eq!(base.default_strategy().label(), "psm6@300dpi");
+    }
+}
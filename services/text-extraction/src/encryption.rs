@@ -0,0 +1,72 @@
+//! Password-protected PDFs.
+//!
+//! A PDF with a user password cannot be opened by the poppler tools without it;
+//! one with only an owner password opens, but `pdftotext` refuses documents
+//! that forbid copying text. [`PdfPassword`] is passed to every tool as owner
+//! and user password (`-opw`/`-upw`, poppler tries the owner password first),
+//! and failures caused by the encryption surface as
+//! [`crate::ExtractionError::Encrypted`] instead of a generic exit status.
+
+use std::fmt;
+
+use crate::sandbox::SandboxError;
+
+/// Password of one document; never printed.
+#[derive(Clone, PartialEq, Eq)]
+pub struct PdfPassword(String);
+
+impl PdfPassword {
+    pub fn new(password: impl Into<String>) -> Self {
+        Self(password.into())
+    }
+}
+
+impl fmt::Debug for PdfPassword {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("PdfPassword(..)")
+    }
+}
+
+/// Arguments prepended to a poppler command line, empty without password.
+pub(crate) fn password_args(password: Option<&PdfPassword>) -> Vec<&str> {
+    match password {
+        Some(PdfPassword(password)) => vec!["-opw", password, "-upw", password],
+        None => Vec::new(),
+    }
+}
+
+/// Whether a tool failed because of the encryption: a missing or wrong
+/// password, or a document that forbids copying text.
+pub(crate) fn is_encryption_failure(err: &SandboxError) -> bool {
+    match err {
+        SandboxError::Failed { stderr, .. } => {
+            stderr.contains("Incorrect password") || stderr.contains("Copying of text")
+        }
+        _ => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn recognizes_password_failures() {
+        let failed = |stderr: &str| SandboxError::Failed {
+            tool: "pdfinfo",
+            code: 1,
+            stderr: stderr.to_string(),
+        };
+        assert!(is_encryption_failure(&failed(
+            "Command Line Error: Incorrect password\n"
+        )));
+        assert!(is_encryption_failure(&failed(
+            "Permission Error: Copying of text from this document is not allowed.\n"
+        )));
+        assert!(!is_encryption_failure(&failed(
+            "Syntax Error: Couldn't find trailer dictionary\n"
+        )));
+        assert!(format!("{:?}", PdfPassword::new("geheim")).ends_with("(..)"));
+        assert_eq!(password_args(None), Vec::<&str>::new());
+    }
+}
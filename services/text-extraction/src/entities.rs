@@ -0,0 +1,195 @@
+//! Optional entity pass after extraction, filling `pdf_entities`.
+//!
+//! Runs the patterns of [`shared::entities`] over every page and, with
+//! `ENTITY_MODEL_URL`, asks an NER model for further names and addresses. The
+//! model receives `{"page_no", "text"}` and answers
+//! `{"entities": [{"kind": "name", "text": "..."}]}`; unknown kinds are ignored.
+
+use std::{collections::HashSet, env, time::Duration};
+
+use anyhow::{Context, Result};
+use serde::Deserialize;
+use serde_json::json;
+use shared::entities::{self, EntityKind, PdfEntity};
+use tracing::warn;
+
+use crate::{PageExtraction, PageLayout};
+
+#[derive(Clone, Debug)]
+/// Configuration of the entity pass (`ENTITY_INDEX`, `ENTITY_MODEL_URL`).
+pub struct EntityOptions {
+    pub enabled: bool,
+    pub model_url: Option<String>,
+}
+
+impl EntityOptions {
+    pub fn from_env() -> Self {
+        Self {
+            enabled: env::var("ENTITY_INDEX")
+                .map(|v| v == "1" || v.eq_ignore_ascii_case("true"))
+                .unwrap_or(false),
+            model_url: env::var("ENTITY_MODEL_URL")
+                .ok()
+                .filter(|v| !v.trim().is_empty()),
+        }
+    }
+}
+
+#[derive(Deserialize)]
+struct ModelResponse {
+    #[serde(default)]
+    entities: Vec<ModelEntity>,
+}
+
+#[derive(Deserialize)]
+struct ModelEntity {
+    #[serde(alias = "label")]
+    kind: String,
+    text: String,
+}
+
+/// Builds the entity index of the given pages of `pdf_id`.
+pub async fn index_pages(
+    pdf_id: i32,
+    pages: &[PageExtraction],
+    options: &EntityOptions,
+) -> Vec<PdfEntity> {
+    let client = options.model_url.as_ref().and_then(|_| {
+        reqwest::Client::builder()
+            .timeout(Duration::from_secs(30))
+            .build()
+            .map_err(|e| warn!(error = %e, "entity model client unavailable"))
+            .ok()
+    });
+
+    let mut out = Vec::new();
+    for page in pages {
+        let mut seen = HashSet::new();
+        for m in entities::extract(&page.text) {
+            if seen.insert((m.kind, m.normalized.clone())) {
+                out.push(PdfEntity {
+                    merged_pdf_id: pdf_id,
+                    page_no: page.page_no,
+                    kind: m.kind,
+                    bbox: page.layout.as_ref().and_then(|l| locate(l, &m.value)),
+                    value: m.value,
+                    normalized: m.normalized,
+                    source: "regex".to_string(),
+                });
+            }
+        }
+
+        let (Some(client), Some(url)) = (client.as_ref(), options.model_url.as_ref()) else {
+            continue;
+        };
+        match query_model(client, url, page).await {
+            Ok(found) => {
+                for (kind, value) in found {
+                    let normalized = entities::normalize(kind, &value);
+                    if seen.insert((kind, normalized.clone())) {
+                        out.push(PdfEntity {
+                            merged_pdf_id: pdf_id,
+                            page_no: page.page_no,
+                            kind,
+                            bbox: page.layout.as_ref().and_then(|l| locate(l, &value)),
+                            value,
+                            normalized,
+                            source: "model".to_string(),
+                        });
+                    }
+                }
+            }
+            Err(e) => warn!(page = page.page_no, error = %e, "entity model failed"),
+        }
+    }
+    out
+}
+
+async fn query_model(
+    client: &reqwest::Client,
+    url: &str,
+    page: &PageExtraction,
+) -> Result<Vec<(EntityKind, String)>> {
+    let response: ModelResponse = client
+        .post(url)
+        .json(&json!({ "page_no": page.page_no, "text": page.text }))
+        .send()
+        .await
+        .context("send entity request")?
+        .error_for_status()
+        .context("entity model status")?
+        .json()
+        .await
+        .context("parse entity response")?;
+    Ok(response
+        .entities
+        .into_iter()
+        .filter_map(|e| {
+            let value = e.text.trim().to_string();
+            EntityKind::parse(&e.kind)
+                .filter(|_| !value.is_empty())
+                .map(|kind| (kind, value))
+        })
+        .collect())
+}
+
+/// Bounding box around the first run of layout words spelling `value`.
+fn locate(layout: &PageLayout, value: &str) -> Option<[i32; 4]> {
+    let clean = |s: &str| {
+        s.trim_matches(|c: char| !c.is_alphanumeric())
+            .to_lowercase()
+    };
+    let tokens: Vec<String> = value
+        .split_whitespace()
+        .map(clean)
+        .filter(|t| !t.is_empty())
+        .collect();
+    if tokens.is_empty() || tokens.len() > layout.words.len() {
+        return None;
+    }
+    let words: Vec<String> = layout
+        .words
+        .iter()
+        .map(|w| clean(w.text.as_str()))
+        .collect();
+    let start = (0..=words.len() - tokens.len())
+        .find(|&i| tokens.iter().enumerate().all(|(j, t)| words[i + j] == *t))?;
+    let hit = &layout.words[start..start + tokens.len()];
+    Some(hit.iter().skip(1).fold(hit[0].bbox, |acc, w| {
+        [
+            acc[0].min(w.bbox[0]),
+            acc[1].min(w.bbox[1]),
+            acc[2].max(w.bbox[2]),
+            acc[3].max(w.bbox[3]),
+        ]
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Word;
+
+    #[test]
+    fn locate_joins_word_boxes() {
+        let word = |text: &str, x: i32| Word {
+            bbox: [x, 10, x + 40, 30],
+            text: text.to_string(),
+            confidence: None,
+        };
+        let layout = PageLayout {
+            page_no: 0,
+            page_width: 600,
+            page_height: 800,
+            rotation: 0,
+            words: vec![
+                word("IBAN:", 0),
+                word("DE89", 50),
+                word("3704", 100),
+                word("0044", 150),
+            ],
+        };
+        assert_eq!(locate(&layout, "DE89 3704 0044"), Some([50, 10, 190, 30]));
+        assert_eq!(locate(&layout, "Max Mustermann"), None);
+    }
+}
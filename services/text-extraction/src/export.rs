@@ -0,0 +1,382 @@
+//! Standard OCR interchange formats for stored extractions.
+//!
+//! [`hocr`] and [`alto`] turn the pages of a document into one hOCR 1.2
+//! (XHTML) or ALTO 4 XML document. Coordinates are those of the stored
+//! [`PageLayout`]: PDF points, origin top-left of the displayed page (see
+//! [`crate::geometry`]); ALTO declares them as `pixel` at 72 dpi. Lines are
+//! rebuilt from the word order and boxes, pages without layout export their
+//! text lines without boxes.
+
+use std::fmt::Write;
+use std::str::FromStr;
+
+use html_escape::{encode_double_quoted_attribute, encode_text};
+use serde_json::{json, Value};
+
+use crate::{PageExtraction, Word};
+
+/// Output of `GET /uploads/{id}/extract?format=`.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum ExportFormat {
+    /// Page texts joined by newlines (the default).
+    #[default]
+    Text,
+    /// Pages with text, OCR details and layout as JSON.
+    Json,
+    Hocr,
+    Alto,
+}
+
+impl ExportFormat {
+    pub fn content_type(self) -> &'static str {
+        match self {
+            ExportFormat::Text => "text/plain; charset=utf-8",
+            ExportFormat::Json => "application/json",
+            ExportFormat::Hocr => "application/xhtml+xml; charset=utf-8",
+            ExportFormat::Alto => "application/xml; charset=utf-8",
+        }
+    }
+
+    /// Renders the pages, sorted by page number by the caller.
+    pub fn render(self, pages: &[PageExtraction]) -> String {
+        match self {
+            ExportFormat::Text => pages
+                .iter()
+                .map(|p| p.text.as_str())
+                .collect::<Vec<_>>()
+                .join("\n"),
+            ExportFormat::Json => Value::Array(pages.iter().map(page_json).collect()).to_string(),
+            ExportFormat::Hocr => hocr(pages),
+            ExportFormat::Alto => alto(pages),
+        }
+    }
+}
+
+impl FromStr for ExportFormat {
+    type Err = String;
+
+    fn from_str(raw: &str) -> Result<Self, Self::Err> {
+        match raw.trim().to_ascii_lowercase().as_str() {
+            "" | "text" | "txt" => Ok(ExportFormat::Text),
+            "json" => Ok(ExportFormat::Json),
+            "hocr" => Ok(ExportFormat::Hocr),
+            "alto" => Ok(ExportFormat::Alto),
+            other => Err(format!(
+                "unknown format '{other}', expected text, json, hocr or alto"
+            )),
+        }
+    }
+}
+
+fn page_json(page: &PageExtraction) -> Value {
+    json!({
+        "page_no": page.page_no,
+        "text": page.text,
+        "ocr_used": page.ocr_used,
+        "ocr_strategy": page.ocr_strategy,
+        "ocr_preprocessing": page.ocr_preprocessing,
+        "quality_score": page.quality_score,
+        "lang": page.lang,
+        "layout": page.layout,
+    })
+}
+
+/// One text line of a page: its words and their enclosing box.
+struct Line<'a> {
+    bbox: [i32; 4],
+    words: Vec<&'a Word>,
+}
+
+/// Groups the words into lines: a word starts a new line when it lies left of
+/// its predecessor or its vertical centre is outside the current line.
+fn lines(words: &[Word]) -> Vec<Line<'_>> {
+    let mut lines: Vec<Line> = Vec::new();
+    for word in words {
+        let centre = (word.bbox[1] + word.bbox[3]) / 2;
+        match lines.last_mut() {
+            Some(line)
+                if centre >= line.bbox[1]
+                    && centre <= line.bbox[3]
+                    && line.words.last().is_some_and(|w| word.bbox[0] >= w.bbox[0]) =>
+            {
+                line.bbox = [
+                    line.bbox[0].min(word.bbox[0]),
+                    line.bbox[1].min(word.bbox[1]),
+                    line.bbox[2].max(word.bbox[2]),
+                    line.bbox[3].max(word.bbox[3]),
+                ];
+                line.words.push(word);
+            }
+            _ => lines.push(Line {
+                bbox: word.bbox,
+                words: vec![word],
+            }),
+        }
+    }
+    lines
+}
+
+/// Size of the page in layout coordinates, `None` without layout.
+fn page_size(page: &PageExtraction) -> Option<(i32, i32)> {
+    page.layout
+        .as_ref()
+        .map(|l| (l.page_width, l.page_height))
+        .filter(|(w, h)| *w > 0 && *h > 0)
+}
+
+fn text_lines(text: &str) -> impl Iterator<Item = &str> {
+    text.lines().map(str::trim).filter(|l| !l.is_empty())
+}
+
+/// Renders the pages as one hOCR document (`ocr_page` → `ocr_line` →
+/// `ocrx_word`, `x_wconf` for OCR words).
+pub fn hocr(pages: &[PageExtraction]) -> String {
+    let mut out = String::from(concat!(
+        "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n",
+        "<!DOCTYPE html PUBLIC \"-//W3C//DTD XHTML 1.0 Transitional//EN\" ",
+        "\"http://www.w3.org/TR/xhtml1/DTD/xhtml1-transitional.dtd\">\n",
+        "<html xmlns=\"http://www.w3.org/1999/xhtml\" xml:lang=\"en\" lang=\"en\">\n",
+        "<head>\n<title></title>\n",
+        "<meta http-equiv=\"Content-Type\" content=\"text/html; charset=utf-8\" />\n",
+        "<meta name=\"ocr-system\" content=\"regress text-extraction\" />\n",
+        "<meta name=\"ocr-capabilities\" content=\"ocr_page ocr_line ocrx_word\" />\n",
+        "</head>\n<body>\n",
+    ));
+    for page in pages {
+        let n = page.page_no;
+        let mut title = format!("ppageno {n}");
+        if let Some((w, h)) = page_size(page) {
+            title = format!("bbox 0 0 {w} {h}; {title}");
+        }
+        let _ = write!(
+            out,
+            "<div class=\"ocr_page\" id=\"page_{n}\" title=\"{title}\""
+        );
+        if let Some(lang) = &page.lang {
+            let _ = write!(out, " lang=\"{}\"", encode_double_quoted_attribute(lang));
+        }
+        out.push_str(">\n");
+        match page.layout.as_ref().filter(|l| !l.words.is_empty()) {
+            Some(layout) => {
+                let mut word_no = 0;
+                for (line_no, line) in lines(&layout.words).iter().enumerate() {
+                    let [x0, y0, x1, y1] = line.bbox;
+                    let _ = writeln!(
+                        out,
+                        "<span class=\"ocr_line\" id=\"line_{n}_{line_no}\" title=\"bbox {x0} {y0} {x1} {y1}\">"
+                    );
+                    for word in &line.words {
+                        word_no += 1;
+                        let [x0, y0, x1, y1] = word.bbox;
+                        let mut title = format!("bbox {x0} {y0} {x1} {y1}");
+                        if let Some(conf) = word.confidence {
+                            let _ = write!(title, "; x_wconf {}", (conf * 100.0).round() as i32);
+                        }
+                        let _ = writeln!(
+                            out,
+                            "<span class=\"ocrx_word\" id=\"word_{n}_{word_no}\" title=\"{title}\">{}</span>",
+                            encode_text(&word.text)
+                        );
+                    }
+                    out.push_str("</span>\n");
+                }
+            }
+            None => {
+                for (line_no, text) in text_lines(&page.text).enumerate() {
+                    let _ = writeln!(
+                        out,
+                        "<span class=\"ocr_line\" id=\"line_{n}_{line_no}\">{}</span>",
+                        encode_text(text)
+                    );
+                }
+            }
+        }
+        out.push_str("</div>\n");
+    }
+    out.push_str("</body>\n</html>\n");
+    out
+}
+
+/// Renders the pages as one ALTO 4 document (one `TextBlock` per page,
+/// `WC` for OCR words).
+pub fn alto(pages: &[PageExtraction]) -> String {
+    let mut out = String::from(concat!(
+        "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n",
+        "<alto xmlns=\"http://www.loc.gov/standards/alto/ns-v4#\" ",
+        "xmlns:xsi=\"http://www.w3.org/2001/XMLSchema-instance\" ",
+        "xsi:schemaLocation=\"http://www.loc.gov/standards/alto/ns-v4# ",
+        "http://www.loc.gov/standards/alto/v4/alto-4-2.xsd\">\n",
+        "<Description>\n<MeasurementUnit>pixel</MeasurementUnit>\n</Description>\n",
+        "<Layout>\n",
+    ));
+    for page in pages {
+        let n = page.page_no;
+        let _ = write!(out, "<Page ID=\"page_{n}\" PHYSICAL_IMG_NR=\"{}\"", n + 1);
+        let size = page_size(page);
+        if let Some((w, h)) = size {
+            let _ = write!(out, " WIDTH=\"{w}\" HEIGHT=\"{h}\"");
+        }
+        out.push_str(">\n");
+        match size {
+            Some((w, h)) => {
+                let _ = writeln!(
+                    out,
+                    "<PrintSpace HPOS=\"0\" VPOS=\"0\" WIDTH=\"{w}\" HEIGHT=\"{h}\">"
+                );
+            }
+            None => out.push_str("<PrintSpace>\n"),
+        }
+        let _ = write!(out, "<TextBlock ID=\"block_{n}\"");
+        if let Some(lang) = &page.lang {
+            let _ = write!(out, " LANG=\"{}\"", encode_double_quoted_attribute(lang));
+        }
+        out.push_str(">\n");
+        match page.layout.as_ref().filter(|l| !l.words.is_empty()) {
+            Some(layout) => {
+                let mut word_no = 0;
+                for (line_no, line) in lines(&layout.words).iter().enumerate() {
+                    let _ = writeln!(
+                        out,
+                        "<TextLine ID=\"line_{n}_{line_no}\"{}>",
+                        position(line.bbox)
+                    );
+                    for (i, word) in line.words.iter().enumerate() {
+                        if i > 0 {
+                            out.push_str("<SP/>\n");
+                        }
+                        word_no += 1;
+                        let _ = write!(
+                            out,
+                            "<String ID=\"string_{n}_{word_no}\"{} CONTENT=\"{}\"",
+                            position(word.bbox),
+                            encode_double_quoted_attribute(&word.text)
+                        );
+                        if let Some(conf) = word.confidence {
+                            let _ = write!(out, " WC=\"{:.2}\"", conf.clamp(0.0, 1.0));
+                        }
+                        out.push_str("/>\n");
+                    }
+                    out.push_str("</TextLine>\n");
+                }
+            }
+            None => {
+                for (line_no, text) in text_lines(&page.text).enumerate() {
+                    let _ = writeln!(out, "<TextLine ID=\"line_{n}_{line_no}\">");
+                    for (i, word) in text.split_whitespace().enumerate() {
+                        if i > 0 {
+                            out.push_str("<SP/>\n");
+                        }
+                        let _ = writeln!(
+                            out,
+                            "<String CONTENT=\"{}\"/>",
+                            encode_double_quoted_attribute(word)
+                        );
+                    }
+                    out.push_str("</TextLine>\n");
+                }
+            }
+        }
+        out.push_str("</TextBlock>\n</PrintSpace>\n</Page>\n");
+    }
+    out.push_str("</Layout>\n</alto>\n");
+    out
+}
+
+/// `HPOS`/`VPOS`/`WIDTH`/`HEIGHT` attributes of a box.
+fn position([x0, y0, x1, y1]: [i32; 4]) -> String {
+    format!(
+        " HPOS=\"{x0}\" VPOS=\"{y0}\" WIDTH=\"{}\" HEIGHT=\"{}\"",
+        (x1 - x0).max(0),
+        (y1 - y0).max(0)
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::PageLayout;
+
+    fn word(bbox: [i32; 4], text: &str, confidence: Option<f32>) -> Word {
+        Word {
+            bbox,
+            text: text.into(),
+            confidence,
+        }
+    }
+
+    fn page(page_no: i32, words: Vec<Word>) -> PageExtraction {
+        PageExtraction {
+            page_no,
+            text: words
+                .iter()
+                .map(|w| w.text.as_str())
+                .collect::<Vec<_>>()
+                .join(" "),
+            ocr_used: true,
+            layout: Some(PageLayout {
+                page_no,
+                page_width: 595,
+                page_height: 842,
+                rotation: 0,
+                words,
+            }),
+            ocr_strategy: None,
+            quality_score: None,
+            lang: Some("deu".into()),
+            ocr_preprocessing: None,
+            image: None,
+        }
+    }
+
+    #[test]
+    fn groups_words_into_lines() {
+        let words = vec![
+            word([10, 20, 60, 32], "Hello", None),
+            word([70, 21, 120, 33], "World", None),
+            word([10, 40, 60, 52], "next", None),
+            word([70, 40, 90, 52], "line", None),
+        ];
+        let lines = lines(&words);
+        assert_eq!(lines.len(), 2);
+        assert_eq!(lines[0].bbox, [10, 20, 120, 33]);
+        assert_eq!(lines[1].words.len(), 2);
+    }
+
+    #[test]
+    fn hocr_round_trips_through_the_parser() {
+        let pages = vec![page(
+            0,
+            vec![
+                word([10, 20, 60, 32], "A&B", Some(0.95)),
+                word([70, 20, 120, 32], "<x>", None),
+            ],
+        )];
+        let doc = hocr(&pages);
+        let layout = crate::parse_hocr_layout(0, &doc).unwrap();
+        assert_eq!((layout.page_width, layout.page_height), (595, 842));
+        let texts: Vec<&str> = layout.words.iter().map(|w| w.text.as_str()).collect();
+        assert_eq!(texts, vec!["A&B", "<x>"]);
+        assert_eq!(layout.words[0].confidence, Some(0.95));
+        assert_eq!(layout.words[1].confidence, None);
+    }
+
+    #[test]
+    fn alto_lists_strings_with_positions() {
+        let mut plain = page(1, vec![]);
+        plain.layout = None;
+        plain.text = "first \"line\"\n\nsecond".into();
+        let pages = vec![
+            page(0, vec![word([10, 20, 60, 32], "Hello", Some(0.5))]),
+            plain,
+        ];
+        let doc = alto(&pages);
+        assert!(doc.contains(
+            "<String ID=\"string_0_1\" HPOS=\"10\" VPOS=\"20\" WIDTH=\"50\" HEIGHT=\"12\" CONTENT=\"Hello\" WC=\"0.50\"/>"
+        ));
+        assert!(doc.contains("<Page ID=\"page_1\" PHYSICAL_IMG_NR=\"2\">"));
+        assert!(doc.contains("<String CONTENT=\"&quot;line&quot;\"/>"));
+        assert_eq!(doc.matches("<TextLine").count(), 3);
+        assert_eq!("ALTO".parse::<ExportFormat>(), Ok(ExportFormat::Alto));
+        assert!("pdf".parse::<ExportFormat>().is_err());
+    }
+}
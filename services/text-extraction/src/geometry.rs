@@ -0,0 +1,190 @@
+//! Page geometry and the coordinate system of stored layouts.
+//!
+//! Every [`PageLayout`] is stored in PDF points with the origin in the top-left
+//! corner of the page *as displayed*, i.e. after applying its `/Rotate`. That
+//! is the orientation `pdftoppm` renders and viewers show, so bbox overlays
+//! line up on mixed A4/Letter documents and rotated scans alike. hOCR pixels
+//! are scaled down from the OCR resolution; `pdftotext -bbox` already reports
+//! rotated points, but the page size it prints is the unrotated media box, so
+//! the size is taken from `pdfinfo` instead.
+
+use anyhow::{anyhow, Context, Result};
+
+use crate::encryption::{self, PdfPassword};
+use crate::metadata;
+use crate::sandbox::{self, Tool};
+use crate::PageLayout;
+
+/// Crop box size (unrotated, in points) and rotation of one page.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct PageGeometry {
+    pub width: f64,
+    pub height: f64,
+    /// Clockwise rotation in degrees: 0, 90, 180 or 270.
+    pub rotation: i32,
+}
+
+impl PageGeometry {
+    /// Size of the page as displayed, with width and height swapped for 90°/270°.
+    pub fn display_size(&self) -> (f64, f64) {
+        if self.rotation % 180 == 0 {
+            (self.width, self.height)
+        } else {
+            (self.height, self.width)
+        }
+    }
+
+    /// Takes the 1-based `page` from the page sizes of `pdfinfo -f N -l N`.
+    pub fn parse_pdfinfo(output: &str, page: i32) -> Option<Self> {
+        metadata::parse_pdfinfo(output)
+            .page_sizes
+            .into_iter()
+            .find(|size| size.page_no == page - 1)
+            .map(|size| Self {
+                width: size.width,
+                height: size.height,
+                rotation: size.rotation,
+            })
+    }
+}
+
+/// Geometry of the 1-based `page` via `pdfinfo`.
+pub async fn page_geometry(
+    path: &str,
+    page: i32,
+    password: Option<&PdfPassword>,
+) -> Result<PageGeometry> {
+    let page_arg = page.to_string();
+    let mut args = encryption::password_args(password);
+    args.extend(["-f", &page_arg, "-l", &page_arg, path]);
+    let output = sandbox::run(Tool::Pdfinfo, args)
+        .await
+        .with_context(|| format!("pdfinfo on page {page}"))?;
+    PageGeometry::parse_pdfinfo(&String::from_utf8_lossy(&output.stdout), page)
+        .ok_or_else(|| anyhow!("pdfinfo without size of page {page}"))
+}
+
+impl PageLayout {
+    /// Scales the layout by `scale` (source units per point, e.g. `72 / dpi`
+    /// for hOCR) and, with `geometry`, takes size and rotation of the page.
+    /// Word boxes are clamped to the page.
+    pub fn normalize(mut self, scale: f64, geometry: Option<PageGeometry>) -> Self {
+        let to_points = |v: i32| (v as f64 * scale).round() as i32;
+        let (width, height) = match geometry {
+            Some(geometry) => {
+                self.rotation = geometry.rotation;
+                let (w, h) = geometry.display_size();
+                (w.round() as i32, h.round() as i32)
+            }
+            None => (to_points(self.page_width), to_points(self.page_height)),
+        };
+        self.page_width = width;
+        self.page_height = height;
+        for word in &mut self.words {
+            let [x0, y0, x1, y1] = word.bbox.map(to_points);
+            let clamp_x = |v: i32| if width > 0 { v.clamp(0, width) } else { v };
+            let clamp_y = |v: i32| if height > 0 { v.clamp(0, height) } else { v };
+            word.bbox = [
+                clamp_x(x0.min(x1)),
+                clamp_y(y0.min(y1)),
+                clamp_x(x0.max(x1)),
+                clamp_y(y0.max(y1)),
+            ];
+        }
+        self
+    }
+
+    /// Maps hOCR boxes of an image that was rotated clockwise by `rotation`
+    /// before recognition back onto the unrotated render.
+    pub fn unrotate(mut self, rotation: i32) -> Self {
+        let (w, h) = (self.page_width, self.page_height);
+        let rotation = rotation.rem_euclid(360);
+        for word in &mut self.words {
+            let [x0, y0, x1, y1] = word.bbox;
+            word.bbox = match rotation {
+                90 => [y0, w - x1, y1, w - x0],
+                180 => [w - x1, h - y1, w - x0, h - y0],
+                270 => [h - y1, x0, h - y0, x1],
+                _ => word.bbox,
+            };
+        }
+        if rotation == 90 || rotation == 270 {
+            self.page_width = h;
+            self.page_height = w;
+        }
+        self
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Word;
+
+    const PDFINFO: &str = "Producer:       scanner\n\
+        Pages:          3\n\
+        Page    2 size: 595.276 x 841.89 pts (A4)\n\
+        Page    2 rot:  90\n\
+        File size:      1234 bytes\n";
+
+    #[test]
+    fn parses_rotated_page() {
+        let geometry = PageGeometry::parse_pdfinfo(PDFINFO, 2).unwrap();
+        assert_eq!(geometry.rotation, 90);
+        assert_eq!(geometry.display_size(), (841.89, 595.276));
+        assert!(PageGeometry::parse_pdfinfo(PDFINFO, 1).is_none());
+    }
+
+    #[test]
+    fn normalizes_hocr_pixels_to_rotated_points() {
+        // Querformatiger A4-Scan mit 300 dpi: 3508 x 2480 Pixel
+        let layout = PageLayout {
+            page_no: 1,
+            page_width: 3508,
+            page_height: 2480,
+            rotation: 0,
+            words: vec![
+                Word {
+                    bbox: [300, 600, 900, 700],
+                    text: "Kaufvertrag".to_string(),
+                    confidence: Some(0.9),
+                },
+                Word {
+                    bbox: [3400, 2400, 3600, 2500],
+                    text: "Rand".to_string(),
+                    confidence: None,
+                },
+            ],
+        };
+        let geometry = PageGeometry::parse_pdfinfo(PDFINFO, 2);
+        let layout = layout.normalize(72.0 / 300.0, geometry);
+        assert_eq!((layout.page_width, layout.page_height), (842, 595));
+        assert_eq!(layout.rotation, 90);
+        assert_eq!(layout.words[0].bbox, [72, 144, 216, 168]);
+        assert_eq!(layout.words[1].bbox, [816, 576, 842, 595]);
+    }
+
+    #[test]
+    fn unrotate_maps_boxes_back() {
+        // Bild 100 x 200, vor der OCR um 90° gedreht → 200 x 100
+        let layout = |bbox| PageLayout {
+            page_no: 0,
+            page_width: 200,
+            page_height: 100,
+            rotation: 0,
+            words: vec![Word {
+                bbox,
+                text: "a".to_string(),
+                confidence: None,
+            }],
+        };
+        // Punkt (10, 20) im Original liegt gedreht bei (200 - 20, 10)
+        let back = layout([170, 10, 180, 15]).unrotate(90);
+        assert_eq!((back.page_width, back.page_height), (100, 200));
+        assert_eq!(back.words[0].bbox, [10, 20, 15, 30]);
+        let turned = layout([170, 10, 180, 15]).unrotate(270).unrotate(90);
+        assert_eq!(turned.words[0].bbox, [170, 10, 180, 15]);
+        let upside = layout([0, 0, 10, 10]).unrotate(180);
+        assert_eq!(upside.words[0].bbox, [190, 90, 200, 100]);
+    }
+}
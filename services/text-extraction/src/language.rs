@@ -0,0 +1,77 @@
+//! Language detection per page.
+//!
+//! `OCR_LANG` is the Tesseract language set for pages whose language is
+//! unknown. With `OCR_LANG_DETECT` (e.g. `deu,eng,fra,ita`) the text of every
+//! page — the text layer or, for scans, the first OCR pass — is classified with
+//! `whatlang`, restricted to these languages. Scanned pages in a language
+//! outside `OCR_LANG` are recognized again with the detected one. Codes are
+//! ISO 639-3, which matches the Tesseract traineddata names of Latin-script
+//! languages (`deu`, `fra`, `nld`, `pol`, ...).
+
+use whatlang::{Detector, Lang};
+
+/// Letters a text needs before its language is classified.
+const MIN_LETTERS: usize = 40;
+
+/// Language of `text` among `candidates`; `None` for short or ambiguous text.
+pub fn detect(text: &str, candidates: &[String]) -> Option<String> {
+    let allowed: Vec<Lang> = candidates
+        .iter()
+        .filter_map(|code| Lang::from_code(code.trim()))
+        .collect();
+    if allowed.is_empty() || text.chars().filter(|c| c.is_alphabetic()).count() < MIN_LETTERS {
+        return None;
+    }
+    if let [only] = allowed.as_slice() {
+        return Some(only.code().to_string());
+    }
+    Detector::with_allowlist(allowed)
+        .detect(text)
+        .filter(|info| info.is_reliable())
+        .map(|info| info.lang().code().to_string())
+}
+
+/// Whether the Tesseract language set `set` (e.g. `deu+eng`) contains `lang`.
+pub fn covers(set: &str, lang: &str) -> bool {
+    set.split('+').any(|l| l.trim() == lang)
+}
+
+/// Parses `OCR_LANG_DETECT`: comma separated codes, unknown ones are skipped.
+pub(crate) fn parse_candidates(raw: &str) -> Vec<String> {
+    raw.split(',')
+        .map(|code| code.trim().to_ascii_lowercase())
+        .filter(|code| Lang::from_code(code).is_some())
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn candidates() -> Vec<String> {
+        parse_candidates("deu, eng,fra,xx")
+    }
+
+    #[test]
+    fn parse_skips_unknown_codes() {
+        assert_eq!(candidates(), vec!["deu", "eng", "fra"]);
+    }
+
+    #[test]
+    fn detects_page_language() {
+        let french = "Le présent contrat de location est conclu entre le bailleur et le \
+                      locataire pour une durée de trois ans à compter de la signature.";
+        let german = "Der Mieter verpflichtet sich, die Wohnung pfleglich zu behandeln \
+                      und die Miete jeweils zum dritten Werktag eines Monats zu zahlen.";
+        assert_eq!(detect(french, &candidates()).as_deref(), Some("fra"));
+        assert_eq!(detect(german, &candidates()).as_deref(), Some("deu"));
+        assert_eq!(detect("Seite 3 von 12", &candidates()), None);
+        assert_eq!(detect(german, &[]), None);
+    }
+
+    #[test]
+    fn covers_language_sets() {
+        assert!(covers("deu+eng", "eng"));
+        assert!(!covers("deu+eng", "fra"));
+    }
+}
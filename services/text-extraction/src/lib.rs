@@ -1,19 +1,58 @@
 //! Text extraction helpers combining `pdftotext` and optional OCR.
 
-use std::{env, sync::Arc, time::Duration};
+use std::{borrow::Borrow, env, future::Future, path::Path};
 
 use anyhow::{anyhow, Context, Result};
+use futures_util::{stream, Stream, TryStreamExt};
 use html_escape::decode_html_entities;
 use once_cell::sync::Lazy;
 use quick_xml::events::Event;
 use quick_xml::Reader;
 use regex::Regex;
-use serde::Serialize;
-use tokio::{process::Command, sync::Semaphore, task::JoinSet, time::timeout};
+use serde::{Deserialize, Serialize};
+use shared::dto::RunPriority;
+use thiserror::Error;
+use tokio::task::JoinSet;
 use tracing::{info, warn};
 use uuid::Uuid;
 
-const PROCESS_TIMEOUT: Duration = Duration::from_secs(60);
+pub mod attachments;
+pub mod cache;
+pub mod config;
+pub mod encryption;
+pub mod entities;
+pub mod export;
+pub mod geometry;
+pub mod language;
+pub mod metadata;
+pub mod ocr;
+pub mod page_images;
+pub mod preprocess;
+pub mod quality;
+pub mod retries;
+pub mod sandbox;
+pub mod scheduler;
+pub mod searchable;
+pub mod temp;
+
+use cache::CacheKey;
+pub use config::{ExtractionConfig, LayoutBackend, OcrStrategy, TextLayerPolicy};
+pub use encryption::PdfPassword;
+use ocr::{OcrOutput, OcrRequest};
+use sandbox::Tool;
+use scheduler::{
+    DocumentHandle, DocumentTicket, PageScheduler, PageSlot, SchedulingMode, TextSlot,
+};
+pub use temp::TempPdf;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Error)]
+/// Failures callers react to specifically; they travel inside the
+/// [`anyhow::Error`] of the extraction functions (`err.downcast_ref()`).
+pub enum ExtractionError {
+    /// The PDF cannot be read without its password (see [`encryption`]).
+    #[error("pdf is encrypted, {}", if *.password_given { "the password was not accepted" } else { "a password is required" })]
+    Encrypted { password_given: bool },
+}
 
 /// Complete extract via `pdftotext` for the whole PDF.
 /// Uses `-layout` when `PDFTEXT_LAYOUT` is not set to "0".
@@ -43,86 +82,55 @@ pub struct PageExtraction {
     pub text: String,
     pub ocr_used: bool,
     pub layout: Option<PageLayout>,
+    /// Winning strategy, `pdftotext` or e.g. `psm6@300dpi` for OCR.
+    pub ocr_strategy: Option<String>,
+    /// Dictionary score of the stored text (see [`quality::dictionary_score`]).
+    pub quality_score: Option<f64>,
+    /// Detected language (ISO 639-3, see [`language`]); `None` without
+    /// `ocr_lang_detect` or for too little text.
+    pub lang: Option<String>,
+    /// Image cleanup applied before the winning OCR attempt, e.g.
+    /// `threshold,deskew:-1.4` (see [`preprocess`]); `None` without OCR or steps.
+    pub ocr_preprocessing: Option<String>,
+    /// Downscaled render of the page kept from OCR (see [`page_images`]);
+    /// `None` without OCR or without [`ExtractionConfig::page_images`].
+    pub image: Option<page_images::PageImage>,
 }
 
-#[derive(Clone, Debug, Serialize)]
+#[derive(Clone, Debug, Serialize, Deserialize)]
 /// Layout information describing bounding boxes for extracted words.
+///
+/// Sizes and boxes are PDF points, origin top-left of the displayed (rotated)
+/// page, see [`geometry`].
 pub struct PageLayout {
     pub page_no: i32,
     pub page_width: i32,
     pub page_height: i32,
+    /// Clockwise `/Rotate` of the page in degrees, already applied to the boxes.
+    pub rotation: i32,
     pub words: Vec<Word>,
 }
 
-#[derive(Clone, Debug, Serialize)]
+impl PageLayout {
+    /// Mean OCR confidence of the words, `None` for vector text.
+    pub fn mean_confidence(&self) -> Option<f32> {
+        let scores: Vec<f32> = self.words.iter().filter_map(|w| w.confidence).collect();
+        if scores.is_empty() {
+            return None;
+        }
+        Some(scores.iter().sum::<f32>() / scores.len() as f32)
+    }
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
 /// Single OCR word alongside its bounding box.
 pub struct Word {
     pub bbox: [i32; 4],
     pub text: String,
-}
-
-#[derive(Clone, Debug)]
-/// Configuration derived from environment variables controlling extraction.
-struct ExtractionOptions {
-    pdftext_layout: bool,
-    ocr_enabled: bool,
-    ocr_lang: String,
-    ocr_psm: String,
-    ocr_dpi: u32,
-    ocr_min_nonws: usize,
-    layout_enabled: bool,
-    layout_backend: LayoutBackend,
-    max_parallel_ocr: usize,
-}
-
-#[derive(Clone, Debug, Copy, PartialEq, Eq)]
-/// Available layout extraction strategies.
-enum LayoutBackend {
-    BBox,
-    PdfToHtml,
-}
-
-impl ExtractionOptions {
-    fn from_env() -> Self {
-        let pdftext_layout = env::var("PDFTEXT_LAYOUT").map(|v| v != "0").unwrap_or(true);
-        let ocr_enabled = env::var("OCR_ENABLED").map(|v| v != "0").unwrap_or(true);
-        let ocr_lang = env::var("OCR_LANG").unwrap_or_else(|_| "deu+eng".to_string());
-        let ocr_psm = env::var("OCR_PSM").unwrap_or_else(|_| "6".to_string());
-        let ocr_dpi = env::var("OCR_DPI")
-            .ok()
-            .and_then(|v| v.parse::<u32>().ok())
-            .unwrap_or(300);
-        let ocr_min_nonws = env::var("OCR_MIN_NONWS")
-            .ok()
-            .and_then(|v| v.parse::<usize>().ok())
-            .unwrap_or(24);
-        let layout_enabled = env::var("LAYOUT_ENABLED").map(|v| v != "0").unwrap_or(true);
-        let layout_backend = match env::var("LAYOUT_BACKEND")
-            .unwrap_or_else(|_| "bbox".to_string())
-            .to_ascii_lowercase()
-            .as_str()
-        {
-            "pdftohtml" => LayoutBackend::PdfToHtml,
-            _ => LayoutBackend::BBox,
-        };
-        let max_parallel_ocr = env::var("MAX_PARALLEL_OCR")
-            .ok()
-            .and_then(|v| v.parse::<usize>().ok())
-            .filter(|v| *v > 0)
-            .unwrap_or(2);
-
-        Self {
-            pdftext_layout,
-            ocr_enabled,
-            ocr_lang,
-            ocr_psm,
-            ocr_dpi,
-            ocr_min_nonws,
-            layout_enabled,
-            layout_backend,
-            max_parallel_ocr,
-        }
-    }
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    /// Tesseract word confidence (`x_wconf`) scaled to 0.0..=1.0; `None` for
+    /// words from the PDF text layer.
+    pub confidence: Option<f32>,
 }
 
 /// Determines if OCR should be executed for the provided text.
@@ -135,11 +143,6 @@ pub fn should_ocr(txt: &str) -> bool {
     count < min_nonws
 }
 
-struct OcrResult {
-    text: String,
-    hocr: Option<String>,
-}
-
 struct TempImageGuard {
     path: String,
 }
@@ -151,158 +154,690 @@ impl Drop for TempImageGuard {
 }
 
 /// Perform OCR on a page rendered via pdftoppm.
-pub async fn ocr_page(path: &str, page: i32) -> Result<String> {
-    let options = ExtractionOptions::from_env();
-    let res = perform_ocr(path, page, &options, false).await?;
-    Ok(res.text)
+pub async fn ocr_page(path: &str, page: i32, config: &ExtractionConfig) -> Result<String> {
+    let res = perform_ocr(path, page, config, false).await?;
+    Ok(res.output.text)
+}
+
+/// One OCR attempt on a page and how it was produced.
+struct OcrAttempt {
+    strategy: OcrStrategy,
+    /// Clockwise rotation applied to the rendered image before recognition.
+    rotation: i32,
+    output: OcrOutput,
+    /// Mean word confidence from the hOCR, when available.
+    confidence: Option<f32>,
+    non_ws: usize,
+    /// Applied [`preprocess`] steps.
+    preprocessing: Vec<String>,
 }
 
+impl OcrAttempt {
+    fn new(strategy: OcrStrategy, rotation: i32, (output, preprocessing): Recognized) -> Self {
+        let confidence = output
+            .hocr
+            .as_deref()
+            .and_then(|hocr| parse_hocr_layout(0, hocr).ok())
+            .and_then(|layout| layout.mean_confidence());
+        let non_ws = output.text.chars().filter(|c| !c.is_whitespace()).count();
+        Self {
+            strategy,
+            rotation,
+            output,
+            confidence,
+            non_ws,
+            preprocessing,
+        }
+    }
+
+    /// Enough text and, if known, a mean confidence above the threshold.
+    fn acceptable(&self, options: &ExtractionConfig) -> bool {
+        self.non_ws >= options.ocr_min_nonws
+            && self
+                .confidence
+                .is_none_or(|c| c >= options.ocr_min_confidence)
+    }
+
+    /// Confidence first, text length on ties or when no hOCR was produced.
+    fn better_than(&self, other: &OcrAttempt) -> bool {
+        let confidence = |a: &OcrAttempt| a.confidence.unwrap_or(0.0);
+        match confidence(self).partial_cmp(&confidence(other)) {
+            Some(std::cmp::Ordering::Equal) | None => self.non_ws > other.non_ws,
+            Some(ordering) => ordering.is_gt(),
+        }
+    }
+
+    /// Label stored in `pdf_texts.ocr_strategy`, e.g. `psm4@400dpi+rot90`.
+    fn label(&self) -> String {
+        match self.rotation {
+            0 => self.strategy.label(),
+            rotation => format!("{}+rot{rotation}", self.strategy.label()),
+        }
+    }
+}
+
+/// OCR with fallback ladder: the default strategy first; when its result is
+/// garbage (too little text or a mean confidence below `ocr_min_confidence`)
+/// the page orientation is detected via OSD and the strategies of
+/// `ocr_fallback_strategies` (other `--psm` values, higher DPI) are tried on the
+/// upright image. The attempt with the best confidence wins.
 async fn perform_ocr(
     path: &str,
     page: i32,
-    options: &ExtractionOptions,
+    options: &ExtractionConfig,
+    capture_layout: bool,
+) -> Result<OcrAttempt> {
+    let ladder = !options.ocr_fallback_strategies.is_empty();
+    // Die Konfidenz steht nur im hOCR, für die Leiter daher immer mit erfassen
+    let capture = capture_layout || ladder;
+    let default_strategy = options.default_strategy();
+    let first = ocr_attempt(path, page, options, &default_strategy, 0, capture).await?;
+    let first = OcrAttempt::new(default_strategy.clone(), 0, first);
+    if !ladder || first.acceptable(options) {
+        return Ok(finish_attempt(first, capture_layout));
+    }
+
+    let rotation = if options.ocr_detect_orientation {
+        match detect_rotation(path, page, options).await {
+            Ok(rotation) => rotation,
+            Err(err) => {
+                warn!(page = page - 1, error = %err, "orientation detection failed");
+                0
+            }
+        }
+    } else {
+        0
+    };
+    let mut candidates: Vec<OcrStrategy> = Vec::new();
+    if rotation != 0 {
+        candidates.push(default_strategy.clone());
+    }
+    candidates.extend(
+        options
+            .ocr_fallback_strategies
+            .iter()
+            .filter(|s| rotation != 0 || **s != default_strategy)
+            .cloned(),
+    );
+
+    let mut best = first;
+    for candidate in candidates {
+        match ocr_attempt(path, page, options, &candidate, rotation, capture).await {
+            Ok(output) => {
+                let attempt = OcrAttempt::new(candidate, rotation, output);
+                info!(
+                    page = page - 1,
+                    strategy = %attempt.label(),
+                    confidence = attempt.confidence,
+                    chars = attempt.non_ws,
+                    "ocr fallback attempt"
+                );
+                let done = attempt.acceptable(options);
+                if attempt.better_than(&best) {
+                    best = attempt;
+                }
+                if done {
+                    break;
+                }
+            }
+            Err(err) => {
+                warn!(page = page - 1, strategy = %candidate.label(), error = %err, "ocr fallback attempt failed");
+            }
+        }
+    }
+    Ok(finish_attempt(best, capture_layout))
+}
+
+/// [`perform_ocr`] in the language of the page. A language `known` from the
+/// text layer is used right away; otherwise the first result is classified and,
+/// if it is outside `ocr_lang`, recognized again in the detected language. The
+/// retry is kept when its confidence is higher. Returns the detected language.
+async fn perform_ocr_in_language(
+    path: &str,
+    page: i32,
+    options: &ExtractionConfig,
+    known: Option<String>,
     capture_layout: bool,
-) -> Result<OcrResult> {
+) -> Result<(OcrAttempt, Option<String>)> {
+    if let Some(lang) = known {
+        let attempt = if language::covers(&options.ocr_lang, &lang) {
+            perform_ocr(path, page, options, capture_layout).await?
+        } else {
+            let localized = options.clone().ocr_lang(lang.clone());
+            perform_ocr(path, page, &localized, capture_layout).await?
+        };
+        return Ok((attempt, Some(lang)));
+    }
+
+    let attempt = perform_ocr(path, page, options, capture_layout).await?;
+    let Some(lang) = language::detect(&attempt.output.text, &options.ocr_lang_detect) else {
+        return Ok((attempt, None));
+    };
+    if language::covers(&options.ocr_lang, &lang) {
+        return Ok((attempt, Some(lang)));
+    }
+
+    // Konfidenz steht nur im hOCR, daher für den Vergleich immer mit erfassen
+    let localized = options.clone().ocr_lang(lang.clone());
+    let retry = ocr_attempt(
+        path,
+        page,
+        &localized,
+        &attempt.strategy,
+        attempt.rotation,
+        true,
+    )
+    .await;
+    match retry {
+        Ok(output) => {
+            let retry = OcrAttempt::new(attempt.strategy.clone(), attempt.rotation, output);
+            info!(
+                page = page - 1,
+                lang = %lang,
+                confidence = retry.confidence,
+                previous = attempt.confidence,
+                "ocr repeated in detected language"
+            );
+            if retry.better_than(&attempt) {
+                return Ok((finish_attempt(retry, capture_layout), Some(lang)));
+            }
+        }
+        Err(err) => {
+            warn!(page = page - 1, lang = %lang, error = %err, "ocr in detected language failed");
+        }
+    }
+    Ok((attempt, Some(lang)))
+}
+
+fn finish_attempt(mut attempt: OcrAttempt, capture_layout: bool) -> OcrAttempt {
+    if !capture_layout {
+        attempt.output.hocr = None;
+    }
+    attempt
+}
+
+/// Renders the page at `strategy.dpi` and asks the engine for its orientation.
+async fn detect_rotation(path: &str, page: i32, options: &ExtractionConfig) -> Result<i32> {
+    let image = render_page(path, page, options.ocr_dpi, options.password.as_ref()).await?;
+    let rotation = options
+        .ocr_engine
+        .detect_orientation(Path::new(&image.path))
+        .await?
+        .unwrap_or(0);
+    if rotation != 0 {
+        info!(page = page - 1, rotation, "page orientation detected");
+    }
+    Ok(rotation)
+}
+
+/// Renders one page via pdftoppm into a temporary PNG.
+async fn render_page(
+    path: &str,
+    page: i32,
+    dpi: u32,
+    password: Option<&PdfPassword>,
+) -> Result<TempImageGuard> {
     let prefix = std::env::temp_dir().join(format!("ocr_page_{}_{}", page, Uuid::new_v4()));
     let prefix_str = prefix
         .to_str()
         .ok_or_else(|| anyhow!("prefix path invalid utf8"))?
         .to_string();
-    let png_path = format!("{prefix_str}.png");
-    let _guard = TempImageGuard {
-        path: png_path.clone(),
+    let guard = TempImageGuard {
+        path: format!("{prefix_str}.png"),
     };
 
-    let mut render_cmd = Command::new("pdftoppm");
-    render_cmd
-        .arg("-r")
-        .arg(options.ocr_dpi.to_string())
-        .arg("-f")
-        .arg(page.to_string())
-        .arg("-l")
-        .arg(page.to_string())
-        .arg("-png")
-        .arg("-singlefile")
-        .arg(path)
-        .arg(&prefix_str);
-
-    let render_output = timeout(PROCESS_TIMEOUT, render_cmd.output())
+    let page_arg = page.to_string();
+    let dpi_arg = dpi.to_string();
+    let mut args = encryption::password_args(password);
+    args.extend([
+        "-r",
+        &dpi_arg,
+        "-f",
+        &page_arg,
+        "-l",
+        &page_arg,
+        "-png",
+        "-singlefile",
+        path,
+        &prefix_str,
+    ]);
+    sandbox::run(Tool::Pdftoppm, args)
         .await
-        .context("timeout running pdftoppm")??;
-    if !render_output.status.success() {
-        return Err(anyhow!(
-            "pdftoppm exit status on page {page}: {}",
-            render_output.status
-        ));
-    }
-
-    let mut text_cmd = Command::new("tesseract");
-    text_cmd
-        .arg(&png_path)
-        .arg("stdout")
-        .arg("-l")
-        .arg(&options.ocr_lang)
-        .arg("--psm")
-        .arg(&options.ocr_psm);
-
-    let text_output = timeout(PROCESS_TIMEOUT, text_cmd.output())
+        .with_context(|| format!("render page {page}"))?;
+    Ok(guard)
+}
+
+/// Rotates a PNG clockwise by `rotation` degrees (multiple of 90) in place.
+async fn rotate_png(path: &str, rotation: i32) -> Result<()> {
+    let path = path.to_string();
+    tokio::task::spawn_blocking(move || -> Result<()> {
+        let img = image::open(&path).context("load page image")?;
+        let img = match rotation.rem_euclid(360) {
+            90 => img.rotate90(),
+            180 => img.rotate180(),
+            270 => img.rotate270(),
+            _ => return Ok(()),
+        };
+        img.save(&path).context("save rotated page image")
+    })
+    .await
+    .map_err(|e| anyhow!("rotate task failed: {e}"))?
+}
+
+/// Engine output of one attempt and the applied [`preprocess`] steps.
+type Recognized = (OcrOutput, Vec<String>);
+
+/// Single OCR attempt: render at `strategy.dpi`, rotate, clean up, recognize.
+async fn ocr_attempt(
+    path: &str,
+    page: i32,
+    options: &ExtractionConfig,
+    strategy: &OcrStrategy,
+    rotation: i32,
+    capture_layout: bool,
+) -> Result<Recognized> {
+    let image = render_page(path, page, strategy.dpi, options.password.as_ref()).await?;
+    if rotation == 0 {
+        page_images::capture(&image.path, options).await;
+    }
+    if rotation != 0 {
+        rotate_png(&image.path, rotation).await?;
+    }
+    let preprocessing = preprocess::preprocess_png(&image.path, &options.ocr_preprocess)
         .await
-        .context("timeout running tesseract")??;
-    if !text_output.status.success() {
-        return Err(anyhow!(
-            "tesseract exit status on page {page}: {}",
-            text_output.status
-        ));
-    }
-    let text = String::from_utf8(text_output.stdout).context("invalid utf8 from tesseract")?;
-
-    let hocr = if capture_layout {
-        let mut hocr_cmd = Command::new("tesseract");
-        hocr_cmd
-            .arg(&png_path)
-            .arg("stdout")
-            .arg("-l")
-            .arg(&options.ocr_lang)
-            .arg("--psm")
-            .arg(&options.ocr_psm)
-            .arg("hocr");
-        let hocr_output = timeout(PROCESS_TIMEOUT, hocr_cmd.output())
-            .await
-            .context("timeout running tesseract hocr")??;
-        if hocr_output.status.success() {
-            Some(
-                String::from_utf8(hocr_output.stdout)
-                    .context("invalid utf8 from tesseract hocr")?,
-            )
-        } else {
-            warn!(page = page - 1, "tesseract hocr failed");
-            None
-        }
-    } else {
-        None
-    };
+        .with_context(|| format!("preprocess page {page}"))?;
 
-    Ok(OcrResult { text, hocr })
+    let output = options
+        .ocr_engine
+        .recognize(OcrRequest {
+            image: Path::new(&image.path),
+            lang: &options.ocr_lang,
+            psm: &strategy.psm,
+            capture_layout,
+        })
+        .await
+        .with_context(|| format!("{} ocr on page {page}", options.ocr_engine.name()))?;
+    Ok((output, preprocessing))
 }
 
 /// Extract per-page text (0-indexed page numbers) including OCR fallback and layout metadata.
-pub async fn extract_text_pages(path: &str) -> Result<Vec<PageExtraction>> {
-    let options = ExtractionOptions::from_env();
-    let pages = detect_pages(path).await?;
-    info!(pages, "detected pages");
+pub async fn extract_text_pages(
+    path: &str,
+    config: &ExtractionConfig,
+) -> Result<Vec<PageExtraction>> {
+    let scheduler = PageScheduler::new(
+        SchedulingMode::Interleaved,
+        config.max_parallel_text,
+        config.max_parallel_ocr,
+    );
+    let ticket = scheduler.register(RunPriority::default());
+    extract_text_pages_scheduled(path, &ticket, config).await
+}
+
+/// Like [`extract_text_pages`], but for a PDF held in memory. The bytes are
+/// written to a [`TempPdf`] that is removed when the extraction is done.
+pub async fn extract_text_pages_from_bytes(
+    data: &[u8],
+    config: &ExtractionConfig,
+) -> Result<Vec<PageExtraction>> {
+    let temp = TempPdf::write(data).await?;
+    extract_text_pages(temp.path(), config).await
+}
+
+/// Like [`extract_text_pages`], but pages wait for slots of a scheduler shared
+/// with other documents (see [`scheduler`]).
+pub async fn extract_text_pages_scheduled(
+    path: &str,
+    ticket: &DocumentTicket,
+    config: &ExtractionConfig,
+) -> Result<Vec<PageExtraction>> {
+    extract_text_pages_from_scheduled(path, ticket, 0, config).await
+}
+
+/// Like [`extract_text_pages_scheduled`], but only extracts the pages from the
+/// 0-indexed `first_page` on (pages appended to an existing document).
+pub async fn extract_text_pages_from_scheduled(
+    path: &str,
+    ticket: &DocumentTicket,
+    first_page: i32,
+    config: &ExtractionConfig,
+) -> Result<Vec<PageExtraction>> {
+    let mut collected: Vec<PageExtraction> =
+        extract_text_pages_stream_from_scheduled(path, ticket, first_page, config)
+            .try_collect()
+            .await?;
+    collected.sort_by_key(|p| p.page_no);
+
+    if collected.is_empty() && first_page <= 0 {
+        let fallback = extract_text(path).await?;
+        return Ok(vec![PageExtraction {
+            page_no: 0,
+            ocr_used: false,
+            layout: None,
+            ocr_strategy: Some("pdftotext".to_string()),
+            quality_score: None,
+            lang: language::detect(&fallback, &config.ocr_lang_detect),
+            ocr_preprocessing: None,
+            image: None,
+            text: fallback,
+        }]);
+    }
+
+    Ok(collected)
+}
+
+/// Like [`extract_text_pages`], but yields every page as soon as it is done,
+/// so callers can persist large documents without holding all pages.
+///
+/// Pages arrive in completion order, not page order. The stream ends after the
+/// first error; dropping it cancels the pages still running.
+pub fn extract_text_pages_stream(
+    path: &str,
+    config: &ExtractionConfig,
+) -> impl Stream<Item = Result<PageExtraction>> + Send + 'static {
+    let scheduler = PageScheduler::new(
+        SchedulingMode::Interleaved,
+        config.max_parallel_text,
+        config.max_parallel_ocr,
+    );
+    let ticket = scheduler.register(RunPriority::default());
+    page_stream(path, ticket, PageSelection::From(0), config)
+}
+
+/// Streaming counterpart of [`extract_text_pages_from_scheduled`].
+pub fn extract_text_pages_stream_from_scheduled<'a>(
+    path: &str,
+    ticket: &'a DocumentTicket,
+    first_page: i32,
+    config: &ExtractionConfig,
+) -> impl Stream<Item = Result<PageExtraction>> + Send + 'a {
+    page_stream(path, ticket, PageSelection::From(first_page), config)
+}
+
+/// Like [`extract_text_pages_stream_from_scheduled`], but only extracts the
+/// listed 0-indexed pages (re-OCR of single pages); pages beyond the end of
+/// the document are skipped.
+pub fn extract_selected_pages_stream_scheduled<'a>(
+    path: &str,
+    ticket: &'a DocumentTicket,
+    pages: &[i32],
+    config: &ExtractionConfig,
+) -> impl Stream<Item = Result<PageExtraction>> + Send + 'a {
+    page_stream(path, ticket, PageSelection::Only(pages.to_vec()), config)
+}
+
+/// Pages of a document to extract.
+enum PageSelection {
+    /// All pages from this 0-indexed page on.
+    From(i32),
+    /// Only these 0-indexed pages.
+    Only(Vec<i32>),
+}
 
-    if pages <= 0 {
-        return Ok(vec![]);
+impl PageSelection {
+    /// 1-based page numbers of a document with `pages` pages, in order.
+    fn resolve(&self, pages: i32) -> Vec<i32> {
+        match *self {
+            Self::From(first_page) => ((first_page.max(0) + 1)..=pages).collect(),
+            Self::Only(ref selected) => {
+                let mut selected: Vec<i32> = selected
+                    .iter()
+                    .map(|page| page + 1)
+                    .filter(|page| (1..=pages).contains(page))
+                    .collect();
+                selected.sort_unstable();
+                selected.dedup();
+                selected
+            }
+        }
     }
+}
 
-    let semaphore = Arc::new(Semaphore::new(options.max_parallel_ocr));
+/// Document waiting for its first poll.
+struct PendingDocument<T> {
+    path: String,
+    ticket: T,
+    selection: PageSelection,
+    options: ExtractionConfig,
+}
+
+enum PageStreamState<T> {
+    Pending(Box<PendingDocument<T>>),
+    // Das Ticket bleibt bis zum Ende registriert, sonst laufen Seiten ohne Slot
+    Running {
+        tasks: JoinSet<Result<PageExtraction>>,
+        ticket: T,
+    },
+    Done,
+}
+
+fn page_stream<'a, T>(
+    path: &str,
+    ticket: T,
+    selection: PageSelection,
+    config: &ExtractionConfig,
+) -> impl Stream<Item = Result<PageExtraction>> + Send + 'a
+where
+    T: Borrow<DocumentTicket> + Send + Sync + 'a,
+{
+    let pending = Box::new(PendingDocument {
+        path: path.to_string(),
+        ticket,
+        selection,
+        options: config.clone(),
+    });
+    stream::unfold(PageStreamState::Pending(pending), |state| async move {
+        let (mut tasks, ticket) = match state {
+            PageStreamState::Pending(pending) => match spawn_pages(*pending).await {
+                Ok(running) => running,
+                Err(err) => return Some((Err(err), PageStreamState::Done)),
+            },
+            PageStreamState::Running { tasks, ticket } => (tasks, ticket),
+            PageStreamState::Done => return None,
+        };
+        match tasks.join_next().await? {
+            Ok(Ok(page)) => Some((Ok(page), PageStreamState::Running { tasks, ticket })),
+            Ok(Err(err)) => Some((Err(err), PageStreamState::Done)),
+            Err(err) => Some((
+                Err(anyhow!("page task join error: {err}")),
+                PageStreamState::Done,
+            )),
+        }
+    })
+}
+
+/// Detects the pages, verifies the text layer if configured and starts one
+/// task per page; each task waits for its scheduler slots.
+async fn spawn_pages<T: Borrow<DocumentTicket>>(
+    pending: PendingDocument<T>,
+) -> Result<(JoinSet<Result<PageExtraction>>, T)> {
+    let PendingDocument {
+        path,
+        ticket,
+        selection,
+        mut options,
+    } = pending;
     let mut join_set = JoinSet::new();
+    let pages = detect_pages(&path, options.password.as_ref()).await?;
+    let selected = selection.resolve(pages);
+    info!(pages, selected = selected.len(), "detected pages");
+
+    let (Some(&first), Some(&last)) = (selected.first(), selected.last()) else {
+        return Ok((join_set, ticket));
+    };
+    let document = ticket.borrow().handle();
 
-    for p in 1..=pages {
-        let path = path.to_string();
-        let semaphore = semaphore.clone();
+    // Mit erzwungener OCR (z. B. erneute OCR einzelner Seiten) gibt es nichts zu prüfen
+    if options.ocr_enabled
+        && !options.force_ocr
+        && options.text_layer_policy == TextLayerPolicy::Verify
+    {
+        let samples = sample_pages(first, last, options.text_layer_samples);
+        // Stichproben-OCR belegt wie jede OCR-Seite einen Seiten-Slot
+        let similarity = ocr_stage(
+            document.acquire(),
+            text_layer_similarity(&path, &samples, &options),
+        )
+        .await;
+        match similarity {
+            Some(similarity) if similarity < options.text_layer_min_similarity => {
+                warn!(
+                    ?path,
+                    similarity,
+                    threshold = options.text_layer_min_similarity,
+                    "text layer diverges from ocr, switching document to ocr"
+                );
+                options.force_ocr = true;
+            }
+            Some(similarity) => info!(?path, similarity, "text layer verified"),
+            None => info!(?path, "text layer not verifiable, deciding per page"),
+        }
+    }
+
+    // Schlüssel erst nach der Prüfung der Textebene bilden, force_ocr gehört dazu
+    let cache_keys = match options.cache {
+        Some(_) => match cache::file_sha256(&path).await {
+            Ok(sha256) => Some((sha256, cache::options_hash(&options))),
+            Err(err) => {
+                warn!(?path, error = %err, "page cache unavailable for document");
+                None
+            }
+        },
+        None => None,
+    };
+
+    // Text-Slots in Seitenreihenfolge anfordern, damit der Scheduler die Reihenfolge kennt
+    for p in selected {
+        let path = path.clone();
         let options = options.clone();
+        let key = cache_keys.as_ref().map(|(sha256, hash)| CacheKey {
+            sha256: sha256.clone(),
+            page_no: p - 1,
+            options: hash.clone(),
+        });
+        let document = document.clone();
+        let text_slot = document.text_slot();
         join_set.spawn(async move {
-            let permit = semaphore
-                .acquire_owned()
-                .await
-                .context("acquire semaphore")?;
-            let res = process_page(&path, p, &options).await;
-            drop(permit);
+            let cache = options.cache.as_ref().zip(key.as_ref());
+            if let Some((cache, key)) = cache {
+                match cache.get(key).await {
+                    // Slot wird nicht gebraucht; der Scheduler gibt ihn beim Verwerfen frei
+                    Ok(Some(page)) => {
+                        info!(page = p - 1, "page cache hit");
+                        return Ok(page);
+                    }
+                    Ok(None) => {}
+                    Err(err) => warn!(page = p - 1, error = %err, "page cache lookup failed"),
+                }
+            }
+            let res = process_page(&path, p, &options, &document, text_slot).await;
+            if let (Some((cache, key)), Ok(page)) = (cache, &res) {
+                if let Err(err) = cache.put(key, page).await {
+                    warn!(page = p - 1, error = %err, "page cache store failed");
+                }
+            }
             res
         });
     }
 
-    let mut collected = Vec::with_capacity(pages as usize);
-    while let Some(joined) = join_set.join_next().await {
-        match joined {
-            Ok(Ok(page)) => collected.push(page),
-            Ok(Err(err)) => return Err(err),
-            Err(err) => return Err(anyhow!("page task join error: {err}")),
-        }
+    Ok((join_set, ticket))
+}
+
+/// Up to `count` 1-based page numbers spread evenly over `first..=last`.
+fn sample_pages(first: i32, last: i32, count: usize) -> Vec<i32> {
+    if last < first || count == 0 {
+        return vec![];
+    }
+    let span = (last - first) as usize;
+    if count == 1 {
+        return vec![first + (span / 2) as i32];
     }
+    let mut pages: Vec<i32> = (0..count)
+        .map(|i| first + (i * span / (count - 1)) as i32)
+        .collect();
+    pages.dedup();
+    pages
+}
 
-    collected.sort_by_key(|p| p.page_no);
+/// Mean similarity between the embedded text and OCR of the sampled pages;
+/// pages without a text layer are skipped.
+async fn text_layer_similarity(
+    path: &str,
+    pages: &[i32],
+    options: &ExtractionConfig,
+) -> Option<f64> {
+    let strategy = options.default_strategy();
+    let mut scores = Vec::new();
+    for &page in pages {
+        let embedded = match run_pdftotext_page(path, page, options).await {
+            Ok(output) => String::from_utf8_lossy(&output.stdout).into_owned(),
+            Err(err) => {
+                warn!(page = page - 1, error = %err, "text layer sample failed");
+                continue;
+            }
+        };
+        if embedded.chars().filter(|c| !c.is_whitespace()).count() < options.ocr_min_nonws {
+            continue;
+        }
+        match ocr_attempt(path, page, options, &strategy, 0, false).await {
+            Ok((ocr, _)) => {
+                if let Some(score) = quality::text_similarity(&embedded, &ocr.text) {
+                    info!(
+                        page = page - 1,
+                        similarity = score,
+                        "text layer sample compared"
+                    );
+                    scores.push(score);
+                }
+            }
+            Err(err) => warn!(page = page - 1, error = %err, "text layer sample ocr failed"),
+        }
+    }
+    if scores.is_empty() {
+        None
+    } else {
+        Some(scores.iter().sum::<f64>() / scores.len() as f64)
+    }
+}
 
-    if collected.is_empty() {
-        let fallback = extract_text(path).await?;
-        return Ok(vec![PageExtraction {
-            page_no: 0,
-            text: fallback,
-            ocr_used: false,
-            layout: None,
-        }]);
+/// Runs `stage` under a text slot; tool timeouts lower the text parallelism.
+fn text_stage<F: Future>(
+    slot: impl Future<Output = TextSlot>,
+    stage: F,
+) -> impl Future<Output = F::Output> {
+    let stage = sandbox::track_timeouts(stage);
+    async move {
+        let slot = slot.await;
+        let (output, timeouts) = stage.await;
+        slot.report(timeouts > 0);
+        output
     }
+}
 
-    Ok(collected)
+/// Runs `stage` under a page slot; tool timeouts lower the OCR parallelism.
+fn ocr_stage<F: Future>(
+    slot: impl Future<Output = PageSlot>,
+    stage: F,
+) -> impl Future<Output = F::Output> {
+    let stage = sandbox::track_timeouts(stage);
+    async move {
+        let slot = slot.await;
+        let (output, timeouts) = stage.await;
+        slot.report(timeouts > 0);
+        output
+    }
 }
 
+/// Extracts one page: `pdftotext` under the requested text slot, OCR (if the
+/// text layer is missing or garbled) under a page slot of the document.
 async fn process_page(
     path: &str,
     page: i32,
-    options: &ExtractionOptions,
+    options: &ExtractionConfig,
+    document: &DocumentHandle,
+    text_slot: impl Future<Output = TextSlot>,
 ) -> Result<PageExtraction> {
-    let pdftotext = run_pdftotext_page(path, page, options.pdftext_layout).await?;
+    let pdftotext = text_stage(text_slot, run_pdftotext_page(path, page, options)).await?;
     let text = String::from_utf8(pdftotext.stdout).context("invalid utf8 from pdftotext")?;
     info!(page = page - 1, "pdftotext ok");
 
@@ -310,22 +845,80 @@ async fn process_page(
     let mut final_text = text.clone();
     let mut ocr_used = false;
     let mut hocr_content = None;
+    let mut ocr_dpi = options.ocr_dpi;
+    let mut ocr_rotation = 0;
+    let mut strategy = "pdftotext".to_string();
+    let mut preprocessing = Vec::new();
+    let mut quality_score = quality::dictionary_score(&text);
+    let mut lang = language::detect(&text, &options.ocr_lang_detect);
+    let mut image = None;
 
-    if options.ocr_enabled && (non_ws < options.ocr_min_nonws || should_ocr(&text)) {
-        match perform_ocr(path, page, options, options.layout_enabled).await {
-            Ok(result) => {
-                let ocr_non_ws = result.text.chars().filter(|c| !c.is_whitespace()).count();
-                if ocr_non_ws > non_ws {
-                    final_text = result.text;
+    if options.ocr_enabled && (options.force_ocr || non_ws < options.ocr_min_nonws) {
+        let ocr =
+            perform_ocr_in_language(path, page, options, lang.clone(), options.layout_enabled);
+        let (ocr, captured) =
+            ocr_stage(document.acquire(), page_images::capturing(options, ocr)).await;
+        image = captured;
+        match ocr {
+            Ok((result, detected)) => {
+                if options.force_ocr || result.non_ws > non_ws {
+                    lang = detected;
+                    quality_score = quality::dictionary_score(&result.output.text);
+                    strategy = result.label();
+                    ocr_dpi = result.strategy.dpi;
+                    ocr_rotation = result.rotation;
+                    preprocessing = result.preprocessing;
+                    final_text = result.output.text;
                     ocr_used = true;
-                    hocr_content = result.hocr;
-                    info!(page = page - 1, "ocr fallback used");
+                    hocr_content = result.output.hocr;
+                    info!(page = page - 1, strategy = %strategy, "ocr fallback used");
                 }
             }
             Err(err) => {
                 warn!(page = page - 1, error = %err, "ocr fallback failed");
             }
         }
+    } else if options.ocr_enabled
+        && !options.ocr_retry_strategies.is_empty()
+        && quality_score.is_some_and(|score| score < options.ocr_quality_min)
+    {
+        // Viel Text, aber kaum echte Wörter (kaputte Font-Encodings): OCR-Varianten
+        // durchprobieren und nur übernehmen, wenn das Ergebnis besser bewertet wird
+        let pdftotext_score = quality_score.unwrap_or_default();
+        let (best, captured) = ocr_stage(
+            document.acquire(),
+            page_images::capturing(options, best_reocr(path, page, options)),
+        )
+        .await;
+        image = captured;
+        match best {
+            Some((score, winner, (result, applied))) if score > pdftotext_score => {
+                final_text = result.text;
+                ocr_used = true;
+                hocr_content = result.hocr;
+                preprocessing = applied;
+                ocr_dpi = winner.dpi;
+                strategy = winner.label();
+                quality_score = Some(score);
+                info!(
+                    page = page - 1,
+                    strategy = %strategy,
+                    pdftotext_score,
+                    score,
+                    "garbled text layer replaced by ocr"
+                );
+            }
+            _ => {
+                info!(
+                    page = page - 1,
+                    pdftotext_score, "re-ocr did not improve text, keeping pdftotext"
+                );
+            }
+        }
+    }
+
+    if lang.is_none() && ocr_used {
+        lang = language::detect(&final_text, &options.ocr_lang_detect);
     }
 
     let layout = if options.layout_enabled {
@@ -333,6 +926,7 @@ async fn process_page(
             match hocr_content {
                 Some(ref hocr) => match parse_hocr_layout(page - 1, hocr) {
                     Ok(layout) => {
+                        let layout = layout.unrotate(ocr_rotation);
                         info!(page = page - 1, words = layout.words.len(), "layout parsed");
                         Some(layout)
                     }
@@ -344,7 +938,12 @@ async fn process_page(
                 None => None,
             }
         } else {
-            match extract_vector_layout(path, page, options).await {
+            match text_stage(
+                document.text_slot(),
+                extract_vector_layout(path, page, options),
+            )
+            .await
+            {
                 Ok(Some(layout)) => {
                     info!(page = page - 1, words = layout.words.len(), "layout parsed");
                     Some(layout)
@@ -359,143 +958,182 @@ async fn process_page(
     } else {
         None
     };
+    let layout = match layout {
+        Some(layout) => {
+            let password = options.password.as_ref();
+            let geometry = geometry::page_geometry(path, page, password);
+            let geometry = match text_stage(document.text_slot(), geometry).await {
+                Ok(geometry) => Some(geometry),
+                Err(err) => {
+                    warn!(page = page - 1, error = %err, "page geometry unavailable");
+                    None
+                }
+            };
+            // hOCR liefert Pixel der OCR-Auflösung, pdftotext bereits Punkte
+            let scale = if ocr_used { 72.0 / ocr_dpi as f64 } else { 1.0 };
+            Some(layout.normalize(scale, geometry))
+        }
+        None => None,
+    };
 
     Ok(PageExtraction {
         page_no: page - 1,
         text: final_text,
         ocr_used,
         layout,
+        ocr_strategy: Some(strategy),
+        quality_score,
+        lang,
+        ocr_preprocessing: (!preprocessing.is_empty()).then(|| preprocessing.join(",")),
+        image,
     })
 }
 
-async fn detect_pages(path: &str) -> Result<i32> {
-    let output = Command::new("pdfinfo")
-        .arg(path)
-        .output()
-        .await
-        .context("spawn pdfinfo")?;
-    if !output.status.success() {
-        return Ok(1);
+/// Scores the OCR of every `ocr_retry_strategies` entry and returns the best one.
+async fn best_reocr(
+    path: &str,
+    page: i32,
+    options: &ExtractionConfig,
+) -> Option<(f64, OcrStrategy, Recognized)> {
+    let mut best: Option<(f64, OcrStrategy, Recognized)> = None;
+    for candidate in &options.ocr_retry_strategies {
+        match ocr_attempt(path, page, options, candidate, 0, options.layout_enabled).await {
+            Ok(result) => {
+                let score = quality::dictionary_score(&result.0.text).unwrap_or_default();
+                info!(
+                    page = page - 1,
+                    strategy = %candidate.label(),
+                    score,
+                    "re-ocr candidate scored"
+                );
+                let better = match &best {
+                    Some((best_score, _, _)) => score > *best_score,
+                    None => true,
+                };
+                if better {
+                    best = Some((score, candidate.clone(), result));
+                }
+            }
+            Err(err) => {
+                warn!(page = page - 1, strategy = %candidate.label(), error = %err, "re-ocr failed");
+            }
+        }
     }
-    let s = String::from_utf8_lossy(&output.stdout);
-    let pages = s
-        .lines()
-        .find(|l| l.trim_start().starts_with("Pages:"))
-        .and_then(|l| l.split_whitespace().nth(1))
-        .and_then(|n| n.parse::<i32>().ok())
-        .unwrap_or(1);
-    Ok(pages)
+    best
+}
+
+/// Page count via `pdfinfo`; fails with [`ExtractionError::Encrypted`] when
+/// the document cannot be opened or its text not be copied without password.
+async fn detect_pages(path: &str, password: Option<&PdfPassword>) -> Result<i32> {
+    let mut args = encryption::password_args(password);
+    args.push(path);
+    let output = match sandbox::run(Tool::Pdfinfo, args).await {
+        Ok(output) => output,
+        Err(err) if encryption::is_encryption_failure(&err) => {
+            return Err(encrypted(password).into())
+        }
+        Err(err) if sandbox::is_exit_failure(&err) => return Ok(1),
+        Err(err) => return Err(err).context("detect pages"),
+    };
+    let meta = metadata::parse_pdfinfo(&String::from_utf8_lossy(&output.stdout));
+    if meta.encrypted {
+        info!(
+            ?path,
+            copy_allowed = !meta.copy_forbidden,
+            "pdf is encrypted"
+        );
+        // pdftotext verweigert solche Dokumente ohne Passwort ohnehin
+        if meta.copy_forbidden && password.is_none() {
+            return Err(encrypted(password).into());
+        }
+    }
+    Ok(meta.page_count.max(1))
 }
 
 async fn run_pdftotext_full(path: &str) -> Result<std::process::Output> {
-    let mut cmd = Command::new("pdftotext");
     let use_layout = env::var("PDFTEXT_LAYOUT").map(|v| v != "0").unwrap_or(true);
+    let mut args = Vec::new();
     if use_layout {
-        cmd.arg("-layout");
+        args.push("-layout");
     }
-    cmd.arg("-q").arg(path).arg("-");
-    let output = timeout(PROCESS_TIMEOUT, cmd.output())
-        .await
-        .context("timeout running pdftotext")??;
-    if !output.status.success() {
-        return Err(anyhow!("pdftotext exit status: {}", output.status));
+    args.extend(["-q", path, "-"]);
+    Ok(sandbox::run(Tool::Pdftotext, args).await?)
+}
+
+fn encrypted(password: Option<&PdfPassword>) -> ExtractionError {
+    ExtractionError::Encrypted {
+        password_given: password.is_some(),
     }
-    Ok(output)
 }
 
 async fn run_pdftotext_page(
     path: &str,
     page: i32,
-    use_layout: bool,
+    options: &ExtractionConfig,
 ) -> Result<std::process::Output> {
-    let mut cmd = Command::new("pdftotext");
-    if use_layout {
-        cmd.arg("-layout");
-    }
-    cmd.arg("-q")
-        .arg("-enc")
-        .arg("UTF-8")
-        .arg("-eol")
-        .arg("unix")
-        .arg("-f")
-        .arg(page.to_string())
-        .arg("-l")
-        .arg(page.to_string())
-        .arg(path)
-        .arg("-");
-    let output = timeout(PROCESS_TIMEOUT, cmd.output())
-        .await
-        .context("timeout running pdftotext page")??;
-    if !output.status.success() {
-        return Err(anyhow!(
-            "pdftotext exit status on page {page}: {}",
-            output.status
-        ));
+    let page_arg = page.to_string();
+    let password = options.password.as_ref();
+    let mut args = encryption::password_args(password);
+    if options.pdftext_layout {
+        args.push("-layout");
+    }
+    args.extend([
+        "-q", "-enc", "UTF-8", "-eol", "unix", "-f", &page_arg, "-l", &page_arg, path, "-",
+    ]);
+    match sandbox::run(Tool::Pdftotext, args).await {
+        Ok(output) => Ok(output),
+        Err(err) if encryption::is_encryption_failure(&err) => Err(encrypted(password).into()),
+        Err(err) => Err(err).with_context(|| format!("pdftotext on page {page}")),
     }
-    Ok(output)
 }
 
 async fn extract_vector_layout(
     path: &str,
     page: i32,
-    options: &ExtractionOptions,
+    options: &ExtractionConfig,
 ) -> Result<Option<PageLayout>> {
     match options.layout_backend {
         LayoutBackend::BBox => {
-            let xml = run_pdftotext_bbox(path, page).await?;
+            let xml = run_pdftotext_bbox(path, page, options.password.as_ref()).await?;
             parse_bbox_layout(page - 1, &xml).map(Some)
         }
         LayoutBackend::PdfToHtml => {
-            let xml = run_pdftohtml_xml(path, page).await?;
+            let xml = run_pdftohtml_xml(path, page, options.password.as_ref()).await?;
             parse_pdftohtml_layout(page - 1, &xml).map(Some)
         }
     }
 }
 
-async fn run_pdftotext_bbox(path: &str, page: i32) -> Result<String> {
-    let mut cmd = Command::new("pdftotext");
-    cmd.arg("-bbox")
-        .arg("-enc")
-        .arg("UTF-8")
-        .arg("-q")
-        .arg("-f")
-        .arg(page.to_string())
-        .arg("-l")
-        .arg(page.to_string())
-        .arg(path)
-        .arg("-");
-    let output = timeout(PROCESS_TIMEOUT, cmd.output())
+async fn run_pdftotext_bbox(
+    path: &str,
+    page: i32,
+    password: Option<&PdfPassword>,
+) -> Result<String> {
+    let page_arg = page.to_string();
+    let mut args = encryption::password_args(password);
+    args.extend([
+        "-bbox", "-enc", "UTF-8", "-q", "-f", &page_arg, "-l", &page_arg, path, "-",
+    ]);
+    let output = sandbox::run(Tool::Pdftotext, args)
         .await
-        .context("timeout running pdftotext bbox")??;
-    if !output.status.success() {
-        return Err(anyhow!(
-            "pdftotext -bbox exit status on page {page}: {}",
-            output.status
-        ));
-    }
+        .with_context(|| format!("pdftotext -bbox on page {page}"))?;
     let xml = String::from_utf8(output.stdout).context("invalid utf8 from pdftotext -bbox")?;
     Ok(xml)
 }
 
-async fn run_pdftohtml_xml(path: &str, page: i32) -> Result<String> {
-    let mut cmd = Command::new("pdftohtml");
-    cmd.arg("-xml")
-        .arg("-i")
-        .arg("-stdout")
-        .arg("-f")
-        .arg(page.to_string())
-        .arg("-l")
-        .arg(page.to_string())
-        .arg(path);
-    let output = timeout(PROCESS_TIMEOUT, cmd.output())
+async fn run_pdftohtml_xml(
+    path: &str,
+    page: i32,
+    password: Option<&PdfPassword>,
+) -> Result<String> {
+    let page_arg = page.to_string();
+    let mut args = encryption::password_args(password);
+    args.extend([
+        "-xml", "-i", "-stdout", "-f", &page_arg, "-l", &page_arg, path,
+    ]);
+    let output = sandbox::run(Tool::Pdftohtml, args)
         .await
-        .context("timeout running pdftohtml -xml")??;
-    if !output.status.success() {
-        return Err(anyhow!(
-            "pdftohtml -xml exit status on page {page}: {}",
-            output.status
-        ));
-    }
+        .with_context(|| format!("pdftohtml -xml on page {page}"))?;
     let xml = String::from_utf8(output.stdout).context("invalid utf8 from pdftohtml -xml")?;
     Ok(xml)
 }
@@ -521,10 +1159,10 @@ fn parse_pdftohtml_layout(page_no: i32, xml: &str) -> Result<PageLayout> {
                         let attr = attr?;
                         let key = attr.key.as_ref();
                         if key == b"width" {
-                            page_width = attr.unescape_value()?.parse().unwrap_or(0);
+                            page_width = parse_coord(&attr.unescape_value()?);
                         }
                         if key == b"height" {
-                            page_height = attr.unescape_value()?.parse().unwrap_or(0);
+                            page_height = parse_coord(&attr.unescape_value()?);
                         }
                     }
                 }
@@ -537,19 +1175,19 @@ fn parse_pdftohtml_layout(page_no: i32, xml: &str) -> Result<PageLayout> {
                         let val = attr.unescape_value()?;
                         match key {
                             b"xMin" => {
-                                coords[0] = val.parse().unwrap_or(0);
+                                coords[0] = parse_coord(&val);
                                 seen[0] = true;
                             }
                             b"yMin" => {
-                                coords[1] = val.parse().unwrap_or(0);
+                                coords[1] = parse_coord(&val);
                                 seen[1] = true;
                             }
                             b"xMax" => {
-                                coords[2] = val.parse().unwrap_or(0);
+                                coords[2] = parse_coord(&val);
                                 seen[2] = true;
                             }
                             b"yMax" => {
-                                coords[3] = val.parse().unwrap_or(0);
+                                coords[3] = parse_coord(&val);
                                 seen[3] = true;
                             }
                             _ => {}
@@ -560,6 +1198,7 @@ fn parse_pdftohtml_layout(page_no: i32, xml: &str) -> Result<PageLayout> {
                         words.push(Word {
                             bbox: coords,
                             text: text.trim().to_string(),
+                            confidence: None,
                         });
                     }
                 }
@@ -576,14 +1215,23 @@ fn parse_pdftohtml_layout(page_no: i32, xml: &str) -> Result<PageLayout> {
         page_no,
         page_width,
         page_height,
+        rotation: 0,
         words,
     })
 }
 
+/// Coordinate of `pdftotext -bbox` (`56.800000`) rounded to whole points.
+fn parse_coord(raw: &str) -> i32 {
+    raw.trim()
+        .parse::<f64>()
+        .map(|v| v.round() as i32)
+        .unwrap_or(0)
+}
+
 fn parse_hocr_layout(page_no: i32, hocr: &str) -> Result<PageLayout> {
     static WORD_RE: Lazy<Regex> = Lazy::new(|| {
         Regex::new(
-            r#"<span[^>]*class=['\"]ocrx_word['\"][^>]*title=['\"][^'\"]*bbox (?P<bbox>\d+ \d+ \d+ \d+)[^'\"]*['\"][^>]*>(?P<text>.*?)</span>"#,
+            r#"<span[^>]*class=['\"]ocrx_word['\"][^>]*title=['\"](?P<title>[^'\"]*bbox \d+ \d+ \d+ \d+[^'\"]*)['\"][^>]*>(?P<text>.*?)</span>"#,
         )
         .expect("valid regex")
     });
@@ -608,8 +1256,8 @@ fn parse_hocr_layout(page_no: i32, hocr: &str) -> Result<PageLayout> {
 
     let mut words = Vec::new();
     for cap in WORD_RE.captures_iter(hocr) {
-        if let (Some(bbox), Some(text_match)) = (cap.name("bbox"), cap.name("text")) {
-            if let Some(word) = build_word(bbox.as_str(), text_match.as_str()) {
+        if let (Some(title), Some(text_match)) = (cap.name("title"), cap.name("text")) {
+            if let Some(word) = build_word(title.as_str(), text_match.as_str()) {
                 words.push(word);
             }
         }
@@ -619,15 +1267,26 @@ fn parse_hocr_layout(page_no: i32, hocr: &str) -> Result<PageLayout> {
         page_no,
         page_width,
         page_height,
+        rotation: 0,
         words,
     })
 }
 
-fn build_word(bbox: &str, text: &str) -> Option<Word> {
-    let coords = parse_bbox_values(bbox);
+/// Builds a word from an hOCR `title` (`bbox x0 y0 x1 y1; x_wconf 95`).
+fn build_word(title: &str, text: &str) -> Option<Word> {
+    let property = |name: &str| {
+        title
+            .split(';')
+            .map(str::trim)
+            .find_map(|p| p.strip_prefix(name)?.strip_prefix(' '))
+    };
+    let coords = parse_bbox_values(property("bbox")?);
     if coords.len() != 4 {
         return None;
     }
+    let confidence = property("x_wconf")
+        .and_then(|v| v.trim().parse::<f32>().ok())
+        .map(|v| (v / 100.0).clamp(0.0, 1.0));
     let decoded = decode_html_entities(text).trim().to_string();
     if decoded.is_empty() {
         return None;
@@ -635,6 +1294,7 @@ fn build_word(bbox: &str, text: &str) -> Option<Word> {
     Some(Word {
         bbox: [coords[0], coords[1], coords[2], coords[3]],
         text: decoded,
+        confidence,
     })
 }
 
@@ -648,20 +1308,46 @@ fn parse_bbox_values(raw: &str) -> Vec<i32> {
 mod tests {
     use super::*;
 
+    #[test]
+    fn sample_pages_spreads_over_range() {
+        assert_eq!(sample_pages(1, 10, 2), vec![1, 10]);
+        assert_eq!(sample_pages(1, 9, 3), vec![1, 5, 9]);
+        assert_eq!(sample_pages(4, 4, 3), vec![4]);
+        assert_eq!(sample_pages(1, 7, 1), vec![4]);
+        assert!(sample_pages(5, 4, 2).is_empty());
+    }
+
+    #[test]
+    fn page_selection_resolves_to_existing_pages() {
+        assert_eq!(PageSelection::From(0).resolve(3), vec![1, 2, 3]);
+        assert_eq!(PageSelection::From(2).resolve(4), vec![3, 4]);
+        assert!(PageSelection::From(5).resolve(4).is_empty());
+        assert_eq!(
+            PageSelection::Only(vec![4, 0, 9, 4, -1]).resolve(6),
+            vec![1, 5]
+        );
+    }
+
     #[test]
     fn parse_hocr_layout_extracts_words() {
         let hocr = "<!DOCTYPE html><html><body><div class='ocr_page' id='page_1' title='bbox 0 0 200 300; ppageno 0'>\
             <span class='ocrx_word' id='word_1' title='bbox 10 20 60 50; x_wconf 95'>Hello</span>\
-            <span class='ocrx_word' id='word_2' title='bbox 70 20 120 50; x_wconf 95'>World</span>\
+            <span class='ocrx_word' id='word_2' title='bbox 70 20 120 50; x_wconf 45'>World</span>\
+            <span class='ocrx_word' id='word_3' title='bbox 130 20 160 50'>!</span>\
             </div></body></html>";
 
         let layout = parse_hocr_layout(0, hocr).expect("parse hocr");
         assert_eq!(layout.page_no, 0);
         assert_eq!(layout.page_width, 200);
         assert_eq!(layout.page_height, 300);
-        assert_eq!(layout.words.len(), 2);
+        assert_eq!(layout.words.len(), 3);
         assert_eq!(layout.words[0].bbox, [10, 20, 60, 50]);
         assert_eq!(layout.words[0].text, "Hello");
+        assert_eq!(layout.words[0].confidence, Some(0.95));
         assert_eq!(layout.words[1].text, "World");
+        assert_eq!(layout.words[1].confidence, Some(0.45));
+        assert_eq!(layout.words[2].confidence, None);
+        let mean = layout.mean_confidence().expect("ocr words");
+        assert!((mean - 0.7).abs() < 1e-6);
     }
 }
@@ -1,25 +1,51 @@
 //! Coordinates PDF text extraction, OCR and Kafka integration.
 
-use actix_cors::Cors;
 use actix_web::{web, App, HttpResponse, HttpServer, Responder};
 use deadpool_postgres::{Manager, ManagerConfig, Pool, RecyclingMethod};
+use futures_util::StreamExt;
 use rdkafka::{
     consumer::{CommitMode, Consumer, StreamConsumer},
     producer::{FutureProducer, FutureRecord},
-    ClientConfig, Message,
+    ClientConfig, Message, Offset, TopicPartitionList,
 };
 use serde::Deserialize;
+use shared::cors::CorsSettings;
+use shared::entities::PdfEntity;
+use shared::envelope::{MasterKey, SealedSecret};
+use shared::similarity::{self, Signature};
 use shared::{
     config::Settings,
-    dto::{PdfUploaded, TextExtracted},
-    kafka,
+    dto::{PdfUploaded, ReocrPages, TextExtracted},
+    kafka, page_texts,
+    timeline::{self, TimelineEvent},
 };
-use std::{str::FromStr, time::Duration};
+use std::{
+    collections::HashMap,
+    pin::pin,
+    str::FromStr,
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+use tokio::sync::Semaphore;
 use tokio_postgres::{types::Json, NoTls};
 use tracing::{error, info, warn};
 use uuid::Uuid;
 
-use text_extraction::extract_text_pages;
+use text_extraction::attachments::{self, AttachmentOptions};
+use text_extraction::cache;
+use text_extraction::entities::{self, EntityOptions};
+use text_extraction::metadata;
+use text_extraction::page_images;
+use text_extraction::retries::{self, RetryConfig};
+use text_extraction::scheduler::{
+    DocumentTicket, Lane, OffsetTracker, PageScheduler, SchedulerConfig,
+};
+use text_extraction::searchable::{self, SearchableOptions};
+use text_extraction::{
+    extract_selected_pages_stream_scheduled, extract_text,
+    extract_text_pages_stream_from_scheduled, ExtractionConfig, ExtractionError, PageExtraction,
+    PdfPassword, TempPdf,
+};
 
 /// Ensures local database connections explicitly disable SSL.
 fn ensure_sslmode_disable(url: &str) -> String {
@@ -44,6 +70,335 @@ fn ensure_sslmode_disable(url: &str) -> String {
     }
 }
 
+// Text liegt dedupliziert in pdf_text_blobs (shared::page_texts), die Zeile nur mit Hash
+const INSERT_PAGE_SQL: &str = "INSERT INTO pdf_texts (
+        merged_pdf_id, page_no, text, text_sha256, ocr_used, char_count, lang, has_bbox,
+        layout_json, ocr_strategy, quality_score, ocr_confidence, ocr_preprocessing
+     ) VALUES ($1,$2,NULL,$3,$4,$5,$6::text,$7::bool,$8::jsonb,$9::text,$10::real,$11::real,$12::text)
+     ON CONFLICT (merged_pdf_id, page_no)
+     DO UPDATE SET text=NULL,
+                   text_sha256=EXCLUDED.text_sha256,
+                   ocr_used=EXCLUDED.ocr_used,
+                   char_count=EXCLUDED.char_count,
+                   lang=EXCLUDED.lang,
+                   has_bbox=EXCLUDED.has_bbox,
+                   layout_json=EXCLUDED.layout_json,
+                   ocr_strategy=EXCLUDED.ocr_strategy,
+                   quality_score=EXCLUDED.quality_score,
+                   ocr_confidence=EXCLUDED.ocr_confidence,
+                   ocr_preprocessing=EXCLUDED.ocr_preprocessing";
+
+/// Stores one extracted page with the prepared [`INSERT_PAGE_SQL`], its text
+/// with the prepared [`page_texts::UPSERT_BLOB_SQL`].
+async fn insert_page(
+    tx: &deadpool_postgres::Transaction<'_>,
+    ins: &tokio_postgres::Statement,
+    blob: &tokio_postgres::Statement,
+    pdf_id: i32,
+    page: &PageExtraction,
+) -> Result<(), tokio_postgres::Error> {
+    let normalized_text = page.text.to_lowercase();
+    let char_count: i32 = normalized_text
+        .chars()
+        .filter(|c| !c.is_whitespace())
+        .count() as i32;
+    let lang: Option<&str> = page.lang.as_deref();
+    let has_bbox = page.layout.as_ref().map(|layout| !layout.words.is_empty());
+    let layout_value: Option<Json<serde_json::Value>> = page
+        .layout
+        .as_ref()
+        .map(|layout| serde_json::to_value(layout).map(Json))
+        .transpose()
+        .map_err(|e| {
+            warn!(page = page.page_no, %e, "serialize layout failed");
+            e
+        })
+        .ok()
+        .flatten();
+    let quality_score: Option<f32> = page.quality_score.map(|s| s as f32);
+    let ocr_confidence: Option<f32> = page
+        .layout
+        .as_ref()
+        .and_then(|layout| layout.mean_confidence());
+
+    let text_sha256 = page_texts::text_sha256(&normalized_text);
+    tx.execute(blob, &[&text_sha256, &normalized_text]).await?;
+    tx.execute(
+        ins,
+        &[
+            &pdf_id,
+            &page.page_no,
+            &text_sha256,
+            &page.ocr_used,
+            &char_count,
+            &lang,
+            &has_bbox,
+            &layout_value,
+            &page.ocr_strategy,
+            &quality_score,
+            &ocr_confidence,
+            &page.ocr_preprocessing,
+        ],
+    )
+    .await
+    .map(|_| ())
+}
+
+/// Replaces the entity index of the extracted pages (best effort).
+async fn store_entities(
+    client: &mut deadpool_postgres::Client,
+    pdf_id: i32,
+    first_page: i32,
+    found: &[PdfEntity],
+) {
+    let result: Result<(), tokio_postgres::Error> = async {
+        let tx = client.transaction().await?;
+        tx.execute(
+            "DELETE FROM pdf_entities WHERE merged_pdf_id=$1 AND page_no >= $2",
+            &[&pdf_id, &first_page],
+        )
+        .await?;
+        let ins = tx.prepare(shared::entities::INSERT_SQL).await?;
+        for e in found {
+            let bbox = e.bbox.map(|b| Json(serde_json::json!(b)));
+            tx.execute(
+                &ins,
+                &[
+                    &e.merged_pdf_id,
+                    &e.page_no,
+                    &e.kind.as_str(),
+                    &e.value,
+                    &e.normalized,
+                    &bbox,
+                    &e.source,
+                ],
+            )
+            .await?;
+        }
+        tx.commit().await
+    }
+    .await;
+    match result {
+        Ok(()) => info!(id = pdf_id, entities = found.len(), "stored entity index"),
+        Err(e) => warn!(%e, id = pdf_id, "store entity index failed"),
+    }
+}
+
+/// Stores the near-duplicate signature of the full text (best effort).
+async fn store_signature(client: &deadpool_postgres::Client, pdf_id: i32, text: &str) {
+    let Some(signature) = Signature::of_text(text) else {
+        return;
+    };
+    if let Err(e) = client
+        .execute(
+            similarity::UPSERT_SQL,
+            &[
+                &pdf_id,
+                &signature.minhash,
+                &signature.bands(),
+                &signature.shingle_count,
+            ],
+        )
+        .await
+    {
+        warn!(%e, id = pdf_id, "store signature failed");
+    }
+}
+
+/// Builds and stores the searchable copy of documents with OCR pages (best
+/// effort); it only replaces the copy while the PDF still has `sha256`.
+async fn store_searchable(
+    client: &deadpool_postgres::Client,
+    pdf_id: i32,
+    sha256: &str,
+    path: &str,
+    ticket: &DocumentTicket,
+    config: &ExtractionConfig,
+    options: &SearchableOptions,
+) {
+    let stats = client
+        .query_one(
+            "SELECT count(*), COALESCE(bool_or(ocr_used), false)
+               FROM pdf_texts WHERE merged_pdf_id = $1",
+            &[&pdf_id],
+        )
+        .await;
+    let (pages, ocr_used): (i64, bool) = match stats {
+        Ok(row) => (row.get(0), row.get(1)),
+        Err(e) => {
+            warn!(%e, id = pdf_id, "load ocr pages failed");
+            return;
+        }
+    };
+    // Ohne OCR-Seite hat das Original bereits eine Textebene
+    if !ocr_used || pages > options.max_pages {
+        info!(
+            id = pdf_id,
+            pages, ocr_used, "no searchable pdf needed or document too long"
+        );
+        return;
+    }
+    let data = match searchable::create_searchable_pdf_scheduled(path, ticket, config).await {
+        Ok(data) => data,
+        Err(e) => {
+            warn!(id = pdf_id, "create searchable pdf failed: {e:#}");
+            return;
+        }
+    };
+    let result = client
+        .execute(
+            "UPDATE merged_pdfs
+                SET searchable_data = $3, searchable_sha256 = $2, searchable_created_at = now()
+              WHERE id = $1 AND sha256 = $2",
+            &[&pdf_id, &sha256, &data],
+        )
+        .await;
+    match result {
+        Ok(0) => info!(id = pdf_id, "pdf changed meanwhile, searchable pdf dropped"),
+        Ok(_) => info!(id = pdf_id, bytes = data.len(), "stored searchable pdf"),
+        Err(e) => warn!(%e, id = pdf_id, "store searchable pdf failed"),
+    }
+}
+
+/// Reads the document metadata with `pdfinfo` and upserts `pdf_metadata` (best effort).
+async fn store_metadata(
+    client: &deadpool_postgres::Client,
+    pdf_id: i32,
+    path: &str,
+    config: &ExtractionConfig,
+) {
+    let meta = match metadata::extract_metadata(path, config).await {
+        Ok(meta) => meta,
+        Err(e) => {
+            warn!(%e, id = pdf_id, "read pdf metadata failed");
+            return;
+        }
+    };
+    let page_sizes = Json(serde_json::json!(meta.page_sizes));
+    let result = client
+        .execute(
+            shared::pdf_metadata::UPSERT_SQL,
+            &[
+                &pdf_id,
+                &meta.title,
+                &meta.subject,
+                &meta.keywords,
+                &meta.author,
+                &meta.creator,
+                &meta.producer,
+                &meta.creation_date,
+                &meta.mod_date,
+                &meta.pdf_version,
+                &meta.page_count,
+                &meta.encrypted,
+                &meta.form,
+                &meta.tagged,
+                &page_sizes,
+            ],
+        )
+        .await;
+    match result {
+        Ok(_) => info!(id = pdf_id, pages = meta.page_count, "stored pdf metadata"),
+        Err(e) => warn!(%e, id = pdf_id, "store pdf metadata failed"),
+    }
+}
+
+/// Opens the password pdf-ingest sealed into `uploads.pdf_password`; `None`
+/// without password or when it cannot be opened (logged).
+fn open_password(pdf_id: i32, sealed: Option<serde_json::Value>) -> Option<PdfPassword> {
+    let sealed: SealedSecret = match serde_json::from_value(sealed?) {
+        Ok(sealed) => sealed,
+        Err(e) => {
+            warn!(%e, id = pdf_id, "stored pdf password is malformed");
+            return None;
+        }
+    };
+    let opened = MasterKey::from_env().and_then(|key| key.open(&sealed));
+    match opened {
+        Ok(password) => Some(PdfPassword::new(password)),
+        Err(e) => {
+            warn!(%e, id = pdf_id, "open stored pdf password failed");
+            None
+        }
+    }
+}
+
+/// Extracts the embedded PDFs and replaces their stored pages (best effort);
+/// returns the number of stored attachments.
+async fn store_attachments(
+    client: &mut deadpool_postgres::Client,
+    pdf_id: i32,
+    path: &str,
+    config: &ExtractionConfig,
+    options: &AttachmentOptions,
+) -> usize {
+    let extracted = match attachments::extract_attachment_pages(path, config, options).await {
+        Ok(extracted) => extracted,
+        Err(e) => {
+            warn!(%e, id = pdf_id, "list attachments failed");
+            return 0;
+        }
+    };
+    let result: Result<(), tokio_postgres::Error> = async {
+        let tx = client.transaction().await?;
+        tx.execute(
+            "DELETE FROM pdf_attachment_texts WHERE merged_pdf_id=$1",
+            &[&pdf_id],
+        )
+        .await?;
+        let ins = tx
+            .prepare(
+                "INSERT INTO pdf_attachment_texts
+                    (merged_pdf_id, attachment_no, name, size_bytes, page_no, text, ocr_used,
+                     lang, layout_json)
+                 VALUES ($1, $2, $3, $4, $5, $6, $7, $8::text, $9::jsonb)",
+            )
+            .await?;
+        for attachment in &extracted {
+            let size_bytes = attachment.size_bytes as i64;
+            for page in &attachment.pages {
+                let layout = page
+                    .layout
+                    .as_ref()
+                    .and_then(|layout| serde_json::to_value(layout).ok())
+                    .map(Json);
+                tx.execute(
+                    &ins,
+                    &[
+                        &pdf_id,
+                        &(attachment.index as i32),
+                        &attachment.name,
+                        &size_bytes,
+                        &page.page_no,
+                        // wie pdf_texts normalisiert
+                        &page.text.to_lowercase(),
+                        &page.ocr_used,
+                        &page.lang,
+                        &layout,
+                    ],
+                )
+                .await?;
+            }
+        }
+        tx.commit().await
+    }
+    .await;
+    match result {
+        Ok(()) => {
+            info!(
+                id = pdf_id,
+                attachments = extracted.len(),
+                "stored attachment pages"
+            );
+            extracted.len()
+        }
+        Err(e) => {
+            warn!(%e, id = pdf_id, "store attachment pages failed");
+            0
+        }
+    }
+}
+
 /// Liveness endpoint to signal service readiness.
 async fn health() -> impl Responder {
     "OK"
@@ -98,7 +453,7 @@ async fn start_analysis(
     let agg_stmt = client
         .prepare(
             "SELECT COALESCE(string_agg(text, E'\n' ORDER BY page_no), '')
-             FROM pdf_texts WHERE merged_pdf_id = $1",
+             FROM pdf_page_texts WHERE merged_pdf_id = $1",
         )
         .await
         .map_err(actix_web::error::ErrorInternalServerError)?;
@@ -107,6 +462,7 @@ async fn start_analysis(
         if let Ok(row) = client.query_one(&agg_stmt, &[&id]).await {
             let text: String = row.get(0);
             let evt = TextExtracted {
+                schema_version: TextExtracted::SCHEMA_VERSION,
                 pdf_id: id,
                 pipeline_id: Uuid::nil(),
                 text,
@@ -116,7 +472,7 @@ async fn start_analysis(
                 .send(
                     FutureRecord::to("text-extracted")
                         .payload(&payload)
-                        .key(&()),
+                        .key(&kafka::document_key(id)),
                     Duration::from_secs(0),
                 )
                 .await;
@@ -126,6 +482,598 @@ async fn start_analysis(
     Ok(HttpResponse::Ok().finish())
 }
 
+/// Data, `sha256` and sealed password of a merged PDF.
+const LOAD_PDF_SQL: &str = "SELECT m.data, m.sha256,
+        (SELECT u.pdf_password FROM uploads u
+          WHERE u.pdf_id = m.id AND u.pdf_password IS NOT NULL
+          ORDER BY u.id DESC LIMIT 1)
+   FROM merged_pdfs m WHERE m.id = $1";
+
+/// Full text of all pages (normalized as in `pdf_texts`), texts of embedded
+/// PDFs behind them.
+async fn load_full_text(
+    client: &deadpool_postgres::Client,
+    pdf_id: i32,
+    attachments: bool,
+) -> Result<String, tokio_postgres::Error> {
+    let sql = if attachments {
+        "SELECT COALESCE(string_agg(text, E'\n' ORDER BY source, attachment_no, page_no), '')
+         FROM (
+             SELECT 0 AS source, 0 AS attachment_no, page_no, text
+               FROM pdf_page_texts WHERE merged_pdf_id = $1
+             UNION ALL
+             SELECT 1, attachment_no, page_no, text
+               FROM pdf_attachment_texts WHERE merged_pdf_id = $1
+         ) pages"
+    } else {
+        "SELECT COALESCE(string_agg(text, E'\n' ORDER BY page_no), '')
+         FROM pdf_page_texts WHERE merged_pdf_id = $1"
+    };
+    Ok(client.query_one(sql, &[&pdf_id]).await?.get(0))
+}
+
+/// Publishes `text-extracted` with the full text of the document.
+async fn publish_text_extracted(producer: &FutureProducer, evt: &PdfUploaded, text: String) {
+    let out = TextExtracted {
+        schema_version: TextExtracted::SCHEMA_VERSION,
+        pdf_id: evt.pdf_id,
+        pipeline_id: evt.pipeline_id,
+        text,
+    };
+    if let Ok(payload) = serde_json::to_string(&out) {
+        let _ = producer
+            .send(
+                FutureRecord::to("text-extracted")
+                    .payload(&payload)
+                    .key(&kafka::document_key(evt.pdf_id)),
+                Duration::from_secs(0),
+            )
+            .await;
+        info!(
+            step = "kafka.produce.ok",
+            topic = "text-extracted",
+            id = out.pdf_id
+        );
+    }
+}
+
+/// Extracts, stores and announces the text of one merged PDF.
+///
+/// With `appended_from` only the appended pages are extracted and replaced;
+/// the `text-extracted` event still carries the text of all pages.
+async fn handle_pdf_merged(
+    pool: &Pool,
+    producer: &FutureProducer,
+    evt: &PdfUploaded,
+    ticket: &DocumentTicket,
+    config: &ExtractionConfig,
+) {
+    let mut client = match pool.get().await {
+        Ok(c) => c,
+        Err(e) => {
+            error!(%e, "db pool get failed");
+            return;
+        }
+    };
+
+    let row = match client.query_opt(LOAD_PDF_SQL, &[&evt.pdf_id]).await {
+        Ok(Some(r)) => r,
+        Ok(None) => {
+            error!(id = evt.pdf_id, "pdf row not found");
+            return;
+        }
+        Err(e) => {
+            error!(%e, "query pdf row failed");
+            return;
+        }
+    };
+    // NULL, solange das PDF im Cold Storage liegt (GET /pdf/{id} stellt es wieder her)
+    let Some(data) = row.get::<_, Option<Vec<u8>>>(0) else {
+        error!(id = evt.pdf_id, "pdf is archived");
+        return;
+    };
+    // Passwort des Uploads gilt nur für dieses Dokument
+    let protected;
+    let sha256: String = row.get(1);
+    let config = match open_password(evt.pdf_id, row.get(2)) {
+        Some(password) => {
+            protected = config.clone().password(password);
+            &protected
+        }
+        None => config,
+    };
+
+    // temporäre Datei, wird beim Verlassen der Funktion gelöscht
+    let temp = match TempPdf::write(&data).await {
+        Ok(t) => t,
+        Err(e) => {
+            error!(%e, id = evt.pdf_id, "write temp pdf failed");
+            return;
+        }
+    };
+    let path = temp.path();
+    info!(
+        step = "tempfile.write.ok",
+        id = evt.pdf_id,
+        path = %path,
+        bytes = data.len(),
+        "temp pdf written"
+    );
+
+    // Transaktion: alte Seiten (ab der ersten neuen) löschen, neue speichern,
+    // sobald sie fertig sind – große PDFs liegen so nie vollständig im Speicher
+    let first_page = evt.appended_from.unwrap_or(0).max(0);
+    let tx = match client.transaction().await {
+        Ok(t) => t,
+        Err(e) => {
+            error!(%e, "begin tx failed");
+            return;
+        }
+    };
+    if let Err(e) = tx
+        .execute(
+            "DELETE FROM pdf_texts WHERE merged_pdf_id=$1 AND page_no >= $2",
+            &[&evt.pdf_id, &first_page],
+        )
+        .await
+    {
+        error!(%e, "delete old pages failed");
+        let _ = tx.rollback().await;
+        return;
+    }
+    let prepared = async {
+        Ok::<_, tokio_postgres::Error>((
+            tx.prepare(INSERT_PAGE_SQL).await?,
+            tx.prepare(page_texts::UPSERT_BLOB_SQL).await?,
+        ))
+    };
+    let (ins, blob) = match prepared.await {
+        Ok(s) => s,
+        Err(e) => {
+            error!(%e, "prepare insert failed");
+            let _ = tx.rollback().await;
+            return;
+        }
+    };
+
+    let entity_options = EntityOptions::from_env();
+    let mut found_entities = Vec::new();
+    // Welche Strategie (pdftotext / OCR-Variante) wie oft gewonnen hat
+    let mut strategies = serde_json::Map::new();
+    let mut page_count = 0usize;
+    let mut extraction_error = None;
+    let mut ok = true;
+    let mut pages =
+        pin!(extract_text_pages_stream_from_scheduled(path, ticket, first_page, config).fuse());
+    loop {
+        let page = match pages.next().await {
+            Some(Ok(page)) => page,
+            Some(Err(e)) => {
+                extraction_error = Some(e);
+                break;
+            }
+            // Ohne erkannte Seiten den Volltext als Seite 0 speichern
+            None if page_count == 0 && first_page == 0 => match extract_text(path).await {
+                Ok(text) => PageExtraction {
+                    page_no: 0,
+                    text,
+                    ocr_used: false,
+                    layout: None,
+                    ocr_strategy: Some("pdftotext".to_string()),
+                    quality_score: None,
+                    lang: None,
+                    ocr_preprocessing: None,
+                    image: None,
+                },
+                Err(e) => {
+                    extraction_error = Some(e);
+                    break;
+                }
+            },
+            None => break,
+        };
+        if let Err(e) = insert_page(&tx, &ins, &blob, evt.pdf_id, &page).await {
+            error!(%e, page_no = page.page_no, "insert page failed");
+            ok = false;
+            break;
+        }
+        if let Some(image) = &page.image {
+            // Bilder hängen nur an den PDF-Daten und bleiben bei erneuter Extraktion gültig
+            if let Err(e) = tx
+                .execute(
+                    page_images::UPSERT_SQL,
+                    &[
+                        &evt.pdf_id,
+                        &page.page_no,
+                        &(image.width as i32),
+                        &(image.height as i32),
+                        &image.png,
+                    ],
+                )
+                .await
+            {
+                error!(%e, page_no = page.page_no, "insert page image failed");
+                ok = false;
+                break;
+            }
+        }
+        if entity_options.enabled {
+            found_entities.extend(
+                entities::index_pages(evt.pdf_id, std::slice::from_ref(&page), &entity_options)
+                    .await,
+            );
+        }
+        if let Some(strategy) = &page.ocr_strategy {
+            let count = strategies
+                .get(strategy)
+                .and_then(|v| v.as_u64())
+                .unwrap_or(0);
+            strategies.insert(strategy.clone(), serde_json::json!(count + 1));
+        }
+        page_count += 1;
+    }
+
+    if ok && extraction_error.is_none() {
+        if let Err(e) = tx.commit().await {
+            error!(%e, "commit failed");
+            ok = false;
+        }
+    } else {
+        let _ = tx.rollback().await;
+    }
+    if let Some(e) = extraction_error {
+        error!(%e, id = evt.pdf_id, "text extraction failed");
+        let mut failed = TimelineEvent::new("text-extraction", "extraction_failed")
+            .pdf(Some(evt.pdf_id))
+            .pipeline(Some(evt.pipeline_id))
+            .message(e.to_string());
+        if let Some(ExtractionError::Encrypted { password_given }) = e.downcast_ref() {
+            // Eigener Upload-Status, damit die Oberfläche nach dem Passwort fragen kann
+            let _ = client
+                .execute(
+                    "UPDATE uploads SET status='encrypted' WHERE pdf_id=$1",
+                    &[&evt.pdf_id],
+                )
+                .await;
+            failed = failed.details(serde_json::json!({
+                "reason": "encrypted",
+                "password_given": password_given,
+            }));
+        } else {
+            // Vorübergehende Fehler (fehlendes Tool, Timeout) später erneut versuchen
+            match retries::record_failure(&client, &RetryConfig::from_env(), evt, &e).await {
+                Ok(failure) => {
+                    if failure.poisoned {
+                        warn!(
+                            id = evt.pdf_id,
+                            attempts = failure.attempts,
+                            "extraction poisoned"
+                        );
+                        let _ = client
+                            .execute(
+                                "UPDATE uploads SET status='failed' WHERE pdf_id=$1",
+                                &[&evt.pdf_id],
+                            )
+                            .await;
+                    }
+                    failed = failed.details(serde_json::json!({
+                        "reason": retries::reason(&e),
+                        "attempts": failure.attempts,
+                        "poisoned": failure.poisoned,
+                        "retry_in_secs": failure.retry_in.map(|d| d.as_secs()),
+                    }));
+                }
+                Err(e) => error!(%e, id = evt.pdf_id, "record extraction retry failed"),
+            }
+        }
+        timeline::record(&client, &failed).await;
+        return;
+    }
+    if !ok {
+        return;
+    }
+    info!(
+        id = evt.pdf_id,
+        first_page,
+        pages = page_count,
+        "stored per-page text"
+    );
+
+    if entity_options.enabled {
+        store_entities(&mut client, evt.pdf_id, first_page, &found_entities).await;
+    }
+    // Nach dem Anhängen ändern sich Seitenzahl und -größen, daher bei jedem Event
+    store_metadata(&client, evt.pdf_id, path, config).await;
+    // Angehängte Seiten bringen keine neuen Anhänge mit, die alten bleiben stehen
+    let attachment_options = AttachmentOptions::from_env();
+    let attachment_count = if attachment_options.enabled && first_page == 0 {
+        Some(store_attachments(&mut client, evt.pdf_id, path, config, &attachment_options).await)
+    } else {
+        None
+    };
+
+    let concat = match load_full_text(&client, evt.pdf_id, attachment_options.enabled).await {
+        Ok(text) => text,
+        Err(e) => {
+            error!(%e, id = evt.pdf_id, "load stored pages failed");
+            return;
+        }
+    };
+
+    // Signatur für GET /pdf/{id}/similar, nach dem Anhängen über alle Seiten
+    store_signature(&client, evt.pdf_id, &concat).await;
+
+    // Upload-Status aktualisieren (best effort)
+    let _ = client
+        .execute(
+            "UPDATE uploads SET status='ready' WHERE pdf_id=$1",
+            &[&evt.pdf_id],
+        )
+        .await;
+    if let Err(e) = retries::clear(&client, evt.pdf_id, first_page).await {
+        warn!(%e, id = evt.pdf_id, "clear extraction retry failed");
+    }
+    timeline::record(
+        &client,
+        &TimelineEvent::new("text-extraction", "text_extracted")
+            .pdf(Some(evt.pdf_id))
+            .pipeline(Some(evt.pipeline_id))
+            .details(serde_json::json!({
+                "pages": page_count,
+                "appended_from": evt.appended_from,
+                "strategies": strategies,
+                "attachments": attachment_count,
+            })),
+    )
+    .await;
+
+    publish_text_extracted(producer, evt, concat).await;
+
+    // Durchsuchbare Kopie erst nach dem Event, die Pipeline wartet nicht darauf
+    let searchable_options = SearchableOptions::from_env();
+    if searchable_options.enabled {
+        store_searchable(
+            &client,
+            evt.pdf_id,
+            &sha256,
+            path,
+            ticket,
+            config,
+            &searchable_options,
+        )
+        .await;
+    }
+
+    // Cleanup
+    drop(temp);
+    info!(step = "tempfile.cleanup.ok", id = evt.pdf_id);
+}
+
+/// OCRs the pages of `evt.reocr` again with [`ExtractionConfig::for_reocr`]
+/// and replaces a stored page only when the new result is more confident.
+///
+/// Publishes `text-extracted` afterwards, even when no page improved, so runs
+/// requested with the re-OCR (`run_state = 'pending'`) still start.
+async fn handle_reocr(
+    pool: &Pool,
+    producer: &FutureProducer,
+    evt: &PdfUploaded,
+    reocr: &ReocrPages,
+    ticket: &DocumentTicket,
+    config: &ExtractionConfig,
+) {
+    let mut client = match pool.get().await {
+        Ok(c) => c,
+        Err(e) => {
+            error!(%e, "db pool get failed");
+            return;
+        }
+    };
+    let row = match client.query_opt(LOAD_PDF_SQL, &[&evt.pdf_id]).await {
+        Ok(Some(r)) => r,
+        Ok(None) => {
+            error!(id = evt.pdf_id, "pdf row not found");
+            return;
+        }
+        Err(e) => {
+            error!(%e, "query pdf row failed");
+            return;
+        }
+    };
+    let Some(data) = row.get::<_, Option<Vec<u8>>>(0) else {
+        error!(id = evt.pdf_id, "pdf is archived");
+        return;
+    };
+    let config = match open_password(evt.pdf_id, row.get(2)) {
+        Some(password) => config.clone().password(password),
+        None => config.clone(),
+    }
+    .for_reocr();
+
+    // Gespeicherte Konfidenz zum Vergleich; Seiten ohne Zeile gibt es nicht (mehr)
+    let stored: HashMap<i32, Option<f32>> = match client
+        .query(
+            "SELECT page_no, ocr_confidence FROM pdf_texts
+              WHERE merged_pdf_id = $1 AND page_no = ANY($2)",
+            &[&evt.pdf_id, &reocr.pages],
+        )
+        .await
+    {
+        Ok(rows) => rows.iter().map(|r| (r.get(0), r.get(1))).collect(),
+        Err(e) => {
+            error!(%e, id = evt.pdf_id, "load page confidence failed");
+            return;
+        }
+    };
+    let pages: Vec<i32> = reocr
+        .pages
+        .iter()
+        .copied()
+        .filter(|page| stored.contains_key(page))
+        .collect();
+
+    let temp = match TempPdf::write(&data).await {
+        Ok(t) => t,
+        Err(e) => {
+            error!(%e, id = evt.pdf_id, "write temp pdf failed");
+            return;
+        }
+    };
+    let tx = match client.transaction().await {
+        Ok(t) => t,
+        Err(e) => {
+            error!(%e, "begin tx failed");
+            return;
+        }
+    };
+    let prepared = async {
+        Ok::<_, tokio_postgres::Error>((
+            tx.prepare(INSERT_PAGE_SQL).await?,
+            tx.prepare(page_texts::UPSERT_BLOB_SQL).await?,
+        ))
+    };
+    let (ins, blob) = match prepared.await {
+        Ok(s) => s,
+        Err(e) => {
+            error!(%e, "prepare insert failed");
+            let _ = tx.rollback().await;
+            return;
+        }
+    };
+
+    let entity_options = EntityOptions::from_env();
+    let mut replaced = Vec::new();
+    let mut kept = Vec::new();
+    let mut failure: Option<String> = None;
+    let mut stream =
+        pin!(extract_selected_pages_stream_scheduled(temp.path(), ticket, &pages, &config).fuse());
+    while let Some(page) = stream.next().await {
+        let page = match page {
+            Ok(page) => page,
+            Err(e) => {
+                failure = Some(format!("{e:#}"));
+                break;
+            }
+        };
+        let before = stored.get(&page.page_no).copied().flatten();
+        let after = page
+            .layout
+            .as_ref()
+            .and_then(|layout| layout.mean_confidence());
+        let detail = serde_json::json!({
+            "page_no": page.page_no,
+            "confidence_before": before,
+            "confidence_after": after,
+            "strategy": page.ocr_strategy,
+        });
+        if !improves(before, after) {
+            info!(
+                id = evt.pdf_id,
+                page_no = page.page_no,
+                ?before,
+                ?after,
+                "re-ocr kept page"
+            );
+            kept.push(detail);
+            continue;
+        }
+        let stored_page = async {
+            insert_page(&tx, &ins, &blob, evt.pdf_id, &page).await?;
+            if entity_options.enabled {
+                tx.execute(
+                    "DELETE FROM pdf_entities WHERE merged_pdf_id=$1 AND page_no=$2",
+                    &[&evt.pdf_id, &page.page_no],
+                )
+                .await?;
+                let found =
+                    entities::index_pages(evt.pdf_id, std::slice::from_ref(&page), &entity_options)
+                        .await;
+                for e in &found {
+                    let bbox = e.bbox.map(|b| Json(serde_json::json!(b)));
+                    tx.execute(
+                        shared::entities::INSERT_SQL,
+                        &[
+                            &e.merged_pdf_id,
+                            &e.page_no,
+                            &e.kind.as_str(),
+                            &e.value,
+                            &e.normalized,
+                            &bbox,
+                            &e.source,
+                        ],
+                    )
+                    .await?;
+                }
+            }
+            Ok::<_, tokio_postgres::Error>(())
+        };
+        if let Err(e) = stored_page.await {
+            failure = Some(e.to_string());
+            break;
+        }
+        info!(
+            id = evt.pdf_id,
+            page_no = page.page_no,
+            ?before,
+            ?after,
+            "re-ocr replaced page"
+        );
+        replaced.push(detail);
+    }
+    let committed = match failure {
+        None => tx.commit().await.map_err(|e| e.to_string()),
+        Some(e) => {
+            let _ = tx.rollback().await;
+            Err(e)
+        }
+    };
+    if let Err(e) = committed {
+        error!(id = evt.pdf_id, error = %e, "re-ocr failed");
+        timeline::record(
+            &client,
+            &TimelineEvent::new("text-extraction", "reocr_failed")
+                .pdf(Some(evt.pdf_id))
+                .message(e),
+        )
+        .await;
+        return;
+    }
+
+    let text =
+        match load_full_text(&client, evt.pdf_id, AttachmentOptions::from_env().enabled).await {
+            Ok(text) => text,
+            Err(e) => {
+                error!(%e, id = evt.pdf_id, "load stored pages failed");
+                return;
+            }
+        };
+    if !replaced.is_empty() {
+        store_signature(&client, evt.pdf_id, &text).await;
+    }
+    timeline::record(
+        &client,
+        &TimelineEvent::new("text-extraction", "reocr_completed")
+            .pdf(Some(evt.pdf_id))
+            .details(serde_json::json!({
+                "min_confidence": reocr.min_confidence,
+                "replaced": replaced,
+                "kept": kept,
+            })),
+    )
+    .await;
+    publish_text_extracted(producer, evt, text).await;
+    drop(temp);
+}
+
+/// A re-OCR result replaces the stored page only with a higher confidence.
+fn improves(before: Option<f32>, after: Option<f32>) -> bool {
+    match (before, after) {
+        (_, None) => false,
+        (None, Some(_)) => true,
+        (Some(before), Some(after)) => after > before,
+    }
+}
+
 #[actix_web::main]
 /// Boots the text extraction service and starts the Kafka loop.
 async fn main() -> std::io::Result<()> {
@@ -174,12 +1122,16 @@ async fn main() -> std::io::Result<()> {
                 "CREATE TABLE IF NOT EXISTS pdf_texts (
                     merged_pdf_id INTEGER NOT NULL,
                     page_no INTEGER NOT NULL,
-                    text TEXT NOT NULL,
+                    text TEXT,
                     ocr_used BOOLEAN NOT NULL DEFAULT false,
                     char_count INTEGER NOT NULL DEFAULT 0,
                     lang TEXT,
                     has_bbox BOOLEAN,
                     layout_json JSONB,
+                    ocr_strategy TEXT,
+                    quality_score REAL,
+                    ocr_confidence REAL,
+                    ocr_preprocessing TEXT,
                     UNIQUE (merged_pdf_id, page_no)
                  )",
                 &[],
@@ -195,9 +1147,22 @@ async fn main() -> std::io::Result<()> {
                 ALTER TABLE pdf_texts ADD COLUMN IF NOT EXISTS lang TEXT;
                 ALTER TABLE pdf_texts ADD COLUMN IF NOT EXISTS has_bbox BOOLEAN;
                 ALTER TABLE pdf_texts ADD COLUMN IF NOT EXISTS layout_json JSONB;
+                ALTER TABLE pdf_texts ADD COLUMN IF NOT EXISTS ocr_strategy TEXT;
+                ALTER TABLE pdf_texts ADD COLUMN IF NOT EXISTS quality_score REAL;
+                ALTER TABLE pdf_texts ADD COLUMN IF NOT EXISTS ocr_confidence REAL;
+                ALTER TABLE pdf_texts ADD COLUMN IF NOT EXISTS ocr_preprocessing TEXT;
                 ",
             )
             .await;
+        // Deduplizierte, komprimierte Seitentexte; lz4 nur, wenn der Server es kann
+        for sql in page_texts::SCHEMA_SQL {
+            if let Err(e) = client.batch_execute(sql).await {
+                warn!(%e, "page text schema failed");
+            }
+        }
+        if let Err(e) = client.batch_execute(page_texts::COMPRESSION_SQL).await {
+            warn!(%e, "lz4 page text compression not applied");
+        }
 
         // uploads (für Status-Update)
         let _ = client
@@ -211,6 +1176,43 @@ async fn main() -> std::io::Result<()> {
                 &[],
             )
             .await;
+        // versiegeltes Passwort geschützter PDFs (pdf-ingest, CREDENTIALS_MASTER_KEY)
+        let _ = client
+            .execute(
+                "ALTER TABLE uploads ADD COLUMN IF NOT EXISTS pdf_password JSONB",
+                &[],
+            )
+            .await;
+        // Entity-Index (optional, ENTITY_INDEX=1)
+        let _ = client
+            .execute(shared::entities::CREATE_TABLE_SQL, &[])
+            .await;
+        // Seiten-Cache (optional, EXTRACTION_CACHE=postgres)
+        let _ = client.execute(cache::CREATE_TABLE_SQL, &[]).await;
+        let _ = client
+            .execute(
+                "ALTER TABLE page_extraction_cache ADD COLUMN IF NOT EXISTS ocr_preprocessing TEXT",
+                &[],
+            )
+            .await;
+        // Texte eingebetteter PDFs (optional, EXTRACT_ATTACHMENTS=1)
+        let _ = client.execute(attachments::CREATE_TABLE_SQL, &[]).await;
+        // Signaturen für die Dublettenerkennung
+        for sql in similarity::SCHEMA_SQL {
+            let _ = client.batch_execute(sql).await;
+        }
+        // Dokument-Metadaten aus pdfinfo
+        let _ = client
+            .execute(shared::pdf_metadata::CREATE_TABLE_SQL, &[])
+            .await;
+        // Durchsuchbare Kopie gescannter PDFs (optional, SEARCHABLE_PDF=1)
+        let _ = client.execute(searchable::SCHEMA_SQL, &[]).await;
+        // Verkleinerte Seitenbilder für Vorschauen (optional, PAGE_IMAGE_CACHE=1)
+        let _ = client.execute(page_images::SCHEMA_SQL, &[]).await;
+        // Wiederholungen fehlgeschlagener Extraktionen
+        for sql in retries::SCHEMA_SQL {
+            let _ = client.execute(sql, &[]).await;
+        }
     }
 
     // Kafka Consumer/Producer
@@ -240,237 +1242,139 @@ async fn main() -> std::io::Result<()> {
     let db_pool = web::Data::new(pool.clone());
     let producer_http = web::Data::new(producer.clone());
 
-    // Kafka-Loop
+    // Kafka-Loop: bis zu EXTRACTION_MAX_DOCUMENTS Dokumente gleichzeitig, Seiten über den Scheduler
     {
+        let sched_cfg = SchedulerConfig::from_env();
+        info!(
+            mode = ?sched_cfg.mode,
+            text_slots = sched_cfg.text_slots,
+            page_slots = sched_cfg.page_slots,
+            interactive_slots = sched_cfg.lanes.interactive,
+            batch_slots = sched_cfg.lanes.batch,
+            max_documents = sched_cfg.max_documents,
+            "extraction scheduler configured"
+        );
+        let scheduler = PageScheduler::with_lanes(
+            sched_cfg.mode,
+            sched_cfg.text_slots,
+            sched_cfg.page_slots,
+            sched_cfg.lanes,
+        );
+        let documents = Arc::new(Semaphore::new(sched_cfg.max_documents));
+        let mut extraction = ExtractionConfig::from_env();
+        if let Some(cache) = cache::cache_from_env(&pool) {
+            info!(?cache, "page extraction cache enabled");
+            extraction = extraction.cache(cache);
+        }
+        let extraction = Arc::new(extraction);
+        let offsets = Arc::new(Mutex::new(OffsetTracker::default()));
+        // Events eines Dokuments (Re-OCR, Anhängen, Retries) nacheinander in Eingangsreihenfolge
+        let sequencer = kafka::KeySequencer::new();
+        let consumer = Arc::new(consumer);
         let pool_consume = pool.clone();
         let producer_consume = producer.clone();
         tokio::spawn(async move {
             info!("starting kafka consume loop");
             loop {
+                let Ok(permit) = documents.clone().acquire_owned().await else {
+                    break;
+                };
                 match consumer.recv().await {
                     Err(e) => error!(%e, "kafka error"),
                     Ok(m) => {
-                        if let Some(Ok(payload)) = m.payload_view::<str>() {
-                            match serde_json::from_str::<PdfUploaded>(payload) {
-                                Ok(evt) => {
-                                    info!(id = evt.pdf_id, "received pdf-merged event");
-
-                                    let mut client = match pool_consume.get().await {
-                                        Ok(c) => c,
-                                        Err(e) => {
-                                            error!(%e, "db pool get failed");
-                                            continue;
-                                        }
-                                    };
-
-                                    let row = match client
-                                        .query_opt(
-                                            "SELECT data FROM merged_pdfs WHERE id = $1",
-                                            &[&evt.pdf_id],
-                                        )
-                                        .await
-                                    {
-                                        Ok(Some(r)) => r,
-                                        Ok(None) => {
-                                            error!(id = evt.pdf_id, "pdf row not found");
-                                            continue;
-                                        }
-                                        Err(e) => {
-                                            error!(%e, "query pdf row failed");
-                                            continue;
-                                        }
-                                    };
-                                    let data: Vec<u8> = row.get(0);
-
-                                    // temporäre Datei
-                                    let path = format!("/tmp/pdf_{}.pdf", evt.pdf_id);
-                                    if let Err(e) = tokio::fs::write(&path, &data).await {
-                                        error!(%e, id = evt.pdf_id, "write temp pdf failed");
-                                        continue;
-                                    }
-                                    info!(
-                                        step = "tempfile.write.ok",
-                                        id = evt.pdf_id,
-                                        path = %path,
-                                        bytes = data.len(),
-                                        "temp pdf written"
-                                    );
-
-                                    // Seiten extrahieren
-                                    let pages = match extract_text_pages(&path).await {
-                                        Ok(v) => v,
-                                        Err(e) => {
-                                            error!(%e, id = evt.pdf_id, "text extraction failed");
-                                            let _ = tokio::fs::remove_file(&path).await;
-                                            continue;
-                                        }
-                                    };
-                                    let concat = pages
-                                        .iter()
-                                        .map(|p| p.text.as_str())
-                                        .collect::<Vec<_>>()
-                                        .join("\n")
-                                        .to_lowercase();
-
-                                    // Transaktion: alte Seiten löschen, neue speichern
-                                    let tx = match client.transaction().await {
-                                        Ok(t) => t,
-                                        Err(e) => {
-                                            error!(%e, "begin tx failed");
-                                            let _ = tokio::fs::remove_file(&path).await;
-                                            continue;
-                                        }
-                                    };
-                                    if let Err(e) = tx
-                                        .execute(
-                                            "DELETE FROM pdf_texts WHERE merged_pdf_id=$1",
-                                            &[&evt.pdf_id],
-                                        )
-                                        .await
-                                    {
-                                        error!(%e, "delete old pages failed");
-                                        let _ = tx.rollback().await;
-                                        let _ = tokio::fs::remove_file(&path).await;
-                                        continue;
-                                    }
-                                    let ins = match tx
-                                        .prepare(
-                                            "INSERT INTO pdf_texts (
-                                                merged_pdf_id, page_no, text, ocr_used, char_count, lang, has_bbox, layout_json
-                                             ) VALUES ($1,$2,$3,$4,$5,$6::text,$7::bool,$8::jsonb)
-                                             ON CONFLICT (merged_pdf_id, page_no)
-                                             DO UPDATE SET text=EXCLUDED.text,
-                                                           ocr_used=EXCLUDED.ocr_used,
-                                                           char_count=EXCLUDED.char_count,
-                                                           lang=EXCLUDED.lang,
-                                                           has_bbox=EXCLUDED.has_bbox,
-                                                           layout_json=EXCLUDED.layout_json"
-                                        )
+                        let Some(Ok(payload)) = m.payload_view::<str>() else {
+                            continue;
+                        };
+                        let evt = match serde_json::from_str::<PdfUploaded>(payload) {
+                            Ok(evt) => evt,
+                            Err(e) => {
+                                error!(%e, "failed to parse pdf-merged payload");
+                                continue;
+                            }
+                        };
+                        info!(
+                            id = evt.pdf_id,
+                            priority = %evt.priority,
+                            lane = ?Lane::for_priority(evt.priority),
+                            "received pdf-merged event"
+                        );
+
+                        // Registrierung in Eingangsreihenfolge bestimmt die FIFO-Position;
+                        // wartende Folge-Events registrieren sich erst, wenn sie an der Reihe sind
+                        let mut turn = sequencer.enter(&kafka::document_key(evt.pdf_id));
+                        let ticket = turn.is_first().then(|| scheduler.register(evt.priority));
+                        let scheduler = scheduler.clone();
+                        let (topic, partition, offset) =
+                            (m.topic().to_string(), m.partition(), m.offset());
+                        offsets.lock().unwrap().begin(&topic, partition, offset);
+
+                        let pool = pool_consume.clone();
+                        let producer = producer_consume.clone();
+                        let consumer = consumer.clone();
+                        let offsets = offsets.clone();
+                        let extraction = extraction.clone();
+                        tokio::spawn(async move {
+                            turn.wait().await;
+                            let ticket = ticket.unwrap_or_else(|| scheduler.register(evt.priority));
+                            match &evt.reocr {
+                                Some(reocr) => {
+                                    handle_reocr(
+                                        &pool,
+                                        &producer,
+                                        &evt,
+                                        reocr,
+                                        &ticket,
+                                        &extraction,
+                                    )
+                                    .await
+                                }
+                                None => {
+                                    handle_pdf_merged(&pool, &producer, &evt, &ticket, &extraction)
                                         .await
-                                    {
-                                        Ok(s) => s,
-                                        Err(e) => {
-                                            error!(%e, "prepare insert failed");
-                                            let _ = tx.rollback().await;
-                                            let _ = tokio::fs::remove_file(&path).await;
-                                            continue;
-                                        }
-                                    };
-                                    let mut ok = true;
-                                    for page in pages {
-                                        let normalized_text = page.text.to_lowercase();
-                                        let char_count: i32 = normalized_text
-                                            .chars()
-                                            .filter(|c| !c.is_whitespace())
-                                            .count()
-                                            as i32;
-                                        let lang: Option<&str> = None;
-                                        let has_bbox = page
-                                            .layout
-                                            .as_ref()
-                                            .map(|layout| !layout.words.is_empty());
-                                        let layout_value: Option<Json<serde_json::Value>> = page
-                                            .layout
-                                            .as_ref()
-                                            .map(|layout| serde_json::to_value(layout).map(Json))
-                                            .transpose()
-                                            .map_err(|e| {
-                                                warn!(page = page.page_no, %e, "serialize layout failed");
-                                                e
-                                            })
-                                            .ok()
-                                            .flatten();
-
-                                        if let Err(e) = tx
-                                            .execute(
-                                                &ins,
-                                                &[
-                                                    &evt.pdf_id,
-                                                    &page.page_no,
-                                                    &normalized_text,
-                                                    &page.ocr_used,
-                                                    &char_count,
-                                                    &lang,
-                                                    &has_bbox,
-                                                    &layout_value,
-                                                ],
-                                            )
-                                            .await
-                                        {
-                                            error!(%e, page_no = page.page_no, "insert page failed");
-                                            ok = false;
-                                            break;
-                                        }
-                                    }
-                                    if ok {
-                                        if let Err(e) = tx.commit().await {
-                                            error!(%e, "commit failed");
-                                            ok = false;
-                                        }
-                                    } else {
-                                        let _ = tx.rollback().await;
-                                    }
-                                    if !ok {
-                                        let _ = tokio::fs::remove_file(&path).await;
-                                        continue;
-                                    }
-                                    info!(id = evt.pdf_id, "stored per-page text");
-
-                                    // Upload-Status aktualisieren (best effort)
-                                    let _ = client
-                                        .execute(
-                                            "UPDATE uploads SET status='ready' WHERE pdf_id=$1",
-                                            &[&evt.pdf_id],
-                                        )
-                                        .await;
-
-                                    // Event publizieren
-                                    let out = TextExtracted {
-                                        pdf_id: evt.pdf_id,
-                                        pipeline_id: evt.pipeline_id,
-                                        text: concat,
-                                    };
-                                    if let Ok(payload) = serde_json::to_string(&out) {
-                                        let _ = producer_consume
-                                            .send(
-                                                FutureRecord::to("text-extracted")
-                                                    .payload(&payload)
-                                                    .key(&()),
-                                                Duration::from_secs(0),
-                                            )
-                                            .await;
-                                        info!(
-                                            step = "kafka.produce.ok",
-                                            topic = "text-extracted",
-                                            id = out.pdf_id
-                                        );
-                                    }
-
-                                    // Commit Kafka offset
-                                    if let Err(e) = consumer.commit_message(&m, CommitMode::Async) {
-                                        error!(%e, "commit failed");
-                                    } else {
-                                        info!(step = "kafka.commit.ok", id = evt.pdf_id);
-                                    }
-
-                                    // Cleanup
-                                    let _ = tokio::fs::remove_file(&path).await;
-                                    info!(step = "tempfile.cleanup.ok", path = %path);
                                 }
-                                Err(e) => error!(%e, "failed to parse pdf-merged payload"),
                             }
-                        }
+                            drop(ticket);
+                            drop(turn);
+
+                            // Nur bis zum ältesten noch laufenden Dokument committen
+                            let commit_offset =
+                                offsets.lock().unwrap().finish(&topic, partition, offset);
+                            let mut tpl = TopicPartitionList::new();
+                            let committed = tpl
+                                .add_partition_offset(
+                                    &topic,
+                                    partition,
+                                    Offset::Offset(commit_offset),
+                                )
+                                .and_then(|_| consumer.commit(&tpl, CommitMode::Async));
+                            match committed {
+                                Ok(()) => info!(
+                                    step = "kafka.commit.ok",
+                                    id = evt.pdf_id,
+                                    offset = commit_offset
+                                ),
+                                Err(e) => error!(%e, "commit failed"),
+                            }
+                            drop(permit);
+                        });
                     }
                 }
             }
         });
     }
 
+    tokio::spawn(retries::run(
+        pool.clone(),
+        producer.clone(),
+        RetryConfig::from_env(),
+    ));
+
     // HTTP-Server
     info!("starting http server on port 8083");
+    let cors = CorsSettings::from_env();
     HttpServer::new(move || {
         App::new()
-            .wrap(Cors::permissive())
+            .wrap(cors.actix())
             .app_data(db_pool.clone())
             .app_data(producer_http.clone())
             .route("/health", web::get().to(health))
@@ -0,0 +1,176 @@
+//! Document metadata via `pdfinfo`.
+//!
+//! [`extract_metadata`] runs `pdfinfo -isodates` over all pages and parses the
+//! information dictionary (title, author, dates, ...), the encryption, form and
+//! tagging flags and the size of every page into [`PdfMetadata`]. Empty entries
+//! are left out, `pdfinfo` only prints what the document contains.
+
+use anyhow::{Context, Result};
+use shared::pdf_metadata::{PageSize, PdfMetadata};
+
+use crate::encryption;
+use crate::sandbox::{self, Tool};
+use crate::ExtractionConfig;
+
+/// Reads the metadata of the PDF at `path`, with the password of `config`.
+pub async fn extract_metadata(path: &str, config: &ExtractionConfig) -> Result<PdfMetadata> {
+    // pdfinfo kürzt -l auf die Seitenzahl
+    let last = i32::MAX.to_string();
+    let mut args = encryption::password_args(config.password.as_ref());
+    args.extend(["-isodates", "-f", "1", "-l", &last, path]);
+    let output = sandbox::run(Tool::Pdfinfo, args)
+        .await
+        .context("pdfinfo metadata")?;
+    Ok(parse_pdfinfo(&String::from_utf8_lossy(&output.stdout)))
+}
+
+/// Parses the output of `pdfinfo` (with or without `-f`/`-l`).
+pub fn parse_pdfinfo(output: &str) -> PdfMetadata {
+    let mut meta = PdfMetadata::default();
+    for line in output.lines() {
+        if let Some(rest) = line.strip_prefix("Page ") {
+            parse_page_line(rest, &mut meta.page_sizes);
+            continue;
+        }
+        let Some((key, value)) = line.split_once(':') else {
+            continue;
+        };
+        let value = value.trim();
+        let text = (!value.is_empty()).then(|| value.to_string());
+        match key.trim() {
+            "Title" => meta.title = text,
+            "Subject" => meta.subject = text,
+            "Keywords" => meta.keywords = text,
+            "Author" => meta.author = text,
+            "Creator" => meta.creator = text,
+            "Producer" => meta.producer = text,
+            "CreationDate" => meta.creation_date = text,
+            "ModDate" => meta.mod_date = text,
+            "PDF version" => meta.pdf_version = text,
+            "Pages" => meta.page_count = value.parse().unwrap_or(0),
+            // "yes (print:yes copy:no ...)"
+            "Encrypted" => {
+                meta.encrypted = value.starts_with("yes");
+                meta.copy_forbidden = meta.encrypted && value.contains("copy:no");
+            }
+            "Form" => meta.form = text.filter(|f| !f.eq_ignore_ascii_case("none")),
+            "Tagged" => meta.tagged = value == "yes",
+            _ => {}
+        }
+    }
+    meta
+}
+
+/// `    1 size: 595.276 x 841.89 pts (A4)`, `    1 rot:  90`, or without the
+/// number for the first page (`pdfinfo` without `-f`/`-l`).
+fn parse_page_line(rest: &str, sizes: &mut Vec<PageSize>) {
+    let mut parts = rest.split_whitespace().peekable();
+    let page: i32 = match parts.peek().and_then(|p| p.parse().ok()) {
+        Some(page) => {
+            parts.next();
+            page
+        }
+        None => 1,
+    };
+    match parts.next() {
+        Some("size:") => {
+            let width = parts.next().and_then(|w| w.parse::<f64>().ok());
+            let height = parts.nth(1).and_then(|h| h.parse::<f64>().ok());
+            if let (Some(width), Some(height)) = (width, height) {
+                sizes.push(PageSize {
+                    page_no: page - 1,
+                    width,
+                    height,
+                    rotation: 0,
+                });
+            }
+        }
+        Some("rot:") => {
+            let Some(degrees) = parts.next().and_then(|d| d.parse::<i32>().ok()) else {
+                return;
+            };
+            if let Some(size) = sizes.iter_mut().rev().find(|s| s.page_no == page - 1) {
+                size.rotation = degrees.rem_euclid(360) / 90 * 90;
+            }
+        }
+        _ => {}
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const PDFINFO: &str = "Title:          Kaufvertrag: Wohnung 3.OG\n\
+        Author:         Notariat Dr. Weber\n\
+        Creator:        Microsoft Word\n\
+        Producer:       Adobe PDF Library 15.0\n\
+        CreationDate:   2024-03-01T09:30:00+01:00\n\
+        ModDate:        2024-03-02T10:00:00+01:00\n\
+        Custom Metadata: no\n\
+        Metadata Stream: yes\n\
+        Tagged:         yes\n\
+        UserProperties: no\n\
+        Suspects:       no\n\
+        Form:           AcroForm\n\
+        JavaScript:     no\n\
+        Pages:          2\n\
+        Encrypted:      yes (print:yes copy:no change:no addNotes:no algorithm:AES-256)\n\
+        Page    1 size: 595.276 x 841.89 pts (A4)\n\
+        Page    1 rot:  0\n\
+        Page    2 size: 612 x 792 pts (letter)\n\
+        Page    2 rot:  270\n\
+        File size:      48213 bytes\n\
+        Optimized:      no\n\
+        PDF version:    1.7\n";
+
+    #[test]
+    fn parses_full_pdfinfo_output() {
+        let meta = parse_pdfinfo(PDFINFO);
+        assert_eq!(meta.title.as_deref(), Some("Kaufvertrag: Wohnung 3.OG"));
+        assert_eq!(meta.author.as_deref(), Some("Notariat Dr. Weber"));
+        assert_eq!(meta.producer.as_deref(), Some("Adobe PDF Library 15.0"));
+        assert_eq!(
+            meta.creation_date.as_deref(),
+            Some("2024-03-01T09:30:00+01:00")
+        );
+        assert_eq!(meta.pdf_version.as_deref(), Some("1.7"));
+        assert_eq!(meta.page_count, 2);
+        assert!(meta.encrypted);
+        assert!(meta.copy_forbidden);
+        assert!(meta.tagged);
+        assert_eq!(meta.form.as_deref(), Some("AcroForm"));
+        assert!(meta.subject.is_none());
+        assert_eq!(
+            meta.page_sizes,
+            vec![
+                PageSize {
+                    page_no: 0,
+                    width: 595.276,
+                    height: 841.89,
+                    rotation: 0,
+                },
+                PageSize {
+                    page_no: 1,
+                    width: 612.0,
+                    height: 792.0,
+                    rotation: 270,
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn parses_single_page_summary() {
+        let meta = parse_pdfinfo(
+            "Producer:       scanner\nForm:           none\nPages:          4\n\
+             Encrypted:      no\nPage size:      595 x 842 pts (A4)\nPage rot:       90\n",
+        );
+        assert!(!meta.encrypted);
+        assert!(!meta.copy_forbidden);
+        assert!(!meta.has_form());
+        assert_eq!(meta.page_count, 4);
+        assert_eq!(meta.page_sizes.len(), 1);
+        assert_eq!(meta.page_sizes[0].rotation, 90);
+    }
+}
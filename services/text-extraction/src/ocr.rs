@@ -0,0 +1,301 @@
+//! OCR engines recognizing one rendered page image.
+//!
+//! [`TesseractCli`] runs the `tesseract` binary per page (default).
+//! [`Leptess`] (cargo feature `leptess`) keeps Tesseract in-process and reuses
+//! one initialized instance per worker thread and language, which saves the
+//! process start and model loading for every page. `OCR_ENGINE` selects the
+//! engine of the service; library callers pass their own, e.g. a mock, via
+//! [`crate::ExtractionConfig::ocr_engine`].
+
+use std::{ffi::OsStr, fmt, future::Future, path::Path, pin::Pin, sync::Arc};
+
+#[cfg(feature = "leptess")]
+use anyhow::anyhow;
+use anyhow::{Context, Result};
+#[cfg(feature = "leptess")]
+use tokio::time::timeout;
+use tracing::warn;
+
+use crate::sandbox::{self, Tool};
+
+/// Text of a page and, when requested, its hOCR for the layout.
+#[derive(Clone, Debug, Default)]
+pub struct OcrOutput {
+    pub text: String,
+    pub hocr: Option<String>,
+}
+
+/// Settings of one recognition call.
+#[derive(Clone, Copy, Debug)]
+pub struct OcrRequest<'a> {
+    /// Rendered page (PNG).
+    pub image: &'a Path,
+    /// Tesseract language(s), e.g. `deu+eng`.
+    pub lang: &'a str,
+    /// Page segmentation mode.
+    pub psm: &'a str,
+    /// Also produce hOCR.
+    pub capture_layout: bool,
+}
+
+pub type OcrFuture<'a> = Pin<Box<dyn Future<Output = Result<OcrOutput>> + Send + 'a>>;
+pub type OrientationFuture<'a> = Pin<Box<dyn Future<Output = Result<Option<i32>>> + Send + 'a>>;
+
+/// Minimum OSD orientation confidence for a detected rotation to be used.
+const MIN_OSD_CONFIDENCE: f32 = 1.5;
+
+pub trait OcrEngine: Send + Sync + fmt::Debug {
+    /// Name used in logs and `OCR_ENGINE`.
+    fn name(&self) -> &'static str;
+
+    fn recognize<'a>(&'a self, request: OcrRequest<'a>) -> OcrFuture<'a>;
+
+    /// Clockwise rotation (0, 90, 180, 270) that makes the page upright;
+    /// `None` when the engine cannot tell.
+    fn detect_orientation<'a>(&'a self, _image: &'a Path) -> OrientationFuture<'a> {
+        Box::pin(async { Ok(None) })
+    }
+}
+
+/// Engine for `OCR_ENGINE` (`tesseract` or `leptess`); unknown or unavailable
+/// engines fall back to the `tesseract` binary.
+pub fn engine_from_name(name: &str) -> Arc<dyn OcrEngine> {
+    match name.trim().to_ascii_lowercase().as_str() {
+        "" | "tesseract" | "cli" => Arc::new(TesseractCli),
+        #[cfg(feature = "leptess")]
+        "leptess" => Arc::new(Leptess),
+        other => {
+            warn!(
+                engine = other,
+                "ocr engine not available, using tesseract binary"
+            );
+            Arc::new(TesseractCli)
+        }
+    }
+}
+
+/// Runs the `tesseract` binary once for the text and once more for hOCR.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct TesseractCli;
+
+impl TesseractCli {
+    async fn run(&self, request: &OcrRequest<'_>, hocr: bool) -> Result<Option<String>> {
+        let mut args: Vec<&OsStr> = vec![
+            request.image.as_os_str(),
+            "stdout".as_ref(),
+            "-l".as_ref(),
+            request.lang.as_ref(),
+            "--psm".as_ref(),
+            request.psm.as_ref(),
+        ];
+        if hocr {
+            args.push("hocr".as_ref());
+        }
+        let output = match sandbox::run(Tool::Tesseract, args).await {
+            Ok(output) => output,
+            Err(err) if hocr && sandbox::is_exit_failure(&err) => return Ok(None),
+            Err(err) => return Err(err.into()),
+        };
+        String::from_utf8(output.stdout)
+            .context("invalid utf8 from tesseract")
+            .map(Some)
+    }
+}
+
+/// Reads `Rotate:` of `tesseract --psm 0`; ignored below [`MIN_OSD_CONFIDENCE`].
+fn parse_osd(output: &str) -> Option<i32> {
+    let value = |key: &str| {
+        output
+            .lines()
+            .find_map(|l| l.trim().strip_prefix(key))
+            .map(str::trim)
+    };
+    let rotate = value("Rotate:")?.parse::<i32>().ok()?;
+    let confidence = value("Orientation confidence:")?.parse::<f32>().ok()?;
+    (confidence >= MIN_OSD_CONFIDENCE).then_some(rotate.rem_euclid(360) / 90 * 90)
+}
+
+impl OcrEngine for TesseractCli {
+    fn name(&self) -> &'static str {
+        "tesseract"
+    }
+
+    fn detect_orientation<'a>(&'a self, image: &'a Path) -> OrientationFuture<'a> {
+        Box::pin(async move {
+            let args = [
+                image.as_os_str(),
+                "stdout".as_ref(),
+                "--psm".as_ref(),
+                "0".as_ref(),
+            ];
+            let output = match sandbox::run(Tool::Tesseract, args).await {
+                Ok(output) => output,
+                // Zu wenig Text für OSD beendet tesseract mit Fehler → keine Aussage
+                Err(err) if sandbox::is_exit_failure(&err) => return Ok(None),
+                Err(err) => return Err(err).context("tesseract osd"),
+            };
+            Ok(parse_osd(&String::from_utf8_lossy(&output.stdout)))
+        })
+    }
+
+    fn recognize<'a>(&'a self, request: OcrRequest<'a>) -> OcrFuture<'a> {
+        Box::pin(async move {
+            let text = self.run(&request, false).await?.unwrap_or_default();
+            let hocr = if request.capture_layout {
+                let hocr = self.run(&request, true).await?;
+                if hocr.is_none() {
+                    warn!(image = ?request.image, "tesseract hocr failed");
+                }
+                hocr
+            } else {
+                None
+            };
+            Ok(OcrOutput { text, hocr })
+        })
+    }
+}
+
+/// In-process Tesseract via `leptess`; recognition runs on the blocking pool.
+#[cfg(feature = "leptess")]
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Leptess;
+
+#[cfg(feature = "leptess")]
+impl Leptess {
+    fn recognize_blocking(
+        image: &Path,
+        lang: &str,
+        psm: &str,
+        capture_layout: bool,
+    ) -> Result<OcrOutput> {
+        use std::{cell::RefCell, collections::HashMap};
+
+        use leptess::{LepTess, Variable};
+
+        thread_local! {
+            // Initialisierung lädt die Sprachmodelle – einmal je Thread und Sprache
+            static INSTANCES: RefCell<HashMap<String, LepTess>> = RefCell::new(HashMap::new());
+        }
+
+        INSTANCES.with(|instances| {
+            let mut instances = instances.borrow_mut();
+            if !instances.contains_key(lang) {
+                let tess = LepTess::new(None, lang)
+                    .map_err(|e| anyhow!("tesseract init for {lang}: {e}"))?;
+                instances.insert(lang.to_string(), tess);
+            }
+            let tess = instances.get_mut(lang).expect("instance inserted above");
+            tess.set_variable(Variable::TesseditPagesegMode, psm)
+                .map_err(|e| anyhow!("set psm {psm}: {e}"))?;
+            tess.set_image(image)
+                .map_err(|e| anyhow!("load page image: {e}"))?;
+            let text = tess
+                .get_utf8_text()
+                .context("invalid utf8 from tesseract")?;
+            let hocr = if capture_layout {
+                match tess.get_hocr_text(0) {
+                    Ok(hocr) => Some(hocr),
+                    Err(e) => {
+                        warn!(error = %e, "tesseract hocr failed");
+                        None
+                    }
+                }
+            } else {
+                None
+            };
+            Ok(OcrOutput { text, hocr })
+        })
+    }
+}
+
+#[cfg(feature = "leptess")]
+impl OcrEngine for Leptess {
+    fn name(&self) -> &'static str {
+        "leptess"
+    }
+
+    fn recognize<'a>(&'a self, request: OcrRequest<'a>) -> OcrFuture<'a> {
+        let image = request.image.to_path_buf();
+        let lang = request.lang.to_string();
+        let psm = request.psm.to_string();
+        let capture_layout = request.capture_layout;
+        Box::pin(async move {
+            timeout(
+                sandbox::limits().timeout,
+                tokio::task::spawn_blocking(move || {
+                    Self::recognize_blocking(&image, &lang, &psm, capture_layout)
+                }),
+            )
+            .await
+            .context("timeout running leptess")?
+            .map_err(|e| anyhow!("leptess task failed: {e}"))?
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Mutex;
+
+    use super::*;
+
+    #[derive(Debug, Default)]
+    struct MockEngine {
+        calls: Mutex<Vec<(String, String, bool)>>,
+    }
+
+    impl OcrEngine for MockEngine {
+        fn name(&self) -> &'static str {
+            "mock"
+        }
+
+        fn recognize<'a>(&'a self, request: OcrRequest<'a>) -> OcrFuture<'a> {
+            self.calls.lock().unwrap().push((
+                request.lang.to_string(),
+                request.psm.to_string(),
+                request.capture_layout,
+            ));
+            Box::pin(async move {
+                Ok(OcrOutput {
+                    text: format!("psm {}", request.psm),
+                    hocr: request.capture_layout.then(String::new),
+                })
+            })
+        }
+    }
+
+    #[tokio::test]
+    async fn engines_are_interchangeable() {
+        let mock = Arc::new(MockEngine::default());
+        let engine: Arc<dyn OcrEngine> = mock.clone();
+        let request = OcrRequest {
+            image: Path::new("/tmp/page.png"),
+            lang: "deu",
+            psm: "4",
+            capture_layout: true,
+        };
+        let output = engine.recognize(request).await.unwrap();
+        assert_eq!(output.text, "psm 4");
+        assert_eq!(output.hocr.as_deref(), Some(""));
+        assert_eq!(
+            *mock.calls.lock().unwrap(),
+            vec![("deu".to_string(), "4".to_string(), true)]
+        );
+
+        assert_eq!(engine_from_name("").name(), "tesseract");
+        assert_eq!(engine_from_name("unknown").name(), "tesseract");
+        assert_eq!(
+            engine.detect_orientation(request.image).await.unwrap(),
+            None
+        );
+    }
+
+    #[test]
+    fn parse_osd_requires_confidence() {
+        let osd = "Page number: 0\nOrientation in degrees: 270\nRotate: 90\n\
+                   Orientation confidence: 7.42\nScript: Latin\nScript confidence: 3.10\n";
+        assert_eq!(parse_osd(osd), Some(90));
+        assert_eq!(parse_osd(&osd.replace("7.42", "0.40")), None);
+        assert_eq!(parse_osd("Rotate: 0\n"), None);
+    }
+}
@@ -0,0 +1,205 @@
+//! Downscaled page images kept from the OCR render (`pdf_page_images`).
+//!
+//! OCR renders every page it recognizes at `OCR_DPI`. With
+//! [`crate::ExtractionConfig::page_images`] (`PAGE_IMAGE_CACHE=1`) the first
+//! render of a page is also scaled down to at most `PAGE_IMAGE_MAX_PX` on the
+//! longer side and returned as PNG in [`crate::PageExtraction::image`]; the
+//! service stores it next to the page text. pdf-ingest serves the images under
+//! `GET /pdf/{id}/pages/{page}/image` and renders pages without OCR on the
+//! first request ([`render_preview`]), so every page is rendered for previews
+//! at most once. Steps that send page images to a model read the same table.
+
+use std::{
+    env, fmt,
+    future::Future,
+    sync::{Arc, Mutex},
+};
+
+use anyhow::{anyhow, Context, Result};
+use image::{imageops::FilterType, ImageFormat};
+use tracing::warn;
+
+use crate::{render_page, ExtractionConfig, PdfPassword};
+
+/// Creates `pdf_page_images` (see migration 0051).
+pub const SCHEMA_SQL: &str = "CREATE TABLE IF NOT EXISTS pdf_page_images (
+    merged_pdf_id INTEGER NOT NULL REFERENCES merged_pdfs(id) ON DELETE CASCADE,
+    page_no INTEGER NOT NULL,
+    width INTEGER NOT NULL,
+    height INTEGER NOT NULL,
+    png BYTEA NOT NULL,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT now(),
+    PRIMARY KEY (merged_pdf_id, page_no)
+)";
+
+/// Stores one image (`$1` pdf, `$2` page, `$3` width, `$4` height, `$5` PNG).
+pub const UPSERT_SQL: &str =
+    "INSERT INTO pdf_page_images (merged_pdf_id, page_no, width, height, png)
+    VALUES ($1, $2, $3, $4, $5)
+    ON CONFLICT (merged_pdf_id, page_no) DO UPDATE
+       SET width = EXCLUDED.width, height = EXCLUDED.height,
+           png = EXCLUDED.png, created_at = now()";
+
+/// Longer side of the stored images without `PAGE_IMAGE_MAX_PX`.
+pub const DEFAULT_MAX_PX: u32 = 1600;
+
+/// Resolution of [`render_preview`]; an A4 page gets about 1750 px.
+const PREVIEW_DPI: u32 = 150;
+
+/// `PAGE_IMAGE_MAX_PX`, default [`DEFAULT_MAX_PX`].
+pub fn max_px_from_env() -> u32 {
+    env::var("PAGE_IMAGE_MAX_PX")
+        .ok()
+        .and_then(|v| v.trim().parse::<u32>().ok())
+        .filter(|v| *v >= 64)
+        .unwrap_or(DEFAULT_MAX_PX)
+}
+
+#[derive(Clone)]
+/// PNG of a page, scaled down for previews.
+pub struct PageImage {
+    pub width: u32,
+    pub height: u32,
+    pub png: Vec<u8>,
+}
+
+impl fmt::Debug for PageImage {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("PageImage")
+            .field("width", &self.width)
+            .field("height", &self.height)
+            .field("bytes", &self.png.len())
+            .finish()
+    }
+}
+
+/// Scales an image down to at most `max_px` on the longer side (smaller
+/// images keep their size) and encodes it as PNG.
+pub fn downscale(data: &[u8], max_px: u32) -> Result<PageImage> {
+    let img = image::load_from_memory(data).context("decode page image")?;
+    let img = if img.width().max(img.height()) > max_px {
+        img.resize(max_px, max_px, FilterType::Triangle)
+    } else {
+        img
+    };
+    let mut png = Vec::new();
+    img.write_to(&mut std::io::Cursor::new(&mut png), ImageFormat::Png)
+        .context("encode page image")?;
+    Ok(PageImage {
+        width: img.width(),
+        height: img.height(),
+        png,
+    })
+}
+
+/// Renders the 1-based `page` for a preview, without OCR.
+pub async fn render_preview(
+    path: &str,
+    page: i32,
+    max_px: u32,
+    password: Option<&PdfPassword>,
+) -> Result<PageImage> {
+    let image = render_page(path, page, PREVIEW_DPI, password).await?;
+    let data = tokio::fs::read(&image.path)
+        .await
+        .context("read rendered page")?;
+    tokio::task::spawn_blocking(move || downscale(&data, max_px))
+        .await
+        .map_err(|e| anyhow!("downscale task failed: {e}"))?
+}
+
+tokio::task_local! {
+    static CAPTURED: Arc<Mutex<Option<PageImage>>>;
+}
+
+/// Runs the OCR of one page and returns the image [`capture`] kept from it;
+/// without `page_images` the future runs unchanged.
+pub(crate) async fn capturing<F: Future>(
+    config: &ExtractionConfig,
+    fut: F,
+) -> (F::Output, Option<PageImage>) {
+    if config.page_image_max_px.is_none() {
+        return (fut.await, None);
+    }
+    let slot = Arc::new(Mutex::new(None));
+    let output = CAPTURED.scope(slot.clone(), fut).await;
+    let image = slot.lock().ok().and_then(|mut image| image.take());
+    (output, image)
+}
+
+/// Keeps a downscaled copy of the rendered page at `path` for the surrounding
+/// [`capturing`]; only the first render counts, failures are logged.
+pub(crate) async fn capture(path: &str, config: &ExtractionConfig) {
+    let Some(max_px) = config.page_image_max_px else {
+        return;
+    };
+    let Ok(slot) = CAPTURED.try_with(Arc::clone) else {
+        return;
+    };
+    if slot.lock().map_or(true, |image| image.is_some()) {
+        return;
+    }
+    let data = match tokio::fs::read(path).await {
+        Ok(data) => data,
+        Err(e) => {
+            warn!(%e, path, "read page image for cache failed");
+            return;
+        }
+    };
+    match tokio::task::spawn_blocking(move || downscale(&data, max_px)).await {
+        Ok(Ok(image)) => {
+            if let Ok(mut slot) = slot.lock() {
+                slot.get_or_insert(image);
+            }
+        }
+        Ok(Err(e)) => warn!(%e, "downscale page image failed"),
+        Err(e) => warn!(%e, "downscale task failed"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use image::{DynamicImage, GrayImage};
+
+    fn png(width: u32, height: u32) -> Vec<u8> {
+        let mut data = Vec::new();
+        DynamicImage::ImageLuma8(GrayImage::new(width, height))
+            .write_to(&mut std::io::Cursor::new(&mut data), ImageFormat::Png)
+            .unwrap();
+        data
+    }
+
+    #[test]
+    fn downscales_the_longer_side_only_when_needed() {
+        let image = downscale(&png(2480, 3508), 1600).unwrap();
+        assert_eq!(image.height, 1600);
+        assert_eq!(image.width, 1131);
+        assert!(image::load_from_memory(&image.png).is_ok());
+
+        let small = downscale(&png(300, 200), 1600).unwrap();
+        assert_eq!((small.width, small.height), (300, 200));
+    }
+
+    #[tokio::test]
+    async fn keeps_only_the_first_capture() {
+        let dir = std::env::temp_dir().join(format!("page_images_{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir(&dir).unwrap();
+        let first = dir.join("first.png");
+        let second = dir.join("second.png");
+        std::fs::write(&first, png(40, 20)).unwrap();
+        std::fs::write(&second, png(10, 10)).unwrap();
+        let config = ExtractionConfig::default().page_images(Some(1600));
+        let ((), image) = capturing(&config, async {
+            capture(first.to_str().unwrap(), &config).await;
+            capture(second.to_str().unwrap(), &config).await;
+        })
+        .await;
+        std::fs::remove_dir_all(&dir).unwrap();
+        assert_eq!(image.map(|i| (i.width, i.height)), Some((40, 20)));
+
+        let off = ExtractionConfig::default();
+        let ((), image) = capturing(&off, async {}).await;
+        assert!(image.is_none());
+    }
+}
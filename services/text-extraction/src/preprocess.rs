@@ -0,0 +1,377 @@
+//! Image cleanup of rendered pages before OCR.
+//!
+//! Faxes and poor scans give Tesseract grey noise, skewed lines and black scan
+//! borders. [`crate::ExtractionConfig::ocr_preprocess`] (`OCR_PREPROCESS`, e.g.
+//! `contrast,threshold,despeckle,deskew,border`) lists the steps applied, in
+//! that order, to every rendered page before recognition. All steps work on a
+//! grayscale copy and keep the image size, so hOCR boxes still map onto the
+//! page; deskewing rotates around the center and fills with white. The applied
+//! steps are recorded in [`crate::PageExtraction::ocr_preprocessing`], deskew
+//! with its angle, e.g. `threshold,deskew:-1.4`.
+
+use anyhow::{anyhow, Context, Result};
+use image::{GrayImage, Luma};
+
+/// Largest skew looked for, in degrees.
+const MAX_SKEW_DEGREES: f32 = 5.0;
+/// Resolution of the skew search, in degrees.
+const SKEW_STEP_DEGREES: f32 = 0.2;
+/// Smaller angles are left alone.
+const MIN_SKEW_DEGREES: f32 = 0.2;
+/// Pixels darker than this count as ink for deskew and border detection.
+const INK_LEVEL: u8 = 128;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum PreprocessStep {
+    /// Converts the page to 8-bit grayscale (implied by every other step).
+    Grayscale,
+    /// Stretches the 1st to 99th percentile of brightness to the full range.
+    Contrast,
+    /// Binarizes against the mean of the neighbourhood (adaptive threshold).
+    Threshold,
+    /// 3x3 median filter against salt-and-pepper noise.
+    Despeckle,
+    /// Straightens lines tilted by up to 5 degrees.
+    Deskew,
+    /// Whitens dark rows and columns along the edges (scanner borders).
+    Border,
+}
+
+impl PreprocessStep {
+    pub fn name(self) -> &'static str {
+        match self {
+            PreprocessStep::Grayscale => "grayscale",
+            PreprocessStep::Contrast => "contrast",
+            PreprocessStep::Threshold => "threshold",
+            PreprocessStep::Despeckle => "despeckle",
+            PreprocessStep::Deskew => "deskew",
+            PreprocessStep::Border => "border",
+        }
+    }
+
+    pub fn parse(raw: &str) -> Option<Self> {
+        match raw.trim().to_ascii_lowercase().as_str() {
+            "grayscale" | "gray" | "grey" => Some(PreprocessStep::Grayscale),
+            "contrast" => Some(PreprocessStep::Contrast),
+            "threshold" | "binarize" => Some(PreprocessStep::Threshold),
+            "despeckle" | "median" => Some(PreprocessStep::Despeckle),
+            "deskew" => Some(PreprocessStep::Deskew),
+            "border" | "borders" => Some(PreprocessStep::Border),
+            _ => None,
+        }
+    }
+}
+
+/// Parses `OCR_PREPROCESS`, e.g. `threshold,deskew`; unknown steps are skipped.
+pub(crate) fn parse_steps(raw: &str) -> Vec<PreprocessStep> {
+    raw.split(',').filter_map(PreprocessStep::parse).collect()
+}
+
+/// Applies `steps` to the PNG at `path` in place; returns the applied steps.
+pub(crate) async fn preprocess_png(path: &str, steps: &[PreprocessStep]) -> Result<Vec<String>> {
+    if steps.is_empty() {
+        return Ok(Vec::new());
+    }
+    let path = path.to_string();
+    let steps = steps.to_vec();
+    tokio::task::spawn_blocking(move || -> Result<Vec<String>> {
+        let image = image::open(&path).context("load page image")?.to_luma8();
+        let (image, applied) = apply(image, &steps);
+        image.save(&path).context("save preprocessed page image")?;
+        Ok(applied)
+    })
+    .await
+    .map_err(|e| anyhow!("preprocess task failed: {e}"))?
+}
+
+/// Runs `steps` on `image`; returns the result and the labels of the applied steps.
+pub fn apply(mut image: GrayImage, steps: &[PreprocessStep]) -> (GrayImage, Vec<String>) {
+    let mut applied = Vec::new();
+    for step in steps {
+        match step {
+            PreprocessStep::Grayscale => {}
+            PreprocessStep::Contrast => stretch_contrast(&mut image),
+            PreprocessStep::Threshold => image = adaptive_threshold(&image),
+            PreprocessStep::Despeckle => image = median3(&image),
+            PreprocessStep::Deskew => {
+                let angle = estimate_skew(&image);
+                if angle.abs() < MIN_SKEW_DEGREES {
+                    continue;
+                }
+                image = rotate(&image, angle);
+                applied.push(format!("deskew:{angle:.1}"));
+                continue;
+            }
+            PreprocessStep::Border => remove_borders(&mut image),
+        }
+        applied.push(step.name().to_string());
+    }
+    (image, applied)
+}
+
+fn stretch_contrast(image: &mut GrayImage) {
+    let mut histogram = [0u64; 256];
+    for pixel in image.pixels() {
+        histogram[pixel[0] as usize] += 1;
+    }
+    let total: u64 = histogram.iter().sum();
+    let percentile = |share: f64| {
+        let target = (total as f64 * share) as u64;
+        let mut seen = 0;
+        for (value, count) in histogram.iter().enumerate() {
+            seen += count;
+            if seen > target {
+                return value as f32;
+            }
+        }
+        255.0
+    };
+    let (low, high) = (percentile(0.01), percentile(0.99));
+    if high - low < 1.0 {
+        return;
+    }
+    for pixel in image.pixels_mut() {
+        let v = (pixel[0] as f32 - low) * 255.0 / (high - low);
+        pixel[0] = v.clamp(0.0, 255.0) as u8;
+    }
+}
+
+/// Black where a pixel is clearly darker than the mean of its window, white otherwise.
+fn adaptive_threshold(image: &GrayImage) -> GrayImage {
+    const OFFSET: i64 = 10;
+    let (w, h) = (image.width() as usize, image.height() as usize);
+    // Fenster ~1/80 der Breite: bei 300 dpi A4 etwa eine Zeilenhöhe
+    let radius = (w / 160).max(7);
+    let mut integral = vec![0u64; (w + 1) * (h + 1)];
+    for y in 0..h {
+        let mut row = 0u64;
+        for x in 0..w {
+            row += image.get_pixel(x as u32, y as u32)[0] as u64;
+            integral[(y + 1) * (w + 1) + x + 1] = integral[y * (w + 1) + x + 1] + row;
+        }
+    }
+    GrayImage::from_fn(w as u32, h as u32, |x, y| {
+        let (x, y) = (x as usize, y as usize);
+        let (x0, y0) = (x.saturating_sub(radius), y.saturating_sub(radius));
+        let (x1, y1) = ((x + radius + 1).min(w), (y + radius + 1).min(h));
+        let sum = integral[y1 * (w + 1) + x1] + integral[y0 * (w + 1) + x0]
+            - integral[y0 * (w + 1) + x1]
+            - integral[y1 * (w + 1) + x0];
+        let mean = (sum / ((x1 - x0) * (y1 - y0)) as u64) as i64;
+        let value = image.get_pixel(x as u32, y as u32)[0] as i64;
+        Luma([if value < mean - OFFSET { 0 } else { 255 }])
+    })
+}
+
+fn median3(image: &GrayImage) -> GrayImage {
+    let (w, h) = image.dimensions();
+    GrayImage::from_fn(w, h, |x, y| {
+        let mut window = [0u8; 9];
+        let mut n = 0;
+        for dy in -1i64..=1 {
+            for dx in -1i64..=1 {
+                let nx = (x as i64 + dx).clamp(0, w as i64 - 1) as u32;
+                let ny = (y as i64 + dy).clamp(0, h as i64 - 1) as u32;
+                window[n] = image.get_pixel(nx, ny)[0];
+                n += 1;
+            }
+        }
+        window.sort_unstable();
+        Luma([window[4]])
+    })
+}
+
+/// Skew of the text lines in degrees (clockwise positive): the shear whose
+/// row profile of ink pixels has the highest variance.
+fn estimate_skew(image: &GrayImage) -> f32 {
+    // Auf ~1000 px verkleinert suchen, die Zeilen bleiben erkennbar
+    let step = (image.width().max(image.height()) / 1000).max(1);
+    let ink: Vec<(f32, f32)> = (0..image.height())
+        .step_by(step as usize)
+        .flat_map(|y| {
+            (0..image.width())
+                .step_by(step as usize)
+                .filter(move |&x| image.get_pixel(x, y)[0] < INK_LEVEL)
+                .map(move |x| (x as f32, y as f32))
+        })
+        .collect();
+    if ink.len() < 100 {
+        return 0.0;
+    }
+    let rows = (image.height() + image.width()) as usize / step as usize + 2;
+    let offset = image.width() as f32;
+    let mut best = (0.0f32, f64::MIN);
+    let steps = (MAX_SKEW_DEGREES / SKEW_STEP_DEGREES).round() as i32;
+    for i in -steps..=steps {
+        let angle = i as f32 * SKEW_STEP_DEGREES;
+        let slope = angle.to_radians().tan();
+        let mut profile = vec![0u32; rows];
+        for &(x, y) in &ink {
+            let row = ((y - x * slope + offset) / step as f32) as usize;
+            if let Some(count) = profile.get_mut(row) {
+                *count += 1;
+            }
+        }
+        let score: f64 = profile.iter().map(|&c| (c as f64).powi(2)).sum();
+        if score > best.1 {
+            best = (angle, score);
+        }
+    }
+    best.0
+}
+
+/// Rotates counter-clockwise by `degrees` around the center (undoing a
+/// clockwise skew); uncovered corners become white.
+fn rotate(image: &GrayImage, degrees: f32) -> GrayImage {
+    let (w, h) = image.dimensions();
+    let (sin, cos) = degrees.to_radians().sin_cos();
+    let (cx, cy) = (w as f32 / 2.0, h as f32 / 2.0);
+    GrayImage::from_fn(w, h, |x, y| {
+        let (dx, dy) = (x as f32 - cx, y as f32 - cy);
+        let sx = dx * cos - dy * sin + cx;
+        let sy = dx * sin + dy * cos + cy;
+        if sx < 0.0 || sy < 0.0 || sx >= (w - 1) as f32 || sy >= (h - 1) as f32 {
+            return Luma([255]);
+        }
+        let (x0, y0) = (sx.floor() as u32, sy.floor() as u32);
+        let (fx, fy) = (sx - x0 as f32, sy - y0 as f32);
+        let p = |x, y| image.get_pixel(x, y)[0] as f32;
+        let top = p(x0, y0) * (1.0 - fx) + p(x0 + 1, y0) * fx;
+        let bottom = p(x0, y0 + 1) * (1.0 - fx) + p(x0 + 1, y0 + 1) * fx;
+        Luma([(top * (1.0 - fy) + bottom * fy).round() as u8])
+    })
+}
+
+/// Whitens rows and columns from each edge inwards while most of their pixels
+/// are ink, at most a tenth of the page per edge.
+fn remove_borders(image: &mut GrayImage) {
+    let (w, h) = image.dimensions();
+    let dark_row = |image: &GrayImage, y: u32| {
+        (0..w)
+            .filter(|&x| image.get_pixel(x, y)[0] < INK_LEVEL)
+            .count()
+            * 2
+            > w as usize
+    };
+    let dark_col = |image: &GrayImage, x: u32| {
+        (0..h)
+            .filter(|&y| image.get_pixel(x, y)[0] < INK_LEVEL)
+            .count()
+            * 2
+            > h as usize
+    };
+    let rows: Vec<u32> = (0..h / 10)
+        .take_while(|&y| dark_row(image, y))
+        .chain((h - h / 10..h).rev().take_while(|&y| dark_row(image, y)))
+        .collect();
+    let cols: Vec<u32> = (0..w / 10)
+        .take_while(|&x| dark_col(image, x))
+        .chain((w - w / 10..w).rev().take_while(|&x| dark_col(image, x)))
+        .collect();
+    for y in rows {
+        for x in 0..w {
+            image.put_pixel(x, y, Luma([255]));
+        }
+    }
+    for x in cols {
+        for y in 0..h {
+            image.put_pixel(x, y, Luma([255]));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// White page with dark text lines tilted clockwise by `degrees`.
+    fn lined_page(degrees: f32) -> GrayImage {
+        let slope = degrees.to_radians().tan();
+        GrayImage::from_fn(600, 400, |x, y| {
+            let y = y as f32 - (x as f32 - 300.0) * slope;
+            let in_line = (y as i32).rem_euclid(40) < 4 && (100.0..300.0).contains(&y);
+            Luma([if in_line && (50..550).contains(&x) {
+                0
+            } else {
+                255
+            }])
+        })
+    }
+
+    #[test]
+    fn parses_steps() {
+        let steps = parse_steps("Threshold, deskew,unknown,border");
+        assert_eq!(
+            steps,
+            vec![
+                PreprocessStep::Threshold,
+                PreprocessStep::Deskew,
+                PreprocessStep::Border
+            ]
+        );
+        assert!(parse_steps("").is_empty());
+    }
+
+    #[test]
+    fn deskew_straightens_lines() {
+        let page = lined_page(2.0);
+        let angle = estimate_skew(&page);
+        assert!((angle - 2.0).abs() <= 0.3, "angle {angle}");
+
+        let (straight, applied) = apply(page, &[PreprocessStep::Deskew]);
+        assert_eq!(applied.len(), 1);
+        assert!(applied[0].starts_with("deskew:"));
+        assert!(estimate_skew(&straight).abs() < MIN_SKEW_DEGREES);
+        assert_eq!(straight.dimensions(), (600, 400));
+
+        let (_, applied) = apply(lined_page(0.0), &[PreprocessStep::Deskew]);
+        assert!(applied.is_empty());
+    }
+
+    #[test]
+    fn threshold_binarizes_and_despeckle_removes_dots() {
+        // Grauer Hintergrund mit dunkler Schrift und einzelnen Störpunkten
+        let page = GrayImage::from_fn(200, 100, |x, y| {
+            if (40..60).contains(&y) && (20..180).contains(&x) {
+                Luma([60])
+            } else if (x * 7 + y * 13) % 97 == 0 {
+                Luma([0])
+            } else {
+                Luma([170])
+            }
+        });
+        let (clean, applied) = apply(
+            page,
+            &[PreprocessStep::Threshold, PreprocessStep::Despeckle],
+        );
+        assert_eq!(applied, vec!["threshold", "despeckle"]);
+        assert!(clean.pixels().all(|p| p[0] == 0 || p[0] == 255));
+        assert_eq!(clean.get_pixel(100, 42)[0], 0);
+        let dots = (0..200u32)
+            .flat_map(|x| (0..30u32).map(move |y| (x, y)))
+            .filter(|&(x, y)| clean.get_pixel(x, y)[0] == 0)
+            .count();
+        assert_eq!(dots, 0);
+    }
+
+    #[test]
+    fn border_and_contrast() {
+        let mut page = GrayImage::from_fn(100, 100, |x, y| {
+            if x < 4 || y >= 97 {
+                Luma([10])
+            } else {
+                Luma([100 + (x % 50) as u8])
+            }
+        });
+        remove_borders(&mut page);
+        assert_eq!(page.get_pixel(2, 50)[0], 255);
+        assert_eq!(page.get_pixel(50, 98)[0], 255);
+        assert_eq!(page.get_pixel(50, 50)[0], 100);
+
+        stretch_contrast(&mut page);
+        let (min, max) = page
+            .pixels()
+            .fold((255, 0), |(lo, hi), p| (p[0].min(lo), p[0].max(hi)));
+        assert_eq!((min, max), (0, 255));
+    }
+}
@@ -0,0 +1,131 @@
+//! Dictionary based quality score for extracted page text.
+//!
+//! PDFs with broken embedded fonts (missing `ToUnicode` maps) make `pdftotext`
+//! return plenty of characters that are no words at all. The score is the share
+//! of word tokens found in a small German/English list of frequent words; a
+//! custom list (one word per line) can be supplied via `OCR_QUALITY_DICT`.
+//! [`text_similarity`] compares two readings of the same page, e.g. an
+//! embedded text layer and its OCR.
+
+use std::{
+    collections::{HashMap, HashSet},
+    env,
+};
+
+use once_cell::sync::Lazy;
+use tracing::warn;
+
+/// Fewer tokens do not allow a meaningful score.
+const MIN_TOKENS: usize = 5;
+
+const COMMON_WORDS: &str = "
+der die das und in zu den von mit ist im für auf dem nicht des ein eine sich es
+auch als an werden aus er hat dass sie nach wird bei einer um am sind noch wie
+einem über einen so zum war haben nur oder aber vor zur bis mehr durch man
+sein wurde sei ab wenn unter diese kann ihre keine ohne gegen sowie alle wir
+ich ihr uns sehr hier zwischen seit beim bereits wieder gemäß
+vertrag versicherung schaden datum seite summe betrag euro eur nummer name
+straße strasse ort telefon firma gesellschaft rechnung kunde kosten jahr jahre
+monat zahlung frist anlage antrag angaben unterschrift herr frau gmbh ag
+the and of to in is for on that with as by at from this be are or an it not
+was have has will which all can your our their may any other such these date
+page total amount number name address contract insurance payment invoice
+";
+
+static DICTIONARY: Lazy<HashSet<String>> = Lazy::new(|| {
+    let mut words: HashSet<String> = COMMON_WORDS
+        .split_whitespace()
+        .map(str::to_string)
+        .collect();
+    if let Ok(path) = env::var("OCR_QUALITY_DICT") {
+        match std::fs::read_to_string(&path) {
+            Ok(content) => words.extend(
+                content
+                    .lines()
+                    .map(|l| l.trim().to_lowercase())
+                    .filter(|l| !l.is_empty()),
+            ),
+            Err(err) => warn!(%path, error = %err, "quality dictionary not readable"),
+        }
+    }
+    words
+});
+
+/// Share (0..=1) of word tokens found in the dictionary, `None` for too little text.
+///
+/// Tokens are lowercased and trimmed of punctuation; tokens containing digits
+/// and single characters are ignored.
+pub fn dictionary_score(text: &str) -> Option<f64> {
+    let mut total = 0usize;
+    let mut known = 0usize;
+    for token in words(text) {
+        total += 1;
+        if DICTIONARY.contains(&token) {
+            known += 1;
+        }
+    }
+    if total < MIN_TOKENS {
+        return None;
+    }
+    Some(known as f64 / total as f64)
+}
+
+/// Word overlap (0..=1, Dice coefficient over word counts) of two texts of the
+/// same page, `None` when one of them has too few words.
+pub fn text_similarity(a: &str, b: &str) -> Option<f64> {
+    let count = |text: &str| {
+        let mut counts: HashMap<String, usize> = HashMap::new();
+        for token in words(text) {
+            *counts.entry(token).or_default() += 1;
+        }
+        counts
+    };
+    let (a, b) = (count(a), count(b));
+    let total_a: usize = a.values().sum();
+    let total_b: usize = b.values().sum();
+    if total_a < MIN_TOKENS || total_b < MIN_TOKENS {
+        return None;
+    }
+    let common: usize = a
+        .iter()
+        .map(|(token, n)| (*n).min(b.get(token).copied().unwrap_or(0)))
+        .sum();
+    Some(2.0 * common as f64 / (total_a + total_b) as f64)
+}
+
+/// Lowercased word tokens without punctuation, digits and single characters.
+fn words(text: &str) -> impl Iterator<Item = String> + '_ {
+    text.split_whitespace().filter_map(|raw| {
+        let token = raw
+            .trim_matches(|c: char| !c.is_alphanumeric())
+            .to_lowercase();
+        (token.chars().count() >= 2 && !token.chars().any(|c| c.is_ascii_digit())).then_some(token)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn garbled_text_scores_below_real_text() {
+        let real = "Der Vertrag wird mit der Versicherung für das Jahr 2024 geschlossen, \
+                    die Zahlung ist bis zum 31.03. fällig.";
+        let garbled = "Ƒǳ ĳǂ ÐĲ¼ ¶ȸǵ ţŦ ĸŉŊ ǭǮ ǈǉǊ ǹǺ ȁȂ ȅȆ Ŏŏ ŵŶŷ";
+        let real_score = dictionary_score(real).unwrap();
+        let garbled_score = dictionary_score(garbled).unwrap();
+        assert!(real_score > 0.4, "{real_score}");
+        assert_eq!(garbled_score, 0.0);
+        assert_eq!(dictionary_score("Seite 1 von 3"), None);
+    }
+
+    #[test]
+    fn similarity_detects_wrong_text_layer() {
+        let ocr = "Die Kündigung des Vertrags ist zum Ende des Monats wirksam.";
+        let same = "Die Kündigung des Vertrags ist zum Ende des Monats wirksam";
+        let wrong = "Dle Kundlgung cles Vertrqgs lst zurn Encle cles Monqts wlrksam.";
+        assert_eq!(text_similarity(ocr, same), Some(1.0));
+        assert!(text_similarity(ocr, wrong).unwrap() < 0.3);
+        assert_eq!(text_similarity(ocr, "Seite 1"), None);
+    }
+}
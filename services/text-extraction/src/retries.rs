@@ -0,0 +1,264 @@
+//! Automatic retries of failed extractions (`extraction_retries`).
+//!
+//! An extraction that fails (missing tool, timeout, ...) is recorded with its
+//! `pdf-merged` event ([`record_failure`]). The retry loop ([`run`]) publishes
+//! the event again once it is due, waiting [`RetryConfig::retry`] after every
+//! failed attempt. After `EXTRACTION_MAX_ATTEMPTS` attempts the
+//! extraction is poisoned: it stays in the table with the reason of the last
+//! failure until an operator requeues it (`POST /extractions/{pdf_id}/requeue`
+//! on pdf-ingest, [`REQUEUE_SQL`]). A successful extraction removes the entry
+//! ([`clear`]). Encrypted PDFs are not retried, they need a password.
+
+use std::{env, time::Duration};
+
+use anyhow::Result;
+use deadpool_postgres::Pool;
+use rdkafka::producer::{FutureProducer, FutureRecord};
+use serde_json::Value;
+use shared::dto::PdfUploaded;
+use shared::kafka;
+use shared::startup::Backoff;
+use tokio_postgres::{types::Json, Client};
+use tracing::{error, info, warn};
+
+use crate::sandbox::SandboxError;
+
+/// Creates `extraction_retries` (see migration 0052).
+pub const SCHEMA_SQL: [&str; 2] = [
+    "CREATE TABLE IF NOT EXISTS extraction_retries (
+        pdf_id INTEGER PRIMARY KEY REFERENCES merged_pdfs(id) ON DELETE CASCADE,
+        event JSONB NOT NULL,
+        first_page INTEGER NOT NULL DEFAULT 0,
+        status TEXT NOT NULL DEFAULT 'pending',
+        attempts INTEGER NOT NULL DEFAULT 0,
+        reason TEXT,
+        last_error TEXT,
+        next_attempt_at TIMESTAMPTZ NOT NULL DEFAULT now(),
+        created_at TIMESTAMPTZ NOT NULL DEFAULT now(),
+        updated_at TIMESTAMPTZ NOT NULL DEFAULT now()
+    )",
+    "CREATE INDEX IF NOT EXISTS idx_extraction_retries_due
+        ON extraction_retries (next_attempt_at) WHERE status = 'pending'",
+];
+
+/// Puts a poisoned extraction (`$1` pdf id) back into the queue with fresh
+/// attempts; affects no row for unknown or still pending extractions.
+pub const REQUEUE_SQL: &str = "UPDATE extraction_retries
+    SET status = 'pending', attempts = 0, next_attempt_at = now(), updated_at = now()
+  WHERE pdf_id = $1 AND status = 'poisoned'";
+
+/// Longest wait between two attempts.
+const MAX_RETRY_DELAY: Duration = Duration::from_secs(3600);
+
+/// A published retry counts as running for this long; if its outcome never
+/// arrives (lost event, crash), the retry is published again afterwards.
+const LEASE: Duration = Duration::from_secs(1800);
+
+const BATCH_SIZE: i64 = 20;
+
+/// Configuration of the retries (`EXTRACTION_RETRY_POLL_SECS`,
+/// `EXTRACTION_MAX_ATTEMPTS`, `EXTRACTION_RETRY_BASE_SECS`).
+#[derive(Clone, Debug)]
+pub struct RetryConfig {
+    pub poll: Duration,
+    pub max_attempts: i32,
+    /// Doubles per failed attempt, up to [`MAX_RETRY_DELAY`].
+    pub retry: Backoff,
+}
+
+impl Default for RetryConfig {
+    fn default() -> Self {
+        Self {
+            poll: Duration::from_secs(30),
+            max_attempts: 5,
+            retry: Backoff {
+                initial: Duration::from_secs(60),
+                max: MAX_RETRY_DELAY,
+            },
+        }
+    }
+}
+
+impl RetryConfig {
+    pub fn from_env() -> Self {
+        let defaults = Self::default();
+        let secs = |key: &str| {
+            env::var(key)
+                .ok()
+                .and_then(|v| v.trim().parse::<u64>().ok())
+                .filter(|v| *v > 0)
+        };
+        Self {
+            poll: secs("EXTRACTION_RETRY_POLL_SECS")
+                .map(Duration::from_secs)
+                .unwrap_or(defaults.poll),
+            max_attempts: secs("EXTRACTION_MAX_ATTEMPTS")
+                .map(|v| v.min(i32::MAX as u64) as i32)
+                .unwrap_or(defaults.max_attempts),
+            retry: Backoff {
+                initial: secs("EXTRACTION_RETRY_BASE_SECS")
+                    .map(Duration::from_secs)
+                    .unwrap_or(defaults.retry.initial)
+                    .min(MAX_RETRY_DELAY),
+                max: MAX_RETRY_DELAY,
+            },
+        }
+    }
+}
+
+/// Kind of failure, kept as `reason`.
+pub fn reason(error: &anyhow::Error) -> &'static str {
+    match error.chain().find_map(|e| e.downcast_ref::<SandboxError>()) {
+        Some(SandboxError::NotFound { .. }) => "missing_binary",
+        Some(SandboxError::Timeout { .. }) => "timeout",
+        Some(SandboxError::ResourceLimit { .. } | SandboxError::OutputTooLarge { .. }) => {
+            "resource_limit"
+        }
+        Some(SandboxError::Crashed { .. }) => "crashed",
+        _ => "error",
+    }
+}
+
+/// State of an extraction after [`record_failure`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Failure {
+    pub attempts: i32,
+    pub poisoned: bool,
+    /// Wait before the next attempt, `None` once poisoned.
+    pub retry_in: Option<Duration>,
+}
+
+/// Counts a failed attempt of `evt`. A pending retry of more pages (smaller
+/// `appended_from`) keeps its event, so the retry covers both.
+pub async fn record_failure(
+    client: &Client,
+    config: &RetryConfig,
+    evt: &PdfUploaded,
+    error: &anyhow::Error,
+) -> Result<Failure> {
+    let event = serde_json::to_value(evt)?;
+    let first_page = evt.appended_from.unwrap_or(0).max(0);
+    let row = client
+        .query_one(
+            "INSERT INTO extraction_retries (pdf_id, event, first_page, attempts)
+             VALUES ($1, $2, $3, 1)
+             ON CONFLICT (pdf_id) DO UPDATE
+                SET attempts = extraction_retries.attempts + 1,
+                    event = CASE WHEN extraction_retries.first_page < EXCLUDED.first_page
+                                 THEN extraction_retries.event ELSE EXCLUDED.event END,
+                    first_page = LEAST(extraction_retries.first_page, EXCLUDED.first_page),
+                    updated_at = now()
+             RETURNING attempts",
+            &[&evt.pdf_id, &Json(&event), &first_page],
+        )
+        .await?;
+    let attempts: i32 = row.get(0);
+    let poisoned = attempts >= config.max_attempts;
+    let retry_in = (!poisoned).then(|| config.retry.after_failures(attempts));
+    client
+        .execute(
+            "UPDATE extraction_retries
+                SET status = $2, reason = $3, last_error = $4,
+                    next_attempt_at = now() + make_interval(secs => $5)
+              WHERE pdf_id = $1",
+            &[
+                &evt.pdf_id,
+                &if poisoned { "poisoned" } else { "pending" },
+                &reason(error),
+                &format!("{error:#}"),
+                &retry_in.unwrap_or_default().as_secs_f64(),
+            ],
+        )
+        .await?;
+    Ok(Failure {
+        attempts,
+        poisoned,
+        retry_in,
+    })
+}
+
+/// Removes the entry of `pdf_id` once pages from `first_page` on were
+/// extracted, unless the failed attempt also covered earlier pages.
+pub async fn clear(client: &Client, pdf_id: i32, first_page: i32) -> Result<bool> {
+    let deleted = client
+        .execute(
+            "DELETE FROM extraction_retries WHERE pdf_id = $1 AND first_page >= $2",
+            &[&pdf_id, &first_page],
+        )
+        .await?;
+    Ok(deleted > 0)
+}
+
+/// Takes the due retries and leases them for [`LEASE`].
+async fn claim(client: &Client) -> Result<Vec<(i32, Value)>> {
+    let rows = client
+        .query(
+            "UPDATE extraction_retries
+                SET next_attempt_at = now() + make_interval(secs => $2), updated_at = now()
+              WHERE pdf_id IN (
+                    SELECT pdf_id FROM extraction_retries
+                     WHERE status = 'pending' AND next_attempt_at <= now()
+                     ORDER BY next_attempt_at
+                     LIMIT $1
+                     FOR UPDATE SKIP LOCKED)
+          RETURNING pdf_id, event",
+            &[&BATCH_SIZE, &LEASE.as_secs_f64()],
+        )
+        .await?;
+    Ok(rows.iter().map(|r| (r.get(0), r.get(1))).collect())
+}
+
+/// Publishes due retries as `pdf-merged` events until the process ends.
+pub async fn run(pool: Pool, producer: FutureProducer, config: RetryConfig) {
+    info!(?config, "extraction retries running");
+    loop {
+        let claimed = match pool.get().await {
+            Ok(client) => claim(&client).await,
+            Err(e) => Err(e.into()),
+        };
+        match claimed {
+            Ok(due) => {
+                for (pdf_id, event) in due {
+                    let payload = event.to_string();
+                    match producer
+                        .send(
+                            FutureRecord::to("pdf-merged")
+                                .payload(&payload)
+                                .key(&kafka::document_key(pdf_id)),
+                            Duration::from_secs(0),
+                        )
+                        .await
+                    {
+                        Ok(_) => info!(pdf_id, "extraction retry published"),
+                        // Lease läuft ab, danach neuer Versuch
+                        Err((e, _)) => error!(%e, pdf_id, "publish extraction retry failed"),
+                    }
+                }
+            }
+            // Tabelle fehlt (Schema noch nicht angelegt) oder DB weg: nächste Runde
+            Err(e) => warn!(%e, "extraction retries not readable"),
+        }
+        tokio::time::sleep(config.poll).await;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn classifies_sandbox_failures() {
+        let timeout = anyhow::Error::new(SandboxError::Timeout {
+            tool: "tesseract",
+            after: Duration::from_secs(60),
+        })
+        .context("ocr page 3");
+        assert_eq!(reason(&timeout), "timeout");
+        let missing = anyhow::Error::new(SandboxError::NotFound {
+            tool: "pdftoppm",
+            dirs: "/usr/bin".into(),
+        });
+        assert_eq!(reason(&missing), "missing_binary");
+        assert_eq!(reason(&anyhow::anyhow!("broken xref")), "error");
+    }
+}
@@ -0,0 +1,477 @@
+//! Sandboxed execution of the poppler and Tesseract binaries.
+//!
+//! Uploaded PDFs are untrusted input, so every external tool runs through
+//! [`run`]:
+//!
+//! * only the binaries of [`Tool`] can be started, resolved to an absolute path
+//!   inside `SUBPROCESS_BIN_DIRS` (default `/usr/local/bin:/usr/bin:/bin`)
+//!   instead of a `PATH` lookup;
+//! * the environment is cleared except for `PATH` (the same directories) and a
+//!   few locale/Tesseract variables, stdin is closed;
+//! * `setrlimit` caps address space (`SUBPROCESS_MEMORY_MB`, 2048), CPU time
+//!   (`SUBPROCESS_CPU_SECS`, 60) and the size of written files, core dumps are
+//!   disabled;
+//! * stdout is capped at `SUBPROCESS_MAX_OUTPUT_MB` (64), the process is killed
+//!   once it writes more, and after `SUBPROCESS_TIMEOUT_SECS` (60).
+//!
+//! Failures are reported as [`SandboxError`], so callers and logs can tell a
+//! timeout from a crash, an exceeded limit or a regular error exit. Timeouts are
+//! also counted per task ([`track_timeouts`]), even where a caller swallows the
+//! error, so the [`crate::scheduler`] can back off.
+
+use std::{
+    cell::Cell,
+    env,
+    ffi::OsStr,
+    future::Future,
+    io,
+    path::PathBuf,
+    process::{Output, Stdio},
+    time::Duration,
+};
+
+use once_cell::sync::Lazy;
+use thiserror::Error;
+use tokio::{
+    io::{AsyncRead, AsyncReadExt},
+    process::Command,
+    time::timeout,
+};
+
+/// Stderr kept for error messages; the rest is drained and dropped.
+const STDERR_LIMIT: usize = 8 * 1024;
+
+/// Environment variables passed on to the tools.
+const PASSED_ENV: &[&str] = &["LANG", "LC_ALL", "TESSDATA_PREFIX", "OMP_THREAD_LIMIT"];
+
+static LIMITS: Lazy<Limits> = Lazy::new(Limits::from_env);
+
+tokio::task_local! {
+    /// Timeouts inside the innermost [`track_timeouts`] of the task.
+    static TIMEOUTS: Cell<u32>;
+}
+
+/// Binaries the extraction may start.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Tool {
+    Pdfinfo,
+    Pdftotext,
+    Pdftoppm,
+    Pdftohtml,
+    Pdfdetach,
+    Pdfunite,
+    Tesseract,
+}
+
+impl Tool {
+    pub fn binary(self) -> &'static str {
+        match self {
+            Tool::Pdfinfo => "pdfinfo",
+            Tool::Pdftotext => "pdftotext",
+            Tool::Pdftoppm => "pdftoppm",
+            Tool::Pdftohtml => "pdftohtml",
+            Tool::Pdfdetach => "pdfdetach",
+            Tool::Pdfunite => "pdfunite",
+            Tool::Tesseract => "tesseract",
+        }
+    }
+}
+
+#[derive(Debug, Error)]
+/// Why a sandboxed tool did not produce a result.
+pub enum SandboxError {
+    #[error("{tool} not found in {dirs}")]
+    NotFound { tool: &'static str, dirs: String },
+    #[error("failed to start {tool}: {source}")]
+    Spawn {
+        tool: &'static str,
+        #[source]
+        source: io::Error,
+    },
+    #[error("{tool} i/o error: {source}")]
+    Io {
+        tool: &'static str,
+        #[source]
+        source: io::Error,
+    },
+    #[error("{tool} timed out after {}s", .after.as_secs())]
+    Timeout { tool: &'static str, after: Duration },
+    #[error("{tool} exceeded its {limit} limit")]
+    ResourceLimit {
+        tool: &'static str,
+        limit: &'static str,
+    },
+    #[error("{tool} output exceeds {limit} bytes")]
+    OutputTooLarge { tool: &'static str, limit: u64 },
+    #[error("{tool} crashed with signal {signal}")]
+    Crashed { tool: &'static str, signal: i32 },
+    #[error("{tool} exited with status {code}: {stderr}")]
+    Failed {
+        tool: &'static str,
+        code: i32,
+        stderr: String,
+    },
+}
+
+/// Limits applied to every tool run.
+#[derive(Clone, Debug)]
+pub struct Limits {
+    pub timeout: Duration,
+    /// Address space in bytes; `None` = unlimited.
+    pub memory_bytes: Option<u64>,
+    /// CPU seconds; `None` = unlimited.
+    pub cpu_secs: Option<u64>,
+    /// Maximum stdout and size of any file the tool writes.
+    pub max_output_bytes: u64,
+    /// Absolute directories the binaries are resolved in.
+    pub bin_dirs: Vec<PathBuf>,
+}
+
+impl Limits {
+    /// Reads the `SUBPROCESS_*` variables; `0` disables memory and CPU limits.
+    pub fn from_env() -> Self {
+        let read = |key: &str, default: u64| {
+            env::var(key)
+                .ok()
+                .and_then(|v| v.trim().parse::<u64>().ok())
+                .unwrap_or(default)
+        };
+        let bin_dirs = env::var("SUBPROCESS_BIN_DIRS")
+            .unwrap_or_else(|_| "/usr/local/bin:/usr/bin:/bin".to_string());
+        Self {
+            timeout: Duration::from_secs(read("SUBPROCESS_TIMEOUT_SECS", 60).max(1)),
+            memory_bytes: Some(read("SUBPROCESS_MEMORY_MB", 2048))
+                .filter(|mb| *mb > 0)
+                .map(|mb| mb * 1024 * 1024),
+            cpu_secs: Some(read("SUBPROCESS_CPU_SECS", 60)).filter(|s| *s > 0),
+            max_output_bytes: read("SUBPROCESS_MAX_OUTPUT_MB", 64).max(1) * 1024 * 1024,
+            bin_dirs: env::split_paths(&bin_dirs)
+                .filter(|dir| dir.is_absolute())
+                .collect(),
+        }
+    }
+
+    /// Absolute path of `tool` in the first directory of `bin_dirs` containing it.
+    pub fn resolve(&self, tool: Tool) -> Result<PathBuf, SandboxError> {
+        self.bin_dirs
+            .iter()
+            .map(|dir| dir.join(tool.binary()))
+            .find(|path| path.is_file())
+            .ok_or_else(|| SandboxError::NotFound {
+                tool: tool.binary(),
+                dirs: env::join_paths(&self.bin_dirs)
+                    .map(|p| p.to_string_lossy().into_owned())
+                    .unwrap_or_default(),
+            })
+    }
+}
+
+/// Limits of the service, read once from the environment.
+pub fn limits() -> &'static Limits {
+    &LIMITS
+}
+
+/// Runs `tool` with `args` under [`limits`]; `Ok` only for exit status 0.
+pub async fn run<I, S>(tool: Tool, args: I) -> Result<Output, SandboxError>
+where
+    I: IntoIterator<Item = S>,
+    S: AsRef<OsStr>,
+{
+    run_with(limits(), tool, args).await
+}
+
+async fn run_with<I, S>(limits: &Limits, tool: Tool, args: I) -> Result<Output, SandboxError>
+where
+    I: IntoIterator<Item = S>,
+    S: AsRef<OsStr>,
+{
+    let name = tool.binary();
+    let mut cmd = Command::new(limits.resolve(tool)?);
+    cmd.args(args)
+        .env_clear()
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .kill_on_drop(true);
+    if let Ok(path) = env::join_paths(&limits.bin_dirs) {
+        cmd.env("PATH", path);
+    }
+    for key in PASSED_ENV {
+        if let Some(value) = env::var_os(key) {
+            cmd.env(key, value);
+        }
+    }
+    #[cfg(unix)]
+    apply_rlimits(&mut cmd, limits);
+
+    let mut child = cmd
+        .spawn()
+        .map_err(|source| SandboxError::Spawn { tool: name, source })?;
+    let stdout = child.stdout.take().expect("stdout is piped");
+    let stderr = child.stderr.take().expect("stderr is piped");
+    let max_output = limits.max_output_bytes;
+
+    let io_error = |source| SandboxError::Io { tool: name, source };
+    let finished = timeout(limits.timeout, async {
+        let read_stdout = async {
+            match read_capped(stdout, max_output as usize, false).await {
+                Ok(Some(data)) => Ok(data),
+                Ok(None) => Err(SandboxError::OutputTooLarge {
+                    tool: name,
+                    limit: max_output,
+                }),
+                Err(source) => Err(io_error(source)),
+            }
+        };
+        let read_stderr = async {
+            read_capped(stderr, STDERR_LIMIT, true)
+                .await
+                .map(Option::unwrap_or_default)
+                .map_err(io_error)
+        };
+        let (stdout, stderr) = tokio::try_join!(read_stdout, read_stderr)?;
+        let status = child.wait().await.map_err(io_error)?;
+        Ok(Output {
+            status,
+            stdout,
+            stderr,
+        })
+    })
+    .await;
+
+    let output = match finished {
+        Ok(Ok(output)) => output,
+        Ok(Err(err)) => {
+            let _ = child.kill().await;
+            return Err(err);
+        }
+        Err(_) => {
+            let _ = child.kill().await;
+            let _ = TIMEOUTS.try_with(|count| count.set(count.get() + 1));
+            return Err(SandboxError::Timeout {
+                tool: name,
+                after: limits.timeout,
+            });
+        }
+    };
+    check_status(name, &output, limits)?;
+    Ok(output)
+}
+
+/// Reads up to `limit` bytes; beyond that `None`, or with `drain` the rest is
+/// read and dropped so the process does not block on a full pipe.
+async fn read_capped<R: AsyncRead + Unpin>(
+    mut reader: R,
+    limit: usize,
+    drain: bool,
+) -> io::Result<Option<Vec<u8>>> {
+    let mut data = Vec::new();
+    let mut buf = [0u8; 8192];
+    loop {
+        let n = reader.read(&mut buf).await?;
+        if n == 0 {
+            return Ok(Some(data));
+        }
+        let room = limit.saturating_sub(data.len());
+        if n > room && !drain {
+            return Ok(None);
+        }
+        data.extend_from_slice(&buf[..n.min(room)]);
+    }
+}
+
+/// Maps the exit status to the matching [`SandboxError`].
+fn check_status(tool: &'static str, output: &Output, limits: &Limits) -> Result<(), SandboxError> {
+    let status = output.status;
+    if status.success() {
+        return Ok(());
+    }
+    #[cfg(unix)]
+    {
+        use std::os::unix::process::ExitStatusExt;
+        if let Some(signal) = status.signal() {
+            return Err(match signal {
+                libc::SIGXCPU => SandboxError::ResourceLimit { tool, limit: "cpu" },
+                // Harte CPU-Grenze oder OOM-Killer
+                libc::SIGKILL => SandboxError::ResourceLimit {
+                    tool,
+                    limit: "cpu or memory",
+                },
+                libc::SIGXFSZ => SandboxError::OutputTooLarge {
+                    tool,
+                    limit: limits.max_output_bytes,
+                },
+                signal => SandboxError::Crashed { tool, signal },
+            });
+        }
+    }
+    #[cfg(not(unix))]
+    let _ = limits;
+    Err(SandboxError::Failed {
+        tool,
+        code: status.code().unwrap_or(-1),
+        stderr: String::from_utf8_lossy(&output.stderr).trim().to_string(),
+    })
+}
+
+#[cfg(unix)]
+fn apply_rlimits(cmd: &mut Command, limits: &Limits) {
+    let memory = limits.memory_bytes;
+    let cpu = limits.cpu_secs;
+    let fsize = limits.max_output_bytes;
+    let limit = |soft: u64, hard: u64| libc::rlimit {
+        rlim_cur: soft as libc::rlim_t,
+        rlim_max: hard as libc::rlim_t,
+    };
+    let check = |ret: libc::c_int| {
+        if ret == 0 {
+            Ok(())
+        } else {
+            Err(io::Error::last_os_error())
+        }
+    };
+    // SAFETY: zwischen fork und exec laufen nur async-signal-sichere setrlimit-Aufrufe
+    unsafe {
+        cmd.pre_exec(move || {
+            check(libc::setrlimit(libc::RLIMIT_CORE, &limit(0, 0)))?;
+            check(libc::setrlimit(libc::RLIMIT_FSIZE, &limit(fsize, fsize)))?;
+            if let Some(memory) = memory {
+                check(libc::setrlimit(libc::RLIMIT_AS, &limit(memory, memory)))?;
+            }
+            if let Some(cpu) = cpu {
+                // SIGXCPU bei der weichen Grenze, SIGKILL eine Sekunde später
+                check(libc::setrlimit(libc::RLIMIT_CPU, &limit(cpu, cpu + 1)))?;
+            }
+            Ok(())
+        });
+    }
+}
+
+/// Runs `fut` and counts the tools it started that hit the timeout, including
+/// those whose errors `fut` handled itself. `fut` is boxed right away, so the
+/// (large) OCR futures are not copied into every caller.
+pub fn track_timeouts<F: Future>(fut: F) -> impl Future<Output = (F::Output, u32)> {
+    let fut = Box::pin(fut);
+    TIMEOUTS.scope(Cell::new(0), async move {
+        let output = fut.await;
+        (output, TIMEOUTS.with(Cell::get))
+    })
+}
+
+/// `true` for an error of a tool that ran and exited with a non-zero status.
+pub fn is_exit_failure(err: &SandboxError) -> bool {
+    matches!(err, SandboxError::Failed { .. })
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+    use std::os::unix::fs::PermissionsExt;
+
+    const NO_ARGS: [&str; 0] = [];
+
+    /// Fake `pdfinfo` running `script` in a temporary bin directory.
+    fn fake_tool(script: &str) -> (tempdir::Dir, Limits) {
+        let dir = tempdir::Dir::new();
+        let path = dir.path().join("pdfinfo");
+        std::fs::write(&path, format!("#!/bin/sh\n{script}\n")).unwrap();
+        std::fs::set_permissions(&path, std::fs::Permissions::from_mode(0o755)).unwrap();
+        let limits = Limits {
+            timeout: Duration::from_secs(5),
+            memory_bytes: None,
+            cpu_secs: Some(10),
+            max_output_bytes: 1024,
+            bin_dirs: vec![dir.path().to_path_buf(), "/usr/bin".into(), "/bin".into()],
+        };
+        (dir, limits)
+    }
+
+    mod tempdir {
+        use std::path::{Path, PathBuf};
+
+        pub struct Dir(PathBuf);
+
+        impl Dir {
+            pub fn new() -> Self {
+                let path = std::env::temp_dir().join(format!("sandbox_{}", uuid::Uuid::new_v4()));
+                std::fs::create_dir_all(&path).unwrap();
+                Self(path)
+            }
+
+            pub fn path(&self) -> &Path {
+                &self.0
+            }
+        }
+
+        impl Drop for Dir {
+            fn drop(&mut self) {
+                let _ = std::fs::remove_dir_all(&self.0);
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn returns_output_and_clears_environment() {
+        std::env::set_var("SANDBOX_SECRET", "x");
+        let (_dir, limits) = fake_tool("echo \"pages ${SANDBOX_SECRET:-none} $1\"");
+        let output = run_with(&limits, Tool::Pdfinfo, ["a.pdf"]).await.unwrap();
+        assert_eq!(
+            String::from_utf8_lossy(&output.stdout),
+            "pages none a.pdf\n"
+        );
+    }
+
+    #[tokio::test]
+    async fn distinguishes_failures() {
+        let (_dir, limits) = fake_tool("echo broken >&2; exit 3");
+        let err = run_with(&limits, Tool::Pdfinfo, NO_ARGS).await.unwrap_err();
+        assert!(
+            matches!(&err, SandboxError::Failed { code: 3, stderr, .. } if stderr == "broken"),
+            "{err}"
+        );
+        assert!(is_exit_failure(&err));
+
+        let (_dir, limits) = fake_tool("kill -SEGV $$");
+        let err = run_with(&limits, Tool::Pdfinfo, NO_ARGS).await.unwrap_err();
+        assert!(
+            matches!(
+                err,
+                SandboxError::Crashed {
+                    signal: libc::SIGSEGV,
+                    ..
+                }
+            ),
+            "{err}"
+        );
+
+        let (_dir, mut limits) = fake_tool("sleep 5");
+        limits.timeout = Duration::from_millis(200);
+        let (result, timeouts) = track_timeouts(run_with(&limits, Tool::Pdfinfo, NO_ARGS)).await;
+        let err = result.unwrap_err();
+        assert!(matches!(err, SandboxError::Timeout { .. }), "{err}");
+        assert_eq!(timeouts, 1);
+
+        let (_dir, limits) = fake_tool("while true; do echo aaaaaaaaaaaaaaaaaaaa; done");
+        let err = run_with(&limits, Tool::Pdfinfo, NO_ARGS).await.unwrap_err();
+        assert!(
+            matches!(err, SandboxError::OutputTooLarge { limit: 1024, .. }),
+            "{err}"
+        );
+
+        let (dir, mut limits) = fake_tool("true");
+        limits.bin_dirs = vec![dir.path().to_path_buf()];
+        let err = run_with(&limits, Tool::Pdftohtml, NO_ARGS)
+            .await
+            .unwrap_err();
+        assert!(
+            matches!(
+                err,
+                SandboxError::NotFound {
+                    tool: "pdftohtml",
+                    ..
+                }
+            ),
+            "{err}"
+        );
+    }
+}
@@ -0,0 +1,721 @@
+//! Page scheduling across concurrently extracted documents.
+//!
+//! Every page first takes one of `MAX_PARALLEL_TEXT` text slots for
+//! `pdftotext` (and later the vector layout); pages that need OCR then queue for
+//! one of the `MAX_PARALLEL_OCR` page slots, so text-only pages of large
+//! documents do not wait behind OCR. The scheduling mode decides which document
+//! gets the next free page slot:
+//!
+//! - `interleaved`: first come, first served per page (pages of all documents mix)
+//! - `fifo`: the oldest document wins; `EXTRACTION_FIFO_OVERLAP` younger documents
+//!   may use slots the older ones leave idle
+//! - `weighted`: weighted fair queuing by upload priority (low 1, normal 2, high 4)
+//!
+//! On top of the mode, documents run in one of two lanes chosen by the upload
+//! priority: `low` is batch reprocessing, `normal`/`high` are interactive
+//! uploads. Each lane has its own slot budget (`EXTRACTION_INTERACTIVE_SLOTS`,
+//! `EXTRACTION_BATCH_SLOTS`) and interactive pages always get the next free slot;
+//! batch documents only continue once no interactive page can be served. Pages
+//! already being extracted are never interrupted, so batch work yields at the
+//! next page boundary. Waiting interactive pages also get text slots first.
+//!
+//! Both limits adapt to the host: when a tool started under a slot hits
+//! `SUBPROCESS_TIMEOUT_SECS` (see [`crate::sandbox::track_timeouts`]), the limit
+//! is halved, and it grows back by one slot after [`GROW_AFTER`] stages in a row
+//! finished without timeout. Running stages are never interrupted; a lower limit
+//! only holds back the next grants.
+
+use std::collections::{BTreeMap, BTreeSet, HashMap, VecDeque};
+use std::env;
+use std::sync::{Arc, Mutex};
+
+use shared::dto::RunPriority;
+use tokio::sync::oneshot;
+use tracing::{info, warn};
+
+/// Stages in a row without timeout after which a lowered limit grows by one slot.
+pub const GROW_AFTER: u32 = 8;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+/// Strategy used to hand out page slots.
+pub enum SchedulingMode {
+    Interleaved,
+    Fifo { overlap: usize },
+    Weighted,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+/// Priority lane of a document.
+pub enum Lane {
+    Interactive,
+    Batch,
+}
+
+impl Lane {
+    pub fn for_priority(priority: RunPriority) -> Self {
+        match priority {
+            RunPriority::Low => Lane::Batch,
+            RunPriority::Normal | RunPriority::High => Lane::Interactive,
+        }
+    }
+
+    fn index(self) -> usize {
+        match self {
+            Lane::Interactive => 0,
+            Lane::Batch => 1,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+/// Maximum page slots each lane may hold at the same time.
+pub struct LaneBudgets {
+    pub interactive: usize,
+    pub batch: usize,
+}
+
+impl LaneBudgets {
+    /// Both lanes may use every slot.
+    pub fn unlimited(page_slots: usize) -> Self {
+        Self {
+            interactive: page_slots,
+            batch: page_slots,
+        }
+    }
+
+    fn get(&self, lane: Lane) -> usize {
+        match lane {
+            Lane::Interactive => self.interactive,
+            Lane::Batch => self.batch,
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+/// Scheduler settings read from the environment.
+pub struct SchedulerConfig {
+    pub mode: SchedulingMode,
+    /// Text slots (`pdftotext`, layout) shared by all documents.
+    pub text_slots: usize,
+    /// Total page slots for OCR shared by all documents.
+    pub page_slots: usize,
+    /// Slot budgets of the interactive and batch lanes.
+    pub lanes: LaneBudgets,
+    /// Documents taken from Kafka at the same time.
+    pub max_documents: usize,
+}
+
+impl SchedulerConfig {
+    pub fn from_env() -> Self {
+        let usize_var = |key: &str, default: usize| {
+            env::var(key)
+                .ok()
+                .and_then(|v| v.trim().parse::<usize>().ok())
+                .unwrap_or(default)
+        };
+        let mode = match env::var("EXTRACTION_SCHEDULING")
+            .unwrap_or_default()
+            .trim()
+            .to_ascii_lowercase()
+            .as_str()
+        {
+            "interleaved" => SchedulingMode::Interleaved,
+            "weighted" | "wfq" => SchedulingMode::Weighted,
+            _ => SchedulingMode::Fifo {
+                overlap: usize_var("EXTRACTION_FIFO_OVERLAP", 1),
+            },
+        };
+        let page_slots = usize_var("MAX_PARALLEL_OCR", 2).max(1);
+        Self {
+            mode,
+            text_slots: usize_var("MAX_PARALLEL_TEXT", 4).max(1),
+            page_slots,
+            lanes: LaneBudgets {
+                interactive: usize_var("EXTRACTION_INTERACTIVE_SLOTS", page_slots)
+                    .clamp(1, page_slots),
+                batch: usize_var("EXTRACTION_BATCH_SLOTS", page_slots).clamp(1, page_slots),
+            },
+            max_documents: usize_var("EXTRACTION_MAX_DOCUMENTS", 3).max(1),
+        }
+    }
+}
+
+fn weight(priority: RunPriority) -> f64 {
+    match priority {
+        RunPriority::Low => 1.0,
+        RunPriority::Normal => 2.0,
+        RunPriority::High => 4.0,
+    }
+}
+
+#[derive(Debug)]
+/// Slot limit that halves on timeouts and grows back additively (AIMD).
+struct AdaptiveLimit {
+    name: &'static str,
+    max: usize,
+    current: usize,
+    streak: u32,
+    /// Bumped on every decrease; only stages granted in the current epoch can
+    /// lower the limit again, so a burst of timeouts halves it once.
+    epoch: u64,
+}
+
+impl AdaptiveLimit {
+    fn new(name: &'static str, max: usize) -> Self {
+        let max = max.max(1);
+        Self {
+            name,
+            max,
+            current: max,
+            streak: 0,
+            epoch: 0,
+        }
+    }
+
+    /// Feeds back one finished stage granted in `epoch`; `true` when the limit grew.
+    fn record(&mut self, epoch: u64, timed_out: bool) -> bool {
+        if timed_out {
+            self.streak = 0;
+            if epoch == self.epoch && self.current > 1 {
+                self.current /= 2;
+                self.epoch += 1;
+                warn!(
+                    slots = self.name,
+                    limit = self.current,
+                    "subprocess timed out, parallelism reduced"
+                );
+            }
+            return false;
+        }
+        if self.current == self.max {
+            return false;
+        }
+        self.streak += 1;
+        if self.streak < GROW_AFTER {
+            return false;
+        }
+        self.streak = 0;
+        self.current += 1;
+        info!(
+            slots = self.name,
+            limit = self.current,
+            "parallelism raised"
+        );
+        true
+    }
+}
+
+struct Waiter {
+    ticket: u64,
+    tx: oneshot::Sender<PageSlot>,
+}
+
+struct DocQueue {
+    lane: Lane,
+    weight: f64,
+    /// Virtual finish tag of the last granted page (weighted mode).
+    finish: f64,
+    waiters: VecDeque<Waiter>,
+}
+
+struct State {
+    ocr: AdaptiveLimit,
+    /// Granted page slots per lane, indexed by [`Lane::index`].
+    busy: [usize; 2],
+    text: AdaptiveLimit,
+    text_busy: usize,
+    /// Pages waiting for a text slot per lane, indexed by [`Lane::index`].
+    text_waiters: [VecDeque<oneshot::Sender<TextSlot>>; 2],
+    next_seq: u64,
+    next_ticket: u64,
+    virtual_time: f64,
+    docs: BTreeMap<u64, DocQueue>,
+}
+
+struct Inner {
+    mode: SchedulingMode,
+    lanes: LaneBudgets,
+    state: Mutex<State>,
+}
+
+#[derive(Clone)]
+/// Shared page-slot dispatcher.
+pub struct PageScheduler {
+    inner: Arc<Inner>,
+}
+
+/// Registration of one document; dropping it removes the document.
+pub struct DocumentTicket {
+    inner: Arc<Inner>,
+    seq: u64,
+}
+
+#[derive(Clone)]
+/// Owned reference to a registered document for page tasks. Requests made
+/// after the [`DocumentTicket`] was dropped are granted without a slot.
+pub struct DocumentHandle {
+    inner: Arc<Inner>,
+    seq: u64,
+}
+
+/// A granted page slot; dropping it returns the slot.
+pub struct PageSlot {
+    inner: Option<Arc<Inner>>,
+    lane: Lane,
+    epoch: u64,
+}
+
+/// A granted text slot; dropping it returns the slot.
+pub struct TextSlot {
+    inner: Option<Arc<Inner>>,
+    epoch: u64,
+}
+
+impl PageScheduler {
+    /// Scheduler whose lanes may both use every page slot.
+    pub fn new(mode: SchedulingMode, text_slots: usize, page_slots: usize) -> Self {
+        Self::with_lanes(
+            mode,
+            text_slots,
+            page_slots,
+            LaneBudgets::unlimited(page_slots),
+        )
+    }
+
+    pub fn with_lanes(
+        mode: SchedulingMode,
+        text_slots: usize,
+        page_slots: usize,
+        lanes: LaneBudgets,
+    ) -> Self {
+        Self {
+            inner: Arc::new(Inner {
+                mode,
+                lanes,
+                state: Mutex::new(State {
+                    ocr: AdaptiveLimit::new("ocr", page_slots),
+                    busy: [0; 2],
+                    text: AdaptiveLimit::new("text", text_slots),
+                    text_busy: 0,
+                    text_waiters: Default::default(),
+                    next_seq: 0,
+                    next_ticket: 0,
+                    virtual_time: 0.0,
+                    docs: BTreeMap::new(),
+                }),
+            }),
+        }
+    }
+
+    /// Registers a document; FIFO order follows registration order, the lane
+    /// follows the priority (see [`Lane::for_priority`]).
+    pub fn register(&self, priority: RunPriority) -> DocumentTicket {
+        let mut state = self.inner.state.lock().unwrap();
+        let seq = state.next_seq;
+        state.next_seq += 1;
+        // Neue Dokumente starten bei der aktuellen virtuellen Zeit, sonst überholen sie alle
+        let finish = state.virtual_time;
+        state.docs.insert(
+            seq,
+            DocQueue {
+                lane: Lane::for_priority(priority),
+                weight: weight(priority),
+                finish,
+                waiters: VecDeque::new(),
+            },
+        );
+        DocumentTicket {
+            inner: self.inner.clone(),
+            seq,
+        }
+    }
+}
+
+impl DocumentTicket {
+    /// Queues a request for a page slot; the position in the queue is taken
+    /// immediately, the returned future resolves once the slot is granted.
+    pub fn acquire(&self) -> impl std::future::Future<Output = PageSlot> + Send + 'static {
+        self.handle().acquire()
+    }
+
+    pub fn handle(&self) -> DocumentHandle {
+        DocumentHandle {
+            inner: self.inner.clone(),
+            seq: self.seq,
+        }
+    }
+}
+
+impl DocumentHandle {
+    /// See [`DocumentTicket::acquire`].
+    pub fn acquire(&self) -> impl std::future::Future<Output = PageSlot> + Send + 'static {
+        let (tx, rx) = oneshot::channel();
+        {
+            let mut state = self.inner.state.lock().unwrap();
+            let ticket = state.next_ticket;
+            state.next_ticket += 1;
+            // Ist das Dokument schon abgemeldet, fällt der Sender weg und die Seite läuft ohne Slot
+            if let Some(doc) = state.docs.get_mut(&self.seq) {
+                doc.waiters.push_back(Waiter { ticket, tx });
+                self.inner.dispatch(&mut state);
+            }
+        }
+        async move {
+            rx.await.unwrap_or(PageSlot {
+                inner: None,
+                lane: Lane::Interactive,
+                epoch: 0,
+            })
+        }
+    }
+
+    /// Queues a request for a text slot in the lane of the document; like
+    /// [`Self::acquire`], the position is taken immediately.
+    pub fn text_slot(&self) -> impl std::future::Future<Output = TextSlot> + Send + 'static {
+        let (tx, rx) = oneshot::channel();
+        {
+            let mut state = self.inner.state.lock().unwrap();
+            if let Some(lane) = state.docs.get(&self.seq).map(|doc| doc.lane) {
+                state.text_waiters[lane.index()].push_back(tx);
+                self.inner.dispatch_text(&mut state);
+            }
+        }
+        async move {
+            rx.await.unwrap_or(TextSlot {
+                inner: None,
+                epoch: 0,
+            })
+        }
+    }
+}
+
+impl Drop for DocumentTicket {
+    fn drop(&mut self) {
+        let mut state = self.inner.state.lock().unwrap();
+        state.docs.remove(&self.seq);
+        self.inner.dispatch(&mut state);
+    }
+}
+
+impl PageSlot {
+    /// Reports whether a tool run under this slot timed out (see module docs).
+    pub fn report(&self, timed_out: bool) {
+        if let Some(inner) = &self.inner {
+            let mut state = inner.state.lock().unwrap();
+            if state.ocr.record(self.epoch, timed_out) {
+                inner.dispatch(&mut state);
+            }
+        }
+    }
+}
+
+impl Drop for PageSlot {
+    fn drop(&mut self) {
+        if let Some(inner) = self.inner.take() {
+            let mut state = inner.state.lock().unwrap();
+            state.busy[self.lane.index()] -= 1;
+            inner.dispatch(&mut state);
+        }
+    }
+}
+
+impl TextSlot {
+    /// Reports whether a tool run under this slot timed out (see module docs).
+    pub fn report(&self, timed_out: bool) {
+        if let Some(inner) = &self.inner {
+            let mut state = inner.state.lock().unwrap();
+            if state.text.record(self.epoch, timed_out) {
+                inner.dispatch_text(&mut state);
+            }
+        }
+    }
+}
+
+impl Drop for TextSlot {
+    fn drop(&mut self) {
+        if let Some(inner) = self.inner.take() {
+            let mut state = inner.state.lock().unwrap();
+            state.text_busy -= 1;
+            inner.dispatch_text(&mut state);
+        }
+    }
+}
+
+impl Inner {
+    /// Hands free slots to the waiters chosen by the scheduling mode.
+    fn dispatch(self: &Arc<Self>, state: &mut State) {
+        while state.busy.iter().sum::<usize>() < state.ocr.current {
+            let Some(seq) = self.pick(state) else {
+                return;
+            };
+            let virtual_time = state.virtual_time;
+            let doc = state.docs.get_mut(&seq).expect("picked document exists");
+            let waiter = doc
+                .waiters
+                .pop_front()
+                .expect("picked document has waiters");
+            let lane = doc.lane;
+            let slot = PageSlot {
+                inner: Some(self.clone()),
+                lane,
+                epoch: state.ocr.epoch,
+            };
+            match waiter.tx.send(slot) {
+                Ok(()) => {
+                    let start = doc.finish.max(virtual_time);
+                    doc.finish = start + 1.0 / doc.weight;
+                    state.virtual_time = start;
+                    state.busy[lane.index()] += 1;
+                }
+                // Wartender Task wurde abgebrochen: Slot ohne Drop-Rekursion zurücknehmen
+                Err(mut slot) => slot.inner = None,
+            }
+        }
+    }
+
+    /// Hands free text slots to waiting pages, interactive lane first.
+    fn dispatch_text(self: &Arc<Self>, state: &mut State) {
+        while state.text_busy < state.text.current {
+            let Some(tx) = state
+                .text_waiters
+                .iter_mut()
+                .find_map(|waiters| waiters.pop_front())
+            else {
+                return;
+            };
+            let slot = TextSlot {
+                inner: Some(self.clone()),
+                epoch: state.text.epoch,
+            };
+            match tx.send(slot) {
+                Ok(()) => state.text_busy += 1,
+                Err(mut slot) => slot.inner = None,
+            }
+        }
+    }
+
+    /// Interactive pages first; batch only when no interactive page can take the slot.
+    fn pick(&self, state: &State) -> Option<u64> {
+        [Lane::Interactive, Lane::Batch]
+            .into_iter()
+            .filter(|lane| state.busy[lane.index()] < self.lanes.get(*lane))
+            .find_map(|lane| self.pick_in_lane(state, lane))
+    }
+
+    fn pick_in_lane(&self, state: &State, lane: Lane) -> Option<u64> {
+        let in_lane = || state.docs.iter().filter(move |(_, doc)| doc.lane == lane);
+        let waiting = in_lane().filter(|(_, doc)| !doc.waiters.is_empty());
+        match self.mode {
+            SchedulingMode::Interleaved => waiting
+                .min_by_key(|(_, doc)| doc.waiters.front().map(|w| w.ticket))
+                .map(|(seq, _)| *seq),
+            SchedulingMode::Fifo { overlap } => {
+                let eligible: BTreeSet<u64> =
+                    in_lane().take(1 + overlap).map(|(seq, _)| *seq).collect();
+                waiting
+                    .map(|(seq, _)| *seq)
+                    .find(|seq| eligible.contains(seq))
+            }
+            SchedulingMode::Weighted => {
+                let tag = |doc: &DocQueue| doc.finish.max(state.virtual_time) + 1.0 / doc.weight;
+                waiting
+                    .min_by(|(a_seq, a), (b_seq, b)| {
+                        tag(a).total_cmp(&tag(b)).then(a_seq.cmp(b_seq))
+                    })
+                    .map(|(seq, _)| *seq)
+            }
+        }
+    }
+}
+
+/// Tracks in-flight Kafka offsets so that concurrent processing only commits
+/// offsets below which every message has been handled.
+#[derive(Default)]
+pub struct OffsetTracker {
+    partitions: HashMap<(String, i32), PartitionOffsets>,
+}
+
+#[derive(Default)]
+struct PartitionOffsets {
+    in_flight: BTreeSet<i64>,
+    highest: i64,
+}
+
+impl OffsetTracker {
+    pub fn begin(&mut self, topic: &str, partition: i32, offset: i64) {
+        let entry = self
+            .partitions
+            .entry((topic.to_string(), partition))
+            .or_default();
+        entry.in_flight.insert(offset);
+        entry.highest = entry.highest.max(offset);
+    }
+
+    /// Marks `offset` as done and returns the offset that is safe to commit.
+    pub fn finish(&mut self, topic: &str, partition: i32, offset: i64) -> i64 {
+        let entry = self
+            .partitions
+            .entry((topic.to_string(), partition))
+            .or_default();
+        entry.in_flight.remove(&offset);
+        entry.highest = entry.highest.max(offset);
+        match entry.in_flight.first() {
+            Some(oldest) => *oldest,
+            None => entry.highest + 1,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Requests `pages` slots per document round-robin and returns the grant order.
+    async fn grant_order(mode: SchedulingMode, docs: &[(RunPriority, usize)]) -> Vec<usize> {
+        let scheduler = PageScheduler::new(mode, 1, 1);
+        // Den einzigen Slot über ein eigenes Dokument belegen, damit sich alle Anfragen einreihen
+        let blocker = scheduler.register(RunPriority::Normal);
+        let held = blocker.acquire().await;
+        let tickets: Vec<_> = docs.iter().map(|(p, _)| scheduler.register(*p)).collect();
+
+        let mut pending = Vec::new();
+        for round in 0..docs.iter().map(|(_, pages)| *pages).max().unwrap_or(0) {
+            for (doc, ticket) in tickets.iter().enumerate() {
+                if round < docs[doc].1 {
+                    pending.push((doc, ticket.acquire()));
+                }
+            }
+        }
+        let order = Arc::new(Mutex::new(Vec::new()));
+        let tasks: Vec<_> = pending
+            .into_iter()
+            .map(|(doc, slot)| {
+                let order = order.clone();
+                tokio::spawn(async move {
+                    let _slot = slot.await;
+                    order.lock().unwrap().push(doc);
+                })
+            })
+            .collect();
+        drop(held);
+        drop(blocker);
+        for task in tasks {
+            task.await.unwrap();
+        }
+        let order = order.lock().unwrap().clone();
+        order
+    }
+
+    #[tokio::test]
+    async fn modes_order_pages_differently() {
+        let docs = [(RunPriority::Normal, 3), (RunPriority::Normal, 3)];
+        let order = grant_order(SchedulingMode::Interleaved, &docs).await;
+        assert_eq!(order, vec![0, 1, 0, 1, 0, 1]);
+
+        let order = grant_order(SchedulingMode::Fifo { overlap: 1 }, &docs).await;
+        assert_eq!(order, vec![0, 0, 0, 1, 1, 1]);
+    }
+
+    #[tokio::test]
+    async fn weighted_prefers_high_priority() {
+        let docs = [(RunPriority::Low, 3), (RunPriority::High, 3)];
+        let order = grant_order(SchedulingMode::Weighted, &docs).await;
+        assert_eq!(order, vec![1, 1, 1, 0, 0, 0]);
+    }
+
+    #[tokio::test]
+    async fn interactive_pages_overtake_queued_batch_pages() {
+        let docs = [(RunPriority::Low, 3), (RunPriority::Normal, 2)];
+        let order = grant_order(SchedulingMode::Fifo { overlap: 1 }, &docs).await;
+        assert_eq!(order, vec![1, 1, 0, 0, 0]);
+    }
+
+    #[tokio::test]
+    async fn batch_lane_stays_within_its_budget() {
+        let budgets = LaneBudgets {
+            interactive: 2,
+            batch: 1,
+        };
+        let scheduler = PageScheduler::with_lanes(SchedulingMode::Interleaved, 2, 2, budgets);
+        let busy = || scheduler.inner.state.lock().unwrap().busy;
+
+        let batch = scheduler.register(RunPriority::Low);
+        let first = batch.acquire().await;
+        let second = batch.acquire();
+        assert_eq!(busy(), [0, 1]);
+
+        // Der freie Slot bleibt für interaktive Seiten reserviert
+        let interactive = scheduler.register(RunPriority::High);
+        let _page = interactive.acquire().await;
+        assert_eq!(busy(), [1, 1]);
+
+        drop(first);
+        let _second = second.await;
+        assert_eq!(busy(), [1, 1]);
+    }
+
+    #[test]
+    fn adaptive_limit_halves_once_per_burst_and_recovers() {
+        let mut limit = AdaptiveLimit::new("ocr", 8);
+        let epoch = limit.epoch;
+        limit.record(epoch, true);
+        // Weitere Timeouts derselben Welle zählen nicht erneut
+        limit.record(epoch, true);
+        assert_eq!(limit.current, 4);
+        limit.record(limit.epoch, true);
+        assert_eq!(limit.current, 2);
+
+        for _ in 1..GROW_AFTER {
+            assert!(!limit.record(limit.epoch, false));
+        }
+        assert!(limit.record(limit.epoch, false));
+        assert_eq!(limit.current, 3);
+
+        let mut limit = AdaptiveLimit::new("text", 1);
+        limit.record(limit.epoch, true);
+        assert_eq!(limit.current, 1);
+        assert!(!limit.record(limit.epoch, false));
+    }
+
+    #[tokio::test]
+    async fn text_slots_are_separate_and_back_off_on_timeouts() {
+        let scheduler = PageScheduler::new(SchedulingMode::Interleaved, 2, 1);
+        let text_busy = || scheduler.inner.state.lock().unwrap().text_busy;
+        let tickets = [
+            scheduler.register(RunPriority::Low),
+            scheduler.register(RunPriority::Normal),
+        ];
+        let [batch, interactive] = tickets.each_ref().map(DocumentTicket::handle);
+
+        // Der OCR-Slot ist belegt, Text-Slots bleiben trotzdem frei
+        let _ocr = interactive.acquire().await;
+        let first = batch.text_slot().await;
+        let second = batch.text_slot().await;
+        assert_eq!(text_busy(), 2);
+
+        let queued_batch = batch.text_slot();
+        let queued_interactive = interactive.text_slot();
+        second.report(true);
+        drop(second);
+        // Limit halbiert: der freie Slot wird nicht neu vergeben
+        assert_eq!(text_busy(), 1);
+
+        drop(first);
+        let granted = queued_interactive.await;
+        assert_eq!(text_busy(), 1);
+        drop(granted);
+        let _granted = queued_batch.await;
+        assert_eq!(text_busy(), 1);
+    }
+
+    #[test]
+    fn offset_tracker_commits_contiguous_prefix() {
+        let mut tracker = OffsetTracker::default();
+        tracker.begin("pdf-merged", 0, 10);
+        tracker.begin("pdf-merged", 0, 11);
+        tracker.begin("pdf-merged", 0, 12);
+        assert_eq!(tracker.finish("pdf-merged", 0, 11), 10);
+        assert_eq!(tracker.finish("pdf-merged", 0, 10), 12);
+        assert_eq!(tracker.finish("pdf-merged", 0, 12), 13);
+    }
+}
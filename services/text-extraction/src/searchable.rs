@@ -0,0 +1,154 @@
+//! Searchable copies of scanned documents (OCR text layer overlay).
+//!
+//! [`create_searchable_pdf`] renders every page at `OCR_DPI`, lets
+//! `tesseract ... pdf` turn the image into a one-page PDF with the recognized
+//! text as an invisible layer on top of it and joins the pages with
+//! `pdfunite`. All pages become images, vector pages included, so the service
+//! only builds the copy for documents with at least one OCR page
+//! (`SEARCHABLE_PDF=1`) and keeps it next to the original in
+//! `merged_pdfs.searchable_data`; pdf-ingest serves it under
+//! `GET /pdf/{id}/searchable`.
+
+use std::{env, ffi::OsStr, path::PathBuf};
+
+use anyhow::{Context, Result};
+use futures_util::future::try_join_all;
+use shared::dto::RunPriority;
+use tracing::{info, warn};
+use uuid::Uuid;
+
+use crate::sandbox::{self, Tool};
+use crate::scheduler::{DocumentTicket, PageScheduler, SchedulingMode};
+use crate::{detect_pages, ocr_stage, render_page, ExtractionConfig};
+
+/// Adds the searchable copy to `merged_pdfs` (see migration 0048).
+pub const SCHEMA_SQL: &str = "ALTER TABLE merged_pdfs
+    ADD COLUMN IF NOT EXISTS searchable_data BYTEA,
+    ADD COLUMN IF NOT EXISTS searchable_sha256 TEXT,
+    ADD COLUMN IF NOT EXISTS searchable_created_at TIMESTAMPTZ";
+
+#[derive(Clone, Debug)]
+/// Configuration of the searchable copy (`SEARCHABLE_PDF`, `SEARCHABLE_PDF_MAX_PAGES`).
+pub struct SearchableOptions {
+    pub enabled: bool,
+    /// Longer documents get no searchable copy.
+    pub max_pages: i64,
+}
+
+impl Default for SearchableOptions {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            max_pages: 200,
+        }
+    }
+}
+
+impl SearchableOptions {
+    pub fn from_env() -> Self {
+        let defaults = Self::default();
+        Self {
+            enabled: env::var("SEARCHABLE_PDF")
+                .map(|v| v == "1" || v.eq_ignore_ascii_case("true"))
+                .unwrap_or(defaults.enabled),
+            max_pages: env::var("SEARCHABLE_PDF_MAX_PAGES")
+                .ok()
+                .and_then(|v| v.trim().parse().ok())
+                .unwrap_or(defaults.max_pages),
+        }
+    }
+}
+
+/// Directory in the temp directory that is removed with its content on drop.
+struct TempDir {
+    path: PathBuf,
+}
+
+impl TempDir {
+    async fn create() -> Result<Self> {
+        let path = env::temp_dir().join(format!("searchable_{}", Uuid::new_v4()));
+        tokio::fs::create_dir(&path)
+            .await
+            .with_context(|| format!("create {}", path.display()))?;
+        Ok(Self { path })
+    }
+}
+
+impl Drop for TempDir {
+    fn drop(&mut self) {
+        if let Err(e) = std::fs::remove_dir_all(&self.path) {
+            warn!(%e, path = %self.path.display(), "remove searchable temp dir failed");
+        }
+    }
+}
+
+/// Builds a searchable copy of the PDF at `path`: every page as image with an
+/// invisible OCR text layer (see module docs).
+pub async fn create_searchable_pdf(path: &str, config: &ExtractionConfig) -> Result<Vec<u8>> {
+    let scheduler = PageScheduler::new(
+        SchedulingMode::Interleaved,
+        config.max_parallel_text,
+        config.max_parallel_ocr,
+    );
+    let ticket = scheduler.register(RunPriority::default());
+    create_searchable_pdf_scheduled(path, &ticket, config).await
+}
+
+/// Like [`create_searchable_pdf`], but every page waits for a page slot of a
+/// scheduler shared with other documents.
+pub async fn create_searchable_pdf_scheduled(
+    path: &str,
+    ticket: &DocumentTicket,
+    config: &ExtractionConfig,
+) -> Result<Vec<u8>> {
+    let pages = detect_pages(path, config.password.as_ref()).await?;
+    let dir = TempDir::create().await?;
+    let document = ticket.handle();
+    // Slots in Seitenreihenfolge anfordern, die Seiten laufen parallel
+    let page_pdfs = try_join_all(
+        (1..=pages)
+            .map(|page| ocr_stage(document.acquire(), overlay_page(path, page, &dir, config))),
+    )
+    .await?;
+
+    let output = dir.path.join("searchable.pdf");
+    let mut args: Vec<&OsStr> = page_pdfs.iter().map(|p| p.as_os_str()).collect();
+    args.push(output.as_os_str());
+    sandbox::run(Tool::Pdfunite, args)
+        .await
+        .context("pdfunite searchable pages")?;
+    let data = tokio::fs::read(&output)
+        .await
+        .context("read searchable pdf")?;
+    info!(?path, pages, bytes = data.len(), "searchable pdf created");
+    Ok(data)
+}
+
+/// Renders the 1-based `page` and lets Tesseract write it as one-page PDF
+/// with text layer into `dir`; returns the path of that PDF.
+async fn overlay_page(
+    path: &str,
+    page: i32,
+    dir: &TempDir,
+    config: &ExtractionConfig,
+) -> Result<PathBuf> {
+    let image = render_page(path, page, config.ocr_dpi, config.password.as_ref()).await?;
+    // tesseract hängt .pdf an den Basisnamen an
+    let base = dir.path.join(format!("page_{page:05}"));
+    let dpi = config.ocr_dpi.to_string();
+    let args: [&OsStr; 9] = [
+        image.path.as_ref(),
+        base.as_os_str(),
+        "--dpi".as_ref(),
+        dpi.as_ref(),
+        "-l".as_ref(),
+        config.ocr_lang.as_ref(),
+        "--psm".as_ref(),
+        config.ocr_psm.as_ref(),
+        "pdf".as_ref(),
+    ];
+    sandbox::run(Tool::Tesseract, args)
+        .await
+        .with_context(|| format!("tesseract pdf on page {page}"))?;
+    Ok(base.with_extension("pdf"))
+}
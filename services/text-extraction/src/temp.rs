@@ -0,0 +1,67 @@
+//! Managed temporary copies of in-memory PDFs.
+//!
+//! poppler and Tesseract read their input from files and `pdftoppm` needs
+//! random access, so bytes held in memory (e.g. `merged_pdfs.data`) are written
+//! to a [`TempPdf`] first. The file is removed when the guard is dropped, on
+//! every return path of the caller.
+
+use std::path::PathBuf;
+
+use anyhow::{Context, Result};
+use tracing::warn;
+use uuid::Uuid;
+
+/// PDF file in the temp directory that is deleted on drop.
+#[derive(Debug)]
+pub struct TempPdf {
+    path: String,
+}
+
+impl TempPdf {
+    /// Writes `data` to a new, uniquely named file in [`std::env::temp_dir`].
+    pub async fn write(data: &[u8]) -> Result<Self> {
+        // Guard vor dem Schreiben anlegen, damit auch Teilschreibungen gelöscht werden
+        let temp = Self::reserve();
+        tokio::fs::write(&temp.path, data)
+            .await
+            .with_context(|| format!("write temp pdf {}", temp.path))?;
+        Ok(temp)
+    }
+
+    /// Unique path for a file a tool writes (e.g. `pdfdetach -o`); whatever
+    /// ends up there is removed on drop.
+    pub fn reserve() -> Self {
+        let path: PathBuf = std::env::temp_dir().join(format!("pdf_{}.pdf", Uuid::new_v4()));
+        Self {
+            path: path.to_string_lossy().into_owned(),
+        }
+    }
+
+    pub fn path(&self) -> &str {
+        &self.path
+    }
+}
+
+impl Drop for TempPdf {
+    fn drop(&mut self) {
+        if let Err(e) = std::fs::remove_file(&self.path) {
+            if e.kind() != std::io::ErrorKind::NotFound {
+                warn!(%e, path = %self.path, "remove temp pdf failed");
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn file_is_removed_on_drop() {
+        let temp = TempPdf::write(b"%PDF-1.4").await.unwrap();
+        let path = temp.path().to_string();
+        assert_eq!(std::fs::read(&path).unwrap(), b"%PDF-1.4");
+        drop(temp);
+        assert!(!std::path::Path::new(&path).exists());
+    }
+}
@@ -1,11 +1,23 @@
 //! Integration tests verifying the OCR extraction workflow.
 
 use base64;
-use text_extraction::{extract_text, extract_text_pages};
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine as _;
+use futures_util::TryStreamExt;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use text_extraction::cache::{CacheFuture, CacheKey, ExtractionCache, MemoryCache};
+use text_extraction::searchable::create_searchable_pdf;
+use text_extraction::{
+    extract_text, extract_text_pages, extract_text_pages_stream, ExtractionConfig, PageExtraction,
+};
+
+/// One page with the text "Hello" in its text layer.
+const HELLO_PDF: &str = "JVBERi0xLjQKMSAwIG9iaiA8PC9UeXBlL0NhdGFsb2cvUGFnZXMgMiAwIFI+PgplbmRvYmoKMiAwIG9iaiA8PC9UeXBlL1BhZ2VzL0tpZHMgWzMgMCBSXS9Db3VudCAxPj4KZW5kb2JqCjMgMCBvYmoKPDwvVHlwZS9QYWdlL1BhcmVudCAyIDAgUi9Db250ZW50cyA0IDAgUi9NZWRpYUJveCBbMCAwIDIwMCAyMDBdPj4KZW5kb2JqCjQgMCBvYmoKPDwvTGVuZ3RoIDQ0Pj4Kc3RyZWFtCkJUL0YxIDI0IFRmIDEwMCAxMDAgVGQgKEhlbGxvKSBUagpFVAplbmRzdHJlYW0KZW5kb2JqCnhyZWYKMCA1CjAwMDAwMDAwMDAgNjU1MzUgZgowMDAwMDAwMDEwIDAwMDAwIG4gCjAwMDAwMDAwNjEgMDAwMDAgbiAKMDAwMDAwMDAxMTcgMDAwMDAgbiAKMDAwMDAwMDAxOTkgMDAwMDAgbiAKdHJhaWxlcgo8PC9TaXplIDUvUm9vdCAxIDAgUj4+CnN0YXJ0eHJlZgo3MjYKJSVFT0YK";
 
 #[tokio::test]
 async fn pdf_to_text() {
-    let pdf_data = base64::decode("JVBERi0xLjQKMSAwIG9iaiA8PC9UeXBlL0NhdGFsb2cvUGFnZXMgMiAwIFI+PgplbmRvYmoKMiAwIG9iaiA8PC9UeXBlL1BhZ2VzL0tpZHMgWzMgMCBSXS9Db3VudCAxPj4KZW5kb2JqCjMgMCBvYmoKPDwvVHlwZS9QYWdlL1BhcmVudCAyIDAgUi9Db250ZW50cyA0IDAgUi9NZWRpYUJveCBbMCAwIDIwMCAyMDBdPj4KZW5kb2JqCjQgMCBvYmoKPDwvTGVuZ3RoIDQ0Pj4Kc3RyZWFtCkJUL0YxIDI0IFRmIDEwMCAxMDAgVGQgKEhlbGxvKSBUagpFVAplbmRzdHJlYW0KZW5kb2JqCnhyZWYKMCA1CjAwMDAwMDAwMDAgNjU1MzUgZgowMDAwMDAwMDEwIDAwMDAwIG4gCjAwMDAwMDAwNjEgMDAwMDAgbiAKMDAwMDAwMDAxMTcgMDAwMDAgbiAKMDAwMDAwMDAxOTkgMDAwMDAgbiAKdHJhaWxlcgo8PC9TaXplIDUvUm9vdCAxIDAgUj4+CnN0YXJ0eHJlZgo3MjYKJSVFT0YK").unwrap();
+    let pdf_data = base64::decode(HELLO_PDF).unwrap();
     let path = "/tmp/test.pdf";
     tokio::fs::write(path, pdf_data).await.unwrap();
     let txt = extract_text(path).await.unwrap();
@@ -15,15 +27,15 @@ async fn pdf_to_text() {
 
 #[tokio::test]
 async fn ocr_image_pdf() {
-    std::env::set_var("OCR_ENABLED", "1");
-    std::env::set_var("LAYOUT_ENABLED", "0");
-    std::env::set_var("MAX_PARALLEL_OCR", "1");
-
     let path = "/tmp/ocr_image.pdf";
     let pdf_data = base64::decode(include_str!("ocr_sample.b64")).unwrap();
     tokio::fs::write(path, pdf_data).await.unwrap();
 
-    let pages = extract_text_pages(path).await.unwrap();
+    let config = ExtractionConfig::default()
+        .ocr_enabled(true)
+        .layout_enabled(false)
+        .max_parallel_ocr(1);
+    let pages = extract_text_pages(path, &config).await.unwrap();
     assert!(!pages.is_empty());
     let page = &pages[0];
     assert!(page.ocr_used, "expected ocr fallback to be used");
@@ -36,7 +48,99 @@ async fn ocr_image_pdf() {
     assert!(char_count > 0);
 
     let _ = tokio::fs::remove_file(path).await;
-    std::env::remove_var("OCR_ENABLED");
-    std::env::remove_var("LAYOUT_ENABLED");
-    std::env::remove_var("MAX_PARALLEL_OCR");
+}
+
+#[tokio::test]
+async fn stream_yields_pages() {
+    let path = "/tmp/stream_test.pdf";
+    tokio::fs::write(path, STANDARD.decode(HELLO_PDF).unwrap())
+        .await
+        .unwrap();
+
+    let config = ExtractionConfig::default()
+        .ocr_enabled(false)
+        .layout_enabled(false);
+    let pages: Vec<PageExtraction> = extract_text_pages_stream(path, &config)
+        .try_collect()
+        .await
+        .unwrap();
+    assert_eq!(pages.len(), 1);
+    assert!(pages[0].text.to_lowercase().contains("hello"));
+
+    let _ = tokio::fs::remove_file(path).await;
+}
+
+/// Memory cache that counts hits and stores.
+#[derive(Debug)]
+struct CountingCache {
+    inner: MemoryCache,
+    hits: AtomicUsize,
+    puts: AtomicUsize,
+}
+
+impl ExtractionCache for CountingCache {
+    fn get<'a>(&'a self, key: &'a CacheKey) -> CacheFuture<'a, Option<PageExtraction>> {
+        Box::pin(async move {
+            let page = self.inner.get(key).await?;
+            if page.is_some() {
+                self.hits.fetch_add(1, Ordering::SeqCst);
+            }
+            Ok(page)
+        })
+    }
+
+    fn put<'a>(&'a self, key: &'a CacheKey, page: &'a PageExtraction) -> CacheFuture<'a, ()> {
+        self.puts.fetch_add(1, Ordering::SeqCst);
+        self.inner.put(key, page)
+    }
+}
+
+#[tokio::test]
+async fn cache_stores_and_reuses_pages() {
+    let path = "/tmp/cache_test.pdf";
+    tokio::fs::write(path, STANDARD.decode(HELLO_PDF).unwrap())
+        .await
+        .unwrap();
+
+    let cache = Arc::new(CountingCache {
+        inner: MemoryCache::new(10),
+        hits: AtomicUsize::new(0),
+        puts: AtomicUsize::new(0),
+    });
+    let config = ExtractionConfig::default()
+        .ocr_enabled(false)
+        .layout_enabled(false)
+        .cache(cache.clone());
+    let first = extract_text_pages(path, &config).await.unwrap();
+    assert_eq!(cache.inner.len(), 1);
+    assert_eq!(cache.hits.load(Ordering::SeqCst), 0);
+    assert_eq!(cache.puts.load(Ordering::SeqCst), 1);
+
+    // Zweiter Lauf kommt vollständig aus dem Cache, ohne erneut zu speichern
+    let second = extract_text_pages(path, &config).await.unwrap();
+    assert_eq!(cache.hits.load(Ordering::SeqCst), 1);
+    assert_eq!(cache.puts.load(Ordering::SeqCst), 1);
+    assert_eq!(first[0].text, second[0].text);
+
+    let _ = tokio::fs::remove_file(path).await;
+}
+
+#[tokio::test]
+async fn searchable_pdf_carries_ocr_text() {
+    let path = "/tmp/searchable_test.pdf";
+    let pdf_data = STANDARD.decode(include_str!("ocr_sample.b64")).unwrap();
+    tokio::fs::write(path, pdf_data).await.unwrap();
+
+    let config = ExtractionConfig::default().max_parallel_ocr(1);
+    let searchable = create_searchable_pdf(path, &config).await.unwrap();
+    assert!(searchable.starts_with(b"%PDF-"));
+
+    // Die unsichtbare Textebene liefert pdftotext den erkannten Text
+    let out = "/tmp/searchable_test_out.pdf";
+    tokio::fs::write(out, &searchable).await.unwrap();
+    let text = extract_text(out).await.unwrap();
+    assert!(text.chars().any(|c| c.is_alphanumeric()));
+
+    let _ = tokio::fs::remove_file(path).await;
+    let _ = tokio::fs::remove_file(out).await;
 }
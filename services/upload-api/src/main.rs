@@ -5,6 +5,7 @@ use actix_web::{web, App, Error, HttpResponse, HttpServer};
 use chrono::{DateTime, Utc};
 use futures_util::StreamExt as _;
 use serde::{Deserialize, Serialize};
+use shared::startup::{retry_with_backoff, Backoff, Readiness};
 use sqlx::{postgres::PgPoolOptions, PgPool};
 use tokio::fs::File;
 use tokio::io::AsyncWriteExt;
@@ -44,6 +45,12 @@ struct StatusEvent {
 struct AppState {
     pool: PgPool,
     tx: broadcast::Sender<StatusEvent>,
+    readiness: Readiness,
+}
+
+/// Readiness endpoint; returns 503 until Postgres is connected and the schema exists.
+async fn readyz(data: web::Data<AppState>) -> HttpResponse {
+    data.readiness.respond()
 }
 
 /// Hängt `sslmode=disable` an, falls die URL noch keinen Wert setzt.
@@ -257,27 +264,38 @@ async fn main() -> std::io::Result<()> {
     });
     let database_url = ensure_sslmode_disable(&raw_url);
 
-    // Verbindungspool
+    // Lazy pool: Verbindung wird im Hintergrund mit Backoff aufgebaut
     let pool = match PgPoolOptions::new()
         .max_connections(8)
-        .connect(&database_url)
-        .await
+        .connect_lazy(&database_url)
     {
         Ok(p) => p,
         Err(e) => {
-            error!(%e, db_url=%database_url, "failed to connect to database");
+            error!(%e, db_url=%database_url, "invalid DATABASE_URL");
             std::process::exit(1);
         }
     };
 
-    // Schema sicherstellen
-    if let Err(e) = ensure_schema(&pool).await {
-        error!(%e, "failed to ensure schema");
-        std::process::exit(1);
+    let readiness = Readiness::new(&["postgres"]);
+    let backoff = Backoff::from_env();
+
+    // Schema sicherstellen, sobald Postgres erreichbar ist (kein Crash-Loop beim Kaltstart)
+    {
+        let pool = pool.clone();
+        let readiness = readiness.clone();
+        actix_web::rt::spawn(async move {
+            retry_with_backoff("postgres", backoff, || ensure_schema(&pool)).await;
+            readiness.mark_ready("postgres");
+            info!("postgres ready");
+        });
     }
 
     let (tx, _rx) = broadcast::channel(32);
-    let data = web::Data::new(AppState { pool, tx });
+    let data = web::Data::new(AppState {
+        pool,
+        tx,
+        readiness,
+    });
 
     info!("starting uploads service on :8095");
     HttpServer::new(move || {
@@ -286,6 +304,7 @@ async fn main() -> std::io::Result<()> {
             .route("/uploads", web::post().to(upload))
             .route("/uploads", web::get().to(list_uploads))
             .route("/uploads/stream", web::get().to(sse_stream))
+            .route("/readyz", web::get().to(readyz))
     })
     .bind(("0.0.0.0", 8095))?
     .run()
@@ -0,0 +1,184 @@
+//! Reviewer markups on document pages (`pdf_annotations`).
+//!
+//! The review UI stores highlights, boxes and notes per page through
+//! `POST /pdf/{id}/annotations` on pdf-ingest. Boxes use the coordinates of
+//! `pdf_texts.layout_json` (PDF points, origin top-left of the displayed page),
+//! so `GET /pdf/{id}/pages/{page}` can return both together. An annotation may
+//! point at a final key of a run; later runs can read those pairs as grounding
+//! hints for the same field.
+
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+/// Idempotent DDL (mirrors `migrations/0038_pdf_annotations.sql`).
+pub const CREATE_TABLE_SQL: &str = "CREATE TABLE IF NOT EXISTS pdf_annotations (
+    id BIGSERIAL PRIMARY KEY,
+    merged_pdf_id INTEGER NOT NULL REFERENCES merged_pdfs(id) ON DELETE CASCADE,
+    page_no INTEGER NOT NULL,
+    kind TEXT NOT NULL,
+    bbox JSONB,
+    note TEXT,
+    final_key TEXT,
+    run_id UUID,
+    author TEXT,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT now()
+)";
+
+/// Longest accepted note, in characters.
+pub const MAX_NOTE_CHARS: usize = 4000;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum AnnotationKind {
+    /// Marked text; needs a box.
+    Highlight,
+    /// Free rectangle; needs a box.
+    Rect,
+    /// Comment on the page, optionally anchored at a box.
+    Note,
+}
+
+impl AnnotationKind {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            AnnotationKind::Highlight => "highlight",
+            AnnotationKind::Rect => "rect",
+            AnnotationKind::Note => "note",
+        }
+    }
+
+    pub fn parse(raw: &str) -> Option<Self> {
+        match raw.trim().to_ascii_lowercase().as_str() {
+            "highlight" => Some(AnnotationKind::Highlight),
+            "rect" | "box" => Some(AnnotationKind::Rect),
+            "note" | "comment" => Some(AnnotationKind::Note),
+            _ => None,
+        }
+    }
+}
+
+/// Body of `POST /pdf/{id}/annotations`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct NewAnnotation {
+    /// 0-based page number, as in `pdf_texts`.
+    pub page_no: i32,
+    pub kind: AnnotationKind,
+    /// `[x0, y0, x1, y1]` in layout coordinates.
+    #[serde(default)]
+    pub bbox: Option<[i32; 4]>,
+    #[serde(default)]
+    pub note: Option<String>,
+    /// Final key of a run the markup belongs to, e.g. `vertragsnummer`.
+    #[serde(default)]
+    pub final_key: Option<String>,
+    #[serde(default)]
+    pub run_id: Option<Uuid>,
+    #[serde(default)]
+    pub author: Option<String>,
+}
+
+impl NewAnnotation {
+    /// Trims the text fields and drops empty ones.
+    pub fn normalized(mut self) -> Self {
+        let clean = |value: Option<String>| {
+            value
+                .map(|v| v.trim().to_string())
+                .filter(|v| !v.is_empty())
+        };
+        self.note = clean(self.note);
+        self.final_key = clean(self.final_key);
+        self.author = clean(self.author);
+        self
+    }
+
+    /// Checks page, box and note; the message is meant for the client.
+    pub fn validate(&self) -> Result<(), String> {
+        if self.page_no < 0 {
+            return Err("page_no must not be negative".into());
+        }
+        match self.bbox {
+            Some([x0, y0, x1, y1]) if x0 < 0 || y0 < 0 || x1 <= x0 || y1 <= y0 => {
+                return Err("bbox must be [x0, y0, x1, y1] with x0 < x1 and y0 < y1".into());
+            }
+            None if self.kind != AnnotationKind::Note => {
+                return Err(format!("{} needs a bbox", self.kind.as_str()));
+            }
+            _ => {}
+        }
+        match self.note.as_deref() {
+            None if self.kind == AnnotationKind::Note => Err("note needs a text".into()),
+            Some(note) if note.chars().count() > MAX_NOTE_CHARS => {
+                Err(format!("note is longer than {MAX_NOTE_CHARS} characters"))
+            }
+            _ => Ok(()),
+        }
+    }
+}
+
+/// Stored annotation as returned by `GET /pdf/{id}/annotations`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PageAnnotation {
+    pub id: i64,
+    pub merged_pdf_id: i32,
+    pub page_no: i32,
+    pub kind: AnnotationKind,
+    pub bbox: Option<[i32; 4]>,
+    pub note: Option<String>,
+    pub final_key: Option<String>,
+    pub run_id: Option<Uuid>,
+    pub author: Option<String>,
+    /// RFC 3339.
+    pub created_at: String,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn new(kind: AnnotationKind, bbox: Option<[i32; 4]>, note: Option<&str>) -> NewAnnotation {
+        NewAnnotation {
+            page_no: 0,
+            kind,
+            bbox,
+            note: note.map(str::to_string),
+            final_key: None,
+            run_id: None,
+            author: None,
+        }
+        .normalized()
+    }
+
+    #[test]
+    fn validates_boxes_and_notes() {
+        assert!(new(AnnotationKind::Highlight, Some([10, 10, 50, 20]), None)
+            .validate()
+            .is_ok());
+        assert!(new(AnnotationKind::Rect, None, None).validate().is_err());
+        assert!(new(AnnotationKind::Rect, Some([50, 10, 10, 20]), None)
+            .validate()
+            .is_err());
+        assert!(new(AnnotationKind::Note, None, Some("  "))
+            .validate()
+            .is_err());
+        assert!(new(AnnotationKind::Note, None, Some("Unterschrift fehlt"))
+            .validate()
+            .is_ok());
+        let long = "x".repeat(MAX_NOTE_CHARS + 1);
+        assert!(new(AnnotationKind::Note, None, Some(&long))
+            .validate()
+            .is_err());
+    }
+
+    #[test]
+    fn parses_body_with_defaults() {
+        let body: NewAnnotation = serde_json::from_str(
+            r#"{"page_no": 2, "kind": "rect", "bbox": [1, 2, 3, 4], "final_key": " iban "}"#,
+        )
+        .unwrap();
+        let body = body.normalized();
+        assert_eq!(body.kind, AnnotationKind::Rect);
+        assert_eq!(body.final_key.as_deref(), Some("iban"));
+        assert!(body.note.is_none());
+        assert_eq!(AnnotationKind::parse("Box"), Some(AnnotationKind::Rect));
+    }
+}
@@ -0,0 +1,323 @@
+//! Admin-editable `app_settings` entries, their audit trail and change events.
+//!
+//! [`KNOWN_SETTINGS`] lists the keys the pipeline-api exposes under
+//! `/admin/settings` together with their validation. Every write through
+//! [`store`] records the old and new value in `app_settings_audit` and queues a
+//! [`SettingsChanged`] event on `settings-changed` via the outbox, in the same
+//! transaction; the pipeline-runner reloads the setting when it arrives.
+//! Tenant credentials (`openai_credentials:*`) keep their own endpoints and are
+//! not listed here.
+
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use sqlx::{PgConnection, PgPool, Row};
+
+use crate::openai_settings;
+use crate::outbox;
+use crate::runner_settings::{RunnerSettings, RUNNER_SETTINGS_KEY};
+
+/// Topic of [`SettingsChanged`].
+pub const SETTINGS_CHANGED_TOPIC: &str = "settings-changed";
+
+/// Idempotent DDL of the audit trail (mirrors `migrations/0046_app_settings_audit.sql`).
+pub const CREATE_AUDIT_SQL: &[&str] = &[
+    "CREATE TABLE IF NOT EXISTS app_settings_audit (
+        id BIGSERIAL PRIMARY KEY,
+        key TEXT NOT NULL,
+        old_value TEXT,
+        new_value TEXT,
+        changed_by TEXT,
+        changed_at TIMESTAMPTZ NOT NULL DEFAULT now()
+    )",
+    "CREATE INDEX IF NOT EXISTS idx_app_settings_audit_key
+        ON app_settings_audit (key, changed_at DESC)",
+];
+
+/// Event queued after a setting was changed or reset.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SettingsChanged {
+    pub key: String,
+    #[serde(default)]
+    pub changed_by: Option<String>,
+}
+
+/// How a known setting is validated and shown.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SettingKind {
+    /// One of [`openai_settings::OPENAI_VERSION_OPTIONS`].
+    #[serde(rename = "openai_version")]
+    OpenAiVersion,
+    /// JSON object of [`RunnerSettings`].
+    RunnerSettings,
+    /// Written by a service, read-only for admins.
+    Status,
+}
+
+/// A key of `app_settings` managed through `/admin/settings`.
+#[derive(Debug, Clone, Copy, Serialize)]
+pub struct KnownSetting {
+    pub key: &'static str,
+    pub description: &'static str,
+    pub kind: SettingKind,
+}
+
+/// All settings the admin API lists.
+pub const KNOWN_SETTINGS: &[KnownSetting] = &[
+    KnownSetting {
+        key: openai_settings::OPENAI_VERSION_KEY,
+        description: "OpenAI deployment used by the pipeline-runner",
+        kind: SettingKind::OpenAiVersion,
+    },
+    KnownSetting {
+        key: RUNNER_SETTINGS_KEY,
+        description: "Runtime overrides of the pipeline-runner batch configuration",
+        kind: SettingKind::RunnerSettings,
+    },
+    KnownSetting {
+        key: "runner_disk_usage",
+        description: "Disk usage reported by the pipeline-runner",
+        kind: SettingKind::Status,
+    },
+];
+
+/// Looks up a key of [`KNOWN_SETTINGS`].
+pub fn known(key: &str) -> Option<&'static KnownSetting> {
+    KNOWN_SETTINGS.iter().find(|s| s.key == key)
+}
+
+impl KnownSetting {
+    pub fn editable(&self) -> bool {
+        self.kind != SettingKind::Status
+    }
+
+    /// Allowed values of enumerated settings, empty otherwise.
+    pub fn options(&self) -> Vec<&'static str> {
+        match self.kind {
+            SettingKind::OpenAiVersion => openai_settings::OPENAI_VERSION_OPTIONS
+                .iter()
+                .map(|o| o.key)
+                .collect(),
+            _ => Vec::new(),
+        }
+    }
+
+    /// Effective value while no row is stored.
+    pub fn default_value(&self) -> Value {
+        match self.kind {
+            SettingKind::OpenAiVersion => Value::from(openai_settings::DEFAULT_OPENAI_VERSION),
+            SettingKind::RunnerSettings => {
+                serde_json::to_value(RunnerSettings::default()).unwrap_or(Value::Null)
+            }
+            SettingKind::Status => Value::Null,
+        }
+    }
+
+    /// Checks a new value and returns the text stored in `app_settings.value`;
+    /// the message is meant for the client.
+    pub fn validate(&self, value: &Value) -> Result<String, String> {
+        match self.kind {
+            SettingKind::OpenAiVersion => match value.as_str() {
+                Some(v) if openai_settings::is_valid_openai_version(v) => Ok(v.to_string()),
+                _ => Err(format!(
+                    "{} must be one of {}",
+                    self.key,
+                    self.options().join(", ")
+                )),
+            },
+            SettingKind::RunnerSettings => {
+                let settings: RunnerSettings = serde_json::from_value(value.clone())
+                    .map_err(|e| format!("invalid {}: {e}", self.key))?;
+                settings.validate()?;
+                serde_json::to_string(&settings).map_err(|e| e.to_string())
+            }
+            SettingKind::Status => Err(format!("{} is read-only", self.key)),
+        }
+    }
+
+    /// Stored text as JSON: plain strings for enumerated settings, parsed JSON
+    /// otherwise (the raw text if it does not parse).
+    pub fn decode(&self, raw: &str) -> Value {
+        match self.kind {
+            SettingKind::OpenAiVersion => Value::from(raw),
+            _ => serde_json::from_str(raw).unwrap_or_else(|_| Value::from(raw)),
+        }
+    }
+}
+
+/// Stored value and last change of a setting.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct StoredSetting {
+    pub value: String,
+    /// RFC 3339.
+    pub updated_at: Option<String>,
+}
+
+/// Reads the stored rows of the given keys.
+pub async fn fetch(
+    pool: &PgPool,
+    keys: &[&str],
+) -> Result<Vec<(String, StoredSetting)>, sqlx::Error> {
+    let keys: Vec<String> = keys.iter().map(|k| k.to_string()).collect();
+    let rows = sqlx::query(
+        "SELECT key, value,
+                to_char(updated_at AT TIME ZONE 'UTC', 'YYYY-MM-DD\"T\"HH24:MI:SS\"Z\"') AS updated_at
+           FROM app_settings WHERE key = ANY($1)",
+    )
+    .bind(&keys)
+    .fetch_all(pool)
+    .await?;
+    Ok(rows
+        .into_iter()
+        .map(|r| {
+            (
+                r.get("key"),
+                StoredSetting {
+                    value: r.get("value"),
+                    updated_at: r.get("updated_at"),
+                },
+            )
+        })
+        .collect())
+}
+
+/// Sets (`Some`) or resets (`None`) a setting. Unless the value stays the
+/// same, the change is audited and a [`SettingsChanged`] event queued in the
+/// same transaction. Returns whether anything changed.
+pub async fn store(
+    pool: &PgPool,
+    key: &str,
+    value: Option<&str>,
+    changed_by: Option<&str>,
+) -> Result<bool, sqlx::Error> {
+    let mut tx = pool.begin().await?;
+    let changed = store_in(&mut tx, key, value, changed_by).await?;
+    if changed {
+        tx.commit().await?;
+    }
+    Ok(changed)
+}
+
+/// [`store`] inside a transaction of the caller.
+pub async fn store_in(
+    tx: &mut PgConnection,
+    key: &str,
+    value: Option<&str>,
+    changed_by: Option<&str>,
+) -> Result<bool, sqlx::Error> {
+    let old: Option<String> =
+        sqlx::query_scalar("SELECT value FROM app_settings WHERE key = $1 FOR UPDATE")
+            .bind(key)
+            .fetch_optional(&mut *tx)
+            .await?;
+    if old.as_deref() == value {
+        return Ok(false);
+    }
+    match value {
+        Some(value) => {
+            sqlx::query(
+                "INSERT INTO app_settings (key, value, updated_at)
+                 VALUES ($1, $2, now())
+                 ON CONFLICT (key)
+                 DO UPDATE SET value = EXCLUDED.value, updated_at = now()",
+            )
+            .bind(key)
+            .bind(value)
+            .execute(&mut *tx)
+            .await?;
+        }
+        None => {
+            sqlx::query("DELETE FROM app_settings WHERE key = $1")
+                .bind(key)
+                .execute(&mut *tx)
+                .await?;
+        }
+    }
+    sqlx::query(
+        "INSERT INTO app_settings_audit (key, old_value, new_value, changed_by)
+         VALUES ($1, $2, $3, $4)",
+    )
+    .bind(key)
+    .bind(&old)
+    .bind(value)
+    .bind(changed_by)
+    .execute(&mut *tx)
+    .await?;
+    let event = SettingsChanged {
+        key: key.to_string(),
+        changed_by: changed_by.map(str::to_string),
+    };
+    let payload = serde_json::to_string(&event).unwrap_or_default();
+    outbox::enqueue(&mut *tx, SETTINGS_CHANGED_TOPIC, Some(key), &payload).await?;
+    Ok(true)
+}
+
+/// One entry of `app_settings_audit`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct AuditEntry {
+    pub id: i64,
+    pub key: String,
+    pub old_value: Option<String>,
+    pub new_value: Option<String>,
+    pub changed_by: Option<String>,
+    /// RFC 3339.
+    pub changed_at: String,
+}
+
+/// Latest audit entries of a key, newest first.
+pub async fn audit(pool: &PgPool, key: &str, limit: i64) -> Result<Vec<AuditEntry>, sqlx::Error> {
+    let rows = sqlx::query(
+        "SELECT id, key, old_value, new_value, changed_by,
+                to_char(changed_at AT TIME ZONE 'UTC', 'YYYY-MM-DD\"T\"HH24:MI:SS\"Z\"') AS changed_at
+           FROM app_settings_audit
+          WHERE key = $1
+          ORDER BY changed_at DESC, id DESC
+          LIMIT $2",
+    )
+    .bind(key)
+    .bind(limit)
+    .fetch_all(pool)
+    .await?;
+    Ok(rows
+        .into_iter()
+        .map(|r| AuditEntry {
+            id: r.get("id"),
+            key: r.get("key"),
+            old_value: r.get("old_value"),
+            new_value: r.get("new_value"),
+            changed_by: r.get("changed_by"),
+            changed_at: r.get("changed_at"),
+        })
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn validates_per_kind() {
+        let version = known(openai_settings::OPENAI_VERSION_KEY).unwrap();
+        assert_eq!(
+            version.validate(&json!("gpt-4o-mini")).unwrap(),
+            "gpt-4o-mini"
+        );
+        assert!(version.validate(&json!("gpt-2")).is_err());
+        assert!(version.validate(&json!(4)).is_err());
+        assert!(version.options().contains(&"responses"));
+
+        let runner = known(RUNNER_SETTINGS_KEY).unwrap();
+        let stored = runner.validate(&json!({ "max_parallel": 4 })).unwrap();
+        assert_eq!(runner.decode(&stored)["max_parallel"], 4);
+        assert_eq!(
+            runner.validate(&json!({ "max_parallel": 0 })).unwrap_err(),
+            "max_parallel must be between 1 and 32"
+        );
+
+        let disk = known("runner_disk_usage").unwrap();
+        assert!(!disk.editable());
+        assert!(disk.validate(&json!({})).is_err());
+        assert!(known("openai_credentials:x").is_none());
+    }
+}
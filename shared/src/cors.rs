@@ -0,0 +1,248 @@
+//! CORS configuration shared by all HTTP services.
+//!
+//! Every service reads the same environment variables:
+//!
+//! | Variable | Meaning | Default |
+//! | --- | --- | --- |
+//! | `CORS_ORIGINS` | Comma separated list of allowed origins, `*` for any | local frontends ([`LOCAL_ORIGINS`]) |
+//! | `CORS_ALLOWED_METHODS` | Comma separated HTTP methods | `GET,POST,PUT,PATCH,DELETE,OPTIONS` |
+//! | `CORS_ALLOWED_HEADERS` | Comma separated request headers, `*` for any | any header |
+//! | `CORS_ALLOW_CREDENTIALS` | `true`/`false` | `false` |
+//! | `CORS_MAX_AGE` | Preflight cache duration in seconds | `3600` |
+//!
+//! The framework specific builders live behind the `actix` and `axum` features.
+
+use tracing::warn;
+
+const DEFAULT_METHODS: &[&str] = &["GET", "POST", "PUT", "PATCH", "DELETE", "OPTIONS"];
+const DEFAULT_MAX_AGE: usize = 3600;
+
+/// Origins allowed without `CORS_ORIGINS`: the Vite dev server and the
+/// frontend of `docker-compose.local.yml`. Any other origin needs an explicit
+/// entry, or `CORS_ORIGINS=*`.
+pub const LOCAL_ORIGINS: &[&str] = &[
+    "http://localhost:3000",
+    "http://127.0.0.1:3000",
+    "http://localhost:3001",
+    "http://127.0.0.1:3001",
+];
+
+/// Parsed CORS settings; `None` lists mean "allow any".
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CorsSettings {
+    pub allowed_origins: Option<Vec<String>>,
+    pub allowed_methods: Vec<String>,
+    pub allowed_headers: Option<Vec<String>>,
+    pub allow_credentials: bool,
+    pub max_age: Option<usize>,
+}
+
+impl Default for CorsSettings {
+    fn default() -> Self {
+        Self {
+            allowed_origins: Some(LOCAL_ORIGINS.iter().map(|o| o.to_string()).collect()),
+            allowed_methods: DEFAULT_METHODS.iter().map(|m| m.to_string()).collect(),
+            allowed_headers: None,
+            allow_credentials: false,
+            max_age: Some(DEFAULT_MAX_AGE),
+        }
+    }
+}
+
+impl CorsSettings {
+    /// Loads the settings from the `CORS_*` environment variables.
+    pub fn from_env() -> Self {
+        Self::from_lookup(|key| std::env::var(key).ok())
+    }
+
+    /// Builds the settings from an arbitrary key lookup (used by tests).
+    pub fn from_lookup<F>(lookup: F) -> Self
+    where
+        F: Fn(&str) -> Option<String>,
+    {
+        let defaults = Self::default();
+        // Any origin only with an explicit `*`
+        let origins = lookup("CORS_ORIGINS")
+            .map(|v| parse_list(&v))
+            .unwrap_or_default();
+        let allowed_origins = if origins.is_empty() {
+            warn!("CORS_ORIGINS not set; allowing only local frontend origins");
+            defaults.allowed_origins
+        } else if origins.iter().any(|o| o == "*") {
+            warn!("CORS_ORIGINS=*; allowing any origin");
+            None
+        } else {
+            Some(origins)
+        };
+        let allowed_methods = lookup("CORS_ALLOWED_METHODS")
+            .map(|v| {
+                parse_list(&v)
+                    .into_iter()
+                    .map(|m| m.to_ascii_uppercase())
+                    .collect::<Vec<_>>()
+            })
+            .filter(|list| !list.is_empty())
+            .unwrap_or(defaults.allowed_methods);
+        let allowed_headers = lookup("CORS_ALLOWED_HEADERS").and_then(|v| parse_list_or_any(&v));
+        let allow_credentials = lookup("CORS_ALLOW_CREDENTIALS")
+            .map(|v| matches!(v.trim().to_ascii_lowercase().as_str(), "1" | "true" | "yes"))
+            .unwrap_or(defaults.allow_credentials);
+        let max_age = match lookup("CORS_MAX_AGE") {
+            Some(v) => v.trim().parse::<usize>().ok().filter(|secs| *secs > 0),
+            None => defaults.max_age,
+        };
+        Self {
+            allowed_origins,
+            allowed_methods,
+            allowed_headers,
+            allow_credentials,
+            max_age,
+        }
+    }
+
+    /// Builds the `actix-cors` middleware for actix-web services.
+    #[cfg(feature = "actix")]
+    pub fn actix(&self) -> actix_cors::Cors {
+        let mut cors = actix_cors::Cors::default()
+            .allowed_methods(self.allowed_methods.iter().map(String::as_str));
+        cors = match &self.allowed_origins {
+            Some(origins) => origins
+                .iter()
+                .fold(cors, |cors, origin| cors.allowed_origin(origin)),
+            None => cors.allow_any_origin(),
+        };
+        cors = match &self.allowed_headers {
+            Some(headers) => cors.allowed_headers(headers.iter().map(String::as_str)),
+            None => cors.allow_any_header(),
+        };
+        if self.allow_credentials {
+            cors = cors.supports_credentials();
+        }
+        cors.max_age(self.max_age)
+    }
+
+    /// Builds the `tower-http` CORS layer for axum services.
+    #[cfg(feature = "axum")]
+    pub fn tower(&self) -> tower_http::cors::CorsLayer {
+        use http::{HeaderName, HeaderValue, Method};
+        use tower_http::cors::{AllowHeaders, AllowOrigin, CorsLayer};
+
+        let methods: Vec<Method> = self
+            .allowed_methods
+            .iter()
+            .filter_map(|m| m.parse().ok())
+            .collect();
+        // Wildcards are not allowed together with credentials, so mirror the request instead.
+        let origin = match &self.allowed_origins {
+            Some(origins) => AllowOrigin::list(
+                origins
+                    .iter()
+                    .filter_map(|o| HeaderValue::from_str(o).ok())
+                    .collect::<Vec<_>>(),
+            ),
+            None if self.allow_credentials => AllowOrigin::mirror_request(),
+            None => AllowOrigin::any(),
+        };
+        let headers = match &self.allowed_headers {
+            Some(headers) => AllowHeaders::list(
+                headers
+                    .iter()
+                    .filter_map(|h| HeaderName::from_bytes(h.as_bytes()).ok())
+                    .collect::<Vec<_>>(),
+            ),
+            None if self.allow_credentials => AllowHeaders::mirror_request(),
+            None => AllowHeaders::any(),
+        };
+        let mut layer = CorsLayer::new()
+            .allow_methods(methods)
+            .allow_origin(origin)
+            .allow_headers(headers)
+            .allow_credentials(self.allow_credentials);
+        if let Some(secs) = self.max_age {
+            layer = layer.max_age(std::time::Duration::from_secs(secs as u64));
+        }
+        layer
+    }
+}
+
+fn parse_list(value: &str) -> Vec<String> {
+    value
+        .split(',')
+        .map(|s| s.trim().to_string())
+        .filter(|s| !s.is_empty())
+        .collect()
+}
+
+/// `None` for "*" or an empty list, otherwise the explicit entries.
+fn parse_list_or_any(value: &str) -> Option<Vec<String>> {
+    let list = parse_list(value);
+    if list.is_empty() || list.iter().any(|s| s == "*") {
+        None
+    } else {
+        Some(list)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+
+    fn settings(vars: &[(&str, &str)]) -> CorsSettings {
+        let map: HashMap<String, String> = vars
+            .iter()
+            .map(|(k, v)| (k.to_string(), v.to_string()))
+            .collect();
+        CorsSettings::from_lookup(|key| map.get(key).cloned())
+    }
+
+    #[test]
+    fn defaults_allow_only_local_origins() {
+        let cors = settings(&[]);
+        assert_eq!(cors, CorsSettings::default());
+        let origins = cors.allowed_origins.unwrap();
+        assert!(origins.contains(&"http://localhost:3000".to_string()));
+        assert!(origins
+            .iter()
+            .all(|o| o.contains("localhost") || o.contains("127.0.0.1")));
+        assert_eq!(
+            settings(&[("CORS_ORIGINS", " , ")]),
+            CorsSettings::default()
+        );
+    }
+
+    #[test]
+    fn parses_explicit_lists() {
+        let cors = settings(&[
+            ("CORS_ORIGINS", "https://a.example, https://b.example"),
+            ("CORS_ALLOWED_METHODS", "get,post"),
+            ("CORS_ALLOWED_HEADERS", "content-type,authorization"),
+            ("CORS_ALLOW_CREDENTIALS", "true"),
+            ("CORS_MAX_AGE", "60"),
+        ]);
+        assert_eq!(
+            cors.allowed_origins,
+            Some(vec![
+                "https://a.example".to_string(),
+                "https://b.example".to_string()
+            ])
+        );
+        assert_eq!(cors.allowed_methods, vec!["GET", "POST"]);
+        assert_eq!(
+            cors.allowed_headers,
+            Some(vec![
+                "content-type".to_string(),
+                "authorization".to_string()
+            ])
+        );
+        assert!(cors.allow_credentials);
+        assert_eq!(cors.max_age, Some(60));
+    }
+
+    #[test]
+    fn wildcard_means_any() {
+        let cors = settings(&[("CORS_ORIGINS", "*"), ("CORS_ALLOWED_HEADERS", " * ")]);
+        assert!(cors.allowed_origins.is_none());
+        assert!(cors.allowed_headers.is_none());
+    }
+}
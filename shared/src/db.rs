@@ -64,6 +64,8 @@ pub async fn list_analyses_with_tenant_json(
     db: &Client,
     tenant_like: Option<&str>,
     status: Option<&str>,
+    job_label_like: Option<&str>,
+    external_ref: Option<&str>,
     limit: i64,
     offset: i64,
 ) -> Result<Vec<Value>> {
@@ -74,10 +76,19 @@ pub async fn list_analyses_with_tenant_json(
           FROM v_pipeline_runs_with_tenant v
          WHERE ($1::text IS NULL OR v.tenant_name ILIKE '%' || $1 || '%')
            AND ($2::text IS NULL OR v.status = $2)
+           AND ($3::text IS NULL OR v.job_label ILIKE '%' || $3 || '%')
+           AND ($4::text IS NULL OR v.external_ref = $4)
          ORDER BY v.created_at DESC
-         LIMIT $3 OFFSET $4
+         LIMIT $5 OFFSET $6
         "#,
-        &[&tenant_like, &status, &limit, &offset],
+        &[
+            &tenant_like,
+            &status,
+            &job_label_like,
+            &external_ref,
+            &limit,
+            &offset,
+        ],
     )
     .await
 }
@@ -87,6 +98,8 @@ pub async fn list_history_with_tenant_json(
     db: &Client,
     tenant_like: Option<&str>,
     status: Option<&str>,
+    job_label_like: Option<&str>,
+    external_ref: Option<&str>,
     limit: i64,
     offset: i64,
 ) -> Result<Vec<Value>> {
@@ -97,10 +110,19 @@ pub async fn list_history_with_tenant_json(
           FROM v_analysis_history_with_tenant v
          WHERE ($1::text IS NULL OR v.tenant_name ILIKE '%' || $1 || '%')
            AND ($2::text IS NULL OR v.status = $2)
+           AND ($3::text IS NULL OR v.job_label ILIKE '%' || $3 || '%')
+           AND ($4::text IS NULL OR v.external_ref = $4)
          ORDER BY v."timestamp" DESC NULLS LAST
-         LIMIT $3 OFFSET $4
+         LIMIT $5 OFFSET $6
         "#,
-        &[&tenant_like, &status, &limit, &offset],
+        &[
+            &tenant_like,
+            &status,
+            &job_label_like,
+            &external_ref,
+            &limit,
+            &offset,
+        ],
     )
     .await
 }
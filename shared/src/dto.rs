@@ -2,18 +2,45 @@
 //!
 //! These types codify the JSON payloads exchanged between components so that
 //! each service and consumer can rely on a consistent schema.
+//!
+//! # Versioning
+//!
+//! Events on Kafka and stored results outlive the service version that wrote
+//! them, so every change has to keep older payloads readable:
+//!
+//! - new fields are `Option` or carry `#[serde(default)]`,
+//! - renamed fields and variants keep the old name as `#[serde(alias)]`,
+//! - enums read by consumers map unknown values to a fallback variant, via
+//!   `#[serde(other)]` or a lenient `Deserialize` as for [`RunPriority`].
+//!
+//! Removing, renaming or retyping a field of a versioned event
+//! ([`PdfUploaded`], [`TextExtracted`], [`PipelineRunResult`]) additionally
+//! bumps its `SCHEMA_VERSION` and adds a fixture under
+//! `shared/tests/fixtures/dto/`; `tests/dto_compat.rs` fails otherwise.
+//! `contract-tests/` additionally checks the fields each consuming service
+//! reads against payloads recorded from the topics.
+//! Events carry the version in `schema_version`, `0` marks payloads written
+//! before versioning.
 
 use serde::{Deserialize, Serialize};
 use serde_json::Value;
 use strum_macros::{Display, EnumString};
 use uuid::Uuid;
 
+use crate::output_mapping::{MappedOutput, OutputField};
+use crate::redaction::RedactionProfile;
+use crate::result_label::LabelRules;
+use crate::step_library::LibraryRef;
+
 #[derive(Debug, Clone, PartialEq, Eq, EnumString, Display, Serialize, Deserialize)]
 #[strum(serialize_all = "PascalCase")]
 /// Describes the purpose of a prompt executed within a pipeline.
 pub enum PromptType {
+    #[serde(alias = "Extraction")]
     ExtractionPrompt,
+    #[serde(alias = "Scoring")]
     ScoringPrompt,
+    #[serde(alias = "Decision")]
     DecisionPrompt,
 }
 
@@ -23,6 +50,8 @@ pub enum PromptType {
 pub enum TernaryLabel {
     Yes,
     No,
+    /// Also used for labels unknown to this version.
+    #[serde(other)]
     Unsure,
 }
 
@@ -43,29 +72,208 @@ pub struct UploadResponse {
     #[serde(skip_serializing_if = "Option::is_none")]
     /// Numeric PDF identifier.
     pub pdf_id: Option<i32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    /// Pipeline trigger state: `pending` (runs after extraction) or `parked`.
+    pub run_state: Option<String>,
+}
+
+#[derive(
+    Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, EnumString, Display, Serialize,
+)]
+#[serde(rename_all = "lowercase")]
+#[strum(serialize_all = "lowercase", ascii_case_insensitive)]
+/// Scheduling hint attached to uploads and pipeline-run events.
+///
+/// Unknown priorities are read as [`RunPriority::Normal`]; variants are
+/// ordered from lowest to highest.
+pub enum RunPriority {
+    Low,
+    #[default]
+    Normal,
+    High,
+}
+
+impl<'de> Deserialize<'de> for RunPriority {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let raw = String::deserialize(deserializer)?;
+        Ok(raw.parse().unwrap_or_default())
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize)]
+#[serde(rename_all = "lowercase")]
+/// Canonical status of a pipeline run, shared by runner, APIs and history.
+///
+/// Older services wrote `finished`/`finalized`/`error`/`timeout`; those strings
+/// are still accepted when reading (see [`RunStatus::parse_lenient`]).
+pub enum RunStatus {
+    Queued,
+    Running,
+    Completed,
+    Failed,
+    Canceled,
+}
+
+impl RunStatus {
+    pub const ALL: [RunStatus; 5] = [
+        RunStatus::Queued,
+        RunStatus::Running,
+        RunStatus::Completed,
+        RunStatus::Failed,
+        RunStatus::Canceled,
+    ];
+
+    /// Legacy spellings still found in stored rows and older events.
+    pub const LEGACY_ALIASES: [(&'static str, RunStatus); 7] = [
+        ("pending", RunStatus::Queued),
+        ("finished", RunStatus::Completed),
+        ("finalized", RunStatus::Completed),
+        ("succeeded", RunStatus::Completed),
+        ("error", RunStatus::Failed),
+        ("timeout", RunStatus::Failed),
+        ("cancelled", RunStatus::Canceled),
+    ];
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            RunStatus::Queued => "queued",
+            RunStatus::Running => "running",
+            RunStatus::Completed => "completed",
+            RunStatus::Failed => "failed",
+            RunStatus::Canceled => "canceled",
+        }
+    }
+
+    /// Parses canonical and legacy spellings, ignoring case and whitespace.
+    pub fn parse_lenient(value: &str) -> Option<RunStatus> {
+        let value = value.trim().to_ascii_lowercase();
+        Self::ALL
+            .into_iter()
+            .find(|status| status.as_str() == value)
+            .or_else(|| {
+                Self::LEGACY_ALIASES
+                    .into_iter()
+                    .find(|(alias, _)| *alias == value)
+                    .map(|(_, status)| status)
+            })
+    }
+
+    /// Canonical spelling of a stored status; unknown values pass through.
+    pub fn canonical(value: &str) -> String {
+        Self::parse_lenient(value).map_or_else(|| value.to_string(), |s| s.to_string())
+    }
+
+    pub fn is_terminal(self) -> bool {
+        matches!(
+            self,
+            RunStatus::Completed | RunStatus::Failed | RunStatus::Canceled
+        )
+    }
+}
+
+impl std::fmt::Display for RunStatus {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl std::str::FromStr for RunStatus {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        Self::parse_lenient(value).ok_or_else(|| format!("unknown run status '{value}'"))
+    }
+}
+
+impl<'de> Deserialize<'de> for RunStatus {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let raw = String::deserialize(deserializer)?;
+        raw.parse().map_err(serde::de::Error::custom)
+    }
+}
+
+/// Reads an optional run status; unknown strings become `None` instead of
+/// rejecting the whole event.
+fn deserialize_lenient_status<'de, D: serde::Deserializer<'de>>(
+    deserializer: D,
+) -> Result<Option<RunStatus>, D::Error> {
+    let raw = Option::<String>::deserialize(deserializer)?;
+    Ok(raw.as_deref().and_then(RunStatus::parse_lenient))
 }
 
 #[derive(Debug, Serialize, Deserialize)]
-/// Event emitted once a PDF has been stored.
+/// Event emitted once a PDF has been stored (`pdf-merged`) and to request a
+/// pipeline run (`pipeline-run`).
 pub struct PdfUploaded {
+    #[serde(default)]
+    /// [`PdfUploaded::SCHEMA_VERSION`] of the producer, `0` for legacy events.
+    pub schema_version: u32,
     pub pdf_id: i32,
     pub pipeline_id: uuid::Uuid,
+    #[serde(default)]
+    pub priority: RunPriority,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    /// Earlier run of the same PDF and pipeline whose final extraction is reused;
+    /// only scoring and decision steps are executed again.
+    pub rerun_of: Option<Uuid>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    /// Set on `pdf-merged` after pages were appended: 0-based number of the
+    /// first new page. Text of earlier pages is kept and not extracted again.
+    pub appended_from: Option<i32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    /// Id the run gets once the runner starts it. Set on `pipeline-run` so a
+    /// queued run can be addressed (`POST /runs/{id}/prioritize`); without it
+    /// the runner picks a new one.
+    pub run_id: Option<Uuid>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    /// Set on `pdf-merged` by `POST /pdf/{id}/reocr`: only the listed pages are
+    /// OCRed again; the rest of the text is kept.
+    pub reocr: Option<ReocrPages>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+/// Pages of a merged PDF whose stored OCR confidence is too low.
+pub struct ReocrPages {
+    /// 0-based page numbers as in `pdf_texts.page_no`.
+    pub pages: Vec<i32>,
+    /// Threshold the pages were selected with; a page is only replaced when
+    /// the new OCR result is more confident than the stored one.
+    pub min_confidence: f32,
 }
 
+impl PdfUploaded {
+    pub const SCHEMA_VERSION: u32 = 2;
+}
+
+/// Topic the runner reads before `pipeline-run`; prioritized runs are
+/// re-published there.
+pub const PRIORITY_RUN_TOPIC: &str = "pipeline-run-priority";
+
 #[derive(Debug, Serialize, Deserialize)]
 /// Event emitted after text extraction completed for a PDF.
 pub struct TextExtracted {
+    #[serde(default)]
+    /// [`TextExtracted::SCHEMA_VERSION`] of the producer, `0` for legacy events.
+    pub schema_version: u32,
     pub pdf_id: i32,
     pub pipeline_id: uuid::Uuid,
     pub text: String,
 }
 
+impl TextExtracted {
+    pub const SCHEMA_VERSION: u32 = 2;
+}
+
 #[derive(Serialize, Deserialize, Debug, Clone)]
 /// Location of a highlighted text passage within a PDF.
 pub struct TextPosition {
     pub page: u32,
     pub bbox: [f32; 4],
     pub quote: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    /// Mean OCR word confidence (0.0..=1.0) of the quoted words; `None` for
+    /// quotes from the PDF text layer or when the quote was not located.
+    pub confidence: Option<f32>,
 }
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
@@ -106,6 +314,7 @@ pub struct ScoringResult {
 pub struct PromptResult {
     pub prompt_id: i32,
     pub prompt_type: PromptType,
+    #[serde(default)]
     pub prompt_text: String,
     pub boolean: Option<bool>,
     pub value: Option<serde_json::Value>,
@@ -114,6 +323,7 @@ pub struct PromptResult {
     pub json_key: Option<String>,
     pub error: Option<String>,
     pub source: Option<TextPosition>,
+    #[serde(default)]
     pub openai_raw: String,
 }
 
@@ -132,19 +342,27 @@ pub struct RunStep {
 #[derive(Debug, Serialize, Deserialize)]
 /// Comprehensive result object returned by the pipeline runner.
 pub struct PipelineRunResult {
+    #[serde(default)]
+    /// [`PipelineRunResult::SCHEMA_VERSION`] of the producer, `0` for legacy results.
+    pub schema_version: u32,
     pub run_id: Option<Uuid>,
 
     pub pdf_id: i32,
     pub pipeline_id: uuid::Uuid,
     pub overall_score: Option<f32>,
 
+    #[serde(default)]
     pub extracted: std::collections::HashMap<String, serde_json::Value>,
 
+    #[serde(default)]
     /// Consolidated scoring results (one entry per rule).
     pub scoring: Vec<ScoringResult>,
 
+    #[serde(default)]
     pub extraction: Vec<PromptResult>,
+    #[serde(default)]
     pub decision: Vec<PromptResult>,
+    #[serde(default)]
     pub log: Vec<RunStep>,
 
     #[serde(default)]
@@ -156,12 +374,36 @@ pub struct PipelineRunResult {
     pub final_score_labels: Option<std::collections::HashMap<String, TernaryLabel>>,
 
     #[serde(default)]
+    /// Final answers per decision key (`decision_<prompt_id>`).
+    pub final_decisions: Option<std::collections::HashMap<String, bool>>,
+
+    #[serde(default, deserialize_with = "deserialize_lenient_status")]
     /// Optional metadata, often populated by the history service.
-    pub status: Option<String>,
+    pub status: Option<RunStatus>,
     #[serde(default)]
     pub started_at: Option<String>,
     #[serde(default)]
     pub finished_at: Option<String>,
+
+    #[serde(default)]
+    /// True when strong votes of at least one final decision contradict each other.
+    pub contested: bool,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    /// Human label of the originating job, copied from the upload.
+    pub job_label: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    /// External reference (e.g. case number) of the originating job.
+    pub external_ref: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    /// Final results under customer field names when the pipeline has an output mapping.
+    pub output: Option<MappedOutput>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    /// Source run when this run reused its extraction (`?reuse=extraction`).
+    pub rerun_of: Option<Uuid>,
+}
+
+impl PipelineRunResult {
+    pub const SCHEMA_VERSION: u32 = 2;
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -189,6 +431,10 @@ pub struct PipelineStep {
 
     /// Additional configuration passed to the step implementation.
     pub config: Option<Value>,
+
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    /// Library item the step is a copy of, see [`crate::step_library`].
+    pub library: Option<LibraryRef>,
 }
 
 #[derive(Serialize, Deserialize)]
@@ -196,6 +442,18 @@ pub struct PipelineStep {
 pub struct PipelineConfig {
     pub name: String,
     pub steps: Vec<PipelineStep>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    /// Customer field names for final keys, see [`crate::output_mapping`].
+    pub output_mapping: Vec<OutputField>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    /// Rules for the history `result_label`, see [`crate::result_label`].
+    pub label_rules: Option<LabelRules>,
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    /// Run the pipeline again when pages are appended to one of its documents.
+    pub run_on_update: bool,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    /// Export redaction per recipient role, see [`crate::redaction`].
+    pub redaction_profiles: Vec<RedactionProfile>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
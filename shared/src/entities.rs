@@ -0,0 +1,286 @@
+//! Per-document index of named entities (`pdf_entities`).
+//!
+//! After extraction the text-extraction service can run an entity pass over
+//! every page (`ENTITY_INDEX=1`): the regular expressions below find dates,
+//! amounts, IBANs, names and addresses, an optional model endpoint adds further
+//! candidates. Each entity keeps its page and, where the layout allows, its
+//! bounding box. pdf-ingest serves the index at `GET /pdf/{id}/entities`;
+//! pipeline steps can read the table instead of scanning the text again.
+
+use once_cell::sync::Lazy;
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+
+/// Idempotent DDL (mirrors `migrations/0031_pdf_entities.sql`).
+pub const CREATE_TABLE_SQL: &str = "CREATE TABLE IF NOT EXISTS pdf_entities (
+    id BIGSERIAL PRIMARY KEY,
+    merged_pdf_id INTEGER NOT NULL REFERENCES merged_pdfs(id) ON DELETE CASCADE,
+    page_no INTEGER NOT NULL,
+    kind TEXT NOT NULL,
+    value TEXT NOT NULL,
+    normalized TEXT NOT NULL,
+    bbox JSONB,
+    source TEXT NOT NULL,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT now()
+)";
+
+/// Insert statement; parameters follow the field order of [`PdfEntity`].
+pub const INSERT_SQL: &str = "INSERT INTO pdf_entities
+    (merged_pdf_id, page_no, kind, value, normalized, bbox, source)
+    VALUES ($1, $2, $3, $4, $5, $6::jsonb, $7)";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum EntityKind {
+    Date,
+    Amount,
+    Iban,
+    Name,
+    Address,
+}
+
+impl EntityKind {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            EntityKind::Date => "date",
+            EntityKind::Amount => "amount",
+            EntityKind::Iban => "iban",
+            EntityKind::Name => "name",
+            EntityKind::Address => "address",
+        }
+    }
+
+    pub fn parse(raw: &str) -> Option<Self> {
+        match raw.trim().to_ascii_lowercase().as_str() {
+            "date" => Some(EntityKind::Date),
+            "amount" => Some(EntityKind::Amount),
+            "iban" => Some(EntityKind::Iban),
+            "name" | "person" | "per" => Some(EntityKind::Name),
+            "address" | "addr" | "loc" => Some(EntityKind::Address),
+            _ => None,
+        }
+    }
+}
+
+/// Stored entity as returned by `GET /pdf/{id}/entities`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PdfEntity {
+    pub merged_pdf_id: i32,
+    /// 0-based page number, as in `pdf_texts`.
+    pub page_no: i32,
+    pub kind: EntityKind,
+    /// Text as it appears on the page.
+    pub value: String,
+    /// Comparable form: ISO date, `1234.56 EUR`, IBAN without spaces, lowercase otherwise.
+    pub normalized: String,
+    /// `[x0, y0, x1, y1]` in layout coordinates when the words were found.
+    pub bbox: Option<[i32; 4]>,
+    /// `regex` or `model`.
+    pub source: String,
+}
+
+/// Entity found in a page text; `start`/`end` are byte offsets.
+#[derive(Debug, Clone, PartialEq)]
+pub struct EntityMatch {
+    pub kind: EntityKind,
+    pub value: String,
+    pub normalized: String,
+    pub start: usize,
+    pub end: usize,
+}
+
+const MONTHS: [&str; 12] = [
+    "januar",
+    "februar",
+    "märz",
+    "april",
+    "mai",
+    "juni",
+    "juli",
+    "august",
+    "september",
+    "oktober",
+    "november",
+    "dezember",
+];
+
+static NUMERIC_DATE_RE: Lazy<Regex> = Lazy::new(|| {
+    Regex::new(r"\b(?:(\d{1,2})\.(\d{1,2})\.(\d{4}|\d{2})|(\d{4})-(\d{2})-(\d{2}))\b")
+        .expect("valid regex")
+});
+static TEXT_DATE_RE: Lazy<Regex> = Lazy::new(|| {
+    Regex::new(
+        r"(?i)\b(\d{1,2})\.\s*(januar|februar|märz|april|mai|juni|juli|august|september|oktober|november|dezember)\s+(\d{4})\b",
+    )
+    .expect("valid regex")
+});
+static AMOUNT_RE: Lazy<Regex> = Lazy::new(|| {
+    Regex::new(r"(?:(EUR|€)\s?)?\b(\d{1,3}(?:\.\d{3})+|\d+),(\d{2})\b(?:\s?(EUR|€))?")
+        .expect("valid regex")
+});
+static IBAN_RE: Lazy<Regex> = Lazy::new(|| {
+    Regex::new(r"\b[A-Z]{2}\d{2}(?: ?[A-Z0-9]{4}){3,7}(?: ?[A-Z0-9]{1,3})?\b").expect("valid regex")
+});
+static NAME_RE: Lazy<Regex> = Lazy::new(|| {
+    Regex::new(
+        r"\b(?:Herrn?|Frau|Hr\.|Fr\.)\s+(?:(?:Dr|Prof)\.\s+)?([A-ZÄÖÜ][a-zäöüß]+(?:[- ][A-ZÄÖÜ][a-zäöüß]+){0,2})",
+    )
+    .expect("valid regex")
+});
+static ADDRESS_RE: Lazy<Regex> = Lazy::new(|| {
+    Regex::new(
+        r"\b[A-ZÄÖÜ][\w.-]*?(?:straße|strasse|str\.|weg|platz|allee|gasse|ring|damm)\s+\d+\s?[a-z]?\s*,?\s+\d{5}\s+[A-ZÄÖÜ][\w-]+",
+    )
+    .expect("valid regex")
+});
+
+/// Finds entities in a page text with the built-in patterns, ordered by position.
+pub fn extract(text: &str) -> Vec<EntityMatch> {
+    let mut found = Vec::new();
+    let mut push = |kind, m: regex::Match<'_>, normalized: String| {
+        found.push(EntityMatch {
+            kind,
+            value: m.as_str().to_string(),
+            normalized,
+            start: m.start(),
+            end: m.end(),
+        })
+    };
+
+    for cap in NUMERIC_DATE_RE.captures_iter(text) {
+        let m = cap.get(0).expect("whole match");
+        let (d, mo, y) = match (cap.get(1), cap.get(4)) {
+            (Some(d), _) => (d.as_str(), cap[2].to_string(), cap[3].to_string()),
+            (None, Some(y)) => (&cap[6], cap[5].to_string(), y.as_str().to_string()),
+            _ => continue,
+        };
+        if let Some(iso) = iso_date(d, &mo, &y) {
+            push(EntityKind::Date, m, iso);
+        }
+    }
+    for cap in TEXT_DATE_RE.captures_iter(text) {
+        let m = cap.get(0).expect("whole match");
+        let month = cap[2].to_lowercase();
+        let Some(idx) = MONTHS.iter().position(|name| *name == month) else {
+            continue;
+        };
+        if let Some(iso) = iso_date(&cap[1], &(idx + 1).to_string(), &cap[3]) {
+            push(EntityKind::Date, m, iso);
+        }
+    }
+    for cap in AMOUNT_RE.captures_iter(text) {
+        if cap.get(1).is_none() && cap.get(4).is_none() {
+            continue;
+        }
+        let m = cap.get(0).expect("whole match");
+        let int = cap[2].replace('.', "");
+        push(EntityKind::Amount, m, format!("{int}.{} EUR", &cap[3]));
+    }
+    for m in IBAN_RE.find_iter(text) {
+        let compact: String = m.as_str().chars().filter(|c| !c.is_whitespace()).collect();
+        if iban_valid(&compact) {
+            push(EntityKind::Iban, m, compact);
+        }
+    }
+    for cap in NAME_RE.captures_iter(text) {
+        let name = cap.get(1).expect("name group");
+        push(EntityKind::Name, name, name.as_str().to_lowercase());
+    }
+    for m in ADDRESS_RE.find_iter(text) {
+        let normalized = m
+            .as_str()
+            .split_whitespace()
+            .collect::<Vec<_>>()
+            .join(" ")
+            .to_lowercase();
+        push(EntityKind::Address, m, normalized);
+    }
+
+    found.sort_by_key(|e| (e.start, e.end));
+    found
+}
+
+/// Normalizes a plain entity value, e.g. one returned by a model.
+pub fn normalize(kind: EntityKind, value: &str) -> String {
+    if let Some(m) = extract(value)
+        .into_iter()
+        .find(|e| e.kind == kind && e.value.len() == value.trim().len())
+    {
+        return m.normalized;
+    }
+    value
+        .split_whitespace()
+        .collect::<Vec<_>>()
+        .join(" ")
+        .to_lowercase()
+}
+
+fn iso_date(day: &str, month: &str, year: &str) -> Option<String> {
+    let day: u32 = day.parse().ok()?;
+    let month: u32 = month.parse().ok()?;
+    let mut year: u32 = year.parse().ok()?;
+    if year < 100 {
+        year += if year < 70 { 2000 } else { 1900 };
+    }
+    if !(1..=31).contains(&day) || !(1..=12).contains(&month) {
+        return None;
+    }
+    Some(format!("{year:04}-{month:02}-{day:02}"))
+}
+
+/// ISO 13616 check digits (mod 97).
+fn iban_valid(iban: &str) -> bool {
+    if iban.len() < 15 || iban.len() > 34 {
+        return false;
+    }
+    let (head, tail) = iban.split_at(4);
+    let mut rem: u32 = 0;
+    for c in tail.chars().chain(head.chars()) {
+        let value = match c.to_digit(36) {
+            Some(v) => v,
+            None => return false,
+        };
+        rem = if value < 10 {
+            (rem * 10 + value) % 97
+        } else {
+            (rem * 100 + value) % 97
+        };
+    }
+    rem == 1
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn finds_and_normalizes_entities() {
+        let text = "Sehr geehrter Herr Dr. Max Mustermann,\n\
+                    wohnhaft Hauptstraße 12a, 10115 Berlin, zahlen Sie bis 31.03.2024 \
+                    bzw. spätestens 5. Mai 2024 den Betrag von 1.234,50 EUR auf \
+                    DE89 3704 0044 0532 0130 00 ein. Rechnung 12,50 Stück.";
+        let found = extract(text);
+        let by_kind = |kind| {
+            found
+                .iter()
+                .filter(|e| e.kind == kind)
+                .map(|e| e.normalized.as_str())
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(by_kind(EntityKind::Name), vec!["max mustermann"]);
+        assert_eq!(
+            by_kind(EntityKind::Address),
+            vec!["hauptstraße 12a, 10115 berlin"]
+        );
+        assert_eq!(by_kind(EntityKind::Date), vec!["2024-03-31", "2024-05-05"]);
+        assert_eq!(by_kind(EntityKind::Amount), vec!["1234.50 EUR"]);
+        assert_eq!(by_kind(EntityKind::Iban), vec!["DE89370400440532013000"]);
+
+        // Prüfziffer falsch → keine IBAN
+        assert!(extract("DE00 3704 0044 0532 0130 00")
+            .iter()
+            .all(|e| e.kind != EntityKind::Iban));
+        assert_eq!(normalize(EntityKind::Amount, "€ 99,00"), "99.00 EUR");
+        assert_eq!(EntityKind::parse("PER"), Some(EntityKind::Name));
+    }
+}
@@ -0,0 +1,144 @@
+//! Envelope encryption for secrets stored in the database.
+//!
+//! Every secret gets its own random data key (AES-256-GCM). The data key is
+//! wrapped with the master key from `CREDENTIALS_MASTER_KEY` (base64, 32 bytes)
+//! and stored next to the ciphertext, so the master key never touches the
+//! database and can be rotated by re-wrapping the data keys.
+
+use aes_gcm::aead::{Aead, AeadCore, KeyInit, OsRng};
+use aes_gcm::{Aes256Gcm, Nonce};
+use base64::{engine::general_purpose::STANDARD, Engine as _};
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+/// Environment variable holding the base64 encoded 256-bit master key.
+pub const MASTER_KEY_ENV: &str = "CREDENTIALS_MASTER_KEY";
+
+#[derive(Error, Debug)]
+pub enum EnvelopeError {
+    #[error("{MASTER_KEY_ENV} is not set")]
+    MissingMasterKey,
+    #[error("master key must be 32 bytes encoded as base64")]
+    InvalidMasterKey,
+    #[error("malformed sealed secret: {0}")]
+    Malformed(String),
+    #[error("encryption failed")]
+    Encrypt,
+    #[error("decryption failed (wrong master key or tampered data)")]
+    Decrypt,
+}
+
+/// A secret encrypted with its own data key; all fields are base64.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SealedSecret {
+    /// Data key encrypted with the master key.
+    pub wrapped_key: String,
+    pub key_nonce: String,
+    pub ciphertext: String,
+    pub nonce: String,
+}
+
+/// Key-encryption key loaded from the environment.
+#[derive(Clone)]
+pub struct MasterKey(Aes256Gcm);
+
+impl std::fmt::Debug for MasterKey {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("MasterKey(..)")
+    }
+}
+
+impl MasterKey {
+    /// Reads the key from [`MASTER_KEY_ENV`].
+    pub fn from_env() -> Result<Self, EnvelopeError> {
+        match std::env::var(MASTER_KEY_ENV) {
+            Ok(v) if !v.trim().is_empty() => Self::from_base64(v.trim()),
+            _ => Err(EnvelopeError::MissingMasterKey),
+        }
+    }
+
+    pub fn from_base64(encoded: &str) -> Result<Self, EnvelopeError> {
+        let bytes = STANDARD
+            .decode(encoded)
+            .map_err(|_| EnvelopeError::InvalidMasterKey)?;
+        Aes256Gcm::new_from_slice(&bytes)
+            .map(Self)
+            .map_err(|_| EnvelopeError::InvalidMasterKey)
+    }
+
+    /// Encrypts `plaintext` with a fresh data key.
+    pub fn seal(&self, plaintext: &str) -> Result<SealedSecret, EnvelopeError> {
+        let data_key = Aes256Gcm::generate_key(&mut OsRng);
+        let nonce = Aes256Gcm::generate_nonce(&mut OsRng);
+        let ciphertext = Aes256Gcm::new(&data_key)
+            .encrypt(&nonce, plaintext.as_bytes())
+            .map_err(|_| EnvelopeError::Encrypt)?;
+
+        let key_nonce = Aes256Gcm::generate_nonce(&mut OsRng);
+        let wrapped_key = self
+            .0
+            .encrypt(&key_nonce, &data_key[..])
+            .map_err(|_| EnvelopeError::Encrypt)?;
+
+        Ok(SealedSecret {
+            wrapped_key: STANDARD.encode(wrapped_key),
+            key_nonce: STANDARD.encode(key_nonce),
+            ciphertext: STANDARD.encode(ciphertext),
+            nonce: STANDARD.encode(nonce),
+        })
+    }
+
+    /// Decrypts a secret produced by [`MasterKey::seal`].
+    pub fn open(&self, sealed: &SealedSecret) -> Result<String, EnvelopeError> {
+        let data_key = self
+            .0
+            .decrypt(
+                &nonce_from(&sealed.key_nonce)?,
+                decode(&sealed.wrapped_key)?.as_slice(),
+            )
+            .map_err(|_| EnvelopeError::Decrypt)?;
+        let plaintext = Aes256Gcm::new_from_slice(&data_key)
+            .map_err(|_| EnvelopeError::Malformed("data key length".into()))?
+            .decrypt(
+                &nonce_from(&sealed.nonce)?,
+                decode(&sealed.ciphertext)?.as_slice(),
+            )
+            .map_err(|_| EnvelopeError::Decrypt)?;
+        String::from_utf8(plaintext).map_err(|e| EnvelopeError::Malformed(e.to_string()))
+    }
+}
+
+fn decode(field: &str) -> Result<Vec<u8>, EnvelopeError> {
+    STANDARD
+        .decode(field)
+        .map_err(|e| EnvelopeError::Malformed(e.to_string()))
+}
+
+fn nonce_from(field: &str) -> Result<Nonce<<Aes256Gcm as AeadCore>::NonceSize>, EnvelopeError> {
+    let bytes: [u8; 12] = decode(field)?
+        .try_into()
+        .map_err(|_| EnvelopeError::Malformed("nonce length".into()))?;
+    Ok(bytes.into())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn key(byte: u8) -> MasterKey {
+        MasterKey::from_base64(&STANDARD.encode([byte; 32])).unwrap()
+    }
+
+    #[test]
+    fn seal_and_open_roundtrip() {
+        let master = key(7);
+        let sealed = master.seal("sk-tenant-secret").unwrap();
+        assert!(!sealed.ciphertext.contains("sk-tenant"));
+        assert_eq!(master.open(&sealed).unwrap(), "sk-tenant-secret");
+        assert!(matches!(key(8).open(&sealed), Err(EnvelopeError::Decrypt)));
+        assert!(matches!(
+            MasterKey::from_base64("c2hvcnQ="),
+            Err(EnvelopeError::InvalidMasterKey)
+        ));
+    }
+}
@@ -0,0 +1,268 @@
+//! Feature flags for gradual rollouts, stored in `app_settings`.
+//!
+//! [`FLAGS`] lists every flag with its default. A flag is resolved in this
+//! order: env override `FLAG_<KEY>` (dots become underscores, e.g.
+//! `FLAG_RUNNER_STRUCTURED_OUTPUTS=off`), tenant override
+//! (`flag:<key>:<tenant_id>`), global value (`flag:<key>`), default. Writes go
+//! through [`app_settings::store`], so every change is audited and announced on
+//! `settings-changed`; readers resolve flags per run and need no reload.
+
+use std::collections::HashMap;
+
+use serde::Serialize;
+use sqlx::{PgConnection, PgPool, Row};
+use uuid::Uuid;
+
+use crate::app_settings;
+
+/// Prefix of the `app_settings` keys holding flag values.
+pub const SETTINGS_PREFIX: &str = "flag:";
+
+/// A flag of the registry.
+#[derive(Debug, Clone, Copy, Serialize)]
+pub struct FeatureFlag {
+    pub key: &'static str,
+    pub description: &'static str,
+    pub default: bool,
+}
+
+/// OpenAI structured outputs (JSON schema response format) for extraction and
+/// decision steps.
+pub const STRUCTURED_OUTPUTS: FeatureFlag = FeatureFlag {
+    key: "runner.structured_outputs",
+    description: "Enforce the JSON schema of extraction and decision answers",
+    default: true,
+};
+
+/// All flags known to the services and the admin API.
+pub const FLAGS: &[FeatureFlag] = &[STRUCTURED_OUTPUTS];
+
+/// Looks up a key of [`FLAGS`].
+pub fn known(key: &str) -> Option<&'static FeatureFlag> {
+    FLAGS.iter().find(|f| f.key == key)
+}
+
+impl FeatureFlag {
+    /// `app_settings` key of the global value or of a tenant override.
+    pub fn settings_key(&self, tenant_id: Option<Uuid>) -> String {
+        match tenant_id {
+            Some(tenant_id) => format!("{SETTINGS_PREFIX}{}:{tenant_id}", self.key),
+            None => format!("{SETTINGS_PREFIX}{}", self.key),
+        }
+    }
+
+    /// Name of the env override.
+    pub fn env_var(&self) -> String {
+        format!(
+            "FLAG_{}",
+            self.key.replace(['.', '-'], "_").to_ascii_uppercase()
+        )
+    }
+
+    /// Value of the env override; unparsable values are ignored.
+    pub fn env_override(&self) -> Option<bool> {
+        std::env::var(self.env_var())
+            .ok()
+            .and_then(|v| parse_bool(&v))
+    }
+}
+
+fn parse_bool(value: &str) -> Option<bool> {
+    match value.trim().to_ascii_lowercase().as_str() {
+        "1" | "true" | "on" | "yes" => Some(true),
+        "0" | "false" | "off" | "no" => Some(false),
+        _ => None,
+    }
+}
+
+fn encode(enabled: bool) -> &'static str {
+    if enabled {
+        "true"
+    } else {
+        "false"
+    }
+}
+
+/// Effective value from the individual layers (see module docs).
+pub fn resolve(
+    flag: &FeatureFlag,
+    env: Option<bool>,
+    tenant: Option<bool>,
+    global: Option<bool>,
+) -> bool {
+    env.or(tenant).or(global).unwrap_or(flag.default)
+}
+
+/// Whether `flag` is on for `tenant_id` (or globally without a tenant).
+pub async fn is_enabled(
+    pool: &PgPool,
+    flag: &FeatureFlag,
+    tenant_id: Option<Uuid>,
+) -> Result<bool, sqlx::Error> {
+    if let Some(env) = flag.env_override() {
+        return Ok(env);
+    }
+    let global_key = flag.settings_key(None);
+    let tenant_key = tenant_id.map(|t| flag.settings_key(Some(t)));
+    let mut keys = vec![global_key.as_str()];
+    keys.extend(tenant_key.as_deref());
+    let stored: HashMap<String, bool> = app_settings::fetch(pool, &keys)
+        .await?
+        .into_iter()
+        .filter_map(|(key, s)| parse_bool(&s.value).map(|v| (key, v)))
+        .collect();
+    Ok(resolve(
+        flag,
+        None,
+        tenant_key.and_then(|k| stored.get(&k).copied()),
+        stored.get(&global_key).copied(),
+    ))
+}
+
+/// Sets (`Some`) or removes (`None`) the global value or a tenant override.
+/// Returns whether anything changed.
+pub async fn store(
+    pool: &PgPool,
+    flag: &FeatureFlag,
+    tenant_id: Option<Uuid>,
+    enabled: Option<bool>,
+    changed_by: Option<&str>,
+) -> Result<bool, sqlx::Error> {
+    app_settings::store(
+        pool,
+        &flag.settings_key(tenant_id),
+        enabled.map(encode),
+        changed_by,
+    )
+    .await
+}
+
+/// [`store`] inside a transaction of the caller.
+pub async fn store_in(
+    tx: &mut PgConnection,
+    flag: &FeatureFlag,
+    tenant_id: Option<Uuid>,
+    enabled: Option<bool>,
+    changed_by: Option<&str>,
+) -> Result<bool, sqlx::Error> {
+    app_settings::store_in(
+        tx,
+        &flag.settings_key(tenant_id),
+        enabled.map(encode),
+        changed_by,
+    )
+    .await
+}
+
+/// Stored override of one tenant.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct TenantOverride {
+    pub tenant_id: Uuid,
+    pub enabled: bool,
+}
+
+/// Configuration of a flag as shown by the admin API.
+#[derive(Debug, Clone, Serialize)]
+pub struct FlagState {
+    #[serde(flatten)]
+    pub flag: FeatureFlag,
+    pub env_var: String,
+    pub env_override: Option<bool>,
+    pub global: Option<bool>,
+    pub tenants: Vec<TenantOverride>,
+    /// Effective value for tenants without override.
+    pub enabled: bool,
+}
+
+/// Current state of every flag of [`FLAGS`].
+pub async fn list(pool: &PgPool) -> Result<Vec<FlagState>, sqlx::Error> {
+    let rows = sqlx::query("SELECT key, value FROM app_settings WHERE starts_with(key, $1)")
+        .bind(SETTINGS_PREFIX)
+        .fetch_all(pool)
+        .await?;
+    let stored: Vec<(String, bool)> = rows
+        .into_iter()
+        .filter_map(|r| {
+            let value: String = r.get("value");
+            parse_bool(&value).map(|v| (r.get("key"), v))
+        })
+        .collect();
+    Ok(FLAGS.iter().map(|flag| state(flag, &stored)).collect())
+}
+
+fn state(flag: &FeatureFlag, stored: &[(String, bool)]) -> FlagState {
+    let global_key = flag.settings_key(None);
+    let tenant_prefix = format!("{global_key}:");
+    let mut global = None;
+    let mut tenants = Vec::new();
+    for (key, enabled) in stored {
+        if *key == global_key {
+            global = Some(*enabled);
+        } else if let Some(tenant_id) = key
+            .strip_prefix(&tenant_prefix)
+            .and_then(|t| Uuid::parse_str(t).ok())
+        {
+            tenants.push(TenantOverride {
+                tenant_id,
+                enabled: *enabled,
+            });
+        }
+    }
+    tenants.sort_by_key(|t| t.tenant_id);
+    let env_override = flag.env_override();
+    FlagState {
+        flag: *flag,
+        env_var: flag.env_var(),
+        env_override,
+        global,
+        tenants,
+        enabled: resolve(flag, env_override, None, global),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const FLAG: FeatureFlag = FeatureFlag {
+        key: "ocr.new-backend",
+        description: "test",
+        default: false,
+    };
+
+    #[test]
+    fn env_beats_tenant_beats_global_beats_default() {
+        assert!(!resolve(&FLAG, None, None, None));
+        assert!(resolve(&FLAG, None, None, Some(true)));
+        assert!(!resolve(&FLAG, None, Some(false), Some(true)));
+        assert!(resolve(&FLAG, Some(true), Some(false), Some(false)));
+        assert_eq!(FLAG.env_var(), "FLAG_OCR_NEW_BACKEND");
+        assert_eq!(parse_bool(" Off "), Some(false));
+        assert_eq!(parse_bool("maybe"), None);
+    }
+
+    #[test]
+    fn splits_global_value_and_tenant_overrides() {
+        let tenant = Uuid::from_u128(7);
+        let stored = vec![
+            ("flag:ocr.new-backend".to_string(), true),
+            (format!("flag:ocr.new-backend:{tenant}"), false),
+            ("flag:ocr.new-backend:not-a-uuid".to_string(), true),
+            (format!("flag:ocr.new-backend-v2:{tenant}"), true),
+        ];
+        let state = state(&FLAG, &stored);
+        assert_eq!(state.global, Some(true));
+        assert_eq!(
+            state.tenants,
+            [TenantOverride {
+                tenant_id: tenant,
+                enabled: false
+            }]
+        );
+        assert_eq!(
+            FLAG.settings_key(Some(tenant)),
+            format!("flag:ocr.new-backend:{tenant}")
+        );
+        assert!(known(STRUCTURED_OUTPUTS.key).is_some());
+    }
+}
@@ -1,21 +1,67 @@
-//! Kafka administration utilities used for bootstrapping topics.
+//! Kafka administration utilities and the partitioning rules shared by all
+//! producers and consumers.
+//!
+//! # Ordering
+//!
+//! Every event about a document (`pdf-merged`, `text-extracted`,
+//! `pipeline-run`, `pipeline-run-priority`, `pipeline-result`) is keyed with
+//! [`document_key`], so all events of one PDF land on the same partition and
+//! keep the order in which they were produced. The tenant is not part of the
+//! key: a PDF belongs to exactly one tenant, and not every producer knows it,
+//! so a tenant component would split the events of one document. Events of
+//! different documents may be consumed in any order.
+//!
+//! Consumers must therefore process the events of one partition in offset
+//! order, or, when they handle several messages at once, at least those with
+//! the same key; [`KeySequencer`] does that for concurrent consumers. The
+//! transactional outbox (`shared::outbox`) holds back later events of a key
+//! while an earlier one is still being retried. Re-extractions, retries of
+//! failed extractions and re-runs are thus applied in the order they were
+//! requested.
+//!
+//! Topics are created with `KAFKA_TOPIC_PARTITIONS` partitions (default 1).
+//! Existing topics keep their partition count; adding partitions later moves
+//! keys to other partitions, so only do it while the topics are drained.
+
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
 
 use rdkafka::admin::{AdminClient, AdminOptions, NewTopic, TopicReplication};
 use rdkafka::error::{KafkaError, RDKafkaErrorCode};
 use rdkafka::ClientConfig;
+use tokio::sync::oneshot;
 use tracing::{info, warn};
 
+/// Default of `KAFKA_TOPIC_PARTITIONS`.
+pub const DEFAULT_TOPIC_PARTITIONS: i32 = 1;
+
+/// Kafka key of all events about the PDF `pdf_id`.
+pub fn document_key(pdf_id: i32) -> String {
+    pdf_id.to_string()
+}
+
+/// Partitions of newly created topics (`KAFKA_TOPIC_PARTITIONS`, at least 1).
+pub fn topic_partitions() -> i32 {
+    std::env::var("KAFKA_TOPIC_PARTITIONS")
+        .ok()
+        .and_then(|v| v.trim().parse::<i32>().ok())
+        .filter(|n| *n >= 1)
+        .unwrap_or(DEFAULT_TOPIC_PARTITIONS)
+}
+
 /// Ensure that the given Kafka topics exist.
 ///
-/// Attempts to create each topic with a single partition and replication
-/// factor 1. If the topic already exists the error is ignored.
+/// Attempts to create each topic with [`topic_partitions`] partitions and
+/// replication factor 1. If the topic already exists the error is ignored.
 pub async fn ensure_topics(broker: &str, topics: &[&str]) -> Result<(), KafkaError> {
     let admin: AdminClient<_> = ClientConfig::new()
         .set("bootstrap.servers", broker)
         .create()?;
+    let partitions = topic_partitions();
     let new_topics: Vec<NewTopic> = topics
         .iter()
-        .map(|t| NewTopic::new(t, 1, TopicReplication::Fixed(1)))
+        .map(|t| NewTopic::new(t, partitions, TopicReplication::Fixed(1)))
         .collect();
     let results = admin
         .create_topics(new_topics.iter(), &AdminOptions::new())
@@ -31,3 +77,118 @@ pub async fn ensure_topics(broker: &str, topics: &[&str]) -> Result<(), KafkaErr
     }
     Ok(())
 }
+
+/// Per key: number and completion signal of the latest turn.
+type Tails = Arc<Mutex<HashMap<String, (u64, oneshot::Receiver<()>)>>>;
+
+/// Serializes the handling of messages with the same key for consumers that
+/// process several messages concurrently.
+///
+/// [`KeySequencer::enter`] must be called in receive order (in the consume
+/// loop, before the message is handed to a task); the returned [`Turn`]
+/// resolves once all earlier turns of the key are dropped.
+#[derive(Clone, Default)]
+pub struct KeySequencer {
+    tails: Tails,
+    next: Arc<AtomicU64>,
+}
+
+/// Place of one message in the sequence of its key; dropping it lets the next
+/// message of the key proceed.
+pub struct Turn {
+    key: String,
+    seq: u64,
+    previous: Option<oneshot::Receiver<()>>,
+    _done: oneshot::Sender<()>,
+    tails: Tails,
+}
+
+impl KeySequencer {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Queues a message with `key` behind the earlier ones.
+    pub fn enter(&self, key: &str) -> Turn {
+        let seq = self.next.fetch_add(1, Ordering::Relaxed);
+        let (done, finished) = oneshot::channel();
+        let previous = self
+            .tails
+            .lock()
+            .unwrap()
+            .insert(key.to_string(), (seq, finished))
+            .map(|(_, rx)| rx);
+        Turn {
+            key: key.to_string(),
+            seq,
+            previous,
+            _done: done,
+            tails: self.tails.clone(),
+        }
+    }
+
+    /// Keys with a message in flight.
+    pub fn active_keys(&self) -> usize {
+        self.tails.lock().unwrap().len()
+    }
+}
+
+impl Turn {
+    /// No earlier message of the key is in flight.
+    pub fn is_first(&self) -> bool {
+        self.previous.is_none()
+    }
+
+    /// Waits until the earlier messages of the key are handled.
+    pub async fn wait(&mut self) {
+        if let Some(previous) = self.previous.take() {
+            // Err: der Vorgänger ist fertig (Sender verworfen)
+            let _ = previous.await;
+        }
+    }
+}
+
+impl Drop for Turn {
+    fn drop(&mut self) {
+        let mut tails = self.tails.lock().unwrap();
+        if tails
+            .get(&self.key)
+            .is_some_and(|(seq, _)| *seq == self.seq)
+        {
+            tails.remove(&self.key);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    #[tokio::test]
+    async fn turns_of_one_key_run_in_order() {
+        let sequencer = KeySequencer::new();
+        let order = Arc::new(Mutex::new(Vec::new()));
+        let mut handles = Vec::new();
+        for (idx, key) in ["7", "8", "7", "7"].into_iter().enumerate() {
+            let mut turn = sequencer.enter(&document_key(key.parse().unwrap()));
+            assert_eq!(turn.is_first(), idx < 2);
+            let order = order.clone();
+            handles.push(tokio::spawn(async move {
+                turn.wait().await;
+                // spätere Nachrichten sind schneller fertig, warten aber auf ihren Vorgänger
+                tokio::time::sleep(Duration::from_millis(30 - 10 * idx as u64)).await;
+                order.lock().unwrap().push(idx);
+                drop(turn);
+            }));
+        }
+        assert_eq!(sequencer.active_keys(), 2);
+        for handle in handles {
+            handle.await.unwrap();
+        }
+        let order = order.lock().unwrap().clone();
+        let key_7: Vec<usize> = order.into_iter().filter(|idx| *idx != 1).collect();
+        assert_eq!(key_7, [0, 2, 3]);
+        assert_eq!(sequencer.active_keys(), 0);
+    }
+}
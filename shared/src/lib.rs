@@ -1,10 +1,35 @@
 //! Shared utilities and DTOs reused across backend services.
 
+pub mod annotations;
+pub mod app_settings;
 pub mod config;
+pub mod cors;
 pub mod db;
 pub mod dto;
+pub mod entities;
+pub mod envelope;
 pub mod error;
+pub mod flags;
 pub mod kafka;
 pub mod openai_client;
 pub mod openai_settings;
+pub mod operator_notes;
+pub mod outbox;
+pub mod output_mapping;
+pub mod packing;
+pub mod page_texts;
+pub mod pdf_metadata;
+pub mod redaction;
+pub mod result_label;
+pub mod run_config;
+pub mod runner_settings;
+pub mod schema_doc;
+pub mod scrubber;
+pub mod sigv4;
+pub mod similarity;
+pub mod startup;
+pub mod step_library;
+pub mod telemetry;
+pub mod tenant_credentials;
+pub mod timeline;
 pub mod utils;
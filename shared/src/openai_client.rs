@@ -1,16 +1,17 @@
 //! OpenAI client utilities with shared prompt templates and response handling.
 
-use crate::dto::{ScoringResult, TernaryLabel, TextPosition};
+use crate::dto::{PipelineStep, PromptType, ScoringResult, TernaryLabel, TextPosition};
 use crate::openai_settings;
 use once_cell::sync::Lazy;
 use openai::chat::{ChatCompletionMessage, ChatCompletionMessageRole};
-use reqwest::{header, Client};
+use reqwest::{header, Client, StatusCode};
 use serde::de::Error as _; // for JsonError::custom(...)
 use serde::Serialize;
 use serde_json::{json, Error as JsonError, Value as JsonValue};
 use std::collections::HashMap;
 use std::fmt;
-use std::sync::RwLock;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::{Arc, RwLock};
 use std::time::Duration;
 use tokio::time;
 use tracing::{debug, error, warn};
@@ -413,6 +414,113 @@ fn resolve_endpoint_details() -> (String, AuthStyle, EndpointKind) {
     (endpoint, auth, preferred)
 }
 
+fn endpoint_details_for(endpoint: &str) -> (String, AuthStyle, EndpointKind) {
+    let auth = if requires_api_key_header(endpoint) {
+        AuthStyle::ApiKey
+    } else {
+        AuthStyle::BearerToken
+    };
+    (endpoint.to_string(), auth, classify_endpoint(endpoint))
+}
+
+/* ======================= Tenant-Credentials (Task-Scope) ======================= */
+
+/// Credentials that replace the global OpenAI key for the current task.
+#[derive(Clone, Default)]
+pub struct OpenAiCredentials {
+    pub api_key: String,
+    pub organization: Option<String>,
+    /// Full chat/responses URL; `None` keeps the globally configured endpoint.
+    pub endpoint: Option<String>,
+    /// Models this key may be used with; empty means no restriction.
+    pub allowed_models: Vec<String>,
+}
+
+impl fmt::Debug for OpenAiCredentials {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("OpenAiCredentials")
+            .field("api_key", &"***")
+            .field("organization", &self.organization)
+            .field("endpoint", &self.endpoint)
+            .field("allowed_models", &self.allowed_models)
+            .finish()
+    }
+}
+
+impl OpenAiCredentials {
+    pub fn allows_model(&self, model: &str) -> bool {
+        self.allowed_models.is_empty()
+            || self
+                .allowed_models
+                .iter()
+                .any(|m| m.eq_ignore_ascii_case(model))
+    }
+}
+
+tokio::task_local! {
+    static TASK_CREDENTIALS: OpenAiCredentials;
+}
+
+/// Runs `fut` with `credentials` used for every OpenAI call made inside it.
+pub async fn with_credentials<F: std::future::Future>(
+    credentials: OpenAiCredentials,
+    fut: F,
+) -> F::Output {
+    TASK_CREDENTIALS.scope(credentials, fut).await
+}
+
+fn task_credentials() -> Option<OpenAiCredentials> {
+    TASK_CREDENTIALS.try_with(|c| c.clone()).ok()
+}
+
+#[derive(Debug, Default)]
+/// Token counters of all OpenAI calls made inside [`with_usage`].
+pub struct TokenUsage {
+    prompt_tokens: AtomicU64,
+    completion_tokens: AtomicU64,
+    calls: AtomicU64,
+}
+
+impl TokenUsage {
+    pub fn prompt_tokens(&self) -> u64 {
+        self.prompt_tokens.load(Ordering::Relaxed)
+    }
+
+    pub fn completion_tokens(&self) -> u64 {
+        self.completion_tokens.load(Ordering::Relaxed)
+    }
+
+    pub fn calls(&self) -> u64 {
+        self.calls.load(Ordering::Relaxed)
+    }
+
+    /// Adds the `usage` block of a chat completions or responses payload.
+    fn record(&self, raw: &JsonValue) {
+        let usage = raw.get("usage");
+        let count = |keys: [&str; 2]| {
+            keys.iter()
+                .find_map(|k| usage.and_then(|u| u.get(*k)).and_then(JsonValue::as_u64))
+                .unwrap_or(0)
+        };
+        self.prompt_tokens
+            .fetch_add(count(["prompt_tokens", "input_tokens"]), Ordering::Relaxed);
+        self.completion_tokens.fetch_add(
+            count(["completion_tokens", "output_tokens"]),
+            Ordering::Relaxed,
+        );
+        self.calls.fetch_add(1, Ordering::Relaxed);
+    }
+}
+
+tokio::task_local! {
+    static TASK_USAGE: Arc<TokenUsage>;
+}
+
+/// Runs `fut` and adds the token usage of every OpenAI call inside it to `usage`.
+pub async fn with_usage<F: std::future::Future>(usage: Arc<TokenUsage>, fut: F) -> F::Output {
+    TASK_USAGE.scope(usage, fut).await
+}
+
 /// Returns the currently configured OpenAI endpoint, authentication style and default model.
 pub fn current_openai_config() -> OpenAiConfigSnapshot {
     let (endpoint, auth, kind) = resolve_endpoint_details();
@@ -443,12 +551,13 @@ IMPORTANT EXTRACTION CONTRACT (STRICT):
 
 Return STRICT JSON ONLY with:
 - "value": string|null — exact substring from DOCUMENT after light whitespace normalisation, or null when unavailable.
-- "source.page": integer|null — 1-based page index or null if unknown.
+- "source.page": integer|null — the number n of the nearest "[[PAGE n]]" marker before the quote, or null if unknown.
 - "source.bbox": [number,number,number,number] — bounding box or [0,0,0,0] if unknown.
 - "source.quote": string|null — verbatim snippet (≤120 chars) from DOCUMENT, or null when absent.
 
 Rules:
 - Use ONLY content that appears in DOCUMENT. Do NOT invent text, pages or coordinates.
+- The "[[PAGE n]]" markers are not part of the text; never include them in "value" or "quote".
 - Avoid placeholders or generic labels (e.g. "Schadennummer", "Max Mustermann", "nicht angegeben").
 - When unsure or the answer is missing, return {"value":null,"source":{"page":null,"bbox":[0,0,0,0],"quote":null}}.
 - Output JSON only. No prose, no markdown.
@@ -473,6 +582,7 @@ Do NOT invent content. Always return STRICT JSON that matches this single-object
 
 Hard rules:
 - "quote" MUST be a verbatim substring of DOCUMENT. Do not fabricate quotes.
+- "page" MUST be the number n of the nearest "[[PAGE n]]" marker before the quote. Never quote the markers.
 - If evidence is inconclusive, use vote="unsure" with a neutral/closest quote.
 - strength/confidence MUST be in [0,1]. Use your best judgement (they are not the same).
 - JSON only. No markdown, no extra keys.
@@ -495,7 +605,7 @@ Return ONE JSON object:
 
 Rules:
 - If unsure → answer=null (route may be null). Do not fabricate evidence.
-- If you set "answer" to true/false, include a verbatim "quote" from DOCUMENT; page/bbox may be unknown → page=null, bbox=[0,0,0,0].
+- If you set "answer" to true/false, include a verbatim "quote" from DOCUMENT; "page" is the number n of the nearest "[[PAGE n]]" marker before the quote (null if unknown), bbox may be unknown → bbox=[0,0,0,0].
 - JSON only. No markdown, no comments, no extra keys.
 "#;
 
@@ -513,6 +623,129 @@ struct ChatRequest<'a> {
     response_format: Option<serde_json::Value>,
 }
 
+/* ======================= Structured Outputs (JSON-Schema) ======================= */
+
+/// Set once the provider rejected a `json_schema` response format; later calls
+/// go straight to the text/JSON parsing path.
+static STRUCTURED_UNSUPPORTED: AtomicBool = AtomicBool::new(false);
+
+/// `OPENAI_STRUCTURED_OUTPUTS=off` disables schema enforcement entirely.
+fn structured_outputs_enabled() -> bool {
+    let configured = env_var_trimmed("OPENAI_STRUCTURED_OUTPUTS")
+        .map(|v| {
+            !matches!(
+                v.to_ascii_lowercase().as_str(),
+                "off" | "false" | "0" | "no"
+            )
+        })
+        .unwrap_or(true);
+    configured && !STRUCTURED_UNSUPPORTED.load(Ordering::Relaxed)
+}
+
+/// JSON schema the model answer has to follow (OpenAI structured outputs).
+#[derive(Debug, Clone, PartialEq)]
+pub struct ResponseSchema {
+    pub name: String,
+    pub schema: JsonValue,
+}
+
+fn source_schema() -> JsonValue {
+    json!({
+        "type": "object",
+        "properties": {
+            "page": {"type": ["integer", "null"]},
+            "bbox": {"type": "array", "items": {"type": "number"}},
+            "quote": {"type": ["string", "null"]}
+        },
+        "required": ["page", "bbox", "quote"],
+        "additionalProperties": false
+    })
+}
+
+impl ResponseSchema {
+    /// Extraction contract; `value_schema` replaces the default `string|null`.
+    pub fn extraction(value_schema: Option<&JsonValue>) -> Self {
+        let value = value_schema
+            .cloned()
+            .unwrap_or_else(|| json!({"type": ["string", "null"]}));
+        Self {
+            name: "extraction_answer".into(),
+            schema: json!({
+                "type": "object",
+                "properties": {"value": value, "source": source_schema()},
+                "required": ["value", "source"],
+                "additionalProperties": false
+            }),
+        }
+    }
+
+    /// Decision contract; with both keys the route is limited to them (or null).
+    pub fn decision(yes_key: Option<&str>, no_key: Option<&str>) -> Self {
+        let route = match (yes_key, no_key) {
+            (Some(y), Some(n)) => json!({"type": ["string", "null"], "enum": [y, n, null]}),
+            _ => json!({"type": ["string", "null"]}),
+        };
+        Self {
+            name: "decision_answer".into(),
+            schema: json!({
+                "type": "object",
+                "properties": {
+                    "answer": {"type": ["boolean", "null"]},
+                    "route": route,
+                    "source": source_schema(),
+                    "explanation": {"type": "string"}
+                },
+                "required": ["answer", "route", "source", "explanation"],
+                "additionalProperties": false
+            }),
+        }
+    }
+
+    /// Derives the schema from the step configuration.
+    ///
+    /// `config.structured_output = false` opts a step out; extraction steps may
+    /// narrow the value via `config.value_schema`. Scoring steps return `None`
+    /// because they already use function calling.
+    pub fn for_step(step: &PipelineStep) -> Option<Self> {
+        let config = step.config.as_ref();
+        if config
+            .and_then(|c| c.get("structured_output"))
+            .and_then(|v| v.as_bool())
+            == Some(false)
+        {
+            return None;
+        }
+        match step.step_type {
+            PromptType::ExtractionPrompt => {
+                Some(Self::extraction(config.and_then(|c| c.get("value_schema"))))
+            }
+            PromptType::DecisionPrompt => Some(Self::decision(
+                Some(step.yes_key.as_deref().unwrap_or("YES")),
+                Some(step.no_key.as_deref().unwrap_or("NO")),
+            )),
+            _ => None,
+        }
+    }
+
+    fn chat_response_format(&self) -> JsonValue {
+        json!({
+            "type": "json_schema",
+            "json_schema": {"name": self.name, "strict": true, "schema": self.schema}
+        })
+    }
+
+    fn responses_text_format(&self) -> JsonValue {
+        json!({
+            "format": {
+                "type": "json_schema",
+                "name": self.name,
+                "strict": true,
+                "schema": self.schema
+            }
+        })
+    }
+}
+
 fn msg(role: ChatCompletionMessageRole, txt: &str) -> ChatCompletionMessage {
     ChatCompletionMessage {
         role,
@@ -688,6 +921,7 @@ fn build_responses_payload(
     messages: &[ChatCompletionMessage],
     functions: Option<&[JsonValue]>,
     function_call: Option<&JsonValue>,
+    schema: Option<&ResponseSchema>,
 ) -> JsonValue {
     let mut payload = json!({
         "model": model,
@@ -715,10 +949,20 @@ fn build_responses_payload(
             .insert("tool_choice".to_string(), choice);
     }
 
-    payload.as_object_mut().unwrap().insert(
-        "response_format".to_string(),
-        json!({"type": "json_object"}),
-    );
+    match schema {
+        Some(schema) => {
+            payload
+                .as_object_mut()
+                .unwrap()
+                .insert("text".to_string(), schema.responses_text_format());
+        }
+        None => {
+            payload.as_object_mut().unwrap().insert(
+                "response_format".to_string(),
+                json!({"type": "json_object"}),
+            );
+        }
+    }
 
     payload
 }
@@ -831,6 +1075,17 @@ fn extract_first_balanced_json(s: &str) -> Option<String> {
 mod tests {
     use super::*;
 
+    #[test]
+    fn token_usage_reads_both_endpoint_shapes() {
+        let usage = TokenUsage::default();
+        usage.record(&json!({ "usage": { "prompt_tokens": 120, "completion_tokens": 30 } }));
+        usage.record(&json!({ "usage": { "input_tokens": 80, "output_tokens": 20 } }));
+        usage.record(&json!({ "choices": [] }));
+        assert_eq!(usage.prompt_tokens(), 200);
+        assert_eq!(usage.completion_tokens(), 50);
+        assert_eq!(usage.calls(), 3);
+    }
+
     #[test]
     fn strip_reasoning_tags_removes_think_blocks() {
         let input = "prefix<think>internal {not json}</think>suffix";
@@ -845,6 +1100,42 @@ mod tests {
             parse_json_block(input).expect("should parse JSON after stripping think block");
         assert_eq!(parsed.get("value").and_then(|v| v.as_i64()), Some(1));
     }
+
+    #[test]
+    fn response_schema_follows_step_config() {
+        let mut step: PipelineStep = serde_json::from_value(json!({
+            "id": "00000000-0000-0000-0000-000000000001",
+            "type": "DecisionPrompt",
+            "promptId": 3,
+            "yesKey": "approve",
+            "noKey": "reject",
+            "active": true,
+            "config": null
+        }))
+        .unwrap();
+        let decision = ResponseSchema::for_step(&step).expect("decision schema");
+        assert_eq!(
+            decision.schema.pointer("/properties/route/enum"),
+            Some(&json!(["approve", "reject", null]))
+        );
+
+        step.step_type = PromptType::ExtractionPrompt;
+        step.config = Some(json!({"value_schema": {"type": ["number", "null"]}}));
+        let extraction = ResponseSchema::for_step(&step).expect("extraction schema");
+        assert_eq!(
+            extraction.schema.pointer("/properties/value"),
+            Some(&json!({"type": ["number", "null"]}))
+        );
+        let payload = build_responses_payload("m", &[], None, None, Some(&extraction));
+        assert_eq!(
+            payload.pointer("/text/format/type"),
+            Some(&json!("json_schema"))
+        );
+        assert!(payload.get("response_format").is_none());
+
+        step.config = Some(json!({"structured_output": false}));
+        assert!(ResponseSchema::for_step(&step).is_none());
+    }
 }
 
 /* ======================= Scoring-Prompt (Tri-State) ======================= */
@@ -875,8 +1166,61 @@ pub async fn call_openai_chat(
     functions: Option<Vec<serde_json::Value>>,
     function_call: Option<serde_json::Value>,
 ) -> Result<String, PromptError> {
-    let key = std::env::var("OPENAI_API_KEY").map_err(|e| PromptError::Network(e.to_string()))?;
-    let (endpoint, auth_style, endpoint_kind) = resolve_endpoint_details();
+    send_chat_request(client, model, messages, functions, function_call, None).await
+}
+
+/// Like [`call_openai_chat`], but asks the provider to enforce `schema`.
+///
+/// Falls back to the plain JSON mode when the provider rejects the schema
+/// (HTTP 400 naming `response_format`/`json_schema`, remembered for the rest
+/// of the process) or when the structured answer still cannot be parsed.
+/// Other 400s (context length, content filter, ...) are returned as errors.
+pub async fn call_openai_structured(
+    client: &Client,
+    model: &str,
+    messages: Vec<ChatCompletionMessage>,
+    schema: &ResponseSchema,
+) -> Result<String, PromptError> {
+    if structured_outputs_enabled() {
+        match send_chat_request(client, model, messages.clone(), None, None, Some(schema)).await {
+            Ok(ans) => return Ok(ans),
+            Err(PromptError::SchemaRejected) => {
+                STRUCTURED_UNSUPPORTED.store(true, Ordering::Relaxed);
+                warn!(
+                    schema = %schema.name,
+                    "provider rejected json_schema response format; falling back to text parsing"
+                );
+            }
+            Err(PromptError::Parse(e)) => {
+                warn!(schema = %schema.name, "structured answer not parseable ({e}); retrying in text mode");
+            }
+            Err(e) => return Err(e),
+        }
+    }
+    send_chat_request(client, model, messages, None, None, None).await
+}
+
+async fn send_chat_request(
+    client: &Client,
+    model: &str,
+    messages: Vec<ChatCompletionMessage>,
+    functions: Option<Vec<serde_json::Value>>,
+    function_call: Option<serde_json::Value>,
+    schema: Option<&ResponseSchema>,
+) -> Result<String, PromptError> {
+    let credentials = task_credentials();
+    let key = match credentials.as_ref() {
+        Some(c) if !c.allows_model(model) => {
+            return Err(PromptError::ModelNotAllowed(model.to_string()))
+        }
+        Some(c) => c.api_key.clone(),
+        None => std::env::var("OPENAI_API_KEY").map_err(|e| PromptError::Network(e.to_string()))?,
+    };
+    let (endpoint, auth_style, endpoint_kind) =
+        match credentials.as_ref().and_then(|c| c.endpoint.as_deref()) {
+            Some(url) => endpoint_details_for(url),
+            None => resolve_endpoint_details(),
+        };
     let mut messages = messages;
     let has_funcs = functions.is_some();
     if !has_funcs {
@@ -892,10 +1236,10 @@ pub async fn call_openai_chat(
                 messages: &messages,
                 functions: functions.as_deref(),
                 function_call,
-                response_format: if has_funcs {
-                    None
-                } else {
-                    Some(json!({"type":"json_object"}))
+                response_format: match schema {
+                    Some(schema) => Some(schema.chat_response_format()),
+                    None if has_funcs => None,
+                    None => Some(json!({"type":"json_object"})),
                 },
             };
             serde_json::to_value(&req).map_err(PromptError::Parse)?
@@ -907,6 +1251,7 @@ pub async fn call_openai_chat(
                 &messages,
                 functions_ref,
                 function_call_clone.as_ref(),
+                schema,
             )
         }
     };
@@ -918,12 +1263,15 @@ pub async fn call_openai_chat(
         model
     );
 
-    let request = match auth_style {
+    let mut request = match auth_style {
         AuthStyle::ApiKey => client.post(endpoint.clone()).header("api-key", key.clone()),
         AuthStyle::BearerToken => client
             .post(endpoint.clone())
             .header(header::AUTHORIZATION, format!("Bearer {}", key)),
     };
+    if let Some(org) = credentials.as_ref().and_then(|c| c.organization.as_deref()) {
+        request = request.header("OpenAI-Organization", org);
+    }
 
     let res = request.json(&payload).send().await.map_err(|e| {
         error!("network error to OpenAI: {e}");
@@ -941,6 +1289,10 @@ pub async fn call_openai_chat(
     debug!("← body[0..512] = {}", body_preview);
 
     if !status.is_success() {
+        if schema.is_some() && status == StatusCode::BAD_REQUEST && rejects_response_format(&bytes)
+        {
+            return Err(PromptError::SchemaRejected);
+        }
         return Err(PromptError::Http(status.as_u16()));
     }
 
@@ -949,6 +1301,8 @@ pub async fn call_openai_chat(
         warn!(kind = ?endpoint_kind, "failed to decode OpenAI response JSON: {e}; snippet={snippet}");
         PromptError::Parse(e)
     })?;
+    // Tokens werden auch dann abgerechnet, wenn der Inhalt unbrauchbar ist
+    let _ = TASK_USAGE.try_with(|usage| usage.record(&raw_json));
 
     let extracted = match endpoint_kind {
         EndpointKind::Responses => parse_responses_output(&raw_json),
@@ -971,6 +1325,13 @@ pub async fn call_openai_chat(
     }
 }
 
+/// Whether a 400 body blames the requested response format rather than the
+/// prompt (e.g. `"param": "response_format"`).
+fn rejects_response_format(body: &[u8]) -> bool {
+    let body = String::from_utf8_lossy(body).to_ascii_lowercase();
+    body.contains("response_format") || body.contains("json_schema")
+}
+
 /* ======================= Fehler & DTOs ======================= */
 
 #[derive(thiserror::Error, Debug)]
@@ -989,6 +1350,11 @@ pub enum PromptError {
     Network(String),
     #[error("http error: {0}")]
     Http(u16),
+    /// The provider does not support the requested `json_schema` response format.
+    #[error("response format rejected")]
+    SchemaRejected,
+    #[error("model {0} is not allowed for the tenant credentials")]
+    ModelNotAllowed(String),
 }
 
 #[derive(Debug, Clone)]
@@ -1057,6 +1423,15 @@ pub fn resolve_page_for_quote_value(
 
 /// Executes an extraction prompt and returns the structured answer.
 pub async fn extract(prompt_id: i32, input: &str) -> Result<OpenAiAnswer, PromptError> {
+    extract_with_schema(prompt_id, input, Some(&ResponseSchema::extraction(None))).await
+}
+
+/// [`extract`] with a step specific schema; `None` uses plain JSON mode.
+pub async fn extract_with_schema(
+    prompt_id: i32,
+    input: &str,
+    schema: Option<&ResponseSchema>,
+) -> Result<OpenAiAnswer, PromptError> {
     let client = Client::builder()
         .timeout(Duration::from_secs(120))
         .build()
@@ -1076,7 +1451,11 @@ pub async fn extract(prompt_id: i32, input: &str) -> Result<OpenAiAnswer, Prompt
     ];
 
     let model = resolve_default_model();
-    if let Ok(ans) = call_openai_chat(&client, &model, msgs, None, None).await {
+    let res = match schema {
+        Some(schema) => call_openai_structured(&client, &model, msgs, schema).await,
+        None => call_openai_chat(&client, &model, msgs, None, None).await,
+    };
+    if let Ok(ans) = res {
         match parse_json_block(&ans) {
             Ok(v) => {
                 let value = v.get("value").cloned();
@@ -1186,6 +1565,7 @@ pub async fn score(prompt_id: i32, document: &str) -> Result<ScoringResult, Prom
                         page: 0,
                         bbox: [0.0, 0.0, 0.0, 0.0],
                         quote: None,
+                        confidence: None,
                     });
                 }
 
@@ -1231,6 +1611,17 @@ pub async fn decide(
     prompt_id: i32,
     document: &str,
     state: &HashMap<String, serde_json::Value>,
+) -> Result<OpenAiAnswer, PromptError> {
+    let schema = ResponseSchema::decision(None, None);
+    decide_with_schema(prompt_id, document, state, Some(&schema)).await
+}
+
+/// [`decide`] with a step specific schema; `None` uses plain JSON mode.
+pub async fn decide_with_schema(
+    prompt_id: i32,
+    document: &str,
+    state: &HashMap<String, serde_json::Value>,
+    schema: Option<&ResponseSchema>,
 ) -> Result<OpenAiAnswer, PromptError> {
     let client = Client::builder()
         .timeout(Duration::from_secs(120))
@@ -1253,7 +1644,11 @@ pub async fn decide(
     ];
 
     let model = resolve_default_model();
-    if let Ok(ans) = call_openai_chat(&client, &model, msgs, None, None).await {
+    let res = match schema {
+        Some(schema) => call_openai_structured(&client, &model, msgs, schema).await,
+        None => call_openai_chat(&client, &model, msgs, None, None).await,
+    };
+    if let Ok(ans) = res {
         match parse_json_block(&ans) {
             Ok(mut v) => {
                 let answer_bool = v.get("answer").and_then(|val| val.as_bool());
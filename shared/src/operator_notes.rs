@@ -0,0 +1,175 @@
+//! Operator tags and notes on uploads and pipeline runs.
+//!
+//! Teams mark uploads (`PUT /uploads/{id}/notes` on pdf-ingest) and runs
+//! (`PUT /runs/{id}/notes` on pipeline-api) with free-form tags such as `test`,
+//! `priority customer` or `duplicate` plus a note. Upload listings and
+//! `GET /analyses` filter by tag; the history entries (and with them the
+//! WebSocket payloads and result sink records) and run bundles carry them.
+
+use serde::{Deserialize, Serialize};
+
+/// Idempotent DDL of `upload_notes` (mirrors `migrations/0045_operator_notes.sql`).
+pub const CREATE_UPLOAD_NOTES_SQL: &[&str] = &[
+    "CREATE TABLE IF NOT EXISTS upload_notes (
+        upload_id INTEGER PRIMARY KEY REFERENCES uploads(id) ON DELETE CASCADE,
+        tags TEXT[] NOT NULL DEFAULT '{}',
+        note TEXT,
+        updated_by TEXT,
+        updated_at TIMESTAMPTZ NOT NULL DEFAULT now()
+    )",
+    "CREATE INDEX IF NOT EXISTS idx_upload_notes_tags ON upload_notes USING GIN (tags)",
+];
+
+/// Idempotent DDL of `run_notes` (mirrors `migrations/0045_operator_notes.sql`).
+pub const CREATE_RUN_NOTES_SQL: &[&str] = &[
+    "CREATE TABLE IF NOT EXISTS run_notes (
+        run_id UUID PRIMARY KEY REFERENCES pipeline_runs(id) ON DELETE CASCADE,
+        tags TEXT[] NOT NULL DEFAULT '{}',
+        note TEXT,
+        updated_by TEXT,
+        updated_at TIMESTAMPTZ NOT NULL DEFAULT now()
+    )",
+    "CREATE INDEX IF NOT EXISTS idx_run_notes_tags ON run_notes USING GIN (tags)",
+];
+
+/// Replaces tags and note of upload `$1` (`$2` tags, `$3` note, `$4` author);
+/// no row when the upload does not exist. Returns the columns of [`OperatorNote`].
+pub const UPSERT_UPLOAD_NOTE_SQL: &str = "INSERT INTO upload_notes (upload_id, tags, note, updated_by)
+    SELECT id, $2, $3, $4 FROM uploads WHERE id = $1
+    ON CONFLICT (upload_id) DO UPDATE
+       SET tags = EXCLUDED.tags, note = EXCLUDED.note,
+           updated_by = EXCLUDED.updated_by, updated_at = now()
+    RETURNING tags, note, updated_by,
+              to_char(updated_at AT TIME ZONE 'UTC', 'YYYY-MM-DD\"T\"HH24:MI:SS\"Z\"') AS updated_at";
+
+/// Like [`UPSERT_UPLOAD_NOTE_SQL`] for run `$1`.
+pub const UPSERT_RUN_NOTE_SQL: &str = "INSERT INTO run_notes (run_id, tags, note, updated_by)
+    SELECT id, $2, $3, $4 FROM pipeline_runs WHERE id = $1
+    ON CONFLICT (run_id) DO UPDATE
+       SET tags = EXCLUDED.tags, note = EXCLUDED.note,
+           updated_by = EXCLUDED.updated_by, updated_at = now()
+    RETURNING tags, note, updated_by,
+              to_char(updated_at AT TIME ZONE 'UTC', 'YYYY-MM-DD\"T\"HH24:MI:SS\"Z\"') AS updated_at";
+
+/// Most tags per upload or run.
+pub const MAX_TAGS: usize = 20;
+/// Longest accepted tag, in characters.
+pub const MAX_TAG_CHARS: usize = 64;
+/// Longest accepted note, in characters.
+pub const MAX_NOTE_CHARS: usize = 4000;
+
+/// Body of `PUT /uploads/{id}/notes` and `PUT /runs/{id}/notes`; replaces
+/// tags and note. An empty body clears both.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct NoteInput {
+    #[serde(default)]
+    pub tags: Vec<String>,
+    #[serde(default)]
+    pub note: Option<String>,
+    #[serde(default)]
+    pub author: Option<String>,
+}
+
+impl NoteInput {
+    /// Normalizes the tags (see [`normalize_tag`]), drops duplicates and
+    /// trims note and author.
+    pub fn normalized(mut self) -> Self {
+        let mut tags: Vec<String> = Vec::new();
+        for tag in self.tags.iter().filter_map(|t| normalize_tag(t)) {
+            if !tags.contains(&tag) {
+                tags.push(tag);
+            }
+        }
+        self.tags = tags;
+        let clean = |value: Option<String>| {
+            value
+                .map(|v| v.trim().to_string())
+                .filter(|v| !v.is_empty())
+        };
+        self.note = clean(self.note);
+        self.author = clean(self.author);
+        self
+    }
+
+    /// Checks the limits; the message is meant for the client.
+    pub fn validate(&self) -> Result<(), String> {
+        if self.tags.len() > MAX_TAGS {
+            return Err(format!("at most {MAX_TAGS} tags"));
+        }
+        if let Some(tag) = self.tags.iter().find(|t| t.chars().count() > MAX_TAG_CHARS) {
+            return Err(format!(
+                "tag '{tag}' is longer than {MAX_TAG_CHARS} characters"
+            ));
+        }
+        match self.note.as_deref() {
+            Some(note) if note.chars().count() > MAX_NOTE_CHARS => {
+                Err(format!("note is longer than {MAX_NOTE_CHARS} characters"))
+            }
+            _ => Ok(()),
+        }
+    }
+}
+
+/// Tags and note of an upload or run as returned by the services.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct OperatorNote {
+    pub tags: Vec<String>,
+    pub note: Option<String>,
+    pub updated_by: Option<String>,
+    /// RFC 3339; `None` while nothing was stored.
+    pub updated_at: Option<String>,
+}
+
+/// Canonical form of a tag: lowercase, inner whitespace collapsed to one
+/// space; `None` when empty. Filters use the same form.
+pub fn normalize_tag(raw: &str) -> Option<String> {
+    let tag = raw
+        .split_whitespace()
+        .collect::<Vec<_>>()
+        .join(" ")
+        .to_lowercase();
+    (!tag.is_empty()).then_some(tag)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn normalizes_and_deduplicates_tags() {
+        let input = NoteInput {
+            tags: vec![
+                " Priority  Customer ".into(),
+                "test".into(),
+                "".into(),
+                "TEST".into(),
+            ],
+            note: Some("  ".into()),
+            author: Some(" ops ".into()),
+        }
+        .normalized();
+        assert_eq!(input.tags, vec!["priority customer", "test"]);
+        assert_eq!(input.note, None);
+        assert_eq!(input.author.as_deref(), Some("ops"));
+        assert!(input.validate().is_ok());
+    }
+
+    #[test]
+    fn rejects_oversized_input() {
+        let many = NoteInput {
+            tags: (0..=MAX_TAGS).map(|i| format!("t{i}")).collect(),
+            ..NoteInput::default()
+        };
+        assert!(many.validate().is_err());
+        let long = NoteInput {
+            tags: vec!["x".repeat(MAX_TAG_CHARS + 1)],
+            ..NoteInput::default()
+        };
+        assert!(long.validate().is_err());
+        let note = NoteInput {
+            note: Some("x".repeat(MAX_NOTE_CHARS + 1)),
+            ..NoteInput::default()
+        };
+        assert!(note.validate().is_err());
+    }
+}
@@ -0,0 +1,231 @@
+//! Transactional outbox for Kafka events that must not get lost.
+//!
+//! Producers write the event into `event_outbox` (ideally in the same
+//! transaction as the state change) and a background [`OutboxRelay`] publishes
+//! pending rows to Kafka, retrying with exponential backoff until the broker
+//! acknowledges them. Published rows can be re-queued, e.g. to re-publish the
+//! `pipeline-result` of a given run.
+//!
+//! Events with the same Kafka key (the document, see `shared::kafka`) are
+//! published in outbox order: while an earlier event of a key waits for its
+//! retry, the later ones of that key are held back.
+//!
+//! The relay claims a batch with a short lease (`claimed_until`) and commits
+//! before sending, so no row locks are held while Kafka is awaited. A relay
+//! that dies mid-batch leaves its rows to be picked up once the lease expires.
+
+use crate::startup::Backoff;
+use rdkafka::producer::{FutureProducer, FutureRecord};
+use sqlx::{PgPool, Postgres, Row};
+use std::time::Duration;
+use tokio::task::JoinHandle;
+use tracing::{debug, info, warn};
+
+/// Idempotent DDL for the outbox table (mirrors `migrations/0015_event_outbox.sql`).
+pub const CREATE_TABLE_SQL: &str = "CREATE TABLE IF NOT EXISTS event_outbox (
+    id BIGSERIAL PRIMARY KEY,
+    topic TEXT NOT NULL,
+    key TEXT,
+    payload TEXT NOT NULL,
+    attempts INTEGER NOT NULL DEFAULT 0,
+    last_error TEXT,
+    next_attempt_at TIMESTAMPTZ NOT NULL DEFAULT now(),
+    claimed_until TIMESTAMPTZ,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT now(),
+    published_at TIMESTAMPTZ
+)";
+
+const CREATE_INDEX_SQL: &[&str] = &[
+    "CREATE INDEX IF NOT EXISTS idx_event_outbox_pending
+        ON event_outbox (next_attempt_at) WHERE published_at IS NULL",
+    // Reihenfolge je Key (migrations/0065_event_outbox_key_order.sql)
+    "CREATE INDEX IF NOT EXISTS idx_event_outbox_pending_key
+        ON event_outbox (topic, key, id) WHERE published_at IS NULL",
+];
+
+/// Creates the outbox table and its pending indexes if they are missing.
+pub async fn ensure_schema(pool: &PgPool) -> Result<(), sqlx::Error> {
+    sqlx::query(CREATE_TABLE_SQL).execute(pool).await?;
+    for sql in CREATE_INDEX_SQL {
+        sqlx::query(sql).execute(pool).await?;
+    }
+    Ok(())
+}
+
+/// Stores an event for publication; returns the outbox id.
+pub async fn enqueue<'e, E>(
+    executor: E,
+    topic: &str,
+    key: Option<&str>,
+    payload: &str,
+) -> Result<i64, sqlx::Error>
+where
+    E: sqlx::Executor<'e, Database = Postgres>,
+{
+    sqlx::query_scalar(
+        "INSERT INTO event_outbox (topic, key, payload) VALUES ($1, $2, $3) RETURNING id",
+    )
+    .bind(topic)
+    .bind(key)
+    .bind(payload)
+    .fetch_one(executor)
+    .await
+}
+
+/// Marks the latest event for `topic` whose payload carries `run_id` as pending
+/// again so the relay publishes it once more. Returns `false` when no such
+/// event exists. Older rows were keyed by the run id, newer ones by the
+/// document, hence the lookup via the payload.
+pub async fn requeue_run(pool: &PgPool, topic: &str, run_id: &str) -> Result<bool, sqlx::Error> {
+    let updated = sqlx::query(
+        "UPDATE event_outbox
+            SET published_at = NULL, attempts = 0, last_error = NULL, next_attempt_at = now(),
+                claimed_until = NULL
+          WHERE id = (SELECT id FROM event_outbox
+                       WHERE topic = $1 AND (key = $2 OR payload::jsonb ->> 'run_id' = $2)
+                       ORDER BY id DESC LIMIT 1)",
+    )
+    .bind(topic)
+    .bind(run_id)
+    .execute(pool)
+    .await?;
+    Ok(updated.rows_affected() > 0)
+}
+
+/// Background publisher for pending outbox rows.
+pub struct OutboxRelay {
+    pool: PgPool,
+    producer: FutureProducer,
+    poll_interval: Duration,
+    batch_size: i64,
+    send_timeout: Duration,
+    backoff: Backoff,
+}
+
+impl OutboxRelay {
+    /// Reads `OUTBOX_POLL_MS`, `OUTBOX_BATCH_SIZE`, `OUTBOX_SEND_TIMEOUT_MS`,
+    /// `OUTBOX_RETRY_INITIAL_MS` and `OUTBOX_RETRY_MAX_MS`.
+    pub fn from_env(pool: PgPool, producer: FutureProducer) -> Self {
+        let read = |key: &str, default: u64| {
+            std::env::var(key)
+                .ok()
+                .and_then(|v| v.trim().parse::<u64>().ok())
+                .unwrap_or(default)
+        };
+        let initial = Duration::from_millis(read("OUTBOX_RETRY_INITIAL_MS", 1_000));
+        Self {
+            pool,
+            producer,
+            poll_interval: Duration::from_millis(read("OUTBOX_POLL_MS", 1_000)),
+            batch_size: read("OUTBOX_BATCH_SIZE", 50) as i64,
+            send_timeout: Duration::from_millis(read("OUTBOX_SEND_TIMEOUT_MS", 10_000)),
+            backoff: Backoff {
+                initial,
+                max: Duration::from_millis(read("OUTBOX_RETRY_MAX_MS", 300_000)).max(initial),
+            },
+        }
+    }
+
+    /// Starts the relay loop on the current runtime.
+    pub fn spawn(self) -> JoinHandle<()> {
+        tokio::spawn(async move { self.run().await })
+    }
+
+    async fn run(self) {
+        info!(
+            poll_ms = self.poll_interval.as_millis() as u64,
+            "outbox relay started"
+        );
+        loop {
+            match self.publish_pending().await {
+                // volle Charge → sofort weitermachen
+                Ok(n) if n as i64 >= self.batch_size => continue,
+                Ok(_) => {}
+                Err(e) => warn!(%e, "outbox relay iteration failed"),
+            }
+            tokio::time::sleep(self.poll_interval).await;
+        }
+    }
+
+    /// Publishes one batch of due events; returns how many were attempted.
+    async fn publish_pending(&self) -> Result<usize, sqlx::Error> {
+        // Lease deckt die Sendezeit der ganzen Charge ab
+        let lease = self
+            .send_timeout
+            .saturating_mul(u32::try_from(self.batch_size + 1).unwrap_or(u32::MAX));
+        // Ein Event wartet, solange ein älteres desselben Keys noch aussteht
+        let mut rows = sqlx::query(
+            "UPDATE event_outbox
+                SET claimed_until = now() + make_interval(secs => $2)
+              WHERE id IN (
+                    SELECT id FROM event_outbox o
+                     WHERE published_at IS NULL AND next_attempt_at <= now()
+                       AND (claimed_until IS NULL OR claimed_until < now())
+                       AND NOT EXISTS (
+                           SELECT 1 FROM event_outbox e
+                            WHERE e.published_at IS NULL AND e.topic = o.topic
+                              AND e.key = o.key AND e.id < o.id)
+                     ORDER BY id
+                     LIMIT $1
+                     FOR UPDATE SKIP LOCKED)
+              RETURNING id, topic, key, payload, attempts",
+        )
+        .bind(self.batch_size)
+        .bind(lease.as_secs_f64())
+        .fetch_all(&self.pool)
+        .await?;
+        rows.sort_by_key(|row| row.get::<i64, _>("id"));
+
+        for row in &rows {
+            let id: i64 = row.get("id");
+            let topic: String = row.get("topic");
+            let key: Option<String> = row.get("key");
+            let payload: String = row.get("payload");
+            let attempts: i32 = row.get("attempts");
+
+            let mut record = FutureRecord::to(&topic).payload(&payload);
+            if let Some(k) = key.as_deref() {
+                record = record.key(k);
+            }
+            match self.producer.send(record, self.send_timeout).await {
+                Ok(_) => {
+                    sqlx::query(
+                        "UPDATE event_outbox
+                            SET published_at = now(), attempts = attempts + 1, last_error = NULL,
+                                claimed_until = NULL
+                          WHERE id = $1",
+                    )
+                    .bind(id)
+                    .execute(&self.pool)
+                    .await?;
+                    debug!(id, topic = %topic, key = ?key, "outbox event published");
+                }
+                Err((e, _)) => {
+                    let delay = self.backoff.delay(attempts.max(0) as u32);
+                    warn!(
+                        id,
+                        topic = %topic,
+                        attempt = attempts + 1,
+                        retry_in_ms = delay.as_millis() as u64,
+                        error = %e,
+                        "outbox publish failed"
+                    );
+                    sqlx::query(
+                        "UPDATE event_outbox
+                            SET attempts = attempts + 1,
+                                last_error = $2,
+                                next_attempt_at = now() + make_interval(secs => $3),
+                                claimed_until = NULL
+                          WHERE id = $1",
+                    )
+                    .bind(id)
+                    .bind(e.to_string())
+                    .bind(delay.as_secs_f64())
+                    .execute(&self.pool)
+                    .await?;
+                }
+            }
+        }
+        Ok(rows.len())
+    }
+}
@@ -0,0 +1,170 @@
+//! Declarative mapping of internal final keys (`score_17`, `decision_4`,
+//! extraction `json_key`s) to customer field names.
+//!
+//! A pipeline lists its fields in `PipelineConfig::output_mapping`; exports and
+//! the `pipeline-result` event carry the rendered [`MappedOutput`] so that
+//! integrations never see the internal keys.
+
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+/// Target type of a mapped field.
+pub enum FieldType {
+    /// Pass the value through unchanged.
+    #[default]
+    Any,
+    String,
+    Number,
+    Boolean,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+/// One entry of a pipeline's output mapping.
+pub struct OutputField {
+    /// Internal final key as stored in `pipeline_run_steps.final_key`.
+    pub key: String,
+    /// Field name exposed to the customer.
+    pub name: String,
+    #[serde(default, rename = "type")]
+    pub field_type: FieldType,
+    #[serde(default)]
+    pub required: bool,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+/// Rendered output of one run.
+pub struct MappedOutput {
+    /// Customer field name → value (`null` when missing or not convertible).
+    pub fields: Map<String, Value>,
+    /// Required fields without a value.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub missing: Vec<String>,
+    /// Fields whose value could not be converted to the configured type.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub invalid: Vec<String>,
+}
+
+impl MappedOutput {
+    /// True when every required field has a valid value.
+    pub fn is_complete(&self) -> bool {
+        self.missing.is_empty() && self.invalid.is_empty()
+    }
+}
+
+/// Plain value of a final result: extraction results carry `value`, scoring
+/// results `score` and decisions `answer`; scalars are taken as they are.
+fn plain_value(result: &Value) -> Value {
+    match result {
+        Value::Object(obj) => ["value", "score", "answer"]
+            .iter()
+            .find_map(|k| obj.get(*k))
+            .cloned()
+            .unwrap_or_else(|| result.clone()),
+        other => other.clone(),
+    }
+}
+
+fn convert(value: Value, field_type: FieldType) -> Option<Value> {
+    match (field_type, value) {
+        (FieldType::Any, v) => Some(v),
+        (FieldType::String, Value::String(s)) => Some(Value::String(s)),
+        (FieldType::String, v @ (Value::Number(_) | Value::Bool(_))) => {
+            Some(Value::String(v.to_string()))
+        }
+        (FieldType::Number, v @ Value::Number(_)) => Some(v),
+        (FieldType::Number, Value::Bool(b)) => Some(Value::from(u8::from(b))),
+        (FieldType::Number, Value::String(s)) => {
+            // Dezimalkomma aus deutschen Dokumenten zulassen
+            let normalized = s.trim().replace(' ', "").replace(',', ".");
+            normalized.parse::<f64>().ok().map(Value::from)
+        }
+        (FieldType::Boolean, v @ Value::Bool(_)) => Some(v),
+        (FieldType::Boolean, Value::String(s)) => match s.trim().to_lowercase().as_str() {
+            "true" | "yes" | "ja" | "1" => Some(Value::Bool(true)),
+            "false" | "no" | "nein" | "0" => Some(Value::Bool(false)),
+            _ => None,
+        },
+        _ => None,
+    }
+}
+
+/// Merges final results keyed by final key into one lookup map.
+pub fn collect_finals<'a>(
+    maps: impl IntoIterator<Item = &'a Map<String, Value>>,
+) -> Map<String, Value> {
+    let mut merged = Map::new();
+    for map in maps {
+        merged.extend(map.iter().map(|(k, v)| (k.clone(), v.clone())));
+    }
+    merged
+}
+
+/// Renders `mapping` against the final results of a run.
+pub fn render(mapping: &[OutputField], finals: &Map<String, Value>) -> MappedOutput {
+    let mut out = MappedOutput::default();
+    for field in mapping {
+        let value = finals
+            .get(&field.key)
+            .map(plain_value)
+            .filter(|v| !v.is_null());
+        let converted = match value {
+            Some(v) => {
+                let converted = convert(v, field.field_type);
+                if converted.is_none() {
+                    out.invalid.push(field.name.clone());
+                }
+                converted
+            }
+            None => {
+                if field.required {
+                    out.missing.push(field.name.clone());
+                }
+                None
+            }
+        };
+        out.fields
+            .insert(field.name.clone(), converted.unwrap_or(Value::Null));
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn renders_customer_fields() {
+        let mapping: Vec<OutputField> = serde_json::from_value(json!([
+            { "key": "score_17", "name": "coverage_score", "type": "number" },
+            { "key": "decision_4", "name": "approved", "type": "boolean", "required": true },
+            { "key": "claim_amount", "name": "amount", "type": "number" },
+            { "key": "policy_no", "name": "policyNumber", "required": true },
+            { "key": "insurer", "name": "insurer", "type": "boolean" }
+        ]))
+        .unwrap();
+        let extraction = json!({
+            "claim_amount": { "value": "1250,5", "confidence": 0.9 },
+            "insurer": { "value": "ACME" }
+        });
+        let scores = json!({ "score_17": 0.5 });
+        let decisions = json!({ "decision_4": { "answer": true, "route": "yes" } });
+        let finals = collect_finals([
+            extraction.as_object().unwrap(),
+            scores.as_object().unwrap(),
+            decisions.as_object().unwrap(),
+        ]);
+
+        let out = render(&mapping, &finals);
+        assert_eq!(out.fields["coverage_score"], json!(0.5));
+        assert_eq!(out.fields["approved"], json!(true));
+        assert_eq!(out.fields["amount"], json!(1250.5));
+        assert_eq!(out.fields["policyNumber"], Value::Null);
+        assert_eq!(out.fields["insurer"], Value::Null);
+        assert_eq!(out.missing, vec!["policyNumber"]);
+        assert_eq!(out.invalid, vec!["insurer"]);
+        assert!(!out.is_complete());
+    }
+}
@@ -0,0 +1,410 @@
+//! Packs document pages into LLM request batches without cutting sentences.
+//!
+//! Every page is prefixed with a `[[PAGE n]]` marker the model has to cite in
+//! `source.page`. Pages are added whole while they fit into `max_chars`; a
+//! page that is larger than the budget on its own is split at sentence
+//! boundaries and continued in the next batch instead of being truncated.
+//!
+//! [`batches_for_step`] decides how each step type is batched; the runner and
+//! the run estimate of the pipeline API both use it, so estimates match runs.
+
+use serde::Serialize;
+
+use crate::dto::PromptType;
+
+/// Marker placed in front of every page (or page part) inside a batch.
+pub fn page_marker(page: i32) -> String {
+    format!("[[PAGE {page}]]")
+}
+
+/// Part of a page that ended up in a batch.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct PageSlice {
+    pub page: i32,
+    /// Characters of the (normalised) page text included in this batch.
+    pub chars: usize,
+    /// Total characters of the normalised page text.
+    pub total_chars: usize,
+    /// Zero-based part index when the page had to be split.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub part: Option<usize>,
+    /// True when the page was only repeated as overlap from the previous batch.
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    pub overlap: bool,
+}
+
+/// One request payload together with the pages it actually contains.
+#[derive(Debug, Clone)]
+pub struct PackedBatch {
+    pub text: String,
+    pub slices: Vec<PageSlice>,
+}
+
+impl PackedBatch {
+    /// Distinct page numbers included in this batch.
+    pub fn pages(&self) -> Vec<i32> {
+        let mut out: Vec<i32> = Vec::new();
+        for s in &self.slices {
+            if !out.contains(&s.page) {
+                out.push(s.page);
+            }
+        }
+        out
+    }
+
+    pub fn char_count(&self) -> usize {
+        self.text.len()
+    }
+
+    pub fn contains_page(&self, page: i32) -> bool {
+        self.slices.iter().any(|s| s.page == page)
+    }
+
+    /// Debug entry for the run log: which pages (and how much of them) were sent.
+    pub fn log_entry(&self) -> serde_json::Value {
+        serde_json::json!({
+            "pages": self.pages(),
+            "char_count": self.char_count(),
+            "included": self.slices,
+        })
+    }
+}
+
+/// A page segment: whole page or one sentence-aligned part of it.
+struct Segment {
+    page: i32,
+    text: String,
+    total_chars: usize,
+    part: Option<usize>,
+}
+
+impl Segment {
+    fn rendered_len(&self) -> usize {
+        page_marker(self.page).len() + 1 + self.text.len()
+    }
+}
+
+/// Packs pages into batches.
+///
+/// * `page_batch_size` – max. distinct pages per batch (`usize::MAX` = all).
+/// * `max_chars` – budget per batch including markers (`0` = unlimited).
+/// * `min_pages_for_batching` – documents with at most this many pages are
+///   sent as one batch if they fit into the budget.
+/// * `overlap_pages` – pages of the previous batch repeated at the start of the
+///   next one (only if they still fit).
+pub fn pack_pages(
+    pages: &[(i32, String)],
+    page_batch_size: usize,
+    max_chars: usize,
+    min_pages_for_batching: usize,
+    overlap_pages: usize,
+) -> Vec<PackedBatch> {
+    if pages.is_empty() {
+        return Vec::new();
+    }
+    let page_batch_size = page_batch_size.max(1);
+    let body_budget = |page: i32| {
+        if max_chars == 0 {
+            usize::MAX
+        } else {
+            // mindestens etwas Inhalt pro Batch, auch bei sehr kleinem Budget
+            max_chars
+                .saturating_sub(page_marker(page).len() + 1)
+                .max(64)
+        }
+    };
+
+    let mut segments: Vec<Segment> = Vec::new();
+    for (pno, raw) in pages {
+        let text = normalize_spaces(raw);
+        let total_chars = text.len();
+        let parts = split_sentences(&text, body_budget(*pno));
+        let split = parts.len() > 1;
+        for (i, part) in parts.into_iter().enumerate() {
+            segments.push(Segment {
+                page: *pno,
+                text: part,
+                total_chars,
+                part: split.then_some(i),
+            });
+        }
+    }
+
+    let fits = |len: usize| max_chars == 0 || len <= max_chars;
+    let total_len: usize = segments.iter().map(|s| s.rendered_len() + 1).sum();
+    let single = pages.len() <= min_pages_for_batching || page_batch_size == usize::MAX;
+    if single && fits(total_len) {
+        let mut batch = Builder::default();
+        for seg in &segments {
+            batch.push(seg, false);
+        }
+        return vec![batch.finish()];
+    }
+    let page_batch_size = if page_batch_size == usize::MAX {
+        // Alles-in-einem passt nicht → moderat große Batches
+        6
+    } else {
+        page_batch_size
+    };
+
+    let mut out: Vec<PackedBatch> = Vec::new();
+    let mut cur = Builder::default();
+    for (idx, seg) in segments.iter().enumerate() {
+        let new_page = !cur.has_page(seg.page);
+        let exceeds_pages = new_page && cur.distinct_pages() >= page_batch_size;
+        let exceeds_chars = !cur.is_empty() && !fits(cur.len_with(seg));
+        if exceeds_pages || exceeds_chars {
+            let prev_pages = cur.own_pages();
+            out.push(std::mem::take(&mut cur).finish());
+
+            // Überlappung: letzte ganze Seiten des vorherigen Batches wiederholen
+            if overlap_pages > 0 && seg.part.unwrap_or(0) == 0 {
+                let overlap: Vec<i32> = prev_pages
+                    .iter()
+                    .rev()
+                    .take(overlap_pages)
+                    .rev()
+                    .copied()
+                    .collect();
+                for prev in segments[..idx]
+                    .iter()
+                    .filter(|s| s.part.is_none() && overlap.contains(&s.page))
+                {
+                    if fits(cur.len_with(prev) + seg.rendered_len() + 1) {
+                        cur.push(prev, true);
+                    }
+                }
+            }
+        }
+        cur.push(seg, false);
+    }
+    if !cur.is_empty() {
+        out.push(cur.finish());
+    }
+    out
+}
+
+/// Batching parameters of a run (runner env defaults plus runtime overrides).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub struct BatchLayout {
+    pub page_batch_size: usize,
+    pub max_chars: usize,
+    pub min_pages_for_batching: usize,
+    pub overlap_pages: usize,
+}
+
+impl BatchLayout {
+    /// Reads `PIPELINE_PAGE_BATCH_SIZE`, `PIPELINE_MAX_CHARS`,
+    /// `PIPELINE_MIN_PAGES_FOR_BATCHING` and `PIPELINE_OVERLAP_PAGES`.
+    pub fn from_env() -> Self {
+        let var = |key: &str, default: usize| {
+            std::env::var(key)
+                .ok()
+                .and_then(|v| v.parse().ok())
+                .unwrap_or(default)
+        };
+        Self {
+            page_batch_size: var("PIPELINE_PAGE_BATCH_SIZE", 5),
+            max_chars: var("PIPELINE_MAX_CHARS", 20_000),
+            min_pages_for_batching: var("PIPELINE_MIN_PAGES_FOR_BATCHING", 4),
+            overlap_pages: var("PIPELINE_OVERLAP_PAGES", 1),
+        }
+    }
+}
+
+/// Batches one step of `step_type` sends to the model: extraction strictly
+/// per page, scoring in (overlapping) batches, decision as a single batch
+/// when the document fits, otherwise in batches without overlap.
+pub fn batches_for_step(
+    step_type: &PromptType,
+    pages: &[(i32, String)],
+    layout: &BatchLayout,
+) -> Vec<PackedBatch> {
+    match step_type {
+        PromptType::ExtractionPrompt => pack_pages(pages, 1, layout.max_chars, 1, 0),
+        PromptType::ScoringPrompt => pack_pages(
+            pages,
+            layout.page_batch_size,
+            layout.max_chars,
+            layout.min_pages_for_batching,
+            layout.overlap_pages,
+        ),
+        PromptType::DecisionPrompt => {
+            let single = pack_pages(pages, usize::MAX, layout.max_chars, usize::MAX, 0);
+            if single.len() == 1 {
+                single
+            } else {
+                pack_pages(
+                    pages,
+                    layout.page_batch_size,
+                    layout.max_chars,
+                    layout.min_pages_for_batching,
+                    0,
+                )
+            }
+        }
+    }
+}
+
+#[derive(Default)]
+struct Builder {
+    text: String,
+    slices: Vec<PageSlice>,
+}
+
+impl Builder {
+    fn is_empty(&self) -> bool {
+        self.slices.is_empty()
+    }
+
+    fn has_page(&self, page: i32) -> bool {
+        self.slices.iter().any(|s| s.page == page && !s.overlap)
+    }
+
+    fn distinct_pages(&self) -> usize {
+        self.own_pages().len()
+    }
+
+    fn own_pages(&self) -> Vec<i32> {
+        let mut out: Vec<i32> = Vec::new();
+        for s in self.slices.iter().filter(|s| !s.overlap) {
+            if !out.contains(&s.page) {
+                out.push(s.page);
+            }
+        }
+        out
+    }
+
+    fn len_with(&self, seg: &Segment) -> usize {
+        self.text.len() + usize::from(!self.text.is_empty()) + seg.rendered_len()
+    }
+
+    fn push(&mut self, seg: &Segment, overlap: bool) {
+        if !self.text.is_empty() {
+            self.text.push('\n');
+        }
+        self.text.push_str(&page_marker(seg.page));
+        self.text.push('\n');
+        self.text.push_str(&seg.text);
+        self.slices.push(PageSlice {
+            page: seg.page,
+            chars: seg.text.len(),
+            total_chars: seg.total_chars,
+            part: seg.part,
+            overlap,
+        });
+    }
+
+    fn finish(self) -> PackedBatch {
+        PackedBatch {
+            text: self.text,
+            slices: self.slices,
+        }
+    }
+}
+
+/// Splits `text` into parts of at most `budget` bytes, preferring sentence
+/// ends, then whitespace, and only as a last resort a char boundary.
+fn split_sentences(text: &str, budget: usize) -> Vec<String> {
+    if text.len() <= budget {
+        return vec![text.to_string()];
+    }
+    let mut parts = Vec::new();
+    let mut rest = text;
+    while rest.len() > budget {
+        let window = &rest[..floor_char_boundary(rest, budget)];
+        let cut = last_sentence_end(window)
+            .or_else(|| window.rfind(' ').filter(|&i| i > 0))
+            .unwrap_or(window.len());
+        let (head, tail) = rest.split_at(cut);
+        parts.push(head.trim().to_string());
+        rest = tail.trim_start();
+    }
+    if !rest.is_empty() {
+        parts.push(rest.to_string());
+    }
+    parts.retain(|p| !p.is_empty());
+    parts
+}
+
+/// Byte index right after the last sentence terminator followed by a space.
+fn last_sentence_end(window: &str) -> Option<usize> {
+    let bytes = window.as_bytes();
+    (1..bytes.len())
+        .rev()
+        .find(|&i| bytes[i] == b' ' && matches!(bytes[i - 1], b'.' | b'!' | b'?' | b';' | b':'))
+}
+
+fn floor_char_boundary(s: &str, mut idx: usize) -> usize {
+    if idx >= s.len() {
+        return s.len();
+    }
+    while idx > 0 && !s.is_char_boundary(idx) {
+        idx -= 1;
+    }
+    idx
+}
+
+fn normalize_spaces(s: &str) -> String {
+    s.split_whitespace().collect::<Vec<_>>().join(" ")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn pages(texts: &[&str]) -> Vec<(i32, String)> {
+        texts
+            .iter()
+            .enumerate()
+            .map(|(i, t)| (i as i32 + 1, t.to_string()))
+            .collect()
+    }
+
+    #[test]
+    fn small_documents_form_one_marked_batch() {
+        let batches = pack_pages(&pages(&["Erste Seite.", "Zweite Seite."]), 5, 1000, 4, 1);
+        assert_eq!(batches.len(), 1);
+        assert_eq!(
+            batches[0].text,
+            "[[PAGE 1]]\nErste Seite.\n[[PAGE 2]]\nZweite Seite."
+        );
+        assert_eq!(batches[0].pages(), vec![1, 2]);
+    }
+
+    #[test]
+    fn oversized_page_is_split_at_sentence_end() {
+        let long = "Satz eins ist hier. Satz zwei folgt jetzt. Satz drei endet. ".repeat(3);
+        let long = long.trim();
+        let batches = pack_pages(&pages(&[long]), 1, 100, 0, 0);
+        assert!(batches.len() > 1);
+        for b in &batches {
+            let body = b.text.strip_prefix("[[PAGE 1]]\n").expect("marker");
+            assert!(body.ends_with('.'), "cut mid-sentence: {body:?}");
+            assert!(b.char_count() <= 100);
+            assert!(b.slices[0].part.is_some());
+        }
+        // nichts geht verloren: nur die Leerzeichen an den Schnittstellen fehlen
+        let included: usize = batches.iter().map(|b| b.slices[0].chars).sum();
+        assert_eq!(included + batches.len() - 1, long.len());
+    }
+
+    #[test]
+    fn respects_page_limit_and_overlap() {
+        let batches = pack_pages(&pages(&["a.", "b.", "c.", "d.", "e."]), 2, 0, 1, 1);
+        let own: Vec<Vec<i32>> = batches
+            .iter()
+            .map(|b| {
+                b.slices
+                    .iter()
+                    .filter(|s| !s.overlap)
+                    .map(|s| s.page)
+                    .collect()
+            })
+            .collect();
+        assert_eq!(own, vec![vec![1, 2], vec![3, 4], vec![5]]);
+        assert!(batches[1].slices[0].overlap);
+        assert_eq!(batches[1].slices[0].page, 2);
+        assert!(batches[1].contains_page(2));
+    }
+}
@@ -0,0 +1,114 @@
+//! Deduplicated, compressed storage of page texts.
+//!
+//! `text-extraction` no longer writes the page text into `pdf_texts.text` but
+//! into `pdf_text_blobs`, one row per distinct text keyed by its SHA-256, and
+//! points `pdf_texts.text_sha256` at it. Re-extractions, appended pages and
+//! re-uploads of the same document therefore store every text only once. The
+//! blob column is compressed by Postgres (LZ4 TOAST compression for texts
+//! above roughly 2 kB, i.e. most full pages) and decompressed transparently on
+//! read.
+//!
+//! Readers select from the view `pdf_page_texts`, which resolves the blob and
+//! falls back to the inline text of rows written before the switch. Old rows
+//! are moved with [`COMPACT_BATCH_SQL`] (`POST /admin/pdf-texts/compact` on
+//! pdf-ingest).
+
+use sha2::{Digest, Sha256};
+
+/// Idempotent DDL in execution order (mirrors `migrations/0044_pdf_text_blobs.sql`).
+pub const SCHEMA_SQL: &[&str] = &[
+    "CREATE TABLE IF NOT EXISTS pdf_text_blobs (
+        sha256 TEXT PRIMARY KEY,
+        text TEXT NOT NULL,
+        char_len INTEGER NOT NULL,
+        created_at TIMESTAMPTZ NOT NULL DEFAULT now(),
+        last_used_at TIMESTAMPTZ NOT NULL DEFAULT now()
+    )",
+    "ALTER TABLE pdf_texts
+        ADD COLUMN IF NOT EXISTS text_sha256 TEXT REFERENCES pdf_text_blobs(sha256),
+        ALTER COLUMN text DROP NOT NULL",
+    "CREATE INDEX IF NOT EXISTS idx_pdf_texts_text_sha256 ON pdf_texts (text_sha256)",
+    "CREATE OR REPLACE VIEW pdf_page_texts AS
+     SELECT t.merged_pdf_id, t.page_no, COALESCE(b.text, t.text, '') AS text
+       FROM pdf_texts t
+       LEFT JOIN pdf_text_blobs b ON b.sha256 = t.text_sha256",
+];
+
+/// Switches `pdf_text_blobs.text` to LZ4. Needs Postgres 14+ built with lz4;
+/// callers run it best effort, otherwise the default `pglz` stays.
+pub const COMPRESSION_SQL: &str =
+    "ALTER TABLE pdf_text_blobs ALTER COLUMN text SET COMPRESSION lz4";
+
+/// Stores a text once (`$1` = [`text_sha256`], `$2` = text). An existing blob
+/// is touched, which also locks it against [`REMOVE_ORPHANS_SQL`] until the
+/// referencing page is committed.
+pub const UPSERT_BLOB_SQL: &str = "INSERT INTO pdf_text_blobs (sha256, text, char_len)
+    VALUES ($1, $2, char_length($2))
+    ON CONFLICT (sha256) DO UPDATE SET last_used_at = now()";
+
+/// Deletes the pages of the documents `$1` (`INTEGER[]`) together with the
+/// blobs no other document references; the row count is that of the pages.
+pub const DELETE_PAGES_SQL: &str = "WITH blobs AS (
+        DELETE FROM pdf_text_blobs b
+         WHERE b.sha256 IN (SELECT text_sha256 FROM pdf_texts WHERE merged_pdf_id = ANY($1))
+           AND NOT EXISTS (SELECT 1 FROM pdf_texts t
+                            WHERE t.text_sha256 = b.sha256 AND t.merged_pdf_id <> ALL($1))
+    )
+    DELETE FROM pdf_texts WHERE merged_pdf_id = ANY($1)";
+
+/// Moves up to `$1` pages with inline text into `pdf_text_blobs`; the hash
+/// matches [`text_sha256`]. `|| ''` yields a fresh value, otherwise Postgres
+/// would keep the pglz compressed datum of the old row.
+pub const COMPACT_BATCH_SQL: &str = "WITH batch AS (
+        SELECT merged_pdf_id, page_no, text,
+               encode(sha256(convert_to(text, 'UTF8')), 'hex') AS sha256
+          FROM pdf_texts
+         WHERE text IS NOT NULL
+         ORDER BY merged_pdf_id, page_no
+         LIMIT $1
+           FOR UPDATE SKIP LOCKED
+    ), blobs AS (
+        INSERT INTO pdf_text_blobs (sha256, text, char_len)
+        SELECT DISTINCT ON (sha256) sha256, text || '', char_length(text) FROM batch
+        ON CONFLICT (sha256) DO UPDATE SET last_used_at = now()
+    )
+    UPDATE pdf_texts t SET text = NULL, text_sha256 = b.sha256
+      FROM batch b
+     WHERE t.merged_pdf_id = b.merged_pdf_id AND t.page_no = b.page_no";
+
+/// Removes blobs no page references any more (left behind by re-extractions)
+/// that were not used during the last hour.
+pub const REMOVE_ORPHANS_SQL: &str = "DELETE FROM pdf_text_blobs b
+     WHERE b.last_used_at < now() - interval '1 hour'
+       AND NOT EXISTS (SELECT 1 FROM pdf_texts t WHERE t.text_sha256 = b.sha256)";
+
+/// Size of the inline and the deduplicated storage, for the compaction report.
+pub const STATS_SQL: &str = "SELECT
+        (SELECT COUNT(*) FROM pdf_texts WHERE text IS NOT NULL),
+        (SELECT COUNT(*) FROM pdf_texts WHERE text_sha256 IS NOT NULL),
+        (SELECT COUNT(*) FROM pdf_text_blobs),
+        (SELECT COALESCE(SUM(char_len), 0)::BIGINT FROM pdf_text_blobs),
+        (SELECT COALESCE(SUM(pg_column_size(text)), 0)::BIGINT FROM pdf_text_blobs)";
+
+/// Key of a text in `pdf_text_blobs`: hex SHA-256 of its UTF-8 bytes.
+pub fn text_sha256(text: &str) -> String {
+    format!("{:x}", Sha256::digest(text.as_bytes()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn hashes_like_postgres() {
+        // encode(sha256(convert_to('seite 1', 'UTF8')), 'hex')
+        assert_eq!(
+            text_sha256("seite 1"),
+            "4c384a4ad529a3276ec14da5e5bfe616c4bd6494412fdb8c7e6f68992dfbabcf"
+        );
+        assert_eq!(
+            text_sha256("prüfung"),
+            "39d8b3b103f229c48beffac0b1c63423a1a0194c51c57c65235a2bb67afe2f57"
+        );
+    }
+}
@@ -0,0 +1,110 @@
+//! Document metadata of merged PDFs (`pdf_metadata`).
+//!
+//! text-extraction reads the document information dictionary and a few
+//! structural flags with `pdfinfo` after every extraction and keeps one row per
+//! merged PDF; pdf-ingest serves it at `GET /pdf/{id}/metadata`. Pipelines can
+//! use it e.g. to tell scanner output (`producer`) from born-digital documents
+//! or to prefer the creation date over dates found in the text.
+
+use serde::{Deserialize, Serialize};
+
+/// Idempotent DDL (mirrors `migrations/0042_pdf_metadata.sql`).
+pub const CREATE_TABLE_SQL: &str = "CREATE TABLE IF NOT EXISTS pdf_metadata (
+    merged_pdf_id INTEGER PRIMARY KEY REFERENCES merged_pdfs(id) ON DELETE CASCADE,
+    title TEXT,
+    subject TEXT,
+    keywords TEXT,
+    author TEXT,
+    creator TEXT,
+    producer TEXT,
+    creation_date TEXT,
+    mod_date TEXT,
+    pdf_version TEXT,
+    page_count INTEGER NOT NULL,
+    encrypted BOOLEAN NOT NULL DEFAULT false,
+    form TEXT,
+    tagged BOOLEAN NOT NULL DEFAULT false,
+    page_sizes JSONB NOT NULL DEFAULT '[]'::jsonb,
+    updated_at TIMESTAMPTZ NOT NULL DEFAULT now()
+)";
+
+/// Upsert; parameters follow the field order of [`PdfMetadata`] after the id,
+/// `page_sizes` as JSON.
+pub const UPSERT_SQL: &str = "INSERT INTO pdf_metadata
+    (merged_pdf_id, title, subject, keywords, author, creator, producer, creation_date,
+     mod_date, pdf_version, page_count, encrypted, form, tagged, page_sizes)
+    VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12, $13, $14, $15::jsonb)
+    ON CONFLICT (merged_pdf_id) DO UPDATE SET
+        title = EXCLUDED.title, subject = EXCLUDED.subject, keywords = EXCLUDED.keywords,
+        author = EXCLUDED.author, creator = EXCLUDED.creator, producer = EXCLUDED.producer,
+        creation_date = EXCLUDED.creation_date, mod_date = EXCLUDED.mod_date,
+        pdf_version = EXCLUDED.pdf_version, page_count = EXCLUDED.page_count,
+        encrypted = EXCLUDED.encrypted, form = EXCLUDED.form, tagged = EXCLUDED.tagged,
+        page_sizes = EXCLUDED.page_sizes, updated_at = now()";
+
+/// Size of one page in points, before applying its rotation.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct PageSize {
+    /// 0-based, as in `pdf_texts`.
+    pub page_no: i32,
+    pub width: f64,
+    pub height: f64,
+    /// Clockwise: 0, 90, 180 or 270.
+    pub rotation: i32,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct PdfMetadata {
+    pub title: Option<String>,
+    pub subject: Option<String>,
+    pub keywords: Option<String>,
+    pub author: Option<String>,
+    /// Application that created the original document.
+    pub creator: Option<String>,
+    /// Application that wrote the PDF (scanner software, printer driver, ...).
+    pub producer: Option<String>,
+    /// ISO 8601 as printed by `pdfinfo -isodates`, e.g. `2024-03-01T09:30:00+01:00`.
+    pub creation_date: Option<String>,
+    pub mod_date: Option<String>,
+    /// e.g. `1.7`.
+    pub pdf_version: Option<String>,
+    pub page_count: i32,
+    pub encrypted: bool,
+    /// Whether the permissions of an encrypted document forbid copying text,
+    /// which `pdftotext` honours; not stored.
+    #[serde(skip)]
+    pub copy_forbidden: bool,
+    /// `AcroForm` or `XFA`; `None` without form.
+    pub form: Option<String>,
+    /// Whether the document carries a structure tree (tagged PDF).
+    pub tagged: bool,
+    #[serde(default)]
+    pub page_sizes: Vec<PageSize>,
+}
+
+impl PdfMetadata {
+    pub fn has_form(&self) -> bool {
+        self.form.is_some()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn round_trips_with_defaults() {
+        let meta: PdfMetadata = serde_json::from_str(
+            r#"{"title": "Kaufvertrag", "subject": null, "keywords": null, "author": null,
+                "creator": null, "producer": "scanner", "creation_date": null,
+                "mod_date": null, "pdf_version": "1.4", "page_count": 2,
+                "encrypted": false, "form": "AcroForm", "tagged": false}"#,
+        )
+        .unwrap();
+        assert!(meta.page_sizes.is_empty());
+        assert!(meta.has_form());
+        let back: PdfMetadata =
+            serde_json::from_value(serde_json::to_value(&meta).unwrap()).unwrap();
+        assert_eq!(back, meta);
+    }
+}
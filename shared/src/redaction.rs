@@ -0,0 +1,228 @@
+//! Role-based redaction of run exports.
+//!
+//! A pipeline lists its recipient roles in `PipelineConfig::redaction_profiles`.
+//! `GET /runs/{id}/bundle.zip?role=<role>` builds the bundle through the
+//! matching [`RedactionProfile`]: whole parts (page texts, source PDF, prompts,
+//! …) are left out, the values of the listed final keys or customer field
+//! names are replaced with [`REDACTED`] wherever the bundle carries results,
+//! and with `mask_pii` the remaining strings go through the scrubber sample
+//! rules (IBANs, e-mail addresses, long numbers, dates). An external reviewer
+//! thus sees decisions and quotes but neither the full page text nor the PII
+//! fields. The applied profile is recorded with the `exported` timeline event.
+
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+use thiserror::Error;
+
+use crate::output_mapping::{MappedOutput, OutputField};
+
+/// Replacement of redacted values.
+pub const REDACTED: &str = "[redacted]";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+/// Part of a run bundle a profile can leave out.
+pub enum BundlePart {
+    /// `text/page-NNNN.txt`.
+    PageText,
+    /// `source.pdf`.
+    SourcePdf,
+    /// `evidence/` crops.
+    Evidence,
+    /// `pipeline.json` and `prompts.json`.
+    Prompts,
+    /// `steps.json`, including intermediate results.
+    Steps,
+    /// `notes.json`.
+    Notes,
+    /// `timeline.json`.
+    Timeline,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+/// What one recipient role may see of a run.
+pub struct RedactionProfile {
+    /// Recipient role, matched case-insensitively against `?role=`.
+    pub role: String,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub omit: Vec<BundlePart>,
+    /// Final keys or output field names whose values are replaced.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub fields: Vec<String>,
+    /// Masks PII patterns in all remaining strings and page texts.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub mask_pii: bool,
+}
+
+#[derive(Debug, Error, PartialEq, Eq)]
+pub enum ProfileError {
+    #[error("redaction profile without role")]
+    EmptyRole,
+    #[error("duplicate redaction profile for role '{0}'")]
+    DuplicateRole(String),
+}
+
+/// Checks the profiles of a pipeline before they are stored.
+pub fn validate(profiles: &[RedactionProfile]) -> Result<(), ProfileError> {
+    let mut seen: Vec<String> = Vec::new();
+    for profile in profiles {
+        let role = profile.role.trim().to_lowercase();
+        if role.is_empty() {
+            return Err(ProfileError::EmptyRole);
+        }
+        if seen.contains(&role) {
+            return Err(ProfileError::DuplicateRole(profile.role.trim().to_string()));
+        }
+        seen.push(role);
+    }
+    Ok(())
+}
+
+/// Profile of `role`, if the pipeline defines one.
+pub fn find<'a>(profiles: &'a [RedactionProfile], role: &str) -> Option<&'a RedactionProfile> {
+    let role = role.trim();
+    profiles
+        .iter()
+        .find(|profile| profile.role.trim().eq_ignore_ascii_case(role))
+}
+
+impl RedactionProfile {
+    pub fn omits(&self, part: BundlePart) -> bool {
+        self.omit.contains(&part)
+    }
+
+    /// True if the value of final key `key` must not leave unredacted.
+    pub fn redacts(&self, key: &str) -> bool {
+        self.fields.iter().any(|field| field == key)
+    }
+
+    /// Replaces the listed keys of a final results map (`final_extraction`,
+    /// one group of `results.json`, …).
+    pub fn redact_finals(&self, finals: &mut Map<String, Value>) {
+        for (key, value) in finals.iter_mut() {
+            if self.redacts(key) {
+                *value = Value::from(REDACTED);
+            }
+        }
+    }
+
+    /// Copy of the profile whose `fields` also hold the final keys of the
+    /// listed customer names, so `results.json` and `steps.json` are covered.
+    pub fn resolve(&self, mapping: &[OutputField]) -> RedactionProfile {
+        let mut profile = self.clone();
+        for field in mapping {
+            if self.redacts(&field.name) && !profile.redacts(&field.key) {
+                profile.fields.push(field.key.clone());
+            }
+        }
+        profile
+    }
+
+    /// Replaces `result` and `final_value` of steps whose `final_key`,
+    /// `decision_key` or `step_id` is listed.
+    pub fn redact_steps(&self, steps: &mut [Value]) {
+        for step in steps.iter_mut().filter_map(Value::as_object_mut) {
+            let listed = ["final_key", "decision_key", "step_id"]
+                .iter()
+                .filter_map(|k| step.get(*k).and_then(Value::as_str))
+                .any(|key| self.redacts(key));
+            if !listed {
+                continue;
+            }
+            for column in ["result", "final_value"] {
+                if let Some(value) = step.get_mut(column).filter(|v| !v.is_null()) {
+                    *value = Value::from(REDACTED);
+                }
+            }
+        }
+    }
+
+    /// Replaces mapped fields listed by final key or by customer name.
+    pub fn redact_output(&self, output: &mut MappedOutput, mapping: &[OutputField]) {
+        for field in mapping {
+            if self.redacts(&field.key) || self.redacts(&field.name) {
+                if let Some(value) = output.fields.get_mut(&field.name) {
+                    *value = Value::from(REDACTED);
+                }
+            }
+        }
+    }
+
+    /// Profile as recorded in the export audit.
+    pub fn audit(&self) -> Value {
+        serde_json::to_value(self).unwrap_or(Value::Null)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn reviewer() -> RedactionProfile {
+        serde_json::from_value(json!({
+            "role": "external_reviewer",
+            "omit": ["page_text", "source_pdf", "notes"],
+            "fields": ["iban", "insured_name"],
+            "mask_pii": true
+        }))
+        .unwrap()
+    }
+
+    #[test]
+    fn profiles_are_found_by_role_and_validated() {
+        let profiles = vec![reviewer()];
+        assert!(find(&profiles, " External_Reviewer ").is_some());
+        assert!(find(&profiles, "auditor").is_none());
+        assert_eq!(validate(&profiles), Ok(()));
+
+        let mut twice = profiles.clone();
+        twice.push(RedactionProfile {
+            role: "EXTERNAL_REVIEWER".into(),
+            omit: Vec::new(),
+            fields: Vec::new(),
+            mask_pii: false,
+        });
+        assert!(matches!(
+            validate(&twice),
+            Err(ProfileError::DuplicateRole(_))
+        ));
+        twice[1].role = " ".into();
+        assert_eq!(validate(&twice), Err(ProfileError::EmptyRole));
+    }
+
+    #[test]
+    fn redacts_finals_steps_and_output() {
+        let profile = reviewer();
+        assert!(profile.omits(BundlePart::PageText));
+        assert!(!profile.omits(BundlePart::Evidence));
+
+        let mut finals = json!({"iban": {"value": "DE89…"}, "amount": {"value": 25000}});
+        profile.redact_finals(finals.as_object_mut().unwrap());
+        assert_eq!(finals, json!({"iban": REDACTED, "amount": {"value": 25000}}));
+
+        let mut steps = vec![
+            json!({"step_id": "s1", "final_key": "iban", "result": {"value": "DE89…"}, "final_value": null}),
+            json!({"step_id": "s2", "decision_key": "decision_4", "result": {"answer": true}}),
+        ];
+        profile.redact_steps(&mut steps);
+        assert_eq!(steps[0]["result"], REDACTED);
+        assert_eq!(steps[0]["final_value"], Value::Null);
+        assert_eq!(steps[1]["result"], json!({"answer": true}));
+
+        let mapping: Vec<OutputField> = serde_json::from_value(json!([
+            {"key": "name_3", "name": "insured_name"},
+            {"key": "decision_4", "name": "covered"}
+        ]))
+        .unwrap();
+        let mut output = MappedOutput::default();
+        output.fields.insert("insured_name".into(), json!("Max Mustermann"));
+        output.fields.insert("covered".into(), json!(true));
+        profile.redact_output(&mut output, &mapping);
+        assert_eq!(output.fields["insured_name"], REDACTED);
+        assert_eq!(output.fields["covered"], true);
+
+        let resolved = profile.resolve(&mapping);
+        assert_eq!(resolved.fields, ["iban", "insured_name", "name_3"]);
+    }
+}
@@ -0,0 +1,174 @@
+//! Per-pipeline rules that turn a run result into a `result_label`
+//! (`approved`, `review`, `rejected`).
+//!
+//! The history service evaluates `PipelineConfig::label_rules` when it persists
+//! a `pipeline-result` event:
+//!
+//! 1. the first score band whose `min` is reached by `overall_score` sets the
+//!    label (a band without `min` matches every run, also runs without score);
+//! 2. every required decision that is missing or has the wrong answer lowers
+//!    the label to at least its `otherwise` label;
+//! 3. contested runs are lowered to at least `contested`, if set.
+
+use std::collections::HashMap;
+
+use serde::{Deserialize, Serialize};
+use strum_macros::{Display, EnumString};
+
+#[derive(
+    Debug,
+    Clone,
+    Copy,
+    PartialEq,
+    Eq,
+    PartialOrd,
+    Ord,
+    Hash,
+    Serialize,
+    Deserialize,
+    EnumString,
+    Display,
+)]
+#[serde(rename_all = "lowercase")]
+#[strum(serialize_all = "lowercase", ascii_case_insensitive)]
+/// Label shown as chip in the history list; ordered from best to worst.
+pub enum ResultLabel {
+    Approved,
+    Review,
+    Rejected,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+/// Label for runs whose `overall_score` is at least `min`.
+pub struct ScoreBand {
+    #[serde(default)]
+    pub min: Option<f32>,
+    pub label: ResultLabel,
+}
+
+fn default_true() -> bool {
+    true
+}
+
+fn default_review() -> ResultLabel {
+    ResultLabel::Review
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+/// Final decision (`decision_<prompt_id>`) that must have the given answer.
+pub struct RequiredDecision {
+    pub key: String,
+    #[serde(default = "default_true")]
+    pub answer: bool,
+    /// Label applied at least when the decision is missing or differs.
+    #[serde(default = "default_review")]
+    pub otherwise: ResultLabel,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+/// Labeling rule set of a pipeline.
+pub struct LabelRules {
+    #[serde(default)]
+    pub bands: Vec<ScoreBand>,
+    #[serde(default)]
+    pub required_decisions: Vec<RequiredDecision>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub contested: Option<ResultLabel>,
+}
+
+/// Keeps the worse of two labels.
+fn lower(current: Option<ResultLabel>, floor: ResultLabel) -> Option<ResultLabel> {
+    Some(current.map_or(floor, |c| c.max(floor)))
+}
+
+impl LabelRules {
+    /// Label of a run; `None` when no rule applies.
+    pub fn evaluate(
+        &self,
+        overall_score: Option<f32>,
+        decisions: &HashMap<String, bool>,
+        contested: bool,
+    ) -> Option<ResultLabel> {
+        let mut bands: Vec<&ScoreBand> = self.bands.iter().collect();
+        // Höchste Schwelle zuerst, Band ohne `min` zuletzt
+        bands.sort_by(|a, b| {
+            b.min
+                .unwrap_or(f32::NEG_INFINITY)
+                .total_cmp(&a.min.unwrap_or(f32::NEG_INFINITY))
+        });
+        let mut label = bands
+            .into_iter()
+            .find(|band| match (band.min, overall_score) {
+                (None, _) => true,
+                (Some(min), Some(score)) => score >= min,
+                (Some(_), None) => false,
+            })
+            .map(|band| band.label);
+
+        for required in &self.required_decisions {
+            if decisions.get(&required.key) != Some(&required.answer) {
+                label = lower(label, required.otherwise);
+            }
+        }
+        if let (true, Some(floor)) = (contested, self.contested) {
+            label = lower(label, floor);
+        }
+        label
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn bands_decisions_and_contested() {
+        let rules: LabelRules = serde_json::from_value(json!({
+            "bands": [
+                { "label": "rejected" },
+                { "min": 0.75, "label": "approved" },
+                { "min": 0.4, "label": "review" }
+            ],
+            "required_decisions": [{ "key": "decision_4" }],
+            "contested": "review"
+        }))
+        .unwrap();
+        let ok = HashMap::from([("decision_4".to_string(), true)]);
+        let no = HashMap::from([("decision_4".to_string(), false)]);
+
+        assert_eq!(
+            rules.evaluate(Some(0.8), &ok, false),
+            Some(ResultLabel::Approved)
+        );
+        assert_eq!(
+            rules.evaluate(Some(0.5), &ok, false),
+            Some(ResultLabel::Review)
+        );
+        assert_eq!(
+            rules.evaluate(Some(0.1), &ok, false),
+            Some(ResultLabel::Rejected)
+        );
+        assert_eq!(
+            rules.evaluate(None, &ok, false),
+            Some(ResultLabel::Rejected)
+        );
+        assert_eq!(
+            rules.evaluate(Some(0.8), &no, false),
+            Some(ResultLabel::Review)
+        );
+        assert_eq!(
+            rules.evaluate(Some(0.8), &HashMap::new(), false),
+            Some(ResultLabel::Review)
+        );
+        assert_eq!(
+            rules.evaluate(Some(0.8), &ok, true),
+            Some(ResultLabel::Review)
+        );
+        assert_eq!(LabelRules::default().evaluate(Some(0.8), &ok, true), None);
+        assert_eq!(
+            "Approved".parse::<ResultLabel>().unwrap(),
+            ResultLabel::Approved
+        );
+    }
+}
@@ -0,0 +1,167 @@
+//! Configuration a pipeline run executed with (`GET /runs/{id}/config`).
+//!
+//! The runner stores a copy of the pipeline config, the texts of the prompts it
+//! references and the model settings in `pipeline_run_configs` when it starts a
+//! run ([`capture`]). Later edits of the pipeline, a prompt or the OpenAI
+//! version do not change what an audit of the run sees. `pipeline_updated_at`
+//! marks the pipeline version the copy was taken from. Runs started before the
+//! table existed have no row.
+
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use sqlx::PgPool;
+use uuid::Uuid;
+
+/// Idempotent DDL (mirrors `migrations/0059_pipeline_run_configs.sql`).
+pub const CREATE_TABLE_SQL: &str = "CREATE TABLE IF NOT EXISTS pipeline_run_configs (
+    run_id UUID PRIMARY KEY REFERENCES pipeline_runs(id) ON DELETE CASCADE,
+    pipeline_id UUID NOT NULL,
+    pipeline_name TEXT,
+    pipeline_updated_at TIMESTAMPTZ,
+    config JSONB NOT NULL,
+    prompts JSONB NOT NULL,
+    model JSONB NOT NULL,
+    captured_at TIMESTAMPTZ NOT NULL DEFAULT now()
+)";
+
+/// OpenAI and batching settings of the runner when the run started.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ModelSettings {
+    pub model: String,
+    pub endpoint: String,
+    /// `chat` or `responses`.
+    pub endpoint_kind: String,
+    /// The run used the OpenAI credentials of its tenant.
+    pub tenant_credentials: bool,
+    pub page_batch_size: usize,
+    pub max_parallel: usize,
+    pub max_chars: usize,
+    pub openai_timeout_ms: u64,
+    pub openai_retries: usize,
+    pub structured_outputs: bool,
+}
+
+/// Prompt as it was stored when the run started.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RunPrompt {
+    pub id: i32,
+    pub prompt_type: String,
+    pub json_key: Option<String>,
+    pub weight: Option<f64>,
+    pub text: String,
+}
+
+/// Stored configuration of one run; timestamps are RFC 3339 strings.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RunConfig {
+    pub run_id: Uuid,
+    pub pipeline_id: Uuid,
+    pub pipeline_name: Option<String>,
+    pub pipeline_updated_at: Option<String>,
+    pub config: Value,
+    pub prompts: Vec<RunPrompt>,
+    pub model: ModelSettings,
+    pub captured_at: String,
+}
+
+/// Prompt ids referenced by the steps of a pipeline config, in step order.
+pub fn prompt_ids(config: &Value) -> Vec<i32> {
+    let mut ids: Vec<i32> = Vec::new();
+    for step in config
+        .get("steps")
+        .and_then(Value::as_array)
+        .into_iter()
+        .flatten()
+    {
+        if let Some(id) = step.get("promptId").and_then(Value::as_i64) {
+            let id = id as i32;
+            if !ids.contains(&id) {
+                ids.push(id);
+            }
+        }
+    }
+    ids
+}
+
+/// Stores `config` (the pipeline's `config_json` the run loaded) with the
+/// current texts of its prompts; a second call for the same run is ignored.
+pub async fn capture(
+    pool: &PgPool,
+    run_id: Uuid,
+    pipeline_id: Uuid,
+    config: &Value,
+    model: &ModelSettings,
+) -> sqlx::Result<()> {
+    sqlx::query(
+        "INSERT INTO pipeline_run_configs
+            (run_id, pipeline_id, pipeline_name, pipeline_updated_at, config, prompts, model)
+         SELECT $1, $2, p.name, p.updated_at, $3,
+                COALESCE((SELECT jsonb_agg(jsonb_build_object(
+                              'id', q.id, 'prompt_type', q.prompt_type, 'json_key', q.json_key,
+                              'weight', q.weight, 'text', q.text) ORDER BY q.id)
+                          FROM prompts q WHERE q.id = ANY($4)), '[]'::jsonb),
+                $5
+         FROM (SELECT 1) AS one
+         LEFT JOIN pipelines p ON p.id = $2
+         ON CONFLICT (run_id) DO NOTHING",
+    )
+    .bind(run_id)
+    .bind(pipeline_id)
+    .bind(config)
+    .bind(prompt_ids(config))
+    .bind(sqlx::types::Json(model))
+    .execute(pool)
+    .await?;
+    Ok(())
+}
+
+/// Stored configuration of `run_id`, `None` without snapshot.
+pub async fn load(pool: &PgPool, run_id: Uuid) -> sqlx::Result<Option<RunConfig>> {
+    let row: Option<Value> =
+        sqlx::query_scalar("SELECT to_jsonb(c) FROM pipeline_run_configs c WHERE c.run_id = $1")
+            .bind(run_id)
+            .fetch_optional(pool)
+            .await?;
+    row.map(serde_json::from_value)
+        .transpose()
+        .map_err(|e| sqlx::Error::Decode(Box::new(e)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn collects_distinct_prompt_ids_in_step_order() {
+        let config = json!({ "name": "p", "steps": [
+            {"promptId": 7}, {"promptId": 3}, {"promptId": 7}, {"type": "x"}
+        ]});
+        assert_eq!(prompt_ids(&config), vec![7, 3]);
+        assert!(prompt_ids(&Value::Null).is_empty());
+    }
+
+    #[test]
+    fn reads_a_stored_row() {
+        // Form von to_jsonb(pipeline_run_configs)
+        let row = json!({
+            "run_id": "6f1c0a52-0000-4000-8000-000000000001",
+            "pipeline_id": "6f1c0a52-0000-4000-8000-000000000002",
+            "pipeline_name": "Kredit",
+            "pipeline_updated_at": "2025-03-04T10:15:00+00:00",
+            "config": {"name": "Kredit", "steps": []},
+            "prompts": [{"id": 7, "prompt_type": "ScoringPrompt", "json_key": null,
+                         "weight": 0.5, "text": "Ist das Einkommen belegt?"}],
+            "model": {"model": "gpt-4o", "endpoint": "https://api.openai.com/v1/chat/completions",
+                      "endpoint_kind": "chat", "tenant_credentials": false,
+                      "page_batch_size": 5, "max_parallel": 3, "max_chars": 20000,
+                      "openai_timeout_ms": 60000, "openai_retries": 2,
+                      "structured_outputs": true},
+            "captured_at": "2025-03-04T10:16:00.123+00:00"
+        });
+        let stored: RunConfig = serde_json::from_value(row).unwrap();
+        assert_eq!(stored.prompts[0].weight, Some(0.5));
+        assert_eq!(stored.model.model, "gpt-4o");
+        assert_eq!(stored.pipeline_name.as_deref(), Some("Kredit"));
+    }
+}
@@ -0,0 +1,97 @@
+//! Runtime overrides for the pipeline-runner batch configuration.
+//!
+//! The pipeline-api stores the overrides as JSON in `app_settings`
+//! (`runner_settings`); the runner polls the row, reloads it on
+//! `settings-changed` (see [`crate::app_settings`]) and applies changed values
+//! to runs started afterwards. Unset fields keep the runner's env defaults.
+
+use serde::{Deserialize, Serialize};
+use sqlx::PgPool;
+
+use crate::app_settings;
+
+/// `app_settings` key holding the overrides.
+pub const RUNNER_SETTINGS_KEY: &str = "runner_settings";
+
+/// Optional overrides; `None` means "use the runner's env default".
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RunnerSettings {
+    #[serde(default)]
+    pub page_batch_size: Option<usize>,
+    #[serde(default)]
+    pub max_parallel: Option<usize>,
+    #[serde(default)]
+    pub max_chars: Option<usize>,
+    #[serde(default)]
+    pub openai_timeout_ms: Option<u64>,
+    #[serde(default)]
+    pub openai_retries: Option<usize>,
+}
+
+fn check<T: PartialOrd + std::fmt::Display>(
+    name: &str,
+    value: Option<T>,
+    min: T,
+    max: T,
+) -> Result<(), String> {
+    match value {
+        Some(v) if v < min || v > max => Err(format!("{name} must be between {min} and {max}")),
+        _ => Ok(()),
+    }
+}
+
+impl RunnerSettings {
+    /// Rejects values that would stall or overload the runner.
+    pub fn validate(&self) -> Result<(), String> {
+        check("page_batch_size", self.page_batch_size, 1, 100)?;
+        check("max_parallel", self.max_parallel, 1, 32)?;
+        check("max_chars", self.max_chars, 1_000, 500_000)?;
+        check("openai_timeout_ms", self.openai_timeout_ms, 1_000, 600_000)?;
+        check("openai_retries", self.openai_retries, 0, 10)?;
+        Ok(())
+    }
+}
+
+/// Reads the stored overrides; a missing row yields the defaults.
+pub async fn fetch(pool: &PgPool) -> anyhow::Result<RunnerSettings> {
+    let value = sqlx::query_scalar::<_, String>("SELECT value FROM app_settings WHERE key = $1")
+        .bind(RUNNER_SETTINGS_KEY)
+        .fetch_optional(pool)
+        .await?;
+    Ok(match value {
+        Some(v) => serde_json::from_str(&v)?,
+        None => RunnerSettings::default(),
+    })
+}
+
+/// Validates and upserts the overrides; audited and announced like every
+/// write through [`app_settings::store`].
+pub async fn store(pool: &PgPool, settings: &RunnerSettings) -> anyhow::Result<()> {
+    settings.validate().map_err(anyhow::Error::msg)?;
+    let value = serde_json::to_string(settings)?;
+    app_settings::store(pool, RUNNER_SETTINGS_KEY, Some(&value), None).await?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn partial_json_and_bounds() {
+        let settings: RunnerSettings =
+            serde_json::from_str(r#"{"max_parallel": 6, "openai_timeout_ms": 40000}"#).unwrap();
+        assert_eq!(settings.max_parallel, Some(6));
+        assert_eq!(settings.page_batch_size, None);
+        assert!(settings.validate().is_ok());
+
+        let too_many = RunnerSettings {
+            max_parallel: Some(0),
+            ..Default::default()
+        };
+        assert_eq!(
+            too_many.validate().unwrap_err(),
+            "max_parallel must be between 1 and 32"
+        );
+    }
+}
@@ -0,0 +1,166 @@
+//! Machine-readable description of the tables a service owns.
+//!
+//! Every DB-owning service serves `GET /admin/schema` with the columns,
+//! indexes and foreign keys of its tables, read from the Postgres catalog, and
+//! the `COMMENT ON` texts that explain column semantics
+//! (`migrations/0029_schema_comments.sql`). The api-gateway combines the
+//! services into one view for integrators reporting against the shared database.
+
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use tokio_postgres::Client;
+
+/// Catalog query; `$1` is the list of table names, the result one JSON array as text.
+pub const DESCRIBE_SQL: &str = r#"
+SELECT COALESCE(json_agg(t ORDER BY t.name), '[]'::json)::text
+  FROM (
+    SELECT c.relname AS name,
+           obj_description(c.oid, 'pg_class') AS comment,
+           (SELECT COALESCE(json_agg(json_build_object(
+                       'name', a.attname,
+                       'type', format_type(a.atttypid, a.atttypmod),
+                       'nullable', NOT a.attnotnull,
+                       'default', pg_get_expr(d.adbin, d.adrelid),
+                       'comment', col_description(c.oid, a.attnum)
+                   ) ORDER BY a.attnum), '[]'::json)
+              FROM pg_attribute a
+              LEFT JOIN pg_attrdef d ON d.adrelid = a.attrelid AND d.adnum = a.attnum
+             WHERE a.attrelid = c.oid AND a.attnum > 0 AND NOT a.attisdropped) AS columns,
+           (SELECT COALESCE(json_agg(json_build_object(
+                       'name', i.relname,
+                       'definition', pg_get_indexdef(x.indexrelid),
+                       'unique', x.indisunique,
+                       'primary', x.indisprimary
+                   ) ORDER BY i.relname), '[]'::json)
+              FROM pg_index x
+              JOIN pg_class i ON i.oid = x.indexrelid
+             WHERE x.indrelid = c.oid) AS indexes,
+           (SELECT COALESCE(json_agg(json_build_object(
+                       'name', k.conname,
+                       'definition', pg_get_constraintdef(k.oid)
+                   ) ORDER BY k.conname), '[]'::json)
+              FROM pg_constraint k
+             WHERE k.conrelid = c.oid AND k.contype = 'f') AS foreign_keys
+      FROM pg_class c
+      JOIN pg_namespace n ON n.oid = c.relnamespace
+     WHERE n.nspname = current_schema()
+       AND c.relkind IN ('r', 'p')
+       AND c.relname::text = ANY($1::text[])
+  ) t
+"#;
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ColumnSchema {
+    pub name: String,
+    #[serde(rename = "type")]
+    pub data_type: String,
+    pub nullable: bool,
+    pub default: Option<String>,
+    pub comment: Option<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct IndexSchema {
+    pub name: String,
+    pub definition: String,
+    pub unique: bool,
+    pub primary: bool,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ForeignKeySchema {
+    pub name: String,
+    /// e.g. `FOREIGN KEY (pdf_id) REFERENCES merged_pdfs(id) ON DELETE CASCADE`
+    pub definition: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct TableSchema {
+    pub name: String,
+    pub comment: Option<String>,
+    pub columns: Vec<ColumnSchema>,
+    pub indexes: Vec<IndexSchema>,
+    pub foreign_keys: Vec<ForeignKeySchema>,
+}
+
+/// Response of `GET /admin/schema`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SchemaDescription {
+    pub service: String,
+    pub tables: Vec<TableSchema>,
+    /// Owned tables that do not exist (yet) in the database.
+    #[serde(default)]
+    pub missing: Vec<String>,
+}
+
+/// Describes `tables` using a tokio-postgres connection.
+pub async fn describe(db: &Client, service: &str, tables: &[&str]) -> Result<SchemaDescription> {
+    let names: Vec<String> = tables.iter().map(|t| t.to_string()).collect();
+    let row = db
+        .query_one(DESCRIBE_SQL, &[&names])
+        .await
+        .context("describe schema")?;
+    from_json(service, tables, &row.get::<_, String>(0))
+}
+
+/// Describes `tables` using a sqlx pool.
+pub async fn describe_pool(
+    pool: &sqlx::PgPool,
+    service: &str,
+    tables: &[&str],
+) -> Result<SchemaDescription> {
+    let names: Vec<String> = tables.iter().map(|t| t.to_string()).collect();
+    let json: String = sqlx::query_scalar(DESCRIBE_SQL)
+        .bind(&names)
+        .fetch_one(pool)
+        .await
+        .context("describe schema")?;
+    from_json(service, tables, &json)
+}
+
+fn from_json(service: &str, tables: &[&str], json: &str) -> Result<SchemaDescription> {
+    let found: Vec<TableSchema> = serde_json::from_str(json).context("parse schema json")?;
+    let missing = tables
+        .iter()
+        .filter(|name| !found.iter().any(|t| t.name == **name))
+        .map(|name| name.to_string())
+        .collect();
+    Ok(SchemaDescription {
+        service: service.to_string(),
+        tables: found,
+        missing,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_catalog_json_and_reports_missing_tables() {
+        let json = r#"[{
+            "name": "pdf_versions",
+            "comment": "Vorherige Stände eines Dokuments",
+            "columns": [
+                {"name": "id", "type": "integer", "nullable": false,
+                 "default": "nextval('pdf_versions_id_seq'::regclass)", "comment": null},
+                {"name": "pdf_id", "type": "integer", "nullable": false,
+                 "default": null, "comment": "merged_pdfs.id"}
+            ],
+            "indexes": [{"name": "pdf_versions_pkey",
+                         "definition": "CREATE UNIQUE INDEX pdf_versions_pkey ON public.pdf_versions USING btree (id)",
+                         "unique": true, "primary": true}],
+            "foreign_keys": [{"name": "pdf_versions_pdf_id_fkey",
+                              "definition": "FOREIGN KEY (pdf_id) REFERENCES merged_pdfs(id) ON DELETE CASCADE"}]
+        }]"#;
+        let schema = from_json("pdf-ingest", &["pdf_versions", "uploads"], json).unwrap();
+        assert_eq!(schema.service, "pdf-ingest");
+        assert_eq!(schema.tables.len(), 1);
+        assert_eq!(schema.tables[0].columns[1].comment.as_deref(), Some("merged_pdfs.id"));
+        assert!(schema.tables[0].indexes[0].primary);
+        assert_eq!(schema.missing, vec!["uploads".to_string()]);
+
+        let value = serde_json::to_value(&schema).unwrap();
+        assert_eq!(value["tables"][0]["columns"][0]["type"], "integer");
+    }
+}
@@ -0,0 +1,327 @@
+//! Masks or hashes sensitive values in result JSON before it is persisted.
+//!
+//! Extraction and decision results embed verbatim quotes (IBANs, health data,
+//! …). The pipeline-runner passes every `pipeline_run_steps.result` through a
+//! [`Scrubber`]; full values survive only in the envelope-encrypted final
+//! extraction payload.
+//!
+//! Rules come from `RESULT_SCRUB_RULES` (JSON array) and default to the built-in
+//! IBAN and e-mail patterns; `RESULT_SCRUBBER=off` disables scrubbing. The
+//! `pipeline-result` event keeps the full values for history-service and the
+//! result sinks unless `RESULT_SCRUB_EVENTS=on` scrubs it as well. Hash
+//! rules use HMAC-SHA256 keyed with `RESULT_SCRUB_HASH_KEY` so equal values stay
+//! comparable without being guessable; without the key they fall back to
+//! masking.
+//!
+//! [`Scrubber::for_samples`] masks free page text handed out as prompt samples
+//! (`GET /prompt-samples` on pdf-ingest) with the same rules plus long numbers
+//! and dates.
+
+use hmac::{Hmac, Mac};
+use regex::Regex;
+use serde::Deserialize;
+use serde_json::Value;
+use sha2::Sha256;
+use thiserror::Error;
+
+pub const SCRUBBER_ENV: &str = "RESULT_SCRUBBER";
+pub const RULES_ENV: &str = "RESULT_SCRUB_RULES";
+pub const HASH_KEY_ENV: &str = "RESULT_SCRUB_HASH_KEY";
+pub const EVENTS_ENV: &str = "RESULT_SCRUB_EVENTS";
+
+/// Whether `RESULT_SCRUB_EVENTS` asks to scrub outgoing result events too.
+pub fn events_from_env() -> bool {
+    std::env::var(EVENTS_ENV)
+        .map(|v| matches!(v.trim().to_ascii_lowercase().as_str(), "on" | "1" | "true"))
+        .unwrap_or(false)
+}
+
+/// Characters kept in clear text at the end of a masked value.
+const MASK_KEEP: usize = 4;
+
+#[derive(Error, Debug)]
+pub enum ScrubError {
+    #[error("invalid {RULES_ENV}: {0}")]
+    Config(#[from] serde_json::Error),
+    #[error("invalid pattern for rule '{name}': {source}")]
+    Pattern { name: String, source: regex::Error },
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ScrubAction {
+    /// Replace all but the last four characters with `*`.
+    #[default]
+    Mask,
+    /// Replace the match with `[<rule>:<hmac prefix>]`.
+    Hash,
+}
+
+/// One entry of `RESULT_SCRUB_RULES`.
+#[derive(Debug, Clone, Deserialize)]
+pub struct ScrubRuleConfig {
+    pub name: String,
+    pub pattern: String,
+    #[serde(default)]
+    pub action: ScrubAction,
+    /// JSON keys the rule applies to; empty means every string value.
+    #[serde(default)]
+    pub fields: Vec<String>,
+}
+
+#[derive(Debug, Clone)]
+struct ScrubRule {
+    name: String,
+    regex: Regex,
+    action: ScrubAction,
+    fields: Vec<String>,
+}
+
+impl ScrubRule {
+    fn applies_to(&self, field: Option<&str>) -> bool {
+        self.fields.is_empty() || field.is_some_and(|f| self.fields.iter().any(|x| x == f))
+    }
+}
+
+fn default_rules() -> Vec<ScrubRuleConfig> {
+    vec![
+        ScrubRuleConfig {
+            name: "iban".into(),
+            pattern: r"\b[A-Z]{2}\d{2}(?: ?[A-Z0-9]{4}){3,7}(?: ?[A-Z0-9]{1,3})?\b".into(),
+            action: ScrubAction::Mask,
+            fields: Vec::new(),
+        },
+        ScrubRuleConfig {
+            name: "email".into(),
+            pattern: r"\b[\w.+-]+@[\w-]+(?:\.[\w-]+)+\b".into(),
+            action: ScrubAction::Mask,
+            fields: Vec::new(),
+        },
+    ]
+}
+
+/// Additional rules of [`Scrubber::for_samples`]: digit runs of six or more
+/// digits (phone, policy and account numbers) and dates.
+fn sample_rules() -> Vec<ScrubRuleConfig> {
+    vec![
+        ScrubRuleConfig {
+            name: "date".into(),
+            pattern: r"\b\d{1,2}[./-]\d{1,2}[./-]\d{2,4}\b".into(),
+            action: ScrubAction::Mask,
+            fields: Vec::new(),
+        },
+        ScrubRuleConfig {
+            name: "number".into(),
+            pattern: r"\+?\d(?:[ ./-]?\d){5,}".into(),
+            action: ScrubAction::Mask,
+            fields: Vec::new(),
+        },
+    ]
+}
+
+fn configured_rules() -> Result<Vec<ScrubRuleConfig>, ScrubError> {
+    Ok(match std::env::var(RULES_ENV) {
+        Ok(v) if !v.trim().is_empty() => serde_json::from_str(&v)?,
+        _ => default_rules(),
+    })
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct Scrubber {
+    rules: Vec<ScrubRule>,
+    hash_key: Option<Vec<u8>>,
+}
+
+impl Scrubber {
+    pub fn new(rules: Vec<ScrubRuleConfig>, hash_key: Option<Vec<u8>>) -> Result<Self, ScrubError> {
+        let rules = rules
+            .into_iter()
+            .map(|r| {
+                Ok(ScrubRule {
+                    regex: Regex::new(&r.pattern).map_err(|source| ScrubError::Pattern {
+                        name: r.name.clone(),
+                        source,
+                    })?,
+                    name: r.name,
+                    action: r.action,
+                    fields: r.fields,
+                })
+            })
+            .collect::<Result<Vec<_>, ScrubError>>()?;
+        Ok(Self { rules, hash_key })
+    }
+
+    /// Scrubber without rules; leaves every value untouched.
+    pub fn disabled() -> Self {
+        Self::default()
+    }
+
+    pub fn from_env() -> Result<Self, ScrubError> {
+        if std::env::var(SCRUBBER_ENV)
+            .map(|v| v.trim().eq_ignore_ascii_case("off"))
+            .unwrap_or(false)
+        {
+            return Ok(Self::disabled());
+        }
+        let rules = configured_rules()?;
+        let hash_key = std::env::var(HASH_KEY_ENV)
+            .ok()
+            .filter(|k| !k.is_empty())
+            .map(String::into_bytes);
+        Self::new(rules, hash_key)
+    }
+
+    /// Scrubber for prompt samples: the rules of `RESULT_SCRUB_RULES` (even with
+    /// `RESULT_SCRUBBER=off`) and [`sample_rules`], all masking every match
+    /// regardless of `fields`; hashes would make values linkable across samples.
+    pub fn for_samples() -> Result<Self, ScrubError> {
+        let rules = configured_rules()?
+            .into_iter()
+            .chain(sample_rules())
+            .map(|rule| ScrubRuleConfig {
+                action: ScrubAction::Mask,
+                fields: Vec::new(),
+                ..rule
+            })
+            .collect();
+        Self::new(rules, None)
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        !self.rules.is_empty()
+    }
+
+    /// Scrubs all matching string values in place.
+    pub fn scrub(&self, value: &mut Value) {
+        if self.is_enabled() {
+            self.scrub_value(value, None);
+        }
+    }
+
+    /// Scrubbed copy of `value`.
+    pub fn scrubbed(&self, value: &Value) -> Value {
+        let mut out = value.clone();
+        self.scrub(&mut out);
+        out
+    }
+
+    /// Scrubbed copy of a free text; rules limited to `fields` do not apply.
+    pub fn scrub_text(&self, text: &str) -> String {
+        self.scrub_str(text, None)
+            .unwrap_or_else(|| text.to_string())
+    }
+
+    fn scrub_value(&self, value: &mut Value, field: Option<&str>) {
+        match value {
+            Value::String(s) => {
+                if let Some(replaced) = self.scrub_str(s, field) {
+                    *s = replaced;
+                }
+            }
+            Value::Array(items) => items.iter_mut().for_each(|v| self.scrub_value(v, field)),
+            Value::Object(map) => {
+                for (k, v) in map.iter_mut() {
+                    self.scrub_value(v, Some(k));
+                }
+            }
+            _ => {}
+        }
+    }
+
+    fn scrub_str(&self, s: &str, field: Option<&str>) -> Option<String> {
+        let mut current: Option<String> = None;
+        for rule in self.rules.iter().filter(|r| r.applies_to(field)) {
+            let input = current.as_deref().unwrap_or(s);
+            if !rule.regex.is_match(input) {
+                continue;
+            }
+            let replaced = rule
+                .regex
+                .replace_all(input, |caps: &regex::Captures| {
+                    self.replacement(rule, &caps[0])
+                })
+                .into_owned();
+            current = Some(replaced);
+        }
+        current
+    }
+
+    fn replacement(&self, rule: &ScrubRule, matched: &str) -> String {
+        match (rule.action, &self.hash_key) {
+            (ScrubAction::Hash, Some(key)) => {
+                let mut mac =
+                    Hmac::<Sha256>::new_from_slice(key).expect("HMAC accepts keys of any length");
+                mac.update(matched.as_bytes());
+                let digest = mac.finalize().into_bytes();
+                let hex: String = digest[..8].iter().map(|b| format!("{b:02x}")).collect();
+                format!("[{}:{hex}]", rule.name)
+            }
+            _ => mask(matched),
+        }
+    }
+}
+
+fn mask(value: &str) -> String {
+    let total = value.chars().filter(|c| !c.is_whitespace()).count();
+    let mut seen = 0;
+    value
+        .chars()
+        .map(|c| {
+            if c.is_whitespace() {
+                return c;
+            }
+            seen += 1;
+            if seen > total.saturating_sub(MASK_KEEP) {
+                c
+            } else {
+                '*'
+            }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn masks_defaults_and_hashes_configured_fields() {
+        let scrubber = Scrubber::new(default_rules(), None).unwrap();
+        let mut v = json!({
+            "value": "DE89 3704 0044 0532 0130 00",
+            "source": { "page": 2, "quote": "Kontakt: max@example.org, Betrag 120 EUR" },
+        });
+        scrubber.scrub(&mut v);
+        assert_eq!(v["value"], "**** **** **** **** **30 00");
+        assert_eq!(
+            v["source"]["quote"],
+            "Kontakt: ***********.org, Betrag 120 EUR"
+        );
+        assert_eq!(v["source"]["page"], 2);
+
+        let rules: Vec<ScrubRuleConfig> = serde_json::from_str(
+            r#"[{"name":"icd","pattern":"\\b[A-Z]\\d{2}\\.\\d\\b","action":"hash","fields":["quote"]}]"#,
+        )
+        .unwrap();
+        let hashing = Scrubber::new(rules, Some(b"secret".to_vec())).unwrap();
+        let out = hashing.scrubbed(&json!({ "quote": "Diagnose F32.1", "value": "F32.1" }));
+        let quote = out["quote"].as_str().unwrap();
+        assert!(quote.starts_with("Diagnose [icd:") && quote.len() == "Diagnose [icd:]".len() + 16);
+        assert_eq!(out["value"], "F32.1");
+        assert!(!Scrubber::disabled().is_enabled());
+    }
+
+    #[test]
+    fn sample_scrubber_masks_numbers_and_dates_in_text() {
+        let scrubber = Scrubber::for_samples().unwrap();
+        assert_eq!(
+            scrubber.scrub_text("Geb. 12.03.1985, Tel. +49 170 1234567, Seite 3 von 12"),
+            "Geb. ******1985, Tel. *** *** ***4567, Seite 3 von 12"
+        );
+        assert_eq!(
+            scrubber.scrub_text("IBAN DE89 3704 0044 0532 0130 00"),
+            "IBAN **** **** **** **** **30 00"
+        );
+    }
+}
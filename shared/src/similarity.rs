@@ -0,0 +1,188 @@
+//! Near-duplicate detection across documents (`pdf_signatures`).
+//!
+//! After every extraction text-extraction stores a MinHash signature of the
+//! word 5-shingles of the full text. pdf-ingest answers
+//! `GET /pdf/{id}/similar` with the earlier documents of the same tenant whose
+//! estimated Jaccard similarity reaches a threshold: candidates share at least
+//! one LSH band (GIN index on `bands`), the signatures decide. With 16 bands of
+//! 4 hashes a pair with similarity 0.8 becomes a candidate in more than 99.9 %
+//! of the cases, one with 0.5 in about 64 %; thresholds below
+//! [`MIN_THRESHOLD`] are therefore refused.
+
+/// Idempotent DDL (mirrors `migrations/0060_pdf_signatures.sql`).
+pub const SCHEMA_SQL: &[&str] = &[
+    "CREATE TABLE IF NOT EXISTS pdf_signatures (
+        merged_pdf_id INTEGER PRIMARY KEY REFERENCES merged_pdfs(id) ON DELETE CASCADE,
+        minhash BIGINT[] NOT NULL,
+        bands BIGINT[] NOT NULL,
+        shingle_count INTEGER NOT NULL,
+        created_at TIMESTAMPTZ NOT NULL DEFAULT now()
+    )",
+    "CREATE INDEX IF NOT EXISTS idx_pdf_signatures_bands ON pdf_signatures USING GIN (bands)",
+];
+
+/// Upsert of one signature: `$1` pdf id, `$2` minhash, `$3` bands, `$4` shingle count.
+pub const UPSERT_SQL: &str =
+    "INSERT INTO pdf_signatures (merged_pdf_id, minhash, bands, shingle_count)
+    VALUES ($1, $2, $3, $4)
+    ON CONFLICT (merged_pdf_id) DO UPDATE
+    SET minhash = EXCLUDED.minhash, bands = EXCLUDED.bands,
+        shingle_count = EXCLUDED.shingle_count, created_at = now()";
+
+/// Words per shingle.
+pub const SHINGLE_WORDS: usize = 5;
+/// Number of MinHash values per document.
+pub const SIGNATURE_LEN: usize = 64;
+/// LSH bands over the signature, [`SIGNATURE_LEN`] / `BANDS` values each.
+pub const BANDS: usize = 16;
+/// Default of `?threshold=`.
+pub const DEFAULT_THRESHOLD: f64 = 0.8;
+/// Lowest threshold the banding finds reliably enough.
+pub const MIN_THRESHOLD: f64 = 0.5;
+
+const FNV_OFFSET: u64 = 0xcbf2_9ce4_8422_2325;
+const FNV_PRIME: u64 = 0x0000_0100_0000_01b3;
+
+/// MinHash signature of one document.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Signature {
+    /// [`SIGNATURE_LEN`] minimum hashes, stored as `BIGINT[]`.
+    pub minhash: Vec<i64>,
+    pub shingle_count: i32,
+}
+
+impl Signature {
+    /// Signature of `text`, `None` for documents without words (e.g. failed OCR).
+    pub fn of_text(text: &str) -> Option<Self> {
+        let shingles = shingles(text);
+        if shingles.is_empty() {
+            return None;
+        }
+        let minhash = (0..SIGNATURE_LEN as u64)
+            .map(|slot| {
+                let seed = mix(slot + 1);
+                shingles
+                    .iter()
+                    .map(|shingle| mix(shingle ^ seed))
+                    .min()
+                    .unwrap_or(u64::MAX) as i64
+            })
+            .collect();
+        Some(Self {
+            minhash,
+            shingle_count: shingles.len() as i32,
+        })
+    }
+
+    /// One hash per LSH band; documents sharing a band value are candidates.
+    pub fn bands(&self) -> Vec<i64> {
+        bands(&self.minhash)
+    }
+}
+
+/// LSH band hashes of a stored signature.
+pub fn bands(minhash: &[i64]) -> Vec<i64> {
+    let rows = (minhash.len() / BANDS).max(1);
+    minhash
+        .chunks(rows)
+        .enumerate()
+        .map(|(band, values)| {
+            let mut hash = fnv(FNV_OFFSET, &(band as u64).to_le_bytes());
+            for value in values {
+                hash = fnv(hash, &value.to_le_bytes());
+            }
+            hash as i64
+        })
+        .collect()
+}
+
+/// Estimated Jaccard similarity of the shingle sets (share of equal slots).
+pub fn similarity(a: &[i64], b: &[i64]) -> f64 {
+    if a.is_empty() || a.len() != b.len() {
+        return 0.0;
+    }
+    let equal = a.iter().zip(b).filter(|(x, y)| x == y).count();
+    equal as f64 / a.len() as f64
+}
+
+/// Hashes of the overlapping word shingles; case, punctuation and line breaks
+/// do not matter. Texts shorter than one shingle yield a single one.
+fn shingles(text: &str) -> Vec<u64> {
+    let words: Vec<String> = text
+        .split(|c: char| !c.is_alphanumeric())
+        .filter(|word| !word.is_empty())
+        .map(str::to_lowercase)
+        .collect();
+    let mut hashes: Vec<u64> = words
+        .windows(SHINGLE_WORDS.min(words.len()).max(1))
+        .map(|window| {
+            window.iter().fold(FNV_OFFSET, |hash, word| {
+                fnv(fnv(hash, word.as_bytes()), b" ")
+            })
+        })
+        .collect();
+    hashes.sort_unstable();
+    hashes.dedup();
+    hashes
+}
+
+fn fnv(mut hash: u64, bytes: &[u8]) -> u64 {
+    for byte in bytes {
+        hash ^= u64::from(*byte);
+        hash = hash.wrapping_mul(FNV_PRIME);
+    }
+    hash
+}
+
+/// SplitMix64 finalizer, derives the independent hash functions.
+fn mix(mut x: u64) -> u64 {
+    x = x.wrapping_add(0x9e37_79b9_7f4a_7c15);
+    x = (x ^ (x >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+    x = (x ^ (x >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+    x ^ (x >> 31)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const CONTRACT: &str = "Darlehensvertrag zwischen der Musterbank AG und Herrn Max Mustermann. \
+        Der Darlehensbetrag beträgt 25.000 EUR bei einem Sollzins von 4,9 Prozent. \
+        Die Rückzahlung erfolgt in 60 monatlichen Raten ab dem 1. März 2025. \
+        Sondertilgungen sind jederzeit ohne Vorfälligkeitsentschädigung möglich. \
+        Gerichtsstand ist Frankfurt am Main.";
+
+    #[test]
+    fn identical_text_matches_regardless_of_layout() {
+        let a = Signature::of_text(CONTRACT).unwrap();
+        let reflowed = CONTRACT.to_uppercase().replace(". ", ".\n\n");
+        let b = Signature::of_text(&reflowed).unwrap();
+        assert_eq!(a.minhash.len(), SIGNATURE_LEN);
+        assert_eq!(similarity(&a.minhash, &b.minhash), 1.0);
+        assert_eq!(a.bands(), b.bands());
+        assert_eq!(a.bands().len(), BANDS);
+    }
+
+    #[test]
+    fn small_edits_stay_similar_and_other_texts_do_not() {
+        let a = Signature::of_text(CONTRACT).unwrap();
+        let edited = CONTRACT.replace("Frankfurt am Main", "Frankfurt am Main. Stand 2025");
+        let b = Signature::of_text(&edited).unwrap();
+        assert!(similarity(&a.minhash, &b.minhash) >= DEFAULT_THRESHOLD);
+        assert!(a.bands().iter().any(|band| b.bands().contains(band)));
+
+        let other = Signature::of_text(
+            "Mietvertrag über eine Wohnung in der Bahnhofstraße 3 in Köln, \
+             Kaltmiete 950 EUR, Kaution drei Monatsmieten, Beginn 1. April 2025.",
+        )
+        .unwrap();
+        assert!(similarity(&a.minhash, &other.minhash) < MIN_THRESHOLD);
+    }
+
+    #[test]
+    fn short_and_empty_texts() {
+        assert_eq!(Signature::of_text(" \n-- "), None);
+        assert_eq!(Signature::of_text("Seite 1").unwrap().shingle_count, 1);
+        assert_eq!(similarity(&[], &[]), 0.0);
+    }
+}
@@ -0,0 +1,219 @@
+//! Startup helpers that wait for dependencies instead of aborting the process.
+//!
+//! Services start their HTTP server right away and connect to Postgres/Kafka
+//! in the background. Until every registered dependency is up, `/readyz`
+//! reports `503` so orchestrators hold back traffic without crash-looping.
+
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::fmt::Display;
+use std::future::Future;
+use std::sync::{Arc, RwLock};
+use std::time::Duration;
+use tracing::{info, warn};
+
+/// Exponential backoff used while waiting for a dependency and between the
+/// retries of failed jobs.
+#[derive(Debug, Clone, Copy)]
+pub struct Backoff {
+    pub initial: Duration,
+    pub max: Duration,
+}
+
+impl Default for Backoff {
+    fn default() -> Self {
+        Self {
+            initial: Duration::from_millis(500),
+            max: Duration::from_secs(30),
+        }
+    }
+}
+
+impl Backoff {
+    /// Reads `STARTUP_BACKOFF_INITIAL_MS` / `STARTUP_BACKOFF_MAX_MS`, falling back to the defaults.
+    pub fn from_env() -> Self {
+        let default = Self::default();
+        let read = |key: &str| {
+            std::env::var(key)
+                .ok()
+                .and_then(|v| v.trim().parse::<u64>().ok())
+                .map(Duration::from_millis)
+        };
+        let initial = read("STARTUP_BACKOFF_INITIAL_MS").unwrap_or(default.initial);
+        let max = read("STARTUP_BACKOFF_MAX_MS")
+            .unwrap_or(default.max)
+            .max(initial);
+        Self { initial, max }
+    }
+
+    /// Delay before the given (zero-based) retry attempt.
+    pub fn delay(&self, attempt: u32) -> Duration {
+        let factor = 1u32.checked_shl(attempt.min(16)).unwrap_or(u32::MAX);
+        self.initial.saturating_mul(factor).min(self.max)
+    }
+
+    /// Delay before the next attempt of a job after `failures` failed ones.
+    pub fn after_failures(&self, failures: i32) -> Duration {
+        self.delay(failures.saturating_sub(1).max(0) as u32)
+    }
+}
+
+/// Runs `init` until it succeeds, sleeping with exponential backoff between attempts.
+pub async fn retry_with_backoff<T, E, F, Fut>(name: &str, backoff: Backoff, mut init: F) -> T
+where
+    F: FnMut() -> Fut,
+    Fut: Future<Output = Result<T, E>>,
+    E: Display,
+{
+    let mut attempt: u32 = 0;
+    loop {
+        match init().await {
+            Ok(value) => {
+                if attempt > 0 {
+                    info!(
+                        dependency = name,
+                        attempts = attempt + 1,
+                        "dependency available"
+                    );
+                }
+                return value;
+            }
+            Err(e) => {
+                let delay = backoff.delay(attempt);
+                warn!(
+                    dependency = name,
+                    attempt = attempt + 1,
+                    retry_in_ms = delay.as_millis() as u64,
+                    error = %e,
+                    "dependency not available yet"
+                );
+                tokio::time::sleep(delay).await;
+                attempt = attempt.saturating_add(1);
+            }
+        }
+    }
+}
+
+/// Readiness report returned by `/readyz`.
+#[derive(Debug, Clone, Serialize)]
+pub struct ReadinessReport {
+    pub ready: bool,
+    pub dependencies: BTreeMap<String, bool>,
+}
+
+/// Tracks which startup dependencies are connected.
+#[derive(Debug, Clone, Default)]
+pub struct Readiness {
+    deps: Arc<RwLock<BTreeMap<String, bool>>>,
+}
+
+impl Readiness {
+    /// Creates a tracker with all given dependencies marked as pending.
+    pub fn new(dependencies: &[&str]) -> Self {
+        let deps = dependencies
+            .iter()
+            .map(|name| (name.to_string(), false))
+            .collect();
+        Self {
+            deps: Arc::new(RwLock::new(deps)),
+        }
+    }
+
+    /// Marks a dependency as connected.
+    pub fn mark_ready(&self, name: &str) {
+        self.set(name, true);
+    }
+
+    /// Marks a dependency as unavailable again.
+    pub fn mark_unready(&self, name: &str) {
+        self.set(name, false);
+    }
+
+    fn set(&self, name: &str, ready: bool) {
+        let mut deps = self.deps.write().unwrap_or_else(|e| e.into_inner());
+        deps.insert(name.to_string(), ready);
+    }
+
+    /// True once every registered dependency is connected.
+    pub fn is_ready(&self) -> bool {
+        let deps = self.deps.read().unwrap_or_else(|e| e.into_inner());
+        deps.values().all(|ready| *ready)
+    }
+
+    /// Snapshot of the current dependency states.
+    pub fn report(&self) -> ReadinessReport {
+        let deps = self.deps.read().unwrap_or_else(|e| e.into_inner());
+        ReadinessReport {
+            ready: deps.values().all(|ready| *ready),
+            dependencies: deps.clone(),
+        }
+    }
+
+    /// `/readyz` response: the report with `200`, or `503` while not ready.
+    #[cfg(feature = "actix")]
+    pub fn respond(&self) -> actix_web::HttpResponse {
+        let report = self.report();
+        if report.ready {
+            actix_web::HttpResponse::Ok().json(report)
+        } else {
+            actix_web::HttpResponse::ServiceUnavailable().json(report)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicU32, Ordering};
+
+    #[test]
+    fn readiness_requires_all_dependencies() {
+        let readiness = Readiness::new(&["postgres", "kafka"]);
+        assert!(!readiness.is_ready());
+        readiness.mark_ready("postgres");
+        assert!(!readiness.is_ready());
+        readiness.mark_ready("kafka");
+        assert!(readiness.is_ready());
+        readiness.mark_unready("kafka");
+        let report = readiness.report();
+        assert!(!report.ready);
+        assert_eq!(report.dependencies.get("postgres"), Some(&true));
+    }
+
+    #[test]
+    fn backoff_is_capped() {
+        let backoff = Backoff {
+            initial: Duration::from_millis(100),
+            max: Duration::from_millis(1000),
+        };
+        assert_eq!(backoff.delay(0), Duration::from_millis(100));
+        assert_eq!(backoff.delay(2), Duration::from_millis(400));
+        assert_eq!(backoff.delay(10), Duration::from_millis(1000));
+        assert_eq!(backoff.delay(u32::MAX), Duration::from_millis(1000));
+        assert_eq!(backoff.after_failures(0), Duration::from_millis(100));
+        assert_eq!(backoff.after_failures(3), Duration::from_millis(400));
+        assert_eq!(
+            backoff.after_failures(i32::MAX),
+            Duration::from_millis(1000)
+        );
+    }
+
+    #[tokio::test]
+    async fn retry_until_success() {
+        let calls = AtomicU32::new(0);
+        let backoff = Backoff {
+            initial: Duration::from_millis(1),
+            max: Duration::from_millis(2),
+        };
+        let value = retry_with_backoff("test", backoff, || async {
+            if calls.fetch_add(1, Ordering::SeqCst) < 2 {
+                Err("not yet")
+            } else {
+                Ok(42)
+            }
+        })
+        .await;
+        assert_eq!(value, 42);
+        assert_eq!(calls.load(Ordering::SeqCst), 3);
+    }
+}
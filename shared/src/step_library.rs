@@ -0,0 +1,227 @@
+//! Library of reusable, fully configured pipeline steps.
+//!
+//! prompt-manager keeps named steps (prompt, type, routing keys and `config`
+//! with thresholds or schema) in `step_library`; every change stores a new row
+//! in `step_library_versions`. A pipeline step points to an item with
+//! `"library": {"id": 3}` and follows its latest version, or pins one with
+//! `"library": {"id": 3, "version": 2}`. The pipeline keeps a copy of the
+//! referenced version, so the runner and every other reader of `config_json`
+//! see an ordinary step: pipeline-api fills the copy whenever it stores a
+//! pipeline ([`expand`]), prompt-manager refreshes the copies of all pipelines
+//! following an item when it saves a new version ([`LibraryStep::apply_json`]).
+//! `id` and `active` stay per pipeline.
+
+use serde::{Deserialize, Deserializer, Serialize};
+use serde_json::Value;
+use sqlx::PgPool;
+
+use crate::dto::{PipelineStep, PromptType};
+
+/// Idempotent DDL of the library (mirrors `migrations/0057_step_library.sql`).
+pub const CREATE_STEP_LIBRARY_SQL: &[&str] = &[
+    "CREATE TABLE IF NOT EXISTS step_library (
+        id SERIAL PRIMARY KEY,
+        name TEXT NOT NULL UNIQUE,
+        description TEXT,
+        version INTEGER NOT NULL DEFAULT 1
+    )",
+    "CREATE TABLE IF NOT EXISTS step_library_versions (
+        item_id INTEGER NOT NULL REFERENCES step_library(id) ON DELETE CASCADE,
+        version INTEGER NOT NULL,
+        step JSONB NOT NULL,
+        created_at TIMESTAMPTZ NOT NULL DEFAULT now(),
+        PRIMARY KEY (item_id, version)
+    )",
+];
+
+/// Reference of a pipeline step to a library item; without `version` the
+/// step follows the latest one.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct LibraryRef {
+    pub id: i32,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub version: Option<i32>,
+}
+
+/// Deserializes a patch field: missing stays `None`, `null` unlinks
+/// (`Some(None)`). Use with `#[serde(default, deserialize_with = ...)]`.
+pub fn deserialize_patch<'de, D>(deserializer: D) -> Result<Option<Option<LibraryRef>>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    Option::<LibraryRef>::deserialize(deserializer).map(Some)
+}
+
+/// Configured step as stored per library version.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct LibraryStep {
+    #[serde(rename = "type")]
+    pub step_type: PromptType,
+    #[serde(rename = "promptId")]
+    pub prompt_id: i32,
+    #[serde(default)]
+    pub route: Option<String>,
+    #[serde(default, rename = "yesKey")]
+    pub yes_key: Option<String>,
+    #[serde(default, rename = "noKey")]
+    pub no_key: Option<String>,
+    #[serde(default)]
+    pub config: Option<Value>,
+}
+
+impl LibraryStep {
+    /// Overwrites the library fields of `step`.
+    pub fn apply(&self, step: &mut PipelineStep) {
+        step.step_type = self.step_type.clone();
+        step.prompt_id = self.prompt_id;
+        step.route = self.route.clone();
+        step.yes_key = self.yes_key.clone();
+        step.no_key = self.no_key.clone();
+        step.config = self.config.clone();
+    }
+
+    /// Like [`apply`](Self::apply) on a step of a stored `config_json`;
+    /// other keys of the step are kept.
+    pub fn apply_json(&self, step: &mut Value) {
+        let Some(step) = step.as_object_mut() else {
+            return;
+        };
+        let Value::Object(fields) = serde_json::to_value(self).unwrap_or_default() else {
+            return;
+        };
+        step.extend(fields);
+    }
+}
+
+/// Reference of a step in a stored `config_json`, if any.
+pub fn step_ref(step: &Value) -> Option<LibraryRef> {
+    serde_json::from_value(step.get("library")?.clone()).ok()
+}
+
+/// Loads the referenced version (the latest without pin); `None` if the item
+/// or the version does not exist.
+pub async fn load(pool: &PgPool, library: &LibraryRef) -> sqlx::Result<Option<(i32, LibraryStep)>> {
+    let row: Option<(i32, Value)> = sqlx::query_as(
+        "SELECT v.version, v.step
+           FROM step_library l
+           JOIN step_library_versions v ON v.item_id = l.id
+          WHERE l.id = $1 AND v.version = COALESCE($2, l.version)",
+    )
+    .bind(library.id)
+    .bind(library.version)
+    .fetch_optional(pool)
+    .await?;
+    Ok(row.and_then(|(version, step)| Some((version, serde_json::from_value(step).ok()?))))
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum ExpandError {
+    #[error("library step {} version {:?} not found", .0.id, .0.version)]
+    Unknown(LibraryRef),
+    #[error(transparent)]
+    Db(#[from] sqlx::Error),
+}
+
+/// Fills every step with a library reference from its library version.
+pub async fn expand(pool: &PgPool, steps: &mut [PipelineStep]) -> Result<(), ExpandError> {
+    for step in steps.iter_mut() {
+        let Some(library) = step.library else {
+            continue;
+        };
+        let (_, content) = load(pool, &library)
+            .await?
+            .ok_or(ExpandError::Unknown(library))?;
+        content.apply(step);
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn content() -> LibraryStep {
+        serde_json::from_value(json!({
+            "type": "ScoringPrompt",
+            "promptId": 7,
+            "config": {"min_signal": 0.6, "weight": 0.5}
+        }))
+        .unwrap()
+    }
+
+    #[test]
+    fn apply_keeps_id_active_and_reference() {
+        let mut step: PipelineStep = serde_json::from_value(json!({
+            "id": "6f1c0a52-0000-4000-8000-000000000001",
+            "type": "ExtractionPrompt",
+            "promptId": 1,
+            "route": "a",
+            "active": false,
+            "config": null,
+            "library": {"id": 3}
+        }))
+        .unwrap();
+        content().apply(&mut step);
+        assert_eq!(step.prompt_id, 7);
+        assert_eq!(step.step_type, PromptType::ScoringPrompt);
+        assert_eq!(step.route, None);
+        assert!(!step.active);
+        assert_eq!(
+            step.library,
+            Some(LibraryRef {
+                id: 3,
+                version: None
+            })
+        );
+        assert_eq!(step.config, Some(json!({"min_signal": 0.6, "weight": 0.5})));
+    }
+
+    #[test]
+    fn apply_json_refreshes_a_stored_step() {
+        let mut step = json!({
+            "id": "6f1c0a52-0000-4000-8000-000000000001",
+            "type": "ScoringPrompt",
+            "promptId": 7,
+            "active": true,
+            "config": {"min_signal": 0.2},
+            "library": {"id": 3}
+        });
+        content().apply_json(&mut step);
+        assert_eq!(step["config"]["min_signal"], json!(0.6));
+        assert_eq!(step["active"], json!(true));
+        assert_eq!(
+            step_ref(&step),
+            Some(LibraryRef {
+                id: 3,
+                version: None
+            })
+        );
+
+        let pinned = json!({"library": {"id": 3, "version": 2}});
+        assert_eq!(step_ref(&pinned).and_then(|r| r.version), Some(2));
+        assert_eq!(step_ref(&json!({"promptId": 7})), None);
+    }
+
+    #[test]
+    fn patch_distinguishes_missing_from_null() {
+        #[derive(Deserialize)]
+        struct Patch {
+            #[serde(default, deserialize_with = "deserialize_patch")]
+            library: Option<Option<LibraryRef>>,
+        }
+        let missing: Patch = serde_json::from_value(json!({})).unwrap();
+        assert_eq!(missing.library, None);
+        let unlink: Patch = serde_json::from_value(json!({"library": null})).unwrap();
+        assert_eq!(unlink.library, Some(None));
+        let pin: Patch =
+            serde_json::from_value(json!({"library": {"id": 3, "version": 1}})).unwrap();
+        assert_eq!(
+            pin.library,
+            Some(Some(LibraryRef {
+                id: 3,
+                version: Some(1)
+            }))
+        );
+    }
+}
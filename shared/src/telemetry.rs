@@ -0,0 +1,279 @@
+//! Opt-in, anonymized product analytics events.
+//!
+//! Services report feature usage (pipeline created, run finished, review
+//! approved, export downloaded) through a [`Telemetry`] handle. Nothing is sent
+//! unless `TELEMETRY_SINK` is set to `kafka:<topic>` or an `http(s)://` URL.
+//!
+//! Every event passes a strict allow-list: per [`EventKind`] only the listed
+//! properties survive, and only as numbers, booleans or short enum-like strings.
+//! Identifiers (`tenant`, `pipeline`) are replaced by an HMAC-SHA256 prefix
+//! keyed with `TELEMETRY_SALT` so adoption can be counted per tenant without
+//! revealing who it is; without a salt they are dropped. Delivery is best
+//! effort and never fails the calling operation.
+
+use hmac::{Hmac, Mac};
+use rdkafka::producer::{FutureProducer, FutureRecord};
+use rdkafka::ClientConfig;
+use serde::Serialize;
+use serde_json::{Map, Value};
+use sha2::Sha256;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use thiserror::Error;
+use tracing::{debug, warn};
+
+pub const SINK_ENV: &str = "TELEMETRY_SINK";
+pub const SALT_ENV: &str = "TELEMETRY_SALT";
+
+/// Properties hashed with the salt instead of being sent verbatim.
+const ID_FIELDS: [&str; 2] = ["tenant", "pipeline"];
+/// Longest string value that is passed through (enum-like values only).
+const MAX_STRING_LEN: usize = 32;
+
+#[derive(Error, Debug)]
+pub enum TelemetryError {
+    #[error("invalid {SINK_ENV} '{0}', expected kafka:<topic> or an http(s) URL")]
+    Sink(String),
+    #[error("failed to create kafka producer: {0}")]
+    Kafka(#[from] rdkafka::error::KafkaError),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum EventKind {
+    PipelineCreated,
+    RunFinished,
+    ReviewApproved,
+    ExportDownloaded,
+}
+
+impl EventKind {
+    /// Properties that may leave the service for this event.
+    pub fn allowed_fields(self) -> &'static [&'static str] {
+        match self {
+            EventKind::PipelineCreated => &["tenant", "pipeline", "steps", "duplicated"],
+            EventKind::RunFinished => &[
+                "tenant",
+                "pipeline",
+                "status",
+                "duration_ms",
+                "pages",
+                "steps",
+                "contested",
+                "reused_extraction",
+            ],
+            EventKind::ReviewApproved => &["tenant", "pipeline", "label"],
+            EventKind::ExportDownloaded => &["tenant", "pipeline", "format"],
+        }
+    }
+}
+
+/// Event as delivered to the sink.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct TelemetryEvent {
+    pub event: EventKind,
+    pub service: String,
+    /// Seconds since the Unix epoch.
+    pub occurred_at: u64,
+    pub props: Map<String, Value>,
+}
+
+#[derive(Clone)]
+enum Sink {
+    Kafka {
+        producer: FutureProducer,
+        topic: String,
+    },
+    Http {
+        client: reqwest::Client,
+        url: String,
+    },
+}
+
+/// Cheap to clone handle; disabled unless `TELEMETRY_SINK` is configured.
+#[derive(Clone, Default)]
+pub struct Telemetry {
+    service: String,
+    sink: Option<Sink>,
+    salt: Option<Vec<u8>>,
+}
+
+impl Telemetry {
+    /// Handle that drops every event.
+    pub fn disabled() -> Self {
+        Self::default()
+    }
+
+    /// Reads `TELEMETRY_SINK` and `TELEMETRY_SALT`; the Kafka sink uses `broker`.
+    pub fn from_env(service: &str, broker: &str) -> Result<Self, TelemetryError> {
+        let sink = match std::env::var(SINK_ENV) {
+            Ok(v) if !v.trim().is_empty() && !v.trim().eq_ignore_ascii_case("off") => v,
+            _ => return Ok(Self::disabled()),
+        };
+        let sink = sink.trim();
+        let sink = if let Some(topic) = sink.strip_prefix("kafka:") {
+            if topic.is_empty() {
+                return Err(TelemetryError::Sink(sink.to_string()));
+            }
+            Sink::Kafka {
+                producer: ClientConfig::new()
+                    .set("bootstrap.servers", broker)
+                    .create()?,
+                topic: topic.to_string(),
+            }
+        } else if sink.starts_with("http://") || sink.starts_with("https://") {
+            Sink::Http {
+                client: reqwest::Client::builder()
+                    .timeout(Duration::from_secs(5))
+                    .build()
+                    .map_err(|_| TelemetryError::Sink(sink.to_string()))?,
+                url: sink.to_string(),
+            }
+        } else {
+            return Err(TelemetryError::Sink(sink.to_string()));
+        };
+        let salt = std::env::var(SALT_ENV)
+            .ok()
+            .filter(|s| !s.is_empty())
+            .map(String::into_bytes);
+        Ok(Self {
+            service: service.to_string(),
+            sink: Some(sink),
+            salt,
+        })
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.sink.is_some()
+    }
+
+    /// Applies the allow-list and anonymizes identifiers.
+    pub fn event(&self, kind: EventKind, props: Value) -> TelemetryEvent {
+        let mut out = Map::new();
+        if let Value::Object(map) = props {
+            for (key, value) in map {
+                if !kind.allowed_fields().contains(&key.as_str()) {
+                    continue;
+                }
+                let value = if ID_FIELDS.contains(&key.as_str()) {
+                    match self.anonymize(&value) {
+                        Some(hashed) => Value::String(hashed),
+                        None => continue,
+                    }
+                } else {
+                    match value {
+                        Value::Bool(_) | Value::Number(_) => value,
+                        Value::String(s) if s.len() <= MAX_STRING_LEN => Value::String(s),
+                        _ => continue,
+                    }
+                };
+                out.insert(key, value);
+            }
+        }
+        TelemetryEvent {
+            event: kind,
+            service: self.service.clone(),
+            occurred_at: SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map(|d| d.as_secs())
+                .unwrap_or_default(),
+            props: out,
+        }
+    }
+
+    /// Sends an event to the configured sink (no-op when disabled).
+    pub async fn emit(&self, kind: EventKind, props: Value) {
+        let Some(sink) = self.sink.as_ref() else {
+            return;
+        };
+        let event = self.event(kind, props);
+        let Ok(payload) = serde_json::to_string(&event) else {
+            return;
+        };
+        let delivered = match sink {
+            Sink::Kafka { producer, topic } => producer
+                .send(
+                    FutureRecord::to(topic).payload(&payload).key(&()),
+                    Duration::from_secs(0),
+                )
+                .await
+                .map(|_| ())
+                .map_err(|(e, _)| e.to_string()),
+            Sink::Http { client, url } => client
+                .post(url)
+                .header("Content-Type", "application/json")
+                .body(payload)
+                .send()
+                .await
+                .and_then(|r| r.error_for_status())
+                .map(|_| ())
+                .map_err(|e| e.to_string()),
+        };
+        match delivered {
+            Ok(()) => debug!(event = ?kind, "telemetry event sent"),
+            Err(e) => warn!(event = ?kind, error = %e, "failed to send telemetry event"),
+        }
+    }
+
+    /// Sends the event in the background so request handlers do not wait for the sink.
+    pub fn spawn_emit(&self, kind: EventKind, props: Value) {
+        if !self.is_enabled() {
+            return;
+        }
+        let telemetry = self.clone();
+        tokio::spawn(async move { telemetry.emit(kind, props).await });
+    }
+
+    fn anonymize(&self, value: &Value) -> Option<String> {
+        let key = self.salt.as_ref()?;
+        let raw = match value {
+            Value::String(s) => s.clone(),
+            Value::Number(n) => n.to_string(),
+            _ => return None,
+        };
+        let mut mac = Hmac::<Sha256>::new_from_slice(key).expect("HMAC accepts keys of any length");
+        mac.update(raw.as_bytes());
+        let digest = mac.finalize().into_bytes();
+        Some(digest[..8].iter().map(|b| format!("{b:02x}")).collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn keeps_only_allowed_fields_and_hashes_ids() {
+        let telemetry = Telemetry {
+            service: "pipeline-runner".into(),
+            sink: None,
+            salt: Some(b"salt".to_vec()),
+        };
+        let event = telemetry.event(
+            EventKind::RunFinished,
+            json!({
+                "pipeline": "3f0c3c52-6c1e-4a4b-9a51-0d8c7d1d2a10",
+                "status": "completed",
+                "pages": 12,
+                "contested": false,
+                "file_name": "Vertrag Müller.pdf",
+                "label": "approved",
+            }),
+        );
+        assert_eq!(event.props.len(), 4);
+        let hashed = event.props["pipeline"].as_str().unwrap();
+        assert_eq!(hashed.len(), 16);
+        assert!(!hashed.contains('-'));
+        assert_eq!(event.props["status"], "completed");
+        assert!(!event.props.contains_key("file_name"));
+        assert!(!event.props.contains_key("label"));
+
+        let unsalted = Telemetry::disabled();
+        let event = unsalted.event(
+            EventKind::ExportDownloaded,
+            json!({ "tenant": "t-1", "format": "x".repeat(MAX_STRING_LEN + 1) }),
+        );
+        assert!(event.props.is_empty());
+        assert!(!unsalted.is_enabled());
+    }
+}
@@ -0,0 +1,236 @@
+//! Per-tenant OpenAI credentials stored in `app_settings`.
+//!
+//! Each record lives under the key `openai_credentials:<tenant_id>` as JSON.
+//! The API key is envelope-encrypted (see [`crate::envelope`]); organization,
+//! endpoint and model allow-list are stored in clear text. The pipeline-runner
+//! resolves the record from the run's upload tenant and scopes the OpenAI
+//! calls of that run to it via [`openai_client::with_credentials`].
+//!
+//! [`openai_client::with_credentials`]: crate::openai_client::with_credentials
+
+use crate::envelope::{EnvelopeError, MasterKey, SealedSecret};
+use crate::openai_client::OpenAiCredentials;
+use serde::{Deserialize, Serialize};
+use sqlx::{PgPool, Row};
+use thiserror::Error;
+use uuid::Uuid;
+
+/// Prefix of the `app_settings` keys holding tenant credentials.
+pub const SETTINGS_PREFIX: &str = "openai_credentials:";
+
+pub fn settings_key(tenant_id: Uuid) -> String {
+    format!("{SETTINGS_PREFIX}{tenant_id}")
+}
+
+#[derive(Error, Debug)]
+pub enum CredentialError {
+    #[error("database error: {0}")]
+    Db(#[from] sqlx::Error),
+    #[error("invalid credential record: {0}")]
+    Json(#[from] serde_json::Error),
+    #[error(transparent)]
+    Envelope(#[from] EnvelopeError),
+    #[error("invalid input: {0}")]
+    Invalid(String),
+}
+
+/// Payload accepted by the settings API.
+#[derive(Debug, Clone, Deserialize)]
+pub struct CredentialInput {
+    pub api_key: String,
+    #[serde(default)]
+    pub organization: Option<String>,
+    #[serde(default)]
+    pub endpoint: Option<String>,
+    #[serde(default)]
+    pub allowed_models: Vec<String>,
+}
+
+/// Credential record as returned by the API; never contains the key itself.
+#[derive(Debug, Clone, Serialize)]
+pub struct CredentialSummary {
+    pub tenant_id: Uuid,
+    pub api_key_hint: String,
+    pub organization: Option<String>,
+    pub endpoint: Option<String>,
+    pub allowed_models: Vec<String>,
+}
+
+/// JSON stored in `app_settings.value`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct StoredCredential {
+    api_key: SealedSecret,
+    api_key_hint: String,
+    organization: Option<String>,
+    endpoint: Option<String>,
+    #[serde(default)]
+    allowed_models: Vec<String>,
+}
+
+impl StoredCredential {
+    fn summary(self, tenant_id: Uuid) -> CredentialSummary {
+        CredentialSummary {
+            tenant_id,
+            api_key_hint: self.api_key_hint,
+            organization: self.organization,
+            endpoint: self.endpoint,
+            allowed_models: self.allowed_models,
+        }
+    }
+}
+
+/// Last four characters of the key, enough to tell keys apart in the UI.
+fn key_hint(api_key: &str) -> String {
+    let chars: Vec<char> = api_key.chars().collect();
+    let tail: String = chars[chars.len().saturating_sub(4)..].iter().collect();
+    format!("…{tail}")
+}
+
+fn non_empty(value: Option<String>) -> Option<String> {
+    value
+        .map(|v| v.trim().to_string())
+        .filter(|v| !v.is_empty())
+}
+
+/// Encrypts and upserts the credentials of `tenant_id` (with a pool or inside
+/// a transaction).
+pub async fn store<'e>(
+    executor: impl sqlx::PgExecutor<'e>,
+    master: &MasterKey,
+    tenant_id: Uuid,
+    input: CredentialInput,
+) -> Result<CredentialSummary, CredentialError> {
+    let api_key = input.api_key.trim();
+    if api_key.is_empty() {
+        return Err(CredentialError::Invalid("api_key must not be empty".into()));
+    }
+    let endpoint = non_empty(input.endpoint);
+    if let Some(url) = endpoint.as_deref() {
+        if !url.starts_with("https://") {
+            return Err(CredentialError::Invalid("endpoint must use https".into()));
+        }
+    }
+    let stored = StoredCredential {
+        api_key: master.seal(api_key)?,
+        api_key_hint: key_hint(api_key),
+        organization: non_empty(input.organization),
+        endpoint,
+        allowed_models: input
+            .allowed_models
+            .into_iter()
+            .map(|m| m.trim().to_string())
+            .filter(|m| !m.is_empty())
+            .collect(),
+    };
+    sqlx::query(
+        "INSERT INTO app_settings (key, value, updated_at)
+         VALUES ($1, $2, now())
+         ON CONFLICT (key)
+         DO UPDATE SET value = EXCLUDED.value, updated_at = now()",
+    )
+    .bind(settings_key(tenant_id))
+    .bind(serde_json::to_string(&stored)?)
+    .execute(executor)
+    .await?;
+    Ok(stored.summary(tenant_id))
+}
+
+async fn fetch(
+    pool: &PgPool,
+    tenant_id: Uuid,
+) -> Result<Option<StoredCredential>, CredentialError> {
+    let value = sqlx::query_scalar::<_, String>("SELECT value FROM app_settings WHERE key = $1")
+        .bind(settings_key(tenant_id))
+        .fetch_optional(pool)
+        .await?;
+    Ok(match value {
+        Some(v) => Some(serde_json::from_str(&v)?),
+        None => None,
+    })
+}
+
+/// Masked view of the tenant's credentials.
+pub async fn summary(
+    pool: &PgPool,
+    tenant_id: Uuid,
+) -> Result<Option<CredentialSummary>, CredentialError> {
+    Ok(fetch(pool, tenant_id).await?.map(|c| c.summary(tenant_id)))
+}
+
+/// Masked views of all tenants that have credentials configured.
+pub async fn list(pool: &PgPool) -> Result<Vec<CredentialSummary>, CredentialError> {
+    let rows = sqlx::query("SELECT key, value FROM app_settings WHERE key LIKE $1 ORDER BY key")
+        .bind(format!("{SETTINGS_PREFIX}%"))
+        .fetch_all(pool)
+        .await?;
+    let mut out = Vec::with_capacity(rows.len());
+    for row in rows {
+        let key: String = row.get("key");
+        let Ok(tenant_id) = Uuid::parse_str(&key[SETTINGS_PREFIX.len()..]) else {
+            continue;
+        };
+        let stored: StoredCredential = serde_json::from_str(row.get("value"))?;
+        out.push(stored.summary(tenant_id));
+    }
+    Ok(out)
+}
+
+/// Removes the tenant's credentials; returns `false` if none were stored.
+pub async fn delete(pool: &PgPool, tenant_id: Uuid) -> Result<bool, CredentialError> {
+    let res = sqlx::query("DELETE FROM app_settings WHERE key = $1")
+        .bind(settings_key(tenant_id))
+        .execute(pool)
+        .await?;
+    Ok(res.rows_affected() > 0)
+}
+
+/// Decrypts the tenant's credentials for use with the OpenAI client.
+pub async fn load(
+    pool: &PgPool,
+    master: &MasterKey,
+    tenant_id: Uuid,
+) -> Result<Option<OpenAiCredentials>, CredentialError> {
+    let Some(stored) = fetch(pool, tenant_id).await? else {
+        return Ok(None);
+    };
+    Ok(Some(OpenAiCredentials {
+        api_key: master.open(&stored.api_key)?,
+        organization: stored.organization,
+        endpoint: stored.endpoint,
+        allowed_models: stored.allowed_models,
+    }))
+}
+
+/// Tenant of the latest upload for `pdf_id` (and `pipeline_id`, if set there).
+pub async fn tenant_for_pdf(
+    pool: &PgPool,
+    pdf_id: i32,
+    pipeline_id: Uuid,
+) -> Result<Option<Uuid>, CredentialError> {
+    let tenant = sqlx::query_scalar::<_, Uuid>(
+        "SELECT tenant_id FROM uploads
+          WHERE pdf_id = $1 AND (pipeline_id IS NULL OR pipeline_id = $2)
+          ORDER BY id DESC
+          LIMIT 1",
+    )
+    .bind(pdf_id)
+    .bind(pipeline_id)
+    .fetch_optional(pool)
+    .await?;
+    Ok(tenant)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn hint_only_reveals_key_tail() {
+        assert_eq!(key_hint("sk-abcdefgh1234"), "…1234");
+        assert_eq!(key_hint("ab"), "…ab");
+        assert_eq!(
+            settings_key(Uuid::nil()),
+            "openai_credentials:00000000-0000-0000-0000-000000000000"
+        );
+    }
+}
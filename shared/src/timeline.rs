@@ -0,0 +1,133 @@
+//! Unified status chronology (`run_timeline`) written by every service.
+//!
+//! Each service appends an event whenever a document changes state (upload
+//! received, text extracted, pipeline run started/finished, SharePoint job
+//! status, ...). The history-service exposes the events via `GET /timeline`.
+
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use tokio_postgres::Client;
+use tracing::warn;
+use uuid::Uuid;
+
+/// Idempotent DDL for the timeline table (mirrors `migrations/0014_run_timeline.sql`).
+pub const CREATE_TABLE_SQL: &str = "CREATE TABLE IF NOT EXISTS run_timeline (
+    id BIGSERIAL PRIMARY KEY,
+    pdf_id INTEGER,
+    upload_id INTEGER,
+    run_id UUID,
+    pipeline_id UUID,
+    source TEXT NOT NULL,
+    status TEXT NOT NULL,
+    message TEXT,
+    details JSONB,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT now()
+)";
+
+/// Insert statement; parameters follow the field order of [`TimelineEvent`].
+pub const INSERT_SQL: &str = "INSERT INTO run_timeline
+    (pdf_id, upload_id, run_id, pipeline_id, source, status, message, details)
+    VALUES ($1, $2, $3, $4, $5, $6, $7, $8::jsonb)";
+
+/// A single status event for the timeline.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct TimelineEvent {
+    pub pdf_id: Option<i32>,
+    pub upload_id: Option<i32>,
+    pub run_id: Option<Uuid>,
+    pub pipeline_id: Option<Uuid>,
+    /// Emitting service, e.g. `pdf-ingest` or `pipeline-runner`.
+    pub source: String,
+    pub status: String,
+    pub message: Option<String>,
+    pub details: Option<Value>,
+}
+
+impl TimelineEvent {
+    pub fn new(source: &str, status: &str) -> Self {
+        Self {
+            source: source.to_string(),
+            status: status.to_string(),
+            ..Default::default()
+        }
+    }
+
+    pub fn pdf(mut self, pdf_id: Option<i32>) -> Self {
+        self.pdf_id = pdf_id;
+        self
+    }
+
+    pub fn upload(mut self, upload_id: Option<i32>) -> Self {
+        self.upload_id = upload_id;
+        self
+    }
+
+    pub fn run(mut self, run_id: Option<Uuid>) -> Self {
+        self.run_id = run_id;
+        self
+    }
+
+    pub fn pipeline(mut self, pipeline_id: Option<Uuid>) -> Self {
+        self.pipeline_id = pipeline_id;
+        self
+    }
+
+    pub fn message<T: Into<String>>(mut self, message: T) -> Self {
+        self.message = Some(message.into());
+        self
+    }
+
+    pub fn details(mut self, details: Value) -> Self {
+        self.details = Some(details);
+        self
+    }
+}
+
+/// Appends an event using a tokio-postgres client.
+pub async fn append(client: &Client, event: &TimelineEvent) -> Result<(), tokio_postgres::Error> {
+    client
+        .execute(
+            INSERT_SQL,
+            &[
+                &event.pdf_id,
+                &event.upload_id,
+                &event.run_id,
+                &event.pipeline_id,
+                &event.source,
+                &event.status,
+                &event.message,
+                &event.details,
+            ],
+        )
+        .await?;
+    Ok(())
+}
+
+/// Best-effort variant of [`append`]: failures are logged, never propagated.
+pub async fn record(client: &Client, event: &TimelineEvent) {
+    if let Err(e) = append(client, event).await {
+        warn!(%e, source = %event.source, status = %event.status, "failed to append run_timeline event");
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn builder_sets_fields() {
+        let run_id = Uuid::new_v4();
+        let event = TimelineEvent::new("pipeline-runner", "failed")
+            .pdf(Some(7))
+            .run(Some(run_id))
+            .message("boom")
+            .details(serde_json::json!({ "attempt": 1 }));
+        assert_eq!(event.source, "pipeline-runner");
+        assert_eq!(event.status, "failed");
+        assert_eq!(event.pdf_id, Some(7));
+        assert_eq!(event.run_id, Some(run_id));
+        assert_eq!(event.upload_id, None);
+        assert_eq!(event.message.as_deref(), Some("boom"));
+        assert_eq!(event.details, Some(serde_json::json!({ "attempt": 1 })));
+    }
+}
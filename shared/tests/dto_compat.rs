@@ -0,0 +1,126 @@
+//! Compatibility of the versioned event DTOs with stored payloads.
+//!
+//! `tests/fixtures/dto/<event>.v<N>.json` holds one payload per schema version.
+//! Every fixture must still deserialize, and the fixture of the current
+//! `SCHEMA_VERSION` must survive a round-trip unchanged. A failing round-trip
+//! means a field was removed, renamed or retyped without a version bump.
+
+use std::{collections::BTreeSet, fs, path::PathBuf};
+
+use serde::{de::DeserializeOwned, Serialize};
+use serde_json::Value;
+use shared::dto::{
+    PdfUploaded, PipelineRunResult, PromptType, RunPriority, TernaryLabel, TextExtracted,
+};
+
+fn fixture(name: &str, version: u32) -> Option<Value> {
+    let path = PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+        .join("tests/fixtures/dto")
+        .join(format!("{name}.v{version}.json"));
+    let raw = fs::read_to_string(&path).ok()?;
+    Some(serde_json::from_str(&raw).unwrap_or_else(|e| panic!("{}: {e}", path.display())))
+}
+
+/// Object keys of `value` as dotted paths; array elements share the path `[]`.
+fn key_paths(value: &Value, prefix: &str, out: &mut BTreeSet<String>) {
+    match value {
+        Value::Object(map) => {
+            for (key, child) in map {
+                let path = format!("{prefix}.{key}");
+                out.insert(path.clone());
+                key_paths(child, &path, out);
+            }
+        }
+        Value::Array(items) => {
+            for item in items {
+                key_paths(item, &format!("{prefix}[]"), out);
+            }
+        }
+        _ => {}
+    }
+}
+
+fn assert_compatible<T: Serialize + DeserializeOwned>(name: &str, version: u32) {
+    for v in 1..=version {
+        let payload = fixture(name, v).unwrap_or_else(|| {
+            panic!("{name}: fixture {name}.v{v}.json missing (SCHEMA_VERSION is {version})")
+        });
+        if let Err(e) = serde_json::from_value::<T>(payload) {
+            panic!("{name}: v{v} payload no longer deserializes: {e}");
+        }
+    }
+
+    let latest = fixture(name, version).expect("checked above");
+    assert_eq!(
+        latest["schema_version"],
+        Value::from(version),
+        "{name}.v{version}.json must carry schema_version {version}"
+    );
+    let event: T = serde_json::from_value(latest.clone()).expect("checked above");
+    let written = serde_json::to_value(&event).unwrap();
+
+    let (mut before, mut after) = (BTreeSet::new(), BTreeSet::new());
+    key_paths(&latest, "", &mut before);
+    key_paths(&written, "", &mut after);
+    let removed: Vec<_> = before.difference(&after).collect();
+    let added: Vec<_> = after.difference(&before).collect();
+    assert!(
+        removed.is_empty(),
+        "{name}: fields {removed:?} are no longer written. This breaks consumers of \
+         v{version}; bump {name} SCHEMA_VERSION and add {name}.v{}.json",
+        version + 1
+    );
+    assert!(
+        added.is_empty(),
+        "{name}: new fields {added:?} are missing in {name}.v{version}.json; add them \
+         (optional fields need no version bump)"
+    );
+    assert_eq!(
+        written,
+        latest,
+        "{name}: v{version} payload changed on round-trip (retyped field?); bump \
+         {name} SCHEMA_VERSION and add {name}.v{}.json",
+        version + 1
+    );
+}
+
+#[test]
+fn pdf_uploaded_is_compatible() {
+    assert_compatible::<PdfUploaded>("pdf_uploaded", PdfUploaded::SCHEMA_VERSION);
+}
+
+#[test]
+fn text_extracted_is_compatible() {
+    assert_compatible::<TextExtracted>("text_extracted", TextExtracted::SCHEMA_VERSION);
+}
+
+#[test]
+fn pipeline_run_result_is_compatible() {
+    assert_compatible::<PipelineRunResult>(
+        "pipeline_run_result",
+        PipelineRunResult::SCHEMA_VERSION,
+    );
+}
+
+#[test]
+fn legacy_payloads_get_defaults() {
+    let evt: PdfUploaded = serde_json::from_value(fixture("pdf_uploaded", 1).unwrap()).unwrap();
+    assert_eq!(evt.schema_version, 0);
+    assert_eq!(evt.priority, RunPriority::Normal);
+
+    let result: PipelineRunResult =
+        serde_json::from_value(fixture("pipeline_run_result", 1).unwrap()).unwrap();
+    assert_eq!(result.schema_version, 0);
+    assert!(!result.contested);
+    assert!(result.output.is_none());
+}
+
+#[test]
+fn unknown_and_renamed_values_are_accepted() {
+    let label: TernaryLabel = serde_json::from_str("\"maybe\"").unwrap();
+    assert_eq!(label, TernaryLabel::Unsure);
+    let priority: RunPriority = serde_json::from_str("\"urgent\"").unwrap();
+    assert_eq!(priority, RunPriority::Normal);
+    let prompt: PromptType = serde_json::from_str("\"Scoring\"").unwrap();
+    assert_eq!(prompt, PromptType::ScoringPrompt);
+}
@@ -1,10 +1,10 @@
-use reqwest::Client;
-use serial_test::serial;
-use tokio::runtime::Builder;
 use httpmock::prelude::*;
 use openai::chat::{ChatCompletionMessage, ChatCompletionMessageRole};
+use reqwest::Client;
 use serde_json::json;
+use serial_test::serial;
 use shared::openai_client;
+use tokio::runtime::Builder;
 
 fn base_messages() -> Vec<ChatCompletionMessage> {
     vec![
@@ -38,28 +38,29 @@ fn responses_endpoint_parses_output_text() -> anyhow::Result<()> {
                 .body(
                     r#"{"output":[{"content":[{"type":"output_text","text":"{\"ok\":true}"}]}]}"#,
                 );
-        })
-        .await;
-
-    let endpoint = format!(
-        "{}/v1/responses?api-version=2025-04-01-preview",
-        server.base_url()
-    );
-    std::env::set_var("OPENAI_API_KEY", "test-key");
-    std::env::set_var("OPENAI_RESPONSES_ENDPOINT", &endpoint);
-    std::env::remove_var("OPENAI_CHAT_COMPLETIONS_ENDPOINT");
-    std::env::remove_var("OPENAI_API_BASE");
-
-    openai_client::configure_openai_defaults("gpt-test", &endpoint);
-    openai_client::prefer_responses_endpoint();
-
-    let client = Client::new();
-    let response =
-        openai_client::call_openai_chat(&client, "gpt-test", base_messages(), None, None).await?;
-    let parsed: serde_json::Value = serde_json::from_str(&response)?;
-    assert_eq!(parsed, json!({"ok": true}));
-
-    mock.assert_async().await;
+            })
+            .await;
+
+        let endpoint = format!(
+            "{}/v1/responses?api-version=2025-04-01-preview",
+            server.base_url()
+        );
+        std::env::set_var("OPENAI_API_KEY", "test-key");
+        std::env::set_var("OPENAI_RESPONSES_ENDPOINT", &endpoint);
+        std::env::remove_var("OPENAI_CHAT_COMPLETIONS_ENDPOINT");
+        std::env::remove_var("OPENAI_API_BASE");
+
+        openai_client::configure_openai_defaults("gpt-test", &endpoint);
+        openai_client::prefer_responses_endpoint();
+
+        let client = Client::new();
+        let response =
+            openai_client::call_openai_chat(&client, "gpt-test", base_messages(), None, None)
+                .await?;
+        let parsed: serde_json::Value = serde_json::from_str(&response)?;
+        assert_eq!(parsed, json!({"ok": true}));
+
+        mock.assert_async().await;
         Ok(())
     })
 }
@@ -81,25 +82,26 @@ fn chat_endpoint_returns_json_content() -> anyhow::Result<()> {
                 .body(
                     r#"{"choices":[{"message":{"role":"assistant","content":"{\"score\":1}"}}]}"#,
                 );
-        })
-        .await;
-
-    let endpoint = format!("{}/v1/chat/completions", server.base_url());
-    std::env::set_var("OPENAI_API_KEY", "test-key");
-    std::env::set_var("OPENAI_CHAT_COMPLETIONS_ENDPOINT", &endpoint);
-    std::env::remove_var("OPENAI_RESPONSES_ENDPOINT");
-    std::env::remove_var("OPENAI_API_BASE");
-
-    openai_client::configure_openai_defaults("gpt-chat", &endpoint);
-    openai_client::prefer_chat_endpoint();
-
-    let client = Client::new();
-    let response =
-        openai_client::call_openai_chat(&client, "gpt-chat", base_messages(), None, None).await?;
-    let parsed: serde_json::Value = serde_json::from_str(&response)?;
-    assert_eq!(parsed, json!({"score": 1}));
-
-    mock.assert_async().await;
+            })
+            .await;
+
+        let endpoint = format!("{}/v1/chat/completions", server.base_url());
+        std::env::set_var("OPENAI_API_KEY", "test-key");
+        std::env::set_var("OPENAI_CHAT_COMPLETIONS_ENDPOINT", &endpoint);
+        std::env::remove_var("OPENAI_RESPONSES_ENDPOINT");
+        std::env::remove_var("OPENAI_API_BASE");
+
+        openai_client::configure_openai_defaults("gpt-chat", &endpoint);
+        openai_client::prefer_chat_endpoint();
+
+        let client = Client::new();
+        let response =
+            openai_client::call_openai_chat(&client, "gpt-chat", base_messages(), None, None)
+                .await?;
+        let parsed: serde_json::Value = serde_json::from_str(&response)?;
+        assert_eq!(parsed, json!({"score": 1}));
+
+        mock.assert_async().await;
         Ok(())
     })
 }
@@ -141,3 +143,82 @@ fn chat_endpoint_extracts_json_from_prefixed_text() -> anyhow::Result<()> {
         Ok(())
     })
 }
+
+#[serial]
+#[test]
+fn structured_call_falls_back_when_schema_is_rejected() -> anyhow::Result<()> {
+    let rt = Builder::new_current_thread()
+        .enable_all()
+        .build()
+        .map_err(anyhow::Error::new)?;
+    rt.block_on(async {
+        let server = MockServer::start_async().await;
+        let too_long = server
+            .mock_async(|when, then| {
+                when.method(POST)
+                    .path("/v1/chat/completions")
+                    .body_contains("zu lang");
+                then.status(400)
+                    .header("content-type", "application/json")
+                    .body(r#"{"error":{"message":"This model's maximum context length is 8192 tokens.","type":"invalid_request_error","param":"messages","code":"context_length_exceeded"}}"#);
+            })
+            .await;
+        let rejected = server
+            .mock_async(|when, then| {
+                when.method(POST)
+                    .path("/v1/chat/completions")
+                    .body_contains("Hallo")
+                    .body_contains("json_schema");
+                then.status(400)
+                    .header("content-type", "application/json")
+                    .body(r#"{"error":{"message":"Invalid parameter: 'response_format' of type 'json_schema' is not supported with this model.","type":"invalid_request_error","param":"response_format","code":null}}"#);
+            })
+            .await;
+        let fallback = server
+            .mock_async(|when, then| {
+                when.method(POST)
+                    .path("/v1/chat/completions")
+                    .body_contains("json_object");
+                then.status(200)
+                    .header("content-type", "application/json")
+                    .body(r#"{"choices":[{"message":{"role":"assistant","content":"{\"value\":\"42\"}"}}]}"#);
+            })
+            .await;
+
+        let endpoint = format!("{}/v1/chat/completions", server.base_url());
+        std::env::set_var("OPENAI_API_KEY", "test-key");
+        std::env::set_var("OPENAI_CHAT_COMPLETIONS_ENDPOINT", &endpoint);
+        std::env::remove_var("OPENAI_RESPONSES_ENDPOINT");
+        std::env::remove_var("OPENAI_API_BASE");
+
+        openai_client::configure_openai_defaults("gpt-chat", &endpoint);
+        openai_client::prefer_chat_endpoint();
+
+        let client = Client::new();
+        let schema = openai_client::ResponseSchema::extraction(None);
+
+        // Andere 400er schalten die Schema-Erzwingung nicht ab
+        let mut long_messages = base_messages();
+        long_messages[1].content = Some("Text ist zu lang".to_string());
+        let err = openai_client::call_openai_structured(&client, "gpt-chat", long_messages, &schema)
+            .await
+            .unwrap_err();
+        assert!(matches!(err, openai_client::PromptError::Http(400)));
+        too_long.assert_hits_async(1).await;
+        fallback.assert_hits_async(0).await;
+
+        let response = openai_client::call_openai_structured(
+            &client,
+            "gpt-chat",
+            base_messages(),
+            &schema,
+        )
+        .await?;
+        let parsed: serde_json::Value = serde_json::from_str(&response)?;
+        assert_eq!(parsed, json!({"value": "42"}));
+
+        rejected.assert_async().await;
+        fallback.assert_async().await;
+        Ok(())
+    })
+}